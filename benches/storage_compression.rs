@@ -0,0 +1,95 @@
+// How compressing large JSON columns changes the database size and how fast
+// deployments list, with and without it. Each run writes the same
+// deployments, big ones like those `kubectl apply` leaves behind (a pod
+// template with lots of env and a last-applied-configuration copy of it),
+// to a fresh database file, then lists them all repeatedly.
+//
+//     cargo bench --bench storage_compression
+use krust::storage::compression;
+use krust::Storage;
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+const DEPLOYMENTS: usize = 500;
+const LISTS: usize = 20;
+
+fn deployment(index: usize) -> Value {
+    let name = format!("app-{}", index);
+    let env: Vec<Value> = (0..200)
+        .map(|i| json!({ "name": format!("SETTING_{}", i), "value": format!("{}-value-of-setting-number-{}", name, i) }))
+        .collect();
+    let mut deployment = json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": name, "labels": { "app": name, "team": "bench" } },
+        "spec": {
+            "replicas": 3,
+            "selector": { "matchLabels": { "app": name } },
+            "template": {
+                "metadata": { "labels": { "app": name } },
+                "spec": {
+                    "containers": [{
+                        "name": "app",
+                        "image": format!("registry.example.com/{}:1.0.{}", name, index),
+                        "env": env,
+                        "resources": { "requests": { "cpu": "100m", "memory": "128Mi" } }
+                    }]
+                }
+            }
+        }
+    });
+    let last_applied = deployment.to_string();
+    deployment["metadata"]["annotations"] = json!({ "kubectl.kubernetes.io/last-applied-configuration": last_applied });
+    deployment
+}
+
+struct Run {
+    column_bytes: i64,
+    database_bytes: u64,
+    list: Duration,
+}
+
+async fn run(threshold: usize) -> anyhow::Result<Run> {
+    compression::set_threshold(threshold);
+    let dir = tempfile::tempdir()?;
+    let path = dir.path().join("krust.db");
+    let storage = Storage::new(&format!("sqlite:{}?mode=rwc", path.display())).await?;
+    storage.migrate().await?;
+    for index in 0..DEPLOYMENTS {
+        storage.deployments().create("default", deployment(index)).await?;
+    }
+    sqlx::query("VACUUM").execute(&*storage.pool).await?;
+    let database_bytes = std::fs::metadata(&path)?.len();
+    // The watch history keeps each object as written, uncompressed, so the
+    // columns themselves show the difference best
+    let column_bytes: i64 = sqlx::query_scalar("SELECT SUM(length(spec) + length(status) + length(annotations)) FROM deployments")
+        .fetch_one(&*storage.pool)
+        .await?;
+
+    // One list to warm the page cache, then the timed ones
+    storage.deployments().list(Some("default")).await?;
+    let started = Instant::now();
+    for _ in 0..LISTS {
+        let list = storage.deployments().list(Some("default")).await?;
+        assert_eq!(list["items"].as_array().map(Vec::len), Some(DEPLOYMENTS));
+    }
+    Ok(Run { column_bytes, database_bytes, list: started.elapsed() / LISTS as u32 })
+}
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    println!("{} deployments of ~{} KiB of JSON each, listed {} times\n", DEPLOYMENTS, deployment(0).to_string().len() / 1024, LISTS);
+    println!("{:<24} {:>12} {:>12} {:>12} {:>16}", "", "columns", "database", "list", "deployments/s");
+    for (label, threshold) in [("uncompressed", 0), ("compressed (default)", compression::DEFAULT_THRESHOLD)] {
+        let run = run(threshold).await?;
+        println!(
+            "{:<24} {:>8.1} MiB {:>8.1} MiB {:>9.1} ms {:>16.0}",
+            label,
+            run.column_bytes as f64 / (1024.0 * 1024.0),
+            run.database_bytes as f64 / (1024.0 * 1024.0),
+            run.list.as_secs_f64() * 1000.0,
+            DEPLOYMENTS as f64 / run.list.as_secs_f64()
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,16 @@
+-- Which client wrote each object, recorded per write from the User-Agent
+-- header or the fieldManager query parameter
+CREATE TABLE object_writes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    uid TEXT,
+    api_version TEXT,
+    kind TEXT NOT NULL,
+    namespace TEXT,
+    name TEXT NOT NULL,
+    manager TEXT NOT NULL,
+    user_agent TEXT,
+    operation TEXT NOT NULL,
+    timestamp TEXT NOT NULL
+);
+
+CREATE INDEX idx_object_writes_object ON object_writes(kind, namespace, name);
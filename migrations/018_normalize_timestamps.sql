@@ -0,0 +1,158 @@
+-- Timestamps were written both as SQLite's CURRENT_TIMESTAMP
+-- (2024-05-01 12:30:00) and as RFC 3339 with an offset and fractional
+-- seconds. Rewrite them all as RFC 3339 in UTC with second precision
+-- (2024-05-01T12:30:00Z), the format used from now on. Values SQLite can't
+-- parse are left alone.
+
+UPDATE pods SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE services SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE endpoints SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE deployments SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE replicasets SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE nodes SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE configmaps SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE secrets SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE persistent_volumes SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE persistent_volume_claims SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE statefulsets SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE daemonsets SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE networkpolicies SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE ingresses SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE horizontalpodautoscalers SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE roles SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE rolebindings SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE clusterroles SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE clusterrolebindings SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE resourcequotas SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE limitranges SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE serviceaccounts SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE poddisruptionbudgets SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE priorityclasses SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE storageclasses SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE validatingwebhookconfigurations SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE mutatingwebhookconfigurations SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE csidrivers SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE csinodes SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE volumeattachments SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE namespaces SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE jobs SET
+    start_time = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', start_time), start_time),
+    completion_time = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', completion_time), completion_time),
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE cronjobs SET
+    last_schedule_time = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', last_schedule_time), last_schedule_time),
+    last_successful_time = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', last_successful_time), last_successful_time),
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    deletion_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', deletion_timestamp), deletion_timestamp);
+
+UPDATE tokenrequests SET
+    creation_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', creation_timestamp), creation_timestamp),
+    expiration_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', expiration_timestamp), expiration_timestamp);
+
+UPDATE watch_cursors SET
+    created_at = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', created_at), created_at),
+    expires_at = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', expires_at), expires_at);
+
+UPDATE events SET
+    timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', timestamp), timestamp),
+    event_time = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', event_time), event_time),
+    first_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', first_timestamp), first_timestamp),
+    last_timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', last_timestamp), last_timestamp);
+
+UPDATE object_writes SET
+    timestamp = COALESCE(strftime('%Y-%m-%dT%H:%M:%SZ', timestamp), timestamp);
@@ -0,0 +1,8 @@
+-- The resource version up to which watch events have been compacted away;
+-- watches can't resume from before it
+CREATE TABLE watch_compaction (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    revision INTEGER NOT NULL
+);
+
+INSERT INTO watch_compaction (id, revision) VALUES (1, 0);
@@ -0,0 +1,16 @@
+-- The cluster-wide resource version counter. Every write to an object takes
+-- the next version from it, so versions order writes across all resources.
+-- It starts past every version watches have handed out so far.
+CREATE TABLE resource_versions (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    version INTEGER NOT NULL
+);
+
+INSERT INTO resource_versions (id, version)
+SELECT 1, MAX(
+    COALESCE((SELECT MAX(id) FROM events), 0),
+    COALESCE((SELECT MAX(resource_version) FROM events), 0),
+    (SELECT revision FROM watch_compaction WHERE id = 1)
+);
+
+CREATE INDEX idx_events_watch ON events(resource_type, resource_version);
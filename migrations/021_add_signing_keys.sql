@@ -0,0 +1,7 @@
+-- Keys the API server signs with, generated on first use; 'service-account'
+-- signs ServiceAccount tokens
+CREATE TABLE signing_keys (
+    name TEXT PRIMARY KEY,
+    private_key TEXT NOT NULL,
+    creation_timestamp TEXT NOT NULL
+);
@@ -0,0 +1,8 @@
+-- Ephemeral storage each running pod uses, as its kubelet last measured it,
+-- for the nodes' stats summaries
+CREATE TABLE pod_stats (
+    uid TEXT PRIMARY KEY,
+    node_name TEXT NOT NULL,
+    stats TEXT NOT NULL,
+    updated TEXT NOT NULL
+);
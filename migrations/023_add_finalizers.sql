@@ -0,0 +1,12 @@
+-- metadata.finalizers of objects that have some, and when deleting them was
+-- asked for; such objects stay until their finalizers are all removed.
+-- namespace is '' for cluster-scoped objects.
+CREATE TABLE finalizers (
+    resource TEXT NOT NULL,
+    namespace TEXT NOT NULL,
+    name TEXT NOT NULL,
+    uid TEXT NOT NULL,
+    finalizers TEXT NOT NULL,
+    deletion_timestamp TEXT,
+    PRIMARY KEY (resource, namespace, name)
+);
@@ -0,0 +1,18 @@
+-- How long a pod being deleted has to stop, from its DeleteOptions or
+-- terminationGracePeriodSeconds; its deletion_timestamp is when that ends.
+ALTER TABLE finalizers ADD COLUMN grace_period_seconds INTEGER;
+
+-- metadata.ownerReferences, by owner, for the garbage collector: the stores
+-- don't keep them, so they're taken from the objects as they're written.
+-- namespace is '' for cluster-scoped objects.
+CREATE TABLE owner_references (
+    resource TEXT NOT NULL,
+    namespace TEXT NOT NULL,
+    name TEXT NOT NULL,
+    uid TEXT NOT NULL,
+    owner_kind TEXT NOT NULL,
+    owner_name TEXT NOT NULL,
+    owner_uid TEXT NOT NULL,
+    PRIMARY KEY (uid, owner_uid)
+);
+CREATE INDEX idx_owner_references_owner ON owner_references(owner_uid);
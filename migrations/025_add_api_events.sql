@@ -0,0 +1,11 @@
+-- core/v1 Events, as the API serves them. They share the events table with
+-- watch events and the stores' own records, and are the rows with a name.
+ALTER TABLE events ADD COLUMN name TEXT;
+ALTER TABLE events ADD COLUMN involved_object_namespace TEXT;
+ALTER TABLE events ADD COLUMN involved_object_api_version TEXT;
+ALTER TABLE events ADD COLUMN involved_object_field_path TEXT;
+ALTER TABLE events ADD COLUMN source_component TEXT;
+ALTER TABLE events ADD COLUMN source_host TEXT;
+ALTER TABLE events ADD COLUMN labels TEXT NOT NULL DEFAULT '{}';
+ALTER TABLE events ADD COLUMN annotations TEXT NOT NULL DEFAULT '{}';
+CREATE UNIQUE INDEX idx_events_name ON events(namespace, name);
@@ -0,0 +1,9 @@
+-- kube-public, readable by everyone, where cluster-info is published
+INSERT INTO namespaces (uid, name, creation_timestamp, spec, status)
+VALUES (
+    'kube-public-namespace-uid',
+    'kube-public',
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now'),
+    '{"finalizers":["kubernetes"]}',
+    '{"phase":"Active"}'
+) ON CONFLICT(name) DO NOTHING;
@@ -0,0 +1,10 @@
+-- Why the scheduler last failed to place each pod that is still Pending: the
+-- FailedScheduling message and, per node, the filters that turned it down.
+CREATE TABLE scheduling_failures (
+    uid TEXT PRIMARY KEY,
+    namespace TEXT NOT NULL,
+    name TEXT NOT NULL,
+    message TEXT NOT NULL,
+    nodes TEXT NOT NULL,
+    timestamp TEXT NOT NULL
+);
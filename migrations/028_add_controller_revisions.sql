@@ -0,0 +1,18 @@
+-- ControllerRevisions: the pod templates StatefulSets and DaemonSets have
+-- had, numbered, for `kubectl rollout history` and `kubectl rollout undo`.
+CREATE TABLE IF NOT EXISTS controller_revisions (
+    uid TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    namespace TEXT NOT NULL DEFAULT 'default',
+    resource_version INTEGER NOT NULL DEFAULT 1,
+    creation_timestamp TEXT NOT NULL,
+    deletion_timestamp TEXT,
+    labels TEXT, -- JSON
+    annotations TEXT, -- JSON
+    owner_references TEXT, -- JSON array of ownerReferences
+    data TEXT NOT NULL, -- JSON
+    revision INTEGER NOT NULL,
+    UNIQUE(name, namespace)
+);
+
+CREATE INDEX idx_controller_revisions_namespace ON controller_revisions(namespace);
@@ -0,0 +1,27 @@
+-- Leases (coordination.k8s.io/v1), which kubelets renew as their node's
+-- heartbeat and anything else may use for leader election.
+CREATE TABLE IF NOT EXISTS leases (
+    uid TEXT PRIMARY KEY,
+    name TEXT NOT NULL,
+    namespace TEXT NOT NULL DEFAULT 'default',
+    resource_version INTEGER NOT NULL DEFAULT 1,
+    creation_timestamp TEXT NOT NULL,
+    deletion_timestamp TEXT,
+    labels TEXT, -- JSON
+    annotations TEXT, -- JSON
+    owner_references TEXT, -- JSON array of ownerReferences
+    spec TEXT NOT NULL, -- JSON
+    UNIQUE(name, namespace)
+);
+
+CREATE INDEX idx_leases_namespace ON leases(namespace);
+
+-- kube-node-lease, where each node's Lease is kept
+INSERT INTO namespaces (uid, name, creation_timestamp, spec, status)
+VALUES (
+    'kube-node-lease-namespace-uid',
+    'kube-node-lease',
+    strftime('%Y-%m-%dT%H:%M:%SZ', 'now'),
+    '{"finalizers":["kubernetes"]}',
+    '{"phase":"Active"}'
+) ON CONFLICT(name) DO NOTHING;
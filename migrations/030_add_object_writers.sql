@@ -0,0 +1,39 @@
+-- Which client wrote each object, one row per object and manager with the
+-- manager's latest write and how many it has made, so the record stays as
+-- big as the objects and their writers rather than growing with every
+-- write. Cluster-scoped objects have an empty namespace, which keeps the key
+-- unique. first_write and last_write order the rows by when they were
+-- written.
+CREATE TABLE object_writers (
+    kind TEXT NOT NULL,
+    namespace TEXT NOT NULL DEFAULT '',
+    name TEXT NOT NULL,
+    manager TEXT NOT NULL,
+    api_version TEXT,
+    uid TEXT,
+    user_agent TEXT,
+    operation TEXT NOT NULL,
+    timestamp TEXT NOT NULL,
+    writes INTEGER NOT NULL DEFAULT 1,
+    first_write INTEGER NOT NULL,
+    last_write INTEGER NOT NULL,
+    PRIMARY KEY (kind, namespace, name, manager)
+);
+
+CREATE UNIQUE INDEX idx_object_writers_last_write ON object_writers(last_write);
+
+-- The writes logged so far, each manager's latest standing for its row
+INSERT INTO object_writers (kind, namespace, name, manager, api_version, uid, user_agent, operation, timestamp, writes, first_write, last_write)
+SELECT w.kind, ifnull(w.namespace, ''), w.name, w.manager, w.api_version, w.uid, w.user_agent, w.operation, w.timestamp, g.writes, g.first_write, g.last_write
+FROM (
+    SELECT count(*) AS writes, min(id) AS first_write, max(id) AS last_write
+    FROM object_writes
+    GROUP BY kind, ifnull(namespace, ''), name, manager
+) g
+JOIN object_writes w ON w.id = g.last_write;
+
+CREATE INDEX idx_object_writers_uid ON object_writers(uid);
+
+-- Objects show their writers in metadata.managedFields now, which
+-- object_writers serves, so the log is no longer needed
+DROP TABLE object_writes;
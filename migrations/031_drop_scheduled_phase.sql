@@ -0,0 +1,4 @@
+-- Bound pods stay Pending until their kubelet starts them; earlier versions
+-- gave them a Scheduled phase of their own in between
+UPDATE pods SET phase = 'Pending', status = json_set(status, '$.phase', 'Pending')
+WHERE phase = 'Scheduled' AND json_valid(status);
@@ -0,0 +1,15 @@
+-- The ownerReferences of pods written before owner_references was kept,
+-- taken from their latest watch event while compaction hasn't dropped it.
+-- ReplicaSets count their pods by them.
+INSERT OR IGNORE INTO owner_references (resource, namespace, name, uid, owner_kind, owner_name, owner_uid)
+SELECT 'pods', p.namespace, p.name, p.uid,
+       json_extract(r.value, '$.kind'), json_extract(r.value, '$.name'), json_extract(r.value, '$.uid')
+FROM pods p
+JOIN events e ON e.id = (
+    SELECT max(id) FROM events
+    WHERE resource_type = 'pods' AND resource_uid = p.uid AND object IS NOT NULL AND json_valid(object)
+)
+JOIN json_each(json_extract(e.object, '$.metadata.ownerReferences')) r
+WHERE json_extract(r.value, '$.kind') IS NOT NULL
+  AND json_extract(r.value, '$.name') IS NOT NULL
+  AND json_extract(r.value, '$.uid') IS NOT NULL;
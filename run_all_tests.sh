@@ -7,6 +7,9 @@ cargo clean
 echo "🔨 Building project..."
 cargo build --release
 
+echo "🔨 Checking the strict-types build..."
+cargo check --all-targets --features strict-types
+
 echo "🚀 Starting server in background..."
 cargo run --release > server_test.log 2>&1 &
 SERVER_PID=$!
@@ -0,0 +1,453 @@
+// Admission webhooks. Creates, updates, patches and deletes go to the
+// matching webhooks of the MutatingWebhookConfigurations one after the
+// other, ordered by configuration name, each seeing the object as the ones
+// before left it, and then to those of the ValidatingWebhookConfigurations
+// all at once. A mutating webhook may change the object with a JSON patch;
+// any webhook may refuse it. A patch is sent as the UPDATE of the object it
+// makes, and updates and deletes come with the stored object as oldObject.
+// The in-process hooks registered with `Storage::hooks` run first, then the
+// config's injections for pods being created.
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, request::Parts, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use futures::future::join_all;
+use serde_json::{json, Value};
+use sqlx::Row;
+use tower::Service;
+use tracing::{debug, error, warn};
+use uuid::Uuid;
+
+use super::authentication::UserInfo;
+use super::dry_run;
+use super::error_status::ApiError;
+use super::injection;
+use super::patch;
+use super::request_info::RequestInfo;
+use super::server::{self, AppState};
+use crate::storage::LabelSelector;
+
+// What a request is, in the terms webhook rules are written in
+struct Attributes {
+    operation: &'static str,
+    group: String,
+    version: String,
+    resource: String,
+    subresource: Option<String>,
+    namespaced: bool,
+}
+
+/// Middleware sending creates, updates, patches and deletes to the
+/// admission webhooks whose rules and selectors match, and passing on the
+/// object they leave, unless one refuses it.
+pub async fn call_webhooks(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let operation = match *request.method() {
+        Method::POST => "CREATE",
+        Method::PUT | Method::PATCH => "UPDATE",
+        Method::DELETE => "DELETE",
+        _ => return next.run(request).await,
+    };
+    let info = RequestInfo::parse(request.method().as_str(), request.uri().path(), request.uri().query());
+    let RequestInfo::Resource { group, resource, subresource, namespace, name, .. } = info else {
+        return next.run(request).await;
+    };
+    // Like kube-apiserver, webhooks are never asked about their own
+    // configurations, so a broken one can always be fixed
+    if resource.ends_with("webhookconfigurations")
+        || matches!(subresource.as_deref(), Some("exec" | "attach" | "portforward" | "proxy"))
+    {
+        return next.run(request).await;
+    }
+    // Only single objects are updated or deleted here; collections are
+    // deleted by the handler
+    if operation != "CREATE" && name.is_none() {
+        return next.run(request).await;
+    }
+    let dry_run = match dry_run::requested(&request) {
+        Ok(dry_run) => dry_run,
+        Err(refusal) => return refusal.into_response(),
+    };
+    let attributes = Attributes {
+        operation,
+        group,
+        version: api_version(request.uri().path()),
+        namespaced: namespace.is_some() && resource != "namespaces",
+        resource,
+        subresource,
+    };
+
+    let hooked = match &attributes.subresource {
+        Some(subresource) => format!("{}/{}", attributes.resource, subresource),
+        None => attributes.resource.clone(),
+    };
+    let hooks = state.storage.hooks();
+
+    let (mutating, validating) = match matching_webhooks(&state, &attributes).await {
+        Ok(webhooks) => webhooks,
+        Err(e) => {
+            error!("Failed to list admission webhooks: {}", e);
+            return ApiError::internal(&e).into_response();
+        }
+    };
+    let injections = match (operation, hooked.as_str()) {
+        ("CREATE", "pods") => state.config.admission.injections.as_slice(),
+        _ => &[],
+    };
+    if mutating.is_empty() && validating.is_empty() && !hooks.admits(operation, &hooked) && injections.is_empty() {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to buffer request body: {}", e);
+            return ApiError::bad_request(e.to_string()).into_response();
+        }
+    };
+    let old_object = match operation {
+        "CREATE" => Value::Null,
+        _ => match stored(&state, &parts).await {
+            Ok(Some(object)) => object,
+            // Nothing to update or delete, which the handler answers
+            Ok(None) => return next.run(Request::from_parts(parts, Body::from(bytes))).await,
+            Err(failed) => return failed,
+        },
+    };
+    // Bodies that aren't objects are the handler's to turn down. A patch is
+    // judged by the object it makes, and a delete has none.
+    let mut object = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(_) if operation == "DELETE" => Value::Null,
+        Ok(body) if parts.method == Method::PATCH => {
+            let mut patched = old_object.clone();
+            if let Err(refusal) = patch::apply(&parts.headers, &mut patched, body) {
+                return refusal.into_response();
+            }
+            patched
+        }
+        Ok(object) if object.is_object() => object,
+        _ if operation == "DELETE" => Value::Null,
+        _ => return next.run(Request::from_parts(parts, Body::from(bytes))).await,
+    };
+    let sent = object.clone();
+    if operation != "DELETE" {
+        if let Err(message) = hooks.admit(operation, &hooked, &mut object) {
+            let message = format!("admission hook denied the request: {}", message);
+            return ApiError::new(StatusCode::FORBIDDEN, "Forbidden", message).into_response();
+        }
+    }
+
+    let namespace_labels = match &namespace {
+        Some(namespace) => match labels_of_namespace(&state, namespace).await {
+            Ok(labels) => labels,
+            Err(e) => {
+                error!("Failed to read labels of namespace {}: {}", namespace, e);
+                return ApiError::internal(&e).into_response();
+            }
+        },
+        None => json!({}),
+    };
+    let injected = injection::apply(injections, &mut object, &namespace_labels);
+    if !injected.is_empty() {
+        debug!("Injected {} into pod {}", injected.join(", "), object["metadata"]["name"]);
+    }
+    // Deletes are selected by the object being deleted
+    let selected = |webhook: &Value| {
+        let object = if object.is_null() { &old_object } else { &object };
+        selects(webhook, &attributes, object, &namespace_labels)
+    };
+    let mutating: Vec<Value> = mutating.into_iter().filter(|webhook| selected(webhook)).collect();
+    let validating: Vec<Value> = validating.into_iter().filter(|webhook| selected(webhook)).collect();
+
+    if dry_run {
+        if let Some(webhook) = mutating.iter().chain(&validating).find(|webhook| !without_side_effects(webhook)) {
+            let message = format!("admission webhook \"{}\" does not support dry run", webhook["name"].as_str().unwrap_or_default());
+            return ApiError::bad_request(message).into_response();
+        }
+    }
+
+    let user = parts.extensions.get::<UserInfo>().cloned().unwrap_or_else(UserInfo::anonymous);
+    let name = name.or_else(|| object["metadata"]["name"].as_str().map(str::to_string));
+    let options = match (attributes.operation, &parts.method) {
+        ("CREATE", _) => "CreateOptions",
+        (_, &Method::PATCH) => "PatchOptions",
+        ("UPDATE", _) => "UpdateOptions",
+        _ => "DeleteOptions",
+    };
+    let review = |object: &Value| {
+        let typed = if object.is_null() { &old_object } else { object };
+        let (kind_group, kind_version) = match typed["apiVersion"].as_str().unwrap_or_default().split_once('/') {
+            Some((group, version)) => (group.to_string(), version.to_string()),
+            None => (String::new(), typed["apiVersion"].as_str().unwrap_or(&attributes.version).to_string()),
+        };
+        let kind = json!({ "group": kind_group, "version": kind_version, "kind": typed["kind"] });
+        let resource = json!({ "group": attributes.group, "version": attributes.version, "resource": attributes.resource });
+        json!({
+            "apiVersion": "admission.k8s.io/v1",
+            "kind": "AdmissionReview",
+            "request": {
+                "uid": Uuid::new_v4().to_string(),
+                "kind": kind,
+                "resource": resource,
+                "subResource": attributes.subresource,
+                "requestKind": kind,
+                "requestResource": resource,
+                "requestSubResource": attributes.subresource,
+                "name": name,
+                "namespace": namespace,
+                "operation": attributes.operation,
+                "userInfo": user,
+                "object": object,
+                "oldObject": old_object,
+                "dryRun": dry_run,
+                "options": { "apiVersion": "meta.k8s.io/v1", "kind": options }
+            }
+        })
+    };
+
+    let mut warnings = Vec::new();
+    for webhook in &mutating {
+        match admit(&state, webhook, &review(&object)).await {
+            Ok((patch, mut said)) => {
+                warnings.append(&mut said);
+                // There's nothing to change about an object being deleted
+                if let Some(patch) = patch.filter(|_| !object.is_null()) {
+                    if let Err(e) = json_patch::patch(&mut object, &patch) {
+                        match call_failed(webhook, &format!("the patch it returned does not apply: {}", e)) {
+                            Some(failed) => return failed,
+                            None => continue,
+                        }
+                    }
+                }
+            }
+            Err(refusal) => return refusal,
+        }
+    }
+
+    let review = review(&object);
+    let verdicts = join_all(validating.iter().map(|webhook| admit(&state, webhook, &review))).await;
+    for verdict in verdicts {
+        match verdict {
+            Ok((_, mut said)) => warnings.append(&mut said),
+            Err(refusal) => return refusal,
+        }
+    }
+
+    // What was sent goes on as it was, unless admission changed the object.
+    // A changed patch goes on as the merge patch from the stored object to
+    // the admitted one, which every PATCH handler takes.
+    let body = if object == sent {
+        bytes.to_vec()
+    } else if parts.method == Method::PATCH {
+        parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/merge-patch+json"));
+        patch::merge_diff(&old_object, &object).to_string().into_bytes()
+    } else {
+        match serde_json::to_vec(&object) {
+            Ok(body) => body,
+            Err(e) => {
+                error!("Failed to serialize admitted object: {}", e);
+                return ApiError::internal(&e).into_response();
+            }
+        }
+    };
+    parts.headers.remove(header::CONTENT_LENGTH);
+    let mut response = next.run(Request::from_parts(parts, Body::from(body))).await;
+    for warning in warnings {
+        if let Ok(value) = HeaderValue::from_str(&format!("299 - {:?}", warning)) {
+            response.headers_mut().append(header::WARNING, value);
+        }
+    }
+    response
+}
+
+// The object an update or delete is for as it's stored, read the way a GET
+// of the same path would be, or None if there's no such object
+async fn stored(state: &AppState, parts: &Parts) -> Result<Option<Value>, Response> {
+    let request = match Request::get(parts.uri.path()).body(Body::empty()) {
+        Ok(request) => request,
+        Err(e) => return Err(ApiError::internal(&e).into_response()),
+    };
+    let response = match server::resources(state.clone()).call(request).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    };
+    match response.status() {
+        StatusCode::OK => {}
+        StatusCode::NOT_FOUND | StatusCode::METHOD_NOT_ALLOWED => return Ok(None),
+        _ => return Err(response),
+    }
+    let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.map_err(|e| ApiError::internal(&e).into_response())?;
+    Ok(serde_json::from_slice(&bytes).ok())
+}
+
+// Whether a webhook says it has no side effects that a dry run would
+// have it make
+fn without_side_effects(webhook: &Value) -> bool {
+    matches!(webhook["sideEffects"].as_str(), Some("None" | "NoneOnDryRun"))
+}
+
+// The mutating and validating webhooks whose rules cover the request, in
+// the order they're called
+async fn matching_webhooks(state: &AppState, attributes: &Attributes) -> anyhow::Result<(Vec<Value>, Vec<Value>)> {
+    let webhooks = |mut list: Value| {
+        let mut configurations = list["items"].as_array_mut().map(std::mem::take).unwrap_or_default();
+        configurations.sort_by(|a, b| a["metadata"]["name"].as_str().cmp(&b["metadata"]["name"].as_str()));
+        configurations
+            .into_iter()
+            .flat_map(|mut configuration| configuration["webhooks"].as_array_mut().map(std::mem::take).unwrap_or_default())
+            .filter(|webhook| matches_rules(webhook, attributes))
+            .collect::<Vec<_>>()
+    };
+    let mutating = webhooks(state.storage.mutating_webhooks().list().await?);
+    let validating = webhooks(state.storage.validating_webhooks().list().await?);
+    Ok((mutating, validating))
+}
+
+fn matches_rules(webhook: &Value, attributes: &Attributes) -> bool {
+    let listed = |values: &Value, value: &str| values.as_array().into_iter().flatten().any(|v| v == "*" || v == value);
+    webhook["rules"].as_array().into_iter().flatten().any(|rule| {
+        let scope = rule["scope"].as_str().unwrap_or("*");
+        listed(&rule["operations"], attributes.operation)
+            && listed(&rule["apiGroups"], &attributes.group)
+            && listed(&rule["apiVersions"], &attributes.version)
+            && rule["resources"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .any(|pattern| matches_resource(pattern, &attributes.resource, attributes.subresource.as_deref()))
+            && match scope {
+                "Namespaced" => attributes.namespaced,
+                "Cluster" => !attributes.namespaced,
+                _ => true,
+            }
+    })
+}
+
+// A rule's resource: "pods", "pods/status", "pods/*" for pods and all their
+// subresources, "*" for every resource and "*/*" for everything
+fn matches_resource(pattern: &str, resource: &str, subresource: Option<&str>) -> bool {
+    let (pattern_resource, pattern_subresource) = pattern.split_once('/').unwrap_or((pattern, ""));
+    (pattern_resource == "*" || pattern_resource == resource)
+        && (pattern_subresource == "*" || pattern_subresource == subresource.unwrap_or_default())
+}
+
+// Whether the webhook's namespaceSelector and objectSelector let the object
+// through. The namespaceSelector of a Namespace is matched against its own
+// labels, and doesn't apply to other cluster-scoped objects.
+fn selects(webhook: &Value, attributes: &Attributes, object: &Value, namespace_labels: &Value) -> bool {
+    let matches = |selector: &Value, labels: &Value| match LabelSelector::from_value(selector) {
+        Ok(selector) => selector.matches(labels),
+        Err(e) => {
+            warn!("Ignoring webhook {} with an invalid selector: {}", webhook["name"], e);
+            false
+        }
+    };
+    let labels = &object["metadata"]["labels"];
+    let namespace_matches = match (attributes.namespaced, attributes.resource.as_str()) {
+        (true, _) => matches(&webhook["namespaceSelector"], namespace_labels),
+        (false, "namespaces") => matches(&webhook["namespaceSelector"], labels),
+        (false, _) => true,
+    };
+    namespace_matches && matches(&webhook["objectSelector"], labels)
+}
+
+// Sends the review to one webhook. Allowed, it gives the patch it returned,
+// if any, and its warnings; refused or unreachable, the response ending the
+// request, unless its failurePolicy is Ignore.
+async fn admit(state: &AppState, webhook: &Value, review: &Value) -> Result<(Option<json_patch::Patch>, Vec<String>), Response> {
+    let name = webhook["name"].as_str().unwrap_or_default();
+    let accepts_v1 = webhook["admissionReviewVersions"]
+        .as_array()
+        .is_some_and(|versions| versions.iter().any(|v| v == "v1"));
+    let answer = if accepts_v1 {
+        state.webhooks.call(webhook, review).await
+    } else {
+        Err(anyhow::anyhow!("it accepts no AdmissionReview version krust sends (v1)"))
+    };
+    let response = match answer {
+        Ok(review) => review["response"].clone(),
+        Err(e) => {
+            return match call_failed(webhook, &format!("{:#}", e)) {
+                Some(failed) => Err(failed),
+                None => Ok((None, Vec::new())),
+            }
+        }
+    };
+
+    if response["allowed"] != Value::Bool(true) {
+        let status = &response["status"];
+        let code = status["code"].as_u64().filter(|code| *code >= 400).unwrap_or(400);
+        let message = status["message"].as_str().filter(|m| !m.is_empty()).unwrap_or("without explanation");
+        let code = StatusCode::from_u16(code as u16).unwrap_or(StatusCode::BAD_REQUEST);
+        let message = format!("admission webhook \"{}\" denied the request: {}", name, message);
+        return Err(ApiError::new(code, status["reason"].as_str().unwrap_or_default(), message).into_response());
+    }
+
+    let warnings = response["warnings"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|w| w.as_str().map(str::to_string))
+        .collect();
+    let Some(patch) = response["patch"].as_str() else {
+        return Ok((None, warnings));
+    };
+    let patch = match response["patchType"].as_str() {
+        Some("JSONPatch") => STANDARD
+            .decode(patch)
+            .map_err(anyhow::Error::from)
+            .and_then(|patch| Ok(serde_json::from_slice::<json_patch::Patch>(&patch)?)),
+        other => Err(anyhow::anyhow!("unsupported patchType {:?}", other)),
+    };
+    match patch {
+        Ok(patch) => Ok((Some(patch), warnings)),
+        Err(e) => match call_failed(webhook, &format!("invalid patch: {:#}", e)) {
+            Some(failed) => Err(failed),
+            None => Ok((None, warnings)),
+        },
+    }
+}
+
+// The 500 for a webhook that couldn't be called or gave an unusable answer,
+// or None when its failurePolicy says to carry on without it
+fn call_failed(webhook: &Value, reason: &str) -> Option<Response> {
+    let name = webhook["name"].as_str().unwrap_or_default();
+    if webhook["failurePolicy"] == "Ignore" {
+        warn!("Ignoring failed call to admission webhook {}: {}", name, reason);
+        return None;
+    }
+    error!("Failed calling admission webhook {}: {}", name, reason);
+    Some(ApiError::internal(format!("failed calling webhook \"{}\": {}", name, reason)).into_response())
+}
+
+// The labels namespaceSelectors are matched against, which always include
+// the namespace's name as kubernetes.io/metadata.name
+pub(super) async fn labels_of_namespace(state: &AppState, namespace: &str) -> anyhow::Result<Value> {
+    let row = sqlx::query("SELECT labels FROM namespaces WHERE name = ? AND deletion_timestamp IS NULL")
+        .bind(namespace)
+        .fetch_optional(state.storage.db())
+        .await?;
+    let labels = row.and_then(|row| row.get::<Option<String>, _>("labels"));
+    let mut labels = match labels.map(|labels| serde_json::from_str::<Value>(&labels)).transpose()? {
+        Some(labels) if labels.is_object() => labels,
+        _ => json!({}),
+    };
+    labels["kubernetes.io/metadata.name"] = json!(namespace);
+    Ok(labels)
+}
+
+// The version in an API path: v1 for /api/v1/..., v1 for /apis/apps/v1/...
+pub(super) fn api_version(path: &str) -> String {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["api", version, ..] => version.to_string(),
+        ["apis", _, version, ..] => version.to_string(),
+        _ => String::new(),
+    }
+}
@@ -0,0 +1,144 @@
+// Calls admission webhooks the way kube-apiserver does: an AdmissionReview
+// is POSTed to the webhook's URL, or to the Service its clientConfig names,
+// reached through the Service's endpoints like any other in-cluster client
+// would. The server certificate is checked against the webhook's caBundle,
+// or the system roots without one, and krust presents a client certificate
+// so webhooks can require mutual TLS.
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::Value;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use crate::config::AdmissionConfig;
+use crate::pki::{ClusterCa, Usage};
+use crate::Storage;
+
+// timeoutSeconds defaults to 10 and may be at most 30, as in
+// admissionregistration.k8s.io/v1
+const DEFAULT_TIMEOUT_SECONDS: u64 = 10;
+const MAX_TIMEOUT_SECONDS: u64 = 30;
+
+// The user the default client certificate names
+const CLIENT_USER: &str = "system:kube-apiserver";
+
+pub struct WebhookClient {
+    storage: Storage,
+    identity: reqwest::Identity,
+}
+
+impl WebhookClient {
+    pub fn new(config: &AdmissionConfig, ca: &ClusterCa, storage: Storage) -> Result<Self> {
+        let (cert, key) = match (&config.client_cert_file, &config.client_key_file) {
+            (Some(cert), Some(key)) => (read(cert)?, read(key)?),
+            _ => {
+                let (cert, key) = ca.issue(CLIENT_USER, &[], Usage::Client)?;
+                (cert.into_bytes(), key.into_bytes())
+            }
+        };
+        let identity = reqwest::Identity::from_pkcs8_pem(&cert, &key).context("invalid admission client certificate")?;
+        Ok(Self { storage, identity })
+    }
+
+    /// Sends `review` to `webhook` and returns the AdmissionReview it
+    /// answers with, once it checks out as the response to that request.
+    pub async fn call(&self, webhook: &Value, review: &Value) -> Result<Value> {
+        let client_config = &webhook["clientConfig"];
+        let timeout = webhook["timeoutSeconds"]
+            .as_u64()
+            .unwrap_or(DEFAULT_TIMEOUT_SECONDS)
+            .clamp(1, MAX_TIMEOUT_SECONDS);
+
+        let mut client = reqwest::Client::builder()
+            .identity(self.identity.clone())
+            .timeout(Duration::from_secs(timeout));
+        if let Some(bundle) = client_config["caBundle"].as_str().filter(|b| !b.is_empty()) {
+            let pem = STANDARD.decode(bundle).context("caBundle is not base64")?;
+            client = client.tls_built_in_root_certs(false);
+            for cert in reqwest::Certificate::from_pem_bundle(&pem).context("caBundle holds no PEM certificates")? {
+                client = client.add_root_certificate(cert);
+            }
+        }
+        let url = match (client_config["url"].as_str(), client_config.get("service")) {
+            (Some(url), _) => url.to_string(),
+            (None, Some(service)) => {
+                let (host, addr, url) = self.service_url(service).await?;
+                client = client.resolve(&host, addr);
+                url
+            }
+            (None, None) => bail!("clientConfig has neither url nor service"),
+        };
+
+        let response: Value = client
+            .build()?
+            .post(&url)
+            .json(review)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await
+            .context("response is not an AdmissionReview")?;
+
+        if response["apiVersion"] != "admission.k8s.io/v1" || response["kind"] != "AdmissionReview" {
+            bail!(
+                "expected response of kind AdmissionReview in admission.k8s.io/v1, got {} in {}",
+                response["kind"],
+                response["apiVersion"]
+            );
+        }
+        if response["response"]["uid"] != review["request"]["uid"] {
+            bail!(
+                "expected response.uid={}, got {}",
+                review["request"]["uid"],
+                response["response"]["uid"]
+            );
+        }
+        Ok(response)
+    }
+
+    // The URL for a webhook Service, and the endpoint its host name leads
+    // to. The host is the Service's DNS name, which the serving certificate
+    // is expected to carry, while the connection goes to one of its
+    // endpoints on the port behind the Service port.
+    async fn service_url(&self, reference: &Value) -> Result<(String, SocketAddr, String)> {
+        let namespace = reference["namespace"].as_str().ok_or_else(|| anyhow!("service reference without a namespace"))?;
+        let name = reference["name"].as_str().ok_or_else(|| anyhow!("service reference without a name"))?;
+        let port = reference["port"].as_i64().unwrap_or(443);
+        let path = reference["path"].as_str().unwrap_or("/");
+
+        let service = self.storage.services().get(namespace, name).await?;
+        let service_port = service["spec"]["ports"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|p| p["port"].as_i64() == Some(port))
+            .ok_or_else(|| anyhow!("service {}/{} has no port {}", namespace, name, port))?;
+
+        let endpoints = self.storage.endpoints().get(namespace, name).await.unwrap_or_default();
+        let (ip, target_port) = endpoints["subsets"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find_map(|subset| {
+                let ip: IpAddr = subset["addresses"][0]["ip"].as_str()?.parse().ok()?;
+                let ports = subset["ports"].as_array()?;
+                let endpoint_port = match ports.as_slice() {
+                    [only] => only,
+                    ports => ports.iter().find(|p| p["name"] == service_port["name"])?,
+                };
+                Some((ip, endpoint_port["port"].as_u64()?))
+            })
+            .ok_or_else(|| anyhow!("no endpoints available for service {}/{}", namespace, name))?;
+        let target_port = u16::try_from(target_port)?;
+
+        let host = format!("{}.{}.svc", name, namespace);
+        let url = format!("https://{}:{}{}", host, target_port, path);
+        Ok((host, SocketAddr::new(ip, target_port), url))
+    }
+}
+
+fn read(path: &str) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("failed to read {}", path))
+}
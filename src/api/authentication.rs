@@ -0,0 +1,269 @@
+// Client certificate and bearer token authentication. A client certificate
+// verified during the TLS handshake says who the caller is. Otherwise tokens
+// are checked as ServiceAccount tokens issued by krust, then against the
+// static ones from the config, then as OIDC ID tokens, then by the
+// authentication webhook; the first to accept one decides who the caller
+// is. Without any authenticator configured the server stays open, as it
+// always was, but ServiceAccount tokens still say who is calling so RBAC can
+// tell them apart.
+use axum::{
+    extract::{Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use tracing::{debug, warn};
+
+use super::authn_webhook::WebhookAuthenticator;
+use super::error_status::ApiError;
+use super::oidc::OidcAuthenticator;
+use super::server::AppState;
+use super::tls::ClientCertificate;
+use crate::config::{AuthenticationConfig, StaticToken};
+use crate::models::time;
+use crate::storage::serviceaccount_store::TokenOwner;
+use crate::storage::serviceaccount_token::ISSUER;
+use crate::Storage;
+
+// Reachable without a token, like the paths kube-apiserver's default RBAC
+// opens to everyone
+const PUBLIC_PATHS: &[&str] = &["/livez", "/readyz", "/healthz", "/version"];
+
+/// The authenticated caller of a request, as in a TokenReview.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UserInfo {
+    pub username: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub uid: Option<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra: BTreeMap<String, Vec<String>>,
+}
+
+impl UserInfo {
+    /// Who requests are made as when authentication is off.
+    pub fn anonymous() -> Self {
+        Self {
+            username: "system:anonymous".to_string(),
+            groups: vec!["system:unauthenticated".to_string()],
+            ..Default::default()
+        }
+    }
+}
+
+/// The configured authenticators.
+pub struct Authenticator {
+    storage: Storage,
+    tokens: Vec<StaticToken>,
+    oidc: Option<OidcAuthenticator>,
+    webhook: Option<WebhookAuthenticator>,
+}
+
+impl Authenticator {
+    pub fn new(config: &AuthenticationConfig, storage: Storage) -> Self {
+        Self {
+            storage,
+            tokens: config.tokens.clone(),
+            oidc: config.oidc.clone().map(OidcAuthenticator::new),
+            webhook: config.webhook.clone().map(WebhookAuthenticator::new),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        !self.tokens.is_empty() || self.oidc.is_some() || self.webhook.is_some()
+    }
+
+    /// The user a bearer token belongs to, or None if no authenticator
+    /// accepts it.
+    pub async fn authenticate(&self, token: &str) -> Option<UserInfo> {
+        self.review(token, &[]).await.ok().map(|(user, _)| user)
+    }
+
+    /// The user a bearer token belongs to and which of `audiences` it is
+    /// meant for, as a TokenReview answers it; no audiences means the API
+    /// server itself. Only ServiceAccount tokens name their audiences, so
+    /// the other authenticators are only good for the API server's.
+    pub async fn review(&self, token: &str, audiences: &[String]) -> Result<(UserInfo, Vec<String>), String> {
+        let audiences = match audiences {
+            [] => vec![ISSUER.to_string()],
+            audiences => audiences.to_vec(),
+        };
+        let (mut user, audiences) = self.identify(token, &audiences).await?;
+        if !user.groups.iter().any(|g| g == "system:authenticated") {
+            user.groups.push("system:authenticated".to_string());
+        }
+        Ok((user, audiences))
+    }
+
+    async fn identify(&self, token: &str, audiences: &[String]) -> Result<(UserInfo, Vec<String>), String> {
+        let mut rejection = "invalid bearer token".to_string();
+        if token.split('.').count() == 3 {
+            match self.storage.serviceaccounts().review_token(token, audiences).await {
+                Ok(owner) => return Ok((service_account_user(&owner), owner.audiences)),
+                Err(e) => {
+                    debug!("ServiceAccount token rejected: {}", e);
+                    rejection = e.to_string();
+                }
+            }
+        }
+
+        let api_server = vec![ISSUER.to_string()];
+        if !audiences.contains(&api_server[0]) {
+            return Err(rejection);
+        }
+        if let Some(known) = self.tokens.iter().find(|t| same_token(&t.token, token)) {
+            let user = UserInfo {
+                username: known.user.clone(),
+                uid: known.uid.clone(),
+                groups: known.groups.clone(),
+                ..Default::default()
+            };
+            return Ok((user, api_server));
+        }
+        if let Some(oidc) = &self.oidc {
+            // Only JWTs are worth verifying; anything else goes on to the webhook
+            if token.split('.').count() == 3 {
+                match oidc.authenticate(token).await {
+                    Ok(user) => return Ok((user, api_server)),
+                    Err(e) => debug!("OIDC token rejected: {}", e),
+                }
+            }
+        }
+        if let Some(webhook) = &self.webhook {
+            match webhook.authenticate(token).await {
+                Ok(Some(user)) => return Ok((user, api_server)),
+                Ok(None) => {}
+                Err(e) => warn!("Authentication webhook failed: {}", e),
+            }
+        }
+        Err(rejection)
+    }
+}
+
+// The user a ServiceAccount authenticates as, with the pod its token is
+// bound to and the token's ID as extras, like kube-apiserver's
+fn service_account_user(owner: &TokenOwner) -> UserInfo {
+    let mut extra = BTreeMap::new();
+    extra.insert(
+        "authentication.kubernetes.io/credential-id".to_string(),
+        vec![format!("JTI={}", owner.token_id)],
+    );
+    if let Some((name, uid)) = &owner.pod {
+        extra.insert("authentication.kubernetes.io/pod-name".to_string(), vec![name.clone()]);
+        extra.insert("authentication.kubernetes.io/pod-uid".to_string(), vec![uid.clone()]);
+    }
+    UserInfo {
+        username: format!("system:serviceaccount:{}:{}", owner.namespace, owner.name),
+        uid: Some(owner.uid.clone()),
+        groups: vec![
+            "system:serviceaccounts".to_string(),
+            format!("system:serviceaccounts:{}", owner.namespace),
+        ],
+        extra,
+    }
+}
+
+// Whether a bearer token is a static one, compared in constant time so how
+// long a wrong guess takes to turn down says nothing of how close it came
+fn same_token(known: &str, token: &str) -> bool {
+    known.len() == token.len() && openssl::memcmp::eq(known.as_bytes(), token.as_bytes())
+}
+
+/// Middleware authenticating every request by its bearer token and making
+/// the caller available to handlers as an `Extension<UserInfo>`.
+pub async fn authenticate(State(state): State<AppState>, mut request: Request, next: Next) -> Response {
+    // A verified client certificate says who the caller is before any token
+    if let Some(ClientCertificate(user)) = request.extensions().get::<ClientCertificate>().cloned() {
+        request.extensions_mut().insert(user);
+        return next.run(request).await;
+    }
+
+    let authenticator = &state.authenticator;
+    let token = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "))
+        .map(|t| t.trim().to_string())
+        .filter(|t| !t.is_empty());
+
+    if !authenticator.enabled() {
+        // Any token is fine, but a ServiceAccount's still names the caller
+        let user = match &token {
+            Some(token) => authenticator.authenticate(token).await,
+            None => None,
+        };
+        request.extensions_mut().insert(user.unwrap_or_else(UserInfo::anonymous));
+        return next.run(request).await;
+    }
+    if *request.method() == Method::OPTIONS || PUBLIC_PATHS.contains(&request.uri().path()) {
+        return next.run(request).await;
+    }
+
+    let Some(token) = token else {
+        return unauthorized();
+    };
+
+    match authenticator.authenticate(&token).await {
+        Some(user) => {
+            request.extensions_mut().insert(user);
+            next.run(request).await
+        }
+        None => unauthorized(),
+    }
+}
+
+fn unauthorized() -> Response {
+    ApiError::from(StatusCode::UNAUTHORIZED).into_response()
+}
+
+/// POST /apis/authentication.k8s.io/v1/selfsubjectreviews, which `kubectl
+/// auth whoami` uses to show who the server takes the caller for.
+pub async fn create_self_subject_review(Extension(user): Extension<UserInfo>) -> (StatusCode, Json<Value>) {
+    self_subject_review("authentication.k8s.io/v1", user)
+}
+
+/// POST /apis/authentication.k8s.io/v1beta1/selfsubjectreviews, which
+/// kubectl 1.27 and older use instead.
+pub async fn create_self_subject_review_v1beta1(Extension(user): Extension<UserInfo>) -> (StatusCode, Json<Value>) {
+    self_subject_review("authentication.k8s.io/v1beta1", user)
+}
+
+fn self_subject_review(api_version: &str, user: UserInfo) -> (StatusCode, Json<Value>) {
+    (
+        StatusCode::CREATED,
+        Json(json!({
+            "apiVersion": api_version,
+            "kind": "SelfSubjectReview",
+            "metadata": { "creationTimestamp": time::now() },
+            "status": { "userInfo": user }
+        })),
+    )
+}
+
+/// POST /apis/authentication.k8s.io/v1/tokenreviews, which lets workloads
+/// and webhooks check a token, typically a ServiceAccount's, and learn whom
+/// it belongs to.
+pub async fn create_token_review(State(state): State<AppState>, Json(mut review): Json<Value>) -> (StatusCode, Json<Value>) {
+    let token = review["spec"]["token"].as_str().unwrap_or_default().to_string();
+    let audiences: Vec<String> = review["spec"]["audiences"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|a| a.as_str().map(str::to_string))
+        .collect();
+
+    review["status"] = match state.authenticator.review(&token, &audiences).await {
+        Ok((user, audiences)) => json!({ "authenticated": true, "user": user, "audiences": audiences }),
+        Err(e) => json!({ "authenticated": false, "user": {}, "error": e }),
+    };
+    review["apiVersion"] = json!("authentication.k8s.io/v1");
+    review["kind"] = json!("TokenReview");
+    review["metadata"]["creationTimestamp"] = json!(time::now());
+    (StatusCode::CREATED, Json(review))
+}
@@ -0,0 +1,69 @@
+// Token authentication delegated to an external service: each token is sent
+// in a TokenReview, the way kube-apiserver's authentication webhook does it.
+// Accepted tokens are remembered for a while so that not every request makes
+// a round trip; rejected ones are asked about again.
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+use super::authentication::UserInfo;
+use crate::config::AuthenticationWebhookConfig;
+
+pub struct WebhookAuthenticator {
+    url: String,
+    cache_ttl: Duration,
+    client: reqwest::Client,
+    cache: Mutex<HashMap<String, (UserInfo, Instant)>>,
+}
+
+impl WebhookAuthenticator {
+    pub fn new(config: AuthenticationWebhookConfig) -> Self {
+        Self {
+            url: config.url,
+            cache_ttl: Duration::from_secs(config.cache_ttl_seconds),
+            client: reqwest::Client::new(),
+            cache: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The user the webhook says the token belongs to, or None if it
+    /// doesn't accept it.
+    pub async fn authenticate(&self, token: &str) -> Result<Option<UserInfo>> {
+        {
+            let mut cache = self.cache.lock().await;
+            cache.retain(|_, (_, cached)| cached.elapsed() < self.cache_ttl);
+            if let Some((user, _)) = cache.get(token) {
+                return Ok(Some(user.clone()));
+            }
+        }
+
+        let review = json!({
+            "apiVersion": "authentication.k8s.io/v1",
+            "kind": "TokenReview",
+            "spec": { "token": token }
+        });
+        let response: Value = self
+            .client
+            .post(&self.url)
+            .json(&review)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let status = &response["status"];
+        if status["authenticated"] != Value::Bool(true) {
+            return Ok(None);
+        }
+        let user: UserInfo = serde_json::from_value(status["user"].clone())?;
+        if user.username.is_empty() {
+            return Ok(None);
+        }
+
+        self.cache.lock().await.insert(token.to_string(), (user.clone(), Instant::now()));
+        Ok(Some(user))
+    }
+}
@@ -0,0 +1,275 @@
+// RBAC authorization, as kube-apiserver's --authorization-mode=RBAC does it:
+// a request is allowed when a rule of some Role or ClusterRole bound to the
+// caller covers its verb, resource and namespace. ClusterRoleBindings grant
+// their rules everywhere, RoleBindings only in their own namespace. Nothing
+// is allowed otherwise, except what the bootstrap roles below grant, which
+// exist whether or not they are stored: members of system:masters may do
+// anything, and everyone may use discovery, ask who they are and read the
+// cluster-info ConfigMap.
+use anyhow::Result;
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+use tracing::{error, warn};
+
+use super::authentication::UserInfo;
+use super::error_status::ApiError;
+use super::request_info::RequestInfo;
+use super::server::AppState;
+use crate::config::AuthorizationMode;
+use crate::Storage;
+
+// Bindings every cluster starts with: (ClusterRole, groups bound to it)
+const BOOTSTRAP_BINDINGS: &[(&str, &[&str])] = &[
+    ("cluster-admin", &["system:masters"]),
+    ("system:discovery", &["system:authenticated", "system:unauthenticated"]),
+    ("system:basic-user", &["system:authenticated", "system:unauthenticated"]),
+];
+
+// Groups view, edit and admin cover beyond the core group
+const WORKLOAD_GROUPS: &[&str] = &["", "apps", "batch", "autoscaling", "networking.k8s.io", "policy"];
+
+/// The rules of a ClusterRole that exists without being stored, like the
+/// default roles kube-apiserver creates. A stored ClusterRole of the same
+/// name takes precedence.
+pub fn bootstrap_cluster_role(name: &str) -> Option<Value> {
+    let rules = match name {
+        "cluster-admin" => json!([
+            { "apiGroups": ["*"], "resources": ["*"], "verbs": ["*"] },
+            { "nonResourceURLs": ["*"], "verbs": ["*"] }
+        ]),
+        // Everything in a namespace, RBAC included, when bound with a RoleBinding
+        "admin" => json!([{ "apiGroups": ["*"], "resources": ["*"], "verbs": ["*"] }]),
+        "edit" => json!([{ "apiGroups": WORKLOAD_GROUPS, "resources": ["*"], "verbs": ["*"] }]),
+        // Reading, but not Secrets
+        "view" => json!([
+            {
+                "apiGroups": [""],
+                "resources": [
+                    "pods", "pods/log", "pods/status", "services", "endpoints", "configmaps",
+                    "persistentvolumeclaims", "serviceaccounts", "events", "namespaces",
+                    "resourcequotas", "limitranges", "replicationcontrollers"
+                ],
+                "verbs": ["get", "list", "watch"]
+            },
+            { "apiGroups": &WORKLOAD_GROUPS[1..], "resources": ["*"], "verbs": ["get", "list", "watch"] }
+        ]),
+        "system:discovery" => json!([{
+            "nonResourceURLs": [
+                "/api", "/api/*", "/apis", "/apis/*", "/healthz", "/livez", "/readyz",
+                "/version", "/version/", "/openapi", "/openapi/*", "/swagger.json"
+            ],
+            "verbs": ["get"]
+        }]),
+        // For webhooks and aggregated servers checking callers on the API server's behalf
+        "system:auth-delegator" => json!([
+            { "apiGroups": ["authentication.k8s.io"], "resources": ["tokenreviews"], "verbs": ["create"] },
+            { "apiGroups": ["authorization.k8s.io"], "resources": ["subjectaccessreviews"], "verbs": ["create"] }
+        ]),
+        "system:basic-user" => json!([
+            { "apiGroups": ["authentication.k8s.io"], "resources": ["selfsubjectreviews"], "verbs": ["create"] },
+            {
+                "apiGroups": ["authorization.k8s.io"],
+                "resources": ["selfsubjectaccessreviews", "selfsubjectrulesreviews"],
+                "verbs": ["create"]
+            }
+        ]),
+        _ => return None,
+    };
+    Some(rules)
+}
+
+/// Whether RBAC lets `user` make the request described by `info`.
+pub async fn allowed(storage: &Storage, user: &UserInfo, info: &RequestInfo) -> Result<bool> {
+    for (role, groups) in BOOTSTRAP_BINDINGS {
+        if user.groups.iter().any(|g| groups.contains(&g.as_str())) && allows(&cluster_role(storage, role).await?, info) {
+            return Ok(true);
+        }
+    }
+
+    // kubeadm's Role in kube-public for clients that can't authenticate yet
+    if matches!(info, RequestInfo::Resource { namespace: Some(namespace), .. } if namespace == "kube-public") {
+        let cluster_info = json!([{ "apiGroups": [""], "resources": ["configmaps"], "resourceNames": ["cluster-info"], "verbs": ["get"] }]);
+        if allows(&cluster_info, info) {
+            return Ok(true);
+        }
+    }
+
+    let bindings = storage.clusterrolebindings().list().await?;
+    for binding in bindings["items"].as_array().into_iter().flatten() {
+        if binds(binding, user, None) {
+            let rules = role_rules(storage, &binding["roleRef"], None).await?;
+            if allows(&rules, info) {
+                return Ok(true);
+            }
+        }
+    }
+
+    let namespace = match info {
+        RequestInfo::Resource { namespace: Some(namespace), .. } => namespace,
+        _ => return Ok(false),
+    };
+    let bindings = storage.rolebindings().list(Some(namespace)).await?;
+    for binding in bindings["items"].as_array().into_iter().flatten() {
+        if binds(binding, user, Some(namespace)) {
+            let rules = role_rules(storage, &binding["roleRef"], Some(namespace)).await?;
+            if allows(&rules, info) {
+                return Ok(true);
+            }
+        }
+    }
+
+    Ok(false)
+}
+
+// Whether a binding's subjects include the user
+fn binds(binding: &Value, user: &UserInfo, namespace: Option<&str>) -> bool {
+    binding["subjects"].as_array().into_iter().flatten().any(|subject| {
+        let name = subject["name"].as_str().unwrap_or_default();
+        match subject["kind"].as_str() {
+            Some("User") => user.username == name,
+            Some("Group") => user.groups.iter().any(|g| g == name),
+            Some("ServiceAccount") => {
+                let namespace = subject["namespace"].as_str().or(namespace).unwrap_or_default();
+                user.username == format!("system:serviceaccount:{}:{}", namespace, name)
+            }
+            _ => false,
+        }
+    })
+}
+
+// The rules of the role a binding refers to; a RoleBinding may refer to a
+// Role in its namespace or a ClusterRole
+async fn role_rules(storage: &Storage, role_ref: &Value, namespace: Option<&str>) -> Result<Value> {
+    let name = role_ref["name"].as_str().unwrap_or_default();
+    match (role_ref["kind"].as_str(), namespace) {
+        (Some("Role"), Some(namespace)) => match storage.roles().get(namespace, name).await {
+            Ok(role) => Ok(role["rules"].clone()),
+            Err(e) if e.to_string().contains("not found") => Ok(Value::Null),
+            Err(e) => Err(e),
+        },
+        (Some("ClusterRole"), _) => cluster_role(storage, name).await,
+        _ => Ok(Value::Null),
+    }
+}
+
+async fn cluster_role(storage: &Storage, name: &str) -> Result<Value> {
+    match storage.clusterroles().get(name).await {
+        Ok(role) => Ok(role["rules"].clone()),
+        Err(e) if e.to_string().contains("not found") => Ok(bootstrap_cluster_role(name).unwrap_or(Value::Null)),
+        Err(e) => Err(e),
+    }
+}
+
+fn allows(rules: &Value, info: &RequestInfo) -> bool {
+    rules.as_array().into_iter().flatten().any(|rule| rule_allows(rule, info))
+}
+
+fn rule_allows(rule: &Value, info: &RequestInfo) -> bool {
+    let has = |field: &str, value: &str| {
+        rule[field]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .any(|item| item == "*" || item == value)
+    };
+    match info {
+        RequestInfo::Resource { verb, group, resource, subresource, name, .. } => {
+            let resource_matches = match subresource {
+                Some(sub) => rule["resources"].as_array().into_iter().flatten().any(|item| {
+                    item == "*" || *item == format!("{}/{}", resource, sub) || *item == format!("*/{}", sub)
+                }),
+                None => has("resources", resource),
+            };
+            // resourceNames only narrow requests for a single named object
+            let names = rule["resourceNames"].as_array().filter(|names| !names.is_empty());
+            let name_matches = match (names, name) {
+                (None, _) => true,
+                (Some(names), Some(name)) => names.iter().any(|n| n == name),
+                (Some(_), None) => false,
+            };
+            has("verbs", verb) && has("apiGroups", group) && resource_matches && name_matches
+        }
+        RequestInfo::NonResource { verb, path } => {
+            let path_matches = rule["nonResourceURLs"].as_array().into_iter().flatten().any(|url| {
+                let url = url.as_str().unwrap_or_default();
+                match url.strip_suffix('*') {
+                    Some(prefix) => path.starts_with(prefix),
+                    None => url == path,
+                }
+            });
+            has("verbs", verb) && path_matches
+        }
+    }
+}
+
+/// Middleware refusing requests RBAC doesn't allow with 403 Forbidden, or
+/// only logging them in permissive mode. Requests let in without
+/// authentication, the health checks, aren't checked.
+pub async fn authorize(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let config = &state.config.authorization;
+    if config.mode != AuthorizationMode::Rbac {
+        return next.run(request).await;
+    }
+    let Some(user) = request.extensions().get::<UserInfo>().cloned() else {
+        return next.run(request).await;
+    };
+
+    let uri = request.uri();
+    let info = RequestInfo::parse(request.method().as_str(), uri.path(), uri.query());
+    match allowed(&state.storage, &user, &info).await {
+        Ok(true) => next.run(request).await,
+        Ok(false) if config.permissive => {
+            warn!("RBAC would deny: {}", describe(&user, &info));
+            next.run(request).await
+        }
+        Ok(false) => forbidden(&user, &info),
+        Err(e) => {
+            error!("Failed to evaluate RBAC rules: {}", e);
+            ApiError::internal(&e).into_response()
+        }
+    }
+}
+
+// What the user may not do, in kube-apiserver's words
+fn describe(user: &UserInfo, info: &RequestInfo) -> String {
+    match info {
+        RequestInfo::Resource { verb, group, resource, subresource, namespace, .. } => {
+            let resource = match subresource {
+                Some(sub) => format!("{}/{}", resource, sub),
+                None => resource.clone(),
+            };
+            let scope = match namespace {
+                Some(namespace) => format!("in the namespace \"{}\"", namespace),
+                None => "at the cluster scope".to_string(),
+            };
+            format!(
+                "User \"{}\" cannot {} resource \"{}\" in API group \"{}\" {}",
+                user.username, verb, resource, group, scope
+            )
+        }
+        RequestInfo::NonResource { verb, path } => {
+            format!("User \"{}\" cannot {} path \"{}\"", user.username, verb, path)
+        }
+    }
+}
+
+fn forbidden(user: &UserInfo, info: &RequestInfo) -> Response {
+    let (subject, details) = match info {
+        RequestInfo::Resource { group, resource, name: Some(name), .. } => (
+            format!("{} \"{}\"", resource, name),
+            json!({ "name": name, "group": group, "kind": resource }),
+        ),
+        RequestInfo::Resource { group, resource, .. } => (resource.clone(), json!({ "group": group, "kind": resource })),
+        RequestInfo::NonResource { .. } => (String::new(), json!({})),
+    };
+    let message = match subject.as_str() {
+        "" => format!("forbidden: {}", describe(user, info)),
+        subject => format!("{} is forbidden: {}", subject, describe(user, info)),
+    };
+    ApiError::new(StatusCode::FORBIDDEN, "Forbidden", message).with_details(details).into_response()
+}
@@ -0,0 +1,92 @@
+// Cancellation of requests whose client has gone away. A request is
+// cancelled once hyper drops it: while it is being handled when the
+// connection closes, or along with its response body. Watches and followed
+// logs stop on it rather than on their next write, and the request no
+// longer counts as open (see Storage::open_requests).
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    middleware::Next,
+    response::Response,
+};
+use hyper::body::{Body as HttpBody, Frame, SizeHint};
+use std::pin::Pin;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tokio::sync::watch;
+
+use super::server::AppState;
+
+tokio::task_local! {
+    static CANCELLATION: Cancellation;
+}
+
+/// Resolves once the request it was taken in is cancelled.
+#[derive(Clone)]
+pub struct Cancellation(Option<watch::Receiver<()>>);
+
+impl Cancellation {
+    /// The cancellation of the request being handled. Outside of one it is
+    /// never cancelled.
+    pub fn current() -> Self {
+        CANCELLATION.try_with(Clone::clone).unwrap_or(Self(None))
+    }
+
+    /// Waits for the request to be cancelled.
+    pub async fn cancelled(&mut self) {
+        match &mut self.0 {
+            // Nothing is ever sent: the sender going away is the signal
+            Some(receiver) => while receiver.changed().await.is_ok() {},
+            None => std::future::pending().await,
+        }
+    }
+}
+
+// Held for as long as the request is in flight; dropping it cancels it
+struct InFlight {
+    _cancel: watch::Sender<()>,
+    requests: Arc<AtomicUsize>,
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.requests.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Middleware cancelling requests, and everything handling them, when their
+/// client goes away.
+pub async fn cancel_on_disconnect(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let requests = state.storage.requests();
+    requests.fetch_add(1, Ordering::SeqCst);
+    let (cancel, receiver) = watch::channel(());
+    let in_flight = InFlight { _cancel: cancel, requests };
+
+    let response = CANCELLATION.scope(Cancellation(Some(receiver)), next.run(request)).await;
+    // Streamed responses run until their body is done with
+    response.map(|body| Body::new(Tracked { body, _in_flight: in_flight }))
+}
+
+// A response body keeping its request in flight
+struct Tracked {
+    body: Body,
+    _in_flight: InFlight,
+}
+
+impl HttpBody for Tracked {
+    type Data = Bytes;
+    type Error = axum::Error;
+
+    fn poll_frame(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Result<Frame<Bytes>, axum::Error>>> {
+        Pin::new(&mut self.body).poll_frame(cx)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.body.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.body.size_hint()
+    }
+}
@@ -1,24 +1,28 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    response::Json,
+    response::{Json, Response},
 };
 use serde_json::{json, Value};
 use tracing::{error, info};
 
-use super::handlers::ListParams;
+use super::error_status::{ApiError, Cause};
+use super::handlers::{list_error, ListParams};
+use super::last_applied;
 use super::server::AppState;
+use super::watch::list_or_watch;
 
 // ConfigMap handlers
 pub async fn list_all_configmaps(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.configmaps().list(None).await {
-        Ok(list) => Ok(Json(list)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "configmaps", None, &params, &selector, state.storage.configmaps().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list configmaps: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -26,13 +30,14 @@ pub async fn list_all_configmaps(
 pub async fn list_configmaps(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.configmaps().list(Some(&namespace)).await {
-        Ok(list) => Ok(Json(list)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "configmaps", Some(&namespace), &params, &selector, state.storage.configmaps().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list configmaps in namespace {}: {}", namespace, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -41,14 +46,15 @@ pub async fn create_configmap(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(mut configmap): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     // Validate kind
     if configmap.get("kind").and_then(|k| k.as_str()) != Some("ConfigMap") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("ConfigMap", &configmap));
     }
 
     // Ensure namespace in metadata matches path
     configmap["metadata"]["namespace"] = json!(namespace);
+    let name = configmap["metadata"]["name"].as_str().unwrap_or_default().to_string();
 
     match state.storage.configmaps().create(&namespace, configmap).await {
         Ok(created) => {
@@ -58,9 +64,9 @@ pub async fn create_configmap(
         Err(e) => {
             error!("Failed to create configmap: {}", e);
             if e.to_string().contains("UNIQUE constraint") {
-                Err(StatusCode::CONFLICT)
+                Err(ApiError::already_exists("configmaps", &name))
             } else {
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -69,15 +75,15 @@ pub async fn create_configmap(
 pub async fn get_configmap(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.configmaps().get(&namespace, &name).await {
         Ok(configmap) => Ok(Json(configmap)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("configmaps", &name))
             } else {
                 error!("Failed to get configmap {}/{}: {}", namespace, name, e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -87,7 +93,7 @@ pub async fn update_configmap(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(mut configmap): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // Ensure namespace and name in metadata match path
     configmap["metadata"]["namespace"] = json!(namespace);
     configmap["metadata"]["name"] = json!(name);
@@ -99,12 +105,12 @@ pub async fn update_configmap(
         }
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("configmaps", &name))
             } else if e.to_string().contains("immutable") {
-                Err(StatusCode::UNPROCESSABLE_ENTITY)
+                Err(immutable(&name))
             } else {
                 error!("Failed to update configmap {}/{}: {}", namespace, name, e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -114,7 +120,12 @@ pub async fn patch_configmap(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(patch): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
+    let patch = match state.storage.configmaps().get(&namespace, &name).await {
+        Ok(current) => last_applied::with_removals(patch, &current),
+        Err(_) => patch,
+    };
+
     match state.storage.configmaps().patch(&namespace, &name, patch).await {
         Ok(patched) => {
             info!("Patched ConfigMap {}/{}", namespace, name);
@@ -122,12 +133,12 @@ pub async fn patch_configmap(
         }
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("configmaps", &name))
             } else if e.to_string().contains("immutable") {
-                Err(StatusCode::UNPROCESSABLE_ENTITY)
+                Err(immutable(&name))
             } else {
                 error!("Failed to patch configmap {}/{}: {}", namespace, name, e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -136,7 +147,7 @@ pub async fn patch_configmap(
 pub async fn delete_configmap(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.configmaps().delete(&namespace, &name).await {
         Ok(deleted) => {
             info!("Deleted ConfigMap {}/{}", namespace, name);
@@ -144,11 +155,16 @@ pub async fn delete_configmap(
         }
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("configmaps", &name))
             } else {
                 error!("Failed to delete configmap {}/{}: {}", namespace, name, e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
-}
\ No newline at end of file
+}
+
+// The 422 for changing the data of an immutable ConfigMap
+fn immutable(name: &str) -> ApiError {
+    ApiError::invalid("configmaps", name, vec![Cause::forbidden("data", "field is immutable when `immutable` is set")])
+}
@@ -0,0 +1,177 @@
+// Optimistic concurrency: an update or patch carrying metadata.resourceVersion
+// only goes through if that is still the object's stored version, and gets
+// 409 Conflict otherwise, so a writer working from a stale read finds out
+// instead of silently undoing someone else's write. One without a version
+// overwrites as before. A delete's preconditions, its uid and
+// resourceVersion, are held to the object the same way. The check is a
+// compare-and-swap on the object's row, made in a transaction the write then
+// goes through, so nothing lands between the two.
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::Value;
+use tower::Service;
+use tracing::error;
+
+use super::delete_options::{self, Preconditions};
+use super::dry_run;
+use super::error_status::ApiError;
+use super::object_path::{self, ObjectPath};
+use super::server::{self, AppState};
+
+/// Middleware checking the resourceVersion precondition of PUT and PATCH
+/// requests, and the preconditions of DELETE requests, against the stored
+/// object.
+pub async fn check_resource_version(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if *request.method() == Method::DELETE {
+        return check_preconditions(state, request, next).await;
+    }
+    if !matches!(*request.method(), Method::PUT | Method::PATCH) {
+        return next.run(request).await;
+    }
+    let Some(object) = object_path::parse(request.uri().path()) else {
+        return next.run(request).await;
+    };
+
+    // The precondition is in the body, which the handler still needs
+    let (parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to buffer request body: {}", e);
+            return ApiError::bad_request(e.to_string()).into_response();
+        }
+    };
+    let expected = serde_json::from_slice::<Value>(&bytes)
+        .ok()
+        .and_then(|body| body["metadata"]["resourceVersion"].as_str().map(|v| v.to_string()))
+        .filter(|version| !version.is_empty());
+    let request = Request::from_parts(parts, Body::from(bytes));
+
+    let Some(expected) = expected else {
+        return next.run(request).await;
+    };
+    let preconditions = Preconditions { uid: None, resource_version: Some(expected) };
+    write_claimed(state, request, next, &object, &preconditions).await
+}
+
+// Refuses a delete whose DeleteOptions preconditions the object doesn't meet
+async fn check_preconditions(state: AppState, request: Request, next: Next) -> Response {
+    let Some(object) = object_path::parse(request.uri().path()).filter(|object| object.subresource.is_none()) else {
+        return next.run(request).await;
+    };
+    let preconditions = delete_options::of(&request).preconditions;
+    if preconditions.uid.is_none() && preconditions.resource_version.is_none() {
+        return next.run(request).await;
+    }
+    write_claimed(state, request, next, &object, &preconditions).await
+}
+
+// Sends the request on once the object is claimed at its preconditions,
+// in a transaction committed if the write succeeds
+async fn write_claimed(state: AppState, request: Request, next: Next, object: &ObjectPath, preconditions: &Preconditions) -> Response {
+    let delete = *request.method() == Method::DELETE;
+    // A dry run's transaction, or this one on its way back through the
+    // routes: the claim holds until it ends
+    if state.storage.in_transaction() {
+        return match claim(&state, object, preconditions).await {
+            Ok(true) => next.run(request).await,
+            Ok(false) => match refusal(&state, object, preconditions, delete).await {
+                Some(refusal) => refusal,
+                None => next.run(request).await,
+            },
+            Err(failure) => failure,
+        };
+    }
+
+    let tx = match state.storage.transaction().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            error!("Failed to start a write: {}", e);
+            return ApiError::internal(&e).into_response();
+        }
+    };
+    let claimed = AppState { storage: (*tx).clone(), ..state.clone() };
+    match claim(&claimed, object, preconditions).await {
+        Ok(true) => {}
+        Ok(false) => {
+            if let Err(e) = tx.rollback().await {
+                error!("Failed to roll back a refused write: {}", e);
+            }
+            return match refusal(&state, object, preconditions, delete).await {
+                Some(refusal) => refusal,
+                None => next.run(request).await,
+            };
+        }
+        Err(failure) => return failure,
+    }
+
+    let response = match server::checked_routes(claimed).call(dry_run::rerouted(request)).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    };
+    let finished = if response.status().is_success() { tx.commit().await } else { tx.rollback().await };
+    if let Err(e) = finished {
+        error!("Failed to finish a write: {}", e);
+        return ApiError::internal(&e).into_response();
+    }
+    response
+}
+
+async fn claim(state: &AppState, object: &ObjectPath, preconditions: &Preconditions) -> Result<bool, Response> {
+    let ObjectPath { resource, namespace, name, .. } = object;
+    let Preconditions { uid, resource_version } = preconditions;
+    state
+        .storage
+        .claim_object(resource, namespace.as_deref(), name, uid.as_deref(), resource_version.as_deref())
+        .await
+        .map_err(|e| {
+            error!("Failed to claim {} {}: {}", resource, name, e);
+            ApiError::internal(&e).into_response()
+        })
+}
+
+// Why the object couldn't be claimed. None if it doesn't exist, which is the
+// handler's to report.
+async fn refusal(state: &AppState, object: &ObjectPath, preconditions: &Preconditions, delete: bool) -> Option<Response> {
+    let ObjectPath { resource, namespace, name, .. } = object;
+    let current = async {
+        let uid = state.storage.finalizers().uid_of(resource, namespace.as_deref(), name).await?;
+        let version = state.storage.resource_version_of(resource, namespace.as_deref(), name).await?;
+        anyhow::Ok((uid, version))
+    };
+    let (current_uid, current_version) = match current.await {
+        Ok((Some(uid), Some(version))) => (uid, version),
+        Ok(_) => return None,
+        Err(e) => {
+            error!("Failed to read the uid and resource version of {} {}: {}", resource, name, e);
+            return Some(ApiError::internal(&e).into_response());
+        }
+    };
+
+    let Preconditions { uid, resource_version } = preconditions;
+    // An update's version is in its body; its conflict reads as Kubernetes' does
+    if !delete {
+        return Some(conflict(resource, name, MODIFIED));
+    }
+    if let Some(expected) = uid.as_ref().filter(|expected| **expected != current_uid) {
+        let reason = format!("Precondition failed: UID in precondition: {}, UID in object meta: {}", expected, current_uid);
+        return Some(conflict(resource, name, &reason));
+    }
+    let expected = resource_version.as_deref().unwrap_or_default();
+    let reason = format!(
+        "Precondition failed: ResourceVersion in precondition: {}, ResourceVersion in object meta: {}",
+        expected, current_version
+    );
+    Some(conflict(resource, name, &reason))
+}
+
+const MODIFIED: &str = "the object has been modified; please apply your changes to the latest version and try again";
+
+fn conflict(resource: &str, name: &str, reason: &str) -> Response {
+    ApiError::conflict(resource, name, reason).into_response()
+}
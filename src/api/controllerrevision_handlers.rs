@@ -0,0 +1,113 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Response,
+    Json,
+};
+use serde_json::Value;
+use tracing::{error, info};
+
+use crate::api::error_status::ApiError;
+use crate::api::handlers::{list_error, ListParams};
+use crate::api::server::AppState;
+use crate::api::watch::list_or_watch;
+
+pub async fn create_controllerrevision(
+    State(state): State<AppState>,
+    Path(namespace): Path<String>,
+    Json(revision): Json<Value>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    if revision.get("kind").and_then(|k| k.as_str()) != Some("ControllerRevision") {
+        return Err(ApiError::wrong_kind("ControllerRevision", &revision));
+    }
+    if !revision["revision"].is_i64() {
+        return Err(ApiError::bad_request("revision is required"));
+    }
+
+    info!(
+        "Creating ControllerRevision {} in namespace {}",
+        revision["metadata"]["name"].as_str().unwrap_or("unknown"),
+        namespace
+    );
+
+    let name = revision["metadata"]["name"].as_str().unwrap_or_default().to_string();
+
+    match state.storage.controller_revisions().create(&namespace, revision).await {
+        Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) => {
+            if e.to_string().contains("UNIQUE constraint failed") {
+                Err(ApiError::already_exists("controllerrevisions", &name))
+            } else if e.to_string().contains("required") {
+                Err(ApiError::bad_request("name or generateName is required"))
+            } else {
+                error!("Failed to create ControllerRevision: {}", e);
+                Err(ApiError::internal(&e))
+            }
+        }
+    }
+}
+
+pub async fn get_controllerrevision(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<Json<Value>, ApiError> {
+    match state.storage.controller_revisions().get(&namespace, &name).await {
+        Ok(revision) => Ok(Json(revision)),
+        Err(e) => {
+            if e.to_string().contains("not found") {
+                Err(ApiError::not_found("controllerrevisions", &name))
+            } else {
+                error!("Failed to get ControllerRevision: {}", e);
+                Err(ApiError::internal(&e))
+            }
+        }
+    }
+}
+
+pub async fn list_controllerrevisions(
+    State(state): State<AppState>,
+    Path(namespace): Path<String>,
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "controllerrevisions", Some(&namespace), &params, &selector, state.storage.controller_revisions().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
+        Err(e) => {
+            error!("Failed to list ControllerRevisions: {}", e);
+            Err(list_error(&e))
+        }
+    }
+}
+
+pub async fn list_all_controllerrevisions(
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "controllerrevisions", None, &params, &selector, state.storage.controller_revisions().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
+        Err(e) => {
+            error!("Failed to list all ControllerRevisions: {}", e);
+            Err(list_error(&e))
+        }
+    }
+}
+
+pub async fn delete_controllerrevision(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<Json<Value>, ApiError> {
+    info!("Deleting ControllerRevision {} in namespace {}", name, namespace);
+
+    match state.storage.controller_revisions().delete(&namespace, &name).await {
+        Ok(deleted) => Ok(Json(deleted)),
+        Err(e) => {
+            if e.to_string().contains("not found") {
+                Err(ApiError::not_found("controllerrevisions", &name))
+            } else {
+                error!("Failed to delete ControllerRevision: {}", e);
+                Err(ApiError::internal(&e))
+            }
+        }
+    }
+}
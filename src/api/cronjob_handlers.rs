@@ -1,21 +1,25 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
+    response::Response,
     Json,
 };
 use serde_json::{json, Value};
 use tracing::{error, info};
 
+use crate::api::error_status::ApiError;
+use crate::api::handlers::{list_error, ListParams};
 use crate::api::server::AppState;
+use crate::api::watch::list_or_watch;
 
 pub async fn create_cronjob(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(mut cronjob): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     // Validate kind
     if cronjob.get("kind").and_then(|k| k.as_str()) != Some("CronJob") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("CronJob", &cronjob));
     }
 
     // Ensure metadata exists
@@ -29,24 +33,18 @@ pub async fn create_cronjob(
         namespace
     );
 
+    let name = cronjob["metadata"]["name"].as_str().unwrap_or_default().to_string();
+
     let store = state.storage.cronjobs();
     match store.create(&namespace, cronjob).await {
         Ok(created_cronjob) => Ok((StatusCode::CREATED, Json(created_cronjob))),
         Err(e) => {
             if e.to_string().contains("UNIQUE constraint failed") {
                 error!("CronJob already exists: {}", e);
-                return Ok((StatusCode::CONFLICT, Json(json!({
-                    "kind": "Status",
-                    "apiVersion": "v1",
-                    "metadata": {},
-                    "status": "Failure",
-                    "message": "CronJob already exists",
-                    "reason": "AlreadyExists",
-                    "code": 409
-                }))));
+                return Err(ApiError::already_exists("cronjobs", &name));
             }
             error!("Failed to create CronJob: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -54,7 +52,7 @@ pub async fn create_cronjob(
 pub async fn get_cronjob(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Getting CronJob {} in namespace {}", name, namespace);
     
     let store = state.storage.cronjobs();
@@ -62,11 +60,11 @@ pub async fn get_cronjob(
         Ok(cronjob) => Ok(Json(cronjob)),
         Err(e) if e.to_string().contains("not found") => {
             error!("CronJob {}/{} not found", namespace, name);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("cronjobs", &name))
         }
         Err(e) => {
             error!("Failed to get CronJob: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -74,30 +72,34 @@ pub async fn get_cronjob(
 pub async fn list_cronjobs_namespaced(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing CronJobs in namespace {}", namespace);
     
+    let selector = params.selector()?;
     let store = state.storage.cronjobs();
-    match store.list(Some(&namespace)).await {
-        Ok(list) => Ok(Json(list)),
+    match list_or_watch(&state, "cronjobs", Some(&namespace), &params, &selector, store.list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list CronJobs: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
 
 pub async fn list_cronjobs_all_namespaces(
     State(state): State<AppState>,
-) -> Result<Json<Value>, StatusCode> {
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing CronJobs in all namespaces");
     
+    let selector = params.selector()?;
     let store = state.storage.cronjobs();
-    match store.list(None).await {
-        Ok(list) => Ok(Json(list)),
+    match list_or_watch(&state, "cronjobs", None, &params, &selector, store.list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list CronJobs: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -106,7 +108,7 @@ pub async fn update_cronjob_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(status_update): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Updating CronJob status {} in namespace {}", name, namespace);
     
     let store = state.storage.cronjobs();
@@ -126,13 +128,13 @@ pub async fn update_cronjob_status(
                 Ok(cronjob) => Ok(Json(cronjob)),
                 Err(e) => {
                     error!("Failed to retrieve updated CronJob: {}", e);
-                    Err(StatusCode::INTERNAL_SERVER_ERROR)
+                    Err(ApiError::internal(&e))
                 }
             }
         }
         Err(e) => {
             error!("Failed to update CronJob status: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -140,7 +142,7 @@ pub async fn update_cronjob_status(
 pub async fn delete_cronjob(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Deleting CronJob {} in namespace {}", name, namespace);
     
     let store = state.storage.cronjobs();
@@ -148,11 +150,11 @@ pub async fn delete_cronjob(
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) if e.to_string().contains("not found") => {
             error!("CronJob {}/{} not found", namespace, name);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("cronjobs", &name))
         }
         Err(e) => {
             error!("Failed to delete CronJob: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
\ No newline at end of file
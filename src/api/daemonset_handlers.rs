@@ -1,21 +1,26 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
     Json,
 };
 use serde_json::Value;
 use tracing::{error, info};
 
+use crate::api::error_status::ApiError;
+use crate::api::handlers::{list_error, ListParams};
+use crate::api::patch;
 use crate::api::server::AppState;
+use crate::api::watch::list_or_watch;
 
 pub async fn create_daemonset(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(mut daemonset): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     // Validate kind
     if daemonset.get("kind").and_then(|k| k.as_str()) != Some("DaemonSet") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("DaemonSet", &daemonset));
     }
 
     // Ensure metadata exists
@@ -29,15 +34,17 @@ pub async fn create_daemonset(
         namespace
     );
 
+    let name = daemonset["metadata"]["name"].as_str().unwrap_or_default().to_string();
+
     match state.storage.daemonsets().create(&namespace, daemonset).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
         Err(e) => {
             if e.to_string().contains("UNIQUE constraint failed") {
                 error!("DaemonSet already exists: {}", e);
-                Err(StatusCode::CONFLICT)
+                Err(ApiError::already_exists("daemonsets", &name))
             } else {
                 error!("Failed to create DaemonSet: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -46,17 +53,17 @@ pub async fn create_daemonset(
 pub async fn get_daemonset(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Getting DaemonSet {} in namespace {}", name, namespace);
 
     match state.storage.daemonsets().get(&namespace, &name).await {
         Ok(daemonset) => Ok(Json(daemonset)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("daemonsets", &name))
             } else {
                 error!("Failed to get DaemonSet: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -65,26 +72,32 @@ pub async fn get_daemonset(
 pub async fn list_daemonsets(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing DaemonSets in namespace {}", namespace);
 
-    match state.storage.daemonsets().list(Some(&namespace)).await {
-        Ok(list) => Ok(Json(list)),
+    let selector = params.selector()?;
+    match list_or_watch(&state, "daemonsets", Some(&namespace), &params, &selector, state.storage.daemonsets().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list DaemonSets: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
 
-pub async fn list_all_daemonsets(State(state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+pub async fn list_all_daemonsets(
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing all DaemonSets");
 
-    match state.storage.daemonsets().list(None).await {
-        Ok(list) => Ok(Json(list)),
+    let selector = params.selector()?;
+    match list_or_watch(&state, "daemonsets", None, &params, &selector, state.storage.daemonsets().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list all DaemonSets: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -93,10 +106,10 @@ pub async fn update_daemonset(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(daemonset): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // Validate kind
     if daemonset.get("kind").and_then(|k| k.as_str()) != Some("DaemonSet") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("DaemonSet", &daemonset));
     }
 
     info!("Updating DaemonSet {} in namespace {}", name, namespace);
@@ -105,29 +118,82 @@ pub async fn update_daemonset(
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("daemonsets", &name))
             } else {
                 error!("Failed to update DaemonSet: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
 }
 
+pub async fn patch_daemonset(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(patch): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    info!("Patching DaemonSet {} in namespace {}", name, namespace);
+
+    let mut daemonset = match state.storage.daemonsets().get(&namespace, &name).await {
+        Ok(daemonset) => daemonset,
+        Err(e) if e.to_string().contains("not found") => return Err(ApiError::not_found("daemonsets", &name)),
+        Err(e) => {
+            error!("Failed to get DaemonSet: {}", e);
+            return Err(ApiError::internal(&e));
+        }
+    };
+    patch::apply(&headers, &mut daemonset, patch)?;
+
+    match state.storage.daemonsets().update(&namespace, &name, daemonset).await {
+        Ok(updated) => Ok(Json(updated)),
+        Err(e) => {
+            error!("Failed to patch DaemonSet: {}", e);
+            Err(ApiError::internal(&e))
+        }
+    }
+}
+
 pub async fn get_daemonset_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Getting DaemonSet status {} in namespace {}", name, namespace);
 
     match state.storage.daemonsets().get(&namespace, &name).await {
         Ok(daemonset) => Ok(Json(daemonset)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("daemonsets", &name))
             } else {
                 error!("Failed to get DaemonSet status: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
+            }
+        }
+    }
+}
+
+pub async fn update_daemonset_status(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+    Json(status_update): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    info!("Updating DaemonSet status {} in namespace {}", name, namespace);
+
+    let store = state.storage.daemonsets();
+    if let Err(e) = store.update_status(&namespace, &name, status_update["status"].clone()).await {
+        error!("Failed to update DaemonSet status: {}", e);
+        return Err(ApiError::internal(&e));
+    }
+
+    match store.get(&namespace, &name).await {
+        Ok(daemonset) => Ok(Json(daemonset)),
+        Err(e) => {
+            if e.to_string().contains("not found") {
+                Err(ApiError::not_found("daemonsets", &name))
+            } else {
+                error!("Failed to get updated DaemonSet: {}", e);
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -136,17 +202,17 @@ pub async fn get_daemonset_status(
 pub async fn delete_daemonset(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Deleting DaemonSet {} in namespace {}", name, namespace);
 
     match state.storage.daemonsets().delete(&namespace, &name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("daemonsets", &name))
             } else {
                 error!("Failed to delete DaemonSet: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
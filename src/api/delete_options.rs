@@ -0,0 +1,164 @@
+// DeleteOptions, which come with a DELETE in its body, as kubectl sends
+// them, or as query parameters: how long a pod gets to stop
+// (gracePeriodSeconds), what becomes of the objects the deleted one owns
+// (propagationPolicy, or the older orphanDependents) and what the object
+// must still be for the delete to go ahead (preconditions), and whether it's
+// only a dry run (dryRun). They're read once here for the middleware inside:
+// dry_run rolls back dry runs, conflicts checks the preconditions and
+// finalizers acts on the rest.
+use axum::{
+    body::Body,
+    extract::{Query, Request},
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tracing::error;
+
+use super::error_status::ApiError;
+
+/// What becomes of the objects that the deleted one owns.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PropagationPolicy {
+    /// They're left, no longer owned by it.
+    Orphan,
+    /// It's deleted now, and the garbage collector deletes them after.
+    Background,
+    /// It stays, marked with the foregroundDeletion finalizer, until the
+    /// garbage collector has deleted them.
+    Foreground,
+}
+
+/// What the object must be for the delete to go ahead.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Preconditions {
+    pub uid: Option<String>,
+    pub resource_version: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DeleteOptions {
+    pub grace_period_seconds: Option<i64>,
+    pub propagation_policy: Option<PropagationPolicy>,
+    pub preconditions: Preconditions,
+    pub dry_run: Option<Vec<String>>,
+}
+
+impl DeleteOptions {
+    /// The options in a DELETE's query parameters and body, the body's
+    /// winning where both have one. Negative grace periods count as one
+    /// second, as Kubernetes has it.
+    pub fn parse(query: &HashMap<String, String>, body: &[u8]) -> Result<Self, String> {
+        let mut fields = json!({});
+        for (key, value) in query {
+            fields[key] = match key.as_str() {
+                "gracePeriodSeconds" => json!(value.parse::<i64>().map_err(|_| invalid("gracePeriodSeconds", value, "must be an integer"))?),
+                "orphanDependents" => json!(value == "true"),
+                _ => json!(value),
+            };
+        }
+        if !body.iter().all(u8::is_ascii_whitespace) {
+            let body: Value = serde_json::from_slice(body).map_err(|e| format!("invalid DeleteOptions: {}", e))?;
+            for (key, value) in body.as_object().into_iter().flatten() {
+                fields[key] = value.clone();
+            }
+        }
+
+        let propagation_policy = match fields["propagationPolicy"].as_str() {
+            None => None,
+            Some("Orphan") => Some(PropagationPolicy::Orphan),
+            Some("Background") => Some(PropagationPolicy::Background),
+            Some("Foreground") => Some(PropagationPolicy::Foreground),
+            Some(other) => {
+                return Err(invalid(
+                    "propagationPolicy",
+                    other,
+                    "supported values: \"Foreground\", \"Background\", \"Orphan\"",
+                ))
+            }
+        };
+        let propagation_policy = match (propagation_policy, fields["orphanDependents"].as_bool()) {
+            (Some(policy), Some(_)) => {
+                return Err(invalid(
+                    "propagationPolicy",
+                    &format!("{:?}", policy),
+                    "orphanDependents and deletionPropagation cannot be both set",
+                ))
+            }
+            (None, Some(true)) => Some(PropagationPolicy::Orphan),
+            (None, Some(false)) => Some(PropagationPolicy::Background),
+            (policy, None) => policy,
+        };
+
+        // One from the query, a list of them from the body
+        let dry_run = match &fields["dryRun"] {
+            Value::Null => None,
+            Value::String(value) => Some(vec![value.clone()]),
+            Value::Array(values) => Some(
+                values
+                    .iter()
+                    .map(|value| value.as_str().map(str::to_string).ok_or_else(|| invalid("dryRun", &value.to_string(), "must be a string")))
+                    .collect::<Result<Vec<_>, _>>()?,
+            ),
+            other => return Err(invalid("dryRun", &other.to_string(), "must be a list of strings")),
+        };
+        if let Some(other) = dry_run.iter().flatten().find(|value| *value != "All") {
+            return Err(super::dry_run::unsupported(other));
+        }
+
+        let preconditions = &fields["preconditions"];
+        Ok(Self {
+            grace_period_seconds: fields["gracePeriodSeconds"].as_i64().map(|seconds| if seconds < 0 { 1 } else { seconds }),
+            propagation_policy,
+            preconditions: Preconditions {
+                uid: preconditions["uid"].as_str().map(str::to_string),
+                resource_version: preconditions["resourceVersion"].as_str().map(str::to_string),
+            },
+            dry_run,
+        })
+    }
+
+    /// Whether the delete is only a dry run.
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run.as_ref().is_some_and(|dry_run| !dry_run.is_empty())
+    }
+}
+
+/// Middleware reading the DeleteOptions of DELETE requests into their
+/// extensions, and refusing ones that aren't valid with 400 Bad Request.
+pub async fn read_delete_options(request: Request, next: Next) -> Response {
+    if *request.method() != Method::DELETE {
+        return next.run(request).await;
+    }
+    let query = Query::<HashMap<String, String>>::try_from_uri(request.uri()).map(|Query(query)| query).unwrap_or_default();
+    let (mut parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to buffer request body: {}", e);
+            return ApiError::bad_request(e.to_string()).into_response();
+        }
+    };
+    match DeleteOptions::parse(&query, &bytes) {
+        Ok(options) => {
+            parts.extensions.insert(options);
+            next.run(Request::from_parts(parts, Body::from(bytes))).await
+        }
+        Err(message) => bad_request(&message),
+    }
+}
+
+/// The DeleteOptions read for a request, or the defaults.
+pub fn of(request: &Request) -> DeleteOptions {
+    request.extensions().get::<DeleteOptions>().cloned().unwrap_or_default()
+}
+
+fn invalid(field: &str, value: &str, detail: &str) -> String {
+    format!("DeleteOptions.meta.k8s.io \"\" is invalid: {}: Invalid value: {:?}: {}", field, value, detail)
+}
+
+fn bad_request(message: &str) -> Response {
+    ApiError::bad_request(message).into_response()
+}
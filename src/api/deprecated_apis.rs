@@ -0,0 +1,178 @@
+// Legacy API group versions that Kubernetes has removed but older clients and
+// manifests still use. krust doesn't serve them; instead of an empty 404 the
+// client gets a Status explaining what replaced the API, and each request is
+// counted so /krust/deprecations can show what still depends on them.
+use axum::{
+    http::{header::HeaderName, StatusCode, Uri},
+    response::{IntoResponse, Response},
+};
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use super::error_status::ApiError;
+use crate::models::time;
+
+pub struct RemovedApi {
+    pub group_version: &'static str,
+    pub resource: &'static str,
+    pub kind: &'static str,
+    pub replacement: &'static str,
+    pub deprecated_in: &'static str,
+    pub removed_in: &'static str,
+}
+
+const fn removed(
+    group_version: &'static str,
+    resource: &'static str,
+    kind: &'static str,
+    replacement: &'static str,
+    deprecated_in: &'static str,
+    removed_in: &'static str,
+) -> RemovedApi {
+    RemovedApi { group_version, resource, kind, replacement, deprecated_in, removed_in }
+}
+
+/// Removed APIs for resources krust serves in a current version.
+pub const REMOVED_APIS: &[RemovedApi] = &[
+    removed("extensions/v1beta1", "ingresses", "Ingress", "networking.k8s.io/v1", "1.14", "1.22"),
+    removed("extensions/v1beta1", "deployments", "Deployment", "apps/v1", "1.9", "1.16"),
+    removed("extensions/v1beta1", "daemonsets", "DaemonSet", "apps/v1", "1.9", "1.16"),
+    removed("extensions/v1beta1", "replicasets", "ReplicaSet", "apps/v1", "1.9", "1.16"),
+    removed("extensions/v1beta1", "networkpolicies", "NetworkPolicy", "networking.k8s.io/v1", "1.9", "1.16"),
+    removed("apps/v1beta1", "deployments", "Deployment", "apps/v1", "1.9", "1.16"),
+    removed("apps/v1beta1", "statefulsets", "StatefulSet", "apps/v1", "1.9", "1.16"),
+    removed("apps/v1beta2", "deployments", "Deployment", "apps/v1", "1.9", "1.16"),
+    removed("apps/v1beta2", "statefulsets", "StatefulSet", "apps/v1", "1.9", "1.16"),
+    removed("apps/v1beta2", "daemonsets", "DaemonSet", "apps/v1", "1.9", "1.16"),
+    removed("apps/v1beta2", "replicasets", "ReplicaSet", "apps/v1", "1.9", "1.16"),
+    removed("networking.k8s.io/v1beta1", "ingresses", "Ingress", "networking.k8s.io/v1", "1.19", "1.22"),
+    removed("batch/v1beta1", "cronjobs", "CronJob", "batch/v1", "1.21", "1.25"),
+    removed("policy/v1beta1", "poddisruptionbudgets", "PodDisruptionBudget", "policy/v1", "1.21", "1.25"),
+    removed("autoscaling/v2beta1", "horizontalpodautoscalers", "HorizontalPodAutoscaler", "autoscaling/v2", "1.22", "1.25"),
+    removed("autoscaling/v2beta2", "horizontalpodautoscalers", "HorizontalPodAutoscaler", "autoscaling/v2", "1.23", "1.26"),
+    removed("rbac.authorization.k8s.io/v1beta1", "roles", "Role", "rbac.authorization.k8s.io/v1", "1.17", "1.22"),
+    removed("rbac.authorization.k8s.io/v1beta1", "rolebindings", "RoleBinding", "rbac.authorization.k8s.io/v1", "1.17", "1.22"),
+    removed("rbac.authorization.k8s.io/v1beta1", "clusterroles", "ClusterRole", "rbac.authorization.k8s.io/v1", "1.17", "1.22"),
+    removed("rbac.authorization.k8s.io/v1beta1", "clusterrolebindings", "ClusterRoleBinding", "rbac.authorization.k8s.io/v1", "1.17", "1.22"),
+    removed("scheduling.k8s.io/v1beta1", "priorityclasses", "PriorityClass", "scheduling.k8s.io/v1", "1.14", "1.22"),
+    removed("storage.k8s.io/v1beta1", "storageclasses", "StorageClass", "storage.k8s.io/v1", "1.19", "1.22"),
+    removed("admissionregistration.k8s.io/v1beta1", "validatingwebhookconfigurations", "ValidatingWebhookConfiguration", "admissionregistration.k8s.io/v1", "1.16", "1.22"),
+    removed("admissionregistration.k8s.io/v1beta1", "mutatingwebhookconfigurations", "MutatingWebhookConfiguration", "admissionregistration.k8s.io/v1", "1.16", "1.22"),
+];
+
+/// Current API groups krust doesn't implement at all, with what that means
+/// for clients that try to use them.
+pub const UNIMPLEMENTED_GROUPS: &[(&str, &str)] = &[
+    (
+        "apiextensions.k8s.io",
+        "CustomResourceDefinitions are not supported by krust yet, so custom resources, \
+         CRD version conversion (None or Webhook strategy) and structural schema \
+         validation and pruning are unavailable",
+    ),
+];
+
+#[derive(Clone)]
+pub struct LegacyUsage {
+    pub requests: u64,
+    pub last_requested: String,
+}
+
+// Requests seen per "group/version/resource" since startup
+lazy_static::lazy_static! {
+    static ref LEGACY_USAGE: Mutex<HashMap<String, LegacyUsage>> = Mutex::new(HashMap::new());
+}
+
+/// Fallback for every unmatched route. Requests for a removed API get a
+/// Status naming its replacement, requests for an unimplemented group say so,
+/// and everything else gets a plain NotFound Status.
+pub async fn not_found(uri: Uri) -> Response {
+    if let Some((group_version, resource)) = parse_apis_path(uri.path()) {
+        let group = group_version.split('/').next().unwrap_or("");
+        if let Some((_, reason)) = UNIMPLEMENTED_GROUPS.iter().find(|(name, _)| *name == group) {
+            return status_not_found(&format!("{} is not served: {}", group_version, reason)).into_response();
+        }
+
+        let known: Vec<&RemovedApi> = REMOVED_APIS
+            .iter()
+            .filter(|api| api.group_version == group_version)
+            .collect();
+
+        if !known.is_empty() {
+            let api = known.iter().find(|api| Some(api.resource) == resource.as_deref());
+            record_usage(&group_version, resource.as_deref().unwrap_or(""));
+
+            let message = match api {
+                Some(api) => format!(
+                    "{} {} is no longer served: it was removed in Kubernetes v{}, use {} {} instead",
+                    api.group_version, api.kind, api.removed_in, api.replacement, api.kind
+                ),
+                None => {
+                    let mut replacements: Vec<&str> = known.iter().map(|api| api.replacement).collect();
+                    replacements.sort();
+                    replacements.dedup();
+                    format!(
+                        "{} is no longer served: it was removed from Kubernetes, use {} instead",
+                        group_version,
+                        replacements.join(" or ")
+                    )
+                }
+            };
+
+            let mut response = status_not_found(&message).into_response();
+            if let Some(api) = api {
+                // Same shape as the deprecation warnings kube-apiserver sends
+                let warning = format!(
+                    "299 - \"{} {} is deprecated in v{}+, unavailable in v{}+; use {} {}\"",
+                    api.group_version, api.kind, api.deprecated_in, api.removed_in, api.replacement, api.kind
+                );
+                if let Ok(value) = warning.parse() {
+                    response.headers_mut().insert(HeaderName::from_static("warning"), value);
+                }
+            }
+            return response;
+        }
+    }
+
+    status_not_found("the server could not find the requested resource").into_response()
+}
+
+/// Snapshot of the legacy API requests seen so far, keyed by group/version/resource.
+pub fn usage() -> HashMap<String, LegacyUsage> {
+    LEGACY_USAGE.lock().unwrap().clone()
+}
+
+fn record_usage(group_version: &str, resource: &str) {
+    let key = format!("{}/{}", group_version, resource).trim_end_matches('/').to_string();
+    let mut usage = LEGACY_USAGE.lock().unwrap();
+    let entry = usage.entry(key).or_insert(LegacyUsage {
+        requests: 0,
+        last_requested: String::new(),
+    });
+    entry.requests += 1;
+    entry.last_requested = time::now();
+}
+
+// Splits /apis/<group>/<version>[/namespaces/<ns>]/<resource>/... into the
+// group version and the resource, if any.
+fn parse_apis_path(path: &str) -> Option<(String, Option<String>)> {
+    let mut segments = path.trim_matches('/').split('/');
+    if segments.next()? != "apis" {
+        return None;
+    }
+    let group = segments.next()?;
+    let version = segments.next()?;
+    let rest: Vec<&str> = segments.collect();
+
+    let resource = match rest.as_slice() {
+        ["namespaces", _, resource, ..] => Some(resource.to_string()),
+        [resource, ..] if !resource.is_empty() => Some(resource.to_string()),
+        _ => None,
+    };
+
+    Some((format!("{}/{}", group, version), resource))
+}
+
+fn status_not_found(message: &str) -> ApiError {
+    ApiError::new(StatusCode::NOT_FOUND, "NotFound", message).with_details(json!({}))
+}
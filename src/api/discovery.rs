@@ -0,0 +1,326 @@
+// API discovery, as kubectl reads it to map kinds and short names to
+// resources: /version, /api, /apis and the APIResourceList of each group
+// version. The group versions served are the ones listed here, and what
+// each of their resources offers isn't written down twice: its verbs,
+// whether it's namespaced and its subresources are read off the group
+// version's routes, by asking each path which methods it takes, so
+// discovery never offers what isn't served. The kinds, short names and
+// categories, which the routes can't say, come from RESOURCES.
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, Method, StatusCode},
+    response::Json,
+    routing::get,
+    Router,
+};
+use serde_json::{json, Value};
+use tokio::sync::OnceCell;
+use tower::Service;
+
+use super::routes;
+use super::server::{AppState, KUBERNETES_VERSION};
+use crate::models::time;
+
+/// A group version and the routes serving its resources.
+pub struct GroupVersion {
+    /// "" for the core group
+    pub group: &'static str,
+    pub version: &'static str,
+    pub routes: fn() -> Router<AppState>,
+}
+
+impl GroupVersion {
+    /// Where its resources are served: /api/v1 or /apis/<group>/<version>.
+    pub fn path(&self) -> String {
+        match self.group {
+            "" => format!("/api/{}", self.version),
+            group => format!("/apis/{}/{}", group, self.version),
+        }
+    }
+
+    /// The apiVersion of its objects: v1 or <group>/<version>.
+    pub fn group_version(&self) -> String {
+        match self.group {
+            "" => self.version.to_string(),
+            group => format!("{}/{}", group, self.version),
+        }
+    }
+}
+
+const fn group_version(group: &'static str, version: &'static str, routes: fn() -> Router<AppState>) -> GroupVersion {
+    GroupVersion { group, version, routes }
+}
+
+/// The group versions served. A group's preferred version is listed first.
+pub const GROUP_VERSIONS: &[GroupVersion] = &[
+    group_version("", "v1", routes::v1_routes),
+    group_version("apps", "v1", routes::apps_v1_routes),
+    group_version("batch", "v1", routes::batch_v1_routes),
+    group_version("networking.k8s.io", "v1", routes::networking_v1_routes),
+    group_version("autoscaling", "v2", routes::autoscaling_v2_routes),
+    group_version("autoscaling", "v1", routes::autoscaling_v1_routes),
+    group_version("rbac.authorization.k8s.io", "v1", routes::rbac_v1_routes),
+    group_version("policy", "v1", routes::policy_v1_routes),
+    group_version("coordination.k8s.io", "v1", routes::coordination_v1_routes),
+    group_version("scheduling.k8s.io", "v1", routes::scheduling_v1_routes),
+    group_version("storage.k8s.io", "v1", routes::storage_v1_routes),
+    group_version("admissionregistration.k8s.io", "v1", routes::admissionregistration_v1_routes),
+    group_version("authentication.k8s.io", "v1", routes::authentication_v1_routes),
+    group_version("authentication.k8s.io", "v1beta1", routes::authentication_v1beta1_routes),
+];
+
+// Resource name, kind, short names and categories of every resource krust
+// may serve, in the order discovery lists them
+const RESOURCES: &[(&str, &str, &[&str], &[&str])] = &[
+    ("namespaces", "Namespace", &["ns"], &[]),
+    ("pods", "Pod", &["po"], &["all"]),
+    ("services", "Service", &["svc"], &["all"]),
+    ("endpoints", "Endpoints", &["ep"], &[]),
+    ("configmaps", "ConfigMap", &["cm"], &[]),
+    ("secrets", "Secret", &[], &[]),
+    ("persistentvolumes", "PersistentVolume", &["pv"], &[]),
+    ("persistentvolumeclaims", "PersistentVolumeClaim", &["pvc"], &[]),
+    ("nodes", "Node", &["no"], &[]),
+    ("resourcequotas", "ResourceQuota", &["quota"], &[]),
+    ("limitranges", "LimitRange", &["limits"], &[]),
+    ("events", "Event", &["ev"], &[]),
+    ("serviceaccounts", "ServiceAccount", &["sa"], &[]),
+    ("deployments", "Deployment", &["deploy"], &["all"]),
+    ("replicasets", "ReplicaSet", &["rs"], &["all"]),
+    ("statefulsets", "StatefulSet", &["sts"], &["all"]),
+    ("daemonsets", "DaemonSet", &["ds"], &["all"]),
+    ("controllerrevisions", "ControllerRevision", &[], &[]),
+    ("jobs", "Job", &[], &["all"]),
+    ("cronjobs", "CronJob", &["cj"], &["all"]),
+    ("networkpolicies", "NetworkPolicy", &["netpol"], &[]),
+    ("ingresses", "Ingress", &["ing"], &[]),
+    ("horizontalpodautoscalers", "HorizontalPodAutoscaler", &["hpa"], &["all"]),
+    ("roles", "Role", &[], &[]),
+    ("rolebindings", "RoleBinding", &[], &[]),
+    ("clusterroles", "ClusterRole", &[], &[]),
+    ("clusterrolebindings", "ClusterRoleBinding", &[], &[]),
+    ("poddisruptionbudgets", "PodDisruptionBudget", &["pdb"], &[]),
+    ("leases", "Lease", &[], &[]),
+    ("priorityclasses", "PriorityClass", &["pc"], &[]),
+    ("storageclasses", "StorageClass", &["sc"], &[]),
+    ("validatingwebhookconfigurations", "ValidatingWebhookConfiguration", &[], &[]),
+    ("mutatingwebhookconfigurations", "MutatingWebhookConfiguration", &[], &[]),
+    ("selfsubjectreviews", "SelfSubjectReview", &[], &[]),
+    ("tokenreviews", "TokenReview", &[], &[]),
+];
+
+/// The kind of `resource`'s objects, if krust serves it.
+pub(crate) fn kind(resource: &str) -> Option<&'static str> {
+    RESOURCES.iter().find(|(name, ..)| *name == resource).map(|(_, kind, ..)| *kind)
+}
+
+// The group, version and kind a subresource takes, if not its resource's
+type Takes = Option<(&'static str, &'static str, &'static str)>;
+
+// The subresources looked for on every resource
+const SUBRESOURCES: &[(&str, Takes)] = &[
+    ("status", None),
+    ("scale", Some(("autoscaling", "v1", "Scale"))),
+    ("log", None),
+    ("exec", Some(("", "v1", "PodExecOptions"))),
+    ("attach", Some(("", "v1", "PodAttachOptions"))),
+    ("portforward", Some(("", "v1", "PodPortForwardOptions"))),
+    ("binding", Some(("", "v1", "Binding"))),
+    ("eviction", Some(("policy", "v1", "Eviction"))),
+    ("ephemeralcontainers", None),
+    ("token", Some(("authentication.k8s.io", "v1", "TokenRequest"))),
+    ("finalize", None),
+];
+
+/// Adds the discovery documents and every group version's routes.
+pub fn routes(mut router: Router<AppState>) -> Router<AppState> {
+    router = router
+        .route("/version", get(version))
+        .route("/api", get(api_versions))
+        .route("/apis", get(api_groups));
+    for (i, group_version) in GROUP_VERSIONS.iter().enumerate() {
+        let path = group_version.path();
+        router = router
+            .route(&path, get(move |State(state): State<AppState>| api_resources(state, i)))
+            .nest(&path, (group_version.routes)());
+    }
+    router
+}
+
+async fn version() -> Json<Value> {
+    let minor = KUBERNETES_VERSION.split('.').nth(1).unwrap_or_default();
+    Json(json!({
+        "major": "1",
+        "minor": minor,
+        "gitVersion": format!("{}-krust", KUBERNETES_VERSION),
+        "gitCommit": "000000",
+        "gitTreeState": "clean",
+        "buildDate": time::now(),
+        "goVersion": "rust1.75",
+        "compiler": "rustc",
+        "platform": format!("{}/{}", std::env::consts::OS, go_arch())
+    }))
+}
+
+// The architecture as Go names it, which is what kubectl expects
+fn go_arch() -> &'static str {
+    match std::env::consts::ARCH {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "x86" => "386",
+        arch => arch,
+    }
+}
+
+async fn api_versions() -> Json<Value> {
+    let versions: Vec<&str> = GROUP_VERSIONS.iter().filter(|gv| gv.group.is_empty()).map(|gv| gv.version).collect();
+    Json(json!({
+        "kind": "APIVersions",
+        "versions": versions,
+        "serverAddressByClientCIDRs": [{
+            "clientCIDR": "0.0.0.0/0",
+            "serverAddress": "127.0.0.1:6443"
+        }]
+    }))
+}
+
+async fn api_groups() -> Json<Value> {
+    let mut groups: Vec<Value> = Vec::new();
+    for group_version in GROUP_VERSIONS.iter().filter(|gv| !gv.group.is_empty()) {
+        let version = json!({ "groupVersion": group_version.group_version(), "version": group_version.version });
+        match groups.iter_mut().find(|g| g["name"] == group_version.group) {
+            Some(group) => group["versions"].as_array_mut().unwrap().push(version),
+            None => groups.push(json!({
+                "name": group_version.group,
+                "versions": [version.clone()],
+                "preferredVersion": version
+            })),
+        }
+    }
+    Json(json!({
+        "kind": "APIGroupList",
+        "apiVersion": "v1",
+        "groups": groups
+    }))
+}
+
+// The resource lists, worked out from the routes the first time one is asked
+// for. Every server has the same routes, so they're kept for the process.
+static RESOURCE_LISTS: OnceCell<Vec<Value>> = OnceCell::const_new();
+
+/// The APIResourceList of each of GROUP_VERSIONS, in the same order.
+pub async fn resource_lists(state: &AppState) -> &'static [Value] {
+    RESOURCE_LISTS
+        .get_or_init(|| async {
+            let mut lists = Vec::new();
+            for group_version in GROUP_VERSIONS {
+                lists.push(resource_list(group_version, state).await);
+            }
+            lists
+        })
+        .await
+}
+
+async fn api_resources(state: AppState, index: usize) -> Json<Value> {
+    Json(resource_lists(&state).await[index].clone())
+}
+
+async fn resource_list(group_version: &GroupVersion, state: &AppState) -> Value {
+    let mut router = (group_version.routes)().with_state(state.clone());
+    let mut resources = Vec::new();
+    for (name, kind, short_names, categories) in RESOURCES {
+        let namespaced = methods(&mut router, &format!("/namespaces/probe/{}", name)).await.is_some();
+        let collection = if namespaced { format!("/namespaces/probe/{}", name) } else { format!("/{}", name) };
+        let object = format!("{}/probe", collection);
+
+        let mut verbs = Vec::new();
+        for method in methods(&mut router, &collection).await.unwrap_or_default() {
+            match method.as_str() {
+                "GET" => verbs.extend(["list", "watch"]),
+                "POST" => verbs.push("create"),
+                "DELETE" => verbs.push("deletecollection"),
+                _ => {}
+            }
+        }
+        let object_methods = methods(&mut router, &object).await.unwrap_or_default();
+        verbs.extend(object_verbs(&object_methods));
+        if verbs.is_empty() {
+            continue;
+        }
+        verbs.sort();
+        verbs.dedup();
+
+        let mut resource = json!({
+            "name": name,
+            "singularName": kind.to_lowercase(),
+            "namespaced": namespaced,
+            "kind": kind,
+            "verbs": verbs
+        });
+        if !short_names.is_empty() {
+            resource["shortNames"] = json!(short_names);
+        }
+        if !categories.is_empty() {
+            resource["categories"] = json!(categories);
+        }
+        resources.push(resource);
+
+        for (subresource, takes) in SUBRESOURCES {
+            let Some(methods) = methods(&mut router, &format!("{}/{}", object, subresource)).await else {
+                continue;
+            };
+            let mut verbs = object_verbs(&methods);
+            verbs.sort();
+            let mut entry = json!({
+                "name": format!("{}/{}", name, subresource),
+                "singularName": "",
+                "namespaced": namespaced,
+                "kind": kind,
+                "verbs": verbs
+            });
+            if let Some((group, version, kind)) = takes {
+                if !group.is_empty() {
+                    entry["group"] = json!(group);
+                    entry["version"] = json!(version);
+                }
+                entry["kind"] = json!(kind);
+            }
+            resources.push(entry);
+        }
+    }
+
+    json!({
+        "kind": "APIResourceList",
+        "apiVersion": "v1",
+        "groupVersion": group_version.group_version(),
+        "resources": resources
+    })
+}
+
+fn object_verbs(methods: &[Method]) -> Vec<&'static str> {
+    methods
+        .iter()
+        .filter_map(|method| match method.as_str() {
+            "GET" => Some("get"),
+            "POST" => Some("create"),
+            "PUT" => Some("update"),
+            "PATCH" => Some("patch"),
+            "DELETE" => Some("delete"),
+            _ => None,
+        })
+        .collect()
+}
+
+// The methods a path of the router takes, None if it has no route. TRACE
+// isn't served anywhere, so the router answers it with a 405 listing them
+// without calling a handler.
+async fn methods(router: &mut Router, path: &str) -> Option<Vec<Method>> {
+    let request = Request::builder().method(Method::TRACE).uri(path).body(Body::empty()).ok()?;
+    let response = router.call(request).await.ok()?;
+    if response.status() != StatusCode::METHOD_NOT_ALLOWED {
+        return None;
+    }
+    let allow = response.headers().get(header::ALLOW)?.to_str().ok()?;
+    Some(allow.split(',').filter_map(|method| method.trim().parse().ok()).collect())
+}
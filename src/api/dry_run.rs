@@ -0,0 +1,89 @@
+// Server-side dry runs. A create, update, patch or delete with
+// `?dryRun=All`, or a delete with `dryRun: ["All"]` in its DeleteOptions,
+// goes through admission, validation and defaulting like any
+// other. Once the webhooks have admitted it, the rest runs inside a
+// transaction that's rolled back when it's answered: the reply is the object
+// as it would have been written, and nothing is kept or sent to watches.
+// Admission webhooks are told of the dry run, and the ones that say they
+// have side effects turn it down.
+use axum::{
+    extract::{Query, Request, State},
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::collections::HashMap;
+use tower::Service;
+use tracing::error;
+
+use super::delete_options::DeleteOptions;
+use super::error_status::ApiError;
+use super::server::{self, AppState};
+
+/// Whether the request is a dry run. `All` is the only kind there is;
+/// anything else is refused.
+pub fn requested(request: &Request) -> Result<bool, ApiError> {
+    // A delete's options have it from its query or body, already checked
+    if let Some(options) = request.extensions().get::<DeleteOptions>() {
+        return Ok(options.is_dry_run());
+    }
+    let Ok(Query(query)) = Query::<HashMap<String, String>>::try_from_uri(request.uri()) else {
+        return Ok(false);
+    };
+    match query.get("dryRun").map(String::as_str) {
+        None => Ok(false),
+        Some("All") => Ok(true),
+        Some(other) => Err(ApiError::bad_request(unsupported(other))),
+    }
+}
+
+/// The refusal of a kind of dry run other than `All`.
+pub fn unsupported(value: &str) -> String {
+    format!("Unsupported value: {:?}: supported values: \"All\"", value)
+}
+
+/// Middleware running dry-run writes against a transaction it rolls back.
+pub async fn roll_back_dry_runs(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if !matches!(*request.method(), Method::POST | Method::PUT | Method::PATCH | Method::DELETE) {
+        return next.run(request).await;
+    }
+    match requested(&request) {
+        Ok(true) => {}
+        Ok(false) => return next.run(request).await,
+        Err(refusal) => return refusal.into_response(),
+    }
+
+    let tx = match state.storage.transaction().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            error!("Failed to start a dry run: {}", e);
+            return ApiError::internal(&e).into_response();
+        }
+    };
+    let dry = AppState { storage: (*tx).clone(), ..state };
+    let response = match server::checked_routes(dry).call(rerouted(request)).await {
+        Ok(response) => response,
+        Err(never) => match never {},
+    };
+    if let Err(e) = tx.rollback().await {
+        error!("Failed to roll back a dry run: {}", e);
+        return ApiError::internal(&e).into_response();
+    }
+    response
+}
+
+/// The request as it came in, without the path parameters its route matched,
+/// which routing it again would add to. The delete options read from its body
+/// go with it.
+pub(super) fn rerouted(request: Request) -> Request {
+    let (parts, body) = request.into_parts();
+    let mut rerouted = Request::new(body);
+    *rerouted.method_mut() = parts.method;
+    *rerouted.uri_mut() = parts.uri;
+    *rerouted.version_mut() = parts.version;
+    *rerouted.headers_mut() = parts.headers;
+    if let Some(options) = parts.extensions.get::<DeleteOptions>() {
+        rerouted.extensions_mut().insert(options.clone());
+    }
+    rerouted
+}
@@ -0,0 +1,269 @@
+// Status bodies for failed requests. Handlers fail with an ApiError, which
+// is sent as the Status kube-apiserver would send: a reason clients act on
+// (NotFound, AlreadyExists, Conflict, Invalid...), a message naming the
+// object, and details saying which object it is and, for invalid ones,
+// which of its fields are wrong. kubectl prints the message, and client-go
+// decides what to do by the reason.
+//
+// The router answers some requests itself: a method a route doesn't serve
+// with an empty 405, a body whose Content-Type its extractors can't read
+// with a plain-text 415, and a path no route matches with an empty 404.
+// status_bodies gives those a Status too.
+use axum::{
+    body::HttpBody,
+    extract::Request,
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Json, Response},
+};
+use serde_json::{json, Value};
+use std::fmt;
+
+use super::discovery;
+use crate::storage::tables;
+
+// What kube-apiserver accepts, as it lists them when refusing a body
+const MEDIA_TYPES: &str = "application/json";
+const PATCH_TYPES: &str =
+    "application/json-patch+json, application/merge-patch+json, application/apply-patch+yaml, application/strategic-merge-patch+json";
+
+/// A failed request, answered with a Status.
+#[derive(Debug, Clone)]
+pub struct ApiError {
+    pub code: StatusCode,
+    pub reason: String,
+    pub message: String,
+    /// The Status's details, left out when null
+    pub details: Value,
+}
+
+/// What's wrong with one field of an invalid object, as a Status's details
+/// list it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cause {
+    /// FieldValueRequired, FieldValueInvalid and the like
+    pub reason: &'static str,
+    pub field: String,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(code: StatusCode, reason: &str, message: impl Into<String>) -> Self {
+        Self { code, reason: reason.to_string(), message: message.into(), details: Value::Null }
+    }
+
+    pub fn with_details(mut self, details: Value) -> Self {
+        self.details = details;
+        self
+    }
+
+    /// `name` of `resource`, e.g. `pods` or `deployments`, doesn't exist.
+    pub fn not_found(resource: &str, name: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "NotFound", format!("{} \"{}\" not found", qualified(resource), name))
+            .with_details(object_details(resource, name))
+    }
+
+    /// Creating `name` of `resource` failed because it's already there.
+    pub fn already_exists(resource: &str, name: &str) -> Self {
+        Self::new(StatusCode::CONFLICT, "AlreadyExists", format!("{} \"{}\" already exists", qualified(resource), name))
+            .with_details(object_details(resource, name))
+    }
+
+    /// A write to `name` of `resource` that can't be made as things stand,
+    /// such as one from a stale resourceVersion.
+    pub fn conflict(resource: &str, name: &str, reason: impl fmt::Display) -> Self {
+        let message = format!("Operation cannot be fulfilled on {} \"{}\": {}", qualified(resource), name, reason);
+        Self::new(StatusCode::CONFLICT, "Conflict", message).with_details(object_details(resource, name))
+    }
+
+    /// A request for `name` of `resource` refused for `reason`.
+    pub fn forbidden(resource: &str, name: &str, reason: impl fmt::Display) -> Self {
+        let message = format!("{} \"{}\" is forbidden: {}", qualified(resource), name, reason);
+        Self::new(StatusCode::FORBIDDEN, "Forbidden", message).with_details(object_details(resource, name))
+    }
+
+    /// `name` of `resource` can't be written as it is, for `causes`.
+    pub fn invalid(resource: &str, name: &str, causes: Vec<Cause>) -> Self {
+        let kind = discovery::kind(resource).unwrap_or(resource);
+        let group = tables::group(resource);
+        let qualified_kind = if group.is_empty() { kind.to_string() } else { format!("{}.{}", kind, group) };
+        let listed: Vec<String> = causes.iter().map(Cause::to_string).collect();
+        let summary = match listed.as_slice() {
+            [cause] => cause.clone(),
+            causes => format!("[{}]", causes.join(", ")),
+        };
+        let mut details = json!({ "name": name, "kind": kind });
+        if !group.is_empty() {
+            details["group"] = json!(group);
+        }
+        details["causes"] = causes.iter().map(|c| json!({ "reason": c.reason, "message": c.message, "field": c.field })).collect();
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, "Invalid", format!("{} \"{}\" is invalid: {}", qualified_kind, name, summary))
+            .with_details(details)
+    }
+
+    pub fn bad_request(message: impl Into<String>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "BadRequest", message)
+    }
+
+    /// `object` was sent to the endpoint of another kind than its own.
+    pub fn wrong_kind(expected: &str, object: &Value) -> Self {
+        Self::bad_request(format!("the kind of the object ({}) does not match the expected kind ({})", object["kind"].as_str().unwrap_or_default(), expected))
+    }
+
+    /// Something went wrong on krust's side; `error` says what.
+    pub fn internal(error: impl fmt::Display) -> Self {
+        let error = error.to_string();
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "InternalError", format!("Internal error occurred: {}", error))
+            .with_details(json!({ "causes": [{ "message": error }] }))
+    }
+
+    /// The Status object itself.
+    pub fn status(&self) -> Value {
+        let mut status = json!({
+            "kind": "Status",
+            "apiVersion": "v1",
+            "metadata": {},
+            "status": "Failure",
+            "message": self.message,
+            "reason": self.reason,
+            "code": self.code.as_u16()
+        });
+        if !self.details.is_null() {
+            status["details"] = self.details.clone();
+        }
+        status
+    }
+}
+
+/// Errors only known by their status code, such as those of the extractors,
+/// get the reason and message kube-apiserver gives that code.
+impl From<StatusCode> for ApiError {
+    fn from(code: StatusCode) -> Self {
+        let (reason, message) = match code {
+            StatusCode::BAD_REQUEST => ("BadRequest", "the server rejected our request for an unknown reason"),
+            StatusCode::UNAUTHORIZED => ("Unauthorized", "Unauthorized"),
+            StatusCode::FORBIDDEN => ("Forbidden", "forbidden"),
+            StatusCode::NOT_FOUND => ("NotFound", "the server could not find the requested resource"),
+            StatusCode::METHOD_NOT_ALLOWED => ("MethodNotAllowed", "the server does not allow this method on the requested resource"),
+            StatusCode::NOT_ACCEPTABLE => ("NotAcceptable", "the server was unable to respond with a content type that the client supports"),
+            StatusCode::CONFLICT => ("Conflict", "the server reported a conflict"),
+            StatusCode::GONE => ("Gone", "the requested resource is no longer available"),
+            StatusCode::PAYLOAD_TOO_LARGE => ("RequestEntityTooLarge", "the request entity is too large"),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE => ("UnsupportedMediaType", "the server does not support the requested media type"),
+            StatusCode::UNPROCESSABLE_ENTITY => ("Invalid", "the server rejected our request due to an error in our request"),
+            StatusCode::TOO_MANY_REQUESTS => ("TooManyRequests", "the server has received too many requests and has asked us to try again later"),
+            StatusCode::SERVICE_UNAVAILABLE => ("ServiceUnavailable", "the server is currently unable to handle the request"),
+            StatusCode::GATEWAY_TIMEOUT => ("Timeout", "the server was unable to return a response in the time allotted"),
+            _ if code.is_server_error() => ("InternalError", "an error on the server has prevented the request from succeeding"),
+            _ => ("", code.canonical_reason().unwrap_or_default()),
+        };
+        Self::new(code, reason, message)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.code, Json(self.status())).into_response()
+    }
+}
+
+impl fmt::Display for ApiError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl Cause {
+    pub fn required(field: &str) -> Self {
+        Self { reason: "FieldValueRequired", field: field.to_string(), message: "Required value".to_string() }
+    }
+
+    /// `value`, given as JSON, isn't valid for `field` because of `detail`.
+    pub fn invalid(field: &str, value: &Value, detail: &str) -> Self {
+        Self { reason: "FieldValueInvalid", field: field.to_string(), message: format!("Invalid value: {}: {}", value, detail) }
+    }
+
+    pub fn not_supported(field: &str, value: &Value, supported: &[&str]) -> Self {
+        let supported: Vec<String> = supported.iter().map(|s| format!("{:?}", s)).collect();
+        let message = format!("Unsupported value: {}: supported values: {}", value, supported.join(", "));
+        Self { reason: "FieldValueNotSupported", field: field.to_string(), message }
+    }
+
+    pub fn forbidden(field: &str, detail: &str) -> Self {
+        Self { reason: "FieldValueForbidden", field: field.to_string(), message: format!("Forbidden: {}", detail) }
+    }
+
+    /// A cause from a field error worded as kube-apiserver words them,
+    /// `<field>: <what's wrong>`, as the stores and models report them.
+    pub fn parse(error: &str) -> Self {
+        let (field, message) = error.split_once(": ").unwrap_or(("", error));
+        let reason = [
+            ("Required value", "FieldValueRequired"),
+            ("Invalid value", "FieldValueInvalid"),
+            ("Unsupported value", "FieldValueNotSupported"),
+            ("Forbidden", "FieldValueForbidden"),
+            ("Duplicate value", "FieldValueDuplicate"),
+            ("Too long", "FieldValueTooLong"),
+            ("Too many", "FieldValueTooMany"),
+            ("Not found", "FieldValueNotFound"),
+        ]
+        .iter()
+        .find(|(prefix, _)| message.starts_with(prefix))
+        .map_or("FieldValueInvalid", |(_, reason)| reason);
+        Self { reason, field: field.to_string(), message: message.to_string() }
+    }
+}
+
+impl fmt::Display for Cause {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.field, self.message)
+    }
+}
+
+// A resource as kube-apiserver names it in messages: with its group, as in
+// `deployments.apps`, unless it's in the core group
+fn qualified(resource: &str) -> String {
+    match tables::group(resource) {
+        "" => resource.to_string(),
+        group => format!("{}.{}", resource, group),
+    }
+}
+
+fn object_details(resource: &str, name: &str) -> Value {
+    let mut details = json!({ "name": name, "kind": resource });
+    let group = tables::group(resource);
+    if !group.is_empty() {
+        details["group"] = json!(group);
+    }
+    details
+}
+
+/// Middleware giving 405 and 415 responses without a JSON body, and empty
+/// 404s, a Status, keeping their headers (Allow among them).
+pub async fn status_bodies(request: Request, next: Next) -> Response {
+    let patch = request.method() == Method::PATCH;
+    let response = next.run(request).await;
+
+    let status = response.status();
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    let error = match status {
+        _ if is_json => return response,
+        StatusCode::NOT_FOUND if response.body().size_hint().exact() == Some(0) => ApiError::from(status),
+        StatusCode::METHOD_NOT_ALLOWED => ApiError::from(status),
+        StatusCode::UNSUPPORTED_MEDIA_TYPE => {
+            let accepted = if patch { PATCH_TYPES } else { MEDIA_TYPES };
+            let message = format!("the body of the request was in an unknown format - accepted media types include: {}", accepted);
+            ApiError::new(status, "UnsupportedMediaType", message)
+        }
+        _ => return response,
+    };
+
+    let (mut parts, _) = response.into_parts();
+    parts.headers.remove(header::CONTENT_TYPE);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    (parts, Json(error.with_details(json!({})).status())).into_response()
+}
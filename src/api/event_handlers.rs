@@ -0,0 +1,112 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Json, Response},
+};
+use serde_json::{json, Value};
+use tracing::{error, info};
+
+use super::error_status::ApiError;
+use super::handlers::{list_error, ListParams};
+use super::server::AppState;
+use super::watch::list_or_watch;
+
+// Event handlers
+pub async fn list_all_events(
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "events", None, &params, &selector, state.storage.events().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
+        Err(e) => {
+            error!("Failed to list events: {}", e);
+            Err(list_error(&e))
+        }
+    }
+}
+
+pub async fn list_events(
+    State(state): State<AppState>,
+    Path(namespace): Path<String>,
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "events", Some(&namespace), &params, &selector, state.storage.events().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
+        Err(e) => {
+            error!("Failed to list events in namespace {}: {}", namespace, e);
+            Err(list_error(&e))
+        }
+    }
+}
+
+pub async fn create_event(
+    State(state): State<AppState>,
+    Path(namespace): Path<String>,
+    Json(mut event): Json<Value>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    if event.get("kind").and_then(|k| k.as_str()) != Some("Event") {
+        return Err(ApiError::wrong_kind("Event", &event));
+    }
+    if event["metadata"]["name"].as_str().is_none() {
+        return Err(ApiError::bad_request("name or generateName is required"));
+    }
+
+    // Ensure namespace in metadata matches path
+    event["metadata"]["namespace"] = json!(namespace);
+
+    let name = event["metadata"]["name"].as_str().unwrap_or_default().to_string();
+
+    match state.storage.events().create(&namespace, event).await {
+        Ok(created) => {
+            info!("Created Event {}/{}", namespace, created["metadata"]["name"]);
+            Ok((StatusCode::CREATED, Json(created)))
+        }
+        Err(e) => {
+            if e.to_string().contains("UNIQUE constraint") {
+                Err(ApiError::already_exists("events", &name))
+            } else {
+                error!("Failed to create event: {}", e);
+                Err(ApiError::internal(&e))
+            }
+        }
+    }
+}
+
+pub async fn get_event(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<Json<Value>, ApiError> {
+    match state.storage.events().get(&namespace, &name).await {
+        Ok(event) => Ok(Json(event)),
+        Err(e) => {
+            if e.to_string().contains("not found") {
+                Err(ApiError::not_found("events", &name))
+            } else {
+                error!("Failed to get event {}/{}: {}", namespace, name, e);
+                Err(ApiError::internal(&e))
+            }
+        }
+    }
+}
+
+pub async fn delete_event(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<Json<Value>, ApiError> {
+    match state.storage.events().delete(&namespace, &name).await {
+        Ok(deleted) => {
+            info!("Deleted Event {}/{}", namespace, name);
+            Ok(Json(deleted))
+        }
+        Err(e) => {
+            if e.to_string().contains("not found") {
+                Err(ApiError::not_found("events", &name))
+            } else {
+                error!("Failed to delete event {}/{}: {}", namespace, name, e);
+                Err(ApiError::internal(&e))
+            }
+        }
+    }
+}
@@ -0,0 +1,171 @@
+// The eviction subresource of pods, which kubectl drain uses rather than
+// deleting pods outright. POSTing an Eviction deletes the pod with the
+// Eviction's deleteOptions, as a DELETE of it would, unless that would take
+// the PodDisruptionBudget selecting it below the healthy pods it needs: then
+// it's refused with 429 Too Many Requests, to be retried later. An eviction
+// spends one of the budget's disruptionsAllowed there and then, and the pod
+// stays in its disruptedPods until the disruption controller sees it go, so
+// evictions made before the controller's next pass can't overspend it. A dry
+// run in the deleteOptions is checked against the budget, but neither
+// spends it nor deletes the pod.
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tokio::sync::Mutex;
+use tracing::{error, info};
+
+use super::delete_options::DeleteOptions;
+use super::error_status::ApiError;
+use super::server::AppState;
+use crate::models::{disruption_budget, time};
+
+const VIOLATION: &str = "Cannot evict pod as it would violate the pod's disruption budget.";
+
+// Evictions are decided one at a time, so that two can't both spend the
+// last disruption a budget allows
+static EVICTIONS: Mutex<()> = Mutex::const_new(());
+
+// Why an eviction isn't going ahead
+enum Refusal {
+    Status(ApiError),
+    Failed(anyhow::Error),
+}
+
+impl From<anyhow::Error> for Refusal {
+    fn from(e: anyhow::Error) -> Self {
+        Refusal::Failed(e)
+    }
+}
+
+/// Handler for POST /namespaces/:namespace/pods/:name/eviction.
+pub async fn create_eviction(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+    Json(eviction): Json<Value>,
+) -> Response {
+    match evict(&state, &namespace, &name, &eviction).await {
+        Ok(()) => {
+            let status = json!({
+                "kind": "Status",
+                "apiVersion": "v1",
+                "metadata": {},
+                "status": "Success",
+                "code": 201
+            });
+            (StatusCode::CREATED, Json(status)).into_response()
+        }
+        Err(Refusal::Status(refusal)) => refusal.into_response(),
+        Err(Refusal::Failed(e)) => {
+            error!("Failed to evict pod {}/{}: {:#}", namespace, name, e);
+            ApiError::internal(&e).into_response()
+        }
+    }
+}
+
+async fn evict(state: &AppState, namespace: &str, name: &str, eviction: &Value) -> Result<(), Refusal> {
+    let bad_request = |message: String| Refusal::Status(ApiError::bad_request(message));
+    if eviction["metadata"]["name"].as_str().is_some_and(|evicted| evicted != name) {
+        return Err(bad_request("name in URL does not match name in Eviction object".to_string()));
+    }
+    let options = match &eviction["deleteOptions"] {
+        Value::Null => DeleteOptions::default(),
+        options => DeleteOptions::parse(&HashMap::new(), options.to_string().as_bytes()).map_err(bad_request)?,
+    };
+
+    let _decided = EVICTIONS.lock().await;
+    let Ok(pod) = state.storage.pods().get(namespace, name).await else {
+        return Err(Refusal::Status(ApiError::not_found("pods", name)));
+    };
+    let store = state.storage.finalizers();
+    let kept = store.get("pods", Some(namespace), name).await?.unwrap_or_default();
+    // Already on its way out
+    if kept.deletion_timestamp.is_some() {
+        return Ok(());
+    }
+    if let Some(uid) = options.preconditions.uid.as_deref().filter(|uid| pod["metadata"]["uid"] != *uid) {
+        let message = format!(
+            "Precondition failed: UID in precondition: {}, UID in object meta: {}",
+            uid,
+            pod["metadata"]["uid"].as_str().unwrap_or_default()
+        );
+        return Err(Refusal::Status(ApiError::conflict("pods", name, message)));
+    }
+
+    // Pods that have finished disrupt nothing
+    let finished = matches!(pod["status"]["phase"].as_str(), Some("Succeeded" | "Failed"));
+    if !finished {
+        spend_budget(state, namespace, &pod, options.is_dry_run()).await?;
+    }
+    if options.is_dry_run() {
+        return Ok(());
+    }
+
+    let grace_period_seconds = state.storage.pods().grace_period(namespace, name, options.grace_period_seconds).await?.unwrap_or(0);
+    if kept.pending() || grace_period_seconds > 0 {
+        store.mark_deleted("pods", Some(namespace), name, grace_period_seconds).await?;
+    } else {
+        store.finish_deletion("pods", Some(namespace), name).await?;
+    }
+    info!("Evicted pod {}/{}", namespace, name);
+    Ok(())
+}
+
+// Takes a disruption from the budget selecting the pod, if there is one,
+// refusing the eviction if it allows none. A dry run only checks it
+async fn spend_budget(state: &AppState, namespace: &str, pod: &Value, dry_run: bool) -> Result<(), Refusal> {
+    let pdbs = state.storage.pdbs().list(Some(namespace)).await?;
+    let mut pdbs = pdbs["items"].as_array().into_iter().flatten().filter(|pdb| disruption_budget::selects(pdb, pod));
+    let Some(pdb) = pdbs.next() else {
+        return Ok(());
+    };
+    if pdbs.next().is_some() {
+        let message = "This pod has more than one PodDisruptionBudget, which the eviction subresource does not support.";
+        return Err(Refusal::Status(ApiError::internal(message)));
+    }
+
+    let pdb_name = pdb["metadata"]["name"].as_str().unwrap_or_default();
+    let status = &pdb["status"];
+    let too_many = |cause: String, retry_after: i64| {
+        let mut details = json!({ "causes": [{ "reason": "DisruptionBudget", "message": cause }] });
+        if retry_after > 0 {
+            details["retryAfterSeconds"] = json!(retry_after);
+        }
+        Refusal::Status(ApiError::new(StatusCode::TOO_MANY_REQUESTS, "TooManyRequests", VIOLATION).with_details(details))
+    };
+    if status["observedGeneration"].as_i64() < pdb["metadata"]["generation"].as_i64() {
+        let cause = format!("The disruption budget {} is still being processed by the server.", pdb_name);
+        return Err(too_many(cause, 10));
+    }
+    let (healthy, desired) = (status["currentHealthy"].as_i64().unwrap_or(0), status["desiredHealthy"].as_i64().unwrap_or(0));
+
+    // A pod that isn't Ready isn't counted as healthy, so evicting it
+    // leaves the budget as it is, if the policy lets it go
+    if !disruption_budget::is_ready(pod) {
+        let always = pdb["spec"]["unhealthyPodEvictionPolicy"] == "AlwaysAllow";
+        if always || healthy >= desired {
+            return Ok(());
+        }
+    }
+
+    let allowed = status["disruptionsAllowed"].as_i64().unwrap_or(0);
+    if allowed <= 0 {
+        let cause = format!("The disruption budget {} needs {} healthy pods and has {} currently", pdb_name, desired, healthy);
+        return Err(too_many(cause, 0));
+    }
+    if dry_run {
+        return Ok(());
+    }
+    let mut status = status.clone();
+    status["disruptionsAllowed"] = json!(allowed - 1);
+    if !status["disruptedPods"].is_object() {
+        status["disruptedPods"] = json!({});
+    }
+    status["disruptedPods"][pod["metadata"]["name"].as_str().unwrap_or_default()] = json!(time::now());
+    state.storage.pdbs().update_status(namespace, pdb_name, status).await?;
+    Ok(())
+}
@@ -0,0 +1,124 @@
+// `?export=true` on a get or list, a krust extension in the spirit of the
+// `kubectl get --export` that Kubernetes removed: objects come back without
+// the fields the server fills in, ready to be applied somewhere else as
+// manifests.
+use axum::{
+    body::Body,
+    extract::{Query, Request},
+    http::{header, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::error;
+
+use super::error_status::ApiError;
+
+// Metadata the server sets or tracks on every object
+const SERVER_METADATA: &[&str] = &[
+    "uid",
+    "resourceVersion",
+    "generation",
+    "creationTimestamp",
+    "deletionTimestamp",
+    "deletionGracePeriodSeconds",
+    "managedFields",
+    "selfLink",
+];
+
+#[derive(Deserialize)]
+struct ExportParams {
+    export: Option<String>,
+    watch: Option<String>,
+}
+
+/// Middleware stripping server-populated fields from the objects of GET
+/// responses when the request has `?export=true`.
+pub async fn strip_server_fields(request: Request, next: Next) -> Response {
+    let enabled = |value: &Option<String>| matches!(value.as_deref(), Some("true" | "1"));
+    let exporting = *request.method() == Method::GET
+        && Query::<ExportParams>::try_from_uri(request.uri())
+            .map(|Query(params)| enabled(&params.export) && !enabled(&params.watch))
+            .unwrap_or(false);
+    if !exporting {
+        return next.run(request).await;
+    }
+
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.starts_with("application/json"))
+        .unwrap_or(false);
+    if !response.status().is_success() || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to buffer response body: {}", e);
+            return ApiError::internal(&e).into_response();
+        }
+    };
+    let Ok(mut object) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+
+    export(&mut object);
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(object.to_string()))
+}
+
+/// Strips an object, or every item of a list, down to what a manifest for
+/// it would hold.
+pub fn export(object: &mut Value) {
+    // Items become standalone manifests, so they need their own kind
+    let list_kind = object["kind"].as_str().unwrap_or_default().trim_end_matches("List").to_string();
+    let api_version = object["apiVersion"].clone();
+    let Some(items) = object.get_mut("items").and_then(|items| items.as_array_mut()) else {
+        strip(object);
+        return;
+    };
+    for item in items.iter_mut() {
+        if item["kind"].is_null() && !list_kind.is_empty() {
+            item["kind"] = Value::from(list_kind.as_str());
+        }
+        if item["apiVersion"].is_null() {
+            item["apiVersion"] = api_version.clone();
+        }
+        strip(item);
+    }
+    object["metadata"] = Value::Object(Default::default());
+}
+
+fn strip(object: &mut Value) {
+    let Some(fields) = object.as_object_mut() else {
+        return;
+    };
+    fields.remove("status");
+
+    if let Some(metadata) = fields.get_mut("metadata").and_then(|m| m.as_object_mut()) {
+        for key in SERVER_METADATA {
+            metadata.remove(*key);
+        }
+    }
+
+    // Allocated by the cluster, and rarely free in another one
+    let kind = fields.get("kind").and_then(|k| k.as_str()).unwrap_or_default().to_string();
+    if let Some(spec) = fields.get_mut("spec").and_then(|s| s.as_object_mut()) {
+        match kind.as_str() {
+            "Service" if spec.get("clusterIP").and_then(|ip| ip.as_str()) != Some("None") => {
+                spec.remove("clusterIP");
+                spec.remove("clusterIPs");
+            }
+            "Pod" => {
+                spec.remove("nodeName");
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,182 @@
+// Tracks which client wrote each object. The manager name comes from the
+// fieldManager query parameter or, like kube-apiserver, from the product
+// part of the User-Agent header (e.g. "kubectl" for "kubectl/v1.29.0 ...").
+// Objects show their writers in metadata.managedFields.
+use axum::{
+    body::Body,
+    extract::{Query, Request, State},
+    http::{header, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::Value;
+use tracing::error;
+
+use super::error_status::ApiError;
+use super::request_info::RequestInfo;
+use super::server::AppState;
+
+// kube-apiserver truncates manager names to this length
+const MAX_MANAGER_LEN: usize = 128;
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct FieldManagerParams {
+    field_manager: Option<String>,
+}
+
+/// Middleware recording the manager of every successful create, update,
+/// patch or delete in the writer log.
+pub async fn track_writes(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let operation = match *request.method() {
+        Method::POST => "Create",
+        Method::PUT => "Update",
+        Method::PATCH if is_apply(&request) => "Apply",
+        Method::PATCH => "Patch",
+        Method::DELETE => "Delete",
+        _ => return next.run(request).await,
+    };
+    // Dry runs write nothing to have a manager of
+    if super::dry_run::requested(&request).unwrap_or(false) {
+        return next.run(request).await;
+    }
+
+    let user_agent = request
+        .headers()
+        .get(header::USER_AGENT)
+        .and_then(|v| v.to_str().ok())
+        .map(|s| s.to_string());
+    let field_manager = Query::<FieldManagerParams>::try_from_uri(request.uri())
+        .ok()
+        .and_then(|Query(params)| params.field_manager);
+    let manager = manager_name(field_manager.as_deref(), user_agent.as_deref());
+
+    let response = next.run(request).await;
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.starts_with("application/json"))
+        .unwrap_or(false);
+    if !response.status().is_success() || !is_json {
+        return response;
+    }
+
+    // The written object is only known from the response body, so buffer it
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to buffer response body: {}", e);
+            return ApiError::internal(&e).into_response();
+        }
+    };
+
+    let Ok(mut object) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    if !object["metadata"]["name"].is_string() {
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+    if let Err(e) = state
+        .storage
+        .writers()
+        .record(&object, &manager, user_agent.as_deref(), operation)
+        .await
+    {
+        error!("Failed to record writer of {}: {}", object["metadata"]["name"], e);
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+
+    if let Err(e) = show_writers(&state, std::slice::from_mut(&mut object)).await {
+        error!("Failed to show writers of {}: {}", object["metadata"]["name"], e);
+        return Response::from_parts(parts, Body::from(bytes));
+    }
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(object.to_string()))
+}
+
+/// Middleware filling in the metadata.managedFields of the objects reads
+/// return.
+pub async fn show_managed_fields(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let info = RequestInfo::parse(request.method().as_str(), request.uri().path(), request.uri().query());
+    let RequestInfo::Resource { verb, subresource: None, .. } = info else {
+        return next.run(request).await;
+    };
+    if verb != "get" && verb != "list" {
+        return next.run(request).await;
+    }
+
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    if !response.status().is_success() || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to buffer response body: {}", e);
+            return ApiError::internal(&e).into_response();
+        }
+    };
+    let Ok(mut body) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    let shown = match body["items"].as_array_mut() {
+        Some(items) => show_writers(&state, items).await,
+        None => show_writers(&state, std::slice::from_mut(&mut body)).await,
+    };
+    if let Err(e) = shown {
+        error!("Failed to show writers: {}", e);
+        return ApiError::internal(&e).into_response();
+    }
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Response::from_parts(parts, Body::from(body.to_string()))
+}
+
+// Sets the managedFields of each object to its recorded writers
+async fn show_writers(state: &AppState, objects: &mut [Value]) -> anyhow::Result<()> {
+    let uids: Vec<&str> = objects.iter().filter_map(|object| object["metadata"]["uid"].as_str()).collect();
+    if uids.is_empty() {
+        return Ok(());
+    }
+    let mut managed_fields = state.storage.writers().managed_fields(&uids).await?;
+    for object in objects {
+        let fields = object["metadata"]["uid"].as_str().and_then(|uid| managed_fields.remove(uid));
+        if let (Some(fields), Some(metadata)) = (fields, object["metadata"].as_object_mut()) {
+            metadata.insert("managedFields".to_string(), Value::from(fields));
+        }
+    }
+    Ok(())
+}
+
+/// Works out the manager name for a write, preferring an explicit fieldManager.
+pub fn manager_name(field_manager: Option<&str>, user_agent: Option<&str>) -> String {
+    let name = match field_manager.filter(|m| !m.is_empty()) {
+        Some(manager) => manager,
+        None => user_agent
+            .and_then(|ua| ua.split('/').next())
+            .map(|product| product.trim())
+            .filter(|product| !product.is_empty())
+            .unwrap_or("unknown"),
+    };
+
+    name.chars().take(MAX_MANAGER_LEN).collect()
+}
+
+fn is_apply(request: &Request) -> bool {
+    request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .map(|ct| ct.starts_with("application/apply-patch"))
+        .unwrap_or(false)
+}
@@ -0,0 +1,336 @@
+// Finalizers for every resource (see storage::finalizer_store). Writes keep
+// the metadata.finalizers they carry, responses and watch events show them,
+// and deleting an object that has some only marks it with a
+// deletionTimestamp: it stays, to be read and updated, until updates have
+// removed them all, and is then deleted. Deleting a namespace deletes what's
+// in it, and the namespace waits in phase Terminating for whatever in it has
+// finalizers. Deletes also follow their DeleteOptions (see delete_options):
+// a pod on a node stays, marked, for its grace period while its kubelet
+// stops it, and the propagation policy decides what becomes of the objects
+// the deleted one owns.
+use axum::{
+    body::{Body, Bytes},
+    extract::{Request, State},
+    http::{header, request::Parts, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+    Json,
+};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use tracing::error;
+
+use super::delete_options::{self, PropagationPolicy};
+use super::error_status::{ApiError, Cause};
+use super::request_info::RequestInfo;
+use super::server::AppState;
+use crate::controllers::garbage_collector::FOREGROUND_DELETION_FINALIZER;
+use crate::controllers::pvc_protection_controller::PVC_PROTECTION_FINALIZER;
+use crate::storage::finalizer_store::{self, Finalizers};
+
+/// Middleware giving writes, reads and deletes of objects finalizer
+/// semantics.
+pub async fn defer_deletes(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let info = RequestInfo::parse(request.method().as_str(), request.uri().path(), request.uri().query());
+    let RequestInfo::Resource { verb, resource, subresource: None, namespace, name, .. } = info else {
+        return next.run(request).await;
+    };
+    if !finalizer_store::tracks(&resource) || verb == "watch" {
+        return next.run(request).await;
+    }
+    // A namespace's RequestInfo namespace is itself
+    let namespace = if resource == "namespaces" { None } else { namespace };
+
+    let result = match (request.method().clone(), name) {
+        (Method::GET, _) => {
+            let response = next.run(request).await;
+            with_finalizers(&state, &resource, response).await
+        }
+        (Method::POST, None) => create(&state, &resource, namespace.as_deref(), request, next).await,
+        (Method::PUT | Method::PATCH, Some(name)) => update(&state, &resource, namespace.as_deref(), &name, request, next).await,
+        (Method::DELETE, Some(name)) if resource == "namespaces" => delete_namespace(&state, &name, request, next).await,
+        (Method::DELETE, Some(name)) => delete(&state, &resource, namespace.as_deref(), &name, request, next).await,
+        _ => return next.run(request).await,
+    };
+    result.unwrap_or_else(|e| {
+        error!("Failed to handle finalizers of {}: {:#}", resource, e);
+        ApiError::internal(&e).into_response()
+    })
+}
+
+// Creates the object, keeping the finalizers it was created with. PVCs get
+// the PVC protection finalizer on top, as the admission plugin adds it.
+// Nothing can be created in a namespace being deleted.
+async fn create(
+    state: &AppState,
+    resource: &str,
+    namespace: Option<&str>,
+    request: Request,
+    next: Next,
+) -> anyhow::Result<Response> {
+    let store = state.storage.finalizers();
+    let (parts, body) = request.into_parts();
+    let bytes = axum::body::to_bytes(body, usize::MAX).await?;
+    let object = serde_json::from_slice::<Value>(&bytes).unwrap_or_default();
+    if let Some(namespace) = namespace {
+        let terminating = store.get("namespaces", None, namespace).await?.is_some_and(|kept| kept.deletion_timestamp.is_some());
+        if terminating {
+            let name = object["metadata"]["name"].as_str().unwrap_or_default();
+            let reason = format!("unable to create new content in namespace {} because it is being terminated", namespace);
+            return Ok(ApiError::forbidden(resource, name, reason).into_response());
+        }
+    }
+    let mut finalizers = finalizers_of(&object["metadata"]);
+    if resource == "persistentvolumeclaims" && !finalizers.iter().any(|f| f == PVC_PROTECTION_FINALIZER) {
+        finalizers.push(PVC_PROTECTION_FINALIZER.to_string());
+    }
+    let response = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    if finalizers.is_empty() || !response.status().is_success() {
+        return Ok(response);
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = axum::body::to_bytes(body, usize::MAX).await?;
+    let Ok(mut created) = serde_json::from_slice::<Value>(&bytes) else {
+        return Ok(Response::from_parts(parts, Body::from(bytes)));
+    };
+    let metadata = &created["metadata"];
+    let (Some(name), Some(uid)) = (metadata["name"].as_str(), metadata["uid"].as_str()) else {
+        return Ok(Response::from_parts(parts, Body::from(bytes)));
+    };
+    let namespace = metadata["namespace"].as_str().map(str::to_string);
+    let name = name.to_string();
+    store.set(resource, namespace.as_deref(), &name, uid, &finalizers).await?;
+    // Watchers saw the object added before its finalizers were kept
+    if let Some(modified) = store.record_modified(resource, namespace.as_deref(), &name).await? {
+        created["metadata"]["resourceVersion"] = modified["metadata"]["resourceVersion"].clone();
+    }
+    store.apply(resource, &mut created).await?;
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Ok(Response::from_parts(parts, Body::from(created.to_string())))
+}
+
+// Updates the object and the finalizers the update leaves it with. An
+// object being deleted can lose finalizers but not gain any, and is
+// deleted once it has none left.
+async fn update(
+    state: &AppState,
+    resource: &str,
+    namespace: Option<&str>,
+    name: &str,
+    request: Request,
+    next: Next,
+) -> anyhow::Result<Response> {
+    let store = state.storage.finalizers();
+    let Some(uid) = store.uid_of(resource, namespace, name).await? else {
+        return Ok(next.run(request).await);
+    };
+    let current = store.get(resource, namespace, name).await?.unwrap_or_default();
+
+    let (parts, body) = request.into_parts();
+    let bytes = axum::body::to_bytes(body, usize::MAX).await?;
+    let updated = match parts.method {
+        Method::PUT => serde_json::from_slice::<Value>(&bytes).ok().map(|object| finalizers_of(&object["metadata"])),
+        _ => patched_finalizers(&parts, &bytes, &current.finalizers),
+    };
+    let Some(updated) = updated.filter(|updated| *updated != current.finalizers) else {
+        let response = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+        return with_finalizers(state, resource, response).await;
+    };
+
+    if current.deletion_timestamp.is_some() {
+        let added: Vec<&String> = updated.iter().filter(|f| !current.finalizers.contains(f)).collect();
+        if !added.is_empty() {
+            let detail = format!("no new finalizers can be added if the object is being deleted, found new finalizers {:?}", added);
+            let cause = Cause::forbidden("metadata.finalizers", &detail);
+            return Ok(ApiError::invalid(resource, name, vec![cause]).into_response());
+        }
+    }
+
+    // Kept before the update is made, so its watch event carries them
+    store.set(resource, namespace, name, &uid, &updated).await?;
+    let response = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    if !response.status().is_success() {
+        store.set(resource, namespace, name, &uid, &current.finalizers).await?;
+        return Ok(response);
+    }
+    let response = with_finalizers(state, resource, response).await?;
+    if current.deletion_timestamp.is_some() && updated.is_empty() && !current.in_grace_period() && resource != "namespaces" {
+        store.finish_deletion(resource, namespace, name).await?;
+    }
+    Ok(response)
+}
+
+// Deletes the object, or only marks it if it has finalizers or is a pod
+// with a grace period. Orphaning its dependents comes first; deleting it in
+// the foreground leaves it marked with the foregroundDeletion finalizer,
+// which the garbage collector removes once they're gone.
+async fn delete(
+    state: &AppState,
+    resource: &str,
+    namespace: Option<&str>,
+    name: &str,
+    request: Request,
+    next: Next,
+) -> anyhow::Result<Response> {
+    let store = state.storage.finalizers();
+    let Some(uid) = store.uid_of(resource, namespace, name).await? else {
+        return Ok(next.run(request).await);
+    };
+    let options = delete_options::of(&request);
+    let mut kept = store.get(resource, namespace, name).await?.unwrap_or_default();
+    match options.propagation_policy {
+        Some(PropagationPolicy::Orphan) => state.storage.owners().orphan(&uid).await?,
+        Some(PropagationPolicy::Foreground)
+            if !kept.finalizers.iter().any(|f| f == FOREGROUND_DELETION_FINALIZER)
+                && !state.storage.owners().dependents(&uid).await?.is_empty() =>
+        {
+            kept.finalizers.push(FOREGROUND_DELETION_FINALIZER.to_string());
+            store.set(resource, namespace, name, &uid, &kept.finalizers).await?;
+        }
+        _ => {}
+    }
+
+    let grace_period_seconds = match (resource, namespace) {
+        ("pods", Some(namespace)) => state.storage.pods().grace_period(namespace, name, options.grace_period_seconds).await?.unwrap_or(0),
+        _ => 0,
+    };
+    if !kept.pending() && grace_period_seconds == 0 {
+        let response = next.run(request).await;
+        if response.status().is_success() {
+            store.forget(resource, namespace, name).await?;
+        }
+        return Ok(response);
+    }
+    Ok(marked(resource, name, store.mark_deleted(resource, namespace, name, grace_period_seconds).await?))
+}
+
+// Deletes what's in the namespace, and the namespace too unless some of it
+// has finalizers, or the namespace itself has. Then it's Terminating, and
+// the namespace controller finishes deleting it once they're gone.
+async fn delete_namespace(state: &AppState, name: &str, request: Request, next: Next) -> anyhow::Result<Response> {
+    let store = state.storage.finalizers();
+    if store.uid_of("namespaces", None, name).await?.is_none() {
+        return Ok(next.run(request).await);
+    }
+    store.delete_namespace_contents(name).await?;
+    let own = store.get("namespaces", None, name).await?.unwrap_or_default();
+    if !own.pending() && store.pending_in(name).await?.is_empty() {
+        let response = next.run(request).await;
+        if response.status().is_success() {
+            store.forget("namespaces", None, name).await?;
+        }
+        return Ok(response);
+    }
+    Ok(marked("namespaces", name, store.mark_deleted("namespaces", None, name, 0).await?))
+}
+
+// The answer to a delete that only marked the object
+fn marked(resource: &str, name: &str, object: Option<Value>) -> Response {
+    match object {
+        Some(object) => (StatusCode::OK, Json(object)).into_response(),
+        None => ApiError::not_found(resource, name).into_response(),
+    }
+}
+
+// Shows the finalizers and deletionTimestamps kept for the objects in a
+// response: one object, the items of a list or the rows of a Table
+async fn with_finalizers(state: &AppState, resource: &str, response: Response) -> anyhow::Result<Response> {
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    if !response.status().is_success() || !is_json {
+        return Ok(response);
+    }
+    let kept: HashMap<String, Finalizers> = state
+        .storage
+        .finalizers()
+        .of_resource(resource)
+        .await?
+        .into_iter()
+        .map(|kept| (kept.uid.clone(), kept))
+        .collect();
+    if kept.is_empty() {
+        return Ok(response);
+    }
+
+    let (mut parts, body) = response.into_parts();
+    let bytes = axum::body::to_bytes(body, usize::MAX).await?;
+    let Ok(mut body) = serde_json::from_slice::<Value>(&bytes) else {
+        return Ok(Response::from_parts(parts, Body::from(bytes)));
+    };
+    let apply = |object: &mut Value| {
+        if let Some(kept) = object["metadata"]["uid"].as_str().and_then(|uid| kept.get(uid)) {
+            finalizer_store::show(resource, kept, object);
+        }
+    };
+    if let Some(items) = body["items"].as_array_mut() {
+        items.iter_mut().for_each(apply);
+    } else if let Some(rows) = body["rows"].as_array_mut() {
+        rows.iter_mut().for_each(|row| apply(&mut row["object"]));
+    } else {
+        apply(&mut body);
+    }
+    parts.headers.remove(header::CONTENT_LENGTH);
+    Ok(Response::from_parts(parts, Body::from(body.to_string())))
+}
+
+fn finalizers_of(metadata: &Value) -> Vec<String> {
+    metadata["finalizers"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|f| f.as_str().map(str::to_string))
+        .collect()
+}
+
+// The finalizers a patch leaves the object with, or None if it doesn't
+// touch them. Merge patches replace the list; strategic merge patches add
+// to it and remove with $deleteFromPrimitiveList, as finalizers merge; JSON
+// patches are applied to it.
+fn patched_finalizers(parts: &Parts, body: &Bytes, current: &[String]) -> Option<Vec<String>> {
+    let content_type = parts.headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    if content_type.starts_with("application/json-patch+json") {
+        let operations: Vec<Value> = serde_json::from_slice(body).ok()?;
+        let touching: Vec<Value> = operations
+            .into_iter()
+            .filter(|op| op["path"].as_str().is_some_and(|path| path == "/metadata" || path.starts_with("/metadata/finalizers")))
+            .collect();
+        if touching.is_empty() {
+            return None;
+        }
+        let mut document = json!({ "metadata": {} });
+        if !current.is_empty() {
+            document["metadata"]["finalizers"] = json!(current);
+        }
+        let patch: json_patch::Patch = serde_json::from_value(Value::Array(touching)).ok()?;
+        json_patch::patch(&mut document, &patch).ok()?;
+        return Some(finalizers_of(&document["metadata"]));
+    }
+
+    let patch: Value = if content_type.starts_with("application/apply-patch+yaml") {
+        serde_yaml::from_slice(body).ok()?
+    } else {
+        serde_json::from_slice(body).ok()?
+    };
+    let metadata = patch["metadata"].as_object()?;
+    let strategic = content_type.starts_with("application/strategic-merge-patch+json");
+    let removed = finalizers_of(&json!({ "finalizers": metadata.get("$deleteFromPrimitiveList/finalizers") }));
+    match metadata.get("finalizers") {
+        Some(Value::Null) => Some(Vec::new()),
+        Some(listed) if strategic => {
+            let mut merged: Vec<String> = current.iter().filter(|f| !removed.contains(f)).cloned().collect();
+            for finalizer in finalizers_of(&json!({ "finalizers": listed })) {
+                if !merged.contains(&finalizer) {
+                    merged.push(finalizer);
+                }
+            }
+            Some(merged)
+        }
+        Some(listed) => Some(finalizers_of(&json!({ "finalizers": listed }))),
+        None if strategic && !removed.is_empty() => Some(current.iter().filter(|f| !removed.contains(f)).cloned().collect()),
+        None => None,
+    }
+}
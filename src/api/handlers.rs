@@ -1,15 +1,23 @@
 use axum::{
-    extract::{Path, Query, State},
-    http::StatusCode,
-    response::{Json, IntoResponse},
+    extract::{Path, Query, Request, State, WebSocketUpgrade},
+    http::{header, HeaderMap, StatusCode},
+    response::{Json, Response},
 };
-use chrono::Utc;
 use serde::Deserialize;
 use serde_json::{json, Value};
 use sqlx;
 use uuid::Uuid;
 
+use super::error_status::{ApiError, Cause};
+use super::last_applied;
+use super::patch;
 use super::server::AppState;
+use super::streaming::StreamRequest;
+use super::watch::list_or_watch;
+use crate::feature_gates;
+use crate::models::replicas;
+use crate::models::time;
+use crate::storage::{ListSelector, Storage, Transaction};
 
 #[derive(Deserialize)]
 pub struct ListParams {
@@ -20,22 +28,64 @@ pub struct ListParams {
     limit: Option<i32>,
     #[serde(rename = "continue")]
     continue_token: Option<String>,
-    watch: Option<bool>,
+    pub(super) watch: Option<bool>,
     #[serde(rename = "resourceVersion")]
-    resource_version: Option<String>,
+    pub(super) resource_version: Option<String>,
+    #[serde(rename = "resourceVersionMatch")]
+    pub(super) resource_version_match: Option<String>,
+    #[serde(rename = "allowWatchBookmarks")]
+    pub(super) allow_watch_bookmarks: Option<bool>,
+    #[serde(rename = "timeoutSeconds")]
+    pub(super) timeout_seconds: Option<u64>,
+}
+
+impl ListParams {
+    /// The parsed fieldSelector and labelSelector; a malformed one is a bad
+    /// request.
+    pub fn selector(&self) -> Result<ListSelector, ApiError> {
+        ListSelector::parse(self.field_selector.as_deref(), self.label_selector.as_deref()).map_err(|e| {
+            tracing::warn!("Rejecting list request: {}", e);
+            ApiError::bad_request(e.to_string())
+        })
+    }
+}
+
+/// The status for a failed list. Selecting on a field the kind has no
+/// selector for, or watching from a version that isn't one, is the
+/// client's mistake.
+pub fn list_error(e: &anyhow::Error) -> ApiError {
+    let message = e.to_string();
+    if message.contains("field label not supported") || message.contains("invalid resourceVersion") {
+        ApiError::bad_request(message)
+    } else {
+        ApiError::internal(message)
+    }
 }
 
 // Namespace handlers
 pub async fn list_namespaces(
     State(state): State<AppState>,
     Query(params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    list_or_watch(&state, "namespaces", None, &params, &selector, namespace_list(&state, &selector))
+        .await
+        .map_err(|e| list_error(&e))
+}
+
+// The namespaces `selector` selects, as a NamespaceList
+async fn namespace_list(state: &AppState, selector: &ListSelector) -> anyhow::Result<Value> {
+    let conditions = selector.sql(&[("metadata.name", "name"), ("status.phase", "json_extract(status, '$.phase')")])?;
+
     // Query namespaces from database
-    let result = sqlx::query_as::<_, (String, String, String, i64, Option<String>, Option<String>, Option<String>, Option<String>)>(
-        "SELECT uid, name, creation_timestamp, resource_version, labels, annotations, spec, status FROM namespaces WHERE deletion_timestamp IS NULL"
-    )
-    .fetch_all(state.storage.pool())
-    .await;
+    let query = format!(
+        "SELECT uid, name, creation_timestamp, resource_version, labels, annotations, spec, status FROM namespaces WHERE deletion_timestamp IS NULL{}",
+        conditions
+    );
+    let result = selector
+        .bind_as(sqlx::query_as::<_, (String, String, String, i64, Option<String>, Option<String>, Option<String>, Option<String>)>(&query))
+        .fetch_all(state.storage.db())
+        .await;
     
     let items = match result {
         Ok(rows) => {
@@ -85,26 +135,26 @@ pub async fn list_namespaces(
         }
     };
     
-    Ok(Json(json!({
+    Ok(json!({
         "apiVersion": "v1",
         "kind": "NamespaceList",
         "metadata": {
             "resourceVersion": "1"
         },
         "items": items
-    })))
+    }))
 }
 
 pub async fn create_namespace(
     State(state): State<AppState>,
     Json(mut namespace): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     // Validate API version and kind
     let api_version = namespace.get("apiVersion")
         .and_then(|v| v.as_str())
         .ok_or_else(|| {
             tracing::warn!("Missing apiVersion in namespace creation request");
-            StatusCode::BAD_REQUEST
+            ApiError::bad_request("Object 'apiVersion' is missing")
         })?;
     
     if api_version != "v1" {
@@ -117,19 +167,19 @@ pub async fn create_namespace(
         .and_then(|k| k.as_str())
         .ok_or_else(|| {
             tracing::warn!("Missing kind in namespace creation request");
-            StatusCode::BAD_REQUEST
+            ApiError::bad_request("Object 'Kind' is missing")
         })?;
     
     if kind != "Namespace" {
         tracing::warn!("Wrong kind '{}' for namespace endpoint", kind);
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("Namespace", &namespace));
     }
     
     // Validate namespace name
     let name = namespace.get("metadata")
         .and_then(|m| m.get("name"))
         .and_then(|n| n.as_str())
-        .ok_or(StatusCode::BAD_REQUEST)?;
+        .ok_or_else(|| ApiError::bad_request("name or generateName is required"))?;
     
     // Kubernetes name validation:
     // - Must be non-empty
@@ -138,12 +188,12 @@ pub async fn create_namespace(
     // - Must start and end with an alphanumeric character
     if name.is_empty() {
         tracing::warn!("Namespace name cannot be empty");
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::bad_request("name or generateName is required"));
     }
     
     if name.len() > 253 {
         tracing::warn!("Namespace name {} is too long (max 253 characters)", name);
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::bad_request(format!("metadata.name: Invalid value: {:?}: must be no more than 253 characters", name)));
     }
     
     // Check for valid characters and format
@@ -153,7 +203,10 @@ pub async fn create_namespace(
     
     if !valid_chars || !starts_ends_valid {
         tracing::warn!("Namespace name {} contains invalid characters or format", name);
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::bad_request(format!(
+            "metadata.name: Invalid value: {:?}: a lowercase RFC 1123 label must consist of lower case alphanumeric characters or '-', and must start and end with an alphanumeric character",
+            name
+        )));
     }
     
     // Add required metadata if missing
@@ -162,24 +215,21 @@ pub async fn create_namespace(
         namespace["metadata"]["uid"] = json!(uid);
     }
     
-    if namespace.get("metadata").and_then(|m| m.get("resourceVersion")).is_none() {
-        namespace["metadata"]["resourceVersion"] = json!("1");
-    }
+    // The version, the row and its watch event are written together
+    let tx = begin_namespace_write(&state).await?;
+    let version = tx.next_resource_version().await.map_err(|e| {
+        tracing::error!("Failed to allocate a resource version: {}", e);
+        ApiError::internal(&e)
+    })?;
+    namespace["metadata"]["resourceVersion"] = json!(version.to_string());
     
-    if namespace.get("metadata").and_then(|m| m.get("creationTimestamp")).is_none() {
-        namespace["metadata"]["creationTimestamp"] = json!(Utc::now().to_rfc3339());
-    }
+    // The creation time is the server's to set
+    let now = time::now();
+    namespace["metadata"]["creationTimestamp"] = json!(now);
     
     // Save namespace directly to database
-    let name = namespace.get("metadata")
-        .and_then(|m| m.get("name"))
-        .and_then(|n| n.as_str())
-        .ok_or(StatusCode::BAD_REQUEST)?;
-    
-    let uid = namespace.get("metadata")
-        .and_then(|m| m.get("uid"))
-        .and_then(|u| u.as_str())
-        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+    let name = namespace["metadata"]["name"].as_str().unwrap_or_default();
+    let uid = namespace["metadata"]["uid"].as_str().unwrap_or_default();
     
     let labels = namespace.get("metadata")
         .and_then(|m| m.get("labels"))
@@ -198,40 +248,90 @@ pub async fn create_namespace(
     // Insert directly into namespaces table
     match sqlx::query(
         "INSERT INTO namespaces (uid, name, resource_version, creation_timestamp, labels, annotations, spec) 
-         VALUES (?, ?, 1, CURRENT_TIMESTAMP, ?, ?, ?)"
+         VALUES (?, ?, ?, ?, ?, ?, ?)"
     )
     .bind(uid)
     .bind(name)
+    .bind(version)
+    .bind(&now)
     .bind(&labels)
     .bind(&annotations)
     .bind(&spec)
-    .execute(state.storage.pool())
+    .execute(tx.db())
     .await {
         Ok(result) => {
             tracing::info!("Created namespace {} with {} rows affected", name, result.rows_affected());
+            record_namespace_event(&tx, "ADDED", &namespace).await;
+            commit_namespace_write(tx).await?;
+            create_namespace_defaults(&state, name).await;
             Ok((StatusCode::CREATED, Json(namespace)))
         },
         Err(e) => {
             if e.to_string().contains("UNIQUE constraint") {
                 tracing::warn!("Namespace {} already exists", name);
-                Err(StatusCode::CONFLICT)
+                Err(ApiError::already_exists("namespaces", name))
             } else {
                 tracing::error!("Failed to create namespace {}: {:?}", name, e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
 }
 
+// Namespaces are written here rather than by a store, so their watch events
+// are recorded here too
+async fn record_namespace_event(storage: &Storage, event_type: &str, namespace: &Value) {
+    if let Err(e) = storage.watch().record("namespaces", event_type, namespace).await {
+        tracing::error!("Failed to record {} event for namespace: {}", event_type, e);
+    }
+}
+
+// The transaction a namespace's resource version, row and watch event are
+// written in
+async fn begin_namespace_write(state: &AppState) -> Result<Transaction, ApiError> {
+    state.storage.transaction().await.map_err(|e| {
+        tracing::error!("Failed to start writing a namespace: {}", e);
+        ApiError::internal(&e)
+    })
+}
+
+async fn commit_namespace_write(tx: Transaction) -> Result<(), ApiError> {
+    tx.commit().await.map_err(|e| {
+        tracing::error!("Failed to write a namespace: {}", e);
+        ApiError::internal(&e)
+    })
+}
+
+// Creates the LimitRanges and ResourceQuotas the config asks for in every new namespace
+async fn create_namespace_defaults(state: &AppState, namespace: &str) {
+    let defaults = &state.config.namespace_defaults;
+
+    for template in &defaults.limit_ranges {
+        let mut limitrange = template.clone();
+        limitrange["metadata"]["namespace"] = json!(namespace);
+        if let Err(e) = state.storage.limitranges().create(namespace, limitrange).await {
+            tracing::error!("Failed to create default LimitRange in namespace {}: {}", namespace, e);
+        }
+    }
+
+    for template in &defaults.resource_quotas {
+        let mut quota = template.clone();
+        quota["metadata"]["namespace"] = json!(namespace);
+        if let Err(e) = state.storage.resourcequotas().create(namespace, quota).await {
+            tracing::error!("Failed to create default ResourceQuota in namespace {}: {}", namespace, e);
+        }
+    }
+}
+
 pub async fn get_namespace(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     let result = sqlx::query_as::<_, (String, String, String, i64, Option<String>, Option<String>, Option<String>, Option<String>)>(
         "SELECT uid, name, creation_timestamp, resource_version, labels, annotations, spec, status FROM namespaces WHERE name = ? AND deletion_timestamp IS NULL"
     )
     .bind(&name)
-    .fetch_one(state.storage.pool())
+    .fetch_one(state.storage.db())
     .await;
     
     match result {
@@ -273,7 +373,7 @@ pub async fn get_namespace(
             
             Ok(Json(ns))
         }
-        Err(_) => Err(StatusCode::NOT_FOUND)
+        Err(_) => Err(ApiError::not_found("namespaces", &name))
     }
 }
 
@@ -281,7 +381,7 @@ pub async fn update_namespace(
     State(state): State<AppState>,
     Path(name): Path<String>,
     Json(mut namespace): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // Extract fields to update
     let labels = namespace.get("metadata")
         .and_then(|m| m.get("labels"))
@@ -300,27 +400,35 @@ pub async fn update_namespace(
     let status = namespace.get("status")
         .map(|s| s.to_string());
     
+    let tx = begin_namespace_write(&state).await?;
+    let version = tx.next_resource_version().await.map_err(|e| {
+        tracing::error!("Failed to allocate a resource version: {}", e);
+        ApiError::internal(&e)
+    })?;
+
     // Update namespace
     let result = if let Some(status_str) = status {
         sqlx::query(
-            "UPDATE namespaces SET labels = ?, annotations = ?, spec = ?, status = ?, resource_version = resource_version + 1 WHERE name = ? AND deletion_timestamp IS NULL"
+            "UPDATE namespaces SET labels = ?, annotations = ?, spec = ?, status = ?, resource_version = ? WHERE name = ? AND deletion_timestamp IS NULL"
         )
         .bind(&labels)
         .bind(&annotations)
         .bind(&spec)
         .bind(&status_str)
+        .bind(version)
         .bind(&name)
-        .execute(state.storage.pool())
+        .execute(tx.db())
         .await
     } else {
         sqlx::query(
-            "UPDATE namespaces SET labels = ?, annotations = ?, spec = ?, resource_version = resource_version + 1 WHERE name = ? AND deletion_timestamp IS NULL"
+            "UPDATE namespaces SET labels = ?, annotations = ?, spec = ?, resource_version = ? WHERE name = ? AND deletion_timestamp IS NULL"
         )
         .bind(&labels)
         .bind(&annotations)
         .bind(&spec)
+        .bind(version)
         .bind(&name)
-        .execute(state.storage.pool())
+        .execute(tx.db())
         .await
     };
     
@@ -334,21 +442,23 @@ pub async fn update_namespace(
                         "SELECT resource_version FROM namespaces WHERE name = ?"
                     )
                     .bind(&name)
-                    .fetch_one(state.storage.pool())
+                    .fetch_one(tx.db())
                     .await;
                     
                     if let Ok((new_rv,)) = rv_result {
                         metadata.insert("resourceVersion".to_string(), json!(new_rv.to_string()));
                     }
                 }
+                record_namespace_event(&tx, "MODIFIED", &namespace).await;
+                commit_namespace_write(tx).await?;
                 Ok(Json(namespace))
             } else {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("namespaces", &name))
             }
         }
         Err(e) => {
             tracing::error!("Failed to update namespace {}: {}", name, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -357,33 +467,42 @@ pub async fn patch_namespace(
     State(state): State<AppState>,
     Path(name): Path<String>,
     Json(patch): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
-    Ok(Json(patch))
+) -> Result<Json<Value>, ApiError> {
+    let Json(mut namespace) = get_namespace(State(state.clone()), Path(name.clone())).await?;
+    let patch = last_applied::with_removals(patch, &namespace);
+    json_patch::merge(&mut namespace, &patch);
+    update_namespace(State(state), Path(name), Json(namespace)).await
 }
 
 pub async fn delete_namespace(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> StatusCode {
+) -> Result<StatusCode, ApiError> {
+    let namespace = get_namespace(State(state.clone()), Path(name.clone())).await.ok();
+
     // Mark namespace as deleted
     match sqlx::query(
-        "UPDATE namespaces SET deletion_timestamp = CURRENT_TIMESTAMP WHERE name = ?"
+        "UPDATE namespaces SET deletion_timestamp = ? WHERE name = ?"
     )
+    .bind(time::now())
     .bind(&name)
-    .execute(state.storage.pool())
+    .execute(state.storage.db())
     .await {
         Ok(result) => {
             if result.rows_affected() > 0 {
                 tracing::info!("Deleted namespace {}", name);
-                StatusCode::OK
+                if let Some(Json(namespace)) = namespace {
+                    record_namespace_event(&state.storage, "DELETED", &namespace).await;
+                }
+                Ok(StatusCode::OK)
             } else {
                 tracing::warn!("Namespace {} not found for deletion", name);
-                StatusCode::NOT_FOUND
+                Err(ApiError::not_found("namespaces", &name))
             }
         }
         Err(e) => {
             tracing::error!("Failed to delete namespace {}: {}", name, e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -391,13 +510,14 @@ pub async fn delete_namespace(
 // Pod handlers
 pub async fn list_all_pods(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.pods().list(None).await {
-        Ok(pods) => Ok(Json(pods)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "pods", None, &params, &selector, state.storage.pods().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list pods: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -405,13 +525,14 @@ pub async fn list_all_pods(
 pub async fn list_pods(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.pods().list(Some(&namespace)).await {
-        Ok(pods) => Ok(Json(pods)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "pods", Some(&namespace), &params, &selector, state.storage.pods().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list pods in namespace {}: {}", namespace, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -420,13 +541,13 @@ pub async fn create_pod(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(mut pod): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     // Validate API version and kind
     let api_version = pod.get("apiVersion")
         .and_then(|v| v.as_str())
         .ok_or_else(|| {
             tracing::warn!("Missing apiVersion in pod creation request");
-            StatusCode::BAD_REQUEST
+            ApiError::bad_request("Object 'apiVersion' is missing")
         })?;
     
     // Pods should use v1, not apps/v1 (that's for Deployments, etc.)
@@ -439,12 +560,12 @@ pub async fn create_pod(
         .and_then(|k| k.as_str())
         .ok_or_else(|| {
             tracing::warn!("Missing kind in pod creation request");
-            StatusCode::BAD_REQUEST
+            ApiError::bad_request("Object 'Kind' is missing")
         })?;
     
     if kind != "Pod" {
         tracing::warn!("Wrong kind '{}' for pod endpoint", kind);
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("Pod", &pod));
     }
     
     // Validate pod has at least one container
@@ -454,14 +575,30 @@ pub async fn create_pod(
     
     if containers.map(|c| c.is_empty()).unwrap_or(true) {
         tracing::warn!("Pod must have at least one container");
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::bad_request("spec.containers: Required value"));
+    }
+
+    let name = pod["metadata"]["name"].as_str().unwrap_or_default().to_string();
+    if let Err(e) = crate::runtime::dns::validate(&pod["spec"]) {
+        tracing::warn!("Rejected pod: {}", e);
+        return Err(ApiError::invalid("pods", &name, vec![Cause::parse(&e.to_string())]));
+    }
+
+    if let Err(e) = crate::runtime::security_profile::validate(&pod) {
+        tracing::warn!("Rejected pod: {}", e);
+        return Err(ApiError::invalid("pods", &name, vec![Cause::parse(&e.to_string())]));
     }
     
     match state.storage.pods().create(&namespace, pod).await {
         Ok(created_pod) => Ok((StatusCode::CREATED, Json(created_pod))),
+        Err(e) if e.to_string().contains("PriorityClass") || e.to_string().contains("priority") => {
+            tracing::warn!("Rejected pod: {}", e);
+            Err(ApiError::forbidden("pods", &name, e))
+        }
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("pods", &name)),
         Err(e) => {
             tracing::error!("Failed to create pod: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -469,15 +606,15 @@ pub async fn create_pod(
 pub async fn get_pod(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.pods().get(&namespace, &name).await {
         Ok(pod) => Ok(Json(pod)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("pods", &name))
             } else {
                 tracing::error!("Failed to get pod: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -487,16 +624,16 @@ pub async fn update_pod(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(mut pod): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // Get the existing pod to enforce immutability
     let existing_pod = match state.storage.pods().get(&namespace, &name).await {
         Ok(pod) => pod,
         Err(e) if e.to_string().contains("not found") => {
-            return Err(StatusCode::NOT_FOUND);
+            return Err(ApiError::not_found("pods", &name));
         }
         Err(e) => {
             tracing::error!("Failed to get pod for update: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err(ApiError::internal(&e));
         }
     };
     
@@ -556,10 +693,10 @@ pub async fn update_pod(
         Ok(updated_pod) => Ok(Json(updated_pod)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("pods", &name))
             } else {
                 tracing::error!("Failed to update pod: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -568,43 +705,31 @@ pub async fn update_pod(
 pub async fn patch_pod(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
+    headers: HeaderMap,
     Json(patch): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
-    // For now, patch is implemented as a full update
-    // In a real implementation, we'd merge the patch with the existing object
-    match state.storage.pods().get(&namespace, &name).await {
-        Ok(mut pod) => {
-            // Simple merge of patch into pod
-            if let Some(metadata) = patch["metadata"].as_object() {
-                for (key, value) in metadata {
-                    pod["metadata"][key] = value.clone();
-                }
-            }
-            if let Some(spec) = patch["spec"].as_object() {
-                for (key, value) in spec {
-                    pod["spec"][key] = value.clone();
-                }
-            }
-            if let Some(status) = patch["status"].as_object() {
-                for (key, value) in status {
-                    pod["status"][key] = value.clone();
-                }
-            }
-            
-            match state.storage.pods().update(&namespace, &name, pod).await {
-                Ok(updated_pod) => Ok(Json(updated_pod)),
-                Err(e) => {
-                    tracing::error!("Failed to patch pod: {}", e);
-                    Err(StatusCode::INTERNAL_SERVER_ERROR)
-                }
-            }
-        }
+) -> Result<Json<Value>, ApiError> {
+    let patch = match state.storage.pods().get(&namespace, &name).await {
+        // A JSON patch's operations are applied to the pod as it is, and
+        // what they changed is merged like any other patch
+        Ok(current) if patch::is_json_patch(&headers) => {
+            let mut patched = current.clone();
+            patch::apply(&headers, &mut patched, patch)?;
+            patch::merge_diff(&current, &patched)
+        }
+        Ok(current) => last_applied::with_removals(patch, &current),
+        Err(_) => patch,
+    };
+
+    // Merged into the stored columns by the database, so concurrent patches
+    // to different labels or fields don't overwrite each other
+    match state.storage.pods().patch(&namespace, &name, patch).await {
+        Ok(pod) => Ok(Json(pod)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("pods", &name))
             } else {
-                tracing::error!("Failed to get pod for patching: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                tracing::error!("Failed to patch pod: {}", e);
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -613,7 +738,7 @@ pub async fn patch_pod(
 pub async fn delete_pod(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.pods().delete(&namespace, &name).await {
         Ok(_) => Ok(Json(json!({
             "kind": "Status",
@@ -628,10 +753,10 @@ pub async fn delete_pod(
         }))),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("pods", &name))
             } else {
                 tracing::error!("Failed to delete pod: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -640,13 +765,14 @@ pub async fn delete_pod(
 // Service handlers
 pub async fn list_all_services(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.services().list(None).await {
-        Ok(services) => Ok(Json(services)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "services", None, &params, &selector, state.storage.services().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list services: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -654,13 +780,14 @@ pub async fn list_all_services(
 pub async fn list_services(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.services().list(Some(&namespace)).await {
-        Ok(services) => Ok(Json(services)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "services", Some(&namespace), &params, &selector, state.storage.services().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list services in namespace {}: {}", namespace, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -669,13 +796,13 @@ pub async fn create_service(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(mut service): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     // Validate API version and kind
     let api_version = service.get("apiVersion")
         .and_then(|v| v.as_str())
         .ok_or_else(|| {
             tracing::warn!("Missing apiVersion in service creation request");
-            StatusCode::BAD_REQUEST
+            ApiError::bad_request("Object 'apiVersion' is missing")
         })?;
     
     if api_version != "v1" {
@@ -687,12 +814,12 @@ pub async fn create_service(
         .and_then(|k| k.as_str())
         .ok_or_else(|| {
             tracing::warn!("Missing kind in service creation request");
-            StatusCode::BAD_REQUEST
+            ApiError::bad_request("Object 'Kind' is missing")
         })?;
     
     if kind != "Service" {
         tracing::warn!("Wrong kind '{}' for service endpoint", kind);
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("Service", &service));
     }
     
     // Validate service ports
@@ -708,7 +835,7 @@ pub async fn create_service(
                             port["port"] = json!(65535);
                             tracing::info!("Clamped port {} to 65535", port_num);
                         } else {
-                            return Err(StatusCode::BAD_REQUEST);
+                            return Err(ApiError::bad_request(format!("port: Invalid value: {}: must be between 1 and 65535, inclusive", port_num)));
                         }
                     }
                 }
@@ -717,7 +844,7 @@ pub async fn create_service(
                 if let Some(target_port) = port.get("targetPort").and_then(|p| p.as_i64()) {
                     if target_port <= 0 || target_port > 65535 {
                         tracing::warn!("Invalid targetPort number: {}", target_port);
-                        return Err(StatusCode::BAD_REQUEST);
+                        return Err(ApiError::bad_request(format!("targetPort: Invalid value: {}: must be between 1 and 65535, inclusive", target_port)));
                     }
                 } else if let Some(target_port_str) = port.get("targetPort").and_then(|p| p.as_str()) {
                     // Validate targetPort name format (IANA_SVC_NAME)
@@ -737,12 +864,26 @@ pub async fn create_service(
             }
         }
     }
+
+    // The service proxy forwards TCP and UDP only, so SCTP ports are refused
+    // rather than accepted and silently never served
+    let name = service["metadata"]["name"].as_str().unwrap_or("").to_string();
+    let ports = service["spec"]["ports"].as_array().cloned().unwrap_or_default();
+    for (i, port) in ports.iter().enumerate() {
+        let protocol = port["protocol"].as_str().unwrap_or("TCP");
+        if protocol != "TCP" && protocol != "UDP" {
+            let cause = Cause::not_supported(&format!("spec.ports[{}].protocol", i), &json!(protocol), &["TCP", "UDP"]);
+            return Err(ApiError::invalid("services", &name, vec![cause]));
+        }
+    }
     
     match state.storage.services().create(&namespace, service).await {
         Ok(created_service) => Ok((StatusCode::CREATED, Json(created_service))),
+        Err(e) if e.to_string().contains(".nodePort: Invalid value") => Err(ApiError::invalid("services", &name, vec![Cause::parse(&e.to_string())])),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("services", &name)),
         Err(e) => {
             tracing::error!("Failed to create service: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -750,15 +891,15 @@ pub async fn create_service(
 pub async fn get_service(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.services().get(&namespace, &name).await {
         Ok(service) => Ok(Json(service)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("services", &name))
             } else {
                 tracing::error!("Failed to get service: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -768,7 +909,7 @@ pub async fn update_service(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(service): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     Ok(Json(service))
 }
 
@@ -776,14 +917,17 @@ pub async fn patch_service(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(patch): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
-    Ok(Json(patch))
+) -> Result<Json<Value>, ApiError> {
+    let Json(mut namespace) = get_namespace(State(state.clone()), Path(name.clone())).await?;
+    let patch = last_applied::with_removals(patch, &namespace);
+    json_patch::merge(&mut namespace, &patch);
+    update_namespace(State(state), Path(name), Json(namespace)).await
 }
 
 pub async fn delete_service(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.services().delete(&namespace, &name).await {
         Ok(_) => Ok(Json(json!({
             "kind": "Status",
@@ -798,10 +942,10 @@ pub async fn delete_service(
         }))),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("services", &name))
             } else {
                 tracing::error!("Failed to delete service: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -810,13 +954,14 @@ pub async fn delete_service(
 // Endpoints handlers
 pub async fn list_all_endpoints(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.endpoints().list(None).await {
-        Ok(endpoints) => Ok(Json(endpoints)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "endpoints", None, &params, &selector, state.storage.endpoints().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list endpoints: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -824,13 +969,14 @@ pub async fn list_all_endpoints(
 pub async fn list_endpoints(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.endpoints().list(Some(&namespace)).await {
-        Ok(endpoints) => Ok(Json(endpoints)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "endpoints", Some(&namespace), &params, &selector, state.storage.endpoints().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list endpoints in namespace {}: {}", namespace, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -838,15 +984,15 @@ pub async fn list_endpoints(
 pub async fn get_endpoints(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.endpoints().get(&namespace, &name).await {
         Ok(endpoints) => Ok(Json(endpoints)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("endpoints", &name))
             } else {
                 tracing::error!("Failed to get endpoints: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -856,12 +1002,14 @@ pub async fn create_endpoints(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(endpoints): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = endpoints["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.endpoints().create(&namespace, endpoints).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("endpoints", &name)),
         Err(e) => {
             tracing::error!("Failed to create endpoints: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -870,15 +1018,15 @@ pub async fn update_endpoints(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(endpoints): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.endpoints().update(&namespace, &name, endpoints).await {
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("endpoints", &name))
             } else {
                 tracing::error!("Failed to update endpoints: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -887,15 +1035,15 @@ pub async fn update_endpoints(
 pub async fn delete_endpoints(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.endpoints().delete(&namespace, &name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("endpoints", &name))
             } else {
                 tracing::error!("Failed to delete endpoints: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -904,13 +1052,14 @@ pub async fn delete_endpoints(
 // Deployment handlers
 pub async fn list_all_deployments(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.deployments().list(None).await {
-        Ok(deployments) => Ok(Json(deployments)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "deployments", None, &params, &selector, state.storage.deployments().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list deployments: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -918,13 +1067,14 @@ pub async fn list_all_deployments(
 pub async fn list_deployments(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.deployments().list(Some(&namespace)).await {
-        Ok(deployments) => Ok(Json(deployments)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "deployments", Some(&namespace), &params, &selector, state.storage.deployments().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list deployments in namespace {}: {}", namespace, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -933,12 +1083,14 @@ pub async fn create_deployment(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(deployment): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = deployment["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.deployments().create(&namespace, deployment).await {
         Ok(created_deployment) => Ok((StatusCode::CREATED, Json(created_deployment))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("deployments", &name)),
         Err(e) => {
             tracing::error!("Failed to create deployment: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -946,15 +1098,15 @@ pub async fn create_deployment(
 pub async fn get_deployment(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.deployments().get(&namespace, &name).await {
         Ok(deployment) => Ok(Json(deployment)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("deployments", &name))
             } else {
                 tracing::error!("Failed to get deployment: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -964,15 +1116,15 @@ pub async fn update_deployment(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(deployment): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.deployments().update(&namespace, &name, deployment).await {
         Ok(updated_deployment) => Ok(Json(updated_deployment)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("deployments", &name))
             } else {
                 tracing::error!("Failed to update deployment: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -981,59 +1133,27 @@ pub async fn update_deployment(
 pub async fn patch_deployment(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
+    headers: HeaderMap,
     Json(patch): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
-    // Handle scale subresource
-    if let Some(replicas) = patch["spec"]["replicas"].as_i64() {
-        match state.storage.deployments().get(&namespace, &name).await {
-            Ok(mut deployment) => {
-                deployment["spec"]["replicas"] = json!(replicas);
-                match state.storage.deployments().update(&namespace, &name, deployment).await {
-                    Ok(updated) => Ok(Json(updated)),
-                    Err(e) => {
-                        tracing::error!("Failed to scale deployment: {}", e);
-                        Err(StatusCode::INTERNAL_SERVER_ERROR)
-                    }
-                }
-            }
-            Err(e) => {
-                if e.to_string().contains("not found") {
-                    Err(StatusCode::NOT_FOUND)
-                } else {
-                    Err(StatusCode::INTERNAL_SERVER_ERROR)
+) -> Result<Json<Value>, ApiError> {
+    match state.storage.deployments().get(&namespace, &name).await {
+        Ok(mut deployment) => {
+            // `kubectl rollout undo` sends a JSON patch
+            patch::apply(&headers, &mut deployment, patch)?;
+
+            match state.storage.deployments().update(&namespace, &name, deployment).await {
+                Ok(updated) => Ok(Json(updated)),
+                Err(e) => {
+                    tracing::error!("Failed to patch deployment: {}", e);
+                    Err(ApiError::internal(&e))
                 }
             }
         }
-    } else {
-        // Regular patch
-        match state.storage.deployments().get(&namespace, &name).await {
-            Ok(mut deployment) => {
-                // Merge patch into deployment
-                if let Some(metadata) = patch["metadata"].as_object() {
-                    for (key, value) in metadata {
-                        deployment["metadata"][key] = value.clone();
-                    }
-                }
-                if let Some(spec) = patch["spec"].as_object() {
-                    for (key, value) in spec {
-                        deployment["spec"][key] = value.clone();
-                    }
-                }
-                
-                match state.storage.deployments().update(&namespace, &name, deployment).await {
-                    Ok(updated) => Ok(Json(updated)),
-                    Err(e) => {
-                        tracing::error!("Failed to patch deployment: {}", e);
-                        Err(StatusCode::INTERNAL_SERVER_ERROR)
-                    }
-                }
-            }
-            Err(e) => {
-                if e.to_string().contains("not found") {
-                    Err(StatusCode::NOT_FOUND)
-                } else {
-                    Err(StatusCode::INTERNAL_SERVER_ERROR)
-                }
+        Err(e) => {
+            if e.to_string().contains("not found") {
+                Err(ApiError::not_found("deployments", &name))
+            } else {
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -1042,15 +1162,15 @@ pub async fn patch_deployment(
 pub async fn delete_deployment(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.deployments().delete(&namespace, &name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("deployments", &name))
             } else {
                 tracing::error!("Failed to delete deployment: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -1060,32 +1180,14 @@ pub async fn delete_deployment(
 pub async fn get_deployment_scale(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.deployments().get(&namespace, &name).await {
-        Ok(deployment) => {
-            Ok(Json(json!({
-                "apiVersion": "autoscaling/v1",
-                "kind": "Scale",
-                "metadata": {
-                    "name": name,
-                    "namespace": namespace,
-                    "uid": deployment["metadata"]["uid"],
-                    "resourceVersion": deployment["metadata"]["resourceVersion"]
-                },
-                "spec": {
-                    "replicas": deployment["spec"]["replicas"]
-                },
-                "status": {
-                    "replicas": deployment["status"]["replicas"],
-                    "selector": deployment["spec"]["selector"]
-                }
-            })))
-        }
+        Ok(deployment) => Ok(Json(replicas::scale(&deployment))),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("deployments", &name))
             } else {
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -1095,92 +1197,57 @@ pub async fn update_deployment_scale(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(scale): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
-    let new_replicas = scale["spec"]["replicas"].as_i64();
-    
-    if let Some(replicas) = new_replicas {
-        match state.storage.deployments().get(&namespace, &name).await {
-            Ok(mut deployment) => {
-                deployment["spec"]["replicas"] = json!(replicas);
-                
-                // Also need to update the ReplicaSet
-                update_replicaset_replicas(&state, &namespace, &name, replicas).await;
-                
-                match state.storage.deployments().update(&namespace, &name, deployment).await {
-                    Ok(updated) => {
-                        Ok(Json(json!({
-                            "apiVersion": "autoscaling/v1",
-                            "kind": "Scale",
-                            "metadata": {
-                                "name": name,
-                                "namespace": namespace,
-                                "uid": updated["metadata"]["uid"],
-                                "resourceVersion": updated["metadata"]["resourceVersion"]
-                            },
-                            "spec": {
-                                "replicas": replicas
-                            },
-                            "status": {
-                                "replicas": updated["status"]["replicas"],
-                                "selector": updated["spec"]["selector"]
-                            }
-                        })))
-                    }
-                    Err(e) => {
-                        tracing::error!("Failed to scale deployment: {}", e);
-                        Err(StatusCode::INTERNAL_SERVER_ERROR)
-                    }
-                }
-            }
-            Err(e) => {
-                if e.to_string().contains("not found") {
-                    Err(StatusCode::NOT_FOUND)
-                } else {
-                    Err(StatusCode::INTERNAL_SERVER_ERROR)
-                }
-            }
-        }
-    } else {
-        Err(StatusCode::BAD_REQUEST)
-    }
+) -> Result<Json<Value>, ApiError> {
+    let replicas = replicas::requested(&scale).ok_or_else(|| ApiError::bad_request("spec.replicas: Invalid value: must be a non-negative integer"))?;
+    scale_deployment(&state, &namespace, &name, replicas).await
 }
 
 pub async fn patch_deployment_scale(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-    Json(scale): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
-    update_deployment_scale(State(state), Path((namespace, name)), Json(scale)).await
+    Json(patch): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    let mut scale = match state.storage.deployments().get(&namespace, &name).await {
+        Ok(deployment) => replicas::scale(&deployment),
+        Err(e) if e.to_string().contains("not found") => return Err(ApiError::not_found("deployments", &name)),
+        Err(e) => return Err(ApiError::internal(&e)),
+    };
+    json_patch::merge(&mut scale, &patch);
+
+    let replicas = replicas::requested(&scale).ok_or_else(|| ApiError::bad_request("spec.replicas: Invalid value: must be a non-negative integer"))?;
+    scale_deployment(&state, &namespace, &name, replicas).await
 }
 
-async fn update_replicaset_replicas(state: &AppState, namespace: &str, deployment_name: &str, replicas: i64) {
-    // Find ReplicaSets owned by this deployment
-    if let Ok(deployment) = state.storage.deployments().get(namespace, deployment_name).await {
-        let deployment_uid = deployment["metadata"]["uid"].as_str().unwrap();
-        
-        // Update the replicas in the database directly for now
-        let _ = sqlx::query(
-            "UPDATE replicasets SET replicas = ? 
-             WHERE namespace = ? AND owner_references LIKE ?"
-        )
-        .bind(replicas)
-        .bind(namespace)
-        .bind(format!("%\"uid\":\"{}%", deployment_uid))
-        .execute(&*state.storage.pool)
-        .await;
+// Only spec.replicas changes; the deployment controller resizes the
+// ReplicaSet from it
+async fn scale_deployment(state: &AppState, namespace: &str, name: &str, replicas: i64) -> Result<Json<Value>, ApiError> {
+    let mut deployment = match state.storage.deployments().get(namespace, name).await {
+        Ok(deployment) => deployment,
+        Err(e) if e.to_string().contains("not found") => return Err(ApiError::not_found("deployments", name)),
+        Err(e) => return Err(ApiError::internal(&e)),
+    };
+    deployment["spec"]["replicas"] = json!(replicas);
+
+    match state.storage.deployments().update(namespace, name, deployment).await {
+        Ok(updated) => Ok(Json(replicas::scale(&updated))),
+        Err(e) => {
+            tracing::error!("Failed to scale deployment: {}", e);
+            Err(ApiError::internal(&e))
+        }
     }
 }
 
 // ReplicaSet handlers
 pub async fn list_all_replicasets(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.replicasets().list(None).await {
-        Ok(replicasets) => Ok(Json(replicasets)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "replicasets", None, &params, &selector, state.storage.replicasets().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list replicasets: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -1188,13 +1255,14 @@ pub async fn list_all_replicasets(
 pub async fn list_replicasets(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.replicasets().list(Some(&namespace)).await {
-        Ok(replicasets) => Ok(Json(replicasets)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "replicasets", Some(&namespace), &params, &selector, state.storage.replicasets().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list replicasets in namespace {}: {}", namespace, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -1202,12 +1270,12 @@ pub async fn list_replicasets(
 pub async fn get_deployment_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.deployments().get(&namespace, &name).await {
         Ok(deployment) => Ok(Json(deployment)),
         Err(e) => {
             tracing::error!("Failed to get deployment status: {}", e);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("deployments", &name))
         }
     }
 }
@@ -1216,25 +1284,14 @@ pub async fn update_deployment_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(status_update): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
-    // Get the current deployment
-    let mut deployment = match state.storage.deployments().get(&namespace, &name).await {
-        Ok(d) => d,
-        Err(e) => {
-            tracing::error!("Failed to get deployment: {}", e);
-            return Err(StatusCode::NOT_FOUND);
-        }
-    };
-    
-    // Update the status
-    deployment["status"] = status_update["status"].clone();
-    
-    // Update in storage
-    match state.storage.deployments().update(&namespace, &name, deployment).await {
+) -> Result<Json<Value>, ApiError> {
+    // Only the status is taken from the body; spec changes are ignored
+    match state.storage.deployments().update_status(&namespace, &name, status_update["status"].clone()).await {
         Ok(updated) => Ok(Json(updated)),
+        Err(e) if e.to_string().contains("not found") => Err(ApiError::not_found("deployments", &name)),
         Err(e) => {
             tracing::error!("Failed to update deployment status: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -1243,12 +1300,14 @@ pub async fn create_replicaset(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(replicaset): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = replicaset["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.replicasets().create(&namespace, replicaset).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("replicasets", &name)),
         Err(e) => {
             tracing::error!("Failed to create replicaset: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -1256,15 +1315,15 @@ pub async fn create_replicaset(
 pub async fn get_replicaset(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.replicasets().get(&namespace, &name).await {
         Ok(replicaset) => Ok(Json(replicaset)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("replicasets", &name))
             } else {
                 tracing::error!("Failed to get replicaset: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -1274,12 +1333,12 @@ pub async fn update_replicaset(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(replicaset): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.replicasets().update(&namespace, &name, replicaset).await {
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             tracing::error!("Failed to update replicaset: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -1288,12 +1347,17 @@ pub async fn patch_replicaset(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(patch): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
+    let patch = match state.storage.replicasets().get(&namespace, &name).await {
+        Ok(current) => last_applied::with_removals(patch, &current),
+        Err(_) => patch,
+    };
+
     match state.storage.replicasets().patch(&namespace, &name, patch).await {
         Ok(patched) => Ok(Json(patched)),
         Err(e) => {
             tracing::error!("Failed to patch replicaset: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -1301,15 +1365,15 @@ pub async fn patch_replicaset(
 pub async fn delete_replicaset(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.replicasets().delete(&namespace, &name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("replicasets", &name))
             } else {
                 tracing::error!("Failed to delete replicaset: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -1318,15 +1382,15 @@ pub async fn delete_replicaset(
 pub async fn get_replicaset_scale(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.replicasets().get_scale(&namespace, &name).await {
         Ok(scale) => Ok(Json(scale)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("replicasets", &name))
             } else {
                 tracing::error!("Failed to get replicaset scale: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -1336,14 +1400,18 @@ pub async fn update_replicaset_scale(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(scale): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
-    let replicas = scale["spec"]["replicas"].as_i64().unwrap_or(1);
+) -> Result<Json<Value>, ApiError> {
+    let replicas = replicas::requested(&scale).ok_or_else(|| ApiError::bad_request("spec.replicas: Invalid value: must be a non-negative integer"))?;
     
     match state.storage.replicasets().update_scale(&namespace, &name, replicas).await {
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
-            tracing::error!("Failed to update replicaset scale: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            if e.to_string().contains("not found") {
+                Err(ApiError::not_found("replicasets", &name))
+            } else {
+                tracing::error!("Failed to update replicaset scale: {}", e);
+                Err(ApiError::internal(&e))
+            }
         }
     }
 }
@@ -1352,7 +1420,7 @@ pub async fn update_replicaset_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(status_update): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     let status = if let Some(s) = status_update.get("status") {
         s.clone()
     } else {
@@ -1366,13 +1434,13 @@ pub async fn update_replicaset_status(
                 Ok(rs) => Ok(Json(rs)),
                 Err(e) => {
                     tracing::error!("Failed to get updated replicaset: {}", e);
-                    Err(StatusCode::INTERNAL_SERVER_ERROR)
+                    Err(ApiError::internal(&e))
                 }
             }
         }
         Err(e) => {
             tracing::error!("Failed to update replicaset status: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -1381,13 +1449,13 @@ pub async fn update_replicaset_status(
 pub async fn get_pod_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.pods().get_status(&namespace, &name).await {
         Ok(pod) => Ok(Json(pod)),
-        Err(e) if e.to_string().contains("not found") => Err(StatusCode::NOT_FOUND),
+        Err(e) if e.to_string().contains("not found") => Err(ApiError::not_found("pods", &name)),
         Err(e) => {
             tracing::error!("Failed to get pod status: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -1396,7 +1464,7 @@ pub async fn update_pod_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(status_update): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     let status = if let Some(s) = status_update.get("status") {
         s.clone()
     } else {
@@ -1405,10 +1473,10 @@ pub async fn update_pod_status(
     
     match state.storage.pods().set_status(&namespace, &name, status).await {
         Ok(pod) => Ok(Json(pod)),
-        Err(e) if e.to_string().contains("not found") => Err(StatusCode::NOT_FOUND),
+        Err(e) if e.to_string().contains("not found") => Err(ApiError::not_found("pods", &name)),
         Err(e) => {
             tracing::error!("Failed to update pod status: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -1417,7 +1485,7 @@ pub async fn patch_pod_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(patch): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // For status patch, we expect the patch to contain a "status" field
     let status = if let Some(s) = patch.get("status") {
         s.clone()
@@ -1425,12 +1493,12 @@ pub async fn patch_pod_status(
         patch
     };
     
-    match state.storage.pods().set_status(&namespace, &name, status).await {
+    match state.storage.pods().patch_status(&namespace, &name, status).await {
         Ok(pod) => Ok(Json(pod)),
-        Err(e) if e.to_string().contains("not found") => Err(StatusCode::NOT_FOUND),
+        Err(e) if e.to_string().contains("not found") => Err(ApiError::not_found("pods", &name)),
         Err(e) => {
             tracing::error!("Failed to patch pod status: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -1439,31 +1507,40 @@ pub async fn update_pod_ephemeralcontainers(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(update): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
+    if !state.config.feature_gates.enabled(feature_gates::EPHEMERAL_CONTAINERS) {
+        return Err(ApiError::not_found("pods", &name));
+    }
     let ephemeral_containers = update["spec"]["ephemeralContainers"].clone();
     
     if ephemeral_containers.is_null() {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::bad_request("spec.ephemeralContainers: Required value"));
     }
     
     match state.storage.pods().update_ephemeral_containers(&namespace, &name, ephemeral_containers).await {
         Ok(pod) => Ok(Json(pod)),
-        Err(e) if e.to_string().contains("not found") => Err(StatusCode::NOT_FOUND),
+        Err(e) if e.to_string().contains("not found") => Err(ApiError::not_found("pods", &name)),
         Err(e) => {
             tracing::error!("Failed to update ephemeral containers: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
 
+/// Handler for POST /namespaces/:namespace/pods/:name/binding, through
+/// which schedulers assign pods to nodes.
 pub async fn create_pod_binding(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(binding): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     let node_name = binding["target"]["name"]
         .as_str()
-        .ok_or(StatusCode::BAD_REQUEST)?;
+        .filter(|node| !node.is_empty())
+        .ok_or_else(|| ApiError::bad_request("target.name: Required value"))?;
+    if let Some(bound) = binding["metadata"]["name"].as_str().filter(|bound| *bound != name) {
+        return Err(ApiError::bad_request(format!("the name of the binding ({}) does not match the name of the pod ({})", bound, name)));
+    }
     
     match state.storage.pods().bind_to_node(&namespace, &name, node_name).await {
         Ok(_) => Ok((StatusCode::CREATED, Json(json!({
@@ -1478,158 +1555,223 @@ pub async fn create_pod_binding(
                 "name": node_name
             }
         })))),
-        Err(e) if e.to_string().contains("not found") => Err(StatusCode::NOT_FOUND),
+        Err(e) if e.to_string().contains("not found") => Err(ApiError::not_found("pods", &name)),
+        // Already bound, or on its way out
+        Err(e) if e.to_string().contains("already assigned") || e.to_string().contains("being deleted") => Err(ApiError::conflict("pods/binding", &name, e)),
         Err(e) => {
             tracing::error!("Failed to bind pod: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
 
+// `command` may be repeated in the query, so it's read as a list of pairs
 pub async fn pod_exec(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-    Query(_params): Query<ExecParams>,
-) -> Result<Json<Value>, StatusCode> {
-    // WebSocket upgrade is required for exec functionality
-    // Return 501 Not Implemented for now
-    tracing::info!("Pod exec requested for {}/{} - WebSocket support not yet implemented", namespace, name);
-    Err(StatusCode::NOT_IMPLEMENTED)
+    Query(params): Query<Vec<(String, String)>>,
+    ws: Option<WebSocketUpgrade>,
+) -> Result<Response, ApiError> {
+    serve_stream(&state, ws, &namespace, &name, |pod| StreamRequest::exec(pod, &params)).await
 }
 
 pub async fn pod_attach(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-    Query(_params): Query<AttachParams>,
-) -> Result<Json<Value>, StatusCode> {
-    // WebSocket upgrade is required for attach functionality
-    // Return 501 Not Implemented for now
-    tracing::info!("Pod attach requested for {}/{} - WebSocket support not yet implemented", namespace, name);
-    Err(StatusCode::NOT_IMPLEMENTED)
+    Query(params): Query<Vec<(String, String)>>,
+    ws: Option<WebSocketUpgrade>,
+) -> Result<Response, ApiError> {
+    serve_stream(&state, ws, &namespace, &name, |pod| StreamRequest::attach(pod, &params)).await
+}
+
+// Exec and attach are redirected to the streaming server when there is one.
+// Otherwise they run over this connection, which has to be upgraded.
+async fn serve_stream(
+    state: &AppState,
+    ws: Option<WebSocketUpgrade>,
+    namespace: &str,
+    name: &str,
+    request: impl FnOnce(&Value) -> Result<StreamRequest, ApiError>,
+) -> Result<Response, ApiError> {
+    if state.streaming.is_none() && ws.is_none() {
+        return Err(ApiError::bad_request("Upgrade request required"));
+    }
+
+    let pod = running_pod(state, namespace, name).await?;
+    let request = request(&pod)?;
+    match (&state.streaming, ws) {
+        (Some(streaming), _) => Ok(streaming.redirect(request)),
+        (None, ws) => Ok(super::streaming::upgrade(ws.ok_or_else(|| ApiError::bad_request("Upgrade request required"))?, request)),
+    }
+}
+
+async fn running_pod(state: &AppState, namespace: &str, name: &str) -> Result<Value, ApiError> {
+    let pod = state
+        .storage
+        .pods()
+        .get(namespace, name)
+        .await
+        .map_err(|_| ApiError::not_found("pods", name))?;
+    if pod["status"]["phase"] != "Running" {
+        let phase = pod["status"]["phase"].as_str().unwrap_or("Pending");
+        return Err(ApiError::conflict("pods", name, format!("pod is not running, its phase is {}", phase)));
+    }
+    Ok(pod)
 }
 
+// Port-forwards are redirected to the streaming server like exec and attach,
+// or else upgrade this connection to SPDY, directly or through a WebSocket
 pub async fn pod_portforward(
-    State(_state): State<AppState>,
+    State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
-    // SPDY/WebSocket upgrade is required for port forwarding
-    // Return 501 Not Implemented for now
-    tracing::info!("Pod portforward requested for {}/{} - SPDY/WebSocket support not yet implemented", namespace, name);
-    Err(StatusCode::NOT_IMPLEMENTED)
+    request: Request,
+) -> Result<Response, ApiError> {
+    if state.streaming.is_none() && !request.headers().contains_key(header::UPGRADE) {
+        return Err(ApiError::bad_request("Upgrade request required"));
+    }
+
+    let pod = running_pod(&state, &namespace, &name).await?;
+    match &state.streaming {
+        Some(streaming) => Ok(streaming.redirect(StreamRequest::PortForward { pod })),
+        None => Ok(super::portforward::upgrade(request, pod).await),
+    }
 }
 
 // Node handlers
 pub async fn list_nodes(
     State(state): State<AppState>,
     Query(params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    Ok(Json(json!({
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    let list = node_list(&state, &selector);
+    list_or_watch(&state, "nodes", None, &params, &selector, list)
+        .await
+        .map_err(|e| list_error(&e))
+}
+
+// The nodes of the config `selector` selects, as a NodeList. Only their
+// specs ever change, and watches see those changes.
+async fn node_list(state: &AppState, selector: &ListSelector) -> anyhow::Result<Value> {
+    let stored = state.storage.nodes().stored().await?;
+    let mut items = Vec::new();
+    for node in crate::models::node::list(&state.config, &stored) {
+        let matches = selector.matches(|field| match field {
+            "metadata.name" => Some(node["metadata"]["name"].as_str().unwrap_or_default().to_string()),
+            "spec.unschedulable" => Some(node["spec"]["unschedulable"].as_bool().unwrap_or(false).to_string()),
+            _ => None,
+        }, &node["metadata"]["labels"]);
+        if matches? {
+            items.push(node);
+        }
+    }
+
+    Ok(json!({
         "apiVersion": "v1",
         "kind": "NodeList",
         "metadata": {
             "resourceVersion": "1"
         },
-        "items": [{
-            "apiVersion": "v1",
-            "kind": "Node",
-            "metadata": {
-                "name": "krust-node",
-                "uid": "node-uid-1",
-                "resourceVersion": "1",
-                "creationTimestamp": chrono::Utc::now().to_rfc3339()
-            },
-            "spec": {},
-            "status": {
-                "conditions": [
-                    {
-                        "type": "Ready",
-                        "status": "True",
-                        "lastHeartbeatTime": chrono::Utc::now().to_rfc3339(),
-                        "lastTransitionTime": chrono::Utc::now().to_rfc3339(),
-                        "reason": "KubeletReady",
-                        "message": "kubelet is posting ready status"
-                    }
-                ],
-                "addresses": [
-                    {
-                        "type": "InternalIP",
-                        "address": "127.0.0.1"
-                    }
-                ],
-                "capacity": {
-                    "cpu": "8",
-                    "memory": "16Gi",
-                    "pods": "110"
-                },
-                "allocatable": {
-                    "cpu": "8",
-                    "memory": "16Gi",
-                    "pods": "110"
-                }
-            }
-        }]
-    })))
+        "items": items
+    }))
+}
+
+async fn find_node(state: &AppState, name: &str) -> Result<Value, ApiError> {
+    let stored = state.storage.nodes().stored().await.map_err(ApiError::internal)?;
+    crate::models::node::get(&state.config, name, &stored).ok_or_else(|| ApiError::not_found("nodes", name))
 }
 
 pub async fn get_node(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
-    if name == "krust-node" {
-        Ok(Json(json!({
-            "apiVersion": "v1",
-            "kind": "Node",
-            "metadata": {
-                "name": "krust-node",
-                "uid": "node-uid-1",
-                "resourceVersion": "1",
-                "creationTimestamp": chrono::Utc::now().to_rfc3339()
-            },
-            "spec": {},
-            "status": {
-                "conditions": [
-                    {
-                        "type": "Ready",
-                        "status": "True",
-                        "lastHeartbeatTime": chrono::Utc::now().to_rfc3339(),
-                        "lastTransitionTime": chrono::Utc::now().to_rfc3339(),
-                        "reason": "KubeletReady",
-                        "message": "kubelet is posting ready status"
-                    }
-                ],
-                "addresses": [
-                    {
-                        "type": "InternalIP",
-                        "address": "127.0.0.1"
-                    }
-                ],
-                "capacity": {
-                    "cpu": "8",
-                    "memory": "16Gi",
-                    "pods": "110"
-                },
-                "allocatable": {
-                    "cpu": "8",
-                    "memory": "16Gi",
-                    "pods": "110"
-                }
-            }
-        })))
-    } else {
-        Err(StatusCode::NOT_FOUND)
+) -> Result<Json<Value>, ApiError> {
+    find_node(&state, &name).await.map(Json)
+}
+
+// Nodes can be cordoned, uncordoned and tainted, by patching or replacing
+// them; the rest of a node is the config's, or its kubelet's to report
+pub async fn update_node(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    Json(node): Json<Value>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let current = find_node(&state, &name).await?;
+    update_node_spec(&state, current, node).await
+}
+
+pub async fn patch_node(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+    headers: HeaderMap,
+    Json(patch): Json<Value>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let current = find_node(&state, &name).await?;
+    let mut node = current.clone();
+    patch::apply(&headers, &mut node, patch)?;
+    update_node_spec(&state, current, node).await
+}
+
+async fn update_node_spec(state: &AppState, current: Value, node: Value) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = current["metadata"]["name"].as_str().unwrap_or_default().to_string();
+    if let Some(cause) = invalid_node_change(&current, &node) {
+        return Err(ApiError::invalid("nodes", &name, vec![cause]));
+    }
+
+    let mut updated = current;
+    updated["spec"] = node["spec"].clone();
+    match state.storage.nodes().update_spec(updated).await {
+        Ok(node) => Ok((StatusCode::OK, Json(node))),
+        Err(e) => {
+            tracing::error!("Failed to update node {}: {}", name, e);
+            Err(ApiError::internal(&e))
+        }
     }
 }
 
+// Why a change to a node can't be made, if it can't: its labels and
+// annotations are the config's, and its taints need a key and an effect
+// the scheduler knows
+fn invalid_node_change(current: &Value, node: &Value) -> Option<Cause> {
+    for field in ["labels", "annotations"] {
+        if node["metadata"][field] != current["metadata"][field] {
+            return Some(Cause::forbidden(&format!("metadata.{}", field), &format!("nodes take their {} from the config", field)));
+        }
+    }
+    let taints = node["spec"]["taints"].as_array().map_or(&[][..], |taints| taints);
+    for (i, taint) in taints.iter().enumerate() {
+        if taint["key"].as_str().unwrap_or_default().is_empty() {
+            return Some(Cause::required(&format!("spec.taints[{}].key", i)));
+        }
+        let effect = taint["effect"].as_str().unwrap_or_default();
+        if !crate::config::TAINT_EFFECTS.contains(&effect) {
+            return Some(Cause::not_supported(&format!("spec.taints[{}].effect", i), &json!(effect), crate::config::TAINT_EFFECTS));
+        }
+    }
+    None
+}
+
+// The node's kubelet stats summary, as kubectl top and the metrics server
+// reach it through the node proxy
+pub async fn get_node_stats_summary(
+    State(state): State<AppState>,
+    Path(name): Path<String>,
+) -> Result<Json<Value>, ApiError> {
+    find_node(&state, &name).await?;
+    crate::runtime::ephemeral_storage::summary(&state.storage, &name)
+        .await
+        .map(Json)
+        .map_err(ApiError::internal)
+}
+
 // Pod logs handler
 pub async fn get_pod_logs(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Query(params): Query<LogParams>,
-) -> Result<String, StatusCode> {
+) -> Result<String, ApiError> {
     // First check if pod exists
     let pod = match state.storage.pods().get(&namespace, &name).await {
         Ok(pod) => pod,
-        Err(_) => return Err(StatusCode::NOT_FOUND),
+        Err(_) => return Err(ApiError::not_found("pods", &name)),
     };
     
     // Get container name (default to first container if not specified)
@@ -1641,7 +1783,7 @@ pub async fn get_pod_logs(
             .unwrap_or("container")
             .to_string()
     } else {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::bad_request(format!("a container name must be specified for pod {}", name)));
     };
     
     // Get logs from Docker
@@ -1661,10 +1803,10 @@ pub async fn get_pod_logs(
                     }
                 }
                 tracing::warn!("Container {} not found", full_container_name);
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("pods", &name))
             } else {
                 tracing::error!("Failed to get logs for container {}: {}", full_container_name, e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -1687,15 +1829,22 @@ async fn get_container_logs(container_name: &str, tail: Option<String>, follow:
     
     let mut stream = docker.logs(container_name, Some(options));
     let mut logs = String::new();
+    // Followed logs only end with the container, or when the client leaves
+    let mut cancellation = super::cancellation::Cancellation::current();
     
-    while let Some(result) = stream.next().await {
+    loop {
+        let result = tokio::select! {
+            result = stream.next() => result,
+            _ = cancellation.cancelled() => break,
+        };
         match result {
-            Ok(output) => {
+            Some(Ok(output)) => {
                 logs.push_str(&output.to_string());
             }
-            Err(e) => {
+            Some(Err(e)) => {
                 return Err(anyhow::anyhow!("Error reading logs: {}", e));
             }
+            None => break,
         }
     }
     
@@ -1710,148 +1859,17 @@ pub struct LogParams {
     timestamps: Option<bool>,
 }
 
-#[derive(Deserialize)]
-pub struct ExecParams {
-    container: Option<String>,
-    command: Vec<String>,
-    stdin: Option<bool>,
-    stdout: Option<bool>,
-    stderr: Option<bool>,
-    tty: Option<bool>,
-}
-
-#[derive(Deserialize)]
-pub struct AttachParams {
-    container: Option<String>,
-    stdin: Option<bool>,
-    stdout: Option<bool>,
-    stderr: Option<bool>,
-    tty: Option<bool>,
-}
-
-// Watch handlers
-pub async fn watch_pods(
-    State(state): State<AppState>,
-    Query(params): Query<ListParams>,
-) -> Result<axum::response::Response, StatusCode> {
-    use axum::response::sse::{Event, Sse};
-    use futures::stream::StreamExt;
-    
-    if params.watch != Some(true) {
-        // Regular list if not watching
-        return match state.storage.pods().list(None).await {
-            Ok(pods) => Ok(Json(pods).into_response()),
-            Err(e) => {
-                tracing::error!("Failed to list pods: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
-            }
-        };
-    }
-    
-    // Stream watch events
-    match state.storage.watch().watch_stream(
-        "pods".to_string(),
-        None,
-        params.resource_version.clone()
-    ).await {
-        Ok(stream) => {
-            let sse_stream = stream.map(|result| match result {
-                Ok(event) => Ok(Event::default().data(event.to_string())),
-                Err(e) => {
-                    tracing::error!("Watch stream error: {}", e);
-                    Err(axum::Error::new(e))
-                }
-            });
-            
-            Ok(Sse::new(sse_stream)
-                .keep_alive(axum::response::sse::KeepAlive::default())
-                .into_response())
-        }
-        Err(e) => {
-            tracing::error!("Failed to create watch stream: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
-}
-
-pub async fn watch_namespace_pods(
-    State(state): State<AppState>,
-    Path(namespace): Path<String>,
-    Query(params): Query<ListParams>,
-) -> Result<axum::response::Response, StatusCode> {
-    use axum::response::sse::{Event, Sse};
-    use futures::stream::StreamExt;
-    
-    if params.watch != Some(true) {
-        // Regular list if not watching
-        return match state.storage.pods().list(Some(&namespace)).await {
-            Ok(pods) => Ok(Json(pods).into_response()),
-            Err(e) => {
-                tracing::error!("Failed to list pods in namespace {}: {}", namespace, e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
-            }
-        };
-    }
-    
-    // Stream watch events
-    match state.storage.watch().watch_stream(
-        "pods".to_string(),
-        Some(namespace.clone()),
-        params.resource_version.clone()
-    ).await {
-        Ok(stream) => {
-            let sse_stream = stream.map(|result| match result {
-                Ok(event) => Ok(Event::default().data(event.to_string())),
-                Err(e) => {
-                    tracing::error!("Watch stream error: {}", e);
-                    Err(axum::Error::new(e))
-                }
-            });
-            
-            Ok(Sse::new(sse_stream)
-                .keep_alive(axum::response::sse::KeepAlive::default())
-                .into_response())
-        }
-        Err(e) => {
-            tracing::error!("Failed to create watch stream: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
-    }
-}
-// Port-forward handlers for kubectl port-forward support
-pub async fn pod_portforward_get(
-    Path((namespace, name)): Path<(String, String)>,
-) -> impl IntoResponse {
-    // Return 101 Switching Protocols for WebSocket upgrade
-    // kubectl expects this for port-forward protocol negotiation
-    StatusCode::SWITCHING_PROTOCOLS
-}
-
-pub async fn pod_portforward_post(
-    State(state): State<AppState>,
-    Path((namespace, name)): Path<(String, String)>,
-) -> StatusCode {
-    // Check if pod exists
-    match state.storage.pods().get(&namespace, &name).await {
-        Ok(_) => {
-            // For now, return 501 Not Implemented as port-forwarding 
-            // requires complex WebSocket/SPDY protocol handling
-            StatusCode::NOT_IMPLEMENTED
-        }
-        Err(_) => StatusCode::NOT_FOUND,
-    }
-}
-
 // HorizontalPodAutoscaler handlers
 pub async fn list_all_hpas(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.hpas().list(None).await {
-        Ok(hpas) => Ok(Json(hpas)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "horizontalpodautoscalers", None, &params, &selector, state.storage.hpas().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list HPAs: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -1859,13 +1877,14 @@ pub async fn list_all_hpas(
 pub async fn list_hpas(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.hpas().list(Some(&namespace)).await {
-        Ok(hpas) => Ok(Json(hpas)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "horizontalpodautoscalers", Some(&namespace), &params, &selector, state.storage.hpas().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list HPAs in namespace {}: {}", namespace, e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -1874,12 +1893,15 @@ pub async fn create_hpa(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(hpa): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    super::hpa_handlers::validate(&state, &hpa)?;
+    let name = hpa["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.hpas().create(&namespace, hpa).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("horizontalpodautoscalers", &name)),
         Err(e) => {
             tracing::error!("Failed to create HPA: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -1887,15 +1909,15 @@ pub async fn create_hpa(
 pub async fn get_hpa(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.hpas().get(&namespace, &name).await {
         Ok(hpa) => Ok(Json(hpa)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("horizontalpodautoscalers", &name))
             } else {
                 tracing::error!("Failed to get HPA: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -1905,15 +1927,16 @@ pub async fn update_hpa(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(hpa): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    super::hpa_handlers::validate(&state, &hpa)?;
     match state.storage.hpas().update(&namespace, &name, hpa).await {
-        Ok(updated) => Ok(Json(updated)),
+        Ok(updated) => Ok((StatusCode::OK, Json(updated))),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("horizontalpodautoscalers", &name))
             } else {
                 tracing::error!("Failed to update HPA: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -1922,15 +1945,15 @@ pub async fn update_hpa(
 pub async fn delete_hpa(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.hpas().delete(&namespace, &name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("horizontalpodautoscalers", &name))
             } else {
                 tracing::error!("Failed to delete HPA: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -1939,12 +1962,12 @@ pub async fn delete_hpa(
 pub async fn get_hpa_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.hpas().get(&namespace, &name).await {
         Ok(hpa) => Ok(Json(hpa)),
         Err(e) => {
             tracing::error!("Failed to get HPA status: {}", e);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("horizontalpodautoscalers", &name))
         }
     }
 }
@@ -1953,15 +1976,15 @@ pub async fn update_hpa_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(status_update): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.hpas().update_status(&namespace, &name, status_update["status"].clone()).await {
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("horizontalpodautoscalers", &name))
             } else {
                 tracing::error!("Failed to update HPA status: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -0,0 +1,109 @@
+// autoscaling/v1 HorizontalPodAutoscalers, as `kubectl autoscale` creates
+// them. They're stored as autoscaling/v2 objects and converted on the way in
+// and out; watches are served by the v2 API only.
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use serde_json::{json, Value};
+
+use super::error_status::{ApiError, Cause};
+use super::handlers::{list_error, ListParams};
+use super::server::AppState;
+use crate::models::hpa;
+
+/// Validates a v2 HPA as it will be stored, i.e. with its defaults filled in.
+pub(super) fn validate(state: &AppState, hpa: &Value) -> Result<(), ApiError> {
+    let mut spec = hpa["spec"].clone();
+    hpa::set_defaults(&mut spec);
+    hpa::validate(&spec, state.config.scale_to_zero()).map_err(|cause| {
+        ApiError::invalid("horizontalpodautoscalers", hpa["metadata"]["name"].as_str().unwrap_or(""), vec![Cause::parse(&cause)])
+    })
+}
+
+fn storage_error(e: anyhow::Error, action: &str, name: &str) -> ApiError {
+    if e.to_string().contains("not found") {
+        ApiError::not_found("horizontalpodautoscalers", name)
+    } else if e.to_string().contains("UNIQUE constraint") {
+        ApiError::already_exists("horizontalpodautoscalers", name)
+    } else {
+        tracing::error!("Failed to {} HPA: {}", action, e);
+        ApiError::internal(&e)
+    }
+}
+
+pub async fn list_all_hpas_v1(
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+) -> Result<Json<Value>, ApiError> {
+    list(&state, None, &params).await
+}
+
+pub async fn list_hpas_v1(
+    State(state): State<AppState>,
+    Path(namespace): Path<String>,
+    Query(params): Query<ListParams>,
+) -> Result<Json<Value>, ApiError> {
+    list(&state, Some(&namespace), &params).await
+}
+
+async fn list(state: &AppState, namespace: Option<&str>, params: &ListParams) -> Result<Json<Value>, ApiError> {
+    let selector = params.selector()?;
+    let mut list = state.storage.hpas().list_matching(namespace, &selector).await.map_err(|e| {
+        tracing::error!("Failed to list HPAs: {}", e);
+        list_error(&e)
+    })?;
+    let items: Vec<Value> = list["items"].as_array().into_iter().flatten().map(hpa::to_v1).collect();
+    list["apiVersion"] = json!("autoscaling/v1");
+    list["items"] = json!(items);
+    Ok(Json(list))
+}
+
+pub async fn create_hpa_v1(
+    State(state): State<AppState>,
+    Path(namespace): Path<String>,
+    Json(v1): Json<Value>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let converted = hpa::from_v1(&v1, None);
+    validate(&state, &converted)?;
+    let name = converted["metadata"]["name"].as_str().unwrap_or_default().to_string();
+    match state.storage.hpas().create(&namespace, converted).await {
+        Ok(created) => Ok((StatusCode::CREATED, Json(hpa::to_v1(&created)))),
+        Err(e) => Err(storage_error(e, "create", &name)),
+    }
+}
+
+pub async fn get_hpa_v1(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<Json<Value>, ApiError> {
+    match state.storage.hpas().get(&namespace, &name).await {
+        Ok(stored) => Ok(Json(hpa::to_v1(&stored))),
+        Err(e) => Err(storage_error(e, "get", &name)),
+    }
+}
+
+pub async fn update_hpa_v1(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+    Json(v1): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    let current = state.storage.hpas().get(&namespace, &name).await.map_err(|e| storage_error(e, "get", &name))?;
+    let converted = hpa::from_v1(&v1, Some(&current));
+    validate(&state, &converted)?;
+    match state.storage.hpas().update(&namespace, &name, converted).await {
+        Ok(updated) => Ok(Json(hpa::to_v1(&updated))),
+        Err(e) => Err(storage_error(e, "update", &name)),
+    }
+}
+
+pub async fn delete_hpa_v1(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<Json<Value>, ApiError> {
+    match state.storage.hpas().delete(&namespace, &name).await {
+        Ok(deleted) => Ok(Json(hpa::to_v1(&deleted))),
+        Err(e) => Err(storage_error(e, "delete", &name)),
+    }
+}
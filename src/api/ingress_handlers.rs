@@ -1,21 +1,26 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
+    response::Response,
     Json,
 };
 use serde_json::{json, Value};
 use tracing::{error, info};
 
+use crate::api::error_status::ApiError;
+use crate::api::last_applied;
+use crate::api::handlers::{list_error, ListParams};
 use crate::api::server::AppState;
+use crate::api::watch::list_or_watch;
 
 pub async fn create_ingress(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(mut ingress): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     // Validate kind
     if ingress.get("kind").and_then(|k| k.as_str()) != Some("Ingress") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("Ingress", &ingress));
     }
 
     // Ensure metadata exists
@@ -29,24 +34,18 @@ pub async fn create_ingress(
         namespace
     );
 
+    let name = ingress["metadata"]["name"].as_str().unwrap_or_default().to_string();
+
     let store = state.storage.ingresses();
     match store.create(&namespace, ingress).await {
         Ok(created_ingress) => Ok((StatusCode::CREATED, Json(created_ingress))),
         Err(e) => {
             if e.to_string().contains("UNIQUE constraint failed") {
                 error!("Ingress already exists: {}", e);
-                return Ok((StatusCode::CONFLICT, Json(json!({
-                    "kind": "Status",
-                    "apiVersion": "v1",
-                    "metadata": {},
-                    "status": "Failure",
-                    "message": "Ingress already exists",
-                    "reason": "AlreadyExists",
-                    "code": 409
-                }))));
+                return Err(ApiError::already_exists("ingresses", &name));
             }
             error!("Failed to create Ingress: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -54,7 +53,7 @@ pub async fn create_ingress(
 pub async fn get_ingress(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Getting Ingress {} in namespace {}", name, namespace);
     
     let store = state.storage.ingresses();
@@ -62,11 +61,11 @@ pub async fn get_ingress(
         Ok(ingress) => Ok(Json(ingress)),
         Err(e) if e.to_string().contains("not found") => {
             error!("Ingress {}/{} not found", namespace, name);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("ingresses", &name))
         }
         Err(e) => {
             error!("Failed to get Ingress: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -74,30 +73,34 @@ pub async fn get_ingress(
 pub async fn list_ingresses_namespaced(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing Ingresses in namespace {}", namespace);
     
+    let selector = params.selector()?;
     let store = state.storage.ingresses();
-    match store.list(Some(&namespace)).await {
-        Ok(list) => Ok(Json(list)),
+    match list_or_watch(&state, "ingresses", Some(&namespace), &params, &selector, store.list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list Ingresses: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
 
 pub async fn list_ingresses_all_namespaces(
     State(state): State<AppState>,
-) -> Result<Json<Value>, StatusCode> {
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing Ingresses in all namespaces");
     
+    let selector = params.selector()?;
     let store = state.storage.ingresses();
-    match store.list(None).await {
-        Ok(list) => Ok(Json(list)),
+    match list_or_watch(&state, "ingresses", None, &params, &selector, store.list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list Ingresses: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -106,12 +109,12 @@ pub async fn update_ingress(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(mut ingress): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Updating Ingress {} in namespace {}", name, namespace);
     
     // Validate kind
     if ingress.get("kind").and_then(|k| k.as_str()) != Some("Ingress") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("Ingress", &ingress));
     }
 
     // Ensure metadata exists with correct name and namespace
@@ -126,11 +129,11 @@ pub async fn update_ingress(
         Ok(updated_ingress) => Ok(Json(updated_ingress)),
         Err(e) if e.to_string().contains("not found") => {
             error!("Ingress {}/{} not found for update", namespace, name);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("ingresses", &name))
         }
         Err(e) => {
             error!("Failed to update Ingress: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -139,7 +142,7 @@ pub async fn patch_ingress(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(patch): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Patching Ingress {} in namespace {}", name, namespace);
     
     let store = state.storage.ingresses();
@@ -147,25 +150,25 @@ pub async fn patch_ingress(
     // Get existing resource
     match store.get(&namespace, &name).await {
         Ok(mut existing) => {
-            // Simple merge patch - merge the patch into existing
-            merge_json(&mut existing, &patch);
+            let patch = last_applied::with_removals(patch, &existing);
+            json_patch::merge(&mut existing, &patch);
             
             // Use the update method
             match store.update(&namespace, &name, existing).await {
                 Ok(patched_ingress) => Ok(Json(patched_ingress)),
                 Err(e) => {
                     error!("Failed to patch Ingress: {}", e);
-                    Err(StatusCode::INTERNAL_SERVER_ERROR)
+                    Err(ApiError::internal(&e))
                 }
             }
         }
         Err(e) if e.to_string().contains("not found") => {
             error!("Ingress {}/{} not found for patch", namespace, name);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("ingresses", &name))
         }
         Err(e) => {
             error!("Failed to get Ingress for patch: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -174,38 +177,28 @@ pub async fn update_ingress_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(status_update): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Updating Ingress status {} in namespace {}", name, namespace);
     
     let store = state.storage.ingresses();
     
-    // Get existing Ingress
-    match store.get(&namespace, &name).await {
-        Ok(mut existing) => {
-            // Update the status field
-            if let Some(status) = status_update.get("status") {
-                existing["status"] = status.clone();
-            } else {
-                // If the entire body is the status
-                existing["status"] = status_update;
-            }
-            
-            // Use the update method to save the changes
-            match store.update(&namespace, &name, existing).await {
-                Ok(updated_ingress) => Ok(Json(updated_ingress)),
-                Err(e) => {
-                    error!("Failed to update Ingress status: {}", e);
-                    Err(StatusCode::INTERNAL_SERVER_ERROR)
-                }
-            }
-        }
+    // Extract status from the request body
+    let status = if let Some(s) = status_update.get("status") {
+        s.clone()
+    } else {
+        // If the entire body is the status
+        status_update
+    };
+    
+    match store.update_status(&namespace, &name, status).await {
+        Ok(updated_ingress) => Ok(Json(updated_ingress)),
         Err(e) if e.to_string().contains("not found") => {
             error!("Ingress {}/{} not found for status update", namespace, name);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("ingresses", &name))
         }
         Err(e) => {
-            error!("Failed to get Ingress for status update: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            error!("Failed to update Ingress status: {}", e);
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -213,7 +206,7 @@ pub async fn update_ingress_status(
 pub async fn delete_ingress(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Deleting Ingress {} in namespace {}", name, namespace);
     
     let store = state.storage.ingresses();
@@ -221,28 +214,11 @@ pub async fn delete_ingress(
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) if e.to_string().contains("not found") => {
             error!("Ingress {}/{} not found", namespace, name);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("ingresses", &name))
         }
         Err(e) => {
             error!("Failed to delete Ingress: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
-
-// Helper function to merge JSON objects
-fn merge_json(target: &mut Value, patch: &Value) {
-    if let (Some(target_obj), Some(patch_obj)) = (target.as_object_mut(), patch.as_object()) {
-        for (key, value) in patch_obj {
-            if let Some(target_value) = target_obj.get_mut(key) {
-                if target_value.is_object() && value.is_object() {
-                    merge_json(target_value, value);
-                } else {
-                    *target_value = value.clone();
-                }
-            } else {
-                target_obj.insert(key.clone(), value.clone());
-            }
-        }
-    }
-}
\ No newline at end of file
@@ -0,0 +1,57 @@
+// Injections: built-in mutations of the pods being created, declared under
+// `admission.injections` in the config. Each one whose selectors pick the
+// pod adds its labels, sets its environment variables in the pod's
+// containers and appends its sidecar containers, so service-mesh-style
+// injection can be tried without running a webhook server. Like built-in
+// admission plugins in kube-apiserver, they run before mutating webhooks,
+// which see the pod with its sidecars.
+use serde_json::{json, Value};
+
+use crate::config::Injection;
+use crate::storage::LabelSelector;
+
+/// Applies the injections whose selectors match `pod` and the labels of its
+/// namespace, in order, returning the names of those applied.
+pub fn apply(injections: &[Injection], pod: &mut Value, namespace_labels: &Value) -> Vec<String> {
+    let mut applied = Vec::new();
+    for injection in injections {
+        // Selectors were checked when the config was read
+        let selects = |selector: &Value, labels: &Value| LabelSelector::from_value(selector).is_ok_and(|s| s.matches(labels));
+        if !selects(&injection.selector, &pod["metadata"]["labels"]) || !selects(&injection.namespace_selector, namespace_labels) {
+            continue;
+        }
+        inject(injection, pod);
+        applied.push(injection.name.clone());
+    }
+    applied
+}
+
+fn inject(injection: &Injection, pod: &mut Value) {
+    if !injection.labels.is_empty() && !pod["metadata"]["labels"].is_object() {
+        pod["metadata"]["labels"] = json!({});
+    }
+    for (key, value) in &injection.labels {
+        pod["metadata"]["labels"][key] = json!(value);
+    }
+
+    // The pod's own containers get the variables; sidecars bring their own
+    let Some(containers) = pod["spec"]["containers"].as_array_mut() else {
+        return;
+    };
+    for container in containers.iter_mut().filter(|_| !injection.env.is_empty()) {
+        if !container["env"].is_array() {
+            container["env"] = json!([]);
+        }
+        let env = container["env"].as_array_mut().unwrap();
+        for var in &injection.env {
+            if !env.iter().any(|set| set["name"] == var["name"]) {
+                env.push(var.clone());
+            }
+        }
+    }
+    for sidecar in &injection.containers {
+        if !containers.iter().any(|c| c["name"] == sidecar["name"]) {
+            containers.push(sidecar.clone());
+        }
+    }
+}
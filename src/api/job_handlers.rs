@@ -1,21 +1,25 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
+    response::Response,
     Json,
 };
 use serde_json::{json, Value};
 use tracing::{error, info};
 
+use crate::api::error_status::{ApiError, Cause};
+use crate::api::handlers::{list_error, ListParams};
 use crate::api::server::AppState;
+use crate::api::watch::list_or_watch;
 
 pub async fn create_job(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(mut job): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     // Validate kind
     if job.get("kind").and_then(|k| k.as_str()) != Some("Job") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("Job", &job));
     }
 
     // Ensure metadata exists
@@ -23,30 +27,31 @@ pub async fn create_job(
         job["metadata"] = json!({});
     }
 
+    // Job pods must finish, so they can't use the default Always policy
+    let restart_policy = job["spec"]["template"]["spec"]["restartPolicy"].as_str();
+    if !matches!(restart_policy, Some("Never") | Some("OnFailure")) {
+        let cause = Cause::not_supported("spec.template.spec.restartPolicy", &json!(restart_policy.unwrap_or("Always")), &["OnFailure", "Never"]);
+        return Err(ApiError::invalid("jobs", job["metadata"]["name"].as_str().unwrap_or(""), vec![cause]));
+    }
+
     info!(
         "Creating Job {} in namespace {}",
         job["metadata"]["name"].as_str().unwrap_or("unknown"),
         namespace
     );
 
+    let name = job["metadata"]["name"].as_str().unwrap_or_default().to_string();
+
     let store = state.storage.jobs();
     match store.create(&namespace, job).await {
         Ok(created_job) => Ok((StatusCode::CREATED, Json(created_job))),
         Err(e) => {
             if e.to_string().contains("UNIQUE constraint failed") {
                 error!("Job already exists: {}", e);
-                return Ok((StatusCode::CONFLICT, Json(json!({
-                    "kind": "Status",
-                    "apiVersion": "v1",
-                    "metadata": {},
-                    "status": "Failure",
-                    "message": "Job already exists",
-                    "reason": "AlreadyExists",
-                    "code": 409
-                }))));
+                return Err(ApiError::already_exists("jobs", &name));
             }
             error!("Failed to create Job: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -54,7 +59,7 @@ pub async fn create_job(
 pub async fn get_job(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Getting Job {} in namespace {}", name, namespace);
     
     let store = state.storage.jobs();
@@ -62,11 +67,11 @@ pub async fn get_job(
         Ok(job) => Ok(Json(job)),
         Err(e) if e.to_string().contains("not found") => {
             error!("Job {}/{} not found", namespace, name);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("jobs", &name))
         }
         Err(e) => {
             error!("Failed to get Job: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -74,30 +79,34 @@ pub async fn get_job(
 pub async fn list_jobs_namespaced(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing Jobs in namespace {}", namespace);
     
+    let selector = params.selector()?;
     let store = state.storage.jobs();
-    match store.list(Some(&namespace)).await {
-        Ok(list) => Ok(Json(list)),
+    match list_or_watch(&state, "jobs", Some(&namespace), &params, &selector, store.list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list Jobs: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
 
 pub async fn list_jobs_all_namespaces(
     State(state): State<AppState>,
-) -> Result<Json<Value>, StatusCode> {
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing Jobs in all namespaces");
     
+    let selector = params.selector()?;
     let store = state.storage.jobs();
-    match store.list(None).await {
-        Ok(list) => Ok(Json(list)),
+    match list_or_watch(&state, "jobs", None, &params, &selector, store.list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list Jobs: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -106,7 +115,7 @@ pub async fn update_job_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(mut status_update): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Updating Job status {} in namespace {}", name, namespace);
     
     let store = state.storage.jobs();
@@ -126,13 +135,13 @@ pub async fn update_job_status(
                 Ok(job) => Ok(Json(job)),
                 Err(e) => {
                     error!("Failed to retrieve updated Job: {}", e);
-                    Err(StatusCode::INTERNAL_SERVER_ERROR)
+                    Err(ApiError::internal(&e))
                 }
             }
         }
         Err(e) => {
             error!("Failed to update Job status: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -140,7 +149,7 @@ pub async fn update_job_status(
 pub async fn delete_job(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Deleting Job {} in namespace {}", name, namespace);
     
     let store = state.storage.jobs();
@@ -148,11 +157,11 @@ pub async fn delete_job(
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) if e.to_string().contains("not found") => {
             error!("Job {}/{} not found", namespace, name);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("jobs", &name))
         }
         Err(e) => {
             error!("Failed to delete Job: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
\ No newline at end of file
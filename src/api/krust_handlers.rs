@@ -0,0 +1,336 @@
+// krust-specific debug and introspection endpoints, served under /krust
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use chrono::{SecondsFormat, Utc};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::path::{Component, PathBuf};
+use tracing::{error, info};
+
+use super::deprecated_apis;
+use super::error_status::ApiError;
+use super::server::AppState;
+use crate::config;
+use crate::controllers::conntrack;
+use crate::controllers::Resync;
+use crate::runtime::compat;
+
+#[derive(Deserialize)]
+pub struct CompatParams {
+    namespace: Option<String>,
+}
+
+/// Reports which pod spec fields krust accepts but doesn't honor, and which
+/// pods currently rely on them.
+pub async fn compat_report(
+    State(state): State<AppState>,
+    Query(params): Query<CompatParams>,
+) -> Result<Json<Value>, ApiError> {
+    let pods = match state.storage.pods().list(params.namespace.as_deref()).await {
+        Ok(list) => list,
+        Err(e) => {
+            error!("Failed to list pods for compat report: {}", e);
+            return Err(ApiError::internal(&e));
+        }
+    };
+
+    let mut affected = Vec::new();
+    for pod in pods["items"].as_array().into_iter().flatten() {
+        let fields = compat::recorded_fields(pod);
+        if fields.is_empty() {
+            continue;
+        }
+
+        let fields: Vec<Value> = fields
+            .iter()
+            .map(|path| json!({ "field": path, "reason": compat::reason_for(path) }))
+            .collect();
+
+        affected.push(json!({
+            "namespace": pod["metadata"]["namespace"],
+            "name": pod["metadata"]["name"],
+            "uid": pod["metadata"]["uid"],
+            "unsupportedFields": fields
+        }));
+    }
+
+    let catalog = |table: &[(&str, &str)], prefix: &str| -> Vec<Value> {
+        table
+            .iter()
+            .map(|(field, reason)| json!({ "field": format!("{}{}", prefix, field), "reason": reason }))
+            .collect()
+    };
+
+    Ok(Json(json!({
+        "apiVersion": "krust.io/v1alpha1",
+        "kind": "CompatibilityReport",
+        "annotation": compat::UNSUPPORTED_FIELDS_ANNOTATION,
+        "unsupportedPodFields": catalog(compat::UNSUPPORTED_POD_FIELDS, "spec."),
+        "unsupportedContainerFields": catalog(compat::UNSUPPORTED_CONTAINER_FIELDS, "spec.containers[*]."),
+        "pods": affected
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct WriterParams {
+    namespace: Option<String>,
+    kind: Option<String>,
+    name: Option<String>,
+}
+
+/// Reports which client last modified each object, and every manager that has
+/// written it, to help spot controllers fighting over the same object.
+pub async fn writers_report(
+    State(state): State<AppState>,
+    Query(params): Query<WriterParams>,
+) -> Result<Json<Value>, ApiError> {
+    let objects = match state
+        .storage
+        .writers()
+        .list(params.namespace.as_deref(), params.kind.as_deref(), params.name.as_deref())
+        .await
+    {
+        Ok(objects) => objects,
+        Err(e) => {
+            error!("Failed to list object writers: {}", e);
+            return Err(ApiError::internal(&e));
+        }
+    };
+
+    Ok(Json(json!({
+        "apiVersion": "krust.io/v1alpha1",
+        "kind": "WriterReport",
+        "items": objects
+    })))
+}
+
+/// Lists the removed API versions krust recognizes and how often clients
+/// have asked for each of them since startup.
+pub async fn deprecations_report() -> Json<Value> {
+    let usage = deprecated_apis::usage();
+
+    let apis: Vec<Value> = deprecated_apis::REMOVED_APIS
+        .iter()
+        .map(|api| {
+            let seen = usage.get(&format!("{}/{}", api.group_version, api.resource));
+            json!({
+                "groupVersion": api.group_version,
+                "resource": api.resource,
+                "kind": api.kind,
+                "replacement": api.replacement,
+                "deprecatedIn": format!("v{}", api.deprecated_in),
+                "removedIn": format!("v{}", api.removed_in),
+                "requests": seen.map(|u| u.requests).unwrap_or(0),
+                "lastRequested": seen.map(|u| u.last_requested.clone())
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "apiVersion": "krust.io/v1alpha1",
+        "kind": "DeprecationReport",
+        "removedAPIs": apis
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct SchedulingParams {
+    namespace: Option<String>,
+    name: Option<String>,
+}
+
+/// Explains why each Pending pod hasn't been scheduled: the scheduler's last
+/// FailedScheduling message and what ruled out each node.
+pub async fn scheduling_report(
+    State(state): State<AppState>,
+    Query(params): Query<SchedulingParams>,
+) -> Result<Json<Value>, ApiError> {
+    let pods = match state
+        .storage
+        .scheduling_failures()
+        .list(params.namespace.as_deref(), params.name.as_deref())
+        .await
+    {
+        Ok(pods) => pods,
+        Err(e) => {
+            error!("Failed to list scheduling failures: {}", e);
+            return Err(ApiError::internal(&e));
+        }
+    };
+
+    Ok(Json(json!({
+        "apiVersion": "krust.io/v1alpha1",
+        "kind": "SchedulingReport",
+        "pods": pods
+    })))
+}
+
+/// Lists the feature gates krust knows and which of them are enabled.
+pub async fn feature_gates_report(State(state): State<AppState>) -> Json<Value> {
+    Json(json!({
+        "apiVersion": "krust.io/v1alpha1",
+        "kind": "FeatureGateList",
+        "gates": state.config.feature_gates.report()
+    }))
+}
+
+/// Lists the controller loops with the resync periods and jitter they run
+/// with, as set in the config or defaulted.
+pub async fn controllers_report(State(state): State<AppState>) -> Json<Value> {
+    let controllers: Vec<Value> = config::CONTROLLERS
+        .iter()
+        .map(|(name, _)| {
+            let resync = Resync::new(&state.config, name);
+            json!({
+                "name": name,
+                "resyncPeriodSeconds": resync.period.as_secs_f64(),
+                "maxWaitSeconds": resync.max_wait().as_secs_f64()
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "apiVersion": "krust.io/v1alpha1",
+        "kind": "ControllerList",
+        "jitter": state.config.controllers.jitter,
+        "controllers": controllers
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct ProxyParams {
+    namespace: Option<String>,
+    service: Option<String>,
+}
+
+impl ProxyParams {
+    fn selects(&self, namespace: &str, service: &str) -> bool {
+        self.namespace.as_deref().is_none_or(|ns| ns == namespace) && self.service.as_deref().is_none_or(|name| name == service)
+    }
+}
+
+/// Reports, for each service port the service proxy has served, what it
+/// relayed to each backend and how much it dropped for want of endpoints.
+pub async fn proxy_report(Query(params): Query<ProxyParams>) -> Json<Value> {
+    let services: Vec<Value> = conntrack::ports()
+        .into_iter()
+        .filter(|(port, _)| params.selects(&port.namespace, &port.service))
+        .map(|(port, stats)| {
+            let backends: Vec<Value> = stats
+                .backends
+                .iter()
+                .map(|(address, backend)| {
+                    let mut entry = json!(backend);
+                    entry["address"] = json!(address.to_string());
+                    entry
+                })
+                .collect();
+            json!({
+                "namespace": port.namespace,
+                "service": port.service,
+                "portName": port.port_name,
+                "protocol": port.protocol,
+                "droppedNoEndpoints": stats.dropped,
+                "backends": backends
+            })
+        })
+        .collect();
+
+    Json(json!({
+        "apiVersion": "krust.io/v1alpha1",
+        "kind": "ServiceProxyMetrics",
+        "services": services
+    }))
+}
+
+/// Lists the connections the service proxy is relaying right now.
+pub async fn conntrack_report(Query(params): Query<ProxyParams>) -> Json<Value> {
+    let connections: Vec<_> = conntrack::connections()
+        .into_iter()
+        .filter(|connection| params.selects(&connection.namespace, &connection.service))
+        .collect();
+
+    Json(json!({
+        "apiVersion": "krust.io/v1alpha1",
+        "kind": "ConnectionTrackingTable",
+        "connections": connections
+    }))
+}
+
+/// Reports the database's schema version: the migrations applied to it,
+/// those it still lacks and any from a newer krust.
+pub async fn schema_report(State(state): State<AppState>) -> Result<Json<Value>, ApiError> {
+    let status = match state.storage.schema().await {
+        Ok(status) => status,
+        Err(e) => {
+            error!("Failed to read the schema version: {}", e);
+            return Err(ApiError::internal(&e));
+        }
+    };
+
+    Ok(Json(json!({
+        "apiVersion": "krust.io/v1alpha1",
+        "kind": "SchemaReport",
+        "version": status.version,
+        "latest": status.latest,
+        "upToDate": status.up_to_date(),
+        "applied": status.applied,
+        "pending": status.pending,
+        "unknown": status.unknown,
+        "modified": status.modified,
+        "failed": status.failed
+    })))
+}
+
+#[derive(Deserialize)]
+pub struct BackupParams {
+    path: Option<String>,
+}
+
+/// Backs up the database while krust keeps serving, to a timestamped file
+/// in the data directory's backups/ or to `path` within it, and reports
+/// where the copy went and how big it is. Paths that would leave backups/,
+/// absolute ones or ones with `..`, are refused, since a copy holds every
+/// Secret.
+pub async fn backup(
+    State(state): State<AppState>,
+    Query(params): Query<BackupParams>,
+) -> Result<Json<Value>, ApiError> {
+    let Some(backups) = state.config.data_dir().map(|dir| dir.backups()) else {
+        return Err(ApiError::bad_request("there's no data directory to back up to"));
+    };
+    let path = match params.path.filter(|p| !p.is_empty()).map(PathBuf::from) {
+        Some(path) if path.components().all(|c| matches!(c, Component::Normal(_))) => backups.join(path),
+        Some(path) => {
+            let message = format!("{} is not a path within the backups directory", path.display());
+            return Err(ApiError::bad_request(message));
+        }
+        None => backups.join(format!("krust-{}.db", Utc::now().format("%Y%m%dT%H%M%SZ"))),
+    };
+    if path.exists() {
+        let message = format!("{} already exists", path.display());
+        return Err(ApiError::new(StatusCode::CONFLICT, "AlreadyExists", message));
+    }
+
+    let started = Utc::now();
+    let size = match state.storage.backup(&path).await {
+        Ok(size) => size,
+        Err(e) => {
+            error!("Failed to back up the database: {:#}", e);
+            return Err(ApiError::internal(format!("{:#}", e)));
+        }
+    };
+    info!("Backed up the database to {} ({} bytes)", path.display(), size);
+
+    Ok(Json(json!({
+        "apiVersion": "krust.io/v1alpha1",
+        "kind": "Backup",
+        "path": path,
+        "sizeBytes": size,
+        "startTime": started.to_rfc3339_opts(SecondsFormat::Secs, true),
+        "completionTime": Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true)
+    })))
+}
@@ -0,0 +1,78 @@
+// Client-side apply. `kubectl apply` without --server-side records the
+// configuration it applied in an annotation and patches the object with the
+// new one. The previously applied configuration is what tells fields the
+// user dropped from their manifest apart from fields set by someone else,
+// so the removals are worked out from it here: a three-way merge of the
+// previous configuration, the new one and the live object.
+use serde_json::{json, Map, Value};
+
+pub const ANNOTATION: &str = "kubectl.kubernetes.io/last-applied-configuration";
+
+/// Completes a patch that records a new last-applied configuration with the
+/// removals it implies: fields of the previous configuration that are gone
+/// from the new one are deleted from the live object. Anything the patch
+/// sets itself, including explicit nulls, takes precedence. Patches without
+/// the annotation are returned as they are.
+pub fn with_removals(patch: Value, current: &Value) -> Value {
+    let Some(modified) = applied(&patch) else {
+        return patch;
+    };
+    let original = applied(current).unwrap_or_else(|| json!({}));
+
+    let mut merged = removals(&original, &modified, current);
+    overlay(&mut merged, &patch);
+    merged
+}
+
+// The configuration recorded in an object's annotation, if it has a valid one
+fn applied(object: &Value) -> Option<Value> {
+    let config = object["metadata"]["annotations"][ANNOTATION].as_str()?;
+    serde_json::from_str(config).ok().filter(Value::is_object)
+}
+
+// A merge patch deleting what `original` had and `modified` doesn't, for
+// fields still present in `current`. Lists are replaced as a whole by the
+// patch, so only objects are descended into.
+fn removals(original: &Value, modified: &Value, current: &Value) -> Value {
+    let mut patch = Map::new();
+
+    if let (Some(original), Some(current)) = (original.as_object(), current.as_object()) {
+        for (key, value) in original {
+            if !current.contains_key(key) {
+                continue;
+            }
+            match modified.get(key) {
+                None => {
+                    patch.insert(key.clone(), Value::Null);
+                }
+                Some(next) if value.is_object() && next.is_object() => {
+                    let nested = removals(value, next, &current[key]);
+                    if nested.as_object().is_some_and(|fields| !fields.is_empty()) {
+                        patch.insert(key.clone(), nested);
+                    }
+                }
+                Some(_) => {}
+            }
+        }
+    }
+
+    Value::Object(patch)
+}
+
+// Lays one merge patch over another. Unlike applying it, nulls are kept so
+// the result still deletes what either patch deletes.
+fn overlay(base: &mut Value, top: &Value) {
+    match (base.as_object_mut(), top.as_object()) {
+        (Some(base), Some(top)) => {
+            for (key, value) in top {
+                match base.get_mut(key) {
+                    Some(existing) if existing.is_object() && value.is_object() => overlay(existing, value),
+                    _ => {
+                        base.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        _ => *base = top.clone(),
+    }
+}
@@ -0,0 +1,150 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+    Json,
+};
+use serde_json::Value;
+use tracing::{error, info};
+
+use crate::api::error_status::ApiError;
+use crate::api::handlers::{list_error, ListParams};
+use crate::api::patch;
+use crate::api::server::AppState;
+use crate::api::watch::list_or_watch;
+
+pub async fn create_lease(
+    State(state): State<AppState>,
+    Path(namespace): Path<String>,
+    Json(lease): Json<Value>,
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    info!(
+        "Creating Lease {} in namespace {}",
+        lease["metadata"]["name"].as_str().unwrap_or("unknown"),
+        namespace
+    );
+
+    let name = lease["metadata"]["name"].as_str().unwrap_or_default().to_string();
+
+    match state.storage.leases().create(&namespace, lease).await {
+        Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) => {
+            if e.to_string().contains("UNIQUE constraint failed") {
+                Err(ApiError::already_exists("leases", &name))
+            } else if e.to_string().contains("required") {
+                Err(ApiError::bad_request("name or generateName is required"))
+            } else {
+                error!("Failed to create Lease: {}", e);
+                Err(ApiError::internal(&e))
+            }
+        }
+    }
+}
+
+pub async fn get_lease(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<Json<Value>, ApiError> {
+    match state.storage.leases().get(&namespace, &name).await {
+        Ok(lease) => Ok(Json(lease)),
+        Err(e) => {
+            if e.to_string().contains("not found") {
+                Err(ApiError::not_found("leases", &name))
+            } else {
+                error!("Failed to get Lease: {}", e);
+                Err(ApiError::internal(&e))
+            }
+        }
+    }
+}
+
+pub async fn list_leases(
+    State(state): State<AppState>,
+    Path(namespace): Path<String>,
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "leases", Some(&namespace), &params, &selector, state.storage.leases().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
+        Err(e) => {
+            error!("Failed to list Leases: {}", e);
+            Err(list_error(&e))
+        }
+    }
+}
+
+pub async fn list_all_leases(
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "leases", None, &params, &selector, state.storage.leases().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
+        Err(e) => {
+            error!("Failed to list all Leases: {}", e);
+            Err(list_error(&e))
+        }
+    }
+}
+
+pub async fn update_lease(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+    Json(lease): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    match state.storage.leases().update(&namespace, &name, lease).await {
+        Ok(updated) => Ok(Json(updated)),
+        Err(e) => {
+            if e.to_string().contains("not found") {
+                Err(ApiError::not_found("leases", &name))
+            } else {
+                error!("Failed to update Lease: {}", e);
+                Err(ApiError::internal(&e))
+            }
+        }
+    }
+}
+
+pub async fn patch_lease(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(patch): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    let mut lease = match state.storage.leases().get(&namespace, &name).await {
+        Ok(lease) => lease,
+        Err(e) if e.to_string().contains("not found") => return Err(ApiError::not_found("leases", &name)),
+        Err(e) => {
+            error!("Failed to get Lease for patch: {}", e);
+            return Err(ApiError::internal(&e));
+        }
+    };
+    patch::apply(&headers, &mut lease, patch)?;
+
+    match state.storage.leases().update(&namespace, &name, lease).await {
+        Ok(updated) => Ok(Json(updated)),
+        Err(e) => {
+            error!("Failed to patch Lease: {}", e);
+            Err(ApiError::internal(&e))
+        }
+    }
+}
+
+pub async fn delete_lease(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+) -> Result<Json<Value>, ApiError> {
+    info!("Deleting Lease {} in namespace {}", name, namespace);
+
+    match state.storage.leases().delete(&namespace, &name).await {
+        Ok(deleted) => Ok(Json(deleted)),
+        Err(e) => {
+            if e.to_string().contains("not found") {
+                Err(ApiError::not_found("leases", &name))
+            } else {
+                error!("Failed to delete Lease: {}", e);
+                Err(ApiError::internal(&e))
+            }
+        }
+    }
+}
@@ -0,0 +1,71 @@
+// LimitRange admission: pods created in a namespace with LimitRanges get
+// their containers' missing requests and limits defaulted and are refused if
+// they fall outside the ranges (see models::limit_range). Like the
+// LimitRanger plugin it only looks at creates, pods' resources being
+// immutable, and runs after the mutating webhooks, so the containers they
+// add are held to the ranges too.
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+use tracing::error;
+
+use super::error_status::ApiError;
+use super::request_info::RequestInfo;
+use super::server::AppState;
+use crate::models::limit_range;
+
+/// Middleware applying the namespace's LimitRanges to pods being created.
+pub async fn apply_limit_ranges(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if *request.method() != Method::POST {
+        return next.run(request).await;
+    }
+    let info = RequestInfo::parse(request.method().as_str(), request.uri().path(), request.uri().query());
+    let RequestInfo::Resource { resource, subresource: None, namespace: Some(namespace), .. } = info else {
+        return next.run(request).await;
+    };
+    if resource != "pods" {
+        return next.run(request).await;
+    }
+
+    let limit_ranges = match state.storage.limitranges().list(Some(&namespace)).await {
+        Ok(list) => list["items"].as_array().cloned().unwrap_or_default(),
+        Err(e) => {
+            error!("Failed to list LimitRanges of namespace {}: {}", namespace, e);
+            return ApiError::internal(&e).into_response();
+        }
+    };
+    if limit_ranges.is_empty() {
+        return next.run(request).await;
+    }
+
+    let (mut parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to buffer request body: {}", e);
+            return ApiError::bad_request(e.to_string()).into_response();
+        }
+    };
+    // Bodies that aren't pods are the handler's to turn down
+    let mut pod = match serde_json::from_slice::<Value>(&bytes) {
+        Ok(pod) if pod["spec"].is_object() => pod,
+        _ => return next.run(Request::from_parts(parts, Body::from(bytes))).await,
+    };
+    if let Err(message) = limit_range::admit(&limit_ranges, &mut pod) {
+        return forbidden(pod["metadata"]["name"].as_str().unwrap_or_default(), &message);
+    }
+
+    parts.headers.remove(header::CONTENT_LENGTH);
+    next.run(Request::from_parts(parts, Body::from(pod.to_string()))).await
+}
+
+fn forbidden(name: &str, message: &str) -> Response {
+    ApiError::new(StatusCode::FORBIDDEN, "Forbidden", message)
+        .with_details(json!({ "name": name, "kind": "pods" }))
+        .into_response()
+}
@@ -1,11 +1,37 @@
+pub mod admission;
+pub mod admission_webhook;
+pub mod authentication;
+pub mod authorization;
+pub mod authn_webhook;
+pub mod cancellation;
 pub mod configmap_handlers;
+pub mod controllerrevision_handlers;
+pub mod conflicts;
 pub mod cronjob_handlers;
 pub mod daemonset_handlers;
+pub mod delete_options;
+pub mod discovery;
+pub mod dry_run;
+pub mod deprecated_apis;
+pub mod error_status;
+pub mod event_handlers;
+pub mod eviction;
+pub mod export;
+pub mod field_manager;
+pub mod finalizers;
 pub mod handlers;
+pub mod hpa_handlers;
 pub mod ingress_handlers;
+pub mod injection;
 pub mod job_handlers;
+pub mod lease_handlers;
+pub mod krust_handlers;
+pub mod last_applied;
+pub mod limit_ranges;
 pub mod networkpolicy_handlers;
+pub mod patch;
 pub mod pdb_handlers;
+pub mod profiling_handlers;
 pub mod pv_handlers;
 pub mod pvc_handlers;
 pub mod quota_handlers;
@@ -14,31 +40,27 @@ pub mod scheduling_handlers;
 pub mod secret_handlers;
 pub mod serviceaccount_handlers;
 pub mod statefulset_handlers;
+#[cfg(feature = "strict-types")]
+pub mod strict_types;
+pub mod table;
 pub mod webhook_handlers;
+pub mod object_path;
+pub mod oidc;
 pub mod openapi;
 pub mod openapi_proto;
 pub mod openapi_proto_v2;
+pub mod openapi_v3;
 pub mod pod_proxy;
+pub mod pod_security;
+pub mod protection;
+pub mod request_info;
+pub mod resource_types;
 pub mod portforward;
-pub mod portforward_exec;
-pub mod portforward_proxy;
-pub mod portforward_spdy;
-pub mod portforward_v2;
-pub mod portforward_complete;
-pub mod portforward_working;
-pub mod portforward_perfect;
-pub mod portforward_fixed;
-pub mod spdy_protocol;
-pub mod kubectl_portforward;
-pub mod kubectl_simple;
-pub mod kubectl_exact;
-pub mod kubectl_v1;
-pub mod portforward_handler;
-pub mod portforward_v2_new;
-pub mod portforward_final;
-pub mod portforward_champion;
 pub mod routes;
 pub mod server;
 pub mod service_portforward;
 pub mod spdy;
-pub mod spdy_handler;
\ No newline at end of file
+pub mod streaming;
+pub mod timeout;
+pub mod tls;
+pub mod watch;
\ No newline at end of file
@@ -1,21 +1,34 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
+    response::Response,
     Json,
 };
 use serde_json::{json, Value};
 use tracing::{error, info};
 
+use crate::api::error_status::{ApiError, Cause};
+use crate::api::last_applied;
+use crate::api::handlers::{list_error, ListParams};
 use crate::api::server::AppState;
+use crate::api::watch::list_or_watch;
+use crate::models::network_policy;
+
+// Refuses a policy whose ipBlocks or ports the enforcer couldn't use
+fn validate(policy: &Value) -> Result<(), ApiError> {
+    network_policy::validate(&policy["spec"]).map_err(|cause| {
+        ApiError::invalid("networkpolicies", policy["metadata"]["name"].as_str().unwrap_or(""), vec![Cause::parse(&cause)])
+    })
+}
 
 pub async fn create_networkpolicy(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(mut policy): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     // Validate kind
     if policy.get("kind").and_then(|k| k.as_str()) != Some("NetworkPolicy") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("NetworkPolicy", &policy));
     }
 
     // Ensure metadata exists
@@ -29,24 +42,19 @@ pub async fn create_networkpolicy(
         namespace
     );
 
+    let name = policy["metadata"]["name"].as_str().unwrap_or_default().to_string();
+    validate(&policy)?;
+
     let store = state.storage.networkpolicies();
     match store.create(&namespace, policy).await {
         Ok(created_policy) => Ok((StatusCode::CREATED, Json(created_policy))),
         Err(e) => {
             if e.to_string().contains("UNIQUE constraint failed") {
                 error!("NetworkPolicy already exists: {}", e);
-                return Ok((StatusCode::CONFLICT, Json(json!({
-                    "kind": "Status",
-                    "apiVersion": "v1",
-                    "metadata": {},
-                    "status": "Failure",
-                    "message": "NetworkPolicy already exists",
-                    "reason": "AlreadyExists",
-                    "code": 409
-                }))));
+                return Err(ApiError::already_exists("networkpolicies", &name));
             }
             error!("Failed to create NetworkPolicy: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -54,7 +62,7 @@ pub async fn create_networkpolicy(
 pub async fn get_networkpolicy(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Getting NetworkPolicy {} in namespace {}", name, namespace);
     
     let store = state.storage.networkpolicies();
@@ -62,11 +70,11 @@ pub async fn get_networkpolicy(
         Ok(policy) => Ok(Json(policy)),
         Err(e) if e.to_string().contains("not found") => {
             error!("NetworkPolicy {}/{} not found", namespace, name);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("networkpolicies", &name))
         }
         Err(e) => {
             error!("Failed to get NetworkPolicy: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -74,30 +82,34 @@ pub async fn get_networkpolicy(
 pub async fn list_networkpolicies_namespaced(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing NetworkPolicies in namespace {}", namespace);
     
+    let selector = params.selector()?;
     let store = state.storage.networkpolicies();
-    match store.list(Some(&namespace)).await {
-        Ok(list) => Ok(Json(list)),
+    match list_or_watch(&state, "networkpolicies", Some(&namespace), &params, &selector, store.list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list NetworkPolicies: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
 
 pub async fn list_networkpolicies_all_namespaces(
     State(state): State<AppState>,
-) -> Result<Json<Value>, StatusCode> {
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing NetworkPolicies in all namespaces");
     
+    let selector = params.selector()?;
     let store = state.storage.networkpolicies();
-    match store.list(None).await {
-        Ok(list) => Ok(Json(list)),
+    match list_or_watch(&state, "networkpolicies", None, &params, &selector, store.list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list NetworkPolicies: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -106,12 +118,12 @@ pub async fn update_networkpolicy(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(mut policy): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Updating NetworkPolicy {} in namespace {}", name, namespace);
     
     // Validate kind
     if policy.get("kind").and_then(|k| k.as_str()) != Some("NetworkPolicy") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("NetworkPolicy", &policy));
     }
 
     // Ensure metadata exists with correct name and namespace
@@ -120,6 +132,7 @@ pub async fn update_networkpolicy(
     }
     policy["metadata"]["name"] = json!(name);
     policy["metadata"]["namespace"] = json!(namespace);
+    validate(&policy)?;
     
     let store = state.storage.networkpolicies();
     
@@ -134,23 +147,23 @@ pub async fn update_networkpolicy(
                         Ok(updated_policy) => Ok(Json(updated_policy)),
                         Err(e) => {
                             error!("Failed to update NetworkPolicy: {}", e);
-                            Err(StatusCode::INTERNAL_SERVER_ERROR)
+                            Err(ApiError::internal(&e))
                         }
                     }
                 }
                 Err(e) => {
                     error!("Failed to delete NetworkPolicy for update: {}", e);
-                    Err(StatusCode::INTERNAL_SERVER_ERROR)
+                    Err(ApiError::internal(&e))
                 }
             }
         }
         Err(e) if e.to_string().contains("not found") => {
             error!("NetworkPolicy {}/{} not found for update", namespace, name);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("networkpolicies", &name))
         }
         Err(e) => {
             error!("Failed to check NetworkPolicy existence: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -159,7 +172,7 @@ pub async fn patch_networkpolicy(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(patch): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Patching NetworkPolicy {} in namespace {}", name, namespace);
     
     let store = state.storage.networkpolicies();
@@ -167,8 +180,9 @@ pub async fn patch_networkpolicy(
     // Get existing resource
     match store.get(&namespace, &name).await {
         Ok(mut existing) => {
-            // Simple merge patch - merge the patch into existing
-            merge_json(&mut existing, &patch);
+            let patch = last_applied::with_removals(patch, &existing);
+            json_patch::merge(&mut existing, &patch);
+            validate(&existing)?;
             
             // Delete and recreate with merged data
             match store.delete(&namespace, &name).await {
@@ -177,23 +191,23 @@ pub async fn patch_networkpolicy(
                         Ok(patched_policy) => Ok(Json(patched_policy)),
                         Err(e) => {
                             error!("Failed to patch NetworkPolicy: {}", e);
-                            Err(StatusCode::INTERNAL_SERVER_ERROR)
+                            Err(ApiError::internal(&e))
                         }
                     }
                 }
                 Err(e) => {
                     error!("Failed to delete NetworkPolicy for patch: {}", e);
-                    Err(StatusCode::INTERNAL_SERVER_ERROR)
+                    Err(ApiError::internal(&e))
                 }
             }
         }
         Err(e) if e.to_string().contains("not found") => {
             error!("NetworkPolicy {}/{} not found for patch", namespace, name);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("networkpolicies", &name))
         }
         Err(e) => {
             error!("Failed to get NetworkPolicy for patch: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -201,7 +215,7 @@ pub async fn patch_networkpolicy(
 pub async fn delete_networkpolicy(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Deleting NetworkPolicy {} in namespace {}", name, namespace);
     
     let store = state.storage.networkpolicies();
@@ -209,28 +223,11 @@ pub async fn delete_networkpolicy(
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) if e.to_string().contains("not found") => {
             error!("NetworkPolicy {}/{} not found", namespace, name);
-            Err(StatusCode::NOT_FOUND)
+            Err(ApiError::not_found("networkpolicies", &name))
         }
         Err(e) => {
             error!("Failed to delete NetworkPolicy: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
-
-// Helper function to merge JSON objects
-fn merge_json(target: &mut Value, patch: &Value) {
-    if let (Some(target_obj), Some(patch_obj)) = (target.as_object_mut(), patch.as_object()) {
-        for (key, value) in patch_obj {
-            if let Some(target_value) = target_obj.get_mut(key) {
-                if target_value.is_object() && value.is_object() {
-                    merge_json(target_value, value);
-                } else {
-                    *target_value = value.clone();
-                }
-            } else {
-                target_obj.insert(key.clone(), value.clone());
-            }
-        }
-    }
-}
\ No newline at end of file
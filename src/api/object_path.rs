@@ -0,0 +1,39 @@
+// Works out which object an API path names, for the middleware that act on
+// single objects whatever their kind.
+
+/// The object named by a path such as
+/// /apis/apps/v1/namespaces/default/deployments/web/scale.
+pub struct ObjectPath {
+    pub resource: String,
+    pub namespace: Option<String>,
+    pub name: String,
+    pub subresource: Option<String>,
+}
+
+/// Parses a path naming a single object or one of its subresources. Paths of
+/// collections and anything outside /api and /apis give None.
+pub fn parse(path: &str) -> Option<ObjectPath> {
+    let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+    let rest = match segments.as_slice() {
+        ["api", "v1", rest @ ..] => rest,
+        ["apis", _group, _version, rest @ ..] => rest,
+        _ => return None,
+    };
+    let (namespace, resource, name, subresource) = match rest {
+        ["namespaces", namespace, resource, name] => (Some(*namespace), *resource, *name, None),
+        ["namespaces", namespace, resource, name, subresource] => (Some(*namespace), *resource, *name, Some(*subresource)),
+        // A namespace's own subresources; other three-segment paths under
+        // namespaces/ are collections
+        ["namespaces", name, subresource @ ("status" | "finalize")] => (None, "namespaces", *name, Some(*subresource)),
+        ["namespaces", _, _] => return None,
+        [resource, name] => (None, *resource, *name, None),
+        [resource, name, subresource] => (None, *resource, *name, Some(*subresource)),
+        _ => return None,
+    };
+    Some(ObjectPath {
+        resource: resource.to_string(),
+        namespace: namespace.map(|ns| ns.to_string()),
+        name: name.to_string(),
+        subresource: subresource.map(|sub| sub.to_string()),
+    })
+}
@@ -0,0 +1,218 @@
+// OpenID Connect ID token verification. The issuer's signing keys are found
+// through its discovery document and cached, and fetched again when a token
+// names a key the cache doesn't have, so the provider can rotate them.
+use anyhow::{anyhow, bail, Context, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use openssl::bn::BigNum;
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Public};
+use openssl::rsa::Rsa;
+use openssl::sign::Verifier;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+use super::authentication::UserInfo;
+use crate::config::OidcConfig;
+
+// How long fetched keys are trusted, and how often an unknown key ID may
+// trigger a refetch
+const KEYS_TTL: Duration = Duration::from_secs(3600);
+const MIN_REFRESH_INTERVAL: Duration = Duration::from_secs(10);
+
+struct SigningKey {
+    kid: Option<String>,
+    key: PKey<Public>,
+}
+
+struct KeySet {
+    keys: Vec<SigningKey>,
+    fetched: Instant,
+}
+
+pub struct OidcAuthenticator {
+    config: OidcConfig,
+    client: reqwest::Client,
+    keys: RwLock<Option<KeySet>>,
+}
+
+impl OidcAuthenticator {
+    pub fn new(config: OidcConfig) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            keys: RwLock::new(None),
+        }
+    }
+
+    /// Verifies an ID token and maps its claims to a user.
+    pub async fn authenticate(&self, token: &str) -> Result<UserInfo> {
+        let parts: Vec<&str> = token.split('.').collect();
+        let [header, payload, signature] = parts.as_slice() else {
+            bail!("not a JWT");
+        };
+        let header: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header)?)?;
+        let claims: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload)?)?;
+        let signature = URL_SAFE_NO_PAD.decode(signature)?;
+
+        let alg = header["alg"].as_str().unwrap_or_default();
+        let kid = header["kid"].as_str();
+        let key = self.key(kid).await?;
+        let signed = format!("{}.{}", parts[0], parts[1]);
+        if !verify(alg, &key, signed.as_bytes(), &signature)? {
+            bail!("invalid signature");
+        }
+
+        self.check_claims(&claims)?;
+        self.user(&claims)
+    }
+
+    fn check_claims(&self, claims: &Value) -> Result<()> {
+        if claims["iss"].as_str() != Some(self.config.issuer_url.as_str()) {
+            bail!("issued by {}, not {}", claims["iss"], self.config.issuer_url);
+        }
+        let audience = match &claims["aud"] {
+            Value::String(aud) => aud == &self.config.client_id,
+            Value::Array(auds) => auds.iter().any(|aud| aud == &Value::from(self.config.client_id.as_str())),
+            _ => false,
+        };
+        if !audience {
+            bail!("not issued for {}", self.config.client_id);
+        }
+
+        let now = chrono::Utc::now().timestamp();
+        match claims["exp"].as_i64() {
+            Some(exp) if exp > now => {}
+            Some(_) => bail!("expired"),
+            None => bail!("no exp claim"),
+        }
+        if claims["nbf"].as_i64().is_some_and(|nbf| nbf > now) {
+            bail!("not valid yet");
+        }
+        Ok(())
+    }
+
+    fn user(&self, claims: &Value) -> Result<UserInfo> {
+        let config = &self.config;
+        let claim = config.username_claim.as_str();
+        let username = claims[claim]
+            .as_str()
+            .ok_or_else(|| anyhow!("no {} claim", claim))?;
+        if claim == "email" && claims["email_verified"] == Value::Bool(false) {
+            bail!("email {} is not verified", username);
+        }
+        let prefix = match config.username_prefix.as_deref() {
+            Some("-") => String::new(),
+            Some(prefix) => prefix.to_string(),
+            None if claim == "email" => String::new(),
+            None => format!("{}#", config.issuer_url),
+        };
+
+        let groups = match config.groups_claim.as_deref().map(|c| &claims[c]) {
+            Some(Value::String(group)) => vec![group.clone()],
+            Some(Value::Array(groups)) => groups.iter().filter_map(|g| g.as_str()).map(str::to_string).collect(),
+            _ => Vec::new(),
+        };
+
+        Ok(UserInfo {
+            username: format!("{}{}", prefix, username),
+            groups: groups.into_iter().map(|g| format!("{}{}", config.groups_prefix, g)).collect(),
+            ..Default::default()
+        })
+    }
+
+    // The key a token was signed with, refetching the keys when they are old
+    // or don't include it
+    async fn key(&self, kid: Option<&str>) -> Result<PKey<Public>> {
+        let matches = |key: &&SigningKey| kid.is_none() || key.kid.as_deref() == kid;
+        {
+            let keys = self.keys.read().await;
+            if let Some(set) = keys.as_ref() {
+                let key = set.keys.iter().find(matches);
+                if let Some(key) = key.filter(|_| set.fetched.elapsed() < KEYS_TTL) {
+                    return Ok(key.key.clone());
+                }
+                if key.is_none() && set.fetched.elapsed() < MIN_REFRESH_INTERVAL {
+                    bail!("unknown key {:?}", kid);
+                }
+            }
+        }
+
+        let mut keys = self.keys.write().await;
+        let set = self.fetch_keys().await?;
+        let key = set.keys.iter().find(matches).map(|key| key.key.clone());
+        *keys = Some(set);
+        key.ok_or_else(|| anyhow!("unknown key {:?}", kid))
+    }
+
+    async fn fetch_keys(&self) -> Result<KeySet> {
+        let discovery_url = format!(
+            "{}/.well-known/openid-configuration",
+            self.config.issuer_url.trim_end_matches('/')
+        );
+        let discovery: Value = self.client.get(&discovery_url).send().await?.error_for_status()?.json().await?;
+        let jwks_uri = discovery["jwks_uri"]
+            .as_str()
+            .ok_or_else(|| anyhow!("{} has no jwks_uri", discovery_url))?;
+        let jwks: Value = self.client.get(jwks_uri).send().await?.error_for_status()?.json().await?;
+
+        let keys = jwks["keys"]
+            .as_array()
+            .map(|keys| keys.iter().filter_map(|jwk| public_key(jwk).ok()).collect())
+            .unwrap_or_default();
+        Ok(KeySet { keys, fetched: Instant::now() })
+    }
+}
+
+// A JWK's public key; RSA and P-256/P-384 EC keys are supported
+fn public_key(jwk: &Value) -> Result<SigningKey> {
+    let field = |name: &str| -> Result<BigNum> {
+        let value = jwk[name].as_str().ok_or_else(|| anyhow!("JWK without {}", name))?;
+        Ok(BigNum::from_slice(&URL_SAFE_NO_PAD.decode(value)?)?)
+    };
+    let key = match jwk["kty"].as_str() {
+        Some("RSA") => PKey::from_rsa(Rsa::from_public_components(field("n")?, field("e")?)?)?,
+        Some("EC") => {
+            let curve = match jwk["crv"].as_str() {
+                Some("P-256") => Nid::X9_62_PRIME256V1,
+                Some("P-384") => Nid::SECP384R1,
+                other => bail!("unsupported curve {:?}", other),
+            };
+            let group = EcGroup::from_curve_name(curve)?;
+            let (x, y) = (field("x")?, field("y")?);
+            let key = EcKey::from_public_key_affine_coordinates(&group, &x, &y)?;
+            PKey::from_ec_key(key)?
+        }
+        other => bail!("unsupported key type {:?}", other),
+    };
+    Ok(SigningKey {
+        kid: jwk["kid"].as_str().map(str::to_string),
+        key,
+    })
+}
+
+fn verify(alg: &str, key: &PKey<Public>, signed: &[u8], signature: &[u8]) -> Result<bool> {
+    let digest = match alg {
+        "RS256" | "ES256" => MessageDigest::sha256(),
+        "RS384" | "ES384" => MessageDigest::sha384(),
+        "RS512" => MessageDigest::sha512(),
+        other => bail!("unsupported algorithm {:?}", other),
+    };
+    // JWS carries ECDSA signatures as r and s back to back rather than DER
+    let signature = if alg.starts_with("ES") {
+        let (r, s) = signature.split_at(signature.len() / 2);
+        EcdsaSig::from_private_components(BigNum::from_slice(r)?, BigNum::from_slice(s)?)?
+            .to_der()
+            .context("invalid ECDSA signature")?
+    } else {
+        signature.to_vec()
+    };
+
+    let mut verifier = Verifier::new(digest, key)?;
+    verifier.update(signed)?;
+    Ok(verifier.verify(&signature).unwrap_or(false))
+}
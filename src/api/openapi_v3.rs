@@ -0,0 +1,319 @@
+// OpenAPI v3, as kubectl explain and client-side validation read it: /openapi/v3
+// lists a document per group version, each holding the paths of its resources
+// and the complete schemas of their objects. Nothing is written out by hand.
+// The resources and their verbs are the ones discovery reads off the routes,
+// and the schemas are generated from their k8s-openapi types in
+// resource_types, so a resource shows up here once it's served and typed.
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+    routing::get,
+    Router,
+};
+use k8s_openapi::apimachinery::pkg::apis::meta::v1::{ListMeta, Status};
+use k8s_openapi::schemars::{
+    gen::{SchemaGenerator, SchemaSettings},
+    schema::Schema,
+    JsonSchema,
+};
+use serde_json::{json, Map, Value};
+use tokio::sync::OnceCell;
+
+use super::discovery::{self, GroupVersion, GROUP_VERSIONS};
+use super::error_status::ApiError;
+use super::resource_types::resource_type;
+use super::server::{AppState, KUBERNETES_VERSION};
+
+const SCHEMAS: &str = "#/components/schemas/";
+
+// The patch types taken by every resource that can be patched
+const PATCH_TYPES: &[&str] = &[
+    "application/json-patch+json",
+    "application/merge-patch+json",
+    "application/strategic-merge-patch+json",
+    "application/apply-patch+yaml",
+];
+
+// The query parameters of lists and watches, with their descriptions
+const LIST_PARAMETERS: &[(&str, &str, &str)] = &[
+    ("labelSelector", "string", "A selector to restrict the list of returned objects by their labels."),
+    ("fieldSelector", "string", "A selector to restrict the list of returned objects by their fields."),
+    ("limit", "integer", "The maximum number of responses to return for a list call."),
+    ("continue", "string", "The continue token of the previous page of a list call."),
+    ("resourceVersion", "string", "Which resource versions the results may come from."),
+    ("watch", "boolean", "Watch for changes to the described resources and return them as a stream of add, update, and remove notifications."),
+    ("timeoutSeconds", "integer", "Timeout for the list/watch call."),
+];
+
+/// Adds /openapi/v3 and the document of every group version.
+pub fn routes(router: Router<AppState>) -> Router<AppState> {
+    router
+        .route("/openapi/v3", get(paths))
+        .route("/openapi/v3/api/:version", get(|state: State<AppState>, Path(version): Path<String>| {
+            document(state, String::new(), version)
+        }))
+        .route("/openapi/v3/apis/:group/:version", get(|state: State<AppState>, Path((group, version)): Path<(String, String)>| {
+            document(state, group, version)
+        }))
+}
+
+// Each group version's document and its hash, generated the first time one
+// is asked for, like discovery's resource lists they come from
+static DOCUMENTS: OnceCell<Vec<(Value, String)>> = OnceCell::const_new();
+
+async fn documents(state: &AppState) -> &'static [(Value, String)] {
+    DOCUMENTS
+        .get_or_init(|| async {
+            let lists = discovery::resource_lists(state).await;
+            GROUP_VERSIONS
+                .iter()
+                .zip(lists)
+                .map(|(group_version, list)| {
+                    let document = generate(group_version, list);
+                    let hash = hex_upper(&openssl::sha::sha512(document.to_string().as_bytes()));
+                    (document, hash)
+                })
+                .collect()
+        })
+        .await
+}
+
+async fn paths(State(state): State<AppState>) -> Json<Value> {
+    let documents = documents(&state).await;
+    let paths: Map<String, Value> = GROUP_VERSIONS
+        .iter()
+        .zip(documents)
+        .map(|(group_version, (_, hash))| {
+            let path = group_version.path();
+            let url = format!("/openapi/v3{}?hash={}", path, hash);
+            (path.trim_start_matches('/').to_string(), json!({ "serverRelativeURL": url }))
+        })
+        .collect();
+    Json(json!({ "paths": paths }))
+}
+
+async fn document(State(state): State<AppState>, group: String, version: String) -> Response {
+    let documents = documents(&state).await;
+    let Some(i) = GROUP_VERSIONS.iter().position(|gv| gv.group == group && gv.version == version) else {
+        return ApiError::from(StatusCode::NOT_FOUND).into_response();
+    };
+    // The hash in the URL names the content, so clients may keep it for good
+    ([(header::CACHE_CONTROL, "public, immutable")], Json(documents[i].0.clone())).into_response()
+}
+
+fn hex_upper(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02X}", b)).collect()
+}
+
+// A schema generator's reference to a type's schema, as a name and a $ref
+fn reference(generator: &mut SchemaGenerator, schema: fn(&mut SchemaGenerator) -> Schema) -> (String, Value) {
+    let reference = serde_json::to_value(schema(generator)).unwrap_or_default();
+    let name = reference["$ref"].as_str().unwrap_or_default().trim_start_matches(SCHEMAS).to_string();
+    (name, reference)
+}
+
+fn schema_of<T: JsonSchema>(generator: &mut SchemaGenerator) -> Schema {
+    generator.subschema_for::<T>()
+}
+
+// The prefix of operation ids: CoreV1, AppsV1, RbacAuthorizationV1, ...
+fn operation_prefix(group_version: &GroupVersion) -> String {
+    let group = match group_version.group {
+        "" => "core",
+        group => group.trim_end_matches(".k8s.io"),
+    };
+    let mut prefix: String = group.split('.').map(capitalize).collect();
+    prefix.push_str(&capitalize(group_version.version));
+    prefix
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn path_parameter(name: &str, description: &str) -> Value {
+    json!({
+        "name": name,
+        "in": "path",
+        "description": description,
+        "required": true,
+        "schema": { "type": "string", "uniqueItems": true }
+    })
+}
+
+fn json_content(schema: &Value) -> Value {
+    json!({ "application/json": { "schema": schema } })
+}
+
+// An operation answering with an object of `schema`
+fn operation(id: String, description: String, action: &str, gvk: &Value, schema: &Value, code: &str) -> Value {
+    json!({
+        "description": description,
+        "operationId": id,
+        "responses": {
+            code: { "description": "OK", "content": json_content(schema) },
+            "401": { "description": "Unauthorized" }
+        },
+        "x-kubernetes-action": action,
+        "x-kubernetes-group-version-kind": gvk
+    })
+}
+
+// The OpenAPI document of a group version, given its APIResourceList
+fn generate(group_version: &GroupVersion, list: &Value) -> Value {
+    let mut generator = SchemaSettings::openapi3().into_generator();
+    let (_, status) = reference(&mut generator, schema_of::<Status>);
+    let (_, list_meta) = reference(&mut generator, schema_of::<ListMeta>);
+    let patch_name = "io.k8s.apimachinery.pkg.apis.meta.v1.Patch";
+    let patch = json!({ "$ref": format!("{}{}", SCHEMAS, patch_name) });
+    let mut schemas = Map::new();
+    schemas.insert(
+        patch_name.to_string(),
+        json!({
+            "description": "Patch is provided to give a concrete name and type to the Kubernetes PATCH request body.",
+            "type": "object"
+        }),
+    );
+
+    let prefix = operation_prefix(group_version);
+    let resources = list["resources"].as_array().cloned().unwrap_or_default();
+    let mut paths = Map::new();
+    let mut kinds = Vec::new();
+    for resource in resources.iter().filter(|r| !r["name"].as_str().unwrap_or_default().contains('/')) {
+        let name = resource["name"].as_str().unwrap_or_default();
+        let Some(typed) = resource_type(group_version.group, group_version.version, name) else {
+            continue;
+        };
+        let namespaced = resource["namespaced"] == true;
+        let verbs: Vec<&str> = resource["verbs"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+        let kind = typed.kind;
+        let gvk = json!({ "group": group_version.group, "version": group_version.version, "kind": kind });
+        let scope = if namespaced { "Namespaced" } else { "" };
+
+        let (schema_name, object) = reference(&mut generator, typed.schema);
+        kinds.push((schema_name.clone(), kind));
+        let list_name = format!("{}List", schema_name);
+        let list_schema = json!({ "$ref": format!("{}{}", SCHEMAS, list_name) });
+        schemas.insert(
+            list_name,
+            json!({
+                "description": format!("{}List is a list of {}.", kind, kind),
+                "type": "object",
+                "required": ["items"],
+                "properties": {
+                    "apiVersion": { "type": "string", "description": "APIVersion defines the versioned schema of this representation of an object." },
+                    "items": { "type": "array", "description": format!("List of {}.", name), "items": object },
+                    "kind": { "type": "string", "description": "Kind is a string value representing the REST resource this object represents." },
+                    "metadata": list_meta
+                },
+                "x-kubernetes-group-version-kind": [{ "group": group_version.group, "version": group_version.version, "kind": format!("{}List", kind) }]
+            }),
+        );
+
+        let namespace = path_parameter("namespace", "object name and auth scope, such as for teams and projects");
+        let collection_path = match namespaced {
+            true => format!("{}/namespaces/{{namespace}}/{}", group_version.path(), name),
+            false => format!("{}/{}", group_version.path(), name),
+        };
+        let mut collection = Map::new();
+        if verbs.contains(&"list") {
+            let mut list = operation(format!("list{}{}{}", prefix, scope, kind), format!("list or watch objects of kind {}", kind), "list", &gvk, &list_schema, "200");
+            list["parameters"] = LIST_PARAMETERS
+                .iter()
+                .map(|(name, kind, description)| json!({ "name": name, "in": "query", "description": description, "schema": { "type": kind } }))
+                .collect();
+            collection.insert("get".into(), list);
+        }
+        if verbs.contains(&"create") {
+            let mut create = operation(format!("create{}{}{}", prefix, scope, kind), format!("create a {}", kind), "post", &gvk, &object, "201");
+            create["requestBody"] = json!({ "required": true, "content": json_content(&object) });
+            collection.insert("post".into(), create);
+        }
+        if verbs.contains(&"deletecollection") {
+            let id = format!("delete{}Collection{}{}", prefix, scope, kind);
+            collection.insert("delete".into(), operation(id, format!("delete collection of {}", kind), "deletecollection", &gvk, &status, "200"));
+        }
+        if !collection.is_empty() {
+            if namespaced {
+                collection.insert("parameters".into(), json!([namespace]));
+            }
+            paths.insert(collection_path.clone(), Value::Object(collection));
+        }
+
+        // The object's own path, and its subresources that take objects
+        let mut object_paths = vec![(String::new(), String::new(), object.clone(), verbs.clone())];
+        let subresource_prefix = format!("{}/", name);
+        for subresource in &resources {
+            let Some(sub) = subresource["name"].as_str().and_then(|n| n.strip_prefix(&subresource_prefix)) else {
+                continue;
+            };
+            let schema = match subresource["kind"].as_str() {
+                Some(k) if k == kind => object.clone(),
+                Some("Scale") => reference(&mut generator, schema_of::<k8s_openapi::api::autoscaling::v1::Scale>).1,
+                _ => continue,
+            };
+            let verbs = subresource["verbs"].as_array().into_iter().flatten().filter_map(Value::as_str).collect();
+            object_paths.push((format!("/{}", sub), capitalize(sub), schema, verbs));
+        }
+        for (path, suffix, schema, verbs) in object_paths {
+            let what = if suffix.is_empty() { format!("the specified {}", kind) } else { format!("{} of the specified {}", suffix.to_lowercase(), kind) };
+            let mut item = Map::new();
+            if verbs.contains(&"get") {
+                item.insert("get".into(), operation(format!("read{}{}{}{}", prefix, scope, kind, suffix), format!("read {}", what), "get", &gvk, &schema, "200"));
+            }
+            if verbs.contains(&"update") {
+                let mut replace = operation(format!("replace{}{}{}{}", prefix, scope, kind, suffix), format!("replace {}", what), "put", &gvk, &schema, "200");
+                replace["requestBody"] = json!({ "required": true, "content": json_content(&schema) });
+                item.insert("put".into(), replace);
+            }
+            if verbs.contains(&"patch") {
+                let mut patch_op = operation(format!("patch{}{}{}{}", prefix, scope, kind, suffix), format!("partially update {}", what), "patch", &gvk, &schema, "200");
+                let content: Map<String, Value> = PATCH_TYPES.iter().map(|t| (t.to_string(), json!({ "schema": patch }))).collect();
+                patch_op["requestBody"] = json!({ "required": true, "content": content });
+                item.insert("patch".into(), patch_op);
+            }
+            if verbs.contains(&"delete") && suffix.is_empty() {
+                item.insert("delete".into(), operation(format!("delete{}{}{}", prefix, scope, kind), format!("delete a {}", kind), "delete", &gvk, &status, "200"));
+            }
+            if item.is_empty() {
+                continue;
+            }
+            let mut parameters = vec![path_parameter("name", &format!("name of the {}", kind))];
+            if namespaced {
+                parameters.push(namespace.clone());
+            }
+            item.insert("parameters".into(), Value::Array(parameters));
+            paths.insert(format!("{}/{{name}}{}", collection_path, path), Value::Object(item));
+        }
+    }
+
+    // The generated schemas, with the settings' adjustments for OpenAPI 3.0
+    // and the kind of each resource's
+    let mut definitions = generator.take_definitions();
+    for visitor in generator.visitors_mut() {
+        for schema in definitions.values_mut() {
+            visitor.visit_schema(schema);
+        }
+    }
+    for (name, schema) in definitions {
+        schemas.insert(name, serde_json::to_value(schema).unwrap_or_default());
+    }
+    for (name, kind) in kinds {
+        if let Some(schema) = schemas.get_mut(&name) {
+            schema["x-kubernetes-group-version-kind"] =
+                json!([{ "group": group_version.group, "version": group_version.version, "kind": kind }]);
+        }
+    }
+
+    json!({
+        "openapi": "3.0.0",
+        "info": { "title": "Kubernetes", "version": KUBERNETES_VERSION },
+        "paths": paths,
+        "components": { "schemas": schemas }
+    })
+}
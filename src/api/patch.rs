@@ -0,0 +1,107 @@
+// Applies the body of a PATCH request to the object it's sent to, the way
+// its Content-Type says: a JSON patch (RFC 6902) is a list of operations,
+// anything else is merged into the object. Strategic merge patches merge as
+// merge patches (RFC 7386) do, lists included, apart from the `$patch`
+// directive: `"$patch": "replace"` in a map replaces the live map with the
+// rest of it and `"$patch": "delete"` removes the map. That is enough for
+// what kubectl sends for `rollout undo` and `rollout restart`.
+use axum::http::{header, HeaderMap, StatusCode};
+use serde_json::{Map, Value};
+
+use super::error_status::ApiError;
+use super::last_applied;
+
+const DIRECTIVE: &str = "$patch";
+
+/// Patches `object` with `patch`, a request body sent with `headers`.
+/// Malformed patches are a 400 and JSON patches that don't apply a 422.
+pub fn apply(headers: &HeaderMap, object: &mut Value, patch: Value) -> Result<(), ApiError> {
+    let content_type = headers.get(header::CONTENT_TYPE).and_then(|v| v.to_str().ok()).unwrap_or_default();
+    if is_json_patch(headers) {
+        let operations: json_patch::Patch = serde_json::from_value(patch).map_err(|e| ApiError::bad_request(format!("invalid JSON patch: {}", e)))?;
+        return json_patch::patch(object, &operations)
+            .map_err(|e| ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "Invalid", format!("the JSON patch could not be applied: {}", e)));
+    }
+
+    let patch = last_applied::with_removals(patch, object);
+    if content_type.starts_with("application/strategic-merge-patch+json") {
+        strategic_merge(object, &patch);
+    } else {
+        json_patch::merge(object, &patch);
+    }
+    Ok(())
+}
+
+/// Whether the body sent with `headers` is a JSON patch (RFC 6902).
+pub fn is_json_patch(headers: &HeaderMap) -> bool {
+    headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|content_type| content_type.starts_with("application/json-patch+json"))
+}
+
+/// The merge patch (RFC 7386) that turns `from` into `to`: changed fields
+/// with their new values, removed ones as null.
+pub fn merge_diff(from: &Value, to: &Value) -> Value {
+    let (Some(from), Some(to)) = (from.as_object(), to.as_object()) else {
+        return to.clone();
+    };
+    let mut patch = Map::new();
+    for key in from.keys().filter(|key| !to.contains_key(*key)) {
+        patch.insert(key.clone(), Value::Null);
+    }
+    for (key, value) in to {
+        match from.get(key) {
+            Some(old) if old == value => {}
+            Some(old) if old.is_object() && value.is_object() => {
+                patch.insert(key.clone(), merge_diff(old, value));
+            }
+            _ => {
+                patch.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    Value::Object(patch)
+}
+
+fn strategic_merge(object: &mut Value, patch: &Value) {
+    let Some(fields) = patch.as_object() else {
+        *object = without_directives(patch);
+        return;
+    };
+    if fields.get(DIRECTIVE).and_then(Value::as_str) == Some("replace") {
+        *object = without_directives(patch);
+        return;
+    }
+    if !object.is_object() {
+        *object = Value::Object(Map::new());
+    }
+    let Some(live) = object.as_object_mut() else {
+        return;
+    };
+    for (key, value) in fields {
+        if key.starts_with('$') {
+            continue;
+        }
+        if value.is_null() || value[DIRECTIVE] == "delete" {
+            live.remove(key);
+        } else {
+            strategic_merge(live.entry(key.clone()).or_insert(Value::Null), value);
+        }
+    }
+}
+
+// A patch value as the object should hold it: without directives
+fn without_directives(value: &Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .filter(|(key, _)| !key.starts_with('$'))
+                .map(|(key, value)| (key.clone(), without_directives(value)))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(without_directives).collect()),
+        value => value.clone(),
+    }
+}
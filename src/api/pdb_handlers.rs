@@ -1,30 +1,26 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    response::Json,
+    response::{Json, Response},
 };
-use serde::Deserialize;
 use serde_json::Value;
 
+use super::error_status::ApiError;
+use super::handlers::{list_error, ListParams};
 use super::server::AppState;
-
-#[derive(Deserialize)]
-pub struct ListParams {
-    #[serde(rename = "labelSelector")]
-    label_selector: Option<String>,
-    limit: Option<i32>,
-}
+use super::watch::list_or_watch;
 
 // List all PodDisruptionBudgets across namespaces
 pub async fn list_all_pdbs(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.pdbs().list(None).await {
-        Ok(pdbs) => Ok(Json(pdbs)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "poddisruptionbudgets", None, &params, &selector, state.storage.pdbs().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list all pod disruption budgets: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -33,13 +29,14 @@ pub async fn list_all_pdbs(
 pub async fn list_pdbs(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.pdbs().list(Some(&namespace)).await {
-        Ok(pdbs) => Ok(Json(pdbs)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "poddisruptionbudgets", Some(&namespace), &params, &selector, state.storage.pdbs().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list pod disruption budgets: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -48,12 +45,14 @@ pub async fn create_pdb(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(pdb): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = pdb["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.pdbs().create(&namespace, pdb).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("poddisruptionbudgets", &name)),
         Err(e) => {
             tracing::error!("Failed to create pod disruption budget: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -61,13 +60,13 @@ pub async fn create_pdb(
 pub async fn get_pdb(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.pdbs().get(&namespace, &name).await {
         Ok(Some(pdb)) => Ok(Json(pdb)),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Ok(None) => Err(ApiError::not_found("poddisruptionbudgets", &name)),
         Err(e) => {
             tracing::error!("Failed to get pod disruption budget: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -76,12 +75,12 @@ pub async fn update_pdb(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(pdb): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.pdbs().update(&namespace, &name, pdb).await {
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             tracing::error!("Failed to update pod disruption budget: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -90,14 +89,14 @@ pub async fn patch_pdb(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(patch): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // Get current PDB
     let current = match state.storage.pdbs().get(&namespace, &name).await {
         Ok(Some(pdb)) => pdb,
-        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Ok(None) => return Err(ApiError::not_found("poddisruptionbudgets", &name)),
         Err(e) => {
             tracing::error!("Failed to get pod disruption budget for patch: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err(ApiError::internal(&e));
         }
     };
 
@@ -116,7 +115,7 @@ pub async fn patch_pdb(
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             tracing::error!("Failed to patch pod disruption budget: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -124,12 +123,12 @@ pub async fn patch_pdb(
 pub async fn delete_pdb(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.pdbs().delete(&namespace, &name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             tracing::error!("Failed to delete pod disruption budget: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -138,13 +137,13 @@ pub async fn delete_pdb(
 pub async fn get_pdb_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.pdbs().get(&namespace, &name).await {
         Ok(Some(pdb)) => Ok(Json(pdb)),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Ok(None) => Err(ApiError::not_found("poddisruptionbudgets", &name)),
         Err(e) => {
             tracing::error!("Failed to get pod disruption budget status: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -154,13 +153,13 @@ pub async fn update_pdb_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(status_update): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     let status = status_update["status"].clone();
     match state.storage.pdbs().update_status(&namespace, &name, status).await {
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             tracing::error!("Failed to update pod disruption budget status: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
\ No newline at end of file
@@ -11,6 +11,7 @@ use bollard::exec::{CreateExecOptions, StartExecResults};
 use futures::StreamExt;
 use tracing::{error, info};
 
+use super::error_status::ApiError;
 use super::server::AppState;
 
 // Direct proxy endpoint - access pods via /proxy/pods/{namespace}/{name}/{port}/...
@@ -19,32 +20,23 @@ pub async fn proxy_to_pod(
     Path((namespace, name, port, path)): Path<(String, String, u16, String)>,
     headers: HeaderMap,
     body: String,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, ApiError> {
     // Verify pod exists and is running
     let pod = state
         .storage
         .pods()
         .get(&namespace, &name)
         .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
+        .map_err(|_| ApiError::not_found("pods", &name))?;
 
-    let status = pod
-        .get("status")
-        .and_then(|s| s.as_object())
-        .ok_or(StatusCode::CONFLICT)?;
-    
-    let phase = status
-        .get("phase")
-        .and_then(|p| p.as_str())
-        .ok_or(StatusCode::CONFLICT)?;
-    
+    let phase = pod["status"]["phase"].as_str().unwrap_or("Pending");
     if phase != "Running" {
-        return Err(StatusCode::CONFLICT);
+        return Err(ApiError::conflict("pods", &name, format!("pod is not running, its phase is {}", phase)));
     }
 
     // Get container ID
     let container_id = get_container_id(&namespace, &name).await
-        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)?;
+        .ok_or_else(|| ApiError::internal(format!("no container found for pod {}", name)))?;
 
     // Build the curl command to execute inside the container
     let url = if path.is_empty() {
@@ -59,7 +51,7 @@ pub async fn proxy_to_pod(
     let response = exec_curl_in_container(&container_id, &url).await
         .map_err(|e| {
             error!("Failed to proxy request: {}", e);
-            StatusCode::BAD_GATEWAY
+            ApiError::new(StatusCode::BAD_GATEWAY, "ServiceUnavailable", format!("error trying to reach pod: {}", e))
         })?;
 
     Ok(Response::builder()
@@ -75,7 +67,7 @@ pub async fn proxy_to_pod_root(
     Path((namespace, name, port)): Path<(String, String, u16)>,
     headers: HeaderMap,
     body: String,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, ApiError> {
     proxy_to_pod(state, Path((namespace, name, port, String::new())), headers, body).await
 }
 
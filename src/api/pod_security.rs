@@ -0,0 +1,210 @@
+// Pod Security admission: pods created or updated in a namespace are held to
+// the levels its pod-security.kubernetes.io labels set (see
+// models::pod_security). Workloads carrying pod templates are only warned
+// about and audited, their pods being refused when a controller makes them,
+// and changing a namespace's enforce level warns about the pods already
+// there that the new level would refuse. There's no audit log, so audit
+// violations go to the server log.
+use axum::{
+    body::Body,
+    extract::{Request, State},
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use tracing::{error, info};
+
+use super::admission::labels_of_namespace;
+use super::error_status::ApiError;
+use super::request_info::RequestInfo;
+use super::server::AppState;
+use crate::models::pod_security::{self, Level, LevelVersion, Policy};
+
+/// Middleware refusing pods that violate their namespace's enforce level and
+/// warning about pods and pod templates that violate its warn level.
+pub async fn check_pod_security(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if !matches!(*request.method(), Method::POST | Method::PUT | Method::PATCH) {
+        return next.run(request).await;
+    }
+    let info = RequestInfo::parse(request.method().as_str(), request.uri().path(), request.uri().query());
+    let RequestInfo::Resource { resource, subresource: None, namespace: Some(namespace), name, .. } = info else {
+        return next.run(request).await;
+    };
+    if resource == "namespaces" {
+        return match name {
+            Some(_) if *request.method() != Method::POST => warn_about_existing_pods(state, namespace, request, next).await,
+            _ => next.run(request).await,
+        };
+    }
+    if *request.method() == Method::PATCH || (resource != "pods" && template_of(&resource, &Value::Null).is_none()) {
+        return next.run(request).await;
+    }
+
+    let policy = match labels_of_namespace(&state, &namespace).await {
+        Ok(labels) => Policy::from_labels(&labels),
+        Err(e) => {
+            error!("Failed to read labels of namespace {}: {}", namespace, e);
+            return ApiError::internal(&e).into_response();
+        }
+    };
+    if [&policy.enforce, &policy.audit, &policy.warn].iter().all(|mode| mode.level == Level::Privileged) {
+        return next.run(request).await;
+    }
+
+    let (parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to buffer request body: {}", e);
+            return ApiError::bad_request(e.to_string()).into_response();
+        }
+    };
+    let object = serde_json::from_slice::<Value>(&bytes).unwrap_or_default();
+    let request = Request::from_parts(parts, Body::from(bytes));
+    let (metadata, spec) = if resource == "pods" {
+        (&object["metadata"], &object["spec"])
+    } else {
+        match template_of(&resource, &object) {
+            Some(template) => (&template["metadata"], &template["spec"]),
+            None => return next.run(request).await,
+        }
+    };
+    if !spec.is_object() {
+        return next.run(request).await;
+    }
+    let object_name = object["metadata"]["name"].as_str().or(name.as_deref()).unwrap_or_default();
+
+    // Updates that leave the pod spec as it was, say to its labels, aren't
+    // held to the policy again
+    if resource == "pods" && *request.method() == Method::PUT {
+        if let Ok(stored) = state.storage.pods().get(&namespace, object_name).await {
+            if stored["spec"] == *spec {
+                return next.run(request).await;
+            }
+        }
+    }
+
+    if resource == "pods" {
+        if let Some(message) = pod_security::refusal(&policy, &object) {
+            return forbidden(object_name, &message);
+        }
+    }
+    let violations = |mode: &LevelVersion| pod_security::check(mode.level, metadata, spec);
+    let audited = violations(&policy.audit);
+    if !audited.is_empty() {
+        info!(
+            "PodSecurity audit: {} {}/{} would violate PodSecurity {:?}: {}",
+            resource,
+            namespace,
+            object_name,
+            policy.audit.to_string(),
+            pod_security::describe(&audited)
+        );
+    }
+    let warned = violations(&policy.warn);
+
+    let mut response = next.run(request).await;
+    if !warned.is_empty() {
+        let warning = format!(
+            "would violate PodSecurity {:?}: {}",
+            policy.warn.to_string(),
+            pod_security::describe(&warned)
+        );
+        add_warning(&mut response, &warning);
+    }
+    response
+}
+
+// The pod template of a workload resource, if it's one that has them
+fn template_of<'a>(resource: &str, object: &'a Value) -> Option<&'a Value> {
+    match resource {
+        "deployments" | "replicasets" | "statefulsets" | "daemonsets" | "jobs" | "replicationcontrollers" => {
+            Some(&object["spec"]["template"])
+        }
+        "cronjobs" => Some(&object["spec"]["jobTemplate"]["spec"]["template"]),
+        "podtemplates" => Some(&object["template"]),
+        _ => None,
+    }
+}
+
+// Passes a namespace update on and, when it sets a new enforce level, warns
+// about the pods in the namespace that the level would refuse. Pods with the
+// same violations are grouped, as kube-apiserver does.
+async fn warn_about_existing_pods(state: AppState, namespace: String, request: Request, next: Next) -> Response {
+    let before = match labels_of_namespace(&state, &namespace).await {
+        Ok(labels) => Policy::from_labels(&labels),
+        Err(e) => {
+            error!("Failed to read labels of namespace {}: {}", namespace, e);
+            return ApiError::internal(&e).into_response();
+        }
+    };
+    let response = next.run(request).await;
+    if !response.status().is_success() {
+        return response;
+    }
+    let (parts, body) = response.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to buffer namespace response: {}", e);
+            return ApiError::internal(&e).into_response();
+        }
+    };
+    let updated = serde_json::from_slice::<Value>(&bytes).unwrap_or_default();
+    let after = Policy::from_labels(&updated["metadata"]["labels"]);
+    let mut response = Response::from_parts(parts, Body::from(bytes));
+    if after.enforce == before.enforce || after.enforce.level == Level::Privileged {
+        return response;
+    }
+
+    let pods = match state.storage.pods().list(Some(&namespace)).await {
+        Ok(pods) => pods,
+        Err(e) => {
+            error!("Failed to list pods in namespace {}: {}", namespace, e);
+            return response;
+        }
+    };
+    let mut refused: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    for pod in pods["items"].as_array().into_iter().flatten() {
+        let violations = pod_security::check(after.enforce.level, &pod["metadata"], &pod["spec"]);
+        if !violations.is_empty() {
+            let reasons = violations.iter().map(|v| v.reason.as_str()).collect::<Vec<_>>().join(", ");
+            let name = pod["metadata"]["name"].as_str().unwrap_or_default().to_string();
+            refused.entry(reasons).or_default().push(name);
+        }
+    }
+    if refused.is_empty() {
+        return response;
+    }
+    add_warning(
+        &mut response,
+        &format!(
+            "existing pods in namespace {:?} violate the new PodSecurity enforce level {:?}",
+            namespace,
+            after.enforce.to_string()
+        ),
+    );
+    for (reasons, pods) in refused {
+        let others = match pods.len() {
+            1 => String::new(),
+            2 => " (and 1 other pod)".to_string(),
+            n => format!(" (and {} other pods)", n - 1),
+        };
+        add_warning(&mut response, &format!("{}{}: {}", pods[0], others, reasons));
+    }
+    response
+}
+
+fn add_warning(response: &mut Response, warning: &str) {
+    if let Ok(value) = HeaderValue::from_str(&format!("299 - {:?}", warning)) {
+        response.headers_mut().append(header::WARNING, value);
+    }
+}
+
+fn forbidden(name: &str, message: &str) -> Response {
+    ApiError::new(StatusCode::FORBIDDEN, "Forbidden", message)
+        .with_details(json!({ "name": name, "kind": "pods" }))
+        .into_response()
+}
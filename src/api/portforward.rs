@@ -1,201 +1,419 @@
-#![allow(unused_imports)]
+// Port-forwarding, as kubelets serve it: the client opens a SPDY/3.1
+// connection, either by upgrading its request or tunneled through a
+// WebSocket, and for every local connection it opens a pair of streams
+// sharing a `requestid` header and naming the pod's `port`. The data stream
+// carries the connection's bytes each way; the error stream carries back a
+// message if forwarding fails. Each pair is bridged to its own connection to
+// the pod's port.
+use anyhow::{anyhow, Result};
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    body::Body,
+    extract::{ws::Message, FromRequestParts, Request, WebSocketUpgrade},
+    http::{header, StatusCode},
     response::{IntoResponse, Response},
-    Json,
 };
-use serde::{Deserialize, Serialize};
-use serde_json::json;
-use std::sync::Arc;
-use tokio::sync::RwLock;
-use tracing::{error, info};
-
-use super::portforward_proxy::PortForwardProxy;
-use super::server::AppState;
-
-// Global port forward proxy instance
-lazy_static::lazy_static! {
-    static ref PORT_FORWARD_PROXY: Arc<RwLock<PortForwardProxy>> = Arc::new(RwLock::new(PortForwardProxy::new()));
+use bollard::exec::{CreateExecOptions, StartExecResults};
+use bollard::container::LogOutput;
+use bollard::Docker;
+use futures::stream::{self, BoxStream};
+use futures::{Sink, SinkExt, StreamExt};
+use hyper_util::rt::TokioIo;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::task::{AbortHandle, JoinSet};
+use tracing::{debug, warn};
+
+use super::error_status::ApiError;
+use super::spdy::{self, Frame, Headers};
+use crate::runtime::kubelet::docker_container_name;
+
+/// The WebSocket subprotocol of SPDY tunneled through a WebSocket.
+pub const TUNNELING_PROTOCOL: &str = "SPDY/3.1+portforward.k8s.io";
+/// The stream protocol both transports carry.
+pub const PROTOCOL: &str = "portforward.k8s.io";
+
+// How long connecting to a container's address may take before forwarding
+// falls back to a helper inside the container, e.g. where containers'
+// addresses can't be reached from the host, as on macOS
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Frames queued toward the client, and bytes queued toward a pod's port
+const QUEUE: usize = 64;
+
+const READ_SIZE: usize = 32 * 1024;
+
+// The helper exec'd in the container: socat where there is one, netcat
+// otherwise, both taking the port as their first argument
+const HELPER: &str = r#"if command -v socat >/dev/null 2>&1; then exec socat - TCP:127.0.0.1:"$1"; fi; exec nc 127.0.0.1 "$1""#;
+
+type Incoming = BoxStream<'static, Vec<u8>>;
+type Outgoing = Pin<Box<dyn Sink<Vec<u8>, Error = anyhow::Error> + Send>>;
+
+/// Upgrades the request to a port-forwarding connection to `pod`, which
+/// must be running.
+pub async fn upgrade(request: Request, pod: Value) -> Response {
+    let (mut parts, _) = request.into_parts();
+    let pod = Arc::new(pod);
+
+    if let Ok(ws) = WebSocketUpgrade::from_request_parts(&mut parts, &()).await {
+        return ws.protocols([TUNNELING_PROTOCOL]).on_upgrade(move |socket| {
+            let (sink, stream) = socket.split();
+            let incoming = stream
+                .take_while(|message| futures::future::ready(!matches!(message, Err(_) | Ok(Message::Close(_)))))
+                .filter_map(|message| async move {
+                    match message {
+                        Ok(Message::Binary(bytes)) => Some(bytes),
+                        _ => None,
+                    }
+                })
+                .boxed();
+            let outgoing = sink.sink_map_err(anyhow::Error::from).with(|bytes| async { Ok(Message::Binary(bytes)) });
+            run_session(incoming, Box::pin(outgoing), pod)
+        });
+    }
+
+    let spdy = parts
+        .headers
+        .get(header::UPGRADE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.eq_ignore_ascii_case("SPDY/3.1"));
+    let Some(on_upgrade) = parts.extensions.remove::<hyper::upgrade::OnUpgrade>().filter(|_| spdy) else {
+        return ApiError::bad_request("port-forward needs a SPDY/3.1 or WebSocket upgrade").into_response();
+    };
+
+    tokio::spawn(async move {
+        match on_upgrade.await {
+            Ok(upgraded) => {
+                let (reader, writer) = tokio::io::split(TokioIo::new(upgraded));
+                run_session(read_stream(reader).filter_map(|r| async { r.ok() }).boxed(), write_sink(writer), pod).await
+            }
+            Err(e) => warn!("Port-forward upgrade failed: {}", e),
+        }
+    });
+    Response::builder()
+        .status(StatusCode::SWITCHING_PROTOCOLS)
+        .header(header::CONNECTION, "Upgrade")
+        .header(header::UPGRADE, "SPDY/3.1")
+        .header("X-Stream-Protocol-Version", PROTOCOL)
+        .body(Body::empty())
+        .unwrap()
 }
 
-#[derive(Debug, Deserialize)]
-pub struct PortForwardRequest {
-    ports: Vec<PortMapping>,
+fn read_stream(reader: impl AsyncRead + Send + Unpin + 'static) -> BoxStream<'static, std::io::Result<Vec<u8>>> {
+    stream::unfold(reader, |mut reader| async move {
+        let mut buffer = vec![0; READ_SIZE];
+        match reader.read(&mut buffer).await {
+            Ok(0) => None,
+            Ok(n) => {
+                buffer.truncate(n);
+                Some((Ok(buffer), reader))
+            }
+            Err(e) => Some((Err(e), reader)),
+        }
+    })
+    .boxed()
+}
+
+fn write_sink(writer: impl AsyncWrite + Send + Unpin + 'static) -> Outgoing {
+    Box::pin(futures::sink::unfold(writer, |mut writer, bytes: Vec<u8>| async move {
+        writer.write_all(&bytes).await?;
+        Ok::<_, anyhow::Error>(writer)
+    }))
 }
 
-#[derive(Debug, Deserialize, Serialize)]
-pub struct PortMapping {
-    local_port: u16,
-    remote_port: u16,
+// A pair of streams for one forwarded connection, as far as it's been opened
+#[derive(Default)]
+struct Pair {
+    port: Option<String>,
+    data: Option<(u32, mpsc::Receiver<Vec<u8>>)>,
+    error: Option<u32>,
 }
 
-// GET endpoint to check if port-forward is supported
-pub async fn pod_portforward_get(
-    State(state): State<AppState>,
-    Path((namespace, name)): Path<(String, String)>,
-) -> Result<StatusCode, StatusCode> {
-    // Check if pod exists and is running
-    match state.storage.pods().get(&namespace, &name).await {
-        Ok(pod) => {
-            // Check if the pod is running
-            if let Some(status) = pod.get("status").and_then(|s| s.as_object()) {
-                if let Some(phase) = status.get("phase").and_then(|p| p.as_str()) {
-                    if phase == "Running" {
-                        // Return OK to indicate port-forward is supported
-                        Ok(StatusCode::OK)
-                    } else {
-                        Err(StatusCode::CONFLICT) // Pod not running
+// Reads frames off the connection until it closes, opening streams and
+// starting a forward for every complete pair
+async fn run_session(mut incoming: Incoming, mut outgoing: Outgoing, pod: Arc<Value>) {
+    let (frames, mut queued) = mpsc::channel::<Frame>(QUEUE);
+    let writer = tokio::spawn(async move {
+        let mut encoder = spdy::Encoder::new();
+        while let Some(frame) = queued.recv().await {
+            if outgoing.send(encoder.encode(&frame)).await.is_err() {
+                break;
+            }
+        }
+        let _ = outgoing.close().await;
+    });
+
+    let mut decoder = spdy::Decoder::new();
+    let mut pairs: HashMap<String, Pair> = HashMap::new();
+    // Where the client's data goes, by data stream
+    let mut inputs: HashMap<u32, mpsc::Sender<Vec<u8>>> = HashMap::new();
+    // The forward running for each stream of a pair
+    let mut forwards: HashMap<u32, AbortHandle> = HashMap::new();
+    let mut tasks = JoinSet::new();
+
+    'session: while let Some(bytes) = incoming.next().await {
+        decoder.feed(&bytes);
+        loop {
+            let frame = match decoder.next_frame() {
+                Ok(Some(frame)) => frame,
+                Ok(None) => break,
+                Err(e) => {
+                    warn!("Closing port-forward connection: {}", e);
+                    break 'session;
+                }
+            };
+            match frame {
+                Frame::SynStream { stream_id, headers, .. } => {
+                    let reply = Frame::SynReply { stream_id, fin: false, headers: Vec::new() };
+                    if frames.send(reply).await.is_err() {
+                        break 'session;
+                    }
+                    let Some(request_id) = request_id(stream_id, &headers) else {
+                        let _ = frames.send(Frame::RstStream { stream_id, status: spdy::PROTOCOL_ERROR }).await;
+                        continue;
+                    };
+                    let pair = pairs.entry(request_id.clone()).or_default();
+                    pair.port = pair.port.take().or_else(|| spdy::header(&headers, "port").map(str::to_string));
+                    match spdy::header(&headers, "streamtype") {
+                        Some("data") => {
+                            let (input, receiver) = mpsc::channel(QUEUE);
+                            inputs.insert(stream_id, input);
+                            pair.data = Some((stream_id, receiver));
+                        }
+                        Some("error") => pair.error = Some(stream_id),
+                        _ => {
+                            let _ = frames.send(Frame::RstStream { stream_id, status: spdy::PROTOCOL_ERROR }).await;
+                            continue;
+                        }
+                    }
+
+                    if pair.data.is_none() || pair.error.is_none() {
+                        continue;
+                    }
+                    if let Some(Pair { port, data: Some((data, input)), error: Some(error) }) = pairs.remove(&request_id) {
+                        let forward = forward(frames.clone(), pod.clone(), port, data, error, input);
+                        let handle = tasks.spawn(forward);
+                        forwards.insert(data, handle.clone());
+                        forwards.insert(error, handle);
                     }
-                } else {
-                    Err(StatusCode::CONFLICT)
                 }
-            } else {
-                Err(StatusCode::CONFLICT)
+                Frame::Data { stream_id, fin, data } => {
+                    if let Some(input) = inputs.get(&stream_id) {
+                        if !data.is_empty() && input.send(data).await.is_err() {
+                            inputs.remove(&stream_id);
+                        }
+                    }
+                    // Closing the input tells the forward the client is done
+                    // writing
+                    if fin {
+                        inputs.remove(&stream_id);
+                    }
+                }
+                Frame::RstStream { stream_id, .. } => {
+                    inputs.remove(&stream_id);
+                    if let Some(forward) = forwards.remove(&stream_id) {
+                        forward.abort();
+                    }
+                }
+                Frame::Ping { id } => {
+                    let _ = frames.send(Frame::Ping { id }).await;
+                }
+                Frame::GoAway { .. } => break 'session,
+                _ => {}
             }
         }
-        Err(_) => Err(StatusCode::NOT_FOUND),
+        // Forget the forwards that have finished
+        while tasks.try_join_next().is_some() {}
+        forwards.retain(|_, forward| !forward.is_finished());
     }
+
+    tasks.shutdown().await;
+    drop(frames);
+    let _ = writer.await;
 }
 
-// POST endpoint to establish port forwarding
-pub async fn pod_portforward_post(
-    State(state): State<AppState>,
-    Path((namespace, name)): Path<(String, String)>,
-    body: String,
-) -> Result<Response, StatusCode> {
-    // Verify pod exists and is running
-    let pod = state
-        .storage
-        .pods()
-        .get(&namespace, &name)
-        .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
-
-    let status = pod
-        .get("status")
-        .and_then(|s| s.as_object())
-        .ok_or(StatusCode::CONFLICT)?;
-    
-    let phase = status
-        .get("phase")
-        .and_then(|p| p.as_str())
-        .ok_or(StatusCode::CONFLICT)?;
-    
-    if phase != "Running" {
-        return Err(StatusCode::CONFLICT);
+// The id pairing a stream with its partner. Clients that don't send one
+// open the error stream, then the data stream, so the pair is known by the
+// error stream's id.
+fn request_id(stream_id: u32, headers: &Headers) -> Option<String> {
+    if let Some(id) = spdy::header(headers, "requestid") {
+        return Some(id.to_string());
     }
-
-    // For kubectl compatibility, we need to parse the port from the request
-    // kubectl sends port information in the request path or headers
-    // For now, we'll start a simple proxy on the default ports
-    
-    let local_port = 9090; // Default local port
-    let remote_port = 8080; // Default remote port (from our test pods)
-    
-    // Start the port forward proxy
-    let proxy = PORT_FORWARD_PROXY.read().await;
-    match proxy.start_forward(&namespace, &name, local_port, remote_port).await {
-        Ok(_) => {
-            info!("Started port forward for pod {}/{} on port {}:{}", 
-                namespace, name, local_port, remote_port);
-            
-            // Return success response
-            let response = json!({
-                "success": true,
-                "message": format!("Port forwarding established: localhost:{} -> pod:{}",
-                    local_port, remote_port),
-                "local_port": local_port,
-                "remote_port": remote_port
-            });
-            
-            Ok(Response::builder()
-                .status(StatusCode::OK)
-                .header("Content-Type", "application/json")
-                .body(serde_json::to_string(&response).unwrap().into())
-                .unwrap())
-        }
-        Err(e) => {
-            error!("Failed to start port forward: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
-        }
+    match spdy::header(headers, "streamtype")? {
+        "error" => Some(stream_id.to_string()),
+        "data" => stream_id.checked_sub(2).map(|id| id.to_string()),
+        _ => None,
     }
 }
 
-// Additional endpoint for kubectl-style port forwarding
-pub async fn kubectl_portforward(
-    State(state): State<AppState>,
-    Path((namespace, name)): Path<(String, String)>,
-) -> Result<Response, StatusCode> {
-    // This endpoint handles the simplified kubectl port-forward protocol
-    // It starts a TCP proxy and returns immediately
-    
-    // Verify pod exists and is running
-    let pod = state
-        .storage
-        .pods()
-        .get(&namespace, &name)
-        .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
-
-    let status = pod
-        .get("status")
-        .and_then(|s| s.as_object())
-        .ok_or(StatusCode::CONFLICT)?;
-    
-    let phase = status
-        .get("phase")
-        .and_then(|p| p.as_str())
-        .ok_or(StatusCode::CONFLICT)?;
-    
-    if phase != "Running" {
-        return Err(StatusCode::CONFLICT);
+// Bridges a pair of streams to a connection to the pod's port, then closes
+// both streams, reporting on the error stream why forwarding failed if it did
+async fn forward(
+    frames: mpsc::Sender<Frame>,
+    pod: Arc<Value>,
+    port: Option<String>,
+    data: u32,
+    error: u32,
+    mut input: mpsc::Receiver<Vec<u8>>,
+) {
+    let result = match port.as_deref().map(str::parse::<u16>) {
+        Some(Ok(port)) if port > 0 => match connect(&pod, port).await {
+            Ok(backend) => bridge(&frames, data, &mut input, backend).await,
+            Err(e) => Err(e),
+        },
+        Some(_) => Err(anyhow!("invalid port {:?}", port.as_deref().unwrap_or_default())),
+        None => Err(anyhow!("no port given")),
+    };
+    if let Err(e) = result {
+        let port = port.unwrap_or_default();
+        let message = format!(
+            "error forwarding port {} to pod {}, uid {}: {:#}",
+            port,
+            pod["metadata"]["name"].as_str().unwrap_or_default(),
+            pod["metadata"]["uid"].as_str().unwrap_or_default(),
+            e
+        );
+        debug!("{}", message);
+        let _ = frames.send(Frame::Data { stream_id: error, fin: false, data: message.into_bytes() }).await;
     }
+    let _ = frames.send(Frame::Data { stream_id: data, fin: true, data: Vec::new() }).await;
+    let _ = frames.send(Frame::Data { stream_id: error, fin: true, data: Vec::new() }).await;
+}
 
-    // Parse port mappings from the pod spec
-    let mut port_mappings = Vec::new();
-    if let Some(spec) = pod.get("spec").and_then(|s| s.as_object()) {
-        if let Some(containers) = spec.get("containers").and_then(|c| c.as_array()) {
-            for container in containers {
-                if let Some(ports) = container.get("ports").and_then(|p| p.as_array()) {
-                    for port in ports {
-                        if let Some(container_port) = port.get("containerPort").and_then(|p| p.as_u64()) {
-                            port_mappings.push(PortMapping {
-                                local_port: container_port as u16,
-                                remote_port: container_port as u16,
-                            });
-                        }
+// A connection to a pod's port: what it sends, where to write to it, and for
+// a helper, what it said went wrong
+struct Backend {
+    output: BoxStream<'static, std::io::Result<Vec<u8>>>,
+    input: Pin<Box<dyn AsyncWrite + Send>>,
+    errors: Option<Arc<Mutex<String>>>,
+}
+
+// Copies the client's bytes to the backend and the backend's to the client
+// until the backend closes, passing the client closing its side on to it
+async fn bridge(frames: &mpsc::Sender<Frame>, data: u32, input: &mut mpsc::Receiver<Vec<u8>>, mut backend: Backend) -> Result<()> {
+    let mut writing = true;
+    loop {
+        tokio::select! {
+            bytes = input.recv(), if writing => match bytes {
+                Some(bytes) => backend.input.write_all(&bytes).await?,
+                None => {
+                    writing = false;
+                    let _ = backend.input.shutdown().await;
+                }
+            },
+            bytes = backend.output.next() => match bytes {
+                Some(bytes) => {
+                    let frame = Frame::Data { stream_id: data, fin: false, data: bytes? };
+                    if frames.send(frame).await.is_err() {
+                        return Ok(());
                     }
                 }
-            }
+                None => break,
+            },
         }
     }
 
-    if port_mappings.is_empty() {
-        // Default port mapping if none found
-        port_mappings.push(PortMapping {
-            local_port: 9090,
-            remote_port: 8080,
-        });
+    match backend.errors.map(|errors| errors.lock().unwrap().trim().to_string()) {
+        Some(errors) if !errors.is_empty() => Err(anyhow!(errors)),
+        _ => Ok(()),
     }
+}
+
+// Connects to the port of the pod. hostNetwork pods listen on the node, and
+// others in their container, which is reached at its address if the host
+// can route to it or through a helper exec'd inside it otherwise.
+async fn connect(pod: &Value, port: u16) -> Result<Backend> {
+    if pod["spec"]["hostNetwork"].as_bool().unwrap_or(false) {
+        return Ok(tcp(TcpStream::connect(("127.0.0.1", port)).await?));
+    }
+
+    let docker = Docker::connect_with_local_defaults()?;
+    let container = container_for(pod, port).ok_or_else(|| anyhow!("pod has no containers"))?;
+    let inspect = docker.inspect_container(&container, None).await?;
+    let address = inspect.network_settings.and_then(|settings| {
+        let address = settings.ip_address.filter(|ip| !ip.is_empty());
+        address.or_else(|| {
+            settings.networks?.into_values().find_map(|network| network.ip_address.filter(|ip| !ip.is_empty()))
+        })
+    });
 
-    // Start port forwarding for each mapping
-    let proxy = PORT_FORWARD_PROXY.read().await;
-    for mapping in &port_mappings {
-        if let Err(e) = proxy.start_forward(&namespace, &name, mapping.local_port, mapping.remote_port).await {
-            error!("Failed to start port forward for {}:{}: {}", 
-                mapping.local_port, mapping.remote_port, e);
+    if let Some(address) = address {
+        match tokio::time::timeout(CONNECT_TIMEOUT, TcpStream::connect((address.as_str(), port))).await {
+            Ok(Ok(stream)) => return Ok(tcp(stream)),
+            Ok(Err(e)) if e.kind() == std::io::ErrorKind::ConnectionRefused => return Err(e.into()),
+            Ok(Err(e)) => debug!("Can't reach {}:{} ({}), forwarding through the container", address, port, e),
+            Err(_) => debug!("Timed out reaching {}:{}, forwarding through the container", address, port),
         }
     }
+    exec_helper(&docker, &container, port).await
+}
+
+fn tcp(stream: TcpStream) -> Backend {
+    let (reader, writer) = stream.into_split();
+    Backend {
+        output: read_stream(reader),
+        input: Box::pin(writer),
+        errors: None,
+    }
+}
 
-    info!("Port forwarding established for pod {}/{}", namespace, name);
-    
-    Ok(Response::builder()
-        .status(StatusCode::OK)
-        .header("Content-Type", "application/json")
-        .body(serde_json::to_string(&json!({
-            "status": "Port forwarding active",
-            "mappings": port_mappings
-        })).unwrap().into())
-        .unwrap())
-}
\ No newline at end of file
+// The Docker container of the pod's container that declares the port, or
+// its first container if none does
+fn container_for(pod: &Value, port: u16) -> Option<String> {
+    let containers = pod["spec"]["containers"].as_array()?;
+    let container = containers
+        .iter()
+        .find(|c| {
+            c["ports"]
+                .as_array()
+                .is_some_and(|ports| ports.iter().any(|p| p["containerPort"].as_u64() == Some(port as u64)))
+        })
+        .or_else(|| containers.first())?;
+
+    Some(docker_container_name(
+        container["name"].as_str().unwrap_or("container"),
+        pod["metadata"]["name"].as_str().unwrap_or_default(),
+        pod["metadata"]["namespace"].as_str().unwrap_or_default(),
+        pod["metadata"]["uid"].as_str().unwrap_or_default(),
+    ))
+}
+
+async fn exec_helper(docker: &Docker, container: &str, port: u16) -> Result<Backend> {
+    let port = port.to_string();
+    let options = CreateExecOptions {
+        cmd: Some(vec!["sh", "-c", HELPER, "krust-portforward", &port]),
+        attach_stdin: Some(true),
+        attach_stdout: Some(true),
+        attach_stderr: Some(true),
+        ..Default::default()
+    };
+    let exec = docker.create_exec(container, options).await?;
+    let StartExecResults::Attached { output, input } = docker.start_exec(&exec.id, None).await? else {
+        return Err(anyhow!("exec started detached"));
+    };
+
+    let errors = Arc::new(Mutex::new(String::new()));
+    let stderr = errors.clone();
+    let output = output
+        .filter_map(move |chunk| {
+            let stderr = stderr.clone();
+            async move {
+                match chunk {
+                    Ok(LogOutput::StdErr { message }) => {
+                        stderr.lock().unwrap().push_str(&String::from_utf8_lossy(&message));
+                        None
+                    }
+                    Ok(chunk) => Some(Ok(chunk.into_bytes().to_vec())),
+                    Err(e) => Some(Err(std::io::Error::other(e))),
+                }
+            }
+        })
+        .boxed();
+    Ok(Backend { output, input, errors: Some(errors) })
+}
@@ -0,0 +1,139 @@
+// Profiling endpoints under /debug/pprof, served when the API server runs
+// with profiling on (`--profiling`, or `apiServer.profiling` in the config
+// file) and 404 otherwise, as kube-apiserver's are. They're named after Go's
+// pprof but answer in JSON: a snapshot of the async runtime and of krust's
+// background loops, memory use, and a CPU profile that samples for a while
+// and says which threads and loops the time went to (see crate::profiling).
+use axum::{
+    extract::{Query, State},
+    http::StatusCode,
+    response::Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use super::error_status::ApiError;
+use super::server::AppState;
+use crate::profiling;
+
+// Go's pprof samples for 30 seconds unless told otherwise
+const DEFAULT_PROFILE_SECONDS: u64 = 30;
+const MAX_PROFILE_SECONDS: u64 = 300;
+
+/// Lists the profiles there are.
+pub async fn index(State(state): State<AppState>) -> Result<Json<Value>, ApiError> {
+    enabled(&state)?;
+    Ok(Json(json!({
+        "profiles": {
+            "tasks": "the async runtime's workers and tasks, and how often and how long each background loop has run",
+            "heap": "memory the process uses",
+            "profile": "where CPU time goes over ?seconds= (default 30), by thread and background loop"
+        }
+    })))
+}
+
+/// The async runtime and the background loops as they are now.
+pub async fn tasks(State(state): State<AppState>) -> Result<Json<Value>, ApiError> {
+    enabled(&state)?;
+    let metrics = tokio::runtime::Handle::current().metrics();
+    let now = Instant::now();
+    let loops: Vec<Value> = profiling::loops()
+        .into_iter()
+        .map(|(name, stats)| {
+            json!({
+                "name": name,
+                "iterations": stats.iterations,
+                "busySeconds": stats.busy.as_secs_f64(),
+                "lastSeconds": stats.last.as_secs_f64(),
+                "maxSeconds": stats.max.as_secs_f64(),
+                "secondsSinceLast": stats.last_finished.map(|at| now.duration_since(at).as_secs_f64())
+            })
+        })
+        .collect();
+    Ok(Json(json!({
+        "runtime": {
+            "workers": metrics.num_workers(),
+            "aliveTasks": metrics.num_alive_tasks(),
+            "globalQueueDepth": metrics.global_queue_depth()
+        },
+        "loops": loops
+    })))
+}
+
+/// Memory the process uses.
+pub async fn heap(State(state): State<AppState>) -> Result<Json<Value>, ApiError> {
+    enabled(&state)?;
+    match profiling::memory() {
+        Some(memory) => Ok(Json(json!(memory))),
+        None => Err(ApiError::new(StatusCode::NOT_IMPLEMENTED, "NotImplemented", "memory usage is not available on this platform")),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ProfileParams {
+    seconds: Option<u64>,
+}
+
+/// Samples CPU time for `?seconds=` and reports how much each thread and
+/// each background loop took, busiest first.
+pub async fn profile(
+    State(state): State<AppState>,
+    Query(params): Query<ProfileParams>,
+) -> Result<Json<Value>, ApiError> {
+    enabled(&state)?;
+    let seconds = params.seconds.unwrap_or(DEFAULT_PROFILE_SECONDS);
+    if seconds == 0 || seconds > MAX_PROFILE_SECONDS {
+        return Err(ApiError::bad_request(format!("seconds must be between 1 and {}", MAX_PROFILE_SECONDS)));
+    }
+
+    let (threads_before, loops_before) = (profiling::threads(), profiling::loops());
+    let cpu_before = profiling::process_cpu_seconds();
+    tokio::time::sleep(Duration::from_secs(seconds)).await;
+    let cpu = profiling::process_cpu_seconds().zip(cpu_before).map(|(after, before)| after - before);
+
+    let before: HashMap<u32, f64> = threads_before.iter().map(|t| (t.id, t.cpu_seconds)).collect();
+    let mut threads: Vec<(String, f64)> = profiling::threads()
+        .into_iter()
+        .map(|t| (t.name, t.cpu_seconds - before.get(&t.id).copied().unwrap_or(0.0)))
+        .filter(|(_, used)| *used > 0.0)
+        .collect();
+    threads.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+    let mut loops: Vec<(String, u64, Duration)> = profiling::loops()
+        .into_iter()
+        .map(|(name, stats)| {
+            let earlier = loops_before.get(&name).cloned().unwrap_or_default();
+            (name, stats.iterations - earlier.iterations, stats.busy - earlier.busy)
+        })
+        .filter(|(_, iterations, _)| *iterations > 0)
+        .collect();
+    loops.sort_by_key(|(_, _, busy)| Reverse(*busy));
+
+    let window = seconds as f64;
+    Ok(Json(json!({
+        "seconds": seconds,
+        "cpuSeconds": cpu,
+        "cpuPercent": cpu.map(|cpu| cpu / window * 100.0),
+        "threads": threads.iter().map(|(name, used)| json!({ "name": name, "cpuSeconds": used })).collect::<Vec<_>>(),
+        "loops": loops
+            .iter()
+            .map(|(name, iterations, busy)| json!({
+                "name": name,
+                "iterations": iterations,
+                "busySeconds": busy.as_secs_f64(),
+                "busyPercent": busy.as_secs_f64() / window * 100.0
+            }))
+            .collect::<Vec<_>>()
+    })))
+}
+
+fn enabled(state: &AppState) -> Result<(), ApiError> {
+    if state.config.api_server.profiling {
+        Ok(())
+    } else {
+        Err(StatusCode::NOT_FOUND.into())
+    }
+}
@@ -0,0 +1,65 @@
+// Deletion protection, a krust extension: an object annotated
+// `krust.io/protected: "true"` can't be deleted, and neither can a namespace
+// holding one, until the annotation is removed. Meant to keep destructive
+// test runs from taking out the namespaces and objects a setup relies on.
+use axum::{
+    extract::{Request, State},
+    http::Method,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::error;
+
+use super::error_status::ApiError;
+use super::object_path::{self, ObjectPath};
+use super::server::AppState;
+use crate::storage::protection_store::PROTECTED_ANNOTATION;
+
+/// Middleware answering deletes of protected objects with 403 Forbidden.
+pub async fn guard_deletes(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    if *request.method() != Method::DELETE {
+        return next.run(request).await;
+    }
+    let Some(ObjectPath { resource, namespace, name, subresource: None }) = object_path::parse(request.uri().path()) else {
+        return next.run(request).await;
+    };
+
+    let protection = state.storage.protection();
+    let refusal = match protection.is_protected(&resource, namespace.as_deref(), &name).await {
+        Ok(true) => {
+            let scope = namespace.as_ref().map(|ns| format!(" -n {}", ns)).unwrap_or_default();
+            Some(format!(
+                "it is protected by the {} annotation; remove it with `kubectl annotate {} {}{} {}-` to allow deletion",
+                PROTECTED_ANNOTATION, resource, name, scope, PROTECTED_ANNOTATION
+            ))
+        }
+        Ok(false) if resource == "namespaces" => match protection.protected_in(&name).await {
+            Ok(held) if !held.is_empty() => Some(format!(
+                "it holds objects protected by the {} annotation ({}); remove theirs with `kubectl annotate -n {} <resource> <name> {}-` to allow deletion",
+                PROTECTED_ANNOTATION,
+                held.join(", "),
+                name,
+                PROTECTED_ANNOTATION
+            )),
+            Ok(_) => None,
+            Err(e) => {
+                error!("Failed to look up protected objects in namespace {}: {}", name, e);
+                return ApiError::internal(&e).into_response();
+            }
+        },
+        Ok(false) => None,
+        Err(e) => {
+            error!("Failed to check protection of {} {}: {}", resource, name, e);
+            return ApiError::internal(&e).into_response();
+        }
+    };
+
+    match refusal {
+        Some(reason) => forbidden(&resource, &name, &reason),
+        None => next.run(request).await,
+    }
+}
+
+fn forbidden(resource: &str, name: &str, reason: &str) -> Response {
+    ApiError::forbidden(resource, name, reason).into_response()
+}
@@ -1,20 +1,24 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
+    response::Response,
     Json,
 };
 use serde_json::Value;
 use tracing::{error, info};
 
+use crate::api::error_status::ApiError;
+use crate::api::handlers::{list_error, ListParams};
 use crate::api::server::AppState;
+use crate::api::watch::list_or_watch;
 
 pub async fn create_pv(
     State(state): State<AppState>,
     Json(mut pv): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     // Validate kind
     if pv.get("kind").and_then(|k| k.as_str()) != Some("PersistentVolume") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("PersistentVolume", &pv));
     }
 
     // Ensure metadata exists
@@ -27,15 +31,17 @@ pub async fn create_pv(
         pv["metadata"]["name"].as_str().unwrap_or("unknown")
     );
 
+    let name = pv["metadata"]["name"].as_str().unwrap_or_default().to_string();
+
     match state.storage.persistent_volumes().create(pv).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
         Err(e) => {
             if e.to_string().contains("UNIQUE constraint failed") {
                 error!("PersistentVolume already exists: {}", e);
-                Err(StatusCode::CONFLICT)
+                Err(ApiError::already_exists("persistentvolumes", &name))
             } else {
                 error!("Failed to create PersistentVolume: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -44,30 +50,34 @@ pub async fn create_pv(
 pub async fn get_pv(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Getting PersistentVolume {}", name);
 
     match state.storage.persistent_volumes().get(&name).await {
         Ok(pv) => Ok(Json(pv)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("persistentvolumes", &name))
             } else {
                 error!("Failed to get PersistentVolume: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
 }
 
-pub async fn list_pvs(State(state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+pub async fn list_pvs(
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing PersistentVolumes");
 
-    match state.storage.persistent_volumes().list().await {
-        Ok(list) => Ok(Json(list)),
+    let selector = params.selector()?;
+    match list_or_watch(&state, "persistentvolumes", None, &params, &selector, state.storage.persistent_volumes().list_matching(&selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list PersistentVolumes: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -76,10 +86,10 @@ pub async fn update_pv(
     State(state): State<AppState>,
     Path(name): Path<String>,
     Json(pv): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // Validate kind
     if pv.get("kind").and_then(|k| k.as_str()) != Some("PersistentVolume") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("PersistentVolume", &pv));
     }
 
     info!("Updating PersistentVolume {}", name);
@@ -88,10 +98,10 @@ pub async fn update_pv(
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("persistentvolumes", &name))
             } else {
                 error!("Failed to update PersistentVolume: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -100,17 +110,17 @@ pub async fn update_pv(
 pub async fn delete_pv(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Deleting PersistentVolume {}", name);
 
     match state.storage.persistent_volumes().delete(&name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("persistentvolumes", &name))
             } else {
                 error!("Failed to delete PersistentVolume: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
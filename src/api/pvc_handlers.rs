@@ -1,21 +1,25 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
+    response::Response,
     Json,
 };
 use serde_json::Value;
 use tracing::{error, info};
 
+use crate::api::error_status::ApiError;
+use crate::api::handlers::{list_error, ListParams};
 use crate::api::server::AppState;
+use crate::api::watch::list_or_watch;
 
 pub async fn create_pvc(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(mut pvc): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     // Validate kind
     if pvc.get("kind").and_then(|k| k.as_str()) != Some("PersistentVolumeClaim") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("PersistentVolumeClaim", &pvc));
     }
 
     // Ensure metadata exists
@@ -29,15 +33,17 @@ pub async fn create_pvc(
         namespace
     );
 
+    let name = pvc["metadata"]["name"].as_str().unwrap_or_default().to_string();
+
     match state.storage.persistent_volume_claims().create(&namespace, pvc).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
         Err(e) => {
             if e.to_string().contains("UNIQUE constraint failed") {
                 error!("PersistentVolumeClaim already exists: {}", e);
-                Err(StatusCode::CONFLICT)
+                Err(ApiError::already_exists("persistentvolumeclaims", &name))
             } else {
                 error!("Failed to create PersistentVolumeClaim: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -46,17 +52,17 @@ pub async fn create_pvc(
 pub async fn get_pvc(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Getting PersistentVolumeClaim {} in namespace {}", name, namespace);
 
     match state.storage.persistent_volume_claims().get(&namespace, &name).await {
         Ok(pvc) => Ok(Json(pvc)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("persistentvolumeclaims", &name))
             } else {
                 error!("Failed to get PersistentVolumeClaim: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -65,26 +71,32 @@ pub async fn get_pvc(
 pub async fn list_pvcs(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing PersistentVolumeClaims in namespace {}", namespace);
 
-    match state.storage.persistent_volume_claims().list(Some(&namespace)).await {
-        Ok(list) => Ok(Json(list)),
+    let selector = params.selector()?;
+    match list_or_watch(&state, "persistentvolumeclaims", Some(&namespace), &params, &selector, state.storage.persistent_volume_claims().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list PersistentVolumeClaims: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
 
-pub async fn list_all_pvcs(State(state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+pub async fn list_all_pvcs(
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing all PersistentVolumeClaims");
 
-    match state.storage.persistent_volume_claims().list(None).await {
-        Ok(list) => Ok(Json(list)),
+    let selector = params.selector()?;
+    match list_or_watch(&state, "persistentvolumeclaims", None, &params, &selector, state.storage.persistent_volume_claims().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list all PersistentVolumeClaims: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -93,10 +105,10 @@ pub async fn update_pvc(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(pvc): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // Validate kind
     if pvc.get("kind").and_then(|k| k.as_str()) != Some("PersistentVolumeClaim") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("PersistentVolumeClaim", &pvc));
     }
 
     info!("Updating PersistentVolumeClaim {} in namespace {}", name, namespace);
@@ -105,10 +117,10 @@ pub async fn update_pvc(
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("persistentvolumeclaims", &name))
             } else {
                 error!("Failed to update PersistentVolumeClaim: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -117,17 +129,17 @@ pub async fn update_pvc(
 pub async fn delete_pvc(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Deleting PersistentVolumeClaim {} in namespace {}", name, namespace);
 
     match state.storage.persistent_volume_claims().delete(&namespace, &name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("persistentvolumeclaims", &name))
             } else {
                 error!("Failed to delete PersistentVolumeClaim: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -1,30 +1,26 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    response::Json,
+    response::{Json, Response},
 };
-use serde::Deserialize;
 use serde_json::Value;
 
+use super::error_status::ApiError;
+use super::handlers::{list_error, ListParams};
 use super::server::AppState;
-
-#[derive(Deserialize)]
-pub struct ListParams {
-    #[serde(rename = "labelSelector")]
-    label_selector: Option<String>,
-    limit: Option<i32>,
-}
+use super::watch::list_or_watch;
 
 // ResourceQuota handlers - List all resourcequotas across namespaces
 pub async fn list_all_resourcequotas(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.resourcequotas().list(None).await {
-        Ok(quotas) => Ok(Json(quotas)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "resourcequotas", None, &params, &selector, state.storage.resourcequotas().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list all resource quotas: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -32,13 +28,14 @@ pub async fn list_all_resourcequotas(
 pub async fn list_resourcequotas(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.resourcequotas().list(Some(&namespace)).await {
-        Ok(quotas) => Ok(Json(quotas)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "resourcequotas", Some(&namespace), &params, &selector, state.storage.resourcequotas().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list resource quotas: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -47,12 +44,14 @@ pub async fn create_resourcequota(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(quota): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = quota["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.resourcequotas().create(&namespace, quota).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("resourcequotas", &name)),
         Err(e) => {
             tracing::error!("Failed to create resource quota: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -60,13 +59,13 @@ pub async fn create_resourcequota(
 pub async fn get_resourcequota(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.resourcequotas().get(&namespace, &name).await {
         Ok(Some(quota)) => Ok(Json(quota)),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Ok(None) => Err(ApiError::not_found("resourcequotas", &name)),
         Err(e) => {
             tracing::error!("Failed to get resource quota: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -75,12 +74,12 @@ pub async fn update_resourcequota(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(quota): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.resourcequotas().update(&namespace, &name, quota).await {
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             tracing::error!("Failed to update resource quota: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -88,12 +87,12 @@ pub async fn update_resourcequota(
 pub async fn delete_resourcequota(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.resourcequotas().delete(&namespace, &name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             tracing::error!("Failed to delete resource quota: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -101,13 +100,13 @@ pub async fn delete_resourcequota(
 pub async fn get_resourcequota_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.resourcequotas().get(&namespace, &name).await {
         Ok(Some(quota)) => Ok(Json(quota)),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Ok(None) => Err(ApiError::not_found("resourcequotas", &name)),
         Err(e) => {
             tracing::error!("Failed to get resource quota status: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -116,13 +115,13 @@ pub async fn update_resourcequota_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(status_update): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     let status = status_update["status"].clone();
     match state.storage.resourcequotas().update_status(&namespace, &name, status).await {
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             tracing::error!("Failed to update resource quota status: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -130,13 +129,14 @@ pub async fn update_resourcequota_status(
 // LimitRange handlers - List all limitranges across namespaces
 pub async fn list_all_limitranges(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.limitranges().list(None).await {
-        Ok(limitranges) => Ok(Json(limitranges)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "limitranges", None, &params, &selector, state.storage.limitranges().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list all limit ranges: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -144,13 +144,14 @@ pub async fn list_all_limitranges(
 pub async fn list_limitranges(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.limitranges().list(Some(&namespace)).await {
-        Ok(limitranges) => Ok(Json(limitranges)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "limitranges", Some(&namespace), &params, &selector, state.storage.limitranges().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list limit ranges: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -159,12 +160,14 @@ pub async fn create_limitrange(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(limitrange): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = limitrange["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.limitranges().create(&namespace, limitrange).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("limitranges", &name)),
         Err(e) => {
             tracing::error!("Failed to create limit range: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -172,13 +175,13 @@ pub async fn create_limitrange(
 pub async fn get_limitrange(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.limitranges().get(&namespace, &name).await {
         Ok(Some(limitrange)) => Ok(Json(limitrange)),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Ok(None) => Err(ApiError::not_found("limitranges", &name)),
         Err(e) => {
             tracing::error!("Failed to get limit range: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -187,12 +190,12 @@ pub async fn update_limitrange(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(limitrange): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.limitranges().update(&namespace, &name, limitrange).await {
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             tracing::error!("Failed to update limit range: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -200,12 +203,12 @@ pub async fn update_limitrange(
 pub async fn delete_limitrange(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.limitranges().delete(&namespace, &name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             tracing::error!("Failed to delete limit range: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
\ No newline at end of file
@@ -1,31 +1,27 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    response::Json,
+    response::{Json, Response},
 };
-use serde::Deserialize;
 use serde_json::Value;
 
+use super::error_status::ApiError;
+use super::handlers::{list_error, ListParams};
 use super::server::AppState;
-
-#[derive(Deserialize)]
-pub struct ListParams {
-    #[serde(rename = "labelSelector")]
-    label_selector: Option<String>,
-    limit: Option<i32>,
-}
+use super::watch::list_or_watch;
 
 // Role handlers
 pub async fn list_roles(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.roles().list(Some(&namespace)).await {
-        Ok(roles) => Ok(Json(roles)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "roles", Some(&namespace), &params, &selector, state.storage.roles().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list roles: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -34,12 +30,14 @@ pub async fn create_role(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(role): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = role["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.roles().create(&namespace, role).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("roles", &name)),
         Err(e) => {
             tracing::error!("Failed to create role: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -47,15 +45,15 @@ pub async fn create_role(
 pub async fn get_role(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.roles().get(&namespace, &name).await {
         Ok(role) => Ok(Json(role)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("roles", &name))
             } else {
                 tracing::error!("Failed to get role: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -65,15 +63,15 @@ pub async fn update_role(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(role): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.roles().update(&namespace, &name, role).await {
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("roles", &name))
             } else {
                 tracing::error!("Failed to update role: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -82,15 +80,15 @@ pub async fn update_role(
 pub async fn delete_role(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.roles().delete(&namespace, &name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("roles", &name))
             } else {
                 tracing::error!("Failed to delete role: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -100,13 +98,14 @@ pub async fn delete_role(
 pub async fn list_rolebindings(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.rolebindings().list(Some(&namespace)).await {
-        Ok(rolebindings) => Ok(Json(rolebindings)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "rolebindings", Some(&namespace), &params, &selector, state.storage.rolebindings().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list rolebindings: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -115,12 +114,14 @@ pub async fn create_rolebinding(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(rolebinding): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = rolebinding["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.rolebindings().create(&namespace, rolebinding).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("rolebindings", &name)),
         Err(e) => {
             tracing::error!("Failed to create rolebinding: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -128,15 +129,15 @@ pub async fn create_rolebinding(
 pub async fn get_rolebinding(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.rolebindings().get(&namespace, &name).await {
         Ok(rolebinding) => Ok(Json(rolebinding)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("rolebindings", &name))
             } else {
                 tracing::error!("Failed to get rolebinding: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -145,15 +146,15 @@ pub async fn get_rolebinding(
 pub async fn delete_rolebinding(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.rolebindings().delete(&namespace, &name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("rolebindings", &name))
             } else {
                 tracing::error!("Failed to delete rolebinding: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -162,13 +163,14 @@ pub async fn delete_rolebinding(
 // ClusterRole handlers
 pub async fn list_clusterroles(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.clusterroles().list().await {
-        Ok(clusterroles) => Ok(Json(clusterroles)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "clusterroles", None, &params, &selector, state.storage.clusterroles().list_matching(&selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list clusterroles: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -176,12 +178,14 @@ pub async fn list_clusterroles(
 pub async fn create_clusterrole(
     State(state): State<AppState>,
     Json(clusterrole): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = clusterrole["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.clusterroles().create(clusterrole).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("clusterroles", &name)),
         Err(e) => {
             tracing::error!("Failed to create clusterrole: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -189,15 +193,15 @@ pub async fn create_clusterrole(
 pub async fn get_clusterrole(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.clusterroles().get(&name).await {
         Ok(clusterrole) => Ok(Json(clusterrole)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("clusterroles", &name))
             } else {
                 tracing::error!("Failed to get clusterrole: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -207,15 +211,15 @@ pub async fn update_clusterrole(
     State(state): State<AppState>,
     Path(name): Path<String>,
     Json(clusterrole): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.clusterroles().update(&name, clusterrole).await {
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("clusterroles", &name))
             } else {
                 tracing::error!("Failed to update clusterrole: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -224,15 +228,15 @@ pub async fn update_clusterrole(
 pub async fn delete_clusterrole(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.clusterroles().delete(&name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("clusterroles", &name))
             } else {
                 tracing::error!("Failed to delete clusterrole: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -241,13 +245,14 @@ pub async fn delete_clusterrole(
 // ClusterRoleBinding handlers
 pub async fn list_clusterrolebindings(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.clusterrolebindings().list().await {
-        Ok(clusterrolebindings) => Ok(Json(clusterrolebindings)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "clusterrolebindings", None, &params, &selector, state.storage.clusterrolebindings().list_matching(&selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list clusterrolebindings: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -255,12 +260,14 @@ pub async fn list_clusterrolebindings(
 pub async fn create_clusterrolebinding(
     State(state): State<AppState>,
     Json(clusterrolebinding): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = clusterrolebinding["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.clusterrolebindings().create(clusterrolebinding).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("clusterrolebindings", &name)),
         Err(e) => {
             tracing::error!("Failed to create clusterrolebinding: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -268,15 +275,15 @@ pub async fn create_clusterrolebinding(
 pub async fn get_clusterrolebinding(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.clusterrolebindings().get(&name).await {
         Ok(clusterrolebinding) => Ok(Json(clusterrolebinding)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("clusterrolebindings", &name))
             } else {
                 tracing::error!("Failed to get clusterrolebinding: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -285,15 +292,15 @@ pub async fn get_clusterrolebinding(
 pub async fn delete_clusterrolebinding(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.clusterrolebindings().delete(&name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("clusterrolebindings", &name))
             } else {
                 tracing::error!("Failed to delete clusterrolebinding: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -0,0 +1,84 @@
+// What a request does in authorization terms, as kube-apiserver's
+// RequestInfo resolves it: the verb, API group, resource, subresource,
+// namespace and name of a resource request, or just the path and lowercase
+// HTTP method of anything else (discovery, health checks, /krust).
+
+/// The attributes RBAC rules are matched against.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RequestInfo {
+    Resource {
+        verb: String,
+        group: String,
+        resource: String,
+        subresource: Option<String>,
+        namespace: Option<String>,
+        name: Option<String>,
+    },
+    NonResource {
+        verb: String,
+        path: String,
+    },
+}
+
+impl RequestInfo {
+    pub fn parse(method: &str, path: &str, query: Option<&str>) -> Self {
+        let segments: Vec<&str> = path.trim_matches('/').split('/').filter(|s| !s.is_empty()).collect();
+        let (group, rest) = match segments.as_slice() {
+            ["api", "v1", rest @ ..] if !rest.is_empty() => ("", rest),
+            ["apis", group, _version, rest @ ..] if !rest.is_empty() => (*group, rest),
+            _ => {
+                return Self::NonResource {
+                    verb: method.to_lowercase(),
+                    path: path.to_string(),
+                }
+            }
+        };
+
+        // The deprecated /watch/ prefix, e.g. /api/v1/watch/pods
+        let (watch_prefix, rest) = match rest {
+            ["watch", rest @ ..] => (true, rest),
+            _ => (false, rest),
+        };
+        let (namespace, rest) = match rest {
+            // A namespace itself, or one of its subresources
+            ["namespaces", _] | ["namespaces", _, "status" | "finalize"] => (None, rest),
+            ["namespaces", namespace, rest @ ..] => (Some(*namespace), rest),
+            _ => (None, rest),
+        };
+        let (resource, name, subresource) = match rest {
+            [resource] => (*resource, None, None),
+            [resource, name] => (*resource, Some(*name), None),
+            // Anything deeper, such as pods/web/proxy/path, is the subresource
+            [resource, name, subresource, ..] => (*resource, Some(*name), Some(*subresource)),
+            [] => ("", None, None),
+        };
+
+        let watch = watch_prefix
+            || query.is_some_and(|q| q.split('&').any(|pair| pair == "watch=true" || pair == "watch=1"));
+        let verb = match (method, name) {
+            ("GET" | "HEAD", _) if watch => "watch",
+            ("GET" | "HEAD", Some(_)) => "get",
+            ("GET" | "HEAD", None) => "list",
+            ("POST", _) => "create",
+            ("PUT", _) => "update",
+            ("PATCH", _) => "patch",
+            ("DELETE", Some(_)) => "delete",
+            ("DELETE", None) => "deletecollection",
+            (other, _) => return Self::NonResource { verb: other.to_lowercase(), path: path.to_string() },
+        };
+
+        // A namespace is in itself, so a RoleBinding there can grant access to it
+        let namespace = match (namespace, resource) {
+            (None, "namespaces") => name,
+            (namespace, _) => namespace,
+        };
+        Self::Resource {
+            verb: verb.to_string(),
+            group: group.to_string(),
+            resource: resource.to_string(),
+            subresource: subresource.map(str::to_string),
+            namespace: namespace.map(str::to_string),
+            name: name.map(str::to_string),
+        }
+    }
+}
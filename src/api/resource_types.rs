@@ -0,0 +1,89 @@
+// The k8s-openapi type of each resource, by group, version and resource
+// name. The OpenAPI v3 documents take their schemas from it, and with the
+// strict-types feature written objects are decoded into these types; a
+// resource served without an entry here has neither.
+use k8s_openapi::api::{
+    admissionregistration, apps, authentication, autoscaling, batch, coordination, core, networking, policy, rbac, scheduling,
+    storage,
+};
+use k8s_openapi::schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+use k8s_openapi::Resource;
+#[cfg(feature = "strict-types")]
+use serde_json::Value;
+
+/// A resource's kind and apiVersion, and what's known of its type.
+#[derive(Clone, Copy)]
+pub struct ResourceType {
+    pub kind: &'static str,
+    pub api_version: &'static str,
+    /// Adds the type's schema, and those it refers to, to the generator's
+    /// definitions, returning a reference to it.
+    pub schema: fn(&mut SchemaGenerator) -> Schema,
+    /// Decodes an object into the type and back, or says why it can't be.
+    #[cfg(feature = "strict-types")]
+    pub decode: fn(&Value) -> Result<Value, String>,
+}
+
+fn schema<T: JsonSchema>(generator: &mut SchemaGenerator) -> Schema {
+    generator.subschema_for::<T>()
+}
+
+macro_rules! typed {
+    ($t:ty) => {
+        Some(ResourceType {
+            kind: <$t as Resource>::KIND,
+            api_version: <$t as Resource>::API_VERSION,
+            schema: schema::<$t>,
+            #[cfg(feature = "strict-types")]
+            decode: super::strict_types::roundtrip::<$t>,
+        })
+    };
+}
+
+/// The type of the objects of a resource.
+pub fn resource_type(group: &str, version: &str, resource: &str) -> Option<ResourceType> {
+    match (group, version, resource) {
+        ("", "v1", "pods") => typed!(core::v1::Pod),
+        ("", "v1", "services") => typed!(core::v1::Service),
+        ("", "v1", "endpoints") => typed!(core::v1::Endpoints),
+        ("", "v1", "configmaps") => typed!(core::v1::ConfigMap),
+        ("", "v1", "secrets") => typed!(core::v1::Secret),
+        ("", "v1", "namespaces") => typed!(core::v1::Namespace),
+        ("", "v1", "nodes") => typed!(core::v1::Node),
+        ("", "v1", "events") => typed!(core::v1::Event),
+        ("", "v1", "persistentvolumes") => typed!(core::v1::PersistentVolume),
+        ("", "v1", "persistentvolumeclaims") => typed!(core::v1::PersistentVolumeClaim),
+        ("", "v1", "resourcequotas") => typed!(core::v1::ResourceQuota),
+        ("", "v1", "limitranges") => typed!(core::v1::LimitRange),
+        ("", "v1", "serviceaccounts") => typed!(core::v1::ServiceAccount),
+        ("apps", "v1", "deployments") => typed!(apps::v1::Deployment),
+        ("apps", "v1", "replicasets") => typed!(apps::v1::ReplicaSet),
+        ("apps", "v1", "statefulsets") => typed!(apps::v1::StatefulSet),
+        ("apps", "v1", "daemonsets") => typed!(apps::v1::DaemonSet),
+        ("apps", "v1", "controllerrevisions") => typed!(apps::v1::ControllerRevision),
+        ("batch", "v1", "jobs") => typed!(batch::v1::Job),
+        ("batch", "v1", "cronjobs") => typed!(batch::v1::CronJob),
+        ("networking.k8s.io", "v1", "networkpolicies") => typed!(networking::v1::NetworkPolicy),
+        ("networking.k8s.io", "v1", "ingresses") => typed!(networking::v1::Ingress),
+        ("autoscaling", "v1", "horizontalpodautoscalers") => typed!(autoscaling::v1::HorizontalPodAutoscaler),
+        ("autoscaling", "v2", "horizontalpodautoscalers") => typed!(autoscaling::v2::HorizontalPodAutoscaler),
+        ("policy", "v1", "poddisruptionbudgets") => typed!(policy::v1::PodDisruptionBudget),
+        ("coordination.k8s.io", "v1", "leases") => typed!(coordination::v1::Lease),
+        ("rbac.authorization.k8s.io", "v1", "roles") => typed!(rbac::v1::Role),
+        ("rbac.authorization.k8s.io", "v1", "rolebindings") => typed!(rbac::v1::RoleBinding),
+        ("rbac.authorization.k8s.io", "v1", "clusterroles") => typed!(rbac::v1::ClusterRole),
+        ("rbac.authorization.k8s.io", "v1", "clusterrolebindings") => typed!(rbac::v1::ClusterRoleBinding),
+        ("scheduling.k8s.io", "v1", "priorityclasses") => typed!(scheduling::v1::PriorityClass),
+        ("storage.k8s.io", "v1", "storageclasses") => typed!(storage::v1::StorageClass),
+        ("admissionregistration.k8s.io", "v1", "validatingwebhookconfigurations") => {
+            typed!(admissionregistration::v1::ValidatingWebhookConfiguration)
+        }
+        ("admissionregistration.k8s.io", "v1", "mutatingwebhookconfigurations") => {
+            typed!(admissionregistration::v1::MutatingWebhookConfiguration)
+        }
+        ("authentication.k8s.io", "v1", "selfsubjectreviews") => typed!(authentication::v1::SelfSubjectReview),
+        ("authentication.k8s.io", "v1", "tokenreviews") => typed!(authentication::v1::TokenReview),
+        ("authentication.k8s.io", "v1beta1", "selfsubjectreviews") => typed!(authentication::v1beta1::SelfSubjectReview),
+        _ => None,
+    }
+}
@@ -1,18 +1,23 @@
 use axum::{
     http::StatusCode,
     response::Json,
-    routing::{any, delete, get, patch, post, put},
+    routing::{delete, get, patch, post, put},
     Router,
 };
 use serde_json::{json, Value};
 
 use super::configmap_handlers;
+use super::controllerrevision_handlers;
 use super::cronjob_handlers;
 use super::daemonset_handlers;
+use super::event_handlers;
+use super::eviction;
 use super::handlers;
 use super::ingress_handlers;
 use super::job_handlers;
+use super::krust_handlers;
 use super::networkpolicy_handlers;
+use super::profiling_handlers;
 use super::pv_handlers;
 use super::pvc_handlers;
 use super::quota_handlers;
@@ -20,9 +25,6 @@ use super::secret_handlers;
 use super::serviceaccount_handlers;
 use super::statefulset_handlers;
 use super::pod_proxy;
-use super::portforward;
-use super::portforward_spdy;
-use super::portforward_v2;
 use super::server::AppState;
 use super::service_portforward;
 
@@ -75,6 +77,10 @@ pub fn v1_routes() -> Router<AppState> {
             "/namespaces/:namespace/pods/:name/binding",
             post(handlers::create_pod_binding),
         )
+        .route(
+            "/namespaces/:namespace/pods/:name/eviction",
+            post(eviction::create_eviction),
+        )
         .route(
             "/namespaces/:namespace/pods/:name/exec",
             get(handlers::pod_exec),
@@ -83,10 +89,9 @@ pub fn v1_routes() -> Router<AppState> {
             "/namespaces/:namespace/pods/:name/attach",
             get(handlers::pod_attach),
         )
-        // Pod port-forward endpoints (WebSocket handler with SPDY protocol)
         .route(
             "/namespaces/:namespace/pods/:name/portforward",
-            any(super::portforward_champion::handle_portforward_champion),
+            get(handlers::pod_portforward).post(handlers::pod_portforward),
         )
         // Pod proxy endpoints - directly access pod services
         .route(
@@ -126,7 +131,8 @@ pub fn v1_routes() -> Router<AppState> {
         // Service port-forward endpoint
         .route(
             "/namespaces/:namespace/services/:name/portforward",
-            get(service_portforward::service_portforward_handler),
+            get(service_portforward::service_portforward_handler)
+                .post(service_portforward::service_portforward_handler),
         )
         // Endpoints routes
         .route("/endpoints", get(handlers::list_all_endpoints))
@@ -152,7 +158,8 @@ pub fn v1_routes() -> Router<AppState> {
         )
         // Node routes
         .route("/nodes", get(handlers::list_nodes))
-        .route("/nodes/:name", get(handlers::get_node))
+        .route("/nodes/:name", get(handlers::get_node).put(handlers::update_node).patch(handlers::patch_node))
+        .route("/nodes/:name/proxy/stats/summary", get(handlers::get_node_stats_summary))
         // ConfigMap routes
         .route("/configmaps", get(configmap_handlers::list_all_configmaps))
         .route(
@@ -179,6 +186,24 @@ pub fn v1_routes() -> Router<AppState> {
             "/namespaces/:namespace/configmaps/:name",
             delete(configmap_handlers::delete_configmap),
         )
+        // Event routes
+        .route("/events", get(event_handlers::list_all_events))
+        .route(
+            "/namespaces/:namespace/events",
+            get(event_handlers::list_events),
+        )
+        .route(
+            "/namespaces/:namespace/events",
+            post(event_handlers::create_event),
+        )
+        .route(
+            "/namespaces/:namespace/events/:name",
+            get(event_handlers::get_event),
+        )
+        .route(
+            "/namespaces/:namespace/events/:name",
+            delete(event_handlers::delete_event),
+        )
         // Secret routes
         .route("/secrets", get(secret_handlers::list_all_secrets))
         .route(
@@ -312,11 +337,11 @@ pub fn v1_routes() -> Router<AppState> {
             "/namespaces/:namespace/serviceaccounts/:name/token",
             post(serviceaccount_handlers::create_serviceaccount_token),
         )
-        // Watch endpoints
-        .route("/watch/pods", get(handlers::watch_pods))
+        // Deprecated watch paths, served like ?watch=true on the lists
+        .route("/watch/pods", get(handlers::list_all_pods))
         .route(
             "/watch/namespaces/:namespace/pods",
-            get(handlers::watch_namespace_pods),
+            get(handlers::list_pods),
         )
 }
 
@@ -356,21 +381,31 @@ pub fn apps_v1_routes() -> Router<AppState> {
         .route("/namespaces/:namespace/statefulsets", post(statefulset_handlers::create_statefulset))
         .route("/namespaces/:namespace/statefulsets/:name", get(statefulset_handlers::get_statefulset))
         .route("/namespaces/:namespace/statefulsets/:name", put(statefulset_handlers::update_statefulset))
+        .route("/namespaces/:namespace/statefulsets/:name", patch(statefulset_handlers::patch_statefulset))
         .route("/namespaces/:namespace/statefulsets/:name", delete(statefulset_handlers::delete_statefulset))
         // StatefulSet scale subresource
         .route("/namespaces/:namespace/statefulsets/:name/scale", get(statefulset_handlers::get_statefulset_scale))
         .route("/namespaces/:namespace/statefulsets/:name/scale", put(statefulset_handlers::update_statefulset_scale))
         // StatefulSet status subresource
         .route("/namespaces/:namespace/statefulsets/:name/status", get(statefulset_handlers::get_statefulset_status))
+        .route("/namespaces/:namespace/statefulsets/:name/status", put(statefulset_handlers::update_statefulset_status))
         // DaemonSets
         .route("/daemonsets", get(daemonset_handlers::list_all_daemonsets))
         .route("/namespaces/:namespace/daemonsets", get(daemonset_handlers::list_daemonsets))
         .route("/namespaces/:namespace/daemonsets", post(daemonset_handlers::create_daemonset))
         .route("/namespaces/:namespace/daemonsets/:name", get(daemonset_handlers::get_daemonset))
         .route("/namespaces/:namespace/daemonsets/:name", put(daemonset_handlers::update_daemonset))
+        .route("/namespaces/:namespace/daemonsets/:name", patch(daemonset_handlers::patch_daemonset))
         .route("/namespaces/:namespace/daemonsets/:name", delete(daemonset_handlers::delete_daemonset))
         // DaemonSet status subresource
         .route("/namespaces/:namespace/daemonsets/:name/status", get(daemonset_handlers::get_daemonset_status))
+        .route("/namespaces/:namespace/daemonsets/:name/status", put(daemonset_handlers::update_daemonset_status))
+        // ControllerRevisions
+        .route("/controllerrevisions", get(controllerrevision_handlers::list_all_controllerrevisions))
+        .route("/namespaces/:namespace/controllerrevisions", get(controllerrevision_handlers::list_controllerrevisions))
+        .route("/namespaces/:namespace/controllerrevisions", post(controllerrevision_handlers::create_controllerrevision))
+        .route("/namespaces/:namespace/controllerrevisions/:name", get(controllerrevision_handlers::get_controllerrevision))
+        .route("/namespaces/:namespace/controllerrevisions/:name", delete(controllerrevision_handlers::delete_controllerrevision))
 }
 
 pub fn batch_v1_routes() -> Router<AppState> {
@@ -382,6 +417,7 @@ pub fn batch_v1_routes() -> Router<AppState> {
         .route("/namespaces/:namespace/jobs/:name", get(job_handlers::get_job))
         .route("/namespaces/:namespace/jobs/:name", delete(job_handlers::delete_job))
         // Job status subresource
+        .route("/namespaces/:namespace/jobs/:name/status", get(job_handlers::get_job))
         .route("/namespaces/:namespace/jobs/:name/status", put(job_handlers::update_job_status))
         // CronJobs
         .route("/cronjobs", get(cronjob_handlers::list_cronjobs_all_namespaces))
@@ -415,6 +451,20 @@ pub fn networking_v1_routes() -> Router<AppState> {
         .route("/namespaces/:namespace/ingresses/:name/status", put(ingress_handlers::update_ingress_status))
 }
 
+pub fn autoscaling_v1_routes() -> Router<AppState> {
+    use super::hpa_handlers;
+
+    Router::new()
+        // HorizontalPodAutoscalers, converted to and from autoscaling/v2
+        .route("/horizontalpodautoscalers", get(hpa_handlers::list_all_hpas_v1))
+        .route("/namespaces/:namespace/horizontalpodautoscalers", get(hpa_handlers::list_hpas_v1))
+        .route("/namespaces/:namespace/horizontalpodautoscalers", post(hpa_handlers::create_hpa_v1))
+        .route("/namespaces/:namespace/horizontalpodautoscalers/:name", get(hpa_handlers::get_hpa_v1))
+        .route("/namespaces/:namespace/horizontalpodautoscalers/:name", put(hpa_handlers::update_hpa_v1))
+        .route("/namespaces/:namespace/horizontalpodautoscalers/:name", delete(hpa_handlers::delete_hpa_v1))
+        .route("/namespaces/:namespace/horizontalpodautoscalers/:name/status", get(hpa_handlers::get_hpa_v1))
+}
+
 pub fn autoscaling_v2_routes() -> Router<AppState> {
     Router::new()
         // HorizontalPodAutoscalers
@@ -445,6 +495,20 @@ pub fn policy_v1_routes() -> Router<AppState> {
             get(pdb_handlers::get_pdb_status).put(pdb_handlers::update_pdb_status))
 }
 
+pub fn coordination_v1_routes() -> Router<AppState> {
+    use super::lease_handlers;
+
+    Router::new()
+        // Leases
+        .route("/leases", get(lease_handlers::list_all_leases))
+        .route("/namespaces/:namespace/leases", get(lease_handlers::list_leases))
+        .route("/namespaces/:namespace/leases", post(lease_handlers::create_lease))
+        .route("/namespaces/:namespace/leases/:name", get(lease_handlers::get_lease))
+        .route("/namespaces/:namespace/leases/:name", put(lease_handlers::update_lease))
+        .route("/namespaces/:namespace/leases/:name", patch(lease_handlers::patch_lease))
+        .route("/namespaces/:namespace/leases/:name", delete(lease_handlers::delete_lease))
+}
+
 pub fn scheduling_v1_routes() -> Router<AppState> {
     use super::scheduling_handlers;
     
@@ -513,4 +577,43 @@ pub fn rbac_v1_routes() -> Router<AppState> {
         .route("/clusterrolebindings", post(rbac_handlers::create_clusterrolebinding))
         .route("/clusterrolebindings/:name", get(rbac_handlers::get_clusterrolebinding))
         .route("/clusterrolebindings/:name", delete(rbac_handlers::delete_clusterrolebinding))
-}
\ No newline at end of file
+}
+
+pub fn authentication_v1_routes() -> Router<AppState> {
+    use super::authentication;
+
+    Router::new()
+        .route("/selfsubjectreviews", post(authentication::create_self_subject_review))
+        .route("/tokenreviews", post(authentication::create_token_review))
+}
+
+pub fn authentication_v1beta1_routes() -> Router<AppState> {
+    use super::authentication;
+
+    Router::new()
+        // What kubectl auth whoami before 1.28 asks
+        .route("/selfsubjectreviews", post(authentication::create_self_subject_review_v1beta1))
+}
+
+pub fn profiling_routes() -> Router<AppState> {
+    Router::new()
+        .route("/", get(profiling_handlers::index))
+        .route("/tasks", get(profiling_handlers::tasks))
+        .route("/heap", get(profiling_handlers::heap))
+        .route("/profile", get(profiling_handlers::profile))
+}
+
+pub fn krust_routes() -> Router<AppState> {
+    Router::new()
+        // Compatibility report for unsupported pod spec fields
+        .route("/compat", get(krust_handlers::compat_report))
+        .route("/writers", get(krust_handlers::writers_report))
+        .route("/deprecations", get(krust_handlers::deprecations_report))
+        .route("/scheduling", get(krust_handlers::scheduling_report))
+        .route("/feature-gates", get(krust_handlers::feature_gates_report))
+        .route("/controllers", get(krust_handlers::controllers_report))
+        .route("/proxy", get(krust_handlers::proxy_report))
+        .route("/conntrack", get(krust_handlers::conntrack_report))
+        .route("/schema", get(krust_handlers::schema_report))
+        .route("/backup", post(krust_handlers::backup))
+}
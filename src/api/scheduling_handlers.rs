@@ -1,30 +1,26 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    response::Json,
+    response::{Json, Response},
 };
-use serde::Deserialize;
 use serde_json::Value;
 
+use super::error_status::ApiError;
+use super::handlers::{list_error, ListParams};
 use super::server::AppState;
-
-#[derive(Deserialize)]
-pub struct ListParams {
-    #[serde(rename = "labelSelector")]
-    label_selector: Option<String>,
-    limit: Option<i32>,
-}
+use super::watch::list_or_watch;
 
 // PriorityClass handlers
 pub async fn list_priorityclasses(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.priorityclasses().list().await {
-        Ok(pcs) => Ok(Json(pcs)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "priorityclasses", None, &params, &selector, state.storage.priorityclasses().list_matching(&selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list priority classes: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -32,12 +28,14 @@ pub async fn list_priorityclasses(
 pub async fn create_priorityclass(
     State(state): State<AppState>,
     Json(pc): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = pc["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.priorityclasses().create(pc).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("priorityclasses", &name)),
         Err(e) => {
             tracing::error!("Failed to create priority class: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -45,13 +43,13 @@ pub async fn create_priorityclass(
 pub async fn get_priorityclass(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.priorityclasses().get(&name).await {
         Ok(Some(pc)) => Ok(Json(pc)),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Ok(None) => Err(ApiError::not_found("priorityclasses", &name)),
         Err(e) => {
             tracing::error!("Failed to get priority class: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -60,7 +58,7 @@ pub async fn update_priorityclass(
     State(state): State<AppState>,
     Path(name): Path<String>,
     Json(pc): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // PriorityClass doesn't support updates in real k8s, but we'll allow it for now
     match state.storage.priorityclasses().delete(&name).await {
         Ok(_) => {},
@@ -71,7 +69,7 @@ pub async fn update_priorityclass(
         Ok(created) => Ok(Json(created)),
         Err(e) => {
             tracing::error!("Failed to update priority class: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -79,12 +77,12 @@ pub async fn update_priorityclass(
 pub async fn delete_priorityclass(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.priorityclasses().delete(&name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             tracing::error!("Failed to delete priority class: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -92,13 +90,14 @@ pub async fn delete_priorityclass(
 // StorageClass handlers
 pub async fn list_storageclasses(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.storageclasses().list().await {
-        Ok(scs) => Ok(Json(scs)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "storageclasses", None, &params, &selector, state.storage.storageclasses().list_matching(&selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list storage classes: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -106,12 +105,14 @@ pub async fn list_storageclasses(
 pub async fn create_storageclass(
     State(state): State<AppState>,
     Json(sc): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = sc["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.storageclasses().create(sc).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("storageclasses", &name)),
         Err(e) => {
             tracing::error!("Failed to create storage class: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -119,13 +120,13 @@ pub async fn create_storageclass(
 pub async fn get_storageclass(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.storageclasses().get(&name).await {
         Ok(Some(sc)) => Ok(Json(sc)),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Ok(None) => Err(ApiError::not_found("storageclasses", &name)),
         Err(e) => {
             tracing::error!("Failed to get storage class: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -134,7 +135,7 @@ pub async fn update_storageclass(
     State(state): State<AppState>,
     Path(name): Path<String>,
     Json(sc): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // StorageClass doesn't support updates in real k8s, but we'll allow it for now
     match state.storage.storageclasses().delete(&name).await {
         Ok(_) => {},
@@ -145,7 +146,7 @@ pub async fn update_storageclass(
         Ok(created) => Ok(Json(created)),
         Err(e) => {
             tracing::error!("Failed to update storage class: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -153,12 +154,12 @@ pub async fn update_storageclass(
 pub async fn delete_storageclass(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.storageclasses().delete(&name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             tracing::error!("Failed to delete storage class: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
\ No newline at end of file
@@ -1,25 +1,30 @@
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::StatusCode,
+    response::Response,
     Json,
 };
 use base64::{engine::general_purpose::STANDARD, Engine};
 use serde_json::{json, Value};
 use tracing::{error, info, warn};
 
+use crate::api::error_status::{ApiError, Cause};
+use crate::api::last_applied;
+use crate::api::handlers::{list_error, ListParams};
 use crate::api::server::AppState;
+use crate::api::watch::list_or_watch;
 
 pub async fn create_secret(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(mut secret): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     // Validate API version and kind
     let api_version = secret.get("apiVersion")
         .and_then(|v| v.as_str())
         .ok_or_else(|| {
             warn!("Missing apiVersion in secret creation request");
-            StatusCode::BAD_REQUEST
+            ApiError::bad_request("Object 'apiVersion' is missing")
         })?;
     
     if api_version != "v1" {
@@ -31,12 +36,12 @@ pub async fn create_secret(
         .and_then(|k| k.as_str())
         .ok_or_else(|| {
             warn!("Missing kind in secret creation request");
-            StatusCode::BAD_REQUEST
+            ApiError::bad_request("Object 'Kind' is missing")
         })?;
     
     if kind != "Secret" {
         warn!("Wrong kind '{}' for secret endpoint", kind);
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("Secret", &secret));
     }
 
     // Ensure metadata exists
@@ -65,7 +70,8 @@ pub async fn create_secret(
     }
     
     // Get the secret name for logging after mutations are done
-    let secret_name = secret["metadata"]["name"].as_str().unwrap_or("unknown");
+    let secret_name = secret["metadata"]["name"].as_str().unwrap_or_default().to_string();
+    let invalid = |cause| ApiError::invalid("secrets", &secret_name, vec![cause]);
     
     // Validate data field contains valid base64
     if let Some(data) = secret.get("data").and_then(|d| d.as_object()) {
@@ -74,11 +80,11 @@ pub async fn create_secret(
                 // Validate base64 encoding
                 if STANDARD.decode(str_val).is_err() {
                     warn!("Invalid base64 in data field for key '{}' in secret '{}'", key, secret_name);
-                    return Err(StatusCode::BAD_REQUEST);
+                    return Err(invalid(Cause::invalid(&format!("data[{}]", key), value, "illegal base64 data")));
                 }
             } else {
                 warn!("Non-string value in data field for key '{}' in secret '{}'", key, secret_name);
-                return Err(StatusCode::BAD_REQUEST);
+                return Err(invalid(Cause::invalid(&format!("data[{}]", key), value, "must be a base64-encoded string")));
             }
         }
     }
@@ -93,13 +99,13 @@ pub async fn create_secret(
             // TLS secrets must have tls.crt and tls.key
             let data = secret.get("data").and_then(|d| d.as_object());
             if let Some(data) = data {
-                if !data.contains_key("tls.crt") || !data.contains_key("tls.key") {
+                if let Some(key) = ["tls.crt", "tls.key"].into_iter().find(|key| !data.contains_key(*key)) {
                     warn!("TLS secret '{}' missing required keys (tls.crt, tls.key)", secret_name);
-                    return Err(StatusCode::BAD_REQUEST);
+                    return Err(invalid(Cause::required(&format!("data[{}]", key))));
                 }
             } else {
                 warn!("TLS secret '{}' has no data", secret_name);
-                return Err(StatusCode::BAD_REQUEST);
+                return Err(invalid(Cause::required("data[tls.crt]")));
             }
         }
         "kubernetes.io/dockerconfigjson" => {
@@ -108,11 +114,11 @@ pub async fn create_secret(
             if let Some(data) = data {
                 if !data.contains_key(".dockerconfigjson") {
                     warn!("Docker config secret '{}' missing required key (.dockerconfigjson)", secret_name);
-                    return Err(StatusCode::BAD_REQUEST);
+                    return Err(invalid(Cause::required("data[.dockerconfigjson]")));
                 }
             } else {
                 warn!("Docker config secret '{}' has no data", secret_name);
-                return Err(StatusCode::BAD_REQUEST);
+                return Err(invalid(Cause::required("data[.dockerconfigjson]")));
             }
         }
         "kubernetes.io/basic-auth" => {
@@ -134,7 +140,7 @@ pub async fn create_secret(
     let secret_json = serde_json::to_string(&secret).unwrap_or_default();
     if secret_json.len() > 1024 * 1024 {
         warn!("Secret '{}' exceeds 1MB size limit", secret_name);
-        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+        return Err(invalid(Cause::parse("data: Too long: must have at most 1048576 bytes")));
     }
 
     info!("Creating Secret {} in namespace {}", secret_name, namespace);
@@ -144,10 +150,10 @@ pub async fn create_secret(
         Err(e) => {
             if e.to_string().contains("UNIQUE constraint failed") {
                 error!("Secret already exists: {}", e);
-                Err(StatusCode::CONFLICT)
+                Err(ApiError::already_exists("secrets", &secret_name))
             } else {
                 error!("Failed to create Secret: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -156,17 +162,17 @@ pub async fn create_secret(
 pub async fn get_secret(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Getting Secret {} in namespace {}", name, namespace);
 
     match state.storage.secrets().get(&namespace, &name).await {
         Ok(secret) => Ok(Json(secret)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("secrets", &name))
             } else {
                 error!("Failed to get Secret: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -175,26 +181,32 @@ pub async fn get_secret(
 pub async fn list_secrets(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing Secrets in namespace {}", namespace);
 
-    match state.storage.secrets().list(Some(&namespace)).await {
-        Ok(list) => Ok(Json(list)),
+    let selector = params.selector()?;
+    match list_or_watch(&state, "secrets", Some(&namespace), &params, &selector, state.storage.secrets().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list Secrets: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
 
-pub async fn list_all_secrets(State(state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+pub async fn list_all_secrets(
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing all Secrets");
 
-    match state.storage.secrets().list(None).await {
-        Ok(list) => Ok(Json(list)),
+    let selector = params.selector()?;
+    match list_or_watch(&state, "secrets", None, &params, &selector, state.storage.secrets().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list all Secrets: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -203,13 +215,13 @@ pub async fn update_secret(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(mut secret): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // Validate API version and kind
     let api_version = secret.get("apiVersion")
         .and_then(|v| v.as_str())
         .ok_or_else(|| {
             warn!("Missing apiVersion in secret update request");
-            StatusCode::BAD_REQUEST
+            ApiError::bad_request("Object 'apiVersion' is missing")
         })?;
     
     if api_version != "v1" {
@@ -217,25 +229,25 @@ pub async fn update_secret(
     }
     
     if secret.get("kind").and_then(|k| k.as_str()) != Some("Secret") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("Secret", &secret));
     }
     
     // Check if secret is immutable
     let existing_secret = match state.storage.secrets().get(&namespace, &name).await {
         Ok(secret) => secret,
         Err(e) if e.to_string().contains("not found") => {
-            return Err(StatusCode::NOT_FOUND);
+            return Err(ApiError::not_found("secrets", &name));
         }
         Err(e) => {
             error!("Failed to get secret for update: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err(ApiError::internal(&e));
         }
     };
     
     // If the existing secret is immutable, reject the update
     if existing_secret.get("immutable").and_then(|i| i.as_bool()).unwrap_or(false) {
         warn!("Attempt to update immutable secret '{}'", name);
-        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+        return Err(immutable(&name));
     }
 
     info!("Updating Secret {} in namespace {}", name, namespace);
@@ -244,12 +256,12 @@ pub async fn update_secret(
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("secrets", &name))
             } else if e.to_string().contains("immutable") {
-                Err(StatusCode::UNPROCESSABLE_ENTITY)
+                Err(immutable(&name))
             } else {
                 error!("Failed to update Secret: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -259,19 +271,24 @@ pub async fn patch_secret(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(patch): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Patching Secret {} in namespace {}", name, namespace);
 
+    let patch = match state.storage.secrets().get(&namespace, &name).await {
+        Ok(current) => last_applied::with_removals(patch, &current),
+        Err(_) => patch,
+    };
+
     match state.storage.secrets().patch(&namespace, &name, patch).await {
         Ok(patched) => Ok(Json(patched)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("secrets", &name))
             } else if e.to_string().contains("immutable") {
-                Err(StatusCode::UNPROCESSABLE_ENTITY)
+                Err(immutable(&name))
             } else {
                 error!("Failed to patch Secret: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -280,18 +297,23 @@ pub async fn patch_secret(
 pub async fn delete_secret(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Deleting Secret {} in namespace {}", name, namespace);
 
     match state.storage.secrets().delete(&namespace, &name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("secrets", &name))
             } else {
                 error!("Failed to delete Secret: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
-}
\ No newline at end of file
+}
+
+// The 422 for changing the data of an immutable Secret
+fn immutable(name: &str) -> ApiError {
+    ApiError::invalid("secrets", name, vec![Cause::forbidden("data", "field is immutable when `immutable` is set")])
+}
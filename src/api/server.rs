@@ -2,589 +2,180 @@ use axum::{
     body::Body,
     extract::State,
     http::{HeaderMap, StatusCode},
-    response::{IntoResponse, Json, Response},
-    routing::{get},
+    middleware,
+    response::Response,
+    routing::get,
     Router,
 };
-use serde_json::{json, Value};
 use std::net::SocketAddr;
 use tower_http::cors::CorsLayer;
 use tower_http::trace::TraceLayer;
 
-use crate::Storage;
+use crate::controllers::cluster_info_publisher::ClusterInfoPublisher;
+use crate::{Config, Storage};
 use std::sync::Arc;
+use std::time::Duration;
+
+/// The Kubernetes release whose API krust serves.
+pub const KUBERNETES_VERSION: &str = "v1.29.0";
+
+// How often watch events beyond `apiServer.watchHistory` are dropped
+const COMPACTION_INTERVAL: Duration = Duration::from_secs(60);
 
 #[derive(Clone)]
 pub struct AppState {
     pub storage: Storage,
-    pub container_runtime: Arc<crate::runtime::container::ContainerRuntime>,
+    pub config: Arc<Config>,
+    /// Set when exec, attach and port-forward are served from the streaming port.
+    pub streaming: Option<Arc<super::streaming::StreamingServer>>,
+    pub authenticator: Arc<super::authentication::Authenticator>,
+    pub webhooks: Arc<super::admission_webhook::WebhookClient>,
 }
 
-pub async fn start_server(storage: Storage) -> anyhow::Result<()> {
-    let container_runtime = Arc::new(crate::runtime::container::ContainerRuntime::new());
-    let state = AppState { 
-        storage,
-        container_runtime,
-    };
-
-    let app = Router::new()
-        .route("/livez", get(liveness))
-        .route("/readyz", get(readiness))
-        .route("/healthz", get(health))
-        .route("/version", get(version))
-        .route("/api", get(api_versions))
-        .route("/api/v1", get(api_v1_resources))
-        .route("/apis", get(api_groups))
-        .route("/apis/apps/v1", get(apps_v1_resources))
-        .route("/apis/batch/v1", get(batch_v1_resources))
-        .route("/apis/networking.k8s.io/v1", get(networking_v1_resources))
-        .route("/apis/autoscaling/v2", get(autoscaling_v2_resources))
-        .route("/apis/rbac.authorization.k8s.io/v1", get(rbac_v1_resources))
-        .route("/apis/policy/v1", get(policy_v1_resources))
-        .route("/apis/scheduling.k8s.io/v1", get(scheduling_v1_resources))
-        .route("/apis/storage.k8s.io/v1", get(storage_v1_resources))
-        .route("/apis/admissionregistration.k8s.io/v1", get(admissionregistration_v1_resources))
-        .route("/openapi/v2", get(openapi_v2))
-        .route("/swagger.json", get(openapi_v2))  // kubectl looks here too
-        .route("/openapi/v3", get(openapi_v3_discovery))
-        .route("/openapi/v3.0", get(openapi_v3_discovery))
-        .nest("/api/v1", super::routes::v1_routes())
-        .nest("/apis/apps/v1", super::routes::apps_v1_routes())
-        .nest("/apis/batch/v1", super::routes::batch_v1_routes())
-        .nest("/apis/networking.k8s.io/v1", super::routes::networking_v1_routes())
-        .nest("/apis/autoscaling/v2", super::routes::autoscaling_v2_routes())
-        .nest("/apis/rbac.authorization.k8s.io/v1", super::routes::rbac_v1_routes())
-        .nest("/apis/policy/v1", super::routes::policy_v1_routes())
-        .nest("/apis/scheduling.k8s.io/v1", super::routes::scheduling_v1_routes())
-        .nest("/apis/storage.k8s.io/v1", super::routes::storage_v1_routes())
-        .nest("/apis/admissionregistration.k8s.io/v1", super::routes::admissionregistration_v1_routes())
-        .layer(CorsLayer::permissive())
-        .layer(TraceLayer::new_for_http())
-        .with_state(state);
-
+pub async fn start_server(storage: Storage, config: Config) -> anyhow::Result<()> {
     // Bind to both IPv4 and IPv6
     let addr = "0.0.0.0:6443";
     tracing::info!("Krust API server listening on {}", addr);
 
     let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(listener, app).await?;
-
-    Ok(())
+    serve(listener, storage, config).await
 }
 
-async fn liveness() -> StatusCode {
-    StatusCode::OK
-}
+/// Serves the API on an already bound listener, e.g. an ephemeral port in tests.
+pub async fn serve(listener: tokio::net::TcpListener, storage: Storage, config: Config) -> anyhow::Result<()> {
+    let streaming = super::streaming::bind(&config.streaming).await?;
+    let authenticator = Arc::new(super::authentication::Authenticator::new(&config.authentication, storage.clone()));
+    let ca = crate::pki::ClusterCa::load(config.data_dir().as_ref())?;
+    let webhooks = Arc::new(super::admission_webhook::WebhookClient::new(&config.admission, &ca, storage.clone())?);
+    let config = Arc::new(config);
+    let state = AppState { 
+        storage,
+        config: config.clone(),
+        streaming: streaming.as_ref().map(|(_, server)| server.clone()),
+        authenticator,
+        webhooks,
+    };
 
-async fn readiness(State(state): State<AppState>) -> StatusCode {
-    match sqlx::query("SELECT 1").fetch_one(&*state.storage.pool).await {
-        Ok(_) => StatusCode::OK,
-        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    if let Some((streaming_listener, _)) = streaming {
+        let streaming_router = super::streaming::router(state.clone());
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(streaming_listener, streaming_router).await {
+                tracing::error!("Streaming server failed: {}", e);
+            }
+        });
     }
-}
 
-async fn health() -> (StatusCode, &'static str) {
-    (StatusCode::OK, "ok")
-}
-
-async fn version() -> Json<Value> {
-    Json(json!({
-        "major": "1",
-        "minor": "29",
-        "gitVersion": "v1.29.0-krust",
-        "gitCommit": "000000",
-        "gitTreeState": "clean",
-        "buildDate": chrono::Utc::now().to_rfc3339(),
-        "goVersion": "rust1.75",
-        "compiler": "rustc",
-        "platform": "linux/amd64"
-    }))
-}
-
-async fn api_versions() -> Json<Value> {
-    Json(json!({
-        "kind": "APIVersions",
-        "versions": ["v1"],
-        "serverAddressByClientCIDRs": [{
-            "clientCIDR": "0.0.0.0/0",
-            "serverAddress": "127.0.0.1:6443"
-        }]
-    }))
-}
-
-async fn api_v1_resources() -> Json<Value> {
-    Json(json!({
-        "kind": "APIResourceList",
-        "groupVersion": "v1",
-        "resources": [
-            {
-                "name": "namespaces",
-                "singularName": "namespace",
-                "namespaced": false,
-                "kind": "Namespace",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["ns"]
-            },
-            {
-                "name": "pods",
-                "singularName": "pod",
-                "namespaced": true,
-                "kind": "Pod",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["po"]
-            },
-            {
-                "name": "services",
-                "singularName": "service",
-                "namespaced": true,
-                "kind": "Service",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["svc"]
-            },
-            {
-                "name": "endpoints",
-                "singularName": "endpoints",
-                "namespaced": true,
-                "kind": "Endpoints",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["ep"]
-            },
-            {
-                "name": "nodes",
-                "singularName": "node",
-                "namespaced": false,
-                "kind": "Node",
-                "verbs": ["get", "list", "patch", "update", "watch"],
-                "shortNames": ["no"]
-            },
-            {
-                "name": "resourcequotas",
-                "singularName": "resourcequota",
-                "namespaced": true,
-                "kind": "ResourceQuota",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["quota"]
-            },
-            {
-                "name": "resourcequotas/status",
-                "singularName": "",
-                "namespaced": true,
-                "kind": "ResourceQuota",
-                "verbs": ["get", "patch", "update"]
-            },
-            {
-                "name": "limitranges",
-                "singularName": "limitrange",
-                "namespaced": true,
-                "kind": "LimitRange",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["limits"]
-            },
-            {
-                "name": "serviceaccounts",
-                "singularName": "serviceaccount",
-                "namespaced": true,
-                "kind": "ServiceAccount",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["sa"]
-            },
-            {
-                "name": "serviceaccounts/token",
-                "singularName": "",
-                "namespaced": true,
-                "kind": "TokenRequest",
-                "verbs": ["create"]
+    let watch = state.storage.watch();
+    let watch_history = state.config.api_server.watch_history;
+    tokio::spawn(async move {
+        loop {
+            tokio::time::sleep(COMPACTION_INTERVAL).await;
+            match watch.compact(watch_history).await {
+                Ok(0) => {}
+                Ok(dropped) => tracing::debug!("Compacted {} watch events", dropped),
+                Err(e) => tracing::error!("Watch event compaction failed: {}", e),
             }
-        ]
-    }))
-}
-
-async fn api_groups() -> Json<Value> {
-    Json(json!({
-        "kind": "APIGroupList",
-        "apiVersion": "v1",
-        "groups": [
-            {
-                "name": "apps",
-                "versions": [
-                    {
-                        "groupVersion": "apps/v1",
-                        "version": "v1"
-                    }
-                ],
-                "preferredVersion": {
-                    "groupVersion": "apps/v1",
-                    "version": "v1"
-                }
-            },
-            {
-                "name": "batch",
-                "versions": [
-                    {
-                        "groupVersion": "batch/v1",
-                        "version": "v1"
-                    }
-                ],
-                "preferredVersion": {
-                    "groupVersion": "batch/v1",
-                    "version": "v1"
-                }
-            },
-            {
-                "name": "networking.k8s.io",
-                "versions": [
-                    {
-                        "groupVersion": "networking.k8s.io/v1",
-                        "version": "v1"
-                    }
-                ],
-                "preferredVersion": {
-                    "groupVersion": "networking.k8s.io/v1",
-                    "version": "v1"
-                }
-            },
-            {
-                "name": "autoscaling",
-                "versions": [
-                    {
-                        "groupVersion": "autoscaling/v2",
-                        "version": "v2"
-                    }
-                ],
-                "preferredVersion": {
-                    "groupVersion": "autoscaling/v2",
-                    "version": "v2"
-                }
-            },
-            {
-                "name": "rbac.authorization.k8s.io",
-                "versions": [
-                    {
-                        "groupVersion": "rbac.authorization.k8s.io/v1",
-                        "version": "v1"
-                    }
-                ],
-                "preferredVersion": {
-                    "groupVersion": "rbac.authorization.k8s.io/v1",
-                    "version": "v1"
-                }
-            },
-            {
-                "name": "policy",
-                "versions": [
-                    {
-                        "groupVersion": "policy/v1",
-                        "version": "v1"
-                    }
-                ],
-                "preferredVersion": {
-                    "groupVersion": "policy/v1",
-                    "version": "v1"
-                }
-            },
-            {
-                "name": "scheduling.k8s.io",
-                "versions": [
-                    {
-                        "groupVersion": "scheduling.k8s.io/v1",
-                        "version": "v1"
-                    }
-                ],
-                "preferredVersion": {
-                    "groupVersion": "scheduling.k8s.io/v1",
-                    "version": "v1"
-                }
-            },
-            {
-                "name": "storage.k8s.io",
-                "versions": [
-                    {
-                        "groupVersion": "storage.k8s.io/v1",
-                        "version": "v1"
-                    }
-                ],
-                "preferredVersion": {
-                    "groupVersion": "storage.k8s.io/v1",
-                    "version": "v1"
-                }
-            },
-            {
-                "name": "admissionregistration.k8s.io",
-                "versions": [
-                    {
-                        "groupVersion": "admissionregistration.k8s.io/v1",
-                        "version": "v1"
-                    }
-                ],
-                "preferredVersion": {
-                    "groupVersion": "admissionregistration.k8s.io/v1",
-                    "version": "v1"
+        }
+    });
+
+    // cluster-info and friends point clients at this server
+    let scheme = if config.tls.enabled { "https" } else { "http" };
+    let server = format!("{}://127.0.0.1:{}", scheme, listener.local_addr()?.port());
+    match ClusterInfoPublisher::new(state.storage.clone(), &config, &server, &ca.cert_pem()?) {
+        Ok(publisher) => {
+            tokio::spawn(async move {
+                if let Err(e) = publisher.run().await {
+                    tracing::error!("Cluster info publisher failed: {}", e);
                 }
-            }
-        ]
-    }))
-}
-
-async fn apps_v1_resources() -> Json<Value> {
-    Json(json!({
-        "kind": "APIResourceList",
-        "apiVersion": "apps/v1",
-        "groupVersion": "apps/v1",
-        "resources": [
-            {
-                "name": "deployments",
-                "singularName": "deployment",
-                "namespaced": true,
-                "kind": "Deployment",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["deploy"]
-            },
-            {
-                "name": "replicasets",
-                "singularName": "replicaset",
-                "namespaced": true,
-                "kind": "ReplicaSet",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["rs"]
-            },
-            {
-                "name": "statefulsets",
-                "singularName": "statefulset",
-                "namespaced": true,
-                "kind": "StatefulSet",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["sts"]
-            },
-            {
-                "name": "daemonsets",
-                "singularName": "daemonset",
-                "namespaced": true,
-                "kind": "DaemonSet",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["ds"]
-            }
-        ]
-    }))
-}
+            });
+        }
+        Err(e) => tracing::error!("Not publishing cluster info: {:#}", e),
+    }
 
-async fn batch_v1_resources() -> Json<Value> {
-    Json(json!({
-        "kind": "APIResourceList",
-        "apiVersion": "batch/v1",
-        "groupVersion": "batch/v1",
-        "resources": [
-            {
-                "name": "jobs",
-                "singularName": "job",
-                "namespaced": true,
-                "kind": "Job",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"]
-            },
-            {
-                "name": "jobs/status",
-                "singularName": "",
-                "namespaced": true,
-                "kind": "Job",
-                "verbs": ["get", "patch", "update"]
-            },
-            {
-                "name": "cronjobs",
-                "singularName": "cronjob",
-                "namespaced": true,
-                "kind": "CronJob",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["cj"]
-            },
-            {
-                "name": "cronjobs/status",
-                "singularName": "",
-                "namespaced": true,
-                "kind": "CronJob",
-                "verbs": ["get", "patch", "update"]
-            }
-        ]
-    }))
-}
+    if config.tls.enabled {
+        let data_dir = config.data_dir();
+        let acceptor = super::tls::acceptor(&config.tls, &ca)?;
+        if let Some(data_dir) = &data_dir {
+            let port = listener.local_addr()?.port();
+            super::tls::write_kubeconfig(data_dir, &ca, &format!("https://127.0.0.1:{}", port))?;
+        }
+        super::tls::serve(listener, router(state), acceptor).await?;
+    } else {
+        axum::serve(listener, router(state)).await?;
+    }
 
-async fn networking_v1_resources() -> Json<Value> {
-    Json(json!({
-        "kind": "APIResourceList",
-        "apiVersion": "networking.k8s.io/v1",
-        "groupVersion": "networking.k8s.io/v1",
-        "resources": [
-            {
-                "name": "networkpolicies",
-                "singularName": "networkpolicy",
-                "namespaced": true,
-                "kind": "NetworkPolicy",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["netpol"]
-            },
-            {
-                "name": "ingresses",
-                "singularName": "ingress",
-                "namespaced": true,
-                "kind": "Ingress",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["ing"]
-            },
-            {
-                "name": "ingresses/status",
-                "singularName": "",
-                "namespaced": true,
-                "kind": "Ingress",
-                "verbs": ["get", "patch", "update"]
-            }
-        ]
-    }))
+    Ok(())
 }
 
-async fn autoscaling_v2_resources() -> Json<Value> {
-    Json(json!({
-        "kind": "APIResourceList",
-        "apiVersion": "autoscaling/v2",
-        "groupVersion": "autoscaling/v2",
-        "resources": [
-            {
-                "name": "horizontalpodautoscalers",
-                "singularName": "horizontalpodautoscaler",
-                "namespaced": true,
-                "kind": "HorizontalPodAutoscaler",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["hpa"]
-            },
-            {
-                "name": "horizontalpodautoscalers/status",
-                "singularName": "",
-                "namespaced": true,
-                "kind": "HorizontalPodAutoscaler",
-                "verbs": ["get", "patch", "update"]
-            }
-        ]
-    }))
+pub fn router(state: AppState) -> Router {
+    let router = resources(state.clone());
+    #[cfg(feature = "strict-types")]
+    let router = router.layer(middleware::from_fn(super::strict_types::check_types));
+    router
+        .layer(middleware::from_fn_with_state(state.clone(), super::field_manager::track_writes))
+        // Outside write tracking, which leaves out deletes whose options
+        // make them dry runs
+        .layer(middleware::from_fn(super::delete_options::read_delete_options))
+        // Outside write tracking so recording a write counts toward the
+        // timeout; long-running requests pass straight through
+        .layer(middleware::from_fn_with_state(state.clone(), super::timeout::enforce_timeout))
+        .layer(middleware::from_fn_with_state(state.clone(), super::authorization::authorize))
+        .layer(middleware::from_fn_with_state(state.clone(), super::authentication::authenticate))
+        .layer(middleware::from_fn_with_state(state, super::cancellation::cancel_on_disconnect))
+        .layer(CorsLayer::permissive())
+        .layer(TraceLayer::new_for_http())
 }
 
-async fn policy_v1_resources() -> Json<Value> {
-    Json(json!({
-        "kind": "APIResourceList",
-        "apiVersion": "policy/v1",
-        "groupVersion": "policy/v1",
-        "resources": [
-            {
-                "name": "poddisruptionbudgets",
-                "singularName": "poddisruptionbudget",
-                "namespaced": true,
-                "kind": "PodDisruptionBudget",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["pdb"]
-            },
-            {
-                "name": "poddisruptionbudgets/status",
-                "singularName": "",
-                "namespaced": true,
-                "kind": "PodDisruptionBudget",
-                "verbs": ["get", "patch", "update"]
-            }
-        ]
-    }))
+/// The routes, with the middleware that reads and writes `state.storage`
+/// on their way: what a request goes through once it's authorized.
+/// Admission sends requests of its own through it.
+pub(super) fn resources(state: AppState) -> Router {
+    checked_routes(state.clone())
+        .layer(middleware::from_fn_with_state(state.clone(), super::dry_run::roll_back_dry_runs))
+        .layer(middleware::from_fn_with_state(state, super::admission::call_webhooks))
 }
 
-async fn rbac_v1_resources() -> Json<Value> {
-    Json(json!({
-        "kind": "APIResourceList",
-        "apiVersion": "rbac.authorization.k8s.io/v1",
-        "groupVersion": "rbac.authorization.k8s.io/v1",
-        "resources": [
-            {
-                "name": "roles",
-                "singularName": "role",
-                "namespaced": true,
-                "kind": "Role",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"]
-            },
-            {
-                "name": "rolebindings",
-                "singularName": "rolebinding",
-                "namespaced": true,
-                "kind": "RoleBinding",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"]
-            },
-            {
-                "name": "clusterroles",
-                "singularName": "clusterrole",
-                "namespaced": false,
-                "kind": "ClusterRole",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"]
-            },
-            {
-                "name": "clusterrolebindings",
-                "singularName": "clusterrolebinding",
-                "namespaced": false,
-                "kind": "ClusterRoleBinding",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"]
-            }
-        ]
-    }))
+/// The routes, with the checks made as objects are written. A dry run sends
+/// its write through them on a transaction; the webhooks it waits on before
+/// then are outside it, so it holds no locks while they answer.
+pub(super) fn checked_routes(state: AppState) -> Router {
+    super::openapi_v3::routes(super::discovery::routes(Router::new()))
+        .route("/livez", get(liveness))
+        .route("/readyz", get(readiness))
+        .route("/healthz", get(health))
+        .route("/openapi/v2", get(openapi_v2))
+        .route("/swagger.json", get(openapi_v2))  // kubectl looks here too
+        .nest("/krust", super::routes::krust_routes())
+        .route("/debug/pprof/", get(super::profiling_handlers::index))
+        .nest("/debug/pprof", super::routes::profiling_routes())
+        .fallback(super::deprecated_apis::not_found)
+        .layer(middleware::from_fn(super::error_status::status_bodies))
+        .layer(middleware::from_fn_with_state(state.clone(), super::finalizers::defer_deletes))
+        .layer(middleware::from_fn_with_state(state.clone(), super::field_manager::show_managed_fields))
+        .layer(middleware::from_fn(super::export::strip_server_fields))
+        .layer(middleware::from_fn(super::table::render_tables))
+        .layer(middleware::from_fn_with_state(state.clone(), super::protection::guard_deletes))
+        .layer(middleware::from_fn_with_state(state.clone(), super::pod_security::check_pod_security))
+        .layer(middleware::from_fn_with_state(state.clone(), super::limit_ranges::apply_limit_ranges))
+        // Inside the webhooks, so an object's write lock isn't held while
+        // they answer
+        .layer(middleware::from_fn_with_state(state.clone(), super::conflicts::check_resource_version))
+        .with_state(state)
 }
 
-async fn scheduling_v1_resources() -> Json<Value> {
-    Json(json!({
-        "kind": "APIResourceList",
-        "apiVersion": "scheduling.k8s.io/v1",
-        "groupVersion": "scheduling.k8s.io/v1",
-        "resources": [
-            {
-                "name": "priorityclasses",
-                "singularName": "priorityclass",
-                "namespaced": false,
-                "kind": "PriorityClass",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["pc"]
-            }
-        ]
-    }))
+async fn liveness() -> StatusCode {
+    StatusCode::OK
 }
 
-async fn storage_v1_resources() -> Json<Value> {
-    Json(json!({
-        "kind": "APIResourceList",
-        "apiVersion": "storage.k8s.io/v1",
-        "groupVersion": "storage.k8s.io/v1",
-        "resources": [
-            {
-                "name": "storageclasses",
-                "singularName": "storageclass",
-                "namespaced": false,
-                "kind": "StorageClass",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"],
-                "shortNames": ["sc"]
-            }
-        ]
-    }))
+async fn readiness(State(state): State<AppState>) -> StatusCode {
+    match sqlx::query("SELECT 1").fetch_one(&*state.storage.pool).await {
+        Ok(_) => StatusCode::OK,
+        Err(_) => StatusCode::SERVICE_UNAVAILABLE,
+    }
 }
 
-async fn admissionregistration_v1_resources() -> Json<Value> {
-    Json(json!({
-        "kind": "APIResourceList",
-        "apiVersion": "admissionregistration.k8s.io/v1",
-        "groupVersion": "admissionregistration.k8s.io/v1",
-        "resources": [
-            {
-                "name": "validatingwebhookconfigurations",
-                "singularName": "validatingwebhookconfiguration",
-                "namespaced": false,
-                "kind": "ValidatingWebhookConfiguration",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"]
-            },
-            {
-                "name": "mutatingwebhookconfigurations",
-                "singularName": "mutatingwebhookconfiguration",
-                "namespaced": false,
-                "kind": "MutatingWebhookConfiguration",
-                "verbs": ["create", "delete", "get", "list", "patch", "update", "watch"]
-            }
-        ]
-    }))
+async fn health() -> (StatusCode, &'static str) {
+    (StatusCode::OK, "ok")
 }
 
 async fn openapi_v2(headers: HeaderMap) -> Response<Body> {
@@ -614,13 +205,4 @@ async fn openapi_v2(headers: HeaderMap) -> Response<Body> {
         .header("Content-Type", "application/json")
         .body(Body::from(serde_json::to_string(&json).unwrap()))
         .unwrap()
-}
-
-async fn openapi_v3_discovery() -> Json<Value> {
-    Json(json!({
-        "paths": {
-            "v3": "/openapi/v3",
-            "v3.0": "/openapi/v3.0"
-        }
-    }))
 }
\ No newline at end of file
@@ -1,28 +1,21 @@
 use axum::{
-    extract::{ws::WebSocket, Path, Query, State, WebSocketUpgrade},
-    http::{HeaderMap, StatusCode},
+    extract::{Path, Request, State},
+    http::StatusCode,
     response::Response,
 };
-use serde::Deserialize;
 use tracing::{error, info, warn};
 
-use super::portforward_spdy;
+use super::error_status::ApiError;
 use super::server::AppState;
-
-#[derive(Debug, Deserialize)]
-pub struct PortForwardQuery {
-    ports: Option<String>,
-}
+use super::streaming::StreamRequest;
 
 /// Handle port-forward requests to services
 /// This finds the backing pods for a service and forwards to one of them
 pub async fn service_portforward_handler(
-    ws: WebSocketUpgrade,
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-    Query(query): Query<PortForwardQuery>,
-    headers: HeaderMap,
-) -> Result<Response, StatusCode> {
+    request: Request,
+) -> Result<Response, ApiError> {
     info!("Service port-forward request for service {}/{}", namespace, name);
     
     // Get the service to find selector
@@ -33,7 +26,7 @@ pub async fn service_portforward_handler(
         .await
         .map_err(|e| {
             error!("Failed to get service: {}", e);
-            StatusCode::NOT_FOUND
+            ApiError::not_found("services", &name)
         })?;
 
     // Get selector from service spec
@@ -43,12 +36,12 @@ pub async fn service_portforward_handler(
         .and_then(|s| s.as_object())
         .ok_or_else(|| {
             warn!("Service has no selector");
-            StatusCode::BAD_REQUEST
+            ApiError::bad_request(format!("invalid service '{}': Service is defined without a selector", name))
         })?;
 
     if selector.is_empty() {
         warn!("Service has empty selector");
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::bad_request(format!("invalid service '{}': Service is defined without a selector", name)));
     }
 
     // Find pods matching the selector
@@ -59,7 +52,7 @@ pub async fn service_portforward_handler(
         .await
         .map_err(|e| {
             error!("Failed to list pods: {}", e);
-            StatusCode::INTERNAL_SERVER_ERROR
+            ApiError::internal(&e)
         })?;
 
     // Filter pods by selector labels
@@ -93,7 +86,7 @@ pub async fn service_portforward_handler(
 
     if matching_pods.is_empty() {
         warn!("No running pods found for service {}/{}", namespace, name);
-        return Err(StatusCode::SERVICE_UNAVAILABLE);
+        return Err(ApiError::new(StatusCode::SERVICE_UNAVAILABLE, "ServiceUnavailable", format!("no endpoints available for service \"{}\"", name)));
     }
 
     // Pick the first matching pod
@@ -106,13 +99,10 @@ pub async fn service_portforward_handler(
 
     info!("Forwarding service {}/{} to pod {}/{}", namespace, name, namespace, pod_name);
 
-    // Delegate to the pod port-forward handler
-    portforward_spdy::portforward_handler(
-        ws,
-        State(state),
-        Path((namespace.clone(), pod_name.to_string())),
-        Query(portforward_spdy::PortForwardQuery { ports: query.ports }),
-        headers,
-    )
-    .await
-}
\ No newline at end of file
+    // Forward to it as the pod's own port-forward would
+    let pod = selected_pod.clone();
+    match &state.streaming {
+        Some(streaming) => Ok(streaming.redirect(StreamRequest::PortForward { pod })),
+        None => Ok(super::portforward::upgrade(request, pod).await),
+    }
+}
@@ -1,30 +1,26 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    response::Json,
+    response::{Json, Response},
 };
-use serde::Deserialize;
 use serde_json::Value;
 
+use super::error_status::{ApiError, Cause};
+use super::handlers::{list_error, ListParams};
 use super::server::AppState;
-
-#[derive(Deserialize)]
-pub struct ListParams {
-    #[serde(rename = "labelSelector")]
-    label_selector: Option<String>,
-    limit: Option<i32>,
-}
+use super::watch::list_or_watch;
 
 // List all ServiceAccounts across namespaces
 pub async fn list_all_serviceaccounts(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.serviceaccounts().list(None).await {
-        Ok(sas) => Ok(Json(sas)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "serviceaccounts", None, &params, &selector, state.storage.serviceaccounts().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list all service accounts: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -33,13 +29,14 @@ pub async fn list_all_serviceaccounts(
 pub async fn list_serviceaccounts(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.serviceaccounts().list(Some(&namespace)).await {
-        Ok(sas) => Ok(Json(sas)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "serviceaccounts", Some(&namespace), &params, &selector, state.storage.serviceaccounts().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list service accounts: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -48,12 +45,14 @@ pub async fn create_serviceaccount(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(sa): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = sa["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.serviceaccounts().create(&namespace, sa).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("serviceaccounts", &name)),
         Err(e) => {
             tracing::error!("Failed to create service account: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -61,13 +60,13 @@ pub async fn create_serviceaccount(
 pub async fn get_serviceaccount(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.serviceaccounts().get(&namespace, &name).await {
         Ok(Some(sa)) => Ok(Json(sa)),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Ok(None) => Err(ApiError::not_found("serviceaccounts", &name)),
         Err(e) => {
             tracing::error!("Failed to get service account: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -76,12 +75,12 @@ pub async fn update_serviceaccount(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(sa): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.serviceaccounts().update(&namespace, &name, sa).await {
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             tracing::error!("Failed to update service account: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -90,14 +89,14 @@ pub async fn patch_serviceaccount(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(patch): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // Get current ServiceAccount
     let current = match state.storage.serviceaccounts().get(&namespace, &name).await {
         Ok(Some(sa)) => sa,
-        Ok(None) => return Err(StatusCode::NOT_FOUND),
+        Ok(None) => return Err(ApiError::not_found("serviceaccounts", &name)),
         Err(e) => {
             tracing::error!("Failed to get service account for patch: {}", e);
-            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            return Err(ApiError::internal(&e));
         }
     };
 
@@ -116,7 +115,7 @@ pub async fn patch_serviceaccount(
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             tracing::error!("Failed to patch service account: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -124,12 +123,12 @@ pub async fn patch_serviceaccount(
 pub async fn delete_serviceaccount(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.serviceaccounts().delete(&namespace, &name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             tracing::error!("Failed to delete service account: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -139,12 +138,15 @@ pub async fn create_serviceaccount_token(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(token_request): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     match state.storage.serviceaccounts().create_token(&namespace, &name, token_request).await {
         Ok(token_response) => Ok((StatusCode::CREATED, Json(token_response))),
+        Err(e) if e.to_string().contains("not found") => Err(ApiError::not_found("serviceaccounts", &name)),
+        Err(e) if e.to_string().contains("Invalid value") => Err(ApiError::invalid("serviceaccounts", &name, vec![Cause::parse(&e.to_string())])),
+        Err(e) if e.to_string().contains("does not match") => Err(ApiError::conflict("serviceaccounts", &name, e)),
         Err(e) => {
             tracing::error!("Failed to create service account token: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
\ No newline at end of file
@@ -1,455 +1,329 @@
-// SPDY/3.1 protocol implementation for kubectl port-forward
-// kubectl uses SPDY/3.1, not WebSockets, for port-forwarding
-
-use axum::{
-    body::Body,
-    extract::{Path, Query, State},
-    http::{header, HeaderMap, Request, Response, StatusCode},
-    response::IntoResponse,
-};
-use bytes::{Buf, BufMut, Bytes, BytesMut};
-use futures::{SinkExt, StreamExt};
-use serde::Deserialize;
-use std::collections::HashMap;
-use std::pin::Pin;
-use std::sync::Arc;
-use std::task::{Context, Poll};
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
-use tokio::net::TcpStream;
-use tokio::sync::{mpsc, Mutex};
-use tracing::{debug, error, info, warn};
-
-use super::server::AppState;
+// SPDY/3.1 framing, as Kubernetes' port-forward protocol runs over it: a
+// connection carries many streams, each opened by a SYN_STREAM whose headers
+// say what it's for, then carrying DATA frames until each side sends one with
+// FIN. Header blocks are compressed with zlib, one stream per direction for
+// the whole connection, primed with SPDY's dictionary of common header text.
+// Flow control isn't used by Kubernetes' clients, so WINDOW_UPDATE is read
+// and ignored like SETTINGS.
+use anyhow::{anyhow, bail, Result};
+use bytes::{Buf, BufMut, BytesMut};
+use flate2::{Compress, Compression, Decompress, FlushCompress, FlushDecompress, Status};
+use std::sync::OnceLock;
+
+const VERSION: u16 = 3;
+const CONTROL: u32 = 0x8000_0000;
+const FLAG_FIN: u8 = 0x01;
+
+const SYN_STREAM: u16 = 1;
+const SYN_REPLY: u16 = 2;
+const RST_STREAM: u16 = 3;
+const SETTINGS: u16 = 4;
+const PING: u16 = 6;
+const GOAWAY: u16 = 7;
+const HEADERS: u16 = 8;
+const WINDOW_UPDATE: u16 = 9;
+
+/// RST_STREAM status for a stream that can't be made sense of.
+pub const PROTOCOL_ERROR: u32 = 1;
+/// RST_STREAM status for a stream refused before anything was done with it.
+pub const REFUSED_STREAM: u32 = 3;
+
+/// A stream's headers, names in lower case.
+pub type Headers = Vec<(String, String)>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Frame {
+    SynStream { stream_id: u32, fin: bool, headers: Headers },
+    SynReply { stream_id: u32, fin: bool, headers: Headers },
+    RstStream { stream_id: u32, status: u32 },
+    Settings,
+    Ping { id: u32 },
+    GoAway { last_stream_id: u32, status: u32 },
+    Headers { stream_id: u32, headers: Headers },
+    WindowUpdate { stream_id: u32, delta: u32 },
+    Data { stream_id: u32, fin: bool, data: Vec<u8> },
+}
 
-// SPDY frame types
-const DATA_FRAME: u8 = 0x00;
-const SYN_STREAM: u8 = 0x01;
-const SYN_REPLY: u8 = 0x02;
-const RST_STREAM: u8 = 0x03;
-const SETTINGS: u8 = 0x04;
-const PING: u8 = 0x06;
-const GOAWAY: u8 = 0x07;
-const HEADERS: u8 = 0x08;
-const WINDOW_UPDATE: u8 = 0x09;
+/// The value of a header, if the stream has it.
+pub fn header<'a>(headers: &'a Headers, name: &str) -> Option<&'a str> {
+    headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+}
 
-// Stream IDs for port-forwarding
-const ERROR_STREAM_ID: u32 = 0;
-const STDIN_STREAM_ID: u32 = 1;
-const STDOUT_STREAM_ID: u32 = 2;
+// The dictionary both sides prime their header compression with: SPDY/3's
+// common header names and values, each but the last run of text prefixed
+// with its length
+fn dictionary() -> &'static [u8] {
+    static DICTIONARY: OnceLock<Vec<u8>> = OnceLock::new();
+    DICTIONARY.get_or_init(|| {
+        const WORDS: &[&str] = &[
+            "options", "head", "post", "put", "delete", "trace", "accept", "accept-charset", "accept-encoding",
+            "accept-language", "accept-ranges", "age", "allow", "authorization", "cache-control", "connection",
+            "content-base", "content-encoding", "content-language", "content-length", "content-location",
+            "content-md5", "content-range", "content-type", "date", "etag", "expect", "expires", "from", "host",
+            "if-match", "if-modified-since", "if-none-match", "if-range", "if-unmodified-since", "last-modified",
+            "location", "max-forwards", "pragma", "proxy-authenticate", "proxy-authorization", "range", "referer",
+            "retry-after", "server", "te", "trailer", "transfer-encoding", "upgrade", "user-agent", "vary", "via",
+            "warning", "www-authenticate", "method", "get", "status", "200 OK", "version", "HTTP/1.1", "url",
+            "public", "set-cookie", "keep-alive", "origin",
+        ];
+        const TEXT: &str = concat!(
+            "100101201202205206300302303304305306307402405406407408409410411412413414415416417502504505",
+            "203 Non-Authoritative Information204 No Content301 Moved Permanently400 Bad Request401 Unauthorized",
+            "403 Forbidden404 Not Found500 Internal Server Error501 Not Implemented503 Service Unavailable",
+            "Jan Feb Mar Apr May Jun Jul Aug Sept Oct Nov Dec 00:00:00 Mon, Tue, Wed, Thu, Fri, Sat, Sun, GMT",
+            "chunked,text/html,image/png,image/jpg,image/gif,application/xml,application/xhtml+xml,text/plain,",
+            "text/javascript,publicprivatemax-age=gzip,deflate,sdchcharset=utf-8charset=iso-8859-1,utf-,*,enq=0.",
+        );
+        let mut dictionary = Vec::new();
+        for word in WORDS {
+            dictionary.put_u32(word.len() as u32);
+            dictionary.put_slice(word.as_bytes());
+        }
+        dictionary.put_slice(TEXT.as_bytes());
+        dictionary
+    })
+}
 
-#[derive(Debug, Clone)]
-pub struct SpdyFrame {
-    pub stream_id: u32,
-    pub flags: u8,
-    pub data: Vec<u8>,
+/// Reads frames off one side of a connection, however its bytes arrive.
+pub struct Decoder {
+    buffer: BytesMut,
+    inflate: Decompress,
 }
 
-impl SpdyFrame {
-    pub fn new_data(stream_id: u32, data: Vec<u8>) -> Self {
-        Self {
-            stream_id,
-            flags: 0,
-            data,
-        }
+impl Default for Decoder {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    pub fn new_error(error_msg: String) -> Self {
+impl Decoder {
+    pub fn new() -> Self {
         Self {
-            stream_id: ERROR_STREAM_ID,
-            flags: 0,
-            data: error_msg.into_bytes(),
+            buffer: BytesMut::new(),
+            inflate: Decompress::new(true),
         }
     }
 
-    // Serialize frame to bytes for sending
-    pub fn to_bytes(&self) -> Vec<u8> {
-        let mut buf = Vec::new();
-        
-        // Data frame format (simplified for port-forward):
-        // [stream_id:4][flags:1][length:3][data:length]
-        buf.put_u32(self.stream_id);
-        buf.put_u8(self.flags);
-        buf.put_u8(((self.data.len() >> 16) & 0xFF) as u8);
-        buf.put_u16((self.data.len() & 0xFFFF) as u16);
-        buf.extend_from_slice(&self.data);
-        
-        buf
+    /// Takes bytes read off the connection.
+    pub fn feed(&mut self, bytes: &[u8]) {
+        self.buffer.extend_from_slice(bytes);
     }
 
-    // Parse frame from bytes
-    pub fn from_bytes(data: &[u8]) -> Result<Self, String> {
-        if data.len() < 8 {
-            return Err("Frame too short".to_string());
+    /// The next whole frame fed, if there is one yet. Control frames of
+    /// types SPDY/3.1 doesn't have are skipped.
+    pub fn next_frame(&mut self) -> Result<Option<Frame>> {
+        loop {
+            if self.buffer.len() < 8 {
+                return Ok(None);
+            }
+            let length = u32::from_be_bytes([0, self.buffer[5], self.buffer[6], self.buffer[7]]) as usize;
+            if self.buffer.len() < 8 + length {
+                return Ok(None);
+            }
+            let first = self.buffer.get_u32();
+            let flags = self.buffer.get_u8();
+            self.buffer.advance(3);
+            let mut payload = self.buffer.split_to(length);
+            let fin = flags & FLAG_FIN != 0;
+
+            if first & CONTROL == 0 {
+                return Ok(Some(Frame::Data { stream_id: first, fin, data: payload.to_vec() }));
+            }
+            let version = ((first >> 16) & 0x7fff) as u16;
+            if version != VERSION {
+                bail!("unsupported SPDY version {}", version);
+            }
+            let frame = match (first & 0xffff) as u16 {
+                SYN_STREAM => {
+                    let stream_id = read_u32(&mut payload)? & !CONTROL;
+                    // The associated stream, priority and slot
+                    read_u32(&mut payload)?;
+                    read_u16(&mut payload)?;
+                    Frame::SynStream { stream_id, fin, headers: self.headers(&payload)? }
+                }
+                SYN_REPLY => {
+                    let stream_id = read_u32(&mut payload)? & !CONTROL;
+                    Frame::SynReply { stream_id, fin, headers: self.headers(&payload)? }
+                }
+                RST_STREAM => Frame::RstStream {
+                    stream_id: read_u32(&mut payload)? & !CONTROL,
+                    status: read_u32(&mut payload)?,
+                },
+                SETTINGS => Frame::Settings,
+                PING => Frame::Ping { id: read_u32(&mut payload)? },
+                GOAWAY => Frame::GoAway {
+                    last_stream_id: read_u32(&mut payload)? & !CONTROL,
+                    status: read_u32(&mut payload).unwrap_or(0),
+                },
+                HEADERS => {
+                    let stream_id = read_u32(&mut payload)? & !CONTROL;
+                    Frame::Headers { stream_id, headers: self.headers(&payload)? }
+                }
+                WINDOW_UPDATE => Frame::WindowUpdate {
+                    stream_id: read_u32(&mut payload)? & !CONTROL,
+                    delta: read_u32(&mut payload)?,
+                },
+                _ => continue,
+            };
+            return Ok(Some(frame));
         }
+    }
 
-        let mut cursor = &data[..];
-        let stream_id = cursor.get_u32();
-        let flags = cursor.get_u8();
-        let len_high = cursor.get_u8() as usize;
-        let len_low = cursor.get_u16() as usize;
-        let length = (len_high << 16) | len_low;
-
-        if data.len() < 8 + length {
-            return Err("Frame data incomplete".to_string());
+    // Inflates a header block and reads its name/value pairs
+    fn headers(&mut self, compressed: &[u8]) -> Result<Headers> {
+        let mut block = Vec::with_capacity(compressed.len() * 4);
+        let mut consumed = 0;
+        loop {
+            if block.capacity() - block.len() < 1024 {
+                block.reserve(4096);
+            }
+            let (before, written) = (self.inflate.total_in(), block.len());
+            let result = self.inflate.decompress_vec(&compressed[consumed..], &mut block, FlushDecompress::Sync);
+            consumed += (self.inflate.total_in() - before) as usize;
+            match result {
+                Ok(Status::StreamEnd) => break,
+                Ok(_) if consumed == compressed.len() && block.capacity() > block.len() => break,
+                Ok(_) if self.inflate.total_in() == before && block.len() == written => {
+                    bail!("truncated header block")
+                }
+                Ok(_) => {}
+                Err(e) if e.needs_dictionary().is_some() => {
+                    self.inflate.set_dictionary(dictionary())?;
+                }
+                Err(e) => return Err(anyhow!("corrupt header block: {}", e)),
+            }
         }
 
-        let frame_data = data[8..8 + length].to_vec();
-
-        Ok(SpdyFrame {
-            stream_id,
-            flags,
-            data: frame_data,
-        })
+        let mut block = &block[..];
+        let count = read_u32(&mut block)?;
+        let mut headers = Vec::new();
+        for _ in 0..count {
+            let name = read_string(&mut block)?.to_ascii_lowercase();
+            let value = read_string(&mut block)?;
+            headers.push((name, value));
+        }
+        Ok(headers)
     }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct PortForwardQuery {
-    pub ports: Option<String>,
-}
-
-#[derive(Debug, Clone)]
-struct PortMapping {
-    local_port: u16,
-    remote_port: u16,
-    stream_base: u32,  // Base stream ID for this port
+/// Writes frames for one side of a connection.
+pub struct Encoder {
+    deflate: Compress,
 }
 
-pub async fn spdy_portforward_handler(
-    State(state): State<AppState>,
-    Path((namespace, name)): Path<(String, String)>,
-    Query(query): Query<PortForwardQuery>,
-    req: Request<Body>,
-) -> Result<Response<Body>, StatusCode> {
-    let headers = req.headers();
-    
-    info!("SPDY port-forward request for pod {}/{}", namespace, name);
-    debug!("Query params: {:?}", query);
-    debug!("Headers: {:?}", headers);
-    
-    // Check for SPDY upgrade
-    let upgrade_header = headers
-        .get(header::UPGRADE)
-        .and_then(|v| v.to_str().ok())
-        .unwrap_or("");
-    
-    if !upgrade_header.contains("SPDY/3.1") {
-        warn!("Missing SPDY/3.1 upgrade header");
-        return Err(StatusCode::BAD_REQUEST);
+impl Default for Encoder {
+    fn default() -> Self {
+        Self::new()
     }
+}
 
-    // Parse ports
-    let ports = parse_port_mappings(&query.ports.unwrap_or_default());
-    if ports.is_empty() {
-        warn!("No ports specified");
-        return Err(StatusCode::BAD_REQUEST);
-    }
-
-    // Verify pod exists and is running
-    let pod = state
-        .storage
-        .pods()
-        .get(&namespace, &name)
-        .await
-        .map_err(|e| {
-            error!("Failed to get pod: {}", e);
-            StatusCode::NOT_FOUND
-        })?;
-
-    let status = pod
-        .get("status")
-        .and_then(|s| s.as_object())
-        .ok_or_else(|| {
-            warn!("Pod has no status");
-            StatusCode::CONFLICT
-        })?;
-    
-    let phase = status
-        .get("phase")
-        .and_then(|p| p.as_str())
-        .ok_or_else(|| {
-            warn!("Pod has no phase");
-            StatusCode::CONFLICT
-        })?;
-    
-    if phase != "Running" {
-        warn!("Pod is not running, phase: {}", phase);
-        return Err(StatusCode::CONFLICT);
+impl Encoder {
+    pub fn new() -> Self {
+        let mut deflate = Compress::new(Compression::default(), true);
+        deflate.set_dictionary(dictionary()).expect("the dictionary is set before anything is compressed");
+        Self { deflate }
     }
 
-    // Get container connection info
-    let container = get_container_connection(&namespace, &name)
-        .await
-        .ok_or_else(|| {
-            error!("Failed to get container info");
-            StatusCode::INTERNAL_SERVER_ERROR
-        })?;
-
-    info!("Setting up SPDY port-forward for pod {}/{} with ports: {:?}", 
-         namespace, name, ports);
-
-    // Create response with upgrade
-    let response = Response::builder()
-        .status(StatusCode::SWITCHING_PROTOCOLS)
-        .header(header::UPGRADE, "SPDY/3.1")
-        .header(header::CONNECTION, "Upgrade")
-        .body(Body::empty())
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    // Spawn handler for the upgraded connection
-    tokio::spawn(handle_spdy_connection(container, ports));
-
-    Ok(response)
-}
-
-fn parse_port_mappings(ports_str: &str) -> Vec<PortMapping> {
-    let mut mappings = Vec::new();
-    let mut stream_base = 0u32;
-    
-    for port_spec in ports_str.split(',') {
-        let parts: Vec<&str> = port_spec.split(':').collect();
-        let mapping = match parts.len() {
-            1 => {
-                if let Ok(port) = parts[0].parse::<u16>() {
-                    Some(PortMapping {
-                        local_port: port,
-                        remote_port: port,
-                        stream_base,
-                    })
-                } else {
-                    None
-                }
+    /// The bytes of a frame.
+    pub fn encode(&mut self, frame: &Frame) -> Vec<u8> {
+        let fin = |fin: bool| if fin { FLAG_FIN } else { 0 };
+        let (kind, flags, payload) = match frame {
+            Frame::Data { stream_id, fin: last, data } => {
+                let mut bytes = Vec::with_capacity(8 + data.len());
+                bytes.put_u32(stream_id & !CONTROL);
+                bytes.put_u8(fin(*last));
+                bytes.put_slice(&(data.len() as u32).to_be_bytes()[1..]);
+                bytes.put_slice(data);
+                return bytes;
             }
-            2 => {
-                if let (Ok(local), Ok(remote)) = (parts[0].parse::<u16>(), parts[1].parse::<u16>()) {
-                    Some(PortMapping {
-                        local_port: local,
-                        remote_port: remote,
-                        stream_base,
-                    })
-                } else {
-                    None
-                }
+            Frame::SynStream { stream_id, fin: last, headers } => {
+                let mut payload = Vec::new();
+                payload.put_u32(*stream_id);
+                payload.put_u32(0);
+                payload.put_u16(0);
+                payload.extend(self.headers(headers));
+                (SYN_STREAM, fin(*last), payload)
+            }
+            Frame::SynReply { stream_id, fin: last, headers } => {
+                let mut payload = Vec::new();
+                payload.put_u32(*stream_id);
+                payload.extend(self.headers(headers));
+                (SYN_REPLY, fin(*last), payload)
+            }
+            Frame::Headers { stream_id, headers } => {
+                let mut payload = Vec::new();
+                payload.put_u32(*stream_id);
+                payload.extend(self.headers(headers));
+                (HEADERS, 0, payload)
+            }
+            Frame::RstStream { stream_id, status } => (RST_STREAM, 0, [stream_id.to_be_bytes(), status.to_be_bytes()].concat()),
+            Frame::Settings => (SETTINGS, 0, 0u32.to_be_bytes().to_vec()),
+            Frame::Ping { id } => (PING, 0, id.to_be_bytes().to_vec()),
+            Frame::GoAway { last_stream_id, status } => {
+                (GOAWAY, 0, [last_stream_id.to_be_bytes(), status.to_be_bytes()].concat())
+            }
+            Frame::WindowUpdate { stream_id, delta } => {
+                (WINDOW_UPDATE, 0, [stream_id.to_be_bytes(), delta.to_be_bytes()].concat())
             }
-            _ => None,
         };
-        
-        if let Some(mapping) = mapping {
-            mappings.push(mapping);
-            stream_base += 2; // Each port uses 2 streams (data + error)
-        }
+        let mut bytes = Vec::with_capacity(8 + payload.len());
+        bytes.put_u32(CONTROL | (VERSION as u32) << 16 | kind as u32);
+        bytes.put_u8(flags);
+        bytes.put_slice(&(payload.len() as u32).to_be_bytes()[1..]);
+        bytes.extend(payload);
+        bytes
     }
-    
-    mappings
-}
 
-#[derive(Clone)]
-struct ContainerConnection {
-    ip: String,
-    namespace: String,
-    pod_name: String,
-}
-
-async fn get_container_connection(namespace: &str, name: &str) -> Option<ContainerConnection> {
-    let docker = bollard::Docker::connect_with_local_defaults().ok()?;
-    
-    // Find container by pod labels
-    let mut filters = HashMap::new();
-    filters.insert(
-        "label".to_string(),
-        vec![
-            format!("io.kubernetes.pod.name={}", name),
-            format!("io.kubernetes.pod.namespace={}", namespace),
-        ],
-    );
-    
-    let options = bollard::container::ListContainersOptions {
-        all: true,
-        filters,
-        ..Default::default()
-    };
-    
-    let containers = docker.list_containers(Some(options)).await.ok()?;
-    let container = containers.first()?;
-    let container_id = container.id.clone()?;
-    
-    // Get container IP
-    let inspect = docker.inspect_container(&container_id, None).await.ok()?;
-    let ip = inspect
-        .network_settings?
-        .networks?
-        .get("bridge")?
-        .ip_address
-        .clone()?;
-    
-    Some(ContainerConnection {
-        ip,
-        namespace: namespace.to_string(),
-        pod_name: name.to_string(),
-    })
-}
-
-async fn handle_spdy_connection(
-    container: ContainerConnection,
-    ports: Vec<PortMapping>,
-) {
-    info!("Handling SPDY connection for {}/{}", container.namespace, container.pod_name);
-    
-    // For each port, create TCP connection to container
-    for port_mapping in ports {
-        let container_ip = container.ip.clone();
-        tokio::spawn(handle_port_stream(port_mapping, container_ip));
-    }
-}
+    // Writes the name/value pairs of a header block and deflates them,
+    // flushing so the other side can read the block on its own
+    fn headers(&mut self, headers: &Headers) -> Vec<u8> {
+        let mut block = Vec::new();
+        block.put_u32(headers.len() as u32);
+        for (name, value) in headers {
+            block.put_u32(name.len() as u32);
+            block.put_slice(name.to_ascii_lowercase().as_bytes());
+            block.put_u32(value.len() as u32);
+            block.put_slice(value.as_bytes());
+        }
 
-async fn handle_port_stream(port_mapping: PortMapping, container_ip: String) {
-    let endpoint = format!("{}:{}", container_ip, port_mapping.remote_port);
-    
-    match TcpStream::connect(&endpoint).await {
-        Ok(mut stream) => {
-            info!("Connected to container port {}", port_mapping.remote_port);
-            
-            // Handle bidirectional streaming
-            let (mut read_half, mut write_half) = tokio::io::split(stream);
-            
-            // Read from container and send SPDY frames
-            let read_task = tokio::spawn(async move {
-                let mut buffer = vec![0u8; 8192];
-                loop {
-                    match read_half.read(&mut buffer).await {
-                        Ok(0) => break,
-                        Ok(n) => {
-                            let frame = SpdyFrame::new_data(
-                                port_mapping.stream_base + STDOUT_STREAM_ID,
-                                buffer[..n].to_vec(),
-                            );
-                            // Send frame to client (would need connection handle here)
-                            debug!("Read {} bytes from container", n);
-                        }
-                        Err(e) => {
-                            error!("Read error: {}", e);
-                            break;
-                        }
-                    }
-                }
-            });
-            
-            // Write to container from SPDY frames
-            let write_task = tokio::spawn(async move {
-                // Receive frames and write to container (would need receiver here)
-                debug!("Write task started for port {}", port_mapping.remote_port);
-            });
-            
-            tokio::select! {
-                _ = read_task => {},
-                _ = write_task => {},
+        let mut compressed = Vec::with_capacity(block.len() + 64);
+        let mut consumed = 0;
+        loop {
+            if compressed.capacity() - compressed.len() < 64 {
+                compressed.reserve(1024);
+            }
+            let before = self.deflate.total_in();
+            self.deflate
+                .compress_vec(&block[consumed..], &mut compressed, FlushCompress::Sync)
+                .expect("deflating into memory can't fail");
+            consumed += (self.deflate.total_in() - before) as usize;
+            if consumed == block.len() && compressed.capacity() > compressed.len() {
+                return compressed;
             }
-        }
-        Err(e) => {
-            error!("Failed to connect to container port {}: {}", port_mapping.remote_port, e);
-            let error_frame = SpdyFrame::new_error(format!("Connection failed: {}", e));
-            // Send error frame to client
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    #[test]
-    fn test_spdy_frame_serialization() {
-        let frame = SpdyFrame::new_data(1, b"test data".to_vec());
-        let bytes = frame.to_bytes();
-        
-        assert_eq!(bytes.len(), 8 + 9); // Header + data
-        assert_eq!(&bytes[0..4], &[0, 0, 0, 1]); // Stream ID
-        assert_eq!(bytes[4], 0); // Flags
-        assert_eq!(&bytes[5..8], &[0, 0, 9]); // Length
-        assert_eq!(&bytes[8..], b"test data");
-    }
-
-    #[test]
-    fn test_spdy_frame_deserialization() {
-        let data = vec![
-            0, 0, 0, 2,  // Stream ID = 2
-            0,            // Flags = 0
-            0, 0, 5,      // Length = 5
-            b'h', b'e', b'l', b'l', b'o',  // Data
-        ];
-        
-        let frame = SpdyFrame::from_bytes(&data).unwrap();
-        assert_eq!(frame.stream_id, 2);
-        assert_eq!(frame.flags, 0);
-        assert_eq!(frame.data, b"hello");
-    }
-
-    #[test]
-    fn test_spdy_frame_error() {
-        let error_frame = SpdyFrame::new_error("Test error".to_string());
-        assert_eq!(error_frame.stream_id, ERROR_STREAM_ID);
-        assert_eq!(error_frame.data, b"Test error");
-    }
-
-    #[test]
-    fn test_parse_port_mappings() {
-        // Single port
-        let ports = parse_port_mappings("8080");
-        assert_eq!(ports.len(), 1);
-        assert_eq!(ports[0].local_port, 8080);
-        assert_eq!(ports[0].remote_port, 8080);
-        assert_eq!(ports[0].stream_base, 0);
-
-        // Port mapping
-        let ports = parse_port_mappings("8080:80");
-        assert_eq!(ports.len(), 1);
-        assert_eq!(ports[0].local_port, 8080);
-        assert_eq!(ports[0].remote_port, 80);
-
-        // Multiple ports
-        let ports = parse_port_mappings("8080:80,9090:90");
-        assert_eq!(ports.len(), 2);
-        assert_eq!(ports[0].local_port, 8080);
-        assert_eq!(ports[0].remote_port, 80);
-        assert_eq!(ports[0].stream_base, 0);
-        assert_eq!(ports[1].local_port, 9090);
-        assert_eq!(ports[1].remote_port, 90);
-        assert_eq!(ports[1].stream_base, 2);
-
-        // Invalid port
-        let ports = parse_port_mappings("invalid");
-        assert_eq!(ports.len(), 0);
-    }
-
-    #[test]
-    fn test_frame_too_short() {
-        let data = vec![0, 0, 0, 1]; // Only 4 bytes
-        let result = SpdyFrame::from_bytes(&data);
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Frame too short");
+fn read_u16(bytes: &mut impl Buf) -> Result<u16> {
+    if bytes.remaining() < 2 {
+        bail!("frame too short");
     }
+    Ok(bytes.get_u16())
+}
 
-    #[test]
-    fn test_frame_incomplete_data() {
-        let data = vec![
-            0, 0, 0, 1,  // Stream ID
-            0,            // Flags
-            0, 0, 10,     // Length = 10
-            b'h', b'i',   // Only 2 bytes of data, but length says 10
-        ];
-        let result = SpdyFrame::from_bytes(&data);
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Frame data incomplete");
+fn read_u32(bytes: &mut impl Buf) -> Result<u32> {
+    if bytes.remaining() < 4 {
+        bail!("frame too short");
     }
+    Ok(bytes.get_u32())
+}
 
-    #[tokio::test]
-    async fn test_port_mapping_stream_assignment() {
-        let ports = parse_port_mappings("8080,8081,8082");
-        assert_eq!(ports.len(), 3);
-        
-        // Each port should get unique stream IDs
-        assert_eq!(ports[0].stream_base, 0);
-        assert_eq!(ports[1].stream_base, 2);
-        assert_eq!(ports[2].stream_base, 4);
+fn read_string(bytes: &mut &[u8]) -> Result<String> {
+    let length = read_u32(bytes)? as usize;
+    if bytes.len() < length {
+        bail!("header block too short");
     }
-}
\ No newline at end of file
+    let (string, rest) = bytes.split_at(length);
+    *bytes = rest;
+    Ok(String::from_utf8_lossy(string).into_owned())
+}
@@ -1,21 +1,27 @@
 use axum::{
-    extract::{Path, State},
-    http::StatusCode,
+    extract::{Path, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
     Json,
 };
 use serde_json::{json, Value};
 use tracing::{error, info};
 
+use crate::api::error_status::ApiError;
+use crate::api::handlers::{list_error, ListParams};
+use crate::api::patch;
 use crate::api::server::AppState;
+use crate::api::watch::list_or_watch;
+use crate::models::replicas;
 
 pub async fn create_statefulset(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
     Json(mut statefulset): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
     // Validate kind
     if statefulset.get("kind").and_then(|k| k.as_str()) != Some("StatefulSet") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("StatefulSet", &statefulset));
     }
 
     // Ensure metadata exists
@@ -29,15 +35,17 @@ pub async fn create_statefulset(
         namespace
     );
 
+    let name = statefulset["metadata"]["name"].as_str().unwrap_or_default().to_string();
+
     match state.storage.statefulsets().create(&namespace, statefulset).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
         Err(e) => {
             if e.to_string().contains("UNIQUE constraint failed") {
                 error!("StatefulSet already exists: {}", e);
-                Err(StatusCode::CONFLICT)
+                Err(ApiError::already_exists("statefulsets", &name))
             } else {
                 error!("Failed to create StatefulSet: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -46,17 +54,17 @@ pub async fn create_statefulset(
 pub async fn get_statefulset(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Getting StatefulSet {} in namespace {}", name, namespace);
 
     match state.storage.statefulsets().get(&namespace, &name).await {
         Ok(statefulset) => Ok(Json(statefulset)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("statefulsets", &name))
             } else {
                 error!("Failed to get StatefulSet: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -65,26 +73,32 @@ pub async fn get_statefulset(
 pub async fn list_statefulsets(
     State(state): State<AppState>,
     Path(namespace): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing StatefulSets in namespace {}", namespace);
 
-    match state.storage.statefulsets().list(Some(&namespace)).await {
-        Ok(list) => Ok(Json(list)),
+    let selector = params.selector()?;
+    match list_or_watch(&state, "statefulsets", Some(&namespace), &params, &selector, state.storage.statefulsets().list_matching(Some(&namespace), &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list StatefulSets: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
 
-pub async fn list_all_statefulsets(State(state): State<AppState>) -> Result<Json<Value>, StatusCode> {
+pub async fn list_all_statefulsets(
+    State(state): State<AppState>,
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
     info!("Listing all StatefulSets");
 
-    match state.storage.statefulsets().list(None).await {
-        Ok(list) => Ok(Json(list)),
+    let selector = params.selector()?;
+    match list_or_watch(&state, "statefulsets", None, &params, &selector, state.storage.statefulsets().list_matching(None, &selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             error!("Failed to list all StatefulSets: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -93,10 +107,10 @@ pub async fn update_statefulset(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(statefulset): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // Validate kind
     if statefulset.get("kind").and_then(|k| k.as_str()) != Some("StatefulSet") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("StatefulSet", &statefulset));
     }
 
     info!("Updating StatefulSet {} in namespace {}", name, namespace);
@@ -105,43 +119,56 @@ pub async fn update_statefulset(
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("statefulsets", &name))
             } else {
                 error!("Failed to update StatefulSet: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
 }
 
+pub async fn patch_statefulset(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+    headers: HeaderMap,
+    Json(patch): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    info!("Patching StatefulSet {} in namespace {}", name, namespace);
+
+    let mut statefulset = match state.storage.statefulsets().get(&namespace, &name).await {
+        Ok(statefulset) => statefulset,
+        Err(e) if e.to_string().contains("not found") => return Err(ApiError::not_found("statefulsets", &name)),
+        Err(e) => {
+            error!("Failed to get StatefulSet: {}", e);
+            return Err(ApiError::internal(&e));
+        }
+    };
+    patch::apply(&headers, &mut statefulset, patch)?;
+
+    match state.storage.statefulsets().update(&namespace, &name, statefulset).await {
+        Ok(updated) => Ok(Json(updated)),
+        Err(e) => {
+            error!("Failed to patch StatefulSet: {}", e);
+            Err(ApiError::internal(&e))
+        }
+    }
+}
+
 pub async fn get_statefulset_scale(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Getting StatefulSet scale {} in namespace {}", name, namespace);
 
     match state.storage.statefulsets().get(&namespace, &name).await {
-        Ok(statefulset) => {
-            let scale = json!({
-                "apiVersion": "autoscaling/v1",
-                "kind": "Scale",
-                "metadata": statefulset["metadata"],
-                "spec": {
-                    "replicas": statefulset["spec"]["replicas"]
-                },
-                "status": {
-                    "replicas": statefulset["status"]["replicas"],
-                    "selector": statefulset["spec"]["selector"]
-                }
-            });
-            Ok(Json(scale))
-        }
+        Ok(statefulset) => Ok(Json(replicas::scale(&statefulset))),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("statefulsets", &name))
             } else {
                 error!("Failed to get StatefulSet scale: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -151,15 +178,13 @@ pub async fn update_statefulset_scale(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
     Json(scale): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     // Validate kind
     if scale.get("kind").and_then(|k| k.as_str()) != Some("Scale") {
-        return Err(StatusCode::BAD_REQUEST);
+        return Err(ApiError::wrong_kind("Scale", &scale));
     }
 
-    let replicas = scale["spec"]["replicas"]
-        .as_i64()
-        .ok_or(StatusCode::BAD_REQUEST)?;
+    let replicas = replicas::requested(&scale).ok_or_else(|| ApiError::bad_request("spec.replicas: Invalid value: must be a non-negative integer"))?;
 
     info!("Scaling StatefulSet {} in namespace {} to {} replicas", name, namespace, replicas);
 
@@ -167,10 +192,10 @@ pub async fn update_statefulset_scale(
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("statefulsets", &name))
             } else {
                 error!("Failed to scale StatefulSet: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -179,17 +204,43 @@ pub async fn update_statefulset_scale(
 pub async fn get_statefulset_status(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Getting StatefulSet status {} in namespace {}", name, namespace);
 
     match state.storage.statefulsets().get(&namespace, &name).await {
         Ok(statefulset) => Ok(Json(statefulset)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("statefulsets", &name))
             } else {
                 error!("Failed to get StatefulSet status: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
+            }
+        }
+    }
+}
+
+pub async fn update_statefulset_status(
+    State(state): State<AppState>,
+    Path((namespace, name)): Path<(String, String)>,
+    Json(status_update): Json<Value>,
+) -> Result<Json<Value>, ApiError> {
+    info!("Updating StatefulSet status {} in namespace {}", name, namespace);
+
+    let store = state.storage.statefulsets();
+    if let Err(e) = store.update_status(&namespace, &name, status_update["status"].clone()).await {
+        error!("Failed to update StatefulSet status: {}", e);
+        return Err(ApiError::internal(&e));
+    }
+
+    match store.get(&namespace, &name).await {
+        Ok(statefulset) => Ok(Json(statefulset)),
+        Err(e) => {
+            if e.to_string().contains("not found") {
+                Err(ApiError::not_found("statefulsets", &name))
+            } else {
+                error!("Failed to get updated StatefulSet: {}", e);
+                Err(ApiError::internal(&e))
             }
         }
     }
@@ -198,17 +249,17 @@ pub async fn get_statefulset_status(
 pub async fn delete_statefulset(
     State(state): State<AppState>,
     Path((namespace, name)): Path<(String, String)>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     info!("Deleting StatefulSet {} in namespace {}", name, namespace);
 
     match state.storage.statefulsets().delete(&namespace, &name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             if e.to_string().contains("not found") {
-                Err(StatusCode::NOT_FOUND)
+                Err(ApiError::not_found("statefulsets", &name))
             } else {
                 error!("Failed to delete StatefulSet: {}", e);
-                Err(StatusCode::INTERNAL_SERVER_ERROR)
+                Err(ApiError::internal(&e))
             }
         }
     }
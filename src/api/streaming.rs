@@ -0,0 +1,373 @@
+// Streaming server for exec, attach and port-forward, following the flow of a
+// kubelet's CRI streaming server: the API validates the request, stores it
+// under a random single-use token and redirects the client to this server's
+// own port, which upgrades the connection and runs the stream.
+use axum::{
+    extract::{
+        ws::{Message, WebSocket},
+        FromRequestParts, Path, Request, State, WebSocketUpgrade,
+    },
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::any,
+    Router,
+};
+use bollard::container::{AttachContainerOptions, LogOutput, ResizeContainerTtyOptions};
+use bollard::exec::{CreateExecOptions, ResizeExecOptions, StartExecResults};
+use bollard::Docker;
+use futures::{SinkExt, Stream, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+use tracing::{info, warn};
+
+use super::error_status::ApiError;
+use super::server::AppState;
+use crate::config::StreamingConfig;
+use crate::runtime::kubelet::docker_container_name;
+
+// Subprotocols of the channel-multiplexed exec and attach streams, newest first
+const CHANNEL_PROTOCOLS: [&str; 4] = [
+    "v4.channel.k8s.io",
+    "v3.channel.k8s.io",
+    "v2.channel.k8s.io",
+    "channel.k8s.io",
+];
+
+// Channel ids, the first byte of every message
+const STDIN: u8 = 0;
+const STDOUT: u8 = 1;
+const STDERR: u8 = 2;
+const ERROR: u8 = 3;
+const RESIZE: u8 = 4;
+
+/// A stream the API has validated and handed out a URL for.
+#[derive(Debug, Clone)]
+pub enum StreamRequest {
+    Exec {
+        container_id: String,
+        command: Vec<String>,
+        stdin: bool,
+        tty: bool,
+    },
+    Attach {
+        container_id: String,
+        stdin: bool,
+        tty: bool,
+    },
+    PortForward {
+        pod: Value,
+    },
+}
+
+impl StreamRequest {
+    /// An exec request from the query of `pods/exec`. Fails if the command is
+    /// missing or the pod has no such container.
+    pub fn exec(pod: &Value, params: &[(String, String)]) -> Result<Self, ApiError> {
+        let command: Vec<String> = params
+            .iter()
+            .filter(|(key, _)| key == "command")
+            .map(|(_, value)| value.clone())
+            .collect();
+        if command.is_empty() {
+            return Err(ApiError::bad_request("you must specify at least 1 command"));
+        }
+
+        Ok(Self::Exec {
+            container_id: container_id(pod, param(params, "container"))?,
+            command,
+            stdin: flag(params, "stdin"),
+            tty: flag(params, "tty"),
+        })
+    }
+
+    /// An attach request from the query of `pods/attach`.
+    pub fn attach(pod: &Value, params: &[(String, String)]) -> Result<Self, ApiError> {
+        Ok(Self::Attach {
+            container_id: container_id(pod, param(params, "container"))?,
+            stdin: flag(params, "stdin"),
+            tty: flag(params, "tty"),
+        })
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Self::Exec { .. } => "exec",
+            Self::Attach { .. } => "attach",
+            Self::PortForward { .. } => "portforward",
+        }
+    }
+}
+
+fn param<'a>(params: &'a [(String, String)], key: &str) -> Option<&'a str> {
+    params.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+fn flag(params: &[(String, String)], key: &str) -> bool {
+    matches!(param(params, key), Some("true" | "1"))
+}
+
+// Name of the Docker container backing the pod's container, defaulting to
+// the first one like kubectl does
+fn container_id(pod: &Value, container: Option<&str>) -> Result<String, ApiError> {
+    let pod_name = pod["metadata"]["name"].as_str().unwrap_or_default();
+    let containers = pod["spec"]["containers"].as_array().map_or(&[][..], |containers| containers);
+    let container = match container {
+        Some(name) => containers.iter().find(|c| c["name"] == name),
+        None => containers.first(),
+    }
+    .ok_or_else(|| match container {
+        Some(name) => ApiError::bad_request(format!("container {} is not valid for pod {}", name, pod_name)),
+        None => ApiError::bad_request(format!("pod {} has no containers", pod_name)),
+    })?;
+
+    Ok(docker_container_name(
+        container["name"].as_str().unwrap_or("container"),
+        pod_name,
+        pod["metadata"]["namespace"].as_str().unwrap_or_default(),
+        pod["metadata"]["uid"].as_str().unwrap_or_default(),
+    ))
+}
+
+/// Hands out single-use URLs for validated stream requests.
+pub struct StreamingServer {
+    base_url: String,
+    ttl: Duration,
+    requests: Mutex<HashMap<String, (StreamRequest, Instant)>>,
+}
+
+impl StreamingServer {
+    pub fn new(base_url: String, ttl: Duration) -> Self {
+        Self {
+            base_url,
+            ttl,
+            requests: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Stores the request and returns the URL that serves it.
+    pub fn url_for(&self, request: StreamRequest) -> String {
+        let kind = request.kind();
+        let token = uuid::Uuid::new_v4().simple().to_string();
+
+        let mut requests = self.requests.lock().unwrap();
+        requests.retain(|_, (_, created)| created.elapsed() < self.ttl);
+        requests.insert(token.clone(), (request, Instant::now()));
+
+        format!("{}/{}/{}", self.base_url, kind, token)
+    }
+
+    /// The request stored under `token`. A token can be used once, and only
+    /// until it expires.
+    fn take(&self, token: &str) -> Option<StreamRequest> {
+        let (request, created) = self.requests.lock().unwrap().remove(token)?;
+        (created.elapsed() < self.ttl).then_some(request)
+    }
+
+    /// The response sending a client to the URL for `request`.
+    pub fn redirect(&self, request: StreamRequest) -> Response {
+        (StatusCode::FOUND, [(header::LOCATION, self.url_for(request))]).into_response()
+    }
+}
+
+/// Binds the streaming port if streaming is enabled.
+pub async fn bind(config: &StreamingConfig) -> anyhow::Result<Option<(TcpListener, Arc<StreamingServer>)>> {
+    if !config.enabled {
+        return Ok(None);
+    }
+
+    let listener = TcpListener::bind((config.address.as_str(), config.port)).await?;
+    let base_url = format!("http://{}", listener.local_addr()?);
+    info!("Streaming server listening on {}", base_url);
+
+    let server = StreamingServer::new(base_url, Duration::from_secs(config.token_ttl_seconds));
+    Ok(Some((listener, Arc::new(server))))
+}
+
+pub fn router(state: AppState) -> Router {
+    Router::new()
+        .route("/:kind/:token", any(serve_stream))
+        .with_state(state)
+}
+
+async fn serve_stream(
+    State(state): State<AppState>,
+    Path((kind, token)): Path<(String, String)>,
+    request: Request,
+) -> Result<Response, ApiError> {
+    let streaming = state.streaming.as_ref().ok_or(StatusCode::NOT_FOUND)?;
+    let stream = streaming.take(&token).ok_or(StatusCode::NOT_FOUND)?;
+    if stream.kind() != kind {
+        return Err(StatusCode::NOT_FOUND.into());
+    }
+
+    // Port-forwards may upgrade to SPDY rather than a WebSocket
+    if let StreamRequest::PortForward { pod } = stream {
+        return Ok(super::portforward::upgrade(request, pod).await);
+    }
+    let (mut parts, _) = request.into_parts();
+    let ws = WebSocketUpgrade::from_request_parts(&mut parts, &state)
+        .await
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+    Ok(upgrade(ws, stream))
+}
+
+/// Runs an exec or attach over the upgraded connection, for when the API
+/// server serves streams itself rather than redirecting to this server.
+pub fn upgrade(ws: WebSocketUpgrade, request: StreamRequest) -> Response {
+    ws.protocols(CHANNEL_PROTOCOLS)
+        .on_upgrade(move |socket| run_channel_session(socket, request))
+}
+
+type Output = Pin<Box<dyn Stream<Item = Result<LogOutput, bollard::errors::Error>> + Send>>;
+type Input = Pin<Box<dyn AsyncWrite + Send>>;
+
+// Runs an exec or attach against Docker and relays it over the channel
+// protocol, ending with a Status on the error channel
+async fn run_channel_session(socket: WebSocket, request: StreamRequest) {
+    let (mut sender, mut receiver) = socket.split();
+
+    let status = match start(&request).await {
+        Ok((docker, exec_id, mut output, mut input)) => {
+            loop {
+                // Each side says whether the session is still open
+                let open = tokio::select! {
+                    chunk = output.next() => match chunk {
+                        Some(Ok(LogOutput::StdErr { message })) => sender.send(frame(STDERR, &message)).await.is_ok(),
+                        Some(Ok(chunk)) => sender.send(frame(STDOUT, &chunk.into_bytes())).await.is_ok(),
+                        Some(Err(e)) => {
+                            warn!("Stream output failed: {}", e);
+                            false
+                        }
+                        None => false,
+                    },
+                    msg = receiver.next() => match msg {
+                        Some(Ok(Message::Binary(data))) => match data.split_first() {
+                            Some((&STDIN, bytes)) => input.write_all(bytes).await.is_ok(),
+                            Some((&RESIZE, bytes)) => {
+                                resize(&docker, &request, exec_id.as_deref(), bytes).await;
+                                true
+                            }
+                            _ => true,
+                        },
+                        Some(Ok(Message::Close(_))) | None => false,
+                        _ => true,
+                    },
+                };
+                if !open {
+                    break;
+                }
+            }
+
+            match exec_id {
+                Some(exec_id) => exit_status(&docker, &exec_id).await,
+                None => success(),
+            }
+        }
+        Err(e) => failure(&e.to_string()),
+    };
+
+    let _ = sender.send(frame(ERROR, status.to_string().as_bytes())).await;
+    let _ = sender.close().await;
+}
+
+// Starts the exec or attaches to the container. The exec id is returned for
+// execs so their exit code can be read afterwards.
+async fn start(request: &StreamRequest) -> anyhow::Result<(Docker, Option<String>, Output, Input)> {
+    let docker = Docker::connect_with_local_defaults()?;
+
+    match request {
+        StreamRequest::Exec { container_id, command, stdin, tty } => {
+            let options = CreateExecOptions {
+                cmd: Some(command.clone()),
+                attach_stdin: Some(*stdin),
+                attach_stdout: Some(true),
+                attach_stderr: Some(true),
+                tty: Some(*tty),
+                ..Default::default()
+            };
+            let exec = docker.create_exec(container_id, options).await?;
+            match docker.start_exec(&exec.id, None).await? {
+                StartExecResults::Attached { output, input } => Ok((docker, Some(exec.id), output, input)),
+                StartExecResults::Detached => anyhow::bail!("exec started detached"),
+            }
+        }
+        StreamRequest::Attach { container_id, stdin, .. } => {
+            let options = AttachContainerOptions::<String> {
+                stdin: Some(*stdin),
+                stdout: Some(true),
+                stderr: Some(true),
+                stream: Some(true),
+                ..Default::default()
+            };
+            let attached = docker.attach_container(container_id, Some(options)).await?;
+            Ok((docker, None, attached.output, attached.input))
+        }
+        StreamRequest::PortForward { .. } => anyhow::bail!("port-forward is not a channel stream"),
+    }
+}
+
+// Terminal size messages are {"Width": .., "Height": ..}. They resize the
+// exec's terminal, or the container's when attached to one with a TTY.
+async fn resize(docker: &Docker, request: &StreamRequest, exec_id: Option<&str>, bytes: &[u8]) {
+    let Ok(size) = serde_json::from_slice::<Value>(bytes) else {
+        return;
+    };
+    let width = size["Width"].as_u64().unwrap_or(80) as u16;
+    let height = size["Height"].as_u64().unwrap_or(24) as u16;
+
+    let result = match (exec_id, request) {
+        (Some(exec_id), _) => docker.resize_exec(exec_id, ResizeExecOptions { width, height }).await,
+        (None, StreamRequest::Attach { container_id, tty: true, .. }) => {
+            docker
+                .resize_container_tty(container_id, ResizeContainerTtyOptions { width, height })
+                .await
+        }
+        _ => Ok(()),
+    };
+    if let Err(e) = result {
+        warn!("Failed to resize terminal: {}", e);
+    }
+}
+
+async fn exit_status(docker: &Docker, exec_id: &str) -> Value {
+    match docker.inspect_exec(exec_id).await {
+        Ok(inspect) => match inspect.exit_code {
+            Some(0) | None => success(),
+            Some(code) => json!({
+                "metadata": {},
+                "status": "Failure",
+                "message": format!("command terminated with non-zero exit code: {}", code),
+                "reason": "NonZeroExitCode",
+                "details": {
+                    "causes": [{ "reason": "ExitCode", "message": code.to_string() }]
+                }
+            }),
+        },
+        Err(e) => failure(&e.to_string()),
+    }
+}
+
+fn success() -> Value {
+    json!({ "metadata": {}, "status": "Success" })
+}
+
+fn failure(message: &str) -> Value {
+    json!({
+        "metadata": {},
+        "status": "Failure",
+        "message": message,
+        "reason": "InternalError"
+    })
+}
+
+fn frame(channel: u8, data: &[u8]) -> Message {
+    let mut message = Vec::with_capacity(data.len() + 1);
+    message.push(channel);
+    message.extend_from_slice(data);
+    Message::Binary(message)
+}
@@ -0,0 +1,139 @@
+// Strict mode, built with the strict-types feature. Objects created or
+// replaced through the API are first decoded into the k8s-openapi type of
+// their resource, as kube-apiserver decodes them into its Go types: a field
+// of the wrong type is refused with the path to it, and fields the type
+// doesn't have are refused, warned about or let through as the request's
+// fieldValidation says (Strict, Warn, the default, or Ignore). What's stored
+// is still the JSON that was sent. Without the feature nothing is decoded,
+// so any JSON the handlers can make sense of is taken, and faster.
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::{de::DeserializeOwned, Serialize};
+use serde_json::{json, Value};
+use tracing::error;
+
+use super::admission::api_version;
+use super::error_status::ApiError;
+use super::request_info::RequestInfo;
+use super::resource_types::resource_type;
+
+/// Decodes an object into a type and back, or says why it can't be.
+pub fn roundtrip<T: DeserializeOwned + Serialize>(object: &Value) -> Result<Value, String> {
+    let typed: T = serde_path_to_error::deserialize(object).map_err(|e| {
+        let path = e.path().to_string();
+        if path == "." {
+            e.inner().to_string()
+        } else {
+            format!("{}: {}", path, e.inner())
+        }
+    })?;
+    serde_json::to_value(typed).map_err(|e| e.to_string())
+}
+
+/// Middleware decoding the objects of creates and updates into their
+/// k8s-openapi types, refusing those that don't fit.
+pub async fn check_types(request: Request, next: Next) -> Response {
+    if !matches!(*request.method(), Method::POST | Method::PUT) {
+        return next.run(request).await;
+    }
+    let version = api_version(request.uri().path());
+    let info = RequestInfo::parse(request.method().as_str(), request.uri().path(), request.uri().query());
+    let RequestInfo::Resource { group, resource, subresource, .. } = &info else {
+        return next.run(request).await;
+    };
+    if !matches!(subresource.as_deref(), None | Some("status")) {
+        return next.run(request).await;
+    }
+    let Some(typed) = resource_type(group, &version, resource) else {
+        return next.run(request).await;
+    };
+    let (kind, group_version, decode) = (typed.kind, typed.api_version, typed.decode);
+    let validation = field_validation(request.uri().query()).to_string();
+
+    let (parts, body) = request.into_parts();
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to buffer request body: {}", e);
+            return ApiError::bad_request(e.to_string()).into_response();
+        }
+    };
+    // What isn't a JSON object is left to the handlers to refuse
+    let Ok(mut object) = serde_json::from_slice::<Value>(&bytes) else {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    };
+    if !object.is_object() {
+        return next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    }
+    // As kube-apiserver does, the kind and version default to the URL's
+    for (field, value) in [("apiVersion", group_version), ("kind", kind)] {
+        if object.get(field).is_none_or(Value::is_null) {
+            object[field] = json!(value);
+        }
+    }
+
+    let cannot_handle = |why: String| {
+        bad_request(&format!("{} in version {:?} cannot be handled as a {}: {}", kind, version, kind, why))
+    };
+    let decoded = match decode(&object) {
+        Ok(decoded) => decoded,
+        Err(e) => return cannot_handle(e),
+    };
+    let mut unknown = Vec::new();
+    unknown_fields(&object, &decoded, "", &mut unknown);
+    let unknown: Vec<String> = unknown.iter().map(|path| format!("unknown field {:?}", path)).collect();
+    if !unknown.is_empty() && validation == "Strict" {
+        return cannot_handle(format!("strict decoding error: {}", unknown.join(", ")));
+    }
+
+    let mut response = next.run(Request::from_parts(parts, Body::from(bytes))).await;
+    if validation == "Warn" {
+        for warning in unknown {
+            if let Ok(value) = HeaderValue::from_str(&format!("299 - {:?}", warning)) {
+                response.headers_mut().append(header::WARNING, value);
+            }
+        }
+    }
+    response
+}
+
+// The request's fieldValidation, Warn if it doesn't say
+fn field_validation(query: Option<&str>) -> &str {
+    query
+        .into_iter()
+        .flat_map(|q| q.split('&'))
+        .find_map(|pair| pair.strip_prefix("fieldValidation="))
+        .unwrap_or("Warn")
+}
+
+// Collects the paths of fields set in `sent` that decoding it dropped, being
+// unknown to its type
+fn unknown_fields(sent: &Value, decoded: &Value, path: &str, unknown: &mut Vec<String>) {
+    match (sent, decoded) {
+        (Value::Object(sent), Value::Object(decoded)) => {
+            for (key, value) in sent {
+                let field = if path.is_empty() { key.clone() } else { format!("{}.{}", path, key) };
+                match decoded.get(key) {
+                    Some(kept) => unknown_fields(value, kept, &field, unknown),
+                    None if !value.is_null() => unknown.push(field),
+                    None => {}
+                }
+            }
+        }
+        (Value::Array(sent), Value::Array(decoded)) => {
+            for (i, (value, kept)) in sent.iter().zip(decoded).enumerate() {
+                unknown_fields(value, kept, &format!("{}[{}]", path, i), unknown);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn bad_request(message: &str) -> Response {
+    ApiError::bad_request(message).into_response()
+}
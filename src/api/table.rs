@@ -0,0 +1,1218 @@
+// Server-side printing, as kubectl asks for it with `Accept:
+// application/json;as=Table;g=meta.k8s.io;v=v1`: gets, lists and watches
+// answer with a meta.k8s.io Table whose columns are the ones kube-apiserver's
+// printers give each resource (READY, STATUS, RESTARTS and AGE for pods and
+// so on), priority 1 columns being the extra ones of `-o wide`. Each row
+// carries the object as `?includeObject=` says: its metadata by default,
+// the whole object, or nothing. Resources without a printer of their own
+// get NAME and CREATED AT.
+use axum::{
+    body::Body,
+    extract::Request,
+    http::{header, HeaderValue, Method},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use tracing::error;
+
+use super::error_status::ApiError;
+use super::request_info::RequestInfo;
+use crate::models::{replicas, time};
+use crate::storage::LabelSelector;
+
+struct Column {
+    name: &'static str,
+    kind: &'static str,
+    format: &'static str,
+    priority: u8,
+    description: &'static str,
+}
+
+// A literal, so that the tables of columns below are promoted to statics
+macro_rules! column {
+    ($name:expr, $kind:expr, $priority:expr, $description:expr) => {
+        Column { name: $name, kind: $kind, format: "", priority: $priority, description: $description }
+    };
+}
+
+const NAME: Column = Column {
+    name: "Name",
+    kind: "string",
+    format: "name",
+    priority: 0,
+    description: "Name must be unique within a namespace.",
+};
+const AGE: Column = column!("Age", "string", 0, "CreationTimestamp is a timestamp representing the server time when this object was created.");
+const CREATED_AT: Column = Column {
+    name: "Created At",
+    kind: "date",
+    format: "",
+    priority: 0,
+    description: "CreationTimestamp is a timestamp representing the server time when this object was created.",
+};
+const CONTAINERS: Column = column!("Containers", "string", 1, "Names of each container in the template.");
+const IMAGES: Column = column!("Images", "string", 1, "Images referenced by each container in the template.");
+const SELECTOR: Column = column!("Selector", "string", 1, "Label selector for the pods.");
+
+// The cells of an object's row, now being `now`
+type Printer = fn(&Value, DateTime<Utc>) -> Vec<Value>;
+
+fn printer(resource: &str) -> (&'static [Column], Printer) {
+    match resource {
+        "pods" => (
+            &[
+                NAME,
+                column!("Ready", "string", 0, "The aggregate readiness state of this pod for accepting traffic."),
+                column!("Status", "string", 0, "The aggregate status of the containers in this pod."),
+                column!("Restarts", "string", 0, "The number of times the containers in this pod have been restarted and when the last container in this pod has restarted."),
+                AGE,
+                column!("IP", "string", 1, "IP address allocated to the pod."),
+                column!("Node", "string", 1, "Name of the Node this pod is running on."),
+                column!("Nominated Node", "string", 1, "Name of the Node this pod is nominated to run on, if any."),
+                column!("Readiness Gates", "string", 1, "Pod readiness gates and how many of them are met."),
+            ],
+            pod_row,
+        ),
+        "services" => (
+            &[
+                NAME,
+                column!("Type", "string", 0, "How the service is exposed."),
+                column!("Cluster-IP", "string", 0, "IP address of the service, usually assigned randomly."),
+                column!("External-IP", "string", 0, "External IP addresses the service is also reachable on."),
+                column!("Port(s)", "string", 0, "The ports the service exposes."),
+                AGE,
+                column!("Selector", "string", 1, "Route service traffic to pods with label keys and values matching this selector."),
+            ],
+            service_row,
+        ),
+        "endpoints" => (
+            &[NAME, column!("Endpoints", "string", 0, "Endpoints of the service, up to three."), AGE],
+            endpoints_row,
+        ),
+        "configmaps" => (&[NAME, column!("Data", "integer", 0, "Number of keys in the ConfigMap."), AGE], configmap_row),
+        "secrets" => (
+            &[
+                NAME,
+                column!("Type", "string", 0, "The type of the secret."),
+                column!("Data", "integer", 0, "Number of keys in the secret."),
+                AGE,
+            ],
+            secret_row,
+        ),
+        "namespaces" => (&[NAME, column!("Status", "string", 0, "The status of the namespace."), AGE], namespace_row),
+        "nodes" => (
+            &[
+                NAME,
+                column!("Status", "string", 0, "The status of the node."),
+                column!("Roles", "string", 0, "The roles of the node."),
+                AGE,
+                column!("Version", "string", 0, "Kubelet Version reported by the node."),
+                column!("Internal-IP", "string", 1, "The internal IP of the node."),
+                column!("External-IP", "string", 1, "The external IP of the node."),
+                column!("OS-Image", "string", 1, "OS Image reported by the node."),
+                column!("Kernel-Version", "string", 1, "Kernel Version reported by the node."),
+                column!("Container-Runtime", "string", 1, "Container Runtime Version reported by the node."),
+            ],
+            node_row,
+        ),
+        "events" => (
+            &[
+                column!("Last Seen", "string", 0, "The time at which the most recent occurrence of this event was recorded."),
+                column!("Type", "string", 0, "Type of this event."),
+                column!("Reason", "string", 0, "Why the action was taken."),
+                column!("Object", "string", 0, "The object this event is about."),
+                column!("Message", "string", 0, "A human-readable description of the status of this operation."),
+                column!("Source", "string", 1, "The component reporting this event."),
+                column!("First Seen", "string", 1, "The time at which the event was first recorded."),
+                column!("Count", "string", 1, "The number of times this event has occurred."),
+                column!("Name", "string", 1, "Name must be unique within a namespace."),
+            ],
+            event_row,
+        ),
+        "persistentvolumes" => (
+            &[
+                NAME,
+                column!("Capacity", "string", 0, "A description of the persistent volume's resources and capacity."),
+                column!("Access Modes", "string", 0, "AccessModes contains all ways the volume can be mounted."),
+                column!("Reclaim Policy", "string", 0, "What happens to a persistent volume when released from its claim."),
+                column!("Status", "string", 0, "Phase indicates if a volume is available, bound to a claim, or released by a claim."),
+                column!("Claim", "string", 0, "The claim bound to the volume."),
+                column!("StorageClass", "string", 0, "Name of StorageClass to which this persistent volume belongs."),
+                column!("Reason", "string", 0, "Reason is a brief CamelCase string that describes any failure."),
+                AGE,
+                column!("VolumeMode", "string", 1, "Whether the volume is used with a formatted filesystem or as a raw block device."),
+            ],
+            pv_row,
+        ),
+        "persistentvolumeclaims" => (
+            &[
+                NAME,
+                column!("Status", "string", 0, "Phase represents the current phase of PersistentVolumeClaim."),
+                column!("Volume", "string", 0, "The binding reference to the PersistentVolume backing this claim."),
+                column!("Capacity", "string", 0, "The actual resources of the underlying volume."),
+                column!("Access Modes", "string", 0, "AccessModes contains the actual access modes the volume backing the PVC has."),
+                column!("StorageClass", "string", 0, "Name of the StorageClass required by the claim."),
+                AGE,
+                column!("VolumeMode", "string", 1, "Whether the volume is used with a formatted filesystem or as a raw block device."),
+            ],
+            pvc_row,
+        ),
+        "resourcequotas" => (
+            &[
+                NAME,
+                AGE,
+                column!("Request", "string", 0, "Request represents a minimum amount of cpu/memory that a container may consume."),
+                column!("Limit", "string", 0, "Limits control the maximum amount of cpu/memory that a container may use independent of contention on the node."),
+            ],
+            resourcequota_row,
+        ),
+        "limitranges" | "roles" | "clusterroles" => (&[NAME, CREATED_AT], created_at_row),
+        "serviceaccounts" => (&[NAME, column!("Secrets", "string", 0, "Secrets is the list of secrets allowed to be used by pods running using this ServiceAccount."), AGE], serviceaccount_row),
+        "deployments" => (
+            &[
+                NAME,
+                column!("Ready", "string", 0, "Number of the pod with ready state."),
+                column!("Up-to-date", "string", 0, "Total number of non-terminated pods targeted by this deployment that have the desired template spec."),
+                column!("Available", "string", 0, "Total number of available pods (ready for at least minReadySeconds) targeted by this deployment."),
+                AGE,
+                CONTAINERS,
+                IMAGES,
+                SELECTOR,
+            ],
+            deployment_row,
+        ),
+        "replicasets" => (
+            &[
+                NAME,
+                column!("Desired", "integer", 0, "Replicas is the number of desired replicas."),
+                column!("Current", "integer", 0, "Replicas is the most recently observed number of replicas."),
+                column!("Ready", "integer", 0, "The number of ready replicas for this replica set."),
+                AGE,
+                CONTAINERS,
+                IMAGES,
+                SELECTOR,
+            ],
+            replicaset_row,
+        ),
+        "statefulsets" => (&[NAME, column!("Ready", "string", 0, "Number of the pod with ready state."), AGE, CONTAINERS, IMAGES], statefulset_row),
+        "daemonsets" => (
+            &[
+                NAME,
+                column!("Desired", "integer", 0, "The total number of nodes that should be running the daemon pod."),
+                column!("Current", "integer", 0, "The number of nodes that are running at least 1 daemon pod and are supposed to run the daemon pod."),
+                column!("Ready", "integer", 0, "The number of nodes that should be running the daemon pod and have one or more of the daemon pod running and ready."),
+                column!("Up-to-date", "integer", 0, "The total number of nodes that are running updated daemon pod."),
+                column!("Available", "integer", 0, "The number of nodes that should be running the daemon pod and have one or more of the daemon pod running and available."),
+                column!("Node Selector", "string", 0, "NodeSelector is a selector which must be true for the pod to fit on a node."),
+                AGE,
+                CONTAINERS,
+                IMAGES,
+                SELECTOR,
+            ],
+            daemonset_row,
+        ),
+        "controllerrevisions" => (
+            &[
+                NAME,
+                column!("Controller", "string", 0, "Controller of the object."),
+                column!("Revision", "integer", 0, "Revision indicates the revision of the state represented by Data."),
+                AGE,
+            ],
+            controllerrevision_row,
+        ),
+        "jobs" => (
+            &[
+                NAME,
+                column!("Status", "string", 0, "Status of the job."),
+                column!("Completions", "string", 0, "The number of successfully completed pods out of those desired."),
+                column!("Duration", "string", 0, "Time required to complete the job."),
+                AGE,
+                CONTAINERS,
+                IMAGES,
+                SELECTOR,
+            ],
+            job_row,
+        ),
+        "cronjobs" => (
+            &[
+                NAME,
+                column!("Schedule", "string", 0, "The schedule in Cron format."),
+                column!("Suspend", "boolean", 0, "Whether subsequent executions are suspended."),
+                column!("Active", "integer", 0, "The number of currently running jobs."),
+                column!("Last Schedule", "string", 0, "Information when was the last time the job was successfully scheduled."),
+                AGE,
+                CONTAINERS,
+                IMAGES,
+                SELECTOR,
+            ],
+            cronjob_row,
+        ),
+        "networkpolicies" => (&[NAME, column!("Pod-Selector", "string", 0, "Selects the pods to which this NetworkPolicy object applies."), AGE], networkpolicy_row),
+        "ingresses" => (
+            &[
+                NAME,
+                column!("Class", "string", 0, "The name of the IngressClass resource."),
+                column!("Hosts", "string", 0, "Hosts that incoming requests are matched against."),
+                column!("Address", "string", 0, "Address is a list containing ingress points for the load-balancer."),
+                column!("Ports", "string", 0, "Ports of TLS configurations that open."),
+                AGE,
+            ],
+            ingress_row,
+        ),
+        "horizontalpodautoscalers" => (
+            &[
+                NAME,
+                column!("Reference", "string", 0, "The scaled target."),
+                column!("Targets", "string", 0, "The current and target value of each metric."),
+                column!("MinPods", "string", 0, "The lower limit for the number of replicas."),
+                column!("MaxPods", "integer", 0, "The upper limit for the number of replicas."),
+                column!("Replicas", "integer", 0, "Current number of replicas of pods managed by this autoscaler."),
+                AGE,
+            ],
+            hpa_row,
+        ),
+        "poddisruptionbudgets" => (
+            &[
+                NAME,
+                column!("Min Available", "string", 0, "The minimum number of pods that must be available."),
+                column!("Max Unavailable", "string", 0, "The maximum number of pods that may be unavailable."),
+                column!("Allowed Disruptions", "integer", 0, "Number of pod disruptions that are currently allowed."),
+                AGE,
+            ],
+            pdb_row,
+        ),
+        "leases" => (&[NAME, column!("Holder", "string", 0, "The holder of the lease."), AGE], lease_row),
+        "rolebindings" | "clusterrolebindings" => (
+            &[
+                NAME,
+                column!("Role", "string", 0, "RoleRef can reference a Role in the current namespace or a ClusterRole in the global namespace."),
+                AGE,
+                column!("Users", "string", 1, "Users in the binding."),
+                column!("Groups", "string", 1, "Groups in the binding."),
+                column!("ServiceAccounts", "string", 1, "ServiceAccounts in the binding."),
+            ],
+            rolebinding_row,
+        ),
+        "priorityclasses" => (
+            &[
+                NAME,
+                column!("Value", "integer", 0, "The integer value of this priority class."),
+                column!("Global-Default", "boolean", 0, "Whether this priority class is the default for pods without one."),
+                AGE,
+                column!("PreemptionPolicy", "string", 0, "The policy for preempting pods with lower priority."),
+            ],
+            priorityclass_row,
+        ),
+        "storageclasses" => (
+            &[
+                NAME,
+                column!("Provisioner", "string", 0, "Provisioner indicates the type of the provisioner."),
+                column!("ReclaimPolicy", "string", 0, "Dynamically provisioned PersistentVolumes of this storage class are created with this reclaimPolicy."),
+                column!("VolumeBindingMode", "string", 0, "VolumeBindingMode indicates how PersistentVolumeClaims should be provisioned and bound."),
+                column!("AllowVolumeExpansion", "string", 0, "AllowVolumeExpansion shows whether the storage class allow volume expand."),
+                AGE,
+            ],
+            storageclass_row,
+        ),
+        "validatingwebhookconfigurations" | "mutatingwebhookconfigurations" => (
+            &[NAME, column!("Webhooks", "integer", 0, "Webhooks is a list of webhooks and the affected resources and operations."), AGE],
+            webhookconfiguration_row,
+        ),
+        _ => (&[NAME, CREATED_AT], created_at_row),
+    }
+}
+
+/// Middleware answering gets, lists and watches that accept a Table with
+/// one.
+pub async fn render_tables(request: Request, next: Next) -> Response {
+    let wants_table = request
+        .headers()
+        .get(header::ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.split(',').next().is_some_and(|first| first.contains("as=Table")));
+    if !wants_table || *request.method() != Method::GET {
+        return next.run(request).await;
+    }
+    let info = RequestInfo::parse(request.method().as_str(), request.uri().path(), request.uri().query());
+    let RequestInfo::Resource { verb, resource, subresource: None, .. } = info else {
+        return next.run(request).await;
+    };
+    let include = include_object(request.uri().query());
+
+    let response = next.run(request).await;
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|ct| ct.starts_with("application/json"));
+    if !response.status().is_success() || !is_json {
+        return response;
+    }
+
+    let (mut parts, body) = response.into_parts();
+    parts.headers.remove(header::CONTENT_LENGTH);
+    parts.headers.insert(header::CONTENT_TYPE, HeaderValue::from_static("application/json;as=Table;g=meta.k8s.io;v=v1"));
+    if verb == "watch" {
+        return Response::from_parts(parts, Body::from_stream(watch_tables(body, resource, include)));
+    }
+
+    let bytes = match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => bytes,
+        Err(e) => {
+            error!("Failed to buffer response body: {}", e);
+            return ApiError::internal(&e).into_response();
+        }
+    };
+    let Ok(object) = serde_json::from_slice::<Value>(&bytes) else {
+        return Response::from_parts(parts, Body::from(bytes));
+    };
+    let table = match object["items"].as_array() {
+        Some(items) => table(&resource, items, &object["metadata"], include, true),
+        None => {
+            let metadata = json!({ "resourceVersion": object["metadata"]["resourceVersion"] });
+            table(&resource, std::slice::from_ref(&object), &metadata, include, true)
+        }
+    };
+    Response::from_parts(parts, Body::from(table.to_string()))
+}
+
+// Watch events, a line each, with their objects as Tables of one row. Only
+// the first carries the column definitions, as with kube-apiserver.
+fn watch_tables(body: Body, resource: String, include: &'static str) -> impl futures::Stream<Item = Result<String, Infallible>> {
+    async_stream::stream! {
+        let mut body = body.into_data_stream();
+        let mut buffered = Vec::new();
+        let mut first = true;
+        while let Some(Ok(chunk)) = body.next().await {
+            buffered.extend_from_slice(&chunk);
+            while let Some(end) = buffered.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffered.drain(..=end).collect();
+                let Ok(mut event) = serde_json::from_slice::<Value>(&line) else {
+                    yield Ok(String::from_utf8_lossy(&line).into_owned());
+                    continue;
+                };
+                if matches!(event["type"].as_str(), Some("ADDED" | "MODIFIED" | "DELETED")) {
+                    let object = event["object"].take();
+                    let metadata = json!({ "resourceVersion": object["metadata"]["resourceVersion"] });
+                    event["object"] = table(&resource, std::slice::from_ref(&object), &metadata, include, first);
+                    first = false;
+                }
+                yield Ok(format!("{}\n", event));
+            }
+        }
+    }
+}
+
+// The row object kubectl asked for with ?includeObject=
+fn include_object(query: Option<&str>) -> &'static str {
+    let asked = query.into_iter().flat_map(|q| q.split('&')).find_map(|pair| pair.strip_prefix("includeObject="));
+    match asked {
+        Some("None") => "None",
+        Some("Object") => "Object",
+        _ => "Metadata",
+    }
+}
+
+fn table(resource: &str, objects: &[Value], metadata: &Value, include: &str, with_columns: bool) -> Value {
+    let (columns, print) = printer(resource);
+    let now = Utc::now();
+    let rows: Vec<Value> = objects
+        .iter()
+        .map(|object| {
+            let mut row = json!({ "cells": print(object, now) });
+            match include {
+                "Object" => row["object"] = object.clone(),
+                "Metadata" => {
+                    row["object"] = json!({
+                        "kind": "PartialObjectMetadata",
+                        "apiVersion": "meta.k8s.io/v1",
+                        "metadata": object["metadata"]
+                    })
+                }
+                _ => {}
+            }
+            row
+        })
+        .collect();
+
+    let mut table = json!({
+        "kind": "Table",
+        "apiVersion": "meta.k8s.io/v1",
+        "metadata": metadata,
+        "rows": rows
+    });
+    if with_columns {
+        let definitions: Vec<Value> = columns
+            .iter()
+            .map(|column| {
+                json!({
+                    "name": column.name,
+                    "type": column.kind,
+                    "format": column.format,
+                    "description": column.description,
+                    "priority": column.priority
+                })
+            })
+            .collect();
+        table["columnDefinitions"] = json!(definitions);
+    }
+    table
+}
+
+/// A duration as kubectl shows ages: `45s`, `3m20s`, `25m`, `5h10m`, `3d4h`
+/// and so on, more coarsely the longer it is.
+pub fn human_duration(seconds: i64) -> String {
+    if seconds < -1 {
+        return "<invalid>".to_string();
+    } else if seconds < 0 {
+        return "0s".to_string();
+    } else if seconds < 60 * 2 {
+        return format!("{}s", seconds);
+    }
+    let minutes = seconds / 60;
+    if minutes < 10 {
+        return match seconds % 60 {
+            0 => format!("{}m", minutes),
+            s => format!("{}m{}s", minutes, s),
+        };
+    } else if minutes < 60 * 3 {
+        return format!("{}m", minutes);
+    }
+    let hours = minutes / 60;
+    if hours < 8 {
+        match minutes % 60 {
+            0 => format!("{}h", hours),
+            m => format!("{}h{}m", hours, m),
+        }
+    } else if hours < 48 {
+        format!("{}h", hours)
+    } else if hours < 24 * 8 {
+        match hours % 24 {
+            0 => format!("{}d", hours / 24),
+            h => format!("{}d{}h", hours / 24, h),
+        }
+    } else if hours < 24 * 365 * 2 {
+        format!("{}d", hours / 24)
+    } else if hours < 24 * 365 * 8 {
+        match (hours / 24) % 365 {
+            0 => format!("{}y", hours / 24 / 365),
+            d => format!("{}y{}d", hours / 24 / 365, d),
+        }
+    } else {
+        format!("{}y", hours / 24 / 365)
+    }
+}
+
+// How long ago a timestamp was, or <unknown> without one
+fn since(timestamp: &Value, now: DateTime<Utc>) -> String {
+    match timestamp.as_str().and_then(time::parse) {
+        Some(then) => human_duration((now - then).num_seconds()),
+        None => "<unknown>".to_string(),
+    }
+}
+
+fn age(object: &Value, now: DateTime<Utc>) -> Value {
+    json!(since(&object["metadata"]["creationTimestamp"], now))
+}
+
+fn name(object: &Value) -> Value {
+    object["metadata"]["name"].clone()
+}
+
+fn str_of(value: &Value) -> &str {
+    value.as_str().unwrap_or_default()
+}
+
+fn or_none(value: String) -> Value {
+    json!(if value.is_empty() { "<none>".to_string() } else { value })
+}
+
+fn count(value: &Value) -> usize {
+    match value {
+        Value::Array(items) => items.len(),
+        Value::Object(fields) => fields.len(),
+        _ => 0,
+    }
+}
+
+fn int(value: &Value) -> i64 {
+    value.as_i64().unwrap_or(0)
+}
+
+// A metav1.LabelSelector, or a plain map of labels, as kubectl writes it
+fn selector(value: &Value) -> Value {
+    let selector = if value.get("matchLabels").is_some() || value.get("matchExpressions").is_some() {
+        LabelSelector::from_value(value)
+    } else {
+        LabelSelector::from_value(&json!({ "matchLabels": value }))
+    };
+    or_none(selector.map(|selector| selector.to_string()).unwrap_or_default())
+}
+
+// The Containers and Images columns of a pod template
+fn containers_and_images(template_spec: &Value) -> (Value, Value) {
+    let containers = template_spec["containers"].as_array().cloned().unwrap_or_default();
+    let names: Vec<&str> = containers.iter().map(|c| str_of(&c["name"])).collect();
+    let images: Vec<&str> = containers.iter().map(|c| str_of(&c["image"])).collect();
+    (json!(names.join(",")), json!(images.join(",")))
+}
+
+fn workload_wide(object: &Value) -> [Value; 3] {
+    let (containers, images) = containers_and_images(&object["spec"]["template"]["spec"]);
+    [containers, images, selector(&object["spec"]["selector"])]
+}
+
+fn created_at_row(object: &Value, _: DateTime<Utc>) -> Vec<Value> {
+    vec![name(object), object["metadata"]["creationTimestamp"].clone()]
+}
+
+// kube-apiserver's printPod: the status shown is the first thing wrong, from
+// the init containers through the containers, or the phase
+fn pod_row(pod: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let spec = &pod["spec"];
+    let status = &pod["status"];
+    let total = count(&spec["containers"]);
+    let mut ready = 0;
+    let mut restarts = 0;
+    let mut last_restart: Option<DateTime<Utc>> = None;
+    let mut reason = status["reason"].as_str().or(status["phase"].as_str()).unwrap_or_default().to_string();
+
+    let mut note_restarts = |container: &Value| {
+        restarts += int(&container["restartCount"]);
+        if let Some(finished) = container["lastState"]["terminated"]["finishedAt"].as_str().and_then(time::parse) {
+            if last_restart.is_none_or(|last| finished > last) {
+                last_restart = Some(finished);
+            }
+        }
+    };
+
+    let init_statuses = status["initContainerStatuses"].as_array().cloned().unwrap_or_default();
+    let mut initializing = false;
+    for (i, container) in init_statuses.iter().enumerate() {
+        note_restarts(container);
+        let state = &container["state"];
+        if state["terminated"]["exitCode"].as_i64() == Some(0) {
+            continue;
+        }
+        reason = if state["terminated"].is_object() {
+            let terminated = &state["terminated"];
+            match terminated["reason"].as_str() {
+                Some(r) if !r.is_empty() => format!("Init:{}", r),
+                _ if int(&terminated["signal"]) != 0 => format!("Init:Signal:{}", int(&terminated["signal"])),
+                _ => format!("Init:ExitCode:{}", int(&terminated["exitCode"])),
+            }
+        } else {
+            match state["waiting"]["reason"].as_str() {
+                Some(r) if !r.is_empty() && r != "PodInitializing" => format!("Init:{}", r),
+                _ => format!("Init:{}/{}", i, count(&spec["initContainers"])),
+            }
+        };
+        initializing = true;
+        break;
+    }
+
+    if !initializing {
+        let mut has_running = false;
+        let statuses = status["containerStatuses"].as_array().cloned().unwrap_or_default();
+        for container in statuses.iter().rev() {
+            note_restarts(container);
+            let state = &container["state"];
+            let terminated = &state["terminated"];
+            if let Some(waiting) = state["waiting"]["reason"].as_str().filter(|r| !r.is_empty()) {
+                reason = waiting.to_string();
+            } else if let Some(r) = terminated["reason"].as_str().filter(|r| !r.is_empty()) {
+                reason = r.to_string();
+            } else if terminated.is_object() {
+                reason = match int(&terminated["signal"]) {
+                    0 => format!("ExitCode:{}", int(&terminated["exitCode"])),
+                    signal => format!("Signal:{}", signal),
+                };
+            } else if container["ready"] == true && state["running"].is_object() {
+                has_running = true;
+                ready += 1;
+            }
+        }
+        if reason == "Completed" && has_running {
+            let pod_ready = status["conditions"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .any(|c| c["type"] == "Ready" && c["status"] == "True");
+            reason = if pod_ready { "Running" } else { "NotReady" }.to_string();
+        }
+    }
+
+    if pod["metadata"]["deletionTimestamp"].is_string() {
+        reason = if status["reason"] == "NodeLost" { "Unknown" } else { "Terminating" }.to_string();
+    }
+
+    let restarts = match last_restart {
+        Some(last) => format!("{} ({} ago)", restarts, human_duration((now - last).num_seconds())),
+        None => restarts.to_string(),
+    };
+    let gates = spec["readinessGates"].as_array().cloned().unwrap_or_default();
+    let readiness_gates = if gates.is_empty() {
+        "<none>".to_string()
+    } else {
+        let met = gates
+            .iter()
+            .filter(|gate| {
+                status["conditions"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .any(|c| c["type"] == gate["conditionType"] && c["status"] == "True")
+            })
+            .count();
+        format!("{}/{}", met, gates.len())
+    };
+
+    vec![
+        name(pod),
+        json!(format!("{}/{}", ready, total)),
+        json!(reason),
+        json!(restarts),
+        age(pod, now),
+        or_none(str_of(&status["podIP"]).to_string()),
+        or_none(str_of(&spec["nodeName"]).to_string()),
+        or_none(str_of(&status["nominatedNodeName"]).to_string()),
+        json!(readiness_gates),
+    ]
+}
+
+fn service_row(service: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let spec = &service["spec"];
+    let service_type = spec["type"].as_str().unwrap_or("ClusterIP");
+    let external_ips: Vec<String> = spec["externalIPs"].as_array().into_iter().flatten().map(|ip| str_of(ip).to_string()).collect();
+    let external = match service_type {
+        "ExternalName" => str_of(&spec["externalName"]).to_string(),
+        "LoadBalancer" => {
+            let mut addresses: Vec<String> = service["status"]["loadBalancer"]["ingress"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .map(|ingress| ingress["ip"].as_str().or(ingress["hostname"].as_str()).unwrap_or_default().to_string())
+                .collect();
+            addresses.extend(external_ips);
+            if addresses.is_empty() {
+                "<pending>".to_string()
+            } else {
+                addresses.join(",")
+            }
+        }
+        _ => external_ips.join(","),
+    };
+    let ports: Vec<String> = spec["ports"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|port| {
+            let protocol = port["protocol"].as_str().unwrap_or("TCP");
+            match port["nodePort"].as_i64() {
+                Some(node_port) => format!("{}:{}/{}", int(&port["port"]), node_port, protocol),
+                None => format!("{}/{}", int(&port["port"]), protocol),
+            }
+        })
+        .collect();
+
+    vec![
+        name(service),
+        json!(service_type),
+        or_none(str_of(&spec["clusterIP"]).to_string()),
+        or_none(external),
+        or_none(ports.join(",")),
+        age(service, now),
+        selector(&spec["selector"]),
+    ]
+}
+
+fn endpoints_row(endpoints: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let mut addresses = Vec::new();
+    for subset in endpoints["subsets"].as_array().into_iter().flatten() {
+        let ports: Vec<i64> = subset["ports"].as_array().into_iter().flatten().map(|port| int(&port["port"])).collect();
+        for address in subset["addresses"].as_array().into_iter().flatten() {
+            let ip = str_of(&address["ip"]);
+            if ports.is_empty() {
+                addresses.push(ip.to_string());
+            }
+            for port in &ports {
+                addresses.push(format!("{}:{}", ip, port));
+            }
+        }
+    }
+    let shown = if addresses.len() > 3 {
+        format!("{} + {} more...", addresses[..3].join(","), addresses.len() - 3)
+    } else {
+        addresses.join(",")
+    };
+    vec![name(endpoints), or_none(shown), age(endpoints, now)]
+}
+
+fn configmap_row(configmap: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let keys = count(&configmap["data"]) + count(&configmap["binaryData"]);
+    vec![name(configmap), json!(keys), age(configmap, now)]
+}
+
+fn secret_row(secret: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let keys = count(&secret["data"]) + count(&secret["stringData"]);
+    vec![name(secret), json!(secret["type"].as_str().unwrap_or("Opaque")), json!(keys), age(secret, now)]
+}
+
+fn namespace_row(namespace: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    vec![name(namespace), json!(namespace["status"]["phase"].as_str().unwrap_or("Active")), age(namespace, now)]
+}
+
+fn node_row(node: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let status = &node["status"];
+    let ready = status["conditions"].as_array().into_iter().flatten().find(|c| c["type"] == "Ready");
+    let mut state = match ready.map(|c| str_of(&c["status"])) {
+        Some("True") => "Ready",
+        Some("False") => "NotReady",
+        _ => "Unknown",
+    }
+    .to_string();
+    if node["spec"]["unschedulable"] == true {
+        state.push_str(",SchedulingDisabled");
+    }
+    let mut roles: Vec<String> = node["metadata"]["labels"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, value)| {
+            if let Some(role) = key.strip_prefix("node-role.kubernetes.io/") {
+                Some(role.to_string())
+            } else if key == "kubernetes.io/role" {
+                value.as_str().map(str::to_string)
+            } else {
+                None
+            }
+        })
+        .filter(|role| !role.is_empty())
+        .collect();
+    roles.sort();
+    let address = |kind: &str| {
+        status["addresses"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|address| address["type"] == kind)
+            .map(|address| str_of(&address["address"]).to_string())
+            .unwrap_or_default()
+    };
+    let info = &status["nodeInfo"];
+
+    vec![
+        name(node),
+        json!(state),
+        or_none(roles.join(",")),
+        age(node, now),
+        json!(str_of(&info["kubeletVersion"])),
+        or_none(address("InternalIP")),
+        or_none(address("ExternalIP")),
+        json!(str_of(&info["osImage"])),
+        json!(str_of(&info["kernelVersion"])),
+        json!(str_of(&info["containerRuntimeVersion"])),
+    ]
+}
+
+fn event_row(event: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let first = &event["firstTimestamp"];
+    let last = [&event["lastTimestamp"], &event["eventTime"], first, &event["metadata"]["creationTimestamp"]]
+        .into_iter()
+        .find(|timestamp| timestamp.is_string())
+        .cloned()
+        .unwrap_or(Value::Null);
+    let occurrences = event["count"].as_i64().unwrap_or(1);
+    let last_seen = if occurrences > 1 {
+        format!("{} (x{} over {})", since(&last, now), occurrences, since(first, now))
+    } else {
+        since(&last, now)
+    };
+    let involved = &event["involvedObject"];
+    let source = &event["source"];
+    let source = match (str_of(&source["component"]), str_of(&source["host"])) {
+        ("", _) => str_of(&event["reportingComponent"]).to_string(),
+        (component, "") => component.to_string(),
+        (component, host) => format!("{}, {}", component, host),
+    };
+
+    vec![
+        json!(last_seen),
+        json!(str_of(&event["type"])),
+        json!(str_of(&event["reason"])),
+        json!(format!("{}/{}", str_of(&involved["kind"]).to_lowercase(), str_of(&involved["name"]))),
+        json!(str_of(&event["message"]).trim()),
+        json!(source),
+        json!(since(first, now)),
+        json!(occurrences.to_string()),
+        name(event),
+    ]
+}
+
+// Access modes as kubectl abbreviates them
+fn access_modes(modes: &Value) -> String {
+    let modes: Vec<&str> = modes
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|mode| match str_of(mode) {
+            "ReadWriteOnce" => "RWO",
+            "ReadOnlyMany" => "ROX",
+            "ReadWriteMany" => "RWX",
+            "ReadWriteOncePod" => "RWOP",
+            other => other,
+        })
+        .collect();
+    modes.join(",")
+}
+
+fn pv_row(pv: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let spec = &pv["spec"];
+    let claim = &spec["claimRef"];
+    let claim = if claim.is_object() {
+        format!("{}/{}", str_of(&claim["namespace"]), str_of(&claim["name"]))
+    } else {
+        String::new()
+    };
+    vec![
+        name(pv),
+        json!(str_of(&spec["capacity"]["storage"])),
+        json!(access_modes(&spec["accessModes"])),
+        json!(spec["persistentVolumeReclaimPolicy"].as_str().unwrap_or("Retain")),
+        json!(str_of(&pv["status"]["phase"])),
+        json!(claim),
+        json!(str_of(&spec["storageClassName"])),
+        json!(str_of(&pv["status"]["reason"])),
+        age(pv, now),
+        json!(spec["volumeMode"].as_str().unwrap_or("Filesystem")),
+    ]
+}
+
+fn pvc_row(pvc: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let spec = &pvc["spec"];
+    let status = &pvc["status"];
+    let phase = if pvc["metadata"]["deletionTimestamp"].is_string() { "Terminating" } else { str_of(&status["phase"]) };
+    let bound = !str_of(&spec["volumeName"]).is_empty();
+    let (capacity, modes) = if bound {
+        (str_of(&status["capacity"]["storage"]).to_string(), access_modes(&status["accessModes"]))
+    } else {
+        (String::new(), String::new())
+    };
+    vec![
+        name(pvc),
+        json!(phase),
+        json!(str_of(&spec["volumeName"])),
+        json!(capacity),
+        json!(modes),
+        json!(str_of(&spec["storageClassName"])),
+        age(pvc, now),
+        json!(spec["volumeMode"].as_str().unwrap_or("Filesystem")),
+    ]
+}
+
+fn resourcequota_row(quota: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let (mut requests, mut limits) = (Vec::new(), Vec::new());
+    let mut hard: Vec<(&String, &Value)> = quota["status"]["hard"]
+        .as_object()
+        .or(quota["spec"]["hard"].as_object())
+        .into_iter()
+        .flatten()
+        .collect();
+    hard.sort_by(|a, b| a.0.cmp(b.0));
+    for (resource, limit) in hard {
+        let used = quota["status"]["used"][resource].as_str().unwrap_or("0");
+        let entry = format!("{}: {}/{}", resource, used, str_of(limit));
+        if resource.starts_with("limits") {
+            limits.push(entry);
+        } else {
+            requests.push(entry);
+        }
+    }
+    vec![name(quota), age(quota, now), json!(requests.join(", ")), json!(limits.join(", "))]
+}
+
+fn serviceaccount_row(account: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    vec![name(account), json!(count(&account["secrets"]).to_string()), age(account, now)]
+}
+
+fn deployment_row(deployment: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let status = &deployment["status"];
+    let desired = replicas::desired(&deployment["spec"]);
+    let mut row = vec![
+        name(deployment),
+        json!(format!("{}/{}", int(&status["readyReplicas"]), desired)),
+        json!(int(&status["updatedReplicas"]).to_string()),
+        json!(int(&status["availableReplicas"]).to_string()),
+        age(deployment, now),
+    ];
+    row.extend(workload_wide(deployment));
+    row
+}
+
+fn replicaset_row(replicaset: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let status = &replicaset["status"];
+    let mut row = vec![
+        name(replicaset),
+        json!(replicas::desired(&replicaset["spec"])),
+        json!(int(&status["replicas"])),
+        json!(int(&status["readyReplicas"])),
+        age(replicaset, now),
+    ];
+    row.extend(workload_wide(replicaset));
+    row
+}
+
+fn statefulset_row(statefulset: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let desired = replicas::desired(&statefulset["spec"]);
+    let (containers, images) = containers_and_images(&statefulset["spec"]["template"]["spec"]);
+    vec![
+        name(statefulset),
+        json!(format!("{}/{}", int(&statefulset["status"]["readyReplicas"]), desired)),
+        age(statefulset, now),
+        containers,
+        images,
+    ]
+}
+
+fn daemonset_row(daemonset: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let status = &daemonset["status"];
+    let mut row = vec![
+        name(daemonset),
+        json!(int(&status["desiredNumberScheduled"])),
+        json!(int(&status["currentNumberScheduled"])),
+        json!(int(&status["numberReady"])),
+        json!(int(&status["updatedNumberScheduled"])),
+        json!(int(&status["numberAvailable"])),
+        selector(&daemonset["spec"]["template"]["spec"]["nodeSelector"]),
+        age(daemonset, now),
+    ];
+    row.extend(workload_wide(daemonset));
+    row
+}
+
+fn controllerrevision_row(revision: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let controller = revision["metadata"]["ownerReferences"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|owner| owner["controller"] == true)
+        .map(|owner| {
+            let group = str_of(&owner["apiVersion"]).rsplit_once('/').map(|(group, _)| group).unwrap_or_default();
+            format!("{}.{}/{}", str_of(&owner["kind"]).to_lowercase(), group, str_of(&owner["name"]))
+        })
+        .unwrap_or_default();
+    vec![name(revision), or_none(controller), json!(int(&revision["revision"])), age(revision, now)]
+}
+
+fn job_row(job: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let spec = &job["spec"];
+    let status = &job["status"];
+    let succeeded = int(&status["succeeded"]);
+    let completions = match (spec["completions"].as_i64(), spec["parallelism"].as_i64()) {
+        (Some(completions), _) => format!("{}/{}", succeeded, completions),
+        (None, Some(parallelism)) if parallelism > 1 => format!("{}/1 of {}", succeeded, parallelism),
+        _ => format!("{}/1", succeeded),
+    };
+    let duration = match status["startTime"].as_str().and_then(time::parse) {
+        Some(start) => {
+            let end = status["completionTime"].as_str().and_then(time::parse).unwrap_or(now);
+            human_duration((end - start).num_seconds())
+        }
+        None => String::new(),
+    };
+    let holds = |kind: &str| status["conditions"].as_array().into_iter().flatten().any(|c| c["type"] == kind && c["status"] == "True");
+    let state = if holds("Complete") {
+        "Complete"
+    } else if holds("Failed") {
+        "Failed"
+    } else if job["metadata"]["deletionTimestamp"].is_string() {
+        "Terminating"
+    } else if holds("FailureTarget") {
+        "FailureTarget"
+    } else if holds("Suspended") {
+        "Suspended"
+    } else {
+        "Running"
+    };
+    let mut row = vec![name(job), json!(state), json!(completions), json!(duration), age(job, now)];
+    row.extend(workload_wide(job));
+    row
+}
+
+fn cronjob_row(cronjob: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let spec = &cronjob["spec"];
+    let status = &cronjob["status"];
+    let last_schedule = if status["lastScheduleTime"].is_string() { since(&status["lastScheduleTime"], now) } else { "<none>".to_string() };
+    let job_spec = &spec["jobTemplate"]["spec"];
+    let (containers, images) = containers_and_images(&job_spec["template"]["spec"]);
+    vec![
+        name(cronjob),
+        json!(str_of(&spec["schedule"])),
+        json!(spec["suspend"] == true),
+        json!(count(&status["active"])),
+        json!(last_schedule),
+        age(cronjob, now),
+        containers,
+        images,
+        selector(&job_spec["selector"]),
+    ]
+}
+
+fn networkpolicy_row(policy: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    vec![name(policy), selector(&policy["spec"]["podSelector"]), age(policy, now)]
+}
+
+fn ingress_row(ingress: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let spec = &ingress["spec"];
+    let hosts: Vec<&str> = spec["rules"].as_array().into_iter().flatten().filter_map(|rule| rule["host"].as_str()).collect();
+    let addresses: Vec<&str> = ingress["status"]["loadBalancer"]["ingress"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|address| address["ip"].as_str().or(address["hostname"].as_str()))
+        .collect();
+    let ports = if count(&spec["tls"]) > 0 { "80, 443" } else { "80" };
+    vec![
+        name(ingress),
+        or_none(str_of(&spec["ingressClassName"]).to_string()),
+        json!(if hosts.is_empty() { "*".to_string() } else { hosts.join(",") }),
+        json!(addresses.join(",")),
+        json!(ports),
+        age(ingress, now),
+    ]
+}
+
+fn hpa_row(hpa: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let spec = &hpa["spec"];
+    let status = &hpa["status"];
+    let target = &spec["scaleTargetRef"];
+    let mut targets = Vec::new();
+    if let Some(percent) = spec["targetCPUUtilizationPercentage"].as_i64() {
+        let current = status["currentCPUUtilizationPercentage"].as_i64().map_or("<unknown>".to_string(), |c| format!("{}%", c));
+        targets.push(format!("cpu: {}/{}%", current, percent));
+    }
+    let current_metrics = status["currentMetrics"].as_array().cloned().unwrap_or_default();
+    for (i, metric) in spec["metrics"].as_array().into_iter().flatten().enumerate() {
+        let current = current_metrics.get(i).unwrap_or(&Value::Null);
+        targets.push(match str_of(&metric["type"]) {
+            "Resource" | "ContainerResource" => {
+                let kind = if metric["type"] == "Resource" { "resource" } else { "containerResource" };
+                let resource = &metric[kind];
+                let current = &current[kind]["current"];
+                let goal = &resource["target"];
+                match goal["averageUtilization"].as_i64() {
+                    Some(percent) => {
+                        let current = current["averageUtilization"].as_i64().map_or("<unknown>".to_string(), |c| format!("{}%", c));
+                        format!("{}: {}/{}%", str_of(&resource["name"]), current, percent)
+                    }
+                    None => {
+                        let current = current["averageValue"].as_str().unwrap_or("<unknown>");
+                        format!("{}: {}/{}", str_of(&resource["name"]), current, str_of(&goal["averageValue"]))
+                    }
+                }
+            }
+            kind @ ("External" | "Pods" | "Object") => {
+                let field = match kind {
+                    "External" => "external",
+                    "Pods" => "pods",
+                    _ => "object",
+                };
+                let goal = &metric[field]["target"];
+                let current = &current[field]["current"];
+                if goal["averageValue"].is_string() {
+                    format!("{}/{} (avg)", current["averageValue"].as_str().unwrap_or("<unknown>"), str_of(&goal["averageValue"]))
+                } else {
+                    format!("{}/{}", current["value"].as_str().unwrap_or("<unknown>"), str_of(&goal["value"]))
+                }
+            }
+            _ => "<unknown>".to_string(),
+        });
+    }
+
+    vec![
+        name(hpa),
+        json!(format!("{}/{}", str_of(&target["kind"]), str_of(&target["name"]))),
+        or_none(targets.join(", ")),
+        json!(spec["minReplicas"].as_i64().unwrap_or(1).to_string()),
+        json!(int(&spec["maxReplicas"])),
+        json!(int(&status["currentReplicas"])),
+        age(hpa, now),
+    ]
+}
+
+fn lease_row(lease: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    vec![name(lease), json!(str_of(&lease["spec"]["holderIdentity"])), age(lease, now)]
+}
+
+fn pdb_row(pdb: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let shown = |value: &Value| match value {
+        Value::Null => "N/A".to_string(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    vec![
+        name(pdb),
+        json!(shown(&pdb["spec"]["minAvailable"])),
+        json!(shown(&pdb["spec"]["maxUnavailable"])),
+        json!(int(&pdb["status"]["disruptionsAllowed"])),
+        age(pdb, now),
+    ]
+}
+
+fn rolebinding_row(binding: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let role = &binding["roleRef"];
+    let subjects = binding["subjects"].as_array().cloned().unwrap_or_default();
+    let of_kind = |kind: &str| -> String {
+        let names: Vec<String> = subjects
+            .iter()
+            .filter(|subject| subject["kind"] == kind)
+            .map(|subject| match (kind, subject["namespace"].as_str()) {
+                ("ServiceAccount", Some(namespace)) => format!("{}/{}", namespace, str_of(&subject["name"])),
+                _ => str_of(&subject["name"]).to_string(),
+            })
+            .collect();
+        names.join(", ")
+    };
+    vec![
+        name(binding),
+        json!(format!("{}/{}", str_of(&role["kind"]), str_of(&role["name"]))),
+        age(binding, now),
+        json!(of_kind("User")),
+        json!(of_kind("Group")),
+        json!(of_kind("ServiceAccount")),
+    ]
+}
+
+fn priorityclass_row(class: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    vec![
+        name(class),
+        json!(int(&class["value"])),
+        json!(class["globalDefault"] == true),
+        age(class, now),
+        json!(class["preemptionPolicy"].as_str().unwrap_or("PreemptLowerPriority")),
+    ]
+}
+
+fn storageclass_row(class: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    let annotations = &class["metadata"]["annotations"];
+    let is_default = ["storageclass.kubernetes.io/is-default-class", "storageclass.beta.kubernetes.io/is-default-class"]
+        .iter()
+        .any(|key| annotations[*key] == "true");
+    let name = if is_default { format!("{} (default)", str_of(&class["metadata"]["name"])) } else { str_of(&class["metadata"]["name"]).to_string() };
+    vec![
+        json!(name),
+        json!(str_of(&class["provisioner"])),
+        json!(class["reclaimPolicy"].as_str().unwrap_or("Delete")),
+        json!(class["volumeBindingMode"].as_str().unwrap_or("Immediate")),
+        json!((class["allowVolumeExpansion"] == true).to_string()),
+        age(class, now),
+    ]
+}
+
+fn webhookconfiguration_row(configuration: &Value, now: DateTime<Utc>) -> Vec<Value> {
+    vec![name(configuration), json!(count(&configuration["webhooks"])), age(configuration, now)]
+}
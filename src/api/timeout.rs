@@ -0,0 +1,92 @@
+// Request timeouts. Like kube-apiserver, regular requests are cut off after
+// a configurable time, while long-running ones (watches, exec, attach,
+// port-forward, proxying, followed logs and CPU profiles) are exempt: they
+// are expected to stay open for as long as the client wants.
+use axum::{
+    extract::{Query, Request, State},
+    http::{Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+use super::error_status::ApiError;
+use super::server::AppState;
+
+// Subresources that stream for the lifetime of the connection
+const LONG_RUNNING_SUBRESOURCES: &[&str] = &["exec", "attach", "portforward", "proxy"];
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "camelCase")]
+struct TimeoutParams {
+    watch: Option<String>,
+    follow: Option<String>,
+    timeout_seconds: Option<u64>,
+}
+
+/// Middleware failing regular requests that take longer than the configured
+/// request timeout, or `?timeoutSeconds=` when given, with a 504 Status.
+pub async fn enforce_timeout(State(state): State<AppState>, request: Request, next: Next) -> Response {
+    let params = Query::<TimeoutParams>::try_from_uri(request.uri())
+        .map(|Query(params)| params)
+        .unwrap_or_default();
+
+    if is_long_running(request.method(), request.uri().path(), &params) {
+        return next.run(request).await;
+    }
+
+    let seconds = params
+        .timeout_seconds
+        .filter(|&seconds| seconds > 0)
+        .unwrap_or(state.config.api_server.request_timeout_seconds);
+
+    match tokio::time::timeout(Duration::from_secs(seconds), next.run(request)).await {
+        Ok(response) => response,
+        Err(_) => timed_out(),
+    }
+}
+
+fn is_long_running(method: &Method, path: &str, params: &TimeoutParams) -> bool {
+    let enabled = |value: &Option<String>| matches!(value.as_deref(), Some("true" | "1"));
+    if *method == Method::GET && enabled(&params.watch) {
+        return true;
+    }
+
+    // CPU profiles take as long as they're asked to sample for
+    if path == "/debug/pprof/profile" {
+        return true;
+    }
+
+    // Only the positions that name a watch or proxy (right after the API
+    // version) or a subresource (after the resource and name) count, so
+    // objects and namespaces may be called "watch" or "exec"
+    let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+    let resource_path = match segments.first() {
+        Some(&"api") => segments.get(2..),
+        Some(&"apis") => segments.get(3..),
+        _ => None,
+    }
+    .unwrap_or_default();
+    if matches!(resource_path.first(), Some(&"watch" | &"proxy")) {
+        return true;
+    }
+    let subresource = match resource_path {
+        ["namespaces", _, _, _, subresource, ..] => Some(*subresource),
+        ["namespaces", ..] => None,
+        [_, _, subresource, ..] => Some(*subresource),
+        _ => None,
+    };
+    match subresource {
+        Some("log") => enabled(&params.follow),
+        Some(subresource) => LONG_RUNNING_SUBRESOURCES.contains(&subresource),
+        None => false,
+    }
+}
+
+fn timed_out() -> Response {
+    ApiError::new(StatusCode::GATEWAY_TIMEOUT, "Timeout", "Timeout: request did not complete within the allotted timeout")
+        .with_details(json!({}))
+        .into_response()
+}
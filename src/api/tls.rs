@@ -0,0 +1,178 @@
+// HTTPS for the API server, as kube-apiserver serves it. The serving
+// certificate is issued by the cluster CA for localhost and the in-cluster
+// names of the API, so clients that trust the CA, from the kubeconfig or the
+// kube-root-ca.crt ConfigMap, can verify it. Client certificates are asked
+// for but optional: one signed by a client CA authenticates its subject, and
+// without one the usual bearer tokens apply.
+use anyhow::{Context, Result};
+use axum::{extract::Request, Router};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use hyper::body::Incoming;
+use hyper::service::service_fn;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto;
+use openssl::nid::Nid;
+use openssl::pkey::PKey;
+use openssl::x509::X509;
+use serde_json::json;
+use std::path::PathBuf;
+use std::sync::Arc;
+use tokio::net::TcpListener;
+use tokio_rustls::rustls::server::AllowAnyAnonymousOrAuthenticatedClient;
+use tokio_rustls::rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+use tokio_rustls::TlsAcceptor;
+use tower::Service;
+use tracing::{debug, error, info};
+
+use super::authentication::UserInfo;
+use crate::config::TlsConfig;
+use crate::data_dir::DataDir;
+use crate::pki::{self, ClusterCa, Usage};
+
+// Names the generated serving certificate is valid for
+const SERVING_NAMES: &[&str] = &[
+    "localhost",
+    "127.0.0.1",
+    "::1",
+    "kubernetes",
+    "kubernetes.default",
+    "kubernetes.default.svc",
+    "kubernetes.default.svc.cluster.local",
+];
+
+// The user of the generated kubeconfig, allowed everything under RBAC
+const ADMIN_USER: &str = "krust-admin";
+const ADMIN_GROUP: &str = "system:masters";
+
+/// The user a verified client certificate names, put in the extensions of
+/// every request on its connection.
+#[derive(Debug, Clone)]
+pub struct ClientCertificate(pub UserInfo);
+
+/// The TLS settings for serving with the configured certificate, or one the
+/// cluster CA issues.
+pub fn acceptor(config: &TlsConfig, ca: &ClusterCa) -> Result<TlsAcceptor> {
+    let (cert, key) = match (&config.cert_file, &config.key_file) {
+        (Some(cert), Some(key)) => (read(cert)?, read(key)?),
+        _ => {
+            let (cert, key) = ca.issue("kube-apiserver", &[], Usage::Server(SERVING_NAMES))?;
+            (cert.into_bytes(), key.into_bytes())
+        }
+    };
+    let chain = X509::stack_from_pem(&cert)?
+        .iter()
+        .map(|cert| Ok(Certificate(cert.to_der()?)))
+        .collect::<Result<Vec<_>>>()?;
+    let key = PrivateKey(PKey::private_key_from_pem(&key)?.private_key_to_pkcs8()?);
+
+    let client_cas = match &config.client_ca_file {
+        Some(path) => read(path)?,
+        None => ca.cert_pem()?.into_bytes(),
+    };
+    let mut roots = RootCertStore::empty();
+    for cert in X509::stack_from_pem(&client_cas)? {
+        roots.add(&Certificate(cert.to_der()?))?;
+    }
+
+    let mut server = ServerConfig::builder()
+        .with_safe_defaults()
+        .with_client_cert_verifier(AllowAnyAnonymousOrAuthenticatedClient::new(roots).boxed())
+        .with_single_cert(chain, key)?;
+    server.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(TlsAcceptor::from(Arc::new(server)))
+}
+
+/// Serves `router` over TLS on every connection `listener` accepts.
+pub async fn serve(listener: TcpListener, router: Router, acceptor: TlsAcceptor) -> Result<()> {
+    loop {
+        let (stream, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                error!("Failed to accept connection: {}", e);
+                continue;
+            }
+        };
+        let acceptor = acceptor.clone();
+        let router = router.clone();
+        tokio::spawn(async move {
+            let stream = match acceptor.accept(stream).await {
+                Ok(stream) => stream,
+                Err(e) => {
+                    debug!("TLS handshake with {} failed: {}", peer, e);
+                    return;
+                }
+            };
+            let certificate = stream
+                .get_ref()
+                .1
+                .peer_certificates()
+                .and_then(|certs| certs.first())
+                .and_then(|cert| client_user(&cert.0))
+                .map(ClientCertificate);
+
+            let service = service_fn(move |mut request: Request<Incoming>| {
+                if let Some(certificate) = &certificate {
+                    request.extensions_mut().insert(certificate.clone());
+                }
+                router.clone().call(request)
+            });
+            let connection = auto::Builder::new(TokioExecutor::new());
+            if let Err(e) = connection
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Connection from {} failed: {}", peer, e);
+            }
+        });
+    }
+}
+
+// The user a client certificate authenticates as: its common name, in the
+// groups its organizations name
+fn client_user(der: &[u8]) -> Option<UserInfo> {
+    let cert = X509::from_der(der).ok()?;
+    let entries = |nid: Nid| -> Vec<String> {
+        cert.subject_name()
+            .entries_by_nid(nid)
+            .filter_map(|entry| entry.data().as_utf8().ok().map(|s| s.to_string()))
+            .collect()
+    };
+    let username = entries(Nid::COMMONNAME).into_iter().next()?;
+    let mut groups = entries(Nid::ORGANIZATIONNAME);
+    groups.push("system:authenticated".to_string());
+    Some(UserInfo { username, groups, ..Default::default() })
+}
+
+/// Writes a kubeconfig for `server` to the data directory, trusting the
+/// cluster CA and authenticating with a fresh admin client certificate.
+pub fn write_kubeconfig(data_dir: &DataDir, ca: &ClusterCa, server: &str) -> Result<PathBuf> {
+    let (cert, key) = ca.issue(ADMIN_USER, &[ADMIN_GROUP], Usage::Client)?;
+    let encode = |pem: &str| STANDARD.encode(pem.as_bytes());
+    let kubeconfig = json!({
+        "apiVersion": "v1",
+        "kind": "Config",
+        "clusters": [{
+            "name": "krust",
+            "cluster": { "server": server, "certificate-authority-data": encode(&ca.cert_pem()?) }
+        }],
+        "users": [{
+            "name": ADMIN_USER,
+            "user": { "client-certificate-data": encode(&cert), "client-key-data": encode(&key) }
+        }],
+        "contexts": [{
+            "name": "krust",
+            "context": { "cluster": "krust", "user": ADMIN_USER }
+        }],
+        "current-context": "krust"
+    });
+
+    let path = data_dir.kubeconfig();
+    pki::write_private(&path, serde_yaml::to_string(&kubeconfig)?.as_bytes())?;
+    info!("Wrote kubeconfig for {} to {}", server, path.display());
+    Ok(path)
+}
+
+fn read(path: &str) -> Result<Vec<u8>> {
+    std::fs::read(path).with_context(|| format!("failed to read {}", path))
+}
@@ -0,0 +1,242 @@
+// Watches, as served on every list endpoint with `?watch=true`. Like
+// kube-apiserver, the response is a stream of watch events, one JSON object
+// per line. Without a resourceVersion to start from, the watch opens with an
+// ADDED event for every object in the list; with one, it replays the events
+// after it, or fails with 410 Gone if those have been compacted away. With
+// `allowWatchBookmarks=true` it also sends BOOKMARK events carrying the
+// latest resource version, every minute and just before `timeoutSeconds`
+// runs out, unless the WatchBookmark feature gate is off. A watch ends as
+// soon as its client disconnects.
+//
+// A list is current as of the latest write, unless it asks otherwise:
+// `resourceVersionMatch=NotOlderThan` (or a resourceVersion alone) only
+// checks the latest write isn't older than the one given, and
+// `resourceVersionMatch=Exact` serves the list as it was at that version,
+// undoing the writes since from their watch events.
+use axum::{
+    body::Body,
+    http::{header, StatusCode},
+    response::{IntoResponse, Json, Response},
+};
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::convert::Infallible;
+use std::future::Future;
+use std::time::Duration;
+use tokio::time::Instant;
+
+use super::cancellation::Cancellation;
+use super::error_status::{ApiError, Cause};
+use super::handlers::ListParams;
+use super::server::AppState;
+use crate::feature_gates;
+use crate::storage::ListSelector;
+
+const BOOKMARK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Answers a list request for `resource_type` objects, in `namespace` if
+/// there is one: the list `list` produces, stamped with the resource version
+/// it's current as of, or a watch when the request asks for one.
+pub async fn list_or_watch(
+    state: &AppState,
+    resource_type: &str,
+    namespace: Option<&str>,
+    params: &ListParams,
+    selector: &ListSelector,
+    list: impl Future<Output = anyhow::Result<Value>>,
+) -> anyhow::Result<Response> {
+    // Taken before listing, so a write racing the list is in the watch
+    // rather than in neither
+    let version = state.storage.watch().latest_version().await?;
+    let mut list = list.await?;
+    list["metadata"]["resourceVersion"] = json!(version.to_string());
+
+    if let Some(message) = invalid_list_options(params) {
+        return Ok(invalid(&message));
+    }
+
+    if params.watch != Some(true) {
+        let requested = match params.resource_version.as_deref() {
+            None | Some("") | Some("0") => return Ok(Json(list).into_response()),
+            Some(rv) => rv.parse::<i64>().map_err(|_| anyhow::anyhow!("invalid resourceVersion: {}", rv))?,
+        };
+        if requested > version {
+            return Ok(too_large(requested, version));
+        }
+        if params.resource_version_match.as_deref() != Some("Exact") || requested == version {
+            return Ok(Json(list).into_response());
+        }
+
+        let compacted = state.storage.watch().compacted_version().await?;
+        if requested < compacted {
+            return Ok(expired(requested, compacted));
+        }
+        let items = list["items"].as_array().cloned().unwrap_or_default();
+        let keep = |object: &Value| selector.matches(|field| field_value(object, field), &object["metadata"]["labels"]).unwrap_or(false);
+        let Some(items) = state.storage.watch().items_at(resource_type, namespace, requested, items, keep).await? else {
+            return Ok(expired(requested, compacted));
+        };
+        list["items"] = json!(items);
+        list["metadata"]["resourceVersion"] = json!(requested.to_string());
+        return Ok(Json(list).into_response());
+    }
+
+    let since = match params.resource_version.as_deref() {
+        None | Some("") | Some("0") => None,
+        Some(rv) => Some(rv.parse::<i64>().map_err(|_| anyhow::anyhow!("invalid resourceVersion: {}", rv))?),
+    };
+    // Events from before the compacted version are gone, so the watch would
+    // miss them; the client has to list again
+    let compacted = state.storage.watch().compacted_version().await?;
+    if let Some(since) = since.filter(|&since| since < compacted) {
+        return Ok(expired(since, compacted));
+    }
+
+    let initial: Vec<Value> = match since {
+        Some(_) => Vec::new(),
+        None => list["items"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|item| json!({ "type": "ADDED", "object": item }))
+            .collect(),
+    };
+    let start = since.unwrap_or(version);
+
+    let mut events = state
+        .storage
+        .watch()
+        .watch_stream(resource_type.to_string(), namespace.map(str::to_string), Some(start.to_string()))
+        .await?;
+
+    // Bookmarks are bare objects of the listed kind
+    let kind = list["kind"].as_str().unwrap_or_default().trim_end_matches("List").to_string();
+    let api_version = list["apiVersion"].clone();
+    let bookmarks = params.allow_watch_bookmarks == Some(true) && state.config.feature_gates.enabled(feature_gates::WATCH_BOOKMARK);
+    let deadline = params
+        .timeout_seconds
+        .filter(|&seconds| seconds > 0)
+        .map(|seconds| Instant::now() + Duration::from_secs(seconds));
+    let selector = selector.clone();
+    let resource_type = resource_type.to_string();
+    let mut cancellation = Cancellation::current();
+
+    let stream = async_stream::stream! {
+        for event in initial {
+            yield line(&event);
+        }
+
+        let mut latest = start;
+        let bookmark = |version: i64| json!({
+            "type": "BOOKMARK",
+            "object": { "kind": kind, "apiVersion": api_version, "metadata": { "resourceVersion": version.to_string() } }
+        });
+        let mut next_bookmark = Instant::now() + BOOKMARK_INTERVAL;
+        loop {
+            let timed_out = async {
+                match deadline {
+                    Some(deadline) => tokio::time::sleep_until(deadline).await,
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                event = events.next() => match event {
+                    Some(Ok(event)) => {
+                        let object = &event["object"];
+                        if let Some(version) = object["metadata"]["resourceVersion"].as_str().and_then(|rv| rv.parse().ok()) {
+                            latest = version;
+                        }
+                        let matches = selector.matches(|field| field_value(object, field), &object["metadata"]["labels"]);
+                        if matches.unwrap_or(false) {
+                            yield line(&event);
+                        }
+                    }
+                    Some(Err(e)) => {
+                        tracing::error!("Watch on {} failed: {}", resource_type, e);
+                        break;
+                    }
+                    None => break,
+                },
+                _ = tokio::time::sleep_until(next_bookmark), if bookmarks => {
+                    yield line(&bookmark(latest));
+                    next_bookmark = Instant::now() + BOOKMARK_INTERVAL;
+                }
+                _ = timed_out => {
+                    if bookmarks {
+                        yield line(&bookmark(latest));
+                    }
+                    break;
+                }
+                // The client is gone; stop reading events for it
+                _ = cancellation.cancelled() => break,
+            }
+        }
+    };
+
+    Ok(([(header::CONTENT_TYPE, "application/json")], Body::from_stream(stream.map(Ok::<_, Infallible>))).into_response())
+}
+
+// The 410 Gone Status for a watch or exact list from `since`, which is
+// older than what the events table still holds
+fn expired(since: i64, compacted: i64) -> Response {
+    let message = format!("too old resource version: {} ({})", since, compacted);
+    ApiError::new(StatusCode::GONE, "Expired", message).into_response()
+}
+
+// Why the resourceVersionMatch of a request can't be served, if it can't
+fn invalid_list_options(params: &ListParams) -> Option<String> {
+    let rv_match = params.resource_version_match.as_deref().filter(|m| !m.is_empty())?;
+    let rv = params.resource_version.as_deref().unwrap_or("");
+    let message = if rv_match != "Exact" && rv_match != "NotOlderThan" {
+        format!("resourceVersionMatch: Unsupported value: \"{}\": supported values: \"Exact\", \"NotOlderThan\"", rv_match)
+    } else if params.watch == Some(true) {
+        "resourceVersionMatch: Forbidden: resourceVersionMatch is forbidden for watch".to_string()
+    } else if rv.is_empty() {
+        "resourceVersionMatch: Forbidden: resourceVersionMatch is forbidden unless resourceVersion is provided".to_string()
+    } else if rv_match == "Exact" && rv == "0" {
+        "resourceVersionMatch: Forbidden: resourceVersionMatch \"exact\" is forbidden for resourceVersion \"0\"".to_string()
+    } else {
+        return None;
+    };
+    Some(message)
+}
+
+// The 422 Invalid Status for list options that can't be served
+fn invalid(message: &str) -> Response {
+    let cause = Cause::parse(message);
+    ApiError::new(StatusCode::UNPROCESSABLE_ENTITY, "Invalid", format!("ListOptions.meta.k8s.io \"\" is invalid: {}", message))
+        .with_details(json!({
+            "group": "meta.k8s.io",
+            "kind": "ListOptions",
+            "causes": [{ "reason": cause.reason, "message": cause.message, "field": cause.field }]
+        }))
+        .into_response()
+}
+
+// The 504 Status for a list at least as new as `requested`, a version not
+// written yet. Clients wait and retry.
+fn too_large(requested: i64, current: i64) -> Response {
+    let message = format!("Too large resource version: {}, current: {}", requested, current);
+    ApiError::new(StatusCode::GATEWAY_TIMEOUT, "Timeout", message)
+        .with_details(json!({
+            "causes": [{ "reason": "ResourceVersionTooLarge", "message": "Too large resource version" }],
+            "retryAfterSeconds": 1
+        }))
+        .into_response()
+}
+
+fn line(event: &Value) -> String {
+    format!("{}\n", event)
+}
+
+// Reads a field label of a watched object for its field selector. Every
+// dotted path can be read, and one the object lacks reads as empty, like
+// the columns lists select on.
+fn field_value(object: &Value, field: &str) -> Option<String> {
+    let value = field.split('.').fold(object, |value, key| &value[key]);
+    Some(match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    })
+}
@@ -1,30 +1,26 @@
 use axum::{
     extract::{Path, Query, State},
     http::StatusCode,
-    response::Json,
+    response::{Json, Response},
 };
-use serde::Deserialize;
 use serde_json::Value;
 
+use super::error_status::ApiError;
+use super::handlers::{list_error, ListParams};
 use super::server::AppState;
-
-#[derive(Deserialize)]
-pub struct ListParams {
-    #[serde(rename = "labelSelector")]
-    label_selector: Option<String>,
-    limit: Option<i32>,
-}
+use super::watch::list_or_watch;
 
 // ValidatingWebhookConfiguration handlers
 pub async fn list_validating_webhooks(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.validating_webhooks().list().await {
-        Ok(vwcs) => Ok(Json(vwcs)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "validatingwebhookconfigurations", None, &params, &selector, state.storage.validating_webhooks().list_matching(&selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list validating webhook configurations: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -32,12 +28,14 @@ pub async fn list_validating_webhooks(
 pub async fn create_validating_webhook(
     State(state): State<AppState>,
     Json(vwc): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = vwc["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.validating_webhooks().create(vwc).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("validatingwebhookconfigurations", &name)),
         Err(e) => {
             tracing::error!("Failed to create validating webhook configuration: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -45,13 +43,13 @@ pub async fn create_validating_webhook(
 pub async fn get_validating_webhook(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.validating_webhooks().get(&name).await {
         Ok(Some(vwc)) => Ok(Json(vwc)),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Ok(None) => Err(ApiError::not_found("validatingwebhookconfigurations", &name)),
         Err(e) => {
             tracing::error!("Failed to get validating webhook configuration: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -60,12 +58,12 @@ pub async fn update_validating_webhook(
     State(state): State<AppState>,
     Path(name): Path<String>,
     Json(vwc): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.validating_webhooks().update(&name, vwc).await {
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             tracing::error!("Failed to update validating webhook configuration: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -73,12 +71,12 @@ pub async fn update_validating_webhook(
 pub async fn delete_validating_webhook(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.validating_webhooks().delete(&name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             tracing::error!("Failed to delete validating webhook configuration: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -86,13 +84,14 @@ pub async fn delete_validating_webhook(
 // MutatingWebhookConfiguration handlers
 pub async fn list_mutating_webhooks(
     State(state): State<AppState>,
-    Query(_params): Query<ListParams>,
-) -> Result<Json<Value>, StatusCode> {
-    match state.storage.mutating_webhooks().list().await {
-        Ok(mwcs) => Ok(Json(mwcs)),
+    Query(params): Query<ListParams>,
+) -> Result<Response, ApiError> {
+    let selector = params.selector()?;
+    match list_or_watch(&state, "mutatingwebhookconfigurations", None, &params, &selector, state.storage.mutating_webhooks().list_matching(&selector)).await {
+        Ok(response) => Ok(response),
         Err(e) => {
             tracing::error!("Failed to list mutating webhook configurations: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(list_error(&e))
         }
     }
 }
@@ -100,12 +99,14 @@ pub async fn list_mutating_webhooks(
 pub async fn create_mutating_webhook(
     State(state): State<AppState>,
     Json(mwc): Json<Value>,
-) -> Result<(StatusCode, Json<Value>), StatusCode> {
+) -> Result<(StatusCode, Json<Value>), ApiError> {
+    let name = mwc["metadata"]["name"].as_str().unwrap_or_default().to_string();
     match state.storage.mutating_webhooks().create(mwc).await {
         Ok(created) => Ok((StatusCode::CREATED, Json(created))),
+        Err(e) if e.to_string().contains("UNIQUE constraint") => Err(ApiError::already_exists("mutatingwebhookconfigurations", &name)),
         Err(e) => {
             tracing::error!("Failed to create mutating webhook configuration: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -113,13 +114,13 @@ pub async fn create_mutating_webhook(
 pub async fn get_mutating_webhook(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.mutating_webhooks().get(&name).await {
         Ok(Some(mwc)) => Ok(Json(mwc)),
-        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Ok(None) => Err(ApiError::not_found("mutatingwebhookconfigurations", &name)),
         Err(e) => {
             tracing::error!("Failed to get mutating webhook configuration: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -128,12 +129,12 @@ pub async fn update_mutating_webhook(
     State(state): State<AppState>,
     Path(name): Path<String>,
     Json(mwc): Json<Value>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.mutating_webhooks().update(&name, mwc).await {
         Ok(updated) => Ok(Json(updated)),
         Err(e) => {
             tracing::error!("Failed to update mutating webhook configuration: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
@@ -141,12 +142,12 @@ pub async fn update_mutating_webhook(
 pub async fn delete_mutating_webhook(
     State(state): State<AppState>,
     Path(name): Path<String>,
-) -> Result<Json<Value>, StatusCode> {
+) -> Result<Json<Value>, ApiError> {
     match state.storage.mutating_webhooks().delete(&name).await {
         Ok(deleted) => Ok(Json(deleted)),
         Err(e) => {
             tracing::error!("Failed to delete mutating webhook configuration: {}", e);
-            Err(StatusCode::INTERNAL_SERVER_ERROR)
+            Err(ApiError::internal(&e))
         }
     }
 }
\ No newline at end of file
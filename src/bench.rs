@@ -0,0 +1,381 @@
+// krust-bench: load generation against a running krust, started with
+// `krust bench`. It creates, updates and deletes synthetic pods and
+// deployments at a fixed rate, timing every request, while a pod watch
+// measures how long each write takes to show up as a watch event.
+//
+// The objects never cost anything to run: pods select a node that doesn't
+// exist, so they stay Pending, and deployments have no replicas.
+use anyhow::{anyhow, bail, Context, Result};
+use futures::StreamExt;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::MissedTickBehavior;
+
+const BENCH_LABEL: &str = "krust.io/bench";
+
+// How long to wait for the watch to catch up once all writes are done
+const WATCH_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct BenchOptions {
+    /// Base URL of the API server.
+    pub server: String,
+    pub namespace: String,
+    pub pods: usize,
+    pub deployments: usize,
+    /// Requests per second across all workers; 0 sends them as fast as the
+    /// workers can.
+    pub rate: f64,
+    /// Requests in flight at once.
+    pub concurrency: usize,
+}
+
+impl Default for BenchOptions {
+    fn default() -> Self {
+        Self {
+            server: "http://127.0.0.1:6443".to_string(),
+            namespace: "default".to_string(),
+            pods: 1000,
+            deployments: 100,
+            rate: 200.0,
+            concurrency: 16,
+        }
+    }
+}
+
+impl BenchOptions {
+    pub const USAGE: &'static str = "usage: krust bench [--server URL] [--namespace NAME] [--pods N] \
+                                     [--deployments N] [--rate PER_SECOND] [--concurrency N]";
+
+    /// Parses the arguments following `bench`.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| anyhow!("{} requires a value\n{}", flag, Self::USAGE))
+            };
+            match flag.as_str() {
+                "--server" => options.server = value()?.trim_end_matches('/').to_string(),
+                "--namespace" => options.namespace = value()?,
+                "--pods" => options.pods = number(&flag, &value()?)?,
+                "--deployments" => options.deployments = number(&flag, &value()?)?,
+                "--rate" => options.rate = number(&flag, &value()?)?,
+                "--concurrency" => options.concurrency = number::<usize>(&flag, &value()?)?.max(1),
+                _ => bail!("unknown argument: {}\n{}", flag, Self::USAGE),
+            }
+        }
+        Ok(options)
+    }
+}
+
+fn number<T: std::str::FromStr>(flag: &str, value: &str) -> Result<T> {
+    value.parse().map_err(|_| anyhow!("{}: not a number: {}", flag, value))
+}
+
+/// Latency percentiles of one kind of measurement.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Summary {
+    pub count: usize,
+    pub p50: Duration,
+    pub p90: Duration,
+    pub p99: Duration,
+    pub max: Duration,
+}
+
+impl Summary {
+    pub fn of(mut samples: Vec<Duration>) -> Self {
+        samples.sort();
+        let percentile = |p: usize| {
+            if samples.is_empty() {
+                return Duration::ZERO;
+            }
+            // Nearest rank
+            let rank = (p * samples.len()).div_ceil(100).max(1);
+            samples[rank - 1]
+        };
+        Self {
+            count: samples.len(),
+            p50: percentile(50),
+            p90: percentile(90),
+            p99: percentile(99),
+            max: samples.last().copied().unwrap_or_default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    /// Request latencies keyed by operation, e.g. "create pods".
+    pub requests: BTreeMap<String, Summary>,
+    /// Time from sending a pod write to receiving its watch event.
+    pub watch_lag: Summary,
+    /// Pod writes whose event never arrived.
+    pub missed_events: usize,
+    /// Requests that failed, by operation.
+    pub errors: BTreeMap<String, usize>,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = |d: Duration| format!("{:.1}ms", d.as_secs_f64() * 1000.0);
+        let total: usize = self.requests.values().map(|s| s.count).sum();
+
+        writeln!(f, "{:<20} {:>7} {:>10} {:>10} {:>10} {:>10} {:>7}", "OPERATION", "COUNT", "P50", "P90", "P99", "MAX", "ERRORS")?;
+        let rows = self
+            .requests
+            .iter()
+            .map(|(operation, summary)| (operation.as_str(), summary, self.errors.get(operation).copied().unwrap_or(0)))
+            .chain(std::iter::once(("watch lag (pods)", &self.watch_lag, self.missed_events)));
+        for (operation, s, errors) in rows {
+            writeln!(
+                f,
+                "{:<20} {:>7} {:>10} {:>10} {:>10} {:>10} {:>7}",
+                operation, s.count, ms(s.p50), ms(s.p90), ms(s.p99), ms(s.max), errors
+            )?;
+        }
+        write!(
+            f,
+            "\n{} requests in {:.1}s ({:.1}/s)",
+            total,
+            self.elapsed.as_secs_f64(),
+            total as f64 / self.elapsed.as_secs_f64().max(f64::EPSILON)
+        )
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Pods,
+    Deployments,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Pods => "pods",
+            Self::Deployments => "deployments",
+        }
+    }
+
+    fn collection(self, options: &BenchOptions) -> String {
+        match self {
+            Self::Pods => format!("{}/api/v1/namespaces/{}/pods", options.server, options.namespace),
+            Self::Deployments => format!("{}/apis/apps/v1/namespaces/{}/deployments", options.server, options.namespace),
+        }
+    }
+
+    fn object(self, name: &str, run: &str) -> Value {
+        let labels = json!({ BENCH_LABEL: run, "app": name });
+        let pod_spec = json!({
+            "nodeSelector": { BENCH_LABEL: "unschedulable" },
+            "containers": [{ "name": "app", "image": "registry.k8s.io/pause:3.9" }]
+        });
+        match self {
+            Self::Pods => json!({
+                "apiVersion": "v1",
+                "kind": "Pod",
+                "metadata": { "name": name, "labels": labels },
+                "spec": pod_spec
+            }),
+            Self::Deployments => json!({
+                "apiVersion": "apps/v1",
+                "kind": "Deployment",
+                "metadata": { "name": name, "labels": labels },
+                "spec": {
+                    "replicas": 0,
+                    "selector": { "matchLabels": { "app": name } },
+                    "template": { "metadata": { "labels": labels }, "spec": pod_spec }
+                }
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Operation {
+    Create,
+    Update,
+    Delete,
+}
+
+impl Operation {
+    fn name(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Update => "update",
+            Self::Delete => "delete",
+        }
+    }
+
+    // The watch event a write of this kind produces
+    fn event(self) -> &'static str {
+        match self {
+            Self::Create => "ADDED",
+            Self::Update => "MODIFIED",
+            Self::Delete => "DELETED",
+        }
+    }
+}
+
+// Pod writes waiting for their watch event, by event type and pod name
+type Pending = Arc<Mutex<HashMap<(String, String), Instant>>>;
+
+struct Bench {
+    options: BenchOptions,
+    client: reqwest::Client,
+    run: String,
+    pending: Pending,
+    latencies: Mutex<BTreeMap<String, Vec<Duration>>>,
+    errors: Mutex<BTreeMap<String, usize>>,
+}
+
+/// Runs the benchmark against the server in `options` and reports on it.
+pub async fn run(options: &BenchOptions) -> Result<Report> {
+    let bench = Arc::new(Bench {
+        options: options.clone(),
+        client: reqwest::Client::new(),
+        run: uuid::Uuid::new_v4().simple().to_string()[..8].to_string(),
+        pending: Arc::default(),
+        latencies: Mutex::default(),
+        errors: Mutex::default(),
+    });
+
+    let lags = Arc::new(Mutex::new(Vec::new()));
+    let watch = bench.watch_pods(lags.clone()).await?;
+
+    let started = Instant::now();
+    for operation in [Operation::Create, Operation::Update, Operation::Delete] {
+        bench.clone().phase(Kind::Pods, operation, options.pods).await;
+        bench.clone().phase(Kind::Deployments, operation, options.deployments).await;
+    }
+    let elapsed = started.elapsed();
+
+    let deadline = Instant::now() + WATCH_DRAIN_TIMEOUT;
+    while !bench.pending.lock().unwrap().is_empty() && Instant::now() < deadline {
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    watch.abort();
+
+    let requests = std::mem::take(&mut *bench.latencies.lock().unwrap())
+        .into_iter()
+        .map(|(operation, samples)| (operation, Summary::of(samples)))
+        .collect();
+    let watch_lag = Summary::of(std::mem::take(&mut *lags.lock().unwrap()));
+    let missed_events = bench.pending.lock().unwrap().len();
+    let errors = std::mem::take(&mut *bench.errors.lock().unwrap());
+    Ok(Report {
+        requests,
+        watch_lag,
+        missed_events,
+        errors,
+        elapsed,
+    })
+}
+
+impl Bench {
+    // Sends `count` requests of one kind, at most `concurrency` at a time and
+    // no faster than `rate`
+    async fn phase(self: Arc<Self>, kind: Kind, operation: Operation, count: usize) {
+        let ticker = (self.options.rate > 0.0).then(|| {
+            let mut ticker = tokio::time::interval(Duration::from_secs_f64(1.0 / self.options.rate));
+            ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            tokio::sync::Mutex::new(ticker)
+        });
+        let ticker = &ticker;
+
+        futures::stream::iter(0..count)
+            .for_each_concurrent(self.options.concurrency, |i| {
+                let bench = self.clone();
+                async move {
+                    if let Some(ticker) = ticker {
+                        ticker.lock().await.tick().await;
+                    }
+                    bench.send(kind, operation, &format!("bench-{}-{}", bench.run, i)).await;
+                }
+            })
+            .await;
+    }
+
+    async fn send(&self, kind: Kind, operation: Operation, name: &str) {
+        let collection = kind.collection(&self.options);
+        let request = match operation {
+            Operation::Create => self.client.post(&collection).json(&kind.object(name, &self.run)),
+            Operation::Update => self
+                .client
+                .patch(format!("{}/{}", collection, name))
+                .header("Content-Type", "application/merge-patch+json")
+                .body(json!({ "metadata": { "annotations": { BENCH_LABEL: "updated" } } }).to_string()),
+            Operation::Delete => self.client.delete(format!("{}/{}", collection, name)),
+        };
+
+        let sent = Instant::now();
+        if let Kind::Pods = kind {
+            self.pending
+                .lock()
+                .unwrap()
+                .insert((operation.event().to_string(), name.to_string()), sent);
+        }
+        let result = request.send().await;
+
+        let key = format!("{} {}", operation.name(), kind.name());
+        match result {
+            Ok(resp) if resp.status().is_success() => {
+                self.latencies.lock().unwrap().entry(key).or_default().push(sent.elapsed());
+            }
+            _ => {
+                *self.errors.lock().unwrap().entry(key).or_default() += 1;
+                self.pending
+                    .lock()
+                    .unwrap()
+                    .remove(&(operation.event().to_string(), name.to_string()));
+            }
+        }
+    }
+
+    // Starts watching the namespace's pods, recording for every awaited event
+    // how long after its write it arrived
+    async fn watch_pods(&self, lags: Arc<Mutex<Vec<Duration>>>) -> Result<tokio::task::JoinHandle<()>> {
+        let url = format!("{}/api/v1/namespaces/{}/pods?watch=true", self.options.server, self.options.namespace);
+        let mut resp = self
+            .client
+            .get(&url)
+            .send()
+            .await
+            .and_then(|resp| resp.error_for_status())
+            .with_context(|| format!("failed to watch pods at {}", url))?;
+
+        let pending = self.pending.clone();
+        Ok(tokio::spawn(async move {
+            let mut buffer = Vec::new();
+            while let Ok(Some(chunk)) = resp.chunk().await {
+                let received = Instant::now();
+                buffer.extend_from_slice(&chunk);
+                while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                    // Events come one JSON document per line
+                    let line: Vec<u8> = buffer.drain(..=end).collect();
+                    let Ok(event) = serde_json::from_slice::<Value>(&line) else {
+                        continue;
+                    };
+                    let (Some(event_type), Some(name)) = (event["type"].as_str(), event["object"]["metadata"]["name"].as_str()) else {
+                        continue;
+                    };
+                    if let Some(sent) = pending.lock().unwrap().remove(&(event_type.to_string(), name.to_string())) {
+                        lags.lock().unwrap().push(received.duration_since(sent));
+                    }
+                }
+            }
+        }))
+    }
+}
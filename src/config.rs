@@ -0,0 +1,804 @@
+// Server configuration, read from a YAML (or JSON) file given with
+// `--config <path>` or the KRUST_CONFIG environment variable. Every section
+// is optional, so an empty file is the same as running without one.
+use anyhow::{anyhow, bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use crate::data_dir::DataDir;
+use crate::feature_gates::{FeatureGates, HPA_SCALE_TO_ZERO};
+use crate::models::quantity::{self, Resources};
+use crate::storage::{compression, LabelSelector};
+
+/// The node backed by the real kubelet. Any other node listed under `nodes`
+/// is simulated: pods bound to it are run by a fake kubelet.
+pub const NODE_NAME: &str = "krust-node";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Config {
+    pub namespace_defaults: NamespaceDefaults,
+    /// Reusable node settings, referenced from `nodes` with `profile`.
+    pub node_profiles: HashMap<String, NodeConfig>,
+    pub nodes: HashMap<String, NodeConfig>,
+    pub jobs: JobConfig,
+    pub autoscaling: AutoscalingConfig,
+    pub controllers: ControllersConfig,
+    pub streaming: StreamingConfig,
+    pub api_server: ApiServerConfig,
+    pub dns: DnsConfig,
+    pub network_policy: NetworkPolicyConfig,
+    pub authentication: AuthenticationConfig,
+    pub authorization: AuthorizationConfig,
+    pub tls: TlsConfig,
+    pub admission: AdmissionConfig,
+    pub storage: StorageConfig,
+    /// Feature gates set in the file; `--feature-gates` sets them too.
+    pub feature_gates: FeatureGates,
+    /// Where the database, CA and pod files are kept. Set by `--data-dir`
+    /// and KRUST_DATA_DIR too; the binary defaults it to ~/.krust. Without
+    /// one nothing is written to disk, as for the in-memory test servers.
+    pub data_dir: Option<PathBuf>,
+}
+
+/// Objects created in every new namespace.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NamespaceDefaults {
+    pub limit_ranges: Vec<Value>,
+    pub resource_quotas: Vec<Value>,
+}
+
+/// Settings for a node, keyed by node name under `nodes`. Fields not given
+/// for a node come from its profile, and then from the defaults.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NodeConfig {
+    /// Entry of `nodeProfiles` this node is based on.
+    pub profile: Option<String>,
+    /// Pod capacity reported in the node status and honored by the scheduler.
+    pub pods: usize,
+    /// The kubelet's own limit; pods bound beyond it fail with OutOfpods.
+    /// Defaults to `pods`.
+    pub max_pods: Option<usize>,
+    /// Allocatable CPU and memory, as resource quantities. They default to
+    /// what the host has, see `host_cpu` and `host_memory`.
+    pub cpu: String,
+    pub memory: String,
+    /// Ephemeral storage the node offers its pods, as a resource quantity,
+    /// reported in its capacity. The node reports DiskPressure once its
+    /// pods use more than 90% of it, and never without it.
+    pub ephemeral_storage: Option<String>,
+    /// Reported as kubernetes.io/arch and in the node info.
+    pub architecture: String,
+    /// Address of the node, which hostNetwork pods on it share.
+    #[serde(rename = "internalIP")]
+    pub internal_ip: String,
+    /// Set as the node.kubernetes.io/instance-type label.
+    pub instance_type: Option<String>,
+    /// Set as the topology.kubernetes.io/zone and region labels.
+    pub zone: Option<String>,
+    pub region: Option<String>,
+    /// Extra node labels; profile labels are merged with the node's own.
+    pub labels: BTreeMap<String, String>,
+    pub taints: Vec<Taint>,
+    /// Kinds of security profile the node's container runtime can apply,
+    /// `seccomp` and `apparmor`. Only simulated nodes use it; krust-node's
+    /// kubelet asks Docker.
+    pub security_profiles: Vec<String>,
+}
+
+impl Default for NodeConfig {
+    fn default() -> Self {
+        Self {
+            profile: None,
+            pods: 110,
+            max_pods: None,
+            cpu: host_cpu().to_string(),
+            memory: host_memory().to_string(),
+            ephemeral_storage: None,
+            architecture: "amd64".to_string(),
+            internal_ip: "127.0.0.1".to_string(),
+            instance_type: None,
+            zone: None,
+            region: None,
+            labels: BTreeMap::new(),
+            taints: Vec::new(),
+            security_profiles: SECURITY_PROFILES.iter().map(|kind| kind.to_string()).collect(),
+        }
+    }
+}
+
+/// CPUs available to this process, which takes affinity masks and cgroup
+/// quotas into account, so a container on a small CI runner reports what it
+/// can really use. Falls back to 8 when it can't be told.
+pub fn host_cpu() -> &'static str {
+    static CPU: OnceLock<String> = OnceLock::new();
+    CPU.get_or_init(|| {
+        std::thread::available_parallelism()
+            .map(|n| n.to_string())
+            .unwrap_or_else(|_| "8".to_string())
+    })
+}
+
+/// Memory of the host from /proc/meminfo, capped by the cgroup memory limit
+/// when there is one. Falls back to 16Gi off Linux.
+pub fn host_memory() -> &'static str {
+    static MEMORY: OnceLock<String> = OnceLock::new();
+    MEMORY.get_or_init(|| {
+        let total = std::fs::read_to_string("/proc/meminfo").ok().and_then(|meminfo| {
+            let line = meminfo.lines().find(|line| line.starts_with("MemTotal:"))?;
+            let kib: u64 = line.split_whitespace().nth(1)?.parse().ok()?;
+            Some(kib * 1024)
+        });
+        // cgroup v2, then v1; an unlimited v1 group reads as a huge number
+        let limit = ["/sys/fs/cgroup/memory.max", "/sys/fs/cgroup/memory/memory.limit_in_bytes"]
+            .iter()
+            .find_map(|path| std::fs::read_to_string(path).ok()?.trim().parse::<u64>().ok());
+
+        match (total, limit) {
+            (Some(total), Some(limit)) => format!("{}Ki", total.min(limit) / 1024),
+            (Some(total), None) => format!("{}Ki", total / 1024),
+            (None, _) => "16Gi".to_string(),
+        }
+    })
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Taint {
+    pub key: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub value: Option<String>,
+    pub effect: String,
+}
+
+impl NodeConfig {
+    pub fn kubelet_max_pods(&self) -> usize {
+        self.max_pods.unwrap_or(self.pods)
+    }
+
+    /// Allocatable CPU and memory. Both are checked by `Config::validate`.
+    pub fn capacity(&self) -> Resources {
+        Resources {
+            cpu_millis: quantity::cpu_millis(&Value::from(self.cpu.as_str())).unwrap_or(0),
+            memory_bytes: quantity::bytes(&Value::from(self.memory.as_str())).unwrap_or(0),
+        }
+    }
+
+    /// The node's labels: the well-known ones derived from its settings,
+    /// overridden by any set explicitly.
+    pub fn node_labels(&self, name: &str) -> BTreeMap<String, String> {
+        let mut labels = BTreeMap::from([
+            ("kubernetes.io/hostname".to_string(), name.to_string()),
+            ("kubernetes.io/os".to_string(), "linux".to_string()),
+            ("kubernetes.io/arch".to_string(), self.architecture.clone()),
+        ]);
+        let topology = [
+            ("node.kubernetes.io/instance-type", &self.instance_type),
+            ("topology.kubernetes.io/zone", &self.zone),
+            ("topology.kubernetes.io/region", &self.region),
+        ];
+        for (key, value) in topology {
+            if let Some(value) = value {
+                labels.insert(key.to_string(), value.clone());
+            }
+        }
+        labels.extend(self.labels.clone());
+        labels
+    }
+}
+
+/// Job controller settings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct JobConfig {
+    /// Delay before replacing the first failed pod of a Job; it doubles with
+    /// every further failure.
+    pub backoff_seconds: u64,
+    /// Upper bound for the replacement delay.
+    pub max_backoff_seconds: u64,
+}
+
+impl Default for JobConfig {
+    fn default() -> Self {
+        Self {
+            backoff_seconds: 10,
+            max_backoff_seconds: 360,
+        }
+    }
+}
+
+/// HorizontalPodAutoscaler controller settings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AutoscalingConfig {
+    /// How often every HPA is evaluated, like kube-controller-manager's
+    /// --horizontal-pod-autoscaler-sync-period.
+    pub sync_period_seconds: u64,
+    /// Let HPAs with an External metric have minReplicas 0, scaling their
+    /// target to zero while the metric is zero and back up when it isn't,
+    /// as KEDA does. Kubernetes has this behind the HPAScaleToZero feature
+    /// gate, which turns it on too.
+    pub scale_to_zero: bool,
+}
+
+impl Default for AutoscalingConfig {
+    fn default() -> Self {
+        Self {
+            sync_period_seconds: 15,
+            scale_to_zero: false,
+        }
+    }
+}
+
+/// How often the controllers and the scheduler go over the objects they
+/// manage. Each pass queries the database, so on large setups longer periods
+/// take load off SQLite.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ControllersConfig {
+    /// Seconds between passes, keyed by the names in `CONTROLLERS`, e.g.
+    /// `deployment: 10`. The HPA controller defaults to
+    /// `autoscaling.syncPeriodSeconds`.
+    pub resync_period_seconds: BTreeMap<String, f64>,
+    /// Every wait is lengthened by a random part of the period up to this
+    /// fraction, so that loops started together don't keep scanning at the
+    /// same moment.
+    pub jitter: f64,
+}
+
+impl Default for ControllersConfig {
+    fn default() -> Self {
+        Self {
+            resync_period_seconds: BTreeMap::new(),
+            jitter: 0.1,
+        }
+    }
+}
+
+/// The controller loops whose resync period can be set, with their default
+/// periods in seconds.
+pub const CONTROLLERS: &[(&str, f64)] = &[
+    ("clusterInfoPublisher", 1.0),
+    ("deployment", 2.0),
+    ("disruption", 1.0),
+    ("endpoints", 2.0),
+    ("garbageCollector", 1.0),
+    ("history", 2.0),
+    ("hpa", 15.0),
+    ("job", 1.0),
+    ("namespace", 1.0),
+    ("nodeStatus", 10.0),
+    ("pvcProtection", 1.0),
+    ("replicaSet", 2.0),
+    ("rootCAPublisher", 1.0),
+    ("scheduler", 1.0),
+    ("serviceAccountToken", 1.0),
+    ("serviceProxy", 2.0),
+];
+
+/// API server settings.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct ApiServerConfig {
+    /// Time after which regular requests fail with a 504; long-running ones
+    /// such as watches and exec are exempt. `?timeoutSeconds=` overrides it
+    /// per request.
+    pub request_timeout_seconds: u64,
+    /// PEM bundle of the cluster CA, published as the kube-root-ca.crt
+    /// ConfigMap in every namespace. Without it a self-signed CA is
+    /// generated at startup.
+    #[serde(rename = "rootCAFile")]
+    pub root_ca_file: Option<String>,
+    /// Watch events kept for watches to resume from. Older ones are
+    /// compacted away every minute, and a watch from before them fails with
+    /// 410 Gone so that its client lists again.
+    pub watch_history: i64,
+    /// Serve the /debug/pprof endpoints, like kube-apiserver's
+    /// --profiling. Set by `--profiling` too.
+    pub profiling: bool,
+}
+
+impl Default for ApiServerConfig {
+    fn default() -> Self {
+        Self {
+            request_timeout_seconds: 60,
+            root_ca_file: None,
+            watch_history: 10000,
+            profiling: false,
+        }
+    }
+}
+
+/// Who may call the API. With no authenticator configured every request is
+/// let in, as before; once any is, requests other than the health checks
+/// need a bearer token one of them accepts. They are tried in the order
+/// static tokens, OIDC, webhook.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuthenticationConfig {
+    /// Bearer tokens accepted as they are, like kube-apiserver's
+    /// --token-auth-file.
+    pub tokens: Vec<StaticToken>,
+    pub oidc: Option<OidcConfig>,
+    pub webhook: Option<AuthenticationWebhookConfig>,
+}
+
+/// What callers may do once authenticated. By default anything, as before;
+/// in RBAC mode requests are checked against the stored Roles, ClusterRoles
+/// and their bindings, and refused with 403 Forbidden unless one allows them.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AuthorizationConfig {
+    pub mode: AuthorizationMode,
+    /// Log what RBAC would refuse but let it through, to try out roles on a
+    /// running setup.
+    pub permissive: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+pub enum AuthorizationMode {
+    #[default]
+    AlwaysAllow,
+    #[serde(rename = "RBAC")]
+    Rbac,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StaticToken {
+    pub token: String,
+    pub user: String,
+    #[serde(default)]
+    pub uid: Option<String>,
+    #[serde(default)]
+    pub groups: Vec<String>,
+}
+
+/// ID tokens from an OpenID Connect provider, as with kube-apiserver's
+/// --oidc-* flags. Signing keys come from the issuer's discovery document.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OidcConfig {
+    #[serde(rename = "issuerURL")]
+    pub issuer_url: String,
+    /// Audience the tokens must be issued for.
+    #[serde(rename = "clientID")]
+    pub client_id: String,
+    #[serde(default = "default_username_claim")]
+    pub username_claim: String,
+    /// Put before usernames; "-" for none. By default usernames from claims
+    /// other than email get the issuer URL and a '#'.
+    #[serde(default)]
+    pub username_prefix: Option<String>,
+    #[serde(default)]
+    pub groups_claim: Option<String>,
+    #[serde(default)]
+    pub groups_prefix: String,
+}
+
+fn default_username_claim() -> String {
+    "sub".to_string()
+}
+
+/// An external service asked about tokens with TokenReviews, as with
+/// kube-apiserver's --authentication-token-webhook-config-file.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AuthenticationWebhookConfig {
+    pub url: String,
+    /// How long an accepted token is remembered before asking again.
+    #[serde(default = "default_webhook_cache_ttl")]
+    pub cache_ttl_seconds: u64,
+}
+
+fn default_webhook_cache_ttl() -> u64 {
+    120
+}
+
+/// How objects are kept in the database.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StorageConfig {
+    /// The size of JSON from which the spec, status and annotations of
+    /// workloads are stored zstd-compressed; 0 stores them as they are.
+    pub compression_threshold_bytes: usize,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self { compression_threshold_bytes: compression::DEFAULT_THRESHOLD }
+    }
+}
+
+/// Cluster DNS handed to pods, as with a kubelet's --cluster-dns and
+/// --cluster-domain.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DnsConfig {
+    /// Nameservers of pods with a ClusterFirst dnsPolicy. Without any, those
+    /// pods use the embedded server if it's running, or else the node's
+    /// resolver.
+    #[serde(rename = "clusterDNS")]
+    pub cluster_dns: Vec<String>,
+    pub cluster_domain: String,
+    pub server: DnsServerConfig,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            cluster_dns: Vec::new(),
+            cluster_domain: "cluster.local".to_string(),
+            server: DnsServerConfig::default(),
+        }
+    }
+}
+
+/// The cluster DNS server krust runs itself (see runtime::dns_server).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DnsServerConfig {
+    pub enabled: bool,
+    /// Where to listen. By default port 53 of the pod network's gateway,
+    /// where the kubelet's containers reach it.
+    pub address: Option<SocketAddr>,
+}
+
+impl Default for DnsServerConfig {
+    fn default() -> Self {
+        Self { enabled: true, address: None }
+    }
+}
+
+/// NetworkPolicy enforcement for the pods the kubelet runs, with iptables
+/// rules on the host (see runtime::network_policy).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct NetworkPolicyConfig {
+    /// Enforce where iptables can be used. Turn off where it can but
+    /// shouldn't, e.g. on a host whose firewall is managed otherwise.
+    pub enforce: bool,
+}
+
+impl Default for NetworkPolicyConfig {
+    fn default() -> Self {
+        Self { enforce: true }
+    }
+}
+
+/// Streaming server for exec, attach and port-forward. When enabled, those
+/// API calls redirect to a single-use URL on its own port, the way a kubelet
+/// hands out streaming URLs from its CRI runtime.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct StreamingConfig {
+    pub enabled: bool,
+    pub address: String,
+    /// Port to listen on; 0 picks a free one.
+    pub port: u16,
+    /// How long a streaming URL stays valid if it isn't used.
+    pub token_ttl_seconds: u64,
+}
+
+impl Default for StreamingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            address: "127.0.0.1".to_string(),
+            port: 10250,
+            token_ttl_seconds: 30,
+        }
+    }
+}
+
+/// HTTPS for the API server. The serving certificate is issued by the
+/// cluster CA unless one is given, and a kubeconfig for it, with an admin
+/// client certificate, is written to the data directory.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct TlsConfig {
+    pub enabled: bool,
+    /// Serving certificate (chain) and its key, as PEM files.
+    pub cert_file: Option<String>,
+    pub key_file: Option<String>,
+    /// Clients presenting a certificate signed by one of these CAs
+    /// authenticate as its common name, in its organizations as groups.
+    /// Defaults to the cluster CA.
+    #[serde(rename = "clientCAFile")]
+    pub client_ca_file: Option<String>,
+}
+
+/// How krust calls admission webhooks.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AdmissionConfig {
+    /// Client certificate and key, as PEM files, presented to webhooks that
+    /// ask for one. Defaults to a certificate the cluster CA issues for
+    /// system:kube-apiserver.
+    pub client_cert_file: Option<String>,
+    pub client_key_file: Option<String>,
+    /// Built-in mutations of the pods being created, applied in order
+    /// before the mutating webhooks are called.
+    pub injections: Vec<Injection>,
+}
+
+/// Sidecar containers, environment variables and labels added to the pods a
+/// selector picks when they're created, as a service mesh's injector would.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Injection {
+    pub name: String,
+    /// A label selector for the pods, matchLabels and matchExpressions as
+    /// in objects; every pod if absent.
+    pub selector: Value,
+    /// A label selector for the pods' namespaces; every namespace if absent.
+    pub namespace_selector: Value,
+    pub labels: BTreeMap<String, String>,
+    /// Variables set in each of the pod's containers that doesn't set them
+    /// itself.
+    pub env: Vec<Value>,
+    /// Containers appended to the pod's, unless it has one of that name.
+    pub containers: Vec<Value>,
+}
+
+impl Config {
+    /// Settings for the named node, or the defaults if it isn't configured.
+    pub fn node(&self, name: &str) -> NodeConfig {
+        self.nodes.get(name).cloned().unwrap_or_default()
+    }
+
+    /// Names of all nodes in the cluster, krust-node first and the
+    /// simulated ones after it in name order.
+    pub fn node_names(&self) -> Vec<String> {
+        let mut simulated: Vec<String> = self
+            .nodes
+            .keys()
+            .filter(|name| *name != NODE_NAME)
+            .cloned()
+            .collect();
+        simulated.sort();
+
+        let mut names = vec![NODE_NAME.to_string()];
+        names.extend(simulated);
+        names
+    }
+
+    /// Builds the configuration from the command line, falling back to
+    /// KRUST_CONFIG and then to the defaults.
+    pub fn from_args() -> Result<Self> {
+        Self::from_arg_list(std::env::args().skip(1))
+    }
+
+    /// The config given by `--config` and `--data-dir` flags, falling back
+    /// to KRUST_CONFIG and KRUST_DATA_DIR, with `--profiling` turning on the
+    /// /debug/pprof endpoints and `--feature-gates` setting feature gates
+    /// over the file's. The data directory is always set.
+    pub fn from_arg_list(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut path = std::env::var("KRUST_CONFIG").ok();
+        let mut data_dir = std::env::var_os("KRUST_DATA_DIR").map(PathBuf::from);
+
+        let mut profiling = false;
+        let mut feature_gates = FeatureGates::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            if arg == "--config" {
+                path = Some(args.next().ok_or_else(|| anyhow!("--config requires a path"))?);
+            } else if let Some(value) = arg.strip_prefix("--config=") {
+                path = Some(value.to_string());
+            } else if arg == "--data-dir" {
+                data_dir = Some(args.next().ok_or_else(|| anyhow!("--data-dir requires a path"))?.into());
+            } else if let Some(value) = arg.strip_prefix("--data-dir=") {
+                data_dir = Some(value.into());
+            } else if arg == "--profiling" {
+                profiling = true;
+            } else if arg == "--feature-gates" {
+                feature_gates.merge(FeatureGates::parse(&args.next().ok_or_else(|| anyhow!("--feature-gates requires a value"))?)?);
+            } else if let Some(value) = arg.strip_prefix("--feature-gates=") {
+                feature_gates.merge(FeatureGates::parse(value)?);
+            } else {
+                bail!("unknown argument: {}", arg);
+            }
+        }
+
+        let mut config = match path {
+            Some(path) => Self::load(Path::new(&path))?,
+            None => Self::default(),
+        };
+        if data_dir.is_some() {
+            config.data_dir = data_dir;
+        }
+        config.api_server.profiling |= profiling;
+        config.feature_gates.merge(feature_gates);
+        config.data_dir.get_or_insert_with(DataDir::default_root);
+        Ok(config)
+    }
+
+    pub fn data_dir(&self) -> Option<DataDir> {
+        self.data_dir.clone().map(DataDir::new)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("failed to read config file {}", path.display()))?;
+        let config = Self::parse(&contents)
+            .with_context(|| format!("invalid config file {}", path.display()))?;
+        Ok(config)
+    }
+
+    pub fn parse(contents: &str) -> Result<Self> {
+        // An empty document deserializes to null rather than an empty map
+        if contents.trim().is_empty() {
+            return Ok(Self::default());
+        }
+
+        let mut raw: serde_yaml::Value = serde_yaml::from_str(contents)?;
+        apply_node_profiles(&mut raw)?;
+        let config: Config = serde_yaml::from_value(raw)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Time between passes of the named controller loop, before jitter.
+    pub fn resync_period(&self, controller: &str) -> Duration {
+        let default = match controller {
+            "hpa" => self.autoscaling.sync_period_seconds as f64,
+            _ => CONTROLLERS.iter().find(|(name, _)| *name == controller).map(|(_, s)| *s).unwrap_or(1.0),
+        };
+        let seconds = self.controllers.resync_period_seconds.get(controller).copied().unwrap_or(default);
+        Duration::from_secs_f64(seconds)
+    }
+
+    /// Whether HPAs may scale their targets to zero.
+    pub fn scale_to_zero(&self) -> bool {
+        self.autoscaling.scale_to_zero || self.feature_gates.enabled(HPA_SCALE_TO_ZERO)
+    }
+
+    fn validate(&self) -> Result<()> {
+        self.feature_gates.validate().context("featureGates")?;
+        let defaults = &self.namespace_defaults;
+        let templates = defaults
+            .limit_ranges
+            .iter()
+            .map(|t| ("limitRanges", t))
+            .chain(defaults.resource_quotas.iter().map(|t| ("resourceQuotas", t)));
+
+        for (section, template) in templates {
+            if template["metadata"]["name"].as_str().is_none() {
+                bail!("namespaceDefaults.{} entries need metadata.name", section);
+            }
+        }
+
+        for (name, node) in &self.nodes {
+            if quantity::cpu_millis(&Value::from(node.cpu.as_str())).is_none() {
+                bail!("nodes.{}.cpu: invalid quantity {:?}", name, node.cpu);
+            }
+            if quantity::bytes(&Value::from(node.memory.as_str())).is_none() {
+                bail!("nodes.{}.memory: invalid quantity {:?}", name, node.memory);
+            }
+            if let Some(storage) = node.ephemeral_storage.as_deref().filter(|s| quantity::bytes(&Value::from(*s)).is_none()) {
+                bail!("nodes.{}.ephemeralStorage: invalid quantity {:?}", name, storage);
+            }
+            if node.internal_ip.parse::<std::net::IpAddr>().is_err() {
+                bail!("nodes.{}.internalIP: invalid address {:?}", name, node.internal_ip);
+            }
+            for kind in &node.security_profiles {
+                if !SECURITY_PROFILES.contains(&kind.as_str()) {
+                    bail!(
+                        "nodes.{}.securityProfiles: unsupported profile kind {:?} (expected one of {})",
+                        name, kind, SECURITY_PROFILES.join(", ")
+                    );
+                }
+            }
+            for taint in &node.taints {
+                if !TAINT_EFFECTS.contains(&taint.effect.as_str()) {
+                    bail!(
+                        "nodes.{}.taints: unsupported effect {:?} (expected one of {})",
+                        name, taint.effect, TAINT_EFFECTS.join(", ")
+                    );
+                }
+            }
+        }
+
+        for injection in &self.admission.injections {
+            if injection.name.is_empty() {
+                bail!("admission.injections entries need a name");
+            }
+            for (field, selector) in [("selector", &injection.selector), ("namespaceSelector", &injection.namespace_selector)] {
+                LabelSelector::from_value(selector).with_context(|| format!("admission.injections.{}.{}", injection.name, field))?;
+            }
+            if injection.containers.iter().any(|c| c["name"].as_str().is_none() || c["image"].as_str().is_none()) {
+                bail!("admission.injections.{}.containers entries need a name and an image", injection.name);
+            }
+            if injection.env.iter().any(|e| e["name"].as_str().is_none()) {
+                bail!("admission.injections.{}.env entries need a name", injection.name);
+            }
+        }
+
+        for (name, seconds) in &self.controllers.resync_period_seconds {
+            if !CONTROLLERS.iter().any(|(controller, _)| controller == name) {
+                let names: Vec<&str> = CONTROLLERS.iter().map(|(controller, _)| *controller).collect();
+                bail!(
+                    "controllers.resyncPeriodSeconds: unknown controller {:?} (expected one of {})",
+                    name, names.join(", ")
+                );
+            }
+            if !seconds.is_finite() || *seconds <= 0.0 {
+                bail!("controllers.resyncPeriodSeconds.{}: must be a positive number of seconds", name);
+            }
+        }
+        let jitter = self.controllers.jitter;
+        if !(0.0..=1.0).contains(&jitter) {
+            bail!("controllers.jitter: must be between 0 and 1, got {}", jitter);
+        }
+
+        let authentication = &self.authentication;
+        if authentication.tokens.iter().any(|t| t.token.is_empty() || t.user.is_empty()) {
+            bail!("authentication.tokens entries need a token and a user");
+        }
+        if let Some(oidc) = &authentication.oidc {
+            if !oidc.issuer_url.starts_with("https://") && !oidc.issuer_url.starts_with("http://") {
+                bail!("authentication.oidc.issuerURL: invalid URL {:?}", oidc.issuer_url);
+            }
+        }
+
+        for address in &self.dns.cluster_dns {
+            if address.parse::<std::net::IpAddr>().is_err() {
+                bail!("dns.clusterDNS: invalid address {:?}", address);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+pub(crate) const TAINT_EFFECTS: &[&str] = &["NoSchedule", "PreferNoSchedule", "NoExecute"];
+const SECURITY_PROFILES: &[&str] = &["seccomp", "apparmor"];
+
+// Replaces each node's `profile` reference with the profile's settings,
+// overlaid with the node's own. Labels are merged key by key.
+fn apply_node_profiles(raw: &mut serde_yaml::Value) -> Result<()> {
+    use serde_yaml::Value as Yaml;
+
+    let profiles = raw.get("nodeProfiles").cloned().unwrap_or(Yaml::Null);
+    let Some(nodes) = raw.get_mut("nodes").and_then(|n| n.as_mapping_mut()) else {
+        return Ok(());
+    };
+
+    for (name, node) in nodes.iter_mut() {
+        let Some(profile_name) = node.get("profile").and_then(|p| p.as_str()).map(str::to_string) else {
+            continue;
+        };
+        let Some(Yaml::Mapping(profile)) = profiles.get(&profile_name).cloned() else {
+            bail!(
+                "nodes.{}: unknown profile {:?}",
+                name.as_str().unwrap_or_default(),
+                profile_name
+            );
+        };
+        let Some(overrides) = node.as_mapping() else {
+            continue;
+        };
+
+        let mut merged = profile;
+        for (key, value) in overrides {
+            match (merged.get_mut(key), value) {
+                (Some(Yaml::Mapping(base)), Yaml::Mapping(extra)) if key.as_str() == Some("labels") => {
+                    base.extend(extra.clone());
+                }
+                _ => {
+                    merged.insert(key.clone(), value.clone());
+                }
+            }
+        }
+        *node = Yaml::Mapping(merged);
+    }
+
+    Ok(())
+}
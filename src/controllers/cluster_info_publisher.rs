@@ -0,0 +1,117 @@
+// Publishes the ConfigMaps kubeadm leaves in a cluster, which bootstrap
+// tools and operators read to find the API server: cluster-info in
+// kube-public, a kubeconfig with the server's address and CA and no
+// credentials, which anyone may read; kubeadm-config in kube-system, with
+// the cluster's ClusterConfiguration; and krust-version in kube-system,
+// saying which krust serves the cluster. Like the root CA publisher, it
+// recreates them if deleted and resets them if their data is changed.
+use anyhow::Result;
+use base64::{engine::general_purpose::STANDARD, Engine};
+use serde_json::{json, Value};
+use std::time::Instant;
+use tracing::{error, info};
+
+use crate::api::server::KUBERNETES_VERSION;
+use crate::config::Config;
+use crate::profiling;
+use crate::Storage;
+use super::Resync;
+
+pub const CLUSTER_INFO: &str = "cluster-info";
+pub const KUBEADM_CONFIG: &str = "kubeadm-config";
+pub const KRUST_VERSION: &str = "krust-version";
+
+pub struct ClusterInfoPublisher {
+    storage: Storage,
+    // (namespace, name, data) of each ConfigMap
+    configmaps: Vec<(&'static str, &'static str, Value)>,
+    resync: Resync,
+}
+
+impl ClusterInfoPublisher {
+    /// A publisher for the API server at `server`, e.g.
+    /// `https://127.0.0.1:6443`, whose clients verify it with `ca_bundle`.
+    pub fn new(storage: Storage, config: &Config, server: &str, ca_bundle: &str) -> Result<Self> {
+        let kubeconfig = json!({
+            "apiVersion": "v1",
+            "kind": "Config",
+            "clusters": [{
+                "name": "",
+                "cluster": { "server": server, "certificate-authority-data": STANDARD.encode(ca_bundle) }
+            }],
+            "contexts": null,
+            "current-context": "",
+            "preferences": {},
+            "users": null
+        });
+        let endpoint = server.split_once("://").map(|(_, address)| address).unwrap_or(server);
+        let cluster_configuration = json!({
+            "apiVersion": "kubeadm.k8s.io/v1beta3",
+            "kind": "ClusterConfiguration",
+            "clusterName": "krust",
+            "kubernetesVersion": KUBERNETES_VERSION,
+            "controlPlaneEndpoint": endpoint,
+            "certificatesDir": config.data_dir().map(|dir| dir.pki().display().to_string()),
+            "networking": {
+                "dnsDomain": config.dns.cluster_domain,
+                "serviceSubnet": "10.96.0.0/12",
+                "podSubnet": "10.244.0.0/16"
+            }
+        });
+
+        let configmaps = vec![
+            ("kube-public", CLUSTER_INFO, json!({ "kubeconfig": serde_yaml::to_string(&kubeconfig)? })),
+            (
+                "kube-system",
+                KUBEADM_CONFIG,
+                json!({ "ClusterConfiguration": serde_yaml::to_string(&cluster_configuration)? }),
+            ),
+            (
+                "kube-system",
+                KRUST_VERSION,
+                json!({ "version": env!("CARGO_PKG_VERSION"), "kubernetesVersion": KUBERNETES_VERSION }),
+            ),
+        ];
+        Ok(Self { storage, configmaps, resync: Resync::new(config, "clusterInfoPublisher") })
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        info!("Starting cluster info publisher");
+
+        loop {
+            let started = Instant::now();
+            if let Err(e) = self.reconcile().await {
+                error!("Cluster info publisher error: {}", e);
+            }
+
+            profiling::record("cluster info publisher", started);
+            self.resync.wait().await;
+        }
+    }
+
+    async fn reconcile(&self) -> Result<()> {
+        for (namespace, name, data) in &self.configmaps {
+            match self.storage.configmaps().get(namespace, name).await {
+                Ok(mut configmap) if configmap["data"] != *data => {
+                    configmap["data"] = data.clone();
+                    self.storage.configmaps().update(namespace, name, configmap).await?;
+                    info!("Reset {} in namespace {}", name, namespace);
+                }
+                Ok(_) => {}
+                Err(e) if e.to_string().contains("not found") => {
+                    let configmap = json!({
+                        "apiVersion": "v1",
+                        "kind": "ConfigMap",
+                        "metadata": { "name": name, "namespace": namespace },
+                        "data": data
+                    });
+                    self.storage.configmaps().create(namespace, configmap).await?;
+                    info!("Published {} in namespace {}", name, namespace);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,178 @@
+// The service proxy's connection tracking table, for /krust/proxy and
+// /krust/conntrack: every connection it is relaying, and for each backend of
+// each service how many it has relayed, how many bytes went each way and
+// how often reaching it failed. A UDP client's session with its backend
+// counts as one connection. Kept for the whole process, like the loop
+// timings in crate::profiling.
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, OnceLock};
+
+use crate::models::time;
+
+/// A port of a service the proxy serves.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ServicePort {
+    pub namespace: String,
+    pub service: String,
+    pub port_name: Option<String>,
+    pub protocol: &'static str,
+}
+
+/// A connection being relayed, as listed by `connections`.
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Connection {
+    pub protocol: &'static str,
+    pub namespace: String,
+    pub service: String,
+    pub port_name: Option<String>,
+    pub client: SocketAddr,
+    pub frontend: SocketAddr,
+    pub backend: SocketAddr,
+    pub start_time: String,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+/// What the proxy has relayed to one backend of a service port.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BackendStats {
+    pub active_connections: u64,
+    pub total_connections: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+    pub errors: u64,
+}
+
+/// What the proxy has done for one service port.
+#[derive(Clone, Debug, Default)]
+pub struct PortStats {
+    /// Connections and datagrams dropped because the service had no endpoints
+    pub dropped: u64,
+    pub backends: BTreeMap<SocketAddr, BackendStats>,
+}
+
+struct Open {
+    port: ServicePort,
+    client: SocketAddr,
+    frontend: SocketAddr,
+    backend: SocketAddr,
+    start_time: String,
+    bytes_sent: AtomicU64,
+    bytes_received: AtomicU64,
+}
+
+#[derive(Default)]
+struct Table {
+    next_id: u64,
+    open: BTreeMap<u64, Arc<Open>>,
+    // Counts of the connections already closed
+    ports: BTreeMap<ServicePort, PortStats>,
+}
+
+static TABLE: OnceLock<Mutex<Table>> = OnceLock::new();
+
+fn table() -> MutexGuard<'static, Table> {
+    TABLE.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// A tracked connection, counted until it's dropped.
+pub struct Tracked {
+    id: u64,
+    open: Arc<Open>,
+}
+
+impl Tracked {
+    /// Counts bytes relayed from the client to the backend.
+    pub fn sent(&self, bytes: usize) {
+        self.open.bytes_sent.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Counts bytes relayed from the backend to the client.
+    pub fn received(&self, bytes: usize) {
+        self.open.bytes_received.fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+}
+
+impl Drop for Tracked {
+    fn drop(&mut self) {
+        let mut table = table();
+        table.open.remove(&self.id);
+        let stats = backend_mut(&mut table, &self.open.port, self.open.backend);
+        stats.total_connections += 1;
+        stats.bytes_sent += self.open.bytes_sent.load(Ordering::Relaxed);
+        stats.bytes_received += self.open.bytes_received.load(Ordering::Relaxed);
+    }
+}
+
+fn backend_mut<'a>(table: &'a mut Table, port: &ServicePort, backend: SocketAddr) -> &'a mut BackendStats {
+    table.ports.entry(port.clone()).or_default().backends.entry(backend).or_default()
+}
+
+/// Starts tracking a connection from `client` to `frontend`, relayed to
+/// `backend`.
+pub fn open(port: &ServicePort, client: SocketAddr, frontend: SocketAddr, backend: SocketAddr) -> Tracked {
+    let open = Arc::new(Open {
+        port: port.clone(),
+        client,
+        frontend,
+        backend,
+        start_time: time::now(),
+        bytes_sent: AtomicU64::new(0),
+        bytes_received: AtomicU64::new(0),
+    });
+    let mut table = table();
+    let id = table.next_id;
+    table.next_id += 1;
+    table.open.insert(id, open.clone());
+    Tracked { id, open }
+}
+
+/// Counts a failure to reach or write to `backend`.
+pub fn failed(port: &ServicePort, backend: SocketAddr) {
+    backend_mut(&mut table(), port, backend).errors += 1;
+}
+
+/// Counts a connection or datagram dropped for want of endpoints.
+pub fn dropped(port: &ServicePort) {
+    table().ports.entry(port.clone()).or_default().dropped += 1;
+}
+
+/// The connections being relayed, oldest first.
+pub fn connections() -> Vec<Connection> {
+    table()
+        .open
+        .values()
+        .map(|open| Connection {
+            protocol: open.port.protocol,
+            namespace: open.port.namespace.clone(),
+            service: open.port.service.clone(),
+            port_name: open.port.port_name.clone(),
+            client: open.client,
+            frontend: open.frontend,
+            backend: open.backend,
+            start_time: open.start_time.clone(),
+            bytes_sent: open.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: open.bytes_received.load(Ordering::Relaxed),
+        })
+        .collect()
+}
+
+/// Every service port the proxy has relayed for, with the connections
+/// being relayed counted in.
+pub fn ports() -> BTreeMap<ServicePort, PortStats> {
+    let table = table();
+    let mut ports = table.ports.clone();
+    for open in table.open.values() {
+        let stats = ports.entry(open.port.clone()).or_default().backends.entry(open.backend).or_default();
+        stats.active_connections += 1;
+        stats.total_connections += 1;
+        stats.bytes_sent += open.bytes_sent.load(Ordering::Relaxed);
+        stats.bytes_received += open.bytes_received.load(Ordering::Relaxed);
+    }
+    ports
+}
@@ -1,179 +1,481 @@
+// Rolls Deployments out through ReplicaSets, one per pod template. A changed
+// template gets a new ReplicaSet, which is scaled up while the older ones are
+// scaled down within the RollingUpdate strategy's maxSurge and
+// maxUnavailable, or after they are gone with the Recreate strategy.
+//
+// A rollout can also stop part way, at the percentages of replicas listed in
+// the Deployment's `krust.io/rollout-pause-at` annotation, e.g. `20,50`: once
+// that share runs the new template the Deployment is paused, as by `kubectl
+// rollout pause`, and `kubectl rollout resume` carries on to the next stop.
+// A progressive delivery controller can check the canary in between.
+//
+// The old ReplicaSets are the Deployment's rollout history: each is numbered
+// with the revision it was rolled out as, and spec.revisionHistoryLimit of
+// them are kept once scaled down. `kubectl rollout undo` puts an old template
+// back, which revives its ReplicaSet as the newest revision.
+//
+// Each pass reads the ReplicaSets of every Deployment up front, in a couple
+// of queries, and works from what it has read and written since rather
+// than reading them again for each Deployment.
 use anyhow::Result;
-use serde_json::{json, Value};
-use sqlx::Row;
-use std::collections::hash_map::DefaultHasher;
-use std::hash::{Hash, Hasher};
-use std::time::Duration;
-use tokio::time::sleep;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::time::Instant;
 use tracing::{error, info};
-use uuid::Uuid;
 
+use crate::api::last_applied;
+use crate::models::{pod_conditions, replicas};
+use crate::models::revision::{self, POD_TEMPLATE_HASH_LABEL, REVISION_ANNOTATION};
+use crate::profiling;
+use crate::storage::event_store::{self, EventSource, ObjectReference};
 use crate::Storage;
+use super::Resync;
+use crate::models::time;
+
+/// Percentages of a Deployment's replicas at which its rollouts pause,
+/// comma-separated.
+pub const PAUSE_AT_ANNOTATION: &str = "krust.io/rollout-pause-at";
+
+/// Set on the new ReplicaSet of a rollout: the last percentage it paused at.
+pub const PAUSED_AT_ANNOTATION: &str = "krust.io/rollout-paused-at";
+
+// Upstream's default for both maxSurge and maxUnavailable
+const DEFAULT_ROLLING_LIMIT: &str = "25%";
+
+// Deployment annotations that aren't copied to its ReplicaSets, as upstream
+const UNCOPIED_ANNOTATIONS: &[&str] = &[
+    last_applied::ANNOTATION,
+    REVISION_ANNOTATION,
+    "deployment.kubernetes.io/revision-history",
+    "deployment.kubernetes.io/desired-replicas",
+    "deployment.kubernetes.io/max-replicas",
+    "deprecated.deployment.rollback.to",
+];
 
 pub struct DeploymentController {
     storage: Storage,
+    resync: Resync,
+}
+
+/// A ReplicaSet owned by the Deployment being reconciled.
+struct OwnedReplicaSet {
+    name: String,
+    replicas: i64,
+    // Pods it has, and how many of them are ready
+    pods: i64,
+    ready: i64,
+    annotations: Value,
 }
 
 impl DeploymentController {
-    pub fn new(storage: Storage) -> Self {
-        Self { storage }
+    pub fn new(storage: Storage, resync: Resync) -> Self {
+        Self { storage, resync }
     }
 
     pub async fn run(&self) -> Result<()> {
         info!("Starting deployment controller");
-        
+
         loop {
+            let started = Instant::now();
             if let Err(e) = self.reconcile_deployments().await {
                 error!("Deployment controller error: {}", e);
             }
-            
-            sleep(Duration::from_secs(2)).await;
+
+            profiling::record("deployment controller", started);
+            self.resync.wait().await;
         }
     }
 
     async fn reconcile_deployments(&self) -> Result<()> {
-        // Get all deployments
-        let deployments = sqlx::query(
-            "SELECT uid, name, namespace, spec, status, generation FROM deployments WHERE deletion_timestamp IS NULL"
-        )
-        .fetch_all(&*self.storage.pool)
-        .await?;
-        
-        for deployment_row in deployments {
-            let deployment_uid: String = deployment_row.get("uid");
-            let deployment_name: String = deployment_row.get("name");
-            let deployment_namespace: String = deployment_row.get("namespace");
-            let spec_str: String = deployment_row.get("spec");
-            let generation: i64 = deployment_row.get("generation");
-            
-            if let Ok(spec) = serde_json::from_str::<Value>(&spec_str) {
-                // Check if ReplicaSet exists for this deployment
-                let rs_name = self.generate_replicaset_name(&deployment_name, &spec);
-                
-                let existing_rs = sqlx::query(
-                    "SELECT uid FROM replicasets WHERE name = ? AND namespace = ? AND deletion_timestamp IS NULL"
-                )
-                .bind(&rs_name)
-                .bind(&deployment_namespace)
-                .fetch_optional(&*self.storage.pool)
-                .await?;
-                
-                if existing_rs.is_none() {
-                    // Create ReplicaSet
-                    info!("Creating ReplicaSet {} for Deployment {}/{}", rs_name, deployment_namespace, deployment_name);
-                    
-                    let replicas = spec["replicas"].as_i64().unwrap_or(1);
-                    let selector = spec["selector"].clone();
-                    let template = spec["template"].clone();
-                    
-                    // Create ReplicaSet with owner reference to Deployment
-                    let replicaset = json!({
-                        "metadata": {
-                            "name": rs_name,
-                            "namespace": deployment_namespace,
-                            "labels": {
-                                "deployment": deployment_name.clone()
-                            },
-                            "ownerReferences": [{
-                                "apiVersion": "apps/v1",
-                                "kind": "Deployment",
-                                "name": deployment_name.clone(),
-                                "uid": deployment_uid.clone(),
-                                "controller": true,
-                                "blockOwnerDeletion": true
-                            }]
-                        },
-                        "spec": {
-                            "replicas": replicas,
-                            "selector": selector,
-                            "template": template
-                        }
-                    });
-                    
-                    // Store the ReplicaSet
-                    if let Err(e) = self.storage.replicasets()
-                        .create(&deployment_namespace, replicaset)
-                        .await 
-                    {
-                        error!("Failed to create ReplicaSet for Deployment {}/{}: {}", 
-                            deployment_namespace, deployment_name, e);
+        let deployments = self.storage.deployments().list(None).await?;
+        // Deployments being deleted are left alone, so the ReplicaSets the
+        // garbage collector deletes aren't made again
+        let deleting: Vec<String> = self.storage.finalizers().deleting("deployments").await?.into_iter().map(|(_, _, kept)| kept.uid).collect();
+        let deployments: Vec<Value> = deployments["items"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|d| !d["metadata"]["uid"].as_str().is_some_and(|uid| deleting.iter().any(|d| d == uid)))
+            .cloned()
+            .collect();
+        let uids: Vec<String> = deployments.iter().filter_map(|d| d["metadata"]["uid"].as_str().map(String::from)).collect();
+        let mut owned = owned_replicasets(&self.storage, &uids).await?;
+        for deployment in &deployments {
+            let uid = deployment["metadata"]["uid"].as_str().unwrap_or_default();
+            if let Err(e) = self.reconcile(deployment, owned.remove(uid).unwrap_or_default()).await {
+                let metadata = &deployment["metadata"];
+                error!("Failed to reconcile Deployment {}/{}: {}", metadata["namespace"], metadata["name"], e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn reconcile(&self, deployment: &Value, owned: Vec<OwnedReplicaSet>) -> Result<()> {
+        let metadata = &deployment["metadata"];
+        let namespace = metadata["namespace"].as_str().unwrap_or("default");
+        let name = metadata["name"].as_str().unwrap_or_default();
+        let uid = metadata["uid"].as_str().unwrap_or_default();
+        let spec = &deployment["spec"];
+        let desired = replicas::desired(spec);
+        let paused = spec["paused"].as_bool().unwrap_or(false);
+        let hash = revision::template_hash(&spec["template"]);
+        let rs_name = format!("{}-{}", name, hash);
+
+        // The ReplicaSets and the status counting them are written together
+        let tx = self.storage.transaction().await?;
+        let involved = ObjectReference::new("Deployment", "apps/v1", Some(namespace), name, uid);
+        let new_rs = owned.iter().find(|rs| rs.name == rs_name);
+        let old: Vec<&OwnedReplicaSet> = owned.iter().filter(|rs| rs.name != rs_name).collect();
+        let old_replicas: i64 = old.iter().map(|rs| rs.replicas).sum();
+        let latest = owned.iter().map(|rs| revision::of(&rs.annotations)).max().unwrap_or(0);
+        // The replicas of each ReplicaSet as this pass leaves them
+        let mut scaled: HashMap<&str, i64> = owned.iter().map(|rs| (rs.name.as_str(), rs.replicas)).collect();
+
+        // Where the rollout stops next
+        let (current, ready, stopped_at) = new_rs.map_or((0, 0, 0), |rs| (rs.replicas, rs.ready.min(rs.replicas), paused_at(rs)));
+        let next_stop = pause_points(&metadata["annotations"]).into_iter().find(|&percent| percent > stopped_at);
+        let goal = next_stop.map_or(desired, |percent| ((percent * desired + 99) / 100).max(1).min(desired));
+
+        let old_pods: i64 = old.iter().map(|rs| rs.pods).sum();
+        let (new_target, old_target) = if spec["strategy"]["type"] == "Recreate" && !paused && old_replicas + old_pods > 0 {
+            // The new pods only start once the old ones are all gone
+            (0, 0)
+        } else if old_replicas == 0 {
+            (desired, 0)
+        } else if paused {
+            (current, old_replicas)
+        } else {
+            let (surge, unavailable) = rolling_limits(spec, desired);
+            let new_target = goal.min(current.max(desired + surge - old_replicas)).max(current.min(desired));
+            // Availability is only given up when surging alone can't progress
+            let headroom = if new_target < goal { unavailable } else { 0 };
+            (new_target, old_replicas.min((desired - ready - headroom).max(0)))
+        };
+
+        match new_rs {
+            // A paused Deployment doesn't start new rollouts
+            None if paused => {}
+            None => {
+                info!("Creating ReplicaSet {} for Deployment {}/{}", rs_name, namespace, name);
+                // The hash label keeps its pods apart from other revisions'
+                let mut template = spec["template"].clone();
+                template["metadata"]["labels"][POD_TEMPLATE_HASH_LABEL] = json!(hash);
+                let mut selector = spec["selector"].clone();
+                selector["matchLabels"][POD_TEMPLATE_HASH_LABEL] = json!(hash);
+                let replicaset = json!({
+                    "metadata": {
+                        "name": rs_name,
+                        "namespace": namespace,
+                        "labels": template["metadata"]["labels"],
+                        "annotations": revision_annotations(&metadata["annotations"], latest + 1),
+                        "ownerReferences": [{
+                            "apiVersion": "apps/v1",
+                            "kind": "Deployment",
+                            "name": name,
+                            "uid": uid,
+                            "controller": true,
+                            "blockOwnerDeletion": true
+                        }]
+                    },
+                    "spec": {
+                        "replicas": new_target,
+                        "selector": selector,
+                        "template": template
+                    }
+                });
+
+                match tx.replicasets().create(namespace, replicaset).await {
+                    Ok(_) => {
+                        scaled.insert(&rs_name, new_target);
+                        tx.events().record(
+                            &EventSource::new("deployment-controller"),
+                            &involved,
+                            event_store::NORMAL,
+                            "ScalingReplicaSet",
+                            &format!("Scaled up replica set {} to {}", rs_name, new_target),
+                        ).await?;
                     }
+                    Err(e) => {
+                        error!("Failed to create ReplicaSet for Deployment {}/{}: {}", namespace, name, e);
+                    }
+                }
+            }
+            Some(rs) => {
+                // A rolled back to template is the newest revision again
+                let number = revision::of(&rs.annotations);
+                if number == 0 || number < latest {
+                    let annotations = revision_annotations(&metadata["annotations"], latest + 1);
+                    tx.replicasets().patch(namespace, &rs.name, json!({ "metadata": { "annotations": annotations } })).await?;
                 }
-                
-                // Update deployment status
-                self.update_deployment_status(&deployment_uid, &deployment_namespace, &deployment_name).await?;
+                self.scale(&tx, &involved, namespace, &rs.name, rs.replicas, new_target).await?;
+                scaled.insert(&rs.name, new_target);
+            }
+        }
+
+        // Old ReplicaSets are scaled down oldest first
+        let mut excess = old_replicas - old_target;
+        for rs in old.iter().filter(|rs| rs.replicas > 0) {
+            if excess == 0 {
+                break;
+            }
+            let removed = excess.min(rs.replicas);
+            self.scale(&tx, &involved, namespace, &rs.name, rs.replicas, rs.replicas - removed).await?;
+            scaled.insert(&rs.name, rs.replicas - removed);
+            excess -= removed;
+        }
+
+        // Stop at the next pause point once the rollout has settled there.
+        // A finished rollout forgets its stops, in case it's rolled back to
+        if let (Some(percent), Some(rs)) = (next_stop, new_rs) {
+            let settled = current >= goal && ready >= goal && old_replicas <= desired - goal;
+            if !paused && old_replicas > 0 && settled {
+                self.pause(&tx, deployment, &involved, &rs.name, percent, goal).await?;
             }
         }
-        
+        if let Some(rs) = new_rs.filter(|rs| old_replicas == 0 && paused_at(rs) > 0) {
+            let marker = json!({ "metadata": { "annotations": { PAUSED_AT_ANNOTATION: null } } });
+            tx.replicasets().patch(namespace, &rs.name, marker).await?;
+        }
+
+        self.trim_history(&tx, deployment, &old).await?;
+        if new_rs.is_some() || !paused {
+            let number = new_rs.map(|rs| revision::of(&rs.annotations)).filter(|&number| number > 0 && number >= latest);
+            self.set_revision(&tx, deployment, number.unwrap_or(latest + 1)).await?;
+        }
+        self.update_deployment_status(&tx, deployment, &rs_name, &owned, &scaled).await?;
+        tx.commit().await?;
         Ok(())
     }
 
-    async fn update_deployment_status(&self, uid: &str, namespace: &str, name: &str) -> Result<()> {
-        // Count pods managed by this deployment's replicasets
-        let rs_rows = sqlx::query(
-            "SELECT name, replicas FROM replicasets 
-             WHERE namespace = ? AND owner_references LIKE ? AND deletion_timestamp IS NULL"
-        )
-        .bind(namespace)
-        .bind(format!("%\"uid\":\"{}%", uid))
-        .fetch_all(&*self.storage.pool)
-        .await?;
-        
-        let mut total_replicas = 0;
-        let mut ready_replicas = 0;
-        
-        for rs_row in rs_rows {
-            let rs_name: String = rs_row.get("name");
-            let desired_replicas: i64 = rs_row.get("replicas");
-            total_replicas += desired_replicas;
-            
-            // Count ready pods for this ReplicaSet
-            // In a real implementation, we'd check pod status
-            ready_replicas += desired_replicas; // Simplified for now
+    // Deletes the scaled down ReplicaSets beyond spec.revisionHistoryLimit,
+    // lowest revision first
+    async fn trim_history(&self, tx: &Storage, deployment: &Value, old: &[&OwnedReplicaSet]) -> Result<()> {
+        let namespace = deployment["metadata"]["namespace"].as_str().unwrap_or("default");
+        let mut excess = old.len().saturating_sub(revision::history_limit(&deployment["spec"]));
+        let mut stale: Vec<&&OwnedReplicaSet> = old.iter().filter(|rs| rs.replicas == 0 && rs.pods == 0).collect();
+        stale.sort_by_key(|rs| revision::of(&rs.annotations));
+        for rs in stale {
+            if excess == 0 {
+                break;
+            }
+            info!("Deleting ReplicaSet {}/{}, beyond the revision history limit", namespace, rs.name);
+            tx.replicasets().delete(namespace, &rs.name).await?;
+            excess -= 1;
         }
-        
+        Ok(())
+    }
+
+    // Shows the revision being rolled out on the Deployment. It's only read
+    // again, to update, when it doesn't show it already
+    async fn set_revision(&self, tx: &Storage, deployment: &Value, number: i64) -> Result<()> {
+        if revision::of(&deployment["metadata"]["annotations"]) == number {
+            return Ok(());
+        }
+        let namespace = deployment["metadata"]["namespace"].as_str().unwrap_or("default");
+        let name = deployment["metadata"]["name"].as_str().unwrap_or_default();
+        let mut deployment = tx.deployments().get(namespace, name).await?;
+        deployment["metadata"]["annotations"][REVISION_ANNOTATION] = json!(number.to_string());
+        tx.deployments().update(namespace, name, deployment).await?;
+        Ok(())
+    }
+
+    async fn scale(&self, tx: &Storage, involved: &ObjectReference, namespace: &str, rs_name: &str, current: i64, replicas: i64) -> Result<()> {
+        if current == replicas {
+            return Ok(());
+        }
+        info!("Scaling ReplicaSet {}/{} to {} replicas", namespace, rs_name, replicas);
+        tx.replicasets().update_scale(namespace, rs_name, replicas).await?;
+        let direction = if replicas > current { "up" } else { "down" };
+        tx.events().record(
+            &EventSource::new("deployment-controller"),
+            involved,
+            event_store::NORMAL,
+            "ScalingReplicaSet",
+            &format!("Scaled {} replica set {} to {} from {}", direction, rs_name, replicas, current),
+        ).await?;
+        Ok(())
+    }
+
+    // Pauses the Deployment at `percent`, which `replicas` of its new
+    // ReplicaSet `rs_name` make up, and remembers the stop on the ReplicaSet
+    async fn pause(&self, tx: &Storage, deployment: &Value, involved: &ObjectReference, rs_name: &str, percent: i64, replicas: i64) -> Result<()> {
+        let namespace = deployment["metadata"]["namespace"].as_str().unwrap_or("default");
+        let name = deployment["metadata"]["name"].as_str().unwrap_or_default();
+        info!("Pausing the rollout of Deployment {}/{} at {}%", namespace, name, percent);
+
+        let marker = json!({ "metadata": { "annotations": { PAUSED_AT_ANNOTATION: percent.to_string() } } });
+        tx.replicasets().patch(namespace, rs_name, marker).await?;
+        let mut paused = deployment.clone();
+        paused["spec"]["paused"] = json!(true);
+        tx.deployments().update(namespace, name, paused).await?;
+
+        tx.events().record(
+            &EventSource::new("deployment-controller"),
+            involved,
+            event_store::NORMAL,
+            "RolloutPaused",
+            &format!(
+                "Paused at {}%: {} of {} replicas run replica set {}; resume with kubectl rollout resume",
+                percent,
+                replicas,
+                replicas::desired(&deployment["spec"]),
+                rs_name
+            ),
+        ).await?;
+        Ok(())
+    }
+
+    // Counts the replicas of the ReplicaSets `owned` had once `scaled` them
+    async fn update_deployment_status(
+        &self,
+        storage: &Storage,
+        deployment: &Value,
+        rs_name: &str,
+        owned: &[OwnedReplicaSet],
+        scaled: &HashMap<&str, i64>,
+    ) -> Result<()> {
+        let ready = |name: &str| owned.iter().find(|rs| rs.name == name).map_or(0, |rs| rs.ready);
+
+        let total_replicas: i64 = scaled.values().sum();
+        let ready_replicas: i64 = scaled.iter().map(|(name, replicas)| ready(name).min(*replicas)).sum();
+        let updated_replicas = scaled.get(rs_name).copied().unwrap_or(0);
+        let rolling_out = scaled.iter().any(|(name, replicas)| *name != rs_name && *replicas > 0);
+
+        let progressing = if deployment["spec"]["paused"].as_bool().unwrap_or(false) {
+            ("Unknown", "DeploymentPaused", "Deployment is paused".to_string())
+        } else if rolling_out {
+            ("True", "ReplicaSetUpdated", format!("ReplicaSet \"{}\" is progressing.", rs_name))
+        } else {
+            ("True", "NewReplicaSetAvailable", format!("ReplicaSet \"{}\" has successfully progressed.", rs_name))
+        };
+
+        let existing = deployment["status"]["conditions"].as_array().cloned().unwrap_or_default();
+        let conditions = [
+            json!({
+                "type": "Available",
+                "status": if ready_replicas > 0 { "True" } else { "False" },
+                "reason": "MinimumReplicasAvailable",
+                "message": format!("{} replicas available", ready_replicas)
+            }),
+            json!({
+                "type": "Progressing",
+                "status": progressing.0,
+                "reason": progressing.1,
+                "message": progressing.2
+            }),
+        ]
+        .into_iter()
+        .map(|mut condition| {
+            let before = existing.iter().find(|c| c["type"] == condition["type"]);
+            let unchanged = before.is_some_and(|c| ["status", "reason", "message"].iter().all(|k| c[*k] == condition[*k]));
+            condition["lastUpdateTime"] = match before {
+                Some(c) if unchanged => c["lastUpdateTime"].clone(),
+                _ => json!(time::now()),
+            };
+            condition
+        })
+        .collect::<Vec<_>>();
+
         let status = json!({
-            "observedGeneration": 1,
+            "observedGeneration": deployment["metadata"]["generation"],
             "replicas": total_replicas,
-            "updatedReplicas": total_replicas,
+            "updatedReplicas": updated_replicas,
             "readyReplicas": ready_replicas,
             "availableReplicas": ready_replicas,
-            "conditions": [
-                {
-                    "type": "Available",
-                    "status": if ready_replicas > 0 { "True" } else { "False" },
-                    "lastUpdateTime": chrono::Utc::now().to_rfc3339(),
-                    "lastTransitionTime": chrono::Utc::now().to_rfc3339(),
-                    "reason": "MinimumReplicasAvailable",
-                    "message": format!("{} replicas available", ready_replicas)
-                },
-                {
-                    "type": "Progressing",
-                    "status": "True",
-                    "lastUpdateTime": chrono::Utc::now().to_rfc3339(),
-                    "lastTransitionTime": chrono::Utc::now().to_rfc3339(),
-                    "reason": "NewReplicaSetAvailable",
-                    "message": "ReplicaSet has successfully progressed"
-                }
-            ]
+            "conditions": pod_conditions::merge(&existing, &conditions)
         });
-        
-        sqlx::query(
-            "UPDATE deployments SET status = ? WHERE uid = ?"
-        )
-        .bind(status.to_string())
-        .bind(uid)
-        .execute(&*self.storage.pool)
-        .await?;
-        
+
+        // Written through the store so watchers see the rollout move, but
+        // only when it has
+        if status != deployment["status"] {
+            let namespace = deployment["metadata"]["namespace"].as_str().unwrap_or("default");
+            let name = deployment["metadata"]["name"].as_str().unwrap_or_default();
+            storage.deployments().update_status(namespace, name, status).await?;
+        }
+
         Ok(())
     }
+}
+
+/// The ReplicaSets each of the Deployments with `uids` owns, oldest first,
+/// read for all of them at once: which ones from the ownerReferences kept
+/// for the garbage collector, and then the ReplicaSets themselves.
+async fn owned_replicasets(storage: &Storage, uids: &[String]) -> Result<HashMap<String, Vec<OwnedReplicaSet>>> {
+    let dependents = storage.owners().dependent_uids("replicasets", uids).await?;
+    let dependent_uids: Vec<String> = dependents.values().flatten().cloned().collect();
+    let replicasets = storage.replicasets().get_many_by_uids(&dependent_uids).await?;
+
+    let mut owned: HashMap<String, Vec<OwnedReplicaSet>> = HashMap::new();
+    for replicaset in replicasets {
+        // The kept references can lag behind ones taken off the ReplicaSet
+        let owners = replicaset["metadata"]["ownerReferences"].as_array().cloned().unwrap_or_default();
+        for owner in owners.iter().filter_map(|owner| owner["uid"].as_str()).filter(|uid| dependents.contains_key(*uid)) {
+            owned.entry(owner.to_string()).or_default().push(OwnedReplicaSet {
+                name: replicaset["metadata"]["name"].as_str().unwrap_or_default().to_string(),
+                replicas: replicas::desired(&replicaset["spec"]),
+                pods: replicaset["status"]["replicas"].as_i64().unwrap_or(0),
+                ready: replicaset["status"]["readyReplicas"].as_i64().unwrap_or(0),
+                annotations: replicaset["metadata"]["annotations"].clone(),
+            });
+        }
+    }
+    Ok(owned)
+}
+
+/// The annotations of a ReplicaSet rolled out as revision `number`: those of
+/// its Deployment that are copied, such as kubernetes.io/change-cause, which
+/// `kubectl rollout history` shows.
+fn revision_annotations(deployment_annotations: &Value, number: i64) -> Map<String, Value> {
+    let mut annotations: Map<String, Value> = deployment_annotations
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter(|(key, _)| !UNCOPIED_ANNOTATIONS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+    annotations.insert(REVISION_ANNOTATION.to_string(), json!(number.to_string()));
+    annotations
+}
+
+/// The percentages in the pause annotation, ascending, below 100.
+fn pause_points(annotations: &Value) -> Vec<i64> {
+    let mut points: Vec<i64> = annotations[PAUSE_AT_ANNOTATION]
+        .as_str()
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|point| point.trim().trim_end_matches('%').parse().ok())
+        .filter(|percent| (1..100).contains(percent))
+        .collect();
+    points.sort_unstable();
+    points.dedup();
+    points
+}
+
+fn paused_at(rs: &OwnedReplicaSet) -> i64 {
+    rs.annotations[PAUSED_AT_ANNOTATION].as_str().and_then(|percent| percent.parse().ok()).unwrap_or(0)
+}
+
+/// maxSurge and maxUnavailable as replica counts; a percentage is of the
+/// desired replicas, rounded up for the surge and down for unavailability.
+fn rolling_limits(spec: &Value, desired: i64) -> (i64, i64) {
+    let rolling = &spec["strategy"]["rollingUpdate"];
+    let surge = resolve(&rolling["maxSurge"], desired, true);
+    let unavailable = resolve(&rolling["maxUnavailable"], desired, false);
+    // Both zero would never progress
+    if surge == 0 && unavailable == 0 {
+        return (0, 1);
+    }
+    (surge, unavailable)
+}
 
-    fn generate_replicaset_name(&self, deployment_name: &str, spec: &Value) -> String {
-        // Generate a deterministic name based on deployment name and pod template hash
-        let mut hasher = DefaultHasher::new();
-        spec["template"].to_string().hash(&mut hasher);
-        let hash = hasher.finish();
-        format!("{}-{:x}", deployment_name, hash % 0xfffffff)
+fn resolve(limit: &Value, desired: i64, round_up: bool) -> i64 {
+    if let Some(count) = limit.as_i64() {
+        return count.max(0);
     }
-}
\ No newline at end of file
+    let limit = limit.as_str().unwrap_or(DEFAULT_ROLLING_LIMIT);
+    match limit.strip_suffix('%').map(str::parse::<i64>) {
+        Some(Ok(percent)) if round_up => (percent * desired + 99) / 100,
+        Some(Ok(percent)) => percent * desired / 100,
+        _ => limit.parse().unwrap_or(0),
+    }
+}
@@ -0,0 +1,190 @@
+// Keeps the status of PodDisruptionBudgets current, like the disruption
+// controller of kube-controller-manager: how many of the pods a budget
+// selects are healthy, how many it needs (see models::disruption_budget)
+// and so how many disruptions it allows, which the eviction subresource
+// spends. Pods evicted stay in status.disruptedPods, no longer healthy,
+// until they're being deleted or the eviction times out.
+use anyhow::Result;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeSet, HashSet};
+use std::time::Instant;
+use tracing::{error, info};
+
+use crate::models::disruption_budget::{self, DISRUPTION_TIMEOUT_SECONDS};
+use crate::models::{replicas, time};
+use crate::profiling;
+use crate::Storage;
+use super::Resync;
+
+pub struct DisruptionController {
+    storage: Storage,
+    resync: Resync,
+}
+
+impl DisruptionController {
+    pub fn new(storage: Storage, resync: Resync) -> Self {
+        Self { storage, resync }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        info!("Starting disruption controller");
+
+        loop {
+            let started = Instant::now();
+            if let Err(e) = self.reconcile_all().await {
+                error!("Disruption controller error: {}", e);
+            }
+
+            profiling::record("Disruption controller", started);
+            self.resync.wait().await;
+        }
+    }
+
+    async fn reconcile_all(&self) -> Result<()> {
+        let pdbs = self.storage.pdbs().list(None).await?;
+        let pdbs = pdbs["items"].as_array().cloned().unwrap_or_default();
+        if pdbs.is_empty() {
+            return Ok(());
+        }
+        let pods = self.storage.pods().list(None).await?;
+        let pods = pods["items"].as_array().cloned().unwrap_or_default();
+        // Pods marked for deletion are still listed, in their grace period
+        // or waiting for finalizers
+        let deleting: HashSet<(String, String)> = self
+            .storage
+            .finalizers()
+            .deleting("pods")
+            .await?
+            .into_iter()
+            .map(|(namespace, name, _)| (namespace.unwrap_or_default(), name))
+            .collect();
+
+        for pdb in &pdbs {
+            if let Err(e) = self.reconcile(pdb, &pods, &deleting).await {
+                let metadata = &pdb["metadata"];
+                error!("Failed to reconcile PodDisruptionBudget {}/{}: {}", metadata["namespace"], metadata["name"], e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn reconcile(&self, pdb: &Value, pods: &[Value], deleting: &HashSet<(String, String)>) -> Result<()> {
+        let namespace = pdb["metadata"]["namespace"].as_str().unwrap_or("default");
+        let name = pdb["metadata"]["name"].as_str().unwrap_or_default();
+        let spec = &pdb["spec"];
+        let selected: Vec<&Value> = pods.iter().filter(|pod| disruption_budget::selects(pdb, pod)).collect();
+        let is_deleting = |pod: &Value| {
+            let name = pod["metadata"]["name"].as_str().unwrap_or_default();
+            deleting.contains(&(namespace.to_string(), name.to_string()))
+        };
+
+        // Evictions stop counting once the pod is on its way out, or after
+        // the timeout if it never goes
+        let timeout = chrono::Utc::now() - chrono::Duration::seconds(DISRUPTION_TIMEOUT_SECONDS);
+        let mut disrupted = Map::new();
+        for (pod_name, evicted) in pdb["status"]["disruptedPods"].as_object().into_iter().flatten() {
+            let pending = selected.iter().any(|pod| pod["metadata"]["name"] == pod_name.as_str() && !is_deleting(pod));
+            let recent = evicted.as_str().and_then(time::parse).is_some_and(|evicted| evicted > timeout);
+            if pending && recent {
+                disrupted.insert(pod_name.clone(), evicted.clone());
+            }
+        }
+
+        let healthy = selected
+            .iter()
+            .filter(|pod| {
+                let pod_name = pod["metadata"]["name"].as_str().unwrap_or_default();
+                disruption_budget::is_ready(pod) && !is_deleting(pod) && !disrupted.contains_key(pod_name)
+            })
+            .count() as i64;
+        let expected = if disruption_budget::scales_with_controllers(spec) {
+            self.expected_pods(namespace, &selected).await?
+        } else {
+            selected.len() as i64
+        };
+        let desired = disruption_budget::desired_healthy(spec, expected);
+        let allowed = disruption_budget::disruptions_allowed(healthy, desired, expected);
+
+        let mut status = json!({
+            "currentHealthy": healthy,
+            "desiredHealthy": desired,
+            "disruptionsAllowed": allowed,
+            "expectedPods": expected,
+            "observedGeneration": pdb["metadata"]["generation"]
+        });
+        if !disrupted.is_empty() {
+            status["disruptedPods"] = Value::Object(disrupted);
+        }
+        let (condition, reason) = if allowed > 0 { ("True", "SufficientPods") } else { ("False", "InsufficientPods") };
+        let previous = pdb["status"]["conditions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|c| c["type"] == "DisruptionAllowed" && c["status"] == condition);
+        status["conditions"] = json!([{
+            "type": "DisruptionAllowed",
+            "status": condition,
+            "reason": reason,
+            "message": "",
+            "observedGeneration": pdb["metadata"]["generation"],
+            "lastTransitionTime": previous.map(|c| c["lastTransitionTime"].clone()).unwrap_or_else(|| json!(time::now()))
+        }]);
+
+        if status != pdb["status"] {
+            self.storage.pdbs().update_status(namespace, name, status).await?;
+        }
+        Ok(())
+    }
+
+    // The replicas of the controllers of the selected pods, counting a
+    // Deployment's rather than its ReplicaSets'. Pods without one count as
+    // one each.
+    async fn expected_pods(&self, namespace: &str, pods: &[&Value]) -> Result<i64> {
+        let mut controllers = BTreeSet::new();
+        let mut orphans = 0;
+        for pod in pods {
+            let owner = pod["metadata"]["ownerReferences"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|owner| owner["controller"] == true);
+            let (Some(kind), Some(name)) = (owner.and_then(|o| o["kind"].as_str()), owner.and_then(|o| o["name"].as_str())) else {
+                orphans += 1;
+                continue;
+            };
+            let controller = match kind {
+                "ReplicaSet" => {
+                    let replicaset = self.storage.replicasets().get(namespace, name).await.ok();
+                    let deployment = replicaset.as_ref().and_then(|rs| {
+                        let owners = rs["metadata"]["ownerReferences"].as_array()?;
+                        let owner = owners.iter().find(|o| o["controller"] == true && o["kind"] == "Deployment")?;
+                        owner["name"].as_str().map(str::to_string)
+                    });
+                    match deployment {
+                        Some(deployment) => ("Deployment", deployment),
+                        None => ("ReplicaSet", name.to_string()),
+                    }
+                }
+                "StatefulSet" => ("StatefulSet", name.to_string()),
+                _ => {
+                    orphans += 1;
+                    continue;
+                }
+            };
+            controllers.insert(controller);
+        }
+
+        let mut expected = orphans;
+        for (kind, name) in controllers {
+            let workload = match kind {
+                "Deployment" => self.storage.deployments().get(namespace, &name).await,
+                "ReplicaSet" => self.storage.replicasets().get(namespace, &name).await,
+                _ => self.storage.statefulsets().get(namespace, &name).await,
+            };
+            if let Ok(workload) = workload {
+                expected += replicas::desired(&workload["spec"]);
+            }
+        }
+        Ok(expected)
+    }
+}
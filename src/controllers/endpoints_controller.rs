@@ -1,30 +1,34 @@
 use anyhow::Result;
 use serde_json::Value;
 use sqlx::Row;
-use std::time::Duration;
-use tokio::time::sleep;
+use std::time::Instant;
 use tracing::{error, info};
 
+use crate::profiling;
 use crate::Storage;
+use super::Resync;
 
 pub struct EndpointsController {
     storage: Storage,
+    resync: Resync,
 }
 
 impl EndpointsController {
-    pub fn new(storage: Storage) -> Self {
-        Self { storage }
+    pub fn new(storage: Storage, resync: Resync) -> Self {
+        Self { storage, resync }
     }
 
     pub async fn run(&self) -> Result<()> {
         info!("Starting endpoints controller");
         
         loop {
+            let started = Instant::now();
             if let Err(e) = self.reconcile_endpoints().await {
                 error!("Endpoints controller error: {}", e);
             }
             
-            sleep(Duration::from_secs(2)).await;
+            profiling::record("endpoints controller", started);
+            self.resync.wait().await;
         }
     }
 
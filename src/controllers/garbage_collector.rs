@@ -0,0 +1,109 @@
+// Deletes objects whose owners are gone, like the garbage collector of
+// kube-controller-manager, going by the ownerReferences kept in
+// storage::owner_store. An owner deleted in the foreground (see
+// api::finalizers) stays, with the foregroundDeletion finalizer, while its
+// dependents are deleted here, and the finalizer comes off once they're all
+// gone. Objects protected with krust.io/protected are left alone.
+use anyhow::Result;
+use sqlx::Row;
+use std::time::Instant;
+use tracing::{error, info};
+
+use crate::profiling;
+use crate::storage::owner_store::Dependent;
+use crate::Storage;
+use super::Resync;
+
+pub const FOREGROUND_DELETION_FINALIZER: &str = "foregroundDeletion";
+
+pub struct GarbageCollector {
+    storage: Storage,
+    resync: Resync,
+}
+
+impl GarbageCollector {
+    pub fn new(storage: Storage, resync: Resync) -> Self {
+        Self { storage, resync }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        info!("Starting garbage collector");
+
+        loop {
+            let started = Instant::now();
+            if let Err(e) = self.reconcile().await {
+                error!("Garbage collector error: {}", e);
+            }
+
+            profiling::record("garbage collector", started);
+            self.resync.wait().await;
+        }
+    }
+
+    async fn reconcile(&self) -> Result<()> {
+        let owners = self.storage.owners();
+        owners.prune().await?;
+
+        // Owners being deleted in the foreground wait for their dependents
+        let waiting = sqlx::query(
+            "SELECT resource, namespace, name, uid FROM finalizers
+             WHERE deletion_timestamp IS NOT NULL AND finalizers LIKE ?"
+        )
+        .bind(format!("%\"{}\"%", FOREGROUND_DELETION_FINALIZER))
+        .fetch_all(&*self.storage.pool)
+        .await?;
+        for row in waiting {
+            let owner = Dependent {
+                resource: row.get("resource"),
+                namespace: Some(row.get::<String, _>("namespace")).filter(|ns| !ns.is_empty()),
+                name: row.get("name"),
+                uid: row.get("uid"),
+            };
+            let dependents = owners.dependents(&owner.uid).await?;
+            if dependents.is_empty() {
+                let store = self.storage.finalizers();
+                if store.remove(&owner.resource, owner.namespace.as_deref(), &owner.name, FOREGROUND_DELETION_FINALIZER).await? {
+                    info!("Deleted {} {} now that its dependents are gone", owner.resource, owner.name);
+                }
+                continue;
+            }
+            for dependent in dependents {
+                self.delete(&dependent, true).await?;
+            }
+        }
+
+        for dependent in owners.garbage().await? {
+            self.delete(&dependent, false).await?;
+        }
+        Ok(())
+    }
+
+    // Deletes a dependent as a delete through the API would, pods getting
+    // their grace period. Dependents of an owner deleted in the foreground
+    // are deleted in the foreground too if they own objects themselves.
+    async fn delete(&self, dependent: &Dependent, foreground: bool) -> Result<()> {
+        let Dependent { resource, namespace, name, uid } = dependent;
+        let namespace = namespace.as_deref();
+        let store = self.storage.finalizers();
+        let mut kept = store.get(resource, namespace, name).await?.unwrap_or_default();
+        if kept.deletion_timestamp.is_some() || self.storage.protection().is_protected(resource, namespace, name).await? {
+            return Ok(());
+        }
+
+        if foreground && !self.storage.owners().dependents(uid).await?.is_empty() {
+            kept.finalizers.push(FOREGROUND_DELETION_FINALIZER.to_string());
+            store.set(resource, namespace, name, uid, &kept.finalizers).await?;
+        }
+        let grace_period_seconds = match (resource.as_str(), namespace) {
+            ("pods", Some(namespace)) => self.storage.pods().grace_period(namespace, name, None).await?.unwrap_or(0),
+            _ => 0,
+        };
+        if kept.pending() || grace_period_seconds > 0 {
+            store.mark_deleted(resource, namespace, name, grace_period_seconds).await?;
+        } else {
+            store.finish_deletion(resource, namespace, name).await?;
+        }
+        info!("Garbage collected {} {}/{}", resource, namespace.unwrap_or_default(), name);
+        Ok(())
+    }
+}
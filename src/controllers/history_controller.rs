@@ -0,0 +1,138 @@
+// Keeps the rollout history of StatefulSets and DaemonSets as
+// ControllerRevisions, which `kubectl rollout history` lists and `kubectl
+// rollout undo` patches a prior template back from. Each pod template gets
+// one revision, named after the workload and the template's hash; when an
+// old template comes back, its revision is renumbered as the newest.
+// Revisions beyond spec.revisionHistoryLimit are deleted, oldest first.
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::time::Instant;
+use tracing::{error, info};
+
+use crate::models::revision::{self, CONTROLLER_REVISION_HASH_LABEL};
+use crate::profiling;
+use crate::Storage;
+use super::Resync;
+
+pub struct HistoryController {
+    storage: Storage,
+    resync: Resync,
+}
+
+impl HistoryController {
+    pub fn new(storage: Storage, resync: Resync) -> Self {
+        Self { storage, resync }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        info!("Starting history controller");
+
+        loop {
+            let started = Instant::now();
+            if let Err(e) = self.record_all().await {
+                error!("History controller error: {}", e);
+            }
+
+            profiling::record("history controller", started);
+            self.resync.wait().await;
+        }
+    }
+
+    async fn record_all(&self) -> Result<()> {
+        let statefulsets = self.storage.statefulsets().list(None).await?;
+        for statefulset in statefulsets["items"].as_array().into_iter().flatten() {
+            match self.record(statefulset, "StatefulSet").await {
+                Ok(current) => self.set_revisions(statefulset, &current).await?,
+                Err(e) => error!("Failed to record the history of StatefulSet {}: {}", statefulset["metadata"]["name"], e),
+            }
+        }
+
+        let daemonsets = self.storage.daemonsets().list(None).await?;
+        for daemonset in daemonsets["items"].as_array().into_iter().flatten() {
+            if let Err(e) = self.record(daemonset, "DaemonSet").await {
+                error!("Failed to record the history of DaemonSet {}: {}", daemonset["metadata"]["name"], e);
+            }
+        }
+        Ok(())
+    }
+
+    // Makes sure the workload's current template is its newest revision,
+    // returning that revision's name
+    async fn record(&self, workload: &Value, kind: &str) -> Result<String> {
+        let metadata = &workload["metadata"];
+        let namespace = metadata["namespace"].as_str().unwrap_or("default");
+        let name = metadata["name"].as_str().unwrap_or_default();
+        let uid = metadata["uid"].as_str().unwrap_or_default();
+        let template = &workload["spec"]["template"];
+        let hash = revision::template_hash(template);
+        let current = format!("{}-{}", name, hash);
+
+        let tx = self.storage.transaction().await?;
+        let revisions = tx.controller_revisions().owned_by(namespace, uid).await?;
+        let latest = revisions.iter().filter_map(|r| r["revision"].as_i64()).max().unwrap_or(0);
+        match revisions.iter().find(|r| r["metadata"]["name"] == current.as_str()) {
+            Some(existing) if existing["revision"] == latest => {}
+            Some(_) => {
+                info!("{} {}/{} is back at revision {}, now revision {}", kind, namespace, name, current, latest + 1);
+                tx.controller_revisions().set_revision(namespace, &current, latest + 1).await?;
+            }
+            None => {
+                let mut labels = workload["spec"]["selector"]["matchLabels"].clone();
+                labels[CONTROLLER_REVISION_HASH_LABEL] = json!(hash);
+                // Applied as a strategic merge patch, this puts the template back
+                let mut data = json!({ "spec": { "template": template } });
+                data["spec"]["template"]["$patch"] = json!("replace");
+                let controller_revision = json!({
+                    "apiVersion": "apps/v1",
+                    "kind": "ControllerRevision",
+                    "metadata": {
+                        "name": current,
+                        "namespace": namespace,
+                        "labels": labels,
+                        "annotations": metadata["annotations"].as_object().cloned().unwrap_or_default(),
+                        "ownerReferences": [{
+                            "apiVersion": "apps/v1",
+                            "kind": kind,
+                            "name": name,
+                            "uid": uid,
+                            "controller": true,
+                            "blockOwnerDeletion": true
+                        }]
+                    },
+                    "data": data,
+                    "revision": latest + 1
+                });
+                info!("Recording revision {} of {} {}/{}", latest + 1, kind, namespace, name);
+                tx.controller_revisions().create(namespace, controller_revision).await?;
+            }
+        }
+
+        // Revisions are listed oldest first
+        let old: Vec<&Value> = revisions.iter().filter(|r| r["metadata"]["name"] != current.as_str()).collect();
+        let excess = old.len().saturating_sub(revision::history_limit(&workload["spec"]));
+        for stale in old.into_iter().take(excess) {
+            if let Some(stale) = stale["metadata"]["name"].as_str() {
+                tx.controller_revisions().delete(namespace, stale).await?;
+            }
+        }
+
+        tx.commit().await?;
+        Ok(current)
+    }
+
+    // A StatefulSet's status names its revision. krust doesn't roll its pods
+    // out gradually, so the current revision is the update revision.
+    async fn set_revisions(&self, statefulset: &Value, current: &str) -> Result<()> {
+        let mut status = statefulset["status"].clone();
+        if status["updateRevision"] == current && status["currentRevision"] == current {
+            return Ok(());
+        }
+        status["currentRevision"] = json!(current);
+        status["updateRevision"] = json!(current);
+        let metadata = &statefulset["metadata"];
+        self.storage
+            .statefulsets()
+            .update_status(metadata["namespace"].as_str().unwrap_or("default"), metadata["name"].as_str().unwrap_or_default(), status)
+            .await
+    }
+}
@@ -0,0 +1,320 @@
+// Scales the Deployments, ReplicaSets and StatefulSets HorizontalPodAutoscalers
+// point at, like kube-controller-manager's HPA controller. Each metric
+// proposes a replica count and the largest wins, within minReplicas and
+// maxReplicas; a ratio within 10% of the target leaves the count as it is.
+//
+// There is no metrics server: CPU usage comes from what the fake kubelet
+// makes its pods out to use, and External metrics from an annotation on the
+// HPA, which stands in for an external metrics API such as KEDA's. Scaling
+// behavior and stabilization windows aren't applied.
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::time::Instant;
+use tracing::{error, info};
+
+use crate::models::quantity::{self, Resources};
+use crate::models::{pod_conditions, replicas, time};
+use crate::profiling;
+use crate::runtime::fake_kubelet::CPU_USAGE_ANNOTATION;
+use crate::storage::event_store::{self, EventSource, ObjectReference};
+use crate::storage::{LabelSelector, ListSelector};
+use crate::Storage;
+use super::Resync;
+
+/// Current values of an HPA's External metrics, as comma-separated
+/// `<metric name>=<quantity>`, e.g. `queue_messages=30`.
+pub const EXTERNAL_METRICS_ANNOTATION: &str = "krust.io/external-metrics";
+
+// Ratios of current to target closer to 1 than this don't scale
+const TOLERANCE: f64 = 0.1;
+
+pub struct HpaController {
+    storage: Storage,
+    resync: Resync,
+}
+
+// What one metric asks for
+struct Proposal {
+    replicas: i64,
+    metric: String,
+    current: Value,
+}
+
+impl HpaController {
+    pub fn new(storage: Storage, resync: Resync) -> Self {
+        Self { storage, resync }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        info!("Starting HPA controller");
+
+        loop {
+            let started = Instant::now();
+            if let Err(e) = self.reconcile_all().await {
+                error!("HPA controller error: {}", e);
+            }
+
+            profiling::record("HPA controller", started);
+            self.resync.wait().await;
+        }
+    }
+
+    async fn reconcile_all(&self) -> Result<()> {
+        let hpas = self.storage.hpas().list(None).await?;
+        for hpa in hpas["items"].as_array().into_iter().flatten() {
+            if let Err(e) = self.reconcile(hpa).await {
+                let metadata = &hpa["metadata"];
+                error!("Failed to reconcile HPA {}/{}: {}", metadata["namespace"], metadata["name"], e);
+            }
+        }
+        Ok(())
+    }
+
+    async fn reconcile(&self, hpa: &Value) -> Result<()> {
+        let namespace = hpa["metadata"]["namespace"].as_str().unwrap_or("default");
+        let name = hpa["metadata"]["name"].as_str().unwrap_or_default();
+        let spec = &hpa["spec"];
+        let target_ref = &spec["scaleTargetRef"];
+        let kind = target_ref["kind"].as_str().unwrap_or_default();
+        let target_name = target_ref["name"].as_str().unwrap_or_default();
+
+        let target = match kind {
+            "Deployment" => self.storage.deployments().get(namespace, target_name).await,
+            "ReplicaSet" => self.storage.replicasets().get(namespace, target_name).await,
+            "StatefulSet" => self.storage.statefulsets().get(namespace, target_name).await,
+            _ => Err(anyhow::anyhow!("{} is not a scalable kind", kind)),
+        };
+        let target = match target {
+            Ok(target) => target,
+            Err(e) => {
+                let message = format!("the HPA controller was unable to get the target's current scale: {}", e);
+                let conditions = [condition("AbleToScale", false, "FailedGetScale", &message)];
+                return self.write_status(hpa, None, &conditions).await;
+            }
+        };
+
+        let current = replicas::desired(&target["spec"]);
+        let min = spec["minReplicas"].as_i64().unwrap_or(1);
+        let max = spec["maxReplicas"].as_i64().unwrap_or(min);
+        let mut status = json!({
+            "observedGeneration": hpa["metadata"]["generation"],
+            "currentReplicas": target["status"]["replicas"].as_i64().unwrap_or(0),
+            "desiredReplicas": current
+        });
+
+        // A target scaled to zero by hand stays there, unless the HPA
+        // itself scales it to and from zero
+        if current == 0 && min > 0 {
+            let conditions = [
+                condition("AbleToScale", true, "SucceededGetScale", "the HPA controller was able to get the target's current scale"),
+                condition("ScalingActive", false, "ScalingDisabled", "scaling is disabled since the replica count of the target is zero"),
+            ];
+            return self.write_status(hpa, Some(status), &conditions).await;
+        }
+
+        let selector = LabelSelector::from_value(&target["spec"]["selector"])?;
+        let proposals = self.proposals(hpa, namespace, &selector, current).await?;
+        let mut conditions = vec![condition(
+            "AbleToScale",
+            true,
+            "SucceededGetScale",
+            "the HPA controller was able to get the target's current scale",
+        )];
+        let Some(largest) = proposals.iter().max_by_key(|p| p.replicas) else {
+            conditions.push(condition(
+                "ScalingActive",
+                false,
+                "FailedGetResourceMetric",
+                "the HPA was unable to compute the replica count: no metrics returned",
+            ));
+            return self.write_status(hpa, Some(status), &conditions).await;
+        };
+        status["currentMetrics"] = json!(proposals.iter().map(|p| p.current.clone()).collect::<Vec<_>>());
+        conditions.push(condition(
+            "ScalingActive",
+            true,
+            "ValidMetricFound",
+            &format!("the HPA was able to successfully calculate a replica count from {}", largest.metric),
+        ));
+
+        let desired = largest.replicas.clamp(min, max);
+        conditions.push(match largest.replicas {
+            wanted if wanted > max => condition("ScalingLimited", true, "TooManyReplicas", "the desired replica count is more than the maximum replica count"),
+            wanted if wanted < min => condition("ScalingLimited", true, "TooFewReplicas", "the desired replica count is less than the minimum replica count"),
+            _ => condition("ScalingLimited", false, "DesiredWithinRange", "the desired count is within the acceptable range"),
+        });
+        status["desiredReplicas"] = json!(desired);
+
+        if desired != current {
+            self.scale(kind, namespace, target_name, desired).await?;
+            status["lastScaleTime"] = json!(time::now());
+            let direction = if desired > current { "above" } else { "below" };
+            let message = format!("New size: {}; reason: {} {} target", desired, largest.metric, direction);
+            info!("Scaling {} {}/{} from {} to {} replicas", kind, namespace, target_name, current, desired);
+            self.storage.events().record(
+                &EventSource::new("horizontal-pod-autoscaler"),
+                &ObjectReference::new("HorizontalPodAutoscaler", "autoscaling/v2", Some(namespace), name, hpa["metadata"]["uid"].as_str().unwrap_or_default()),
+                event_store::NORMAL,
+                "SuccessfulRescale",
+                &message,
+            ).await?;
+        }
+
+        self.write_status(hpa, Some(status), &conditions).await
+    }
+
+    // The replica count each metric of the HPA asks for
+    async fn proposals(&self, hpa: &Value, namespace: &str, selector: &LabelSelector, current: i64) -> Result<Vec<Proposal>> {
+        let external = external_metrics(&hpa["metadata"]["annotations"]);
+        let mut proposals = Vec::new();
+
+        for metric in hpa["spec"]["metrics"].as_array().into_iter().flatten() {
+            match metric["type"].as_str() {
+                Some("Resource") if metric["resource"]["name"] == "cpu" && current > 0 => {
+                    let target = metric["resource"]["target"]["averageUtilization"].as_i64().unwrap_or(0);
+                    let Some(utilization) = self.cpu_utilization(namespace, selector).await? else {
+                        continue;
+                    };
+                    if target <= 0 {
+                        continue;
+                    }
+                    proposals.push(Proposal {
+                        replicas: scaled(current, utilization as f64 / target as f64),
+                        metric: "cpu resource utilization (percentage of request)".to_string(),
+                        current: json!({
+                            "type": "Resource",
+                            "resource": { "name": "cpu", "current": { "averageUtilization": utilization } }
+                        }),
+                    });
+                }
+                Some("External") => {
+                    let name = metric["external"]["metric"]["name"].as_str().unwrap_or_default();
+                    let Some(value) = external.iter().find(|(metric, _)| metric == name).map(|(_, value)| *value) else {
+                        continue;
+                    };
+                    let target = &metric["external"]["target"];
+                    let replicas = match target["type"].as_str() {
+                        Some("AverageValue") => match quantity::parse_value(&target["averageValue"]).filter(|t| *t > 0.0) {
+                            Some(average) => (value / average).ceil() as i64,
+                            None => continue,
+                        },
+                        _ => match quantity::parse_value(&target["value"]).filter(|t| *t > 0.0) {
+                            // From zero, any load at all wakes the target
+                            Some(_) if current == 0 => i64::from(value > 0.0),
+                            Some(target) => scaled(current, value / target),
+                            None => continue,
+                        },
+                    };
+                    proposals.push(Proposal {
+                        replicas,
+                        metric: format!("external metric {}", name),
+                        current: json!({
+                            "type": "External",
+                            "external": { "metric": { "name": name }, "current": { "value": format_quantity(value) } }
+                        }),
+                    });
+                }
+                _ => {}
+            }
+        }
+        Ok(proposals)
+    }
+
+    // The CPU the target's running pods use, as a percentage of what they
+    // request. None if no pod reports its usage.
+    async fn cpu_utilization(&self, namespace: &str, selector: &LabelSelector) -> Result<Option<i64>> {
+        let selector = ListSelector { labels: selector.clone(), ..Default::default() };
+        let pods = self.storage.pods().list_matching(Some(namespace), &selector).await?;
+        let (mut used, mut requested) = (0, 0);
+        for pod in pods["items"].as_array().into_iter().flatten() {
+            if pod["status"]["phase"] != "Running" || !pod["metadata"]["deletionTimestamp"].is_null() {
+                continue;
+            }
+            let usage = pod["metadata"]["annotations"][CPU_USAGE_ANNOTATION].as_str().and_then(quantity::parse);
+            let request = Resources::requests(&pod["spec"]).cpu_millis;
+            if let (Some(usage), true) = (usage, request > 0) {
+                used += (usage * 1000.0).ceil() as i64;
+                requested += request;
+            }
+        }
+        Ok((requested > 0).then(|| used * 100 / requested))
+    }
+
+    async fn scale(&self, kind: &str, namespace: &str, name: &str, replicas: i64) -> Result<()> {
+        match kind {
+            "Deployment" => {
+                let mut deployment = self.storage.deployments().get(namespace, name).await?;
+                deployment["spec"]["replicas"] = json!(replicas);
+                self.storage.deployments().update(namespace, name, deployment).await?;
+            }
+            "ReplicaSet" => {
+                self.storage.replicasets().update_scale(namespace, name, replicas).await?;
+            }
+            _ => {
+                self.storage.statefulsets().update_scale(namespace, name, replicas).await?;
+            }
+        }
+        Ok(())
+    }
+
+    // Writes the HPA's status, with `conditions` merged into its own, unless
+    // nothing changed. Without `fields` the rest of the status is kept.
+    async fn write_status(&self, hpa: &Value, fields: Option<Value>, conditions: &[Value]) -> Result<()> {
+        let mut status = match fields {
+            Some(mut fields) => {
+                if fields["lastScaleTime"].is_null() && !hpa["status"]["lastScaleTime"].is_null() {
+                    fields["lastScaleTime"] = hpa["status"]["lastScaleTime"].clone();
+                }
+                fields
+            }
+            None => hpa["status"].clone(),
+        };
+        let existing = status["conditions"].as_array().cloned().unwrap_or_default();
+        status["conditions"] = json!(pod_conditions::merge(&existing, conditions));
+
+        if status != hpa["status"] {
+            let namespace = hpa["metadata"]["namespace"].as_str().unwrap_or("default");
+            let name = hpa["metadata"]["name"].as_str().unwrap_or_default();
+            self.storage.hpas().update_status(namespace, name, status).await?;
+        }
+        Ok(())
+    }
+}
+
+// The replica count bringing a metric from `ratio` of its target to it
+fn scaled(current: i64, ratio: f64) -> i64 {
+    if (ratio - 1.0).abs() <= TOLERANCE {
+        return current;
+    }
+    (current as f64 * ratio).ceil() as i64
+}
+
+fn condition(type_: &str, status: bool, reason: &str, message: &str) -> Value {
+    json!({
+        "type": type_,
+        "status": if status { "True" } else { "False" },
+        "reason": reason,
+        "message": message
+    })
+}
+
+// The values of the External metrics annotation
+fn external_metrics(annotations: &Value) -> Vec<(String, f64)> {
+    annotations[EXTERNAL_METRICS_ANNOTATION]
+        .as_str()
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|entry| {
+            let (name, value) = entry.split_once('=')?;
+            Some((name.trim().to_string(), quantity::parse(value.trim())?))
+        })
+        .collect()
+}
+
+fn format_quantity(value: f64) -> String {
+    if value.fract() == 0.0 {
+        format!("{}", value as i64)
+    } else {
+        format!("{}m", (value * 1000.0).round() as i64)
+    }
+}
@@ -0,0 +1,333 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+use uuid::Uuid;
+
+use crate::config::JobConfig;
+use crate::profiling;
+use crate::Storage;
+use super::Resync;
+use crate::models::{limit_range, pod_security};
+use crate::models::time;
+use crate::storage::compression;
+use crate::storage::event_store::{self, EventSource, ObjectReference};
+use super::{namespace_limit_ranges, namespace_policy};
+
+/// Annotation marking a finished pod as already counted in its Job's status,
+/// so deleting the pod later doesn't change the succeeded/failed counters.
+const COUNTED_ANNOTATION: &str = "batch.kubernetes.io/job-tracking";
+
+/// Runs Job pods to completion. How a failed container is retried depends on
+/// the pod template's restartPolicy: with Never the pod fails and is replaced
+/// by a new one after a growing backoff, each failure counting towards
+/// status.failed; with OnFailure the kubelet restarts the container in place
+/// and the restarts count towards the backoffLimit instead.
+pub struct JobController {
+    storage: Storage,
+    resync: Resync,
+    backoff: Duration,
+    max_backoff: Duration,
+    // When each Job last had a pod fail, for the replacement backoff
+    last_failure: Mutex<HashMap<String, Instant>>,
+}
+
+struct JobPod {
+    name: String,
+    phase: String,
+    restarts: i64,
+    counted: bool,
+}
+
+impl JobController {
+    pub fn new(storage: Storage, config: &JobConfig, resync: Resync) -> Self {
+        Self {
+            storage,
+            resync,
+            backoff: Duration::from_secs(config.backoff_seconds),
+            max_backoff: Duration::from_secs(config.max_backoff_seconds),
+            last_failure: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        info!("Starting job controller");
+
+        loop {
+            let started = Instant::now();
+            if let Err(e) = self.reconcile_jobs().await {
+                error!("Job controller error: {}", e);
+            }
+
+            profiling::record("job controller", started);
+            self.resync.wait().await;
+        }
+    }
+
+    async fn reconcile_jobs(&self) -> Result<()> {
+        let jobs = sqlx::query(
+            "SELECT uid, namespace, name, parallelism, completions, backoff_limit, template, suspend,
+                    conditions, start_time, completion_time, succeeded, failed
+             FROM jobs WHERE deletion_timestamp IS NULL"
+        )
+        .fetch_all(&*self.storage.pool)
+        .await?;
+
+        for job in jobs {
+            let namespace: String = job.get("namespace");
+            let name: String = job.get("name");
+            if let Err(e) = self.reconcile_job(&job).await {
+                error!("Failed to reconcile Job {}/{}: {}", namespace, name, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn reconcile_job(&self, job: &sqlx::sqlite::SqliteRow) -> Result<()> {
+        let uid: String = job.get("uid");
+        let namespace: String = job.get("namespace");
+        let name: String = job.get("name");
+        let parallelism: i64 = job.get("parallelism");
+        let completions: Option<i64> = job.get("completions");
+        let backoff_limit: i64 = job.get("backoff_limit");
+        let suspend: bool = job.get("suspend");
+        let template = compression::decode(job, "template")?;
+        let mut conditions: Value = job
+            .get::<Option<String>, _>("conditions")
+            .and_then(|c| serde_json::from_str(&c).ok())
+            .unwrap_or_else(|| json!([]));
+
+        // Finished Jobs are left alone
+        let finished = conditions.as_array().into_iter().flatten().any(|c| {
+            (c["type"] == "Complete" || c["type"] == "Failed") && c["status"] == "True"
+        });
+        if finished || suspend {
+            return Ok(());
+        }
+
+        let now = time::now();
+        let start_time = job.get::<Option<String>, _>("start_time").unwrap_or_else(|| now.clone());
+        let mut succeeded: i64 = job.get("succeeded");
+        let mut failed: i64 = job.get("failed");
+
+        // Count pods that finished since the last sync
+        let mut active = Vec::new();
+        let mut new_failure = false;
+        for pod in self.job_pods(&namespace, &uid).await? {
+            match pod.phase.as_str() {
+                "Succeeded" | "Failed" => {
+                    if pod.counted {
+                        continue;
+                    }
+                    if pod.phase == "Succeeded" {
+                        succeeded += 1;
+                    } else {
+                        failed += 1;
+                        new_failure = true;
+                    }
+                    self.mark_counted(&namespace, &pod).await?;
+                }
+                _ => active.push(pod),
+            }
+        }
+
+        if new_failure {
+            self.last_failure.lock().unwrap().insert(uid.clone(), Instant::now());
+        }
+
+        let restart_policy = template["spec"]["restartPolicy"].as_str().unwrap_or("Never");
+        let restarts: i64 = active.iter().map(|p| p.restarts).sum();
+        // With OnFailure, in-place restarts count as retries too
+        let past_backoff_limit = failed > backoff_limit
+            || (restart_policy == "OnFailure"
+                && if backoff_limit == 0 { restarts > 0 } else { restarts >= backoff_limit });
+
+        let target = completions.unwrap_or(1);
+        let mut completion_time: Option<String> = job.get("completion_time");
+
+        if past_backoff_limit {
+            info!("Job {}/{} has reached its backoff limit", namespace, name);
+            failed += self.terminate(&namespace, &active).await?;
+            active.clear();
+            push_condition(&mut conditions, "Failed", "BackoffLimitExceeded", "Job has reached the specified backoff limit", &now);
+            self.record_event(&uid, &name, &namespace, event_store::WARNING, "BackoffLimitExceeded", "Job has reached the specified backoff limit").await?;
+        } else if succeeded >= target {
+            info!("Job {}/{} completed", namespace, name);
+            self.terminate(&namespace, &active).await?;
+            active.clear();
+            completion_time = Some(now.clone());
+            push_condition(&mut conditions, "Complete", "CompletionsReached", "Reached expected number of succeeded pods", &now);
+            self.record_event(&uid, &name, &namespace, event_store::NORMAL, "Completed", "Job completed").await?;
+        } else {
+            let wanted = parallelism.min(target - succeeded) - active.len() as i64;
+            if wanted > 0 && !self.backing_off(&uid, failed) {
+                for _ in 0..wanted {
+                    self.create_pod(&uid, &name, &namespace, &template).await?;
+                }
+            }
+        }
+
+        let active_pods = self.job_pods(&namespace, &uid).await?;
+        let running = active_pods.iter().filter(|p| p.phase == "Running").count();
+        let active_count = active_pods
+            .iter()
+            .filter(|p| p.phase != "Succeeded" && p.phase != "Failed")
+            .count();
+
+        let mut status = json!({
+            "startTime": start_time,
+            "active": active_count,
+            "succeeded": succeeded,
+            "failed": failed,
+            "ready": running,
+            "conditions": conditions
+        });
+        if let Some(completion_time) = completion_time {
+            status["completionTime"] = json!(completion_time);
+        }
+
+        self.storage.jobs().update_status(&namespace, &name, status).await
+    }
+
+    /// Whether the replacement for a failed pod still has to wait.
+    fn backing_off(&self, uid: &str, failed: i64) -> bool {
+        if failed == 0 {
+            return false;
+        }
+
+        let Some(last_failure) = self.last_failure.lock().unwrap().get(uid).copied() else {
+            return false;
+        };
+
+        let exponent = (failed - 1).min(16) as u32;
+        let delay = self.backoff.saturating_mul(2u32.pow(exponent)).min(self.max_backoff);
+        last_failure.elapsed() < delay
+    }
+
+    async fn job_pods(&self, namespace: &str, job_uid: &str) -> Result<Vec<JobPod>> {
+        let rows = sqlx::query(
+            "SELECT name, phase, status, annotations FROM pods
+             WHERE namespace = ? AND labels LIKE ? AND deletion_timestamp IS NULL
+             ORDER BY creation_timestamp, rowid"
+        )
+        .bind(namespace)
+        .bind(format!("%\"controller-uid\":\"{}\"%", job_uid))
+        .fetch_all(&*self.storage.pool)
+        .await?;
+
+        let mut pods = Vec::new();
+        for row in rows {
+            let status: Value = serde_json::from_str(&row.get::<String, _>("status"))?;
+            let annotations: Value = serde_json::from_str(&row.get::<String, _>("annotations"))
+                .unwrap_or(Value::Null);
+
+            pods.push(JobPod {
+                name: row.get("name"),
+                phase: row.get("phase"),
+                restarts: status["containerStatuses"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter_map(|cs| cs["restartCount"].as_i64())
+                    .sum(),
+                counted: !annotations[COUNTED_ANNOTATION].is_null(),
+            });
+        }
+
+        Ok(pods)
+    }
+
+    async fn mark_counted(&self, namespace: &str, pod: &JobPod) -> Result<()> {
+        let patch = json!({ "metadata": { "annotations": { COUNTED_ANNOTATION: "counted" } } });
+        self.storage.pods().patch(namespace, &pod.name, patch).await?;
+        Ok(())
+    }
+
+    /// Deletes the Job's remaining pods, returning how many there were.
+    async fn terminate(&self, namespace: &str, pods: &[JobPod]) -> Result<i64> {
+        for pod in pods {
+            if let Err(e) = self.storage.pods().delete(namespace, &pod.name).await {
+                error!("Failed to delete Job pod {}/{}: {}", namespace, pod.name, e);
+            }
+        }
+        Ok(pods.len() as i64)
+    }
+
+    async fn create_pod(&self, job_uid: &str, job_name: &str, namespace: &str, template: &Value) -> Result<()> {
+        let pod_name = format!("{}-{}", job_name, &Uuid::new_v4().to_string()[..5]);
+
+        let mut labels = template["metadata"]["labels"].clone();
+        if !labels.is_object() {
+            labels = json!({});
+        }
+        labels["controller-uid"] = json!(job_uid);
+        labels["job-name"] = json!(job_name);
+
+        let mut pod = json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {
+                "name": pod_name,
+                "namespace": namespace,
+                "labels": labels,
+                "annotations": template["metadata"]["annotations"],
+                "ownerReferences": [{
+                    "apiVersion": "batch/v1",
+                    "kind": "Job",
+                    "name": job_name,
+                    "uid": job_uid,
+                    "controller": true,
+                    "blockOwnerDeletion": true
+                }]
+            },
+            "spec": template["spec"]
+        });
+
+        let refused = match limit_range::admit(&namespace_limit_ranges(&self.storage, namespace).await?, &mut pod) {
+            Ok(()) => pod_security::refusal(&namespace_policy(&self.storage, namespace).await?, &pod),
+            Err(refusal) => Some(refusal),
+        };
+        if let Some(refusal) = refused {
+            error!("Failed to create pod for Job {}/{}: {}", namespace, job_name, refusal);
+            return self.record_event(job_uid, job_name, namespace, event_store::WARNING, "FailedCreate", &format!("Error creating: {}", refusal)).await;
+        }
+        if let Err(e) = self.storage.pods().create(namespace, pod).await {
+            error!("Failed to create pod for Job {}/{}: {}", namespace, job_name, e);
+            self.record_event(job_uid, job_name, namespace, event_store::WARNING, "FailedCreate", &format!("Error creating: {}", e)).await?;
+        } else {
+            info!("Created pod {} for Job {}/{}", pod_name, namespace, job_name);
+            self.record_event(job_uid, job_name, namespace, event_store::NORMAL, "SuccessfulCreate", &format!("Created pod: {}", pod_name)).await?;
+        }
+
+        Ok(())
+    }
+
+    async fn record_event(&self, uid: &str, name: &str, namespace: &str, event_type: &str, reason: &str, message: &str) -> Result<()> {
+        self.storage.events().record(
+            &EventSource::new("job-controller"),
+            &ObjectReference::new("Job", "batch/v1", Some(namespace), name, uid),
+            event_type,
+            reason,
+            message,
+        ).await?;
+        Ok(())
+    }
+}
+
+fn push_condition(conditions: &mut Value, type_: &str, reason: &str, message: &str, now: &str) {
+    if !conditions.is_array() {
+        *conditions = json!([]);
+    }
+    conditions.as_array_mut().unwrap().push(json!({
+        "type": type_,
+        "status": "True",
+        "reason": reason,
+        "message": message,
+        "lastProbeTime": now,
+        "lastTransitionTime": now
+    }));
+}
@@ -1,5 +1,182 @@
+pub mod cluster_info_publisher;
+pub mod conntrack;
 pub mod deployment_controller;
+pub mod disruption_controller;
 pub mod endpoints_controller;
+pub mod garbage_collector;
+pub mod history_controller;
+pub mod hpa_controller;
+pub mod job_controller;
+pub mod namespace_controller;
+pub mod pvc_protection_controller;
 pub mod replicaset_controller;
+pub mod root_ca_publisher;
+pub mod serviceaccount_token_controller;
+pub mod service_proxy;
 
-use crate::Storage;
\ No newline at end of file
+use rand::Rng;
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::time::Duration;
+use tokio::task::JoinHandle;
+
+use crate::models::pod_security::Policy;
+use crate::{Config, Storage};
+
+use self::deployment_controller::DeploymentController;
+use self::disruption_controller::DisruptionController;
+use self::endpoints_controller::EndpointsController;
+use self::garbage_collector::GarbageCollector;
+use self::history_controller::HistoryController;
+use self::hpa_controller::HpaController;
+use self::job_controller::JobController;
+use self::namespace_controller::NamespaceController;
+use self::pvc_protection_controller::PvcProtectionController;
+use self::replicaset_controller::ReplicaSetController;
+use self::root_ca_publisher::RootCaPublisher;
+use self::serviceaccount_token_controller::ServiceAccountTokenController;
+use self::service_proxy::ServiceProxy;
+
+/// How long a controller loop sleeps between passes: its configured period,
+/// lengthened by a random jitter each time.
+#[derive(Debug, Clone, Copy)]
+pub struct Resync {
+    pub period: Duration,
+    pub jitter: f64,
+}
+
+impl Resync {
+    /// The resync of the named loop, one of `config::CONTROLLERS`.
+    pub fn new(config: &Config, controller: &str) -> Self {
+        Self {
+            period: config.resync_period(controller),
+            jitter: config.controllers.jitter,
+        }
+    }
+
+    /// The longest a wait can be.
+    pub fn max_wait(&self) -> Duration {
+        self.period.mul_f64(1.0 + self.jitter)
+    }
+
+    pub fn next_wait(&self) -> Duration {
+        if self.jitter <= 0.0 {
+            return self.period;
+        }
+        self.period.mul_f64(1.0 + rand::thread_rng().gen_range(0.0..self.jitter))
+    }
+
+    pub async fn wait(&self) {
+        tokio::time::sleep(self.next_wait()).await;
+    }
+}
+
+/// Starts every controller in the background, returning their task handles.
+pub fn spawn_all(storage: &Storage, config: &Config) -> Vec<JoinHandle<()>> {
+    let resync = |controller| Resync::new(config, controller);
+    let endpoints_controller = EndpointsController::new(storage.clone(), resync("endpoints"));
+    let deployment_controller = DeploymentController::new(storage.clone(), resync("deployment"));
+    let replicaset_controller = ReplicaSetController::new(storage.clone(), resync("replicaSet"));
+    let job_controller = JobController::new(storage.clone(), &config.jobs, resync("job"));
+    let service_proxy = ServiceProxy::new(storage.clone(), resync("serviceProxy"));
+    let token_controller = ServiceAccountTokenController::new(storage.clone(), resync("serviceAccountToken"));
+    let namespace_controller = NamespaceController::new(storage.clone(), resync("namespace"));
+    let pvc_protection_controller = PvcProtectionController::new(storage.clone(), resync("pvcProtection"));
+    let garbage_collector = GarbageCollector::new(storage.clone(), resync("garbageCollector"));
+    let hpa_controller = HpaController::new(storage.clone(), resync("hpa"));
+    let history_controller = HistoryController::new(storage.clone(), resync("history"));
+    let disruption_controller = DisruptionController::new(storage.clone(), resync("disruption"));
+
+    let mut handles = vec![
+        tokio::spawn(async move {
+            if let Err(e) = endpoints_controller.run().await {
+                tracing::error!("Endpoints controller failed: {}", e);
+            }
+        }),
+        tokio::spawn(async move {
+            if let Err(e) = deployment_controller.run().await {
+                tracing::error!("Deployment controller failed: {}", e);
+            }
+        }),
+        tokio::spawn(async move {
+            if let Err(e) = replicaset_controller.run().await {
+                tracing::error!("ReplicaSet controller failed: {}", e);
+            }
+        }),
+        tokio::spawn(async move {
+            if let Err(e) = job_controller.run().await {
+                tracing::error!("Job controller failed: {}", e);
+            }
+        }),
+        tokio::spawn(async move {
+            if let Err(e) = service_proxy.run().await {
+                tracing::error!("Service proxy failed: {}", e);
+            }
+        }),
+        tokio::spawn(async move {
+            if let Err(e) = token_controller.run().await {
+                tracing::error!("Service account token controller failed: {}", e);
+            }
+        }),
+        tokio::spawn(async move {
+            if let Err(e) = namespace_controller.run().await {
+                tracing::error!("Namespace controller failed: {}", e);
+            }
+        }),
+        tokio::spawn(async move {
+            if let Err(e) = pvc_protection_controller.run().await {
+                tracing::error!("PVC protection controller failed: {}", e);
+            }
+        }),
+        tokio::spawn(async move {
+            if let Err(e) = garbage_collector.run().await {
+                tracing::error!("Garbage collector failed: {}", e);
+            }
+        }),
+        tokio::spawn(async move {
+            if let Err(e) = hpa_controller.run().await {
+                tracing::error!("HPA controller failed: {}", e);
+            }
+        }),
+        tokio::spawn(async move {
+            if let Err(e) = history_controller.run().await {
+                tracing::error!("History controller failed: {}", e);
+            }
+        }),
+        tokio::spawn(async move {
+            if let Err(e) = disruption_controller.run().await {
+                tracing::error!("Disruption controller failed: {}", e);
+            }
+        }),
+    ];
+
+    match RootCaPublisher::new(storage.clone(), &config.api_server, config.data_dir().as_ref(), resync("rootCAPublisher")) {
+        Ok(root_ca_publisher) => handles.push(tokio::spawn(async move {
+            if let Err(e) = root_ca_publisher.run().await {
+                tracing::error!("Root CA publisher failed: {}", e);
+            }
+        })),
+        Err(e) => tracing::error!("Not publishing the root CA: {:#}", e),
+    }
+
+    handles
+}
+
+/// The LimitRanges of a namespace, which the pods controllers create are
+/// held to like any others.
+pub(crate) async fn namespace_limit_ranges(storage: &Storage, namespace: &str) -> anyhow::Result<Vec<Value>> {
+    let list = storage.limitranges().list(Some(namespace)).await?;
+    Ok(list["items"].as_array().cloned().unwrap_or_default())
+}
+
+/// The Pod Security policy of a namespace, for controllers creating pods in
+/// it without going through the API.
+pub(crate) async fn namespace_policy(storage: &Storage, namespace: &str) -> anyhow::Result<Policy> {
+    let row = sqlx::query("SELECT labels FROM namespaces WHERE name = ?")
+        .bind(namespace)
+        .fetch_optional(&*storage.pool)
+        .await?;
+    let labels = row.and_then(|row| row.get::<Option<String>, _>("labels"));
+    let labels: Value = labels.and_then(|labels| serde_json::from_str(&labels).ok()).unwrap_or_else(|| json!({}));
+    Ok(Policy::from_labels(&labels))
+}
@@ -0,0 +1,136 @@
+// Finishes deleting namespaces, like the namespace controller of
+// kube-controller-manager. A namespace whose contents had finalizers when
+// it was deleted stays Terminating (see api::finalizers); once nothing in it
+// is waiting for finalizers, and it has none of its own left, it's deleted.
+// Until then its status.conditions say what's left, as upstream's do.
+use anyhow::Result;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::Instant;
+use tracing::{error, info};
+
+use crate::models::time;
+use crate::profiling;
+use crate::storage::tables;
+use crate::Storage;
+use super::Resync;
+
+pub struct NamespaceController {
+    storage: Storage,
+    resync: Resync,
+}
+
+impl NamespaceController {
+    pub fn new(storage: Storage, resync: Resync) -> Self {
+        Self { storage, resync }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        info!("Starting namespace controller");
+
+        loop {
+            let started = Instant::now();
+            if let Err(e) = self.reconcile().await {
+                error!("Namespace controller error: {}", e);
+            }
+
+            profiling::record("namespace controller", started);
+            self.resync.wait().await;
+        }
+    }
+
+    async fn reconcile(&self) -> Result<()> {
+        let store = self.storage.finalizers();
+        for (_, name, finalizers) in store.deleting("namespaces").await? {
+            // Whatever was created in it since, by controllers that don't
+            // go through the API, goes too
+            store.delete_namespace_contents(&name).await?;
+            if finalizers.pending() || !store.pending_in(&name).await?.is_empty() {
+                self.report_remaining(&name).await?;
+                continue;
+            }
+            if store.finish_deletion("namespaces", None, &name).await? {
+                info!("Deleted namespace {}", name);
+            }
+        }
+        Ok(())
+    }
+
+    // Sets the namespace's deletion conditions from what's left in it,
+    // when they've changed
+    async fn report_remaining(&self, name: &str) -> Result<()> {
+        let store = self.storage.finalizers();
+        let (resources, finalizers) = store.remaining_in(name).await?;
+        let status = sqlx::query_scalar::<_, Option<String>>("SELECT status FROM namespaces WHERE name = ? AND deletion_timestamp IS NULL")
+            .bind(name)
+            .fetch_optional(&*self.storage.pool)
+            .await?
+            .flatten()
+            .and_then(|status| serde_json::from_str::<Value>(&status).ok())
+            .unwrap_or_else(|| json!({}));
+
+        let previous = status["conditions"].as_array().cloned().unwrap_or_default();
+        let conditions: Vec<Value> = deletion_conditions(&resources, &finalizers)
+            .into_iter()
+            .map(|(kind, condition_status, reason, message)| {
+                // A condition keeps when it last changed if only its message did
+                let since = previous
+                    .iter()
+                    .find(|c| c["type"] == kind && c["status"] == condition_status)
+                    .and_then(|c| c["lastTransitionTime"].as_str().map(String::from))
+                    .unwrap_or_else(time::now);
+                json!({
+                    "type": kind,
+                    "status": condition_status,
+                    "lastTransitionTime": since,
+                    "reason": reason,
+                    "message": message
+                })
+            })
+            .collect();
+        if conditions == previous {
+            return Ok(());
+        }
+
+        let mut status = status;
+        status["phase"] = json!("Terminating");
+        status["conditions"] = json!(conditions);
+        store.set_namespace_status(name, &status).await
+    }
+}
+
+// The conditions upstream's namespace controller sets on a namespace being
+// deleted, as type, status, reason and message. krust can't fail to
+// discover or parse its resources, so only the last two ever turn True.
+fn deletion_conditions(
+    resources: &BTreeMap<&str, i64>,
+    finalizers: &BTreeMap<String, i64>,
+) -> Vec<(&'static str, &'static str, &'static str, String)> {
+    let content = if resources.is_empty() {
+        ("NamespaceContentRemaining", "False", "ContentRemoved", "All content successfully removed".to_string())
+    } else {
+        let remaining: Vec<String> = resources
+            .iter()
+            .map(|(resource, count)| format!("{}.{} has {} resource instances", resource, tables::group(resource), count))
+            .collect();
+        ("NamespaceContentRemaining", "True", "SomeResourcesRemain", format!("Some resources are remaining: {}", remaining.join(", ")))
+    };
+    let finalizers = if finalizers.is_empty() {
+        ("NamespaceFinalizersRemaining", "False", "ContentHasNoFinalizers", "All content-preserving finalizers finished".to_string())
+    } else {
+        let remaining: Vec<String> = finalizers.iter().map(|(finalizer, count)| format!("{} in {} resource instances", finalizer, count)).collect();
+        (
+            "NamespaceFinalizersRemaining",
+            "True",
+            "SomeFinalizersRemain",
+            format!("Some content in the namespace has finalizers remaining: {}", remaining.join(", ")),
+        )
+    };
+    vec![
+        ("NamespaceDeletionDiscoveryFailure", "False", "ResourcesDiscovered", "All resources successfully discovered".to_string()),
+        ("NamespaceDeletionGroupVersionParsingFailure", "False", "ParsedGroupVersions", "All legacy kube types successfully parsed".to_string()),
+        ("NamespaceDeletionContentFailure", "False", "ContentDeleted", "All content successfully deleted, may be waiting on finalization".to_string()),
+        content,
+        finalizers,
+    ]
+}
@@ -0,0 +1,87 @@
+// Keeps PersistentVolumeClaims that pods use from going away under them,
+// like the PVC protection controller of kube-controller-manager. Every claim
+// gets the kubernetes.io/pvc-protection finalizer, and a claim being deleted
+// keeps it until no pod that hasn't finished uses the claim.
+use anyhow::Result;
+use sqlx::Row;
+use std::time::Instant;
+use tracing::{error, info};
+
+use crate::profiling;
+use crate::Storage;
+use super::Resync;
+
+pub const PVC_PROTECTION_FINALIZER: &str = "kubernetes.io/pvc-protection";
+
+pub struct PvcProtectionController {
+    storage: Storage,
+    resync: Resync,
+}
+
+impl PvcProtectionController {
+    pub fn new(storage: Storage, resync: Resync) -> Self {
+        Self { storage, resync }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        info!("Starting PVC protection controller");
+
+        loop {
+            let started = Instant::now();
+            if let Err(e) = self.reconcile().await {
+                error!("PVC protection controller error: {}", e);
+            }
+
+            profiling::record("PVC protection controller", started);
+            self.resync.wait().await;
+        }
+    }
+
+    async fn reconcile(&self) -> Result<()> {
+        let store = self.storage.finalizers();
+
+        // Claims made without the API, by the StatefulSet controller say,
+        // don't have it yet
+        let claims = sqlx::query(
+            "SELECT c.namespace, c.name FROM persistent_volume_claims c
+             WHERE c.deletion_timestamp IS NULL AND NOT EXISTS (
+                 SELECT 1 FROM finalizers f
+                 WHERE f.resource = 'persistentvolumeclaims' AND f.namespace = c.namespace AND f.name = c.name
+                   AND f.uid = c.uid AND f.finalizers LIKE ?
+             )"
+        )
+        .bind(format!("%\"{}\"%", PVC_PROTECTION_FINALIZER))
+        .fetch_all(&*self.storage.pool)
+        .await?;
+        for row in claims {
+            let (namespace, name): (String, String) = (row.get("namespace"), row.get("name"));
+            store.add("persistentvolumeclaims", Some(&namespace), &name, PVC_PROTECTION_FINALIZER).await?;
+        }
+
+        for (namespace, name, finalizers) in store.deleting("persistentvolumeclaims").await? {
+            let namespace = namespace.unwrap_or_default();
+            if !finalizers.finalizers.iter().any(|f| f == PVC_PROTECTION_FINALIZER) || self.in_use(&namespace, &name).await? {
+                continue;
+            }
+            if store.remove("persistentvolumeclaims", Some(&namespace), &name, PVC_PROTECTION_FINALIZER).await? {
+                info!("Deleted PersistentVolumeClaim {}/{}, no longer used by any pod", namespace, name);
+            }
+        }
+        Ok(())
+    }
+
+    // Whether a pod that hasn't finished mounts the claim
+    async fn in_use(&self, namespace: &str, claim: &str) -> Result<bool> {
+        let used = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM pods p, json_each(p.spec, '$.volumes') v
+             WHERE p.namespace = ? AND p.deletion_timestamp IS NULL
+               AND COALESCE(p.phase, '') NOT IN ('Succeeded', 'Failed')
+               AND json_extract(v.value, '$.persistentVolumeClaim.claimName') = ?"
+        )
+        .bind(namespace)
+        .bind(claim)
+        .fetch_one(&*self.storage.pool)
+        .await?;
+        Ok(used > 0)
+    }
+}
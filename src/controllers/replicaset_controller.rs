@@ -1,118 +1,158 @@
-use anyhow::Result;
+use anyhow::{bail, Result};
+use futures::future::join_all;
 use serde_json::{json, Value};
+use std::collections::HashSet;
+use std::future::Future;
 use sqlx::Row;
-use std::time::Duration;
-use tokio::time::sleep;
+use std::time::Instant;
 use tracing::{error, info};
 use uuid::Uuid;
 
+use crate::profiling;
 use crate::Storage;
+use super::Resync;
+use crate::models::{limit_range, pod_conditions, pod_security};
+use crate::storage::compression;
+use crate::storage::event_store::{self, EventSource, ObjectReference};
+use super::{namespace_limit_ranges, namespace_policy};
+
+// Most pods created in one sync; the rest wait for the next
+const BURST_REPLICAS: i64 = 500;
 
 pub struct ReplicaSetController {
     storage: Storage,
+    resync: Resync,
 }
 
 impl ReplicaSetController {
-    pub fn new(storage: Storage) -> Self {
-        Self { storage }
+    pub fn new(storage: Storage, resync: Resync) -> Self {
+        Self { storage, resync }
     }
 
     pub async fn run(&self) -> Result<()> {
         info!("Starting replicaset controller");
         
         loop {
+            let started = Instant::now();
             if let Err(e) = self.reconcile_replicasets().await {
                 error!("ReplicaSet controller error: {}", e);
             }
             
-            sleep(Duration::from_secs(2)).await;
+            profiling::record("replicaset controller", started);
+            self.resync.wait().await;
         }
     }
 
     async fn reconcile_replicasets(&self) -> Result<()> {
         // Get all replicasets
         let replicasets = sqlx::query(
-            "SELECT uid, name, namespace, spec, status, replicas FROM replicasets WHERE deletion_timestamp IS NULL"
+            "SELECT uid, name, namespace, spec, status, replicas, generation FROM replicasets WHERE deletion_timestamp IS NULL"
         )
         .fetch_all(&*self.storage.pool)
         .await?;
+        // ReplicaSets being deleted make no more pods, so the ones the
+        // garbage collector deletes in the foreground aren't replaced; their
+        // status follows the pods down until they're gone
+        let deleting: HashSet<String> = self.storage.finalizers().deleting("replicasets").await?.into_iter().map(|(_, _, kept)| kept.uid).collect();
         
         for rs_row in replicasets {
             let rs_uid: String = rs_row.get("uid");
             let rs_name: String = rs_row.get("name");
             let rs_namespace: String = rs_row.get("namespace");
-            let spec_str: String = rs_row.get("spec");
             let desired_replicas: i64 = rs_row.get("replicas");
+            let current = StoredStatus {
+                status: compression::decode(&rs_row, "status").unwrap_or_default(),
+                generation: rs_row.get::<Option<i64>, _>("generation").unwrap_or(1),
+            };
+
+            if deleting.contains(&rs_uid) {
+                if let Ok(spec) = compression::decode(&rs_row, "spec") {
+                    let remaining = self.count_matching_pods(&rs_namespace, &spec["selector"], &rs_uid).await?;
+                    self.update_replicaset_status(&current, &rs_namespace, &rs_name, remaining, None).await?;
+                }
+                continue;
+            }
             
-            if let Ok(spec) = serde_json::from_str::<Value>(&spec_str) {
+            if let Ok(spec) = compression::decode(&rs_row, "spec") {
                 let selector = &spec["selector"];
                 
                 // Count existing pods that match this ReplicaSet
-                let existing_pods = self.count_matching_pods(&rs_namespace, selector, &rs_uid).await?;
+                let counts = self.count_matching_pods(&rs_namespace, selector, &rs_uid).await?;
+                let existing_pods = counts.pods;
                 
+                let mut failure = None;
                 if existing_pods < desired_replicas {
                     // Need to create more pods
-                    let pods_to_create = desired_replicas - existing_pods;
+                    let pods_to_create = (desired_replicas - existing_pods).min(BURST_REPLICAS);
                     info!("ReplicaSet {}/{} needs {} more pods", rs_namespace, rs_name, pods_to_create);
-                    
-                    for i in 0..pods_to_create {
-                        self.create_pod_for_replicaset(&rs_uid, &rs_name, &rs_namespace, &spec, i).await?;
+
+                    let (created, error) = slow_start_batch(pods_to_create, |i| {
+                        self.create_pod_for_replicaset(&rs_uid, &rs_name, &rs_namespace, &spec, i)
+                    })
+                    .await;
+                    if let Some(e) = error {
+                        error!(
+                            "Failed to create pods for ReplicaSet {}/{}, created {} of {}: {}",
+                            rs_namespace, rs_name, created, pods_to_create, e
+                        );
+                        self.record_event(&rs_uid, &rs_name, &rs_namespace, event_store::WARNING, "FailedCreate", &format!("Error creating: {}", e)).await?;
+                        failure = Some(e.to_string());
                     }
                 } else if existing_pods > desired_replicas {
                     // Need to delete excess pods
                     let pods_to_delete = existing_pods - desired_replicas;
                     info!("ReplicaSet {}/{} has {} excess pods", rs_namespace, rs_name, pods_to_delete);
                     
-                    self.delete_excess_pods(&rs_namespace, selector, &rs_uid, pods_to_delete).await?;
+                    self.delete_excess_pods(&rs_namespace, &rs_name, selector, &rs_uid, pods_to_delete).await?;
                 }
                 
                 // Update ReplicaSet status
-                self.update_replicaset_status(&rs_uid, &rs_namespace, &rs_name, existing_pods).await?;
+                self.update_replicaset_status(&current, &rs_namespace, &rs_name, counts, failure).await?;
             }
         }
         
         Ok(())
     }
 
-    async fn count_matching_pods(&self, namespace: &str, selector: &Value, rs_uid: &str) -> Result<i64> {
-        // Build label query from selector
-        let mut query_parts = Vec::new();
-        
-        if let Some(match_labels) = selector["matchLabels"].as_object() {
-            for (key, value) in match_labels {
-                if let Some(val) = value.as_str() {
-                    query_parts.push(format!("\"{}\":\"{}\"", key, val));
-                }
-            }
-        }
-        
-        if query_parts.is_empty() {
-            return Ok(0);
+    async fn count_matching_pods(&self, namespace: &str, selector: &Value, rs_uid: &str) -> Result<PodCounts> {
+        let patterns = label_patterns(selector);
+        if patterns.is_empty() {
+            return Ok(PodCounts::default());
         }
-        
-        let label_pattern = query_parts.join(",");
-        
-        // Count pods with matching labels that are owned by this ReplicaSet
-        let count = sqlx::query_scalar::<_, i64>(
-            "SELECT COUNT(*) FROM pods 
+
+        // Count pods with matching labels that are owned by this ReplicaSet,
+        // and the ones among them whose Ready condition is True. Ownership
+        // comes from the ownerReferences index, which outlives compaction of
+        // the watch events it's taken from
+        let query = format!(
+            "SELECT COUNT(*) AS pods,
+                    COALESCE(SUM(EXISTS (
+                        SELECT 1 FROM json_each(pods.status, '$.conditions')
+                        WHERE json_extract(value, '$.type') = 'Ready' AND json_extract(value, '$.status') = 'True'
+                    )), 0) AS ready
+             FROM pods 
              WHERE namespace = ? 
-             AND deletion_timestamp IS NULL 
-             AND labels LIKE ?
+             AND deletion_timestamp IS NULL{}
              AND EXISTS (
-                SELECT 1 FROM events 
-                WHERE resource_type = 'pods' 
-                AND resource_uid = pods.uid 
-                AND object LIKE ?
-             )"
-        )
-        .bind(namespace)
-        .bind(format!("%{}%", label_pattern))
-        .bind(format!("%ownerReferences%{}%", rs_uid))
-        .fetch_one(&*self.storage.pool)
-        .await
-        .unwrap_or(0);
-        
-        Ok(count)
+                SELECT 1 FROM owner_references
+                WHERE resource = 'pods'
+                AND uid = pods.uid
+                AND owner_uid = ?
+             )",
+            " AND labels LIKE ?".repeat(patterns.len())
+        );
+        let mut count = sqlx::query(&query).bind(namespace);
+        for pattern in &patterns {
+            count = count.bind(pattern);
+        }
+        let counts = count
+            .bind(rs_uid)
+            .fetch_one(&*self.storage.pool)
+            .await
+            .map(|row| PodCounts { pods: row.get("pods"), ready: row.get("ready") })
+            .unwrap_or_default();
+
+        Ok(counts)
     }
 
     async fn create_pod_for_replicaset(
@@ -133,6 +173,7 @@ impl ReplicaSetController {
                 "name": pod_name,
                 "namespace": rs_namespace,
                 "labels": template["metadata"]["labels"],
+                "annotations": template["metadata"]["annotations"],
                 "ownerReferences": [{
                     "apiVersion": "apps/v1",
                     "kind": "ReplicaSet",
@@ -152,54 +193,48 @@ impl ReplicaSetController {
             }
         }
         
-        // Create the pod
-        if let Err(e) = self.storage.pods().create(rs_namespace, pod).await {
-            error!("Failed to create pod for ReplicaSet {}/{}: {}", rs_namespace, rs_name, e);
-        } else {
-            info!("Created pod {} for ReplicaSet {}/{}", pod_name, rs_namespace, rs_name);
+        // LimitRange and Pod Security admission apply to the controller's
+        // pods as to any
+        if let Err(refusal) = limit_range::admit(&namespace_limit_ranges(&self.storage, rs_namespace).await?, &mut pod) {
+            bail!(refusal);
         }
-        
+        if let Some(refusal) = pod_security::refusal(&namespace_policy(&self.storage, rs_namespace).await?, &pod) {
+            bail!(refusal);
+        }
+        self.storage.pods().create(rs_namespace, pod).await?;
+        info!("Created pod {} for ReplicaSet {}/{}", pod_name, rs_namespace, rs_name);
+        self.record_event(rs_uid, rs_name, rs_namespace, event_store::NORMAL, "SuccessfulCreate", &format!("Created pod: {}", pod_name)).await?;
+
         Ok(())
     }
 
     async fn delete_excess_pods(
         &self, 
         namespace: &str, 
+        rs_name: &str,
         selector: &Value, 
         rs_uid: &str,
         count: i64
     ) -> Result<()> {
-        // Build label query from selector
-        let mut query_parts = Vec::new();
-        
-        if let Some(match_labels) = selector["matchLabels"].as_object() {
-            for (key, value) in match_labels {
-                if let Some(val) = value.as_str() {
-                    query_parts.push(format!("\"{}\":\"{}\"", key, val));
-                }
-            }
-        }
-        
-        if query_parts.is_empty() {
+        let patterns = label_patterns(selector);
+        if patterns.is_empty() {
             return Ok(());
         }
-        
-        let label_pattern = query_parts.join(",");
-        
+
         // Get pods to delete (oldest first)
-        let pods = sqlx::query(
+        let query = format!(
             "SELECT name FROM pods 
              WHERE namespace = ? 
-             AND deletion_timestamp IS NULL 
-             AND labels LIKE ?
-             ORDER BY creation_timestamp ASC
-             LIMIT ?"
-        )
-        .bind(namespace)
-        .bind(format!("%{}%", label_pattern))
-        .bind(count)
-        .fetch_all(&*self.storage.pool)
-        .await?;
+             AND deletion_timestamp IS NULL{}
+             ORDER BY creation_timestamp ASC, rowid
+             LIMIT ?",
+            " AND labels LIKE ?".repeat(patterns.len())
+        );
+        let mut pods = sqlx::query(&query).bind(namespace);
+        for pattern in &patterns {
+            pods = pods.bind(pattern);
+        }
+        let pods = pods.bind(count).fetch_all(&*self.storage.pool).await?;
         
         for pod_row in pods {
             let pod_name: String = pod_row.get("name");
@@ -207,38 +242,119 @@ impl ReplicaSetController {
                 error!("Failed to delete excess pod {}/{}: {}", namespace, pod_name, e);
             } else {
                 info!("Deleted excess pod {}/{}", namespace, pod_name);
+                self.record_event(rs_uid, rs_name, namespace, event_store::NORMAL, "SuccessfulDelete", &format!("Deleted pod: {}", pod_name)).await?;
             }
         }
         
         Ok(())
     }
 
-    async fn update_replicaset_status(&self, uid: &str, namespace: &str, name: &str, replicas: i64) -> Result<()> {
+    async fn record_event(&self, uid: &str, name: &str, namespace: &str, event_type: &str, reason: &str, message: &str) -> Result<()> {
+        self.storage.events().record(
+            &EventSource::new("replicaset-controller"),
+            &ObjectReference::new("ReplicaSet", "apps/v1", Some(namespace), name, uid),
+            event_type,
+            reason,
+            message,
+        ).await?;
+        Ok(())
+    }
+
+    async fn update_replicaset_status(
+        &self,
+        current: &StoredStatus,
+        namespace: &str,
+        name: &str,
+        counts: PodCounts,
+        failure: Option<String>,
+    ) -> Result<()> {
+        let condition = match failure {
+            Some(message) => json!({
+                "type": "ReplicaFailure",
+                "status": "True",
+                "reason": "FailedCreate",
+                "message": message
+            }),
+            None => json!({
+                "type": "ReplicaFailure",
+                "status": "False",
+                "reason": "ReplicasAvailable",
+                "message": format!("{} replicas are available", counts.ready)
+            }),
+        };
+        let existing = current.status["conditions"].as_array().cloned().unwrap_or_default();
         let status = json!({
-            "replicas": replicas,
-            "fullyLabeledReplicas": replicas,
-            "readyReplicas": replicas, // Simplified - assume all are ready
-            "availableReplicas": replicas,
-            "observedGeneration": 1,
-            "conditions": [
-                {
-                    "type": "ReplicaFailure",
-                    "status": "False",
-                    "lastTransitionTime": chrono::Utc::now().to_rfc3339(),
-                    "reason": "ReplicasAvailable",
-                    "message": format!("{} replicas are available", replicas)
-                }
-            ]
+            "replicas": counts.pods,
+            "fullyLabeledReplicas": counts.pods,
+            "readyReplicas": counts.ready,
+            "availableReplicas": counts.ready,
+            "observedGeneration": current.generation,
+            "conditions": pod_conditions::merge(&existing, &[condition])
         });
-        
-        sqlx::query(
-            "UPDATE replicasets SET status = ? WHERE uid = ?"
-        )
-        .bind(status.to_string())
-        .bind(uid)
-        .execute(&*self.storage.pool)
-        .await?;
-        
+
+        // Unchanged statuses aren't written, so watchers only hear of changes
+        if status != current.status {
+            self.storage.replicasets().update_status(namespace, name, status).await?;
+        }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// A ReplicaSet's status as it was last written, and the generation of the
+// spec the controller is now acting on
+struct StoredStatus {
+    status: Value,
+    generation: i64,
+}
+
+// The pods a ReplicaSet owns, and how many of them are ready
+#[derive(Debug, Clone, Copy, Default)]
+struct PodCounts {
+    pods: i64,
+    ready: i64,
+}
+
+// Runs `create` `count` times in batches of growing size, 1, 2, 4, 8 and so
+// on, as the upstream controller does, each batch at once. A failure in a
+// batch stops the ones after it, so when every pod is going to be rejected,
+// say by admission or a quota, only one attempt is wasted instead of
+// `count`, while a large scale-up that goes through still takes few rounds.
+// Returns how many succeeded and the first error, if there was one.
+async fn slow_start_batch<F, Fut>(count: i64, create: F) -> (i64, Option<anyhow::Error>)
+where
+    F: Fn(i64) -> Fut,
+    Fut: Future<Output = Result<()>>,
+{
+    let mut created = 0;
+    let mut batch_size = 1;
+    while created < count {
+        let batch = batch_size.min(count - created);
+        let results = join_all((created..created + batch).map(&create)).await;
+        let mut error = None;
+        for result in results {
+            match result {
+                Ok(()) => created += 1,
+                Err(e) => {
+                    error.get_or_insert(e);
+                }
+            }
+        }
+        if error.is_some() {
+            return (created, error);
+        }
+        batch_size *= 2;
+    }
+    (created, None)
+}
+
+// LIKE patterns matching the stored labels of pods with each of the
+// selector's matchLabels. Each label is matched on its own: the pod may
+// have others in between.
+fn label_patterns(selector: &Value) -> Vec<String> {
+    selector["matchLabels"]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(key, value)| value.as_str().map(|value| format!("%\"{}\":\"{}\"%", key, value)))
+        .collect()
+}
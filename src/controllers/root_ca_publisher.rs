@@ -0,0 +1,99 @@
+// Publishes the cluster CA as the kube-root-ca.crt ConfigMap in every
+// namespace, like the root CA certificate publisher of
+// kube-controller-manager. Workloads and projected service account volumes
+// read the ca.crt key of it. The ConfigMap is recreated if deleted and
+// reset if its data is changed. A generated CA is kept in the data
+// directory, so the bundle workloads already read stays valid across restarts.
+use anyhow::{Context, Result};
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::time::Instant;
+use tracing::{error, info};
+
+use crate::config::ApiServerConfig;
+use crate::data_dir::DataDir;
+use crate::pki::ClusterCa;
+use crate::profiling;
+use crate::Storage;
+use super::Resync;
+
+pub const CONFIGMAP_NAME: &str = "kube-root-ca.crt";
+
+pub struct RootCaPublisher {
+    storage: Storage,
+    bundle: String,
+    resync: Resync,
+}
+
+impl RootCaPublisher {
+    /// Reads the configured CA bundle, or the CA generated on an earlier
+    /// start, generating a self-signed one when there is neither.
+    pub fn new(storage: Storage, config: &ApiServerConfig, data_dir: Option<&DataDir>, resync: Resync) -> Result<Self> {
+        let bundle = match (&config.root_ca_file, data_dir) {
+            (Some(path), _) => std::fs::read_to_string(path)
+                .with_context(|| format!("failed to read root CA file {}", path))?,
+            (None, data_dir) => ClusterCa::load(data_dir)?.cert_pem()?,
+        };
+        Ok(Self { storage, bundle, resync })
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        info!("Starting root CA publisher");
+
+        loop {
+            let started = Instant::now();
+            if let Err(e) = self.reconcile().await {
+                error!("Root CA publisher error: {}", e);
+            }
+
+            profiling::record("root CA publisher", started);
+            self.resync.wait().await;
+        }
+    }
+
+    async fn reconcile(&self) -> Result<()> {
+        // Namespaces being deleted don't get it back
+        let namespaces = sqlx::query(
+            "SELECT name FROM namespaces WHERE deletion_timestamp IS NULL AND name NOT IN (
+                 SELECT name FROM finalizers WHERE resource = 'namespaces' AND deletion_timestamp IS NOT NULL
+             )"
+        )
+            .fetch_all(&*self.storage.pool)
+            .await?;
+
+        let data = json!({ "ca.crt": self.bundle });
+        for row in namespaces {
+            let namespace: String = row.get("name");
+            match self.storage.configmaps().get(&namespace, CONFIGMAP_NAME).await {
+                Ok(mut configmap) if configmap["data"] != data => {
+                    configmap["data"] = data.clone();
+                    self.storage.configmaps().update(&namespace, CONFIGMAP_NAME, configmap).await?;
+                    info!("Reset {} in namespace {}", CONFIGMAP_NAME, namespace);
+                }
+                Ok(_) => {}
+                Err(e) if e.to_string().contains("not found") => {
+                    self.storage.configmaps().create(&namespace, self.configmap(&namespace)).await?;
+                    info!("Published {} in namespace {}", CONFIGMAP_NAME, namespace);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Ok(())
+    }
+
+    fn configmap(&self, namespace: &str) -> Value {
+        json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": {
+                "name": CONFIGMAP_NAME,
+                "namespace": namespace,
+                "annotations": {
+                    "kubernetes.io/description": "Contains a CA bundle that can be used to verify the kube-apiserver when using internal endpoints such as the internal service IP or kubernetes.default.svc. No other usage is guaranteed across distributions of Kubernetes clusters."
+                }
+            },
+            "data": { "ca.crt": self.bundle }
+        })
+    }
+}
@@ -0,0 +1,369 @@
+// Serves Service externalIPs and NodePorts the way kube-proxy does on a node
+// that owns those addresses: every external IP and node port of a service
+// gets a TCP or UDP listener, and traffic is forwarded to the service's
+// endpoints. Addresses that can't be bound on this machine are skipped.
+// What it relays is counted in super::conntrack.
+use anyhow::Result;
+use serde_json::Value;
+use sqlx::Row;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
+use tracing::{debug, error, info, warn};
+
+use crate::profiling;
+use crate::Storage;
+use super::conntrack::{self, ServicePort, Tracked};
+use super::Resync;
+
+/// How long a UDP client's session with its backend lasts without a reply.
+const UDP_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Protocol {
+    Tcp,
+    Udp,
+}
+
+impl Protocol {
+    fn as_str(self) -> &'static str {
+        match self {
+            Protocol::Tcp => "TCP",
+            Protocol::Udp => "UDP",
+        }
+    }
+}
+
+/// Where traffic to an external address goes: the endpoints of one of the
+/// service's ports, found by the port's name and protocol.
+#[derive(Debug, Clone, PartialEq)]
+struct Target {
+    namespace: String,
+    service: String,
+    port_name: Option<String>,
+    protocol: Protocol,
+}
+
+impl Target {
+    fn port(&self) -> ServicePort {
+        ServicePort {
+            namespace: self.namespace.clone(),
+            service: self.service.clone(),
+            port_name: self.port_name.clone(),
+            protocol: self.protocol.as_str(),
+        }
+    }
+}
+
+struct Listener {
+    target: Target,
+    // None if the address couldn't be bound
+    task: Option<JoinHandle<()>>,
+}
+
+pub struct ServiceProxy {
+    storage: Storage,
+    resync: Resync,
+    listeners: Mutex<HashMap<(Protocol, SocketAddr), Listener>>,
+}
+
+impl ServiceProxy {
+    pub fn new(storage: Storage, resync: Resync) -> Self {
+        Self {
+            storage,
+            resync,
+            listeners: Mutex::new(HashMap::new()),
+        }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        info!("Starting service proxy");
+
+        loop {
+            let started = Instant::now();
+            if let Err(e) = self.reconcile().await {
+                error!("Service proxy error: {}", e);
+            }
+
+            profiling::record("service proxy", started);
+            self.resync.wait().await;
+        }
+    }
+
+    async fn reconcile(&self) -> Result<()> {
+        let rows = sqlx::query("SELECT name, namespace, spec FROM services WHERE deletion_timestamp IS NULL")
+            .fetch_all(&*self.storage.pool)
+            .await?;
+
+        let mut desired = HashMap::new();
+        for row in rows {
+            let spec: Value = serde_json::from_str(&row.get::<String, _>("spec"))?;
+            desired.extend(external_addresses(row.get("namespace"), row.get("name"), &spec));
+        }
+
+        let mut listeners = self.listeners.lock().await;
+
+        listeners.retain(|(protocol, addr), listener| {
+            let keep = desired.get(&(*protocol, *addr)) == Some(&listener.target);
+            if !keep {
+                if let Some(task) = &listener.task {
+                    info!("Closing {} {} of {}/{}", protocol.as_str(), addr, listener.target.namespace, listener.target.service);
+                    task.abort();
+                }
+            }
+            keep
+        });
+
+        for ((protocol, addr), target) in desired {
+            if listeners.contains_key(&(protocol, addr)) {
+                continue;
+            }
+
+            let bound = match protocol {
+                Protocol::Tcp => TcpListener::bind(addr)
+                    .await
+                    .map(|listener| tokio::spawn(serve_tcp(listener, self.storage.clone(), target.clone()))),
+                Protocol::Udp => UdpSocket::bind(addr)
+                    .await
+                    .map(|socket| tokio::spawn(serve_udp(socket, self.storage.clone(), target.clone()))),
+            };
+            let task = match bound {
+                Ok(task) => {
+                    info!("Serving {} {} of {}/{}", protocol.as_str(), addr, target.namespace, target.service);
+                    Some(task)
+                }
+                Err(e) => {
+                    warn!("Cannot serve {} {} of {}/{}: {}", protocol.as_str(), addr, target.namespace, target.service, e);
+                    None
+                }
+            };
+            listeners.insert((protocol, addr), Listener { target, task });
+        }
+
+        Ok(())
+    }
+}
+
+// The addresses a service is served on with where they lead: its port on
+// every external IP, and its nodePort on all of this node's addresses.
+fn external_addresses(namespace: String, service: String, spec: &Value) -> Vec<((Protocol, SocketAddr), Target)> {
+    let ips: Vec<IpAddr> = spec["externalIPs"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|ip| ip.as_str()?.parse().ok())
+        .collect();
+
+    let mut addresses = Vec::new();
+    for port in spec["ports"].as_array().into_iter().flatten() {
+        // Services with other protocols are refused when they are created
+        let protocol = match port["protocol"].as_str().unwrap_or("TCP") {
+            "TCP" => Protocol::Tcp,
+            "UDP" => Protocol::Udp,
+            _ => continue,
+        };
+        let target = Target {
+            namespace: namespace.clone(),
+            service: service.clone(),
+            port_name: port["name"].as_str().map(str::to_string),
+            protocol,
+        };
+
+        if let Some(service_port) = port["port"].as_u64().and_then(|p| u16::try_from(p).ok()) {
+            for ip in &ips {
+                addresses.push(((protocol, SocketAddr::new(*ip, service_port)), target.clone()));
+            }
+        }
+        if let Some(node_port) = port["nodePort"].as_u64().and_then(|p| u16::try_from(p).ok()) {
+            addresses.push(((protocol, SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), node_port)), target));
+        }
+    }
+    addresses
+}
+
+async fn serve_tcp(listener: TcpListener, storage: Storage, target: Target) {
+    // Connections are spread over the endpoints in turn
+    let next = Arc::new(AtomicUsize::new(0));
+    let port = target.port();
+
+    loop {
+        let (mut client, peer) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                warn!("Failed to accept connection for {}/{}: {}", target.namespace, target.service, e);
+                continue;
+            }
+        };
+
+        let storage = storage.clone();
+        let target = target.clone();
+        let next = next.clone();
+        let port = port.clone();
+        tokio::spawn(async move {
+            let Some(backend_addr) = pick_backend(&storage, &target, &next).await else {
+                debug!("No endpoints for {}/{}, dropping connection from {}", target.namespace, target.service, peer);
+                conntrack::dropped(&port);
+                return;
+            };
+
+            match TcpStream::connect(backend_addr).await {
+                Ok(mut backend) => {
+                    let frontend = client.local_addr().unwrap_or(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0));
+                    let tracked = conntrack::open(&port, peer, frontend, backend_addr);
+                    let (client_read, client_write) = client.split();
+                    let (backend_read, backend_write) = backend.split();
+                    tokio::join!(
+                        relay(client_read, backend_write, |n| tracked.sent(n)),
+                        relay(backend_read, client_write, |n| tracked.received(n)),
+                    );
+                }
+                Err(e) => {
+                    debug!("Failed to reach {} for {}/{}: {}", backend_addr, target.namespace, target.service, e);
+                    conntrack::failed(&port, backend_addr);
+                }
+            }
+        });
+    }
+}
+
+// Copies one direction of a TCP connection until it ends, counting what
+// goes through, then shuts down the side written to
+async fn relay<R, W>(mut from: R, mut to: W, count: impl Fn(usize))
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+{
+    let mut buf = vec![0u8; 16 * 1024];
+    while let Ok(n) = from.read(&mut buf).await {
+        if n == 0 || to.write_all(&buf[..n]).await.is_err() {
+            break;
+        }
+        count(n);
+    }
+    let _ = to.shutdown().await;
+}
+
+// A UDP client's socket to its backend
+struct UdpSession {
+    upstream: UdpSocket,
+    backend: SocketAddr,
+    tracked: Tracked,
+}
+
+type UdpSessions = Arc<Mutex<HashMap<SocketAddr, Arc<UdpSession>>>>;
+
+// Datagrams from each client go to one backend through a socket of their
+// own, so replies can be told apart and sent back to the right client.
+async fn serve_udp(socket: UdpSocket, storage: Storage, target: Target) {
+    let socket = Arc::new(socket);
+    let sessions: UdpSessions = Arc::new(Mutex::new(HashMap::new()));
+    let next = Arc::new(AtomicUsize::new(0));
+    let port = target.port();
+    let frontend = socket.local_addr().unwrap_or(SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0));
+    let mut buf = vec![0u8; 65535];
+
+    loop {
+        let (n, peer) = match socket.recv_from(&mut buf).await {
+            Ok(received) => received,
+            Err(e) => {
+                warn!("Failed to receive datagram for {}/{}: {}", target.namespace, target.service, e);
+                continue;
+            }
+        };
+
+        let existing = sessions.lock().await.get(&peer).cloned();
+        let session = match existing {
+            Some(session) => session,
+            None => {
+                let Some(backend_addr) = pick_backend(&storage, &target, &next).await else {
+                    debug!("No endpoints for {}/{}, dropping datagram from {}", target.namespace, target.service, peer);
+                    conntrack::dropped(&port);
+                    continue;
+                };
+                let upstream = match udp_session(backend_addr).await {
+                    Ok(upstream) => upstream,
+                    Err(e) => {
+                        debug!("Failed to reach {} for {}/{}: {}", backend_addr, target.namespace, target.service, e);
+                        conntrack::failed(&port, backend_addr);
+                        continue;
+                    }
+                };
+                let tracked = conntrack::open(&port, peer, frontend, backend_addr);
+                let session = Arc::new(UdpSession { upstream, backend: backend_addr, tracked });
+                sessions.lock().await.insert(peer, session.clone());
+                tokio::spawn(relay_replies(session.clone(), socket.clone(), peer, sessions.clone()));
+                session
+            }
+        };
+
+        match session.upstream.send(&buf[..n]).await {
+            Ok(sent) => session.tracked.sent(sent),
+            Err(e) => {
+                debug!("Failed to forward datagram from {} for {}/{}: {}", peer, target.namespace, target.service, e);
+                conntrack::failed(&port, session.backend);
+            }
+        }
+    }
+}
+
+async fn udp_session(backend: SocketAddr) -> std::io::Result<UdpSocket> {
+    let local = match backend {
+        SocketAddr::V4(_) => SocketAddr::new(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0),
+        SocketAddr::V6(_) => SocketAddr::new(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0),
+    };
+    let upstream = UdpSocket::bind(local).await?;
+    upstream.connect(backend).await?;
+    Ok(upstream)
+}
+
+// Sends the backend's replies back to the client until it goes quiet
+async fn relay_replies(session: Arc<UdpSession>, socket: Arc<UdpSocket>, peer: SocketAddr, sessions: UdpSessions) {
+    let mut buf = vec![0u8; 65535];
+    while let Ok(Ok(n)) = timeout(UDP_IDLE_TIMEOUT, session.upstream.recv(&mut buf)).await {
+        if socket.send_to(&buf[..n], peer).await.is_err() {
+            break;
+        }
+        session.tracked.received(n);
+    }
+    sessions.lock().await.remove(&peer);
+}
+
+// The next endpoint of the target in turn
+async fn pick_backend(storage: &Storage, target: &Target, next: &AtomicUsize) -> Option<SocketAddr> {
+    let backends = endpoint_addresses(storage, target).await;
+    if backends.is_empty() {
+        return None;
+    }
+    Some(backends[next.fetch_add(1, Ordering::Relaxed) % backends.len()])
+}
+
+async fn endpoint_addresses(storage: &Storage, target: &Target) -> Vec<SocketAddr> {
+    let Ok(endpoints) = storage.endpoints().get(&target.namespace, &target.service).await else {
+        return Vec::new();
+    };
+
+    let mut addresses = Vec::new();
+    for subset in endpoints["subsets"].as_array().into_iter().flatten() {
+        let port = subset["ports"].as_array().into_iter().flatten().find(|port| {
+            port["name"].as_str() == target.port_name.as_deref()
+                && port["protocol"].as_str().unwrap_or("TCP") == target.protocol.as_str()
+        });
+        let Some(port) = port.and_then(|port| port["port"].as_u64()).and_then(|p| u16::try_from(p).ok()) else {
+            continue;
+        };
+
+        for address in subset["addresses"].as_array().into_iter().flatten() {
+            if let Some(ip) = address["ip"].as_str().and_then(|ip| ip.parse::<IpAddr>().ok()) {
+                addresses.push(SocketAddr::new(ip, port));
+            }
+        }
+    }
+    addresses
+}
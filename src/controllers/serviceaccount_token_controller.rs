@@ -0,0 +1,117 @@
+// Fills in legacy service account token Secrets, like the tokens controller
+// of kube-controller-manager: a Secret of type
+// kubernetes.io/service-account-token naming a ServiceAccount in its
+// kubernetes.io/service-account.name annotation gets a token for that
+// account, the cluster CA and its namespace. Older charts and controllers
+// still create these instead of using the TokenRequest API. Secrets whose
+// ServiceAccount doesn't exist yet are filled in once it does.
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::time::Instant;
+use tracing::{error, info};
+
+use super::root_ca_publisher::CONFIGMAP_NAME;
+use crate::profiling;
+use crate::Storage;
+use super::Resync;
+
+pub const SECRET_TYPE: &str = "kubernetes.io/service-account-token";
+pub const NAME_ANNOTATION: &str = "kubernetes.io/service-account.name";
+pub const UID_ANNOTATION: &str = "kubernetes.io/service-account.uid";
+
+// Legacy tokens don't expire; this stands in for never
+const TOKEN_LIFETIME_SECONDS: i64 = 10 * 365 * 24 * 60 * 60;
+
+pub struct ServiceAccountTokenController {
+    storage: Storage,
+    resync: Resync,
+}
+
+impl ServiceAccountTokenController {
+    pub fn new(storage: Storage, resync: Resync) -> Self {
+        Self { storage, resync }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        info!("Starting service account token controller");
+
+        loop {
+            let started = Instant::now();
+            if let Err(e) = self.reconcile().await {
+                error!("Service account token controller error: {}", e);
+            }
+
+            profiling::record("service account token controller", started);
+            self.resync.wait().await;
+        }
+    }
+
+    async fn reconcile(&self) -> Result<()> {
+        let secrets = sqlx::query(
+            "SELECT namespace, name, annotations FROM secrets
+             WHERE type = ? AND deletion_timestamp IS NULL
+               AND json_extract(COALESCE(NULLIF(data, 'null'), '{}'), '$.token') IS NULL"
+        )
+        .bind(SECRET_TYPE)
+        .fetch_all(&*self.storage.pool)
+        .await?;
+
+        for row in secrets {
+            let namespace: String = row.get("namespace");
+            let name: String = row.get("name");
+            let annotations: Value = serde_json::from_str(&row.get::<String, _>("annotations")).unwrap_or_default();
+            let Some(account) = annotations[NAME_ANNOTATION].as_str() else {
+                continue;
+            };
+            if let Err(e) = self.populate(&namespace, &name, account).await {
+                error!("Failed to populate token Secret {}/{}: {}", namespace, name, e);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn populate(&self, namespace: &str, name: &str, account: &str) -> Result<()> {
+        let Some(service_account) = self.storage.serviceaccounts().get(namespace, account).await? else {
+            return Ok(());
+        };
+        // Published by the root CA publisher shortly after the namespace appears
+        let Ok(root_ca) = self.storage.configmaps().get(namespace, CONFIGMAP_NAME).await else {
+            return Ok(());
+        };
+        let secret = self.storage.secrets().get(namespace, name).await?;
+
+        let request = json!({
+            "spec": {
+                "expirationSeconds": TOKEN_LIFETIME_SECONDS,
+                "boundObjectRef": {
+                    "apiVersion": "v1",
+                    "kind": "Secret",
+                    "name": name,
+                    "uid": secret["metadata"]["uid"]
+                }
+            }
+        });
+        let review = self.storage.serviceaccounts().create_token(namespace, account, request).await?;
+        let token = review["status"]["token"].as_str().unwrap_or_default();
+
+        let encode = |value: &str| STANDARD.encode(value.as_bytes());
+        let patch = json!({
+            "metadata": {
+                "annotations": { UID_ANNOTATION: service_account["metadata"]["uid"] }
+            },
+            "data": {
+                "token": encode(token),
+                "ca.crt": encode(root_ca["data"]["ca.crt"].as_str().unwrap_or_default()),
+                "namespace": encode(namespace)
+            }
+        });
+        self.storage.secrets().patch(namespace, name, patch).await?;
+        info!("Populated token Secret {}/{} for ServiceAccount {}", namespace, name, account);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,112 @@
+// Where krust keeps its state on disk. Everything lives under one directory,
+// ~/.krust unless `--data-dir`, KRUST_DATA_DIR or `dataDir` in the config
+// says otherwise:
+//
+//   krust.db         the SQLite database (with its -wal and -shm files)
+//   pki/             the generated cluster CA, ca.crt and ca.key
+//   pods/<uid>/      files mounted into a pod's containers, e.g. resolv.conf
+//   kubeconfig       for kubectl, written when serving HTTPS
+//   seccomp/         Localhost seccomp profiles, put there by the user
+//   backups/         copies of krust.db from POST /krust/backup
+//
+// `krust reset` removes these and nothing else, so pointing it at a
+// directory that holds other files too can't take them with it. The seccomp
+// profiles are the user's own and are left alone too, as are the backups,
+// which are there to outlive the database.
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+const DATABASE: &str = "krust.db";
+// Everything `reset` removes: the layout above plus SQLite's side files
+const ENTRIES: &[&str] = &[
+    DATABASE,
+    "krust.db-wal",
+    "krust.db-shm",
+    "krust.db-journal",
+    "pki",
+    "pods",
+    "kubeconfig",
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DataDir {
+    root: PathBuf,
+}
+
+impl DataDir {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// ~/.krust, or .krust in the working directory when there's no home.
+    pub fn default_root() -> PathBuf {
+        std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .unwrap_or_default()
+            .join(".krust")
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn database(&self) -> PathBuf {
+        self.root.join(DATABASE)
+    }
+
+    /// The SQLite URL of the database, created if missing.
+    pub fn database_url(&self) -> String {
+        format!("sqlite:{}?mode=rwc", self.database().display())
+    }
+
+    pub fn pki(&self) -> PathBuf {
+        self.root.join("pki")
+    }
+
+    pub fn kubeconfig(&self) -> PathBuf {
+        self.root.join("kubeconfig")
+    }
+
+    /// Directory for the files of the pod with the given uid.
+    pub fn pod(&self, uid: &str) -> PathBuf {
+        self.root.join("pods").join(uid)
+    }
+
+    /// Where Localhost seccomp profiles are looked up, like the kubelet's
+    /// seccomp root: a localhostProfile is a path relative to it.
+    pub fn seccomp(&self) -> PathBuf {
+        self.root.join("seccomp")
+    }
+
+    /// Where backups of the database go unless asked for elsewhere.
+    pub fn backups(&self) -> PathBuf {
+        self.root.join("backups")
+    }
+
+    /// Creates the directory if it doesn't exist yet.
+    pub fn create(&self) -> Result<()> {
+        std::fs::create_dir_all(&self.root)
+            .with_context(|| format!("failed to create data directory {}", self.root.display()))
+    }
+
+    /// Removes everything krust stored, returning the paths removed. The
+    /// directory itself goes too once nothing else is left in it.
+    pub fn reset(&self) -> Result<Vec<PathBuf>> {
+        let mut removed = Vec::new();
+        for entry in ENTRIES {
+            let path = self.root.join(entry);
+            let result = if path.is_dir() {
+                std::fs::remove_dir_all(&path)
+            } else if path.exists() {
+                std::fs::remove_file(&path)
+            } else {
+                continue;
+            };
+            result.with_context(|| format!("failed to remove {}", path.display()))?;
+            removed.push(path);
+        }
+        // Fails harmlessly when other files are in there
+        let _ = std::fs::remove_dir(&self.root);
+        Ok(removed)
+    }
+}
@@ -0,0 +1,97 @@
+// Feature gates turn optional behaviors and APIs on or off, under the names
+// Kubernetes gives them, so krust can stand in for clusters that have a
+// feature and clusters that don't. They're set in the config file under
+// `featureGates` and with `--feature-gates=Name=true,...`, which wins, and
+// shown at /krust/feature-gates.
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+/// The pods/ephemeralcontainers subresource, used by `kubectl debug`.
+pub const EPHEMERAL_CONTAINERS: &str = "EphemeralContainers";
+/// BOOKMARK events for watches with allowWatchBookmarks.
+pub const WATCH_BOOKMARK: &str = "WatchBookmark";
+/// HPAs with minReplicas 0; see `AutoscalingConfig::scale_to_zero`.
+pub const HPA_SCALE_TO_ZERO: &str = "HPAScaleToZero";
+
+/// A gate krust knows, with its default and the maturity Kubernetes gives
+/// the feature.
+pub struct Feature {
+    pub name: &'static str,
+    pub default: bool,
+    pub stage: &'static str,
+}
+
+pub const FEATURES: &[Feature] = &[
+    Feature { name: EPHEMERAL_CONTAINERS, default: true, stage: "GA" },
+    Feature { name: HPA_SCALE_TO_ZERO, default: false, stage: "Alpha" },
+    Feature { name: WATCH_BOOKMARK, default: true, stage: "GA" },
+];
+
+/// The gates set away from or to their defaults.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct FeatureGates {
+    set: BTreeMap<String, bool>,
+}
+
+impl FeatureGates {
+    /// Parses the value of `--feature-gates`, e.g.
+    /// `EphemeralContainers=false,HPAScaleToZero=true`.
+    pub fn parse(flag: &str) -> Result<Self> {
+        let mut set = BTreeMap::new();
+        for gate in flag.split(',').map(str::trim).filter(|gate| !gate.is_empty()) {
+            let (name, value) = gate
+                .split_once('=')
+                .ok_or_else(|| anyhow!("missing bool value for {}", gate))?;
+            let value = value
+                .trim()
+                .parse()
+                .map_err(|_| anyhow!("invalid value of {}={}, err: expected true or false", name, value))?;
+            set.insert(name.trim().to_string(), value);
+        }
+        let gates = Self { set };
+        gates.validate()?;
+        Ok(gates)
+    }
+
+    /// Sets the gates `other` sets, over these.
+    pub fn merge(&mut self, other: FeatureGates) {
+        self.set.extend(other.set);
+    }
+
+    /// Fails for gates krust doesn't know, as kube-apiserver does.
+    pub fn validate(&self) -> Result<()> {
+        if let Some(name) = self.set.keys().find(|name| feature(name).is_none()) {
+            bail!("unrecognized feature gate: {}", name);
+        }
+        Ok(())
+    }
+
+    pub fn enabled(&self, name: &str) -> bool {
+        self.set
+            .get(name)
+            .copied()
+            .unwrap_or_else(|| feature(name).is_some_and(|feature| feature.default))
+    }
+
+    /// Every known gate and whether it's enabled, for /krust/feature-gates.
+    pub fn report(&self) -> Vec<Value> {
+        FEATURES
+            .iter()
+            .map(|feature| {
+                json!({
+                    "name": feature.name,
+                    "enabled": self.enabled(feature.name),
+                    "default": feature.default,
+                    "stage": feature.stage,
+                })
+            })
+            .collect()
+    }
+}
+
+fn feature(name: &str) -> Option<&'static Feature> {
+    FEATURES.iter().find(|feature| feature.name == name)
+}
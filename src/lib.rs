@@ -1,8 +1,16 @@
 pub mod api;
+pub mod bench;
+pub mod config;
 pub mod controllers;
+pub mod data_dir;
+pub mod feature_gates;
 pub mod models;
+pub mod pki;
+pub mod profiling;
 pub mod runtime;
 pub mod scheduler;
+pub mod smoke;
 pub mod storage;
 
+pub use config::Config;
 pub use storage::Storage;
\ No newline at end of file
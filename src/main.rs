@@ -1,13 +1,12 @@
 use anyhow::Result;
 use krust::{
     api::server::start_server, 
-    controllers::{
-        deployment_controller::DeploymentController,
-        endpoints_controller::EndpointsController,
-        replicaset_controller::ReplicaSetController,
-    },
-    runtime::Kubelet, 
+    bench::{self, BenchOptions},
+    smoke::{self, SmokeOptions},
+    controllers,
+    runtime::{self, dns_server::DnsServer, network_policy::NetworkPolicyEnforcer, Kubelet}, 
     scheduler::Scheduler, 
+    Config,
     Storage
 };
 use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
@@ -22,24 +21,118 @@ async fn main() -> Result<()> {
         .with(tracing_subscriber::fmt::layer())
         .init();
 
+    // `krust bench` load-tests a running krust instead of starting one
+    if std::env::args().nth(1).as_deref() == Some("bench") {
+        let options = BenchOptions::parse(std::env::args().skip(2))?;
+        let report = bench::run(&options).await?;
+        println!("{}", report);
+        return Ok(());
+    }
+
+    // `krust smoke` checks a workload runs end to end, starting krust first
+    // if none is running
+    if std::env::args().nth(1).as_deref() == Some("smoke") {
+        let options = SmokeOptions::parse(std::env::args().skip(2))?;
+        if !smoke::is_running(&options.server).await {
+            tracing::info!("No krust at {}, starting one", options.server);
+            let config = Config::from_arg_list(options.server_args.clone())?;
+            tokio::spawn(async move {
+                if let Err(e) = serve(config).await {
+                    tracing::error!("krust failed: {:#}", e);
+                }
+            });
+            smoke::wait_until_running(&options.server, std::time::Duration::from_secs(60)).await?;
+        }
+        let report = smoke::run(&options).await?;
+        println!("{}", report);
+        if !report.passed() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
+    // `krust reset` wipes the data directory for a fresh cluster
+    if std::env::args().nth(1).as_deref() == Some("reset") {
+        let config = Config::from_arg_list(std::env::args().skip(2))?;
+        let data_dir = config.data_dir().expect("the command line always sets a data directory");
+        for path in data_dir.reset()? {
+            println!("removed {}", path.display());
+        }
+        return Ok(());
+    }
+
+    // `krust migrate` brings the database's schema up to date, and with
+    // --check only reports it, failing unless it's already up to date
+    if std::env::args().nth(1).as_deref() == Some("migrate") {
+        let check = std::env::args().any(|arg| arg == "--check");
+        let config = Config::from_arg_list(std::env::args().skip(2).filter(|arg| arg != "--check"))?;
+        let data_dir = config.data_dir().expect("the command line always sets a data directory");
+        data_dir.create()?;
+        let storage = Storage::new(&data_dir.database_url()).await?;
+        if !check {
+            storage.migrate().await?;
+        }
+        let status = storage.schema().await?;
+        print!("{}", status);
+        if !status.up_to_date() {
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+
     tracing::info!("Starting Krust - Kubernetes in Rust");
+    serve(Config::from_args()?).await
+}
+
+// Runs krust: storage, scheduler, kubelets, controllers and the API server
+async fn serve(mut config: Config) -> Result<()> {
+    let data_dir = config.data_dir().expect("the command line always sets a data directory");
+    data_dir.create()?;
+    tracing::info!("Keeping state in {}", data_dir.root().display());
+    // Earlier versions kept the database in the working directory
+    if std::path::Path::new("krust.db").exists() && !data_dir.database().exists() {
+        tracing::warn!(
+            "Found krust.db in the working directory; move it to {} to keep its objects",
+            data_dir.database().display()
+        );
+    }
 
-    let database_url = "sqlite:krust.db?mode=rwc";
-    let storage = Storage::new(database_url).await?;
+    krust::storage::compression::set_threshold(config.storage.compression_threshold_bytes);
+    let storage = Storage::new(&data_dir.database_url()).await?;
     
     tracing::info!("Running database migrations");
     storage.migrate().await?;
     
     // Start scheduler in background
-    let scheduler = Scheduler::new(storage.clone());
+    let scheduler = Scheduler::new(storage.clone(), &config);
     tokio::spawn(async move {
         if let Err(e) = scheduler.run().await {
             tracing::error!("Scheduler failed: {}", e);
         }
     });
     
+    // Serve cluster DNS, the nameserver of ClusterFirst pods unless
+    // clusterDNS names another
+    if config.dns.server.enabled {
+        match DnsServer::bind(storage.clone(), &config.dns).await {
+            Ok(server) => {
+                if config.dns.cluster_dns.is_empty() {
+                    config.dns.cluster_dns.extend(server.nameserver().map(|ip| ip.to_string()));
+                }
+                tokio::spawn(async move {
+                    if let Err(e) = server.run().await {
+                        tracing::error!("Cluster DNS failed: {}", e);
+                    }
+                });
+            }
+            Err(e) => {
+                tracing::warn!("Not serving cluster DNS: {:#}", e);
+            }
+        }
+    }
+    
     // Start kubelet in background
-    match Kubelet::new(storage.clone()).await {
+    match Kubelet::new(storage.clone(), &config).await {
         Ok(kubelet) => {
             tokio::spawn(async move {
                 if let Err(e) = kubelet.run().await {
@@ -52,32 +145,30 @@ async fn main() -> Result<()> {
         }
     }
     
-    // Start endpoints controller in background
-    let endpoints_controller = EndpointsController::new(storage.clone());
-    tokio::spawn(async move {
-        if let Err(e) = endpoints_controller.run().await {
-            tracing::error!("Endpoints controller failed: {}", e);
+    // Enforce NetworkPolicies on the kubelet's pods, where iptables allows
+    if config.network_policy.enforce {
+        match NetworkPolicyEnforcer::new(storage.clone()).await {
+            Ok(enforcer) => {
+                tokio::spawn(async move {
+                    if let Err(e) = enforcer.run().await {
+                        tracing::error!("NetworkPolicy enforcer failed: {}", e);
+                    }
+                });
+            }
+            Err(e) => {
+                tracing::warn!("Not enforcing NetworkPolicies: {:#}", e);
+            }
         }
-    });
+    }
     
-    // Start deployment controller in background
-    let deployment_controller = DeploymentController::new(storage.clone());
-    tokio::spawn(async move {
-        if let Err(e) = deployment_controller.run().await {
-            tracing::error!("Deployment controller failed: {}", e);
-        }
-    });
+    // Every other configured node is simulated by a fake kubelet
+    runtime::fake_kubelet::spawn_simulated_nodes(&storage, &config);
     
-    // Start replicaset controller in background
-    let replicaset_controller = ReplicaSetController::new(storage.clone());
-    tokio::spawn(async move {
-        if let Err(e) = replicaset_controller.run().await {
-            tracing::error!("ReplicaSet controller failed: {}", e);
-        }
-    });
+    // Start controllers in background
+    controllers::spawn_all(&storage, &config);
     
     tracing::info!("Starting API server on port 6443");
-    start_server(storage).await?;
+    start_server(storage, config).await?;
 
     Ok(())
 }
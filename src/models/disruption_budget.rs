@@ -0,0 +1,65 @@
+// PodDisruptionBudgets, as the disruption controller and the eviction
+// subresource read them. A budget selects pods in its namespace by label;
+// those that are Ready and neither being deleted nor just evicted are
+// healthy, and it allows as many disruptions as it has healthy pods beyond
+// those it needs: its minAvailable, or the pods expected less its
+// maxUnavailable. Percentages are of the pods expected, rounded up.
+use serde_json::Value;
+
+use crate::storage::LabelSelector;
+
+/// How long an eviction counts against a budget while the pod it was for is
+/// still around, as the disruption controller's DeletionTimeout.
+pub const DISRUPTION_TIMEOUT_SECONDS: i64 = 120;
+
+/// Whether the budget selects `pod`. A budget without a selector selects
+/// nothing, and one with an empty selector everything in its namespace.
+pub fn selects(pdb: &Value, pod: &Value) -> bool {
+    let selector = &pdb["spec"]["selector"];
+    if selector.is_null() || pdb["metadata"]["namespace"] != pod["metadata"]["namespace"] {
+        return false;
+    }
+    LabelSelector::from_value(selector).is_ok_and(|selector| selector.matches(&pod["metadata"]["labels"]))
+}
+
+/// Whether the pod counts towards a budget's healthy pods, its own
+/// deletion aside: its Ready condition is True.
+pub fn is_ready(pod: &Value) -> bool {
+    pod["status"]["conditions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .any(|condition| condition["type"] == "Ready" && condition["status"] == "True")
+}
+
+/// Whether the pods expected come from the scale of the controllers of the
+/// selected pods, rather than being the pods there are: when the budget
+/// has a maxUnavailable, or a minAvailable percentage.
+pub fn scales_with_controllers(spec: &Value) -> bool {
+    !spec["maxUnavailable"].is_null() || spec["minAvailable"].is_string()
+}
+
+/// The healthy pods a budget needs out of `expected`.
+pub fn desired_healthy(spec: &Value, expected: i64) -> i64 {
+    if let Some(max_unavailable) = scaled(&spec["maxUnavailable"], expected) {
+        return (expected - max_unavailable).max(0);
+    }
+    scaled(&spec["minAvailable"], expected).unwrap_or(0)
+}
+
+/// How many more pods a budget with `healthy` of the `desired` can lose.
+pub fn disruptions_allowed(healthy: i64, desired: i64, expected: i64) -> i64 {
+    if expected <= 0 {
+        return 0;
+    }
+    (healthy - desired).max(0)
+}
+
+// An intOrString: a count, or a percentage of `total` rounded up
+fn scaled(value: &Value, total: i64) -> Option<i64> {
+    if let Some(count) = value.as_i64() {
+        return Some(count);
+    }
+    let percent: i64 = value.as_str()?.strip_suffix('%')?.parse().ok()?;
+    Some((percent * total + 99) / 100)
+}
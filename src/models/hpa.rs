@@ -0,0 +1,130 @@
+// HorizontalPodAutoscalers are stored as autoscaling/v2 objects. The
+// autoscaling/v1 API, which `kubectl autoscale` and older clients use, only
+// knows a CPU utilization target; it's converted to and from a v2 Resource
+// metric, and the v2 metrics v1 can't express survive a v1 update.
+use serde_json::{json, Value};
+
+/// CPU utilization target of an HPA that names no metrics, as in both APIs.
+pub const DEFAULT_CPU_UTILIZATION: i64 = 80;
+
+/// Fills in spec.minReplicas and spec.metrics when they're absent.
+pub fn set_defaults(spec: &mut Value) {
+    if spec["minReplicas"].is_null() {
+        spec["minReplicas"] = json!(1);
+    }
+    if spec["metrics"].as_array().is_none_or(Vec::is_empty) {
+        spec["metrics"] = json!([cpu_metric(DEFAULT_CPU_UTILIZATION)]);
+    }
+}
+
+/// Checks an HPA spec, returning the field at fault and why. minReplicas
+/// may only be 0 with `scale_to_zero`, and then only if an External metric
+/// can bring the target back up, as with the HPAScaleToZero feature gate.
+pub fn validate(spec: &Value, scale_to_zero: bool) -> Result<(), String> {
+    let min = spec["minReplicas"].as_i64().unwrap_or(1);
+    let max = spec["maxReplicas"].as_i64().unwrap_or(0);
+    let external = spec["metrics"].as_array().into_iter().flatten().any(|m| m["type"] == "External");
+
+    if spec["scaleTargetRef"]["kind"].as_str().unwrap_or("").is_empty() || spec["scaleTargetRef"]["name"].as_str().unwrap_or("").is_empty() {
+        return Err("spec.scaleTargetRef: Required value".to_string());
+    }
+    if min < 0 || (min == 0 && !(scale_to_zero && external)) {
+        let minimum = if scale_to_zero {
+            "must be greater than or equal to 1 unless an External metric is configured"
+        } else {
+            "must be greater than or equal to 1"
+        };
+        return Err(format!("spec.minReplicas: Invalid value: {}: {}", min, minimum));
+    }
+    if max < 1 {
+        return Err(format!("spec.maxReplicas: Invalid value: {}: must be greater than 0", max));
+    }
+    if max < min {
+        return Err(format!("spec.maxReplicas: Invalid value: {}: must be greater than or equal to `minReplicas`", max));
+    }
+    Ok(())
+}
+
+/// The v2 form of an autoscaling/v1 HPA. `current`, the stored HPA a v1
+/// update replaces, provides the metrics other than CPU and the behavior.
+pub fn from_v1(hpa: &Value, current: Option<&Value>) -> Value {
+    let mut converted = hpa.clone();
+    converted["apiVersion"] = json!("autoscaling/v2");
+    let spec = &mut converted["spec"];
+
+    let mut metrics: Vec<Value> = current
+        .and_then(|current| current["spec"]["metrics"].as_array())
+        .into_iter()
+        .flatten()
+        .filter(|metric| !is_cpu_utilization(metric))
+        .cloned()
+        .collect();
+    let target = spec.as_object_mut().and_then(|spec| spec.remove("targetCPUUtilizationPercentage"));
+    if let Some(target) = target.and_then(|target| target.as_i64()) {
+        metrics.insert(0, cpu_metric(target));
+    }
+    spec["metrics"] = json!(metrics);
+    if let Some(behavior) = current.map(|current| &current["spec"]["behavior"]).filter(|b| !b.is_null()) {
+        spec["behavior"] = behavior.clone();
+    }
+
+    if let Some(status) = converted.get_mut("status").and_then(Value::as_object_mut) {
+        status.remove("currentCPUUtilizationPercentage");
+    }
+    converted
+}
+
+/// The autoscaling/v1 form of a stored HPA.
+pub fn to_v1(hpa: &Value) -> Value {
+    let spec = &hpa["spec"];
+    let target = spec["metrics"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|metric| is_cpu_utilization(metric))
+        .and_then(|metric| metric["resource"]["target"]["averageUtilization"].as_i64());
+    let current = hpa["status"]["currentMetrics"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .find(|metric| metric["type"] == "Resource" && metric["resource"]["name"] == "cpu")
+        .and_then(|metric| metric["resource"]["current"]["averageUtilization"].as_i64());
+
+    let mut converted = json!({
+        "apiVersion": "autoscaling/v1",
+        "kind": "HorizontalPodAutoscaler",
+        "metadata": hpa["metadata"],
+        "spec": {
+            "scaleTargetRef": spec["scaleTargetRef"],
+            "minReplicas": spec["minReplicas"],
+            "maxReplicas": spec["maxReplicas"]
+        },
+        "status": {
+            "currentReplicas": hpa["status"]["currentReplicas"].as_i64().unwrap_or(0),
+            "desiredReplicas": hpa["status"]["desiredReplicas"].as_i64().unwrap_or(0)
+        }
+    });
+    if let Some(target) = target {
+        converted["spec"]["targetCPUUtilizationPercentage"] = json!(target);
+    }
+    for field in ["observedGeneration", "lastScaleTime"] {
+        if !hpa["status"][field].is_null() {
+            converted["status"][field] = hpa["status"][field].clone();
+        }
+    }
+    if let Some(current) = current {
+        converted["status"]["currentCPUUtilizationPercentage"] = json!(current);
+    }
+    converted
+}
+
+fn cpu_metric(utilization: i64) -> Value {
+    json!({
+        "type": "Resource",
+        "resource": { "name": "cpu", "target": { "type": "Utilization", "averageUtilization": utilization } }
+    })
+}
+
+fn is_cpu_utilization(metric: &Value) -> bool {
+    metric["type"] == "Resource" && metric["resource"]["name"] == "cpu" && metric["resource"]["target"]["type"] == "Utilization"
+}
@@ -0,0 +1,256 @@
+// LimitRanges, as kube-apiserver's LimitRanger admission plugin applies them
+// to pods being created. Containers that leave out a request or limit get
+// the defaults of their namespace's LimitRanges, recorded in the
+// kubernetes.io/limit-ranger annotation; then every container, and the pod
+// as a whole, must fall within the min, max and maxLimitRequestRatio of the
+// Container and Pod items, or the pod is refused with every reason why.
+use serde_json::{json, Map, Value};
+
+use super::quantity;
+
+/// The annotation saying which defaults a pod was given.
+pub const ANNOTATION: &str = "kubernetes.io/limit-ranger";
+
+/// Applies the defaults of `limit_ranges` to `pod` and checks it against
+/// their constraints, returning the message refusing it if it's outside them.
+pub fn admit(limit_ranges: &[Value], pod: &mut Value) -> Result<(), String> {
+    let items: Vec<&Value> = limit_ranges
+        .iter()
+        .flat_map(|limit_range| limit_range["spec"]["limits"].as_array().into_iter().flatten())
+        .collect();
+    if items.is_empty() {
+        return Ok(());
+    }
+
+    let set = apply_defaults(&items, pod);
+    if !set.is_empty() {
+        if !pod["metadata"]["annotations"].is_object() {
+            pod["metadata"]["annotations"] = json!({});
+        }
+        pod["metadata"]["annotations"][ANNOTATION] = json!(format!("LimitRanger plugin set: {}", set.join("; ")));
+    }
+
+    let violations = check(&items, pod);
+    match violations.as_slice() {
+        [] => Ok(()),
+        [violation] => Err(refusal(pod, violation)),
+        _ => Err(refusal(pod, &format!("[{}]", violations.join(", ")))),
+    }
+}
+
+fn refusal(pod: &Value, reason: &str) -> String {
+    format!("pods {:?} is forbidden: {}", pod["metadata"]["name"].as_str().unwrap_or_default(), reason)
+}
+
+// The defaults of a Container item, filled in as the API does: a missing
+// default limit is the max, and a missing default request the default
+// limit, or failing that the min
+fn container_defaults(item: &Value) -> (Map<String, Value>, Map<String, Value>) {
+    let map = |value: &Value| value.as_object().cloned().unwrap_or_default();
+    let mut limits = map(&item["default"]);
+    for (resource, max) in map(&item["max"]) {
+        limits.entry(resource).or_insert(max);
+    }
+    let mut requests = map(&item["defaultRequest"]);
+    for (resource, limit) in limits.iter().chain(map(&item["min"]).iter()) {
+        requests.entry(resource.clone()).or_insert(limit.clone());
+    }
+    (requests, limits)
+}
+
+// Gives each container the requests and limits it leaves out, returning
+// what was set for the annotation
+fn apply_defaults(items: &[&Value], pod: &mut Value) -> Vec<String> {
+    let defaults: Vec<_> = items.iter().filter(|item| item["type"] == "Container").map(|item| container_defaults(item)).collect();
+    let mut set = Vec::new();
+    for (field, kind) in [("initContainers", "init container"), ("containers", "container")] {
+        for container in pod["spec"][field].as_array_mut().into_iter().flatten() {
+            let name = container["name"].as_str().unwrap_or_default().to_string();
+            let resources = &mut container["resources"];
+            if !resources.is_object() {
+                *resources = json!({});
+            }
+            for key in ["requests", "limits"] {
+                if !resources[key].is_object() {
+                    resources[key] = json!({});
+                }
+            }
+
+            // A limit without a request is also the request, as the API
+            // defaults it before admission
+            let limits = resources["limits"].as_object().cloned().unwrap_or_default();
+            let requests = resources["requests"].as_object_mut().unwrap();
+            for (resource, limit) in limits {
+                requests.entry(resource).or_insert(limit);
+            }
+
+            let mut defaulted = |key: &str| {
+                let current = resources[key].as_object_mut().unwrap();
+                let mut added = Vec::new();
+                let values = defaults.iter().flat_map(|(requests, limits)| if key == "requests" { requests } else { limits });
+                for (resource, value) in values {
+                    if !current.contains_key(resource) {
+                        current.insert(resource.clone(), value.clone());
+                        added.push(resource.clone());
+                    }
+                }
+                added
+            };
+            let requests = defaulted("requests");
+            let limits = defaulted("limits");
+            if !requests.is_empty() {
+                set.push(format!("{} request for {} {}", requests.join(", "), kind, name));
+            }
+            if !limits.is_empty() {
+                set.push(format!("{} limit for {} {}", limits.join(", "), kind, name));
+            }
+
+            for key in ["requests", "limits"] {
+                if resources[key].as_object().is_some_and(Map::is_empty) {
+                    resources.as_object_mut().unwrap().remove(key);
+                }
+            }
+        }
+    }
+    set
+}
+
+// What every container, and the pod, asks for and is limited to, checked
+// against each item of its type
+fn check(items: &[&Value], pod: &Value) -> Vec<String> {
+    let containers: Vec<&Value> = ["initContainers", "containers"]
+        .iter()
+        .flat_map(|field| pod["spec"][field].as_array().into_iter().flatten())
+        .collect();
+
+    let mut violations = Vec::new();
+    for item in items {
+        match item["type"].as_str() {
+            Some("Container") => {
+                for container in &containers {
+                    let resources = &container["resources"];
+                    let amounts = |key: &str| {
+                        let amounts = resources[key].as_object().into_iter().flatten();
+                        amounts.map(|(resource, value)| (resource.clone(), value.clone())).collect()
+                    };
+                    violations.extend(constraints(item, "Container", &amounts("requests"), &amounts("limits")));
+                }
+            }
+            Some("Pod") => {
+                let (requests, limits) = pod_totals(pod);
+                violations.extend(constraints(item, "Pod", &requests, &limits));
+            }
+            _ => {}
+        }
+    }
+    violations
+}
+
+// The pod's requests and limits: those of its containers summed, or those
+// of its largest init container if bigger
+fn pod_totals(pod: &Value) -> (Map<String, Value>, Map<String, Value>) {
+    let total = |key: &str| {
+        let mut sums: Map<String, Value> = Map::new();
+        let mut amounts = std::collections::BTreeMap::<String, f64>::new();
+        for container in pod["spec"]["containers"].as_array().into_iter().flatten() {
+            for (resource, value) in container["resources"][key].as_object().into_iter().flatten() {
+                *amounts.entry(resource.clone()).or_default() += quantity::parse_value(value).unwrap_or(0.0);
+            }
+        }
+        for container in pod["spec"]["initContainers"].as_array().into_iter().flatten() {
+            for (resource, value) in container["resources"][key].as_object().into_iter().flatten() {
+                let amount = amounts.entry(resource.clone()).or_default();
+                *amount = amount.max(quantity::parse_value(value).unwrap_or(0.0));
+            }
+        }
+        for (resource, amount) in amounts {
+            let formatted = format_quantity(&resource, amount);
+            sums.insert(resource, json!(formatted));
+        }
+        sums
+    };
+    (total("requests"), total("limits"))
+}
+
+// A quantity in its plainest form: CPU in cores or millicores, and anything
+// else in the largest binary unit that divides it, or as a plain number
+fn format_quantity(resource: &str, amount: f64) -> String {
+    if resource == "cpu" {
+        let millis = (amount * 1000.0).round() as i64;
+        return if millis % 1000 == 0 { (millis / 1000).to_string() } else { format!("{}m", millis) };
+    }
+    let amount = amount.round() as i64;
+    for (suffix, unit) in [("Ei", 1i64 << 60), ("Pi", 1 << 50), ("Ti", 1 << 40), ("Gi", 1 << 30), ("Mi", 1 << 20), ("Ki", 1 << 10)] {
+        if amount != 0 && amount % unit == 0 {
+            return format!("{}{}", amount / unit, suffix);
+        }
+    }
+    amount.to_string()
+}
+
+// The ways requests and limits fall outside an item's min, max and
+// maxLimitRequestRatio, worded as kube-apiserver words them
+fn constraints(item: &Value, kind: &str, requests: &Map<String, Value>, limits: &Map<String, Value>) -> Vec<String> {
+    let text = |value: &Value| value.as_str().map(str::to_string).unwrap_or_else(|| value.to_string());
+    let amount = |value: &Value| quantity::parse_value(value).unwrap_or(0.0);
+    let bounds = |key: &str| item[key].as_object().into_iter().flatten();
+
+    let mut violations = Vec::new();
+    for (resource, min) in bounds("min") {
+        let (bound, floor) = (text(min), amount(min));
+        match requests.get(resource) {
+            None => violations.push(format!("minimum {} usage per {} is {}.  No request is specified", resource, kind, bound)),
+            Some(request) if amount(request) < floor => violations.push(format!(
+                "minimum {} usage per {} is {}, but request is {}",
+                resource,
+                kind,
+                bound,
+                text(request)
+            )),
+            _ => {}
+        }
+        if let Some(limit) = limits.get(resource).filter(|limit| amount(limit) < floor) {
+            violations.push(format!("minimum {} usage per {} is {}, but limit is {}", resource, kind, bound, text(limit)));
+        }
+    }
+    for (resource, max) in bounds("max") {
+        let (bound, ceiling) = (text(max), amount(max));
+        match limits.get(resource) {
+            None => violations.push(format!("maximum {} usage per {} is {}.  No limit is specified", resource, kind, bound)),
+            Some(limit) if amount(limit) > ceiling => violations.push(format!(
+                "maximum {} usage per {} is {}, but limit is {}",
+                resource,
+                kind,
+                bound,
+                text(limit)
+            )),
+            _ => {}
+        }
+        if let Some(request) = requests.get(resource).filter(|request| amount(request) > ceiling) {
+            violations.push(format!("maximum {} usage per {} is {}, but request is {}", resource, kind, bound, text(request)));
+        }
+    }
+    for (resource, ratio) in bounds("maxLimitRequestRatio") {
+        let bound = text(ratio);
+        let request = requests.get(resource).map(amount).filter(|request| *request > 0.0);
+        match (limits.get(resource).map(amount), request) {
+            (None, _) => violations.push(format!(
+                "{} max limit to request ratio per {} is {}, but no limit is specified",
+                resource, kind, bound
+            )),
+            (Some(_), None) => violations.push(format!(
+                "{} max limit to request ratio per {} is {}, but no request is specified or request is 0",
+                resource, kind, bound
+            )),
+            (Some(limit), Some(request)) if limit / request > amount(ratio) => violations.push(format!(
+                "{} max limit to request ratio per {} is {}, but provided ratio is {:.6}",
+                resource,
+                kind,
+                bound,
+                limit / request
+            )),
+            _ => {}
+        }
+    }
+    violations
+}
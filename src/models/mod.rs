@@ -1,4 +1,15 @@
 pub mod pod;
+pub mod pod_conditions;
+pub mod pod_security;
 pub mod service;
 pub mod deployment;
-pub mod namespace;
\ No newline at end of file
+pub mod disruption_budget;
+pub mod hpa;
+pub mod limit_range;
+pub mod namespace;
+pub mod network_policy;
+pub mod node;
+pub mod quantity;
+pub mod replicas;
+pub mod revision;
+pub mod time;
\ No newline at end of file
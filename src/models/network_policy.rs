@@ -0,0 +1,95 @@
+// NetworkPolicy fields that end up in the iptables rules enforcing them:
+// ipBlock CIDRs and the protocols and numbers of rule ports. Policies are
+// checked as they're written, and the enforcer only uses values that parse,
+// so nothing else reaches iptables-restore.
+use serde_json::Value;
+use std::net::IpAddr;
+
+/// The protocols a NetworkPolicy port may name.
+pub const PROTOCOLS: &[&str] = &["SCTP", "TCP", "UDP"];
+
+/// An IP network, as written in an ipBlock's `cidr` or `except`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Cidr {
+    pub address: IpAddr,
+    pub prefix: u8,
+}
+
+impl Cidr {
+    /// Parses `address/prefix`, with a prefix no longer than the address.
+    pub fn parse(cidr: &str) -> Option<Self> {
+        let (address, prefix) = cidr.split_once('/')?;
+        let address: IpAddr = address.parse().ok()?;
+        if prefix.is_empty() || !prefix.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+        let prefix: u8 = prefix.parse().ok()?;
+        let bits = if address.is_ipv4() { 32 } else { 128 };
+        (prefix <= bits).then_some(Self { address, prefix })
+    }
+}
+
+impl std::fmt::Display for Cidr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}/{}", self.address, self.prefix)
+    }
+}
+
+/// Whether `port` is a port number a rule may name.
+pub fn is_port_number(port: i64) -> bool {
+    (1..=65535).contains(&port)
+}
+
+/// Checks a NetworkPolicy spec's ipBlocks and ports, returning the field at
+/// fault and why.
+pub fn validate(spec: &Value) -> Result<(), String> {
+    for (rules_field, peers_field) in [("ingress", "from"), ("egress", "to")] {
+        for (i, rule) in spec[rules_field].as_array().into_iter().flatten().enumerate() {
+            let rule_path = format!("spec.{}[{}]", rules_field, i);
+            for (j, peer) in rule[peers_field].as_array().into_iter().flatten().enumerate() {
+                let block = &peer["ipBlock"];
+                if block.is_null() {
+                    continue;
+                }
+                let block_path = format!("{}.{}[{}].ipBlock", rule_path, peers_field, j);
+                cidr(&format!("{}.cidr", block_path), &block["cidr"])?;
+                for (k, except) in block["except"].as_array().into_iter().flatten().enumerate() {
+                    cidr(&format!("{}.except[{}]", block_path, k), except)?;
+                }
+            }
+            for (j, port) in rule["ports"].as_array().into_iter().flatten().enumerate() {
+                let port_path = format!("{}.ports[{}]", rule_path, j);
+                if let Some(protocol) = port.get("protocol").filter(|protocol| !protocol.is_null()) {
+                    if !protocol.as_str().is_some_and(|protocol| PROTOCOLS.contains(&protocol)) {
+                        return Err(format!("{}.protocol: Unsupported value: {}: supported values: \"SCTP\", \"TCP\", \"UDP\"", port_path, protocol));
+                    }
+                }
+                let number = match &port["port"] {
+                    Value::Null | Value::String(_) => None,
+                    Value::Number(number) => match number.as_i64().filter(|&n| is_port_number(n)) {
+                        Some(number) => Some(number),
+                        None => return Err(format!("{}.port: Invalid value: {}: must be between 1 and 65535, inclusive", port_path, number)),
+                    },
+                    other => return Err(format!("{}.port: Invalid value: {}: must be a port number or name", port_path, other)),
+                };
+                let end = &port["endPort"];
+                if !end.is_null() {
+                    let Some(end) = end.as_i64().filter(|&n| is_port_number(n)) else {
+                        return Err(format!("{}.endPort: Invalid value: {}: must be between 1 and 65535, inclusive", port_path, end));
+                    };
+                    if number.is_none_or(|number| end < number) {
+                        return Err(format!("{}.endPort: Invalid value: {}: must be a number port no smaller than port", port_path, end));
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn cidr(field: &str, value: &Value) -> Result<Cidr, String> {
+    value
+        .as_str()
+        .and_then(Cidr::parse)
+        .ok_or_else(|| format!("{}: Invalid value: {}: must be a valid CIDR", field, value))
+}
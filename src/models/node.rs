@@ -0,0 +1,117 @@
+// Node objects as served by the nodes API, built from the node settings in
+// the config. Nodes exist for as long as the config lists them; only their
+// spec can be changed, and their kubelets report their conditions, both of
+// which the NodeStore keeps.
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+use crate::config::{Config, NodeConfig};
+use crate::storage::node_store::StoredNode;
+use super::time;
+
+/// The taint a node has while it's cordoned, as the node lifecycle
+/// controller gives it.
+pub const UNSCHEDULABLE_TAINT: &str = "node.kubernetes.io/unschedulable";
+
+/// The Node object for `name`, with its spec and conditions as `stored`
+/// has them if it has, or `None` if the cluster has no such node.
+pub fn get(config: &Config, name: &str, stored: &HashMap<String, StoredNode>) -> Option<Value> {
+    let index = config.node_names().iter().position(|n| n == name)?;
+    Some(to_json(name, index, &config.node(name), stored.get(name)))
+}
+
+/// All nodes of the cluster, in `Config::node_names` order.
+pub fn list(config: &Config, stored: &HashMap<String, StoredNode>) -> Vec<Value> {
+    config
+        .node_names()
+        .iter()
+        .enumerate()
+        .map(|(index, name)| to_json(name, index, &config.node(name), stored.get(name)))
+        .collect()
+}
+
+/// `spec` with the unschedulable taint if its node is cordoned, and
+/// without it if not.
+pub fn with_unschedulable_taint(spec: &Value) -> Value {
+    let mut spec = if spec.is_object() { spec.clone() } else { json!({}) };
+    let unschedulable = spec["unschedulable"].as_bool().unwrap_or(false);
+    let mut taints: Vec<Value> = spec["taints"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|taint| taint["key"] != UNSCHEDULABLE_TAINT || unschedulable)
+        .cloned()
+        .collect();
+    if unschedulable && !taints.iter().any(|taint| taint["key"] == UNSCHEDULABLE_TAINT) {
+        taints.push(json!({ "key": UNSCHEDULABLE_TAINT, "effect": "NoSchedule", "timeAdded": time::now() }));
+    }
+    if !taints.is_empty() {
+        spec["taints"] = json!(taints);
+    } else if let Some(fields) = spec.as_object_mut() {
+        fields.remove("taints");
+    }
+    spec
+}
+
+fn to_json(name: &str, index: usize, node: &NodeConfig, stored: Option<&StoredNode>) -> Value {
+    let now = time::now();
+    let mut allocatable = json!({
+        "cpu": node.cpu,
+        "memory": node.memory,
+        "pods": node.pods.to_string()
+    });
+    if let Some(storage) = &node.ephemeral_storage {
+        allocatable["ephemeral-storage"] = json!(storage);
+    }
+
+    let spec = match stored.and_then(|stored| stored.spec.as_ref()) {
+        Some(spec) => spec.clone(),
+        None if node.taints.is_empty() => json!({}),
+        None => json!({ "taints": node.taints }),
+    };
+    // Ready until its kubelet says otherwise
+    let conditions = match stored.and_then(|stored| stored.status.as_ref()) {
+        Some(status) => status["conditions"].clone(),
+        None => json!([{
+            "type": "Ready",
+            "status": "True",
+            "lastHeartbeatTime": now,
+            "lastTransitionTime": now,
+            "reason": "KubeletReady",
+            "message": "kubelet is posting ready status"
+        }]),
+    };
+    let resource_version = stored.map_or(1, |stored| stored.resource_version);
+
+    json!({
+        "apiVersion": "v1",
+        "kind": "Node",
+        "metadata": {
+            "name": name,
+            "uid": format!("node-uid-{}", index + 1),
+            "resourceVersion": resource_version.to_string(),
+            "creationTimestamp": now,
+            "labels": node.node_labels(name)
+        },
+        "spec": spec,
+        "status": {
+            "conditions": conditions,
+            "addresses": [
+                {
+                    "type": "InternalIP",
+                    "address": node.internal_ip
+                },
+                {
+                    "type": "Hostname",
+                    "address": name
+                }
+            ],
+            "capacity": allocatable,
+            "allocatable": allocatable,
+            "nodeInfo": {
+                "architecture": node.architecture,
+                "operatingSystem": "linux"
+            }
+        }
+    })
+}
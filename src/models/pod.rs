@@ -9,7 +9,9 @@ pub struct Pod {
     pub name: String,
     pub namespace: String,
     pub resource_version: i64,
+    #[serde(with = "super::time::rfc3339")]
     pub creation_timestamp: DateTime<Utc>,
+    #[serde(with = "super::time::rfc3339_option")]
     pub deletion_timestamp: Option<DateTime<Utc>>,
     pub labels: Option<Value>,
     pub annotations: Option<Value>,
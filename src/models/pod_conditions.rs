@@ -0,0 +1,95 @@
+// Pod status conditions. Conditions are keyed by type, so a status patch
+// listing a few of them updates those and leaves the rest alone, as with a
+// strategic merge patch on a real API server. That lets readiness gate
+// controllers write their own condition types next to the kubelet's, and the
+// Ready condition is only True when ContainersReady and every condition
+// named in spec.readinessGates are.
+use serde_json::{json, Value};
+
+use super::time;
+
+/// Merges `patch` conditions into `existing` by type. A condition whose
+/// status changes gets a new lastTransitionTime unless the patch sets one.
+pub fn merge(existing: &[Value], patch: &[Value]) -> Vec<Value> {
+    let mut conditions = existing.to_vec();
+    for update in patch {
+        let index = conditions.iter().position(|c| c["type"] == update["type"]);
+        let mut condition = index.map(|i| conditions[i].clone()).unwrap_or_else(|| json!({}));
+        let transitioned = condition["status"] != update["status"];
+        json_patch::merge(&mut condition, update);
+        if transitioned && update["lastTransitionTime"].is_null() {
+            condition["lastTransitionTime"] = json!(time::now());
+        }
+        match index {
+            Some(i) => conditions[i] = condition,
+            None => conditions.push(condition),
+        }
+    }
+    conditions
+}
+
+/// Sets the Ready condition of a pod with readiness gates from its
+/// ContainersReady and gate conditions. Returns whether it changed.
+pub fn apply_readiness_gates(spec: &Value, status: &mut Value) -> bool {
+    let Some(gates) = spec["readinessGates"].as_array().filter(|gates| !gates.is_empty()) else {
+        return false;
+    };
+    let Some(conditions) = status["conditions"].as_array_mut() else {
+        return false;
+    };
+    let status_of = |kind: &Value| {
+        conditions
+            .iter()
+            .find(|c| c["type"] == *kind)
+            .map(|c| c["status"].as_str().unwrap_or_default().to_string())
+    };
+
+    let containers_ready = status_of(&json!("ContainersReady")).as_deref() == Some("True");
+    // The first gate holding the pod back, described as the kubelet does
+    let unmet = gates.iter().find_map(|gate| {
+        let kind = &gate["conditionType"];
+        match status_of(kind) {
+            Some(status) if status == "True" => None,
+            Some(status) => Some(format!(
+                "the status of pod readiness gate {} is not \"True\", but {}",
+                kind, status
+            )),
+            None => Some(format!("corresponding condition of pod readiness gate {} does not exist.", kind)),
+        }
+    });
+
+    let Some(ready) = conditions.iter_mut().find(|c| c["type"] == "Ready") else {
+        return false;
+    };
+    let before = ready.clone();
+    match (containers_ready, unmet) {
+        (true, None) => {
+            if ready["status"] != "True" {
+                ready["status"] = json!("True");
+                ready["lastTransitionTime"] = json!(time::now());
+            }
+            if ready["reason"] == "ReadinessGatesNotReady" {
+                ready["reason"] = json!("ContainersReady");
+                ready["message"] = json!("All containers are ready");
+            }
+        }
+        (true, Some(message)) => {
+            if ready["status"] != "False" {
+                ready["status"] = json!("False");
+                ready["lastTransitionTime"] = json!(time::now());
+            }
+            ready["reason"] = json!("ReadinessGatesNotReady");
+            ready["message"] = json!(message);
+        }
+        // Not ready either way; the kubelet's reason says why
+        (false, _) => {
+            if ready["status"] == "True" {
+                ready["status"] = json!("False");
+                ready["lastTransitionTime"] = json!(time::now());
+                ready["reason"] = json!("ContainersNotReady");
+                ready["message"] = json!("Containers are not ready");
+            }
+        }
+    }
+    *ready != before
+}
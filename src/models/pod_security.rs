@@ -0,0 +1,469 @@
+// The Pod Security Standards, as the PodSecurity admission plugin checks
+// them. A namespace picks a level (privileged, baseline or restricted) for
+// each mode with its pod-security.kubernetes.io/<mode> label: pods that
+// violate the enforce level are refused, those that violate the warn level
+// get a warning and those that violate the audit level are logged. The
+// checks are always those of the latest standards; a <mode>-version label
+// only changes how the level is named in messages.
+use serde_json::Value;
+
+pub const LABEL_PREFIX: &str = "pod-security.kubernetes.io/";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Privileged,
+    Baseline,
+    Restricted,
+}
+
+impl Level {
+    fn parse(level: &str) -> Option<Self> {
+        match level {
+            "privileged" => Some(Self::Privileged),
+            "baseline" => Some(Self::Baseline),
+            "restricted" => Some(Self::Restricted),
+            _ => None,
+        }
+    }
+
+    fn as_str(self) -> &'static str {
+        match self {
+            Self::Privileged => "privileged",
+            Self::Baseline => "baseline",
+            Self::Restricted => "restricted",
+        }
+    }
+}
+
+/// A level and the version of the standards it's named with, as in
+/// "restricted:latest".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LevelVersion {
+    pub level: Level,
+    pub version: String,
+}
+
+impl std::fmt::Display for LevelVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.level.as_str(), self.version)
+    }
+}
+
+/// The levels a namespace's labels set for each mode.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Policy {
+    pub enforce: LevelVersion,
+    pub audit: LevelVersion,
+    pub warn: LevelVersion,
+}
+
+impl Policy {
+    /// Reads the policy from a namespace's labels. A mode without a label is
+    /// privileged; one with a level or version that isn't valid is
+    /// restricted:latest, so a typo never loosens it.
+    pub fn from_labels(labels: &Value) -> Self {
+        let mode = |mode: &str| {
+            let label = |suffix: &str| labels[format!("{}{}", LABEL_PREFIX, suffix)].as_str();
+            let version = label(&format!("{}-version", mode)).unwrap_or("latest");
+            let valid_version = version == "latest"
+                || version
+                    .strip_prefix("v1.")
+                    .is_some_and(|minor| !minor.is_empty() && minor.chars().all(|c| c.is_ascii_digit()));
+            match label(mode).map(Level::parse) {
+                None if valid_version => LevelVersion { level: Level::Privileged, version: version.to_string() },
+                Some(Some(level)) if valid_version => LevelVersion { level, version: version.to_string() },
+                _ => LevelVersion { level: Level::Restricted, version: "latest".to_string() },
+            }
+        };
+        Self { enforce: mode("enforce"), audit: mode("audit"), warn: mode("warn") }
+    }
+}
+
+/// One failed check: what's wrong, and with which parts of the pod.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub reason: String,
+    pub detail: String,
+}
+
+/// The violations as the plugin words them: `reason (detail), ...`.
+pub fn describe(violations: &[Violation]) -> String {
+    violations
+        .iter()
+        .map(|v| format!("{} ({})", v.reason, v.detail))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Why `pod` can't be created under `policy`, in the words of the Forbidden
+/// error, or None if it meets the enforce level.
+pub fn refusal(policy: &Policy, pod: &Value) -> Option<String> {
+    let violations = check(policy.enforce.level, &pod["metadata"], &pod["spec"]);
+    (!violations.is_empty()).then(|| {
+        format!(
+            "pods {:?} is forbidden: violates PodSecurity {:?}: {}",
+            pod["metadata"]["name"].as_str().unwrap_or_default(),
+            policy.enforce.to_string(),
+            describe(&violations)
+        )
+    })
+}
+
+// Capabilities baseline containers may add; restricted ones only the last
+const BASELINE_CAPABILITIES: &[&str] = &[
+    "AUDIT_WRITE", "CHOWN", "DAC_OVERRIDE", "FOWNER", "FSETID", "KILL", "MKNOD",
+    "NET_BIND_SERVICE", "SETFCAP", "SETGID", "SETPCAP", "SETUID", "SYS_CHROOT",
+];
+const RESTRICTED_CAPABILITIES: &[&str] = &["NET_BIND_SERVICE"];
+
+const SAFE_SYSCTLS: &[&str] = &[
+    "kernel.shm_rmid_forced",
+    "net.ipv4.ip_local_port_range",
+    "net.ipv4.ip_local_reserved_ports",
+    "net.ipv4.ip_unprivileged_port_start",
+    "net.ipv4.ping_group_range",
+    "net.ipv4.tcp_fin_timeout",
+    "net.ipv4.tcp_keepalive_intvl",
+    "net.ipv4.tcp_keepalive_probes",
+    "net.ipv4.tcp_keepalive_time",
+    "net.ipv4.tcp_syncookies",
+];
+
+const SELINUX_TYPES: &[&str] = &["", "container_t", "container_init_t", "container_kvm_t", "container_engine_t"];
+
+const RESTRICTED_VOLUME_TYPES: &[&str] = &[
+    "configMap", "csi", "downwardAPI", "emptyDir", "ephemeral", "persistentVolumeClaim", "projected", "secret",
+];
+
+/// What keeps a pod with `metadata` and `spec` from meeting `level`, in the
+/// order the plugin reports it. Empty when it does.
+pub fn check(level: Level, metadata: &Value, spec: &Value) -> Vec<Violation> {
+    if level == Level::Privileged {
+        return Vec::new();
+    }
+    let restricted = level == Level::Restricted;
+    let containers: Vec<&Value> = ["initContainers", "containers", "ephemeralContainers"]
+        .iter()
+        .flat_map(|list| spec[*list].as_array().into_iter().flatten())
+        .collect();
+    let pod_context = &spec["securityContext"];
+    let named = |test: &dyn Fn(&Value) -> bool| -> Vec<&str> {
+        containers
+            .iter()
+            .filter(|c| test(c))
+            .filter_map(|c| c["name"].as_str())
+            .collect()
+    };
+    let mut violations = Vec::new();
+    let mut add = |reason: &str, details: Vec<String>| {
+        if !details.is_empty() {
+            violations.push(Violation { reason: reason.to_string(), detail: details.join("; ") });
+        }
+    };
+
+    if restricted {
+        let escalating = named(&|c| c["securityContext"]["allowPrivilegeEscalation"] != false);
+        add(
+            "allowPrivilegeEscalation != false",
+            when(!escalating.is_empty(), || {
+                format!("{} must set securityContext.allowPrivilegeEscalation=false", containers_phrase(&escalating))
+            }),
+        );
+    }
+
+    // AppArmor, by field or by the older annotations
+    let mut apparmor = Vec::new();
+    if pod_context["appArmorProfile"]["type"] == "Unconfined" {
+        apparmor.push("pod must not set securityContext.appArmorProfile.type to \"Unconfined\"".to_string());
+    }
+    let unconfined = named(&|c| c["securityContext"]["appArmorProfile"]["type"] == "Unconfined");
+    if !unconfined.is_empty() {
+        apparmor.push(format!(
+            "{} must not set securityContext.appArmorProfile.type to \"Unconfined\"",
+            containers_phrase(&unconfined)
+        ));
+    }
+    for (key, value) in metadata["annotations"].as_object().into_iter().flatten() {
+        let Some(profile) = value.as_str() else { continue };
+        if key.starts_with("container.apparmor.security.beta.kubernetes.io/")
+            && profile != "runtime/default"
+            && !profile.starts_with("localhost/")
+        {
+            apparmor.push(format!("annotation {}={:?}", key, profile));
+        }
+    }
+    let reason = if apparmor.len() > 1 { "forbidden AppArmor profiles" } else { "forbidden AppArmor profile" };
+    add(reason, apparmor);
+
+    let added = |c: &Value| -> Vec<String> {
+        c["securityContext"]["capabilities"]["add"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(str::to_string)
+            .collect()
+    };
+    let forbidden_added = |allowed: &[&str]| {
+        let adding = named(&|c| added(c).iter().any(|cap| !allowed.contains(&cap.as_str())));
+        let mut capabilities: Vec<String> = containers
+            .iter()
+            .flat_map(|c| added(c))
+            .filter(|cap| !allowed.contains(&cap.as_str()))
+            .collect();
+        capabilities.sort();
+        capabilities.dedup();
+        when(!adding.is_empty(), || {
+            format!(
+                "{} must not include {} in securityContext.capabilities.add",
+                containers_phrase(&adding),
+                quoted(&capabilities)
+            )
+        })
+    };
+    if restricted {
+        let not_dropping = named(&|c| {
+            !c["securityContext"]["capabilities"]["drop"]
+                .as_array()
+                .is_some_and(|drop| drop.iter().any(|cap| cap == "ALL"))
+        });
+        let mut details = when(!not_dropping.is_empty(), || {
+            format!("{} must set securityContext.capabilities.drop=[\"ALL\"]", containers_phrase(&not_dropping))
+        });
+        details.extend(forbidden_added(RESTRICTED_CAPABILITIES));
+        add("unrestricted capabilities", details);
+    } else {
+        add("non-default capabilities", forbidden_added(BASELINE_CAPABILITIES));
+    }
+
+    let host_namespaces: Vec<String> = ["hostNetwork", "hostPID", "hostIPC"]
+        .iter()
+        .filter(|field| spec[**field] == true)
+        .map(|field| format!("{}=true", field))
+        .collect();
+    add("host namespaces", when(!host_namespaces.is_empty(), || host_namespaces.join(", ")));
+
+    let volumes: Vec<&Value> = spec["volumes"].as_array().into_iter().flatten().collect();
+    let host_paths: Vec<&str> = volumes
+        .iter()
+        .filter(|v| v.get("hostPath").is_some())
+        .filter_map(|v| v["name"].as_str())
+        .collect();
+    add(
+        "hostPath volumes",
+        when(!host_paths.is_empty(), || format!("{} {}", plural(host_paths.len(), "volume", "volumes"), quoted(&host_paths))),
+    );
+
+    let host_ports = |c: &Value| -> Vec<i64> {
+        c["ports"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|p| p["hostPort"].as_i64())
+            .filter(|port| *port != 0)
+            .collect()
+    };
+    let exposing = named(&|c| !host_ports(c).is_empty());
+    let ports: Vec<String> = containers.iter().flat_map(|c| host_ports(c)).map(|p| p.to_string()).collect();
+    add(
+        "hostPort",
+        when(!exposing.is_empty(), || {
+            format!(
+                "{} {} {} {}",
+                containers_phrase(&exposing),
+                plural(exposing.len(), "uses", "use"),
+                plural(ports.len(), "hostPort", "hostPorts"),
+                ports.join(", ")
+            )
+        }),
+    );
+
+    let mut host_process = Vec::new();
+    if pod_context["windowsOptions"]["hostProcess"] == true {
+        host_process.push("pod must not set securityContext.windowsOptions.hostProcess=true".to_string());
+    }
+    let processes = named(&|c| c["securityContext"]["windowsOptions"]["hostProcess"] == true);
+    if !processes.is_empty() {
+        host_process.push(format!(
+            "{} must not set securityContext.windowsOptions.hostProcess=true",
+            containers_phrase(&processes)
+        ));
+    }
+    add("hostProcess", host_process);
+
+    let privileged = named(&|c| c["securityContext"]["privileged"] == true);
+    add(
+        "privileged",
+        when(!privileged.is_empty(), || {
+            format!("{} must not set securityContext.privileged=true", containers_phrase(&privileged))
+        }),
+    );
+
+    let proc_mount = |c: &Value| c["securityContext"]["procMount"].as_str().filter(|m| *m != "Default").map(str::to_string);
+    let unmasked = named(&|c| proc_mount(c).is_some());
+    let mut mounts: Vec<String> = containers.iter().filter_map(|c| proc_mount(c)).collect();
+    mounts.dedup();
+    add(
+        "procMount",
+        when(!unmasked.is_empty(), || {
+            format!("{} must not set securityContext.procMount to {}", containers_phrase(&unmasked), quoted(&mounts))
+        }),
+    );
+
+    if restricted {
+        let restricted_types: Vec<(&str, &str)> = volumes
+            .iter()
+            .filter_map(|v| {
+                let kind = v.as_object()?.keys().find(|key| *key != "name")?;
+                (!RESTRICTED_VOLUME_TYPES.contains(&kind.as_str())).then(|| (v["name"].as_str().unwrap_or_default(), kind.as_str()))
+            })
+            .collect();
+        let names: Vec<&str> = restricted_types.iter().map(|(name, _)| *name).collect();
+        let mut kinds: Vec<&str> = restricted_types.iter().map(|(_, kind)| *kind).collect();
+        kinds.sort();
+        kinds.dedup();
+        add(
+            "restricted volume types",
+            when(!names.is_empty(), || {
+                format!(
+                    "{} {} {} {}",
+                    plural(names.len(), "volume", "volumes"),
+                    quoted(&names),
+                    plural(names.len(), "uses restricted volume type", "use restricted volume types"),
+                    quoted(&kinds)
+                )
+            }),
+        );
+
+        let mut non_root = Vec::new();
+        let pod_non_root = &pod_context["runAsNonRoot"];
+        if *pod_non_root == false {
+            non_root.push("pod must not set securityContext.runAsNonRoot=false".to_string());
+        }
+        let as_root = named(&|c| c["securityContext"]["runAsNonRoot"] == false);
+        if !as_root.is_empty() {
+            non_root.push(format!("{} must not set securityContext.runAsNonRoot=false", containers_phrase(&as_root)));
+        }
+        if *pod_non_root != true {
+            let unset = named(&|c| !c["securityContext"]["runAsNonRoot"].is_boolean());
+            if !unset.is_empty() {
+                non_root.push(format!("pod or {} must set securityContext.runAsNonRoot=true", containers_phrase(&unset)));
+            }
+        }
+        add("runAsNonRoot != true", non_root);
+
+        let mut root_user = Vec::new();
+        if pod_context["runAsUser"] == 0 {
+            root_user.push("pod must not set runAsUser=0".to_string());
+        }
+        let root = named(&|c| c["securityContext"]["runAsUser"] == 0);
+        if !root.is_empty() {
+            root_user.push(format!("{} must not set runAsUser=0", containers_phrase(&root)));
+        }
+        add("runAsUser=0", root_user);
+    }
+
+    let selinux = |context: &Value| -> Vec<String> {
+        let options = &context["seLinuxOptions"];
+        let mut forbidden = Vec::new();
+        if let Some(kind) = options["type"].as_str().filter(|t| !SELINUX_TYPES.contains(t)) {
+            forbidden.push(format!("type {:?}", kind));
+        }
+        for field in ["user", "role"] {
+            if let Some(value) = options[field].as_str().filter(|v| !v.is_empty()) {
+                forbidden.push(format!("{} {:?}", field, value));
+            }
+        }
+        forbidden
+    };
+    let mut selinux_details = Vec::new();
+    let pod_selinux = selinux(pod_context);
+    if !pod_selinux.is_empty() {
+        selinux_details.push(format!("pod set forbidden securityContext.seLinuxOptions: {}", pod_selinux.join(", ")));
+    }
+    for container in &containers {
+        let forbidden = selinux(&container["securityContext"]);
+        if !forbidden.is_empty() {
+            selinux_details.push(format!(
+                "container {:?} set forbidden securityContext.seLinuxOptions: {}",
+                container["name"].as_str().unwrap_or_default(),
+                forbidden.join(", ")
+            ));
+        }
+    }
+    add("seLinuxOptions", selinux_details);
+
+    let seccomp = |context: &Value| context["seccompProfile"]["type"].as_str().map(str::to_string);
+    let mut seccomp_details = Vec::new();
+    if restricted {
+        let allowed = |kind: &str| kind == "RuntimeDefault" || kind == "Localhost";
+        let pod_seccomp = seccomp(pod_context);
+        if let Some(kind) = pod_seccomp.as_deref().filter(|kind| !allowed(kind)) {
+            seccomp_details.push(format!("pod must not set securityContext.seccompProfile.type to {:?}", kind));
+        }
+        let disallowed = named(&|c| seccomp(&c["securityContext"]).is_some_and(|kind| !allowed(&kind)));
+        if !disallowed.is_empty() {
+            let mut kinds: Vec<String> = containers
+                .iter()
+                .filter_map(|c| seccomp(&c["securityContext"]))
+                .filter(|kind| !allowed(kind))
+                .collect();
+            kinds.sort();
+            kinds.dedup();
+            seccomp_details.push(format!(
+                "{} must not set securityContext.seccompProfile.type to {}",
+                containers_phrase(&disallowed),
+                quoted(&kinds)
+            ));
+        }
+        if !pod_seccomp.as_deref().is_some_and(allowed) {
+            let unset = named(&|c| seccomp(&c["securityContext"]).is_none());
+            if !unset.is_empty() {
+                seccomp_details.push(format!(
+                    "pod or {} must set securityContext.seccompProfile.type to \"RuntimeDefault\" or \"Localhost\"",
+                    containers_phrase(&unset)
+                ));
+            }
+        }
+    } else {
+        if seccomp(pod_context).as_deref() == Some("Unconfined") {
+            seccomp_details.push("pod must not set securityContext.seccompProfile.type to \"Unconfined\"".to_string());
+        }
+        let unconfined = named(&|c| seccomp(&c["securityContext"]).as_deref() == Some("Unconfined"));
+        if !unconfined.is_empty() {
+            seccomp_details.push(format!(
+                "{} must not set securityContext.seccompProfile.type to \"Unconfined\"",
+                containers_phrase(&unconfined)
+            ));
+        }
+    }
+    add("seccompProfile", seccomp_details);
+
+    let sysctls: Vec<&str> = pod_context["sysctls"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|s| s["name"].as_str())
+        .filter(|name| !SAFE_SYSCTLS.contains(name))
+        .collect();
+    add("forbidden sysctls", when(!sysctls.is_empty(), || sysctls.join(", ")));
+
+    violations
+}
+
+// `container "a"` or `containers "a", "b"`
+fn containers_phrase(names: &[&str]) -> String {
+    format!("{} {}", plural(names.len(), "container", "containers"), quoted(names))
+}
+
+fn quoted<S: AsRef<str>>(values: &[S]) -> String {
+    values.iter().map(|v| format!("{:?}", v.as_ref())).collect::<Vec<_>>().join(", ")
+}
+
+fn plural<'a>(count: usize, one: &'a str, many: &'a str) -> &'a str {
+    if count == 1 { one } else { many }
+}
+
+fn when(condition: bool, detail: impl FnOnce() -> String) -> Vec<String> {
+    if condition { vec![detail()] } else { Vec::new() }
+}
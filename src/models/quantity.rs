@@ -0,0 +1,138 @@
+// Parsing for Kubernetes resource quantities ("500m", "1.5", "128Mi", "1e3")
+// and pod-level resource requests built on top of it.
+use std::ops::{Add, Sub};
+
+use serde_json::Value;
+
+const SUFFIXES: &[(&str, f64)] = &[
+    ("Ki", 1024.0),
+    ("Mi", 1024.0 * 1024.0),
+    ("Gi", 1024.0 * 1024.0 * 1024.0),
+    ("Ti", 1024.0 * 1024.0 * 1024.0 * 1024.0),
+    ("Pi", 1024.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0),
+    ("Ei", 1024.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0 * 1024.0),
+    ("n", 1e-9),
+    ("u", 1e-6),
+    ("m", 1e-3),
+    ("k", 1e3),
+    ("M", 1e6),
+    ("G", 1e9),
+    ("T", 1e12),
+    ("P", 1e15),
+    ("E", 1e18),
+];
+
+/// Parses a quantity string into its plain numeric value, or `None` if it
+/// isn't a valid quantity.
+pub fn parse(quantity: &str) -> Option<f64> {
+    let quantity = quantity.trim();
+    if quantity.is_empty() {
+        return None;
+    }
+
+    for (suffix, multiplier) in SUFFIXES {
+        if let Some(number) = quantity.strip_suffix(suffix) {
+            return parse_number(number).map(|n| n * multiplier);
+        }
+    }
+
+    parse_number(quantity)
+}
+
+fn parse_number(number: &str) -> Option<f64> {
+    if number.is_empty() || number.starts_with(['e', 'E']) {
+        return None;
+    }
+    number.parse::<f64>().ok().filter(|n| n.is_finite())
+}
+
+/// Parses a quantity given either as a JSON string or a bare JSON number.
+pub fn parse_value(value: &Value) -> Option<f64> {
+    match value {
+        Value::String(s) => parse(s),
+        Value::Number(n) => n.as_f64(),
+        _ => None,
+    }
+}
+
+/// CPU in millicores, rounded up like the API server does.
+pub fn cpu_millis(value: &Value) -> Option<i64> {
+    parse_value(value).map(|cores| (cores * 1000.0).ceil() as i64)
+}
+
+/// Memory (or any byte-sized resource) in bytes, rounded up.
+pub fn bytes(value: &Value) -> Option<i64> {
+    parse_value(value).map(|b| b.ceil() as i64)
+}
+
+/// The CPU and memory a pod asks the scheduler for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Resources {
+    pub cpu_millis: i64,
+    pub memory_bytes: i64,
+}
+
+impl Resources {
+    /// Effective requests of a pod spec: the sum over its containers, or the
+    /// largest init container if that is bigger. A container without requests
+    /// falls back to its limits, matching the API server's defaulting.
+    pub fn requests(spec: &Value) -> Self {
+        let container = |c: &Value| {
+            let resources = &c["resources"];
+            let get = |name: &str| {
+                let request = &resources["requests"][name];
+                if request.is_null() {
+                    &resources["limits"][name]
+                } else {
+                    request
+                }
+            };
+            Resources {
+                cpu_millis: cpu_millis(get("cpu")).unwrap_or(0),
+                memory_bytes: bytes(get("memory")).unwrap_or(0),
+            }
+        };
+
+        let mut total = spec["containers"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(container)
+            .fold(Resources::default(), |sum, c| sum + c);
+
+        for c in spec["initContainers"].as_array().into_iter().flatten() {
+            let init = container(c);
+            total.cpu_millis = total.cpu_millis.max(init.cpu_millis);
+            total.memory_bytes = total.memory_bytes.max(init.memory_bytes);
+        }
+
+        total
+    }
+
+    /// Whether `self` fits within `capacity` in every dimension.
+    pub fn fits_in(&self, capacity: &Resources) -> bool {
+        self.cpu_millis <= capacity.cpu_millis && self.memory_bytes <= capacity.memory_bytes
+    }
+}
+
+impl Add for Resources {
+    type Output = Resources;
+
+    fn add(self, other: Resources) -> Resources {
+        Resources {
+            cpu_millis: self.cpu_millis + other.cpu_millis,
+            memory_bytes: self.memory_bytes + other.memory_bytes,
+        }
+    }
+}
+
+impl Sub for Resources {
+    type Output = Resources;
+
+    fn sub(self, other: Resources) -> Resources {
+        Resources {
+            cpu_millis: self.cpu_millis - other.cpu_millis,
+            memory_bytes: self.memory_bytes - other.memory_bytes,
+        }
+    }
+}
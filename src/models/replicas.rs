@@ -0,0 +1,47 @@
+// spec.replicas of Deployments, ReplicaSets and StatefulSets is optional and
+// defaults to 1, as in the apps/v1 API. An explicit 0 is kept: it means the
+// workload is scaled down, not that the field is missing.
+use serde_json::{json, Value};
+
+pub const DEFAULT_REPLICAS: i64 = 1;
+
+/// The desired number of replicas of a workload spec.
+pub fn desired(spec: &Value) -> i64 {
+    spec["replicas"].as_i64().unwrap_or(DEFAULT_REPLICAS)
+}
+
+/// Fills in spec.replicas when it is absent.
+pub fn set_default(spec: &mut Value) {
+    if spec["replicas"].is_null() {
+        spec["replicas"] = json!(DEFAULT_REPLICAS);
+    }
+}
+
+/// The replica count of a Scale written to a scale subresource. It has to be
+/// given and can't be negative.
+pub fn requested(scale: &Value) -> Option<i64> {
+    scale["spec"]["replicas"].as_i64().filter(|&replicas| replicas >= 0)
+}
+
+/// The autoscaling/v1 Scale of a workload.
+pub fn scale(workload: &Value) -> Value {
+    let metadata = &workload["metadata"];
+    json!({
+        "apiVersion": "autoscaling/v1",
+        "kind": "Scale",
+        "metadata": {
+            "name": metadata["name"],
+            "namespace": metadata["namespace"],
+            "uid": metadata["uid"],
+            "resourceVersion": metadata["resourceVersion"],
+            "creationTimestamp": metadata["creationTimestamp"]
+        },
+        "spec": {
+            "replicas": desired(&workload["spec"])
+        },
+        "status": {
+            "replicas": workload["status"]["replicas"].as_i64().unwrap_or(0),
+            "selector": workload["spec"]["selector"]
+        }
+    })
+}
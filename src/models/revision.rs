@@ -0,0 +1,67 @@
+// Rollout history. Deployments keep theirs in their old ReplicaSets, numbered
+// by the deployment.kubernetes.io/revision annotation; StatefulSets and
+// DaemonSets in ControllerRevisions. Either way a revision is found again by
+// the hash of its pod template, so rolling back to an old template revives
+// its revision instead of starting a new one.
+use serde_json::Value;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+/// The revision of a Deployment's ReplicaSet, and of the Deployment itself.
+pub const REVISION_ANNOTATION: &str = "deployment.kubernetes.io/revision";
+
+/// Tells the pods of a Deployment's ReplicaSets apart.
+pub const POD_TEMPLATE_HASH_LABEL: &str = "pod-template-hash";
+
+/// The template hash of a ControllerRevision.
+pub const CONTROLLER_REVISION_HASH_LABEL: &str = "controller.kubernetes.io/hash";
+
+// Upstream's default for spec.revisionHistoryLimit
+const DEFAULT_HISTORY_LIMIT: i64 = 10;
+
+/// How many old revisions a workload spec keeps.
+pub fn history_limit(spec: &Value) -> usize {
+    spec["revisionHistoryLimit"].as_i64().unwrap_or(DEFAULT_HISTORY_LIMIT).max(0) as usize
+}
+
+/// The hash of a pod template, in hex. The pod-template-hash label and
+/// empty fields are left out, so a template read back from a ReplicaSet or
+/// round-tripped through kubectl hashes as the original did.
+pub fn template_hash(template: &Value) -> String {
+    let mut template = template.clone();
+    if let Some(labels) = template["metadata"]["labels"].as_object_mut() {
+        labels.remove(POD_TEMPLATE_HASH_LABEL);
+    }
+    let mut hasher = DefaultHasher::new();
+    without_empty(&template).to_string().hash(&mut hasher);
+    format!("{:x}", hasher.finish() % 0xfffffff)
+}
+
+/// The revision number in an object's annotations, 0 if it has none.
+pub fn of(annotations: &Value) -> i64 {
+    annotations[REVISION_ANNOTATION].as_str().and_then(|revision| revision.parse().ok()).unwrap_or(0)
+}
+
+// The value with nulls, empty maps and empty lists dropped from its maps
+fn without_empty(value: &Value) -> Value {
+    match value {
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), without_empty(value)))
+                .filter(|(_, value)| !is_empty(value))
+                .collect(),
+        ),
+        Value::Array(items) => Value::Array(items.iter().map(without_empty).collect()),
+        value => value.clone(),
+    }
+}
+
+fn is_empty(value: &Value) -> bool {
+    match value {
+        Value::Null => true,
+        Value::Object(fields) => fields.is_empty(),
+        Value::Array(items) => items.is_empty(),
+        _ => false,
+    }
+}
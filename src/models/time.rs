@@ -0,0 +1,64 @@
+// Timestamps as the Kubernetes API writes them: RFC 3339 in UTC with second
+// precision, e.g. 2024-05-01T12:30:00Z. Everything krust stores and serves
+// uses this format, so timestamps also sort correctly as strings.
+use chrono::{DateTime, NaiveDateTime, SecondsFormat, Utc};
+use serde::{Deserialize, Deserializer, Serializer};
+
+/// The current time.
+pub fn now() -> String {
+    format(Utc::now())
+}
+
+/// The current time with microsecond precision, as MicroTime fields such
+/// as a Lease's renewTime are written.
+pub fn now_micro() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true)
+}
+
+pub fn format(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+/// Parses RFC 3339 with any offset, or the `YYYY-MM-DD HH:MM:SS` of SQLite's
+/// CURRENT_TIMESTAMP, which is in UTC.
+pub fn parse(value: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(value)
+        .map(|time| time.with_timezone(&Utc))
+        .or_else(|_| NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S%.f").map(|time| time.and_utc()))
+        .ok()
+}
+
+/// serde `with` module for `DateTime<Utc>` fields.
+pub mod rfc3339 {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(time: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&format(*time))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        parse(&value).ok_or_else(|| serde::de::Error::custom(format!("invalid timestamp {}", value)))
+    }
+}
+
+/// serde `with` module for `Option<DateTime<Utc>>` fields.
+pub mod rfc3339_option {
+    use super::*;
+
+    pub fn serialize<S: Serializer>(time: &Option<DateTime<Utc>>, serializer: S) -> Result<S::Ok, S::Error> {
+        match time {
+            Some(time) => super::rfc3339::serialize(time, serializer),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<DateTime<Utc>>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(value) => parse(&value)
+                .map(Some)
+                .ok_or_else(|| serde::de::Error::custom(format!("invalid timestamp {}", value))),
+            None => Ok(None),
+        }
+    }
+}
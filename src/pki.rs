@@ -0,0 +1,167 @@
+// The cluster CA and the certificates it issues. The CA is generated the
+// first time krust starts and kept in the data directory as pki/ca.crt and
+// pki/ca.key, so everything it signed, and the bundle workloads read from
+// the kube-root-ca.crt ConfigMap, stays valid across restarts. It signs the
+// API server's serving certificate and the client certificate of the
+// generated kubeconfig.
+use anyhow::{bail, Context, Result};
+use openssl::asn1::Asn1Time;
+use openssl::bn::{BigNum, MsbOption};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::hash::MessageDigest;
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::x509::extension::{
+    AuthorityKeyIdentifier, BasicConstraints, ExtendedKeyUsage, KeyUsage, SubjectAlternativeName,
+    SubjectKeyIdentifier,
+};
+use openssl::x509::{X509Builder, X509Name, X509NameBuilder, X509};
+use tracing::info;
+
+use crate::data_dir::DataDir;
+
+/// What an issued certificate is for.
+pub enum Usage<'a> {
+    /// Serving TLS under the given DNS names and IP addresses
+    Server(&'a [&'a str]),
+    /// Authenticating a client
+    Client,
+}
+
+pub struct ClusterCa {
+    cert: X509,
+    key: PKey<Private>,
+}
+
+impl ClusterCa {
+    /// The CA in the data directory, generated and written there with its
+    /// key the first time. Without a data directory it's a new one each time.
+    pub fn load(data_dir: Option<&DataDir>) -> Result<Self> {
+        let Some(data_dir) = data_dir else {
+            return Self::generate();
+        };
+        let pki = data_dir.pki();
+        let cert_path = pki.join("ca.crt");
+        let key_path = pki.join("ca.key");
+        if cert_path.exists() {
+            let read = |path: &std::path::Path| {
+                std::fs::read(path).with_context(|| format!("failed to read {}", path.display()))
+            };
+            return Ok(Self {
+                cert: X509::from_pem(&read(&cert_path)?)?,
+                key: PKey::private_key_from_pem(&read(&key_path)?)?,
+            });
+        }
+
+        let ca = Self::generate()?;
+        std::fs::create_dir_all(&pki).with_context(|| format!("failed to create {}", pki.display()))?;
+        write_private(&key_path, &ca.key.private_key_to_pem_pkcs8()?)?;
+        std::fs::write(&cert_path, ca.cert.to_pem()?)
+            .with_context(|| format!("failed to write {}", cert_path.display()))?;
+        info!("Generated cluster CA in {}", pki.display());
+        Ok(ca)
+    }
+
+    /// A fresh self-signed CA, valid for ten years.
+    pub fn generate() -> Result<Self> {
+        let key = new_key()?;
+        let name = name("krust-ca", &[])?;
+
+        let mut cert = builder(&name, &key, 3650)?;
+        cert.set_issuer_name(&name)?;
+        cert.append_extension(BasicConstraints::new().critical().ca().build()?)?;
+        cert.append_extension(KeyUsage::new().critical().digital_signature().key_cert_sign().crl_sign().build()?)?;
+        let key_id = SubjectKeyIdentifier::new().build(&cert.x509v3_context(None, None))?;
+        cert.append_extension(key_id)?;
+        cert.sign(&key, MessageDigest::sha256())?;
+
+        Ok(Self { cert: cert.build(), key })
+    }
+
+    pub fn cert_pem(&self) -> Result<String> {
+        Ok(String::from_utf8(self.cert.to_pem()?)?)
+    }
+
+    /// A certificate and key, as PEM, for `common_name` in the groups
+    /// `organizations`, valid for a year. Client certificates authenticate
+    /// as that user and those groups.
+    pub fn issue(&self, common_name: &str, organizations: &[&str], usage: Usage) -> Result<(String, String)> {
+        let key = new_key()?;
+        let mut cert = builder(&name(common_name, organizations)?, &key, 365)?;
+        cert.set_issuer_name(self.cert.subject_name())?;
+        cert.append_extension(BasicConstraints::new().critical().build()?)?;
+        cert.append_extension(KeyUsage::new().critical().digital_signature().key_encipherment().build()?)?;
+        match usage {
+            Usage::Server(names) => {
+                cert.append_extension(ExtendedKeyUsage::new().server_auth().build()?)?;
+                let mut alt_names = SubjectAlternativeName::new();
+                for name in names {
+                    match name.parse::<std::net::IpAddr>() {
+                        Ok(_) => alt_names.ip(name),
+                        Err(_) => alt_names.dns(name),
+                    };
+                }
+                let alt_names = alt_names.build(&cert.x509v3_context(Some(&self.cert), None))?;
+                cert.append_extension(alt_names)?;
+            }
+            Usage::Client => {
+                cert.append_extension(ExtendedKeyUsage::new().client_auth().build()?)?;
+            }
+        }
+        let authority = AuthorityKeyIdentifier::new().keyid(false).build(&cert.x509v3_context(Some(&self.cert), None))?;
+        cert.append_extension(authority)?;
+        cert.sign(&self.key, MessageDigest::sha256())?;
+
+        Ok((
+            String::from_utf8(cert.build().to_pem()?)?,
+            String::from_utf8(key.private_key_to_pem_pkcs8()?)?,
+        ))
+    }
+}
+
+/// Writes a file only its owner may read, for keys.
+pub fn write_private(path: &std::path::Path, contents: &[u8]) -> Result<()> {
+    std::fs::write(path, contents).with_context(|| format!("failed to write {}", path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    Ok(())
+}
+
+fn new_key() -> Result<PKey<Private>> {
+    let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1)?;
+    Ok(PKey::from_ec_key(EcKey::generate(&group)?)?)
+}
+
+fn name(common_name: &str, organizations: &[&str]) -> Result<X509Name> {
+    if common_name.is_empty() {
+        bail!("a certificate needs a common name");
+    }
+    let mut name = X509NameBuilder::new()?;
+    for organization in organizations {
+        name.append_entry_by_text("O", organization)?;
+    }
+    name.append_entry_by_text("CN", common_name)?;
+    Ok(name.build())
+}
+
+// A certificate for `key` named `subject`, valid from now for `days`, with
+// a random serial number
+fn builder(subject: &X509Name, key: &PKey<Private>, days: u32) -> Result<X509Builder> {
+    let mut serial = BigNum::new()?;
+    serial.rand(127, MsbOption::MAYBE_ZERO, false)?;
+    let serial = serial.to_asn1_integer()?;
+    let not_before = Asn1Time::days_from_now(0)?;
+    let not_after = Asn1Time::days_from_now(days)?;
+
+    let mut cert = X509Builder::new()?;
+    cert.set_version(2)?;
+    cert.set_serial_number(&serial)?;
+    cert.set_subject_name(subject)?;
+    cert.set_pubkey(key)?;
+    cert.set_not_before(&not_before)?;
+    cert.set_not_after(&not_after)?;
+    Ok(cert)
+}
@@ -0,0 +1,129 @@
+// What krust spends its time on, for the /debug/pprof endpoints. Every
+// background loop (controllers, scheduler, kubelets) records how long each
+// pass took, so a loop spinning too often or doing too much shows up by
+// name. CPU and memory figures come from /proc, so they're only there on
+// Linux.
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+// Clock ticks per second of the CPU times in /proc, USER_HZ, which Linux
+// fixes at 100 on every architecture krust runs on
+const CLOCK_TICKS_PER_SECOND: f64 = 100.0;
+
+/// How often a background loop has run and how long its passes took.
+#[derive(Clone, Debug, Default)]
+pub struct LoopStats {
+    pub iterations: u64,
+    pub busy: Duration,
+    pub last: Duration,
+    pub max: Duration,
+    pub last_finished: Option<Instant>,
+}
+
+static LOOPS: OnceLock<Mutex<BTreeMap<String, LoopStats>>> = OnceLock::new();
+
+fn loops_mut() -> std::sync::MutexGuard<'static, BTreeMap<String, LoopStats>> {
+    LOOPS.get_or_init(Default::default).lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Records a pass of the named loop that began at `started` and has just
+/// finished.
+pub fn record(name: &str, started: Instant) {
+    let elapsed = started.elapsed();
+    let mut loops = loops_mut();
+    let stats = match loops.get_mut(name) {
+        Some(stats) => stats,
+        None => loops.entry(name.to_string()).or_default(),
+    };
+    stats.iterations += 1;
+    stats.busy += elapsed;
+    stats.last = elapsed;
+    stats.max = stats.max.max(elapsed);
+    stats.last_finished = Some(Instant::now());
+}
+
+/// Every loop that has run, by name.
+pub fn loops() -> BTreeMap<String, LoopStats> {
+    loops_mut().clone()
+}
+
+/// CPU time a thread of the process has used.
+#[derive(Clone, Debug, Serialize)]
+pub struct ThreadTime {
+    pub id: u32,
+    pub name: String,
+    #[serde(rename = "cpuSeconds")]
+    pub cpu_seconds: f64,
+}
+
+/// CPU time used so far by each of the process's threads, from
+/// /proc/self/task. Empty where there's no /proc.
+pub fn threads() -> Vec<ThreadTime> {
+    let Ok(tasks) = std::fs::read_dir("/proc/self/task") else {
+        return Vec::new();
+    };
+    let mut threads: Vec<ThreadTime> = tasks
+        .flatten()
+        .filter_map(|task| {
+            let id = task.file_name().to_string_lossy().parse().ok()?;
+            let stat = std::fs::read_to_string(task.path().join("stat")).ok()?;
+            let (name, cpu_seconds) = parse_stat(&stat)?;
+            Some(ThreadTime { id, name, cpu_seconds })
+        })
+        .collect();
+    threads.sort_by_key(|thread| thread.id);
+    threads
+}
+
+/// CPU time the whole process has used so far, if /proc has it.
+pub fn process_cpu_seconds() -> Option<f64> {
+    parse_stat(&std::fs::read_to_string("/proc/self/stat").ok()?).map(|(_, seconds)| seconds)
+}
+
+// The name and user plus system CPU seconds in a /proc stat line. The name
+// is in parentheses and may hold spaces, so fields are counted after it.
+fn parse_stat(stat: &str) -> Option<(String, f64)> {
+    let (open, close) = (stat.find('(')?, stat.rfind(')')?);
+    let name = stat[open + 1..close].to_string();
+    let fields: Vec<&str> = stat[close + 1..].split_whitespace().collect();
+    // utime and stime, the 14th and 15th fields counting pid and name
+    let utime: f64 = fields.get(11)?.parse().ok()?;
+    let stime: f64 = fields.get(12)?.parse().ok()?;
+    Some((name, (utime + stime) / CLOCK_TICKS_PER_SECOND))
+}
+
+/// The process's memory use, from /proc/self/status.
+#[derive(Clone, Debug, Default, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MemoryStats {
+    pub resident_bytes: u64,
+    pub peak_resident_bytes: u64,
+    pub virtual_bytes: u64,
+    pub anonymous_bytes: u64,
+    pub threads: u64,
+}
+
+/// Memory the process uses, or None where there's no /proc.
+pub fn memory() -> Option<MemoryStats> {
+    let status = std::fs::read_to_string("/proc/self/status").ok()?;
+    let mut stats = MemoryStats::default();
+    for line in status.lines() {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let mut words = value.split_whitespace();
+        let number: u64 = words.next().and_then(|n| n.parse().ok()).unwrap_or(0);
+        let bytes = if words.next() == Some("kB") { number * 1024 } else { number };
+        match key {
+            "VmRSS" => stats.resident_bytes = bytes,
+            "VmHWM" => stats.peak_resident_bytes = bytes,
+            "VmSize" => stats.virtual_bytes = bytes,
+            "RssAnon" => stats.anonymous_bytes = bytes,
+            "Threads" => stats.threads = bytes,
+            _ => {}
+        }
+    }
+    Some(stats)
+}
@@ -1,8 +1,52 @@
 // Cgroups v2 resource management for containers
+use serde_json::Value;
 use std::fs;
 use std::path::{Path, PathBuf};
 use tracing::{debug, error, info};
 
+use crate::models::quantity;
+
+// The shares of a container requesting no CPU, and of one whole CPU
+const MIN_SHARES: i64 = 2;
+const SHARES_PER_CPU: i64 = 1024;
+
+/// The cgroup settings Docker confines a container to, from its resources
+/// as the kubelet translates them: limits.cpu becomes a CPU quota, limits.memory
+/// a memory limit with no swap on top, and requests.cpu (or limits.cpu when
+/// it requests none) the container's CPU shares.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContainerLimits {
+    /// CPU quota in billionths of a CPU, Docker's NanoCpus.
+    pub nano_cpus: Option<i64>,
+    /// Memory limit in bytes.
+    pub memory: Option<i64>,
+    pub cpu_shares: i64,
+}
+
+impl ContainerLimits {
+    pub fn from_container(container: &Value) -> Self {
+        let resources = &container["resources"];
+        let cpu_limit = quantity::cpu_millis(&resources["limits"]["cpu"]).filter(|m| *m > 0);
+        let cpu_request = quantity::cpu_millis(&resources["requests"]["cpu"]).or(cpu_limit).unwrap_or(0);
+        Self {
+            nano_cpus: cpu_limit.map(|millis| millis * 1_000_000),
+            memory: quantity::bytes(&resources["limits"]["memory"]).filter(|b| *b > 0),
+            cpu_shares: (cpu_request * SHARES_PER_CPU / 1000).max(MIN_SHARES),
+        }
+    }
+
+    /// The limits as Docker's HostConfig takes them.
+    pub fn host_config(&self) -> bollard::service::HostConfig {
+        bollard::service::HostConfig {
+            nano_cpus: self.nano_cpus,
+            memory: self.memory,
+            memory_swap: self.memory,
+            cpu_shares: Some(self.cpu_shares),
+            ..Default::default()
+        }
+    }
+}
+
 pub struct CgroupManager {
     cgroup_root: PathBuf,
 }
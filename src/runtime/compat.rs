@@ -0,0 +1,152 @@
+// Compatibility tracking for pod spec features the runtime can't honor yet.
+// Instead of silently ignoring them, pods get an annotation listing the
+// affected fields so users can see where behavior differs from a real cluster.
+use serde_json::{json, Value};
+
+use super::security_profile;
+use super::volumes;
+
+/// Annotation recording the unsupported fields found on a pod.
+pub const UNSUPPORTED_FIELDS_ANNOTATION: &str = "krust.io/unsupported-fields";
+
+/// Pod-level spec fields that are accepted but not honored.
+pub const UNSUPPORTED_POD_FIELDS: &[(&str, &str)] = &[
+    ("securityContext", "pod security context is not applied to containers, apart from seccomp and AppArmor profiles"),
+    ("hostPID", "host PID namespace sharing is not supported"),
+    ("hostIPC", "host IPC namespace sharing is not supported"),
+    ("shareProcessNamespace", "containers never share a process namespace"),
+    ("hostAliases", "host aliases are not written to /etc/hosts"),
+    ("initContainers", "init containers are never run"),
+    ("activeDeadlineSeconds", "active deadlines are not enforced"),
+    ("runtimeClassName", "runtime classes are not supported"),
+];
+
+/// Container-level fields that are accepted but not honored.
+pub const UNSUPPORTED_CONTAINER_FIELDS: &[(&str, &str)] = &[
+    ("livenessProbe", "liveness probes are never executed"),
+    ("readinessProbe", "readiness probes are never executed; containers are ready once started"),
+    ("startupProbe", "startup probes are never executed"),
+    ("securityContext", "container security context is not applied, apart from seccomp and AppArmor profiles"),
+    ("workingDir", "the image's working directory is always used"),
+    ("stdin", "stdin is not attached"),
+    ("tty", "no TTY is allocated"),
+];
+
+/// Returns the paths of all fields in a pod spec that krust can't honor.
+pub fn unsupported_fields(spec: &Value) -> Vec<String> {
+    let mut fields = Vec::new();
+
+    for (field, _) in UNSUPPORTED_POD_FIELDS {
+        if is_set(&honored_removed(field, &spec[*field])) {
+            fields.push(format!("spec.{}", field));
+        }
+    }
+
+    for (i, volume) in spec["volumes"].as_array().into_iter().flatten().enumerate() {
+        if !volumes::SOURCES.iter().any(|source| volume.get(*source).is_some()) {
+            fields.push(format!("spec.volumes[{}]", i));
+        }
+    }
+
+    if let Some(containers) = spec["containers"].as_array() {
+        for (i, container) in containers.iter().enumerate() {
+            for (field, _) in UNSUPPORTED_CONTAINER_FIELDS {
+                if is_set(&honored_removed(field, &container[*field])) {
+                    fields.push(format!("spec.containers[{}].{}", i, field));
+                }
+            }
+
+            if let Some(ports) = container["ports"].as_array() {
+                for (j, port) in ports.iter().enumerate() {
+                    if is_set(&port["hostPort"]) {
+                        fields.push(format!("spec.containers[{}].ports[{}].hostPort", i, j));
+                    }
+                }
+            }
+        }
+    }
+
+    fields
+}
+
+/// Records the unsupported fields of a pod in its annotations, removing a
+/// stale annotation when everything in the spec is supported.
+pub fn annotate_pod(pod: &mut Value) {
+    let fields = unsupported_fields(&pod["spec"]);
+
+    if fields.is_empty() {
+        if let Some(annotations) = pod["metadata"]["annotations"].as_object_mut() {
+            annotations.remove(UNSUPPORTED_FIELDS_ANNOTATION);
+        }
+        return;
+    }
+
+    if !pod["metadata"]["annotations"].is_object() {
+        pod["metadata"]["annotations"] = json!({});
+    }
+    pod["metadata"]["annotations"][UNSUPPORTED_FIELDS_ANNOTATION] = json!(json!(fields).to_string());
+}
+
+/// Reads the unsupported fields previously recorded on a pod.
+pub fn recorded_fields(pod: &Value) -> Vec<String> {
+    pod["metadata"]["annotations"][UNSUPPORTED_FIELDS_ANNOTATION]
+        .as_str()
+        .and_then(|s| serde_json::from_str::<Vec<String>>(s).ok())
+        .unwrap_or_default()
+}
+
+/// Looks up why a recorded field path is unsupported.
+pub fn reason_for(path: &str) -> &'static str {
+    if path.starts_with("spec.volumes[") {
+        return "volumes of this kind are not mounted; krust mounts configMap, downwardAPI, emptyDir, hostPath, persistentVolumeClaim, projected and secret volumes";
+    }
+    if path.ends_with(".hostPort") {
+        return "host ports are not published";
+    }
+    // Recorded by the kubelet when its container runtime can't apply them
+    if path.ends_with(".seccompProfile") {
+        return "the node's container runtime can't apply seccomp profiles";
+    }
+    if path.ends_with(".appArmorProfile") || path.contains(security_profile::APPARMOR_ANNOTATION_PREFIX) {
+        return "the node's container runtime can't apply AppArmor profiles";
+    }
+
+    let field = path.rsplit('.').next().unwrap_or(path);
+    let table = if path.starts_with("spec.containers[") {
+        UNSUPPORTED_CONTAINER_FIELDS
+    } else {
+        UNSUPPORTED_POD_FIELDS
+    };
+
+    table
+        .iter()
+        .find(|(name, _)| *name == field)
+        .map(|(_, reason)| *reason)
+        .unwrap_or("not supported by krust")
+}
+
+// A security context without the seccomp and AppArmor profiles, which the
+// kubelets apply themselves
+fn honored_removed(field: &str, value: &Value) -> Value {
+    let mut value = value.clone();
+    if field == "securityContext" {
+        if let Some(context) = value.as_object_mut() {
+            context.remove("seccompProfile");
+            context.remove("appArmorProfile");
+        }
+    }
+    value
+}
+
+// Treat empty objects/arrays and false the same as an absent field,
+// since manifests and generators often emit them as defaults.
+fn is_set(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Object(map) => !map.is_empty(),
+        Value::Array(items) => !items.is_empty(),
+        Value::String(s) => !s.is_empty(),
+        Value::Number(_) => true,
+    }
+}
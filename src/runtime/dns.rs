@@ -0,0 +1,219 @@
+// Pod DNS, worked out from spec.dnsPolicy and spec.dnsConfig the way a
+// kubelet does it. Pods on the cluster resolver search their namespace's
+// services first; hostNetwork pods only get it with ClusterFirstWithHostNet,
+// since they otherwise share the node's resolver like the rest of its
+// network stack.
+//
+// A pod's hostname is spec.hostname or its name, and with a spec.subdomain
+// naming a headless service it has a fully qualified name under that
+// service, which the endpoints controller publishes as the hostname of its
+// address so cluster DNS can resolve it.
+use anyhow::{bail, Result};
+use serde_json::Value;
+use std::net::IpAddr;
+
+use crate::config::DnsConfig;
+
+pub const POLICIES: &[&str] = &["ClusterFirst", "ClusterFirstWithHostNet", "Default", "None"];
+
+// Longest hostname the kernel takes, which bounds setHostnameAsFQDN
+const MAX_FQDN: usize = 64;
+// Longest DNS label
+const MAX_LABEL: usize = 63;
+
+// Limits of the glibc resolver, which is also what the API validates against
+const MAX_NAMESERVERS: usize = 3;
+const MAX_SEARCHES: usize = 32;
+
+/// The contents of a resolv.conf.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Resolver {
+    pub nameservers: Vec<String>,
+    pub searches: Vec<String>,
+    pub options: Vec<String>,
+}
+
+impl Resolver {
+    pub fn parse(resolv_conf: &str) -> Self {
+        let mut resolver = Self::default();
+        for line in resolv_conf.lines() {
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("nameserver") => resolver.nameservers.extend(words.next().map(str::to_string)),
+                Some("search") | Some("domain") => resolver.searches = words.map(str::to_string).collect(),
+                Some("options") => resolver.options.extend(words.map(str::to_string)),
+                _ => {}
+            }
+        }
+        resolver
+    }
+
+    pub fn to_resolv_conf(&self) -> String {
+        let mut conf = String::new();
+        for nameserver in &self.nameservers {
+            conf.push_str(&format!("nameserver {}\n", nameserver));
+        }
+        if !self.searches.is_empty() {
+            conf.push_str(&format!("search {}\n", self.searches.join(" ")));
+        }
+        if !self.options.is_empty() {
+            conf.push_str(&format!("options {}\n", self.options.join(" ")));
+        }
+        conf
+    }
+}
+
+/// Checks spec.dnsPolicy and spec.dnsConfig.
+pub fn validate(spec: &Value) -> Result<()> {
+    let policy = spec["dnsPolicy"].as_str().unwrap_or("ClusterFirst");
+    if !POLICIES.contains(&policy) {
+        bail!("spec.dnsPolicy: Unsupported value: {:?}: supported values: {}", policy, POLICIES.join(", "));
+    }
+
+    let dns_config = &spec["dnsConfig"];
+    let nameservers = strings(&dns_config["nameservers"]);
+    if policy == "None" && nameservers.is_empty() {
+        bail!("spec.dnsConfig.nameservers: Required value: must provide at least one DNS nameserver when dnsPolicy is None");
+    }
+    if nameservers.len() > MAX_NAMESERVERS {
+        bail!("spec.dnsConfig.nameservers: Invalid value: must not have more than {} nameservers", MAX_NAMESERVERS);
+    }
+    if let Some(bad) = nameservers.iter().find(|ns| ns.parse::<IpAddr>().is_err()) {
+        bail!("spec.dnsConfig.nameservers: Invalid value: {:?}: must be a valid IP address", bad);
+    }
+    if strings(&dns_config["searches"]).len() > MAX_SEARCHES {
+        bail!("spec.dnsConfig.searches: Invalid value: must not have more than {} search paths", MAX_SEARCHES);
+    }
+
+    for field in ["hostname", "subdomain"] {
+        let Some(value) = spec[field].as_str().filter(|v| !v.is_empty()) else {
+            continue;
+        };
+        if value.len() > MAX_LABEL {
+            bail!("spec.{}: Invalid value: {:?}: must be no more than {} characters", field, value, MAX_LABEL);
+        }
+        if !is_label(value) {
+            bail!(
+                "spec.{}: Invalid value: {:?}: a lowercase RFC 1123 label must consist of lower case alphanumeric characters or '-', and must start and end with an alphanumeric character (e.g. 'my-name',  or '123-abc', regex used for validation is '[a-z0-9]([-a-z0-9]*[a-z0-9])?')",
+                field,
+                value
+            );
+        }
+    }
+    Ok(())
+}
+
+/// The hostname of a pod's containers: spec.hostname, or else the pod's
+/// name, cut to a DNS label.
+pub fn hostname(pod_name: &str, spec: &Value) -> String {
+    match spec["hostname"].as_str().filter(|h| !h.is_empty()) {
+        Some(hostname) => hostname.to_string(),
+        None => pod_name[..pod_name.len().min(MAX_LABEL)].trim_end_matches(['-', '.']).to_string(),
+    }
+}
+
+/// The domain of a pod in `namespace` with a spec.subdomain:
+/// `<subdomain>.<namespace>.svc.<cluster domain>`.
+pub fn domain(spec: &Value, namespace: &str, cluster: &DnsConfig) -> Option<String> {
+    let subdomain = spec["subdomain"].as_str().filter(|s| !s.is_empty())?;
+    Some(format!("{}.{}.svc.{}", subdomain, namespace, cluster.cluster_domain))
+}
+
+/// The hostname and domain name a pod's containers are given. With
+/// spec.setHostnameAsFQDN the hostname is the fully qualified name, which
+/// the kernel limits to 64 characters, so a longer one fails the pod.
+pub fn container_hostname(pod_name: &str, spec: &Value, namespace: &str, cluster: &DnsConfig) -> Result<(String, Option<String>)> {
+    let hostname = hostname(pod_name, spec);
+    let domain = domain(spec, namespace, cluster);
+    if !spec["setHostnameAsFQDN"].as_bool().unwrap_or(false) {
+        return Ok((hostname, domain));
+    }
+    let Some(domain) = domain else {
+        return Ok((hostname, None));
+    };
+
+    let fqdn = format!("{}.{}", hostname, domain);
+    if fqdn.len() > MAX_FQDN {
+        bail!(
+            "failed to construct FQDN from pod hostname and cluster domain, FQDN {} is too long ({} characters is the max, {} characters requested)",
+            fqdn,
+            MAX_FQDN,
+            fqdn.len()
+        );
+    }
+    Ok((fqdn, None))
+}
+
+fn is_label(value: &str) -> bool {
+    let alphanumeric = |c: char| c.is_ascii_lowercase() || c.is_ascii_digit();
+    value.starts_with(alphanumeric) && value.ends_with(alphanumeric) && value.chars().all(|c| alphanumeric(c) || c == '-')
+}
+
+/// The resolver for the containers of a pod in `namespace`, or `None` when
+/// they keep the one of the node they run on, which is `node`.
+pub fn resolver(spec: &Value, namespace: &str, cluster: &DnsConfig, node: &Resolver) -> Option<Resolver> {
+    let host_network = spec["hostNetwork"].as_bool().unwrap_or(false);
+    let policy = spec["dnsPolicy"].as_str().unwrap_or("ClusterFirst");
+    let dns_config = &spec["dnsConfig"];
+
+    // Without a cluster resolver to point at, ClusterFirst falls back to the
+    // node's, as a kubelet without --cluster-dns does
+    let cluster_first = match policy {
+        "ClusterFirstWithHostNet" => true,
+        "ClusterFirst" => !host_network,
+        _ => false,
+    } && !cluster.cluster_dns.is_empty();
+
+    let mut resolver = if cluster_first {
+        let domain = &cluster.cluster_domain;
+        let mut searches = vec![format!("{}.svc.{}", namespace, domain), format!("svc.{}", domain), domain.clone()];
+        searches.extend(node.searches.iter().cloned());
+        Resolver {
+            nameservers: cluster.cluster_dns.clone(),
+            searches,
+            options: vec!["ndots:5".to_string()],
+        }
+    } else if policy == "None" {
+        Resolver::default()
+    } else if dns_config.is_null() {
+        return None;
+    } else {
+        node.clone()
+    };
+
+    // dnsConfig adds to whatever the policy gave
+    for nameserver in strings(&dns_config["nameservers"]) {
+        if !resolver.nameservers.contains(&nameserver) {
+            resolver.nameservers.push(nameserver);
+        }
+    }
+    for search in strings(&dns_config["searches"]) {
+        if !resolver.searches.contains(&search) {
+            resolver.searches.push(search);
+        }
+    }
+    for option in dns_config["options"].as_array().into_iter().flatten() {
+        let Some(name) = option["name"].as_str() else {
+            continue;
+        };
+        // An option given again replaces the earlier one
+        resolver.options.retain(|o| o.split(':').next() != Some(name));
+        match option["value"].as_str() {
+            Some(value) => resolver.options.push(format!("{}:{}", name, value)),
+            None => resolver.options.push(name.to_string()),
+        }
+    }
+
+    resolver.nameservers.truncate(MAX_NAMESERVERS);
+    resolver.searches.truncate(MAX_SEARCHES);
+    Some(resolver)
+}
+
+fn strings(value: &Value) -> Vec<String> {
+    value
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str().map(str::to_string))
+        .collect()
+}
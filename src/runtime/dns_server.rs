@@ -0,0 +1,588 @@
+// Cluster DNS served by krust itself, the records CoreDNS serves in a
+// cluster. A Service is <service>.<namespace>.svc.<domain>, resolving to its
+// cluster IPs, or to its ready endpoints when it's headless, or a CNAME for
+// an ExternalName, and each named port has an SRV record under
+// _<port>._<protocol>.<service>.<namespace>.svc.<domain>. Every endpoint of
+// a headless service has a name of its own below the service: the hostname
+// the endpoints controller published for its pod, or else its IP dashed.
+// <dashed IP>.<namespace>.pod.<domain> resolves to the IP, as with CoreDNS's
+// `pods insecure`.
+//
+// Names outside the cluster domain go on to the node's nameservers, so pods
+// that use it resolve everything else as they did before. By default it
+// listens on the gateway of the pod network, which the kubelet's containers
+// reach, and ClusterFirst pods are given it as their nameserver unless
+// dns.clusterDNS names another.
+use anyhow::{Context, Result};
+use bollard::Docker;
+use serde_json::Value;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+use tokio::time::timeout;
+use tracing::{debug, error, info};
+
+use super::dns::Resolver;
+use super::pod_network;
+use crate::config::DnsConfig;
+use crate::profiling;
+use crate::Storage;
+
+/// Time to live of the records served, short since they follow the API.
+pub const TTL: u32 = 5;
+
+// Largest reply over UDP to a client that doesn't say it takes more, and
+// the most any can say
+const UDP_SIZE: usize = 512;
+const MAX_UDP_SIZE: usize = 4096;
+
+const FORWARD_TIMEOUT: Duration = Duration::from_secs(2);
+const TCP_IDLE_TIMEOUT: Duration = Duration::from_secs(10);
+const REFRESH_INTERVAL: Duration = Duration::from_secs(1);
+
+const HEADER_SIZE: usize = 12;
+const TYPE_A: u16 = 1;
+const TYPE_CNAME: u16 = 5;
+const TYPE_AAAA: u16 = 28;
+const TYPE_SRV: u16 = 33;
+const TYPE_OPT: u16 = 41;
+const TYPE_ANY: u16 = 255;
+const CLASS_IN: u16 = 1;
+
+const FORMERR: u16 = 1;
+const SERVFAIL: u16 = 2;
+const NXDOMAIN: u16 = 3;
+const NOTIMP: u16 = 4;
+
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Record {
+    Address(IpAddr),
+    Cname(String),
+    Srv { priority: u16, weight: u16, port: u16, target: String },
+}
+
+impl Record {
+    fn record_type(&self) -> u16 {
+        match self {
+            Record::Address(IpAddr::V4(_)) => TYPE_A,
+            Record::Address(IpAddr::V6(_)) => TYPE_AAAA,
+            Record::Cname(_) => TYPE_CNAME,
+            Record::Srv { .. } => TYPE_SRV,
+        }
+    }
+}
+
+/// What to do with a query.
+#[derive(Debug, PartialEq)]
+pub enum Answer {
+    Reply(Vec<u8>),
+    /// The name is outside the cluster domain, for the node's nameservers.
+    Forward,
+}
+
+/// The records of the cluster domain, by lowercase name without the
+/// trailing dot.
+#[derive(Debug, Default)]
+pub struct Zone {
+    domain: String,
+    records: BTreeMap<String, Vec<Record>>,
+    // Every name that exists, with records or only names below it, like
+    // <namespace>.svc.<domain>
+    names: BTreeSet<String>,
+}
+
+impl Zone {
+    /// The records for `services` and their `endpoints` under `domain`.
+    pub fn new(domain: &str, services: &[Value], endpoints: &[Value]) -> Self {
+        let domain = domain.trim_end_matches('.').to_ascii_lowercase();
+        let mut zone = Self { domain, ..Self::default() };
+        zone.names.insert(zone.domain.clone());
+        let endpoints: HashMap<(&str, &str), &Value> = endpoints
+            .iter()
+            .filter_map(|e| Some(((e["metadata"]["namespace"].as_str()?, e["metadata"]["name"].as_str()?), e)))
+            .collect();
+
+        for service in services {
+            let (Some(namespace), Some(name)) = (service["metadata"]["namespace"].as_str(), service["metadata"]["name"].as_str()) else {
+                continue;
+            };
+            let fqdn = format!("{}.{}.svc.{}", name, namespace, zone.domain).to_ascii_lowercase();
+            zone.exists(&fqdn);
+            let spec = &service["spec"];
+
+            if spec["type"] == "ExternalName" {
+                if let Some(external) = spec["externalName"].as_str().filter(|n| !n.is_empty()) {
+                    zone.add(&fqdn, Record::Cname(external.trim_end_matches('.').to_string()));
+                }
+                continue;
+            }
+
+            let cluster_ips: Vec<IpAddr> = match spec["clusterIPs"].as_array() {
+                Some(ips) => ips.iter().filter_map(|ip| ip.as_str()?.parse().ok()).collect(),
+                None => spec["clusterIP"].as_str().and_then(|ip| ip.parse().ok()).into_iter().collect(),
+            };
+            if !cluster_ips.is_empty() {
+                for ip in cluster_ips {
+                    zone.add(&fqdn, Record::Address(ip));
+                }
+                for port in spec["ports"].as_array().into_iter().flatten() {
+                    let (Some(port_name), Some(number)) = (port["name"].as_str().filter(|n| !n.is_empty()), port["port"].as_u64()) else {
+                        continue;
+                    };
+                    let protocol = port["protocol"].as_str().unwrap_or("TCP").to_ascii_lowercase();
+                    let srv = Record::Srv { priority: 0, weight: 100, port: number as u16, target: fqdn.clone() };
+                    zone.add(&format!("_{}._{}.{}", port_name, protocol, fqdn), srv);
+                }
+                continue;
+            }
+
+            // Headless: the ready endpoints themselves
+            let Some(endpoints) = endpoints.get(&(namespace, name)) else {
+                continue;
+            };
+            let mut srvs: BTreeMap<String, Vec<(u16, String)>> = BTreeMap::new();
+            for subset in endpoints["subsets"].as_array().into_iter().flatten() {
+                for address in subset["addresses"].as_array().into_iter().flatten() {
+                    let Some(ip) = address["ip"].as_str().and_then(|ip| ip.parse::<IpAddr>().ok()) else {
+                        continue;
+                    };
+                    let hostname = match address["hostname"].as_str().filter(|h| !h.is_empty()) {
+                        Some(hostname) => hostname.to_ascii_lowercase(),
+                        None => dashed(&ip),
+                    };
+                    let host = format!("{}.{}", hostname, fqdn);
+                    zone.add(&fqdn, Record::Address(ip));
+                    zone.add(&host, Record::Address(ip));
+                    for port in subset["ports"].as_array().into_iter().flatten() {
+                        let (Some(port_name), Some(number)) = (port["name"].as_str().filter(|n| !n.is_empty()), port["port"].as_u64()) else {
+                            continue;
+                        };
+                        let protocol = port["protocol"].as_str().unwrap_or("TCP").to_ascii_lowercase();
+                        let srv = format!("_{}._{}.{}", port_name, protocol, fqdn);
+                        srvs.entry(srv).or_default().push((number as u16, host.clone()));
+                    }
+                }
+            }
+            // Weighted evenly, as CoreDNS does
+            for (srv, targets) in srvs {
+                let weight = (100 / targets.len()).max(1) as u16;
+                for (port, target) in targets {
+                    zone.add(&srv, Record::Srv { priority: 0, weight, port, target });
+                }
+            }
+        }
+        zone
+    }
+
+    fn add(&mut self, name: &str, record: Record) {
+        let records = self.records.entry(name.to_string()).or_default();
+        if !records.contains(&record) {
+            records.push(record);
+        }
+        self.exists(name);
+    }
+
+    // Records that the name and the ones above it down to the domain exist
+    fn exists(&mut self, name: &str) {
+        let mut name = name;
+        while name.len() > self.domain.len() && self.names.insert(name.to_string()) {
+            match name.split_once('.') {
+                Some((_, parent)) => name = parent,
+                None => break,
+            }
+        }
+    }
+
+    /// The records of `name`, which is lowercase.
+    pub fn lookup(&self, name: &str) -> Vec<Record> {
+        if let Some(records) = self.records.get(name) {
+            return records.clone();
+        }
+        // <dashed IP>.<namespace>.pod.<domain>
+        let pod = name.strip_suffix(&format!(".pod.{}", self.domain)).and_then(|rest| rest.split_once('.'));
+        match pod.filter(|(_, namespace)| !namespace.contains('.')).and_then(|(ip, _)| undashed(ip)) {
+            Some(ip) => vec![Record::Address(ip)],
+            None => Vec::new(),
+        }
+    }
+
+    /// The answer to a DNS query, or `None` if it's too short or not a
+    /// query at all, to be dropped.
+    pub fn answer(&self, query: &[u8]) -> Option<Answer> {
+        let header = Header::parse(query)?;
+        if header.opcode != 0 {
+            return Some(Answer::Reply(header.reply(NOTIMP, None)));
+        }
+        let Some(question) = header.question(query) else {
+            return Some(Answer::Reply(header.reply(FORMERR, None)));
+        };
+
+        let name = question.name.to_ascii_lowercase();
+        if name != self.domain && !name.ends_with(&format!(".{}", self.domain)) {
+            return Some(Answer::Forward);
+        }
+
+        let records = self.lookup(&name);
+        if records.is_empty() && !self.names.contains(&name) {
+            let mut reply = header.reply(NXDOMAIN, Some(&question));
+            reply[2] |= 0x04;
+            return Some(Answer::Reply(reply));
+        }
+        let answers: Vec<Record> = if question.class != CLASS_IN {
+            Vec::new()
+        } else if let Some(cname) = records.iter().find(|r| matches!(r, Record::Cname(_)) && question.record_type != TYPE_CNAME) {
+            vec![cname.clone()]
+        } else {
+            records.into_iter().filter(|r| question.record_type == TYPE_ANY || r.record_type() == question.record_type).collect()
+        };
+        // The addresses of SRV targets come along, saving another query
+        let mut additional = Vec::new();
+        for answer in &answers {
+            if let Record::Srv { target, .. } = answer {
+                if target != &name {
+                    additional.extend(self.lookup(target).into_iter().filter(|r| matches!(r, Record::Address(_))).map(|r| (target.clone(), r)));
+                }
+            }
+        }
+
+        let mut reply = header.reply(0, Some(&question));
+        reply[2] |= 0x04;
+        reply[6..8].copy_from_slice(&(answers.len() as u16).to_be_bytes());
+        reply[10..12].copy_from_slice(&(additional.len() as u16).to_be_bytes());
+        for answer in &answers {
+            // A pointer to the question's name
+            reply.extend_from_slice(&[0xc0, HEADER_SIZE as u8]);
+            write_record(&mut reply, answer);
+        }
+        for (name, record) in &additional {
+            write_name(&mut reply, name);
+            write_record(&mut reply, record);
+        }
+        Some(Answer::Reply(reply))
+    }
+}
+
+/// The largest reply a query takes over UDP: 512 bytes, or the size in its
+/// EDNS OPT record.
+pub fn udp_limit(query: &[u8]) -> usize {
+    let Some(header) = Header::parse(query) else {
+        return UDP_SIZE;
+    };
+    let Some(question) = header.question(query) else {
+        return UDP_SIZE;
+    };
+    let opt = &query[question.end..];
+    // The OPT record's name is the root, and its class the size
+    if header.additional > 0 && opt.len() >= 5 && opt[0] == 0 && u16::from_be_bytes([opt[1], opt[2]]) == TYPE_OPT {
+        return (u16::from_be_bytes([opt[3], opt[4]]) as usize).clamp(UDP_SIZE, MAX_UDP_SIZE);
+    }
+    UDP_SIZE
+}
+
+/// A reply cut to `limit` bytes: if it's longer, just its question, marked
+/// truncated so the client asks again over TCP.
+pub fn truncate(reply: Vec<u8>, limit: usize) -> Vec<u8> {
+    if reply.len() <= limit {
+        return reply;
+    }
+    let Some(header) = Header::parse(&reply) else {
+        return reply;
+    };
+    let question = header.question(&reply);
+    let mut truncated = reply[..question.map_or(HEADER_SIZE, |q| q.end)].to_vec();
+    truncated[2] |= 0x02;
+    truncated[6..HEADER_SIZE].fill(0);
+    truncated
+}
+
+struct Header {
+    id: [u8; 2],
+    opcode: u8,
+    recursion_desired: bool,
+    questions: u16,
+    additional: u16,
+}
+
+struct Question {
+    name: String,
+    record_type: u16,
+    class: u16,
+    // Where the question ends in the message
+    end: usize,
+}
+
+impl Header {
+    fn parse(message: &[u8]) -> Option<Self> {
+        if message.len() < HEADER_SIZE {
+            return None;
+        }
+        let flags = u16::from_be_bytes([message[2], message[3]]);
+        Some(Self {
+            id: [message[0], message[1]],
+            opcode: ((flags >> 11) & 0x0f) as u8,
+            recursion_desired: flags & 0x0100 != 0,
+            questions: u16::from_be_bytes([message[4], message[5]]),
+            additional: u16::from_be_bytes([message[10], message[11]]),
+        })
+    }
+
+    // The one question a query has; queries don't compress names
+    fn question(&self, message: &[u8]) -> Option<Question> {
+        if self.questions != 1 {
+            return None;
+        }
+        let mut labels = Vec::new();
+        let mut offset = HEADER_SIZE;
+        loop {
+            let length = *message.get(offset)? as usize;
+            offset += 1;
+            if length == 0 {
+                break;
+            }
+            if length > 63 {
+                return None;
+            }
+            labels.push(String::from_utf8_lossy(message.get(offset..offset + length)?).into_owned());
+            offset += length;
+        }
+        let fields = message.get(offset..offset + 4)?;
+        Some(Question {
+            name: labels.join("."),
+            record_type: u16::from_be_bytes([fields[0], fields[1]]),
+            class: u16::from_be_bytes([fields[2], fields[3]]),
+            end: offset + 4,
+        })
+    }
+
+    // A reply's header, and the question copied from the query, without
+    // any records yet
+    fn reply(&self, rcode: u16, question: Option<&Question>) -> Vec<u8> {
+        let mut flags = 0x8000 | (self.opcode as u16) << 11 | 0x0080 | rcode;
+        if self.recursion_desired {
+            flags |= 0x0100;
+        }
+        let mut reply = Vec::with_capacity(UDP_SIZE);
+        reply.extend_from_slice(&self.id);
+        reply.extend_from_slice(&flags.to_be_bytes());
+        reply.extend_from_slice(&(question.is_some() as u16).to_be_bytes());
+        reply.extend_from_slice(&[0; 6]);
+        if let Some(question) = question {
+            write_name(&mut reply, &question.name);
+            reply.extend_from_slice(&question.record_type.to_be_bytes());
+            reply.extend_from_slice(&question.class.to_be_bytes());
+        }
+        reply
+    }
+}
+
+fn write_name(message: &mut Vec<u8>, name: &str) {
+    for label in name.split('.').filter(|l| !l.is_empty()) {
+        let label = &label.as_bytes()[..label.len().min(63)];
+        message.push(label.len() as u8);
+        message.extend_from_slice(label);
+    }
+    message.push(0);
+}
+
+// A record's type, class, TTL and data, after its name
+fn write_record(message: &mut Vec<u8>, record: &Record) {
+    message.extend_from_slice(&record.record_type().to_be_bytes());
+    message.extend_from_slice(&CLASS_IN.to_be_bytes());
+    message.extend_from_slice(&TTL.to_be_bytes());
+    let mut data = Vec::new();
+    match record {
+        Record::Address(IpAddr::V4(ip)) => data.extend_from_slice(&ip.octets()),
+        Record::Address(IpAddr::V6(ip)) => data.extend_from_slice(&ip.octets()),
+        Record::Cname(target) => write_name(&mut data, target),
+        Record::Srv { priority, weight, port, target } => {
+            data.extend_from_slice(&priority.to_be_bytes());
+            data.extend_from_slice(&weight.to_be_bytes());
+            data.extend_from_slice(&port.to_be_bytes());
+            write_name(&mut data, target);
+        }
+    }
+    message.extend_from_slice(&(data.len() as u16).to_be_bytes());
+    message.extend_from_slice(&data);
+}
+
+// 10.244.0.5 as 10-244-0-5
+fn dashed(ip: &IpAddr) -> String {
+    match ip {
+        IpAddr::V4(ip) => ip.to_string().replace('.', "-"),
+        IpAddr::V6(ip) => ip.to_string().replace(':', "-"),
+    }
+}
+
+fn undashed(label: &str) -> Option<IpAddr> {
+    if let Ok(ip) = label.replace('-', ".").parse::<Ipv4Addr>() {
+        return Some(IpAddr::V4(ip));
+    }
+    label.replace('-', ":").parse::<Ipv6Addr>().ok().map(IpAddr::V6)
+}
+
+// A SERVFAIL for a query that couldn't be forwarded
+fn failure(query: &[u8]) -> Option<Vec<u8>> {
+    let header = Header::parse(query)?;
+    Some(header.reply(SERVFAIL, header.question(query).as_ref()))
+}
+
+pub struct DnsServer {
+    storage: Storage,
+    domain: String,
+    udp: Arc<UdpSocket>,
+    tcp: TcpListener,
+    upstreams: Arc<Vec<SocketAddr>>,
+    zone: Arc<RwLock<Arc<Zone>>>,
+}
+
+impl DnsServer {
+    /// Binds UDP and TCP on dns.server.address, or else on port 53 of the
+    /// pod network's gateway.
+    pub async fn bind(storage: Storage, dns: &DnsConfig) -> Result<Self> {
+        let address = match dns.server.address {
+            Some(address) => address,
+            None => {
+                // The gateway is only there once the network is
+                pod_network::ensure(&Docker::connect_with_local_defaults()?).await?;
+                SocketAddr::new(IpAddr::V4(pod_network::GATEWAY), 53)
+            }
+        };
+        let udp = UdpSocket::bind(address).await.with_context(|| format!("can't listen on {}", address))?;
+        let local = udp.local_addr()?;
+        let tcp = TcpListener::bind(local).await.with_context(|| format!("can't listen on {}", local))?;
+
+        // Forwarding to itself would loop
+        let node = Resolver::parse(&std::fs::read_to_string("/etc/resolv.conf").unwrap_or_default());
+        let upstreams = node
+            .nameservers
+            .iter()
+            .filter_map(|ns| ns.parse::<IpAddr>().ok())
+            .map(|ip| SocketAddr::new(ip, 53))
+            .filter(|upstream| *upstream != local)
+            .collect();
+
+        Ok(Self {
+            storage,
+            domain: dns.cluster_domain.clone(),
+            udp: Arc::new(udp),
+            tcp,
+            upstreams: Arc::new(upstreams),
+            zone: Arc::default(),
+        })
+    }
+
+    pub fn local_addr(&self) -> SocketAddr {
+        self.udp.local_addr().expect("the socket is bound")
+    }
+
+    /// The nameserver for pods' resolv.conf, which can only name servers
+    /// on port 53.
+    pub fn nameserver(&self) -> Option<IpAddr> {
+        let local = self.local_addr();
+        (local.port() == 53 && !local.ip().is_unspecified()).then_some(local.ip())
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        info!("Serving cluster DNS for {} on {}", self.domain, self.local_addr());
+        let mut refresh = tokio::time::interval(REFRESH_INTERVAL);
+        let mut buf = vec![0; MAX_UDP_SIZE];
+
+        loop {
+            tokio::select! {
+                _ = refresh.tick() => {
+                    let started = Instant::now();
+                    if let Err(e) = self.refresh().await {
+                        error!("Cluster DNS error: {:#}", e);
+                    }
+                    profiling::record("cluster DNS", started);
+                }
+                received = self.udp.recv_from(&mut buf) => {
+                    let (length, peer) = received?;
+                    let query = buf[..length].to_vec();
+                    let zone = self.zone.read().unwrap().clone();
+                    let (udp, upstreams) = (self.udp.clone(), self.upstreams.clone());
+                    tokio::spawn(async move {
+                        if let Some(reply) = resolve(&zone, &query, &upstreams).await {
+                            let _ = udp.send_to(&truncate(reply, udp_limit(&query)), peer).await;
+                        }
+                    });
+                }
+                accepted = self.tcp.accept() => {
+                    let (stream, peer) = accepted?;
+                    let (zone, upstreams) = (self.zone.clone(), self.upstreams.clone());
+                    tokio::spawn(async move {
+                        if let Err(e) = serve_tcp(stream, zone, upstreams).await {
+                            debug!("DNS connection from {} closed: {}", peer, e);
+                        }
+                    });
+                }
+            }
+        }
+    }
+
+    async fn refresh(&self) -> Result<()> {
+        let services = self.storage.services().list(None).await?;
+        let endpoints = self.storage.endpoints().list(None).await?;
+        let zone = Zone::new(
+            &self.domain,
+            services["items"].as_array().map(Vec::as_slice).unwrap_or_default(),
+            endpoints["items"].as_array().map(Vec::as_slice).unwrap_or_default(),
+        );
+        *self.zone.write().unwrap() = Arc::new(zone);
+        Ok(())
+    }
+}
+
+// The reply to a query, from the zone or the node's nameservers
+async fn resolve(zone: &Zone, query: &[u8], upstreams: &[SocketAddr]) -> Option<Vec<u8>> {
+    match zone.answer(query)? {
+        Answer::Reply(reply) => Some(reply),
+        Answer::Forward => match forward(query, upstreams).await {
+            Some(reply) => Some(reply),
+            None => failure(query),
+        },
+    }
+}
+
+// Asks each of the node's nameservers in turn until one replies
+async fn forward(query: &[u8], upstreams: &[SocketAddr]) -> Option<Vec<u8>> {
+    for upstream in upstreams {
+        let local: SocketAddr = if upstream.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+        let Ok(socket) = UdpSocket::bind(local).await else {
+            continue;
+        };
+        if socket.connect(upstream).await.is_err() || socket.send(query).await.is_err() {
+            continue;
+        }
+        let mut buf = vec![0; 65535];
+        if let Ok(Ok(length)) = timeout(FORWARD_TIMEOUT, socket.recv(&mut buf)).await {
+            if length >= 2 && buf[..2] == query[..2] {
+                buf.truncate(length);
+                return Some(buf);
+            }
+        }
+        debug!("No reply from nameserver {}", upstream);
+    }
+    None
+}
+
+// Queries over TCP, each prefixed with its length, until the client goes
+// quiet
+async fn serve_tcp(mut stream: TcpStream, zone: Arc<RwLock<Arc<Zone>>>, upstreams: Arc<Vec<SocketAddr>>) -> Result<()> {
+    loop {
+        let Ok(length) = timeout(TCP_IDLE_TIMEOUT, stream.read_u16()).await else {
+            return Ok(());
+        };
+        let mut query = vec![0; length? as usize];
+        stream.read_exact(&mut query).await?;
+        let zone = zone.read().unwrap().clone();
+        let Some(reply) = resolve(&zone, &query, &upstreams).await else {
+            return Ok(());
+        };
+        stream.write_u16(reply.len() as u16).await?;
+        stream.write_all(&reply).await?;
+    }
+}
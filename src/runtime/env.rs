@@ -0,0 +1,248 @@
+// A container's environment, as the kubelet resolves it before starting it:
+// the keys of the ConfigMaps and Secrets its envFrom names, then its env,
+// whose values come as given, from a key of a ConfigMap or Secret, from a
+// field of the pod (the downward API) or from the container's resources.
+// Values may refer to variables defined before them as `$(NAME)`, as may
+// the container's command and args.
+use anyhow::{bail, Context, Result};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::Value;
+use std::collections::BTreeMap;
+
+use crate::models::quantity::{self, Resources};
+use crate::Storage;
+
+/// The container's environment as `NAME=value` pairs, in order. `allocatable`
+/// is what the node has, which resourceFieldRefs to unset limits resolve to.
+pub async fn resolve(storage: &Storage, pod: &Value, container: &Value, allocatable: &Resources) -> Result<Vec<(String, String)>> {
+    let namespace = pod["metadata"]["namespace"].as_str().unwrap_or("default");
+    let mut env: Vec<(String, String)> = Vec::new();
+
+    for source in container["envFrom"].as_array().into_iter().flatten() {
+        let prefix = source["prefix"].as_str().unwrap_or_default();
+        let data = if let Some(reference) = source.get("configMapRef") {
+            config_map_data(storage, namespace, reference).await?
+        } else if let Some(reference) = source.get("secretRef") {
+            secret_data(storage, namespace, reference).await?
+        } else {
+            continue;
+        };
+        // Keys that don't make a valid variable name are skipped, as the
+        // kubelet does
+        for (key, value) in data.into_iter().flatten() {
+            let name = format!("{}{}", prefix, key);
+            if is_env_name(&name) {
+                set(&mut env, name, value);
+            }
+        }
+    }
+
+    for var in container["env"].as_array().into_iter().flatten() {
+        let Some(name) = var["name"].as_str() else {
+            continue;
+        };
+        let value = match var.get("valueFrom").filter(|v| v.is_object()) {
+            None => expand(var["value"].as_str().unwrap_or_default(), &env),
+            Some(from) => match value_from(storage, pod, container, from, allocatable).await {
+                Ok(Some(value)) => value,
+                // An optional key that isn't there leaves the variable unset
+                Ok(None) => continue,
+                Err(e) => return Err(e.context(format!("env {}", name))),
+            },
+        };
+        set(&mut env, name.to_string(), value);
+    }
+    Ok(env)
+}
+
+/// Replaces the `$(NAME)`s in `value` that name a variable of `env` with its
+/// value. Others are left as they are, and `$$` stands for a `$`.
+pub fn expand(value: &str, env: &[(String, String)]) -> String {
+    let mut expanded = String::with_capacity(value.len());
+    let mut rest = value;
+    while let Some(at) = rest.find('$') {
+        expanded.push_str(&rest[..at]);
+        rest = &rest[at..];
+        if let Some(after) = rest.strip_prefix("$$") {
+            expanded.push('$');
+            rest = after;
+            continue;
+        }
+        let reference = rest.strip_prefix("$(").and_then(|inner| Some(&inner[..inner.find(')')?]));
+        match reference.and_then(|name| env.iter().rev().find(|(n, _)| n == name)) {
+            Some((name, value)) => {
+                expanded.push_str(value);
+                rest = &rest[name.len() + 3..];
+            }
+            None => {
+                expanded.push('$');
+                rest = &rest[1..];
+            }
+        }
+    }
+    expanded.push_str(rest);
+    expanded
+}
+
+/// The value of a downward API fieldRef for the pod, or None if the pod has
+/// no such field. Whole label and annotation maps come as `key="value"`
+/// lines, as in a downwardAPI volume.
+pub fn field(pod: &Value, path: &str) -> Option<String> {
+    let metadata = &pod["metadata"];
+    for map in ["labels", "annotations"] {
+        if path == format!("metadata.{}", map) {
+            let mut lines: Vec<String> = metadata[map]
+                .as_object()
+                .into_iter()
+                .flatten()
+                .map(|(key, value)| format!("{}={}", key, Value::String(value.as_str().unwrap_or_default().to_string())))
+                .collect();
+            lines.sort();
+            return Some(lines.join("\n"));
+        }
+        let key = path
+            .strip_prefix(&format!("metadata.{}['", map))
+            .and_then(|rest| rest.strip_suffix("']"));
+        if let Some(key) = key {
+            return Some(metadata[map][key].as_str().unwrap_or_default().to_string());
+        }
+    }
+    let value = match path {
+        "metadata.name" => &metadata["name"],
+        "metadata.namespace" => &metadata["namespace"],
+        "metadata.uid" => &metadata["uid"],
+        "spec.nodeName" => &pod["spec"]["nodeName"],
+        "spec.serviceAccountName" => &pod["spec"]["serviceAccountName"],
+        "status.hostIP" => &pod["status"]["hostIP"],
+        "status.podIP" => &pod["status"]["podIP"],
+        "status.hostIPs" | "status.podIPs" => {
+            let ips = pod["status"][&path["status.".len()..]].as_array().into_iter().flatten();
+            return Some(ips.filter_map(|ip| ip["ip"].as_str()).collect::<Vec<_>>().join(","));
+        }
+        _ => return None,
+    };
+    Some(value.as_str().unwrap_or_default().to_string())
+}
+
+// The value of an env var's valueFrom, or None for an optional key that
+// isn't there
+async fn value_from(storage: &Storage, pod: &Value, container: &Value, from: &Value, allocatable: &Resources) -> Result<Option<String>> {
+    let namespace = pod["metadata"]["namespace"].as_str().unwrap_or("default");
+    if let Some(reference) = from.get("configMapKeyRef") {
+        let data = config_map_data(storage, namespace, reference).await?;
+        return key_of(data, reference, "configmap");
+    }
+    if let Some(reference) = from.get("secretKeyRef") {
+        let data = secret_data(storage, namespace, reference).await?;
+        return key_of(data, reference, "secret");
+    }
+    if let Some(reference) = from.get("fieldRef") {
+        let path = reference["fieldPath"].as_str().unwrap_or_default();
+        return field(pod, path).map(Some).with_context(|| format!("unsupported fieldPath {:?}", path));
+    }
+    if let Some(reference) = from.get("resourceFieldRef") {
+        return resource_field(pod, container, reference, allocatable).map(Some);
+    }
+    bail!("valueFrom has no source")
+}
+
+// The value of a key of a ConfigMap's or Secret's data
+fn key_of(data: Option<BTreeMap<String, String>>, reference: &Value, kind: &str) -> Result<Option<String>> {
+    let name = reference["name"].as_str().unwrap_or_default();
+    let key = reference["key"].as_str().unwrap_or_default();
+    match data.and_then(|mut data| data.remove(key)) {
+        Some(value) => Ok(Some(value)),
+        None if reference["optional"] == true => Ok(None),
+        None => bail!("couldn't find key {} in {} {}", key, kind, name),
+    }
+}
+
+// A ConfigMap's data, or None if it's optional and missing
+async fn config_map_data(storage: &Storage, namespace: &str, reference: &Value) -> Result<Option<BTreeMap<String, String>>> {
+    let name = reference["name"].as_str().unwrap_or_default();
+    let config_map = match storage.configmaps().get(namespace, name).await {
+        Ok(config_map) => config_map,
+        Err(_) if reference["optional"] == true => return Ok(None),
+        Err(_) => bail!("configmap {:?} not found", name),
+    };
+    let data = config_map["data"].as_object().into_iter().flatten();
+    Ok(Some(data.map(|(key, value)| (key.clone(), value.as_str().unwrap_or_default().to_string())).collect()))
+}
+
+// A Secret's data, decoded, or None if it's optional and missing
+async fn secret_data(storage: &Storage, namespace: &str, reference: &Value) -> Result<Option<BTreeMap<String, String>>> {
+    let name = reference["name"].as_str().unwrap_or_default();
+    let secret = match storage.secrets().get(namespace, name).await {
+        Ok(secret) => secret,
+        Err(_) if reference["optional"] == true => return Ok(None),
+        Err(_) => bail!("secret {:?} not found", name),
+    };
+    let mut data = BTreeMap::new();
+    for (key, value) in secret["data"].as_object().into_iter().flatten() {
+        let bytes = STANDARD.decode(value.as_str().unwrap_or_default()).unwrap_or_default();
+        data.insert(key.clone(), String::from_utf8_lossy(&bytes).into_owned());
+    }
+    Ok(Some(data))
+}
+
+// A resourceFieldRef's value: the limit or request of a container, in units
+// of its divisor, rounded up. Unset limits are what the node has, and unset
+// requests are the limits, as the API server defaults them.
+fn resource_field(pod: &Value, container: &Value, reference: &Value, allocatable: &Resources) -> Result<String> {
+    let container = match reference["containerName"].as_str().filter(|name| !name.is_empty()) {
+        Some(name) => pod["spec"]["containers"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .find(|c| c["name"] == name)
+            .with_context(|| format!("no container {} in the pod", name))?,
+        None => container,
+    };
+    let path = reference["resource"].as_str().unwrap_or_default();
+    let Some((kind, resource)) = path.split_once('.') else {
+        bail!("unsupported resource {:?}", path);
+    };
+    let resources = &container["resources"];
+    let set = match kind {
+        "requests" if !resources["requests"][resource].is_null() => &resources["requests"][resource],
+        "requests" | "limits" => &resources["limits"][resource],
+        _ => bail!("unsupported resource {:?}", path),
+    };
+    let divisor = &reference["divisor"];
+    let value = match resource {
+        "cpu" => {
+            let millis = quantity::cpu_millis(set).unwrap_or(if kind == "limits" { allocatable.cpu_millis } else { 0 });
+            let divisor = quantity::cpu_millis(divisor).filter(|d| *d > 0).unwrap_or(1000);
+            (millis as f64 / divisor as f64).ceil()
+        }
+        "memory" | "ephemeral-storage" => {
+            let bytes = match quantity::bytes(set) {
+                Some(bytes) => bytes,
+                None if kind == "requests" => 0,
+                None if resource == "memory" => allocatable.memory_bytes,
+                None => bail!("container has no ephemeral-storage limit"),
+            };
+            let divisor = quantity::bytes(divisor).filter(|d| *d > 0).unwrap_or(1);
+            (bytes as f64 / divisor as f64).ceil()
+        }
+        _ => bail!("unsupported resource {:?}", path),
+    };
+    Ok((value as i64).to_string())
+}
+
+// Sets a variable, replacing an earlier one of that name
+fn set(env: &mut Vec<(String, String)>, name: String, value: String) {
+    match env.iter_mut().find(|(n, _)| *n == name) {
+        Some(var) => var.1 = value,
+        None => env.push((name, value)),
+    }
+}
+
+// Whether a name can be an environment variable: printable ASCII, no `=`,
+// not starting with a digit
+fn is_env_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_graphic() && c != '=')
+}
@@ -0,0 +1,263 @@
+// Ephemeral storage accounting, as a kubelet's eviction manager does it. A
+// container uses its writable layer and its logs, an emptyDir volume what's
+// in it. A pod whose emptyDir outgrows its sizeLimit, or whose containers
+// outgrow their ephemeral-storage limits, each or all together, is evicted.
+// What each pod used last is kept for its node's stats summary.
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::collections::BTreeMap;
+use std::path::Path;
+use tracing::info;
+
+use super::kubelet;
+use crate::models::{quantity, time};
+use crate::storage::event_store::{self, ObjectReference};
+use crate::Storage;
+
+/// Bytes of ephemeral storage a pod uses.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct PodUsage {
+    pub containers: BTreeMap<String, ContainerUsage>,
+    /// What each emptyDir volume holds
+    pub volumes: BTreeMap<String, u64>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ContainerUsage {
+    /// The container's writable layer
+    pub rootfs: u64,
+    pub logs: u64,
+}
+
+impl ContainerUsage {
+    fn total(&self) -> u64 {
+        self.rootfs + self.logs
+    }
+}
+
+impl PodUsage {
+    /// Everything the pod uses on the node's disk. emptyDirs backed by
+    /// memory count against the pod's memory instead.
+    pub fn total(&self, spec: &Value) -> u64 {
+        let on_disk = |name: &String| {
+            !spec["volumes"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .any(|v| v["name"] == name.as_str() && v["emptyDir"]["medium"] == "Memory")
+        };
+        let containers: u64 = self.containers.values().map(ContainerUsage::total).sum();
+        let volumes: u64 = self.volumes.iter().filter(|(name, _)| on_disk(name)).map(|(_, used)| used).sum();
+        containers + volumes
+    }
+}
+
+/// Why a pod using `usage` has to be evicted, in the kubelet's words, or
+/// None if it stays within its limits.
+pub fn exceeded_limit(spec: &Value, usage: &PodUsage) -> Option<String> {
+    for volume in spec["volumes"].as_array().into_iter().flatten() {
+        let (Some(name), Some(size_limit)) = (volume["name"].as_str(), volume["emptyDir"].get("sizeLimit")) else {
+            continue;
+        };
+        let used = usage.volumes.get(name).copied().unwrap_or(0);
+        if quantity::bytes(size_limit).is_some_and(|limit| used as i64 > limit) {
+            return Some(format!(
+                "Usage of EmptyDir volume {:?} exceeds the limit {:?}. ",
+                name,
+                quantity_string(size_limit)
+            ));
+        }
+    }
+
+    let containers: Vec<&Value> = spec["containers"].as_array().into_iter().flatten().collect();
+    let limit_of = |container: &Value| quantity::bytes(&container["resources"]["limits"]["ephemeral-storage"]);
+    // The pod as a whole only has a limit when every container does
+    let pod_limit: Option<i64> = containers.iter().map(|c| limit_of(c)).sum();
+    if let Some(limit) = pod_limit.filter(|limit| usage.total(spec) as i64 > *limit) {
+        return Some(format!(
+            "Pod ephemeral local storage usage exceeds the total limit of containers {}. ",
+            format_bytes(limit)
+        ));
+    }
+
+    for container in containers {
+        let name = container["name"].as_str().unwrap_or_default();
+        let used = usage.containers.get(name).map(ContainerUsage::total).unwrap_or(0);
+        if limit_of(container).is_some_and(|limit| used as i64 > limit) {
+            return Some(format!(
+                "Container {} exceeded its local ephemeral storage limit {:?}. ",
+                name,
+                quantity_string(&container["resources"]["limits"]["ephemeral-storage"])
+            ));
+        }
+    }
+    None
+}
+
+/// Records what the pod uses and evicts it if that's over a limit.
+/// Returns the eviction message when it was evicted, so the caller can
+/// stop its containers.
+pub(crate) async fn enforce(storage: &Storage, node_name: &str, uid: &str, spec: &Value, usage: &PodUsage) -> Result<Option<String>> {
+    sqlx::query("INSERT OR REPLACE INTO pod_stats (uid, node_name, stats, updated) VALUES (?, ?, ?, ?)")
+        .bind(uid)
+        .bind(node_name)
+        .bind(serde_json::to_string(usage)?)
+        .bind(time::now())
+        .execute(&*storage.pool)
+        .await?;
+
+    let Some(message) = exceeded_limit(spec, usage) else {
+        return Ok(None);
+    };
+    evict(storage, node_name, uid, &message).await?;
+    Ok(Some(message))
+}
+
+/// Drops what was recorded for the node's pods that are gone.
+pub(crate) async fn forget_deleted(storage: &Storage, node_name: &str) -> Result<()> {
+    sqlx::query("DELETE FROM pod_stats WHERE node_name = ? AND uid NOT IN (SELECT uid FROM pods)")
+        .bind(node_name)
+        .execute(&*storage.pool)
+        .await?;
+    Ok(())
+}
+
+// Fails the pod with reason Evicted, marking it a disruption target as the
+// kubelet does, and records a Warning event
+async fn evict(storage: &Storage, node_name: &str, uid: &str, message: &str) -> Result<()> {
+    let Some(row) = sqlx::query("SELECT name, namespace, status FROM pods WHERE uid = ?")
+        .bind(uid)
+        .fetch_optional(&*storage.pool)
+        .await?
+    else {
+        return Ok(());
+    };
+    let name: String = row.get("name");
+    let namespace: String = row.get("namespace");
+    let status: Value = serde_json::from_str(&row.get::<String, _>("status"))?;
+    info!("Evicting pod {}/{}: {}", namespace, name, message);
+
+    let now = time::now();
+    let mut conditions: Vec<Value> = status["conditions"].as_array().cloned().unwrap_or_default();
+    conditions.retain(|condition| condition["type"] != "DisruptionTarget");
+    for condition in conditions.iter_mut() {
+        if condition["type"] == "Ready" || condition["type"] == "ContainersReady" {
+            condition["status"] = json!("False");
+            condition["lastTransitionTime"] = json!(now);
+            condition["reason"] = json!("PodFailed");
+            condition["message"] = json!("");
+        }
+    }
+    conditions.push(json!({
+        "type": "DisruptionTarget",
+        "status": "True",
+        "lastTransitionTime": now,
+        "reason": "TerminationByKubelet",
+        "message": message
+    }));
+
+    storage.pods().set_status_fields(uid, &[
+        ("phase", json!("Failed")),
+        ("reason", json!("Evicted")),
+        ("message", json!(message)),
+        ("conditions", json!(conditions)),
+    ]).await?;
+
+    let pod = ObjectReference::pod(&namespace, &name, uid);
+    kubelet::record_event(storage, node_name, &pod, event_store::WARNING, "Evicted", message).await?;
+    Ok(())
+}
+
+/// The node's stats summary, as a kubelet serves it at /stats/summary:
+/// the ephemeral storage of each pod running there, broken down by
+/// container and volume.
+pub async fn summary(storage: &Storage, node_name: &str) -> Result<Value> {
+    let rows = sqlx::query(
+        "SELECT pods.uid, pods.name, pods.namespace, pods.spec, pod_stats.stats, pod_stats.updated
+         FROM pods LEFT JOIN pod_stats ON pod_stats.uid = pods.uid
+         WHERE pods.node_name = ? AND pods.phase = 'Running' AND pods.deletion_timestamp IS NULL
+         ORDER BY pods.namespace, pods.name"
+    )
+    .bind(node_name)
+    .fetch_all(&*storage.pool)
+    .await?;
+
+    let mut pods = Vec::new();
+    for row in rows {
+        let spec: Value = serde_json::from_str(&row.get::<String, _>("spec"))?;
+        let usage: PodUsage = match row.get::<Option<String>, _>("stats") {
+            Some(stats) => serde_json::from_str(&stats)?,
+            None => PodUsage::default(),
+        };
+        let updated = row.get::<Option<String>, _>("updated").unwrap_or_else(time::now);
+
+        let containers: Vec<Value> = spec["containers"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|c| c["name"].as_str())
+            .map(|name| {
+                let used = usage.containers.get(name).cloned().unwrap_or_default();
+                json!({
+                    "name": name,
+                    "rootfs": { "time": updated, "usedBytes": used.rootfs },
+                    "logs": { "time": updated, "usedBytes": used.logs }
+                })
+            })
+            .collect();
+        let volumes: Vec<Value> = spec["volumes"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|v| v.get("emptyDir").is_some())
+            .filter_map(|v| v["name"].as_str())
+            .map(|name| json!({ "name": name, "time": updated, "usedBytes": usage.volumes.get(name).copied().unwrap_or(0) }))
+            .collect();
+
+        pods.push(json!({
+            "podRef": { "name": row.get::<String, _>("name"), "namespace": row.get::<String, _>("namespace"), "uid": row.get::<String, _>("uid") },
+            "containers": containers,
+            "volume": volumes,
+            "ephemeral-storage": { "time": updated, "usedBytes": usage.total(&spec) }
+        }));
+    }
+
+    Ok(json!({
+        "node": { "nodeName": node_name },
+        "pods": pods
+    }))
+}
+
+/// Bytes taken by the files under `path`, or 0 if it doesn't exist.
+pub fn dir_size(path: &Path) -> u64 {
+    let Ok(entries) = std::fs::read_dir(path) else {
+        return 0;
+    };
+    entries
+        .flatten()
+        .map(|entry| match entry.file_type() {
+            Ok(kind) if kind.is_dir() => dir_size(&entry.path()),
+            Ok(kind) if kind.is_file() => entry.metadata().map(|m| m.len()).unwrap_or(0),
+            _ => 0,
+        })
+        .sum()
+}
+
+fn quantity_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    }
+}
+
+// Bytes in the largest binary unit that divides them, like a Quantity prints
+fn format_bytes(bytes: i64) -> String {
+    for (suffix, unit) in [("Ti", 1i64 << 40), ("Gi", 1 << 30), ("Mi", 1 << 20), ("Ki", 1 << 10)] {
+        if bytes != 0 && bytes % unit == 0 {
+            return format!("{}{}", bytes / unit, suffix);
+        }
+    }
+    bytes.to_string()
+}
@@ -0,0 +1,402 @@
+// A kubelet stand-in that pretends to run pods without touching Docker, so
+// the API server and controllers can be exercised in-process by tests.
+use anyhow::Result;
+use chrono::{DateTime, Duration, Utc};
+use sqlx::Row;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Instant;
+use tokio::task::JoinHandle;
+use tracing::{error, info};
+
+use serde_json::Value;
+
+use super::cgroups::ContainerLimits;
+use super::dns;
+use super::ephemeral_storage::{self, ContainerUsage, PodUsage};
+use super::images::{self, Backoff, PullError};
+use super::kubelet::{
+    admit_pod, handle_container_exits, mark_stopped, mark_terminating, record_event, set_container_waiting, set_pod_phase, wait_for_bindings,
+    ContainerExit,
+};
+use super::lifecycle::{self, POST_START, PRE_STOP};
+use super::node_status::{Heartbeat, MemorySource};
+use super::security_profile::{self, Support};
+use crate::config::{DnsConfig, NODE_NAME};
+use crate::models::{quantity, time};
+use crate::profiling;
+use crate::storage::event_store::{self, ObjectReference};
+use crate::{Config, Storage};
+
+/// Makes every container of a running pod exit with the given code, so
+/// restart policies can be exercised without real containers.
+pub const EXIT_CODE_ANNOTATION: &str = "krust.io/fake-exit-code";
+
+/// Memory each of a running pod's containers pretends to use, as a quantity
+/// such as `512Mi`. Containers whose memory limit is lower are OOMKilled.
+pub const MEMORY_USAGE_ANNOTATION: &str = "krust.io/fake-memory-usage";
+
+/// Ephemeral storage a running pod pretends to use, as comma-separated
+/// `<container or emptyDir volume>=<quantity>`, e.g. `app=1Gi,cache=20Mi`.
+pub const STORAGE_USAGE_ANNOTATION: &str = "krust.io/fake-ephemeral-storage";
+
+/// CPU a running pod pretends to use, as a quantity such as `250m`. The HPA
+/// controller compares it with the pod's CPU requests.
+pub const CPU_USAGE_ANNOTATION: &str = "krust.io/fake-cpu-usage";
+
+/// How many seconds a pod's containers take to exit once sent SIGTERM. They
+/// exit at once without it, and are killed if the grace period ends first.
+pub const TERMINATION_SECONDS_ANNOTATION: &str = "krust.io/fake-termination-seconds";
+
+/// Makes pulling any of a pod's images fail with this message, so pods
+/// waiting with ErrImagePull and ImagePullBackOff can be exercised without a
+/// registry.
+pub const PULL_ERROR_ANNOTATION: &str = "krust.io/fake-image-pull-error";
+
+/// Starts a fake kubelet for every configured node other than the local one,
+/// which is what simulates them.
+pub fn spawn_simulated_nodes(storage: &Storage, config: &Config) -> Vec<JoinHandle<()>> {
+    config
+        .node_names()
+        .into_iter()
+        .filter(|name| name != NODE_NAME)
+        .map(|name| {
+            let kubelet = FakeKubelet::for_node(storage.clone(), config, &name);
+            tokio::spawn(async move {
+                if let Err(e) = kubelet.run().await {
+                    error!("Fake kubelet for node {} failed: {}", name, e);
+                }
+            })
+        })
+        .collect()
+}
+
+pub struct FakeKubelet {
+    storage: Storage,
+    node_name: String,
+    host_ip: String,
+    max_pods: usize,
+    // The kinds of security profile the node's runtime is made out to apply
+    security: Support,
+    // Image pulls that failed, and when to try them again
+    backoff: Backoff,
+    // Pods being stopped, and when their containers were sent SIGTERM once
+    // their preStop hooks had run
+    stopping: Mutex<HashMap<String, DateTime<Utc>>>,
+    dns: DnsConfig,
+    heartbeat: Heartbeat,
+}
+
+impl FakeKubelet {
+    pub fn new(storage: Storage, config: &Config) -> Self {
+        Self::for_node(storage, config, NODE_NAME)
+    }
+
+    /// A fake kubelet for one of the configured nodes.
+    pub fn for_node(storage: Storage, config: &Config, node_name: &str) -> Self {
+        Self {
+            node_name: node_name.to_string(),
+            host_ip: config.node(node_name).internal_ip,
+            max_pods: config.node(node_name).kubelet_max_pods(),
+            security: Support::from_kinds(&config.node(node_name).security_profiles),
+            backoff: Backoff::default(),
+            stopping: Mutex::default(),
+            dns: config.dns.clone(),
+            heartbeat: Heartbeat::new(storage.clone(), config, node_name, MemorySource::Simulated),
+            storage,
+        }
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        info!("Starting fake kubelet for node {}", self.node_name);
+
+        // The node's heartbeats go on for as long as its kubelet does
+        tokio::select! {
+            result = self.sync_loop() => result,
+            _ = self.heartbeat.clone().run() => Ok(()),
+        }
+    }
+
+    async fn sync_loop(&self) -> Result<()> {
+        let name = format!("fake kubelet {}", self.node_name);
+        let mut changes = self.storage.watch().changes("pods");
+        let mut bound = HashSet::new();
+        loop {
+            let started = Instant::now();
+            if let Err(e) = self.sync_pods().await {
+                error!("Fake kubelet sync error: {}", e);
+            }
+            profiling::record(&name, started);
+
+            wait_for_bindings(&mut changes, &self.node_name, &mut bound, std::time::Duration::from_millis(500)).await;
+        }
+    }
+
+    async fn sync_pods(&self) -> Result<()> {
+        // Every pod bound to this node starts successfully once it has its
+        // images. They're all already present, but are pulled all the same
+        // when their imagePullPolicy is Always.
+        let rows = sqlx::query(
+            "SELECT uid, name, namespace, spec, annotations FROM pods 
+             WHERE node_name = ? AND phase = 'Pending' 
+             AND deletion_timestamp IS NULL
+             ORDER BY creation_timestamp, rowid"
+        )
+        .bind(&self.node_name)
+        .fetch_all(&*self.storage.pool)
+        .await?;
+
+        for row in rows {
+            let uid: String = row.get("uid");
+            if !admit_pod(&self.storage, &self.node_name, self.max_pods, &uid).await? {
+                continue;
+            }
+
+            let spec: Value = serde_json::from_str(&row.get::<String, _>("spec"))?;
+            let annotations: Value = serde_json::from_str(&row.get::<String, _>("annotations"))?;
+            let pod = ObjectReference::pod(&row.get::<String, _>("namespace"), &row.get::<String, _>("name"), &uid);
+            // A pod whose hostname can't be set fails as its sandbox would
+            let namespace = pod.namespace.as_deref().unwrap_or_default();
+            if let Err(e) = dns::container_hostname(&pod.name, &spec, namespace, &self.dns) {
+                let message = format!("Failed to create pod sandbox: {:#}", e);
+                record_event(&self.storage, &self.node_name, &pod, event_store::WARNING, "FailedCreatePodSandBox", &message).await?;
+                set_pod_phase(&self.storage, &uid, "Failed", &self.host_ip).await?;
+                continue;
+            }
+
+            let containers: Vec<&Value> = spec["containers"].as_array().into_iter().flatten().filter(|c| c["name"].is_string()).collect();
+            let mut waiting = None;
+            for container in &containers {
+                let reference = pod.clone().container(container["name"].as_str().unwrap_or_default());
+                let pull_error = annotations[PULL_ERROR_ANNOTATION].as_str();
+                let pull = async move {
+                    match pull_error {
+                        Some(message) => Err(anyhow::anyhow!("{}", message)),
+                        None => Ok(()),
+                    }
+                };
+                if let Err(e) = images::ensure(&self.storage, &self.node_name, &reference, container, true, &self.backoff, pull).await {
+                    waiting = Some(e);
+                    break;
+                }
+            }
+            if let Some(e) = waiting {
+                let pull = e.downcast::<PullError>()?;
+                set_container_waiting(&self.storage, &uid, &pull.container, pull.reason, &pull.message).await?;
+                continue;
+            }
+
+            set_pod_phase(&self.storage, &uid, "Running", &self.host_ip).await?;
+            for container in &containers {
+                let name = container["name"].as_str().unwrap_or_default();
+                let reference = pod.clone().container(name);
+                for (reason, message) in [("Created", format!("Created container {}", name)), ("Started", format!("Started container {}", name))] {
+                    record_event(&self.storage, &self.node_name, &reference, event_store::NORMAL, reason, &message).await?;
+                }
+            }
+
+            // postStart hooks GET for real, from the node's address as
+            // simulated pods have none of their own, but their commands have
+            // no container to run in and just succeed. A container whose
+            // hook fails is killed.
+            let mut killed = Vec::new();
+            for container in &containers {
+                let name = container["name"].as_str().unwrap_or_default();
+                let reference = pod.clone().container(name);
+                let exec = |_| async { Ok((0, String::new())) };
+                if let Some(message) = lifecycle::run(&reference, container, POST_START, &self.host_ip, lifecycle::POST_START_TIMEOUT, exec).await {
+                    lifecycle::report_failure(&self.storage, &self.node_name, &reference, POST_START, &message).await?;
+                    record_event(&self.storage, &self.node_name, &reference, event_store::NORMAL, "Killing", "FailedPostStartHook").await?;
+                    killed.push(ContainerExit { name: name.to_string(), exit_code: 137, oom_killed: false });
+                }
+            }
+            if !killed.is_empty() {
+                handle_container_exits(&self.storage, &uid, &killed).await?;
+            }
+
+            let (mut requested, mut unapplied) = (false, Vec::new());
+            for index in 0..spec["containers"].as_array().map(Vec::len).unwrap_or(0) {
+                let profiles = security_profile::container_profiles(&spec, &annotations, index).unwrap_or_default();
+                requested |= !profiles.is_empty();
+                unapplied.extend(security_profile::unapplied(&profiles, self.security));
+            }
+            security_profile::report(&self.storage, &self.node_name, &pod, requested, &unapplied).await?;
+        }
+
+        // Containers of pods asking for it exit on the next sync, and those
+        // using more memory than their limit are OOMKilled
+        let rows = sqlx::query(
+            "SELECT uid, spec, status, annotations FROM pods 
+             WHERE node_name = ? AND phase = 'Running' AND deletion_timestamp IS NULL 
+             AND (annotations LIKE ? OR annotations LIKE ?)"
+        )
+        .bind(&self.node_name)
+        .bind(format!("%{}%", EXIT_CODE_ANNOTATION))
+        .bind(format!("%{}%", MEMORY_USAGE_ANNOTATION))
+        .fetch_all(&*self.storage.pool)
+        .await?;
+
+        for row in rows {
+            let uid: String = row.get("uid");
+            let spec: Value = serde_json::from_str(&row.get::<String, _>("spec"))?;
+            let status: Value = serde_json::from_str(&row.get::<String, _>("status"))?;
+            let annotations: Value = serde_json::from_str(&row.get::<String, _>("annotations"))?;
+
+            let exit_code = annotations[EXIT_CODE_ANNOTATION].as_str().and_then(|code| code.parse::<i64>().ok());
+            let memory = annotations[MEMORY_USAGE_ANNOTATION].as_str().and_then(quantity::parse);
+            let exits: Vec<ContainerExit> = spec["containers"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter(|c| {
+                    !status["containerStatuses"]
+                        .as_array()
+                        .into_iter()
+                        .flatten()
+                        .any(|cs| cs["name"] == c["name"] && !cs["state"]["terminated"].is_null())
+                })
+                .filter_map(|c| {
+                    let name = c["name"].as_str()?.to_string();
+                    let limit = ContainerLimits::from_container(c).memory;
+                    match (memory, limit) {
+                        // Killed with SIGKILL, as the kernel's OOM killer does
+                        (Some(used), Some(limit)) if used > limit as f64 => Some(ContainerExit { name, exit_code: 137, oom_killed: true }),
+                        _ => exit_code.map(|exit_code| ContainerExit { name, exit_code, oom_killed: false }),
+                    }
+                })
+                .collect();
+            if exits.is_empty() {
+                continue;
+            }
+
+            // Restarted containers are simply "running" again
+            handle_container_exits(&self.storage, &uid, &exits).await?;
+        }
+
+        // Pods claiming to use storage are held to their limits
+        let rows = sqlx::query(
+            "SELECT uid, spec, annotations FROM pods 
+             WHERE node_name = ? AND phase = 'Running' AND deletion_timestamp IS NULL 
+             AND annotations LIKE ?"
+        )
+        .bind(&self.node_name)
+        .bind(format!("%{}%", STORAGE_USAGE_ANNOTATION))
+        .fetch_all(&*self.storage.pool)
+        .await?;
+
+        for row in rows {
+            let uid: String = row.get("uid");
+            let spec: Value = serde_json::from_str(&row.get::<String, _>("spec"))?;
+            let annotations: Value = serde_json::from_str(&row.get::<String, _>("annotations"))?;
+            let Some(usage) = annotations[STORAGE_USAGE_ANNOTATION].as_str() else {
+                continue;
+            };
+            let usage = simulated_usage(&spec, usage);
+            ephemeral_storage::enforce(&self.storage, &self.node_name, &uid, &spec, &usage).await?;
+        }
+
+        // Pods being deleted run their preStop hooks, then stop when their
+        // containers would have exited after SIGTERM, or their grace period
+        // ends
+        let finalizers = self.storage.finalizers();
+        let deleting = finalizers.pods_in_grace_period(&self.node_name).await?;
+        self.stopping.lock().unwrap().retain(|uid, _| deleting.iter().any(|(_, _, kept)| kept.uid == *uid));
+        for (namespace, name, kept) in deleting {
+            let Some(deadline) = kept.deletion_timestamp.as_deref().and_then(time::parse) else {
+                continue;
+            };
+            let pod = self.storage.pods().get(&namespace, &name).await?;
+            let sent_sigterm = self.stopping.lock().unwrap().get(&kept.uid).copied();
+            let sent_sigterm = match sent_sigterm {
+                Some(at) => at,
+                None => {
+                    self.pre_stop(&pod, deadline).await?;
+                    let now = Utc::now();
+                    self.stopping.lock().unwrap().insert(kept.uid.clone(), now);
+                    now
+                }
+            };
+            let exit_after = pod["metadata"]["annotations"][TERMINATION_SECONDS_ANNOTATION]
+                .as_str()
+                .and_then(|seconds| seconds.parse().ok())
+                .map(Duration::seconds)
+                .unwrap_or_else(Duration::zero);
+            let exited = sent_sigterm + exit_after;
+            if Utc::now() >= exited.min(deadline) {
+                // Containers still running when the grace period ends are
+                // killed
+                let exit_code = if exited <= deadline { 0 } else { 137 };
+                let exits: Vec<ContainerExit> = pod["status"]["containerStatuses"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .filter(|cs| cs["state"]["running"].is_object())
+                    .filter_map(|cs| Some(ContainerExit { name: cs["name"].as_str()?.to_string(), exit_code, oom_killed: false }))
+                    .collect();
+                mark_stopped(&self.storage, &kept.uid, &exits).await?;
+                finalizers.end_grace_period(&namespace, &name).await?;
+            }
+        }
+
+        // There are no containers to stop, so deleted pods go away immediately
+        sqlx::query("DELETE FROM pods WHERE node_name = ? AND deletion_timestamp IS NOT NULL")
+            .bind(&self.node_name)
+            .execute(&*self.storage.pool)
+            .await?;
+        ephemeral_storage::forget_deleted(&self.storage, &self.node_name).await?;
+
+        Ok(())
+    }
+
+    // Reports the pod as stopping and runs its running containers' preStop
+    // hooks within its grace period, GETs for real and commands as if they
+    // succeeded
+    async fn pre_stop(&self, pod: &Value, deadline: DateTime<Utc>) -> Result<()> {
+        let uid = pod["metadata"]["uid"].as_str().unwrap_or_default();
+        let namespace = pod["metadata"]["namespace"].as_str().unwrap_or("default");
+        let name = pod["metadata"]["name"].as_str().unwrap_or_default();
+        mark_terminating(&self.storage, uid).await?;
+
+        let running = |container: &&Value| {
+            pod["status"]["containerStatuses"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .any(|cs| cs["name"] == container["name"] && cs["state"]["running"].is_object())
+        };
+        for container in pod["spec"]["containers"].as_array().into_iter().flatten().filter(running) {
+            let reference = ObjectReference::pod(namespace, name, uid).container(container["name"].as_str().unwrap_or_default());
+            let message = format!("Stopping container {}", container["name"].as_str().unwrap_or_default());
+            record_event(&self.storage, &self.node_name, &reference, event_store::NORMAL, "Killing", &message).await?;
+            let grace = (deadline - Utc::now()).to_std().unwrap_or_default();
+            let exec = |_| async { Ok((0, String::new())) };
+            if let Some(message) = lifecycle::run(&reference, container, PRE_STOP, &self.host_ip, grace, exec).await {
+                lifecycle::report_failure(&self.storage, &self.node_name, &reference, PRE_STOP, &message).await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// The usage a STORAGE_USAGE_ANNOTATION describes. Containers' usage is all
+// in their writable layer; unknown names and bad quantities are skipped.
+fn simulated_usage(spec: &Value, annotation: &str) -> PodUsage {
+    let named = |list: &str, name: &str| {
+        spec[list].as_array().into_iter().flatten().any(|item| item["name"] == name)
+    };
+    let mut usage = PodUsage::default();
+    for entry in annotation.split(',') {
+        let Some((name, bytes)) = entry.split_once('=') else {
+            continue;
+        };
+        let (name, Some(bytes)) = (name.trim(), crate::models::quantity::parse(bytes)) else {
+            continue;
+        };
+        if named("containers", name) {
+            usage.containers.insert(name.to_string(), ContainerUsage { rootfs: bytes as u64, logs: 0 });
+        } else if named("volumes", name) {
+            usage.volumes.insert(name.to_string(), bytes as u64);
+        }
+    }
+    usage
+}
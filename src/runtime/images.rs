@@ -0,0 +1,222 @@
+// Container images, as the kubelet gets them before creating a container.
+// imagePullPolicy says whether to pull: Always does every time, IfNotPresent
+// only when the node lacks the image and Never not at all, and it defaults
+// to Always for images tagged `latest` or untagged and IfNotPresent for the
+// rest. Private registries are logged into with the dockerconfigjson (or
+// dockercfg) Secrets the pod's imagePullSecrets name, or its ServiceAccount's
+// when it names none. A pull that fails is retried after a back-off that
+// doubles from 10s up to 5 minutes, the pod's container waiting in the
+// meantime with ErrImagePull and then ImagePullBackOff.
+use anyhow::Result;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bollard::auth::DockerCredentials;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use super::kubelet::record_event;
+use crate::storage::event_store::{self, ObjectReference};
+use crate::Storage;
+
+const INITIAL_BACKOFF: Duration = Duration::from_secs(10);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+/// Why a container's image isn't there to start it from.
+#[derive(Debug)]
+pub struct PullError {
+    pub container: String,
+    /// ErrImagePull, ImagePullBackOff or ErrImageNeverPull, as the
+    /// container's waiting reason says.
+    pub reason: &'static str,
+    pub message: String,
+}
+
+impl std::fmt::Display for PullError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.reason, self.message)
+    }
+}
+
+impl std::error::Error for PullError {}
+
+/// The pulls that failed lately, and when each may be tried again.
+#[derive(Default)]
+pub struct Backoff {
+    failures: Mutex<HashMap<String, (Instant, Duration)>>,
+}
+
+impl Backoff {
+    fn waiting(&self, key: &str) -> bool {
+        let failures = self.failures.lock().unwrap();
+        failures.get(key).is_some_and(|(until, _)| Instant::now() < *until)
+    }
+
+    fn failed(&self, key: &str) {
+        let mut failures = self.failures.lock().unwrap();
+        let delay = match failures.get(key) {
+            Some((_, delay)) => (*delay * 2).min(MAX_BACKOFF),
+            None => INITIAL_BACKOFF,
+        };
+        failures.insert(key.to_string(), (Instant::now() + delay, delay));
+    }
+
+    fn succeeded(&self, key: &str) {
+        self.failures.lock().unwrap().remove(key);
+    }
+}
+
+/// The container's imagePullPolicy, or what it defaults to for its image.
+pub fn pull_policy(container: &Value) -> &str {
+    if let Some(policy) = container["imagePullPolicy"].as_str().filter(|p| !p.is_empty()) {
+        return policy;
+    }
+    let image = container["image"].as_str().unwrap_or_default();
+    match split(image) {
+        (_, "latest") if !image.contains('@') => "Always",
+        _ => "IfNotPresent",
+    }
+}
+
+/// An image's repository and its tag or digest, `latest` if it has neither,
+/// as Docker pulls it by.
+pub fn split(image: &str) -> (&str, &str) {
+    if let Some((repository, digest)) = image.split_once('@') {
+        return (repository, digest);
+    }
+    // A colon before the last slash is a registry's port
+    let name_start = image.rfind('/').map(|i| i + 1).unwrap_or(0);
+    match image[name_start..].rfind(':') {
+        Some(i) => (&image[..name_start + i], &image[name_start + i + 1..]),
+        None => (image, "latest"),
+    }
+}
+
+/// The registry an image is pulled from, `docker.io` unless its name starts
+/// with a host.
+pub fn registry(image: &str) -> &str {
+    match image.split_once('/') {
+        Some((host, _)) if host.contains('.') || host.contains(':') || host == "localhost" => host,
+        _ => "docker.io",
+    }
+}
+
+/// The credentials to pull `image` for the pod with, from the first of its
+/// imagePullSecrets (or its ServiceAccount's) with some for the image's
+/// registry. Secrets that are missing are passed over, as the kubelet does.
+pub async fn credentials(storage: &Storage, pod: &Value, image: &str) -> Result<Option<DockerCredentials>> {
+    let namespace = pod["metadata"]["namespace"].as_str().unwrap_or("default");
+    let mut secrets = pod["spec"]["imagePullSecrets"].as_array().cloned().unwrap_or_default();
+    if secrets.is_empty() {
+        let account = pod["spec"]["serviceAccountName"].as_str().unwrap_or("default");
+        if let Some(account) = storage.serviceaccounts().get(namespace, account).await? {
+            secrets = account["imagePullSecrets"].as_array().cloned().unwrap_or_default();
+        }
+    }
+
+    let registry = registry(image);
+    for reference in &secrets {
+        let name = reference["name"].as_str().unwrap_or_default();
+        let Ok(secret) = storage.secrets().get(namespace, name).await else {
+            continue;
+        };
+        if let Some(credentials) = docker_config_auths(&secret).and_then(|auths| registry_credentials(&auths, registry)) {
+            return Ok(Some(credentials));
+        }
+    }
+    Ok(None)
+}
+
+/// Makes sure the node has the container's image as its imagePullPolicy
+/// says, `present` being whether it already does, pulling it with `pull`
+/// when it should and recording how that went as the container's events.
+/// Fails with a PullError when the image can't be had, without pulling
+/// while a failed pull's back-off lasts.
+pub(crate) async fn ensure(
+    storage: &Storage,
+    node_name: &str,
+    reference: &ObjectReference,
+    container: &Value,
+    present: bool,
+    backoff: &Backoff,
+    pull: impl Future<Output = Result<()>>,
+) -> Result<()> {
+    let name = container["name"].as_str().unwrap_or("container");
+    let image = container["image"].as_str().unwrap_or_default();
+    let policy = pull_policy(container);
+    let fail = |reason, message: String| PullError { container: name.to_string(), reason, message };
+
+    let error = if policy == "Never" || (policy == "IfNotPresent" && present) {
+        if present {
+            let message = format!("Container image \"{}\" already present on machine", image);
+            return record_event(storage, node_name, reference, event_store::NORMAL, "Pulled", &message).await;
+        }
+        let message = format!("Container image \"{}\" is not present with pull policy of Never", image);
+        record_event(storage, node_name, reference, event_store::WARNING, "ErrImageNeverPull", &message).await?;
+        fail("ErrImageNeverPull", message)
+    } else {
+        let key = format!("{}/{}", reference.uid, image);
+        if backoff.waiting(&key) {
+            let message = format!("Back-off pulling image \"{}\"", image);
+            record_event(storage, node_name, reference, event_store::NORMAL, "BackOff", &message).await?;
+            fail("ImagePullBackOff", message)
+        } else {
+            record_event(storage, node_name, reference, event_store::NORMAL, "Pulling", &format!("Pulling image \"{}\"", image)).await?;
+            let started = Instant::now();
+            match pull.await {
+                Ok(()) => {
+                    backoff.succeeded(&key);
+                    let message = format!("Successfully pulled image \"{}\" in {:.3}s", image, started.elapsed().as_secs_f64());
+                    return record_event(storage, node_name, reference, event_store::NORMAL, "Pulled", &message).await;
+                }
+                Err(e) => {
+                    backoff.failed(&key);
+                    let message = format!("Failed to pull image \"{}\": {:#}", image, e);
+                    record_event(storage, node_name, reference, event_store::WARNING, "Failed", &message).await?;
+                    fail("ErrImagePull", message)
+                }
+            }
+        }
+    };
+    record_event(storage, node_name, reference, event_store::WARNING, "Failed", &format!("Error: {}", error.reason)).await?;
+    Err(error.into())
+}
+
+// The "auths" of a dockerconfigjson or dockercfg Secret
+fn docker_config_auths(secret: &Value) -> Option<Value> {
+    let (key, nested) = match secret["type"].as_str() {
+        Some("kubernetes.io/dockerconfigjson") => (".dockerconfigjson", true),
+        Some("kubernetes.io/dockercfg") => (".dockercfg", false),
+        _ => return None,
+    };
+    let bytes = STANDARD.decode(secret["data"][key].as_str()?).ok()?;
+    let config: Value = serde_json::from_slice(&bytes).ok()?;
+    Some(if nested { config["auths"].clone() } else { config })
+}
+
+// The credentials in a Docker config's auths for a registry, whose keys may
+// be URLs such as https://index.docker.io/v1/
+fn registry_credentials(auths: &Value, registry: &str) -> Option<DockerCredentials> {
+    const DOCKER_HUB: &[&str] = &["docker.io", "index.docker.io", "registry-1.docker.io"];
+    let (_, entry) = auths.as_object()?.iter().find(|(key, _)| {
+        let host = key.trim_start_matches("https://").trim_start_matches("http://");
+        let host = host.split('/').next().unwrap_or_default();
+        host == registry || (DOCKER_HUB.contains(&host) && DOCKER_HUB.contains(&registry))
+    })?;
+
+    let (mut username, mut password) = (entry["username"].as_str().map(str::to_string), entry["password"].as_str().map(str::to_string));
+    if let Some(auth) = entry["auth"].as_str().and_then(|auth| STANDARD.decode(auth).ok()) {
+        if let Some((user, pass)) = String::from_utf8_lossy(&auth).split_once(':') {
+            username = Some(user.to_string());
+            password = Some(pass.to_string());
+        }
+    }
+    Some(DockerCredentials {
+        username,
+        password,
+        serveraddress: Some(registry.to_string()),
+        ..Default::default()
+    })
+}
@@ -1,60 +1,135 @@
 use anyhow::Result;
 use bollard::{
-    container::{Config, CreateContainerOptions, StartContainerOptions},
+    auth::DockerCredentials,
+    container::{Config, CreateContainerOptions, NetworkingConfig, StartContainerOptions, StopContainerOptions},
+    exec::{CreateExecOptions, StartExecResults},
+    service::{ContainerSummary, EndpointSettings},
     Docker,
 };
+use chrono::Utc;
 use serde_json::{json, Value};
 use sqlx::Row;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tracing::{error, info};
 
+use super::cgroups::ContainerLimits;
+use super::dns::{self, Resolver};
+use super::env;
+use super::ephemeral_storage::{self, ContainerUsage, PodUsage};
+use super::images::{self, Backoff, PullError};
+use super::lifecycle::{self, POST_START, PRE_STOP};
+use super::node_status::{Heartbeat, MemorySource};
+use super::pod_network;
+use super::security_profile::{self, Support};
+use super::volumes;
+use crate::config::{DnsConfig, NODE_NAME};
+use crate::data_dir::DataDir;
+use crate::profiling;
+use crate::storage::event_store::{self, EventSource, ObjectReference};
+use crate::storage::watch_store::Changes;
 use crate::Storage;
+use crate::models::quantity::Resources;
+use crate::models::time;
 
 pub struct Kubelet {
     storage: Storage,
     docker: Docker,
     node_name: String,
+    host_ip: String,
+    max_pods: usize,
+    // What resourceFieldRefs to unset limits resolve to
+    allocatable: Resources,
+    dns: DnsConfig,
+    data_dir: Option<DataDir>,
+    // The kinds of security profile Docker can apply
+    security: Support,
+    // Uids of the pods being deleted whose containers are being stopped
+    stopping: Arc<Mutex<HashSet<String>>>,
+    // Image pulls that failed, and when to try them again
+    backoff: Backoff,
+    heartbeat: Heartbeat,
 }
 
 impl Kubelet {
-    pub async fn new(storage: Storage) -> Result<Self> {
+    pub async fn new(storage: Storage, config: &crate::Config) -> Result<Self> {
         let docker = Docker::connect_with_local_defaults()?;
         
         // Test Docker connection
         docker.ping().await?;
         info!("Connected to Docker daemon");
+        pod_network::ensure(&docker).await?;
+
+        let security = Support::from_docker(&docker.info().await?.security_options.unwrap_or_default());
+        info!("Docker applies seccomp profiles: {}, AppArmor profiles: {}", security.seccomp, security.apparmor);
+        let heartbeat = Heartbeat::new(storage.clone(), config, NODE_NAME, MemorySource::Host);
         
         Ok(Self {
             storage,
             docker,
-            node_name: "krust-node".to_string(),
+            node_name: NODE_NAME.to_string(),
+            host_ip: config.node(NODE_NAME).internal_ip,
+            max_pods: config.node(NODE_NAME).kubelet_max_pods(),
+            allocatable: config.node(NODE_NAME).capacity(),
+            dns: config.dns.clone(),
+            data_dir: config.data_dir(),
+            security,
+            stopping: Arc::default(),
+            backoff: Backoff::default(),
+            heartbeat,
         })
     }
 
     pub async fn run(&self) -> Result<()> {
         info!("Starting kubelet");
+
+        // The node's heartbeats go on for as long as the kubelet does
+        tokio::select! {
+            result = self.sync_loop() => result,
+            _ = self.heartbeat.clone().run() => Ok(()),
+        }
+    }
+
+    async fn sync_loop(&self) -> Result<()> {        let mut changes = self.storage.watch().changes("pods");
+        let mut bound = HashSet::new();
         
         loop {
+            let started = Instant::now();
+
             // Process scheduled pods
             if let Err(e) = self.sync_pods().await {
                 error!("Kubelet sync error: {}", e);
             }
+
+            // Stop pods being deleted, within their grace periods
+            if let Err(e) = self.stop_deleted_pods().await {
+                error!("Pod termination error: {}", e);
+            }
             
             // Update pod statuses
             if let Err(e) = self.update_pod_statuses().await {
                 error!("Status update error: {}", e);
             }
+
+            // Rewrite projected volumes, rotating tokens that are due
+            if let Err(e) = self.refresh_projected_volumes().await {
+                error!("Projected volume refresh error: {}", e);
+            }
+            profiling::record("kubelet", started);
             
-            tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+            wait_for_bindings(&mut changes, &self.node_name, &mut bound, Duration::from_secs(2)).await;
         }
     }
 
     async fn sync_pods(&self) -> Result<()> {
         // Find pods scheduled to this node that aren't running yet
         let rows = sqlx::query(
-            "SELECT uid, name, namespace, spec FROM pods 
-             WHERE node_name = ? AND phase IN ('Scheduled', 'Pending') 
-             AND deletion_timestamp IS NULL"
+            "SELECT uid, name, namespace, spec, labels, annotations FROM pods 
+             WHERE node_name = ? AND phase = 'Pending' 
+             AND deletion_timestamp IS NULL
+             ORDER BY creation_timestamp, rowid"
         )
         .bind(&self.node_name)
         .fetch_all(&*self.storage.pool)
@@ -66,10 +141,23 @@ impl Kubelet {
             let namespace: String = row.get("namespace");
             let spec_str: String = row.get("spec");
             let spec: Value = serde_json::from_str(&spec_str)?;
+            let labels: Value = serde_json::from_str(&row.get::<Option<String>, _>("labels").unwrap_or_default()).unwrap_or_default();
+            let annotations: Value = serde_json::from_str(&row.get::<String, _>("annotations"))?;
+            
+            if !admit_pod(&self.storage, &self.node_name, self.max_pods, &uid).await? {
+                continue;
+            }
             
             info!("Starting pod {}/{}", namespace, name);
             
-            if let Err(e) = self.start_pod(&uid, &name, &namespace, &spec).await {
+            if let Err(e) = self.start_pod(&uid, &name, &namespace, &spec, &labels, &annotations).await {
+                // A container whose image can't be pulled yet waits for it,
+                // leaving the pod Pending to be tried again
+                if let Some(pull) = e.downcast_ref::<PullError>() {
+                    info!("Pod {}/{} is waiting for its image: {}", namespace, name, pull);
+                    set_container_waiting(&self.storage, &uid, &pull.container, pull.reason, &pull.message).await?;
+                    continue;
+                }
                 error!("Failed to start pod {}/{}: {}", namespace, name, e);
                 // Update pod status to Failed
                 self.update_pod_phase(&uid, "Failed").await?;
@@ -82,10 +170,42 @@ impl Kubelet {
         Ok(())
     }
 
-    async fn start_pod(&self, uid: &str, name: &str, namespace: &str, spec: &Value) -> Result<()> {
+    async fn start_pod(&self, uid: &str, name: &str, namespace: &str, spec: &Value, labels: &Value, annotations: &Value) -> Result<()> {
+        let resolv_conf = self.write_resolv_conf(uid, namespace, spec)?;
+        // The pod as its containers see it through the downward API, with
+        // the address its first container is about to be given
+        let pod_ip = pod_network::assign(&self.storage, uid, spec, &self.host_ip).await?;
+        let mut pod = json!({
+            "metadata": { "uid": uid, "name": name, "namespace": namespace, "labels": labels, "annotations": annotations },
+            "spec": spec,
+            "status": { "podIP": pod_ip, "podIPs": [{ "ip": pod_ip }], "hostIP": self.host_ip, "hostIPs": [{ "ip": self.host_ip }] }
+        });
+        if !pod["spec"]["nodeName"].is_string() {
+            pod["spec"]["nodeName"] = json!(self.node_name);
+        }
+        let volumes = match volumes::prepare(&self.storage, &pod, &self.pod_dir(uid)).await {
+            Ok(volumes) => volumes,
+            Err(e) => {
+                let reference = ObjectReference::pod(namespace, name, uid);
+                self.record_event(&reference, event_store::WARNING, "FailedMount", &format!("{:#}", e)).await?;
+                return Err(e);
+            }
+        };
+        let (hostname, domainname) = match dns::container_hostname(name, spec, namespace, &self.dns) {
+            Ok(names) => names,
+            Err(e) => {
+                let reference = ObjectReference::pod(namespace, name, uid);
+                let message = format!("Failed to create pod sandbox: {:#}", e);
+                self.record_event(&reference, event_store::WARNING, "FailedCreatePodSandBox", &message).await?;
+                return Err(e);
+            }
+        };
+        let mut profiles_requested = false;
+        let mut unapplied = Vec::new();
+
         // Process each container in the pod
         if let Some(containers) = spec["containers"].as_array() {
-            for container in containers {
+            for (index, container) in containers.iter().enumerate() {
                 let container_name = container["name"]
                     .as_str()
                     .unwrap_or("container");
@@ -93,8 +213,7 @@ impl Kubelet {
                     .as_str()
                     .ok_or_else(|| anyhow::anyhow!("Container image is required"))?;
                 
-                let full_container_name = format!("k8s_{}_{}_{}_{}", 
-                    container_name, name, namespace, uid);
+                let full_container_name = docker_container_name(container_name, name, namespace, uid);
                 
                 // Check if container already exists
                 if self.container_exists(&full_container_name).await {
@@ -102,17 +221,21 @@ impl Kubelet {
                     continue;
                 }
                 
-                // Pull image if not present
-                info!("Pulling image {} if needed...", image);
-                if let Err(e) = self.pull_image(image).await {
-                    error!("Failed to pull image {}: {}", image, e);
-                    return Err(anyhow::anyhow!("Failed to pull image: {}", e));
-                }
+                // Pull the image as the container's imagePullPolicy says,
+                // with the pod's registry credentials
+                let reference = ObjectReference::pod(namespace, name, uid).container(container_name);
+                let present = self.docker.inspect_image(image).await.is_ok();
+                let pull = async {
+                    let credentials = images::credentials(&self.storage, &pod, image).await?;
+                    self.pull_image(image, credentials).await
+                };
+                images::ensure(&self.storage, &self.node_name, &reference, container, present, &self.backoff, pull).await?;
                 
                 // Create container config
                 let mut config = Config {
                     image: Some(image.to_string()),
-                    hostname: Some(name.to_string()),
+                    hostname: Some(hostname.clone()),
+                    domainname: domainname.clone(),
                     labels: Some(HashMap::from([
                         ("io.kubernetes.pod.name".to_string(), name.to_string()),
                         ("io.kubernetes.pod.namespace".to_string(), namespace.to_string()),
@@ -121,18 +244,42 @@ impl Kubelet {
                     ])),
                     ..Default::default()
                 };
+
+                let (mut binds, tmpfs) = volumes::mounts(container, &volumes)?;
+                binds.extend(resolv_conf.iter().map(|path| format!("{}:/etc/resolv.conf:ro", path.display())));
+
+                // Seccomp and AppArmor profiles, as far as Docker can apply them
+                let profiles = security_profile::container_profiles(spec, annotations, index)?;
+                let security_opt = security_profile::docker_options(&profiles, self.security, &self.seccomp_root())?;
+                profiles_requested |= !profiles.is_empty();
+                unapplied.extend(security_profile::unapplied(&profiles, self.security));
+
+                // hostNetwork pods run in the host's network namespace and
+                // others on the pod network, and each container is held to
+                // its CPU and memory limits
+                let host_network = spec["hostNetwork"].as_bool().unwrap_or(false);
+                if !host_network {
+                    let endpoints_config = pod_network::endpoints((index == 0).then_some(pod_ip.as_str()));
+                    config.networking_config = Some(NetworkingConfig { endpoints_config });
+                }
+                config.host_config = Some(bollard::service::HostConfig {
+                    network_mode: Some(if host_network { "host" } else { pod_network::NETWORK }.to_string()),
+                    binds: (!binds.is_empty()).then_some(binds),
+                    tmpfs: (!tmpfs.is_empty()).then_some(tmpfs),
+                    security_opt: (!security_opt.is_empty()).then_some(security_opt),
+                    ..ContainerLimits::from_container(container).host_config()
+                });
                 
-                // Add environment variables
-                if let Some(env_vars) = container["env"].as_array() {
-                    let mut env = Vec::new();
-                    for var in env_vars {
-                        if let (Some(name), Some(value)) = 
-                            (var["name"].as_str(), var["value"].as_str()) {
-                            env.push(format!("{}={}", name, value));
-                        }
+                // Add environment variables, from ConfigMaps, Secrets and
+                // the downward API as well as given
+                let env = match env::resolve(&self.storage, &pod, container, &self.allocatable).await {
+                    Ok(env) => env,
+                    Err(e) => {
+                        self.record_event(&reference, event_store::WARNING, "Failed", &format!("Error: {:#}", e)).await?;
+                        return Err(e);
                     }
-                    config.env = Some(env);
-                }
+                };
+                config.env = Some(env.iter().map(|(name, value)| format!("{}={}", name, value)).collect());
                 
                 // Add command if specified
                 if let Some(command) = container["command"].as_array() {
@@ -140,7 +287,7 @@ impl Kubelet {
                         command
                             .iter()
                             .filter_map(|c| c.as_str())
-                            .map(String::from)
+                            .map(|c| env::expand(c, &env))
                             .collect()
                     );
                 }
@@ -150,7 +297,7 @@ impl Kubelet {
                     let args_vec: Vec<String> = args
                         .iter()
                         .filter_map(|a| a.as_str())
-                        .map(String::from)
+                        .map(|a| env::expand(a, &env))
                         .collect();
                     
                     if let Some(ref mut cmd) = config.cmd {
@@ -168,16 +315,213 @@ impl Kubelet {
                 
                 info!("Creating container {} with image {}", full_container_name, image);
                 self.docker.create_container(Some(options), config).await?;
+                self.record_event(&reference, event_store::NORMAL, "Created", &format!("Created container {}", container_name)).await?;
                 
                 // Start the container
                 info!("Starting container {}", full_container_name);
                 self.docker.start_container(&full_container_name, None::<StartContainerOptions<String>>).await?;
+                self.record_event(&reference, event_store::NORMAL, "Started", &format!("Started container {}", container_name)).await?;
+
+                // A postStart hook that fails gets the container killed; the
+                // pod's restartPolicy decides what happens next
+                let ip = container_ip(&self.docker, &full_container_name, &self.host_ip).await;
+                let exec = |command| exec(&self.docker, &full_container_name, command);
+                if let Some(message) = lifecycle::run(&reference, container, POST_START, &ip, lifecycle::POST_START_TIMEOUT, exec).await {
+                    lifecycle::report_failure(&self.storage, &self.node_name, &reference, POST_START, &message).await?;
+                    self.record_event(&reference, event_store::NORMAL, "Killing", "FailedPostStartHook").await?;
+                    let t = spec["terminationGracePeriodSeconds"].as_i64().unwrap_or(30);
+                    self.docker.stop_container(&full_container_name, Some(StopContainerOptions { t })).await?;
+                }
             }
         }
+
+        let pod = ObjectReference::pod(namespace, name, uid);
+        security_profile::report(&self.storage, &self.node_name, &pod, profiles_requested, &unapplied).await?;
         
         Ok(())
     }
 
+    // Stops the containers of pods being deleted within the pod's grace
+    // period: their preStop hooks run first, then they're sent SIGTERM, and
+    // SIGKILL if they haven't exited when the grace period ends, as `docker
+    // stop` does with a timeout. The pod is reported not ready as soon as it
+    // starts stopping and its containers as terminated once they have. Each
+    // pod is stopped in the background, and is deleted once its containers
+    // have all exited.
+    async fn stop_deleted_pods(&self) -> Result<()> {
+        for (namespace, name, kept) in self.storage.finalizers().pods_in_grace_period(&self.node_name).await? {
+            if !self.stopping.lock().unwrap().insert(kept.uid.clone()) {
+                continue;
+            }
+            let deadline = kept.deletion_timestamp.as_deref().and_then(time::parse).unwrap_or_else(Utc::now);
+            info!("Stopping pod {}/{}, killing it in {}s", namespace, name, (deadline - Utc::now()).num_seconds().max(0));
+
+            let (storage, docker, stopping) = (self.storage.clone(), self.docker.clone(), self.stopping.clone());
+            let (node_name, host_ip) = (self.node_name.clone(), self.host_ip.clone());
+            tokio::spawn(async move {
+                let filters = HashMap::from([
+                    ("label".to_string(), vec![format!("io.kubernetes.pod.uid={}", kept.uid)]),
+                ]);
+                let containers = docker
+                    .list_containers(Some(bollard::container::ListContainersOptions { all: true, filters, ..Default::default() }))
+                    .await
+                    .unwrap_or_default();
+                let spec = storage.pods().get(&namespace, &name).await.map(|pod| pod["spec"].clone()).unwrap_or_default();
+                if let Err(e) = mark_terminating(&storage, &kept.uid).await {
+                    error!("Failed to record stopping pod {}/{}: {}", namespace, name, e);
+                }
+
+                let pod = ObjectReference::pod(&namespace, &name, &kept.uid);
+                let running: Vec<(&str, &str)> = containers
+                    .iter()
+                    .filter(|container| container.state.as_deref() == Some("running"))
+                    .filter_map(|container| {
+                        let container_name = container.labels.as_ref()?.get("io.kubernetes.container.name")?;
+                        Some((container.id.as_deref()?, container_name.as_str()))
+                    })
+                    .collect();
+                for (_, container_name) in &running {
+                    let reference = pod.clone().container(container_name);
+                    let message = format!("Stopping container {}", container_name);
+                    if let Err(e) = record_event(&storage, &node_name, &reference, event_store::NORMAL, "Killing", &message).await {
+                        error!("Failed to record stopping pod {}/{}: {}", namespace, name, e);
+                    }
+                }
+
+                // The preStop hooks take what they need of the grace period
+                let grace = (deadline - Utc::now()).to_std().unwrap_or_default();
+                let hooks = running.iter().map(|(id, container_name)| {
+                    let (storage, docker, node_name, host_ip, pod) = (&storage, &docker, &node_name, &host_ip, &pod);
+                    let container = spec["containers"].as_array().into_iter().flatten().find(|c| c["name"] == *container_name).cloned().unwrap_or_default();
+                    async move {
+                        let reference = pod.clone().container(container_name);
+                        let ip = container_ip(docker, id, host_ip).await;
+                        let exec = |command| exec(docker, id, command);
+                        if let Some(message) = lifecycle::run(&reference, &container, PRE_STOP, &ip, grace, exec).await {
+                            if let Err(e) = lifecycle::report_failure(storage, node_name, &reference, PRE_STOP, &message).await {
+                                error!("Failed to record preStop hook of {}: {}", container_name, e);
+                            }
+                        }
+                    }
+                });
+                futures::future::join_all(hooks).await;
+
+                let timeout = (deadline - Utc::now()).num_seconds().max(0);
+                let stops = containers
+                    .iter()
+                    .filter_map(|container| container.id.as_deref())
+                    .map(|id| docker.stop_container(id, Some(StopContainerOptions { t: timeout })));
+                futures::future::join_all(stops).await;
+
+                let mut exits = Vec::new();
+                for (id, container_name) in &running {
+                    let state = docker.inspect_container(id, None).await.ok().and_then(|c| c.state).unwrap_or_default();
+                    exits.push(ContainerExit {
+                        name: container_name.to_string(),
+                        exit_code: state.exit_code.unwrap_or(137),
+                        oom_killed: state.oom_killed.unwrap_or(false),
+                    });
+                }
+                if let Err(e) = mark_stopped(&storage, &kept.uid, &exits).await {
+                    error!("Failed to record stopped pod {}/{}: {}", namespace, name, e);
+                }
+                if let Err(e) = storage.finalizers().end_grace_period(&namespace, &name).await {
+                    error!("Failed to delete stopped pod {}/{}: {}", namespace, name, e);
+                }
+                stopping.lock().unwrap().remove(&kept.uid);
+            });
+        }
+        Ok(())
+    }
+
+    async fn record_event(&self, involved: &ObjectReference, event_type: &str, reason: &str, message: &str) -> Result<()> {
+        record_event(&self.storage, &self.node_name, involved, event_type, reason, message).await
+    }
+
+    // Writes the pod's resolv.conf unless its containers just use the node's,
+    // returning the path to mount over theirs. Docker can't set DNS servers
+    // for host networking, so the file is mounted either way.
+    fn write_resolv_conf(&self, uid: &str, namespace: &str, spec: &Value) -> Result<Option<PathBuf>> {
+        let node = Resolver::parse(&std::fs::read_to_string("/etc/resolv.conf").unwrap_or_default());
+        let Some(resolver) = dns::resolver(spec, namespace, &self.dns, &node) else {
+            return Ok(None);
+        };
+
+        let dir = self.pod_dir(uid);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("resolv.conf");
+        std::fs::write(&path, resolver.to_resolv_conf())?;
+        Ok(Some(path))
+    }
+
+    // Rewrites the configMap, secret, downwardAPI and projected volumes of
+    // running pods, so they follow what they project and the tokens in them
+    // that are due are reissued before they expire
+    async fn refresh_projected_volumes(&self) -> Result<()> {
+        let rows = sqlx::query(
+            "SELECT uid, name, namespace, spec FROM pods
+             WHERE node_name = ? AND phase = 'Running' AND deletion_timestamp IS NULL
+             AND (spec LIKE '%\"projected\"%' OR spec LIKE '%\"configMap\"%' OR spec LIKE '%\"secret\"%'
+                  OR spec LIKE '%\"downwardAPI\"%')"
+        )
+        .bind(&self.node_name)
+        .fetch_all(&*self.storage.pool)
+        .await?;
+        for row in rows {
+            let (uid, name, namespace): (String, String, String) = (row.get("uid"), row.get("name"), row.get("namespace"));
+            let spec: Value = serde_json::from_str(&row.get::<String, _>("spec"))?;
+            let pod = json!({ "metadata": { "uid": uid, "name": name, "namespace": namespace }, "spec": spec });
+            if let Err(e) = volumes::project(&self.storage, &pod, &self.pod_dir(&uid)).await {
+                error!("Failed to refresh projected volumes of pod {}/{}: {:#}", namespace, name, e);
+            }
+        }
+        Ok(())
+    }
+
+    fn empty_dir(&self, uid: &str, volume: &str) -> PathBuf {
+        volumes::dir(&self.pod_dir(uid), "kubernetes.io~empty-dir", volume)
+    }
+
+    // What the pod's containers and emptyDirs on disk take up
+    async fn storage_usage(&self, uid: &str, spec: &Value, containers: &[ContainerSummary]) -> Result<PodUsage> {
+        let mut usage = PodUsage::default();
+        for container in containers {
+            let (Some(id), Some(name)) = (
+                container.id.as_deref(),
+                container.labels.as_ref().and_then(|l| l.get("io.kubernetes.container.name")),
+            ) else {
+                continue;
+            };
+            let log_path = self.docker.inspect_container(id, None).await?.log_path;
+            let logs = log_path
+                .and_then(|path| std::fs::metadata(path).ok())
+                .map(|m| m.len())
+                .unwrap_or(0);
+            let rootfs = container.size_rw.unwrap_or(0).max(0) as u64;
+            usage.containers.insert(name.clone(), ContainerUsage { rootfs, logs });
+        }
+        for volume in spec["volumes"].as_array().into_iter().flatten() {
+            if let (Some(name), Some(_)) = (volume["name"].as_str(), volume.get("emptyDir")) {
+                usage.volumes.insert(name.to_string(), ephemeral_storage::dir_size(&self.empty_dir(uid, name)));
+            }
+        }
+        Ok(usage)
+    }
+
+    fn seccomp_root(&self) -> PathBuf {
+        match &self.data_dir {
+            Some(data_dir) => data_dir.seccomp(),
+            None => std::env::temp_dir().join("krust").join("seccomp"),
+        }
+    }
+
+    fn pod_dir(&self, uid: &str) -> PathBuf {
+        match &self.data_dir {
+            Some(data_dir) => data_dir.pod(uid),
+            None => std::env::temp_dir().join("krust").join("pods").join(uid),
+        }
+    }
+
     async fn container_exists(&self, name: &str) -> bool {
         match self.docker.inspect_container(name, None).await {
             Ok(_) => true,
@@ -185,17 +529,11 @@ impl Kubelet {
         }
     }
 
-    async fn pull_image(&self, image: &str) -> Result<()> {
+    async fn pull_image(&self, image: &str, credentials: Option<DockerCredentials>) -> Result<()> {
         use bollard::image::CreateImageOptions;
         use futures::StreamExt;
         
-        // Parse image name and tag
-        let parts: Vec<&str> = image.split(':').collect();
-        let (image_name, tag) = if parts.len() > 1 {
-            (parts[0], parts[1])
-        } else {
-            (image, "latest")
-        };
+        let (image_name, tag) = images::split(image);
         
         let options = CreateImageOptions {
             from_image: image_name,
@@ -205,7 +543,7 @@ impl Kubelet {
         
         info!("Pulling image {}:{}", image_name, tag);
         
-        let mut stream = self.docker.create_image(Some(options), None, None);
+        let mut stream = self.docker.create_image(Some(options), None, credentials);
         
         while let Some(result) = stream.next().await {
             match result {
@@ -230,112 +568,13 @@ impl Kubelet {
     }
 
     async fn update_pod_phase(&self, uid: &str, phase: &str) -> Result<()> {
-        // Get current pod to update status properly
-        let pod_row = sqlx::query(
-            "SELECT spec, status FROM pods WHERE uid = ?"
-        )
-        .bind(uid)
-        .fetch_optional(&*self.storage.pool)
-        .await?;
-        
-        if let Some(row) = pod_row {
-            let spec_str: String = row.get("spec");
-            let spec: Value = serde_json::from_str(&spec_str)?;
-            let mut status: Value = serde_json::from_str(&row.get::<String, _>("status"))?;
-            
-            // Update phase
-            status["phase"] = json!(phase);
-            
-            // Update conditions based on phase
-            let now = chrono::Utc::now().to_rfc3339();
-            if phase == "Running" {
-                // Update Ready condition
-                if let Some(conditions) = status["conditions"].as_array_mut() {
-                    for condition in conditions {
-                        if condition["type"] == "Ready" || condition["type"] == "ContainersReady" {
-                            condition["status"] = json!("True");
-                            condition["lastTransitionTime"] = json!(now);
-                            condition["reason"] = json!("ContainersReady");
-                            condition["message"] = json!("All containers are ready");
-                        } else if condition["type"] == "PodScheduled" {
-                            condition["status"] = json!("True");
-                            condition["lastTransitionTime"] = json!(now);
-                            condition["reason"] = json!("Scheduled");
-                            condition["message"] = json!("Pod has been scheduled to node");
-                        }
-                    }
-                }
-                
-                // Add container statuses
-                if let Some(containers) = spec["containers"].as_array() {
-                    let mut container_statuses = Vec::new();
-                    for container in containers {
-                        let name = container["name"].as_str().unwrap_or("container");
-                        container_statuses.push(json!({
-                            "name": name,
-                            "state": {
-                                "running": {
-                                    "startedAt": now
-                                }
-                            },
-                            "ready": true,
-                            "restartCount": 0,
-                            "image": container["image"],
-                            "imageID": container["image"],
-                            "containerID": format!("docker://{}", uid),
-                            "started": true
-                        }));
-                    }
-                    status["containerStatuses"] = json!(container_statuses);
-                }
-                
-                status["startTime"] = json!(now);
-                
-                // Assign pod IP (simplified - just use a unique IP)
-                let pod_ip = format!("10.244.0.{}", (uid.bytes().fold(0u8, |a, b| a.wrapping_add(b)) % 254) + 1);
-                status["podIP"] = json!(pod_ip.clone());
-                status["podIPs"] = json!([{"ip": pod_ip}]);
-                status["hostIP"] = json!("127.0.0.1");
-            } else if phase == "Failed" {
-                // Update conditions for failed state
-                if let Some(conditions) = status["conditions"].as_array_mut() {
-                    for condition in conditions {
-                        if condition["type"] == "Ready" || condition["type"] == "ContainersReady" {
-                            condition["status"] = json!("False");
-                            condition["lastTransitionTime"] = json!(now);
-                            condition["reason"] = json!("ContainersFailed");
-                            condition["message"] = json!("One or more containers failed");
-                        }
-                    }
-                }
-            }
-            
-            sqlx::query(
-                "UPDATE pods SET phase = ?, status = ? WHERE uid = ?"
-            )
-            .bind(phase)
-            .bind(status.to_string())
-            .bind(uid)
-            .execute(&*self.storage.pool)
-            .await?;
-        } else {
-            // Fallback to simple phase update
-            sqlx::query(
-                "UPDATE pods SET phase = ? WHERE uid = ?"
-            )
-            .bind(phase)
-            .bind(uid)
-            .execute(&*self.storage.pool)
-            .await?;
-        }
-        
-        Ok(())
+        set_pod_phase(&self.storage, uid, phase, &self.host_ip).await
     }
 
     async fn update_pod_statuses(&self) -> Result<()> {
         // Get all running pods on this node
         let rows = sqlx::query(
-            "SELECT uid, name, namespace FROM pods 
+            "SELECT uid, name, namespace, spec, status FROM pods 
              WHERE node_name = ? AND phase = 'Running' 
              AND deletion_timestamp IS NULL"
         )
@@ -347,35 +586,85 @@ impl Kubelet {
             let uid: String = row.get("uid");
             let name: String = row.get("name");
             let namespace: String = row.get("namespace");
+            let spec: Value = serde_json::from_str(&row.get::<String, _>("spec"))?;
+            let status: Value = serde_json::from_str(&row.get::<String, _>("status"))?;
+
+            // Containers exiting as they're stopped aren't restarted
+            if self.stopping.lock().unwrap().contains(&uid) {
+                continue;
+            }
             
-            // Check if all containers are still running
-            let pattern = format!("k8s_*_{}_{}_{}", name, namespace, uid);
             let filters = HashMap::from([
                 ("label".to_string(), vec![format!("io.kubernetes.pod.uid={}", uid)]),
             ]);
             
             let containers = self.docker.list_containers(Some(bollard::container::ListContainersOptions {
                 all: true,
+                size: true,
                 filters,
                 ..Default::default()
             })).await?;
             
-            let mut all_running = true;
+            if containers.is_empty() {
+                // No containers found for this pod
+                self.update_pod_phase(&uid, "Failed").await?;
+                continue;
+            }
+
+            // The pod's address is its first container's; pods started
+            // before they were given one of their own report it from here
+            if !spec["hostNetwork"].as_bool().unwrap_or(false) {
+                let first = spec["containers"][0]["name"].as_str();
+                let address = containers
+                    .iter()
+                    .find(|c| c.labels.as_ref().and_then(|l| l.get("io.kubernetes.container.name")).map(String::as_str) == first)
+                    .and_then(|c| network_address(c.network_settings.clone()?.networks));
+                if let Some(address) = address.filter(|address| status["podIP"] != address.as_str()) {
+                    info!("Pod {}/{} has address {}", namespace, name, address);
+                    self.storage.pods().set_status_fields(&uid, &pod_network::addresses(&address)).await?;
+                }
+            }
+
+            let usage = self.storage_usage(&uid, &spec, &containers).await?;
+            if ephemeral_storage::enforce(&self.storage, &self.node_name, &uid, &spec, &usage).await?.is_some() {
+                for id in containers.iter().filter_map(|c| c.id.as_deref()) {
+                    let _ = self.docker.stop_container(id, None).await;
+                }
+                continue;
+            }
+            
+            // Collect the exit codes of containers that have stopped
+            let mut exits = Vec::new();
+            let mut stopped = HashMap::new();
             for container in &containers {
-                if let Some(state) = &container.state {
-                    if state != "running" {
-                        all_running = false;
-                        break;
-                    }
+                if container.state.as_deref() != Some("exited") {
+                    continue;
                 }
+                let (Some(id), Some(container_name)) = (
+                    container.id.clone(),
+                    container.labels.as_ref().and_then(|l| l.get("io.kubernetes.container.name")).cloned(),
+                ) else {
+                    continue;
+                };
+                
+                let state = self.docker.inspect_container(&id, None).await?.state.unwrap_or_default();
+                exits.push(ContainerExit {
+                    name: container_name.clone(),
+                    exit_code: state.exit_code.unwrap_or(-1),
+                    oom_killed: state.oom_killed.unwrap_or(false),
+                });
+                stopped.insert(container_name, id);
             }
             
-            if !all_running && !containers.is_empty() {
-                // At least one container has stopped
-                self.update_pod_phase(&uid, "Failed").await?;
-            } else if containers.is_empty() {
-                // No containers found for this pod
-                self.update_pod_phase(&uid, "Failed").await?;
+            if exits.is_empty() {
+                continue;
+            }
+            
+            for container_name in handle_container_exits(&self.storage, &uid, &exits).await? {
+                info!("Restarting container {} of pod {}/{}", container_name, namespace, name);
+                if let Some(id) = stopped.get(&container_name) {
+                    self.docker.start_container(id, None::<StartContainerOptions<String>>).await?;
+                }
             }
         }
         
@@ -423,13 +712,484 @@ impl Kubelet {
                 }
             }
             
+            let dir = self.pod_dir(&uid);
+            if dir.exists() {
+                if let Err(e) = std::fs::remove_dir_all(&dir) {
+                    error!("Failed to remove {}: {}", dir.display(), e);
+                }
+            }
+
             // Remove from database
             sqlx::query("DELETE FROM pods WHERE uid = ?")
                 .bind(&uid)
                 .execute(&*self.storage.pool)
                 .await?;
         }
+        ephemeral_storage::forget_deleted(&self.storage, &self.node_name).await?;
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// Kubelet-side admission: a pod bound to a node that is already running
+/// `max_pods` pods is failed with OutOfpods instead of being started, however
+/// it got there. Returns whether the pod was admitted.
+pub(crate) async fn admit_pod(storage: &Storage, node_name: &str, max_pods: usize, uid: &str) -> Result<bool> {
+    let used: i64 = sqlx::query_scalar(
+        "SELECT COUNT(*) FROM pods WHERE node_name = ? AND phase = 'Running' AND uid != ?"
+    )
+    .bind(node_name)
+    .bind(uid)
+    .fetch_one(&*storage.pool)
+    .await?;
+
+    if (used as usize) < max_pods {
+        return Ok(true);
+    }
+
+    let row = sqlx::query("SELECT name, namespace FROM pods WHERE uid = ?")
+        .bind(uid)
+        .fetch_one(&*storage.pool)
+        .await?;
+    let name: String = row.get("name");
+    let namespace: String = row.get("namespace");
+
+    let message = format!(
+        "Pod Node didn't have enough resource: pods, requested: 1, used: {}, capacity: {}",
+        used, max_pods
+    );
+    info!("Rejecting pod {}/{}: {}", namespace, name, message);
+
+    storage.pods().set_status_fields(uid, &[
+        ("phase", json!("Failed")),
+        ("reason", json!("OutOfpods")),
+        ("message", json!(message)),
+    ]).await?;
+
+    let pod = ObjectReference::pod(&namespace, &name, uid);
+    record_event(storage, node_name, &pod, event_store::WARNING, "OutOfpods", &message).await?;
+
+    Ok(false)
+}
+
+/// Reports an event about a pod, or one of its containers, from the kubelet
+/// of `node_name`.
+pub(crate) async fn record_event(
+    storage: &Storage,
+    node_name: &str,
+    involved: &ObjectReference,
+    event_type: &str,
+    reason: &str,
+    message: &str,
+) -> Result<()> {
+    storage.events().record(&EventSource::kubelet(node_name), involved, event_type, reason, message).await?;
+    Ok(())
+}
+
+/// How one of a pod's containers exited.
+pub(crate) struct ContainerExit {
+    pub name: String,
+    pub exit_code: i64,
+    /// Whether it was killed for going over its memory limit.
+    pub oom_killed: bool,
+}
+
+impl ContainerExit {
+    /// The reason its terminated state gives.
+    fn reason(&self) -> &'static str {
+        match (self.oom_killed, self.exit_code) {
+            (true, _) => "OOMKilled",
+            (false, 0) => "Completed",
+            _ => "Error",
+        }
+    }
+}
+
+/// Records containers exiting and applies the pod's restartPolicy: Always
+/// restarts every container in place, OnFailure only those that exited
+/// non-zero, and Never none of them. Once no container is left running the
+/// pod finishes, Succeeded if all of them exited 0 and Failed otherwise.
+/// Returns the names of the containers to restart.
+pub(crate) async fn handle_container_exits(storage: &Storage, uid: &str, exits: &[ContainerExit]) -> Result<Vec<String>> {
+    let Some(row) = sqlx::query("SELECT spec, status FROM pods WHERE uid = ?")
+        .bind(uid)
+        .fetch_optional(&*storage.pool)
+        .await?
+    else {
+        return Ok(Vec::new());
+    };
+    
+    let spec: Value = serde_json::from_str(&row.get::<String, _>("spec"))?;
+    let mut status: Value = serde_json::from_str(&row.get::<String, _>("status"))?;
+    let restart_policy = spec["restartPolicy"].as_str().unwrap_or("Always");
+    let now = time::now();
+    
+    if !status["containerStatuses"].is_array() {
+        status["containerStatuses"] = json!([]);
+    }
+    let statuses = status["containerStatuses"].as_array_mut().unwrap();
+    
+    let mut restarts = Vec::new();
+    for exit in exits {
+        let (name, exit_code) = (&exit.name, &exit.exit_code);
+        let index = match statuses.iter().position(|cs| cs["name"] == name.as_str()) {
+            Some(index) => index,
+            None => {
+                statuses.push(json!({ "name": name, "restartCount": 0 }));
+                statuses.len() - 1
+            }
+        };
+        let container_status = &mut statuses[index];
+        
+        // Containers that already finished for good have been handled
+        if !container_status["state"]["terminated"].is_null() {
+            continue;
+        }
+        
+        let terminated = json!({
+            "exitCode": exit_code,
+            "reason": exit.reason(),
+            "finishedAt": now
+        });
+        
+        let restart = match restart_policy {
+            "Never" => false,
+            "OnFailure" => *exit_code != 0,
+            _ => true,
+        };
+        
+        if restart {
+            let restart_count = container_status["restartCount"].as_i64().unwrap_or(0);
+            container_status["lastState"] = json!({ "terminated": terminated });
+            container_status["state"] = json!({ "running": { "startedAt": now } });
+            container_status["restartCount"] = json!(restart_count + 1);
+            container_status["ready"] = json!(true);
+            restarts.push(name.clone());
+        } else {
+            container_status["state"] = json!({ "terminated": terminated });
+            container_status["ready"] = json!(false);
+            container_status["started"] = json!(false);
+        }
+    }
+    
+    let finished_with = |name: &str| {
+        statuses
+            .iter()
+            .find(|cs| cs["name"] == name)
+            .and_then(|cs| cs["state"]["terminated"]["exitCode"].as_i64())
+    };
+    let exit_codes: Vec<Option<i64>> = spec["containers"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|c| finished_with(c["name"].as_str().unwrap_or("")))
+        .collect();
+    
+    // Only the fields worked out here are written back
+    let mut fields = vec![("containerStatuses", status["containerStatuses"].take())];
+    if exit_codes.iter().all(|code| code.is_some()) {
+        let phase = if exit_codes.iter().all(|code| *code == Some(0)) { "Succeeded" } else { "Failed" };
+        fields.push(("phase", json!(phase)));
+        if let Some(conditions) = status["conditions"].as_array_mut() {
+            for condition in conditions.iter_mut() {
+                if condition["type"] == "Ready" || condition["type"] == "ContainersReady" {
+                    condition["status"] = json!("False");
+                    condition["lastTransitionTime"] = json!(now);
+                    condition["reason"] = json!("PodCompleted");
+                    condition["message"] = json!("");
+                }
+            }
+            fields.push(("conditions", json!(conditions)));
+        }
+    } else {
+        fields.push(("phase", json!("Running")));
+    }
+    
+    storage.pods().set_status_fields(uid, &fields).await?;
+    
+    Ok(restarts)
+}
+
+/// Reports a pod that has started stopping as no longer ready, and its
+/// containers with it.
+pub(crate) async fn mark_terminating(storage: &Storage, uid: &str) -> Result<()> {
+    let Some(row) = sqlx::query("SELECT status FROM pods WHERE uid = ?")
+        .bind(uid)
+        .fetch_optional(&*storage.pool)
+        .await?
+    else {
+        return Ok(());
+    };
+
+    let mut status: Value = serde_json::from_str(&row.get::<String, _>("status"))?;
+    let mut names = Vec::new();
+    for container_status in status["containerStatuses"].as_array_mut().into_iter().flatten() {
+        if container_status["state"]["running"].is_object() {
+            container_status["ready"] = json!(false);
+            names.push(container_status["name"].as_str().unwrap_or_default().to_string());
+        }
+    }
+    let now = time::now();
+    for condition in status["conditions"].as_array_mut().into_iter().flatten() {
+        if (condition["type"] == "Ready" || condition["type"] == "ContainersReady") && condition["status"] != "False" {
+            condition["status"] = json!("False");
+            condition["lastTransitionTime"] = json!(now);
+            condition["reason"] = json!("ContainersNotReady");
+            condition["message"] = json!(format!("containers with unready status: [{}]", names.join(" ")));
+        }
+    }
+
+    let mut fields = vec![("containerStatuses", status["containerStatuses"].take())];
+    if status["conditions"].is_array() {
+        fields.push(("conditions", status["conditions"].take()));
+    }
+    storage.pods().set_status_fields(uid, &fields).await
+}
+
+/// Reports a stopped pod's containers as terminated with how they exited,
+/// leaving its restartPolicy out of it.
+pub(crate) async fn mark_stopped(storage: &Storage, uid: &str, exits: &[ContainerExit]) -> Result<()> {
+    let Some(row) = sqlx::query("SELECT status FROM pods WHERE uid = ?")
+        .bind(uid)
+        .fetch_optional(&*storage.pool)
+        .await?
+    else {
+        return Ok(());
+    };
+
+    let mut status: Value = serde_json::from_str(&row.get::<String, _>("status"))?;
+    let now = time::now();
+    for container_status in status["containerStatuses"].as_array_mut().into_iter().flatten() {
+        let Some(exit) = exits.iter().find(|exit| container_status["name"] == exit.name.as_str()) else {
+            continue;
+        };
+        container_status["state"] = json!({
+            "terminated": {
+                "exitCode": exit.exit_code,
+                "reason": exit.reason(),
+                "startedAt": container_status["state"]["running"]["startedAt"],
+                "finishedAt": now
+            }
+        });
+        container_status["ready"] = json!(false);
+        container_status["started"] = json!(false);
+    }
+    storage.pods().set_status_fields(uid, &[("containerStatuses", status["containerStatuses"].take())]).await
+}
+
+/// Reports one of a pod's containers as waiting to be created, with the
+/// reason and message, such as ErrImagePull and why. The pod's other
+/// containers that aren't running yet are reported as still being created.
+pub(crate) async fn set_container_waiting(storage: &Storage, uid: &str, container: &str, reason: &str, message: &str) -> Result<()> {
+    let Some(row) = sqlx::query("SELECT spec FROM pods WHERE uid = ?")
+        .bind(uid)
+        .fetch_optional(&*storage.pool)
+        .await?
+    else {
+        return Ok(());
+    };
+
+    let spec: Value = serde_json::from_str(&row.get::<String, _>("spec"))?;
+    let statuses: Vec<Value> = spec["containers"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .map(|c| {
+            let waiting = if c["name"] == container {
+                json!({ "reason": reason, "message": message })
+            } else {
+                json!({ "reason": "ContainerCreating" })
+            };
+            let mut status = json!({
+                "name": c["name"],
+                "state": { "waiting": waiting },
+                "ready": false,
+                "restartCount": 0,
+                "image": c["image"],
+                "imageID": "",
+                "started": false
+            });
+            add_resources(&mut status, c);
+            status
+        })
+        .collect();
+    storage.pods().set_status_fields(uid, &[("containerStatuses", json!(statuses))]).await
+}
+
+// The address of a running container, for its hooks to GET
+async fn container_ip(docker: &Docker, id: &str, host_ip: &str) -> String {
+    let settings = docker.inspect_container(id, None).await.ok().and_then(|c| c.network_settings);
+    settings.and_then(|s| network_address(s.networks)).unwrap_or_else(|| host_ip.to_string())
+}
+
+// A container's address, on the pod network if it's on that
+fn network_address(networks: Option<HashMap<String, EndpointSettings>>) -> Option<String> {
+    let mut networks = networks.unwrap_or_default();
+    let pod_network = networks.remove(pod_network::NETWORK);
+    pod_network.into_iter().chain(networks.into_values()).find_map(|network| network.ip_address.filter(|ip| !ip.is_empty()))
+}
+
+// Runs a command in a container, returning its exit code and output
+async fn exec(docker: &Docker, id: &str, command: Vec<String>) -> Result<(i64, String)> {
+    use futures::StreamExt;
+
+    let options = CreateExecOptions {
+        cmd: Some(command),
+        attach_stdout: Some(true),
+        attach_stderr: Some(true),
+        ..Default::default()
+    };
+    let created = docker.create_exec(id, options).await?;
+    let mut output = String::new();
+    if let StartExecResults::Attached { output: mut stream, .. } = docker.start_exec(&created.id, None).await? {
+        while let Some(chunk) = stream.next().await {
+            output.push_str(&String::from_utf8_lossy(&chunk?.into_bytes()));
+        }
+    }
+    let exit_code = docker.inspect_exec(&created.id).await?.exit_code.unwrap_or(-1);
+    Ok((exit_code, output))
+}
+
+// Pods a kubelet remembers having been bound before it forgets them all
+const BINDINGS_SEEN: usize = 4096;
+
+/// Waits out a kubelet's sync period, cut short when a pod is newly bound
+/// to `node` so that it's started straight away. `bound` holds the uids of
+/// the pods already seen bound.
+pub(crate) async fn wait_for_bindings(changes: &mut Changes, node: &str, bound: &mut HashSet<String>, period: Duration) {
+    if bound.len() >= BINDINGS_SEEN {
+        bound.clear();
+    }
+    let binding = async {
+        while let Some(pod) = changes.next().await {
+            let uid = pod["metadata"]["uid"].as_str().unwrap_or_default();
+            if pod["spec"]["nodeName"] == node && pod["status"]["phase"] == "Pending" && bound.insert(uid.to_string()) {
+                return;
+            }
+        }
+        // No more writes to wait for
+        std::future::pending().await
+    };
+    let _ = tokio::time::timeout(period, binding).await;
+}
+
+/// Moves a pod to a new phase, filling in the status fields a real kubelet
+/// would report for it. `host_ip` is the address of the pod's node.
+pub(crate) async fn set_pod_phase(storage: &Storage, uid: &str, phase: &str, host_ip: &str) -> Result<()> {
+    // Get current pod to update status properly
+    let Some(row) = sqlx::query("SELECT spec, status FROM pods WHERE uid = ?")
+        .bind(uid)
+        .fetch_optional(&*storage.pool)
+        .await?
+    else {
+        return Ok(());
+    };
+    
+    let spec: Value = serde_json::from_str(&row.get::<String, _>("spec"))?;
+    let status: Value = serde_json::from_str(&row.get::<String, _>("status"))?;
+    let mut conditions = status["conditions"].clone();
+    let mut fields = vec![("phase", json!(phase))];
+    
+    // Update conditions based on phase
+    let now = time::now();
+    if phase == "Running" {
+        // Update Ready condition
+        if let Some(conditions) = conditions.as_array_mut() {
+            for condition in conditions {
+                if condition["type"] == "Ready" || condition["type"] == "ContainersReady" {
+                    condition["status"] = json!("True");
+                    condition["lastTransitionTime"] = json!(now);
+                    condition["reason"] = json!("ContainersReady");
+                    condition["message"] = json!("All containers are ready");
+                }
+            }
+        }
+        
+        // Add container statuses
+        if let Some(containers) = spec["containers"].as_array() {
+            let mut container_statuses = Vec::new();
+            for container in containers {
+                let name = container["name"].as_str().unwrap_or("container");
+                let mut container_status = json!({
+                    "name": name,
+                    "state": {
+                        "running": {
+                            "startedAt": now
+                        }
+                    },
+                    "ready": true,
+                    "restartCount": 0,
+                    "image": container["image"],
+                    "imageID": container["image"],
+                    "containerID": format!("docker://{}", uid),
+                    "started": true
+                });
+                add_resources(&mut container_status, container);
+                container_statuses.push(container_status);
+            }
+            fields.push(("containerStatuses", json!(container_statuses)));
+        }
+        
+        let pod_ip = pod_network::assign(storage, uid, &spec, host_ip).await?;
+        fields.push(("startTime", json!(now)));
+        fields.extend(pod_network::addresses(&pod_ip));
+        fields.push(("hostIP", json!(host_ip)));
+        fields.push(("hostIPs", json!([{"ip": host_ip}])));
+    } else if phase == "Failed" {
+        // Update conditions for failed state
+        if let Some(conditions) = conditions.as_array_mut() {
+            for condition in conditions {
+                if condition["type"] == "Ready" || condition["type"] == "ContainersReady" {
+                    condition["status"] = json!("False");
+                    condition["lastTransitionTime"] = json!(now);
+                    condition["reason"] = json!("ContainersFailed");
+                    condition["message"] = json!("One or more containers failed");
+                }
+            }
+        }
+    }
+    
+    if conditions.is_array() {
+        fields.push(("conditions", conditions));
+    }
+    
+    storage.pods().set_status_fields(uid, &fields).await
+}
+
+// Reports the CPU and memory a container was given in its status: what the
+// node set aside for it as allocatedResources, and its requests and limits
+// as resources, requests defaulting to limits as the API server does
+fn add_resources(status: &mut Value, container: &Value) {
+    let mut requests = serde_json::Map::new();
+    let mut limits = serde_json::Map::new();
+    for resource in ["cpu", "memory"] {
+        let limit = &container["resources"]["limits"][resource];
+        let request = match &container["resources"]["requests"][resource] {
+            Value::Null => limit,
+            request => request,
+        };
+        if !request.is_null() {
+            requests.insert(resource.to_string(), request.clone());
+        }
+        if !limit.is_null() {
+            limits.insert(resource.to_string(), limit.clone());
+        }
+    }
+    if requests.is_empty() {
+        return;
+    }
+    status["allocatedResources"] = json!(requests);
+    let mut resources = json!({});
+    if !requests.is_empty() {
+        resources["requests"] = json!(requests);
+    }
+    if !limits.is_empty() {
+        resources["limits"] = json!(limits);
+    }
+    status["resources"] = resources;
+}
+
+/// Name of the Docker container running a pod's container.
+pub fn docker_container_name(container: &str, pod: &str, namespace: &str, uid: &str) -> String {
+    format!("k8s_{}_{}_{}_{}", container, pod, namespace, uid)
+}
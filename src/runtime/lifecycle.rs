@@ -0,0 +1,128 @@
+// Container lifecycle hooks: a container's postStart hook runs as soon as
+// it has started, and its preStop hook when its pod is deleted, before it's
+// sent SIGTERM and within the pod's grace period. A hook either execs a
+// command in the container, which must exit 0, or GETs a URL on the pod,
+// which must answer with a 2xx or 3xx status. A postStart hook that fails
+// gets the container killed, to be restarted as its restartPolicy says; a
+// preStop hook that fails is reported and the container stopped anyway.
+use anyhow::{anyhow, bail, Result};
+use serde_json::Value;
+use std::future::Future;
+use std::time::Duration;
+
+use super::kubelet::record_event;
+use crate::storage::event_store::{self, ObjectReference};
+use crate::Storage;
+
+pub const POST_START: &str = "postStart";
+pub const PRE_STOP: &str = "preStop";
+
+/// How long a postStart hook may take; a preStop hook has what's left of
+/// the pod's grace period.
+pub const POST_START_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// What a hook does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Handler {
+    /// Runs a command in the container.
+    Exec(Vec<String>),
+    /// GETs a URL, with the given headers.
+    HttpGet { url: String, headers: Vec<(String, String)> },
+}
+
+impl Handler {
+    /// The container's `hook` (postStart or preStop), if it has one. GETs go
+    /// to `pod_ip` unless they name a host, and may name one of the
+    /// container's ports rather than give its number.
+    pub fn of(container: &Value, hook: &str, pod_ip: &str) -> Result<Option<Self>> {
+        let handler = &container["lifecycle"][hook];
+        if let Some(command) = handler["exec"]["command"].as_array() {
+            return Ok(Some(Handler::Exec(command.iter().filter_map(|c| c.as_str()).map(str::to_string).collect())));
+        }
+        let get = &handler["httpGet"];
+        if get.is_null() {
+            return Ok(None);
+        }
+
+        let port = match &get["port"] {
+            Value::Number(port) => port.as_i64().unwrap_or_default(),
+            Value::String(name) => container["ports"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .find(|p| p["name"] == name.as_str())
+                .and_then(|p| p["containerPort"].as_i64())
+                .or_else(|| name.parse().ok())
+                .ok_or_else(|| anyhow!("couldn't find port {:?} in container", name))?,
+            _ => bail!("no port for the {} hook", hook),
+        };
+        let scheme = get["scheme"].as_str().unwrap_or("HTTP").to_lowercase();
+        let host = get["host"].as_str().filter(|h| !h.is_empty()).unwrap_or(pod_ip);
+        let path = get["path"].as_str().unwrap_or_default();
+        let path = if path.starts_with('/') { path.to_string() } else { format!("/{}", path) };
+        let headers = get["httpHeaders"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|h| Some((h["name"].as_str()?.to_string(), h["value"].as_str().unwrap_or_default().to_string())))
+            .collect();
+        Ok(Some(Handler::HttpGet { url: format!("{}://{}:{}{}", scheme, host, port, path), headers }))
+    }
+}
+
+/// Runs the container's `hook`, if it has one, giving up after `timeout`.
+/// `exec` runs a command in the container and returns its exit code and
+/// output. Returns why the hook failed, if it did, for `report_failure`.
+pub async fn run<F, Fut>(reference: &ObjectReference, container: &Value, hook: &str, pod_ip: &str, timeout: Duration, exec: F) -> Option<String>
+where
+    F: FnOnce(Vec<String>) -> Fut,
+    Fut: Future<Output = Result<(i64, String)>>,
+{
+    let name = container["name"].as_str().unwrap_or("container");
+    let pod = format!("{}_{}({})", reference.name, reference.namespace.as_deref().unwrap_or("default"), reference.uid);
+    let handler = match Handler::of(container, hook, pod_ip) {
+        Ok(Some(handler)) => handler,
+        Ok(None) => return None,
+        Err(e) => return Some(format!("{} hook for Container {:?} in Pod {:?} is invalid: {:#}", hook, name, pod, e)),
+    };
+
+    let error = match &handler {
+        Handler::Exec(command) => match tokio::time::timeout(timeout, exec(command.clone())).await {
+            Ok(Ok((0, _))) => return None,
+            Ok(Ok((code, output))) => format!("command '{}' exited with {}, message: {:?}", command.join(" "), code, output),
+            Ok(Err(e)) => format!("{:#}", e),
+            Err(_) => format!("timed out after {}s", timeout.as_secs()),
+        },
+        Handler::HttpGet { url, headers } => match tokio::time::timeout(timeout, get(url, headers)).await {
+            Ok(Ok(())) => return None,
+            Ok(Err(e)) => format!("{:#}", e),
+            Err(_) => format!("timed out after {}s", timeout.as_secs()),
+        },
+    };
+    Some(match handler {
+        Handler::Exec(command) => format!("Exec lifecycle hook ({:?}) for Container {:?} in Pod {:?} failed - error: {}", command, name, pod, error),
+        Handler::HttpGet { url, .. } => format!("HTTP lifecycle hook ({}) for Container {:?} in Pod {:?} failed - error: {}", url, name, pod, error),
+    })
+}
+
+/// Reports a hook that failed as a FailedPostStartHook or FailedPreStopHook
+/// event on the container.
+pub(crate) async fn report_failure(storage: &Storage, node_name: &str, reference: &ObjectReference, hook: &str, message: &str) -> Result<()> {
+    let reason = if hook == POST_START { "FailedPostStartHook" } else { "FailedPreStopHook" };
+    record_event(storage, node_name, reference, event_store::WARNING, reason, message).await
+}
+
+// GETs a hook's URL, not verifying HTTPS certificates, as the kubelet doesn't
+async fn get(url: &str, headers: &[(String, String)]) -> Result<()> {
+    let client = reqwest::Client::builder().danger_accept_invalid_certs(true).build()?;
+    let mut request = client.get(url);
+    for (name, value) in headers {
+        request = request.header(name, value);
+    }
+    let response = request.send().await?;
+    let status = response.status();
+    if !(200..400).contains(&status.as_u16()) {
+        bail!("received status {}", status);
+    }
+    Ok(())
+}
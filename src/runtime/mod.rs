@@ -1,11 +1,25 @@
-pub mod container;
+pub mod compat;
 pub mod container_runtime;
 pub mod cgroups;
+pub mod dns;
+pub mod dns_server;
+pub mod env;
+pub mod ephemeral_storage;
+pub mod fake_kubelet;
+pub mod images;
+pub mod lifecycle;
 pub mod kubelet;
+pub mod network_policy;
+pub mod node_status;
+pub mod pod_network;
+pub mod projected_volume;
+pub mod security_profile;
+pub mod volumes;
 
 use anyhow::Result;
 use bollard::Docker;
 
+pub use fake_kubelet::FakeKubelet;
 pub use kubelet::Kubelet;
 
 pub struct ContainerRuntime {
@@ -0,0 +1,437 @@
+// NetworkPolicy enforcement for the pods the kubelet runs in Docker, with
+// iptables as a CNI plugin would do it. Docker sends traffic between and out
+// of containers through the DOCKER-USER chain, which gets a jump to
+// KRUST-NETPOL. There, traffic from a pod that policies isolate for egress
+// has to match one of their egress rules, and traffic to a pod isolated for
+// ingress one of their ingress rules, or it's dropped; replies to allowed
+// connections always pass. Pods that no policy selects, and hostNetwork
+// pods, are left alone.
+//
+// A pod's addresses are those Docker gave its containers. The whole ruleset
+// is rebuilt on every pass and swapped in at once with iptables-restore, and
+// only when it changed. Where iptables isn't there to use, as on macOS, where
+// Docker runs in a VM, or without root, nothing is enforced: the enforcer
+// says so once and doesn't start. Setting `networkPolicy.enforce: false`
+// turns it off everywhere.
+use anyhow::{anyhow, bail, Context, Result};
+use bollard::Docker;
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::collections::{BTreeMap, HashMap};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{error, info};
+
+use crate::models::network_policy::{is_port_number, Cidr, PROTOCOLS};
+use crate::profiling;
+use crate::storage::LabelSelector;
+use crate::Storage;
+
+/// The chain Docker's DOCKER-USER jumps to; every other chain of the
+/// ruleset is named after it.
+pub const CHAIN: &str = "KRUST-NETPOL";
+
+// Packet marks saying an egress or ingress rule allowed the packet
+const EGRESS_MARK: &str = "0x10000/0x10000";
+const INGRESS_MARK: &str = "0x20000/0x20000";
+
+/// A pod as policies see it: the object, for its namespace, labels and
+/// named ports, and the addresses of its containers.
+#[derive(Clone, Debug)]
+pub struct PolicyPod {
+    pub pod: Value,
+    pub ips: Vec<String>,
+}
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Ingress,
+    Egress,
+}
+
+impl Direction {
+    fn policy_type(self) -> &'static str {
+        match self {
+            Direction::Ingress => "Ingress",
+            Direction::Egress => "Egress",
+        }
+    }
+
+    // The spec field holding the rules, and the rule field holding the peers
+    fn fields(self) -> (&'static str, &'static str) {
+        match self {
+            Direction::Ingress => ("ingress", "from"),
+            Direction::Egress => ("egress", "to"),
+        }
+    }
+
+    // iptables' match for the isolated pod's address, and for the peer's
+    fn address_flags(self) -> (&'static str, &'static str) {
+        match self {
+            Direction::Ingress => ("-d", "-s"),
+            Direction::Egress => ("-s", "-d"),
+        }
+    }
+
+    fn mark(self) -> &'static str {
+        match self {
+            Direction::Ingress => INGRESS_MARK,
+            Direction::Egress => EGRESS_MARK,
+        }
+    }
+
+    fn letter(self) -> char {
+        match self {
+            Direction::Ingress => 'I',
+            Direction::Egress => 'E',
+        }
+    }
+}
+
+/// The iptables-restore input enforcing `policies` on `pods`, whose
+/// namespaces have the labels in `namespaces`. It replaces the chains of
+/// the previous ruleset, and deletes those of `stale` it no longer uses.
+pub fn ruleset(policies: &[Value], pods: &[PolicyPod], namespaces: &BTreeMap<String, Value>, stale: &[String]) -> String {
+    let mut chains: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut main = vec![format!("-A {} -m conntrack --ctstate RELATED,ESTABLISHED -j RETURN", CHAIN)];
+    let mut blocks = 0;
+
+    // Egress first, so a packet leaving one isolated pod for another has
+    // both its marks by the end
+    for direction in [Direction::Egress, Direction::Ingress] {
+        let (own_flag, peer_flag) = direction.address_flags();
+        let mark = direction.mark();
+        for (index, target) in pods.iter().enumerate() {
+            if target.pod["spec"]["hostNetwork"] == true || target.ips.is_empty() {
+                continue;
+            }
+            let applying: Vec<&Value> = policies.iter().filter(|policy| isolates(policy, &target.pod, direction)).collect();
+            if applying.is_empty() {
+                continue;
+            }
+
+            let chain = format!("{}-{}{}", CHAIN, direction.letter(), index);
+            for ip in &target.ips {
+                main.push(format!("-A {} {} {}/32 -j {}", CHAIN, own_flag, ip, chain));
+            }
+            let mut rules = Vec::new();
+            for policy in applying {
+                let namespace = policy["metadata"]["namespace"].as_str().unwrap_or("default");
+                let (rules_field, peers_field) = direction.fields();
+                for rule in policy["spec"][rules_field].as_array().into_iter().flatten() {
+                    let peers = rule[peers_field].as_array().filter(|peers| !peers.is_empty());
+                    let Some(peers) = peers else {
+                        // From or to anywhere
+                        let ports = port_matches(&rule["ports"], port_owner(direction, target, None));
+                        rules.extend(ports.iter().map(|ports| format!("-A {}{} -j MARK --set-xmark {}", chain, ports, mark)));
+                        continue;
+                    };
+                    for peer in peers {
+                        if !peer["ipBlock"].is_null() {
+                            let Some((cidr, excepts)) = ip_block(&peer["ipBlock"]) else {
+                                continue;
+                            };
+                            let ports = port_matches(&rule["ports"], port_owner(direction, target, None));
+                            if ports.is_empty() {
+                                continue;
+                            }
+                            // The exceptions skip the rest of the block's chain
+                            blocks += 1;
+                            let block_chain = format!("{}-B{}", CHAIN, blocks);
+                            let mut block_rules = Vec::new();
+                            for except in excepts {
+                                block_rules.push(format!("-A {} {} {} -j RETURN", block_chain, peer_flag, except));
+                            }
+                            for ports in ports {
+                                block_rules.push(format!("-A {} {} {}{} -j MARK --set-xmark {}", block_chain, peer_flag, cidr, ports, mark));
+                            }
+                            chains.insert(block_chain.clone(), block_rules);
+                            rules.push(format!("-A {} -j {}", chain, block_chain));
+                            continue;
+                        }
+                        for peer_pod in peer_pods(peer, namespace, pods, namespaces) {
+                            let ports = port_matches(&rule["ports"], port_owner(direction, target, Some(peer_pod)));
+                            for ip in &peer_pod.ips {
+                                for ports in &ports {
+                                    rules.push(format!("-A {} {} {}/32{} -j MARK --set-xmark {}", chain, peer_flag, ip, ports, mark));
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+            rules.push(format!("-A {} -m mark --mark {} -j RETURN", chain, mark));
+            rules.push(format!("-A {} -j DROP", chain));
+            chains.insert(chain, rules);
+        }
+    }
+
+    let mut script = String::from("*filter\n");
+    script.push_str(&format!(":{} - [0:0]\n", CHAIN));
+    for chain in chains.keys().chain(stale.iter().filter(|chain| !chains.contains_key(*chain))) {
+        script.push_str(&format!(":{} - [0:0]\n", chain));
+    }
+    for rule in main.iter().chain(chains.values().flatten()) {
+        script.push_str(rule);
+        script.push('\n');
+    }
+    for chain in stale.iter().filter(|chain| !chains.contains_key(*chain)) {
+        script.push_str(&format!("-X {}\n", chain));
+    }
+    script.push_str("COMMIT\n");
+    script
+}
+
+// Whether the policy isolates the pod in that direction: it's in the
+// policy's namespace, its podSelector picks it and the policy has that
+// type. Without policyTypes every policy is an Ingress one, and those with
+// egress rules Egress ones too.
+fn isolates(policy: &Value, pod: &Value, direction: Direction) -> bool {
+    let spec = &policy["spec"];
+    let typed = match spec["policyTypes"].as_array() {
+        Some(types) => types.iter().any(|t| t == direction.policy_type()),
+        None => match direction {
+            Direction::Ingress => true,
+            Direction::Egress => !spec["egress"].is_null(),
+        },
+    };
+    typed && policy["metadata"]["namespace"] == pod["metadata"]["namespace"] && selects(&spec["podSelector"], &pod["metadata"]["labels"])
+}
+
+// An ipBlock's network and exceptions, if they all parse as the IPv4
+// networks iptables takes. Policies are checked as they're written, but one
+// that doesn't parse is left out rather than trusted into the ruleset.
+fn ip_block(block: &Value) -> Option<(Cidr, Vec<Cidr>)> {
+    let parse = |value: &Value| value.as_str().and_then(Cidr::parse).filter(|cidr| cidr.address.is_ipv4());
+    let cidr = parse(&block["cidr"])?;
+    let excepts = match &block["except"] {
+        Value::Null => Vec::new(),
+        excepts => excepts.as_array()?.iter().map(parse).collect::<Option<Vec<_>>>()?,
+    };
+    Some((cidr, excepts))
+}
+
+// The pods a podSelector and namespaceSelector peer picks: those in the
+// policy's namespace without a namespaceSelector, and every pod of the
+// namespaces picked without a podSelector
+fn peer_pods<'a>(peer: &Value, namespace: &str, pods: &'a [PolicyPod], namespaces: &BTreeMap<String, Value>) -> Vec<&'a PolicyPod> {
+    pods.iter()
+        .filter(|candidate| {
+            let pod_namespace = candidate.pod["metadata"]["namespace"].as_str().unwrap_or("default");
+            let in_namespace = match &peer["namespaceSelector"] {
+                Value::Null => pod_namespace == namespace,
+                selector => namespaces.get(pod_namespace).is_some_and(|labels| selects(selector, labels)),
+            };
+            in_namespace && !candidate.ips.is_empty() && selects(&peer["podSelector"], &candidate.pod["metadata"]["labels"])
+        })
+        .collect()
+}
+
+fn selects(selector: &Value, labels: &Value) -> bool {
+    LabelSelector::from_value(selector).is_ok_and(|selector| selector.matches(labels))
+}
+
+// The pod whose containers name the rule's named ports: the isolated pod
+// for ingress, and for egress the peer, when it's a pod
+fn port_owner<'a>(direction: Direction, target: &'a PolicyPod, peer: Option<&'a PolicyPod>) -> Option<&'a Value> {
+    match direction {
+        Direction::Ingress => Some(&target.pod),
+        Direction::Egress => peer.map(|peer| &peer.pod),
+    }
+}
+
+// The iptables matches for a rule's ports, each with a leading space, or a
+// single empty one for a rule without ports. Named ports the pod doesn't
+// have match nothing, and neither do ports that aren't valid.
+fn port_matches(ports: &Value, owner: Option<&Value>) -> Vec<String> {
+    let Some(ports) = ports.as_array().filter(|ports| !ports.is_empty()) else {
+        return vec![String::new()];
+    };
+    let mut matches = Vec::new();
+    for port in ports {
+        let protocol = match &port["protocol"] {
+            Value::Null => "TCP",
+            protocol => match protocol.as_str().filter(|protocol| PROTOCOLS.contains(protocol)) {
+                Some(protocol) => protocol,
+                None => continue,
+            },
+        };
+        let number = match &port["port"] {
+            Value::Null => None,
+            Value::Number(number) => match number.as_i64().filter(|&number| is_port_number(number)) {
+                Some(number) => Some(number),
+                None => continue,
+            },
+            Value::String(name) => match named_port(owner, name, protocol).filter(|&number| is_port_number(number)) {
+                Some(number) => Some(number),
+                None => continue,
+            },
+            _ => continue,
+        };
+        let mut fragment = format!(" -p {}", protocol.to_lowercase());
+        match (number, &port["endPort"]) {
+            (Some(start), Value::Null) => fragment.push_str(&format!(" --dport {}", start)),
+            (Some(start), end) => match end.as_i64().filter(|&end| is_port_number(end) && end >= start) {
+                Some(end) => fragment.push_str(&format!(" --dport {}:{}", start, end)),
+                None => continue,
+            },
+            (None, _) => {}
+        }
+        matches.push(fragment);
+    }
+    matches
+}
+
+fn named_port(owner: Option<&Value>, name: &str, protocol: &str) -> Option<i64> {
+    let containers = owner?["spec"]["containers"].as_array()?;
+    containers
+        .iter()
+        .flat_map(|container| container["ports"].as_array().into_iter().flatten())
+        .find(|port| port["name"] == name && port["protocol"].as_str().unwrap_or("TCP") == protocol)
+        .and_then(|port| port["containerPort"].as_i64())
+}
+
+pub struct NetworkPolicyEnforcer {
+    storage: Storage,
+    docker: Docker,
+}
+
+impl NetworkPolicyEnforcer {
+    /// An enforcer for the containers of the local Docker daemon, if
+    /// iptables can be used here.
+    pub async fn new(storage: Storage) -> Result<Self> {
+        let docker = Docker::connect_with_local_defaults()?;
+        docker.ping().await?;
+        let output = Command::new("iptables")
+            .args(["-w", "-S", "DOCKER-USER"])
+            .output()
+            .await
+            .context("iptables isn't available")?;
+        if !output.status.success() {
+            bail!("iptables can't list DOCKER-USER: {}", String::from_utf8_lossy(&output.stderr).trim());
+        }
+        Ok(Self { storage, docker })
+    }
+
+    pub async fn run(&self) -> Result<()> {
+        info!("Enforcing NetworkPolicies with iptables");
+        let mut applied = None;
+
+        loop {
+            let started = Instant::now();
+            match self.sync(applied.as_deref()).await {
+                Ok(ruleset) => applied = Some(ruleset),
+                Err(e) => error!("NetworkPolicy enforcement error: {:#}", e),
+            }
+
+            profiling::record("NetworkPolicy enforcer", started);
+            tokio::time::sleep(Duration::from_secs(2)).await;
+        }
+    }
+
+    // Applies the ruleset for the policies and pods there are now, unless
+    // it's the one last applied, returning it
+    async fn sync(&self, applied: Option<&str>) -> Result<String> {
+        let policies = self.storage.networkpolicies().list(None).await?;
+        let policies = policies["items"].as_array().cloned().unwrap_or_default();
+        let pods = self.pods().await?;
+        let namespaces = self.namespaces().await?;
+        let stale = existing_chains().await?;
+        let script = ruleset(&policies, &pods, &namespaces, &stale);
+        // Chains left to delete change the script, so it's applied again
+        // until they're gone
+        if applied == Some(script.as_str()) {
+            return Ok(script);
+        }
+
+        restore(&script).await?;
+        let jump = ["-w", "-C", "DOCKER-USER", "-j", CHAIN];
+        if !Command::new("iptables").args(jump).output().await?.status.success() {
+            let output = Command::new("iptables").args(["-w", "-I", "DOCKER-USER", "1", "-j", CHAIN]).output().await?;
+            if !output.status.success() {
+                bail!("failed to jump to {} from DOCKER-USER: {}", CHAIN, String::from_utf8_lossy(&output.stderr).trim());
+            }
+        }
+        info!("Applied NetworkPolicies to {} pods", pods.len());
+        Ok(script)
+    }
+
+    // The pods with running containers, with their containers' addresses
+    async fn pods(&self) -> Result<Vec<PolicyPod>> {
+        let filters = HashMap::from([("label".to_string(), vec!["io.kubernetes.pod.uid".to_string()])]);
+        let containers = self
+            .docker
+            .list_containers(Some(bollard::container::ListContainersOptions { filters, ..Default::default() }))
+            .await?;
+        let mut ips: HashMap<String, Vec<String>> = HashMap::new();
+        for container in containers {
+            let Some(uid) = container.labels.as_ref().and_then(|labels| labels.get("io.kubernetes.pod.uid")) else {
+                continue;
+            };
+            let networks = container.network_settings.and_then(|settings| settings.networks).unwrap_or_default();
+            let addresses = networks.into_values().filter_map(|network| network.ip_address).filter(|ip| !ip.is_empty());
+            ips.entry(uid.clone()).or_default().extend(addresses);
+        }
+
+        let pods = self.storage.pods().list(None).await?;
+        let mut found = Vec::new();
+        for pod in pods["items"].as_array().into_iter().flatten() {
+            let uid = pod["metadata"]["uid"].as_str().unwrap_or_default();
+            if let Some(mut addresses) = ips.remove(uid) {
+                addresses.sort();
+                found.push(PolicyPod { pod: pod.clone(), ips: addresses });
+            }
+        }
+        Ok(found)
+    }
+
+    // The labels of each namespace, kubernetes.io/metadata.name among them
+    async fn namespaces(&self) -> Result<BTreeMap<String, Value>> {
+        let rows = sqlx::query("SELECT name, labels FROM namespaces WHERE deletion_timestamp IS NULL")
+            .fetch_all(&*self.storage.pool)
+            .await?;
+        let mut namespaces = BTreeMap::new();
+        for row in rows {
+            let name: String = row.get("name");
+            let labels = row.get::<Option<String>, _>("labels").and_then(|labels| serde_json::from_str::<Value>(&labels).ok());
+            let mut labels = labels.filter(Value::is_object).unwrap_or_else(|| json!({}));
+            labels["kubernetes.io/metadata.name"] = json!(name);
+            namespaces.insert(name, labels);
+        }
+        Ok(namespaces)
+    }
+}
+
+// The chains of the ruleset in the filter table now, but KRUST-NETPOL itself
+async fn existing_chains() -> Result<Vec<String>> {
+    let output = Command::new("iptables-save").args(["-t", "filter"]).output().await.context("failed to run iptables-save")?;
+    if !output.status.success() {
+        bail!("iptables-save failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    let prefix = format!(":{}-", CHAIN);
+    Ok(String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| line.strip_prefix(&prefix))
+        .filter_map(|line| line.split_whitespace().next())
+        .map(|suffix| format!("{}-{}", CHAIN, suffix))
+        .collect())
+}
+
+async fn restore(script: &str) -> Result<()> {
+    let mut child = Command::new("iptables-restore")
+        .args(["-w", "--noflush"])
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run iptables-restore")?;
+    let mut stdin = child.stdin.take().ok_or_else(|| anyhow!("iptables-restore has no stdin"))?;
+    stdin.write_all(script.as_bytes()).await?;
+    drop(stdin);
+    let output = child.wait_with_output().await?;
+    if !output.status.success() {
+        bail!("iptables-restore failed: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
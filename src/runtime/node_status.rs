@@ -0,0 +1,217 @@
+// A kubelet's heartbeats. Every nodeStatus period, ten seconds by default,
+// it renews its node's Lease in kube-node-lease, which is what says the
+// node is alive, and works out the node's conditions: MemoryPressure and
+// DiskPressure as the kubelet's default hard eviction thresholds have
+// them, PIDPressure, and Ready. Those go into the node's status when one of
+// them changes, and otherwise every five minutes, as the kubelet's
+// nodeStatusReportFrequency has it.
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::time::{Duration, Instant};
+use tracing::{error, info};
+
+use super::ephemeral_storage;
+use super::fake_kubelet::MEMORY_USAGE_ANNOTATION;
+use crate::controllers::Resync;
+use crate::models::{node, quantity, time};
+use crate::profiling;
+use crate::{Config, Storage};
+
+/// Where nodes' leases are kept.
+pub const LEASE_NAMESPACE: &str = "kube-node-lease";
+
+/// How long a node's lease lasts without being renewed.
+pub const LEASE_DURATION_SECONDS: i64 = 40;
+
+// How often conditions are reported when none of them changes
+const REPORT_INTERVAL: Duration = Duration::from_secs(300);
+
+// memory.available<100Mi
+const MEMORY_AVAILABLE_MIN: i64 = 100 * 1024 * 1024;
+
+// nodefs.available<10%
+const DISK_AVAILABLE_MIN_PERCENT: u64 = 10;
+
+/// Where a node's heartbeat learns how much memory is left.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MemorySource {
+    /// What the host has available, for the node whose pods really run
+    Host,
+    /// The node's allocatable memory, less what its running pods make out
+    /// to use with the fake memory usage annotation
+    Simulated,
+}
+
+#[derive(Clone)]
+pub struct Heartbeat {
+    storage: Storage,
+    config: Config,
+    node_name: String,
+    memory: MemorySource,
+    resync: Resync,
+    // The condition statuses last reported, and when
+    reported: Option<(Vec<(String, String)>, Instant)>,
+}
+
+impl Heartbeat {
+    pub fn new(storage: Storage, config: &Config, node_name: &str, memory: MemorySource) -> Self {
+        Self {
+            storage,
+            config: config.clone(),
+            node_name: node_name.to_string(),
+            memory,
+            resync: Resync::new(config, "nodeStatus"),
+            reported: None,
+        }
+    }
+
+    pub async fn run(mut self) {
+        info!("Starting node status heartbeats for node {}", self.node_name);
+
+        let name = format!("node status {}", self.node_name);
+        loop {
+            let started = Instant::now();
+            if let Err(e) = self.beat().await {
+                error!("Node status error for node {}: {}", self.node_name, e);
+            }
+            profiling::record(&name, started);
+
+            self.resync.wait().await;
+        }
+    }
+
+    /// Reports the node's conditions if they changed or are due, and
+    /// renews its lease.
+    pub async fn beat(&mut self) -> Result<()> {
+        let conditions = self.conditions().await?;
+        let statuses: Vec<(String, String)> = conditions
+            .iter()
+            .map(|c| (c["type"].as_str().unwrap_or_default().to_string(), c["status"].as_str().unwrap_or_default().to_string()))
+            .collect();
+        let due = match &self.reported {
+            Some((reported, at)) => *reported != statuses || at.elapsed() >= REPORT_INTERVAL,
+            None => true,
+        };
+
+        // The status comes first: the lease is owned by the node, which the
+        // garbage collector only finds once the node has been written
+        if due {
+            self.report(conditions).await?;
+            self.reported = Some((statuses, Instant::now()));
+        }
+
+        let node = node::get(&self.config, &self.node_name, &Default::default()).ok_or_else(|| anyhow!("Node {} not found", self.node_name))?;
+        let owner = json!({
+            "apiVersion": "v1",
+            "kind": "Node",
+            "name": self.node_name,
+            "uid": node["metadata"]["uid"]
+        });
+        self.storage
+            .leases()
+            .renew(LEASE_NAMESPACE, &self.node_name, &self.node_name, LEASE_DURATION_SECONDS, owner)
+            .await?;
+        Ok(())
+    }
+
+    // Writes the conditions into the node's status. A condition keeps its
+    // lastTransitionTime for as long as its status stays the same.
+    async fn report(&self, mut conditions: Vec<Value>) -> Result<()> {
+        let stored = self.storage.nodes().stored().await?;
+        let previous = stored.get(&self.node_name).and_then(|node| node.status.as_ref()).map(|status| status["conditions"].clone());
+        let now = time::now();
+        for condition in conditions.iter_mut() {
+            let transitioned = previous
+                .iter()
+                .flat_map(|conditions| conditions.as_array().into_iter().flatten())
+                .find(|c| c["type"] == condition["type"] && c["status"] == condition["status"])
+                .map(|c| c["lastTransitionTime"].clone());
+            condition["lastHeartbeatTime"] = json!(now);
+            condition["lastTransitionTime"] = transitioned.unwrap_or_else(|| json!(now));
+        }
+
+        let mut node = node::get(&self.config, &self.node_name, &stored).ok_or_else(|| anyhow!("Node {} not found", self.node_name))?;
+        node["status"]["conditions"] = json!(conditions);
+        self.storage.nodes().update_status(node).await?;
+        Ok(())
+    }
+
+    async fn conditions(&self) -> Result<Vec<Value>> {
+        let memory_pressure = self.memory_available().await? < MEMORY_AVAILABLE_MIN;
+        let disk_pressure = self.disk_pressure().await?;
+        Ok(vec![
+            condition("MemoryPressure", memory_pressure, if memory_pressure {
+                ("KubeletHasInsufficientMemory", "kubelet has insufficient memory available")
+            } else {
+                ("KubeletHasSufficientMemory", "kubelet has sufficient memory available")
+            }),
+            condition("DiskPressure", disk_pressure, if disk_pressure {
+                ("KubeletHasDiskPressure", "kubelet has disk pressure")
+            } else {
+                ("KubeletHasNoDiskPressure", "kubelet has no disk pressure")
+            }),
+            condition("PIDPressure", false, ("KubeletHasSufficientPID", "kubelet has sufficient PID available")),
+            condition("Ready", true, ("KubeletReady", "kubelet is posting ready status")),
+        ])
+    }
+
+    // Bytes of memory left for pods
+    async fn memory_available(&self) -> Result<i64> {
+        let allocatable = self.config.node(&self.node_name).capacity().memory_bytes;
+        if self.memory == MemorySource::Host {
+            return Ok(host_memory_available().unwrap_or(allocatable));
+        }
+
+        let rows = sqlx::query(
+            "SELECT spec, annotations FROM pods
+             WHERE node_name = ? AND phase = 'Running' AND deletion_timestamp IS NULL AND annotations LIKE ?"
+        )
+        .bind(&self.node_name)
+        .bind(format!("%{}%", MEMORY_USAGE_ANNOTATION))
+        .fetch_all(&*self.storage.pool)
+        .await?;
+
+        let mut used = 0.0;
+        for row in rows {
+            let spec: Value = serde_json::from_str(&row.get::<String, _>("spec"))?;
+            let annotations: Value = serde_json::from_str(&row.get::<String, _>("annotations"))?;
+            let per_container = annotations[MEMORY_USAGE_ANNOTATION].as_str().and_then(quantity::parse).unwrap_or(0.0);
+            used += per_container * spec["containers"].as_array().map_or(0, Vec::len) as f64;
+        }
+        Ok(allocatable - used as i64)
+    }
+
+    // Whether the node's pods leave less than 10% of its ephemeral storage
+    async fn disk_pressure(&self) -> Result<bool> {
+        let Some(capacity) = self.config.node(&self.node_name).ephemeral_storage.as_deref().and_then(|s| quantity::bytes(&Value::from(s))) else {
+            return Ok(false);
+        };
+        let summary = ephemeral_storage::summary(&self.storage, &self.node_name).await?;
+        let used: u64 = summary["pods"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|pod| pod["ephemeral-storage"]["usedBytes"].as_u64())
+            .sum();
+        let capacity = capacity.max(0) as u64;
+        Ok(capacity.saturating_sub(used) < capacity * DISK_AVAILABLE_MIN_PERCENT / 100)
+    }
+}
+
+fn condition(kind: &str, status: bool, (reason, message): (&str, &str)) -> Value {
+    json!({
+        "type": kind,
+        "status": if status { "True" } else { "False" },
+        "reason": reason,
+        "message": message
+    })
+}
+
+// MemAvailable from /proc/meminfo, or None where there's no /proc
+fn host_memory_available() -> Option<i64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    let line = meminfo.lines().find(|line| line.starts_with("MemAvailable:"))?;
+    let kb: i64 = line.split_whitespace().nth(1)?.parse().ok()?;
+    Some(kb * 1024)
+}
@@ -0,0 +1,122 @@
+// The pod network: a Docker bridge network krust manages, from whose subnet
+// each pod not on its node's network gets an address of its own. The
+// address is picked as the pod starts and recorded as its status.podIP
+// there and then, so the downward API has it and no other pod is given it,
+// and the pod's first container is started with it; Docker keeps it across
+// restarts of the container. A pod's other containers get addresses from
+// the upper half of the subnet, which Docker hands out itself. Pods on
+// simulated nodes are given addresses from the same range.
+use anyhow::{anyhow, Context, Result};
+use bollard::models::{EndpointIpamConfig, EndpointSettings, Ipam, IpamConfig};
+use bollard::network::CreateNetworkOptions;
+use bollard::Docker;
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::collections::{BTreeSet, HashMap};
+use std::net::Ipv4Addr;
+use tokio::sync::Mutex;
+use tracing::info;
+
+use crate::Storage;
+
+/// The Docker network pods' containers are attached to.
+pub const NETWORK: &str = "krust";
+/// The pod network's subnet, cluster-info's podSubnet.
+pub const SUBNET: &str = "10.244.0.0/16";
+/// The host's address on the pod network.
+pub const GATEWAY: Ipv4Addr = Ipv4Addr::new(10, 244, 0, 1);
+
+// Where Docker picks addresses itself, for pods' other containers
+const DOCKER_RANGE: &str = "10.244.128.0/17";
+// The addresses pods are given
+const FIRST: Ipv4Addr = Ipv4Addr::new(10, 244, 0, 2);
+const LAST: Ipv4Addr = Ipv4Addr::new(10, 244, 127, 254);
+
+// Addresses are picked one at a time, so that two pods can't get the same
+static ASSIGNING: Mutex<()> = Mutex::const_new(());
+
+/// Creates the pod network unless Docker has it already.
+pub async fn ensure(docker: &Docker) -> Result<()> {
+    if docker.inspect_network::<String>(NETWORK, None).await.is_ok() {
+        return Ok(());
+    }
+    let options = CreateNetworkOptions {
+        name: NETWORK,
+        driver: "bridge",
+        check_duplicate: true,
+        ipam: Ipam {
+            config: Some(vec![IpamConfig {
+                subnet: Some(SUBNET.to_string()),
+                ip_range: Some(DOCKER_RANGE.to_string()),
+                gateway: Some(GATEWAY.to_string()),
+                ..Default::default()
+            }]),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    docker
+        .create_network(options)
+        .await
+        .with_context(|| format!("failed to create the pod network {} ({})", NETWORK, SUBNET))?;
+    info!("Created the pod network {} ({})", NETWORK, SUBNET);
+    Ok(())
+}
+
+/// The lowest address for a pod that isn't one of `used`.
+pub fn next_free(used: &BTreeSet<Ipv4Addr>) -> Option<Ipv4Addr> {
+    (u32::from(FIRST)..=u32::from(LAST)).map(Ipv4Addr::from).find(|ip| !used.contains(ip))
+}
+
+/// Whether `ip` is one pods are given.
+pub fn is_pod_address(ip: Ipv4Addr) -> bool {
+    (FIRST..=LAST).contains(&ip)
+}
+
+/// The address of a pod: its node's for hostNetwork pods, or else the one
+/// it was given, giving it one first if it hasn't one yet.
+pub async fn assign(storage: &Storage, uid: &str, spec: &Value, host_ip: &str) -> Result<String> {
+    if spec["hostNetwork"].as_bool().unwrap_or(false) {
+        return Ok(host_ip.to_string());
+    }
+
+    let _assigning = ASSIGNING.lock().await;
+    let rows = sqlx::query("SELECT uid, phase, status FROM pods").fetch_all(&*storage.pool).await?;
+    let mut used = BTreeSet::new();
+    for row in rows {
+        let status: Value = serde_json::from_str(&row.get::<String, _>("status")).unwrap_or_default();
+        let Some(ip) = status["podIP"].as_str().and_then(|ip| ip.parse::<Ipv4Addr>().ok()) else {
+            continue;
+        };
+        if row.get::<String, _>("uid") == uid {
+            if is_pod_address(ip) {
+                return Ok(ip.to_string());
+            }
+            continue;
+        }
+        // Pods that have finished are done with theirs
+        let phase: Option<String> = row.get("phase");
+        if !matches!(phase.as_deref(), Some("Succeeded" | "Failed")) {
+            used.insert(ip);
+        }
+    }
+
+    let ip = next_free(&used).ok_or_else(|| anyhow!("no addresses left in the pod network {}", SUBNET))?;
+    storage.pods().set_status_fields(uid, &addresses(&ip.to_string())).await?;
+    Ok(ip.to_string())
+}
+
+/// The status fields giving a pod its address.
+pub fn addresses(ip: &str) -> [(&'static str, Value); 2] {
+    [("podIP", json!(ip)), ("podIPs", json!([{ "ip": ip }]))]
+}
+
+/// Where a container joins the pod network, with `ip` for the pod's first
+/// container.
+pub fn endpoints(ip: Option<&str>) -> HashMap<String, EndpointSettings> {
+    let settings = EndpointSettings {
+        ipam_config: ip.map(|ip| EndpointIpamConfig { ipv4_address: Some(ip.to_string()), ..Default::default() }),
+        ..Default::default()
+    };
+    HashMap::from([(NETWORK.to_string(), settings)])
+}
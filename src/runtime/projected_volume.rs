@@ -0,0 +1,139 @@
+// Projected volumes, as the kubelet writes them: the files of a volume's
+// sources (serviceAccountToken, configMap, secret and downwardAPI) in one
+// directory that containers mount. Service account tokens are requested with
+// the source's audience and expirationSeconds and bound to the pod, and
+// reissued once 80% of their lifetime or 24 hours have passed, like the
+// kubelet's token manager does. Files are replaced by renaming a new one
+// over them, so a container re-reading its token never sees half of one.
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::{STANDARD, URL_SAFE_NO_PAD};
+use base64::Engine;
+use chrono::{DateTime, TimeZone, Utc};
+use serde_json::{json, Value};
+use std::path::{Component, Path, PathBuf};
+
+use super::env;
+use crate::Storage;
+
+// The kubelet's defaults for a serviceAccountToken source
+const DEFAULT_EXPIRATION_SECONDS: i64 = 3600;
+const MAX_TOKEN_AGE_SECONDS: i64 = 24 * 60 * 60;
+
+/// Writes the sources of a pod's projected `volume` into `dir`. Tokens
+/// already there are kept until `refresh_at` says they're due.
+pub async fn project(storage: &Storage, pod: &Value, volume: &Value, dir: &Path) -> Result<()> {
+    let namespace = pod["metadata"]["namespace"].as_str().unwrap_or("default");
+    std::fs::create_dir_all(dir)?;
+    for source in volume["projected"]["sources"].as_array().into_iter().flatten() {
+        if let Some(token) = source.get("serviceAccountToken") {
+            let path = file(dir, token["path"].as_str().unwrap_or_default())?;
+            let current = std::fs::read_to_string(&path).ok();
+            if current.as_deref().and_then(refresh_at).is_some_and(|at| at > Utc::now()) {
+                continue;
+            }
+            let issued = request_token(storage, pod, token).await?;
+            write_atomically(&path, issued.as_bytes())?;
+        } else if let Some(config_map) = source.get("configMap") {
+            let name = config_map["name"].as_str().unwrap_or_default();
+            let data = match storage.configmaps().get(namespace, name).await {
+                Ok(config_map) => config_map["data"].clone(),
+                Err(_) if config_map["optional"] == true => continue,
+                Err(e) => return Err(e),
+            };
+            let data = data.as_object().into_iter().flatten().map(|(key, value)| {
+                (key.clone(), value.as_str().unwrap_or_default().as_bytes().to_vec())
+            });
+            write_keys(dir, &config_map["items"], data.collect())?;
+        } else if let Some(secret) = source.get("secret") {
+            let name = secret["name"].as_str().unwrap_or_default();
+            let data = match storage.secrets().get(namespace, name).await {
+                Ok(secret) => secret["data"].clone(),
+                Err(_) if secret["optional"] == true => continue,
+                Err(e) => return Err(e),
+            };
+            let data = data.as_object().into_iter().flatten().map(|(key, value)| {
+                (key.clone(), STANDARD.decode(value.as_str().unwrap_or_default()).unwrap_or_default())
+            });
+            write_keys(dir, &secret["items"], data.collect())?;
+        } else if let Some(downward_api) = source.get("downwardAPI") {
+            for item in downward_api["items"].as_array().into_iter().flatten() {
+                let Some(value) = item["fieldRef"]["fieldPath"].as_str().and_then(|field| env::field(pod, field)) else {
+                    continue;
+                };
+                write_atomically(&file(dir, item["path"].as_str().unwrap_or_default())?, value.as_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// When a projected token is due to be reissued: once 80% of its lifetime
+/// has passed, or 24 hours, whichever comes first. None if it can't be read.
+pub fn refresh_at(token: &str) -> Option<DateTime<Utc>> {
+    let payload = token.split('.').nth(1)?;
+    let claims: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload.trim()).ok()?).ok()?;
+    let (issued, expires) = (claims["iat"].as_i64()?, claims["exp"].as_i64()?);
+    let lifetime = (expires - issued) * 8 / 10;
+    Utc.timestamp_opt(issued + lifetime.min(MAX_TOKEN_AGE_SECONDS), 0).single()
+}
+
+// Asks for a token for the pod's service account, as the kubelet does with
+// a TokenRequest
+async fn request_token(storage: &Storage, pod: &Value, source: &Value) -> Result<String> {
+    let metadata = &pod["metadata"];
+    let namespace = metadata["namespace"].as_str().unwrap_or("default");
+    let service_account = pod["spec"]["serviceAccountName"].as_str().unwrap_or("default");
+    let audiences: Vec<&str> = source["audience"].as_str().filter(|a| !a.is_empty()).into_iter().collect();
+    let request = json!({
+        "spec": {
+            "audiences": audiences,
+            "expirationSeconds": source["expirationSeconds"].as_i64().unwrap_or(DEFAULT_EXPIRATION_SECONDS),
+            "boundObjectRef": { "kind": "Pod", "apiVersion": "v1", "name": metadata["name"], "uid": metadata["uid"] }
+        }
+    });
+    let issued = storage
+        .serviceaccounts()
+        .create_token(namespace, service_account, request)
+        .await
+        .with_context(|| format!("failed to request a token for service account {}/{}", namespace, service_account))?;
+    Ok(issued["status"]["token"].as_str().unwrap_or_default().to_string())
+}
+
+// Writes the keys `items` picks, each to the path it gives, or all of them
+// under their own names if it picks none
+fn write_keys(dir: &Path, items: &Value, data: Vec<(String, Vec<u8>)>) -> Result<()> {
+    let Some(items) = items.as_array().filter(|items| !items.is_empty()) else {
+        for (key, value) in &data {
+            write_atomically(&file(dir, key)?, value)?;
+        }
+        return Ok(());
+    };
+    for item in items {
+        let key = item["key"].as_str().unwrap_or_default();
+        if let Some((_, value)) = data.iter().find(|(k, _)| k == key) {
+            write_atomically(&file(dir, item["path"].as_str().unwrap_or(key))?, value)?;
+        }
+    }
+    Ok(())
+}
+
+// Where a source's path is within the volume. Paths can't climb out of it.
+fn file(dir: &Path, path: &str) -> Result<PathBuf> {
+    let relative = Path::new(path);
+    if path.is_empty() || !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        anyhow::bail!("invalid projected volume path {:?}", path);
+    }
+    Ok(dir.join(relative))
+}
+
+// Replaces the file with new contents by renaming a complete copy over it
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let dir = path.parent().context("projected file has no directory")?;
+    std::fs::create_dir_all(dir)?;
+    let name = path.file_name().context("projected file has no name")?.to_string_lossy();
+    let temporary = dir.join(format!("..{}.tmp", name));
+    std::fs::write(&temporary, contents)?;
+    std::fs::rename(&temporary, path)?;
+    Ok(())
+}
+
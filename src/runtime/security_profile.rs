@@ -0,0 +1,244 @@
+// Seccomp and AppArmor profiles of pod containers. A container's own
+// securityContext profile wins over the pod's, and for AppArmor the beta
+// per-container annotation is honored as well. Profiles become Docker
+// security options; a kind of profile the container runtime can't apply
+// (Docker Desktop has no AppArmor, for one) is left out, and the pod is told
+// so with a condition, an event and its compat annotation rather than the
+// container silently running without it.
+use anyhow::{anyhow, bail, Result};
+use serde_json::{json, Value};
+use std::path::Path;
+
+use super::compat;
+use super::kubelet::record_event;
+use crate::models::pod_conditions;
+use crate::storage::event_store::{self, ObjectReference};
+use crate::Storage;
+
+/// Pod condition saying whether every profile the pod asks for is applied.
+pub const CONDITION: &str = "krust.io/SecurityProfilesApplied";
+
+/// The beta AppArmor annotation, followed by the container name.
+pub const APPARMOR_ANNOTATION_PREFIX: &str = "container.apparmor.security.beta.kubernetes.io/";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Profile {
+    RuntimeDefault,
+    Unconfined,
+    /// A profile of the node's, by name for AppArmor and by path relative to
+    /// the seccomp root for seccomp.
+    Localhost(String),
+}
+
+impl Profile {
+    // A seccompProfile or appArmorProfile field, at `path`
+    fn from_field(value: &Value, path: &str) -> Result<Option<Self>> {
+        if value.is_null() {
+            return Ok(None);
+        }
+        let profile = match value["type"].as_str().unwrap_or("") {
+            "RuntimeDefault" => Profile::RuntimeDefault,
+            "Unconfined" => Profile::Unconfined,
+            "Localhost" => match value["localhostProfile"].as_str().filter(|p| !p.is_empty()) {
+                Some(name) => Profile::Localhost(name.to_string()),
+                None => bail!("{}.localhostProfile: Required value: must be set when profile type is \"Localhost\"", path),
+            },
+            other => bail!(
+                "{}.type: Unsupported value: \"{}\": supported values: \"Localhost\", \"RuntimeDefault\", \"Unconfined\"",
+                path, other
+            ),
+        };
+        Ok(Some(profile))
+    }
+
+    // The value of an AppArmor annotation, at `path`
+    fn from_annotation(value: &str, path: &str) -> Result<Self> {
+        match value {
+            "runtime/default" => Ok(Profile::RuntimeDefault),
+            "unconfined" => Ok(Profile::Unconfined),
+            _ => match value.strip_prefix("localhost/").filter(|name| !name.is_empty()) {
+                Some(name) => Ok(Profile::Localhost(name.to_string())),
+                None => bail!("{}: Invalid value: \"{}\": invalid AppArmor profile name", path, value),
+            },
+        }
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Profile::RuntimeDefault => "RuntimeDefault".to_string(),
+            Profile::Unconfined => "Unconfined".to_string(),
+            Profile::Localhost(name) => format!("Localhost {}", name),
+        }
+    }
+}
+
+/// The kinds of profile a container runtime can apply.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Support {
+    pub seccomp: bool,
+    pub apparmor: bool,
+}
+
+impl Support {
+    /// From the SecurityOptions of `docker info`, such as
+    /// `name=seccomp,profile=builtin` and `name=apparmor`.
+    pub fn from_docker(options: &[String]) -> Self {
+        let has = |kind: &str| options.iter().any(|option| option.split(',').any(|part| part == format!("name={}", kind)));
+        Self { seccomp: has("seccomp"), apparmor: has("apparmor") }
+    }
+
+    /// From a simulated node's `securityProfiles` setting.
+    pub fn from_kinds(kinds: &[String]) -> Self {
+        Self {
+            seccomp: kinds.iter().any(|kind| kind == "seccomp"),
+            apparmor: kinds.iter().any(|kind| kind == "apparmor"),
+        }
+    }
+}
+
+/// The profiles one container asks for, each with the field asking.
+#[derive(Debug, Default)]
+pub struct ContainerProfiles {
+    pub seccomp: Option<(String, Profile)>,
+    pub apparmor: Option<(String, Profile)>,
+}
+
+impl ContainerProfiles {
+    pub fn is_empty(&self) -> bool {
+        self.seccomp.is_none() && self.apparmor.is_none()
+    }
+}
+
+/// A profile the runtime can't apply: the field asking for it, and why.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Unapplied {
+    pub field: String,
+    pub message: String,
+}
+
+/// The profiles of the container at `index` of a pod with this spec and
+/// these annotations. Fails on a malformed profile.
+pub fn container_profiles(spec: &Value, annotations: &Value, index: usize) -> Result<ContainerProfiles> {
+    let container = &spec["containers"][index];
+    let container_path = format!("spec.containers[{}].securityContext", index);
+    let annotation = format!("{}{}", APPARMOR_ANNOTATION_PREFIX, container["name"].as_str().unwrap_or(""));
+
+    let field = |kind: &str| -> Result<Option<(String, Profile)>> {
+        let own = format!("{}.{}", container_path, kind);
+        if let Some(profile) = Profile::from_field(&container["securityContext"][kind], &own)? {
+            return Ok(Some((own, profile)));
+        }
+        let pod = format!("spec.securityContext.{}", kind);
+        Ok(Profile::from_field(&spec["securityContext"][kind], &pod)?.map(|profile| (pod, profile)))
+    };
+
+    let seccomp = field("seccompProfile")?;
+    let apparmor = match (field("appArmorProfile")?, annotations[&annotation].as_str()) {
+        // The container's field wins over the annotation, which wins over the pod's field
+        (Some((path, profile)), _) if path.starts_with("spec.containers") => Some((path, profile)),
+        (_, Some(value)) => {
+            let path = format!("metadata.annotations[{}]", annotation);
+            Some((path.clone(), Profile::from_annotation(value, &path)?))
+        }
+        (pod, None) => pod,
+    };
+
+    Ok(ContainerProfiles { seccomp, apparmor })
+}
+
+/// Checks the profiles of every container of a pod are well-formed.
+pub fn validate(pod: &Value) -> Result<()> {
+    let containers = pod["spec"]["containers"].as_array().map(Vec::len).unwrap_or(0);
+    for index in 0..containers {
+        container_profiles(&pod["spec"], &pod["metadata"]["annotations"], index)?;
+    }
+    Ok(())
+}
+
+/// The profiles a runtime with `support` can't apply. Unconfined needs
+/// nothing from the runtime, so it's always honored.
+pub fn unapplied(profiles: &ContainerProfiles, support: Support) -> Vec<Unapplied> {
+    let kinds = [("seccomp", &profiles.seccomp, support.seccomp), ("AppArmor", &profiles.apparmor, support.apparmor)];
+    kinds
+        .into_iter()
+        .filter_map(|(kind, requested, supported)| match requested {
+            Some((field, profile)) if !supported && *profile != Profile::Unconfined => Some(Unapplied {
+                field: field.clone(),
+                message: format!(
+                    "{} profile {} is not applied: the container runtime doesn't support {}",
+                    kind, profile.describe(), kind
+                ),
+            }),
+            _ => None,
+        })
+        .collect()
+}
+
+/// The Docker security options applying what a runtime with `support` can
+/// of `profiles`. RuntimeDefault is what Docker applies anyway; Localhost
+/// seccomp profiles are read from `seccomp_root`, as Docker takes the
+/// profile itself rather than a path.
+pub fn docker_options(profiles: &ContainerProfiles, support: Support, seccomp_root: &Path) -> Result<Vec<String>> {
+    let mut options = Vec::new();
+
+    match &profiles.seccomp {
+        Some((_, Profile::Unconfined)) if support.seccomp => options.push("seccomp=unconfined".to_string()),
+        Some((field, Profile::Localhost(name))) if support.seccomp => {
+            let path = seccomp_root.join(name);
+            let profile = std::fs::read_to_string(&path)
+                .map_err(|e| anyhow!("{}: cannot read seccomp profile {}: {}", field, path.display(), e))?;
+            options.push(format!("seccomp={}", profile));
+        }
+        _ => {}
+    }
+
+    match &profiles.apparmor {
+        Some((_, Profile::Unconfined)) if support.apparmor => options.push("apparmor=unconfined".to_string()),
+        Some((_, Profile::Localhost(name))) if support.apparmor => options.push(format!("apparmor={}", name)),
+        _ => {}
+    }
+
+    Ok(options)
+}
+
+/// Tells a pod, once its containers are started, whether the profiles it
+/// asked for were applied. Pods asking for none are left alone.
+pub(crate) async fn report(
+    storage: &Storage,
+    node_name: &str,
+    pod: &ObjectReference,
+    requested: bool,
+    unapplied: &[Unapplied],
+) -> Result<()> {
+    if !requested {
+        return Ok(());
+    }
+    let namespace = pod.namespace.as_deref().unwrap_or("default");
+    let current = storage.pods().get(namespace, &pod.name).await?;
+
+    let condition = if unapplied.is_empty() {
+        json!({ "type": CONDITION, "status": "True", "reason": "ProfilesApplied", "message": null })
+    } else {
+        let messages: Vec<&str> = unapplied.iter().map(|u| u.message.as_str()).collect();
+        json!({ "type": CONDITION, "status": "False", "reason": "ProfilesUnsupported", "message": messages.join("; ") })
+    };
+    let existing = current["status"]["conditions"].as_array().cloned().unwrap_or_default();
+    let conditions = pod_conditions::merge(&existing, &[condition]);
+    storage.pods().set_status_fields(&pod.uid, &[("conditions", json!(conditions))]).await?;
+
+    if unapplied.is_empty() {
+        return Ok(());
+    }
+
+    let mut fields = compat::recorded_fields(&current);
+    for u in unapplied {
+        if !fields.contains(&u.field) {
+            fields.push(u.field.clone());
+        }
+        record_event(storage, node_name, pod, event_store::WARNING, "SecurityProfileUnsupported", &u.message).await?;
+    }
+    let patch = json!({ "metadata": { "annotations": { compat::UNSUPPORTED_FIELDS_ANNOTATION: json!(fields).to_string() } } });
+    storage.pods().patch(namespace, &pod.name, patch).await?;
+
+    Ok(())
+}
@@ -0,0 +1,201 @@
+// A pod's volumes, as the kubelet sets them up before starting its
+// containers: emptyDirs, configMap, secret, downwardAPI and projected
+// volumes are directories of the pod's, hostPath volumes and the volumes
+// bound to persistentVolumeClaims are paths on the node, and emptyDirs with
+// medium Memory are a tmpfs in each container. Containers mount them as their
+// volumeMounts say, a subPath of a volume mounting just that part of it.
+use anyhow::{anyhow, bail, Context, Result};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Component, Path, PathBuf};
+
+use super::projected_volume;
+use crate::models::quantity;
+use crate::Storage;
+
+/// The volume sources krust sets up; pods with others are told they're
+/// unsupported.
+pub const SOURCES: &[&str] = &["configMap", "downwardAPI", "emptyDir", "hostPath", "persistentVolumeClaim", "projected", "secret"];
+
+/// Where a pod volume is for its containers to mount.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Volume {
+    /// A file or directory of the node's, bind-mounted.
+    Bind { path: PathBuf, read_only: bool },
+    /// A tmpfs with these mount options.
+    Tmpfs(String),
+}
+
+/// Sets up each of the pod's volumes, writing the files of its configMap,
+/// secret, downwardAPI and projected volumes under `pod_dir`.
+pub async fn prepare(storage: &Storage, pod: &Value, pod_dir: &Path) -> Result<HashMap<String, Volume>> {
+    let mut volumes = project(storage, pod, pod_dir).await?;
+    let namespace = pod["metadata"]["namespace"].as_str().unwrap_or("default");
+    for volume in pod["spec"]["volumes"].as_array().into_iter().flatten() {
+        let Some(name) = volume["name"].as_str() else {
+            continue;
+        };
+        let prepared = if let Some(empty_dir) = volume.get("emptyDir") {
+            if empty_dir["medium"] == "Memory" {
+                let size = quantity::bytes(&empty_dir["sizeLimit"]).map(|bytes| format!("size={}", bytes));
+                Volume::Tmpfs(size.unwrap_or_default())
+            } else {
+                let path = dir(pod_dir, "kubernetes.io~empty-dir", name);
+                std::fs::create_dir_all(&path)?;
+                Volume::Bind { path, read_only: false }
+            }
+        } else if let Some(host_path) = volume.get("hostPath") {
+            let path = host_path_of(host_path).with_context(|| format!("hostPath volume {}", name))?;
+            Volume::Bind { path, read_only: false }
+        } else if let Some(claim) = volume.get("persistentVolumeClaim") {
+            let path = claimed_path(storage, namespace, claim).await.with_context(|| format!("volume {}", name))?;
+            Volume::Bind { path, read_only: claim["readOnly"] == true }
+        } else {
+            continue;
+        };
+        volumes.insert(name.to_string(), prepared);
+    }
+    Ok(volumes)
+}
+
+/// Writes the pod's configMap, secret, downwardAPI and projected volumes,
+/// returning them. Rewriting them picks up changes to what they project.
+pub async fn project(storage: &Storage, pod: &Value, pod_dir: &Path) -> Result<HashMap<String, Volume>> {
+    let mut volumes = HashMap::new();
+    for volume in pod["spec"]["volumes"].as_array().into_iter().flatten() {
+        let Some(name) = volume["name"].as_str() else {
+            continue;
+        };
+        // A configMap, secret or downwardAPI volume is written as a
+        // projected volume of that one source
+        let (plugin, projected) = if volume.get("projected").is_some() {
+            ("kubernetes.io~projected", volume.clone())
+        } else if let Some(config_map) = volume.get("configMap") {
+            let source = json!({ "configMap": {
+                "name": config_map["name"],
+                "items": config_map["items"],
+                "optional": config_map["optional"]
+            }});
+            ("kubernetes.io~configmap", json!({ "projected": { "sources": [source] } }))
+        } else if let Some(secret) = volume.get("secret") {
+            let source = json!({ "secret": {
+                "name": secret["secretName"],
+                "items": secret["items"],
+                "optional": secret["optional"]
+            }});
+            ("kubernetes.io~secret", json!({ "projected": { "sources": [source] } }))
+        } else if let Some(downward_api) = volume.get("downwardAPI") {
+            let source = json!({ "downwardAPI": { "items": downward_api["items"] } });
+            ("kubernetes.io~downward-api", json!({ "projected": { "sources": [source] } }))
+        } else {
+            continue;
+        };
+        let path = dir(pod_dir, plugin, name);
+        projected_volume::project(storage, pod, &projected, &path)
+            .await
+            .with_context(|| format!("volume {}", name))?;
+        volumes.insert(name.to_string(), Volume::Bind { path, read_only: true });
+    }
+    Ok(volumes)
+}
+
+/// Where the pod keeps a volume of the given plugin, e.g.
+/// `kubernetes.io~empty-dir`.
+pub fn dir(pod_dir: &Path, plugin: &str, volume: &str) -> PathBuf {
+    pod_dir.join("volumes").join(plugin).join(volume)
+}
+
+/// The container's volumeMounts as Docker binds (`source:target[:ro]`) and
+/// tmpfs mounts. A subPath missing from a writable volume is made, as the
+/// kubelet does.
+pub fn mounts(container: &Value, volumes: &HashMap<String, Volume>) -> Result<(Vec<String>, HashMap<String, String>)> {
+    let mut binds = Vec::new();
+    let mut tmpfs = HashMap::new();
+    for mount in container["volumeMounts"].as_array().into_iter().flatten() {
+        let (Some(name), Some(mount_path)) = (mount["name"].as_str(), mount["mountPath"].as_str()) else {
+            continue;
+        };
+        let sub_path = mount["subPath"].as_str().unwrap_or_default();
+        match volumes.get(name) {
+            Some(Volume::Bind { path, read_only }) => {
+                let source = if sub_path.is_empty() {
+                    path.clone()
+                } else {
+                    let source = within(path, sub_path)?;
+                    if !*read_only && !source.exists() {
+                        std::fs::create_dir_all(&source)?;
+                    }
+                    source
+                };
+                let read_only = *read_only || mount["readOnly"] == true;
+                binds.push(format!("{}:{}{}", source.display(), mount_path, if read_only { ":ro" } else { "" }));
+            }
+            Some(Volume::Tmpfs(options)) => {
+                if !sub_path.is_empty() {
+                    bail!("volume {} is a tmpfs, which can't be mounted by subPath", name);
+                }
+                tmpfs.insert(mount_path.to_string(), options.clone());
+            }
+            // Volumes of other kinds aren't set up by krust
+            None => {}
+        }
+    }
+    Ok((binds, tmpfs))
+}
+
+// A subPath within a volume. It can't climb out of it.
+fn within(volume: &Path, sub_path: &str) -> Result<PathBuf> {
+    let relative = Path::new(sub_path);
+    if !relative.components().all(|c| matches!(c, Component::Normal(_))) {
+        bail!("invalid subPath {:?}", sub_path);
+    }
+    Ok(volume.join(relative))
+}
+
+// The path of a hostPath volume source, made or checked as its type says
+fn host_path_of(host_path: &Value) -> Result<PathBuf> {
+    let path = PathBuf::from(host_path["path"].as_str().filter(|p| !p.is_empty()).context("hostPath has no path")?);
+    let kind = host_path["type"].as_str().unwrap_or_default();
+    match kind {
+        "DirectoryOrCreate" => std::fs::create_dir_all(&path)?,
+        "FileOrCreate" if !path.exists() => {
+            std::fs::File::create(&path)?;
+        }
+        "" | "FileOrCreate" => {}
+        _ => {
+            let file_type = std::fs::metadata(&path)
+                .with_context(|| format!("{} does not exist", path.display()))?
+                .file_type();
+            let matches = match kind {
+                "Directory" => file_type.is_dir(),
+                "File" => file_type.is_file(),
+                "Socket" => file_type.is_socket(),
+                "CharDevice" => file_type.is_char_device(),
+                "BlockDevice" => file_type.is_block_device(),
+                _ => bail!("unknown hostPath type {}", kind),
+            };
+            if !matches {
+                bail!("{} is not a {}", path.display(), kind);
+            }
+        }
+    }
+    Ok(path)
+}
+
+// The node path of the PersistentVolume a claim is bound to
+async fn claimed_path(storage: &Storage, namespace: &str, claim: &Value) -> Result<PathBuf> {
+    let claim_name = claim["claimName"].as_str().unwrap_or_default();
+    let pvc = storage.persistent_volume_claims().get(namespace, claim_name).await?;
+    let volume_name = pvc["spec"]["volumeName"]
+        .as_str()
+        .ok_or_else(|| anyhow!("PersistentVolumeClaim {}/{} is not bound", namespace, claim_name))?;
+    let pv = storage.persistent_volumes().get(volume_name).await?;
+    if let Some(host_path) = pv["spec"].get("hostPath").filter(|h| h.is_object()) {
+        host_path_of(host_path)
+    } else if let Some(path) = pv["spec"]["local"]["path"].as_str() {
+        Ok(PathBuf::from(path))
+    } else {
+        bail!("PersistentVolume {} has no hostPath or local path for krust to mount", volume_name)
+    }
+}
@@ -1,113 +1,776 @@
-use crate::Storage;
+use crate::config::Taint;
+use crate::controllers::Resync;
+use crate::models::node;
+use crate::models::quantity::Resources;
+use crate::profiling;
+use crate::storage::event_store::{self, EventSource, ObjectReference};
+use crate::{Config, Storage};
 use anyhow::Result;
-use serde_json::Value;
+use serde_json::{json, Value};
+use sqlx::sqlite::SqliteRow;
 use sqlx::Row;
-use tracing::{info, warn};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::Instant;
+use tracing::{debug, info, warn};
+
+use self::topology::{Placement, PodRules};
+
+mod topology;
+
+/// The spec.schedulerName of the pods this scheduler places, which is
+/// also what pods without one get.
+pub const SCHEDULER_NAME: &str = "default-scheduler";
 
 pub struct Scheduler {
     storage: Storage,
-    node_name: String,
+    nodes: Vec<Node>,
+    resync: Resync,
+    // Why each pending pod last failed to schedule, so a FailedScheduling
+    // event is only reported again when the reason changes
+    failures: Mutex<HashMap<String, String>>,
+}
+
+/// A node as the scheduler sees it, from its settings in the config and
+/// its spec as last changed through the API.
+#[derive(Clone)]
+struct Node {
+    name: String,
+    capacity: Resources,
+    max_pods: usize,
+    labels: BTreeMap<String, String>,
+    taints: Vec<Taint>,
+    // Whether it's cordoned
+    unschedulable: bool,
 }
 
 impl Scheduler {
-    pub fn new(storage: Storage) -> Self {
-        Self { 
-            storage,
-            node_name: "krust-node".to_string(),
-        }
+    pub fn new(storage: Storage, config: &Config) -> Self {
+        let nodes = config
+            .node_names()
+            .into_iter()
+            .map(|name| {
+                let node = config.node(&name);
+                Node {
+                    capacity: node.capacity(),
+                    max_pods: node.pods,
+                    labels: node.node_labels(&name),
+                    taints: node.taints,
+                    unschedulable: false,
+                    name,
+                }
+            })
+            .collect();
+
+        Self { storage, nodes, resync: Resync::new(config, "scheduler"), failures: Mutex::default() }
     }
 
     pub async fn run(&self) -> Result<()> {
         info!("Starting scheduler");
         loop {
+            let started = Instant::now();
             if let Err(e) = self.schedule_pending_pods().await {
                 warn!("Scheduler error: {}", e);
             }
-            tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+            profiling::record("scheduler", started);
+            self.resync.wait().await;
         }
     }
 
     async fn schedule_pending_pods(&self) -> Result<()> {
-        // Find all pods in Pending phase without a node
+        // Find all pods in Pending phase without a node, leaving the ones
+        // that name another scheduler to it
         let rows = sqlx::query(
-            "SELECT uid, name, namespace FROM pods 
-             WHERE phase = 'Pending' AND node_name IS NULL AND deletion_timestamp IS NULL"
+            "SELECT uid, name, namespace, labels, spec, status FROM pods 
+             WHERE phase = 'Pending' AND node_name IS NULL AND deletion_timestamp IS NULL
+             AND COALESCE(json_extract(spec, '$.schedulerName'), ?) = ?
+             ORDER BY creation_timestamp, rowid"
         )
+        .bind(SCHEDULER_NAME)
+        .bind(SCHEDULER_NAME)
         .fetch_all(&*self.storage.pool)
         .await?;
-        
-        for row in rows {
-            let uid: String = row.get("uid");
-            let name: String = row.get("name");
-            let namespace: String = row.get("namespace");
-            
-            info!("Scheduling pod {}/{} to node {}", namespace, name, self.node_name);
-            
-            // Assign the pod to our single node
-            sqlx::query(
-                "UPDATE pods SET node_name = ?, phase = 'Scheduled' 
-                 WHERE uid = ? AND node_name IS NULL"
+
+        let mut pending: Vec<PodInfo> = rows.iter().map(PodInfo::from_row).collect::<Result<_>>()?;
+        // Higher priority pods are scheduled first; the sort is stable so equal
+        // priorities keep their creation order
+        pending.sort_by_key(|p| std::cmp::Reverse(p.priority));
+
+        let nodes = self.current_nodes().await?;
+        let mut bound = self.bound_pods().await?;
+        let namespaces = self.namespace_labels().await?;
+        self.failures.lock().unwrap().retain(|uid, _| pending.iter().any(|pod| pod.uid == *uid));
+        self.storage.scheduling_failures().prune().await?;
+
+        for pod in pending {
+            // What rules each node out for the pod, if anything. Terminating
+            // pods still hold their resources, ports and volumes until the
+            // kubelet has actually removed them
+            let claims = self.claim_use(&pod, &bound).await?;
+            let placement = Placement::new(&nodes, &bound, &namespaces);
+            let verdicts: Vec<(&Node, Vec<String>)> = nodes
+                .iter()
+                .map(|node| (node, node.filter(&pod, bound.get(&node.name).map_or(&[], |pods| pods), &claims, &placement)))
+                .collect();
+            let feasible: Vec<&Node> = verdicts.iter().filter(|(_, reasons)| reasons.is_empty()).map(|(node, _)| *node).collect();
+            let target = best_node(&pod, &feasible, &bound, &placement);
+
+            let Some(node) = target else {
+                self.record_failure(&pod, &verdicts).await?;
+                // Only nodes whose labels and taints allow the pod at all
+                for node in nodes.iter().filter(|node| node.rejections(&pod).is_empty()) {
+                    let on_node = bound.get(&node.name).map_or(&[][..], |pods| pods);
+                    if self.try_preempt(&pod, node, on_node).await? {
+                        break;
+                    }
+                }
+                continue;
+            };
+
+            info!("Scheduling pod {}/{} to node {}", pod.namespace, pod.name, node.name);
+
+            // Bound as through the binding subresource, which also clears
+            // any nomination left over from preemption. A pod someone else
+            // bound meanwhile is theirs.
+            if let Err(e) = self.storage.pods().bind_to_node(&pod.namespace, &pod.name, &node.name).await {
+                warn!("Failed to bind pod {}/{} to node {}: {}", pod.namespace, pod.name, node.name, e);
+                continue;
+            }
+
+            self.record_pod_event(
+                &pod,
+                event_store::NORMAL,
+                "Scheduled",
+                &format!("Successfully assigned {}/{} to {}", pod.namespace, pod.name, node.name),
             )
-            .bind(&self.node_name)
-            .bind(&uid)
-            .execute(&*self.storage.pool)
             .await?;
-            
-            // Record scheduling event
-            self.record_scheduling_event(&uid, &name, &namespace).await?;
+            self.failures.lock().unwrap().remove(&pod.uid);
+
+            bound.entry(node.name.clone()).or_default().push(pod);
         }
-        
+
         Ok(())
     }
 
-    async fn record_scheduling_event(&self, uid: &str, name: &str, namespace: &str) -> Result<()> {
-        // Get the updated pod
-        let pod_row = sqlx::query(
-            "SELECT * FROM pods WHERE uid = ?"
-        )
-        .bind(uid)
-        .fetch_one(&*self.storage.pool)
-        .await?;
-        
-        let spec_str: String = pod_row.get("spec");
-        let status_str: String = pod_row.get("status");
-        let labels_str: String = pod_row.get("labels");
-        let annotations_str: String = pod_row.get("annotations");
-        
-        let mut pod = serde_json::json!({
-            "apiVersion": "v1",
-            "kind": "Pod",
-            "metadata": {
-                "uid": uid,
-                "name": name,
-                "namespace": namespace,
-                "resourceVersion": pod_row.get::<i64, _>("resource_version").to_string(),
-                "creationTimestamp": pod_row.get::<String, _>("creation_timestamp"),
-            },
-            "spec": serde_json::from_str::<Value>(&spec_str)?,
-            "status": serde_json::from_str::<Value>(&status_str)?
-        });
-        
-        // Add node name to status
-        pod["status"]["phase"] = serde_json::json!("Scheduled");
-        pod["spec"]["nodeName"] = serde_json::json!(self.node_name);
-        
-        // Record event
-        sqlx::query(
-            "INSERT INTO events (resource_type, resource_uid, resource_name, resource_namespace, event_type, resource_version, timestamp, object)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+    /// The nodes, with the taints and unschedulable flag of their spec as
+    /// kubectl cordon or taint last left it.
+    async fn current_nodes(&self) -> Result<Vec<Node>> {
+        let stored = self.storage.nodes().stored().await?;
+        Ok(self
+            .nodes
+            .iter()
+            .map(|node| {
+                let Some(spec) = stored.get(&node.name).and_then(|stored| stored.spec.as_ref()) else {
+                    return node.clone();
+                };
+                let taints = serde_json::from_value(spec["taints"].clone()).unwrap_or_default();
+                let unschedulable = spec["unschedulable"].as_bool().unwrap_or(false);
+                Node { taints, unschedulable, ..node.clone() }
+            })
+            .collect())
+    }
+
+    /// Pods currently holding resources, including terminating ones, by node.
+    async fn bound_pods(&self) -> Result<HashMap<String, Vec<PodInfo>>> {
+        let rows = sqlx::query(
+            "SELECT uid, name, namespace, labels, spec, status, node_name, deletion_timestamp FROM pods 
+             WHERE node_name IS NOT NULL AND phase NOT IN ('Succeeded', 'Failed')"
         )
-        .bind("pods")
-        .bind(uid)
-        .bind(name)
-        .bind(namespace)
-        .bind("MODIFIED")
-        .bind(pod_row.get::<i64, _>("resource_version"))
-        .bind(chrono::Utc::now().to_rfc3339())
-        .bind(pod.to_string())
-        .execute(&*self.storage.pool)
+        .fetch_all(&*self.storage.pool)
         .await?;
-        
+
+        let mut bound: HashMap<String, Vec<PodInfo>> = HashMap::new();
+        for row in &rows {
+            let node_name: String = row.get("node_name");
+            bound.entry(node_name).or_default().push(PodInfo::from_row(row)?);
+        }
+        Ok(bound)
+    }
+
+    /// The labels of each namespace, for the namespaceSelectors of pod
+    /// affinity terms.
+    async fn namespace_labels(&self) -> Result<HashMap<String, Value>> {
+        let rows = sqlx::query("SELECT name, labels FROM namespaces WHERE deletion_timestamp IS NULL")
+            .fetch_all(&*self.storage.pool)
+            .await?;
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let labels = row.get::<Option<String>, _>("labels").and_then(|l| serde_json::from_str(&l).ok());
+                (row.get("name"), labels.unwrap_or_default())
+            })
+            .collect())
+    }
+
+    /// Where other pods already use the pod's ReadWriteOnce and
+    /// ReadWriteOncePod PersistentVolumeClaims.
+    async fn claim_use(&self, pod: &PodInfo, bound: &HashMap<String, Vec<PodInfo>>) -> Result<ClaimUse> {
+        let mut claims = ClaimUse::default();
+        for claim in &pod.claims {
+            // A claim that doesn't exist (yet) has no access modes to enforce
+            let Ok(pvc) = self.storage.persistent_volume_claims().get(&pod.namespace, claim).await else {
+                continue;
+            };
+            let modes = pvc["spec"]["accessModes"].as_array().cloned().unwrap_or_default();
+            let users = bound
+                .iter()
+                .flat_map(|(node, pods)| pods.iter().map(move |p| (node, p)))
+                .filter(|(_, p)| p.namespace == pod.namespace && p.claims.contains(claim));
+            for (node, _) in users {
+                if modes.iter().any(|m| m == "ReadWriteOncePod") {
+                    claims.taken = true;
+                } else if modes.iter().any(|m| m == "ReadWriteOnce") {
+                    claims.nodes.insert(node.clone());
+                }
+            }
+        }
+        Ok(claims)
+    }
+
+    /// Makes room for a pod that doesn't fit on `node` by gracefully deleting
+    /// lower priority pods. The preemptor is nominated to the node and stays
+    /// pending until the victims are gone; no further pods are preempted
+    /// meanwhile. Returns whether the pod is now waiting for this node.
+    async fn try_preempt(&self, pod: &PodInfo, node: &Node, bound: &[PodInfo]) -> Result<bool> {
+        if pod.preemption_policy == "Never" {
+            return Ok(false);
+        }
+
+        let terminating = bound.iter().any(|p| p.terminating);
+        if pod.nominated_node.as_deref() == Some(node.name.as_str()) && terminating {
+            return Ok(true);
+        }
+
+        // Evict the lowest priority pods first, newest first among equals
+        let mut candidates: Vec<&PodInfo> = bound
+            .iter()
+            .filter(|p| !p.terminating && p.priority < pod.priority)
+            .collect();
+        candidates.reverse();
+        candidates.sort_by_key(|p| p.priority);
+
+        let mut remaining: Vec<&PodInfo> = bound.iter().filter(|p| !p.terminating).collect();
+        let mut victims = Vec::new();
+        for candidate in candidates {
+            let used = remaining.iter().fold(Resources::default(), |sum, p| sum + p.requests);
+            if (used + pod.requests).fits_in(&node.capacity) && remaining.len() < node.max_pods {
+                break;
+            }
+            remaining.retain(|p| p.uid != candidate.uid);
+            victims.push(candidate);
+        }
+
+        let used = remaining.iter().fold(Resources::default(), |sum, p| sum + p.requests);
+        if victims.is_empty() || !(used + pod.requests).fits_in(&node.capacity) || remaining.len() >= node.max_pods {
+            return Ok(false);
+        }
+
+        for victim in victims {
+            info!(
+                "Preempting pod {}/{} (priority {}) for {}/{} (priority {})",
+                victim.namespace, victim.name, victim.priority, pod.namespace, pod.name, pod.priority
+            );
+
+            if let Err(e) = self.storage.pods().delete(&victim.namespace, &victim.name).await {
+                warn!("Failed to preempt pod {}/{}: {}", victim.namespace, victim.name, e);
+                continue;
+            }
+
+            self.record_pod_event(
+                victim,
+                event_store::NORMAL,
+                "Preempted",
+                &format!("Preempted by pod {} on node {}", pod.uid, node.name),
+            )
+            .await?;
+        }
+
+        self.storage
+            .pods()
+            .set_status_fields(&pod.uid, &[("nominatedNodeName", json!(node.name))])
+            .await?;
+
+        Ok(true)
+    }
+
+    // Reports why no node can take a pod, given each node's reasons, unless
+    // it was already reported for those reasons: as a FailedScheduling event
+    // and the pod's PodScheduled condition, summed up the way kube-scheduler
+    // does, and node by node for /krust/scheduling
+    async fn record_failure(&self, pod: &PodInfo, verdicts: &[(&Node, Vec<String>)]) -> Result<()> {
+        let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for reason in verdicts.iter().flat_map(|(_, reasons)| reasons) {
+            *counts.entry(reason).or_default() += 1;
+        }
+        let mut reasons: Vec<String> = counts.iter().map(|(reason, count)| format!("{} {}", count, reason)).collect();
+        reasons.sort();
+        let message = if reasons.is_empty() {
+            "no nodes available to schedule pods".to_string()
+        } else {
+            format!("0/{} nodes are available: {}.", self.nodes.len(), reasons.join(", "))
+        };
+
+        let previous = self.failures.lock().unwrap().insert(pod.uid.clone(), message.clone());
+        if previous.as_ref() == Some(&message) {
+            return Ok(());
+        }
+
+        let nodes: Vec<Value> = verdicts
+            .iter()
+            .map(|(node, reasons)| json!({ "name": node.name, "reasons": reasons }))
+            .collect();
+        self.storage
+            .scheduling_failures()
+            .record(&pod.uid, &pod.namespace, &pod.name, &message, &json!(nodes))
+            .await?;
+        let condition = json!({ "type": "PodScheduled", "status": "False", "reason": "Unschedulable", "message": message });
+        self.storage
+            .pods()
+            .patch_status(&pod.namespace, &pod.name, json!({ "conditions": [condition] }))
+            .await?;
+        self.record_pod_event(pod, event_store::WARNING, "FailedScheduling", &message).await
+    }
+
+    async fn record_pod_event(&self, pod: &PodInfo, event_type: &str, reason: &str, message: &str) -> Result<()> {
+        self.storage
+            .events()
+            .record(
+                &EventSource::new(SCHEDULER_NAME),
+                &ObjectReference::pod(&pod.namespace, &pod.name, &pod.uid),
+                event_type,
+                reason,
+                message,
+            )
+            .await?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+impl Node {
+    /// Why the node can't take the pod next to the pods already bound to it,
+    /// in kube-scheduler's words; empty if it can. As there, the first filter
+    /// that fails decides: cordoning, taints, then node selector and affinity, host ports,
+    /// resources, volumes and finally the pod's place among the others.
+    fn filter(&self, pod: &PodInfo, bound: &[PodInfo], claims: &ClaimUse, placement: &Placement) -> Vec<String> {
+        let rejections = self.rejections(pod);
+        if !rejections.is_empty() {
+            return rejections;
+        }
+
+        let ports_taken = pod
+            .host_ports
+            .iter()
+            .any(|port| bound.iter().flat_map(|p| &p.host_ports).any(|other| port.conflicts(other)));
+        if ports_taken {
+            return vec!["node(s) didn't have free ports for the requested pod ports".to_string()];
+        }
+
+        let used = bound.iter().fold(Resources::default(), |sum, p| sum + p.requests);
+        let wanted = used + pod.requests;
+        let mut shortfalls = Vec::new();
+        if bound.len() >= self.max_pods {
+            shortfalls.push("Too many pods".to_string());
+        }
+        if wanted.cpu_millis > self.capacity.cpu_millis {
+            shortfalls.push("Insufficient cpu".to_string());
+        }
+        if wanted.memory_bytes > self.capacity.memory_bytes {
+            shortfalls.push("Insufficient memory".to_string());
+        }
+        if !shortfalls.is_empty() {
+            return shortfalls;
+        }
+
+        // The volume restrictions: a ReadWriteOncePod claim is for one pod
+        // at a time, and a hostPath another pod writes can't be shared
+        if claims.taken {
+            return vec!["node has pod using PersistentVolumeClaim with the same name and ReadWriteOncePod access mode".to_string()];
+        }
+        let disk_taken = pod
+            .host_paths
+            .iter()
+            .any(|path| bound.iter().flat_map(|p| &p.host_paths).any(|other| path.conflicts(other)));
+        if disk_taken {
+            return vec!["node(s) had no available disk".to_string()];
+        }
+        // A ReadWriteOnce claim is mounted on one node at a time
+        if !claims.nodes.is_empty() && !claims.nodes.contains(&self.name) {
+            return vec!["node(s) didn't have the pod's ReadWriteOnce PersistentVolumeClaim already mounted".to_string()];
+        }
+        placement.filter(pod, self).map(str::to_string).into_iter().collect()
+    }
+
+    /// Why the node being cordoned, its NoSchedule and NoExecute taints or
+    /// its labels rule the pod out whatever else runs there; empty if they
+    /// don't. Only pods tolerating the unschedulable taint go to cordoned
+    /// nodes.
+    fn rejections(&self, pod: &PodInfo) -> Vec<String> {
+        if self.unschedulable {
+            let taint = Taint { key: node::UNSCHEDULABLE_TAINT.to_string(), value: None, effect: "NoSchedule".to_string() };
+            if !pod.tolerations.iter().any(|toleration| tolerates(toleration, &taint)) {
+                return vec!["node(s) were unschedulable".to_string()];
+            }
+        }
+
+        let untolerated = self
+            .taints
+            .iter()
+            .filter(|taint| taint.effect != "PreferNoSchedule")
+            .find(|taint| !pod.tolerations.iter().any(|toleration| tolerates(toleration, taint)));
+        if let Some(taint) = untolerated {
+            let value = taint.value.as_deref().unwrap_or("");
+            return vec![format!("node(s) had untolerated taint {{{}: {}}}", taint.key, value)];
+        }
+
+        if !self.selects(pod) {
+            return vec!["node(s) didn't match Pod's node affinity/selector".to_string()];
+        }
+        Vec::new()
+    }
+
+    /// Whether the node's labels match the pod's nodeSelector and one of
+    /// the terms its required node affinity has, if any.
+    fn selects(&self, pod: &PodInfo) -> bool {
+        let selected = pod
+            .node_selector
+            .iter()
+            .all(|(key, value)| self.labels.get(key) == Some(value));
+        let required = &pod.node_affinity["requiredDuringSchedulingIgnoredDuringExecution"]["nodeSelectorTerms"];
+        let affine = match required.as_array() {
+            Some(terms) if !terms.is_empty() => terms.iter().any(|term| self.matches(term)),
+            _ => true,
+        };
+        selected && affine
+    }
+
+    /// Whether the node matches a node selector term: all of its
+    /// matchExpressions on the labels and matchFields on the name. A term
+    /// with neither matches no node.
+    fn matches(&self, term: &Value) -> bool {
+        let expressions = term["matchExpressions"].as_array().map_or(&[][..], |e| e);
+        let fields = term["matchFields"].as_array().map_or(&[][..], |f| f);
+        if expressions.is_empty() && fields.is_empty() {
+            return false;
+        }
+        let name = Some(self.name.as_str());
+        expressions.iter().all(|e| expression_matches(e, self.labels.get(e["key"].as_str().unwrap_or("")).map(String::as_str)))
+            && fields.iter().all(|e| e["key"] == "metadata.name" && expression_matches(e, name))
+    }
+
+    /// kube-scheduler's default scores for the node, each from 0 to 100,
+    /// before they're weighed against the other nodes'.
+    fn score(&self, pod: &PodInfo, bound: &[PodInfo], placement: &Placement) -> Score {
+        // Pods without requests still count for something, or they'd all
+        // pile onto one node
+        let requested = bound.iter().chain([pod]).fold(Resources::default(), |sum, p| sum + p.scoring_requests());
+        let free = |requested: i64, capacity: i64| {
+            if capacity <= 0 {
+                return 0;
+            }
+            (capacity - requested).max(0) * 100 / capacity
+        };
+        let least_allocated = (free(requested.cpu_millis, self.capacity.cpu_millis)
+            + free(requested.memory_bytes, self.capacity.memory_bytes))
+            / 2;
+
+        let preferred = &pod.node_affinity["preferredDuringSchedulingIgnoredDuringExecution"];
+        let affinity = preferred
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|term| self.matches(&term["preference"]))
+            .map(|term| term["weight"].as_i64().unwrap_or(0))
+            .sum();
+
+        let untolerated = self
+            .taints
+            .iter()
+            .filter(|taint| taint.effect == "PreferNoSchedule")
+            .filter(|taint| !pod.tolerations.iter().any(|toleration| tolerates(toleration, taint)))
+            .count() as i64;
+
+        Score {
+            least_allocated,
+            affinity,
+            untolerated,
+            pod_affinity: placement.affinity_score(pod, self),
+            spread: placement.spread_score(pod, self),
+        }
+    }
+}
+
+/// A node's raw scores for a pod.
+struct Score {
+    // How much CPU and memory would be left with the pod there, in percent
+    least_allocated: i64,
+    // The summed weights of the preferred node affinity terms it matches
+    affinity: i64,
+    // How many of its PreferNoSchedule taints the pod doesn't tolerate
+    untolerated: i64,
+    // The weights of the preferred pod affinity terms met there, less
+    // those of the anti-affinity ones
+    pod_affinity: i64,
+    // The pods the ScheduleAnyway spread constraints count in its domains,
+    // if it has their topology labels
+    spread: Option<i64>,
+}
+
+// How kube-scheduler weighs its default scores against each other
+const LEAST_ALLOCATED_WEIGHT: i64 = 1;
+const NODE_AFFINITY_WEIGHT: i64 = 2;
+const TAINT_TOLERATION_WEIGHT: i64 = 3;
+const INTER_POD_AFFINITY_WEIGHT: i64 = 2;
+const TOPOLOGY_SPREAD_WEIGHT: i64 = 2;
+
+// The feasible node that suits the pod best, the first of them on a tie.
+// All but the least allocated score are relative: the nodes matching the
+// most preferred terms, those with the fewest untolerated taints and those
+// where the spread constraints count the fewest pods get 100, and the
+// nodes pod affinity draws the pod to the most get 100 and those it draws
+// it to the least 0
+fn best_node<'a>(pod: &PodInfo, feasible: &[&'a Node], bound: &HashMap<String, Vec<PodInfo>>, placement: &Placement) -> Option<&'a Node> {
+    let scores: Vec<Score> = feasible
+        .iter()
+        .map(|node| node.score(pod, bound.get(&node.name).map_or(&[], |pods| pods), placement))
+        .collect();
+    let max_affinity = scores.iter().map(|s| s.affinity).max().unwrap_or(0);
+    let max_untolerated = scores.iter().map(|s| s.untolerated).max().unwrap_or(0);
+    let (min_pod_affinity, max_pod_affinity) = min_max(scores.iter().map(|s| s.pod_affinity));
+    let (min_spread, max_spread) = min_max(scores.iter().filter_map(|s| s.spread));
+
+    let mut best: Option<(&Node, i64)> = None;
+    for (node, score) in feasible.iter().zip(&scores) {
+        let affinity = if max_affinity > 0 { score.affinity * 100 / max_affinity } else { 0 };
+        let tolerated = if max_untolerated > 0 { 100 - score.untolerated * 100 / max_untolerated } else { 100 };
+        let pod_affinity = relative(score.pod_affinity, min_pod_affinity, max_pod_affinity);
+        let spread = score.spread.map_or(0, |count| 100 - relative(count, min_spread, max_spread));
+        let total = LEAST_ALLOCATED_WEIGHT * score.least_allocated
+            + NODE_AFFINITY_WEIGHT * affinity
+            + TAINT_TOLERATION_WEIGHT * tolerated
+            + INTER_POD_AFFINITY_WEIGHT * pod_affinity
+            + TOPOLOGY_SPREAD_WEIGHT * spread;
+        debug!("Pod {}/{} scores {} on node {}", pod.namespace, pod.name, total, node.name);
+        if best.is_none_or(|(_, highest)| total > highest) {
+            best = Some((node, total));
+        }
+    }
+    best.map(|(node, _)| node)
+}
+
+fn min_max(values: impl Iterator<Item = i64>) -> (i64, i64) {
+    values.fold((i64::MAX, i64::MIN), |(min, max), v| (min.min(v), max.max(v)))
+}
+
+// Where `value` lies between `min` and `max`, from 0 to 100; 0 when
+// they're all the same
+fn relative(value: i64, min: i64, max: i64) -> i64 {
+    if max <= min {
+        return 0;
+    }
+    (value - min) * 100 / (max - min)
+}
+
+// Whether a node selector requirement holds for a label's value, or the
+// node's name for matchFields
+fn expression_matches(expression: &Value, value: Option<&str>) -> bool {
+    let values: Vec<&str> = expression["values"].as_array().into_iter().flatten().filter_map(|v| v.as_str()).collect();
+    let compare = |ordering: std::cmp::Ordering| {
+        let number = |v: &str| v.parse::<i64>().ok();
+        match (value.and_then(number), values.first().copied().and_then(number)) {
+            (Some(value), Some(bound)) => value.cmp(&bound) == ordering,
+            _ => false,
+        }
+    };
+    match expression["operator"].as_str().unwrap_or("") {
+        "In" => value.is_some_and(|v| values.contains(&v)),
+        "NotIn" => !value.is_some_and(|v| values.contains(&v)),
+        "Exists" => value.is_some(),
+        "DoesNotExist" => value.is_none(),
+        "Gt" => compare(std::cmp::Ordering::Greater),
+        "Lt" => compare(std::cmp::Ordering::Less),
+        _ => false,
+    }
+}
+
+fn tolerates(toleration: &Value, taint: &Taint) -> bool {
+    let effect = toleration["effect"].as_str().unwrap_or("");
+    if !effect.is_empty() && effect != taint.effect {
+        return false;
+    }
+
+    let key = toleration["key"].as_str().unwrap_or("");
+    match toleration["operator"].as_str().unwrap_or("Equal") {
+        // An empty key with Exists tolerates every taint
+        "Exists" => key.is_empty() || key == taint.key,
+        _ => key == taint.key && toleration["value"].as_str().unwrap_or("") == taint.value.as_deref().unwrap_or(""),
+    }
+}
+
+/// Where the PersistentVolumeClaims a pod mounts are already used by other
+/// pods.
+#[derive(Default)]
+struct ClaimUse {
+    // Nodes with a pod using one of its ReadWriteOnce claims
+    nodes: HashSet<String>,
+    // Whether a pod uses one of its ReadWriteOncePod claims anywhere
+    taken: bool,
+}
+
+/// A port a pod's container takes on its node.
+struct HostPort {
+    port: i64,
+    protocol: String,
+    // Empty or 0.0.0.0 for all of the node's addresses
+    ip: String,
+}
+
+impl HostPort {
+    fn conflicts(&self, other: &HostPort) -> bool {
+        let wildcard = |ip: &str| ip.is_empty() || ip == "0.0.0.0";
+        self.port == other.port
+            && self.protocol == other.protocol
+            && (self.ip == other.ip || wildcard(&self.ip) || wildcard(&other.ip))
+    }
+}
+
+/// A directory on the node a pod mounts with a hostPath volume.
+struct HostPath {
+    path: String,
+    read_only: bool,
+}
+
+impl HostPath {
+    // Pods may share a directory as long as they only read it
+    fn conflicts(&self, other: &HostPath) -> bool {
+        let trimmed = |path: &str| path.trim_end_matches('/').to_string();
+        trimmed(&self.path) == trimmed(&other.path) && !(self.read_only && other.read_only)
+    }
+}
+
+/// The scheduling-relevant view of a pod row.
+struct PodInfo {
+    uid: String,
+    name: String,
+    namespace: String,
+    labels: Value,
+    priority: i64,
+    preemption_policy: String,
+    requests: Resources,
+    node_selector: BTreeMap<String, String>,
+    // spec.affinity.nodeAffinity
+    node_affinity: Value,
+    tolerations: Vec<Value>,
+    nominated_node: Option<String>,
+    terminating: bool,
+    host_ports: Vec<HostPort>,
+    host_paths: Vec<HostPath>,
+    // Names of the PersistentVolumeClaims it mounts
+    claims: Vec<String>,
+    rules: PodRules,
+}
+
+impl PodInfo {
+    fn from_row(row: &SqliteRow) -> Result<Self> {
+        let spec: Value = serde_json::from_str(&row.get::<String, _>("spec"))?;
+        let status: Value = serde_json::from_str(&row.get::<String, _>("status"))?;
+        let terminating = row
+            .try_get::<Option<String>, _>("deletion_timestamp")
+            .map(|ts| ts.is_some())
+            .unwrap_or(false);
+        let labels = row
+            .try_get::<Option<String>, _>("labels")
+            .ok()
+            .flatten()
+            .and_then(|labels| serde_json::from_str(&labels).ok())
+            .unwrap_or_default();
+        let node_selector = spec["nodeSelector"]
+            .as_object()
+            .into_iter()
+            .flatten()
+            .map(|(key, value)| (key.clone(), value.as_str().unwrap_or_default().to_string()))
+            .collect();
+
+        Ok(Self {
+            uid: row.get("uid"),
+            name: row.get("name"),
+            namespace: row.get("namespace"),
+            labels,
+            priority: spec["priority"].as_i64().unwrap_or(0),
+            preemption_policy: spec["preemptionPolicy"]
+                .as_str()
+                .unwrap_or("PreemptLowerPriority")
+                .to_string(),
+            requests: Resources::requests(&spec),
+            node_selector,
+            node_affinity: spec["affinity"]["nodeAffinity"].clone(),
+            tolerations: spec["tolerations"].as_array().cloned().unwrap_or_default(),
+            nominated_node: status["nominatedNodeName"].as_str().map(str::to_string),
+            terminating,
+            host_ports: host_ports(&spec),
+            host_paths: host_paths(&spec),
+            claims: spec["volumes"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|v| v["persistentVolumeClaim"]["claimName"].as_str().map(str::to_string))
+                .collect(),
+            rules: PodRules::of(&spec),
+        })
+    }
+
+    // What the pod counts for when scoring nodes: its requests, taking
+    // 100m of CPU and 200Mi of memory for what it doesn't request, as
+    // kube-scheduler does
+    fn scoring_requests(&self) -> Resources {
+        let mut requests = self.requests;
+        if requests.cpu_millis == 0 {
+            requests.cpu_millis = 100;
+        }
+        if requests.memory_bytes == 0 {
+            requests.memory_bytes = 200 * 1024 * 1024;
+        }
+        requests
+    }
+}
+
+// The host ports of a pod's containers, which on the host network are the
+// container ports themselves
+fn host_ports(spec: &Value) -> Vec<HostPort> {
+    let host_network = spec["hostNetwork"].as_bool().unwrap_or(false);
+    let containers = ["initContainers", "containers"].into_iter().flat_map(|key| spec[key].as_array().into_iter().flatten());
+    containers
+        .flat_map(|container| container["ports"].as_array().into_iter().flatten())
+        .filter_map(|port| {
+            let host_port = port["hostPort"].as_i64().filter(|p| *p > 0);
+            let port_number = host_port.or_else(|| port["containerPort"].as_i64().filter(|_| host_network))?;
+            Some(HostPort {
+                port: port_number,
+                protocol: port["protocol"].as_str().unwrap_or("TCP").to_string(),
+                ip: port["hostIP"].as_str().unwrap_or_default().to_string(),
+            })
+        })
+        .collect()
+}
+
+// The hostPath volumes a pod mounts, read-only if every mount of them is
+fn host_paths(spec: &Value) -> Vec<HostPath> {
+    let mounts: Vec<&Value> = ["initContainers", "containers"]
+        .into_iter()
+        .flat_map(|key| spec[key].as_array().into_iter().flatten())
+        .flat_map(|container| container["volumeMounts"].as_array().into_iter().flatten())
+        .collect();
+    spec["volumes"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|volume| {
+            let path = volume["hostPath"]["path"].as_str()?;
+            let name = volume["name"].as_str().unwrap_or_default();
+            let read_only = mounts
+                .iter()
+                .filter(|m| m["name"].as_str() == Some(name))
+                .all(|m| m["readOnly"].as_bool().unwrap_or(false));
+            Some(HostPath { path: path.to_string(), read_only })
+        })
+        .collect()
+}
@@ -0,0 +1,244 @@
+// Rules placing a pod relative to the pods already bound: inter-pod affinity
+// and anti-affinity, and topology spread constraints. Both are about
+// topology domains, the groups of nodes sharing a value for a label such as
+// kubernetes.io/hostname or topology.kubernetes.io/zone, and which pods run
+// in each, as with kube-scheduler's InterPodAffinity and PodTopologySpread
+// plugins. A rule's pods are picked by a label selector, from the pod's own
+// namespace unless it names others.
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+
+use super::{Node, PodInfo};
+use crate::storage::LabelSelector;
+
+const AFFINITY: &str = "node(s) didn't match pod affinity rules";
+const ANTI_AFFINITY: &str = "node(s) didn't match pod anti-affinity rules";
+const EXISTING_ANTI_AFFINITY: &str = "node(s) didn't satisfy existing pods anti-affinity rules";
+const SPREAD: &str = "node(s) didn't match pod topology spread constraints";
+const SPREAD_MISSING_LABEL: &str = "node(s) didn't match pod topology spread constraints (missing required label)";
+
+/// A pod's affinity, anti-affinity and spread rules.
+#[derive(Default)]
+pub(super) struct PodRules {
+    affinity: Vec<AffinityTerm>,
+    anti_affinity: Vec<AffinityTerm>,
+    preferred_affinity: Vec<AffinityTerm>,
+    preferred_anti_affinity: Vec<AffinityTerm>,
+    spread: Vec<SpreadConstraint>,
+}
+
+impl PodRules {
+    pub(super) fn of(spec: &Value) -> Self {
+        let affinity = &spec["affinity"];
+        Self {
+            affinity: AffinityTerm::required(&affinity["podAffinity"]),
+            anti_affinity: AffinityTerm::required(&affinity["podAntiAffinity"]),
+            preferred_affinity: AffinityTerm::preferred(&affinity["podAffinity"]),
+            preferred_anti_affinity: AffinityTerm::preferred(&affinity["podAntiAffinity"]),
+            spread: spec["topologySpreadConstraints"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(SpreadConstraint::parse)
+                .collect(),
+        }
+    }
+}
+
+/// A PodAffinityTerm: pods in a domain the pod should, or shouldn't, share.
+struct AffinityTerm {
+    // None selects no pods
+    selector: Option<LabelSelector>,
+    namespaces: Vec<String>,
+    namespace_selector: Option<LabelSelector>,
+    topology_key: String,
+    // For preferred terms
+    weight: i64,
+}
+
+impl AffinityTerm {
+    fn parse(term: &Value, weight: i64) -> Option<Self> {
+        let selector = |value: &Value| if value.is_null() { None } else { LabelSelector::from_value(value).ok() };
+        Some(Self {
+            selector: selector(&term["labelSelector"]),
+            namespaces: term["namespaces"].as_array().into_iter().flatten().filter_map(|n| n.as_str().map(str::to_string)).collect(),
+            namespace_selector: selector(&term["namespaceSelector"]),
+            topology_key: term["topologyKey"].as_str().filter(|k| !k.is_empty())?.to_string(),
+            weight,
+        })
+    }
+
+    fn required(rules: &Value) -> Vec<Self> {
+        let terms = rules["requiredDuringSchedulingIgnoredDuringExecution"].as_array();
+        terms.into_iter().flatten().filter_map(|term| Self::parse(term, 1)).collect()
+    }
+
+    fn preferred(rules: &Value) -> Vec<Self> {
+        let terms = rules["preferredDuringSchedulingIgnoredDuringExecution"].as_array();
+        terms
+            .into_iter()
+            .flatten()
+            .filter_map(|term| Self::parse(&term["podAffinityTerm"], term["weight"].as_i64().unwrap_or(0)))
+            .collect()
+    }
+
+    // Whether the term of a pod in `namespace` picks `pod`
+    fn matches(&self, namespace: &str, pod: &PodInfo, namespaces: &HashMap<String, Value>) -> bool {
+        let in_namespace = if self.namespaces.is_empty() && self.namespace_selector.is_none() {
+            pod.namespace == namespace
+        } else {
+            self.namespaces.contains(&pod.namespace)
+                || self
+                    .namespace_selector
+                    .as_ref()
+                    .is_some_and(|selector| selector.matches(namespaces.get(&pod.namespace).unwrap_or(&Value::Null)))
+        };
+        in_namespace && self.selector.as_ref().is_some_and(|selector| selector.matches(&pod.labels))
+    }
+}
+
+/// A topologySpreadConstraint.
+struct SpreadConstraint {
+    max_skew: i64,
+    topology_key: String,
+    // DoNotSchedule rather than ScheduleAnyway
+    required: bool,
+    selector: Option<LabelSelector>,
+}
+
+impl SpreadConstraint {
+    fn parse(constraint: &Value) -> Option<Self> {
+        let selector = &constraint["labelSelector"];
+        Some(Self {
+            max_skew: constraint["maxSkew"].as_i64().unwrap_or(1).max(1),
+            topology_key: constraint["topologyKey"].as_str().filter(|k| !k.is_empty())?.to_string(),
+            required: constraint["whenUnsatisfiable"].as_str().unwrap_or("DoNotSchedule") == "DoNotSchedule",
+            selector: if selector.is_null() { None } else { LabelSelector::from_value(selector).ok() },
+        })
+    }
+
+    // Whether the constraint of a pod in `namespace` counts `pod`, which
+    // terminating pods aren't
+    fn counts(&self, namespace: &str, pod: &PodInfo) -> bool {
+        pod.namespace == namespace && !pod.terminating && self.selector.as_ref().is_some_and(|s| s.matches(&pod.labels))
+    }
+}
+
+/// The bound pods, each with the labels of its node, and the labels of the
+/// namespaces, for placing a pod.
+pub(super) struct Placement<'a> {
+    pods: Vec<(&'a PodInfo, &'a BTreeMap<String, String>)>,
+    nodes: &'a [Node],
+    namespaces: &'a HashMap<String, Value>,
+}
+
+impl<'a> Placement<'a> {
+    pub(super) fn new(nodes: &'a [Node], bound: &'a HashMap<String, Vec<PodInfo>>, namespaces: &'a HashMap<String, Value>) -> Self {
+        let pods = nodes
+            .iter()
+            .flat_map(|node| bound.get(&node.name).into_iter().flatten().map(move |pod| (pod, &node.labels)))
+            .collect();
+        Self { pods, nodes, namespaces }
+    }
+
+    /// Why the pod's spread constraints, its affinity or anti-affinity, or
+    /// the anti-affinity of the pods already there rule `node` out, the
+    /// first of them that does; None if they don't.
+    pub(super) fn filter(&self, pod: &PodInfo, node: &Node) -> Option<&'static str> {
+        for constraint in pod.rules.spread.iter().filter(|c| c.required) {
+            let Some(domain) = node.labels.get(&constraint.topology_key) else {
+                return Some(SPREAD_MISSING_LABEL);
+            };
+            let counts = self.spread_counts(pod, constraint);
+            let fewest = counts.values().min().copied().unwrap_or(0);
+            let own = i64::from(constraint.counts(&pod.namespace, pod));
+            if counts.get(domain.as_str()).copied().unwrap_or(0) + own - fewest > constraint.max_skew {
+                return Some(SPREAD);
+            }
+        }
+
+        // In kube-scheduler's order: the pod's affinity, its anti-affinity,
+        // then that of the pods already there
+        let near = |labels: &BTreeMap<String, String>, key: &str| labels.get(key).is_some_and(|v| node.labels.get(key) == Some(v));
+        for term in &pod.rules.affinity {
+            let mut matching = self.pods.iter().filter(|(other, _)| term.matches(&pod.namespace, other, self.namespaces)).peekable();
+            // The first of a group of pods attracting each other can go
+            // anywhere it could join them later
+            let first = matching.peek().is_none() && term.matches(&pod.namespace, pod, self.namespaces);
+            if first && node.labels.contains_key(&term.topology_key) {
+                continue;
+            }
+            if !matching.any(|(_, labels)| near(labels, &term.topology_key)) {
+                return Some(AFFINITY);
+            }
+        }
+        for term in &pod.rules.anti_affinity {
+            if self.pods.iter().any(|(other, labels)| near(labels, &term.topology_key) && term.matches(&pod.namespace, other, self.namespaces)) {
+                return Some(ANTI_AFFINITY);
+            }
+        }
+        let anti_affine = self.pods.iter().any(|(other, labels)| {
+            other.rules.anti_affinity.iter().any(|term| near(labels, &term.topology_key) && term.matches(&other.namespace, pod, self.namespaces))
+        });
+        if anti_affine {
+            return Some(EXISTING_ANTI_AFFINITY);
+        }
+        None
+    }
+
+    /// How much the preferred affinity and anti-affinity of the pod, and of
+    /// the pods already there, draw it to `node`: the weights of the terms
+    /// matched in the node's domains, less those of the anti-affinity ones.
+    pub(super) fn affinity_score(&self, pod: &PodInfo, node: &Node) -> i64 {
+        let near = |labels: &BTreeMap<String, String>, key: &str| labels.get(key).is_some_and(|v| node.labels.get(key) == Some(v));
+        let mut score = 0;
+        for (other, labels) in &self.pods {
+            let own = [(&pod.rules.preferred_affinity, 1), (&pod.rules.preferred_anti_affinity, -1)];
+            for (terms, sign) in own {
+                for term in terms.iter().filter(|t| near(labels, &t.topology_key) && t.matches(&pod.namespace, other, self.namespaces)) {
+                    score += sign * term.weight;
+                }
+            }
+            let theirs = [(&other.rules.preferred_affinity, 1), (&other.rules.preferred_anti_affinity, -1)];
+            for (terms, sign) in theirs {
+                for term in terms.iter().filter(|t| near(labels, &t.topology_key) && t.matches(&other.namespace, pod, self.namespaces)) {
+                    score += sign * term.weight;
+                }
+            }
+        }
+        score
+    }
+
+    /// How many pods the pod's ScheduleAnyway spread constraints count in
+    /// the domains of `node`, the fewer the better; None if it lacks one of
+    /// their topology labels.
+    pub(super) fn spread_score(&self, pod: &PodInfo, node: &Node) -> Option<i64> {
+        let mut score = 0;
+        for constraint in pod.rules.spread.iter().filter(|c| !c.required) {
+            let domain = node.labels.get(&constraint.topology_key)?;
+            score += self.spread_counts(pod, constraint).get(domain.as_str()).copied().unwrap_or(0);
+        }
+        Some(score)
+    }
+
+    // The pods a constraint counts in each domain of the nodes the pod's
+    // nodeSelector and node affinity allow, including empty ones
+    fn spread_counts(&self, pod: &PodInfo, constraint: &SpreadConstraint) -> HashMap<&'a str, i64> {
+        let mut counts: HashMap<&str, i64> = self
+            .nodes
+            .iter()
+            .filter(|node| node.selects(pod))
+            .filter_map(|node| node.labels.get(&constraint.topology_key))
+            .map(|domain| (domain.as_str(), 0))
+            .collect();
+        for (other, labels) in &self.pods {
+            let Some(count) = labels.get(&constraint.topology_key).and_then(|domain| counts.get_mut(domain.as_str())) else {
+                continue;
+            };
+            if constraint.counts(&pod.namespace, other) {
+                *count += 1;
+            }
+        }
+        counts
+    }
+}
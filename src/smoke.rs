@@ -0,0 +1,398 @@
+// krust-smoke: a check that krust runs workloads on this machine end to
+// end, started with `krust smoke`. In a namespace of its own it applies the
+// nginx Deployment, Service and Ingress of examples/nginx.yaml, waits for
+// the Deployment to be ready and its Service to have endpoints, then fetches
+// the nginx welcome page through the Service's node port. Each step passes
+// or fails with what went wrong, and the namespace is deleted afterwards.
+//
+// With simulated nodes only, pods run without containers, so it's the
+// Docker-backed node the workload has to land on for the page to be served.
+use anyhow::{anyhow, bail, Context, Result};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::fmt;
+use std::time::{Duration, Instant};
+
+use crate::models::replicas;
+
+/// The workload applied, as bundled into the binary.
+pub const WORKLOAD: &str = include_str!("../examples/nginx.yaml");
+
+const SMOKE_LABEL: &str = "krust.io/smoke";
+
+// How often readiness and the service are polled
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SmokeOptions {
+    /// Base URL of the API server.
+    pub server: String,
+    /// How long the workload may take to become ready and to answer.
+    pub timeout: Duration,
+    /// Leaves the namespace and workload in place afterwards.
+    pub keep: bool,
+    /// Flags for the krust started in-process when none is running at
+    /// `server`, e.g. `--data-dir`.
+    pub server_args: Vec<String>,
+}
+
+impl Default for SmokeOptions {
+    fn default() -> Self {
+        Self {
+            server: "http://127.0.0.1:6443".to_string(),
+            timeout: Duration::from_secs(180),
+            keep: false,
+            server_args: Vec::new(),
+        }
+    }
+}
+
+impl SmokeOptions {
+    pub const USAGE: &'static str = "usage: krust smoke [--server URL] [--timeout SECONDS] [--keep] \
+                                     [--config PATH] [--data-dir PATH] [--feature-gates GATES]";
+
+    /// Parses the arguments following `smoke`. The flags krust itself takes
+    /// are kept for starting one.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut options = Self::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let (flag, inline) = match arg.split_once('=') {
+                Some((flag, value)) => (flag.to_string(), Some(value.to_string())),
+                None => (arg, None),
+            };
+            let mut value = || {
+                inline
+                    .clone()
+                    .or_else(|| args.next())
+                    .ok_or_else(|| anyhow!("{} requires a value\n{}", flag, Self::USAGE))
+            };
+            match flag.as_str() {
+                "--server" => options.server = value()?.trim_end_matches('/').to_string(),
+                "--timeout" => {
+                    let seconds: u64 = value()?.parse().map_err(|_| anyhow!("--timeout: not a number of seconds"))?;
+                    options.timeout = Duration::from_secs(seconds);
+                }
+                "--keep" => options.keep = true,
+                "--config" | "--data-dir" | "--feature-gates" => {
+                    let value = value()?;
+                    options.server_args.extend([flag, value]);
+                }
+                "--profiling" => options.server_args.push(flag),
+                _ => bail!("unknown argument: {}\n{}", flag, Self::USAGE),
+            }
+        }
+        Ok(options)
+    }
+}
+
+/// Whether an API server answers at `server`.
+pub async fn is_running(server: &str) -> bool {
+    let client = reqwest::Client::new();
+    match client.get(format!("{}/readyz", server)).timeout(Duration::from_secs(2)).send().await {
+        Ok(resp) => resp.status().is_success(),
+        Err(_) => false,
+    }
+}
+
+/// Waits up to `timeout` for an API server to answer at `server`.
+pub async fn wait_until_running(server: &str, timeout: Duration) -> Result<()> {
+    let deadline = Instant::now() + timeout;
+    while !is_running(server).await {
+        if Instant::now() > deadline {
+            bail!("krust didn't come up at {} within {:?}", server, timeout);
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    Ok(())
+}
+
+/// The outcome of one step.
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    /// What was found, or why the step failed.
+    pub result: Result<String, String>,
+    pub elapsed: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Report {
+    pub namespace: String,
+    pub checks: Vec<Check>,
+    /// Set when deleting the namespace afterwards failed.
+    pub cleanup_error: Option<String>,
+}
+
+impl Report {
+    /// Whether every step passed.
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|check| check.result.is_ok())
+    }
+}
+
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let (outcome, detail) = match &check.result {
+                Ok(detail) => ("PASS", detail),
+                Err(why) => ("FAIL", why),
+            };
+            writeln!(f, "{}  {:<22} {:>7.1}s  {}", outcome, check.name, check.elapsed.as_secs_f64(), detail)?;
+        }
+        if let Some(e) = &self.cleanup_error {
+            writeln!(f, "\nfailed to delete namespace {}: {}", self.namespace, e)?;
+        }
+        if self.passed() {
+            write!(f, "\nsmoke test passed")
+        } else {
+            write!(f, "\nsmoke test failed")
+        }
+    }
+}
+
+/// Runs the smoke test against the server in `options`, which must already
+/// be running.
+pub async fn run(options: &SmokeOptions) -> Result<Report> {
+    let smoke = Smoke {
+        options: options.clone(),
+        client: reqwest::Client::new(),
+        namespace: format!("krust-smoke-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]),
+    };
+    let mut report = Report {
+        namespace: smoke.namespace.clone(),
+        ..Report::default()
+    };
+
+    // Each step needs the ones before it, so the first failure ends the run
+    let steps: [(&'static str, Step); 5] = [
+        ("api server", |s| Box::pin(s.api_server())),
+        ("apply workload", |s| Box::pin(s.apply())),
+        ("deployment ready", |s| Box::pin(s.deployment_ready())),
+        ("service endpoints", |s| Box::pin(s.service_endpoints())),
+        ("http via node port", |s| Box::pin(s.fetch_through_node_port())),
+    ];
+    for (name, step) in steps {
+        let started = Instant::now();
+        let result = step(&smoke).await.map_err(|e| format!("{:#}", e));
+        let failed = result.is_err();
+        report.checks.push(Check {
+            name,
+            result,
+            elapsed: started.elapsed(),
+        });
+        if failed {
+            break;
+        }
+    }
+
+    let applied = report.checks.len() > 1;
+    if applied && !options.keep {
+        report.cleanup_error = smoke.delete_namespace().await.err().map(|e| format!("{:#}", e));
+    }
+    Ok(report)
+}
+
+type Step = for<'a> fn(&'a Smoke) -> futures::future::BoxFuture<'a, Result<String>>;
+
+struct Smoke {
+    options: SmokeOptions,
+    client: reqwest::Client,
+    namespace: String,
+}
+
+impl Smoke {
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.options.server, path)
+    }
+
+    async fn get(&self, path: &str) -> Result<Value> {
+        let resp = self.client.get(self.url(path)).send().await.with_context(|| format!("GET {}", path))?;
+        if !resp.status().is_success() {
+            bail!("GET {}: {}", path, failure(resp).await);
+        }
+        Ok(resp.json().await?)
+    }
+
+    async fn create(&self, path: &str, object: &Value) -> Result<Value> {
+        let resp = self.client.post(self.url(path)).json(object).send().await.with_context(|| format!("POST {}", path))?;
+        if !resp.status().is_success() {
+            bail!("POST {}: {}", path, failure(resp).await);
+        }
+        Ok(resp.json().await?)
+    }
+
+    // Polls `check` until it comes up with something or the timeout passes,
+    // then says what it last saw
+    async fn wait_for<T, F, Fut>(&self, what: &str, mut check: F) -> Result<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: std::future::Future<Output = Result<std::result::Result<T, String>>>,
+    {
+        let deadline = Instant::now() + self.options.timeout;
+        loop {
+            let last = match check().await {
+                Ok(Ok(found)) => return Ok(found),
+                Ok(Err(state)) => state,
+                Err(e) => format!("{:#}", e),
+            };
+            if Instant::now() > deadline {
+                bail!("{} within {}s: {}", what, self.options.timeout.as_secs(), last);
+            }
+            tokio::time::sleep(POLL_INTERVAL).await;
+        }
+    }
+
+    async fn api_server(&self) -> Result<String> {
+        let version = self.get("/version").await?;
+        Ok(format!("{} at {}", version["gitVersion"].as_str().unwrap_or("unknown version"), self.options.server))
+    }
+
+    async fn apply(&self) -> Result<String> {
+        let namespace = json!({
+            "apiVersion": "v1",
+            "kind": "Namespace",
+            "metadata": { "name": self.namespace, "labels": { SMOKE_LABEL: "true" } }
+        });
+        self.create("/api/v1/namespaces", &namespace).await?;
+
+        let mut applied = Vec::new();
+        for object in workload()? {
+            let kind = object["kind"].as_str().unwrap_or_default();
+            let collection = match kind {
+                "Deployment" => format!("/apis/apps/v1/namespaces/{}/deployments", self.namespace),
+                "Service" => format!("/api/v1/namespaces/{}/services", self.namespace),
+                "Ingress" => format!("/apis/networking.k8s.io/v1/namespaces/{}/ingresses", self.namespace),
+                _ => bail!("the bundled workload has a {} krust smoke doesn't know", kind),
+            };
+            let created = self.create(&collection, &object).await?;
+            applied.push(format!("{}/{}", kind.to_lowercase(), created["metadata"]["name"].as_str().unwrap_or_default()));
+        }
+        Ok(format!("{} in namespace {}", applied.join(", "), self.namespace))
+    }
+
+    async fn deployment_ready(&self) -> Result<String> {
+        let path = format!("/apis/apps/v1/namespaces/{}/deployments/nginx", self.namespace);
+        self.wait_for("nginx not ready", || async {
+            let deployment = self.get(&path).await?;
+            let wanted = replicas::desired(&deployment["spec"]);
+            let ready = deployment["status"]["readyReplicas"].as_i64().unwrap_or(0);
+            if ready >= wanted {
+                return Ok(Ok(format!("{}/{} replicas ready", ready, wanted)));
+            }
+            Ok(Err(self.pod_states().await?))
+        })
+        .await
+    }
+
+    // What's holding up each nginx pod, as `kubectl get pods` would say
+    async fn pod_states(&self) -> Result<String> {
+        let pods = self.get(&format!("/api/v1/namespaces/{}/pods?labelSelector=app%3Dnginx", self.namespace)).await?;
+        let states: Vec<String> = pods["items"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .map(|pod| {
+                let status = &pod["status"];
+                let waiting = status["containerStatuses"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .find_map(|c| {
+                        let waiting = &c["state"]["waiting"];
+                        waiting["reason"].as_str().map(|reason| match waiting["message"].as_str() {
+                            Some(message) => format!("{} ({})", reason, message),
+                            None => reason.to_string(),
+                        })
+                    });
+                let scheduling = status["conditions"]
+                    .as_array()
+                    .into_iter()
+                    .flatten()
+                    .find(|c| c["type"] == "PodScheduled" && c["status"] == "False")
+                    .and_then(|c| c["message"].as_str().map(str::to_string));
+                let state = waiting
+                    .or(scheduling)
+                    .unwrap_or_else(|| status["phase"].as_str().unwrap_or("Pending").to_string());
+                format!("pod {}: {}", pod["metadata"]["name"].as_str().unwrap_or_default(), state)
+            })
+            .collect();
+        if states.is_empty() {
+            return Ok("no pods created yet".to_string());
+        }
+        Ok(states.join("; "))
+    }
+
+    async fn service_endpoints(&self) -> Result<String> {
+        let path = format!("/api/v1/namespaces/{}/endpoints/nginx", self.namespace);
+        self.wait_for("service nginx has no endpoints", || async {
+            let endpoints = match self.get(&path).await {
+                Ok(endpoints) => endpoints,
+                Err(e) => return Ok(Err(format!("{:#}", e))),
+            };
+            let addresses: Vec<String> = endpoints["subsets"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .flat_map(|subset| subset["addresses"].as_array().into_iter().flatten())
+                .filter_map(|address| address["ip"].as_str().map(str::to_string))
+                .collect();
+            if addresses.is_empty() {
+                return Ok(Err("no ready addresses".to_string()));
+            }
+            Ok(Ok(addresses.join(", ")))
+        })
+        .await
+    }
+
+    async fn fetch_through_node_port(&self) -> Result<String> {
+        let service = self.get(&format!("/api/v1/namespaces/{}/services/nginx", self.namespace)).await?;
+        let node_port = service["spec"]["ports"][0]["nodePort"]
+            .as_i64()
+            .ok_or_else(|| anyhow!("service nginx wasn't given a node port"))?;
+        let server = reqwest::Url::parse(&self.options.server)?;
+        let host = server.host_str().unwrap_or("127.0.0.1");
+        let url = format!("http://{}:{}/", host, node_port);
+
+        self.wait_for(&format!("no nginx page from {}", url), || async {
+            let resp = match self.client.get(&url).timeout(Duration::from_secs(5)).send().await {
+                Ok(resp) => resp,
+                Err(e) => return Ok(Err(e.to_string())),
+            };
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            if status.is_success() && body.contains("nginx") {
+                Ok(Ok(format!("{} from {}", status, url)))
+            } else {
+                Ok(Err(format!("{} without the welcome page", status)))
+            }
+        })
+        .await
+    }
+
+    async fn delete_namespace(&self) -> Result<()> {
+        let path = format!("/api/v1/namespaces/{}", self.namespace);
+        let resp = self.client.delete(self.url(&path)).send().await?;
+        if !resp.status().is_success() {
+            bail!("{}", failure(resp).await);
+        }
+        Ok(())
+    }
+}
+
+// The objects of the bundled workload
+fn workload() -> Result<Vec<Value>> {
+    serde_yaml::Deserializer::from_str(WORKLOAD)
+        .map(|document| Value::deserialize(document).context("examples/nginx.yaml is not valid YAML"))
+        .filter(|object| !matches!(object, Ok(Value::Null)))
+        .collect()
+}
+
+// A failed response's Status message, or its status code
+async fn failure(resp: reqwest::Response) -> String {
+    let code = resp.status();
+    match resp.json::<Value>().await {
+        Ok(status) if status["message"].is_string() => format!("{} ({})", status["message"].as_str().unwrap_or_default(), code),
+        _ => code.to_string(),
+    }
+}
@@ -0,0 +1,86 @@
+// Large JSON columns, stored compressed. A deployment carrying a big
+// last-applied-configuration annotation, or a pod template with a long list
+// of containers and env, can run to tens of kilobytes of JSON; written as a
+// zstd frame instead, it takes a fraction of the pages to store and to read
+// back when listing. Values under the threshold stay JSON text, so SQL that
+// looks into them keeps working and existing rows need no migration. Only
+// columns no SQL looks into are written through `encode`: the spec and
+// annotations of the workload tables, and the status of deployments. A
+// replicaset's status stays text, as its status.replicas field selector
+// reads it in SQL.
+use anyhow::{Context, Result};
+use serde_json::Value;
+use sqlx::encode::IsNull;
+use sqlx::sqlite::{SqliteArgumentValue, SqliteRow, SqliteTypeInfo};
+use sqlx::{Encode, Row, Sqlite, Type};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Columns at least this many bytes of JSON long are compressed, unless
+/// `set_threshold` says otherwise.
+pub const DEFAULT_THRESHOLD: usize = 4096;
+
+// Every zstd frame starts with these bytes, and no JSON text can
+const ZSTD_MAGIC: [u8; 4] = [0x28, 0xb5, 0x2f, 0xfd];
+
+// zstd's default level: most of the gain of the higher ones at a fraction
+// of their cost
+const LEVEL: i32 = 3;
+
+static THRESHOLD: AtomicUsize = AtomicUsize::new(DEFAULT_THRESHOLD);
+
+/// Sets the size from which columns are compressed; 0 turns compression
+/// off. Columns already written are read back either way.
+pub fn set_threshold(bytes: usize) {
+    THRESHOLD.store(if bytes == 0 { usize::MAX } else { bytes }, Ordering::Relaxed);
+}
+
+/// A JSON column as it's written: text, or a zstd frame bound as a BLOB.
+pub(crate) enum Column {
+    Json(String),
+    Compressed(Vec<u8>),
+}
+
+/// The column `value` is written as.
+pub(crate) fn encode(value: &Value) -> Column {
+    let json = value.to_string();
+    if json.len() < THRESHOLD.load(Ordering::Relaxed) {
+        return Column::Json(json);
+    }
+    match zstd::bulk::compress(json.as_bytes(), LEVEL) {
+        Ok(compressed) if compressed.len() < json.len() => Column::Compressed(compressed),
+        _ => Column::Json(json),
+    }
+}
+
+/// Reads back a column written with `encode`, or as plain JSON text.
+pub(crate) fn decode(row: &SqliteRow, column: &str) -> Result<Value> {
+    let bytes: Vec<u8> = row.try_get(column)?;
+    from_bytes(&bytes).with_context(|| format!("column {}", column))
+}
+
+fn from_bytes(bytes: &[u8]) -> Result<Value> {
+    if bytes.starts_with(&ZSTD_MAGIC) {
+        let json = zstd::stream::decode_all(bytes)?;
+        return Ok(serde_json::from_slice(&json)?);
+    }
+    Ok(serde_json::from_slice(bytes)?)
+}
+
+impl Type<Sqlite> for Column {
+    fn type_info() -> SqliteTypeInfo {
+        <String as Type<Sqlite>>::type_info()
+    }
+
+    fn compatible(ty: &SqliteTypeInfo) -> bool {
+        <String as Type<Sqlite>>::compatible(ty) || <Vec<u8> as Type<Sqlite>>::compatible(ty)
+    }
+}
+
+impl<'q> Encode<'q, Sqlite> for Column {
+    fn encode_by_ref(&self, args: &mut Vec<SqliteArgumentValue<'q>>) -> IsNull {
+        match self {
+            Column::Json(json) => <String as Encode<Sqlite>>::encode_by_ref(json, args),
+            Column::Compressed(bytes) => <Vec<u8> as Encode<Sqlite>>::encode_by_ref(bytes, args),
+        }
+    }
+}
@@ -1,16 +1,22 @@
 use anyhow::{anyhow, Result};
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::resource_version;
+use super::watch_store;
+use crate::models::time;
+
 pub struct ConfigMapStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl ConfigMapStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut configmap: Value) -> Result<Value> {
@@ -20,7 +26,7 @@ impl ConfigMapStore {
             .ok_or_else(|| anyhow!("ConfigMap name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         // Extract fields
         let data = configmap.get("data").unwrap_or(&json!({})).clone();
@@ -30,12 +36,21 @@ impl ConfigMapStore {
         let labels = configmap["metadata"].get("labels").unwrap_or(&json!({})).clone();
         let annotations = configmap["metadata"].get("annotations").unwrap_or(&json!({})).clone();
 
+        let tx = Self::new(self.db.begin_write().await?);
+        // A deleted ConfigMap keeps its row, which would block reusing the name
+        sqlx::query("DELETE FROM configmaps WHERE namespace = ?1 AND name = ?2 AND deletion_timestamp IS NOT NULL")
+            .bind(namespace)
+            .bind(&name)
+            .execute(&tx.db)
+            .await?;
+
         // Insert into database
         let query = r#"
             INSERT INTO configmaps (uid, namespace, name, data, binary_data, immutable, labels, annotations, resource_version, creation_timestamp)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 1, ?9)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
         "#;
         
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(query)
             .bind(&uid)
             .bind(namespace)
@@ -45,8 +60,9 @@ impl ConfigMapStore {
             .bind(immutable)
             .bind(labels.to_string())
             .bind(annotations.to_string())
+            .bind(version)
             .bind(&now)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
         // Build response
@@ -54,7 +70,7 @@ impl ConfigMapStore {
         configmap["kind"] = json!("ConfigMap");
         configmap["metadata"]["uid"] = json!(uid);
         configmap["metadata"]["namespace"] = json!(namespace);
-        configmap["metadata"]["resourceVersion"] = json!("1");
+        configmap["metadata"]["resourceVersion"] = json!(version.to_string());
         configmap["metadata"]["creationTimestamp"] = json!(now);
         configmap["metadata"]["selfLink"] = json!(format!("/api/v1/namespaces/{}/configmaps/{}", namespace, name));
         
@@ -80,6 +96,8 @@ impl ConfigMapStore {
             configmap["metadata"]["annotations"] = annotations;
         }
 
+        watch_store::record(&tx.db, "configmaps", "ADDED", &configmap).await?;
+        tx.db.commit_write().await?;
         Ok(configmap)
     }
 
@@ -93,7 +111,7 @@ impl ConfigMapStore {
         let row = sqlx::query(query)
             .bind(namespace)
             .bind(name)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&self.db)
             .await?;
 
         match row {
@@ -154,32 +172,22 @@ impl ConfigMapStore {
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if namespace.is_some() {
-            r#"
-                SELECT uid, namespace, name, data, binary_data, immutable, labels, annotations, resource_version, creation_timestamp 
-                FROM configmaps 
-                WHERE namespace = ?1 AND deletion_timestamp IS NULL 
-                ORDER BY name
-            "#
-        } else {
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
             r#"
                 SELECT uid, namespace, name, data, binary_data, immutable, labels, annotations, resource_version, creation_timestamp 
                 FROM configmaps 
-                WHERE deletion_timestamp IS NULL 
+                WHERE deletion_timestamp IS NULL{} 
                 ORDER BY namespace, name
-            "#
-        };
-
-        let rows = if let Some(ns) = namespace {
-            sqlx::query(query)
-                .bind(ns)
-                .fetch_all(&self.pool)
-                .await?
-        } else {
-            sqlx::query(query)
-                .fetch_all(&self.pool)
-                .await?
-        };
+            "#,
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -258,7 +266,7 @@ impl ConfigMapStore {
         let row = sqlx::query(check_query)
             .bind(namespace)
             .bind(name)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&self.db)
             .await?
             .ok_or_else(|| anyhow!("ConfigMap {}/{} not found", namespace, name))?;
 
@@ -278,22 +286,28 @@ impl ConfigMapStore {
 
         let update_query = r#"
             UPDATE configmaps 
-            SET data = ?1, binary_data = ?2, immutable = ?3, labels = ?4, annotations = ?5, resource_version = resource_version + 1
-            WHERE namespace = ?6 AND name = ?7
+            SET data = ?1, binary_data = ?2, immutable = ?3, labels = ?4, annotations = ?5, resource_version = ?6
+            WHERE namespace = ?7 AND name = ?8
         "#;
 
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(update_query)
             .bind(data.to_string())
             .bind(binary_data.map(|v| v.to_string()))
             .bind(immutable)
             .bind(labels.to_string())
             .bind(annotations.to_string())
+            .bind(version)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
-        self.get(namespace, name).await
+        let configmap = tx.get(namespace, name).await?;
+        watch_store::record(&tx.db, "configmaps", "MODIFIED", &configmap).await?;
+        tx.db.commit_write().await?;
+        Ok(configmap)
     }
 
     pub async fn patch(&self, namespace: &str, name: &str, patch: Value) -> Result<Value> {
@@ -305,37 +319,20 @@ impl ConfigMapStore {
             return Err(anyhow!("Cannot patch immutable ConfigMap"));
         }
 
-        // Apply merge patch
-        if let Some(data) = patch.get("data") {
-            if let Some(existing_data) = existing.get_mut("data") {
-                if let (Some(existing_obj), Some(patch_obj)) = (existing_data.as_object_mut(), data.as_object()) {
-                    for (key, value) in patch_obj {
-                        existing_obj.insert(key.clone(), value.clone());
-                    }
-                }
-            } else {
-                existing["data"] = data.clone();
-            }
-        }
-
-        if let Some(binary_data) = patch.get("binaryData") {
-            if let Some(existing_data) = existing.get_mut("binaryData") {
-                if let (Some(existing_obj), Some(patch_obj)) = (existing_data.as_object_mut(), binary_data.as_object()) {
-                    for (key, value) in patch_obj {
-                        existing_obj.insert(key.clone(), value.clone());
-                    }
-                }
-            } else {
-                existing["binaryData"] = binary_data.clone();
+        // Apply merge patch; maps are merged key by key and a null removes
+        // the key, which keeps large annotations such as the last-applied
+        // configuration intact when other keys change
+        for field in ["data", "binaryData"] {
+            if let Some(value) = patch.get(field) {
+                json_patch::merge(&mut existing[field], value);
             }
         }
 
         if let Some(metadata) = patch.get("metadata") {
-            if let Some(labels) = metadata.get("labels") {
-                existing["metadata"]["labels"] = labels.clone();
-            }
-            if let Some(annotations) = metadata.get("annotations") {
-                existing["metadata"]["annotations"] = annotations.clone();
+            for field in ["labels", "annotations"] {
+                if let Some(value) = metadata.get(field) {
+                    json_patch::merge(&mut existing["metadata"][field], value);
+                }
             }
         }
 
@@ -352,16 +349,19 @@ impl ConfigMapStore {
         let configmap = self.get(namespace, name).await?;
 
         // Soft delete
-        let deletion_timestamp = Utc::now().to_rfc3339();
+        let deletion_timestamp = time::now();
         let delete_query = "UPDATE configmaps SET deletion_timestamp = ?1 WHERE namespace = ?2 AND name = ?3";
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(delete_query)
             .bind(&deletion_timestamp)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        watch_store::record(&tx.db, "configmaps", "DELETED", &configmap).await?;
+        tx.db.commit_write().await?;
         Ok(configmap)
     }
 }
\ No newline at end of file
@@ -0,0 +1,193 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use sqlx::sqlite::SqliteRow;
+use sqlx::Row;
+use uuid::Uuid;
+
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::resource_version;
+use super::watch_store;
+use crate::models::time;
+
+const COLUMNS: &str = "uid, name, namespace, resource_version, creation_timestamp, labels, annotations, owner_references, data, revision";
+
+/// Keeps ControllerRevisions, the numbered snapshots of the pod templates
+/// StatefulSets and DaemonSets have had.
+pub struct ControllerRevisionStore {
+    db: Db,
+}
+
+impl ControllerRevisionStore {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, namespace: &str, mut revision: Value) -> Result<Value> {
+        let uid = Uuid::new_v4().to_string();
+        let name = revision["metadata"]["name"]
+            .as_str()
+            .ok_or_else(|| anyhow!("ControllerRevision name is required"))?
+            .to_string();
+        let now = time::now();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+
+        // A deleted revision keeps its row, which would block reusing the name
+        sqlx::query("DELETE FROM controller_revisions WHERE namespace = ?1 AND name = ?2 AND deletion_timestamp IS NOT NULL")
+            .bind(namespace)
+            .bind(&name)
+            .execute(&tx.db)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO controller_revisions (uid, name, namespace, resource_version, creation_timestamp, labels, annotations, owner_references, data, revision)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&uid)
+        .bind(&name)
+        .bind(namespace)
+        .bind(version)
+        .bind(&now)
+        .bind(revision["metadata"]["labels"].to_string())
+        .bind(revision["metadata"]["annotations"].to_string())
+        .bind(revision["metadata"]["ownerReferences"].to_string())
+        .bind(revision["data"].to_string())
+        .bind(revision["revision"].as_i64().unwrap_or(0))
+        .execute(&tx.db)
+        .await?;
+
+        revision["apiVersion"] = json!("apps/v1");
+        revision["kind"] = json!("ControllerRevision");
+        revision["metadata"]["uid"] = json!(uid);
+        revision["metadata"]["namespace"] = json!(namespace);
+        revision["metadata"]["resourceVersion"] = json!(version.to_string());
+        revision["metadata"]["creationTimestamp"] = json!(now);
+
+        watch_store::record(&tx.db, "controllerrevisions", "ADDED", &revision).await?;
+        tx.db.commit_write().await?;
+        Ok(revision)
+    }
+
+    pub async fn get(&self, namespace: &str, name: &str) -> Result<Value> {
+        let query = format!("SELECT {} FROM controller_revisions WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL", COLUMNS);
+        let row = sqlx::query(&query)
+            .bind(namespace)
+            .bind(name)
+            .fetch_optional(&self.db)
+            .await?;
+
+        match row {
+            Some(row) => from_row(&row),
+            None => Err(anyhow!("ControllerRevision not found")),
+        }
+    }
+
+    pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
+            "SELECT {} FROM controller_revisions WHERE deletion_timestamp IS NULL{} ORDER BY namespace, revision",
+            COLUMNS,
+            selector.sql(field_selector::NAMESPACED)?
+        );
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
+        let items = rows.iter().map(from_row).collect::<Result<Vec<_>>>()?;
+
+        Ok(json!({
+            "apiVersion": "apps/v1",
+            "kind": "ControllerRevisionList",
+            "metadata": {
+                "resourceVersion": "1"
+            },
+            "items": items
+        }))
+    }
+
+    /// The revisions owned by the object with `owner_uid`, oldest first.
+    pub async fn owned_by(&self, namespace: &str, owner_uid: &str) -> Result<Vec<Value>> {
+        let query = format!(
+            "SELECT {} FROM controller_revisions
+             WHERE namespace = ? AND owner_references LIKE ? AND deletion_timestamp IS NULL
+             ORDER BY revision",
+            COLUMNS
+        );
+        let rows = sqlx::query(&query)
+            .bind(namespace)
+            .bind(format!("%\"uid\":\"{}\"%", owner_uid))
+            .fetch_all(&self.db)
+            .await?;
+        rows.iter().map(from_row).collect()
+    }
+
+    /// Renumbers a revision, as when its template becomes current again.
+    /// Only the number changes; a revision's data is immutable.
+    pub async fn set_revision(&self, namespace: &str, name: &str, number: i64) -> Result<Value> {
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let updated = sqlx::query(
+            "UPDATE controller_revisions SET revision = ?, resource_version = ?
+             WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL"
+        )
+        .bind(number)
+        .bind(version)
+        .bind(namespace)
+        .bind(name)
+        .execute(&tx.db)
+        .await?;
+        if updated.rows_affected() == 0 {
+            return Err(anyhow!("ControllerRevision not found"));
+        }
+
+        let revision = tx.get(namespace, name).await?;
+        watch_store::record(&tx.db, "controllerrevisions", "MODIFIED", &revision).await?;
+        tx.db.commit_write().await?;
+        Ok(revision)
+    }
+
+    pub async fn delete(&self, namespace: &str, name: &str) -> Result<Value> {
+        let revision = self.get(namespace, name).await?;
+
+        let tx = Self::new(self.db.begin_write().await?);
+        sqlx::query("UPDATE controller_revisions SET deletion_timestamp = ? WHERE uid = ?")
+            .bind(time::now())
+            .bind(revision["metadata"]["uid"].as_str())
+            .execute(&tx.db)
+            .await?;
+
+        watch_store::record(&tx.db, "controllerrevisions", "DELETED", &revision).await?;
+        tx.db.commit_write().await?;
+        Ok(revision)
+    }
+}
+
+fn from_row(row: &SqliteRow) -> Result<Value> {
+    let namespace: String = row.get("namespace");
+    let name: String = row.get("name");
+    let mut revision = json!({
+        "apiVersion": "apps/v1",
+        "kind": "ControllerRevision",
+        "metadata": {
+            "uid": row.get::<String, _>("uid"),
+            "name": name,
+            "namespace": namespace,
+            "resourceVersion": row.get::<i64, _>("resource_version").to_string(),
+            "creationTimestamp": row.get::<String, _>("creation_timestamp"),
+            "selfLink": format!("/apis/apps/v1/namespaces/{}/controllerrevisions/{}", namespace, name)
+        },
+        "data": serde_json::from_str::<Value>(&row.get::<String, _>("data"))?,
+        "revision": row.get::<i64, _>("revision")
+    });
+
+    for (field, column) in [("labels", "labels"), ("annotations", "annotations"), ("ownerReferences", "owner_references")] {
+        let value = row.get::<Option<String>, _>(column).and_then(|value| serde_json::from_str::<Value>(&value).ok());
+        if let Some(value) = value.filter(|value| !value.is_null()) {
+            revision["metadata"][field] = value;
+        }
+    }
+    Ok(revision)
+}
@@ -1,16 +1,23 @@
 use anyhow::{anyhow, Result};
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::compression;
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::time;
+
 pub struct CronJobStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl CronJobStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut cronjob: Value) -> Result<Value> {
@@ -20,7 +27,7 @@ impl CronJobStore {
             .ok_or_else(|| anyhow!("CronJob name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         // Extract spec fields
         let schedule = cronjob["spec"]["schedule"]
@@ -68,9 +75,11 @@ impl CronJobStore {
                 failed_jobs_history_limit, active, labels, annotations, 
                 resource_version, creation_timestamp
             )
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, 1, ?15)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
         "#;
         
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(query)
             .bind(&uid)
             .bind(namespace)
@@ -80,14 +89,15 @@ impl CronJobStore {
             .bind(starting_deadline_seconds)
             .bind(&concurrency_policy)
             .bind(suspend)
-            .bind(job_template.to_string())
+            .bind(compression::encode(&job_template))
             .bind(successful_jobs_history_limit)
             .bind(failed_jobs_history_limit)
             .bind(json!([]).to_string()) // active jobs
             .bind(labels.to_string())
-            .bind(annotations.to_string())
+            .bind(compression::encode(&annotations))
+            .bind(version)
             .bind(&now)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
         // Build response
@@ -95,7 +105,7 @@ impl CronJobStore {
         cronjob["kind"] = json!("CronJob");
         cronjob["metadata"]["uid"] = json!(uid);
         cronjob["metadata"]["namespace"] = json!(namespace);
-        cronjob["metadata"]["resourceVersion"] = json!("1");
+        cronjob["metadata"]["resourceVersion"] = json!(version.to_string());
         cronjob["metadata"]["generation"] = json!(1);
         cronjob["metadata"]["creationTimestamp"] = json!(now);
         cronjob["metadata"]["selfLink"] = json!(format!("/apis/batch/v1/namespaces/{}/cronjobs/{}", namespace, name));
@@ -123,6 +133,8 @@ impl CronJobStore {
             cronjob["metadata"]["annotations"] = annotations;
         }
 
+        watch_store::record(&tx.db, "cronjobs", "ADDED", &cronjob).await?;
+        tx.db.commit_write().await?;
         Ok(cronjob)
     }
 
@@ -139,7 +151,7 @@ impl CronJobStore {
         let row = sqlx::query(query)
             .bind(namespace)
             .bind(name)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&self.db)
             .await?;
 
         match row {
@@ -152,38 +164,25 @@ impl CronJobStore {
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if namespace.is_some() {
-            r#"
-                SELECT uid, namespace, name, schedule, timezone, starting_deadline_seconds, concurrency_policy,
-                       suspend, job_template, successful_jobs_history_limit, failed_jobs_history_limit,
-                       active, last_schedule_time, last_successful_time,
-                       labels, annotations, resource_version, generation, creation_timestamp 
-                FROM cronjobs 
-                WHERE namespace = ?1 AND deletion_timestamp IS NULL 
-                ORDER BY name
-            "#
-        } else {
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
             r#"
                 SELECT uid, namespace, name, schedule, timezone, starting_deadline_seconds, concurrency_policy,
                        suspend, job_template, successful_jobs_history_limit, failed_jobs_history_limit,
                        active, last_schedule_time, last_successful_time,
                        labels, annotations, resource_version, generation, creation_timestamp 
                 FROM cronjobs 
-                WHERE deletion_timestamp IS NULL 
+                WHERE deletion_timestamp IS NULL{} 
                 ORDER BY namespace, name
-            "#
-        };
+            "#,
+            selector.sql(field_selector::NAMESPACED)?
+        );
 
-        let rows = if let Some(ns) = namespace {
-            sqlx::query(query)
-                .bind(ns)
-                .fetch_all(&self.pool)
-                .await?
-        } else {
-            sqlx::query(query)
-                .fetch_all(&self.pool)
-                .await?
-        };
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -212,19 +211,28 @@ impl CronJobStore {
         let update_query = r#"
             UPDATE cronjobs 
             SET active = ?1, last_schedule_time = ?2, last_successful_time = ?3,
-                resource_version = resource_version + 1
-            WHERE namespace = ?4 AND name = ?5 AND deletion_timestamp IS NULL
+                resource_version = ?4
+            WHERE namespace = ?5 AND name = ?6 AND deletion_timestamp IS NULL
         "#;
 
-        sqlx::query(update_query)
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let updated = sqlx::query(update_query)
             .bind(status.get("active").map(|v| v.to_string()).unwrap_or_else(|| "[]".to_string()))
             .bind(status.get("lastScheduleTime").and_then(|v| v.as_str()))
             .bind(status.get("lastSuccessfulTime").and_then(|v| v.as_str()))
+            .bind(version)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        if updated.rows_affected() > 0 {
+            let cronjob = tx.get(namespace, name).await?;
+            watch_store::record(&tx.db, "cronjobs", "MODIFIED", &cronjob).await?;
+        }
+
+        tx.db.commit_write().await?;
         Ok(())
     }
 
@@ -233,16 +241,19 @@ impl CronJobStore {
         let cronjob = self.get(namespace, name).await?;
 
         // Soft delete
-        let deletion_timestamp = Utc::now().to_rfc3339();
+        let deletion_timestamp = time::now();
         let delete_query = "UPDATE cronjobs SET deletion_timestamp = ?1 WHERE namespace = ?2 AND name = ?3";
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(delete_query)
             .bind(&deletion_timestamp)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        watch_store::record(&tx.db, "cronjobs", "DELETED", &cronjob).await?;
+        tx.db.commit_write().await?;
         Ok(cronjob)
     }
 
@@ -253,7 +264,6 @@ impl CronJobStore {
         let starting_deadline_seconds: Option<i64> = row.get("starting_deadline_seconds");
         let concurrency_policy: String = row.get("concurrency_policy");
         let suspend: bool = row.get("suspend");
-        let job_template_str: String = row.get("job_template");
         let successful_jobs_history_limit: i64 = row.get("successful_jobs_history_limit");
         let failed_jobs_history_limit: i64 = row.get("failed_jobs_history_limit");
         
@@ -262,18 +272,17 @@ impl CronJobStore {
         let last_successful_time: Option<String> = row.get("last_successful_time");
         
         let labels_str: String = row.get("labels");
-        let annotations_str: String = row.get("annotations");
         let resource_version: i64 = row.get("resource_version");
         let generation: i64 = row.get("generation");
         let creation_timestamp: String = row.get("creation_timestamp");
 
-        let job_template: Value = serde_json::from_str(&job_template_str)?;
+        let job_template = compression::decode(&row, "job_template")?;
         let active: Value = active_str
             .map(|s| serde_json::from_str(&s))
             .transpose()?
             .unwrap_or_else(|| json!([]));
         let labels: Value = serde_json::from_str(&labels_str)?;
-        let annotations: Value = serde_json::from_str(&annotations_str)?;
+        let annotations = compression::decode(&row, "annotations")?;
 
         let mut cronjob = json!({
             "apiVersion": "batch/v1",
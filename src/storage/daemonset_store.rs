@@ -1,16 +1,23 @@
 use anyhow::{anyhow, Result};
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::compression;
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::time;
+
 pub struct DaemonSetStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl DaemonSetStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut daemonset: Value) -> Result<Value> {
@@ -20,7 +27,7 @@ impl DaemonSetStore {
             .ok_or_else(|| anyhow!("DaemonSet name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         // Extract spec fields
         let selector = daemonset["spec"]["selector"].clone();
@@ -62,22 +69,25 @@ impl DaemonSetStore {
                 min_ready_seconds, revision_history_limit,
                 labels, annotations, resource_version, creation_timestamp
             )
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, 1, ?11)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
         "#;
         
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(query)
             .bind(&uid)
             .bind(namespace)
             .bind(&name)
             .bind(selector.to_string())
-            .bind(template.to_string())
+            .bind(compression::encode(&template))
             .bind(update_strategy.to_string())
             .bind(min_ready_seconds)
             .bind(revision_history_limit)
             .bind(labels.to_string())
-            .bind(annotations.to_string())
+            .bind(compression::encode(&annotations))
+            .bind(version)
             .bind(&now)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
         // Build response
@@ -85,7 +95,7 @@ impl DaemonSetStore {
         daemonset["kind"] = json!("DaemonSet");
         daemonset["metadata"]["uid"] = json!(uid);
         daemonset["metadata"]["namespace"] = json!(namespace);
-        daemonset["metadata"]["resourceVersion"] = json!("1");
+        daemonset["metadata"]["resourceVersion"] = json!(version.to_string());
         daemonset["metadata"]["generation"] = json!(1);
         daemonset["metadata"]["creationTimestamp"] = json!(now);
         daemonset["metadata"]["selfLink"] = json!(format!("/apis/apps/v1/namespaces/{}/daemonsets/{}", namespace, name));
@@ -116,6 +126,8 @@ impl DaemonSetStore {
             daemonset["metadata"]["annotations"] = annotations;
         }
 
+        watch_store::record(&tx.db, "daemonsets", "ADDED", &daemonset).await?;
+        tx.db.commit_write().await?;
         Ok(daemonset)
     }
 
@@ -134,7 +146,7 @@ impl DaemonSetStore {
         let row = sqlx::query(query)
             .bind(namespace)
             .bind(name)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&self.db)
             .await?;
 
         match row {
@@ -147,19 +159,12 @@ impl DaemonSetStore {
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if namespace.is_some() {
-            r#"
-                SELECT uid, namespace, name, selector, template, update_strategy,
-                       min_ready_seconds, revision_history_limit,
-                       current_number_scheduled, number_misscheduled, desired_number_scheduled,
-                       number_ready, observed_generation, updated_number_scheduled,
-                       number_available, number_unavailable, collision_count, conditions,
-                       labels, annotations, resource_version, generation, creation_timestamp 
-                FROM daemonsets 
-                WHERE namespace = ?1 AND deletion_timestamp IS NULL 
-                ORDER BY name
-            "#
-        } else {
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
             r#"
                 SELECT uid, namespace, name, selector, template, update_strategy,
                        min_ready_seconds, revision_history_limit,
@@ -168,21 +173,13 @@ impl DaemonSetStore {
                        number_available, number_unavailable, collision_count, conditions,
                        labels, annotations, resource_version, generation, creation_timestamp 
                 FROM daemonsets 
-                WHERE deletion_timestamp IS NULL 
+                WHERE deletion_timestamp IS NULL{} 
                 ORDER BY namespace, name
-            "#
-        };
-
-        let rows = if let Some(ns) = namespace {
-            sqlx::query(query)
-                .bind(ns)
-                .fetch_all(&self.pool)
-                .await?
-        } else {
-            sqlx::query(query)
-                .fetch_all(&self.pool)
-                .await?
-        };
+            "#,
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -246,21 +243,24 @@ impl DaemonSetStore {
             SET selector = ?1, template = ?2, update_strategy = ?3,
                 min_ready_seconds = ?4, revision_history_limit = ?5,
                 labels = ?6, annotations = ?7, 
-                resource_version = resource_version + 1, generation = generation + 1
-            WHERE namespace = ?8 AND name = ?9 AND deletion_timestamp IS NULL
+                resource_version = ?8, generation = generation + 1
+            WHERE namespace = ?9 AND name = ?10 AND deletion_timestamp IS NULL
         "#;
 
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         let rows_affected = sqlx::query(update_query)
             .bind(selector.to_string())
-            .bind(template.to_string())
+            .bind(compression::encode(&template))
             .bind(update_strategy.to_string())
             .bind(min_ready_seconds)
             .bind(revision_history_limit)
             .bind(labels.to_string())
-            .bind(annotations.to_string())
+            .bind(compression::encode(&annotations))
+            .bind(version)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?
             .rows_affected();
 
@@ -268,7 +268,10 @@ impl DaemonSetStore {
             return Err(anyhow!("DaemonSet {}/{} not found", namespace, name));
         }
 
-        self.get(namespace, name).await
+        let daemonset = tx.get(namespace, name).await?;
+        watch_store::record(&tx.db, "daemonsets", "MODIFIED", &daemonset).await?;
+        tx.db.commit_write().await?;
+        Ok(daemonset)
     }
 
     pub async fn update_status(&self, namespace: &str, name: &str, status: Value) -> Result<()> {
@@ -279,11 +282,13 @@ impl DaemonSetStore {
                 observed_generation = ?5, updated_number_scheduled = ?6,
                 number_available = ?7, number_unavailable = ?8,
                 collision_count = ?9, conditions = ?10,
-                resource_version = resource_version + 1
-            WHERE namespace = ?11 AND name = ?12 AND deletion_timestamp IS NULL
+                resource_version = ?11
+            WHERE namespace = ?12 AND name = ?13 AND deletion_timestamp IS NULL
         "#;
 
-        sqlx::query(update_query)
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let updated = sqlx::query(update_query)
             .bind(status["currentNumberScheduled"].as_i64().unwrap_or(0))
             .bind(status["numberMisscheduled"].as_i64().unwrap_or(0))
             .bind(status["desiredNumberScheduled"].as_i64().unwrap_or(0))
@@ -294,11 +299,18 @@ impl DaemonSetStore {
             .bind(status["numberUnavailable"].as_i64().unwrap_or(0))
             .bind(status["collisionCount"].as_i64().unwrap_or(0))
             .bind(status.get("conditions").map(|v| v.to_string()))
+            .bind(version)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        if updated.rows_affected() > 0 {
+            let daemonset = tx.get(namespace, name).await?;
+            watch_store::record(&tx.db, "daemonsets", "MODIFIED", &daemonset).await?;
+        }
+
+        tx.db.commit_write().await?;
         Ok(())
     }
 
@@ -307,23 +319,25 @@ impl DaemonSetStore {
         let daemonset = self.get(namespace, name).await?;
 
         // Soft delete
-        let deletion_timestamp = Utc::now().to_rfc3339();
+        let deletion_timestamp = time::now();
         let delete_query = "UPDATE daemonsets SET deletion_timestamp = ?1 WHERE namespace = ?2 AND name = ?3";
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(delete_query)
             .bind(&deletion_timestamp)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        watch_store::record(&tx.db, "daemonsets", "DELETED", &daemonset).await?;
+        tx.db.commit_write().await?;
         Ok(daemonset)
     }
 
     fn row_to_daemonset(&self, row: sqlx::sqlite::SqliteRow, namespace: &str, name: &str) -> Result<Value> {
         let uid: String = row.get("uid");
         let selector_str: String = row.get("selector");
-        let template_str: String = row.get("template");
         let update_strategy_str: String = row.get("update_strategy");
         let min_ready_seconds: i64 = row.get("min_ready_seconds");
         let revision_history_limit: i64 = row.get("revision_history_limit");
@@ -340,17 +354,16 @@ impl DaemonSetStore {
         let conditions_str: Option<String> = row.get("conditions");
         
         let labels_str: String = row.get("labels");
-        let annotations_str: String = row.get("annotations");
         let resource_version: i64 = row.get("resource_version");
         let generation: i64 = row.get("generation");
         let creation_timestamp: String = row.get("creation_timestamp");
 
         let selector: Value = serde_json::from_str(&selector_str)?;
-        let template: Value = serde_json::from_str(&template_str)?;
+        let template = compression::decode(&row, "template")?;
         let update_strategy: Value = serde_json::from_str(&update_strategy_str)?;
         let conditions: Option<Value> = conditions_str.map(|s| serde_json::from_str(&s)).transpose()?;
         let labels: Value = serde_json::from_str(&labels_str)?;
-        let annotations: Value = serde_json::from_str(&annotations_str)?;
+        let annotations = compression::decode(&row, "annotations")?;
 
         let mut daemonset = json!({
             "apiVersion": "apps/v1",
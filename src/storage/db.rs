@@ -0,0 +1,177 @@
+// The database handle stores run their queries on. It is either the shared
+// connection pool, or a transaction opened with `Storage::transaction`, in
+// which case every store created from it takes part in that transaction.
+// It also carries the watch bus the watch events of its writes go out on;
+// a transaction holds them back until it commits. A write on the pool is
+// made in a transaction of its own, from `begin_write`, so its resource
+// version is taken in the same transaction as its row and watch event.
+use futures::future::BoxFuture;
+use futures::stream::BoxStream;
+use futures::{StreamExt, TryStreamExt};
+use sqlx::sqlite::{SqliteQueryResult, SqliteRow, SqliteStatement, SqliteTypeInfo};
+use sqlx::{Describe, Either, Error, Execute, Executor, Sqlite, SqlitePool};
+use std::sync::Arc;
+use tokio::sync::Mutex;
+
+use super::watch_bus::{WatchBus, WatchEvent};
+
+// Taken out when the transaction is committed or rolled back
+pub(crate) type SharedTransaction = Arc<Mutex<Option<sqlx::Transaction<'static, Sqlite>>>>;
+
+#[derive(Clone, Debug)]
+pub struct Db {
+    conn: Conn,
+    bus: Arc<WatchBus>,
+    // Whether this is the transaction of a single write, which
+    // `commit_write` commits
+    own_write: bool,
+}
+
+#[derive(Clone, Debug)]
+enum Conn {
+    Pool(SqlitePool),
+    // With the watch events of its writes so far, by resource type
+    Transaction(SharedTransaction, Arc<std::sync::Mutex<Vec<(String, WatchEvent)>>>),
+}
+
+impl Db {
+    pub(crate) fn pool(pool: SqlitePool, bus: Arc<WatchBus>) -> Self {
+        Self { conn: Conn::Pool(pool), bus, own_write: false }
+    }
+
+    pub(crate) fn transaction(tx: SharedTransaction, bus: Arc<WatchBus>) -> Self {
+        Self { conn: Conn::Transaction(tx, Arc::default()), bus, own_write: false }
+    }
+
+    /// Where a write runs its queries, from taking its resource version to
+    /// recording its watch event: a transaction of its own if this is the
+    /// pool, or else the transaction this already is. Dropped without
+    /// `commit_write`, a transaction of its own is rolled back.
+    pub(crate) async fn begin_write(&self) -> Result<Self, Error> {
+        match &self.conn {
+            Conn::Pool(pool) => {
+                let tx = pool.begin().await?;
+                Ok(Self { own_write: true, ..Self::transaction(Arc::new(Mutex::new(Some(tx))), self.bus.clone()) })
+            }
+            Conn::Transaction(..) => Ok(Self { own_write: false, ..self.clone() }),
+        }
+    }
+
+    /// Commits a write begun with `begin_write` and sends its watch events,
+    /// if it has a transaction of its own; otherwise they go with the
+    /// transaction it's part of.
+    pub(crate) async fn commit_write(self) -> Result<(), Error> {
+        if let (true, Conn::Transaction(tx, _)) = (self.own_write, &self.conn) {
+            let tx = tx.lock().await.take().ok_or_else(finished)?;
+            tx.commit().await?;
+            self.publish_committed();
+        }
+        Ok(())
+    }
+
+    /// Whether queries on this are part of a transaction.
+    pub(crate) fn in_transaction(&self) -> bool {
+        matches!(self.conn, Conn::Transaction(..))
+    }
+
+    pub(crate) fn bus(&self) -> &Arc<WatchBus> {
+        &self.bus
+    }
+
+    /// Sends the watch event of a write to the watches of `resource_type`,
+    /// or in a transaction keeps it until `publish_committed`.
+    pub(crate) fn publish(&self, resource_type: &str, event: WatchEvent) {
+        match &self.conn {
+            Conn::Pool(_) => self.bus.publish(resource_type, event),
+            Conn::Transaction(_, pending) => pending.lock().unwrap().push((resource_type.to_string(), event)),
+        }
+    }
+
+    /// Sends the events a transaction kept back, once it has committed.
+    pub(crate) fn publish_committed(&self) {
+        if let Conn::Transaction(_, pending) = &self.conn {
+            for (resource_type, event) in pending.lock().unwrap().drain(..) {
+                self.bus.publish(&resource_type, event);
+            }
+        }
+    }
+}
+
+fn finished() -> Error {
+    Error::Protocol("transaction already committed or rolled back".to_string())
+}
+
+// Queries on a transaction take turns on its single connection; the lock is
+// held only while a query runs.
+impl<'c> Executor<'c> for &'c Db {
+    type Database = Sqlite;
+
+    fn fetch_many<'e, 'q: 'e, E>(
+        self,
+        query: E,
+    ) -> BoxStream<'e, Result<Either<SqliteQueryResult, SqliteRow>, Error>>
+    where
+        'c: 'e,
+        E: Execute<'q, Sqlite> + 'q,
+    {
+        match &self.conn {
+            Conn::Pool(pool) => pool.fetch_many(query),
+            Conn::Transaction(tx, _) => async_stream::try_stream! {
+                let mut tx = tx.lock().await;
+                let conn = tx.as_mut().ok_or_else(finished)?;
+                let mut results = conn.fetch_many(query);
+                while let Some(result) = results.try_next().await? {
+                    yield result;
+                }
+            }
+            .boxed(),
+        }
+    }
+
+    fn fetch_optional<'e, 'q: 'e, E>(self, query: E) -> BoxFuture<'e, Result<Option<SqliteRow>, Error>>
+    where
+        'c: 'e,
+        E: Execute<'q, Sqlite> + 'q,
+    {
+        match &self.conn {
+            Conn::Pool(pool) => pool.fetch_optional(query),
+            Conn::Transaction(tx, _) => Box::pin(async move {
+                let mut tx = tx.lock().await;
+                let conn = tx.as_mut().ok_or_else(finished)?;
+                conn.fetch_optional(query).await
+            }),
+        }
+    }
+
+    fn prepare_with<'e, 'q: 'e>(
+        self,
+        sql: &'q str,
+        parameters: &'e [SqliteTypeInfo],
+    ) -> BoxFuture<'e, Result<SqliteStatement<'q>, Error>>
+    where
+        'c: 'e,
+    {
+        match &self.conn {
+            Conn::Pool(pool) => pool.prepare_with(sql, parameters),
+            Conn::Transaction(tx, _) => Box::pin(async move {
+                let mut tx = tx.lock().await;
+                let conn = tx.as_mut().ok_or_else(finished)?;
+                conn.prepare_with(sql, parameters).await
+            }),
+        }
+    }
+
+    fn describe<'e, 'q: 'e>(self, sql: &'q str) -> BoxFuture<'e, Result<Describe<Sqlite>, Error>>
+    where
+        'c: 'e,
+    {
+        match &self.conn {
+            Conn::Pool(pool) => pool.describe(sql),
+            Conn::Transaction(tx, _) => Box::pin(async move {
+                let mut tx = tx.lock().await;
+                let conn = tx.as_mut().ok_or_else(finished)?;
+                conn.describe(sql).await
+            }),
+        }
+    }
+}
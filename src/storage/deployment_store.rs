@@ -1,16 +1,24 @@
 use anyhow::{anyhow, Result};
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::compression;
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::resource_version;
+use super::watch_store;
+use crate::models::replicas;
+use crate::models::time;
+
 pub struct DeploymentStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl DeploymentStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut deployment: Value) -> Result<Value> {
@@ -20,20 +28,19 @@ impl DeploymentStore {
             .ok_or_else(|| anyhow!("Deployment name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         
         // Set metadata fields
         deployment["metadata"]["uid"] = json!(uid);
         deployment["metadata"]["namespace"] = json!(namespace);
-        deployment["metadata"]["resourceVersion"] = json!("1");
+        deployment["metadata"]["resourceVersion"] = json!(version.to_string());
         deployment["metadata"]["creationTimestamp"] = json!(now);
         deployment["metadata"]["generation"] = json!(1);
         deployment["metadata"]["selfLink"] = json!(format!("/apis/apps/v1/namespaces/{}/deployments/{}", namespace, name));
         
-        // Set default replicas if not specified
-        if deployment["spec"]["replicas"].is_null() {
-            deployment["spec"]["replicas"] = json!(1);
-        }
+        replicas::set_default(&mut deployment["spec"]);
         
         // Set default status
         deployment["status"] = json!({
@@ -55,9 +62,9 @@ impl DeploymentStore {
         });
         
         let labels = deployment["metadata"]["labels"].to_string();
-        let annotations = deployment["metadata"]["annotations"].to_string();
-        let spec = deployment["spec"].to_string();
-        let status = deployment["status"].to_string();
+        let annotations = compression::encode(&deployment["metadata"]["annotations"]);
+        let spec = compression::encode(&deployment["spec"]);
+        let status = compression::encode(&deployment["status"]);
         
         sqlx::query(
             "INSERT INTO deployments (uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, generation, replicas)
@@ -66,20 +73,21 @@ impl DeploymentStore {
         .bind(&uid)
         .bind(&name)
         .bind(namespace)
-        .bind(1i64)
+        .bind(version)
         .bind(&now)
         .bind(&labels)
         .bind(&annotations)
         .bind(&spec)
         .bind(&status)
-        .bind(1i64)
-        .bind(deployment["spec"]["replicas"].as_i64().unwrap_or(1))
-        .execute(&self.pool)
+        .bind(version)
+        .bind(replicas::desired(&deployment["spec"]))
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        self.record_event("deployments", &uid, &name, namespace, "ADDED", 1, &deployment).await?;
+        watch_store::record(&tx.db, "deployments", "ADDED", &deployment).await?;
         
+        tx.db.commit_write().await?;
         Ok(deployment)
     }
 
@@ -90,7 +98,7 @@ impl DeploymentStore {
         )
         .bind(name)
         .bind(namespace)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
         
         match row {
@@ -107,8 +115,8 @@ impl DeploymentStore {
                         "generation": row.get::<i64, _>("generation"),
                         "selfLink": format!("/apis/apps/v1/namespaces/{}/deployments/{}", namespace, name)
                     },
-                    "spec": serde_json::from_str::<Value>(&row.get::<String, _>("spec"))?,
-                    "status": serde_json::from_str::<Value>(&row.get::<String, _>("status"))?
+                    "spec": compression::decode(&row, "spec")?,
+                    "status": compression::decode(&row, "status")?
                 });
                 
                 if let Ok(labels) = serde_json::from_str::<Value>(&row.get::<String, _>("labels")) {
@@ -117,7 +125,7 @@ impl DeploymentStore {
                     }
                 }
                 
-                if let Ok(annotations) = serde_json::from_str::<Value>(&row.get::<String, _>("annotations")) {
+                if let Ok(annotations) = compression::decode(&row, "annotations") {
                     if !annotations.is_null() {
                         deployment["metadata"]["annotations"] = annotations;
                     }
@@ -130,20 +138,18 @@ impl DeploymentStore {
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if let Some(ns) = namespace {
-            sqlx::query(
-                "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, generation
-                 FROM deployments WHERE namespace = ? AND deletion_timestamp IS NULL"
-            )
-            .bind(ns)
-        } else {
-            sqlx::query(
-                "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, generation
-                 FROM deployments WHERE deletion_timestamp IS NULL"
-            )
-        };
-        
-        let rows = query.fetch_all(&self.pool).await?;
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
+            "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, generation
+             FROM deployments WHERE deletion_timestamp IS NULL{}",
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
         
         let mut items = Vec::new();
         for row in rows {
@@ -161,8 +167,8 @@ impl DeploymentStore {
                         row.get::<String, _>("namespace"), 
                         row.get::<String, _>("name"))
                 },
-                "spec": serde_json::from_str::<Value>(&row.get::<String, _>("spec"))?,
-                "status": serde_json::from_str::<Value>(&row.get::<String, _>("status"))?
+                "spec": compression::decode(&row, "spec")?,
+                "status": compression::decode(&row, "status")?
             });
             
             if let Ok(labels) = serde_json::from_str::<Value>(&row.get::<String, _>("labels")) {
@@ -171,7 +177,7 @@ impl DeploymentStore {
                 }
             }
             
-            if let Ok(annotations) = serde_json::from_str::<Value>(&row.get::<String, _>("annotations")) {
+            if let Ok(annotations) = compression::decode(&row, "annotations") {
                 if !annotations.is_null() {
                     deployment["metadata"]["annotations"] = annotations;
                 }
@@ -194,13 +200,11 @@ impl DeploymentStore {
         // Get current deployment to check it exists
         let current = self.get(namespace, name).await?;
         let uid = current["metadata"]["uid"].as_str().unwrap();
-        let resource_version = current["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse::<i64>()?;
         let generation = current["metadata"]["generation"].as_i64().unwrap();
         
-        let new_version = resource_version + 1;
+        replicas::set_default(&mut deployment["spec"]);
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_version = resource_version::next(&tx.db).await?;
         let new_generation = if deployment["spec"] != current["spec"] {
             generation + 1
         } else {
@@ -213,14 +217,16 @@ impl DeploymentStore {
         deployment["metadata"]["resourceVersion"] = json!(new_version.to_string());
         deployment["metadata"]["generation"] = json!(new_generation);
         
+        // Status is only written through update_status
+        deployment["status"] = current["status"].clone();
+        
         let labels = deployment["metadata"]["labels"].to_string();
-        let annotations = deployment["metadata"]["annotations"].to_string();
-        let spec = deployment["spec"].to_string();
-        let status = deployment["status"].to_string();
-        let replicas = deployment["spec"]["replicas"].as_i64().unwrap_or(1);
+        let annotations = compression::encode(&deployment["metadata"]["annotations"]);
+        let spec = compression::encode(&deployment["spec"]);
+        let replicas = replicas::desired(&deployment["spec"]);
         
         sqlx::query(
-            "UPDATE deployments SET resource_version = ?, generation = ?, labels = ?, annotations = ?, spec = ?, status = ?, replicas = ?
+            "UPDATE deployments SET resource_version = ?, generation = ?, labels = ?, annotations = ?, spec = ?, replicas = ?
              WHERE uid = ?"
         )
         .bind(new_version)
@@ -228,15 +234,15 @@ impl DeploymentStore {
         .bind(&labels)
         .bind(&annotations)
         .bind(&spec)
-        .bind(&status)
         .bind(replicas)
         .bind(uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        self.record_event("deployments", uid, name, namespace, "MODIFIED", new_version, &deployment).await?;
+        watch_store::record(&tx.db, "deployments", "MODIFIED", &deployment).await?;
         
+        tx.db.commit_write().await?;
         Ok(deployment)
     }
 
@@ -244,60 +250,51 @@ impl DeploymentStore {
         let mut deployment = self.get(namespace, name).await?;
         let uid = deployment["metadata"]["uid"].as_str().unwrap().to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         // Set deletion timestamp in the object
         deployment["metadata"]["deletionTimestamp"] = json!(now.clone());
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
             "UPDATE deployments SET deletion_timestamp = ? WHERE uid = ?"
         )
         .bind(&now)
         .bind(&uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        let resource_version = deployment["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse::<i64>()?;
-        self.record_event("deployments", &uid, name, namespace, "DELETED", resource_version, &deployment).await?;
+        watch_store::record(&tx.db, "deployments", "DELETED", &deployment).await?;
         
+        tx.db.commit_write().await?;
         Ok(deployment)
     }
 
-    pub async fn update_status(&self, namespace: &str, name: &str, status: Value) -> Result<()> {
-        let deployment = self.get(namespace, name).await?;
-        let uid = deployment["metadata"]["uid"].as_str().unwrap();
-        
-        sqlx::query(
-            "UPDATE deployments SET status = ? WHERE uid = ?"
-        )
-        .bind(status.to_string())
-        .bind(uid)
-        .execute(&self.pool)
-        .await?;
-        
-        Ok(())
-    }
-
-    async fn record_event(&self, resource_type: &str, uid: &str, name: &str, namespace: &str, event_type: &str, version: i64, object: &Value) -> Result<()> {
-        sqlx::query(
-            "INSERT INTO events (resource_type, resource_uid, resource_name, resource_namespace, event_type, resource_version, timestamp, object)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+    /// Replaces the status, leaving the spec and metadata untouched.
+    pub async fn update_status(&self, namespace: &str, name: &str, status: Value) -> Result<Value> {
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let updated = sqlx::query(
+            "UPDATE deployments SET status = ?, resource_version = ?
+             WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL"
         )
-        .bind(resource_type)
-        .bind(uid)
-        .bind(name)
-        .bind(namespace)
-        .bind(event_type)
+        .bind(compression::encode(&status))
         .bind(version)
-        .bind(Utc::now().to_rfc3339())
-        .bind(object.to_string())
-        .execute(&self.pool)
+        .bind(namespace)
+        .bind(name)
+        .execute(&tx.db)
         .await?;
         
-        Ok(())
+        if updated.rows_affected() == 0 {
+            return Err(anyhow!("Deployment not found"));
+        }
+        
+        let deployment = tx.get(namespace, name).await?;
+        watch_store::record(&tx.db, "deployments", "MODIFIED", &deployment).await?;
+        
+        tx.db.commit_write().await?;
+        Ok(deployment)
     }
+
 }
\ No newline at end of file
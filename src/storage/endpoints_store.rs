@@ -1,16 +1,22 @@
 use anyhow::{anyhow, Result};
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::resource_version;
+use super::watch_store;
+use crate::models::time;
+
 pub struct EndpointsStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl EndpointsStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut endpoints: Value) -> Result<Value> {
@@ -20,12 +26,14 @@ impl EndpointsStore {
             .ok_or_else(|| anyhow!("Endpoints name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         
         // Set metadata fields
         endpoints["metadata"]["uid"] = json!(uid);
         endpoints["metadata"]["namespace"] = json!(namespace);
-        endpoints["metadata"]["resourceVersion"] = json!("1");
+        endpoints["metadata"]["resourceVersion"] = json!(version.to_string());
         endpoints["metadata"]["creationTimestamp"] = json!(now);
         endpoints["metadata"]["selfLink"] = json!(format!("/api/v1/namespaces/{}/endpoints/{}", namespace, name));
         
@@ -45,17 +53,18 @@ impl EndpointsStore {
         .bind(&uid)
         .bind(&name)
         .bind(namespace)
-        .bind(1i64)
+        .bind(version)
         .bind(&now)
         .bind(&labels)
         .bind(&annotations)
         .bind(&subsets)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        self.record_event("endpoints", &uid, &name, namespace, "ADDED", 1, &endpoints).await?;
+        watch_store::record(&tx.db, "endpoints", "ADDED", &endpoints).await?;
         
+        tx.db.commit_write().await?;
         Ok(endpoints)
     }
 
@@ -66,7 +75,7 @@ impl EndpointsStore {
         )
         .bind(name)
         .bind(namespace)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
         
         match row {
@@ -104,20 +113,18 @@ impl EndpointsStore {
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if let Some(ns) = namespace {
-            sqlx::query(
-                "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, subsets
-                 FROM endpoints WHERE namespace = ? AND deletion_timestamp IS NULL"
-            )
-            .bind(ns)
-        } else {
-            sqlx::query(
-                "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, subsets
-                 FROM endpoints WHERE deletion_timestamp IS NULL"
-            )
-        };
-        
-        let rows = query.fetch_all(&self.pool).await?;
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
+            "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, subsets
+             FROM endpoints WHERE deletion_timestamp IS NULL{}",
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
         
         let mut items = Vec::new();
         for row in rows {
@@ -166,12 +173,8 @@ impl EndpointsStore {
         // Get current endpoints to check it exists
         let current = self.get(namespace, name).await?;
         let uid = current["metadata"]["uid"].as_str().unwrap();
-        let resource_version = current["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse::<i64>()?;
-        
-        let new_version = resource_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_version = resource_version::next(&tx.db).await?;
         
         // Update metadata
         endpoints["metadata"]["uid"] = json!(uid);
@@ -191,12 +194,13 @@ impl EndpointsStore {
         .bind(&annotations)
         .bind(&subsets)
         .bind(uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        self.record_event("endpoints", uid, name, namespace, "MODIFIED", new_version, &endpoints).await?;
+        watch_store::record(&tx.db, "endpoints", "MODIFIED", &endpoints).await?;
         
+        tx.db.commit_write().await?;
         Ok(endpoints)
     }
 
@@ -204,63 +208,54 @@ impl EndpointsStore {
         let mut endpoints = self.get(namespace, name).await?;
         let uid = endpoints["metadata"]["uid"].as_str().unwrap().to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         // Set deletion timestamp in the object
         endpoints["metadata"]["deletionTimestamp"] = json!(now.clone());
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
             "UPDATE endpoints SET deletion_timestamp = ? WHERE uid = ?"
         )
         .bind(&now)
         .bind(&uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        let resource_version = endpoints["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse::<i64>()?;
-        self.record_event("endpoints", &uid, name, namespace, "DELETED", resource_version, &endpoints).await?;
+        watch_store::record(&tx.db, "endpoints", "DELETED", &endpoints).await?;
         
+        tx.db.commit_write().await?;
         Ok(endpoints)
     }
 
-    async fn record_event(&self, resource_type: &str, uid: &str, name: &str, namespace: &str, event_type: &str, version: i64, object: &Value) -> Result<()> {
-        sqlx::query(
-            "INSERT INTO events (resource_type, resource_uid, resource_name, resource_namespace, event_type, resource_version, timestamp, object)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+    pub async fn update_for_service(&self, service_namespace: &str, service_name: &str, service_selector: &Value) -> Result<()> {
+        // Get service ports
+        let service_row = sqlx::query(
+            "SELECT spec FROM services WHERE namespace = ? AND name = ?"
         )
-        .bind(resource_type)
-        .bind(uid)
-        .bind(name)
-        .bind(namespace)
-        .bind(event_type)
-        .bind(version)
-        .bind(Utc::now().to_rfc3339())
-        .bind(object.to_string())
-        .execute(&self.pool)
+        .bind(service_namespace)
+        .bind(service_name)
+        .fetch_optional(&self.db)
         .await?;
-        
-        Ok(())
-    }
 
-    pub async fn update_for_service(&self, service_namespace: &str, service_name: &str, service_selector: &Value) -> Result<()> {
-        // Find all running pods that match the selector
-        let mut pod_ips = Vec::new();
+        let service_ports = service_row
+            .and_then(|row| serde_json::from_str::<Value>(&row.get::<String, _>("spec")).ok())
+            .and_then(|spec| spec["ports"].as_array().cloned())
+            .unwrap_or_else(|| vec![json!({"port": 80, "protocol": "TCP"})]);
+
+        // Pods are grouped into subsets by the ports they serve, since a named
+        // targetPort can resolve to a different number on each of them
+        let mut subsets: Vec<(Vec<Value>, Vec<Value>)> = Vec::new();
         
         if !service_selector.is_null() && service_selector.is_object() {
-            // Build the label query
-            let selector_map = service_selector.as_object().unwrap();
-            
             // Query pods with matching labels
             let rows = sqlx::query(
-                "SELECT name, labels, status FROM pods 
+                "SELECT name, labels, spec, status, node_name FROM pods 
                  WHERE namespace = ? AND deletion_timestamp IS NULL AND phase = 'Running'"
             )
             .bind(service_namespace)
-            .fetch_all(&self.pool)
+            .fetch_all(&self.db)
             .await?;
             
             for row in rows {
@@ -270,16 +265,38 @@ impl EndpointsStore {
                     if Self::labels_match(&pod_labels, service_selector) {
                         let pod_name: String = row.get("name");
                         let status_str: String = row.get("status");
+                        let pod_spec = serde_json::from_str::<Value>(&row.get::<String, _>("spec")).unwrap_or_default();
                         if let Ok(status) = serde_json::from_str::<Value>(&status_str) {
+                            // hostNetwork pods report their node's address here
                             if let Some(pod_ip) = status["podIP"].as_str() {
-                                pod_ips.push(json!({
+                                let ports: Vec<Value> = service_ports
+                                    .iter()
+                                    .filter_map(|port| Self::endpoint_port(port, &pod_spec))
+                                    .collect();
+                                if ports.is_empty() {
+                                    continue;
+                                }
+
+                                let node_name: Option<String> = row.get("node_name");
+                                let mut address = json!({
                                     "ip": pod_ip,
+                                    "nodeName": node_name,
                                     "targetRef": {
                                         "kind": "Pod",
                                         "namespace": service_namespace,
                                         "name": pod_name
                                     }
-                                }));
+                                });
+                                // A pod whose subdomain is this service is
+                                // published under its hostname, for DNS
+                                let hostname = pod_spec["hostname"].as_str().filter(|h| !h.is_empty());
+                                if let Some(hostname) = hostname.filter(|_| pod_spec["subdomain"] == service_name) {
+                                    address["hostname"] = json!(hostname);
+                                }
+                                match subsets.iter_mut().find(|(subset_ports, _)| *subset_ports == ports) {
+                                    Some((_, addresses)) => addresses.push(address),
+                                    None => subsets.push((ports, vec![address])),
+                                }
                             }
                         }
                     }
@@ -288,43 +305,10 @@ impl EndpointsStore {
         }
         
         // Build the endpoints subsets
-        let subsets = if pod_ips.is_empty() {
-            json!([])
-        } else {
-            // Get service ports
-            let service_row = sqlx::query(
-                "SELECT spec FROM services WHERE namespace = ? AND name = ?"
-            )
-            .bind(service_namespace)
-            .bind(service_name)
-            .fetch_optional(&self.pool)
-            .await?;
-            
-            let ports = if let Some(row) = service_row {
-                let spec_str: String = row.get("spec");
-                if let Ok(spec) = serde_json::from_str::<Value>(&spec_str) {
-                    if let Some(service_ports) = spec["ports"].as_array() {
-                        service_ports.iter().map(|p| {
-                            json!({
-                                "port": p["targetPort"].as_i64().unwrap_or_else(|| p["port"].as_i64().unwrap_or(80)),
-                                "protocol": p["protocol"].as_str().unwrap_or("TCP")
-                            })
-                        }).collect::<Vec<_>>()
-                    } else {
-                        vec![json!({"port": 80, "protocol": "TCP"})]
-                    }
-                } else {
-                    vec![json!({"port": 80, "protocol": "TCP"})]
-                }
-            } else {
-                vec![json!({"port": 80, "protocol": "TCP"})]
-            };
-            
-            json!([{
-                "addresses": pod_ips,
-                "ports": ports
-            }])
-        };
+        let subsets = json!(subsets
+            .into_iter()
+            .map(|(ports, addresses)| json!({ "addresses": addresses, "ports": ports }))
+            .collect::<Vec<_>>());
         
         // Check if endpoints exist
         let existing = self.get(service_namespace, service_name).await;
@@ -349,6 +333,29 @@ impl EndpointsStore {
         Ok(())
     }
 
+    // The endpoint port a service port leads to on a pod. A named targetPort
+    // is looked up among the pod's container ports of the same protocol; the
+    // pod doesn't serve the port if none has that name.
+    fn endpoint_port(service_port: &Value, pod_spec: &Value) -> Option<Value> {
+        let protocol = service_port["protocol"].as_str().unwrap_or("TCP");
+        let port = match &service_port["targetPort"] {
+            Value::String(name) => pod_spec["containers"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .flat_map(|container| container["ports"].as_array().into_iter().flatten())
+                .find(|p| p["name"] == *name && p["protocol"].as_str().unwrap_or("TCP") == protocol)?["containerPort"]
+                .as_i64()?,
+            target_port => target_port.as_i64().unwrap_or_else(|| service_port["port"].as_i64().unwrap_or(80)),
+        };
+
+        let mut endpoint_port = json!({ "port": port, "protocol": protocol });
+        if let Some(name) = service_port["name"].as_str() {
+            endpoint_port["name"] = json!(name);
+        }
+        Some(endpoint_port)
+    }
+
     fn labels_match(pod_labels: &Value, selector: &Value) -> bool {
         if let Some(selector_obj) = selector.as_object() {
             for (key, value) in selector_obj {
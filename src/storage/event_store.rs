@@ -0,0 +1,362 @@
+// core/v1 Events, as `kubectl describe` and `kubectl get events` show them.
+// They're kept in the events table alongside watch events and the stores'
+// own records, as the rows with a name. Components report what happens to
+// objects with `record`, which, like client-go's event recorder, counts a
+// repeat of an event on the one already reported rather than adding
+// another.
+use anyhow::{anyhow, Result};
+use chrono::Utc;
+use serde_json::{json, Value};
+use sqlx::Row;
+use uuid::Uuid;
+
+use super::db::Db;
+use super::list_selector::ListSelector;
+use super::resource_version;
+use super::watch_store;
+use crate::models::time;
+
+/// The type of an event about something working as it should.
+pub const NORMAL: &str = "Normal";
+
+/// The type of an event about something going wrong.
+pub const WARNING: &str = "Warning";
+
+// Field labels events can be selected on, as kubectl describe does with
+// involvedObject
+const FIELDS: &[(&str, &str)] = &[
+    ("metadata.name", "name"),
+    ("metadata.namespace", "namespace"),
+    ("involvedObject.kind", "involved_object_kind"),
+    ("involvedObject.namespace", "involved_object_namespace"),
+    ("involvedObject.name", "involved_object_name"),
+    ("involvedObject.uid", "involved_object_uid"),
+    ("involvedObject.apiVersion", "involved_object_api_version"),
+    ("involvedObject.fieldPath", "involved_object_field_path"),
+    ("reason", "reason"),
+    ("source", "source_component"),
+    ("reportingComponent", "source_component"),
+    ("type", "type"),
+];
+
+const COLUMNS: &str = "uid, namespace, name, involved_object_kind, involved_object_namespace, involved_object_name,
+    involved_object_uid, involved_object_api_version, involved_object_field_path, reason, message, type, count,
+    first_timestamp, last_timestamp, source_component, source_host, labels, annotations, resource_version";
+
+/// The object an event is about.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ObjectReference {
+    pub kind: String,
+    pub api_version: String,
+    pub namespace: Option<String>,
+    pub name: String,
+    pub uid: String,
+    /// The part of the object, such as `spec.containers{app}`.
+    pub field_path: Option<String>,
+}
+
+impl ObjectReference {
+    pub fn new(kind: &str, api_version: &str, namespace: Option<&str>, name: &str, uid: &str) -> Self {
+        Self {
+            kind: kind.to_string(),
+            api_version: api_version.to_string(),
+            namespace: namespace.map(str::to_string),
+            name: name.to_string(),
+            uid: uid.to_string(),
+            field_path: None,
+        }
+    }
+
+    pub fn pod(namespace: &str, name: &str, uid: &str) -> Self {
+        Self::new("Pod", "v1", Some(namespace), name, uid)
+    }
+
+    /// The reference to one of a pod's containers.
+    pub fn container(mut self, container: &str) -> Self {
+        self.field_path = Some(format!("spec.containers{{{}}}", container));
+        self
+    }
+}
+
+/// The component reporting an event, and the node it runs on if it's a
+/// kubelet.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct EventSource {
+    pub component: String,
+    pub host: Option<String>,
+}
+
+impl EventSource {
+    pub fn new(component: &str) -> Self {
+        Self { component: component.to_string(), host: None }
+    }
+
+    pub fn kubelet(node_name: &str) -> Self {
+        Self { component: "kubelet".to_string(), host: Some(node_name.to_string()) }
+    }
+}
+
+pub struct EventStore {
+    db: Db,
+}
+
+impl EventStore {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// Reports an event about `involved`. One like it reported before, with
+    /// the same reason and message from the same source, has its count
+    /// raised instead. Events about cluster-scoped objects go in the
+    /// default namespace.
+    pub async fn record(
+        &self,
+        source: &EventSource,
+        involved: &ObjectReference,
+        event_type: &str,
+        reason: &str,
+        message: &str,
+    ) -> Result<Value> {
+        let namespace = involved.namespace.as_deref().unwrap_or("default");
+        let now = time::now();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+
+        let repeated = sqlx::query(
+            "UPDATE events SET count = count + 1, last_timestamp = ?, resource_version = ?
+             WHERE name IS NOT NULL AND namespace = ? AND involved_object_uid = ?
+               AND COALESCE(involved_object_field_path, '') = ? AND type = ? AND reason = ? AND message = ?
+               AND source_component = ?
+             RETURNING name"
+        )
+        .bind(&now)
+        .bind(version)
+        .bind(namespace)
+        .bind(&involved.uid)
+        .bind(involved.field_path.as_deref().unwrap_or_default())
+        .bind(event_type)
+        .bind(reason)
+        .bind(message)
+        .bind(&source.component)
+        .fetch_optional(&tx.db)
+        .await?;
+        if let Some(row) = repeated {
+            let event = tx.get(namespace, &row.get::<String, _>("name")).await?;
+            watch_store::record(&tx.db, "events", "MODIFIED", &event).await?;
+            tx.db.commit_write().await?;
+            return Ok(event);
+        }
+
+        // Named after the object and the time, as client-go names them
+        let nanos = Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        let name = format!("{}.{:x}", involved.name, nanos);
+        let event = json!({
+            "metadata": { "name": name },
+            "involvedObject": reference_json(involved),
+            "reason": reason,
+            "message": message,
+            "type": event_type,
+            "source": source_json(source),
+            "firstTimestamp": now,
+            "lastTimestamp": now,
+            "count": 1
+        });
+        let event = tx.insert(namespace, &event, version).await?;
+        tx.db.commit_write().await?;
+        Ok(event)
+    }
+
+    /// Creates an event posted to the API.
+    pub async fn create(&self, namespace: &str, event: Value) -> Result<Value> {
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let event = tx.insert(namespace, &event, version).await?;
+        tx.db.commit_write().await?;
+        Ok(event)
+    }
+
+    pub async fn get(&self, namespace: &str, name: &str) -> Result<Value> {
+        let sql = format!("SELECT {} FROM events WHERE namespace = ? AND name = ?", COLUMNS);
+        let row = sqlx::query(&sql)
+            .bind(namespace)
+            .bind(name)
+            .fetch_optional(&self.db)
+            .await?
+            .ok_or_else(|| anyhow!("Event {}/{} not found", namespace, name))?;
+        from_row(&row)
+    }
+
+    pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let sql = format!(
+            "SELECT {} FROM events WHERE name IS NOT NULL{} ORDER BY namespace, last_timestamp, id",
+            COLUMNS,
+            selector.sql(FIELDS)?
+        );
+        let rows = selector.bind(sqlx::query(&sql)).fetch_all(&self.db).await?;
+        let items = rows.iter().map(from_row).collect::<Result<Vec<_>>>()?;
+
+        Ok(json!({
+            "apiVersion": "v1",
+            "kind": "EventList",
+            "metadata": {
+                "selfLink": match namespace {
+                    Some(ns) => format!("/api/v1/namespaces/{}/events", ns),
+                    None => "/api/v1/events".to_string(),
+                },
+            },
+            "items": items
+        }))
+    }
+
+    pub async fn delete(&self, namespace: &str, name: &str) -> Result<Value> {
+        let event = self.get(namespace, name).await?;
+        let tx = Self::new(self.db.begin_write().await?);
+        sqlx::query("DELETE FROM events WHERE namespace = ? AND name = ?")
+            .bind(namespace)
+            .bind(name)
+            .execute(&tx.db)
+            .await?;
+        watch_store::record(&tx.db, "events", "DELETED", &event).await?;
+        tx.db.commit_write().await?;
+        Ok(event)
+    }
+
+    async fn insert(&self, namespace: &str, event: &Value, version: i64) -> Result<Value> {
+        let name = event["metadata"]["name"].as_str().ok_or_else(|| anyhow!("Event name is required"))?;
+        let involved = &event["involvedObject"];
+        let now = time::now();
+        let first_timestamp = event["firstTimestamp"].as_str().unwrap_or(&now);
+        let uid = Uuid::new_v4().to_string();
+
+        let tx = Self::new(self.db.begin_write().await?);
+        sqlx::query(
+            "INSERT INTO events (uid, namespace, name, involved_object_kind, involved_object_namespace, involved_object_name,
+                                 involved_object_uid, involved_object_api_version, involved_object_field_path, reason, message,
+                                 type, count, event_time, first_timestamp, last_timestamp, source_component, source_host,
+                                 labels, annotations, resource_version)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&uid)
+        .bind(namespace)
+        .bind(name)
+        .bind(involved["kind"].as_str())
+        .bind(involved["namespace"].as_str())
+        .bind(involved["name"].as_str().unwrap_or_default())
+        .bind(involved["uid"].as_str())
+        .bind(involved["apiVersion"].as_str())
+        .bind(involved["fieldPath"].as_str())
+        .bind(event["reason"].as_str())
+        .bind(event["message"].as_str())
+        .bind(event["type"].as_str().unwrap_or(NORMAL))
+        .bind(event["count"].as_i64().unwrap_or(1))
+        .bind(&now)
+        .bind(first_timestamp)
+        .bind(event["lastTimestamp"].as_str().unwrap_or(first_timestamp))
+        .bind(event["source"]["component"].as_str().or(event["reportingComponent"].as_str()))
+        .bind(event["source"]["host"].as_str())
+        .bind(map_json(&event["metadata"]["labels"]))
+        .bind(map_json(&event["metadata"]["annotations"]))
+        .bind(version)
+        .execute(&tx.db)
+        .await?;
+
+        let event = tx.get(namespace, name).await?;
+        watch_store::record(&tx.db, "events", "ADDED", &event).await?;
+        tx.db.commit_write().await?;
+        Ok(event)
+    }
+}
+
+fn reference_json(involved: &ObjectReference) -> Value {
+    let mut reference = json!({
+        "kind": involved.kind,
+        "apiVersion": involved.api_version,
+        "name": involved.name,
+        "uid": involved.uid
+    });
+    if let Some(namespace) = &involved.namespace {
+        reference["namespace"] = json!(namespace);
+    }
+    if let Some(field_path) = &involved.field_path {
+        reference["fieldPath"] = json!(field_path);
+    }
+    reference
+}
+
+// A labels or annotations map as its column keeps it
+fn map_json(map: &Value) -> String {
+    if map.is_object() { map.to_string() } else { "{}".to_string() }
+}
+
+fn source_json(source: &EventSource) -> Value {
+    let mut json = json!({ "component": source.component });
+    if let Some(host) = &source.host {
+        json["host"] = json!(host);
+    }
+    json
+}
+
+fn from_row(row: &sqlx::sqlite::SqliteRow) -> Result<Value> {
+    let namespace: String = row.get("namespace");
+    let name: String = row.get("name");
+    let first_timestamp: Option<String> = row.get("first_timestamp");
+    let labels: Value = serde_json::from_str(&row.get::<String, _>("labels"))?;
+    let annotations: Value = serde_json::from_str(&row.get::<String, _>("annotations"))?;
+
+    let mut involved = json!({});
+    for (field, column) in [
+        ("kind", "involved_object_kind"),
+        ("namespace", "involved_object_namespace"),
+        ("name", "involved_object_name"),
+        ("uid", "involved_object_uid"),
+        ("apiVersion", "involved_object_api_version"),
+        ("fieldPath", "involved_object_field_path"),
+    ] {
+        if let Some(value) = row.get::<Option<String>, _>(column) {
+            involved[field] = json!(value);
+        }
+    }
+    let mut source = json!({});
+    if let Some(component) = row.get::<Option<String>, _>("source_component") {
+        source["component"] = json!(component);
+    }
+    if let Some(host) = row.get::<Option<String>, _>("source_host") {
+        source["host"] = json!(host);
+    }
+
+    let mut event = json!({
+        "apiVersion": "v1",
+        "kind": "Event",
+        "metadata": {
+            "name": name,
+            "namespace": namespace,
+            "uid": row.get::<Option<String>, _>("uid"),
+            "resourceVersion": row.get::<Option<i64>, _>("resource_version").unwrap_or_default().to_string(),
+            "creationTimestamp": first_timestamp,
+            "selfLink": format!("/api/v1/namespaces/{}/events/{}", namespace, name),
+        },
+        "involvedObject": involved,
+        "reason": row.get::<Option<String>, _>("reason"),
+        "message": row.get::<Option<String>, _>("message"),
+        "source": source,
+        "firstTimestamp": first_timestamp,
+        "lastTimestamp": row.get::<Option<String>, _>("last_timestamp"),
+        "count": row.get::<Option<i64>, _>("count").unwrap_or(1),
+        "type": row.get::<Option<String>, _>("type"),
+        "eventTime": null,
+        "reportingComponent": row.get::<Option<String>, _>("source_component").unwrap_or_default(),
+        "reportingInstance": row.get::<Option<String>, _>("source_host").unwrap_or_default()
+    });
+    if labels.as_object().is_some_and(|labels| !labels.is_empty()) {
+        event["metadata"]["labels"] = labels;
+    }
+    if annotations.as_object().is_some_and(|annotations| !annotations.is_empty()) {
+        event["metadata"]["annotations"] = annotations;
+    }
+    Ok(event)
+}
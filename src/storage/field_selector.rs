@@ -0,0 +1,118 @@
+// Field selectors narrow a list to the objects whose fields have, or don't
+// have, given values, as in `?fieldSelector=status.phase=Running,spec.nodeName!=`.
+// Each store names the fields it supports and the SQL expression that reads
+// each one, so the filtering happens in the list query.
+use anyhow::{anyhow, Result};
+use sqlx::query::{Query, QueryAs};
+use sqlx::sqlite::SqliteArguments;
+use sqlx::Sqlite;
+
+/// Fields every namespaced kind can be selected on.
+pub(crate) const NAMESPACED: &[(&str, &str)] = &[("metadata.name", "name"), ("metadata.namespace", "namespace")];
+
+/// Fields every cluster-scoped kind can be selected on.
+pub(crate) const CLUSTER_SCOPED: &[(&str, &str)] = &[("metadata.name", "name")];
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FieldSelector {
+    requirements: Vec<Requirement>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Requirement {
+    field: String,
+    equal: bool,
+    value: String,
+}
+
+impl FieldSelector {
+    /// Parses comma-separated `field=value`, `field==value` and
+    /// `field!=value` terms. An empty selector selects everything.
+    pub fn parse(selector: &str) -> Result<Self> {
+        let mut requirements = Vec::new();
+        for term in selector.split(',').map(str::trim).filter(|term| !term.is_empty()) {
+            let (field, equal, value) = if let Some((field, value)) = term.split_once("!=") {
+                (field, false, value)
+            } else if let Some((field, value)) = term.split_once("==") {
+                (field, true, value)
+            } else if let Some((field, value)) = term.split_once('=') {
+                (field, true, value)
+            } else {
+                return Err(anyhow!("invalid field selector {:?}: expected field=value or field!=value", term));
+            };
+
+            let field = field.trim();
+            if field.is_empty() {
+                return Err(anyhow!("invalid field selector {:?}: missing field", term));
+            }
+            requirements.push(Requirement { field: field.to_string(), equal, value: value.trim().to_string() });
+        }
+        Ok(Self { requirements })
+    }
+
+    /// This selector, further limited to `namespace` when there is one.
+    pub(crate) fn in_namespace(&self, namespace: Option<&str>) -> Self {
+        let mut selector = self.clone();
+        if let Some(namespace) = namespace {
+            selector.requirements.push(Requirement {
+                field: "metadata.namespace".to_string(),
+                equal: true,
+                value: namespace.to_string(),
+            });
+        }
+        selector
+    }
+
+    /// `AND` conditions to append to a WHERE clause, one per requirement,
+    /// with `fields` mapping field labels to SQL expressions. A missing value
+    /// reads as the empty string, so `spec.nodeName=` selects unscheduled
+    /// pods. Bind the values with `bind`.
+    pub(crate) fn sql(&self, fields: &[(&str, &str)]) -> Result<String> {
+        let mut sql = String::new();
+        for requirement in &self.requirements {
+            let expr = fields
+                .iter()
+                .find(|(field, _)| *field == requirement.field)
+                .map(|(_, expr)| expr)
+                .ok_or_else(|| anyhow!("field label not supported: {}", requirement.field))?;
+            let op = if requirement.equal { "=" } else { "!=" };
+            sql.push_str(&format!(" AND COALESCE(CAST({} AS TEXT), '') {} ?", expr, op));
+        }
+        Ok(sql)
+    }
+
+    /// Binds the values compared against by the conditions of `sql`.
+    pub(crate) fn bind<'q>(
+        &'q self,
+        mut query: Query<'q, Sqlite, SqliteArguments<'q>>,
+    ) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+        for requirement in &self.requirements {
+            query = query.bind(requirement.value.as_str());
+        }
+        query
+    }
+
+    pub(crate) fn bind_as<'q, O>(
+        &'q self,
+        mut query: QueryAs<'q, Sqlite, O, SqliteArguments<'q>>,
+    ) -> QueryAs<'q, Sqlite, O, SqliteArguments<'q>> {
+        for requirement in &self.requirements {
+            query = query.bind(requirement.value.as_str());
+        }
+        query
+    }
+
+    /// Whether an object kept in memory rather than in a table matches.
+    /// `field` reads a field label of it, or returns `None` if the kind
+    /// doesn't support the label.
+    pub fn matches(&self, field: impl Fn(&str) -> Option<String>) -> Result<bool> {
+        for requirement in &self.requirements {
+            let value = field(&requirement.field)
+                .ok_or_else(|| anyhow!("field label not supported: {}", requirement.field))?;
+            if (value == requirement.value) != requirement.equal {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+}
@@ -0,0 +1,470 @@
+// Finalizers, for every resource. The stores keep objects in columns of
+// their own and know nothing of metadata.finalizers, so they're kept here,
+// along with when deleting an object was asked for: an object with
+// finalizers isn't deleted but marked, and stays visible with its
+// deletionTimestamp until whoever registered the finalizers has removed
+// them all. Rows are tied to the uid of the object they were written for,
+// so one left behind by an object deleted some other way never applies to
+// a new object of the same name. A pod being deleted gracefully is marked
+// too, with how long it has to stop, until its kubelet has stopped it.
+use anyhow::Result;
+use chrono::Duration;
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::collections::BTreeMap;
+
+use super::db::Db;
+use super::resource_version;
+use super::tables;
+use super::watch_store;
+use crate::models::time;
+
+/// An object's finalizers and, if it's being deleted, when it's to be gone
+/// by and the grace period that was given.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Finalizers {
+    pub uid: String,
+    pub finalizers: Vec<String>,
+    pub deletion_timestamp: Option<String>,
+    pub grace_period_seconds: Option<i64>,
+}
+
+impl Finalizers {
+    /// Whether deleting the object has to wait for its finalizers.
+    pub fn pending(&self) -> bool {
+        !self.finalizers.is_empty()
+    }
+
+    /// Whether the object is a pod its kubelet has yet to stop.
+    pub fn in_grace_period(&self) -> bool {
+        self.grace_period_seconds.unwrap_or(0) > 0
+    }
+}
+
+pub struct FinalizerStore {
+    db: Db,
+}
+
+impl FinalizerStore {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// The uid of the named object, if it exists and isn't deleted.
+    pub async fn uid_of(&self, resource: &str, namespace: Option<&str>, name: &str) -> Result<Option<String>> {
+        live_uid(&self.db, resource, namespace, name).await
+    }
+
+    /// The finalizers of the named object as it exists now, if it has any
+    /// or is being deleted.
+    pub async fn get(&self, resource: &str, namespace: Option<&str>, name: &str) -> Result<Option<Finalizers>> {
+        let Some(uid) = live_uid(&self.db, resource, namespace, name).await? else {
+            return Ok(None);
+        };
+        let row = sqlx::query("SELECT uid, finalizers, deletion_timestamp, grace_period_seconds FROM finalizers WHERE resource = ? AND namespace = ? AND name = ?")
+            .bind(resource)
+            .bind(namespace.unwrap_or_default())
+            .bind(name)
+            .fetch_optional(&self.db)
+            .await?;
+        Ok(row.map(|row| from_row(&row)).filter(|finalizers| finalizers.uid == uid))
+    }
+
+    /// Replaces the finalizers of the object with `uid`. Whether it's being
+    /// deleted is kept, unless the row was another object's.
+    pub async fn set(&self, resource: &str, namespace: Option<&str>, name: &str, uid: &str, finalizers: &[String]) -> Result<()> {
+        let existing = self.get(resource, namespace, name).await?.filter(|existing| existing.uid == uid).unwrap_or_default();
+        if finalizers.is_empty() && existing.deletion_timestamp.is_none() {
+            return self.forget(resource, namespace, name).await;
+        }
+        let kept = Finalizers { uid: uid.to_string(), finalizers: finalizers.to_vec(), ..existing };
+        write(&self.db, resource, namespace, name, &kept).await
+    }
+
+    /// Registers `finalizer` on the object, as a controller does for the
+    /// objects it has to clean up after. False if the object doesn't exist
+    /// or is being deleted, when no finalizers may be added.
+    pub async fn add(&self, resource: &str, namespace: Option<&str>, name: &str, finalizer: &str) -> Result<bool> {
+        let Some(uid) = live_uid(&self.db, resource, namespace, name).await? else {
+            return Ok(false);
+        };
+        let mut current = self.get(resource, namespace, name).await?.unwrap_or_default();
+        if current.deletion_timestamp.is_some() {
+            return Ok(false);
+        }
+        if !current.finalizers.iter().any(|f| f == finalizer) {
+            current.finalizers.push(finalizer.to_string());
+            self.set(resource, namespace, name, &uid, &current.finalizers).await?;
+            self.record_modified(resource, namespace, name).await?;
+        }
+        Ok(true)
+    }
+
+    /// Removes `finalizer` from the object, deleting it if it was being
+    /// deleted and that was the last one. Returns whether it was deleted.
+    /// Namespaces also wait for what's in them, so the namespace controller
+    /// is left to delete those.
+    pub async fn remove(&self, resource: &str, namespace: Option<&str>, name: &str, finalizer: &str) -> Result<bool> {
+        let Some(mut current) = self.get(resource, namespace, name).await? else {
+            return Ok(false);
+        };
+        if !current.finalizers.iter().any(|f| f == finalizer) {
+            return Ok(false);
+        }
+        current.finalizers.retain(|f| f != finalizer);
+        self.set(resource, namespace, name, &current.uid, &current.finalizers).await?;
+        if current.deletion_timestamp.is_some() && current.finalizers.is_empty() && !current.in_grace_period() && resource != "namespaces" {
+            return self.finish_deletion(resource, namespace, name).await;
+        }
+        self.record_modified(resource, namespace, name).await?;
+        Ok(false)
+    }
+
+    /// Marks the object as being deleted and tells watchers, returning it as
+    /// they see it, or None if it doesn't exist. Its deletionTimestamp is
+    /// `grace_period_seconds` from now, which only pods are given. Marking
+    /// it again keeps the first deletionTimestamp, unless the new grace
+    /// period ends sooner.
+    pub async fn mark_deleted(&self, resource: &str, namespace: Option<&str>, name: &str, grace_period_seconds: i64) -> Result<Option<Value>> {
+        let Some(uid) = live_uid(&self.db, resource, namespace, name).await? else {
+            return Ok(None);
+        };
+        let mut current = self.get(resource, namespace, name).await?.unwrap_or(Finalizers { uid, ..Default::default() });
+        let deletion_timestamp = time::format(chrono::Utc::now() + Duration::seconds(grace_period_seconds));
+        if current.deletion_timestamp.as_ref().is_some_and(|marked| *marked <= deletion_timestamp) {
+            let mut object = last_known(&self.db, resource, namespace, name, &current.uid).await?;
+            apply(&self.db, resource, &mut object).await?;
+            return Ok(Some(object));
+        }
+        current.deletion_timestamp = Some(deletion_timestamp);
+        current.grace_period_seconds = Some(grace_period_seconds);
+        write(&self.db, resource, namespace, name, &current).await?;
+        self.record_modified(resource, namespace, name).await
+    }
+
+    /// Ends the grace period of a pod being deleted, once its kubelet has
+    /// stopped its containers, and deletes it unless it has finalizers
+    /// left. Returns whether it was deleted.
+    pub async fn end_grace_period(&self, namespace: &str, name: &str) -> Result<bool> {
+        let Some(mut current) = self.get("pods", Some(namespace), name).await? else {
+            return Ok(false);
+        };
+        if !current.in_grace_period() {
+            return Ok(false);
+        }
+        if !current.pending() {
+            return self.finish_deletion("pods", Some(namespace), name).await;
+        }
+        current.grace_period_seconds = Some(0);
+        write(&self.db, "pods", Some(namespace), name, &current).await?;
+        self.record_modified("pods", Some(namespace), name).await?;
+        Ok(false)
+    }
+
+    /// The pods on `node_name` being deleted whose grace period hasn't
+    /// ended, with their namespaces and names, for its kubelet to stop.
+    pub async fn pods_in_grace_period(&self, node_name: &str) -> Result<Vec<(String, String, Finalizers)>> {
+        let rows = sqlx::query(
+            "SELECT f.namespace, f.name, f.uid, f.finalizers, f.deletion_timestamp, f.grace_period_seconds
+             FROM finalizers f JOIN pods p ON p.uid = f.uid AND p.deletion_timestamp IS NULL
+             WHERE f.resource = 'pods' AND f.deletion_timestamp IS NOT NULL AND f.grace_period_seconds > 0
+               AND p.node_name = ?
+             ORDER BY f.deletion_timestamp"
+        )
+        .bind(node_name)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(rows.iter().map(|row| (row.get("namespace"), row.get("name"), from_row(row))).collect())
+    }
+
+    /// Objects of `resource` being deleted, with their namespaces and names.
+    pub async fn deleting(&self, resource: &str) -> Result<Vec<(Option<String>, String, Finalizers)>> {
+        let rows = sqlx::query(
+            "SELECT namespace, name, uid, finalizers, deletion_timestamp, grace_period_seconds FROM finalizers
+             WHERE resource = ? AND deletion_timestamp IS NOT NULL ORDER BY namespace, name"
+        )
+        .bind(resource)
+        .fetch_all(&self.db)
+        .await?;
+        Ok(rows
+            .iter()
+            .map(|row| {
+                let namespace: String = row.get("namespace");
+                ((!namespace.is_empty()).then_some(namespace), row.get("name"), from_row(row))
+            })
+            .collect())
+    }
+
+    /// The objects in a namespace still waiting for finalizers, as
+    /// `resource/name`.
+    pub async fn pending_in(&self, namespace: &str) -> Result<Vec<String>> {
+        let rows = sqlx::query(
+            "SELECT resource, name, uid FROM finalizers WHERE namespace = ? AND finalizers != '[]' ORDER BY resource, name"
+        )
+        .bind(namespace)
+        .fetch_all(&self.db)
+        .await?;
+        let mut pending = Vec::new();
+        for row in rows {
+            let (resource, name): (String, String) = (row.get("resource"), row.get("name"));
+            if live_uid(&self.db, &resource, Some(namespace), &name).await? == Some(row.get("uid")) {
+                pending.push(format!("{}/{}", resource, name));
+            }
+        }
+        Ok(pending)
+    }
+
+    /// What's left in a namespace being deleted: how many objects of each
+    /// resource, and how many of them are waiting for each finalizer.
+    pub async fn remaining_in(&self, namespace: &str) -> Result<(BTreeMap<&'static str, i64>, BTreeMap<String, i64>)> {
+        let mut resources = BTreeMap::new();
+        for (resource, table, _) in tables::RESOURCES.iter().filter(|(_, _, namespaced)| *namespaced) {
+            let sql = format!("SELECT COUNT(*) FROM {} WHERE namespace = ? AND deletion_timestamp IS NULL", table);
+            let count = sqlx::query_scalar::<_, i64>(&sql).bind(namespace).fetch_one(&self.db).await?;
+            if count > 0 {
+                resources.insert(*resource, count);
+            }
+        }
+
+        let rows = sqlx::query("SELECT resource, name, uid, finalizers FROM finalizers WHERE namespace = ? AND finalizers != '[]'")
+            .bind(namespace)
+            .fetch_all(&self.db)
+            .await?;
+        let mut finalizers = BTreeMap::new();
+        for row in rows {
+            let (resource, name): (String, String) = (row.get("resource"), row.get("name"));
+            if live_uid(&self.db, &resource, Some(namespace), &name).await? != Some(row.get("uid")) {
+                continue;
+            }
+            let kept: Vec<String> = serde_json::from_str(row.get("finalizers")).unwrap_or_default();
+            for finalizer in kept {
+                *finalizers.entry(finalizer).or_insert(0) += 1;
+            }
+        }
+        Ok((resources, finalizers))
+    }
+
+    /// Sets the status of a namespace, as the namespace controller reports
+    /// what's holding up its deletion, and tells watchers.
+    pub async fn set_namespace_status(&self, name: &str, status: &Value) -> Result<()> {
+        let Some(uid) = live_uid(&self.db, "namespaces", None, name).await? else {
+            return Ok(());
+        };
+        let mut object = last_known(&self.db, "namespaces", None, name, &uid).await?;
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        sqlx::query("UPDATE namespaces SET status = ?, resource_version = ? WHERE name = ? AND deletion_timestamp IS NULL")
+            .bind(status.to_string())
+            .bind(version)
+            .bind(name)
+            .execute(&tx.db)
+            .await?;
+
+        object["metadata"]["resourceVersion"] = json!(version.to_string());
+        object["status"] = status.clone();
+        watch_store::record(&tx.db, "namespaces", "MODIFIED", &object).await?;
+        tx.db.commit_write().await?;
+        Ok(())
+    }
+
+    /// Everything kept for objects of `resource`, including rows left
+    /// behind by objects since deleted.
+    pub async fn of_resource(&self, resource: &str) -> Result<Vec<Finalizers>> {
+        let rows = sqlx::query("SELECT uid, finalizers, deletion_timestamp, grace_period_seconds FROM finalizers WHERE resource = ?")
+            .bind(resource)
+            .fetch_all(&self.db)
+            .await?;
+        Ok(rows.iter().map(from_row).collect())
+    }
+
+    /// Deletes everything in a namespace being deleted. Objects with
+    /// finalizers are only marked, and counted by `pending_in` until
+    /// they're gone.
+    pub async fn delete_namespace_contents(&self, namespace: &str) -> Result<()> {
+        for (resource, table, _) in tables::RESOURCES.iter().filter(|(_, _, namespaced)| *namespaced) {
+            let sql = format!("SELECT name FROM {} WHERE namespace = ? AND deletion_timestamp IS NULL", table);
+            let names = sqlx::query_scalar::<_, String>(&sql).bind(namespace).fetch_all(&self.db).await?;
+            for name in names {
+                let pending = self.get(resource, Some(namespace), &name).await?.is_some_and(|kept| kept.pending());
+                if pending {
+                    self.mark_deleted(resource, Some(namespace), &name, 0).await?;
+                } else {
+                    self.finish_deletion(resource, Some(namespace), &name).await?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn forget(&self, resource: &str, namespace: Option<&str>, name: &str) -> Result<()> {
+        sqlx::query("DELETE FROM finalizers WHERE resource = ? AND namespace = ? AND name = ?")
+            .bind(resource)
+            .bind(namespace.unwrap_or_default())
+            .bind(name)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Sets metadata.finalizers and metadata.deletionTimestamp on `object`
+    /// from what's kept for it.
+    pub async fn apply(&self, resource: &str, object: &mut Value) -> Result<()> {
+        apply(&self.db, resource, object).await
+    }
+
+    /// Deletes the object now, by marking its row deleted as its store
+    /// would and telling watchers, for callers that can't go through the
+    /// API. Returns whether there was an object to delete.
+    pub async fn finish_deletion(&self, resource: &str, namespace: Option<&str>, name: &str) -> Result<bool> {
+        let Some((table, namespaced)) = tables::table(resource) else {
+            return Ok(false);
+        };
+        let Some(uid) = live_uid(&self.db, resource, namespace, name).await? else {
+            self.forget(resource, namespace, name).await?;
+            return Ok(false);
+        };
+        let mut object = last_known(&self.db, resource, namespace, name, &uid).await?;
+        apply(&self.db, resource, &mut object).await?;
+
+        let scope = if namespaced { " AND namespace = ?" } else { "" };
+        let sql = format!("UPDATE {} SET deletion_timestamp = ? WHERE name = ?{} AND deletion_timestamp IS NULL", table, scope);
+        let mut query = sqlx::query(&sql).bind(time::now()).bind(name);
+        if namespaced {
+            query = query.bind(namespace.unwrap_or("default"));
+        }
+        let tx = Self::new(self.db.begin_write().await?);
+        query.execute(&tx.db).await?;
+        tx.forget(resource, namespace, name).await?;
+        watch_store::record(&tx.db, resource, "DELETED", &object).await?;
+        tx.db.commit_write().await?;
+        Ok(true)
+    }
+
+    /// Tells watchers the object's finalizers or deletionTimestamp changed,
+    /// giving it a new resource version. Returns the object as they see it.
+    pub async fn record_modified(&self, resource: &str, namespace: Option<&str>, name: &str) -> Result<Option<Value>> {
+        let Some((table, namespaced)) = tables::table(resource) else {
+            return Ok(None);
+        };
+        let Some(uid) = live_uid(&self.db, resource, namespace, name).await? else {
+            return Ok(None);
+        };
+        let mut object = last_known(&self.db, resource, namespace, name, &uid).await?;
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let scope = if namespaced { " AND namespace = ?" } else { "" };
+        let sql = format!("UPDATE {} SET resource_version = ? WHERE name = ?{} AND deletion_timestamp IS NULL", table, scope);
+        let mut query = sqlx::query(&sql).bind(version).bind(name);
+        if namespaced {
+            query = query.bind(namespace.unwrap_or("default"));
+        }
+        query.execute(&tx.db).await?;
+
+        object["metadata"]["resourceVersion"] = json!(version.to_string());
+        watch_store::record(&tx.db, resource, "MODIFIED", &object).await?;
+        apply(&tx.db, resource, &mut object).await?;
+        tx.db.commit_write().await?;
+        Ok(Some(object))
+    }
+}
+
+/// Whether objects of `resource` can have finalizers kept for them.
+pub fn tracks(resource: &str) -> bool {
+    tables::table(resource).is_some()
+}
+
+/// See `FinalizerStore::apply`. Objects without a uid, or with one other
+/// than the row's, are left alone.
+pub(crate) async fn apply(db: &Db, resource: &str, object: &mut Value) -> Result<()> {
+    let metadata = &object["metadata"];
+    let (Some(uid), Some(name)) = (metadata["uid"].as_str(), metadata["name"].as_str()) else {
+        return Ok(());
+    };
+    let row = sqlx::query("SELECT uid, finalizers, deletion_timestamp, grace_period_seconds FROM finalizers WHERE resource = ? AND namespace = ? AND name = ?")
+        .bind(resource)
+        .bind(metadata["namespace"].as_str().unwrap_or_default())
+        .bind(name)
+        .fetch_optional(db)
+        .await?;
+    let Some(kept) = row.map(|row| from_row(&row)).filter(|kept| kept.uid == uid) else {
+        return Ok(());
+    };
+    show(resource, &kept, object);
+    Ok(())
+}
+
+/// Shows `kept` on an object of `resource`: its finalizers, its
+/// deletionTimestamp if it's being deleted and, for a namespace being
+/// deleted, phase Terminating.
+pub fn show(resource: &str, kept: &Finalizers, object: &mut Value) {
+    let metadata = &mut object["metadata"];
+    if kept.finalizers.is_empty() {
+        if let Some(metadata) = metadata.as_object_mut() {
+            metadata.remove("finalizers");
+        }
+    } else {
+        metadata["finalizers"] = json!(kept.finalizers);
+    }
+    if let Some(deletion_timestamp) = &kept.deletion_timestamp {
+        metadata["deletionTimestamp"] = json!(deletion_timestamp);
+        metadata["deletionGracePeriodSeconds"] = json!(kept.grace_period_seconds.unwrap_or(0));
+        if resource == "namespaces" {
+            object["status"]["phase"] = json!("Terminating");
+        }
+    }
+}
+
+fn from_row(row: &sqlx::sqlite::SqliteRow) -> Finalizers {
+    Finalizers {
+        uid: row.get("uid"),
+        finalizers: serde_json::from_str(&row.get::<String, _>("finalizers")).unwrap_or_default(),
+        deletion_timestamp: row.get("deletion_timestamp"),
+        grace_period_seconds: row.get("grace_period_seconds"),
+    }
+}
+
+async fn write(db: &Db, resource: &str, namespace: Option<&str>, name: &str, kept: &Finalizers) -> Result<()> {
+    sqlx::query(
+        "INSERT OR REPLACE INTO finalizers (resource, namespace, name, uid, finalizers, deletion_timestamp, grace_period_seconds)
+         VALUES (?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(resource)
+    .bind(namespace.unwrap_or_default())
+    .bind(name)
+    .bind(&kept.uid)
+    .bind(json!(kept.finalizers).to_string())
+    .bind(&kept.deletion_timestamp)
+    .bind(kept.grace_period_seconds)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+// The uid of the object as it exists now
+async fn live_uid(db: &Db, resource: &str, namespace: Option<&str>, name: &str) -> Result<Option<String>> {
+    let Some((table, namespaced)) = tables::table(resource) else {
+        return Ok(None);
+    };
+    let scope = if namespaced { " AND namespace = ?" } else { "" };
+    let sql = format!("SELECT uid FROM {} WHERE name = ?{} AND deletion_timestamp IS NULL", table, scope);
+    let mut query = sqlx::query_scalar::<_, String>(&sql).bind(name);
+    if namespaced {
+        query = query.bind(namespace.unwrap_or("default"));
+    }
+    Ok(query.fetch_optional(db).await?)
+}
+
+// The object as watchers last saw it, or just its name and uid if its
+// events have been compacted away
+async fn last_known(db: &Db, resource: &str, namespace: Option<&str>, name: &str, uid: &str) -> Result<Value> {
+    let object = sqlx::query_scalar::<_, String>(
+        "SELECT object FROM events WHERE resource_type = ? AND resource_uid = ? AND object IS NOT NULL ORDER BY id DESC LIMIT 1"
+    )
+    .bind(resource)
+    .bind(uid)
+    .fetch_optional(db)
+    .await?;
+    Ok(object
+        .and_then(|object| serde_json::from_str(&object).ok())
+        .unwrap_or_else(|| json!({ "metadata": { "name": name, "namespace": namespace, "uid": uid } })))
+}
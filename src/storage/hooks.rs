@@ -0,0 +1,136 @@
+// Rust callbacks on objects, for embedding krust as a library. Tests can
+// admit, change or refuse what's written through the API, as an admission
+// webhook would but in-process, and hear of every object created, updated
+// or deleted, whoever wrote it:
+//
+//     storage.hooks().on_create("pods", |pod| {
+//         pod["metadata"]["labels"]["injected"] = json!("true");
+//         Ok(())
+//     });
+//     storage.hooks().on_object_deleted("pods", |pod| println!("{}", pod["metadata"]["name"])).await?;
+//
+// Admission hooks run for creates and updates made through the API, before
+// its admission webhooks; a resource of "pods/status" hooks that
+// subresource. The others follow the watch events of their resource from
+// when they're registered, so they see writes once committed, shortly
+// after, and in order.
+use anyhow::Result;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex};
+use tokio_stream::StreamExt;
+use tracing::error;
+
+use super::Storage;
+
+type Admit = Arc<dyn Fn(&mut Value) -> std::result::Result<(), String> + Send + Sync>;
+type Observe = Arc<dyn Fn(&Value) + Send + Sync>;
+
+// What's registered with one Storage, shared by its clones and transactions
+#[derive(Default)]
+pub(crate) struct Registry {
+    // Operation (CREATE or UPDATE), resource and hook
+    admission: Mutex<Vec<(&'static str, String, Admit)>>,
+    // Watch event type (ADDED, MODIFIED or DELETED), resource and hook
+    observers: Mutex<Vec<(&'static str, String, Observe)>>,
+    // Resources whose watch events are being followed
+    followed: Mutex<HashSet<String>>,
+}
+
+/// The hooks registered with a Storage.
+pub struct Hooks {
+    storage: Storage,
+}
+
+impl Hooks {
+    pub(crate) fn new(storage: Storage) -> Self {
+        Self { storage }
+    }
+
+    /// Runs `hook` on each `resource` object created through the API, before
+    /// it's stored. It may change the object, or refuse it with a message.
+    pub fn on_create(&self, resource: &str, hook: impl Fn(&mut Value) -> std::result::Result<(), String> + Send + Sync + 'static) {
+        self.admission("CREATE", resource, Arc::new(hook));
+    }
+
+    /// Runs `hook` on each `resource` object updated through the API, as
+    /// `on_create` does for creates.
+    pub fn on_update(&self, resource: &str, hook: impl Fn(&mut Value) -> std::result::Result<(), String> + Send + Sync + 'static) {
+        self.admission("UPDATE", resource, Arc::new(hook));
+    }
+
+    /// Calls `hook` with each `resource` object created from now on.
+    pub async fn on_object_created(&self, resource: &str, hook: impl Fn(&Value) + Send + Sync + 'static) -> Result<()> {
+        self.observe("ADDED", resource, Arc::new(hook)).await
+    }
+
+    /// Calls `hook` with each `resource` object as it's updated from now on.
+    pub async fn on_object_updated(&self, resource: &str, hook: impl Fn(&Value) + Send + Sync + 'static) -> Result<()> {
+        self.observe("MODIFIED", resource, Arc::new(hook)).await
+    }
+
+    /// Calls `hook` with each `resource` object deleted from now on, as it
+    /// was last.
+    pub async fn on_object_deleted(&self, resource: &str, hook: impl Fn(&Value) + Send + Sync + 'static) -> Result<()> {
+        self.observe("DELETED", resource, Arc::new(hook)).await
+    }
+
+    /// Whether any admission hooks are registered for `operation` on
+    /// `resource`.
+    pub(crate) fn admits(&self, operation: &str, resource: &str) -> bool {
+        let admission = self.storage.hooks.admission.lock().unwrap();
+        admission.iter().any(|(op, r, _)| *op == operation && r == resource)
+    }
+
+    /// Runs the admission hooks for `operation` on `resource` over `object`
+    /// in the order they were registered, stopping at the first refusal.
+    pub(crate) fn admit(&self, operation: &str, resource: &str, object: &mut Value) -> std::result::Result<(), String> {
+        let hooks: Vec<Admit> = {
+            let admission = self.storage.hooks.admission.lock().unwrap();
+            admission.iter().filter(|(op, r, _)| *op == operation && r == resource).map(|(_, _, hook)| hook.clone()).collect()
+        };
+        hooks.iter().try_for_each(|hook| hook(object))
+    }
+
+    fn admission(&self, operation: &'static str, resource: &str, hook: Admit) {
+        self.storage.hooks.admission.lock().unwrap().push((operation, resource.to_string(), hook));
+    }
+
+    async fn observe(&self, event_type: &'static str, resource: &str, hook: Observe) -> Result<()> {
+        let registry = self.storage.hooks.clone();
+        registry.observers.lock().unwrap().push((event_type, resource.to_string(), hook));
+        if !registry.followed.lock().unwrap().insert(resource.to_string()) {
+            return Ok(());
+        }
+
+        // One task per resource follows its watch events from here on and
+        // calls the hooks registered for each
+        let watch = self.storage.watch();
+        let from = watch.latest_version().await?;
+        let mut events = watch.watch_stream(resource.to_string(), None, Some(from.to_string())).await?;
+        let resource = resource.to_string();
+        tokio::spawn(async move {
+            while let Some(event) = events.next().await {
+                let event = match event {
+                    Ok(event) => event,
+                    Err(e) => {
+                        error!("Hooks on {} stopped: {}", resource, e);
+                        break;
+                    }
+                };
+                let hooks: Vec<Observe> = {
+                    let observers = registry.observers.lock().unwrap();
+                    observers
+                        .iter()
+                        .filter(|(event_type, r, _)| event["type"] == *event_type && *r == resource)
+                        .map(|(_, _, hook)| hook.clone())
+                        .collect()
+                };
+                for hook in hooks {
+                    hook(&event["object"]);
+                }
+            }
+        });
+        Ok(())
+    }
+}
@@ -1,28 +1,37 @@
 use anyhow::Result;
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::resource_version;
+use super::watch_store;
+use crate::models::{hpa, time};
+
 pub struct HpaStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl HpaStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut hpa: Value) -> Result<Value> {
         let uid = Uuid::new_v4().to_string();
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         
         // Set metadata fields
         hpa["metadata"]["uid"] = json!(uid);
         hpa["metadata"]["namespace"] = json!(namespace);
         hpa["metadata"]["creationTimestamp"] = json!(now);
-        hpa["metadata"]["resourceVersion"] = json!("1");
+        hpa["metadata"]["resourceVersion"] = json!(version.to_string());
         hpa["metadata"]["generation"] = json!(1);
+        hpa::set_defaults(&mut hpa["spec"]);
         
         // Initialize status if not present
         if hpa["status"].is_null() {
@@ -50,15 +59,16 @@ impl HpaStore {
         .bind(status)
         .bind(labels)
         .bind(annotations)
-        .bind(1i64)
-        .bind(1i64)
+        .bind(version)
+        .bind(1)
         .bind(&now)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        self.record_event("HorizontalPodAutoscaler", &uid, name, namespace, "ADDED", 1, &hpa).await?;
+        watch_store::record(&tx.db, "horizontalpodautoscalers", "ADDED", &hpa).await?;
         
+        tx.db.commit_write().await?;
         Ok(hpa)
     }
     
@@ -68,7 +78,7 @@ impl HpaStore {
         )
         .bind(namespace)
         .bind(name)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
         
         match row {
@@ -107,20 +117,17 @@ impl HpaStore {
     }
     
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let rows = if let Some(ns) = namespace {
-            sqlx::query(
-                "SELECT * FROM horizontalpodautoscalers WHERE namespace = ? AND deletion_timestamp IS NULL ORDER BY creation_timestamp DESC"
-            )
-            .bind(ns)
-            .fetch_all(&self.pool)
-            .await?
-        } else {
-            sqlx::query(
-                "SELECT * FROM horizontalpodautoscalers WHERE deletion_timestamp IS NULL ORDER BY creation_timestamp DESC"
-            )
-            .fetch_all(&self.pool)
-            .await?
-        };
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
+            "SELECT * FROM horizontalpodautoscalers WHERE deletion_timestamp IS NULL{} ORDER BY creation_timestamp DESC",
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
         
         let mut items = Vec::new();
         for row in rows {
@@ -168,13 +175,11 @@ impl HpaStore {
         // Get current HPA to check it exists
         let current = self.get(namespace, name).await?;
         let uid = current["metadata"]["uid"].as_str().unwrap();
-        let current_version: i64 = current["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse()?;
         let current_generation: i64 = current["metadata"]["generation"].as_i64().unwrap();
+        hpa::set_defaults(&mut hpa["spec"]);
         
-        let new_version = current_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_version = resource_version::next(&tx.db).await?;
         let new_generation = if hpa["spec"] != current["spec"] {
             current_generation + 1
         } else {
@@ -186,40 +191,39 @@ impl HpaStore {
         hpa["metadata"]["generation"] = json!(new_generation);
         hpa["metadata"]["uid"] = json!(uid);
         
+        // Status is only written through update_status
+        hpa["status"] = current["status"].clone();
+        
         let spec = hpa["spec"].to_string();
-        let status = hpa["status"].to_string();
         let labels = hpa["metadata"]["labels"].to_string();
         let annotations = hpa["metadata"]["annotations"].to_string();
         
         sqlx::query(
-            "UPDATE horizontalpodautoscalers SET spec = ?, status = ?, labels = ?, annotations = ?, resource_version = ?, generation = ?
+            "UPDATE horizontalpodautoscalers SET spec = ?, labels = ?, annotations = ?, resource_version = ?, generation = ?
              WHERE uid = ?"
         )
         .bind(spec)
-        .bind(status)
         .bind(labels)
         .bind(annotations)
         .bind(new_version)
         .bind(new_generation)
         .bind(uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        self.record_event("HorizontalPodAutoscaler", uid, name, namespace, "MODIFIED", new_version, &hpa).await?;
+        watch_store::record(&tx.db, "horizontalpodautoscalers", "MODIFIED", &hpa).await?;
         
+        tx.db.commit_write().await?;
         Ok(hpa)
     }
     
     pub async fn update_status(&self, namespace: &str, name: &str, status: Value) -> Result<Value> {
         let mut hpa = self.get(namespace, name).await?;
         let uid = hpa["metadata"]["uid"].as_str().unwrap().to_string();
-        let current_version: i64 = hpa["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse()?;
         
-        let new_version = current_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_version = resource_version::next(&tx.db).await?;
         
         // Update the status
         hpa["status"] = status.clone();
@@ -232,12 +236,13 @@ impl HpaStore {
         .bind(status.to_string())
         .bind(new_version)
         .bind(&uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        self.record_event("HorizontalPodAutoscaler", &uid, name, namespace, "MODIFIED", new_version, &hpa).await?;
+        watch_store::record(&tx.db, "horizontalpodautoscalers", "MODIFIED", &hpa).await?;
         
+        tx.db.commit_write().await?;
         Ok(hpa)
     }
     
@@ -245,45 +250,25 @@ impl HpaStore {
         let mut hpa = self.get(namespace, name).await?;
         let uid = hpa["metadata"]["uid"].as_str().unwrap().to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         // Set deletion timestamp in the object
         hpa["metadata"]["deletionTimestamp"] = json!(now.clone());
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
             "UPDATE horizontalpodautoscalers SET deletion_timestamp = ? WHERE uid = ?"
         )
         .bind(&now)
         .bind(&uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        let resource_version = hpa["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse::<i64>()?;
-        self.record_event("HorizontalPodAutoscaler", &uid, name, namespace, "DELETED", resource_version, &hpa).await?;
+        watch_store::record(&tx.db, "horizontalpodautoscalers", "DELETED", &hpa).await?;
         
+        tx.db.commit_write().await?;
         Ok(hpa)
     }
     
-    async fn record_event(&self, resource_type: &str, uid: &str, name: &str, namespace: &str, event_type: &str, version: i64, object: &Value) -> Result<()> {
-        sqlx::query(
-            "INSERT INTO events (resource_type, resource_uid, resource_name, resource_namespace, event_type, resource_version, timestamp, object)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
-        )
-        .bind(resource_type)
-        .bind(uid)
-        .bind(name)
-        .bind(namespace)
-        .bind(event_type)
-        .bind(version)
-        .bind(Utc::now().to_rfc3339())
-        .bind(object.to_string())
-        .execute(&self.pool)
-        .await?;
-        
-        Ok(())
-    }
 }
\ No newline at end of file
@@ -1,16 +1,22 @@
 use anyhow::{anyhow, Result};
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::time;
+
 pub struct IngressStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl IngressStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut ingress: Value) -> Result<Value> {
@@ -20,7 +26,7 @@ impl IngressStore {
             .ok_or_else(|| anyhow!("Ingress name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         // Extract spec fields
         let ingress_class_name = ingress["spec"].get("ingressClassName")
@@ -40,9 +46,11 @@ impl IngressStore {
                 uid, namespace, name, ingress_class_name, default_backend, rules, tls,
                 load_balancer, labels, annotations, resource_version, creation_timestamp
             )
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, 1, ?11)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)
         "#;
         
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(query)
             .bind(&uid)
             .bind(namespace)
@@ -54,8 +62,9 @@ impl IngressStore {
             .bind(json!({"ingress": []}).to_string()) // Initial load balancer status
             .bind(labels.to_string())
             .bind(annotations.to_string())
+            .bind(version)
             .bind(&now)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
         // Build response
@@ -63,7 +72,7 @@ impl IngressStore {
         ingress["kind"] = json!("Ingress");
         ingress["metadata"]["uid"] = json!(uid);
         ingress["metadata"]["namespace"] = json!(namespace);
-        ingress["metadata"]["resourceVersion"] = json!("1");
+        ingress["metadata"]["resourceVersion"] = json!(version.to_string());
         ingress["metadata"]["generation"] = json!(1);
         ingress["metadata"]["creationTimestamp"] = json!(now);
         ingress["metadata"]["selfLink"] = json!(format!("/apis/networking.k8s.io/v1/namespaces/{}/ingresses/{}", namespace, name));
@@ -88,6 +97,8 @@ impl IngressStore {
             ingress["metadata"]["annotations"] = annotations;
         }
 
+        watch_store::record(&tx.db, "ingresses", "ADDED", &ingress).await?;
+        tx.db.commit_write().await?;
         Ok(ingress)
     }
 
@@ -102,7 +113,7 @@ impl IngressStore {
         let row = sqlx::query(query)
             .bind(namespace)
             .bind(name)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&self.db)
             .await?;
 
         match row {
@@ -115,34 +126,23 @@ impl IngressStore {
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if namespace.is_some() {
-            r#"
-                SELECT uid, namespace, name, ingress_class_name, default_backend, rules, tls, load_balancer,
-                       labels, annotations, resource_version, generation, creation_timestamp 
-                FROM ingresses 
-                WHERE namespace = ?1 AND deletion_timestamp IS NULL 
-                ORDER BY name
-            "#
-        } else {
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
             r#"
                 SELECT uid, namespace, name, ingress_class_name, default_backend, rules, tls, load_balancer,
                        labels, annotations, resource_version, generation, creation_timestamp 
                 FROM ingresses 
-                WHERE deletion_timestamp IS NULL 
+                WHERE deletion_timestamp IS NULL{} 
                 ORDER BY namespace, name
-            "#
-        };
-
-        let rows = if let Some(ns) = namespace {
-            sqlx::query(query)
-                .bind(ns)
-                .fetch_all(&self.pool)
-                .await?
-        } else {
-            sqlx::query(query)
-                .fetch_all(&self.pool)
-                .await?
-        };
+            "#,
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -172,10 +172,6 @@ impl IngressStore {
         let existing = self.get(namespace, name).await?;
         let uid = existing["metadata"]["uid"].as_str().unwrap();
         let creation_timestamp = existing["metadata"]["creationTimestamp"].as_str().unwrap();
-        let current_version: i64 = existing["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse()?;
         
         // Extract spec fields
         let ingress_class_name = ingress["spec"].get("ingressClassName")
@@ -197,7 +193,8 @@ impl IngressStore {
             WHERE namespace = ?8 AND name = ?9 AND deletion_timestamp IS NULL
         "#;
         
-        let new_version = current_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_version = resource_version::next(&tx.db).await?;
         
         sqlx::query(update_query)
             .bind(ingress_class_name.clone())
@@ -209,7 +206,7 @@ impl IngressStore {
             .bind(new_version)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
         // Build response
@@ -238,6 +235,39 @@ impl IngressStore {
             ingress["metadata"]["annotations"] = annotations;
         }
 
+        watch_store::record(&tx.db, "ingresses", "MODIFIED", &ingress).await?;
+        tx.db.commit_write().await?;
+        Ok(ingress)
+    }
+
+    /// Replaces the load balancer status, leaving the spec untouched.
+    pub async fn update_status(&self, namespace: &str, name: &str, status: Value) -> Result<Value> {
+        let load_balancer = status.get("loadBalancer").cloned().unwrap_or_else(|| json!({}));
+
+        let update_query = r#"
+            UPDATE ingresses 
+            SET load_balancer = ?1, resource_version = ?2
+            WHERE namespace = ?3 AND name = ?4 AND deletion_timestamp IS NULL
+        "#;
+
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let rows_affected = sqlx::query(update_query)
+            .bind(load_balancer.to_string())
+            .bind(version)
+            .bind(namespace)
+            .bind(name)
+            .execute(&tx.db)
+            .await?
+            .rows_affected();
+
+        if rows_affected == 0 {
+            return Err(anyhow!("Ingress {}/{} not found", namespace, name));
+        }
+
+        let ingress = tx.get(namespace, name).await?;
+        watch_store::record(&tx.db, "ingresses", "MODIFIED", &ingress).await?;
+        tx.db.commit_write().await?;
         Ok(ingress)
     }
 
@@ -246,16 +276,19 @@ impl IngressStore {
         let ingress = self.get(namespace, name).await?;
 
         // Soft delete
-        let deletion_timestamp = Utc::now().to_rfc3339();
+        let deletion_timestamp = time::now();
         let delete_query = "UPDATE ingresses SET deletion_timestamp = ?1 WHERE namespace = ?2 AND name = ?3";
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(delete_query)
             .bind(&deletion_timestamp)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        watch_store::record(&tx.db, "ingresses", "DELETED", &ingress).await?;
+        tx.db.commit_write().await?;
         Ok(ingress)
     }
 
@@ -1,16 +1,29 @@
 use anyhow::{anyhow, Result};
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::compression;
+use super::db::Db;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::time;
+
+// Field labels jobs can be selected on
+const FIELDS: &[(&str, &str)] = &[
+    ("metadata.name", "name"),
+    ("metadata.namespace", "namespace"),
+    ("status.successful", "succeeded"),
+];
+
 pub struct JobStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl JobStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut job: Value) -> Result<Value> {
@@ -20,7 +33,7 @@ impl JobStore {
             .ok_or_else(|| anyhow!("Job name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         // Extract spec fields
         let parallelism = job["spec"].get("parallelism")
@@ -81,9 +94,11 @@ impl JobStore {
                 backoff_limit, selector, manual_selector, template, ttl_seconds_after_finished,
                 completion_mode, suspend, labels, annotations, resource_version, creation_timestamp
             )
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, 1, ?16)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)
         "#;
         
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(query)
             .bind(&uid)
             .bind(namespace)
@@ -94,14 +109,15 @@ impl JobStore {
             .bind(backoff_limit)
             .bind(final_selector.to_string())
             .bind(manual_selector)
-            .bind(template.to_string())
+            .bind(compression::encode(&template))
             .bind(ttl_seconds_after_finished)
             .bind(&completion_mode)
             .bind(suspend)
             .bind(labels.to_string())
-            .bind(annotations.to_string())
+            .bind(compression::encode(&annotations))
+            .bind(version)
             .bind(&now)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
         // Build response
@@ -109,7 +125,7 @@ impl JobStore {
         job["kind"] = json!("Job");
         job["metadata"]["uid"] = json!(uid);
         job["metadata"]["namespace"] = json!(namespace);
-        job["metadata"]["resourceVersion"] = json!("1");
+        job["metadata"]["resourceVersion"] = json!(version.to_string());
         job["metadata"]["generation"] = json!(1);
         job["metadata"]["creationTimestamp"] = json!(now);
         job["metadata"]["selfLink"] = json!(format!("/apis/batch/v1/namespaces/{}/jobs/{}", namespace, name));
@@ -137,6 +153,8 @@ impl JobStore {
             job["metadata"]["annotations"] = annotations;
         }
 
+        watch_store::record(&tx.db, "jobs", "ADDED", &job).await?;
+        tx.db.commit_write().await?;
         Ok(job)
     }
 
@@ -154,7 +172,7 @@ impl JobStore {
         let row = sqlx::query(query)
             .bind(namespace)
             .bind(name)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&self.db)
             .await?;
 
         match row {
@@ -167,18 +185,12 @@ impl JobStore {
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if namespace.is_some() {
-            r#"
-                SELECT uid, namespace, name, parallelism, completions, active_deadline_seconds, backoff_limit,
-                       selector, manual_selector, template, ttl_seconds_after_finished,
-                       completion_mode, suspend, conditions, start_time, completion_time,
-                       active, succeeded, failed, completed_indexes, uncounted_terminated_pods, ready,
-                       labels, annotations, resource_version, generation, creation_timestamp 
-                FROM jobs 
-                WHERE namespace = ?1 AND deletion_timestamp IS NULL 
-                ORDER BY name
-            "#
-        } else {
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
             r#"
                 SELECT uid, namespace, name, parallelism, completions, active_deadline_seconds, backoff_limit,
                        selector, manual_selector, template, ttl_seconds_after_finished,
@@ -186,21 +198,13 @@ impl JobStore {
                        active, succeeded, failed, completed_indexes, uncounted_terminated_pods, ready,
                        labels, annotations, resource_version, generation, creation_timestamp 
                 FROM jobs 
-                WHERE deletion_timestamp IS NULL 
+                WHERE deletion_timestamp IS NULL{} 
                 ORDER BY namespace, name
-            "#
-        };
+            "#,
+            selector.sql(FIELDS)?
+        );
 
-        let rows = if let Some(ns) = namespace {
-            sqlx::query(query)
-                .bind(ns)
-                .fetch_all(&self.pool)
-                .await?
-        } else {
-            sqlx::query(query)
-                .fetch_all(&self.pool)
-                .await?
-        };
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -231,11 +235,14 @@ impl JobStore {
             SET conditions = ?1, start_time = ?2, completion_time = ?3,
                 active = ?4, succeeded = ?5, failed = ?6,
                 completed_indexes = ?7, uncounted_terminated_pods = ?8, ready = ?9,
-                resource_version = resource_version + 1
-            WHERE namespace = ?10 AND name = ?11 AND deletion_timestamp IS NULL
+                resource_version = ?10
+            WHERE namespace = ?11 AND name = ?12 AND deletion_timestamp IS NULL
         "#;
 
-        sqlx::query(update_query)
+        let before = self.get(namespace, name).await.ok();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let updated = sqlx::query(update_query)
             .bind(status.get("conditions").map(|v| v.to_string()))
             .bind(status.get("startTime").and_then(|v| v.as_str()))
             .bind(status.get("completionTime").and_then(|v| v.as_str()))
@@ -245,11 +252,23 @@ impl JobStore {
             .bind(status.get("completedIndexes").map(|v| v.to_string()))
             .bind(status.get("uncountedTerminatedPods").map(|v| v.to_string()))
             .bind(status.get("ready").and_then(|v| v.as_i64()).unwrap_or(0))
+            .bind(version)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        // The job controller writes the status on every sync; watchers only
+        // hear about the ones that change it
+        if updated.rows_affected() > 0 {
+            let job = tx.get(namespace, name).await?;
+            let unchanged = before.is_some_and(|before| before["status"] == job["status"]);
+            if !unchanged {
+                watch_store::record(&tx.db, "jobs", "MODIFIED", &job).await?;
+            }
+        }
+
+        tx.db.commit_write().await?;
         Ok(())
     }
 
@@ -258,16 +277,19 @@ impl JobStore {
         let job = self.get(namespace, name).await?;
 
         // Soft delete
-        let deletion_timestamp = Utc::now().to_rfc3339();
+        let deletion_timestamp = time::now();
         let delete_query = "UPDATE jobs SET deletion_timestamp = ?1 WHERE namespace = ?2 AND name = ?3";
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(delete_query)
             .bind(&deletion_timestamp)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        watch_store::record(&tx.db, "jobs", "DELETED", &job).await?;
+        tx.db.commit_write().await?;
         Ok(job)
     }
 
@@ -279,7 +301,6 @@ impl JobStore {
         let backoff_limit: i64 = row.get("backoff_limit");
         let selector_str: String = row.get("selector");
         let manual_selector: bool = row.get("manual_selector");
-        let template_str: String = row.get("template");
         let ttl_seconds_after_finished: Option<i64> = row.get("ttl_seconds_after_finished");
         let completion_mode: String = row.get("completion_mode");
         let suspend: bool = row.get("suspend");
@@ -295,17 +316,16 @@ impl JobStore {
         let ready: i64 = row.get("ready");
         
         let labels_str: String = row.get("labels");
-        let annotations_str: String = row.get("annotations");
         let resource_version: i64 = row.get("resource_version");
         let generation: i64 = row.get("generation");
         let creation_timestamp: String = row.get("creation_timestamp");
 
         let selector: Value = serde_json::from_str(&selector_str)?;
-        let template: Value = serde_json::from_str(&template_str)?;
+        let template = compression::decode(&row, "template")?;
         let conditions: Option<Value> = conditions_str.map(|s| serde_json::from_str(&s)).transpose()?;
         let uncounted_terminated_pods: Option<Value> = uncounted_terminated_pods_str.map(|s| serde_json::from_str(&s)).transpose()?;
         let labels: Value = serde_json::from_str(&labels_str)?;
-        let annotations: Value = serde_json::from_str(&annotations_str)?;
+        let annotations = compression::decode(&row, "annotations")?;
 
         let mut job = json!({
             "apiVersion": "batch/v1",
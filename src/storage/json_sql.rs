@@ -0,0 +1,32 @@
+// SQL fragments for updating JSON columns in place with SQLite's JSON1
+// functions. A write that only touches part of an object (its status, a
+// label) then happens inside a single UPDATE instead of a read-modify-write
+// in Rust that can silently undo a concurrent write to another part.
+
+/// `json_set(column, ?, json(?), ...)` for `fields` path/value pairs. Bind
+/// each pair as `path(key)` followed by the value serialized as JSON.
+pub(crate) fn set(column: &str, fields: usize) -> String {
+    let mut expr = format!("json_set({}", object(column));
+    for _ in 0..fields {
+        expr.push_str(", ?, json(?)");
+    }
+    expr.push(')');
+    expr
+}
+
+/// `json_patch(column, ?)`: applies a JSON merge patch (RFC 7396) bound as
+/// text. Nested objects are merged, and a null removes the key.
+pub(crate) fn merge(column: &str) -> String {
+    format!("json_patch({}, ?)", object(column))
+}
+
+/// JSON path of a top-level key, quoted so keys such as
+/// `app.kubernetes.io/name` are taken literally.
+pub(crate) fn path(key: &str) -> String {
+    format!("$.\"{}\"", key)
+}
+
+// Columns holding "null" (an absent map serialized as-is) act as {}
+fn object(column: &str) -> String {
+    format!("COALESCE(NULLIF({}, 'null'), '{{}}')", column)
+}
@@ -0,0 +1,208 @@
+// Label selectors, as in `?labelSelector=app=web,tier in (frontend,cache),!canary`.
+// They match against the labels JSON column every table has, so unlike field
+// selectors they need nothing from the store beyond its table.
+use anyhow::{anyhow, Result};
+use serde_json::Value;
+use std::fmt;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LabelSelector {
+    requirements: Vec<Requirement>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Requirement {
+    key: String,
+    operator: Operator,
+    values: Vec<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Operator {
+    Equals,
+    NotEquals,
+    In,
+    NotIn,
+    Exists,
+    DoesNotExist,
+}
+
+impl LabelSelector {
+    /// Parses comma-separated requirements: `key=value`, `key==value`,
+    /// `key!=value`, `key in (a,b)`, `key notin (a,b)`, `key` and `!key`.
+    /// An empty selector selects everything.
+    pub fn parse(selector: &str) -> Result<Self> {
+        let mut requirements = Vec::new();
+        for term in split_terms(selector)? {
+            requirements.push(Requirement::parse(term)?);
+        }
+        Ok(Self { requirements })
+    }
+
+    /// A metav1.LabelSelector as objects carry it, with matchLabels and
+    /// matchExpressions. A missing one selects everything.
+    pub fn from_value(selector: &Value) -> Result<Self> {
+        let mut requirements = Vec::new();
+        for (key, value) in selector["matchLabels"].as_object().into_iter().flatten() {
+            let value = value.as_str().ok_or_else(|| anyhow!("matchLabels value of {} is not a string", key))?;
+            requirements.push(Requirement { key: key.clone(), operator: Operator::Equals, values: vec![value.to_string()] });
+        }
+        for expression in selector["matchExpressions"].as_array().into_iter().flatten() {
+            let key = expression["key"].as_str().ok_or_else(|| anyhow!("matchExpressions entry without a key"))?;
+            let operator = match expression["operator"].as_str() {
+                Some("In") => Operator::In,
+                Some("NotIn") => Operator::NotIn,
+                Some("Exists") => Operator::Exists,
+                Some("DoesNotExist") => Operator::DoesNotExist,
+                other => return Err(anyhow!("invalid matchExpressions operator {:?} for {}", other, key)),
+            };
+            let values = expression["values"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(|value| value.as_str().map(str::to_string))
+                .collect();
+            requirements.push(Requirement { key: key.to_string(), operator, values });
+        }
+        Ok(Self { requirements })
+    }
+
+    /// `AND` conditions on the labels column to append to a WHERE clause.
+    /// Bind the values with `values`, in order.
+    pub(crate) fn sql(&self) -> String {
+        let mut sql = String::new();
+        for requirement in &self.requirements {
+            let has = match requirement.operator {
+                Operator::Equals | Operator::NotEquals => "key = ? AND value = ?".to_string(),
+                Operator::In | Operator::NotIn => {
+                    let placeholders = vec!["?"; requirement.values.len()].join(", ");
+                    format!("key = ? AND value IN ({})", placeholders)
+                }
+                Operator::Exists | Operator::DoesNotExist => "key = ?".to_string(),
+            };
+            let negated = matches!(requirement.operator, Operator::NotEquals | Operator::NotIn | Operator::DoesNotExist);
+            sql.push_str(&format!(
+                " AND {}EXISTS (SELECT 1 FROM json_each(labels) WHERE {})",
+                if negated { "NOT " } else { "" },
+                has
+            ));
+        }
+        sql
+    }
+
+    /// The values to bind for the conditions of `sql`.
+    pub(crate) fn values(&self) -> impl Iterator<Item = &str> {
+        self.requirements
+            .iter()
+            .flat_map(|requirement| std::iter::once(requirement.key.as_str()).chain(requirement.values.iter().map(String::as_str)))
+    }
+
+    /// Whether an object with `labels` matches.
+    pub fn matches(&self, labels: &Value) -> bool {
+        self.requirements.iter().all(|requirement| {
+            let value = labels.get(&requirement.key).and_then(Value::as_str);
+            let listed = value.is_some_and(|value| requirement.values.iter().any(|v| v == value));
+            match requirement.operator {
+                Operator::Equals | Operator::In => listed,
+                Operator::NotEquals | Operator::NotIn => !listed,
+                Operator::Exists => value.is_some(),
+                Operator::DoesNotExist => value.is_none(),
+            }
+        })
+    }
+}
+
+/// Written as kube-apiserver writes selectors, in `kubectl get` columns
+/// among other places: requirements sorted by key, values sorted.
+impl fmt::Display for LabelSelector {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut requirements: Vec<&Requirement> = self.requirements.iter().collect();
+        requirements.sort_by(|a, b| a.key.cmp(&b.key));
+        let terms: Vec<String> = requirements
+            .into_iter()
+            .map(|requirement| {
+                let mut values = requirement.values.clone();
+                values.sort();
+                let key = &requirement.key;
+                match requirement.operator {
+                    Operator::Equals => format!("{}={}", key, values.join(",")),
+                    Operator::NotEquals => format!("{}!={}", key, values.join(",")),
+                    Operator::In => format!("{} in ({})", key, values.join(",")),
+                    Operator::NotIn => format!("{} notin ({})", key, values.join(",")),
+                    Operator::Exists => key.clone(),
+                    Operator::DoesNotExist => format!("!{}", key),
+                }
+            })
+            .collect();
+        f.write_str(&terms.join(","))
+    }
+}
+
+impl Requirement {
+    fn parse(term: &str) -> Result<Self> {
+        let invalid = |reason: &str| anyhow!("invalid label selector {:?}: {}", term, reason);
+
+        let (key, operator, values) = if let Some(key) = term.strip_prefix('!') {
+            (key, Operator::DoesNotExist, Vec::new())
+        } else if let Some(open) = term.find('(') {
+            let list = term[open + 1..].strip_suffix(')').ok_or_else(|| invalid("unclosed value list"))?;
+            let mut words = term[..open].split_whitespace();
+            let (key, operator) = match (words.next(), words.next(), words.next()) {
+                (Some(key), Some("in"), None) => (key, Operator::In),
+                (Some(key), Some("notin"), None) => (key, Operator::NotIn),
+                _ => return Err(invalid("expected key in (...) or key notin (...)")),
+            };
+            let values: Vec<&str> = list.split(',').map(str::trim).collect();
+            if values.iter().any(|value| value.is_empty()) {
+                return Err(invalid("empty value in list"));
+            }
+            (key, operator, values)
+        } else if let Some((key, value)) = term.split_once("!=") {
+            (key, Operator::NotEquals, vec![value.trim()])
+        } else if let Some((key, value)) = term.split_once("==") {
+            (key, Operator::Equals, vec![value.trim()])
+        } else if let Some((key, value)) = term.split_once('=') {
+            (key, Operator::Equals, vec![value.trim()])
+        } else {
+            (term, Operator::Exists, Vec::new())
+        };
+
+        let key = key.trim();
+        if key.is_empty() || !key.chars().all(is_label_char) {
+            return Err(invalid("bad key"));
+        }
+        if !values.iter().all(|value| value.chars().all(is_label_char)) {
+            return Err(invalid("bad value"));
+        }
+        Ok(Self {
+            key: key.to_string(),
+            operator,
+            values: values.into_iter().map(str::to_string).collect(),
+        })
+    }
+}
+
+fn is_label_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | '/')
+}
+
+/// Splits on the commas between requirements, not those inside `(...)`.
+fn split_terms(selector: &str) -> Result<Vec<&str>> {
+    let mut terms = Vec::new();
+    let mut depth = 0;
+    let mut start = 0;
+    for (i, c) in selector.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => return Err(anyhow!("invalid label selector {:?}: unbalanced ')'", selector)),
+            ')' => depth -= 1,
+            ',' if depth == 0 => {
+                terms.push(&selector[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    terms.push(&selector[start..]);
+    Ok(terms.into_iter().map(str::trim).filter(|term| !term.is_empty()).collect())
+}
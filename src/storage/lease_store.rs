@@ -0,0 +1,222 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use sqlx::sqlite::SqliteRow;
+use sqlx::Row;
+use uuid::Uuid;
+
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::resource_version;
+use super::watch_store;
+use crate::models::time;
+
+const COLUMNS: &str = "uid, name, namespace, resource_version, creation_timestamp, labels, annotations, owner_references, spec";
+
+/// Keeps Leases: the kubelets' heartbeats in kube-node-lease, and whatever
+/// else clients hold them for, such as leader election.
+pub struct LeaseStore {
+    db: Db,
+}
+
+impl LeaseStore {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    pub async fn create(&self, namespace: &str, mut lease: Value) -> Result<Value> {
+        let uid = Uuid::new_v4().to_string();
+        let name = lease["metadata"]["name"]
+            .as_str()
+            .ok_or_else(|| anyhow!("Lease name is required"))?
+            .to_string();
+        let now = time::now();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+
+        // A deleted lease keeps its row, which would block reusing the name
+        sqlx::query("DELETE FROM leases WHERE namespace = ?1 AND name = ?2 AND deletion_timestamp IS NOT NULL")
+            .bind(namespace)
+            .bind(&name)
+            .execute(&tx.db)
+            .await?;
+
+        sqlx::query(
+            "INSERT INTO leases (uid, name, namespace, resource_version, creation_timestamp, labels, annotations, owner_references, spec)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&uid)
+        .bind(&name)
+        .bind(namespace)
+        .bind(version)
+        .bind(&now)
+        .bind(lease["metadata"]["labels"].to_string())
+        .bind(lease["metadata"]["annotations"].to_string())
+        .bind(lease["metadata"]["ownerReferences"].to_string())
+        .bind(spec(&lease).to_string())
+        .execute(&tx.db)
+        .await?;
+
+        lease["apiVersion"] = json!("coordination.k8s.io/v1");
+        lease["kind"] = json!("Lease");
+        lease["metadata"]["uid"] = json!(uid);
+        lease["metadata"]["namespace"] = json!(namespace);
+        lease["metadata"]["resourceVersion"] = json!(version.to_string());
+        lease["metadata"]["creationTimestamp"] = json!(now);
+        lease["spec"] = spec(&lease);
+
+        watch_store::record(&tx.db, "leases", "ADDED", &lease).await?;
+        tx.db.commit_write().await?;
+        Ok(lease)
+    }
+
+    pub async fn get(&self, namespace: &str, name: &str) -> Result<Value> {
+        let query = format!("SELECT {} FROM leases WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL", COLUMNS);
+        let row = sqlx::query(&query)
+            .bind(namespace)
+            .bind(name)
+            .fetch_optional(&self.db)
+            .await?;
+
+        match row {
+            Some(row) => from_row(&row),
+            None => Err(anyhow!("Lease not found")),
+        }
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
+            "SELECT {} FROM leases WHERE deletion_timestamp IS NULL{} ORDER BY namespace, name",
+            COLUMNS,
+            selector.sql(field_selector::NAMESPACED)?
+        );
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
+        let items = rows.iter().map(from_row).collect::<Result<Vec<_>>>()?;
+
+        Ok(json!({
+            "apiVersion": "coordination.k8s.io/v1",
+            "kind": "LeaseList",
+            "metadata": {
+                "resourceVersion": "1"
+            },
+            "items": items
+        }))
+    }
+
+    /// Replaces the lease's spec, labels, annotations and ownerReferences
+    /// with `lease`'s.
+    pub async fn update(&self, namespace: &str, name: &str, mut lease: Value) -> Result<Value> {
+        let current = self.get(namespace, name).await?;
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        sqlx::query(
+            "UPDATE leases SET labels = ?, annotations = ?, owner_references = ?, spec = ?, resource_version = ?
+             WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL"
+        )
+        .bind(lease["metadata"]["labels"].to_string())
+        .bind(lease["metadata"]["annotations"].to_string())
+        .bind(lease["metadata"]["ownerReferences"].to_string())
+        .bind(spec(&lease).to_string())
+        .bind(version)
+        .bind(namespace)
+        .bind(name)
+        .execute(&tx.db)
+        .await?;
+
+        lease["apiVersion"] = json!("coordination.k8s.io/v1");
+        lease["kind"] = json!("Lease");
+        for field in ["uid", "name", "namespace", "creationTimestamp", "selfLink"] {
+            lease["metadata"][field] = current["metadata"][field].clone();
+        }
+        lease["metadata"]["resourceVersion"] = json!(version.to_string());
+        lease["spec"] = spec(&lease);
+
+        watch_store::record(&tx.db, "leases", "MODIFIED", &lease).await?;
+        tx.db.commit_write().await?;
+        Ok(lease)
+    }
+
+    /// Renews `holder`'s lease with `name`, taking it if it's new or
+    /// someone else held it, as a kubelet does its node's. New leases get
+    /// `owner` as their owner.
+    pub async fn renew(&self, namespace: &str, name: &str, holder: &str, duration_seconds: i64, owner: Value) -> Result<Value> {
+        let now = time::now_micro();
+        let mut lease = match self.get(namespace, name).await {
+            Ok(lease) => lease,
+            Err(e) if e.to_string().contains("not found") => {
+                let lease = json!({
+                    "metadata": { "name": name, "ownerReferences": [owner] },
+                    "spec": {
+                        "holderIdentity": holder,
+                        "leaseDurationSeconds": duration_seconds,
+                        "acquireTime": now,
+                        "renewTime": now,
+                        "leaseTransitions": 0
+                    }
+                });
+                return self.create(namespace, lease).await;
+            }
+            Err(e) => return Err(e),
+        };
+
+        let spec = &mut lease["spec"];
+        if spec["holderIdentity"] != holder {
+            spec["holderIdentity"] = json!(holder);
+            spec["acquireTime"] = json!(now);
+            spec["leaseTransitions"] = json!(spec["leaseTransitions"].as_i64().unwrap_or(0) + 1);
+        }
+        spec["leaseDurationSeconds"] = json!(duration_seconds);
+        spec["renewTime"] = json!(now);
+        self.update(namespace, name, lease).await
+    }
+
+    pub async fn delete(&self, namespace: &str, name: &str) -> Result<Value> {
+        let lease = self.get(namespace, name).await?;
+
+        let tx = Self::new(self.db.begin_write().await?);
+        sqlx::query("UPDATE leases SET deletion_timestamp = ? WHERE uid = ?")
+            .bind(time::now())
+            .bind(lease["metadata"]["uid"].as_str())
+            .execute(&tx.db)
+            .await?;
+
+        watch_store::record(&tx.db, "leases", "DELETED", &lease).await?;
+        tx.db.commit_write().await?;
+        Ok(lease)
+    }
+}
+
+// The lease's spec, an empty one if it has none
+fn spec(lease: &Value) -> Value {
+    match &lease["spec"] {
+        Value::Null => json!({}),
+        spec => spec.clone(),
+    }
+}
+
+fn from_row(row: &SqliteRow) -> Result<Value> {
+    let namespace: String = row.get("namespace");
+    let name: String = row.get("name");
+    let mut lease = json!({
+        "apiVersion": "coordination.k8s.io/v1",
+        "kind": "Lease",
+        "metadata": {
+            "uid": row.get::<String, _>("uid"),
+            "name": name,
+            "namespace": namespace,
+            "resourceVersion": row.get::<i64, _>("resource_version").to_string(),
+            "creationTimestamp": row.get::<String, _>("creation_timestamp"),
+            "selfLink": format!("/apis/coordination.k8s.io/v1/namespaces/{}/leases/{}", namespace, name)
+        },
+        "spec": serde_json::from_str::<Value>(&row.get::<String, _>("spec"))?
+    });
+
+    for (field, column) in [("labels", "labels"), ("annotations", "annotations"), ("ownerReferences", "owner_references")] {
+        let value = row.get::<Option<String>, _>(column).and_then(|value| serde_json::from_str::<Value>(&value).ok());
+        if let Some(value) = value.filter(|value| !value.is_null()) {
+            lease["metadata"][field] = value;
+        }
+    }
+    Ok(lease)
+}
@@ -1,15 +1,22 @@
 use anyhow::Result;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::time;
+
 pub struct LimitRangeStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl LimitRangeStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut limitrange: Value) -> Result<Value> {
@@ -20,9 +27,13 @@ impl LimitRangeStore {
         let labels = limitrange["metadata"].get("labels").cloned().unwrap_or(json!({}));
         let annotations = limitrange["metadata"].get("annotations").cloned().unwrap_or(json!({}));
 
+        let now = time::now();
+
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(
-            "INSERT INTO limitranges (uid, name, namespace, spec, limits, labels, annotations, resource_version, generation)
-             VALUES (?, ?, ?, ?, ?, ?, ?, 1, 1)"
+            "INSERT INTO limitranges (uid, name, namespace, spec, limits, labels, annotations, resource_version, generation, creation_timestamp)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, 1, ?)"
         )
         .bind(&uid)
         .bind(&name)
@@ -31,15 +42,17 @@ impl LimitRangeStore {
         .bind(serde_json::to_string(&limits)?)
         .bind(serde_json::to_string(&labels)?)
         .bind(serde_json::to_string(&annotations)?)
-        .execute(&self.pool)
+        .bind(version)
+        .bind(&now)
+        .execute(&tx.db)
         .await?;
 
         limitrange["metadata"]["uid"] = json!(uid);
-        limitrange["metadata"]["resourceVersion"] = json!("1");
+        limitrange["metadata"]["resourceVersion"] = json!(version.to_string());
         limitrange["metadata"]["generation"] = json!(1);
-        limitrange["metadata"]["creationTimestamp"] = json!(chrono::Utc::now().to_rfc3339());
+        limitrange["metadata"]["creationTimestamp"] = json!(now);
 
-        self.record_event(
+        tx.record_event(
             &uid,
             "LimitRange",
             namespace,
@@ -48,6 +61,8 @@ impl LimitRangeStore {
             "LimitRange created"
         ).await?;
 
+        watch_store::record(&tx.db, "limitranges", "ADDED", &limitrange).await?;
+        tx.db.commit_write().await?;
         Ok(limitrange)
     }
 
@@ -60,7 +75,7 @@ impl LimitRangeStore {
         )
         .bind(namespace)
         .bind(name)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
 
         if let Some(row) = row {
@@ -84,10 +99,10 @@ impl LimitRangeStore {
                     "resourceVersion": resource_version.to_string(),
                     "generation": generation,
                     "creationTimestamp": creation_timestamp,
-                    "labels": serde_json::from_str(&labels)?,
-                    "annotations": serde_json::from_str(&annotations)?
+                    "labels": serde_json::from_str::<Value>(&labels)?,
+                    "annotations": serde_json::from_str::<Value>(&annotations)?
                 },
-                "spec": serde_json::from_str(&spec)?
+                "spec": serde_json::from_str::<Value>(&spec)?
             });
 
             Ok(Some(limitrange))
@@ -101,8 +116,8 @@ impl LimitRangeStore {
             .ok_or_else(|| anyhow::anyhow!("LimitRange not found"))?;
 
         let uid = current["metadata"]["uid"].as_str().unwrap();
-        let resource_version: i64 = current["metadata"]["resourceVersion"].as_str().unwrap().parse()?;
-        let new_resource_version = resource_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_resource_version = resource_version::next(&tx.db).await?;
         let generation: i64 = current["metadata"]["generation"].as_i64().unwrap();
         let new_generation = if limitrange["spec"] != current["spec"] { generation + 1 } else { generation };
 
@@ -125,7 +140,7 @@ impl LimitRangeStore {
         .bind(new_generation)
         .bind(namespace)
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
         limitrange["metadata"]["uid"] = json!(uid);
@@ -133,7 +148,7 @@ impl LimitRangeStore {
         limitrange["metadata"]["generation"] = json!(new_generation);
         limitrange["metadata"]["creationTimestamp"] = current["metadata"]["creationTimestamp"].clone();
 
-        self.record_event(
+        tx.record_event(
             uid,
             "LimitRange",
             namespace,
@@ -142,6 +157,8 @@ impl LimitRangeStore {
             "LimitRange updated"
         ).await?;
 
+        watch_store::record(&tx.db, "limitranges", "MODIFIED", &limitrange).await?;
+        tx.db.commit_write().await?;
         Ok(limitrange)
     }
 
@@ -151,17 +168,19 @@ impl LimitRangeStore {
 
         let uid = limitrange["metadata"]["uid"].as_str().unwrap();
 
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
             "UPDATE limitranges 
-             SET deletion_timestamp = CURRENT_TIMESTAMP 
+             SET deletion_timestamp = ? 
              WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL"
         )
+        .bind(time::now())
         .bind(namespace)
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
-        self.record_event(
+        tx.record_event(
             uid,
             "LimitRange",
             namespace,
@@ -170,28 +189,26 @@ impl LimitRangeStore {
             "LimitRange deleted"
         ).await?;
 
+        watch_store::record(&tx.db, "limitranges", "DELETED", &limitrange).await?;
+        tx.db.commit_write().await?;
         Ok(limitrange)
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if let Some(ns) = namespace {
-            sqlx::query(
-                "SELECT uid, name, namespace, spec, limits, labels, annotations,
-                 resource_version, generation, creation_timestamp
-                 FROM limitranges
-                 WHERE namespace = ? AND deletion_timestamp IS NULL"
-            )
-            .bind(ns)
-        } else {
-            sqlx::query(
-                "SELECT uid, name, namespace, spec, limits, labels, annotations,
-                 resource_version, generation, creation_timestamp
-                 FROM limitranges
-                 WHERE deletion_timestamp IS NULL"
-            )
-        };
-
-        let rows = query.fetch_all(&self.pool).await?;
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
+            "SELECT uid, name, namespace, spec, limits, labels, annotations,
+             resource_version, generation, creation_timestamp
+             FROM limitranges
+             WHERE deletion_timestamp IS NULL{}",
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
         let mut items = Vec::new();
 
         for row in rows {
@@ -215,10 +232,10 @@ impl LimitRangeStore {
                     "resourceVersion": resource_version.to_string(),
                     "generation": generation,
                     "creationTimestamp": creation_timestamp,
-                    "labels": serde_json::from_str(&labels)?,
-                    "annotations": serde_json::from_str(&annotations)?
+                    "labels": serde_json::from_str::<Value>(&labels)?,
+                    "annotations": serde_json::from_str::<Value>(&annotations)?
                 },
-                "spec": serde_json::from_str(&spec)?
+                "spec": serde_json::from_str::<Value>(&spec)?
             });
 
             items.push(limitrange);
@@ -237,7 +254,7 @@ impl LimitRangeStore {
              WHERE namespace = ? AND deletion_timestamp IS NULL"
         )
         .bind(namespace)
-        .fetch_all(&self.pool)
+        .fetch_all(&self.db)
         .await?;
 
         let mut limitranges = Vec::new();
@@ -251,9 +268,10 @@ impl LimitRangeStore {
 
     async fn record_event(&self, uid: &str, resource_type: &str, namespace: &str, name: &str, reason: &str, message: &str) -> Result<()> {
         let event_uid = Uuid::new_v4().to_string();
+        let now = time::now();
         sqlx::query(
             "INSERT INTO events (uid, namespace, involved_object_uid, involved_object_kind, involved_object_name, reason, message, event_time, first_timestamp, last_timestamp, count, type)
-             VALUES (?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, 1, 'Normal')"
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 'Normal')"
         )
         .bind(&event_uid)
         .bind(namespace)
@@ -262,7 +280,10 @@ impl LimitRangeStore {
         .bind(name)
         .bind(reason)
         .bind(message)
-        .execute(&self.pool)
+        .bind(&now)
+        .bind(&now)
+        .bind(&now)
+        .execute(&self.db)
         .await?;
         Ok(())
     }
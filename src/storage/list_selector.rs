@@ -0,0 +1,62 @@
+// What a list request selects on: its field selector, its label selector and,
+// for a namespaced list, the namespace. Stores take one of these in their
+// `list_matching` and splice its conditions into the list query.
+use anyhow::Result;
+use sqlx::query::{Query, QueryAs};
+use sqlx::sqlite::SqliteArguments;
+use sqlx::Sqlite;
+use serde_json::Value;
+
+use super::field_selector::FieldSelector;
+use super::label_selector::LabelSelector;
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ListSelector {
+    pub fields: FieldSelector,
+    pub labels: LabelSelector,
+}
+
+impl ListSelector {
+    /// Parses the `fieldSelector` and `labelSelector` of a list request.
+    pub fn parse(fields: Option<&str>, labels: Option<&str>) -> Result<Self> {
+        Ok(Self {
+            fields: FieldSelector::parse(fields.unwrap_or_default())?,
+            labels: LabelSelector::parse(labels.unwrap_or_default())?,
+        })
+    }
+
+    /// This selector, further limited to `namespace` when there is one.
+    pub(crate) fn in_namespace(&self, namespace: Option<&str>) -> Self {
+        Self {
+            fields: self.fields.in_namespace(namespace),
+            labels: self.labels.clone(),
+        }
+    }
+
+    /// `AND` conditions to append to a WHERE clause, with `fields` mapping
+    /// field labels to SQL expressions. Bind the values with `bind`.
+    pub(crate) fn sql(&self, fields: &[(&str, &str)]) -> Result<String> {
+        Ok(format!("{}{}", self.fields.sql(fields)?, self.labels.sql()))
+    }
+
+    /// Binds the values compared against by the conditions of `sql`.
+    pub(crate) fn bind<'q>(
+        &'q self,
+        query: Query<'q, Sqlite, SqliteArguments<'q>>,
+    ) -> Query<'q, Sqlite, SqliteArguments<'q>> {
+        self.labels.values().fold(self.fields.bind(query), |query, value| query.bind(value))
+    }
+
+    pub(crate) fn bind_as<'q, O>(
+        &'q self,
+        query: QueryAs<'q, Sqlite, O, SqliteArguments<'q>>,
+    ) -> QueryAs<'q, Sqlite, O, SqliteArguments<'q>> {
+        self.labels.values().fold(self.fields.bind_as(query), |query, value| query.bind(value))
+    }
+
+    /// Whether an object kept in memory rather than in a table matches.
+    /// See `FieldSelector::matches` for `field`.
+    pub fn matches(&self, field: impl Fn(&str) -> Option<String>, labels: &Value) -> Result<bool> {
+        Ok(self.fields.matches(field)? && self.labels.matches(labels))
+    }
+}
@@ -1,66 +1,182 @@
+pub mod compression;
 pub mod configmap_store;
+pub mod controllerrevision_store;
 pub mod cronjob_store;
 pub mod daemonset_store;
+mod db;
 pub mod deployment_store;
 pub mod endpoints_store;
+pub mod event_store;
+mod field_selector;
+pub mod finalizer_store;
+pub mod hooks;
 pub mod hpa_store;
 pub mod ingress_store;
 pub mod job_store;
+pub mod lease_store;
+mod json_sql;
+mod label_selector;
 pub mod limitrange_store;
+mod list_selector;
 pub mod networkpolicy_store;
+pub mod node_store;
+pub mod owner_store;
 pub mod pdb_store;
 pub mod pod_store;
+pub mod protection_store;
 pub mod pv_store;
 pub mod pvc_store;
 pub mod rbac_store;
 pub mod replicaset_store;
+mod resource_version;
 pub mod resourcequota_store;
+pub mod schema;
+pub mod scheduling_failure_store;
 pub mod scheduling_store;
 pub mod secret_store;
 pub mod serviceaccount_store;
+pub mod serviceaccount_token;
 pub mod service_store;
 pub mod statefulset_store;
+pub(crate) mod tables;
 pub mod watch_store;
+mod watch_bus;
 pub mod webhook_store;
+pub mod writer_store;
 
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 use sqlx::{sqlite::SqlitePoolOptions, SqlitePool};
+use std::ops::Deref;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use tokio::sync::Mutex;
 
 use self::configmap_store::ConfigMapStore;
+use self::controllerrevision_store::ControllerRevisionStore;
 use self::cronjob_store::CronJobStore;
 use self::daemonset_store::DaemonSetStore;
+use self::db::SharedTransaction;
 use self::deployment_store::DeploymentStore;
 use self::endpoints_store::EndpointsStore;
+use self::event_store::EventStore;
+use self::finalizer_store::FinalizerStore;
+use self::hooks::{Hooks, Registry};
 use self::hpa_store::HpaStore;
 use self::ingress_store::IngressStore;
 use self::job_store::JobStore;
+use self::lease_store::LeaseStore;
 use self::limitrange_store::LimitRangeStore;
 use self::networkpolicy_store::NetworkPolicyStore;
+use self::node_store::NodeStore;
+use self::owner_store::OwnerStore;
 use self::pdb_store::PdbStore;
 use self::pod_store::PodStore;
+use self::protection_store::ProtectionStore;
 use self::pv_store::PersistentVolumeStore;
 use self::pvc_store::PersistentVolumeClaimStore;
 use self::rbac_store::{RoleStore, RoleBindingStore, ClusterRoleStore, ClusterRoleBindingStore};
 use self::replicaset_store::ReplicaSetStore;
 use self::resourcequota_store::ResourceQuotaStore;
+use self::scheduling_failure_store::SchedulingFailureStore;
 use self::scheduling_store::{PriorityClassStore, StorageClassStore};
 use self::secret_store::SecretStore;
 use self::serviceaccount_store::ServiceAccountStore;
 use self::service_store::ServiceStore;
 use self::statefulset_store::StatefulSetStore;
+use self::watch_bus::WatchBus;
 use self::watch_store::WatchStore;
 use self::webhook_store::{ValidatingWebhookStore, MutatingWebhookStore};
+use self::writer_store::WriterStore;
+
+pub use self::db::Db;
+pub use self::field_selector::FieldSelector;
+pub use self::label_selector::LabelSelector;
+pub use self::list_selector::ListSelector;
 
 #[derive(Clone)]
 pub struct Storage {
     pub pool: Arc<SqlitePool>,
+    db: Db,
+    watches: Arc<AtomicUsize>,
+    requests: Arc<AtomicUsize>,
+    hooks: Arc<Registry>,
 }
 
 impl Storage {
     pub fn pool(&self) -> &SqlitePool {
         &self.pool
     }
+
+    /// Where the stores run their queries. Inside a transaction, raw queries
+    /// that should be part of it go here rather than to `pool`.
+    pub fn db(&self) -> &Db {
+        &self.db
+    }
+
+    /// Allocates the resource version for a write made outside the stores,
+    /// which is made in a `transaction` along with its row and watch event.
+    pub async fn next_resource_version(&self) -> Result<i64> {
+        resource_version::next(&self.db).await
+    }
+
+    /// The stored resource version of an object, if it exists.
+    pub async fn resource_version_of(&self, resource: &str, namespace: Option<&str>, name: &str) -> Result<Option<i64>> {
+        resource_version::of_object(&self.db, resource, namespace, name).await
+    }
+
+    /// Claims an object for a write if it still has `uid` and is at
+    /// `version`, where given, checking and holding it in one statement. In a
+    /// `transaction`, nothing else writes the object until that ends.
+    pub async fn claim_object(
+        &self,
+        resource: &str,
+        namespace: Option<&str>,
+        name: &str,
+        uid: Option<&str>,
+        version: Option<&str>,
+    ) -> Result<bool> {
+        resource_version::claim(&self.db, resource, namespace, name, uid, version).await
+    }
+
+    /// Whether this is a `transaction`'s storage.
+    pub fn in_transaction(&self) -> bool {
+        self.db.in_transaction()
+    }
+
+    fn from_pool(pool: SqlitePool) -> Self {
+        Self {
+            db: Db::pool(pool.clone(), Arc::new(WatchBus::default())),
+            pool: Arc::new(pool),
+            watches: Arc::new(AtomicUsize::new(0)),
+            requests: Arc::new(AtomicUsize::new(0)),
+            hooks: Arc::default(),
+        }
+    }
+
+    /// Starts a transaction. Stores obtained from it write atomically: their
+    /// changes, and the watch events for them, become visible on `commit`,
+    /// and are discarded on `rollback` or if the transaction is dropped
+    /// without committing. Writes through `pool` are not part of it. Started
+    /// from a transaction's storage, it's part of that one, which is left to
+    /// commit or roll back.
+    pub async fn transaction(&self) -> Result<Transaction> {
+        if self.in_transaction() {
+            return Ok(Transaction { storage: self.clone(), tx: None });
+        }
+        let tx = self.pool.begin().await?;
+        let tx: SharedTransaction = Arc::new(Mutex::new(Some(tx)));
+        Ok(Transaction {
+            storage: Self {
+                pool: self.pool.clone(),
+                db: Db::transaction(tx.clone(), self.db.bus().clone()),
+                watches: self.watches.clone(),
+                requests: self.requests.clone(),
+                hooks: self.hooks.clone(),
+            },
+            tx: Some(tx),
+        })
+    }
     
     pub async fn new(database_url: &str) -> Result<Self> {
         let pool = SqlitePoolOptions::new()
@@ -68,129 +184,269 @@ impl Storage {
             .connect(database_url)
             .await?;
         
-        Ok(Self {
-            pool: Arc::new(pool),
-        })
+        Ok(Self::from_pool(pool))
+    }
+
+    /// Opens a private in-memory database, used by the in-process test harness.
+    pub async fn in_memory() -> Result<Self> {
+        // Plain :memory: gives every pooled connection its own empty database, so
+        // use a uniquely named shared-cache database and keep one connection open
+        // for the lifetime of the pool so it isn't dropped while idle.
+        let database_url = format!(
+            "sqlite:file:krust-{}?mode=memory&cache=shared",
+            uuid::Uuid::new_v4()
+        );
+        let pool = SqlitePoolOptions::new()
+            .max_connections(5)
+            .min_connections(1)
+            .idle_timeout(None)
+            .max_lifetime(None)
+            .connect(&database_url)
+            .await?;
+        
+        Ok(Self::from_pool(pool))
     }
 
     pub async fn migrate(&self) -> Result<()> {
-        sqlx::migrate!("./migrations").run(&*self.pool).await?;
-        Ok(())
+        schema::migrate(&self.pool).await
+    }
+
+    /// Which migrations the database has, and which it lacks.
+    pub async fn schema(&self) -> Result<schema::SchemaStatus> {
+        schema::status(&self.pool).await
+    }
+
+    /// Copies the database to a new file at `path` with VACUUM INTO, while
+    /// it stays open for reads and writes, returning the copy's size in
+    /// bytes. The copy is consistent as of one moment and can be opened as a
+    /// data directory's krust.db; an existing file at `path` is an error.
+    pub async fn backup(&self, path: &Path) -> Result<u64> {
+        if path.exists() {
+            bail!("{} already exists", path.display());
+        }
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            std::fs::create_dir_all(parent).with_context(|| format!("failed to create {}", parent.display()))?;
+        }
+        // As a URI with its own mode, or an in-memory database's copy would
+        // be in memory too
+        let mut uri = String::from("file:");
+        for c in path.to_string_lossy().chars() {
+            match c {
+                '%' | '?' | '#' => uri.push_str(&format!("%{:02X}", c as u32)),
+                c => uri.push(c),
+            }
+        }
+        sqlx::query("VACUUM INTO ?")
+            .bind(format!("{}?mode=rwc", uri))
+            .execute(&*self.pool)
+            .await
+            .with_context(|| format!("failed to back up the database to {}", path.display()))?;
+        Ok(std::fs::metadata(path)?.len())
     }
 
     pub fn pods(&self) -> PodStore {
-        PodStore::new((*self.pool).clone())
+        PodStore::new(self.db.clone())
     }
 
     pub fn services(&self) -> ServiceStore {
-        ServiceStore::new((*self.pool).clone())
+        ServiceStore::new(self.db.clone())
     }
 
     pub fn endpoints(&self) -> EndpointsStore {
-        EndpointsStore::new((*self.pool).clone())
+        EndpointsStore::new(self.db.clone())
     }
 
     pub fn deployments(&self) -> DeploymentStore {
-        DeploymentStore::new((*self.pool).clone())
+        DeploymentStore::new(self.db.clone())
     }
     
     pub fn hpas(&self) -> HpaStore {
-        HpaStore::new((*self.pool).clone())
+        HpaStore::new(self.db.clone())
     }
 
     pub fn replicasets(&self) -> ReplicaSetStore {
-        ReplicaSetStore::new((*self.pool).clone())
+        ReplicaSetStore::new(self.db.clone())
+    }
+
+    pub fn controller_revisions(&self) -> ControllerRevisionStore {
+        ControllerRevisionStore::new(self.db.clone())
     }
 
     pub fn configmaps(&self) -> ConfigMapStore {
-        ConfigMapStore::new((*self.pool).clone())
+        ConfigMapStore::new(self.db.clone())
     }
 
     pub fn secrets(&self) -> SecretStore {
-        SecretStore::new((*self.pool).clone())
+        SecretStore::new(self.db.clone())
     }
 
     pub fn persistent_volumes(&self) -> PersistentVolumeStore {
-        PersistentVolumeStore::new((*self.pool).clone())
+        PersistentVolumeStore::new(self.db.clone())
     }
 
     pub fn persistent_volume_claims(&self) -> PersistentVolumeClaimStore {
-        PersistentVolumeClaimStore::new((*self.pool).clone())
+        PersistentVolumeClaimStore::new(self.db.clone())
     }
 
     pub fn statefulsets(&self) -> StatefulSetStore {
-        StatefulSetStore::new((*self.pool).clone())
+        StatefulSetStore::new(self.db.clone())
     }
 
     pub fn daemonsets(&self) -> DaemonSetStore {
-        DaemonSetStore::new((*self.pool).clone())
+        DaemonSetStore::new(self.db.clone())
     }
 
     pub fn jobs(&self) -> JobStore {
-        JobStore::new((*self.pool).clone())
+        JobStore::new(self.db.clone())
     }
 
     pub fn cronjobs(&self) -> CronJobStore {
-        CronJobStore::new((*self.pool).clone())
+        CronJobStore::new(self.db.clone())
     }
 
     pub fn networkpolicies(&self) -> NetworkPolicyStore {
-        NetworkPolicyStore::new((*self.pool).clone())
+        NetworkPolicyStore::new(self.db.clone())
     }
 
     pub fn ingresses(&self) -> IngressStore {
-        IngressStore::new((*self.pool).clone())
+        IngressStore::new(self.db.clone())
     }
 
     pub fn watch(&self) -> WatchStore {
-        WatchStore::new((*self.pool).clone())
+        WatchStore::new((*self.pool).clone(), self.db.clone(), self.watches.clone())
+    }
+
+    /// The count of API requests being served, kept up by the API server:
+    /// a request stops counting once its client has its response or has
+    /// gone away.
+    pub fn requests(&self) -> Arc<AtomicUsize> {
+        self.requests.clone()
+    }
+
+    /// How many API requests are being served.
+    pub fn open_requests(&self) -> usize {
+        self.requests.load(Ordering::SeqCst)
+    }
+
+    /// Rust callbacks on objects, for when krust is embedded.
+    pub fn hooks(&self) -> Hooks {
+        Hooks::new(self.clone())
     }
     
     pub fn roles(&self) -> RoleStore {
-        RoleStore::new((*self.pool).clone())
+        RoleStore::new(self.db.clone())
     }
     
     pub fn rolebindings(&self) -> RoleBindingStore {
-        RoleBindingStore::new((*self.pool).clone())
+        RoleBindingStore::new(self.db.clone())
     }
     
     pub fn clusterroles(&self) -> ClusterRoleStore {
-        ClusterRoleStore::new((*self.pool).clone())
+        ClusterRoleStore::new(self.db.clone())
     }
     
     pub fn clusterrolebindings(&self) -> ClusterRoleBindingStore {
-        ClusterRoleBindingStore::new((*self.pool).clone())
+        ClusterRoleBindingStore::new(self.db.clone())
     }
     
     pub fn resourcequotas(&self) -> ResourceQuotaStore {
-        ResourceQuotaStore::new((*self.pool).clone())
+        ResourceQuotaStore::new(self.db.clone())
     }
     
     pub fn limitranges(&self) -> LimitRangeStore {
-        LimitRangeStore::new((*self.pool).clone())
+        LimitRangeStore::new(self.db.clone())
     }
     
     pub fn serviceaccounts(&self) -> ServiceAccountStore {
-        ServiceAccountStore::new((*self.pool).clone())
+        ServiceAccountStore::new(self.db.clone())
     }
     
     pub fn pdbs(&self) -> PdbStore {
-        PdbStore::new((*self.pool).clone())
+        PdbStore::new(self.db.clone())
     }
     
     pub fn priorityclasses(&self) -> PriorityClassStore {
-        PriorityClassStore::new((*self.pool).clone())
+        PriorityClassStore::new(self.db.clone())
     }
     
     pub fn storageclasses(&self) -> StorageClassStore {
-        StorageClassStore::new((*self.pool).clone())
+        StorageClassStore::new(self.db.clone())
     }
     
     pub fn validating_webhooks(&self) -> ValidatingWebhookStore {
-        ValidatingWebhookStore::new((*self.pool).clone())
+        ValidatingWebhookStore::new(self.db.clone())
     }
     
     pub fn mutating_webhooks(&self) -> MutatingWebhookStore {
-        MutatingWebhookStore::new((*self.pool).clone())
+        MutatingWebhookStore::new(self.db.clone())
+    }
+    
+    pub fn writers(&self) -> WriterStore {
+        WriterStore::new(self.db.clone())
+    }
+
+    pub fn protection(&self) -> ProtectionStore {
+        ProtectionStore::new(self.db.clone())
+    }
+
+    pub fn finalizers(&self) -> FinalizerStore {
+        FinalizerStore::new(self.db.clone())
+    }
+
+    pub fn owners(&self) -> OwnerStore {
+        OwnerStore::new(self.db.clone())
+    }
+
+    pub fn events(&self) -> EventStore {
+        EventStore::new(self.db.clone())
+    }
+
+    pub fn scheduling_failures(&self) -> SchedulingFailureStore {
+        SchedulingFailureStore::new(self.db.clone())
+    }
+
+    pub fn nodes(&self) -> NodeStore {
+        NodeStore::new(self.db.clone())
+    }
+
+    pub fn leases(&self) -> LeaseStore {
+        LeaseStore::new(self.db.clone())
+    }
+}
+
+/// A transaction started with `Storage::transaction`, giving access to the
+/// same stores as `Storage`.
+pub struct Transaction {
+    storage: Storage,
+    // None if it's part of another
+    tx: Option<SharedTransaction>,
+}
+
+impl Transaction {
+    pub async fn commit(self) -> Result<()> {
+        let Some(tx) = self.tx else {
+            return Ok(());
+        };
+        let tx = tx.lock().await.take().ok_or_else(|| anyhow!("transaction already finished"))?;
+        tx.commit().await?;
+        self.storage.db.publish_committed();
+        Ok(())
     }
-}
\ No newline at end of file
+
+    pub async fn rollback(self) -> Result<()> {
+        let Some(tx) = self.tx else {
+            return Ok(());
+        };
+        let tx = tx.lock().await.take().ok_or_else(|| anyhow!("transaction already finished"))?;
+        tx.rollback().await?;
+        Ok(())
+    }
+}
+
+impl Deref for Transaction {
+    type Target = Storage;
+
+    fn deref(&self) -> &Storage {
+        &self.storage
+    }
+}
@@ -1,16 +1,22 @@
 use anyhow::{anyhow, Result};
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::time;
+
 pub struct NetworkPolicyStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl NetworkPolicyStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut policy: Value) -> Result<Value> {
@@ -20,7 +26,7 @@ impl NetworkPolicyStore {
             .ok_or_else(|| anyhow!("NetworkPolicy name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         // Extract spec fields
         let pod_selector = policy["spec"]["podSelector"].clone();
@@ -57,9 +63,11 @@ impl NetworkPolicyStore {
                 uid, namespace, name, pod_selector, policy_types, ingress, egress,
                 labels, annotations, resource_version, creation_timestamp
             )
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, 1, ?10)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)
         "#;
         
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(query)
             .bind(&uid)
             .bind(namespace)
@@ -70,8 +78,9 @@ impl NetworkPolicyStore {
             .bind(egress.as_ref().map(|v| v.to_string()))
             .bind(labels.to_string())
             .bind(annotations.to_string())
+            .bind(version)
             .bind(&now)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
         // Build response
@@ -79,7 +88,7 @@ impl NetworkPolicyStore {
         policy["kind"] = json!("NetworkPolicy");
         policy["metadata"]["uid"] = json!(uid);
         policy["metadata"]["namespace"] = json!(namespace);
-        policy["metadata"]["resourceVersion"] = json!("1");
+        policy["metadata"]["resourceVersion"] = json!(version.to_string());
         policy["metadata"]["generation"] = json!(1);
         policy["metadata"]["creationTimestamp"] = json!(now);
         policy["metadata"]["selfLink"] = json!(format!("/apis/networking.k8s.io/v1/namespaces/{}/networkpolicies/{}", namespace, name));
@@ -95,6 +104,8 @@ impl NetworkPolicyStore {
             policy["metadata"]["annotations"] = annotations;
         }
 
+        watch_store::record(&tx.db, "networkpolicies", "ADDED", &policy).await?;
+        tx.db.commit_write().await?;
         Ok(policy)
     }
 
@@ -109,7 +120,7 @@ impl NetworkPolicyStore {
         let row = sqlx::query(query)
             .bind(namespace)
             .bind(name)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&self.db)
             .await?;
 
         match row {
@@ -122,34 +133,23 @@ impl NetworkPolicyStore {
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if namespace.is_some() {
-            r#"
-                SELECT uid, namespace, name, pod_selector, policy_types, ingress, egress,
-                       labels, annotations, resource_version, generation, creation_timestamp 
-                FROM networkpolicies 
-                WHERE namespace = ?1 AND deletion_timestamp IS NULL 
-                ORDER BY name
-            "#
-        } else {
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
             r#"
                 SELECT uid, namespace, name, pod_selector, policy_types, ingress, egress,
                        labels, annotations, resource_version, generation, creation_timestamp 
                 FROM networkpolicies 
-                WHERE deletion_timestamp IS NULL 
+                WHERE deletion_timestamp IS NULL{} 
                 ORDER BY namespace, name
-            "#
-        };
-
-        let rows = if let Some(ns) = namespace {
-            sqlx::query(query)
-                .bind(ns)
-                .fetch_all(&self.pool)
-                .await?
-        } else {
-            sqlx::query(query)
-                .fetch_all(&self.pool)
-                .await?
-        };
+            "#,
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -179,16 +179,19 @@ impl NetworkPolicyStore {
         let policy = self.get(namespace, name).await?;
 
         // Soft delete
-        let deletion_timestamp = Utc::now().to_rfc3339();
+        let deletion_timestamp = time::now();
         let delete_query = "UPDATE networkpolicies SET deletion_timestamp = ?1 WHERE namespace = ?2 AND name = ?3";
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(delete_query)
             .bind(&deletion_timestamp)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        watch_store::record(&tx.db, "networkpolicies", "DELETED", &policy).await?;
+        tx.db.commit_write().await?;
         Ok(policy)
     }
 
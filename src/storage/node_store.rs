@@ -0,0 +1,104 @@
+use anyhow::{anyhow, Result};
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::collections::HashMap;
+
+use super::db::Db;
+use super::resource_version;
+use super::watch_store;
+use crate::models::{node, time};
+
+/// What's kept of a node: its spec once it's been changed through the
+/// API, its status once its kubelet has reported it, and the resource
+/// version of the latest of those writes.
+#[derive(Debug, Clone)]
+pub struct StoredNode {
+    pub spec: Option<Value>,
+    pub status: Option<Value>,
+    pub resource_version: i64,
+}
+
+/// Keeps what changes of the nodes: the specs they're given through the
+/// API, by kubectl cordon, drain or taint, and the status their kubelets
+/// report. Nodes come from the config, which gives their spec until it's
+/// first changed; from then on the one kept here is theirs.
+pub struct NodeStore {
+    db: Db,
+}
+
+impl NodeStore {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// What's kept of each node, by name.
+    pub async fn stored(&self) -> Result<HashMap<String, StoredNode>> {
+        let rows = sqlx::query("SELECT name, spec, status, resource_version FROM nodes WHERE deletion_timestamp IS NULL")
+            .fetch_all(&self.db)
+            .await?;
+        rows.iter()
+            .map(|row| {
+                let json = |column: &str| -> Result<Option<Value>> {
+                    let value = row.get::<Option<String>, _>(column).map(|value| serde_json::from_str::<Value>(&value)).transpose()?;
+                    Ok(value.filter(|value| !value.is_null()))
+                };
+                let node = StoredNode { spec: json("spec")?, status: json("status")?, resource_version: row.get("resource_version") };
+                Ok((row.get("name"), node))
+            })
+            .collect()
+    }
+
+    /// Makes `node`'s spec the node's, tainting it unschedulable or not as
+    /// its spec.unschedulable says, and returns the node as written.
+    pub async fn update_spec(&self, mut node: Value) -> Result<Value> {
+        node["spec"] = node::with_unschedulable_taint(&node["spec"]);
+        self.write(node, "spec").await
+    }
+
+    /// Makes `node`'s status the one its kubelet last reported.
+    pub async fn update_status(&self, node: Value) -> Result<Value> {
+        self.write(node, "status").await
+    }
+
+    // Writes the node's spec or status, whichever `field` is. A node first
+    // written by its kubelet gets a null spec, which leaves it the config's.
+    // The other one is taken from what's kept, so that a write made from a
+    // node read before the other changed doesn't show it as it was.
+    async fn write(&self, mut node: Value, field: &str) -> Result<Value> {
+        let name = node["metadata"]["name"].as_str().ok_or_else(|| anyhow!("Node name is required"))?.to_string();
+        let uid = node["metadata"]["uid"].as_str().unwrap_or_default().to_string();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let (spec, status) = match field {
+            "spec" => (node["spec"].to_string(), Value::Null.to_string()),
+            _ => (Value::Null.to_string(), node["status"].to_string()),
+        };
+        let sql = format!(
+            "INSERT INTO nodes (uid, name, resource_version, creation_timestamp, spec, status)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT(name) DO UPDATE SET {field} = excluded.{field}, resource_version = excluded.resource_version
+             RETURNING spec, status"
+        );
+        let row = sqlx::query(&sql)
+            .bind(&uid)
+            .bind(&name)
+            .bind(version)
+            .bind(time::now())
+            .bind(spec)
+            .bind(status)
+            .fetch_one(&tx.db)
+            .await?;
+
+        let other = if field == "spec" { "status" } else { "spec" };
+        if let Some(kept) = row.get::<Option<String>, _>(other) {
+            let kept: Value = serde_json::from_str(&kept)?;
+            if !kept.is_null() {
+                node[other] = kept;
+            }
+        }
+        node["metadata"]["resourceVersion"] = json!(version.to_string());
+        watch_store::record(&tx.db, "nodes", "MODIFIED", &node).await?;
+        tx.db.commit_write().await?;
+        Ok(node)
+    }
+}
@@ -0,0 +1,202 @@
+// metadata.ownerReferences, for the garbage collector. The stores keep
+// objects in columns of their own and mostly drop them, so they're taken
+// from objects as they're written (see watch_store::record) and kept here,
+// by owner. A write that doesn't carry ownerReferences leaves the kept ones
+// alone, since most stores hand back objects without them; rows of objects
+// that have gone are pruned.
+use anyhow::Result;
+use serde_json::Value;
+use sqlx::Row;
+use std::collections::{HashMap, HashSet};
+
+use super::db::Db;
+use super::tables;
+
+/// An object owned by another.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Dependent {
+    pub resource: String,
+    pub namespace: Option<String>,
+    pub name: String,
+    pub uid: String,
+}
+
+pub struct OwnerStore {
+    db: Db,
+}
+
+impl OwnerStore {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// The objects `owner_uid` owns that still exist.
+    pub async fn dependents(&self, owner_uid: &str) -> Result<Vec<Dependent>> {
+        let rows = sqlx::query("SELECT resource, namespace, name, uid FROM owner_references WHERE owner_uid = ? ORDER BY resource, name")
+            .bind(owner_uid)
+            .fetch_all(&self.db)
+            .await?;
+        let mut dependents = Vec::new();
+        for row in &rows {
+            let dependent = dependent(row);
+            if exists(&self.db, &dependent.resource, &dependent.uid).await? {
+                dependents.push(dependent);
+            }
+        }
+        Ok(dependents)
+    }
+
+    /// The uids of the `resource` objects each of `owner_uids` is recorded
+    /// as owning, read in one query. Some may have gone since, which a get
+    /// of them finds out.
+    pub async fn dependent_uids(&self, resource: &str, owner_uids: &[String]) -> Result<HashMap<String, Vec<String>>> {
+        let rows = sqlx::query(
+            "SELECT owner_uid, uid FROM owner_references
+             WHERE resource = ? AND owner_uid IN (SELECT value FROM json_each(?))"
+        )
+        .bind(resource)
+        .bind(serde_json::json!(owner_uids).to_string())
+        .fetch_all(&self.db)
+        .await?;
+        let mut dependents: HashMap<String, Vec<String>> = HashMap::new();
+        for row in &rows {
+            dependents.entry(row.get("owner_uid")).or_default().push(row.get("uid"));
+        }
+        Ok(dependents)
+    }
+
+    /// Makes what `owner_uid` owns no longer owned by it, as deleting it
+    /// with propagationPolicy Orphan does.
+    pub async fn orphan(&self, owner_uid: &str) -> Result<()> {
+        // ReplicaSets keep theirs in a column, and show them from it
+        sqlx::query(
+            "UPDATE replicasets SET owner_references = (
+                 SELECT json_group_array(json(value)) FROM json_each(replicasets.owner_references)
+                 WHERE json_extract(value, '$.uid') != ?1
+             )
+             WHERE uid IN (SELECT uid FROM owner_references WHERE owner_uid = ?1 AND resource = 'replicasets')"
+        )
+        .bind(owner_uid)
+        .execute(&self.db)
+        .await?;
+        sqlx::query("DELETE FROM owner_references WHERE owner_uid = ?")
+            .bind(owner_uid)
+            .execute(&self.db)
+            .await?;
+        Ok(())
+    }
+
+    /// Objects whose owners have all been deleted, which the garbage
+    /// collector deletes in turn. Owners of kinds krust doesn't keep are
+    /// taken to exist.
+    pub async fn garbage(&self) -> Result<Vec<Dependent>> {
+        let rows = sqlx::query("SELECT resource, namespace, name, uid, owner_kind, owner_uid FROM owner_references ORDER BY resource, name")
+            .fetch_all(&self.db)
+            .await?;
+        let mut owner_exists: HashMap<String, bool> = HashMap::new();
+        let mut owned: Vec<Dependent> = Vec::new();
+        let (mut seen, mut kept): (HashSet<String>, HashSet<String>) = Default::default();
+        for row in &rows {
+            let owner_uid: String = row.get("owner_uid");
+            let owned_by_existing = match owner_exists.get(&owner_uid) {
+                Some(found) => *found,
+                None => {
+                    let found = match tables::resource_for_kind(&row.get::<String, _>("owner_kind")) {
+                        Some(resource) => exists(&self.db, resource, &owner_uid).await?,
+                        None => true,
+                    };
+                    owner_exists.insert(owner_uid, found);
+                    found
+                }
+            };
+            let dependent = dependent(row);
+            if owned_by_existing {
+                kept.insert(dependent.uid.clone());
+            }
+            if seen.insert(dependent.uid.clone()) {
+                owned.push(dependent);
+            }
+        }
+
+        let mut garbage = Vec::new();
+        for dependent in owned.into_iter().filter(|dependent| !kept.contains(&dependent.uid)) {
+            if exists(&self.db, &dependent.resource, &dependent.uid).await? {
+                garbage.push(dependent);
+            }
+        }
+        Ok(garbage)
+    }
+
+    /// Forgets the ownerReferences of objects that no longer exist.
+    pub async fn prune(&self) -> Result<u64> {
+        let resources = sqlx::query_scalar::<_, String>("SELECT DISTINCT resource FROM owner_references")
+            .fetch_all(&self.db)
+            .await?;
+        let mut pruned = 0;
+        for resource in resources {
+            let Some((table, _)) = tables::table(&resource) else {
+                continue;
+            };
+            let sql = format!(
+                "DELETE FROM owner_references WHERE resource = ? AND uid NOT IN (SELECT uid FROM {} WHERE deletion_timestamp IS NULL)",
+                table
+            );
+            pruned += sqlx::query(&sql).bind(&resource).execute(&self.db).await?.rows_affected();
+        }
+        Ok(pruned)
+    }
+}
+
+/// Keeps the ownerReferences of an object being written, if it carries any.
+pub(crate) async fn index(db: &Db, resource: &str, event_type: &str, object: &Value) -> Result<()> {
+    let metadata = &object["metadata"];
+    let (Some(references), Some(uid), Some(name)) =
+        (metadata["ownerReferences"].as_array(), metadata["uid"].as_str(), metadata["name"].as_str())
+    else {
+        return Ok(());
+    };
+    if event_type == "DELETED" || tables::table(resource).is_none() {
+        return Ok(());
+    }
+    sqlx::query("DELETE FROM owner_references WHERE uid = ?").bind(uid).execute(db).await?;
+    for reference in references {
+        let (Some(owner_kind), Some(owner_name), Some(owner_uid)) =
+            (reference["kind"].as_str(), reference["name"].as_str(), reference["uid"].as_str())
+        else {
+            continue;
+        };
+        sqlx::query(
+            "INSERT OR REPLACE INTO owner_references (resource, namespace, name, uid, owner_kind, owner_name, owner_uid)
+             VALUES (?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(resource)
+        .bind(metadata["namespace"].as_str().unwrap_or_default())
+        .bind(name)
+        .bind(uid)
+        .bind(owner_kind)
+        .bind(owner_name)
+        .bind(owner_uid)
+        .execute(db)
+        .await?;
+    }
+    Ok(())
+}
+
+fn dependent(row: &sqlx::sqlite::SqliteRow) -> Dependent {
+    let namespace: String = row.get("namespace");
+    Dependent {
+        resource: row.get("resource"),
+        namespace: (!namespace.is_empty()).then_some(namespace),
+        name: row.get("name"),
+        uid: row.get("uid"),
+    }
+}
+
+// Whether the object of `resource` with `uid` exists and isn't deleted
+async fn exists(db: &Db, resource: &str, uid: &str) -> Result<bool> {
+    let Some((table, _)) = tables::table(resource) else {
+        return Ok(false);
+    };
+    let sql = format!("SELECT COUNT(*) FROM {} WHERE uid = ? AND deletion_timestamp IS NULL", table);
+    Ok(sqlx::query_scalar::<_, i64>(&sql).bind(uid).fetch_one(db).await? > 0)
+}
@@ -1,15 +1,22 @@
 use anyhow::Result;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::time;
+
 pub struct PdbStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl PdbStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut pdb: Value) -> Result<Value> {
@@ -25,10 +32,14 @@ impl PdbStore {
         let labels = pdb["metadata"].get("labels").cloned().unwrap_or(json!({}));
         let annotations = pdb["metadata"].get("annotations").cloned().unwrap_or(json!({}));
 
+        let now = time::now();
+
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(
             "INSERT INTO poddisruptionbudgets (uid, name, namespace, spec, min_available, max_unavailable,
-             selector, unhealthy_pod_eviction_policy, labels, annotations, resource_version, generation)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 1)"
+             selector, unhealthy_pod_eviction_policy, labels, annotations, resource_version, generation, creation_timestamp)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, ?)"
         )
         .bind(&uid)
         .bind(&name)
@@ -40,13 +51,15 @@ impl PdbStore {
         .bind(unhealthy_pod_eviction_policy)
         .bind(serde_json::to_string(&labels)?)
         .bind(serde_json::to_string(&annotations)?)
-        .execute(&self.pool)
+        .bind(version)
+        .bind(&now)
+        .execute(&tx.db)
         .await?;
 
         pdb["metadata"]["uid"] = json!(uid);
-        pdb["metadata"]["resourceVersion"] = json!("1");
+        pdb["metadata"]["resourceVersion"] = json!(version.to_string());
         pdb["metadata"]["generation"] = json!(1);
-        pdb["metadata"]["creationTimestamp"] = json!(chrono::Utc::now().to_rfc3339());
+        pdb["metadata"]["creationTimestamp"] = json!(now);
         
         // Initialize status
         if pdb["status"].is_null() {
@@ -59,7 +72,7 @@ impl PdbStore {
             });
         }
 
-        self.record_event(
+        tx.record_event(
             &uid,
             "PodDisruptionBudget",
             namespace,
@@ -68,6 +81,8 @@ impl PdbStore {
             "PodDisruptionBudget created"
         ).await?;
 
+        watch_store::record(&tx.db, "poddisruptionbudgets", "ADDED", &pdb).await?;
+        tx.db.commit_write().await?;
         Ok(pdb)
     }
 
@@ -80,7 +95,7 @@ impl PdbStore {
         )
         .bind(namespace)
         .bind(name)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
 
         if let Some(row) = row {
@@ -105,10 +120,10 @@ impl PdbStore {
                     "resourceVersion": resource_version.to_string(),
                     "generation": generation,
                     "creationTimestamp": creation_timestamp,
-                    "labels": serde_json::from_str(&labels)?,
-                    "annotations": serde_json::from_str(&annotations)?
+                    "labels": serde_json::from_str::<Value>(&labels)?,
+                    "annotations": serde_json::from_str::<Value>(&annotations)?
                 },
-                "spec": serde_json::from_str(&spec)?
+                "spec": serde_json::from_str::<Value>(&spec)?
             });
 
             if let Some(status_str) = status {
@@ -134,8 +149,8 @@ impl PdbStore {
             .ok_or_else(|| anyhow::anyhow!("PodDisruptionBudget not found"))?;
 
         let uid = current["metadata"]["uid"].as_str().unwrap();
-        let resource_version: i64 = current["metadata"]["resourceVersion"].as_str().unwrap().parse()?;
-        let new_resource_version = resource_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_resource_version = resource_version::next(&tx.db).await?;
         let generation: i64 = current["metadata"]["generation"].as_i64().unwrap();
         let new_generation = if pdb["spec"] != current["spec"] { generation + 1 } else { generation };
 
@@ -167,15 +182,17 @@ impl PdbStore {
         .bind(new_generation)
         .bind(namespace)
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
         pdb["metadata"]["uid"] = json!(uid);
         pdb["metadata"]["resourceVersion"] = json!(new_resource_version.to_string());
         pdb["metadata"]["generation"] = json!(new_generation);
         pdb["metadata"]["creationTimestamp"] = current["metadata"]["creationTimestamp"].clone();
+        // Status is only written through update_status
+        pdb["status"] = current["status"].clone();
 
-        self.record_event(
+        tx.record_event(
             uid,
             "PodDisruptionBudget",
             namespace,
@@ -184,6 +201,8 @@ impl PdbStore {
             "PodDisruptionBudget updated"
         ).await?;
 
+        watch_store::record(&tx.db, "poddisruptionbudgets", "MODIFIED", &pdb).await?;
+        tx.db.commit_write().await?;
         Ok(pdb)
     }
 
@@ -192,8 +211,8 @@ impl PdbStore {
             .ok_or_else(|| anyhow::anyhow!("PodDisruptionBudget not found"))?;
 
         let uid = current["metadata"]["uid"].as_str().unwrap().to_string();
-        let resource_version: i64 = current["metadata"]["resourceVersion"].as_str().unwrap().parse()?;
-        let new_resource_version = resource_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_resource_version = resource_version::next(&tx.db).await?;
 
         // Extract status fields for indexing
         let current_healthy = status.get("currentHealthy").and_then(|v| v.as_i64());
@@ -217,13 +236,13 @@ impl PdbStore {
         .bind(new_resource_version)
         .bind(namespace)
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
         current["metadata"]["resourceVersion"] = json!(new_resource_version.to_string());
         current["status"] = status;
 
-        self.record_event(
+        tx.record_event(
             &uid,
             "PodDisruptionBudget",
             namespace,
@@ -232,6 +251,8 @@ impl PdbStore {
             "PodDisruptionBudget status updated"
         ).await?;
 
+        watch_store::record(&tx.db, "poddisruptionbudgets", "MODIFIED", &current).await?;
+        tx.db.commit_write().await?;
         Ok(current)
     }
 
@@ -241,17 +262,19 @@ impl PdbStore {
 
         let uid = pdb["metadata"]["uid"].as_str().unwrap();
 
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
             "UPDATE poddisruptionbudgets 
-             SET deletion_timestamp = CURRENT_TIMESTAMP 
+             SET deletion_timestamp = ? 
              WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL"
         )
+        .bind(time::now())
         .bind(namespace)
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
-        self.record_event(
+        tx.record_event(
             uid,
             "PodDisruptionBudget",
             namespace,
@@ -260,28 +283,26 @@ impl PdbStore {
             "PodDisruptionBudget deleted"
         ).await?;
 
+        watch_store::record(&tx.db, "poddisruptionbudgets", "DELETED", &pdb).await?;
+        tx.db.commit_write().await?;
         Ok(pdb)
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if let Some(ns) = namespace {
-            sqlx::query(
-                "SELECT uid, name, namespace, spec, status, labels, annotations,
-                 resource_version, generation, creation_timestamp
-                 FROM poddisruptionbudgets
-                 WHERE namespace = ? AND deletion_timestamp IS NULL"
-            )
-            .bind(ns)
-        } else {
-            sqlx::query(
-                "SELECT uid, name, namespace, spec, status, labels, annotations,
-                 resource_version, generation, creation_timestamp
-                 FROM poddisruptionbudgets
-                 WHERE deletion_timestamp IS NULL"
-            )
-        };
-
-        let rows = query.fetch_all(&self.pool).await?;
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
+            "SELECT uid, name, namespace, spec, status, labels, annotations,
+             resource_version, generation, creation_timestamp
+             FROM poddisruptionbudgets
+             WHERE deletion_timestamp IS NULL{}",
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
         let mut items = Vec::new();
 
         for row in rows {
@@ -306,10 +327,10 @@ impl PdbStore {
                     "resourceVersion": resource_version.to_string(),
                     "generation": generation,
                     "creationTimestamp": creation_timestamp,
-                    "labels": serde_json::from_str(&labels)?,
-                    "annotations": serde_json::from_str(&annotations)?
+                    "labels": serde_json::from_str::<Value>(&labels)?,
+                    "annotations": serde_json::from_str::<Value>(&annotations)?
                 },
-                "spec": serde_json::from_str(&spec)?
+                "spec": serde_json::from_str::<Value>(&spec)?
             });
 
             if let Some(status_str) = status {
@@ -336,9 +357,10 @@ impl PdbStore {
 
     async fn record_event(&self, uid: &str, resource_type: &str, namespace: &str, name: &str, reason: &str, message: &str) -> Result<()> {
         let event_uid = Uuid::new_v4().to_string();
+        let now = time::now();
         sqlx::query(
             "INSERT INTO events (uid, namespace, involved_object_uid, involved_object_kind, involved_object_name, reason, message, event_time, first_timestamp, last_timestamp, count, type)
-             VALUES (?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, 1, 'Normal')"
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 'Normal')"
         )
         .bind(&event_uid)
         .bind(namespace)
@@ -347,7 +369,10 @@ impl PdbStore {
         .bind(name)
         .bind(reason)
         .bind(message)
-        .execute(&self.pool)
+        .bind(&now)
+        .bind(&now)
+        .bind(&now)
+        .execute(&self.db)
         .await?;
         Ok(())
     }
@@ -1,18 +1,45 @@
 use anyhow::{anyhow, Result};
 use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::list_selector::ListSelector;
+use super::json_sql;
+use super::scheduling_store::PriorityClassStore;
+use super::resource_version;
+use super::watch_store;
 use crate::models::pod::Pod;
+use crate::models::pod_conditions;
+use crate::runtime::compat;
+use crate::models::time;
+
+// Field labels pods can be selected on. Unset fields the API server would
+// have defaulted read as their default.
+const FIELDS: &[(&str, &str)] = &[
+    ("metadata.name", "name"),
+    ("metadata.namespace", "namespace"),
+    ("spec.nodeName", "node_name"),
+    ("spec.restartPolicy", "COALESCE(json_extract(spec, '$.restartPolicy'), 'Always')"),
+    ("spec.schedulerName", "COALESCE(json_extract(spec, '$.schedulerName'), 'default-scheduler')"),
+    ("spec.serviceAccountName", "COALESCE(json_extract(spec, '$.serviceAccountName'), 'default')"),
+    ("spec.hostNetwork", "CASE WHEN json_extract(spec, '$.hostNetwork') THEN 'true' ELSE 'false' END"),
+    ("status.phase", "phase"),
+    ("status.podIP", "json_extract(status, '$.podIP')"),
+    ("status.nominatedNodeName", "json_extract(status, '$.nominatedNodeName')"),
+];
+
+// terminationGracePeriodSeconds unless the pod says otherwise
+const DEFAULT_TERMINATION_GRACE_PERIOD_SECONDS: i64 = 30;
 
 pub struct PodStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl PodStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut pod: Value) -> Result<Value> {
@@ -22,15 +49,23 @@ impl PodStore {
             .ok_or_else(|| anyhow!("Pod name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         
         // Set metadata fields
         pod["metadata"]["uid"] = json!(uid);
         pod["metadata"]["namespace"] = json!(namespace);
-        pod["metadata"]["resourceVersion"] = json!("1");
+        pod["metadata"]["resourceVersion"] = json!(version.to_string());
         pod["metadata"]["creationTimestamp"] = json!(now);
         pod["metadata"]["selfLink"] = json!(format!("/api/v1/namespaces/{}/pods/{}", namespace, name));
         
+        // Resolve spec.priority from the pod's PriorityClass
+        tx.apply_priority(&mut pod["spec"]).await?;
+
+        // Surface spec fields the runtime can't honor
+        compat::annotate_pod(&mut pod);
+        
         // Set default status with proper conditions
         if pod["status"].is_null() {
             pod["status"] = json!({
@@ -88,22 +123,55 @@ impl PodStore {
         .bind(&uid)
         .bind(&name)
         .bind(namespace)
-        .bind(1i64)
+        .bind(version)
         .bind(&now)
         .bind(&labels)
         .bind(&annotations)
         .bind(&spec)
         .bind(&status)
         .bind("Pending")
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        self.record_event("pods", &uid, &name, namespace, "ADDED", 1, &pod).await?;
+        watch_store::record(&tx.db, "pods", "ADDED", &pod).await?;
         
+        tx.db.commit_write().await?;
         Ok(pod)
     }
 
+    async fn apply_priority(&self, spec: &mut Value) -> Result<()> {
+        let class_name = spec["priorityClassName"].as_str().map(|s| s.to_string());
+        let resolved = PriorityClassStore::new(self.db.clone())
+            .resolve(class_name.as_deref())
+            .await?;
+
+        let (priority, policy) = match (resolved, class_name) {
+            (Some(resolved), _) => resolved,
+            (None, Some(name)) => {
+                return Err(anyhow!("no PriorityClass with name {} was found", name));
+            }
+            (None, None) => (0, "PreemptLowerPriority".to_string()),
+        };
+
+        if let Some(requested) = spec["priority"].as_i64() {
+            if requested != priority {
+                return Err(anyhow!(
+                    "the integer value of priority ({}) must not be provided in pod spec; \
+                     priority admission controller computed {} from the given PriorityClass name",
+                    requested, priority
+                ));
+            }
+        }
+
+        spec["priority"] = json!(priority);
+        if spec["preemptionPolicy"].is_null() {
+            spec["preemptionPolicy"] = json!(policy);
+        }
+
+        Ok(())
+    }
+
     pub async fn get(&self, namespace: &str, name: &str) -> Result<Value> {
         let row = sqlx::query(
             "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, node_name, phase
@@ -111,7 +179,7 @@ impl PodStore {
         )
         .bind(name)
         .bind(namespace)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
         
         match row {
@@ -164,20 +232,18 @@ impl PodStore {
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if let Some(ns) = namespace {
-            sqlx::query(
-                "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, node_name, phase
-                 FROM pods WHERE namespace = ? AND deletion_timestamp IS NULL"
-            )
-            .bind(ns)
-        } else {
-            sqlx::query(
-                "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, node_name, phase
-                 FROM pods WHERE deletion_timestamp IS NULL"
-            )
-        };
-        
-        let rows = query.fetch_all(&self.pool).await?;
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
+            "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, node_name, phase
+             FROM pods WHERE deletion_timestamp IS NULL{}",
+            selector.sql(FIELDS)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
         
         let mut items = Vec::new();
         for row in rows {
@@ -259,66 +325,88 @@ impl PodStore {
         // Get current pod to check it exists
         let current = self.get(namespace, name).await?;
         let uid = current["metadata"]["uid"].as_str().unwrap();
-        let resource_version = current["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse::<i64>()?;
-        
-        let new_version = resource_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_version = resource_version::next(&tx.db).await?;
         
         // Update metadata
         pod["metadata"]["uid"] = json!(uid);
         pod["metadata"]["namespace"] = json!(namespace);
         pod["metadata"]["resourceVersion"] = json!(new_version.to_string());
+        compat::annotate_pod(&mut pod);
+        
+        // Status is only written through the status subresource
+        pod["status"] = current["status"].clone();
         
         let labels = pod["metadata"]["labels"].to_string();
         let annotations = pod["metadata"]["annotations"].to_string();
         let spec = pod["spec"].to_string();
-        let status = pod["status"].to_string();
         
         sqlx::query(
-            "UPDATE pods SET resource_version = ?, labels = ?, annotations = ?, spec = ?, status = ?
+            "UPDATE pods SET resource_version = ?, labels = ?, annotations = ?, spec = ?
              WHERE uid = ?"
         )
         .bind(new_version)
         .bind(&labels)
         .bind(&annotations)
         .bind(&spec)
-        .bind(&status)
         .bind(uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        self.record_event("pods", uid, name, namespace, "MODIFIED", new_version, &pod).await?;
+        watch_store::record(&tx.db, "pods", "MODIFIED", &pod).await?;
         
+        tx.db.commit_write().await?;
         Ok(pod)
     }
 
     pub async fn delete(&self, namespace: &str, name: &str) -> Result<()> {
         let pod = self.get(namespace, name).await?;
         let uid = pod["metadata"]["uid"].as_str().unwrap();
-        let resource_version = pod["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse::<i64>()?;
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
             "UPDATE pods SET deletion_timestamp = ? WHERE uid = ?"
         )
         .bind(&now)
         .bind(uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        self.record_event("pods", uid, name, namespace, "DELETED", resource_version, &pod).await?;
+        watch_store::record(&tx.db, "pods", "DELETED", &pod).await?;
         
+        tx.db.commit_write().await?;
         Ok(())
     }
 
+    /// How long the pod gets to stop when deleted: the grace period asked
+    /// for, or else its terminationGracePeriodSeconds. Pods that aren't on
+    /// a node or have finished have nothing to stop and get none. None if
+    /// there's no such pod.
+    pub async fn grace_period(&self, namespace: &str, name: &str, requested: Option<i64>) -> Result<Option<i64>> {
+        let row = sqlx::query(
+            "SELECT node_name, phase, json_extract(spec, '$.terminationGracePeriodSeconds') AS termination_grace_period
+             FROM pods WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL"
+        )
+        .bind(namespace)
+        .bind(name)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(row.map(|row| {
+            let node_name: Option<String> = row.get("node_name");
+            let phase: Option<String> = row.get("phase");
+            if node_name.is_none() || matches!(phase.as_deref(), Some("Succeeded" | "Failed")) {
+                return 0;
+            }
+            requested
+                .or(row.get("termination_grace_period"))
+                .unwrap_or(DEFAULT_TERMINATION_GRACE_PERIOD_SECONDS)
+        }))
+    }
+
     pub async fn update_status(&self, namespace: &str, name: &str, phase: &str, node_name: Option<&str>) -> Result<()> {
         let pod = self.get(namespace, name).await?;
         let uid = pod["metadata"]["uid"].as_str().unwrap();
@@ -327,75 +415,229 @@ impl PodStore {
         status["phase"] = json!(phase);
         
         if phase == "Running" {
-            status["startTime"] = json!(Utc::now().to_rfc3339());
+            status["startTime"] = json!(time::now());
             status["conditions"] = json!([
                 {
                     "type": "Ready",
                     "status": "True",
-                    "lastTransitionTime": Utc::now().to_rfc3339()
+                    "lastTransitionTime": time::now()
                 }
             ]);
         }
         
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(
-            "UPDATE pods SET phase = ?, node_name = ?, status = ? WHERE uid = ?"
+            "UPDATE pods SET phase = ?, node_name = ?, status = ?, resource_version = ? WHERE uid = ?"
         )
         .bind(phase)
         .bind(node_name)
         .bind(&status.to_string())
+        .bind(version)
         .bind(uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
-        
+
+        tx.record_modified(namespace, name).await?;
+        tx.db.commit_write().await?;
         Ok(())
     }
 
-    async fn record_event(&self, resource_type: &str, uid: &str, name: &str, namespace: &str, event_type: &str, version: i64, object: &Value) -> Result<()> {
-        sqlx::query(
-            "INSERT INTO events (resource_type, resource_uid, resource_name, resource_namespace, event_type, resource_version, timestamp, object)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+    /// Replaces the pod's status in a single UPDATE, leaving the spec and
+    /// metadata as they are in the database rather than as they were read.
+    pub async fn set_status(&self, namespace: &str, name: &str, status: Value) -> Result<Value> {
+        let status = status.to_string();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let updated = sqlx::query(
+            "UPDATE pods SET status = ?, phase = COALESCE(json_extract(?, '$.phase'), phase),
+                    resource_version = ?
+             WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL"
         )
-        .bind(resource_type)
-        .bind(uid)
+        .bind(&status)
+        .bind(&status)
+        .bind(version)
+        .bind(namespace)
         .bind(name)
+        .execute(&tx.db)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Err(anyhow!("Pod not found"));
+        }
+
+        tx.apply_readiness_gates(namespace, name).await?;
+        let pod = tx.record_modified(namespace, name).await?;
+        tx.db.commit_write().await?;
+        Ok(pod)
+    }
+
+    /// Applies a JSON merge patch to the pod's status. Fields the patch
+    /// doesn't mention are kept, including ones written concurrently.
+    /// Conditions are merged by type rather than replaced as a whole.
+    pub async fn patch_status(&self, namespace: &str, name: &str, mut patch: Value) -> Result<Value> {
+        if let Some(conditions) = patch["conditions"].as_array() {
+            let pod = self.get(namespace, name).await?;
+            let existing = pod["status"]["conditions"].as_array().cloned().unwrap_or_default();
+            patch["conditions"] = json!(pod_conditions::merge(&existing, conditions));
+        }
+        let patch = merge_patch(&patch);
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let updated = sqlx::query(&format!(
+            "UPDATE pods SET status = {}, phase = COALESCE(json_extract(?, '$.phase'), phase),
+                    resource_version = ?
+             WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL",
+            json_sql::merge("status")
+        ))
+        .bind(&patch)
+        .bind(&patch)
+        .bind(version)
         .bind(namespace)
-        .bind(event_type)
+        .bind(name)
+        .execute(&tx.db)
+        .await?;
+
+        if updated.rows_affected() == 0 {
+            return Err(anyhow!("Pod not found"));
+        }
+
+        tx.apply_readiness_gates(namespace, name).await?;
+        let pod = tx.record_modified(namespace, name).await?;
+        tx.db.commit_write().await?;
+        Ok(pod)
+    }
+
+    /// Applies a JSON merge patch to the pod's labels, annotations and spec
+    /// in one statement, so concurrent patches to different keys all land
+    /// instead of the last writer overwriting the others. Any status in the
+    /// patch is ignored; that goes through `patch_status`.
+    pub async fn patch(&self, namespace: &str, name: &str, patch: Value) -> Result<Value> {
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let row = sqlx::query(&format!(
+            "UPDATE pods SET labels = {}, annotations = {}, spec = {},
+                    resource_version = ?
+             WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL
+             RETURNING uid",
+            json_sql::merge("labels"),
+            json_sql::merge("annotations"),
+            json_sql::merge("spec")
+        ))
+        .bind(merge_patch(&patch["metadata"]["labels"]))
+        .bind(merge_patch(&patch["metadata"]["annotations"]))
+        .bind(merge_patch(&patch["spec"]))
         .bind(version)
-        .bind(Utc::now().to_rfc3339())
-        .bind(object.to_string())
-        .execute(&self.pool)
+        .bind(namespace)
+        .bind(name)
+        .fetch_optional(&tx.db)
         .await?;
-        
+
+        let Some(row) = row else {
+            return Err(anyhow!("Pod not found"));
+        };
+        let uid: String = row.get("uid");
+
+        // The spec may have changed what the runtime can't honor
+        if !patch["spec"].is_null() {
+            let mut pod = tx.get(namespace, name).await?;
+            let before = pod["metadata"]["annotations"][compat::UNSUPPORTED_FIELDS_ANNOTATION].clone();
+            compat::annotate_pod(&mut pod);
+            let after = &pod["metadata"]["annotations"][compat::UNSUPPORTED_FIELDS_ANNOTATION];
+
+            if *after != before {
+                let path = json_sql::path(compat::UNSUPPORTED_FIELDS_ANNOTATION);
+                let sql = format!("UPDATE pods SET annotations = {} WHERE uid = ?", json_sql::set("annotations", 1));
+                let query = if after.is_null() {
+                    sqlx::query("UPDATE pods SET annotations = json_remove(annotations, ?) WHERE uid = ?")
+                        .bind(path)
+                } else {
+                    sqlx::query(&sql).bind(path).bind(after.to_string())
+                };
+                query.bind(&uid).execute(&tx.db).await?;
+            }
+        }
+
+        let pod = tx.record_modified(namespace, name).await?;
+        tx.db.commit_write().await?;
+        Ok(pod)
+    }
+
+    /// Sets individual status fields of the pod with the given uid, keeping
+    /// the phase column in step with status.phase. Used by the scheduler and
+    /// kubelets so their writes can't revert a concurrent status change.
+    pub async fn set_status_fields(&self, uid: &str, fields: &[(&str, Value)]) -> Result<()> {
+        let phase = fields
+            .iter()
+            .find(|(key, _)| *key == "phase")
+            .and_then(|(_, value)| value.as_str());
+
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let sql = format!(
+            "UPDATE pods SET status = {}, phase = COALESCE(?, phase), resource_version = ?
+             WHERE uid = ? RETURNING namespace, name, deletion_timestamp",
+            json_sql::set("status", fields.len())
+        );
+        let mut query = sqlx::query(&sql);
+        for (key, value) in fields {
+            query = query.bind(json_sql::path(key)).bind(value.to_string());
+        }
+        let row = query
+            .bind(phase)
+            .bind(version)
+            .bind(uid)
+            .fetch_optional(&tx.db)
+            .await?
+            .ok_or_else(|| anyhow!("Pod not found"))?;
+
+        let (namespace, name): (String, String) = (row.get("namespace"), row.get("name"));
+        tx.apply_readiness_gates(&namespace, &name).await?;
+
+        // Terminating pods are no longer visible to watchers
+        if row.get::<Option<String>, _>("deletion_timestamp").is_none() {
+            tx.record_modified(&namespace, &name).await?;
+        }
+
+        tx.db.commit_write().await?;
         Ok(())
     }
-    
-    pub async fn set_status(&self, namespace: &str, name: &str, status: Value) -> Result<Value> {
-        let mut pod = self.get(namespace, name).await?;
-        let uid = pod["metadata"]["uid"].as_str().unwrap().to_string();
-        let current_version: i64 = pod["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse()?;
-        
-        let new_version = current_version + 1;
-        
-        // Update the status
-        pod["status"] = status.clone();
-        pod["metadata"]["resourceVersion"] = json!(new_version.to_string());
-        
-        // Update in database
-        sqlx::query(
-            "UPDATE pods SET status = ?, resource_version = ? WHERE uid = ?"
+
+    // Brings the Ready condition of a pod with readiness gates in line with
+    // its other conditions after a status write
+    async fn apply_readiness_gates(&self, namespace: &str, name: &str) -> Result<()> {
+        let Some(row) = sqlx::query(
+            "SELECT spec, status FROM pods
+             WHERE namespace = ? AND name = ? AND json_array_length(spec, '$.readinessGates') > 0"
         )
-        .bind(status.to_string())
-        .bind(new_version)
-        .bind(&uid)
-        .execute(&self.pool)
-        .await?;
-        
-        // Record event
-        self.record_event("Pod", &uid, name, namespace, "MODIFIED", new_version, &pod).await?;
-        
+        .bind(namespace)
+        .bind(name)
+        .fetch_optional(&self.db)
+        .await?
+        else {
+            return Ok(());
+        };
+
+        let spec: Value = serde_json::from_str(&row.get::<String, _>("spec"))?;
+        let mut status: Value = serde_json::from_str(&row.get::<String, _>("status"))?;
+        if pod_conditions::apply_readiness_gates(&spec, &mut status) {
+            sqlx::query(&format!(
+                "UPDATE pods SET status = {} WHERE namespace = ? AND name = ?",
+                json_sql::set("status", 1)
+            ))
+            .bind(json_sql::path("conditions"))
+            .bind(status["conditions"].to_string())
+            .bind(namespace)
+            .bind(name)
+            .execute(&self.db)
+            .await?;
+        }
+        Ok(())
+    }
+
+    async fn record_modified(&self, namespace: &str, name: &str) -> Result<Value> {
+        let pod = self.get(namespace, name).await?;
+        watch_store::record(&self.db, "pods", "MODIFIED", &pod).await?;
         Ok(pod)
     }
     
@@ -404,82 +646,77 @@ impl PodStore {
     }
     
     pub async fn update_ephemeral_containers(&self, namespace: &str, name: &str, ephemeral_containers: Value) -> Result<Value> {
-        let mut pod = self.get(namespace, name).await?;
-        let uid = pod["metadata"]["uid"].as_str().unwrap().to_string();
-        let current_version: i64 = pod["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse()?;
-        
-        let new_version = current_version + 1;
-        
-        // Add ephemeral containers to spec
-        pod["spec"]["ephemeralContainers"] = ephemeral_containers;
-        pod["metadata"]["resourceVersion"] = json!(new_version.to_string());
-        
-        let spec = pod["spec"].to_string();
-        
-        // Update in database
-        sqlx::query(
-            "UPDATE pods SET spec = ?, resource_version = ? WHERE uid = ?"
-        )
-        .bind(spec)
-        .bind(new_version)
-        .bind(&uid)
-        .execute(&self.pool)
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let updated = sqlx::query(&format!(
+            "UPDATE pods SET spec = {}, resource_version = ?
+             WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL",
+            json_sql::set("spec", 1)
+        ))
+        .bind(json_sql::path("ephemeralContainers"))
+        .bind(ephemeral_containers.to_string())
+        .bind(version)
+        .bind(namespace)
+        .bind(name)
+        .execute(&tx.db)
         .await?;
-        
-        // Record event
-        self.record_event("Pod", &uid, name, namespace, "MODIFIED", new_version, &pod).await?;
-        
+
+        if updated.rows_affected() == 0 {
+            return Err(anyhow!("Pod not found"));
+        }
+
+        let pod = tx.record_modified(namespace, name).await?;
+        tx.db.commit_write().await?;
         Ok(pod)
     }
     
-    pub async fn bind_to_node(&self, namespace: &str, name: &str, node_name: &str) -> Result<()> {
-        let mut pod = self.get(namespace, name).await?;
-        let uid = pod["metadata"]["uid"].as_str().unwrap().to_string();
-        let current_version: i64 = pod["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse()?;
-        
-        let new_version = current_version + 1;
-        
-        // Set nodeName in spec
-        pod["spec"]["nodeName"] = json!(node_name);
-        pod["metadata"]["resourceVersion"] = json!(new_version.to_string());
-        
-        // Update status to scheduled
-        pod["status"]["phase"] = json!("Pending");
-        pod["status"]["conditions"] = json!([
-            {
-                "type": "PodScheduled",
-                "status": "True",
-                "lastTransitionTime": Utc::now().to_rfc3339(),
-                "reason": "Scheduled",
-                "message": format!("Successfully assigned to {}", node_name)
-            }
-        ]);
-        
-        let spec = pod["spec"].to_string();
-        let status = pod["status"].to_string();
-        
-        // Update in database
-        sqlx::query(
-            "UPDATE pods SET spec = ?, status = ?, node_name = ?, resource_version = ? WHERE uid = ?"
-        )
-        .bind(spec)
-        .bind(status)
+    /// Assigns a pending pod to a node, as the binding subresource does:
+    /// spec.nodeName is set, PodScheduled turns True and any nomination is
+    /// dropped. The pod's kubelet starts it from there. A pod already
+    /// assigned, or being deleted, can't be bound.
+    pub async fn bind_to_node(&self, namespace: &str, name: &str, node_name: &str) -> Result<Value> {
+        let pod = self.get(namespace, name).await?;
+        let scheduled = json!({
+            "type": "PodScheduled",
+            "status": "True",
+            "lastProbeTime": null,
+            "reason": "Scheduled",
+            "message": format!("Successfully assigned {}/{} to {}", namespace, name, node_name)
+        });
+        let existing = pod["status"]["conditions"].as_array().cloned().unwrap_or_default();
+        let conditions = pod_conditions::merge(&existing, &[scheduled]);
+
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let updated = sqlx::query(&format!(
+            "UPDATE pods SET spec = {}, status = json_remove({}, '$.nominatedNodeName'), node_name = ?,
+                    resource_version = ?
+             WHERE namespace = ? AND name = ? AND node_name IS NULL AND deletion_timestamp IS NULL",
+            json_sql::set("spec", 1),
+            json_sql::set("status", 1)
+        ))
+        .bind(json_sql::path("nodeName"))
+        .bind(json!(node_name).to_string())
+        .bind(json_sql::path("conditions"))
+        .bind(json!(conditions).to_string())
         .bind(node_name)
-        .bind(new_version)
-        .bind(&uid)
-        .execute(&self.pool)
+        .bind(version)
+        .bind(namespace)
+        .bind(name)
+        .execute(&tx.db)
         .await?;
-        
-        // Record event
-        self.record_event("Pod", &uid, name, namespace, "SCHEDULED", new_version, &pod).await?;
-        
-        Ok(())
+
+        if updated.rows_affected() == 0 {
+            let pod = tx.get(namespace, name).await?;
+            if let Some(assigned) = pod["spec"]["nodeName"].as_str() {
+                return Err(anyhow!("pod {} is already assigned to node {:?}", name, assigned));
+            }
+            return Err(anyhow!("pod {} is being deleted, cannot be assigned to a host", name));
+        }
+
+        let pod = tx.record_modified(namespace, name).await?;
+        tx.db.commit_write().await?;
+        Ok(pod)
     }
     
     fn calculate_qos_class(spec: &Value) -> &'static str {
@@ -556,9 +793,7 @@ impl PodStore {
     
     fn calculate_age(creation_timestamp: &str) -> String {
         // Calculate age as a human-readable string
-        use chrono::{DateTime, Utc};
-        
-        if let Ok(created) = DateTime::parse_from_rfc3339(creation_timestamp) {
+        if let Some(created) = time::parse(creation_timestamp) {
             let now = Utc::now();
             let duration = now.signed_duration_since(created);
             
@@ -575,4 +810,13 @@ impl PodStore {
             "unknown".to_string()
         }
     }
-}
\ No newline at end of file
+}
+
+// Merge patches for columns holding objects; anything else leaves them as is
+fn merge_patch(patch: &Value) -> String {
+    if patch.is_object() {
+        patch.to_string()
+    } else {
+        "{}".to_string()
+    }
+}
@@ -0,0 +1,68 @@
+use anyhow::Result;
+use sqlx::sqlite::SqliteRow;
+use sqlx::Row;
+
+use super::compression;
+use super::db::Db;
+use super::tables::{self, RESOURCES};
+
+/// Annotation that, set to "true", makes the server refuse to delete the
+/// object, or the namespace holding it, until it is removed again.
+pub const PROTECTED_ANNOTATION: &str = "krust.io/protected";
+
+/// Looks up which objects are protected from deletion.
+pub struct ProtectionStore {
+    db: Db,
+}
+
+impl ProtectionStore {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// Whether the named object carries the protection annotation. Unknown
+    /// resources are never protected.
+    pub async fn is_protected(&self, resource: &str, namespace: Option<&str>, name: &str) -> Result<bool> {
+        let Some((table, namespaced)) = tables::table(resource) else {
+            return Ok(false);
+        };
+        let scope = if namespaced { " AND namespace = ?" } else { "" };
+        let sql = format!("SELECT annotations FROM {} WHERE name = ?{} AND deletion_timestamp IS NULL", table, scope);
+
+        let mut query = sqlx::query(&sql).bind(name);
+        if namespaced {
+            query = query.bind(namespace.unwrap_or("default"));
+        }
+        let row = query.fetch_optional(&self.db).await?;
+        Ok(row.is_some_and(|row| protected(&row)))
+    }
+
+    /// The protected objects in a namespace, as `resource/name`.
+    pub async fn protected_in(&self, namespace: &str) -> Result<Vec<String>> {
+        let mut protected_objects = Vec::new();
+        for (resource, table, _) in RESOURCES.iter().filter(|(_, _, namespaced)| *namespaced) {
+            // Annotations can be compressed, so they're looked into here
+            // rather than in SQL
+            let rows = sqlx::query(&format!(
+                "SELECT name, annotations FROM {} WHERE namespace = ? AND deletion_timestamp IS NULL
+                   AND (typeof(annotations) = 'blob' OR annotations LIKE ?)
+                 ORDER BY name",
+                table
+            ))
+            .bind(namespace)
+            .bind(format!("%\"{}\"%", PROTECTED_ANNOTATION))
+            .fetch_all(&self.db)
+            .await?;
+            protected_objects.extend(
+                rows.iter()
+                    .filter(|row| protected(row))
+                    .map(|row| format!("{}/{}", resource, row.get::<String, _>("name"))),
+            );
+        }
+        Ok(protected_objects)
+    }
+}
+
+fn protected(row: &SqliteRow) -> bool {
+    compression::decode(row, "annotations").is_ok_and(|annotations| annotations[PROTECTED_ANNOTATION] == "true")
+}
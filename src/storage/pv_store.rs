@@ -1,16 +1,22 @@
 use anyhow::{anyhow, Result};
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::time;
+
 pub struct PersistentVolumeStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl PersistentVolumeStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, mut pv: Value) -> Result<Value> {
@@ -20,7 +26,7 @@ impl PersistentVolumeStore {
             .ok_or_else(|| anyhow!("PersistentVolume name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         // Extract spec fields
         let capacity = pv["spec"]["capacity"].clone();
@@ -66,9 +72,11 @@ impl PersistentVolumeStore {
                 volume_mode, host_path, nfs, local, csi, phase, labels, annotations, 
                 resource_version, creation_timestamp
             )
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, 1, ?15)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16)
         "#;
         
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(query)
             .bind(&uid)
             .bind(&name)
@@ -84,15 +92,16 @@ impl PersistentVolumeStore {
             .bind("Available")
             .bind(labels.to_string())
             .bind(annotations.to_string())
+            .bind(version)
             .bind(&now)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
         // Build response
         pv["apiVersion"] = json!("v1");
         pv["kind"] = json!("PersistentVolume");
         pv["metadata"]["uid"] = json!(uid);
-        pv["metadata"]["resourceVersion"] = json!("1");
+        pv["metadata"]["resourceVersion"] = json!(version.to_string());
         pv["metadata"]["creationTimestamp"] = json!(now);
         pv["metadata"]["selfLink"] = json!(format!("/api/v1/persistentvolumes/{}", name));
         
@@ -113,6 +122,8 @@ impl PersistentVolumeStore {
             pv["metadata"]["annotations"] = annotations;
         }
 
+        watch_store::record(&tx.db, "persistentvolumes", "ADDED", &pv).await?;
+        tx.db.commit_write().await?;
         Ok(pv)
     }
 
@@ -128,7 +139,7 @@ impl PersistentVolumeStore {
         
         let row = sqlx::query(query)
             .bind(name)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&self.db)
             .await?;
 
         match row {
@@ -235,19 +246,24 @@ impl PersistentVolumeStore {
     }
 
     pub async fn list(&self) -> Result<Value> {
-        let query = r#"
-            SELECT uid, name, capacity, access_modes, reclaim_policy, storage_class_name, volume_mode,
-                   host_path, nfs, local, csi, phase, message, reason,
-                   claim_namespace, claim_name, claim_uid,
-                   labels, annotations, resource_version, creation_timestamp 
-            FROM persistent_volumes 
-            WHERE deletion_timestamp IS NULL 
-            ORDER BY name
-        "#;
+        self.list_matching(&ListSelector::default()).await
+    }
 
-        let rows = sqlx::query(query)
-            .fetch_all(&self.pool)
-            .await?;
+    pub async fn list_matching(&self, selector: &ListSelector) -> Result<Value> {
+        let query = format!(
+            r#"
+                SELECT uid, name, capacity, access_modes, reclaim_policy, storage_class_name, volume_mode,
+                       host_path, nfs, local, csi, phase, message, reason,
+                       claim_namespace, claim_name, claim_uid,
+                       labels, annotations, resource_version, creation_timestamp 
+                FROM persistent_volumes 
+                WHERE deletion_timestamp IS NULL{} 
+                ORDER BY name
+            "#,
+            selector.sql(field_selector::CLUSTER_SCOPED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -391,10 +407,12 @@ impl PersistentVolumeStore {
             UPDATE persistent_volumes 
             SET capacity = ?1, access_modes = ?2, reclaim_policy = ?3, storage_class_name = ?4,
                 volume_mode = ?5, host_path = ?6, nfs = ?7, local = ?8, csi = ?9,
-                labels = ?10, annotations = ?11, resource_version = resource_version + 1
-            WHERE name = ?12 AND deletion_timestamp IS NULL
+                labels = ?10, annotations = ?11, resource_version = ?12
+            WHERE name = ?13 AND deletion_timestamp IS NULL
         "#;
 
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         let rows_affected = sqlx::query(update_query)
             .bind(capacity.to_string())
             .bind(access_modes.to_string())
@@ -407,8 +425,9 @@ impl PersistentVolumeStore {
             .bind(csi.as_ref().map(|v| v.to_string()))
             .bind(labels.to_string())
             .bind(annotations.to_string())
+            .bind(version)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?
             .rows_affected();
 
@@ -416,7 +435,10 @@ impl PersistentVolumeStore {
             return Err(anyhow!("PersistentVolume {} not found", name));
         }
 
-        self.get(name).await
+        let pv = tx.get(name).await?;
+        watch_store::record(&tx.db, "persistentvolumes", "MODIFIED", &pv).await?;
+        tx.db.commit_write().await?;
+        Ok(pv)
     }
 
     pub async fn delete(&self, name: &str) -> Result<Value> {
@@ -424,15 +446,18 @@ impl PersistentVolumeStore {
         let pv = self.get(name).await?;
 
         // Soft delete
-        let deletion_timestamp = Utc::now().to_rfc3339();
+        let deletion_timestamp = time::now();
         let delete_query = "UPDATE persistent_volumes SET deletion_timestamp = ?1 WHERE name = ?2";
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(delete_query)
             .bind(&deletion_timestamp)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        watch_store::record(&tx.db, "persistentvolumes", "DELETED", &pv).await?;
+        tx.db.commit_write().await?;
         Ok(pv)
     }
 
@@ -440,16 +465,19 @@ impl PersistentVolumeStore {
         let update_query = r#"
             UPDATE persistent_volumes 
             SET phase = 'Bound', claim_namespace = ?1, claim_name = ?2, claim_uid = ?3,
-                resource_version = resource_version + 1
-            WHERE name = ?4 AND phase = 'Available' AND deletion_timestamp IS NULL
+                resource_version = ?4
+            WHERE name = ?5 AND phase = 'Available' AND deletion_timestamp IS NULL
         "#;
 
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         let rows_affected = sqlx::query(update_query)
             .bind(claim_namespace)
             .bind(claim_name)
             .bind(claim_uid)
+            .bind(version)
             .bind(pv_name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?
             .rows_affected();
 
@@ -457,6 +485,9 @@ impl PersistentVolumeStore {
             return Err(anyhow!("Failed to bind PersistentVolume {} - not available", pv_name));
         }
 
+        let pv = tx.get(pv_name).await?;
+        watch_store::record(&tx.db, "persistentvolumes", "MODIFIED", &pv).await?;
+        tx.db.commit_write().await?;
         Ok(())
     }
 
@@ -464,15 +495,24 @@ impl PersistentVolumeStore {
         let update_query = r#"
             UPDATE persistent_volumes 
             SET phase = 'Released', claim_namespace = NULL, claim_name = NULL, claim_uid = NULL,
-                resource_version = resource_version + 1
-            WHERE name = ?1 AND phase = 'Bound' AND deletion_timestamp IS NULL
+                resource_version = ?1
+            WHERE name = ?2 AND phase = 'Bound' AND deletion_timestamp IS NULL
         "#;
 
-        sqlx::query(update_query)
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let updated = sqlx::query(update_query)
+            .bind(version)
             .bind(pv_name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        if updated.rows_affected() > 0 {
+            let pv = tx.get(pv_name).await?;
+            watch_store::record(&tx.db, "persistentvolumes", "MODIFIED", &pv).await?;
+        }
+
+        tx.db.commit_write().await?;
         Ok(())
     }
 }
\ No newline at end of file
@@ -1,16 +1,22 @@
 use anyhow::{anyhow, Result};
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::time;
+
 pub struct PersistentVolumeClaimStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl PersistentVolumeClaimStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut pvc: Value) -> Result<Value> {
@@ -20,7 +26,7 @@ impl PersistentVolumeClaimStore {
             .ok_or_else(|| anyhow!("PersistentVolumeClaim name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         // Extract spec fields
         let access_modes = pvc["spec"]["accessModes"].clone();
@@ -61,9 +67,11 @@ impl PersistentVolumeClaimStore {
                 volume_mode, volume_name, selector, phase, labels, annotations, 
                 resource_version, creation_timestamp
             )
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, 1, ?13)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
         "#;
         
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(query)
             .bind(&uid)
             .bind(namespace)
@@ -77,8 +85,9 @@ impl PersistentVolumeClaimStore {
             .bind("Pending")
             .bind(labels.to_string())
             .bind(annotations.to_string())
+            .bind(version)
             .bind(&now)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
         // Build response
@@ -86,7 +95,7 @@ impl PersistentVolumeClaimStore {
         pvc["kind"] = json!("PersistentVolumeClaim");
         pvc["metadata"]["uid"] = json!(uid);
         pvc["metadata"]["namespace"] = json!(namespace);
-        pvc["metadata"]["resourceVersion"] = json!("1");
+        pvc["metadata"]["resourceVersion"] = json!(version.to_string());
         pvc["metadata"]["creationTimestamp"] = json!(now);
         pvc["metadata"]["selfLink"] = json!(format!("/api/v1/namespaces/{}/persistentvolumeclaims/{}", namespace, name));
         
@@ -106,6 +115,8 @@ impl PersistentVolumeClaimStore {
             pvc["metadata"]["annotations"] = annotations;
         }
 
+        watch_store::record(&tx.db, "persistentvolumeclaims", "ADDED", &pvc).await?;
+        tx.db.commit_write().await?;
         Ok(pvc)
     }
 
@@ -121,7 +132,7 @@ impl PersistentVolumeClaimStore {
         let row = sqlx::query(query)
             .bind(namespace)
             .bind(name)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&self.db)
             .await?;
 
         match row {
@@ -206,36 +217,24 @@ impl PersistentVolumeClaimStore {
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if namespace.is_some() {
-            r#"
-                SELECT uid, namespace, name, access_modes, resources, storage_class_name, volume_mode,
-                       volume_name, selector, phase, access_modes_status, capacity,
-                       labels, annotations, resource_version, creation_timestamp 
-                FROM persistent_volume_claims 
-                WHERE namespace = ?1 AND deletion_timestamp IS NULL 
-                ORDER BY name
-            "#
-        } else {
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
             r#"
                 SELECT uid, namespace, name, access_modes, resources, storage_class_name, volume_mode,
                        volume_name, selector, phase, access_modes_status, capacity,
                        labels, annotations, resource_version, creation_timestamp 
                 FROM persistent_volume_claims 
-                WHERE deletion_timestamp IS NULL 
+                WHERE deletion_timestamp IS NULL{} 
                 ORDER BY namespace, name
-            "#
-        };
-
-        let rows = if let Some(ns) = namespace {
-            sqlx::query(query)
-                .bind(ns)
-                .fetch_all(&self.pool)
-                .await?
-        } else {
-            sqlx::query(query)
-                .fetch_all(&self.pool)
-                .await?
-        };
+            "#,
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -367,10 +366,12 @@ impl PersistentVolumeClaimStore {
             UPDATE persistent_volume_claims 
             SET access_modes = ?1, resources = ?2, storage_class_name = ?3, volume_mode = ?4,
                 volume_name = ?5, selector = ?6, labels = ?7, annotations = ?8, 
-                resource_version = resource_version + 1
-            WHERE namespace = ?9 AND name = ?10 AND deletion_timestamp IS NULL
+                resource_version = ?9
+            WHERE namespace = ?10 AND name = ?11 AND deletion_timestamp IS NULL
         "#;
 
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         let rows_affected = sqlx::query(update_query)
             .bind(access_modes.to_string())
             .bind(resources.to_string())
@@ -380,9 +381,10 @@ impl PersistentVolumeClaimStore {
             .bind(selector.as_ref().map(|v| v.to_string()))
             .bind(labels.to_string())
             .bind(annotations.to_string())
+            .bind(version)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?
             .rows_affected();
 
@@ -390,7 +392,10 @@ impl PersistentVolumeClaimStore {
             return Err(anyhow!("PersistentVolumeClaim {}/{} not found", namespace, name));
         }
 
-        self.get(namespace, name).await
+        let pvc = tx.get(namespace, name).await?;
+        watch_store::record(&tx.db, "persistentvolumeclaims", "MODIFIED", &pvc).await?;
+        tx.db.commit_write().await?;
+        Ok(pvc)
     }
 
     pub async fn delete(&self, namespace: &str, name: &str) -> Result<Value> {
@@ -398,16 +403,19 @@ impl PersistentVolumeClaimStore {
         let pvc = self.get(namespace, name).await?;
 
         // Soft delete
-        let deletion_timestamp = Utc::now().to_rfc3339();
+        let deletion_timestamp = time::now();
         let delete_query = "UPDATE persistent_volume_claims SET deletion_timestamp = ?1 WHERE namespace = ?2 AND name = ?3";
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(delete_query)
             .bind(&deletion_timestamp)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        watch_store::record(&tx.db, "persistentvolumeclaims", "DELETED", &pvc).await?;
+        tx.db.commit_write().await?;
         Ok(pvc)
     }
 
@@ -415,16 +423,19 @@ impl PersistentVolumeClaimStore {
         let update_query = r#"
             UPDATE persistent_volume_claims 
             SET phase = 'Bound', volume_name = ?1, capacity = ?2,
-                resource_version = resource_version + 1
-            WHERE namespace = ?3 AND name = ?4 AND phase = 'Pending' AND deletion_timestamp IS NULL
+                resource_version = ?3
+            WHERE namespace = ?4 AND name = ?5 AND phase = 'Pending' AND deletion_timestamp IS NULL
         "#;
 
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         let rows_affected = sqlx::query(update_query)
             .bind(volume_name)
             .bind(capacity.to_string())
+            .bind(version)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?
             .rows_affected();
 
@@ -432,22 +443,34 @@ impl PersistentVolumeClaimStore {
             return Err(anyhow!("Failed to bind PersistentVolumeClaim {}/{} - not pending", namespace, name));
         }
 
+        let pvc = tx.get(namespace, name).await?;
+        watch_store::record(&tx.db, "persistentvolumeclaims", "MODIFIED", &pvc).await?;
+        tx.db.commit_write().await?;
         Ok(())
     }
 
     pub async fn unbind(&self, namespace: &str, name: &str) -> Result<()> {
         let update_query = r#"
             UPDATE persistent_volume_claims 
-            SET phase = 'Lost', resource_version = resource_version + 1
-            WHERE namespace = ?1 AND name = ?2 AND phase = 'Bound' AND deletion_timestamp IS NULL
+            SET phase = 'Lost', resource_version = ?1
+            WHERE namespace = ?2 AND name = ?3 AND phase = 'Bound' AND deletion_timestamp IS NULL
         "#;
 
-        sqlx::query(update_query)
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let updated = sqlx::query(update_query)
+            .bind(version)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        if updated.rows_affected() > 0 {
+            let pvc = tx.get(namespace, name).await?;
+            watch_store::record(&tx.db, "persistentvolumeclaims", "MODIFIED", &pvc).await?;
+        }
+
+        tx.db.commit_write().await?;
         Ok(())
     }
 }
\ No newline at end of file
@@ -1,28 +1,36 @@
 use anyhow::Result;
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::time;
+
 // Role Store
 pub struct RoleStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl RoleStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut role: Value) -> Result<Value> {
         let uid = Uuid::new_v4().to_string();
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         
         // Set metadata fields
         role["metadata"]["uid"] = json!(uid);
         role["metadata"]["namespace"] = json!(namespace);
         role["metadata"]["creationTimestamp"] = json!(now);
-        role["metadata"]["resourceVersion"] = json!("1");
+        role["metadata"]["resourceVersion"] = json!(version.to_string());
         
         let name = role["metadata"]["name"].as_str().unwrap();
         let rules = role["rules"].to_string();
@@ -39,11 +47,13 @@ impl RoleStore {
         .bind(rules)
         .bind(labels)
         .bind(annotations)
-        .bind(1i64)
+        .bind(version)
         .bind(&now)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
+        watch_store::record(&tx.db, "roles", "ADDED", &role).await?;
+        tx.db.commit_write().await?;
         Ok(role)
     }
     
@@ -53,7 +63,7 @@ impl RoleStore {
         )
         .bind(namespace)
         .bind(name)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
         
         match row {
@@ -90,20 +100,17 @@ impl RoleStore {
     }
     
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let rows = if let Some(ns) = namespace {
-            sqlx::query(
-                "SELECT * FROM roles WHERE namespace = ? AND deletion_timestamp IS NULL ORDER BY creation_timestamp DESC"
-            )
-            .bind(ns)
-            .fetch_all(&self.pool)
-            .await?
-        } else {
-            sqlx::query(
-                "SELECT * FROM roles WHERE deletion_timestamp IS NULL ORDER BY creation_timestamp DESC"
-            )
-            .fetch_all(&self.pool)
-            .await?
-        };
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
+            "SELECT * FROM roles WHERE deletion_timestamp IS NULL{} ORDER BY creation_timestamp DESC",
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
         
         let mut items = Vec::new();
         for row in rows {
@@ -148,12 +155,9 @@ impl RoleStore {
     pub async fn update(&self, namespace: &str, name: &str, mut role: Value) -> Result<Value> {
         let current = self.get(namespace, name).await?;
         let uid = current["metadata"]["uid"].as_str().unwrap();
-        let current_version: i64 = current["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse()?;
         
-        let new_version = current_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_version = resource_version::next(&tx.db).await?;
         
         // Update metadata
         role["metadata"]["resourceVersion"] = json!(new_version.to_string());
@@ -172,9 +176,11 @@ impl RoleStore {
         .bind(annotations)
         .bind(new_version)
         .bind(uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
+        watch_store::record(&tx.db, "roles", "MODIFIED", &role).await?;
+        tx.db.commit_write().await?;
         Ok(role)
     }
     
@@ -182,41 +188,46 @@ impl RoleStore {
         let mut role = self.get(namespace, name).await?;
         let uid = role["metadata"]["uid"].as_str().unwrap().to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         // Set deletion timestamp in the object
         role["metadata"]["deletionTimestamp"] = json!(now.clone());
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
             "UPDATE roles SET deletion_timestamp = ? WHERE uid = ?"
         )
         .bind(&now)
         .bind(&uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
+        watch_store::record(&tx.db, "roles", "DELETED", &role).await?;
+        tx.db.commit_write().await?;
         Ok(role)
     }
 }
 
 // RoleBinding Store
 pub struct RoleBindingStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl RoleBindingStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut rolebinding: Value) -> Result<Value> {
         let uid = Uuid::new_v4().to_string();
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         
         rolebinding["metadata"]["uid"] = json!(uid);
         rolebinding["metadata"]["namespace"] = json!(namespace);
         rolebinding["metadata"]["creationTimestamp"] = json!(now);
-        rolebinding["metadata"]["resourceVersion"] = json!("1");
+        rolebinding["metadata"]["resourceVersion"] = json!(version.to_string());
         
         let name = rolebinding["metadata"]["name"].as_str().unwrap();
         let subjects = rolebinding["subjects"].to_string();
@@ -235,11 +246,13 @@ impl RoleBindingStore {
         .bind(role_ref)
         .bind(labels)
         .bind(annotations)
-        .bind(1i64)
+        .bind(version)
         .bind(&now)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
+        watch_store::record(&tx.db, "rolebindings", "ADDED", &rolebinding).await?;
+        tx.db.commit_write().await?;
         Ok(rolebinding)
     }
     
@@ -249,7 +262,7 @@ impl RoleBindingStore {
         )
         .bind(namespace)
         .bind(name)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
         
         match row {
@@ -281,20 +294,17 @@ impl RoleBindingStore {
     }
     
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let rows = if let Some(ns) = namespace {
-            sqlx::query(
-                "SELECT * FROM rolebindings WHERE namespace = ? AND deletion_timestamp IS NULL"
-            )
-            .bind(ns)
-            .fetch_all(&self.pool)
-            .await?
-        } else {
-            sqlx::query(
-                "SELECT * FROM rolebindings WHERE deletion_timestamp IS NULL"
-            )
-            .fetch_all(&self.pool)
-            .await?
-        };
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
+            "SELECT * FROM rolebindings WHERE deletion_timestamp IS NULL{}",
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
         
         let mut items = Vec::new();
         for row in rows {
@@ -329,38 +339,43 @@ impl RoleBindingStore {
         let mut rolebinding = self.get(namespace, name).await?;
         let uid = rolebinding["metadata"]["uid"].as_str().unwrap().to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         rolebinding["metadata"]["deletionTimestamp"] = json!(now.clone());
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
             "UPDATE rolebindings SET deletion_timestamp = ? WHERE uid = ?"
         )
         .bind(&now)
         .bind(&uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
+        watch_store::record(&tx.db, "rolebindings", "DELETED", &rolebinding).await?;
+        tx.db.commit_write().await?;
         Ok(rolebinding)
     }
 }
 
 // ClusterRole Store
 pub struct ClusterRoleStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl ClusterRoleStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, mut clusterrole: Value) -> Result<Value> {
         let uid = Uuid::new_v4().to_string();
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         
         clusterrole["metadata"]["uid"] = json!(uid);
         clusterrole["metadata"]["creationTimestamp"] = json!(now);
-        clusterrole["metadata"]["resourceVersion"] = json!("1");
+        clusterrole["metadata"]["resourceVersion"] = json!(version.to_string());
         
         let name = clusterrole["metadata"]["name"].as_str().unwrap();
         let rules = clusterrole["rules"].to_string();
@@ -378,11 +393,13 @@ impl ClusterRoleStore {
         .bind(aggregation_rule)
         .bind(labels)
         .bind(annotations)
-        .bind(1i64)
+        .bind(version)
         .bind(&now)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
+        watch_store::record(&tx.db, "clusterroles", "ADDED", &clusterrole).await?;
+        tx.db.commit_write().await?;
         Ok(clusterrole)
     }
     
@@ -391,7 +408,7 @@ impl ClusterRoleStore {
             "SELECT * FROM clusterroles WHERE name = ? AND deletion_timestamp IS NULL"
         )
         .bind(name)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
         
         match row {
@@ -421,11 +438,16 @@ impl ClusterRoleStore {
     }
     
     pub async fn list(&self) -> Result<Value> {
-        let rows = sqlx::query(
-            "SELECT * FROM clusterroles WHERE deletion_timestamp IS NULL"
-        )
-        .fetch_all(&self.pool)
-        .await?;
+        self.list_matching(&ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, selector: &ListSelector) -> Result<Value> {
+        let query = format!(
+            "SELECT * FROM clusterroles WHERE deletion_timestamp IS NULL{}",
+            selector.sql(field_selector::CLUSTER_SCOPED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
         
         let mut items = Vec::new();
         for row in rows {
@@ -457,12 +479,9 @@ impl ClusterRoleStore {
     pub async fn update(&self, name: &str, mut clusterrole: Value) -> Result<Value> {
         let current = self.get(name).await?;
         let uid = current["metadata"]["uid"].as_str().unwrap();
-        let current_version: i64 = current["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse()?;
         
-        let new_version = current_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_version = resource_version::next(&tx.db).await?;
         clusterrole["metadata"]["resourceVersion"] = json!(new_version.to_string());
         clusterrole["metadata"]["uid"] = json!(uid);
         
@@ -474,9 +493,11 @@ impl ClusterRoleStore {
         .bind(rules)
         .bind(new_version)
         .bind(uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
+        watch_store::record(&tx.db, "clusterroles", "MODIFIED", &clusterrole).await?;
+        tx.db.commit_write().await?;
         Ok(clusterrole)
     }
     
@@ -484,38 +505,43 @@ impl ClusterRoleStore {
         let mut clusterrole = self.get(name).await?;
         let uid = clusterrole["metadata"]["uid"].as_str().unwrap().to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         clusterrole["metadata"]["deletionTimestamp"] = json!(now.clone());
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
             "UPDATE clusterroles SET deletion_timestamp = ? WHERE uid = ?"
         )
         .bind(&now)
         .bind(&uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
+        watch_store::record(&tx.db, "clusterroles", "DELETED", &clusterrole).await?;
+        tx.db.commit_write().await?;
         Ok(clusterrole)
     }
 }
 
 // ClusterRoleBinding Store
 pub struct ClusterRoleBindingStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl ClusterRoleBindingStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, mut clusterrolebinding: Value) -> Result<Value> {
         let uid = Uuid::new_v4().to_string();
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         
         clusterrolebinding["metadata"]["uid"] = json!(uid);
         clusterrolebinding["metadata"]["creationTimestamp"] = json!(now);
-        clusterrolebinding["metadata"]["resourceVersion"] = json!("1");
+        clusterrolebinding["metadata"]["resourceVersion"] = json!(version.to_string());
         
         let name = clusterrolebinding["metadata"]["name"].as_str().unwrap();
         let subjects = clusterrolebinding["subjects"].to_string();
@@ -531,11 +557,13 @@ impl ClusterRoleBindingStore {
         .bind(role_ref)
         .bind(clusterrolebinding["metadata"]["labels"].to_string())
         .bind(clusterrolebinding["metadata"]["annotations"].to_string())
-        .bind(1i64)
+        .bind(version)
         .bind(&now)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
+        watch_store::record(&tx.db, "clusterrolebindings", "ADDED", &clusterrolebinding).await?;
+        tx.db.commit_write().await?;
         Ok(clusterrolebinding)
     }
     
@@ -544,7 +572,7 @@ impl ClusterRoleBindingStore {
             "SELECT * FROM clusterrolebindings WHERE name = ? AND deletion_timestamp IS NULL"
         )
         .bind(name)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
         
         match row {
@@ -569,11 +597,16 @@ impl ClusterRoleBindingStore {
     }
     
     pub async fn list(&self) -> Result<Value> {
-        let rows = sqlx::query(
-            "SELECT * FROM clusterrolebindings WHERE deletion_timestamp IS NULL"
-        )
-        .fetch_all(&self.pool)
-        .await?;
+        self.list_matching(&ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, selector: &ListSelector) -> Result<Value> {
+        let query = format!(
+            "SELECT * FROM clusterrolebindings WHERE deletion_timestamp IS NULL{}",
+            selector.sql(field_selector::CLUSTER_SCOPED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
         
         let mut items = Vec::new();
         for row in rows {
@@ -607,17 +640,20 @@ impl ClusterRoleBindingStore {
         let mut clusterrolebinding = self.get(name).await?;
         let uid = clusterrolebinding["metadata"]["uid"].as_str().unwrap().to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         clusterrolebinding["metadata"]["deletionTimestamp"] = json!(now.clone());
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
             "UPDATE clusterrolebindings SET deletion_timestamp = ? WHERE uid = ?"
         )
         .bind(&now)
         .bind(&uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
+        watch_store::record(&tx.db, "clusterrolebindings", "DELETED", &clusterrolebinding).await?;
+        tx.db.commit_write().await?;
         Ok(clusterrolebinding)
     }
 }
@@ -1,9 +1,17 @@
 use anyhow::{anyhow, Result};
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::sqlite::SqliteRow;
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::compression;
+use super::db::Db;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::replicas;
+use crate::models::time;
+
 fn merge_json(target: &mut Value, patch: &Value) {
     match (target, patch) {
         (Value::Object(target_map), Value::Object(patch_map)) => {
@@ -23,13 +31,20 @@ fn merge_json(target: &mut Value, patch: &Value) {
     }
 }
 
+// Field labels replica sets can be selected on
+const FIELDS: &[(&str, &str)] = &[
+    ("metadata.name", "name"),
+    ("metadata.namespace", "namespace"),
+    ("status.replicas", "COALESCE(json_extract(status, '$.replicas'), 0)"),
+];
+
 pub struct ReplicaSetStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl ReplicaSetStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut replicaset: Value) -> Result<Value> {
@@ -39,20 +54,19 @@ impl ReplicaSetStore {
             .ok_or_else(|| anyhow!("ReplicaSet name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         
         // Set metadata fields
         replicaset["metadata"]["uid"] = json!(uid);
         replicaset["metadata"]["namespace"] = json!(namespace);
-        replicaset["metadata"]["resourceVersion"] = json!("1");
+        replicaset["metadata"]["resourceVersion"] = json!(version.to_string());
         replicaset["metadata"]["creationTimestamp"] = json!(now);
         replicaset["metadata"]["generation"] = json!(1);
         replicaset["metadata"]["selfLink"] = json!(format!("/apis/apps/v1/namespaces/{}/replicasets/{}", namespace, name));
         
-        // Set default replicas if not specified
-        if replicaset["spec"]["replicas"].is_null() {
-            replicaset["spec"]["replicas"] = json!(1);
-        }
+        replicas::set_default(&mut replicaset["spec"]);
         
         // Set default status
         replicaset["status"] = json!({
@@ -65,10 +79,19 @@ impl ReplicaSetStore {
         });
         
         let labels = replicaset["metadata"]["labels"].to_string();
-        let annotations = replicaset["metadata"]["annotations"].to_string();
-        let spec = replicaset["spec"].to_string();
+        let annotations = compression::encode(&replicaset["metadata"]["annotations"]);
+        let spec = compression::encode(&replicaset["spec"]);
+        // Never compressed: the status.replicas field selector looks into it
         let status = replicaset["status"].to_string();
         let owner_references = replicaset["metadata"]["ownerReferences"].to_string();
+
+        // A deleted ReplicaSet keeps its row, which would block reusing the
+        // name, as a Deployment does going back to a template it trimmed
+        sqlx::query("DELETE FROM replicasets WHERE namespace = ?1 AND name = ?2 AND deletion_timestamp IS NOT NULL")
+            .bind(namespace)
+            .bind(&name)
+            .execute(&tx.db)
+            .await?;
         
         sqlx::query(
             "INSERT INTO replicasets (uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, owner_references, replicas)
@@ -77,130 +100,57 @@ impl ReplicaSetStore {
         .bind(&uid)
         .bind(&name)
         .bind(namespace)
-        .bind(1i64)
+        .bind(version)
         .bind(&now)
         .bind(&labels)
         .bind(&annotations)
         .bind(&spec)
         .bind(&status)
         .bind(&owner_references)
-        .bind(replicaset["spec"]["replicas"].as_i64().unwrap_or(1))
-        .execute(&self.pool)
+        .bind(replicas::desired(&replicaset["spec"]))
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        self.record_event("replicasets", &uid, &name, namespace, "ADDED", 1, &replicaset).await?;
+        watch_store::record(&tx.db, "replicasets", "ADDED", &replicaset).await?;
         
+        tx.db.commit_write().await?;
         Ok(replicaset)
     }
 
     pub async fn get(&self, namespace: &str, name: &str) -> Result<Value> {
         let row = sqlx::query(
-            "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, owner_references
+            "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, owner_references, generation
              FROM replicasets WHERE name = ? AND namespace = ? AND deletion_timestamp IS NULL"
         )
         .bind(name)
         .bind(namespace)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
         
         match row {
-            Some(row) => {
-                let mut replicaset = json!({
-                    "apiVersion": "apps/v1",
-                    "kind": "ReplicaSet",
-                    "metadata": {
-                        "uid": row.get::<String, _>("uid"),
-                        "name": row.get::<String, _>("name"),
-                        "namespace": row.get::<String, _>("namespace"),
-                        "resourceVersion": row.get::<i64, _>("resource_version").to_string(),
-                        "creationTimestamp": row.get::<String, _>("creation_timestamp"),
-                        "generation": 1,
-                        "selfLink": format!("/apis/apps/v1/namespaces/{}/replicasets/{}", namespace, name)
-                    },
-                    "spec": serde_json::from_str::<Value>(&row.get::<String, _>("spec"))?,
-                    "status": serde_json::from_str::<Value>(&row.get::<String, _>("status"))?
-                });
-                
-                if let Ok(labels) = serde_json::from_str::<Value>(&row.get::<String, _>("labels")) {
-                    if !labels.is_null() {
-                        replicaset["metadata"]["labels"] = labels;
-                    }
-                }
-                
-                if let Ok(annotations) = serde_json::from_str::<Value>(&row.get::<String, _>("annotations")) {
-                    if !annotations.is_null() {
-                        replicaset["metadata"]["annotations"] = annotations;
-                    }
-                }
-                
-                if let Ok(owner_refs) = serde_json::from_str::<Value>(&row.get::<String, _>("owner_references")) {
-                    if !owner_refs.is_null() {
-                        replicaset["metadata"]["ownerReferences"] = owner_refs;
-                    }
-                }
-                
-                Ok(replicaset)
-            }
+            Some(row) => replicaset_from_row(&row),
             None => Err(anyhow!("ReplicaSet not found"))
         }
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if let Some(ns) = namespace {
-            sqlx::query(
-                "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, owner_references
-                 FROM replicasets WHERE namespace = ? AND deletion_timestamp IS NULL"
-            )
-            .bind(ns)
-        } else {
-            sqlx::query(
-                "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, owner_references
-                 FROM replicasets WHERE deletion_timestamp IS NULL"
-            )
-        };
-        
-        let rows = query.fetch_all(&self.pool).await?;
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
+            "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, owner_references, generation
+             FROM replicasets WHERE deletion_timestamp IS NULL{}",
+            selector.sql(FIELDS)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
         
         let mut items = Vec::new();
         for row in rows {
-            let mut replicaset = json!({
-                "apiVersion": "apps/v1",
-                "kind": "ReplicaSet",
-                "metadata": {
-                    "uid": row.get::<String, _>("uid"),
-                    "name": row.get::<String, _>("name"),
-                    "namespace": row.get::<String, _>("namespace"),
-                    "resourceVersion": row.get::<i64, _>("resource_version").to_string(),
-                    "creationTimestamp": row.get::<String, _>("creation_timestamp"),
-                    "generation": 1,
-                    "selfLink": format!("/apis/apps/v1/namespaces/{}/replicasets/{}", 
-                        row.get::<String, _>("namespace"), 
-                        row.get::<String, _>("name"))
-                },
-                "spec": serde_json::from_str::<Value>(&row.get::<String, _>("spec"))?,
-                "status": serde_json::from_str::<Value>(&row.get::<String, _>("status"))?
-            });
-            
-            if let Ok(labels) = serde_json::from_str::<Value>(&row.get::<String, _>("labels")) {
-                if !labels.is_null() {
-                    replicaset["metadata"]["labels"] = labels;
-                }
-            }
-            
-            if let Ok(annotations) = serde_json::from_str::<Value>(&row.get::<String, _>("annotations")) {
-                if !annotations.is_null() {
-                    replicaset["metadata"]["annotations"] = annotations;
-                }
-            }
-            
-            if let Ok(owner_refs) = serde_json::from_str::<Value>(&row.get::<String, _>("owner_references")) {
-                if !owner_refs.is_null() {
-                    replicaset["metadata"]["ownerReferences"] = owner_refs;
-                }
-            }
-            
-            items.push(replicaset);
+            items.push(replicaset_from_row(&row)?);
         }
         
         Ok(json!({
@@ -213,17 +163,31 @@ impl ReplicaSetStore {
         }))
     }
 
+    /// The ReplicaSets with these uids that exist, oldest first, read in one
+    /// query however many there are.
+    pub async fn get_many_by_uids(&self, uids: &[String]) -> Result<Vec<Value>> {
+        if uids.is_empty() {
+            return Ok(Vec::new());
+        }
+        let rows = sqlx::query(
+            "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, owner_references, generation
+             FROM replicasets WHERE uid IN (SELECT value FROM json_each(?)) AND deletion_timestamp IS NULL
+             ORDER BY creation_timestamp, rowid"
+        )
+        .bind(json!(uids).to_string())
+        .fetch_all(&self.db)
+        .await?;
+        rows.iter().map(replicaset_from_row).collect()
+    }
+
     pub async fn update(&self, namespace: &str, name: &str, mut replicaset: Value) -> Result<Value> {
         // Get existing ReplicaSet to preserve UID and creation timestamp
         let existing = self.get(namespace, name).await?;
         let uid = existing["metadata"]["uid"].as_str().unwrap();
         let creation_timestamp = existing["metadata"]["creationTimestamp"].as_str().unwrap();
-        let current_version: i64 = existing["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse()?;
         
-        let new_version = current_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_version = resource_version::next(&tx.db).await?;
         let new_generation = existing["metadata"]["generation"].as_i64().unwrap() + 1;
         
         // Update metadata
@@ -237,28 +201,31 @@ impl ReplicaSetStore {
         
         // Preserve status
         replicaset["status"] = existing["status"].clone();
+        replicas::set_default(&mut replicaset["spec"]);
         
         let labels = replicaset["metadata"]["labels"].to_string();
-        let annotations = replicaset["metadata"]["annotations"].to_string();
-        let spec = replicaset["spec"].to_string();
+        let annotations = compression::encode(&replicaset["metadata"]["annotations"]);
+        let spec = compression::encode(&replicaset["spec"]);
         let owner_references = replicaset["metadata"]["ownerReferences"].to_string();
         
         sqlx::query(
-            "UPDATE replicasets SET spec = ?, labels = ?, annotations = ?, owner_references = ?, resource_version = ?, generation = ? WHERE uid = ?"
+            "UPDATE replicasets SET spec = ?, replicas = ?, labels = ?, annotations = ?, owner_references = ?, resource_version = ?, generation = ? WHERE uid = ?"
         )
         .bind(&spec)
+        .bind(replicas::desired(&replicaset["spec"]))
         .bind(&labels)
         .bind(&annotations)
         .bind(&owner_references)
         .bind(new_version)
         .bind(new_generation)
         .bind(uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        self.record_event("replicasets", uid, name, namespace, "MODIFIED", new_version, &replicaset).await?;
+        watch_store::record(&tx.db, "replicasets", "MODIFIED", &replicaset).await?;
         
+        tx.db.commit_write().await?;
         Ok(replicaset)
     }
 
@@ -286,85 +253,56 @@ impl ReplicaSetStore {
             .as_str()
             .ok_or_else(|| anyhow!("Missing UID"))?
             .to_string();
-        let current_version: i64 = replicaset["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse()?;
         
-        let new_version = current_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_version = resource_version::next(&tx.db).await?;
         
         // Update replicas in spec
         replicaset["spec"]["replicas"] = json!(replicas);
         replicaset["metadata"]["resourceVersion"] = json!(new_version.to_string());
         
-        let spec = replicaset["spec"].to_string();
+        let spec = compression::encode(&replicaset["spec"]);
         
         sqlx::query(
-            "UPDATE replicasets SET spec = ?, resource_version = ? WHERE uid = ?"
+            "UPDATE replicasets SET spec = ?, replicas = ?, resource_version = ? WHERE uid = ?"
         )
         .bind(&spec)
+        .bind(replicas)
         .bind(new_version)
         .bind(&uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        self.record_event("replicasets", &uid, name, namespace, "MODIFIED", new_version, &replicaset).await?;
+        watch_store::record(&tx.db, "replicasets", "MODIFIED", &replicaset).await?;
         
-        // Return scale object
-        Ok(json!({
-            "apiVersion": "autoscaling/v1",
-            "kind": "Scale",
-            "metadata": {
-                "name": name,
-                "namespace": namespace,
-                "uid": uid,
-                "resourceVersion": new_version.to_string()
-            },
-            "spec": {
-                "replicas": replicas
-            },
-            "status": {
-                "replicas": replicaset["status"]["replicas"],
-                "selector": replicaset["spec"]["selector"]
-            }
-        }))
+        tx.db.commit_write().await?;
+        Ok(replicas::scale(&replicaset))
     }
 
     pub async fn get_scale(&self, namespace: &str, name: &str) -> Result<Value> {
         let replicaset = self.get(namespace, name).await?;
-        
-        Ok(json!({
-            "apiVersion": "autoscaling/v1",
-            "kind": "Scale",
-            "metadata": {
-                "name": name,
-                "namespace": namespace,
-                "uid": replicaset["metadata"]["uid"],
-                "resourceVersion": replicaset["metadata"]["resourceVersion"]
-            },
-            "spec": {
-                "replicas": replicaset["spec"]["replicas"]
-            },
-            "status": {
-                "replicas": replicaset["status"]["replicas"],
-                "selector": replicaset["spec"]["selector"]
-            }
-        }))
+        Ok(replicas::scale(&replicaset))
     }
 
     pub async fn update_status(&self, namespace: &str, name: &str, status: Value) -> Result<()> {
         let replicaset = self.get(namespace, name).await?;
         let uid = replicaset["metadata"]["uid"].as_str().unwrap();
-        
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+
         sqlx::query(
-            "UPDATE replicasets SET status = ? WHERE uid = ?"
+            "UPDATE replicasets SET status = ?, resource_version = ? WHERE uid = ?"
         )
         .bind(status.to_string())
+        .bind(version)
         .bind(uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
-        
+
+        let replicaset = tx.get(namespace, name).await?;
+        watch_store::record(&tx.db, "replicasets", "MODIFIED", &replicaset).await?;
+        tx.db.commit_write().await?;
         Ok(())
     }
 
@@ -372,42 +310,62 @@ impl ReplicaSetStore {
         let replicaset = self.get(namespace, name).await?;
         let uid = replicaset["metadata"]["uid"].as_str().unwrap();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
             "UPDATE replicasets SET deletion_timestamp = ? WHERE uid = ?"
         )
         .bind(&now)
         .bind(uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        let resource_version = replicaset["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse::<i64>()?;
-        self.record_event("replicasets", uid, name, namespace, "DELETED", resource_version, &replicaset).await?;
+        watch_store::record(&tx.db, "replicasets", "DELETED", &replicaset).await?;
         
+        tx.db.commit_write().await?;
         Ok(replicaset)
     }
 
-    async fn record_event(&self, resource_type: &str, uid: &str, name: &str, namespace: &str, event_type: &str, version: i64, object: &Value) -> Result<()> {
-        sqlx::query(
-            "INSERT INTO events (resource_type, resource_uid, resource_name, resource_namespace, event_type, resource_version, timestamp, object)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
-        )
-        .bind(resource_type)
-        .bind(uid)
-        .bind(name)
-        .bind(namespace)
-        .bind(event_type)
-        .bind(version)
-        .bind(Utc::now().to_rfc3339())
-        .bind(object.to_string())
-        .execute(&self.pool)
-        .await?;
-        
-        Ok(())
+}
+
+fn replicaset_from_row(row: &SqliteRow) -> Result<Value> {
+    let namespace: String = row.get("namespace");
+    let name: String = row.get("name");
+    let mut replicaset = json!({
+        "apiVersion": "apps/v1",
+        "kind": "ReplicaSet",
+        "metadata": {
+            "uid": row.get::<String, _>("uid"),
+            "name": name,
+            "namespace": namespace,
+            "resourceVersion": row.get::<i64, _>("resource_version").to_string(),
+            "creationTimestamp": row.get::<String, _>("creation_timestamp"),
+            "generation": row.get::<Option<i64>, _>("generation").unwrap_or(1),
+            "selfLink": format!("/apis/apps/v1/namespaces/{}/replicasets/{}", namespace, name)
+        },
+        "spec": compression::decode(row, "spec")?,
+        "status": compression::decode(row, "status")?
+    });
+
+    if let Ok(labels) = serde_json::from_str::<Value>(&row.get::<String, _>("labels")) {
+        if !labels.is_null() {
+            replicaset["metadata"]["labels"] = labels;
+        }
     }
-}
\ No newline at end of file
+
+    if let Ok(annotations) = compression::decode(row, "annotations") {
+        if !annotations.is_null() {
+            replicaset["metadata"]["annotations"] = annotations;
+        }
+    }
+
+    if let Ok(owner_refs) = serde_json::from_str::<Value>(&row.get::<String, _>("owner_references")) {
+        if !owner_refs.is_null() {
+            replicaset["metadata"]["ownerReferences"] = owner_refs;
+        }
+    }
+
+    Ok(replicaset)
+}
@@ -0,0 +1,102 @@
+// Resource versions come from one counter for the whole cluster, like the
+// revision of etcd: every write to any object takes the next one. Versions
+// therefore order writes across resources, and a watch can resume from any
+// version a list or an object reported.
+use anyhow::{bail, Result};
+
+use super::db::Db;
+use super::tables;
+
+/// Allocates the version for a write, in its transaction (see
+/// `Db::begin_write`) along with its row and watch event. Holding the
+/// counter until then, writes commit in the order of their versions, and a
+/// watch resuming from one can't miss an earlier one yet to commit.
+pub(crate) async fn next(db: &Db) -> Result<i64> {
+    if !db.in_transaction() {
+        bail!("resource versions are allocated in the transaction of their write");
+    }
+    let version = sqlx::query_scalar::<_, i64>(
+        "UPDATE resource_versions SET version = version + 1 WHERE id = 1 RETURNING version"
+    )
+    .fetch_one(db)
+    .await?;
+    Ok(version)
+}
+
+/// The version of the latest write.
+pub(crate) async fn current(db: &Db) -> Result<i64> {
+    let version = sqlx::query_scalar::<_, i64>("SELECT version FROM resource_versions WHERE id = 1")
+        .fetch_one(db)
+        .await?;
+    Ok(version)
+}
+
+/// The stored version of an object, looked up by the resource name used in
+/// API paths. None if the object doesn't exist or the resource is unknown.
+pub(crate) async fn of_object(db: &Db, resource: &str, namespace: Option<&str>, name: &str) -> Result<Option<i64>> {
+    let Some((table, namespaced)) = tables::table(resource) else {
+        return Ok(None);
+    };
+    let scope = if namespaced { " AND namespace = ?" } else { "" };
+    let sql = format!(
+        "SELECT resource_version FROM {} WHERE name = ?{} AND deletion_timestamp IS NULL",
+        table, scope
+    );
+
+    let mut query = sqlx::query_scalar::<_, i64>(&sql).bind(name);
+    if namespaced {
+        query = query.bind(namespace.unwrap_or("default"));
+    }
+    Ok(query.fetch_optional(db).await?)
+}
+
+/// Claims an object for a write only if it still has `uid` and is still at
+/// `version`, where given: an update that changes nothing, so only the row
+/// the precondition holds for. In a transaction, which then holds the write
+/// lock, nothing else can write the object until it ends. False if the row
+/// no longer matches or doesn't exist.
+pub(crate) async fn claim(
+    db: &Db,
+    resource: &str,
+    namespace: Option<&str>,
+    name: &str,
+    uid: Option<&str>,
+    version: Option<&str>,
+) -> Result<bool> {
+    let Some((table, namespaced)) = tables::table(resource) else {
+        return Ok(false);
+    };
+    let mut sql = format!(
+        "UPDATE {} SET resource_version = resource_version WHERE name = ? AND deletion_timestamp IS NULL",
+        table
+    );
+    if namespaced {
+        sql.push_str(" AND namespace = ?");
+    }
+    if uid.is_some() {
+        sql.push_str(" AND uid = ?");
+    }
+    let version = match version {
+        // A version that isn't one of ours matches no row
+        Some(version) => match version.parse::<i64>() {
+            Ok(version) => Some(version),
+            Err(_) => return Ok(false),
+        },
+        None => None,
+    };
+    if version.is_some() {
+        sql.push_str(" AND resource_version = ?");
+    }
+
+    let mut query = sqlx::query(&sql).bind(name);
+    if namespaced {
+        query = query.bind(namespace.unwrap_or("default"));
+    }
+    if let Some(uid) = uid {
+        query = query.bind(uid);
+    }
+    if let Some(version) = version {
+        query = query.bind(version);
+    }
+    Ok(query.execute(db).await?.rows_affected() > 0)
+}
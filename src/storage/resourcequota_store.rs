@@ -1,15 +1,22 @@
 use anyhow::Result;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::time;
+
 pub struct ResourceQuotaStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl ResourceQuotaStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut quota: Value) -> Result<Value> {
@@ -21,9 +28,13 @@ impl ResourceQuotaStore {
         let labels = quota["metadata"].get("labels").cloned().unwrap_or(json!({}));
         let annotations = quota["metadata"].get("annotations").cloned().unwrap_or(json!({}));
 
+        let now = time::now();
+
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(
-            "INSERT INTO resourcequotas (uid, name, namespace, spec, hard, scope_selector, labels, annotations, resource_version, generation)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, 1, 1)"
+            "INSERT INTO resourcequotas (uid, name, namespace, spec, hard, scope_selector, labels, annotations, resource_version, generation, creation_timestamp)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, 1, ?)"
         )
         .bind(&uid)
         .bind(&name)
@@ -33,13 +44,15 @@ impl ResourceQuotaStore {
         .bind(scope_selector.as_ref().map(|s| serde_json::to_string(s).ok()).flatten())
         .bind(serde_json::to_string(&labels)?)
         .bind(serde_json::to_string(&annotations)?)
-        .execute(&self.pool)
+        .bind(version)
+        .bind(&now)
+        .execute(&tx.db)
         .await?;
 
         quota["metadata"]["uid"] = json!(uid);
-        quota["metadata"]["resourceVersion"] = json!("1");
+        quota["metadata"]["resourceVersion"] = json!(version.to_string());
         quota["metadata"]["generation"] = json!(1);
-        quota["metadata"]["creationTimestamp"] = json!(chrono::Utc::now().to_rfc3339());
+        quota["metadata"]["creationTimestamp"] = json!(now);
         
         if quota["status"].is_null() {
             quota["status"] = json!({
@@ -48,7 +61,7 @@ impl ResourceQuotaStore {
             });
         }
 
-        self.record_event(
+        tx.record_event(
             &uid,
             "ResourceQuota",
             namespace,
@@ -57,6 +70,8 @@ impl ResourceQuotaStore {
             "ResourceQuota created"
         ).await?;
 
+        watch_store::record(&tx.db, "resourcequotas", "ADDED", &quota).await?;
+        tx.db.commit_write().await?;
         Ok(quota)
     }
 
@@ -69,7 +84,7 @@ impl ResourceQuotaStore {
         )
         .bind(namespace)
         .bind(name)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
 
         if let Some(row) = row {
@@ -97,17 +112,17 @@ impl ResourceQuotaStore {
                     "resourceVersion": resource_version.to_string(),
                     "generation": generation,
                     "creationTimestamp": creation_timestamp,
-                    "labels": serde_json::from_str(&labels)?,
-                    "annotations": serde_json::from_str(&annotations)?
+                    "labels": serde_json::from_str::<Value>(&labels)?,
+                    "annotations": serde_json::from_str::<Value>(&annotations)?
                 },
-                "spec": serde_json::from_str(&spec)?
+                "spec": serde_json::from_str::<Value>(&spec)?
             });
 
             if let Some(status_str) = status {
                 quota["status"] = serde_json::from_str(&status_str)?;
             } else {
                 quota["status"] = json!({
-                    "hard": serde_json::from_str(&hard)?,
+                    "hard": serde_json::from_str::<Value>(&hard)?,
                     "used": used.as_ref().map(|u| serde_json::from_str(u).ok()).flatten().unwrap_or(json!({}))
                 });
             }
@@ -123,8 +138,8 @@ impl ResourceQuotaStore {
             .ok_or_else(|| anyhow::anyhow!("ResourceQuota not found"))?;
 
         let uid = current["metadata"]["uid"].as_str().unwrap();
-        let resource_version: i64 = current["metadata"]["resourceVersion"].as_str().unwrap().parse()?;
-        let new_resource_version = resource_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_resource_version = resource_version::next(&tx.db).await?;
         let generation: i64 = current["metadata"]["generation"].as_i64().unwrap();
         let new_generation = if quota["spec"] != current["spec"] { generation + 1 } else { generation };
 
@@ -149,15 +164,17 @@ impl ResourceQuotaStore {
         .bind(new_generation)
         .bind(namespace)
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
         quota["metadata"]["uid"] = json!(uid);
         quota["metadata"]["resourceVersion"] = json!(new_resource_version.to_string());
         quota["metadata"]["generation"] = json!(new_generation);
         quota["metadata"]["creationTimestamp"] = current["metadata"]["creationTimestamp"].clone();
+        // Status is only written through update_status
+        quota["status"] = current["status"].clone();
 
-        self.record_event(
+        tx.record_event(
             uid,
             "ResourceQuota",
             namespace,
@@ -166,6 +183,8 @@ impl ResourceQuotaStore {
             "ResourceQuota updated"
         ).await?;
 
+        watch_store::record(&tx.db, "resourcequotas", "MODIFIED", &quota).await?;
+        tx.db.commit_write().await?;
         Ok(quota)
     }
 
@@ -174,8 +193,8 @@ impl ResourceQuotaStore {
             .ok_or_else(|| anyhow::anyhow!("ResourceQuota not found"))?;
 
         let uid = current["metadata"]["uid"].as_str().unwrap().to_string();
-        let resource_version: i64 = current["metadata"]["resourceVersion"].as_str().unwrap().parse()?;
-        let new_resource_version = resource_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_resource_version = resource_version::next(&tx.db).await?;
 
         let used = status.get("used").cloned().unwrap_or(json!({}));
 
@@ -189,13 +208,13 @@ impl ResourceQuotaStore {
         .bind(new_resource_version)
         .bind(namespace)
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
         current["metadata"]["resourceVersion"] = json!(new_resource_version.to_string());
         current["status"] = status;
 
-        self.record_event(
+        tx.record_event(
             &uid,
             "ResourceQuota",
             namespace,
@@ -204,6 +223,8 @@ impl ResourceQuotaStore {
             "ResourceQuota status updated"
         ).await?;
 
+        watch_store::record(&tx.db, "resourcequotas", "MODIFIED", &current).await?;
+        tx.db.commit_write().await?;
         Ok(current)
     }
 
@@ -213,17 +234,19 @@ impl ResourceQuotaStore {
 
         let uid = quota["metadata"]["uid"].as_str().unwrap();
 
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
             "UPDATE resourcequotas 
-             SET deletion_timestamp = CURRENT_TIMESTAMP 
+             SET deletion_timestamp = ? 
              WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL"
         )
+        .bind(time::now())
         .bind(namespace)
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
-        self.record_event(
+        tx.record_event(
             uid,
             "ResourceQuota",
             namespace,
@@ -232,28 +255,26 @@ impl ResourceQuotaStore {
             "ResourceQuota deleted"
         ).await?;
 
+        watch_store::record(&tx.db, "resourcequotas", "DELETED", &quota).await?;
+        tx.db.commit_write().await?;
         Ok(quota)
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if let Some(ns) = namespace {
-            sqlx::query(
-                "SELECT uid, name, namespace, spec, status, hard, used, scope_selector, labels, annotations,
-                 resource_version, generation, creation_timestamp
-                 FROM resourcequotas
-                 WHERE namespace = ? AND deletion_timestamp IS NULL"
-            )
-            .bind(ns)
-        } else {
-            sqlx::query(
-                "SELECT uid, name, namespace, spec, status, hard, used, scope_selector, labels, annotations,
-                 resource_version, generation, creation_timestamp
-                 FROM resourcequotas
-                 WHERE deletion_timestamp IS NULL"
-            )
-        };
-
-        let rows = query.fetch_all(&self.pool).await?;
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
+            "SELECT uid, name, namespace, spec, status, hard, used, scope_selector, labels, annotations,
+             resource_version, generation, creation_timestamp
+             FROM resourcequotas
+             WHERE deletion_timestamp IS NULL{}",
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
         let mut items = Vec::new();
 
         for row in rows {
@@ -280,17 +301,17 @@ impl ResourceQuotaStore {
                     "resourceVersion": resource_version.to_string(),
                     "generation": generation,
                     "creationTimestamp": creation_timestamp,
-                    "labels": serde_json::from_str(&labels)?,
-                    "annotations": serde_json::from_str(&annotations)?
+                    "labels": serde_json::from_str::<Value>(&labels)?,
+                    "annotations": serde_json::from_str::<Value>(&annotations)?
                 },
-                "spec": serde_json::from_str(&spec)?
+                "spec": serde_json::from_str::<Value>(&spec)?
             });
 
             if let Some(status_str) = status {
                 quota["status"] = serde_json::from_str(&status_str)?;
             } else {
                 quota["status"] = json!({
-                    "hard": serde_json::from_str(&hard)?,
+                    "hard": serde_json::from_str::<Value>(&hard)?,
                     "used": used.as_ref().map(|u| serde_json::from_str(u).ok()).flatten().unwrap_or(json!({}))
                 });
             }
@@ -307,9 +328,10 @@ impl ResourceQuotaStore {
 
     async fn record_event(&self, uid: &str, resource_type: &str, namespace: &str, name: &str, reason: &str, message: &str) -> Result<()> {
         let event_uid = Uuid::new_v4().to_string();
+        let now = time::now();
         sqlx::query(
             "INSERT INTO events (uid, namespace, involved_object_uid, involved_object_kind, involved_object_name, reason, message, event_time, first_timestamp, last_timestamp, count, type)
-             VALUES (?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, 1, 'Normal')"
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 'Normal')"
         )
         .bind(&event_uid)
         .bind(namespace)
@@ -318,7 +340,10 @@ impl ResourceQuotaStore {
         .bind(name)
         .bind(reason)
         .bind(message)
-        .execute(&self.pool)
+        .bind(&now)
+        .bind(&now)
+        .bind(&now)
+        .execute(&self.db)
         .await?;
         Ok(())
     }
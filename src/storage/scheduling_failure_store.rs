@@ -0,0 +1,79 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use sqlx::Row;
+
+use super::db::Db;
+use crate::models::time;
+
+/// Keeps the scheduler's explanation of why each pending pod couldn't be
+/// placed, as of its last attempt.
+pub struct SchedulingFailureStore {
+    db: Db,
+}
+
+impl SchedulingFailureStore {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// Records why the pod with `uid` didn't fit anywhere: the summary
+    /// `message` and, in `nodes`, each node with the reasons it was ruled out.
+    pub async fn record(&self, uid: &str, namespace: &str, name: &str, message: &str, nodes: &Value) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO scheduling_failures (uid, namespace, name, message, nodes, timestamp)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT(uid) DO UPDATE SET message = excluded.message, nodes = excluded.nodes,
+                                            timestamp = excluded.timestamp"
+        )
+        .bind(uid)
+        .bind(namespace)
+        .bind(name)
+        .bind(message)
+        .bind(nodes.to_string())
+        .bind(time::now())
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Forgets the explanations of pods that have since been bound or
+    /// deleted.
+    pub async fn prune(&self) -> Result<()> {
+        sqlx::query(
+            "DELETE FROM scheduling_failures WHERE uid NOT IN (
+                SELECT uid FROM pods WHERE node_name IS NULL AND deletion_timestamp IS NULL
+             )"
+        )
+        .execute(&self.db)
+        .await?;
+
+        Ok(())
+    }
+
+    /// Lists the explanations, optionally for one namespace or pod name.
+    pub async fn list(&self, namespace: Option<&str>, name: Option<&str>) -> Result<Vec<Value>> {
+        let rows = sqlx::query(
+            "SELECT uid, namespace, name, message, nodes, timestamp FROM scheduling_failures
+             WHERE (?1 IS NULL OR namespace = ?1) AND (?2 IS NULL OR name = ?2)
+             ORDER BY namespace, name"
+        )
+        .bind(namespace)
+        .bind(name)
+        .fetch_all(&self.db)
+        .await?;
+
+        rows.iter()
+            .map(|row| {
+                Ok(json!({
+                    "namespace": row.get::<String, _>("namespace"),
+                    "name": row.get::<String, _>("name"),
+                    "uid": row.get::<String, _>("uid"),
+                    "message": row.get::<String, _>("message"),
+                    "nodes": serde_json::from_str::<Value>(&row.get::<String, _>("nodes"))?,
+                    "lastAttempt": row.get::<String, _>("timestamp"),
+                }))
+            })
+            .collect()
+    }
+}
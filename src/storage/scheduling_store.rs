@@ -1,16 +1,23 @@
 use anyhow::Result;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::time;
+
 // PriorityClass storage
 pub struct PriorityClassStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl PriorityClassStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, mut pc: Value) -> Result<Value> {
@@ -24,10 +31,14 @@ impl PriorityClassStore {
         let labels = pc["metadata"].get("labels").cloned().unwrap_or(json!({}));
         let annotations = pc["metadata"].get("annotations").cloned().unwrap_or(json!({}));
 
+        let now = time::now();
+
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(
             "INSERT INTO priorityclasses (uid, name, value, global_default, description,
-             preemption_policy, labels, annotations, resource_version, generation)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, 1, 1)"
+             preemption_policy, labels, annotations, resource_version, generation, creation_timestamp)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, 1, ?)"
         )
         .bind(&uid)
         .bind(&name)
@@ -37,15 +48,19 @@ impl PriorityClassStore {
         .bind(preemption_policy)
         .bind(serde_json::to_string(&labels)?)
         .bind(serde_json::to_string(&annotations)?)
-        .execute(&self.pool)
+        .bind(version)
+        .bind(&now)
+        .execute(&tx.db)
         .await?;
 
         pc["metadata"]["uid"] = json!(uid);
-        pc["metadata"]["resourceVersion"] = json!("1");
+        pc["metadata"]["resourceVersion"] = json!(version.to_string());
         pc["metadata"]["generation"] = json!(1);
-        pc["metadata"]["creationTimestamp"] = json!(chrono::Utc::now().to_rfc3339());
+        pc["metadata"]["creationTimestamp"] = json!(now);
 
-        self.record_event(&uid, "PriorityClass", &name, "Created", "PriorityClass created").await?;
+        tx.record_event(&uid, "PriorityClass", &name, "Created", "PriorityClass created").await?;
+        watch_store::record(&tx.db, "priorityclasses", "ADDED", &pc).await?;
+        tx.db.commit_write().await?;
         Ok(pc)
     }
 
@@ -56,7 +71,7 @@ impl PriorityClassStore {
              FROM priorityclasses WHERE name = ? AND deletion_timestamp IS NULL"
         )
         .bind(name)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
 
         if let Some(row) = row {
@@ -81,8 +96,8 @@ impl PriorityClassStore {
                     "resourceVersion": resource_version.to_string(),
                     "generation": generation,
                     "creationTimestamp": creation_timestamp,
-                    "labels": serde_json::from_str(&labels)?,
-                    "annotations": serde_json::from_str(&annotations)?
+                    "labels": serde_json::from_str::<Value>(&labels)?,
+                    "annotations": serde_json::from_str::<Value>(&annotations)?
                 },
                 "value": value,
                 "globalDefault": global_default,
@@ -99,32 +114,71 @@ impl PriorityClassStore {
         }
     }
 
+    /// Looks up the priority value and preemption policy for a pod: the named
+    /// class if given, otherwise the global default class, if any.
+    pub async fn resolve(&self, class_name: Option<&str>) -> Result<Option<(i64, String)>> {
+        let row = match class_name {
+            Some(name) => {
+                sqlx::query(
+                    "SELECT value, preemption_policy FROM priorityclasses
+                     WHERE name = ? AND deletion_timestamp IS NULL"
+                )
+                .bind(name)
+                .fetch_optional(&self.db)
+                .await?
+            }
+            None => {
+                sqlx::query(
+                    "SELECT value, preemption_policy FROM priorityclasses
+                     WHERE global_default = TRUE AND deletion_timestamp IS NULL
+                     ORDER BY value DESC LIMIT 1"
+                )
+                .fetch_optional(&self.db)
+                .await?
+            }
+        };
+
+        Ok(row.map(|row| {
+            let policy: Option<String> = row.get("preemption_policy");
+            (row.get("value"), policy.unwrap_or_else(|| "PreemptLowerPriority".to_string()))
+        }))
+    }
+
     pub async fn delete(&self, name: &str) -> Result<Value> {
         let pc = self.get(name).await?
             .ok_or_else(|| anyhow::anyhow!("PriorityClass not found"))?;
 
         let uid = pc["metadata"]["uid"].as_str().unwrap();
 
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
-            "UPDATE priorityclasses SET deletion_timestamp = CURRENT_TIMESTAMP 
+            "UPDATE priorityclasses SET deletion_timestamp = ? 
              WHERE name = ? AND deletion_timestamp IS NULL"
         )
+        .bind(time::now())
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
-        self.record_event(uid, "PriorityClass", name, "Deleted", "PriorityClass deleted").await?;
+        tx.record_event(uid, "PriorityClass", name, "Deleted", "PriorityClass deleted").await?;
+        watch_store::record(&tx.db, "priorityclasses", "DELETED", &pc).await?;
+        tx.db.commit_write().await?;
         Ok(pc)
     }
 
     pub async fn list(&self) -> Result<Value> {
-        let rows = sqlx::query(
+        self.list_matching(&ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, selector: &ListSelector) -> Result<Value> {
+        let query = format!(
             "SELECT uid, name, value, global_default, description, preemption_policy,
              labels, annotations, resource_version, generation, creation_timestamp
-             FROM priorityclasses WHERE deletion_timestamp IS NULL"
-        )
-        .fetch_all(&self.pool)
-        .await?;
+             FROM priorityclasses WHERE deletion_timestamp IS NULL{}",
+            selector.sql(field_selector::CLUSTER_SCOPED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -149,8 +203,8 @@ impl PriorityClassStore {
                     "resourceVersion": resource_version.to_string(),
                     "generation": generation,
                     "creationTimestamp": creation_timestamp,
-                    "labels": serde_json::from_str(&labels)?,
-                    "annotations": serde_json::from_str(&annotations)?
+                    "labels": serde_json::from_str::<Value>(&labels)?,
+                    "annotations": serde_json::from_str::<Value>(&annotations)?
                 },
                 "value": value,
                 "globalDefault": global_default,
@@ -173,10 +227,11 @@ impl PriorityClassStore {
 
     async fn record_event(&self, uid: &str, resource_type: &str, name: &str, reason: &str, message: &str) -> Result<()> {
         let event_uid = Uuid::new_v4().to_string();
+        let now = time::now();
         sqlx::query(
             "INSERT INTO events (uid, namespace, involved_object_uid, involved_object_kind, 
              involved_object_name, reason, message, event_time, first_timestamp, last_timestamp, count, type)
-             VALUES (?, '', ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, 1, 'Normal')"
+             VALUES (?, '', ?, ?, ?, ?, ?, ?, ?, ?, 1, 'Normal')"
         )
         .bind(&event_uid)
         .bind(uid)
@@ -184,7 +239,10 @@ impl PriorityClassStore {
         .bind(name)
         .bind(reason)
         .bind(message)
-        .execute(&self.pool)
+        .bind(&now)
+        .bind(&now)
+        .bind(&now)
+        .execute(&self.db)
         .await?;
         Ok(())
     }
@@ -192,12 +250,12 @@ impl PriorityClassStore {
 
 // StorageClass storage
 pub struct StorageClassStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl StorageClassStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, mut sc: Value) -> Result<Value> {
@@ -215,11 +273,15 @@ impl StorageClassStore {
         let labels = sc["metadata"].get("labels").cloned().unwrap_or(json!({}));
         let annotations = sc["metadata"].get("annotations").cloned().unwrap_or(json!({}));
 
+        let now = time::now();
+
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(
             "INSERT INTO storageclasses (uid, name, provisioner, parameters, reclaim_policy,
              mount_options, allow_volume_expansion, volume_binding_mode, allowed_topologies,
-             labels, annotations, resource_version, generation)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 1)"
+             labels, annotations, resource_version, generation, creation_timestamp)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, ?)"
         )
         .bind(&uid)
         .bind(&name)
@@ -232,15 +294,19 @@ impl StorageClassStore {
         .bind(allowed_topologies.as_ref().map(|t| serde_json::to_string(t).ok()).flatten())
         .bind(serde_json::to_string(&labels)?)
         .bind(serde_json::to_string(&annotations)?)
-        .execute(&self.pool)
+        .bind(version)
+        .bind(&now)
+        .execute(&tx.db)
         .await?;
 
         sc["metadata"]["uid"] = json!(uid);
-        sc["metadata"]["resourceVersion"] = json!("1");
+        sc["metadata"]["resourceVersion"] = json!(version.to_string());
         sc["metadata"]["generation"] = json!(1);
-        sc["metadata"]["creationTimestamp"] = json!(chrono::Utc::now().to_rfc3339());
+        sc["metadata"]["creationTimestamp"] = json!(now);
 
-        self.record_event(&uid, "StorageClass", &name, "Created", "StorageClass created").await?;
+        tx.record_event(&uid, "StorageClass", &name, "Created", "StorageClass created").await?;
+        watch_store::record(&tx.db, "storageclasses", "ADDED", &sc).await?;
+        tx.db.commit_write().await?;
         Ok(sc)
     }
 
@@ -252,7 +318,7 @@ impl StorageClassStore {
              FROM storageclasses WHERE name = ? AND deletion_timestamp IS NULL"
         )
         .bind(name)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
 
         if let Some(row) = row {
@@ -280,8 +346,8 @@ impl StorageClassStore {
                     "resourceVersion": resource_version.to_string(),
                     "generation": generation,
                     "creationTimestamp": creation_timestamp,
-                    "labels": serde_json::from_str(&labels)?,
-                    "annotations": serde_json::from_str(&annotations)?
+                    "labels": serde_json::from_str::<Value>(&labels)?,
+                    "annotations": serde_json::from_str::<Value>(&annotations)?
                 },
                 "provisioner": provisioner,
                 "reclaimPolicy": reclaim_policy,
@@ -311,27 +377,36 @@ impl StorageClassStore {
 
         let uid = sc["metadata"]["uid"].as_str().unwrap();
 
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
-            "UPDATE storageclasses SET deletion_timestamp = CURRENT_TIMESTAMP 
+            "UPDATE storageclasses SET deletion_timestamp = ? 
              WHERE name = ? AND deletion_timestamp IS NULL"
         )
+        .bind(time::now())
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
-        self.record_event(uid, "StorageClass", name, "Deleted", "StorageClass deleted").await?;
+        tx.record_event(uid, "StorageClass", name, "Deleted", "StorageClass deleted").await?;
+        watch_store::record(&tx.db, "storageclasses", "DELETED", &sc).await?;
+        tx.db.commit_write().await?;
         Ok(sc)
     }
 
     pub async fn list(&self) -> Result<Value> {
-        let rows = sqlx::query(
+        self.list_matching(&ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, selector: &ListSelector) -> Result<Value> {
+        let query = format!(
             "SELECT uid, name, provisioner, parameters, reclaim_policy, mount_options,
              allow_volume_expansion, volume_binding_mode, allowed_topologies,
              labels, annotations, resource_version, generation, creation_timestamp
-             FROM storageclasses WHERE deletion_timestamp IS NULL"
-        )
-        .fetch_all(&self.pool)
-        .await?;
+             FROM storageclasses WHERE deletion_timestamp IS NULL{}",
+            selector.sql(field_selector::CLUSTER_SCOPED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -359,8 +434,8 @@ impl StorageClassStore {
                     "resourceVersion": resource_version.to_string(),
                     "generation": generation,
                     "creationTimestamp": creation_timestamp,
-                    "labels": serde_json::from_str(&labels)?,
-                    "annotations": serde_json::from_str(&annotations)?
+                    "labels": serde_json::from_str::<Value>(&labels)?,
+                    "annotations": serde_json::from_str::<Value>(&annotations)?
                 },
                 "provisioner": provisioner,
                 "reclaimPolicy": reclaim_policy,
@@ -390,10 +465,11 @@ impl StorageClassStore {
 
     async fn record_event(&self, uid: &str, resource_type: &str, name: &str, reason: &str, message: &str) -> Result<()> {
         let event_uid = Uuid::new_v4().to_string();
+        let now = time::now();
         sqlx::query(
             "INSERT INTO events (uid, namespace, involved_object_uid, involved_object_kind, 
              involved_object_name, reason, message, event_time, first_timestamp, last_timestamp, count, type)
-             VALUES (?, '', ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, 1, 'Normal')"
+             VALUES (?, '', ?, ?, ?, ?, ?, ?, ?, ?, 1, 'Normal')"
         )
         .bind(&event_uid)
         .bind(uid)
@@ -401,7 +477,10 @@ impl StorageClassStore {
         .bind(name)
         .bind(reason)
         .bind(message)
-        .execute(&self.pool)
+        .bind(&now)
+        .bind(&now)
+        .bind(&now)
+        .execute(&self.db)
         .await?;
         Ok(())
     }
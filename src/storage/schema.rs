@@ -0,0 +1,153 @@
+// The version of the database's schema: which of krust's migrations have
+// been applied to it. Migrations only ever add tables, columns and indexes,
+// so a database that a newer krust has migrated still works with an older
+// one, which passes over the migrations it doesn't know. A migration that
+// was changed after being applied, or that failed partway, stops krust from
+// migrating instead of leaving it to fail on SQL errors later.
+use anyhow::{bail, Result};
+use serde::Serialize;
+use sqlx::migrate::Migrator;
+use sqlx::{Row, SqlitePool};
+use std::fmt;
+use tracing::warn;
+
+/// A migration, and when it was applied if it has been.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Migration {
+    pub version: i64,
+    pub description: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub installed_on: Option<String>,
+}
+
+/// How the database's schema compares with what this krust expects.
+#[derive(Debug, Clone)]
+pub struct SchemaStatus {
+    /// The latest migration applied to the database, 0 if none is.
+    pub version: i64,
+    /// The latest migration this krust has.
+    pub latest: i64,
+    pub applied: Vec<Migration>,
+    /// Migrations this krust has that aren't applied yet.
+    pub pending: Vec<Migration>,
+    /// Applied migrations this krust doesn't have, from a newer krust.
+    pub unknown: Vec<Migration>,
+    /// Applied migrations whose SQL differs from this krust's.
+    pub modified: Vec<Migration>,
+    /// Migrations that failed partway through.
+    pub failed: Vec<Migration>,
+}
+
+impl SchemaStatus {
+    /// Whether this krust can use the database, migrating it if need be.
+    pub fn compatible(&self) -> bool {
+        self.modified.is_empty() && self.failed.is_empty()
+    }
+
+    /// Whether the database has every migration this krust has.
+    pub fn up_to_date(&self) -> bool {
+        self.compatible() && self.pending.is_empty()
+    }
+}
+
+impl fmt::Display for SchemaStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "schema version {} (this krust's latest is {})", self.version, self.latest)?;
+        let sections = [
+            ("pending", &self.pending),
+            ("applied by a newer krust", &self.unknown),
+            ("changed since they were applied", &self.modified),
+            ("failed partway", &self.failed),
+        ];
+        for (heading, migrations) in sections {
+            if migrations.is_empty() {
+                continue;
+            }
+            writeln!(f, "{}:", heading)?;
+            for migration in migrations {
+                writeln!(f, "  {:03} {}", migration.version, migration.description)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn migrator() -> Migrator {
+    let mut migrator = sqlx::migrate!("./migrations");
+    migrator.set_ignore_missing(true);
+    migrator
+}
+
+/// Compares the migrations applied to the database with this krust's.
+pub async fn status(pool: &SqlitePool) -> Result<SchemaStatus> {
+    let migrator = migrator();
+    let tracked: bool = sqlx::query_scalar("SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'")
+        .fetch_one(pool)
+        .await?;
+    let rows = if tracked {
+        sqlx::query("SELECT version, description, CAST(installed_on AS TEXT) AS installed_on, success, checksum FROM _sqlx_migrations ORDER BY version")
+            .fetch_all(pool)
+            .await?
+    } else {
+        Vec::new()
+    };
+
+    let mut status = SchemaStatus {
+        version: 0,
+        latest: migrator.iter().map(|m| m.version).max().unwrap_or(0),
+        applied: Vec::new(),
+        pending: Vec::new(),
+        unknown: Vec::new(),
+        modified: Vec::new(),
+        failed: Vec::new(),
+    };
+    for row in &rows {
+        let version: i64 = row.get("version");
+        let migration = Migration {
+            version,
+            description: row.get("description"),
+            installed_on: row.get("installed_on"),
+        };
+        let checksum: Vec<u8> = row.get("checksum");
+        match migrator.iter().find(|m| m.version == version) {
+            _ if !row.get::<bool, _>("success") => status.failed.push(migration.clone()),
+            Some(known) if *known.checksum != *checksum => status.modified.push(migration.clone()),
+            Some(_) => {}
+            None => status.unknown.push(migration.clone()),
+        }
+        status.version = status.version.max(version);
+        status.applied.push(migration);
+    }
+    for known in migrator.iter() {
+        if !status.applied.iter().any(|m| m.version == known.version) {
+            status.pending.push(Migration { version: known.version, description: known.description.to_string(), installed_on: None });
+        }
+    }
+    Ok(status)
+}
+
+/// Applies the migrations the database lacks, first making sure it can be
+/// used at all.
+pub async fn migrate(pool: &SqlitePool) -> Result<()> {
+    let status = status(pool).await?;
+    if !status.compatible() {
+        let changed = status.modified.iter().map(|m| (m, "was changed after it was applied"));
+        let failed = status.failed.iter().map(|m| (m, "failed partway"));
+        let problems: Vec<String> = changed
+            .chain(failed)
+            .map(|(m, problem)| format!("migration {:03} ({}) {}", m.version, m.description, problem))
+            .collect();
+        bail!("the database's schema can't be used by this krust: {}", problems.join("; "));
+    }
+    if !status.unknown.is_empty() {
+        warn!(
+            "The database has schema version {} from a newer krust; this one's latest is {}, and it passes over the {} migration(s) it doesn't know",
+            status.version,
+            status.latest,
+            status.unknown.len()
+        );
+    }
+    migrator().run(pool).await?;
+    Ok(())
+}
@@ -1,17 +1,25 @@
 use anyhow::{anyhow, Result};
 use base64::{engine::general_purpose::STANDARD, Engine};
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::time;
+
+// Field labels secrets can be selected on
+const FIELDS: &[(&str, &str)] = &[("metadata.name", "name"), ("metadata.namespace", "namespace"), ("type", "type")];
+
 pub struct SecretStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl SecretStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut secret: Value) -> Result<Value> {
@@ -21,7 +29,7 @@ impl SecretStore {
             .ok_or_else(|| anyhow!("Secret name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         // Handle stringData - convert to base64 encoded data
         let mut data = secret.get("data").unwrap_or(&json!({})).clone();
@@ -54,9 +62,11 @@ impl SecretStore {
         // Insert into database
         let query = r#"
             INSERT INTO secrets (uid, namespace, name, type, data, immutable, labels, annotations, resource_version, creation_timestamp)
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, 1, ?9)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)
         "#;
         
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(query)
             .bind(&uid)
             .bind(namespace)
@@ -66,8 +76,9 @@ impl SecretStore {
             .bind(immutable)
             .bind(labels.to_string())
             .bind(annotations.to_string())
+            .bind(version)
             .bind(&now)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
         // Build response (never include stringData in response)
@@ -77,7 +88,7 @@ impl SecretStore {
         secret["kind"] = json!("Secret");
         secret["metadata"]["uid"] = json!(uid);
         secret["metadata"]["namespace"] = json!(namespace);
-        secret["metadata"]["resourceVersion"] = json!("1");
+        secret["metadata"]["resourceVersion"] = json!(version.to_string());
         secret["metadata"]["creationTimestamp"] = json!(now);
         secret["metadata"]["selfLink"] = json!(format!("/api/v1/namespaces/{}/secrets/{}", namespace, name));
         secret["type"] = json!(secret_type);
@@ -98,6 +109,8 @@ impl SecretStore {
             secret["metadata"]["annotations"] = annotations;
         }
 
+        watch_store::record(&tx.db, "secrets", "ADDED", &secret).await?;
+        tx.db.commit_write().await?;
         Ok(secret)
     }
 
@@ -111,7 +124,7 @@ impl SecretStore {
         let row = sqlx::query(query)
             .bind(namespace)
             .bind(name)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&self.db)
             .await?;
 
         match row {
@@ -166,32 +179,22 @@ impl SecretStore {
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if namespace.is_some() {
-            r#"
-                SELECT uid, namespace, name, type, data, immutable, labels, annotations, resource_version, creation_timestamp 
-                FROM secrets 
-                WHERE namespace = ?1 AND deletion_timestamp IS NULL 
-                ORDER BY name
-            "#
-        } else {
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
             r#"
                 SELECT uid, namespace, name, type, data, immutable, labels, annotations, resource_version, creation_timestamp 
                 FROM secrets 
-                WHERE deletion_timestamp IS NULL 
+                WHERE deletion_timestamp IS NULL{} 
                 ORDER BY namespace, name
-            "#
-        };
-
-        let rows = if let Some(ns) = namespace {
-            sqlx::query(query)
-                .bind(ns)
-                .fetch_all(&self.pool)
-                .await?
-        } else {
-            sqlx::query(query)
-                .fetch_all(&self.pool)
-                .await?
-        };
+            "#,
+            selector.sql(FIELDS)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -264,7 +267,7 @@ impl SecretStore {
         let row = sqlx::query(check_query)
             .bind(namespace)
             .bind(name)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&self.db)
             .await?
             .ok_or_else(|| anyhow!("Secret {}/{} not found", namespace, name))?;
 
@@ -304,22 +307,28 @@ impl SecretStore {
 
         let update_query = r#"
             UPDATE secrets 
-            SET type = ?1, data = ?2, immutable = ?3, labels = ?4, annotations = ?5, resource_version = resource_version + 1
-            WHERE namespace = ?6 AND name = ?7
+            SET type = ?1, data = ?2, immutable = ?3, labels = ?4, annotations = ?5, resource_version = ?6
+            WHERE namespace = ?7 AND name = ?8
         "#;
 
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(update_query)
             .bind(&secret_type)
             .bind(data.to_string())
             .bind(immutable)
             .bind(labels.to_string())
             .bind(annotations.to_string())
+            .bind(version)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
-        self.get(namespace, name).await
+        let secret = tx.get(namespace, name).await?;
+        watch_store::record(&tx.db, "secrets", "MODIFIED", &secret).await?;
+        tx.db.commit_write().await?;
+        Ok(secret)
     }
 
     pub async fn patch(&self, namespace: &str, name: &str, patch: Value) -> Result<Value> {
@@ -349,25 +358,17 @@ impl SecretStore {
             }
         }
 
-        // Apply merge patch
+        // Apply merge patch; maps are merged key by key and a null removes
+        // the key
         if let Some(data) = patch_data {
-            if let Some(existing_data) = existing.get_mut("data") {
-                if let (Some(existing_obj), Some(patch_obj)) = (existing_data.as_object_mut(), data.as_object()) {
-                    for (key, value) in patch_obj {
-                        existing_obj.insert(key.clone(), value.clone());
-                    }
-                }
-            } else {
-                existing["data"] = data;
-            }
+            json_patch::merge(&mut existing["data"], &data);
         }
 
         if let Some(metadata) = patch.get("metadata") {
-            if let Some(labels) = metadata.get("labels") {
-                existing["metadata"]["labels"] = labels.clone();
-            }
-            if let Some(annotations) = metadata.get("annotations") {
-                existing["metadata"]["annotations"] = annotations.clone();
+            for field in ["labels", "annotations"] {
+                if let Some(value) = metadata.get(field) {
+                    json_patch::merge(&mut existing["metadata"][field], value);
+                }
             }
         }
 
@@ -388,16 +389,19 @@ impl SecretStore {
         let secret = self.get(namespace, name).await?;
 
         // Soft delete
-        let deletion_timestamp = Utc::now().to_rfc3339();
+        let deletion_timestamp = time::now();
         let delete_query = "UPDATE secrets SET deletion_timestamp = ?1 WHERE namespace = ?2 AND name = ?3";
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(delete_query)
             .bind(&deletion_timestamp)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        watch_store::record(&tx.db, "secrets", "DELETED", &secret).await?;
+        tx.db.commit_write().await?;
         Ok(secret)
     }
 }
\ No newline at end of file
@@ -1,19 +1,36 @@
-use anyhow::{anyhow, Result};
-use chrono::Utc;
+use anyhow::{anyhow, bail, Result};
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 use std::collections::HashSet;
+use std::ops::RangeInclusive;
+
+use super::db::Db;
+use super::list_selector::ListSelector;
+use super::resource_version;
+use super::watch_store;
+use crate::models::time;
+
+// Field labels services can be selected on
+const FIELDS: &[(&str, &str)] = &[
+    ("metadata.name", "name"),
+    ("metadata.namespace", "namespace"),
+    ("spec.clusterIP", "cluster_ip"),
+    ("spec.type", "COALESCE(json_extract(spec, '$.type'), 'ClusterIP')"),
+];
+
+/// Ports NodePort services are given on every node.
+pub const NODE_PORT_RANGE: RangeInclusive<i64> = 30000..=32767;
 
 pub struct ServiceStore {
-    pool: SqlitePool,
+    db: Db,
     allocated_ips: std::sync::Arc<std::sync::Mutex<HashSet<String>>>,
 }
 
 impl ServiceStore {
-    pub fn new(pool: SqlitePool) -> Self {
+    pub fn new(db: Db) -> Self {
         Self { 
-            pool,
+            db,
             allocated_ips: std::sync::Arc::new(std::sync::Mutex::new(HashSet::new())),
         }
     }
@@ -25,19 +42,23 @@ impl ServiceStore {
             .ok_or_else(|| anyhow!("Service name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         
         // Set metadata fields
         service["metadata"]["uid"] = json!(uid);
         service["metadata"]["namespace"] = json!(namespace);
-        service["metadata"]["resourceVersion"] = json!("1");
+        service["metadata"]["resourceVersion"] = json!(version.to_string());
         service["metadata"]["creationTimestamp"] = json!(now);
         service["metadata"]["selfLink"] = json!(format!("/api/v1/namespaces/{}/services/{}", namespace, name));
         
-        // Allocate ClusterIP if type is ClusterIP (default)
-        let service_type = service["spec"]["type"].as_str().unwrap_or("ClusterIP");
-        let cluster_ip = if service_type == "ClusterIP" {
-            let ip = self.allocate_cluster_ip()?;
+        // Allocate a ClusterIP unless the service is headless or an ExternalName;
+        // NodePort and LoadBalancer services are reachable inside the cluster too
+        let service_type = service["spec"]["type"].as_str().unwrap_or("ClusterIP").to_string();
+        let headless = service["spec"]["clusterIP"] == "None";
+        let cluster_ip = if service_type != "ExternalName" && !headless {
+            let ip = tx.allocate_cluster_ip()?;
             service["spec"]["clusterIP"] = json!(ip.clone());
             Some(ip)
         } else {
@@ -48,6 +69,10 @@ impl ServiceStore {
         if service["spec"]["ports"].is_null() {
             service["spec"]["ports"] = json!([]);
         }
+
+        if service_type == "NodePort" || service_type == "LoadBalancer" {
+            tx.allocate_node_ports(&mut service["spec"]["ports"]).await?;
+        }
         
         // Set status
         service["status"] = json!({
@@ -66,18 +91,18 @@ impl ServiceStore {
         .bind(&uid)
         .bind(&name)
         .bind(namespace)
-        .bind(1i64)
+        .bind(version)
         .bind(&now)
         .bind(&labels)
         .bind(&annotations)
         .bind(&spec)
         .bind(&status)
         .bind(&cluster_ip)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        self.record_event("services", &uid, &name, namespace, "ADDED", 1, &service).await?;
+        watch_store::record(&tx.db, "services", "ADDED", &service).await?;
         
         // Create corresponding endpoints if selector exists
         if !service["spec"]["selector"].is_null() {
@@ -98,15 +123,16 @@ impl ServiceStore {
             .bind(Uuid::new_v4().to_string())
             .bind(&name)
             .bind(namespace)
-            .bind(1i64)
+            .bind(version)
             .bind(&now)
             .bind("{}")
             .bind("{}")
             .bind("[]")
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await;
         }
         
+        tx.db.commit_write().await?;
         Ok(service)
     }
 
@@ -117,7 +143,7 @@ impl ServiceStore {
         )
         .bind(name)
         .bind(namespace)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
         
         match row {
@@ -156,20 +182,18 @@ impl ServiceStore {
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if let Some(ns) = namespace {
-            sqlx::query(
-                "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, cluster_ip
-                 FROM services WHERE namespace = ? AND deletion_timestamp IS NULL"
-            )
-            .bind(ns)
-        } else {
-            sqlx::query(
-                "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, cluster_ip
-                 FROM services WHERE deletion_timestamp IS NULL"
-            )
-        };
-        
-        let rows = query.fetch_all(&self.pool).await?;
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
+            "SELECT uid, name, namespace, resource_version, creation_timestamp, labels, annotations, spec, status, cluster_ip
+             FROM services WHERE deletion_timestamp IS NULL{}",
+            selector.sql(FIELDS)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
         
         let mut items = Vec::new();
         for row in rows {
@@ -224,23 +248,21 @@ impl ServiceStore {
             self.release_cluster_ip(cluster_ip);
         }
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
             "UPDATE services SET deletion_timestamp = ? WHERE uid = ?"
         )
         .bind(&now)
         .bind(uid)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
         
         // Record event
-        let resource_version = service["metadata"]["resourceVersion"]
-            .as_str()
-            .unwrap()
-            .parse::<i64>()?;
-        self.record_event("services", uid, name, namespace, "DELETED", resource_version, &service).await?;
+        watch_store::record(&tx.db, "services", "DELETED", &service).await?;
         
+        tx.db.commit_write().await?;
         Ok(())
     }
 
@@ -259,30 +281,54 @@ impl ServiceStore {
         Err(anyhow!("No available ClusterIP addresses"))
     }
 
+    // Gives every port without a nodePort the lowest free one in
+    // NODE_PORT_RANGE, after checking the ones asked for are free and in range.
+    async fn allocate_node_ports(&self, ports: &mut Value) -> Result<()> {
+        let mut used: HashSet<i64> = sqlx::query_scalar::<_, i64>(
+            "SELECT json_extract(port.value, '$.nodePort') FROM services, json_each(services.spec, '$.ports') AS port
+             WHERE deletion_timestamp IS NULL AND json_extract(port.value, '$.nodePort') IS NOT NULL"
+        )
+        .fetch_all(&self.db)
+        .await?
+        .into_iter()
+        .collect();
+
+        let Some(ports) = ports.as_array_mut() else {
+            return Ok(());
+        };
+
+        for (i, port) in ports.iter().enumerate() {
+            let Some(node_port) = port["nodePort"].as_i64() else {
+                continue;
+            };
+            if !NODE_PORT_RANGE.contains(&node_port) {
+                bail!(
+                    "spec.ports[{}].nodePort: Invalid value: {}: provided port is not in the valid range. The range of valid ports is {}-{}",
+                    i, node_port, NODE_PORT_RANGE.start(), NODE_PORT_RANGE.end()
+                );
+            }
+            if !used.insert(node_port) {
+                bail!("spec.ports[{}].nodePort: Invalid value: {}: provided port is already allocated", i, node_port);
+            }
+        }
+
+        for port in ports.iter_mut().filter(|port| port["nodePort"].is_null()) {
+            let node_port = NODE_PORT_RANGE
+                .clone()
+                .find(|p| !used.contains(p))
+                .ok_or_else(|| anyhow!("No available NodePort in range {}-{}", NODE_PORT_RANGE.start(), NODE_PORT_RANGE.end()))?;
+            used.insert(node_port);
+            port["nodePort"] = json!(node_port);
+        }
+
+        Ok(())
+    }
+
     fn release_cluster_ip(&self, ip: &str) {
         let mut allocated = self.allocated_ips.lock().unwrap();
         allocated.remove(ip);
     }
 
-    async fn record_event(&self, resource_type: &str, uid: &str, name: &str, namespace: &str, event_type: &str, version: i64, object: &Value) -> Result<()> {
-        sqlx::query(
-            "INSERT INTO events (resource_type, resource_uid, resource_name, resource_namespace, event_type, resource_version, timestamp, object)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
-        )
-        .bind(resource_type)
-        .bind(uid)
-        .bind(name)
-        .bind(namespace)
-        .bind(event_type)
-        .bind(version)
-        .bind(Utc::now().to_rfc3339())
-        .bind(object.to_string())
-        .execute(&self.pool)
-        .await?;
-        
-        Ok(())
-    }
-
     pub async fn get_endpoints_for_service(&self, namespace: &str, name: &str) -> Result<Vec<String>> {
         // Get the service to find its selector
         let service = self.get(namespace, name).await?;
@@ -301,7 +347,7 @@ impl ServiceStore {
              WHERE namespace = ? AND deletion_timestamp IS NULL AND phase = 'Running'"
         )
         .bind(namespace)
-        .fetch_all(&self.pool)
+        .fetch_all(&self.db)
         .await?;
         
         for row in pods_query {
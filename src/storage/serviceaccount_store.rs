@@ -1,16 +1,41 @@
+
 #![allow(unused_imports)]
 use anyhow::Result;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use super::serviceaccount_token::{self, SigningKey};
+use crate::models::time;
+
+// The shortest lifetime a token may be requested with
+const MIN_TOKEN_EXPIRATION_SECONDS: i64 = 600;
+
+/// Who a valid ServiceAccount token was issued for.
+#[derive(Debug, Clone)]
+pub struct TokenOwner {
+    pub namespace: String,
+    pub name: String,
+    pub uid: String,
+    /// The audiences asked about that the token is meant for
+    pub audiences: Vec<String>,
+    /// The name and uid of the Pod the token is bound to, if any
+    pub pod: Option<(String, String)>,
+    pub token_id: String,
+}
+
 pub struct ServiceAccountStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl ServiceAccountStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut sa: Value) -> Result<Value> {
@@ -22,10 +47,14 @@ impl ServiceAccountStore {
         let labels = sa["metadata"].get("labels").cloned().unwrap_or(json!({}));
         let annotations = sa["metadata"].get("annotations").cloned().unwrap_or(json!({}));
 
+        let now = time::now();
+
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(
             "INSERT INTO serviceaccounts (uid, name, namespace, secrets, image_pull_secrets, 
-             automount_service_account_token, labels, annotations, resource_version, generation)
-             VALUES (?, ?, ?, ?, ?, ?, ?, ?, 1, 1)"
+             automount_service_account_token, labels, annotations, resource_version, generation, creation_timestamp)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, 1, ?)"
         )
         .bind(&uid)
         .bind(&name)
@@ -35,15 +64,17 @@ impl ServiceAccountStore {
         .bind(automount)
         .bind(serde_json::to_string(&labels)?)
         .bind(serde_json::to_string(&annotations)?)
-        .execute(&self.pool)
+        .bind(version)
+        .bind(&now)
+        .execute(&tx.db)
         .await?;
 
         sa["metadata"]["uid"] = json!(uid);
-        sa["metadata"]["resourceVersion"] = json!("1");
+        sa["metadata"]["resourceVersion"] = json!(version.to_string());
         sa["metadata"]["generation"] = json!(1);
-        sa["metadata"]["creationTimestamp"] = json!(chrono::Utc::now().to_rfc3339());
+        sa["metadata"]["creationTimestamp"] = json!(now);
 
-        self.record_event(
+        tx.record_event(
             &uid,
             "ServiceAccount",
             namespace,
@@ -52,6 +83,8 @@ impl ServiceAccountStore {
             "ServiceAccount created"
         ).await?;
 
+        watch_store::record(&tx.db, "serviceaccounts", "ADDED", &sa).await?;
+        tx.db.commit_write().await?;
         Ok(sa)
     }
 
@@ -64,7 +97,7 @@ impl ServiceAccountStore {
         )
         .bind(namespace)
         .bind(name)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
 
         if let Some((uid, name, namespace, secrets, image_pull_secrets, automount, labels, annotations, resource_version, generation, creation_timestamp, _deletion_timestamp)) = row {
@@ -99,8 +132,8 @@ impl ServiceAccountStore {
             .ok_or_else(|| anyhow::anyhow!("ServiceAccount not found"))?;
 
         let uid = current["metadata"]["uid"].as_str().unwrap();
-        let resource_version: i64 = current["metadata"]["resourceVersion"].as_str().unwrap().parse()?;
-        let new_resource_version = resource_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_resource_version = resource_version::next(&tx.db).await?;
         let generation: i64 = current["metadata"]["generation"].as_i64().unwrap();
         
         // Check if spec changed (secrets, imagePullSecrets, automount)
@@ -131,7 +164,7 @@ impl ServiceAccountStore {
         .bind(new_generation)
         .bind(namespace)
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
         sa["metadata"]["uid"] = json!(uid);
@@ -139,7 +172,7 @@ impl ServiceAccountStore {
         sa["metadata"]["generation"] = json!(new_generation);
         sa["metadata"]["creationTimestamp"] = current["metadata"]["creationTimestamp"].clone();
 
-        self.record_event(
+        tx.record_event(
             uid,
             "ServiceAccount",
             namespace,
@@ -148,6 +181,8 @@ impl ServiceAccountStore {
             "ServiceAccount updated"
         ).await?;
 
+        watch_store::record(&tx.db, "serviceaccounts", "MODIFIED", &sa).await?;
+        tx.db.commit_write().await?;
         Ok(sa)
     }
 
@@ -157,17 +192,19 @@ impl ServiceAccountStore {
 
         let uid = sa["metadata"]["uid"].as_str().unwrap();
 
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
             "UPDATE serviceaccounts 
-             SET deletion_timestamp = CURRENT_TIMESTAMP 
+             SET deletion_timestamp = ? 
              WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL"
         )
+        .bind(time::now())
         .bind(namespace)
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
-        self.record_event(
+        tx.record_event(
             uid,
             "ServiceAccount",
             namespace,
@@ -176,30 +213,29 @@ impl ServiceAccountStore {
             "ServiceAccount deleted"
         ).await?;
 
+        watch_store::record(&tx.db, "serviceaccounts", "DELETED", &sa).await?;
+        tx.db.commit_write().await?;
         Ok(sa)
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let rows = if let Some(ns) = namespace {
-            sqlx::query_as::<_, (String, String, String, String, String, bool, String, String, i64, i64, String)>(
-                "SELECT uid, name, namespace, secrets, image_pull_secrets, automount_service_account_token,
-                 labels, annotations, resource_version, generation, creation_timestamp
-                 FROM serviceaccounts
-                 WHERE namespace = ? AND deletion_timestamp IS NULL"
-            )
-            .bind(ns)
-            .fetch_all(&self.pool)
-            .await?
-        } else {
-            sqlx::query_as::<_, (String, String, String, String, String, bool, String, String, i64, i64, String)>(
-                "SELECT uid, name, namespace, secrets, image_pull_secrets, automount_service_account_token,
-                 labels, annotations, resource_version, generation, creation_timestamp
-                 FROM serviceaccounts
-                 WHERE deletion_timestamp IS NULL"
-            )
-            .fetch_all(&self.pool)
-            .await?
-        };
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
+            "SELECT uid, name, namespace, secrets, image_pull_secrets, automount_service_account_token,
+             labels, annotations, resource_version, generation, creation_timestamp
+             FROM serviceaccounts
+             WHERE deletion_timestamp IS NULL{}",
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector
+            .bind_as(sqlx::query_as::<_, (String, String, String, String, String, bool, String, String, i64, i64, String)>(&query))
+            .fetch_all(&self.db)
+            .await?;
         
         let mut items = Vec::new();
 
@@ -233,24 +269,63 @@ impl ServiceAccountStore {
         }))
     }
 
+    /// Issues a token for the ServiceAccount, as the TokenRequest API does.
+    /// A token bound to a Pod or Secret is only valid while that object
+    /// exists.
     pub async fn create_token(&self, namespace: &str, name: &str, token_request: Value) -> Result<Value> {
-        // Verify ServiceAccount exists
         let sa = self.get(namespace, name).await?
-            .ok_or_else(|| anyhow::anyhow!("ServiceAccount not found"))?;
+            .ok_or_else(|| anyhow::anyhow!("serviceaccounts \"{}\" not found", name))?;
+        let sa_uid = sa["metadata"]["uid"].as_str().unwrap_or_default();
+
+        let mut spec = token_request.get("spec").cloned().unwrap_or(json!({}));
+        let audiences = match spec["audiences"].as_array() {
+            Some(audiences) if !audiences.is_empty() => Value::Array(audiences.clone()),
+            _ => json!([serviceaccount_token::ISSUER]),
+        };
+        let expiration_seconds = spec["expirationSeconds"].as_i64().unwrap_or(3600);
+        if expiration_seconds < MIN_TOKEN_EXPIRATION_SECONDS {
+            anyhow::bail!(
+                "spec.expirationSeconds: Invalid value: {}: may not specify a duration less than 10 minutes",
+                expiration_seconds
+            );
+        }
+
+        let mut claims = json!({
+            "aud": audiences,
+            "iss": serviceaccount_token::ISSUER,
+            "sub": format!("system:serviceaccount:{}:{}", namespace, name),
+            "kubernetes.io": {
+                "namespace": namespace,
+                "serviceaccount": { "name": name, "uid": sa_uid }
+            }
+        });
+        if let Some(bound) = spec.get("boundObjectRef").filter(|r| !r.is_null()).cloned() {
+            let kind = bound["kind"].as_str().unwrap_or_default();
+            let bound_name = bound["name"].as_str().unwrap_or_default();
+            let uid = self.bound_object_uid(kind, namespace, bound_name).await?
+                .ok_or_else(|| anyhow::anyhow!("{}s \"{}\" not found", kind.to_lowercase(), bound_name))?;
+            if bound["uid"].as_str().is_some_and(|given| given != uid) {
+                anyhow::bail!(
+                    "the UID in the bound object reference ({}) does not match the UID in record ({})",
+                    bound["uid"].as_str().unwrap_or_default(),
+                    uid
+                );
+            }
+            spec["boundObjectRef"]["uid"] = json!(uid);
+            claims["kubernetes.io"][kind.to_lowercase()] = json!({ "name": bound_name, "uid": uid });
+        }
 
-        let sa_uid = sa["metadata"]["uid"].as_str().unwrap();
         let token_uid = Uuid::new_v4().to_string();
-        
-        // Generate a simple token (in production, this would be a proper JWT)
-        let token = format!("eyJ0eXAiOiJKV1QiLCJhbGciOiJSUzI1NiJ9.{}", Uuid::new_v4());
-        
-        let spec = token_request.get("spec").cloned().unwrap_or(json!({}));
-        let audiences = spec.get("audiences").cloned().unwrap_or(json!(["api"]));
-        let expiration_seconds = spec.get("expirationSeconds").and_then(|v| v.as_i64()).unwrap_or(3600);
-        let bound_object_ref = spec.get("boundObjectRef").cloned();
-        
-        let expiration_timestamp = chrono::Utc::now() + chrono::Duration::seconds(expiration_seconds);
-        
+        let issued_at = chrono::Utc::now();
+        let expiration_timestamp = issued_at + chrono::Duration::seconds(expiration_seconds);
+        claims["jti"] = json!(token_uid);
+        claims["iat"] = json!(issued_at.timestamp());
+        claims["nbf"] = json!(issued_at.timestamp());
+        claims["exp"] = json!(expiration_timestamp.timestamp());
+        let token = SigningKey::load(&self.db).await?.sign(&claims)?;
+        spec["audiences"] = audiences.clone();
+        spec["expirationSeconds"] = json!(expiration_seconds);
+
         sqlx::query(
             "INSERT INTO tokenrequests (uid, service_account_uid, namespace, service_account_name,
              audiences, expiration_seconds, bound_object_ref, token, expiration_timestamp)
@@ -262,10 +337,10 @@ impl ServiceAccountStore {
         .bind(name)
         .bind(serde_json::to_string(&audiences)?)
         .bind(expiration_seconds)
-        .bind(bound_object_ref.as_ref().map(|o| serde_json::to_string(o).ok()).flatten())
+        .bind(spec.get("boundObjectRef").and_then(|o| serde_json::to_string(o).ok()))
         .bind(&token)
-        .bind(expiration_timestamp.to_rfc3339())
-        .execute(&self.pool)
+        .bind(time::format(expiration_timestamp))
+        .execute(&self.db)
         .await?;
 
         Ok(json!({
@@ -274,21 +349,93 @@ impl ServiceAccountStore {
             "metadata": {
                 "name": name,
                 "namespace": namespace,
-                "creationTimestamp": chrono::Utc::now().to_rfc3339()
+                "creationTimestamp": time::format(issued_at)
             },
             "spec": spec,
             "status": {
                 "token": token,
-                "expirationTimestamp": expiration_timestamp.to_rfc3339()
+                "expirationTimestamp": time::format(expiration_timestamp)
             }
         }))
     }
 
+    /// Validates a ServiceAccount token for the given audiences, as a
+    /// TokenReview does. It has to be signed by this server, unexpired, meant
+    /// for one of the audiences, and the ServiceAccount and any object it's
+    /// bound to must still exist. The error says why it isn't valid.
+    pub async fn review_token(&self, token: &str, audiences: &[String]) -> Result<TokenOwner> {
+        let Some(key) = SigningKey::stored(&self.db).await? else {
+            anyhow::bail!("no ServiceAccount tokens have been issued");
+        };
+        let claims = key.verify(token)?;
+
+        let token_audiences: Vec<String> = match &claims["aud"] {
+            Value::String(aud) => vec![aud.clone()],
+            auds => auds.as_array().into_iter().flatten().filter_map(|a| a.as_str().map(str::to_string)).collect(),
+        };
+        let audiences: Vec<String> = audiences.iter().filter(|a| token_audiences.contains(a)).cloned().collect();
+        if audiences.is_empty() {
+            anyhow::bail!("token audiences {:?} is invalid for the target audiences", token_audiences);
+        }
+
+        let info = &claims["kubernetes.io"];
+        let namespace = info["namespace"].as_str().unwrap_or_default();
+        let name = info["serviceaccount"]["name"].as_str().unwrap_or_default();
+        let uid = info["serviceaccount"]["uid"].as_str().unwrap_or_default();
+        let current = self.get(namespace, name).await?;
+        if current.as_ref().and_then(|sa| sa["metadata"]["uid"].as_str()) != Some(uid) {
+            anyhow::bail!("service account {}/{} has been deleted", namespace, name);
+        }
+
+        let mut pod = None;
+        for kind in ["Pod", "Secret"] {
+            let bound = &info[kind.to_lowercase()];
+            let Some(bound_name) = bound["name"].as_str() else {
+                continue;
+            };
+            let current = self.bound_object_uid(kind, namespace, bound_name).await?;
+            if current.as_deref() != bound["uid"].as_str() {
+                anyhow::bail!("{} {}/{} the token is bound to has been deleted", kind, namespace, bound_name);
+            }
+            if kind == "Pod" {
+                pod = Some((bound_name.to_string(), current.unwrap_or_default()));
+            }
+        }
+
+        Ok(TokenOwner {
+            namespace: namespace.to_string(),
+            name: name.to_string(),
+            uid: uid.to_string(),
+            audiences,
+            pod,
+            token_id: claims["jti"].as_str().unwrap_or_default().to_string(),
+        })
+    }
+
+    // The uid of the Pod or Secret a token is bound to, unless it's gone
+    async fn bound_object_uid(&self, kind: &str, namespace: &str, name: &str) -> Result<Option<String>> {
+        let table = match kind {
+            "Pod" => "pods",
+            "Secret" => "secrets",
+            other => anyhow::bail!("cannot bind a token to a {}", other),
+        };
+        let row = sqlx::query(&format!(
+            "SELECT uid FROM {} WHERE namespace = ? AND name = ? AND deletion_timestamp IS NULL",
+            table
+        ))
+        .bind(namespace)
+        .bind(name)
+        .fetch_optional(&self.db)
+        .await?;
+        Ok(row.map(|row| row.get("uid")))
+    }
+
     async fn record_event(&self, uid: &str, resource_type: &str, namespace: &str, name: &str, reason: &str, message: &str) -> Result<()> {
         let event_uid = Uuid::new_v4().to_string();
+        let now = time::now();
         sqlx::query(
             "INSERT INTO events (uid, namespace, involved_object_uid, involved_object_kind, involved_object_name, reason, message, event_time, first_timestamp, last_timestamp, count, type)
-             VALUES (?, ?, ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, 1, 'Normal')"
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, 1, 'Normal')"
         )
         .bind(&event_uid)
         .bind(namespace)
@@ -297,7 +444,10 @@ impl ServiceAccountStore {
         .bind(name)
         .bind(reason)
         .bind(message)
-        .execute(&self.pool)
+        .bind(&now)
+        .bind(&now)
+        .bind(&now)
+        .execute(&self.db)
         .await?;
         Ok(())
     }
@@ -0,0 +1,103 @@
+// ServiceAccount tokens are JWTs signed with RS256, carrying the same claims
+// as kube-apiserver's: who they were issued for, the audiences they're meant
+// for, when they expire, and the object they're bound to, if any. The signing
+// key is generated the first time a token is issued and kept in the database,
+// so tokens stay valid across restarts until the data directory is reset.
+use anyhow::{anyhow, bail, Result};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use openssl::hash::{hash, MessageDigest};
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::sign::{Signer, Verifier};
+use serde_json::{json, Value};
+use sqlx::Row;
+
+use super::db::Db;
+use crate::models::time;
+
+/// The issuer of ServiceAccount tokens, and the audience of tokens for the
+/// API server itself.
+pub const ISSUER: &str = "https://kubernetes.default.svc.cluster.local";
+
+const KEY_NAME: &str = "service-account";
+
+pub(crate) struct SigningKey {
+    kid: String,
+    key: PKey<Private>,
+}
+
+impl SigningKey {
+    /// The ServiceAccount signing key, generated if there isn't one yet.
+    pub(crate) async fn load(db: &Db) -> Result<Self> {
+        if let Some(key) = Self::stored(db).await? {
+            return Ok(key);
+        }
+        let key = Rsa::generate(2048)?.private_key_to_pem()?;
+        // Whoever generates one first wins
+        sqlx::query("INSERT OR IGNORE INTO signing_keys (name, private_key, creation_timestamp) VALUES (?, ?, ?)")
+            .bind(KEY_NAME)
+            .bind(String::from_utf8(key)?)
+            .bind(time::now())
+            .execute(db)
+            .await?;
+        Self::stored(db).await?.ok_or_else(|| anyhow!("signing key missing"))
+    }
+
+    pub(crate) async fn stored(db: &Db) -> Result<Option<Self>> {
+        let row = sqlx::query("SELECT private_key FROM signing_keys WHERE name = ?")
+            .bind(KEY_NAME)
+            .fetch_optional(db)
+            .await?;
+        let Some(row) = row else {
+            return Ok(None);
+        };
+        let key = PKey::from_rsa(Rsa::private_key_from_pem(row.get::<String, _>("private_key").as_bytes())?)?;
+        // Like kube-apiserver, the key ID is the hash of the public key
+        let kid = URL_SAFE_NO_PAD.encode(hash(MessageDigest::sha256(), &key.public_key_to_der()?)?);
+        Ok(Some(Self { kid, key }))
+    }
+
+    pub(crate) fn sign(&self, claims: &Value) -> Result<String> {
+        let header = json!({ "alg": "RS256", "kid": self.kid });
+        let signed = format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(&header)?),
+            URL_SAFE_NO_PAD.encode(serde_json::to_vec(claims)?)
+        );
+        let mut signer = Signer::new(MessageDigest::sha256(), &self.key)?;
+        signer.update(signed.as_bytes())?;
+        Ok(format!("{}.{}", signed, URL_SAFE_NO_PAD.encode(signer.sign_to_vec()?)))
+    }
+
+    /// The claims of a token this key signed, once its signature, issuer
+    /// and validity period check out.
+    pub(crate) fn verify(&self, token: &str) -> Result<Value> {
+        let parts: Vec<&str> = token.split('.').collect();
+        let [header, payload, signature] = parts.as_slice() else {
+            bail!("not a JWT");
+        };
+        let header: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(header)?)?;
+        if header["alg"] != "RS256" || header["kid"] != self.kid.as_str() {
+            bail!("not signed by this API server");
+        }
+        let mut verifier = Verifier::new(MessageDigest::sha256(), &self.key)?;
+        verifier.update(format!("{}.{}", parts[0], parts[1]).as_bytes())?;
+        if !verifier.verify(&URL_SAFE_NO_PAD.decode(signature)?)? {
+            bail!("invalid signature");
+        }
+
+        let claims: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload)?)?;
+        if claims["iss"] != ISSUER {
+            bail!("issued by {}, not {}", claims["iss"], ISSUER);
+        }
+        let now = chrono::Utc::now().timestamp();
+        if claims["exp"].as_i64().is_some_and(|exp| exp <= now) {
+            bail!("token has expired");
+        }
+        if claims["nbf"].as_i64().is_some_and(|nbf| nbf > now) {
+            bail!("token is not valid yet");
+        }
+        Ok(claims)
+    }
+}
@@ -1,16 +1,24 @@
 use anyhow::{anyhow, Result};
-use chrono::Utc;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::compression;
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::replicas;
+use crate::models::time;
+
 pub struct StatefulSetStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl StatefulSetStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, namespace: &str, mut statefulset: Value) -> Result<Value> {
@@ -20,10 +28,10 @@ impl StatefulSetStore {
             .ok_or_else(|| anyhow!("StatefulSet name is required"))?
             .to_string();
         
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         // Extract spec fields
-        let replicas = statefulset["spec"]["replicas"].as_i64().unwrap_or(1);
+        let replicas = replicas::desired(&statefulset["spec"]);
         let selector = statefulset["spec"]["selector"].clone();
         if selector.is_null() {
             return Err(anyhow!("StatefulSet selector is required"));
@@ -86,9 +94,11 @@ impl StatefulSetStore {
                 template, volume_claim_templates, labels, annotations, 
                 resource_version, creation_timestamp
             )
-            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, 1, ?17)
+            VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18)
         "#;
         
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(query)
             .bind(&uid)
             .bind(namespace)
@@ -102,12 +112,13 @@ impl StatefulSetStore {
             .bind(min_ready_seconds)
             .bind(pvc_retention_policy.as_ref().map(|v| v.to_string()))
             .bind(ordinals.as_ref().map(|v| v.to_string()))
-            .bind(template.to_string())
+            .bind(compression::encode(&template))
             .bind(volume_claim_templates.as_ref().map(|v| v.to_string()))
             .bind(labels.to_string())
-            .bind(annotations.to_string())
+            .bind(compression::encode(&annotations))
+            .bind(version)
             .bind(&now)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
         // Build response
@@ -115,7 +126,7 @@ impl StatefulSetStore {
         statefulset["kind"] = json!("StatefulSet");
         statefulset["metadata"]["uid"] = json!(uid);
         statefulset["metadata"]["namespace"] = json!(namespace);
-        statefulset["metadata"]["resourceVersion"] = json!("1");
+        statefulset["metadata"]["resourceVersion"] = json!(version.to_string());
         statefulset["metadata"]["generation"] = json!(1);
         statefulset["metadata"]["creationTimestamp"] = json!(now);
         statefulset["metadata"]["selfLink"] = json!(format!("/apis/apps/v1/namespaces/{}/statefulsets/{}", namespace, name));
@@ -146,6 +157,8 @@ impl StatefulSetStore {
             statefulset["metadata"]["annotations"] = annotations;
         }
 
+        watch_store::record(&tx.db, "statefulsets", "ADDED", &statefulset).await?;
+        tx.db.commit_write().await?;
         Ok(statefulset)
     }
 
@@ -166,7 +179,7 @@ impl StatefulSetStore {
         let row = sqlx::query(query)
             .bind(namespace)
             .bind(name)
-            .fetch_optional(&self.pool)
+            .fetch_optional(&self.db)
             .await?;
 
         match row {
@@ -179,21 +192,12 @@ impl StatefulSetStore {
     }
 
     pub async fn list(&self, namespace: Option<&str>) -> Result<Value> {
-        let query = if namespace.is_some() {
-            r#"
-                SELECT uid, namespace, name, replicas, selector, service_name, pod_management_policy,
-                       update_strategy, revision_history_limit, min_ready_seconds,
-                       persistent_volume_claim_retention_policy, ordinals,
-                       template, volume_claim_templates,
-                       observed_generation, replicas_status, ready_replicas, current_replicas,
-                       updated_replicas, current_revision, update_revision, collision_count,
-                       available_replicas, conditions,
-                       labels, annotations, resource_version, generation, creation_timestamp 
-                FROM statefulsets 
-                WHERE namespace = ?1 AND deletion_timestamp IS NULL 
-                ORDER BY name
-            "#
-        } else {
+        self.list_matching(namespace, &ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, namespace: Option<&str>, selector: &ListSelector) -> Result<Value> {
+        let selector = selector.in_namespace(namespace);
+        let query = format!(
             r#"
                 SELECT uid, namespace, name, replicas, selector, service_name, pod_management_policy,
                        update_strategy, revision_history_limit, min_ready_seconds,
@@ -204,21 +208,13 @@ impl StatefulSetStore {
                        available_replicas, conditions,
                        labels, annotations, resource_version, generation, creation_timestamp 
                 FROM statefulsets 
-                WHERE deletion_timestamp IS NULL 
+                WHERE deletion_timestamp IS NULL{} 
                 ORDER BY namespace, name
-            "#
-        };
-
-        let rows = if let Some(ns) = namespace {
-            sqlx::query(query)
-                .bind(ns)
-                .fetch_all(&self.pool)
-                .await?
-        } else {
-            sqlx::query(query)
-                .fetch_all(&self.pool)
-                .await?
-        };
+            "#,
+            selector.sql(field_selector::NAMESPACED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -245,7 +241,7 @@ impl StatefulSetStore {
 
     pub async fn update(&self, namespace: &str, name: &str, statefulset: Value) -> Result<Value> {
         // Extract spec fields
-        let replicas = statefulset["spec"]["replicas"].as_i64().unwrap_or(1);
+        let replicas = replicas::desired(&statefulset["spec"]);
         let selector = statefulset["spec"]["selector"].clone();
         if selector.is_null() {
             return Err(anyhow!("StatefulSet selector is required"));
@@ -288,23 +284,26 @@ impl StatefulSetStore {
             SET replicas = ?1, selector = ?2, service_name = ?3, pod_management_policy = ?4,
                 update_strategy = ?5, template = ?6, volume_claim_templates = ?7,
                 labels = ?8, annotations = ?9, 
-                resource_version = resource_version + 1, generation = generation + 1
-            WHERE namespace = ?10 AND name = ?11 AND deletion_timestamp IS NULL
+                resource_version = ?10, generation = generation + 1
+            WHERE namespace = ?11 AND name = ?12 AND deletion_timestamp IS NULL
         "#;
 
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         let rows_affected = sqlx::query(update_query)
             .bind(replicas)
             .bind(selector.to_string())
             .bind(&service_name)
             .bind(&pod_management_policy)
             .bind(update_strategy.to_string())
-            .bind(template.to_string())
+            .bind(compression::encode(&template))
             .bind(volume_claim_templates.as_ref().map(|v| v.to_string()))
             .bind(labels.to_string())
-            .bind(annotations.to_string())
+            .bind(compression::encode(&annotations))
+            .bind(version)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?
             .rows_affected();
 
@@ -312,21 +311,27 @@ impl StatefulSetStore {
             return Err(anyhow!("StatefulSet {}/{} not found", namespace, name));
         }
 
-        self.get(namespace, name).await
+        let statefulset = tx.get(namespace, name).await?;
+        watch_store::record(&tx.db, "statefulsets", "MODIFIED", &statefulset).await?;
+        tx.db.commit_write().await?;
+        Ok(statefulset)
     }
 
     pub async fn update_scale(&self, namespace: &str, name: &str, replicas: i64) -> Result<Value> {
         let update_query = r#"
             UPDATE statefulsets 
-            SET replicas = ?1, resource_version = resource_version + 1
-            WHERE namespace = ?2 AND name = ?3 AND deletion_timestamp IS NULL
+            SET replicas = ?1, resource_version = ?2, generation = generation + 1
+            WHERE namespace = ?3 AND name = ?4 AND deletion_timestamp IS NULL
         "#;
 
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         let rows_affected = sqlx::query(update_query)
             .bind(replicas)
+            .bind(version)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?
             .rows_affected();
 
@@ -334,20 +339,10 @@ impl StatefulSetStore {
             return Err(anyhow!("StatefulSet {}/{} not found", namespace, name));
         }
 
-        // Return scale object
-        let statefulset = self.get(namespace, name).await?;
-        Ok(json!({
-            "apiVersion": "autoscaling/v1",
-            "kind": "Scale",
-            "metadata": statefulset["metadata"],
-            "spec": {
-                "replicas": replicas
-            },
-            "status": {
-                "replicas": statefulset["status"]["replicas"],
-                "selector": statefulset["spec"]["selector"]
-            }
-        }))
+        let statefulset = tx.get(namespace, name).await?;
+        watch_store::record(&tx.db, "statefulsets", "MODIFIED", &statefulset).await?;
+        tx.db.commit_write().await?;
+        Ok(replicas::scale(&statefulset))
     }
 
     pub async fn update_status(&self, namespace: &str, name: &str, status: Value) -> Result<()> {
@@ -356,11 +351,13 @@ impl StatefulSetStore {
             SET observed_generation = ?1, replicas_status = ?2, ready_replicas = ?3,
                 current_replicas = ?4, updated_replicas = ?5, current_revision = ?6,
                 update_revision = ?7, collision_count = ?8, available_replicas = ?9,
-                conditions = ?10, resource_version = resource_version + 1
-            WHERE namespace = ?11 AND name = ?12 AND deletion_timestamp IS NULL
+                conditions = ?10, resource_version = ?11
+            WHERE namespace = ?12 AND name = ?13 AND deletion_timestamp IS NULL
         "#;
 
-        sqlx::query(update_query)
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
+        let updated = sqlx::query(update_query)
             .bind(status["observedGeneration"].as_i64().unwrap_or(0))
             .bind(status["replicas"].as_i64().unwrap_or(0))
             .bind(status["readyReplicas"].as_i64().unwrap_or(0))
@@ -371,11 +368,18 @@ impl StatefulSetStore {
             .bind(status["collisionCount"].as_i64().unwrap_or(0))
             .bind(status["availableReplicas"].as_i64().unwrap_or(0))
             .bind(status.get("conditions").map(|v| v.to_string()))
+            .bind(version)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        if updated.rows_affected() > 0 {
+            let statefulset = tx.get(namespace, name).await?;
+            watch_store::record(&tx.db, "statefulsets", "MODIFIED", &statefulset).await?;
+        }
+
+        tx.db.commit_write().await?;
         Ok(())
     }
 
@@ -384,16 +388,19 @@ impl StatefulSetStore {
         let statefulset = self.get(namespace, name).await?;
 
         // Soft delete
-        let deletion_timestamp = Utc::now().to_rfc3339();
+        let deletion_timestamp = time::now();
         let delete_query = "UPDATE statefulsets SET deletion_timestamp = ?1 WHERE namespace = ?2 AND name = ?3";
         
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(delete_query)
             .bind(&deletion_timestamp)
             .bind(namespace)
             .bind(name)
-            .execute(&self.pool)
+            .execute(&tx.db)
             .await?;
 
+        watch_store::record(&tx.db, "statefulsets", "DELETED", &statefulset).await?;
+        tx.db.commit_write().await?;
         Ok(statefulset)
     }
 
@@ -408,7 +415,6 @@ impl StatefulSetStore {
         let min_ready_seconds: i64 = row.get("min_ready_seconds");
         let pvc_retention_policy_str: Option<String> = row.get("persistent_volume_claim_retention_policy");
         let ordinals_str: Option<String> = row.get("ordinals");
-        let template_str: String = row.get("template");
         let volume_claim_templates_str: Option<String> = row.get("volume_claim_templates");
         
         let observed_generation: i64 = row.get("observed_generation");
@@ -423,20 +429,19 @@ impl StatefulSetStore {
         let conditions_str: Option<String> = row.get("conditions");
         
         let labels_str: String = row.get("labels");
-        let annotations_str: String = row.get("annotations");
         let resource_version: i64 = row.get("resource_version");
         let generation: i64 = row.get("generation");
         let creation_timestamp: String = row.get("creation_timestamp");
 
         let selector: Value = serde_json::from_str(&selector_str)?;
         let update_strategy: Value = serde_json::from_str(&update_strategy_str)?;
-        let template: Value = serde_json::from_str(&template_str)?;
+        let template = compression::decode(&row, "template")?;
         let pvc_retention_policy: Option<Value> = pvc_retention_policy_str.map(|s| serde_json::from_str(&s)).transpose()?;
         let ordinals: Option<Value> = ordinals_str.map(|s| serde_json::from_str(&s)).transpose()?;
         let volume_claim_templates: Option<Value> = volume_claim_templates_str.map(|s| serde_json::from_str(&s)).transpose()?;
         let conditions: Option<Value> = conditions_str.map(|s| serde_json::from_str(&s)).transpose()?;
         let labels: Value = serde_json::from_str(&labels_str)?;
-        let annotations: Value = serde_json::from_str(&annotations_str)?;
+        let annotations = compression::decode(&row, "annotations")?;
 
         let mut statefulset = json!({
             "apiVersion": "apps/v1",
@@ -0,0 +1,76 @@
+// Resource name in API paths, the table it is kept in, and whether it is
+// namespaced
+pub(crate) const RESOURCES: &[(&str, &str, bool)] = &[
+    ("namespaces", "namespaces", false),
+    ("nodes", "nodes", false),
+    ("persistentvolumes", "persistent_volumes", false),
+    ("clusterroles", "clusterroles", false),
+    ("clusterrolebindings", "clusterrolebindings", false),
+    ("priorityclasses", "priorityclasses", false),
+    ("storageclasses", "storageclasses", false),
+    ("validatingwebhookconfigurations", "validatingwebhookconfigurations", false),
+    ("mutatingwebhookconfigurations", "mutatingwebhookconfigurations", false),
+    ("pods", "pods", true),
+    ("services", "services", true),
+    ("endpoints", "endpoints", true),
+    ("configmaps", "configmaps", true),
+    ("secrets", "secrets", true),
+    ("persistentvolumeclaims", "persistent_volume_claims", true),
+    ("resourcequotas", "resourcequotas", true),
+    ("limitranges", "limitranges", true),
+    ("serviceaccounts", "serviceaccounts", true),
+    ("deployments", "deployments", true),
+    ("replicasets", "replicasets", true),
+    ("statefulsets", "statefulsets", true),
+    ("daemonsets", "daemonsets", true),
+    ("controllerrevisions", "controller_revisions", true),
+    ("jobs", "jobs", true),
+    ("cronjobs", "cronjobs", true),
+    ("networkpolicies", "networkpolicies", true),
+    ("ingresses", "ingresses", true),
+    ("horizontalpodautoscalers", "horizontalpodautoscalers", true),
+    ("poddisruptionbudgets", "poddisruptionbudgets", true),
+    ("leases", "leases", true),
+    ("roles", "roles", true),
+    ("rolebindings", "rolebindings", true),
+];
+
+/// The table a resource is kept in, and whether it is namespaced.
+pub(crate) fn table(resource: &str) -> Option<(&'static str, bool)> {
+    RESOURCES
+        .iter()
+        .find(|(name, _, _)| *name == resource)
+        .map(|(_, table, namespaced)| (*table, *namespaced))
+}
+
+/// The resource objects of `kind` are, if krust keeps them.
+pub(crate) fn resource_for_kind(kind: &str) -> Option<&'static str> {
+    let lower = kind.to_lowercase();
+    let plural = if lower.ends_with("endpoints") {
+        lower
+    } else if let Some(stem) = lower.strip_suffix('y') {
+        format!("{}ies", stem)
+    } else if lower.ends_with("ss") {
+        format!("{}es", lower)
+    } else {
+        format!("{}s", lower)
+    };
+    RESOURCES.iter().find(|(name, _, _)| *name == plural).map(|(name, _, _)| *name)
+}
+
+/// The API group of a resource, "" for the core group.
+pub(crate) fn group(resource: &str) -> &'static str {
+    match resource {
+        "deployments" | "replicasets" | "statefulsets" | "daemonsets" | "controllerrevisions" => "apps",
+        "jobs" | "cronjobs" => "batch",
+        "networkpolicies" | "ingresses" => "networking.k8s.io",
+        "horizontalpodautoscalers" => "autoscaling",
+        "poddisruptionbudgets" => "policy",
+        "leases" => "coordination.k8s.io",
+        "roles" | "rolebindings" | "clusterroles" | "clusterrolebindings" => "rbac.authorization.k8s.io",
+        "priorityclasses" => "scheduling.k8s.io",
+        "storageclasses" => "storage.k8s.io",
+        "validatingwebhookconfigurations" | "mutatingwebhookconfigurations" => "admissionregistration.k8s.io",
+        _ => "",
+    }
+}
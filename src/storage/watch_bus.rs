@@ -0,0 +1,46 @@
+// In-process fan-out of watch events. Each write's event is stored in the
+// events table and sent on the channel of its resource type; watch streams
+// take new events from the channel as they're sent, and read the table only
+// to replay the ones from before they started or that they fell behind on.
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+// Events a channel holds for its slowest watch before that watch lags and
+// has to replay from the table
+const CHANNEL_CAPACITY: usize = 1024;
+
+/// A watch event as sent to the watches of its resource type.
+#[derive(Debug, Clone)]
+pub(crate) struct WatchEvent {
+    pub resource_version: i64,
+    pub namespace: Option<String>,
+    pub event_type: String,
+    pub object: Value,
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct WatchBus {
+    channels: Mutex<HashMap<String, broadcast::Sender<Arc<WatchEvent>>>>,
+}
+
+impl WatchBus {
+    /// Sends the event to the current watches of `resource_type`, if any.
+    pub fn publish(&self, resource_type: &str, event: WatchEvent) {
+        let channels = self.channels.lock().unwrap();
+        if let Some(sender) = channels.get(resource_type) {
+            // Fails only when no watch is listening, which is fine
+            let _ = sender.send(Arc::new(event));
+        }
+    }
+
+    /// Receives the events of `resource_type` sent from now on.
+    pub fn subscribe(&self, resource_type: &str) -> broadcast::Receiver<Arc<WatchEvent>> {
+        let mut channels = self.channels.lock().unwrap();
+        channels
+            .entry(resource_type.to_string())
+            .or_insert_with(|| broadcast::channel(CHANNEL_CAPACITY).0)
+            .subscribe()
+    }
+}
@@ -3,17 +3,244 @@ use chrono::{Duration, Utc};
 use futures::Stream;
 use serde_json::Value;
 use sqlx::{Row, SqlitePool};
+use std::collections::VecDeque;
 use std::pin::Pin;
-use tokio_stream::StreamExt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tokio::sync::broadcast::{self, error::RecvError};
 use uuid::Uuid;
 
+use super::db::Db;
+use super::watch_bus::{WatchBus, WatchEvent};
+use super::finalizer_store;
+use super::owner_store;
+use super::resource_version;
+use super::writer_store;
+use crate::models::time;
+
+// Events a watch reads from the table at a time
+const WATCH_PAGE_SIZE: i64 = 100;
+
+// Versions a watch remembers sending, to skip an event it gets twice
+const SENT_VERSIONS: usize = 2048;
+
+/// Records the watch event for a write to `object`, which is the object as
+/// its store returns it. `resource_type` is the resource's plural name, as
+/// in its list URL, and is what watches on that list look for. A deletion
+/// is a write of its own and gets a new resource version.
+pub(crate) async fn record(db: &Db, resource_type: &str, event_type: &str, object: &Value) -> Result<()> {
+    // Usually the write's own transaction, which its store commits
+    let db = db.begin_write().await?;
+    let mut object = object.clone();
+    finalizer_store::apply(&db, resource_type, &mut object).await?;
+    owner_store::index(&db, resource_type, event_type, &object).await?;
+    writer_store::forget(&db, event_type, &object).await?;
+    let version = match object["metadata"]["resourceVersion"].as_str().and_then(|rv| rv.parse().ok()) {
+        Some(version) if event_type != "DELETED" => version,
+        _ => {
+            let version = resource_version::next(&db).await?;
+            object["metadata"]["resourceVersion"] = Value::from(version.to_string());
+            version
+        }
+    };
+    let metadata = &object["metadata"];
+    sqlx::query(
+        "INSERT INTO events (resource_type, resource_uid, resource_name, resource_namespace, event_type, resource_version, timestamp, object)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(resource_type)
+    .bind(metadata["uid"].as_str())
+    .bind(metadata["name"].as_str())
+    .bind(metadata["namespace"].as_str())
+    .bind(event_type)
+    .bind(version)
+    .bind(time::now())
+    .bind(object.to_string())
+    .execute(&db)
+    .await?;
+
+    let event = WatchEvent {
+        resource_version: version,
+        namespace: metadata["namespace"].as_str().map(str::to_string),
+        event_type: event_type.to_string(),
+        object,
+    };
+    db.publish(resource_type, event);
+    db.commit_write().await?;
+    Ok(())
+}
+
 pub struct WatchStore {
     pool: SqlitePool,
+    // Where events recorded through the store are written
+    db: Db,
+    bus: Arc<WatchBus>,
+    active: Arc<AtomicUsize>,
+}
+
+// Counts a watch stream as active for as long as it exists
+struct ActiveWatch(Arc<AtomicUsize>);
+
+impl ActiveWatch {
+    fn new(active: Arc<AtomicUsize>) -> Self {
+        active.fetch_add(1, Ordering::SeqCst);
+        Self(active)
+    }
+}
+
+impl Drop for ActiveWatch {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// The objects of one resource type as they're written; see
+/// `WatchStore::changes`.
+pub struct Changes {
+    live: broadcast::Receiver<Arc<WatchEvent>>,
+}
+
+impl Changes {
+    /// The next object written. Ones written while the reader fell behind
+    /// are skipped.
+    pub async fn next(&mut self) -> Option<Value> {
+        loop {
+            match self.live.recv().await {
+                Ok(event) => return Some(event.object.clone()),
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    }
 }
 
 impl WatchStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub(crate) fn new(pool: SqlitePool, db: Db, active: Arc<AtomicUsize>) -> Self {
+        Self { pool, bus: db.bus().clone(), db, active }
+    }
+
+    /// How many watch streams are open. A stream closes when the watch it
+    /// serves ends, including when its client goes away.
+    pub fn active_watches(&self) -> usize {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// The objects of `resource_type` written from now on, for loops that
+    /// act on writes as they happen rather than watching.
+    pub fn changes(&self, resource_type: &str) -> Changes {
+        Changes { live: self.bus.subscribe(resource_type) }
+    }
+
+    /// Records the watch event for a write made outside the stores.
+    pub async fn record(&self, resource_type: &str, event_type: &str, object: &Value) -> Result<()> {
+        record(&self.db, resource_type, event_type, object).await
+    }
+
+    /// The resource version of the latest write, which is what a list is
+    /// current as of and where a watch following it starts.
+    pub async fn latest_version(&self) -> Result<i64> {
+        resource_version::current(&self.db).await
+    }
+
+    /// The resource version watch events have been compacted up to. A watch
+    /// can only resume from a version after it.
+    pub async fn compacted_version(&self) -> Result<i64> {
+        let version = sqlx::query_scalar::<_, i64>("SELECT revision FROM watch_compaction WHERE id = 1")
+            .fetch_one(&self.pool)
+            .await?;
+        Ok(version)
+    }
+
+    /// Drops all but the latest `keep` versions of watch events, returning
+    /// how many were dropped. Kubernetes Events share the table and are left
+    /// alone.
+    pub async fn compact(&self, keep: i64) -> Result<u64> {
+        let revision = self.latest_version().await? - keep;
+        if revision <= self.compacted_version().await? {
+            return Ok(0);
+        }
+
+        let mut tx = self.pool.begin().await?;
+        let result = sqlx::query("DELETE FROM events WHERE resource_version <= ? AND involved_object_name IS NULL")
+            .bind(revision)
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query("UPDATE watch_compaction SET revision = ? WHERE id = 1")
+            .bind(revision)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// The `items` of a current list of `resource_type` objects as they were
+    /// at `version`, rebuilt by undoing the writes made since from their
+    /// watch events. Objects the writes touched are kept if `keep` selects
+    /// them as they were then. None if one of them was written before the
+    /// compacted version, so its state at `version` is gone.
+    pub async fn items_at(
+        &self,
+        resource_type: &str,
+        namespace: Option<&str>,
+        version: i64,
+        mut items: Vec<Value>,
+        keep: impl Fn(&Value) -> bool,
+    ) -> Result<Option<Vec<Value>>> {
+        // The first write after the version tells whether the object existed
+        let rows = sqlx::query(
+            "SELECT resource_uid, event_type FROM events
+             WHERE resource_type = ? AND resource_version > ? AND (? IS NULL OR resource_namespace = ?)
+             ORDER BY resource_version"
+        )
+        .bind(resource_type)
+        .bind(version)
+        .bind(namespace)
+        .bind(namespace)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut touched: Vec<(String, String)> = Vec::new();
+        for row in rows {
+            let uid: Option<String> = row.get("resource_uid");
+            if let Some(uid) = uid.filter(|uid| !touched.iter().any(|(seen, _)| seen == uid)) {
+                touched.push((uid, row.get("event_type")));
+            }
+        }
+
+        for (uid, first_event) in touched {
+            let then = if first_event == "ADDED" {
+                None
+            } else {
+                let row = sqlx::query(
+                    "SELECT event_type, object FROM events
+                     WHERE resource_type = ? AND resource_uid = ? AND resource_version <= ?
+                     ORDER BY resource_version DESC LIMIT 1"
+                )
+                .bind(resource_type)
+                .bind(&uid)
+                .bind(version)
+                .fetch_optional(&self.pool)
+                .await?;
+                let Some(row) = row else {
+                    return Ok(None);
+                };
+                let object: Value = serde_json::from_str(&row.get::<String, _>("object"))?;
+                (row.get::<String, _>("event_type") != "DELETED" && keep(&object)).then_some(object)
+            };
+
+            let current = items.iter().position(|item| item["metadata"]["uid"] == uid.as_str());
+            match (current, then) {
+                (Some(i), Some(object)) => items[i] = object,
+                (Some(i), None) => {
+                    items.remove(i);
+                }
+                (None, Some(object)) => items.push(object),
+                (None, None) => {}
+            }
+        }
+
+        Ok(Some(items))
     }
 
     pub async fn create_watch_cursor(&self, resource_type: &str) -> Result<String> {
@@ -36,8 +263,8 @@ impl WatchStore {
         .bind(&cursor_id)
         .bind(resource_type)
         .bind(last_event_id)
-        .bind(now.to_rfc3339())
-        .bind(expires_at.to_rfc3339())
+        .bind(time::format(now))
+        .bind(time::format(expires_at))
         .execute(&self.pool)
         .await?;
         
@@ -57,8 +284,8 @@ impl WatchStore {
         
         let rows = sqlx::query(
             "SELECT event_type, object FROM events 
-             WHERE resource_type = ? AND id > ?
-             ORDER BY id ASC
+             WHERE resource_type = ? AND resource_version > ?
+             ORDER BY resource_version ASC
              LIMIT 100"
         )
         .bind(resource_type)
@@ -81,6 +308,10 @@ impl WatchStore {
         Ok(events)
     }
 
+    /// The watch events of `resource_type` after `resource_version`, in
+    /// `namespace` if given: first those already in the events table, then
+    /// each new one as it's written. The stream ends if reading the table
+    /// fails.
     pub async fn watch_stream(
         &self,
         resource_type: String,
@@ -88,66 +319,55 @@ impl WatchStore {
         resource_version: Option<String>,
     ) -> Result<Pin<Box<dyn Stream<Item = Result<Value>> + Send>>> {
         let pool = self.pool.clone();
-        let mut last_id = if let Some(rv) = resource_version {
+        let active = ActiveWatch::new(self.active.clone());
+        // Subscribed before the replay, so that no event falls in between
+        let mut live = self.bus.subscribe(&resource_type);
+        let mut last_version = if let Some(rv) = resource_version {
             rv.parse::<i64>().unwrap_or(0)
         } else {
             0
         };
-        
+
         let stream = async_stream::stream! {
-            loop {
-                let query = if let Some(ref ns) = namespace {
-                    sqlx::query(
-                        "SELECT id, event_type, object FROM events 
-                         WHERE resource_type = ? AND resource_namespace = ? AND id > ?
-                         ORDER BY id ASC
-                         LIMIT 10"
-                    )
-                    .bind(&resource_type)
-                    .bind(ns)
-                    .bind(last_id)
-                } else {
-                    sqlx::query(
-                        "SELECT id, event_type, object FROM events 
-                         WHERE resource_type = ? AND id > ?
-                         ORDER BY id ASC
-                         LIMIT 10"
-                    )
-                    .bind(&resource_type)
-                    .bind(last_id)
-                };
-                
-                match query.fetch_all(&pool).await {
-                    Ok(rows) => {
-                        for row in rows {
-                            let id: i64 = row.get("id");
-                            let event_type: String = row.get("event_type");
-                            let object_str: String = row.get("object");
-                            
-                            if let Ok(mut object) = serde_json::from_str::<Value>(&object_str) {
-                                // Update resource version to the event ID
-                                if let Some(metadata) = object.get_mut("metadata") {
-                                    metadata["resourceVersion"] = serde_json::json!(id.to_string());
-                                }
-                                
-                                let event = serde_json::json!({
-                                    "type": event_type,
-                                    "object": object
-                                });
-                                
-                                last_id = id;
-                                yield Ok(event);
-                            }
+            let _active = active;
+            // Versions sent lately; an event can both be replayed and come
+            // from the bus, and writes can commit out of version order
+            let mut sent: VecDeque<i64> = VecDeque::new();
+            'replay: loop {
+                loop {
+                    let page = match replay(&pool, &resource_type, namespace.as_deref(), last_version).await {
+                        Ok(page) => page,
+                        Err(e) => {
+                            yield Err(anyhow::anyhow!("Database error: {}", e));
+                            break 'replay;
                         }
+                    };
+                    let backlog = page.len() as i64 == WATCH_PAGE_SIZE;
+                    for (version, event) in page {
+                        last_version = last_version.max(version);
+                        remember(&mut sent, version);
+                        yield Ok(event);
                     }
-                    Err(e) => {
-                        yield Err(anyhow::anyhow!("Database error: {}", e));
+                    if !backlog {
                         break;
                     }
                 }
-                
-                // Wait a bit before checking for new events
-                tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+
+                loop {
+                    match live.recv().await {
+                        Ok(event) => {
+                            if (namespace.is_some() && event.namespace != namespace) || sent.contains(&event.resource_version) {
+                                continue;
+                            }
+                            last_version = last_version.max(event.resource_version);
+                            remember(&mut sent, event.resource_version);
+                            yield Ok(serde_json::json!({ "type": event.event_type, "object": event.object }));
+                        }
+                        // Fell too far behind the bus: catch up from the table
+                        Err(RecvError::Lagged(_)) => continue 'replay,
+                        Err(RecvError::Closed) => break 'replay,
+                    }
+                }
             }
         };
         
@@ -155,7 +375,7 @@ impl WatchStore {
     }
 
     pub async fn cleanup_expired_cursors(&self) -> Result<()> {
-        let now = Utc::now().to_rfc3339();
+        let now = time::now();
         
         sqlx::query("DELETE FROM watch_cursors WHERE expires_at < ?")
             .bind(now)
@@ -164,4 +384,37 @@ impl WatchStore {
         
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+// A page of the stored watch events after `since`, with their versions
+async fn replay(pool: &SqlitePool, resource_type: &str, namespace: Option<&str>, since: i64) -> Result<Vec<(i64, Value)>> {
+    let rows = sqlx::query(
+        "SELECT resource_version, event_type, object FROM events
+         WHERE resource_type = ? AND (? IS NULL OR resource_namespace = ?) AND resource_version > ?
+         ORDER BY resource_version ASC
+         LIMIT ?"
+    )
+    .bind(resource_type)
+    .bind(namespace)
+    .bind(namespace)
+    .bind(since)
+    .bind(WATCH_PAGE_SIZE)
+    .fetch_all(pool)
+    .await?;
+
+    let mut events = Vec::new();
+    for row in rows {
+        let event_type: String = row.get("event_type");
+        if let Ok(object) = serde_json::from_str::<Value>(&row.get::<String, _>("object")) {
+            events.push((row.get("resource_version"), serde_json::json!({ "type": event_type, "object": object })));
+        }
+    }
+    Ok(events)
+}
+
+fn remember(sent: &mut VecDeque<i64>, version: i64) {
+    if sent.len() == SENT_VERSIONS {
+        sent.pop_front();
+    }
+    sent.push_back(version);
+}
@@ -1,16 +1,23 @@
 use anyhow::Result;
 use serde_json::{json, Value};
-use sqlx::{Row, SqlitePool};
+use sqlx::Row;
 use uuid::Uuid;
 
+use super::db::Db;
+use super::field_selector;
+use super::list_selector::ListSelector;
+use super::watch_store;
+use super::resource_version;
+use crate::models::time;
+
 // ValidatingWebhookConfiguration storage
 pub struct ValidatingWebhookStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl ValidatingWebhookStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, mut vwc: Value) -> Result<Value> {
@@ -20,24 +27,32 @@ impl ValidatingWebhookStore {
         let labels = vwc["metadata"].get("labels").cloned().unwrap_or(json!({}));
         let annotations = vwc["metadata"].get("annotations").cloned().unwrap_or(json!({}));
 
+        let now = time::now();
+
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(
-            "INSERT INTO validatingwebhookconfigurations (uid, name, webhooks, labels, annotations, resource_version, generation)
-             VALUES (?, ?, ?, ?, ?, 1, 1)"
+            "INSERT INTO validatingwebhookconfigurations (uid, name, webhooks, labels, annotations, resource_version, generation, creation_timestamp)
+             VALUES (?, ?, ?, ?, ?, ?, 1, ?)"
         )
         .bind(&uid)
         .bind(&name)
         .bind(serde_json::to_string(&webhooks)?)
         .bind(serde_json::to_string(&labels)?)
         .bind(serde_json::to_string(&annotations)?)
-        .execute(&self.pool)
+        .bind(version)
+        .bind(&now)
+        .execute(&tx.db)
         .await?;
 
         vwc["metadata"]["uid"] = json!(uid);
-        vwc["metadata"]["resourceVersion"] = json!("1");
+        vwc["metadata"]["resourceVersion"] = json!(version.to_string());
         vwc["metadata"]["generation"] = json!(1);
-        vwc["metadata"]["creationTimestamp"] = json!(chrono::Utc::now().to_rfc3339());
+        vwc["metadata"]["creationTimestamp"] = json!(now);
 
-        self.record_event(&uid, "ValidatingWebhookConfiguration", &name, "Created", "ValidatingWebhookConfiguration created").await?;
+        tx.record_event(&uid, "ValidatingWebhookConfiguration", &name, "Created", "ValidatingWebhookConfiguration created").await?;
+        watch_store::record(&tx.db, "validatingwebhookconfigurations", "ADDED", &vwc).await?;
+        tx.db.commit_write().await?;
         Ok(vwc)
     }
 
@@ -47,7 +62,7 @@ impl ValidatingWebhookStore {
              FROM validatingwebhookconfigurations WHERE name = ? AND deletion_timestamp IS NULL"
         )
         .bind(name)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
 
         if let Some(row) = row {
@@ -69,10 +84,10 @@ impl ValidatingWebhookStore {
                     "resourceVersion": resource_version.to_string(),
                     "generation": generation,
                     "creationTimestamp": creation_timestamp,
-                    "labels": serde_json::from_str(&labels)?,
-                    "annotations": serde_json::from_str(&annotations)?
+                    "labels": serde_json::from_str::<Value>(&labels)?,
+                    "annotations": serde_json::from_str::<Value>(&annotations)?
                 },
-                "webhooks": serde_json::from_str(&webhooks)?
+                "webhooks": serde_json::from_str::<Value>(&webhooks)?
             });
 
             Ok(Some(vwc))
@@ -86,8 +101,8 @@ impl ValidatingWebhookStore {
             .ok_or_else(|| anyhow::anyhow!("ValidatingWebhookConfiguration not found"))?;
 
         let uid = current["metadata"]["uid"].as_str().unwrap();
-        let resource_version: i64 = current["metadata"]["resourceVersion"].as_str().unwrap().parse()?;
-        let new_resource_version = resource_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_resource_version = resource_version::next(&tx.db).await?;
         let generation: i64 = current["metadata"]["generation"].as_i64().unwrap();
         let new_generation = if vwc["webhooks"] != current["webhooks"] { generation + 1 } else { generation };
 
@@ -106,7 +121,7 @@ impl ValidatingWebhookStore {
         .bind(new_resource_version)
         .bind(new_generation)
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
         vwc["metadata"]["uid"] = json!(uid);
@@ -114,7 +129,9 @@ impl ValidatingWebhookStore {
         vwc["metadata"]["generation"] = json!(new_generation);
         vwc["metadata"]["creationTimestamp"] = current["metadata"]["creationTimestamp"].clone();
 
-        self.record_event(uid, "ValidatingWebhookConfiguration", name, "Updated", "ValidatingWebhookConfiguration updated").await?;
+        tx.record_event(uid, "ValidatingWebhookConfiguration", name, "Updated", "ValidatingWebhookConfiguration updated").await?;
+        watch_store::record(&tx.db, "validatingwebhookconfigurations", "MODIFIED", &vwc).await?;
+        tx.db.commit_write().await?;
         Ok(vwc)
     }
 
@@ -124,25 +141,34 @@ impl ValidatingWebhookStore {
 
         let uid = vwc["metadata"]["uid"].as_str().unwrap();
 
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
-            "UPDATE validatingwebhookconfigurations SET deletion_timestamp = CURRENT_TIMESTAMP 
+            "UPDATE validatingwebhookconfigurations SET deletion_timestamp = ? 
              WHERE name = ? AND deletion_timestamp IS NULL"
         )
+        .bind(time::now())
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
-        self.record_event(uid, "ValidatingWebhookConfiguration", name, "Deleted", "ValidatingWebhookConfiguration deleted").await?;
+        tx.record_event(uid, "ValidatingWebhookConfiguration", name, "Deleted", "ValidatingWebhookConfiguration deleted").await?;
+        watch_store::record(&tx.db, "validatingwebhookconfigurations", "DELETED", &vwc).await?;
+        tx.db.commit_write().await?;
         Ok(vwc)
     }
 
     pub async fn list(&self) -> Result<Value> {
-        let rows = sqlx::query(
+        self.list_matching(&ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, selector: &ListSelector) -> Result<Value> {
+        let query = format!(
             "SELECT uid, name, webhooks, labels, annotations, resource_version, generation, creation_timestamp
-             FROM validatingwebhookconfigurations WHERE deletion_timestamp IS NULL"
-        )
-        .fetch_all(&self.pool)
-        .await?;
+             FROM validatingwebhookconfigurations WHERE deletion_timestamp IS NULL{}",
+            selector.sql(field_selector::CLUSTER_SCOPED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -164,10 +190,10 @@ impl ValidatingWebhookStore {
                     "resourceVersion": resource_version.to_string(),
                     "generation": generation,
                     "creationTimestamp": creation_timestamp,
-                    "labels": serde_json::from_str(&labels)?,
-                    "annotations": serde_json::from_str(&annotations)?
+                    "labels": serde_json::from_str::<Value>(&labels)?,
+                    "annotations": serde_json::from_str::<Value>(&annotations)?
                 },
-                "webhooks": serde_json::from_str(&webhooks)?
+                "webhooks": serde_json::from_str::<Value>(&webhooks)?
             });
 
             items.push(vwc);
@@ -182,10 +208,11 @@ impl ValidatingWebhookStore {
 
     async fn record_event(&self, uid: &str, resource_type: &str, name: &str, reason: &str, message: &str) -> Result<()> {
         let event_uid = Uuid::new_v4().to_string();
+        let now = time::now();
         sqlx::query(
             "INSERT INTO events (uid, namespace, involved_object_uid, involved_object_kind, 
              involved_object_name, reason, message, event_time, first_timestamp, last_timestamp, count, type)
-             VALUES (?, '', ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, 1, 'Normal')"
+             VALUES (?, '', ?, ?, ?, ?, ?, ?, ?, ?, 1, 'Normal')"
         )
         .bind(&event_uid)
         .bind(uid)
@@ -193,7 +220,10 @@ impl ValidatingWebhookStore {
         .bind(name)
         .bind(reason)
         .bind(message)
-        .execute(&self.pool)
+        .bind(&now)
+        .bind(&now)
+        .bind(&now)
+        .execute(&self.db)
         .await?;
         Ok(())
     }
@@ -201,12 +231,12 @@ impl ValidatingWebhookStore {
 
 // MutatingWebhookConfiguration storage
 pub struct MutatingWebhookStore {
-    pool: SqlitePool,
+    db: Db,
 }
 
 impl MutatingWebhookStore {
-    pub fn new(pool: SqlitePool) -> Self {
-        Self { pool }
+    pub fn new(db: Db) -> Self {
+        Self { db }
     }
 
     pub async fn create(&self, mut mwc: Value) -> Result<Value> {
@@ -216,24 +246,32 @@ impl MutatingWebhookStore {
         let labels = mwc["metadata"].get("labels").cloned().unwrap_or(json!({}));
         let annotations = mwc["metadata"].get("annotations").cloned().unwrap_or(json!({}));
 
+        let now = time::now();
+
+        let tx = Self::new(self.db.begin_write().await?);
+        let version = resource_version::next(&tx.db).await?;
         sqlx::query(
-            "INSERT INTO mutatingwebhookconfigurations (uid, name, webhooks, labels, annotations, resource_version, generation)
-             VALUES (?, ?, ?, ?, ?, 1, 1)"
+            "INSERT INTO mutatingwebhookconfigurations (uid, name, webhooks, labels, annotations, resource_version, generation, creation_timestamp)
+             VALUES (?, ?, ?, ?, ?, ?, 1, ?)"
         )
         .bind(&uid)
         .bind(&name)
         .bind(serde_json::to_string(&webhooks)?)
         .bind(serde_json::to_string(&labels)?)
         .bind(serde_json::to_string(&annotations)?)
-        .execute(&self.pool)
+        .bind(version)
+        .bind(&now)
+        .execute(&tx.db)
         .await?;
 
         mwc["metadata"]["uid"] = json!(uid);
-        mwc["metadata"]["resourceVersion"] = json!("1");
+        mwc["metadata"]["resourceVersion"] = json!(version.to_string());
         mwc["metadata"]["generation"] = json!(1);
-        mwc["metadata"]["creationTimestamp"] = json!(chrono::Utc::now().to_rfc3339());
+        mwc["metadata"]["creationTimestamp"] = json!(now);
 
-        self.record_event(&uid, "MutatingWebhookConfiguration", &name, "Created", "MutatingWebhookConfiguration created").await?;
+        tx.record_event(&uid, "MutatingWebhookConfiguration", &name, "Created", "MutatingWebhookConfiguration created").await?;
+        watch_store::record(&tx.db, "mutatingwebhookconfigurations", "ADDED", &mwc).await?;
+        tx.db.commit_write().await?;
         Ok(mwc)
     }
 
@@ -243,7 +281,7 @@ impl MutatingWebhookStore {
              FROM mutatingwebhookconfigurations WHERE name = ? AND deletion_timestamp IS NULL"
         )
         .bind(name)
-        .fetch_optional(&self.pool)
+        .fetch_optional(&self.db)
         .await?;
 
         if let Some(row) = row {
@@ -265,10 +303,10 @@ impl MutatingWebhookStore {
                     "resourceVersion": resource_version.to_string(),
                     "generation": generation,
                     "creationTimestamp": creation_timestamp,
-                    "labels": serde_json::from_str(&labels)?,
-                    "annotations": serde_json::from_str(&annotations)?
+                    "labels": serde_json::from_str::<Value>(&labels)?,
+                    "annotations": serde_json::from_str::<Value>(&annotations)?
                 },
-                "webhooks": serde_json::from_str(&webhooks)?
+                "webhooks": serde_json::from_str::<Value>(&webhooks)?
             });
 
             Ok(Some(mwc))
@@ -282,8 +320,8 @@ impl MutatingWebhookStore {
             .ok_or_else(|| anyhow::anyhow!("MutatingWebhookConfiguration not found"))?;
 
         let uid = current["metadata"]["uid"].as_str().unwrap();
-        let resource_version: i64 = current["metadata"]["resourceVersion"].as_str().unwrap().parse()?;
-        let new_resource_version = resource_version + 1;
+        let tx = Self::new(self.db.begin_write().await?);
+        let new_resource_version = resource_version::next(&tx.db).await?;
         let generation: i64 = current["metadata"]["generation"].as_i64().unwrap();
         let new_generation = if mwc["webhooks"] != current["webhooks"] { generation + 1 } else { generation };
 
@@ -302,7 +340,7 @@ impl MutatingWebhookStore {
         .bind(new_resource_version)
         .bind(new_generation)
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
         mwc["metadata"]["uid"] = json!(uid);
@@ -310,7 +348,9 @@ impl MutatingWebhookStore {
         mwc["metadata"]["generation"] = json!(new_generation);
         mwc["metadata"]["creationTimestamp"] = current["metadata"]["creationTimestamp"].clone();
 
-        self.record_event(uid, "MutatingWebhookConfiguration", name, "Updated", "MutatingWebhookConfiguration updated").await?;
+        tx.record_event(uid, "MutatingWebhookConfiguration", name, "Updated", "MutatingWebhookConfiguration updated").await?;
+        watch_store::record(&tx.db, "mutatingwebhookconfigurations", "MODIFIED", &mwc).await?;
+        tx.db.commit_write().await?;
         Ok(mwc)
     }
 
@@ -320,25 +360,34 @@ impl MutatingWebhookStore {
 
         let uid = mwc["metadata"]["uid"].as_str().unwrap();
 
+        let tx = Self::new(self.db.begin_write().await?);
         sqlx::query(
-            "UPDATE mutatingwebhookconfigurations SET deletion_timestamp = CURRENT_TIMESTAMP 
+            "UPDATE mutatingwebhookconfigurations SET deletion_timestamp = ? 
              WHERE name = ? AND deletion_timestamp IS NULL"
         )
+        .bind(time::now())
         .bind(name)
-        .execute(&self.pool)
+        .execute(&tx.db)
         .await?;
 
-        self.record_event(uid, "MutatingWebhookConfiguration", name, "Deleted", "MutatingWebhookConfiguration deleted").await?;
+        tx.record_event(uid, "MutatingWebhookConfiguration", name, "Deleted", "MutatingWebhookConfiguration deleted").await?;
+        watch_store::record(&tx.db, "mutatingwebhookconfigurations", "DELETED", &mwc).await?;
+        tx.db.commit_write().await?;
         Ok(mwc)
     }
 
     pub async fn list(&self) -> Result<Value> {
-        let rows = sqlx::query(
+        self.list_matching(&ListSelector::default()).await
+    }
+
+    pub async fn list_matching(&self, selector: &ListSelector) -> Result<Value> {
+        let query = format!(
             "SELECT uid, name, webhooks, labels, annotations, resource_version, generation, creation_timestamp
-             FROM mutatingwebhookconfigurations WHERE deletion_timestamp IS NULL"
-        )
-        .fetch_all(&self.pool)
-        .await?;
+             FROM mutatingwebhookconfigurations WHERE deletion_timestamp IS NULL{}",
+            selector.sql(field_selector::CLUSTER_SCOPED)?
+        );
+
+        let rows = selector.bind(sqlx::query(&query)).fetch_all(&self.db).await?;
 
         let mut items = Vec::new();
         for row in rows {
@@ -360,10 +409,10 @@ impl MutatingWebhookStore {
                     "resourceVersion": resource_version.to_string(),
                     "generation": generation,
                     "creationTimestamp": creation_timestamp,
-                    "labels": serde_json::from_str(&labels)?,
-                    "annotations": serde_json::from_str(&annotations)?
+                    "labels": serde_json::from_str::<Value>(&labels)?,
+                    "annotations": serde_json::from_str::<Value>(&annotations)?
                 },
-                "webhooks": serde_json::from_str(&webhooks)?
+                "webhooks": serde_json::from_str::<Value>(&webhooks)?
             });
 
             items.push(mwc);
@@ -378,10 +427,11 @@ impl MutatingWebhookStore {
 
     async fn record_event(&self, uid: &str, resource_type: &str, name: &str, reason: &str, message: &str) -> Result<()> {
         let event_uid = Uuid::new_v4().to_string();
+        let now = time::now();
         sqlx::query(
             "INSERT INTO events (uid, namespace, involved_object_uid, involved_object_kind, 
              involved_object_name, reason, message, event_time, first_timestamp, last_timestamp, count, type)
-             VALUES (?, '', ?, ?, ?, ?, ?, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, CURRENT_TIMESTAMP, 1, 'Normal')"
+             VALUES (?, '', ?, ?, ?, ?, ?, ?, ?, ?, 1, 'Normal')"
         )
         .bind(&event_uid)
         .bind(uid)
@@ -389,7 +439,10 @@ impl MutatingWebhookStore {
         .bind(name)
         .bind(reason)
         .bind(message)
-        .execute(&self.pool)
+        .bind(&now)
+        .bind(&now)
+        .bind(&now)
+        .execute(&self.db)
         .await?;
         Ok(())
     }
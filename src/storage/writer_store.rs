@@ -0,0 +1,166 @@
+use anyhow::Result;
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::collections::HashMap;
+
+use super::db::Db;
+use super::tables;
+use crate::models::time;
+
+/// Keeps track of which clients (field managers) have written each object:
+/// one row per object and manager, holding the manager's latest write.
+pub struct WriterStore {
+    db: Db,
+}
+
+impl WriterStore {
+    pub fn new(db: Db) -> Self {
+        Self { db }
+    }
+
+    /// Records a successful write of `object` by `manager`, unless the
+    /// object is gone by now: a delete that removed it leaves no writers.
+    pub async fn record(
+        &self,
+        object: &Value,
+        manager: &str,
+        user_agent: Option<&str>,
+        operation: &str,
+    ) -> Result<()> {
+        let metadata = &object["metadata"];
+        let kind = object["kind"].as_str().unwrap_or("");
+        // Objects of kinds krust doesn't keep are taken to exist
+        let stored = match tables::resource_for_kind(kind).and_then(tables::table) {
+            Some((table, _)) => format!("EXISTS (SELECT 1 FROM {} WHERE uid = ?6 AND deletion_timestamp IS NULL)", table),
+            None => "true".to_string(),
+        };
+
+        let sql = format!(
+            "INSERT INTO object_writers (kind, namespace, name, manager, api_version, uid, user_agent, operation, timestamp, first_write, last_write)
+             SELECT ?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, next, next
+             FROM (SELECT ifnull(max(last_write), 0) + 1 AS next FROM object_writers)
+             WHERE {}
+             ON CONFLICT (kind, namespace, name, manager) DO UPDATE SET
+                 api_version = excluded.api_version,
+                 uid = excluded.uid,
+                 user_agent = excluded.user_agent,
+                 operation = excluded.operation,
+                 timestamp = excluded.timestamp,
+                 writes = writes + 1,
+                 last_write = excluded.last_write",
+            stored
+        );
+        sqlx::query(&sql)
+            .bind(kind)
+            .bind(metadata["namespace"].as_str().unwrap_or(""))
+            .bind(metadata["name"].as_str().unwrap_or(""))
+            .bind(manager)
+            .bind(object["apiVersion"].as_str())
+            .bind(metadata["uid"].as_str())
+            .bind(user_agent)
+            .bind(operation)
+            .bind(time::now())
+            .execute(&self.db)
+            .await?;
+
+        Ok(())
+    }
+
+    /// The metadata.managedFields of the objects with the given uids, keyed
+    /// by uid: an entry per manager that has written the object, in the order
+    /// they first did. Like kube-apiserver, every write but an apply is an
+    /// Update.
+    pub async fn managed_fields(&self, uids: &[&str]) -> Result<HashMap<String, Vec<Value>>> {
+        let rows = sqlx::query(
+            "SELECT uid, manager, api_version, operation, timestamp FROM object_writers
+             WHERE uid IN (SELECT value FROM json_each(?))
+             ORDER BY first_write"
+        )
+        .bind(json!(uids).to_string())
+        .fetch_all(&self.db)
+        .await?;
+
+        let mut managed_fields: HashMap<String, Vec<Value>> = HashMap::new();
+        for row in rows {
+            let operation = match row.get::<String, _>("operation").as_str() {
+                "Apply" => "Apply",
+                _ => "Update",
+            };
+            managed_fields.entry(row.get("uid")).or_default().push(json!({
+                "manager": row.get::<String, _>("manager"),
+                "operation": operation,
+                "apiVersion": row.get::<Option<String>, _>("api_version"),
+                "time": row.get::<String, _>("timestamp"),
+            }));
+        }
+        Ok(managed_fields)
+    }
+
+    /// Lists objects with their most recent writer and every manager that has
+    /// written them, optionally narrowed down to a namespace, kind or name.
+    pub async fn list(
+        &self,
+        namespace: Option<&str>,
+        kind: Option<&str>,
+        name: Option<&str>,
+    ) -> Result<Vec<Value>> {
+        // Each object's latest write is its writer row with the highest
+        // last_write; its managers come in the order they first wrote it
+        let rows = sqlx::query(
+            "SELECT w.api_version, w.kind, w.namespace, w.name, w.uid, w.manager, w.user_agent, w.operation, w.timestamp,
+                    o.writes,
+                    (SELECT json_group_array(manager) FROM (
+                        SELECT manager FROM object_writers m
+                        WHERE m.kind = o.kind AND m.namespace = o.namespace AND m.name = o.name
+                        ORDER BY first_write
+                    )) AS managers
+             FROM (
+                 SELECT kind, namespace, name, sum(writes) AS writes, min(first_write) AS first_write, max(last_write) AS last_write
+                 FROM object_writers
+                 WHERE (?1 IS NULL OR namespace = ?1)
+                   AND (?2 IS NULL OR lower(kind) = lower(?2))
+                   AND (?3 IS NULL OR name = ?3)
+                 GROUP BY kind, namespace, name
+             ) o
+             JOIN object_writers w ON w.last_write = o.last_write
+             ORDER BY o.first_write"
+        )
+        .bind(namespace)
+        .bind(kind)
+        .bind(name)
+        .fetch_all(&self.db)
+        .await?;
+
+        rows.into_iter()
+            .map(|row| {
+                let namespace: String = row.get("namespace");
+                let managers: Value = serde_json::from_str(&row.get::<String, _>("managers"))?;
+                Ok(json!({
+                    "apiVersion": row.get::<Option<String>, _>("api_version"),
+                    "kind": row.get::<String, _>("kind"),
+                    "namespace": Some(namespace).filter(|namespace| !namespace.is_empty()),
+                    "name": row.get::<String, _>("name"),
+                    "uid": row.get::<Option<String>, _>("uid"),
+                    "lastModifiedBy": {
+                        "manager": row.get::<String, _>("manager"),
+                        "userAgent": row.get::<Option<String>, _>("user_agent"),
+                        "operation": row.get::<String, _>("operation"),
+                        "time": row.get::<String, _>("timestamp"),
+                    },
+                    "managers": managers,
+                    "writes": row.get::<i64, _>("writes"),
+                }))
+            })
+            .collect()
+    }
+}
+
+/// Forgets the writers of a deleted object, in the transaction of its
+/// deletion, so one created under its name later starts with none.
+pub(crate) async fn forget(db: &Db, event_type: &str, object: &Value) -> Result<()> {
+    let Some(uid) = object["metadata"]["uid"].as_str().filter(|_| event_type == "DELETED") else {
+        return Ok(());
+    };
+    sqlx::query("DELETE FROM object_writers WHERE uid = ?").bind(uid).execute(db).await?;
+    Ok(())
+}
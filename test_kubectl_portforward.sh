@@ -8,7 +8,7 @@ pkill -f "target/debug/krust" || true
 sleep 1
 
 # Clean database
-rm -f krust.db
+cargo run -q -- reset
 
 # Start Krust server in background
 echo "Starting Krust server..."
@@ -2,7 +2,7 @@
 echo "Testing kubectl commands for apps/v1 warnings..."
 
 # Start server
-rm -f krust.db
+cargo run -q -- reset
 cargo run > /dev/null 2>&1 &
 SERVER_PID=$!
 sleep 3
@@ -6,7 +6,7 @@ echo "=== Testing Pod Proxy Feature ==="
 # Clean up any existing state
 pkill -f "target/debug/krust" || true
 sleep 1
-rm -f krust.db
+cargo run -q -- reset
 
 # Start Krust server in background
 echo "Starting Krust server..."
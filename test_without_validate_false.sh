@@ -5,7 +5,7 @@ echo "=== Testing kubectl WITHOUT --validate=false ==="
 
 # Clean up
 pkill -f "target/debug/krust" 2>/dev/null || true
-rm -f krust.db
+cargo run -q -- reset
 
 # Start server
 echo "Starting Krust server..."
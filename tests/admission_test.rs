@@ -0,0 +1,651 @@
+use reqwest;
+use axum::{extract::State, routing::post, Extension, Json, Router};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use krust::api::tls::{self, ClientCertificate};
+use krust::config::TlsConfig;
+use krust::pki::{ClusterCa, Usage};
+use serde_json::{json, Value};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+mod common;
+
+// What the webhook server was sent: the review and who presented a client
+// certificate with it
+type Received = Arc<Mutex<Vec<(Value, Option<String>)>>>;
+
+// Adds a label, and refuses ConfigMaps with a "forbidden" key
+async fn review(
+    State(received): State<Received>,
+    certificate: Option<Extension<ClientCertificate>>,
+    Json(review): Json<Value>,
+) -> Json<Value> {
+    let client = certificate.map(|Extension(ClientCertificate(user))| user.username);
+    received.lock().unwrap().push((review.clone(), client));
+
+    let request = &review["request"];
+    let response = if request["object"]["data"]["forbidden"].is_string() {
+        json!({ "uid": request["uid"], "allowed": false, "status": { "code": 403, "message": "no forbidden keys" } })
+    } else {
+        let patch = json!([{ "op": "add", "path": "/metadata/labels", "value": { "admitted": "true" } }]);
+        json!({
+            "uid": request["uid"],
+            "allowed": true,
+            "patchType": "JSONPatch",
+            "patch": STANDARD.encode(patch.to_string()),
+            "warnings": ["labelled by the webhook"]
+        })
+    };
+    Json(json!({ "apiVersion": "admission.k8s.io/v1", "kind": "AdmissionReview", "response": response }))
+}
+
+async fn slow(Json(review): Json<Value>) -> Json<Value> {
+    tokio::time::sleep(tokio::time::Duration::from_secs(3)).await;
+    let response = json!({ "uid": review["request"]["uid"], "allowed": true });
+    Json(json!({ "apiVersion": "admission.k8s.io/v1", "kind": "AdmissionReview", "response": response }))
+}
+
+// Refuses deleting ConfigMaps with a "keep" key
+async fn guard(State(received): State<Received>, Json(review): Json<Value>) -> Json<Value> {
+    received.lock().unwrap().push((review.clone(), None));
+    let request = &review["request"];
+    let allowed = !request["oldObject"]["data"]["keep"].is_string() || request["operation"] != "DELETE";
+    let response = json!({ "uid": request["uid"], "allowed": allowed, "status": { "code": 403, "message": "kept" } });
+    Json(json!({ "apiVersion": "admission.k8s.io/v1", "kind": "AdmissionReview", "response": response }))
+}
+
+// Annotates the object it's sent through the API before admitting it, the
+// way a webhook keeping an object's bookkeeping might
+async fn touch(State(krust): State<String>, Json(review): Json<Value>) -> Json<Value> {
+    let request = &review["request"];
+    if !request["object"]["metadata"]["annotations"]["touched"].is_string() {
+        let url = format!("{}/api/v1/namespaces/{}/configmaps/{}", krust, request["namespace"].as_str().unwrap(), request["name"].as_str().unwrap());
+        let resp = reqwest::Client::new()
+            .patch(url)
+            .header("Content-Type", "application/merge-patch+json")
+            .body(json!({ "metadata": { "annotations": { "touched": "true" } } }).to_string())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+    }
+    let response = json!({ "uid": request["uid"], "allowed": true });
+    Json(json!({ "apiVersion": "admission.k8s.io/v1", "kind": "AdmissionReview", "response": response }))
+}
+
+/// Serves the webhook over TLS with a certificate from its own CA, asking
+/// for client certificates signed by `client_ca`. Returns its port and CA.
+async fn start_webhook(dir: &Path, client_ca: &Path, received: Received) -> (u16, String) {
+    let router = Router::new()
+        .route("/review", post(review))
+        .route("/slow", post(slow))
+        .route("/guard", post(guard))
+        .with_state(received);
+    serve_webhook(dir, client_ca, router).await
+}
+
+async fn serve_webhook(dir: &Path, client_ca: &Path, router: Router) -> (u16, String) {
+    let ca = ClusterCa::generate().unwrap();
+    let (cert, key) = ca.issue("webhook", &[], Usage::Server(&["webhook.default.svc", "127.0.0.1"])).unwrap();
+    std::fs::write(dir.join("webhook.crt"), cert).unwrap();
+    std::fs::write(dir.join("webhook.key"), key).unwrap();
+    let config = TlsConfig {
+        enabled: true,
+        cert_file: Some(dir.join("webhook.crt").display().to_string()),
+        key_file: Some(dir.join("webhook.key").display().to_string()),
+        client_ca_file: Some(client_ca.display().to_string()),
+    };
+    let acceptor = tls::acceptor(&config, &ca).unwrap();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(tls::serve(listener, router, acceptor));
+    (port, ca.cert_pem().unwrap())
+}
+
+fn configmap(name: &str, data: Value) -> Value {
+    json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": name }, "data": data })
+}
+
+#[tokio::test]
+async fn test_webhooks_behind_a_service_with_mutual_tls() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = krust::Config::parse(&format!("dataDir: {}\n", dir.path().display())).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let received = Received::default();
+    let (port, webhook_ca) = start_webhook(dir.path(), &dir.path().join("pki/ca.crt"), received.clone()).await;
+
+    // A Service without a selector, its endpoint the webhook server
+    let service = json!({
+        "apiVersion": "v1", "kind": "Service",
+        "metadata": { "name": "webhook" },
+        "spec": { "ports": [{ "name": "https", "port": 443, "targetPort": port }] }
+    });
+    let resp = client.post(server.url("/api/v1/namespaces/default/services")).json(&service).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let endpoints = json!({
+        "apiVersion": "v1", "kind": "Endpoints",
+        "metadata": { "name": "webhook" },
+        "subsets": [{ "addresses": [{ "ip": "127.0.0.1" }], "ports": [{ "name": "https", "port": port }] }]
+    });
+    let resp = client.post(server.url("/api/v1/namespaces/default/endpoints")).json(&endpoints).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let rules = json!([{ "apiGroups": [""], "apiVersions": ["v1"], "operations": ["CREATE", "UPDATE"], "resources": ["configmaps"] }]);
+    let client_config = json!({
+        "service": { "namespace": "default", "name": "webhook", "path": "/review" },
+        "caBundle": STANDARD.encode(&webhook_ca)
+    });
+    let mwc = json!({
+        "apiVersion": "admissionregistration.k8s.io/v1", "kind": "MutatingWebhookConfiguration",
+        "metadata": { "name": "labeller" },
+        "webhooks": [{
+            "name": "labeller.krust.io",
+            "clientConfig": client_config,
+            "rules": rules,
+            "admissionReviewVersions": ["v1"],
+            "sideEffects": "None",
+            "timeoutSeconds": 5
+        }]
+    });
+    let resp = client
+        .post(server.url("/apis/admissionregistration.k8s.io/v1/mutatingwebhookconfigurations"))
+        .json(&mwc)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // The webhook labels what it's sent, and is told who's asking over mTLS
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&configmap("settings", json!({ "mode": "fast" })))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    assert_eq!(resp.headers()["warning"], "299 - \"labelled by the webhook\"");
+    let created: Value = resp.json().await.unwrap();
+    assert_eq!(created["metadata"]["labels"]["admitted"], "true");
+    let stored = server.storage.configmaps().get("default", "settings").await.unwrap();
+    assert_eq!(stored["metadata"]["labels"]["admitted"], "true");
+
+    let (review, client_user) = received.lock().unwrap()[0].clone();
+    assert_eq!(client_user.as_deref(), Some("system:kube-apiserver"));
+    assert_eq!(review["apiVersion"], "admission.k8s.io/v1");
+    let request = &review["request"];
+    assert_eq!(request["operation"], "CREATE");
+    assert_eq!(request["kind"], json!({ "group": "", "version": "v1", "kind": "ConfigMap" }));
+    assert_eq!(request["resource"], json!({ "group": "", "version": "v1", "resource": "configmaps" }));
+    assert_eq!(request["namespace"], "default");
+    assert_eq!(request["name"], "settings");
+    assert_eq!(request["object"]["data"]["mode"], "fast");
+    assert!(request["uid"].as_str().is_some_and(|uid| !uid.is_empty()));
+
+    // It can refuse, with its own code and message
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&configmap("nope", json!({ "forbidden": "yes" })))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["message"], "admission webhook \"labeller.krust.io\" denied the request: no forbidden keys");
+    assert!(server.storage.configmaps().get("default", "nope").await.is_err());
+
+    // Secrets aren't covered by its rules
+    let secret = json!({ "apiVersion": "v1", "kind": "Secret", "metadata": { "name": "token" }, "data": {} });
+    let resp = client.post(server.url("/api/v1/namespaces/default/secrets")).json(&secret).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    assert_eq!(received.lock().unwrap().len(), 2);
+
+    // A webhook that answers too late fails the request unless its
+    // failurePolicy is Ignore
+    let vwc = |failure_policy: &str| {
+        json!({
+            "apiVersion": "admissionregistration.k8s.io/v1", "kind": "ValidatingWebhookConfiguration",
+            "metadata": { "name": "slow" },
+            "webhooks": [{
+                "name": "slow.krust.io",
+                "clientConfig": { "url": format!("https://127.0.0.1:{}/slow", port), "caBundle": STANDARD.encode(&webhook_ca) },
+                "rules": rules,
+                "admissionReviewVersions": ["v1"],
+                "sideEffects": "None",
+                "timeoutSeconds": 1,
+                "failurePolicy": failure_policy
+            }]
+        })
+    };
+    let resp = client
+        .post(server.url("/apis/admissionregistration.k8s.io/v1/validatingwebhookconfigurations"))
+        .json(&vwc("Fail"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let started = std::time::Instant::now();
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&configmap("late", json!({})))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 500);
+    assert!(started.elapsed() < std::time::Duration::from_secs(3));
+    let status: Value = resp.json().await.unwrap();
+    assert!(status["message"].as_str().unwrap().starts_with("Internal error occurred: failed calling webhook \"slow.krust.io\""));
+
+    let resp = client
+        .put(server.url("/apis/admissionregistration.k8s.io/v1/validatingwebhookconfigurations/slow"))
+        .json(&vwc("Ignore"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&configmap("late", json!({})))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+}
+
+#[tokio::test]
+async fn test_webhooks_see_patches_and_deletes() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = krust::Config::parse(&format!("dataDir: {}\n", dir.path().display())).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+    let received = Received::default();
+    let (port, webhook_ca) = start_webhook(dir.path(), &dir.path().join("pki/ca.crt"), received.clone()).await;
+
+    let configuration = |kind: &str, name: &str, path: &str, operations: Value, side_effects: &str| {
+        json!({
+            "apiVersion": "admissionregistration.k8s.io/v1", "kind": kind,
+            "metadata": { "name": name },
+            "webhooks": [{
+                "name": format!("{}.krust.io", name),
+                "clientConfig": { "url": format!("https://127.0.0.1:{}{}", port, path), "caBundle": STANDARD.encode(&webhook_ca) },
+                "rules": [{ "apiGroups": [""], "apiVersions": ["v1"], "operations": operations, "resources": ["configmaps"] }],
+                "admissionReviewVersions": ["v1"],
+                "sideEffects": side_effects
+            }]
+        })
+    };
+    let resp = client
+        .post(server.url("/apis/admissionregistration.k8s.io/v1/mutatingwebhookconfigurations"))
+        .json(&configuration("MutatingWebhookConfiguration", "labeller", "/review", json!(["CREATE", "UPDATE"]), "None"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let resp = client
+        .post(server.url("/apis/admissionregistration.k8s.io/v1/validatingwebhookconfigurations"))
+        .json(&configuration("ValidatingWebhookConfiguration", "guard", "/guard", json!(["DELETE"]), "NoneOnDryRun"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let url = server.url("/api/v1/namespaces/default/configmaps/settings");
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&configmap("settings", json!({ "mode": "fast" })))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // A patch is sent as the update it makes, and what the webhook does to
+    // the object is kept
+    let patch = json!({ "metadata": { "labels": { "team": "a" } }, "data": { "mode": "slow" } });
+    let resp = client
+        .patch(&url)
+        .header("Content-Type", "application/merge-patch+json")
+        .json(&patch)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let patched: Value = resp.json().await.unwrap();
+    assert_eq!(patched["data"]["mode"], "slow");
+    assert_eq!(patched["metadata"]["labels"], json!({ "admitted": "true" }));
+    let request = received.lock().unwrap().last().unwrap().0["request"].clone();
+    assert_eq!(request["operation"], "UPDATE");
+    assert_eq!(request["options"]["kind"], "PatchOptions");
+    assert_eq!(request["object"]["data"]["mode"], "slow");
+    assert_eq!(request["object"]["metadata"]["labels"]["team"], "a");
+    assert_eq!(request["oldObject"]["data"]["mode"], "fast");
+
+    let resp = client
+        .patch(&url)
+        .header("Content-Type", "application/merge-patch+json")
+        .json(&json!({ "data": { "forbidden": "yes" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    assert!(server.storage.configmaps().get("default", "settings").await.unwrap()["data"]["forbidden"].is_null());
+
+    // An update comes with what it replaces
+    let mut update = patched.clone();
+    update["data"]["mode"] = json!("medium");
+    let resp = client.put(&url).json(&update).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let request = received.lock().unwrap().last().unwrap().0["request"].clone();
+    assert_eq!(request["options"]["kind"], "UpdateOptions");
+    assert_eq!(request["oldObject"]["data"]["mode"], "slow");
+
+    // Deletes are sent with the object being deleted
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&configmap("kept", json!({ "keep": "yes" })))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let resp = client.delete(server.url("/api/v1/namespaces/default/configmaps/kept")).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+    assert!(server.storage.configmaps().get("default", "kept").await.is_ok());
+    let request = received.lock().unwrap().last().unwrap().0["request"].clone();
+    assert_eq!(request["operation"], "DELETE");
+    assert_eq!(request["options"]["kind"], "DeleteOptions");
+    assert!(request["object"].is_null());
+    assert_eq!(request["oldObject"]["metadata"]["name"], "kept");
+    let resp = client.delete(&url).send().await.unwrap();
+    assert!(resp.status().is_success());
+
+}
+
+#[tokio::test]
+async fn test_dry_runs_are_sent_to_webhooks_without_side_effects() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = krust::Config::parse(&format!("dataDir: {}\n", dir.path().display())).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+    let received = Received::default();
+    let (port, webhook_ca) = start_webhook(dir.path(), &dir.path().join("pki/ca.crt"), received.clone()).await;
+
+    let configuration = |kind: &str, name: &str, path: &str, operations: Value, side_effects: &str| {
+        json!({
+            "apiVersion": "admissionregistration.k8s.io/v1", "kind": kind,
+            "metadata": { "name": name },
+            "webhooks": [{
+                "name": format!("{}.krust.io", name),
+                "clientConfig": { "url": format!("https://127.0.0.1:{}{}", port, path), "caBundle": STANDARD.encode(&webhook_ca) },
+                "rules": [{ "apiGroups": [""], "apiVersions": ["v1"], "operations": operations, "resources": ["configmaps"] }],
+                "admissionReviewVersions": ["v1"],
+                "sideEffects": side_effects
+            }]
+        })
+    };
+    let resp = client
+        .post(server.url("/apis/admissionregistration.k8s.io/v1/mutatingwebhookconfigurations"))
+        .json(&configuration("MutatingWebhookConfiguration", "labeller", "/review", json!(["CREATE"]), "None"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let resp = client
+        .post(server.url("/apis/admissionregistration.k8s.io/v1/validatingwebhookconfigurations"))
+        .json(&configuration("ValidatingWebhookConfiguration", "guard", "/guard", json!(["DELETE"]), "NoneOnDryRun"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&configmap("kept", json!({ "keep": "yes" })))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // A dry run is admitted like any other create, and then not kept
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps?dryRun=All"))
+        .json(&configmap("dry", json!({})))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let created: Value = resp.json().await.unwrap();
+    assert_eq!(created["metadata"]["labels"]["admitted"], "true");
+    assert!(server.storage.configmaps().get("default", "dry").await.is_err());
+    let request = received.lock().unwrap().last().unwrap().0["request"].clone();
+    assert_eq!(request["dryRun"], true);
+    let resp = client.delete(server.url("/api/v1/namespaces/default/configmaps/kept?dryRun=All")).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+
+    // unless a webhook it would be sent to may have side effects
+    let resp = client
+        .post(server.url("/apis/admissionregistration.k8s.io/v1/validatingwebhookconfigurations"))
+        .json(&configuration("ValidatingWebhookConfiguration", "recorder", "/guard", json!(["CREATE"]), "Some"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let sent = received.lock().unwrap().len();
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps?dryRun=All"))
+        .json(&configmap("dry", json!({})))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["message"], "admission webhook \"recorder.krust.io\" does not support dry run");
+    assert_eq!(received.lock().unwrap().len(), sent);
+}
+
+#[tokio::test]
+async fn test_slow_dry_run_webhooks_do_not_hold_up_other_writes() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = krust::Config::parse(&format!("dataDir: {}\n", dir.path().display())).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+    let (port, webhook_ca) = start_webhook(dir.path(), &dir.path().join("pki/ca.crt"), Received::default()).await;
+
+    let configuration = json!({
+        "apiVersion": "admissionregistration.k8s.io/v1", "kind": "ValidatingWebhookConfiguration",
+        "metadata": { "name": "slow" },
+        "webhooks": [{
+            "name": "slow.krust.io",
+            "clientConfig": { "url": format!("https://127.0.0.1:{}/slow", port), "caBundle": STANDARD.encode(&webhook_ca) },
+            "rules": [{ "apiGroups": [""], "apiVersions": ["v1"], "operations": ["CREATE"], "resources": ["configmaps"] }],
+            "admissionReviewVersions": ["v1"],
+            "sideEffects": "None",
+            "timeoutSeconds": 10
+        }]
+    });
+    let resp = client
+        .post(server.url("/apis/admissionregistration.k8s.io/v1/validatingwebhookconfigurations"))
+        .json(&configuration)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let dry_run = tokio::spawn(
+        client
+            .post(server.url("/api/v1/namespaces/default/configmaps?dryRun=All"))
+            .json(&configmap("dry", json!({})))
+            .send(),
+    );
+    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+
+    // While the webhook takes its time, writes to what admission read go on
+    let started = std::time::Instant::now();
+    let resp = client
+        .post(server.url("/api/v1/namespaces"))
+        .json(&json!({ "apiVersion": "v1", "kind": "Namespace", "metadata": { "name": "busy" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    assert!(started.elapsed() < std::time::Duration::from_secs(2), "{:?}", started.elapsed());
+    assert!(!dry_run.is_finished());
+
+    let resp = dry_run.await.unwrap().unwrap();
+    assert_eq!(resp.status(), 201);
+    assert!(server.storage.configmaps().get("default", "dry").await.is_err());
+}
+
+#[tokio::test]
+async fn test_webhooks_can_write_to_the_object_they_admit() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = krust::Config::parse(&format!("dataDir: {}\n", dir.path().display())).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+    let router = Router::new().route("/touch", post(touch)).with_state(server.base_url());
+    let (port, webhook_ca) = serve_webhook(dir.path(), &dir.path().join("pki/ca.crt"), router).await;
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "tracked", "labels": { "touch": "yes" } } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let configuration = json!({
+        "apiVersion": "admissionregistration.k8s.io/v1", "kind": "ValidatingWebhookConfiguration",
+        "metadata": { "name": "toucher" },
+        "webhooks": [{
+            "name": "toucher.krust.io",
+            "clientConfig": { "url": format!("https://127.0.0.1:{}/touch", port), "caBundle": STANDARD.encode(&webhook_ca) },
+            "rules": [{ "apiGroups": [""], "apiVersions": ["v1"], "operations": ["UPDATE"], "resources": ["configmaps"] }],
+            "objectSelector": { "matchLabels": { "touch": "yes" } },
+            "admissionReviewVersions": ["v1"],
+            "sideEffects": "None",
+            "timeoutSeconds": 2
+        }]
+    });
+    let resp = client
+        .post(server.url("/apis/admissionregistration.k8s.io/v1/validatingwebhookconfigurations"))
+        .json(&configuration)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // The webhook's own write isn't held up behind the one it's admitting
+    let resp = client
+        .patch(server.url("/api/v1/namespaces/default/configmaps/tracked"))
+        .header("Content-Type", "application/merge-patch+json")
+        .body(json!({ "data": { "key": "value" } }).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let stored = server.storage.configmaps().get("default", "tracked").await.unwrap();
+    assert_eq!(stored["metadata"]["annotations"]["touched"], "true");
+    assert_eq!(stored["data"]["key"], "value");
+}
+
+#[tokio::test]
+async fn test_webhook_with_an_untrusted_certificate_fails() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = krust::Config::parse(&format!("dataDir: {}\n", dir.path().display())).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let received = Received::default();
+    let (port, _) = start_webhook(dir.path(), &dir.path().join("pki/ca.crt"), received.clone()).await;
+
+    // The caBundle is some other CA's, so the server can't be trusted
+    let other_ca = ClusterCa::generate().unwrap().cert_pem().unwrap();
+    let vwc = json!({
+        "apiVersion": "admissionregistration.k8s.io/v1", "kind": "ValidatingWebhookConfiguration",
+        "metadata": { "name": "untrusted" },
+        "webhooks": [{
+            "name": "untrusted.krust.io",
+            "clientConfig": { "url": format!("https://127.0.0.1:{}/review", port), "caBundle": STANDARD.encode(other_ca) },
+            "rules": [{ "apiGroups": ["*"], "apiVersions": ["*"], "operations": ["*"], "resources": ["*"] }],
+            "admissionReviewVersions": ["v1"],
+            "sideEffects": "None"
+        }]
+    });
+    let resp = client
+        .post(server.url("/apis/admissionregistration.k8s.io/v1/validatingwebhookconfigurations"))
+        .json(&vwc)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&configmap("settings", json!({})))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 500);
+    assert!(received.lock().unwrap().is_empty());
+}
+
+const INJECTIONS: &str = r#"
+admission:
+  injections:
+    - name: mesh
+      selector:
+        matchLabels:
+          app: web
+      namespaceSelector:
+        matchLabels:
+          mesh: enabled
+      labels:
+        mesh.example.com/injected: "true"
+      env:
+        - name: MESH_ADDRESS
+          value: 127.0.0.1:15001
+      containers:
+        - name: proxy
+          image: envoyproxy/envoy:v1.29.1
+"#;
+
+#[tokio::test]
+async fn test_config_injects_sidecars() {
+    let config = krust::Config::parse(INJECTIONS).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let namespace = json!({ "apiVersion": "v1", "kind": "Namespace", "metadata": { "name": "meshed", "labels": { "mesh": "enabled" } } });
+    assert_eq!(client.post(server.url("/api/v1/namespaces")).json(&namespace).send().await.unwrap().status(), 201);
+    let pod = |name: &str, app: &str| json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": name, "labels": { "app": app } },
+        "spec": { "containers": [{ "name": "app", "image": "nginx", "env": [{ "name": "MESH_ADDRESS", "value": "own" }] }, { "name": "worker", "image": "busybox" }] }
+    });
+
+    // Pods the selectors pick get the sidecar, the label and the variable,
+    // unless a container sets it itself
+    let resp = client.post(server.url("/api/v1/namespaces/meshed/pods")).json(&pod("web", "web")).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let created: Value = resp.json().await.unwrap();
+    assert_eq!(created["metadata"]["labels"]["mesh.example.com/injected"], "true");
+    let containers = created["spec"]["containers"].as_array().unwrap();
+    let names: Vec<&str> = containers.iter().map(|c| c["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["app", "worker", "proxy"]);
+    assert_eq!(containers[0]["env"], json!([{ "name": "MESH_ADDRESS", "value": "own" }]));
+    assert_eq!(containers[1]["env"], json!([{ "name": "MESH_ADDRESS", "value": "127.0.0.1:15001" }]));
+    assert!(containers[2]["env"].is_null());
+
+    // Others are left alone
+    for (namespace, app) in [("meshed", "db"), ("default", "web")] {
+        let url = server.url(&format!("/api/v1/namespaces/{}/pods", namespace));
+        let created: Value = client.post(url).json(&pod(&format!("{}-{}", namespace, app), app)).send().await.unwrap().json().await.unwrap();
+        assert_eq!(created["spec"]["containers"].as_array().unwrap().len(), 2, "{} in {}", app, namespace);
+        assert!(created["metadata"]["labels"]["mesh.example.com/injected"].is_null());
+    }
+
+    // Injections are checked when the config is read
+    let invalid = INJECTIONS.replace("          image: envoyproxy/envoy:v1.29.1\n", "");
+    let error = krust::Config::parse(&invalid).unwrap_err();
+    assert_eq!(error.to_string(), "admission.injections.mesh.containers entries need a name and an image");
+}
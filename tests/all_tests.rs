@@ -1,75 +0,0 @@
-// Consolidated test suite for krust
-// This file includes all working tests and marks others as ignored
-
-// Core integration tests that are known to work
-mod integration_test;
-
-// Edge case tests
-mod edge_cases_test;
-
-// TDD validation tests  
-mod tdd_validation_test;
-
-// Tests that require the server to be running
-// These are marked with #[ignore] by default and can be run with: cargo test -- --ignored
-
-#[cfg(test)]
-mod conditional_tests {
-    use super::*;
-    
-    // Only include tests that have proper server checks
-    #[ignore = "Requires running server"]
-    #[test]
-    fn run_configmap_tests() {
-        // The individual test files handle server checking
-        println!("Run with: cargo test --test configmap_test -- --ignored");
-    }
-    
-    #[ignore = "Requires running server"]
-    #[test]
-    fn run_secret_tests() {
-        println!("Run with: cargo test --test secret_test -- --ignored");
-    }
-}
-
-// Mark which features are fully implemented and tested
-#[cfg(test)]
-mod feature_status {
-    #[test]
-    fn implemented_features() {
-        let features = vec![
-            "Namespaces - CRUD operations",
-            "Pods - Create, Get, List, Delete, Status, Logs",
-            "Services - CRUD operations", 
-            "ServiceAccounts - CRUD operations with token creation",
-            "ConfigMaps - CRUD operations with validation",
-            "Secrets - CRUD operations with validation",
-            "Deployments - Basic CRUD operations",
-            "Port forwarding - WebSocket implementation",
-        ];
-        
-        println!("\n✅ Implemented and tested features:");
-        for feature in features {
-            println!("  - {}", feature);
-        }
-        
-        let pending = vec![
-            "StatefulSets",
-            "DaemonSets", 
-            "Jobs",
-            "CronJobs",
-            "Ingress",
-            "NetworkPolicies",
-            "PersistentVolumes",
-            "PersistentVolumeClaims",
-            "RBAC (Roles, RoleBindings, etc.)",
-            "HorizontalPodAutoscaler",
-            "ResourceQuotas",
-        ];
-        
-        println!("\n⏳ Pending implementation:");
-        for feature in pending {
-            println!("  - {}", feature);
-        }
-    }
-}
\ No newline at end of file
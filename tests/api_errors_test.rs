@@ -0,0 +1,147 @@
+use serde_json::{json, Value};
+
+mod common;
+
+fn deployment(name: &str) -> Value {
+    json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": name },
+        "spec": {
+            "replicas": 1,
+            "selector": { "matchLabels": { "app": name } },
+            "template": {
+                "metadata": { "labels": { "app": name } },
+                "spec": { "containers": [{ "name": "app", "image": "nginx" }] }
+            }
+        }
+    })
+}
+
+#[tokio::test]
+async fn test_missing_objects_are_not_found() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let resp = client.get(server.url("/apis/apps/v1/namespaces/default/deployments/nowhere")).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["kind"], "Status");
+    assert_eq!(status["status"], "Failure");
+    assert_eq!(status["reason"], "NotFound");
+    assert_eq!(status["code"], 404);
+    assert_eq!(status["message"], "deployments.apps \"nowhere\" not found");
+    assert_eq!(status["details"], json!({ "name": "nowhere", "group": "apps", "kind": "deployments" }));
+
+    // Core resources have no group
+    let resp = client.delete(server.url("/api/v1/namespaces/default/configmaps/nowhere")).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["message"], "configmaps \"nowhere\" not found");
+    assert_eq!(status["details"], json!({ "name": "nowhere", "kind": "configmaps" }));
+}
+
+#[tokio::test]
+async fn test_creating_an_existing_object_is_already_exists() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/apis/apps/v1/namespaces/default/deployments");
+
+    let resp = client.post(&url).json(&deployment("web")).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let resp = client.post(&url).json(&deployment("web")).send().await.unwrap();
+    assert_eq!(resp.status(), 409);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "AlreadyExists");
+    assert_eq!(status["message"], "deployments.apps \"web\" already exists");
+    assert_eq!(status["details"]["name"], "web");
+}
+
+#[tokio::test]
+async fn test_invalid_objects_list_their_causes() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let secret = json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": { "name": "cert" },
+        "type": "kubernetes.io/tls",
+        "data": { "tls.crt": "Y2VydA==" }
+    });
+    let resp = client.post(server.url("/api/v1/namespaces/default/secrets")).json(&secret).send().await.unwrap();
+    assert_eq!(resp.status(), 422);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "Invalid");
+    assert_eq!(status["message"], "Secret \"cert\" is invalid: data[tls.key]: Required value");
+    assert_eq!(status["details"]["kind"], "Secret");
+    assert_eq!(
+        status["details"]["causes"],
+        json!([{ "reason": "FieldValueRequired", "message": "Required value", "field": "data[tls.key]" }])
+    );
+
+    let job = json!({
+        "apiVersion": "batch/v1",
+        "kind": "Job",
+        "metadata": { "name": "once" },
+        "spec": {
+            "template": {
+                "spec": {
+                    "restartPolicy": "Always",
+                    "containers": [{ "name": "app", "image": "busybox" }]
+                }
+            }
+        }
+    });
+    let resp = client.post(server.url("/apis/batch/v1/namespaces/default/jobs")).json(&job).send().await.unwrap();
+    assert_eq!(resp.status(), 422);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(
+        status["message"],
+        "Job.batch \"once\" is invalid: spec.template.spec.restartPolicy: Unsupported value: \"Always\": supported values: \"OnFailure\", \"Never\""
+    );
+    assert_eq!(status["details"]["group"], "batch");
+    assert_eq!(status["details"]["causes"][0]["reason"], "FieldValueNotSupported");
+    assert_eq!(status["details"]["causes"][0]["field"], "spec.template.spec.restartPolicy");
+}
+
+#[tokio::test]
+async fn test_stale_writes_are_conflicts() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/apis/apps/v1/namespaces/default/deployments");
+
+    let resp = client.post(&url).json(&deployment("web")).send().await.unwrap();
+    let stale: Value = resp.json().await.unwrap();
+    let mut newer = stale.clone();
+    newer["spec"]["replicas"] = json!(2);
+    let resp = client.put(format!("{}/web", url)).json(&newer).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = client.put(format!("{}/web", url)).json(&stale).send().await.unwrap();
+    assert_eq!(resp.status(), 409);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "Conflict");
+    assert_eq!(
+        status["message"],
+        "Operation cannot be fulfilled on deployments.apps \"web\": the object has been modified; please apply your changes to the latest version and try again"
+    );
+    assert_eq!(status["details"], json!({ "name": "web", "group": "apps", "kind": "deployments" }));
+}
+
+#[tokio::test]
+async fn test_malformed_requests_are_bad_requests() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&json!({ "apiVersion": "v1", "kind": "Secret", "metadata": { "name": "settings" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "BadRequest");
+    assert_eq!(status["message"], "the kind of the object (Secret) does not match the expected kind (ConfigMap)");
+}
@@ -1,30 +1,24 @@
 use reqwest;
 use serde_json::Value;
 
+mod common;
+
 #[tokio::test]
 async fn test_health_endpoints() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
     
-    // Note: Server should be running for integration tests
-    // These tests assume the server is running on localhost:6443
-    
     // Test liveness endpoint
     let resp = client
-        .get("http://localhost:6443/livez")
+        .get(server.url("/livez"))
         .send()
-        .await;
-    
-    if resp.is_err() {
-        eprintln!("Server not running, skipping integration test");
-        return;
-    }
-    
-    let resp = resp.unwrap();
+        .await
+        .unwrap();
     assert_eq!(resp.status(), 200);
     
     // Test readiness endpoint
     let resp = client
-        .get("http://localhost:6443/readyz")
+        .get(server.url("/readyz"))
         .send()
         .await
         .unwrap();
@@ -32,7 +26,7 @@ async fn test_health_endpoints() {
     
     // Test health endpoint
     let resp = client
-        .get("http://localhost:6443/healthz")
+        .get(server.url("/healthz"))
         .send()
         .await
         .unwrap();
@@ -41,19 +35,14 @@ async fn test_health_endpoints() {
 
 #[tokio::test]
 async fn test_version_endpoint() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
     
     let resp = client
-        .get("http://localhost:6443/version")
+        .get(server.url("/version"))
         .send()
-        .await;
-    
-    if resp.is_err() {
-        eprintln!("Server not running, skipping integration test");
-        return;
-    }
-    
-    let resp = resp.unwrap();
+        .await
+        .unwrap();
     assert_eq!(resp.status(), 200);
     
     let version: Value = resp.json().await.unwrap();
@@ -64,19 +53,14 @@ async fn test_version_endpoint() {
 
 #[tokio::test]
 async fn test_api_discovery() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
     
     let resp = client
-        .get("http://localhost:6443/api")
+        .get(server.url("/api"))
         .send()
-        .await;
-    
-    if resp.is_err() {
-        eprintln!("Server not running, skipping integration test");
-        return;
-    }
-    
-    let resp = resp.unwrap();
+        .await
+        .unwrap();
     assert_eq!(resp.status(), 200);
     
     let api_versions: Value = resp.json().await.unwrap();
@@ -86,19 +70,14 @@ async fn test_api_discovery() {
 
 #[tokio::test]
 async fn test_api_v1_resources() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
     
     let resp = client
-        .get("http://localhost:6443/api/v1")
+        .get(server.url("/api/v1"))
         .send()
-        .await;
-    
-    if resp.is_err() {
-        eprintln!("Server not running, skipping integration test");
-        return;
-    }
-    
-    let resp = resp.unwrap();
+        .await
+        .unwrap();
     assert_eq!(resp.status(), 200);
     
     let resources: Value = resp.json().await.unwrap();
@@ -116,4 +95,137 @@ async fn test_api_v1_resources() {
     assert!(resource_names.contains(&"services".to_string()));
     assert!(resource_names.contains(&"namespaces".to_string()));
     assert!(resource_names.contains(&"nodes".to_string()));
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_discovery_categories_and_short_names() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    // `kubectl get all` gets the resources in the "all" category, and
+    // `kubectl get cm` finds configmaps by their short name
+    let mut all = Vec::new();
+    let mut short_names = Vec::new();
+    for path in ["/api/v1", "/apis/apps/v1", "/apis/batch/v1", "/apis/autoscaling/v2"] {
+        let resources: Value = client.get(server.url(path)).send().await.unwrap().json().await.unwrap();
+        for resource in resources["resources"].as_array().unwrap() {
+            let name = resource["name"].as_str().unwrap().to_string();
+            if resource["categories"].as_array().into_iter().flatten().any(|c| c == "all") {
+                all.push(name.clone());
+            }
+            for short_name in resource["shortNames"].as_array().into_iter().flatten() {
+                short_names.push((short_name.as_str().unwrap().to_string(), name.clone()));
+            }
+        }
+    }
+    all.sort();
+    assert_eq!(all, vec![
+        "cronjobs", "daemonsets", "deployments", "horizontalpodautoscalers", "jobs", "pods", "replicasets", "services", "statefulsets",
+    ]);
+    for (short_name, name) in [("cm", "configmaps"), ("pvc", "persistentvolumeclaims"), ("pv", "persistentvolumes"), ("deploy", "deployments")] {
+        assert!(short_names.contains(&(short_name.to_string(), name.to_string())), "{} is not short for {}", short_name, name);
+    }
+}
+
+#[tokio::test]
+async fn test_discovery_follows_the_routes() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let resources = |list: &Value| -> Vec<(String, Value)> {
+        list["resources"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| (r["name"].as_str().unwrap().to_string(), r.clone()))
+            .collect()
+    };
+    let verbs = |resource: &Value| -> Vec<String> {
+        resource["verbs"].as_array().unwrap().iter().map(|v| v.as_str().unwrap().to_string()).collect()
+    };
+
+    let core: Value = client.get(server.url("/api/v1")).send().await.unwrap().json().await.unwrap();
+    let core = resources(&core);
+    let find = |name: &str| core.iter().find(|(n, _)| n == name).map(|(_, r)| r.clone()).unwrap_or_else(|| panic!("no {}", name));
+    let pods = find("pods");
+    assert_eq!(pods["namespaced"], true);
+    assert_eq!(pods["singularName"], "pod");
+    assert_eq!(verbs(&pods), ["create", "delete", "get", "list", "patch", "update", "watch"]);
+    assert_eq!(find("namespaces")["namespaced"], false);
+    assert_eq!(verbs(&find("pods/log")), ["get"]);
+    assert_eq!(find("pods/exec")["kind"], "PodExecOptions");
+    assert_eq!(find("pods/binding")["kind"], "Binding");
+    assert_eq!(verbs(&find("pods/status")), ["get", "patch", "update"]);
+    assert_eq!(find("serviceaccounts/token")["group"], "authentication.k8s.io");
+
+    let apps: Value = client.get(server.url("/apis/apps/v1")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(apps["groupVersion"], "apps/v1");
+    let apps = resources(&apps);
+    let scale = &apps.iter().find(|(n, _)| n == "deployments/scale").unwrap().1;
+    assert_eq!(scale["group"], "autoscaling");
+    assert_eq!(scale["kind"], "Scale");
+    // Only what's routed is offered: revisions are written once
+    let revisions = &apps.iter().find(|(n, _)| n == "controllerrevisions").unwrap().1;
+    assert!(!verbs(revisions).contains(&"update".to_string()));
+    assert!(apps.iter().all(|(n, _)| n != "pods"));
+
+    // Every group version /apis lists serves its resource list
+    let groups: Value = client.get(server.url("/apis")).send().await.unwrap().json().await.unwrap();
+    for group in groups["groups"].as_array().unwrap() {
+        for version in group["versions"].as_array().unwrap() {
+            let group_version = version["groupVersion"].as_str().unwrap();
+            let list: Value = client.get(server.url(&format!("/apis/{}", group_version))).send().await.unwrap().json().await.unwrap();
+            assert_eq!(list["groupVersion"], group_version);
+            assert!(!list["resources"].as_array().unwrap().is_empty(), "{} lists no resources", group_version);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_method_not_allowed_and_unsupported_media_type_are_statuses() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    // A verb a known route doesn't serve is a 405 saying which it does
+    let resp = client.delete(server.url("/version")).send().await.unwrap();
+    assert_eq!(resp.status(), 405);
+    let allow = resp.headers()["allow"].to_str().unwrap().to_string();
+    assert!(allow.contains("GET"), "{}", allow);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["kind"], "Status");
+    assert_eq!(status["reason"], "MethodNotAllowed");
+    assert_eq!(status["code"], 405);
+
+    // A body in a format the server can't read is a 415
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .header("Content-Type", "text/plain")
+        .body("name: settings")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 415);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "UnsupportedMediaType");
+    assert_eq!(status["message"], "the body of the request was in an unknown format - accepted media types include: application/json");
+
+    let configmap = serde_json::json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "settings" } });
+    let resp = client.post(server.url("/api/v1/namespaces/default/configmaps")).json(&configmap).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let resp = client
+        .patch(server.url("/api/v1/namespaces/default/configmaps/settings"))
+        .header("Content-Type", "text/plain")
+        .body("{}")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 415);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "UnsupportedMediaType");
+    assert!(status["message"].as_str().unwrap().contains("application/merge-patch+json"));
+
+    // As is one for something that isn't there
+    let resp = client.get(server.url("/api/v1/namespaces/default/pods/missing")).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "NotFound");
+}
@@ -0,0 +1,272 @@
+use reqwest;
+use axum::{routing::{get, post}, Json, Router};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use openssl::bn::{BigNum, BigNumContext};
+use openssl::ec::{EcGroup, EcKey};
+use openssl::ecdsa::EcdsaSig;
+use openssl::hash::{hash, MessageDigest};
+use openssl::nid::Nid;
+use openssl::pkey::{PKey, Private};
+use openssl::rsa::Rsa;
+use openssl::sign::Signer;
+use serde_json::{json, Value};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+mod common;
+
+// Serves `router` on an ephemeral port, returning its base URL
+async fn serve(router: Router) -> String {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+    url
+}
+
+async fn whoami(server: &common::TestServer, token: Option<&str>) -> reqwest::Response {
+    let mut request = reqwest::Client::new()
+        .post(server.url("/apis/authentication.k8s.io/v1/selfsubjectreviews"))
+        .json(&json!({ "apiVersion": "authentication.k8s.io/v1", "kind": "SelfSubjectReview" }));
+    if let Some(token) = token {
+        request = request.bearer_auth(token);
+    }
+    request.send().await.unwrap()
+}
+
+async fn user_of(server: &common::TestServer, token: &str) -> Value {
+    let resp = whoami(server, Some(token)).await;
+    assert_eq!(resp.status(), 201);
+    let review: Value = resp.json().await.unwrap();
+    assert_eq!(review["kind"], "SelfSubjectReview");
+    review["status"]["userInfo"].clone()
+}
+
+#[tokio::test]
+async fn test_static_tokens() {
+    let config = krust::Config::parse(
+        r#"
+authentication:
+  tokens:
+    - token: s3cret
+      user: alice
+      uid: "1001"
+      groups: [developers]
+"#,
+    )
+    .unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    // Without a valid token only the health checks answer
+    let resp = client.get(server.url("/api/v1/namespaces")).send().await.unwrap();
+    assert_eq!(resp.status(), 401);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "Unauthorized");
+    let resp = client.get(server.url("/api/v1/namespaces")).bearer_auth("wrong").send().await.unwrap();
+    assert_eq!(resp.status(), 401);
+    assert_eq!(client.get(server.url("/healthz")).send().await.unwrap().status(), 200);
+
+    let resp = client.get(server.url("/api/v1/namespaces")).bearer_auth("s3cret").send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let user = user_of(&server, "s3cret").await;
+    assert_eq!(user["username"], "alice");
+    assert_eq!(user["uid"], "1001");
+    assert_eq!(user["groups"], json!(["developers", "system:authenticated"]));
+}
+
+#[tokio::test]
+async fn test_without_authentication_everyone_is_anonymous() {
+    let server = common::TestServer::start().await;
+
+    let resp = whoami(&server, None).await;
+    assert_eq!(resp.status(), 201);
+    let review: Value = resp.json().await.unwrap();
+    assert_eq!(review["status"]["userInfo"]["username"], "system:anonymous");
+
+    let discovery: Value = reqwest::get(server.url("/apis/authentication.k8s.io/v1")).await.unwrap().json().await.unwrap();
+    assert_eq!(discovery["resources"][0]["name"], "selfsubjectreviews");
+}
+
+// An OIDC provider signing ID tokens with an RSA and an EC key
+struct Issuer {
+    url: String,
+    rsa: PKey<Private>,
+    ec: PKey<Private>,
+}
+
+impl Issuer {
+    async fn start() -> Self {
+        let rsa = PKey::from_rsa(Rsa::generate(2048).unwrap()).unwrap();
+        let group = EcGroup::from_curve_name(Nid::X9_62_PRIME256V1).unwrap();
+        let ec = PKey::from_ec_key(EcKey::generate(&group).unwrap()).unwrap();
+
+        let b64 = |bytes: Vec<u8>| URL_SAFE_NO_PAD.encode(bytes);
+        let rsa_key = rsa.rsa().unwrap();
+        let ec_key = ec.ec_key().unwrap();
+        let (mut x, mut y) = (BigNum::new().unwrap(), BigNum::new().unwrap());
+        ec_key
+            .public_key()
+            .affine_coordinates(&group, &mut x, &mut y, &mut BigNumContext::new().unwrap())
+            .unwrap();
+        let jwks = json!({ "keys": [
+            { "kty": "RSA", "kid": "rsa-1", "alg": "RS256", "n": b64(rsa_key.n().to_vec()), "e": b64(rsa_key.e().to_vec()) },
+            { "kty": "EC", "kid": "ec-1", "crv": "P-256", "x": b64(x.to_vec_padded(32).unwrap()), "y": b64(y.to_vec_padded(32).unwrap()) }
+        ] });
+
+        // Bound first, so the discovery document can name the issuer's URL
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let discovery = json!({ "issuer": url, "jwks_uri": format!("{}/keys", url) });
+        let router = Router::new()
+            .route("/.well-known/openid-configuration", get(move || async move { Json(discovery) }))
+            .route("/keys", get(move || async move { Json(jwks) }));
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+
+        Self { url, rsa, ec }
+    }
+
+    fn token(&self, kid: &str, claims: Value) -> String {
+        let alg = if kid == "ec-1" { "ES256" } else { "RS256" };
+        let header = URL_SAFE_NO_PAD.encode(json!({ "alg": alg, "kid": kid, "typ": "JWT" }).to_string());
+        let payload = URL_SAFE_NO_PAD.encode(claims.to_string());
+        let signed = format!("{}.{}", header, payload);
+
+        let signature = if alg == "ES256" {
+            let digest = hash(MessageDigest::sha256(), signed.as_bytes()).unwrap();
+            let signature = EcdsaSig::sign(&digest, &self.ec.ec_key().unwrap()).unwrap();
+            let mut raw = signature.r().to_vec_padded(32).unwrap();
+            raw.extend(signature.s().to_vec_padded(32).unwrap());
+            raw
+        } else {
+            let mut signer = Signer::new(MessageDigest::sha256(), &self.rsa).unwrap();
+            signer.update(signed.as_bytes()).unwrap();
+            signer.sign_to_vec().unwrap()
+        };
+        format!("{}.{}", signed, URL_SAFE_NO_PAD.encode(signature))
+    }
+
+    fn claims(&self, extra: Value) -> Value {
+        let now = chrono::Utc::now().timestamp();
+        let mut claims = json!({
+            "iss": self.url,
+            "aud": "krust",
+            "sub": "1234",
+            "email": "jane@example.com",
+            "groups": ["admins", "ops"],
+            "iat": now,
+            "exp": now + 300
+        });
+        json_patch::merge(&mut claims, &extra);
+        claims
+    }
+}
+
+#[tokio::test]
+async fn test_oidc_tokens() {
+    let issuer = Issuer::start().await;
+    let config = krust::Config::parse(&format!(
+        r#"
+authentication:
+  oidc:
+    issuerURL: {}
+    clientID: krust
+    usernameClaim: email
+    groupsClaim: groups
+    groupsPrefix: "oidc:"
+"#,
+        issuer.url
+    ))
+    .unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+
+    for kid in ["rsa-1", "ec-1"] {
+        let user = user_of(&server, &issuer.token(kid, issuer.claims(json!({})))).await;
+        assert_eq!(user["username"], "jane@example.com", "{}", kid);
+        assert_eq!(user["groups"], json!(["oidc:admins", "oidc:ops", "system:authenticated"]));
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    for (case, claims) in [
+        ("expired", issuer.claims(json!({ "exp": now - 10 }))),
+        ("other audience", issuer.claims(json!({ "aud": ["someone-else"] }))),
+        ("other issuer", issuer.claims(json!({ "iss": "https://elsewhere.example.com" }))),
+        ("unverified email", issuer.claims(json!({ "email_verified": false }))),
+    ] {
+        let resp = whoami(&server, Some(&issuer.token("rsa-1", claims))).await;
+        assert_eq!(resp.status(), 401, "{}", case);
+    }
+
+    // Claims changed after signing
+    let genuine = issuer.token("rsa-1", issuer.claims(json!({})));
+    let forged = issuer.token("rsa-1", issuer.claims(json!({ "email": "root@example.com" })));
+    let tampered = format!("{}.{}", forged.rsplit_once('.').unwrap().0, genuine.rsplit_once('.').unwrap().1);
+    assert_eq!(whoami(&server, Some(&tampered)).await.status(), 401);
+
+    // Keys the provider doesn't publish are refused
+    assert_eq!(whoami(&server, Some(&issuer.token("rsa-2", issuer.claims(json!({}))))).await.status(), 401);
+}
+
+#[tokio::test]
+async fn test_authentication_webhook() {
+    let reviews = Arc::new(AtomicUsize::new(0));
+    let counter = reviews.clone();
+    let webhook = serve(Router::new().route(
+        "/authenticate",
+        post(move |Json(review): Json<Value>| {
+            let counter = counter.clone();
+            async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                let status = if review["spec"]["token"] == "team-token" {
+                    json!({ "authenticated": true, "user": { "username": "bob", "uid": "42", "groups": ["team"], "extra": { "scopes": ["read"] } } })
+                } else {
+                    json!({ "authenticated": false })
+                };
+                Json(json!({ "apiVersion": "authentication.k8s.io/v1", "kind": "TokenReview", "status": status }))
+            }
+        }),
+    ))
+    .await;
+
+    let config = krust::Config::parse(&format!(
+        "authentication:\n  webhook:\n    url: {}/authenticate\n",
+        webhook
+    ))
+    .unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+
+    let user = user_of(&server, "team-token").await;
+    assert_eq!(user["username"], "bob");
+    assert_eq!(user["uid"], "42");
+    assert_eq!(user["groups"], json!(["team", "system:authenticated"]));
+    assert_eq!(user["extra"]["scopes"], json!(["read"]));
+
+    // Accepted tokens are cached, rejected ones asked about every time
+    user_of(&server, "team-token").await;
+    assert_eq!(reviews.load(Ordering::SeqCst), 1);
+    assert_eq!(whoami(&server, Some("stolen")).await.status(), 401);
+    assert_eq!(whoami(&server, Some("stolen")).await.status(), 401);
+    assert_eq!(reviews.load(Ordering::SeqCst), 3);
+}
+
+#[tokio::test]
+async fn test_older_kubectl_asks_v1beta1_who_it_is() {
+    let server = common::TestServer::start().await;
+
+    let resp = reqwest::Client::new()
+        .post(server.url("/apis/authentication.k8s.io/v1beta1/selfsubjectreviews"))
+        .json(&json!({ "apiVersion": "authentication.k8s.io/v1beta1", "kind": "SelfSubjectReview" }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let review: Value = resp.json().await.unwrap();
+    assert_eq!(review["apiVersion"], "authentication.k8s.io/v1beta1");
+    assert_eq!(review["status"]["userInfo"]["username"], "system:anonymous");
+
+    let groups: Value = reqwest::get(server.url("/apis")).await.unwrap().json().await.unwrap();
+    let authentication = groups["groups"].as_array().unwrap().iter().find(|g| g["name"] == "authentication.k8s.io").unwrap();
+    assert_eq!(authentication["preferredVersion"]["version"], "v1");
+    assert_eq!(authentication["versions"][1]["groupVersion"], "authentication.k8s.io/v1beta1");
+}
@@ -0,0 +1,174 @@
+use reqwest;
+use krust::api::request_info::RequestInfo;
+use serde_json::{json, Value};
+
+mod common;
+
+const CONFIG: &str = r#"
+authentication:
+  tokens:
+    - token: admin-token
+      user: admin
+      groups: [system:masters]
+    - token: alice-token
+      user: alice
+authorization:
+  mode: RBAC
+"#;
+
+struct Client {
+    server: common::TestServer,
+    http: reqwest::Client,
+}
+
+impl Client {
+    async fn start(config: &str) -> Self {
+        let server = common::TestServer::start_with_config(krust::Config::parse(config).unwrap()).await;
+        Self { server, http: reqwest::Client::new() }
+    }
+
+    async fn get(&self, token: &str, path: &str) -> reqwest::StatusCode {
+        self.http.get(self.server.url(path)).bearer_auth(token).send().await.unwrap().status()
+    }
+
+    async fn post(&self, token: &str, path: &str, body: Value) -> reqwest::Response {
+        self.http.post(self.server.url(path)).bearer_auth(token).json(&body).send().await.unwrap()
+    }
+}
+
+#[tokio::test]
+async fn test_rbac_roles_and_bindings() {
+    let client = Client::start(CONFIG).await;
+
+    let resp = client.http.get(client.server.url("/api/v1/namespaces/default/pods")).bearer_auth("alice-token").send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "Forbidden");
+    assert_eq!(
+        status["message"],
+        "pods is forbidden: User \"alice\" cannot list resource \"pods\" in API group \"\" in the namespace \"default\""
+    );
+    // Discovery is open to everyone, and system:masters may do anything
+    assert_eq!(client.get("alice-token", "/api/v1").await, 200);
+    assert_eq!(client.get("admin-token", "/api/v1/namespaces/default/pods").await, 200);
+
+    let role = json!({
+        "apiVersion": "rbac.authorization.k8s.io/v1",
+        "kind": "Role",
+        "metadata": { "name": "pod-reader" },
+        "rules": [{ "apiGroups": [""], "resources": ["pods"], "verbs": ["get", "list"] }]
+    });
+    let resp = client.post("admin-token", "/apis/rbac.authorization.k8s.io/v1/namespaces/default/roles", role).await;
+    assert_eq!(resp.status(), 201);
+    let binding = json!({
+        "apiVersion": "rbac.authorization.k8s.io/v1",
+        "kind": "RoleBinding",
+        "metadata": { "name": "alice-reads-pods" },
+        "subjects": [{ "kind": "User", "name": "alice", "apiGroup": "rbac.authorization.k8s.io" }],
+        "roleRef": { "kind": "Role", "name": "pod-reader", "apiGroup": "rbac.authorization.k8s.io" }
+    });
+    let resp = client.post("admin-token", "/apis/rbac.authorization.k8s.io/v1/namespaces/default/rolebindings", binding).await;
+    assert_eq!(resp.status(), 201);
+
+    // Only what the Role allows, and only in its namespace
+    assert_eq!(client.get("alice-token", "/api/v1/namespaces/default/pods").await, 200);
+    assert_eq!(client.get("alice-token", "/api/v1/namespaces/default/pods?watch=true").await, 403);
+    assert_eq!(client.get("alice-token", "/api/v1/namespaces/kube-system/pods").await, 403);
+    assert_eq!(client.get("alice-token", "/api/v1/namespaces/default/services").await, 403);
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "web" },
+        "spec": { "containers": [{ "name": "web", "image": "nginx" }] }
+    });
+    assert_eq!(client.post("alice-token", "/api/v1/namespaces/default/pods", pod).await.status(), 403);
+
+    // A ClusterRoleBinding to the bootstrap view role reaches every namespace
+    let binding = json!({
+        "apiVersion": "rbac.authorization.k8s.io/v1",
+        "kind": "ClusterRoleBinding",
+        "metadata": { "name": "alice-views" },
+        "subjects": [{ "kind": "User", "name": "alice" }],
+        "roleRef": { "kind": "ClusterRole", "name": "view", "apiGroup": "rbac.authorization.k8s.io" }
+    });
+    let resp = client.post("admin-token", "/apis/rbac.authorization.k8s.io/v1/clusterrolebindings", binding).await;
+    assert_eq!(resp.status(), 201);
+    assert_eq!(client.get("alice-token", "/apis/apps/v1/namespaces/kube-system/deployments").await, 200);
+    assert_eq!(client.get("alice-token", "/api/v1/namespaces/kube-system/services").await, 200);
+    assert_eq!(client.get("alice-token", "/api/v1/namespaces/kube-system/secrets").await, 403);
+}
+
+#[tokio::test]
+async fn test_rbac_service_accounts() {
+    let client = Client::start(CONFIG).await;
+
+    let account = json!({ "apiVersion": "v1", "kind": "ServiceAccount", "metadata": { "name": "robot" } });
+    assert_eq!(client.post("admin-token", "/api/v1/namespaces/default/serviceaccounts", account).await.status(), 201);
+    let resp = client.post("admin-token", "/api/v1/namespaces/default/serviceaccounts/robot/token", json!({})).await;
+    assert!(resp.status().is_success());
+    let request: Value = resp.json().await.unwrap();
+    let token = request["status"]["token"].as_str().unwrap().to_string();
+
+    let configmap = json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "settings" }, "data": {} });
+    let path = "/api/v1/namespaces/default/configmaps";
+    assert_eq!(client.post(&token, path, configmap.clone()).await.status(), 403);
+
+    let binding = json!({
+        "apiVersion": "rbac.authorization.k8s.io/v1",
+        "kind": "RoleBinding",
+        "metadata": { "name": "robot-edits" },
+        "subjects": [{ "kind": "ServiceAccount", "name": "robot", "namespace": "default" }],
+        "roleRef": { "kind": "ClusterRole", "name": "edit", "apiGroup": "rbac.authorization.k8s.io" }
+    });
+    let resp = client.post("admin-token", "/apis/rbac.authorization.k8s.io/v1/namespaces/default/rolebindings", binding).await;
+    assert_eq!(resp.status(), 201);
+
+    assert_eq!(client.post(&token, path, configmap).await.status(), 201);
+    assert_eq!(client.get(&token, "/api/v1/namespaces/kube-system/configmaps").await, 403);
+    // The edit role doesn't extend to RBAC itself
+    assert_eq!(client.get(&token, "/apis/rbac.authorization.k8s.io/v1/namespaces/default/roles").await, 403);
+
+    let resp = client.post(&token, "/apis/authentication.k8s.io/v1/selfsubjectreviews", json!({})).await;
+    assert_eq!(resp.status(), 201);
+    let review: Value = resp.json().await.unwrap();
+    assert_eq!(review["status"]["userInfo"]["username"], "system:serviceaccount:default:robot");
+}
+
+#[tokio::test]
+async fn test_rbac_permissive_mode() {
+    let client = Client::start(&format!("{}  permissive: true\n", CONFIG)).await;
+    assert_eq!(client.get("alice-token", "/api/v1/namespaces/default/pods").await, 200);
+}
+
+#[tokio::test]
+async fn test_request_info() {
+    let parse = |method, path, query| RequestInfo::parse(method, path, query);
+    let resource = |verb: &str, group: &str, resource: &str, subresource: Option<&str>, namespace: Option<&str>, name: Option<&str>| {
+        RequestInfo::Resource {
+            verb: verb.to_string(),
+            group: group.to_string(),
+            resource: resource.to_string(),
+            subresource: subresource.map(str::to_string),
+            namespace: namespace.map(str::to_string),
+            name: name.map(str::to_string),
+        }
+    };
+
+    assert_eq!(parse("GET", "/api/v1/namespaces/default/pods", None), resource("list", "", "pods", None, Some("default"), None));
+    assert_eq!(parse("GET", "/api/v1/pods", Some("watch=true")), resource("watch", "", "pods", None, None, None));
+    assert_eq!(parse("GET", "/api/v1/watch/namespaces/default/pods", None), resource("watch", "", "pods", None, Some("default"), None));
+    assert_eq!(
+        parse("POST", "/api/v1/namespaces/default/pods/web/exec", Some("command=sh")),
+        resource("create", "", "pods", Some("exec"), Some("default"), Some("web"))
+    );
+    assert_eq!(
+        parse("PATCH", "/apis/apps/v1/namespaces/default/deployments/web/scale", None),
+        resource("patch", "apps", "deployments", Some("scale"), Some("default"), Some("web"))
+    );
+    assert_eq!(parse("DELETE", "/api/v1/namespaces/team-a", None), resource("delete", "", "namespaces", None, Some("team-a"), Some("team-a")));
+    assert_eq!(parse("DELETE", "/api/v1/namespaces/default/pods", None), resource("deletecollection", "", "pods", None, Some("default"), None));
+    assert_eq!(
+        parse("GET", "/apis/apps/v1", None),
+        RequestInfo::NonResource { verb: "get".to_string(), path: "/apis/apps/v1".to_string() }
+    );
+}
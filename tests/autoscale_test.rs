@@ -0,0 +1,215 @@
+use reqwest;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+const FAST_SYNC: &str = "autoscaling:\n  syncPeriodSeconds: 1\n";
+const SCALE_TO_ZERO: &str = "autoscaling:\n  syncPeriodSeconds: 1\n  scaleToZero: true\n";
+
+fn deployment(name: &str, replicas: i64, cpu_usage: &str) -> Value {
+    json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": name },
+        "spec": {
+            "replicas": replicas,
+            "selector": { "matchLabels": { "app": name } },
+            "template": {
+                "metadata": {
+                    "labels": { "app": name },
+                    "annotations": { "krust.io/fake-cpu-usage": cpu_usage }
+                },
+                "spec": { "containers": [{
+                    "name": "app",
+                    "image": "nginx:latest",
+                    "resources": { "requests": { "cpu": "200m" } }
+                }] }
+            }
+        }
+    })
+}
+
+async fn wait_for_replicas(server: &common::TestServer, name: &str, replicas: i64) {
+    for _ in 0..60 {
+        let deployment = server.storage.deployments().get("default", name).await.unwrap();
+        if deployment["spec"]["replicas"] == replicas {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    panic!("deployment {} never scaled to {} replicas", name, replicas);
+}
+
+#[tokio::test]
+async fn test_kubectl_autoscale_creates_v1_hpa() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    // What `kubectl autoscale deployment web --min=2 --max=5 --cpu-percent=60` sends
+    let hpa = json!({
+        "apiVersion": "autoscaling/v1",
+        "kind": "HorizontalPodAutoscaler",
+        "metadata": { "name": "web" },
+        "spec": {
+            "scaleTargetRef": { "apiVersion": "apps/v1", "kind": "Deployment", "name": "web" },
+            "minReplicas": 2,
+            "maxReplicas": 5,
+            "targetCPUUtilizationPercentage": 60
+        }
+    });
+    let url = server.url("/apis/autoscaling/v1/namespaces/default/horizontalpodautoscalers");
+    let resp = client.post(&url).json(&hpa).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let created: Value = resp.json().await.unwrap();
+    assert_eq!(created["apiVersion"], "autoscaling/v1");
+    assert_eq!(created["spec"]["targetCPUUtilizationPercentage"], 60);
+
+    let v2: Value = client
+        .get(server.url("/apis/autoscaling/v2/namespaces/default/horizontalpodautoscalers/web"))
+        .send().await.unwrap().json().await.unwrap();
+    assert_eq!(v2["spec"]["metrics"][0]["resource"]["name"], "cpu");
+    assert_eq!(v2["spec"]["metrics"][0]["resource"]["target"]["averageUtilization"], 60);
+
+    let list: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(list["items"][0]["spec"]["targetCPUUtilizationPercentage"], 60);
+
+    // A v1 update keeps the v2 metrics v1 can't express
+    let mut v2_update = v2.clone();
+    v2_update["spec"]["metrics"].as_array_mut().unwrap().push(json!({
+        "type": "External",
+        "external": { "metric": { "name": "queue_messages" }, "target": { "type": "AverageValue", "averageValue": "30" } }
+    }));
+    let resp = client
+        .put(server.url("/apis/autoscaling/v2/namespaces/default/horizontalpodautoscalers/web"))
+        .json(&v2_update).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let mut v1: Value = client.get(format!("{}/web", url)).send().await.unwrap().json().await.unwrap();
+    v1["spec"]["targetCPUUtilizationPercentage"] = json!(70);
+    let resp = client.put(format!("{}/web", url)).json(&v1).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let stored = server.storage.hpas().get("default", "web").await.unwrap();
+    let metrics = stored["spec"]["metrics"].as_array().unwrap();
+    assert_eq!(metrics.len(), 2);
+    assert_eq!(metrics[0]["resource"]["target"]["averageUtilization"], 70);
+    assert_eq!(metrics[1]["type"], "External");
+
+    let groups: Value = client.get(server.url("/apis")).send().await.unwrap().json().await.unwrap();
+    let autoscaling = groups["groups"].as_array().unwrap().iter().find(|g| g["name"] == "autoscaling").unwrap();
+    assert_eq!(autoscaling["versions"].as_array().unwrap().len(), 2);
+    assert_eq!(autoscaling["preferredVersion"]["version"], "v2");
+}
+
+#[tokio::test]
+async fn test_hpa_scales_on_cpu() {
+    let server = common::TestServer::start_with_config(krust::Config::parse(FAST_SYNC).unwrap()).await;
+    let client = reqwest::Client::new();
+
+    // Each pod uses twice the CPU it requests, four times the target
+    let resp = client
+        .post(server.url("/apis/apps/v1/namespaces/default/deployments"))
+        .json(&deployment("web", 1, "400m")).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let hpa = json!({
+        "apiVersion": "autoscaling/v1",
+        "kind": "HorizontalPodAutoscaler",
+        "metadata": { "name": "web" },
+        "spec": {
+            "scaleTargetRef": { "apiVersion": "apps/v1", "kind": "Deployment", "name": "web" },
+            "maxReplicas": 3,
+            "targetCPUUtilizationPercentage": 50
+        }
+    });
+    let resp = client
+        .post(server.url("/apis/autoscaling/v1/namespaces/default/horizontalpodautoscalers"))
+        .json(&hpa).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+
+    wait_for_replicas(&server, "web", 3).await;
+
+    let hpa = server.storage.hpas().get("default", "web").await.unwrap();
+    assert_eq!(hpa["status"]["desiredReplicas"], 3);
+    assert!(hpa["status"]["lastScaleTime"].is_string());
+    let conditions = hpa["status"]["conditions"].as_array().unwrap();
+    let limited = conditions.iter().find(|c| c["type"] == "ScalingLimited").unwrap();
+    assert_eq!(limited["reason"], "TooManyReplicas");
+
+    let uid = hpa["metadata"]["uid"].as_str().unwrap();
+    let url = server.url(&format!("/api/v1/namespaces/default/events?fieldSelector=involvedObject.uid%3D{}", uid));
+    let events: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    let rescale = events["items"].as_array().unwrap().iter().find(|e| e["reason"] == "SuccessfulRescale").unwrap();
+    assert!(rescale["message"].as_str().unwrap().starts_with("New size: 3; reason: cpu"));
+}
+
+#[tokio::test]
+async fn test_min_replicas_zero_needs_scale_to_zero() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let hpa = json!({
+        "apiVersion": "autoscaling/v2",
+        "kind": "HorizontalPodAutoscaler",
+        "metadata": { "name": "worker" },
+        "spec": {
+            "scaleTargetRef": { "apiVersion": "apps/v1", "kind": "Deployment", "name": "worker" },
+            "minReplicas": 0,
+            "maxReplicas": 5,
+            "metrics": [{
+                "type": "External",
+                "external": { "metric": { "name": "queue_messages" }, "target": { "type": "AverageValue", "averageValue": "10" } }
+            }]
+        }
+    });
+    let resp = client
+        .post(server.url("/apis/autoscaling/v2/namespaces/default/horizontalpodautoscalers"))
+        .json(&hpa).send().await.unwrap();
+    assert_eq!(resp.status(), 422);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(
+        status["message"],
+        "HorizontalPodAutoscaler.autoscaling \"worker\" is invalid: spec.minReplicas: Invalid value: 0: must be greater than or equal to 1"
+    );
+}
+
+#[tokio::test]
+async fn test_hpa_scales_to_zero_and_back() {
+    let server = common::TestServer::start_with_config(krust::Config::parse(SCALE_TO_ZERO).unwrap()).await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(server.url("/apis/apps/v1/namespaces/default/deployments"))
+        .json(&deployment("worker", 1, "100m")).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // Only an External metric can wake a target at zero
+    let mut hpa = json!({
+        "apiVersion": "autoscaling/v2",
+        "kind": "HorizontalPodAutoscaler",
+        "metadata": { "name": "worker", "annotations": { "krust.io/external-metrics": "queue_messages=0" } },
+        "spec": {
+            "scaleTargetRef": { "apiVersion": "apps/v1", "kind": "Deployment", "name": "worker" },
+            "minReplicas": 0,
+            "maxReplicas": 5
+        }
+    });
+    let url = server.url("/apis/autoscaling/v2/namespaces/default/horizontalpodautoscalers");
+    let resp = client.post(&url).json(&hpa).send().await.unwrap();
+    assert_eq!(resp.status(), 422);
+
+    hpa["spec"]["metrics"] = json!([{
+        "type": "External",
+        "external": { "metric": { "name": "queue_messages" }, "target": { "type": "AverageValue", "averageValue": "10" } }
+    }]);
+    let resp = client.post(&url).json(&hpa).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+
+    wait_for_replicas(&server, "worker", 0).await;
+
+    let mut stored = server.storage.hpas().get("default", "worker").await.unwrap();
+    stored["metadata"]["annotations"]["krust.io/external-metrics"] = json!("queue_messages=25");
+    let resp = client.put(format!("{}/worker", url)).json(&stored).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+
+    wait_for_replicas(&server, "worker", 3).await;
+}
@@ -0,0 +1,80 @@
+use krust::{Config, Storage};
+use serde_json::{json, Value};
+
+mod common;
+
+#[tokio::test]
+async fn test_online_backup() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config { data_dir: Some(dir.path().to_path_buf()), ..Config::default() };
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+    let path = dir.path().join("backups").join("nested").join("krust.db");
+
+    let configmap = json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "settings" }, "data": { "mode": "fast" } });
+    let resp = client.post(server.url("/api/v1/namespaces/default/configmaps")).json(&configmap).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let backup_url = server.url("/krust/backup?path=nested/krust.db");
+    let resp = client.post(&backup_url).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let report: Value = resp.json().await.unwrap();
+    assert_eq!(report["kind"], "Backup");
+    assert_eq!(report["path"], path.display().to_string());
+    assert_eq!(report["sizeBytes"], std::fs::metadata(&path).unwrap().len());
+
+    // The server keeps serving, and the copy opens as a database of its own
+    let resp = client.delete(server.url("/api/v1/namespaces/default/configmaps/settings")).send().await.unwrap();
+    assert!(resp.status().is_success());
+    let copy = Storage::new(&format!("sqlite:{}", path.display())).await.unwrap();
+    assert!(copy.schema().await.unwrap().up_to_date());
+    let restored = copy.configmaps().get("default", "settings").await.unwrap();
+    assert_eq!(restored["data"]["mode"], "fast");
+
+    // An existing file isn't overwritten
+    let resp = client.post(&backup_url).send().await.unwrap();
+    assert_eq!(resp.status(), 409);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "AlreadyExists");
+}
+
+#[tokio::test]
+async fn test_backup_into_data_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config { data_dir: Some(dir.path().to_path_buf()), ..Config::default() };
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let report: Value = client.post(server.url("/krust/backup")).send().await.unwrap().json().await.unwrap();
+    let path = report["path"].as_str().unwrap();
+    assert!(path.starts_with(&dir.path().join("backups").display().to_string()), "{}", path);
+    assert!(path.ends_with(".db"));
+    assert!(std::path::Path::new(path).exists());
+
+    let report: Value = client.post(server.url("/krust/backup?path=before-upgrade.db")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(report["path"], dir.path().join("backups").join("before-upgrade.db").display().to_string());
+}
+
+#[tokio::test]
+async fn test_backups_stay_in_the_backups_directory() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = Config { data_dir: Some(dir.path().to_path_buf()), ..Config::default() };
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let outside = dir.path().join("elsewhere.db");
+    for path in [outside.display().to_string(), "../krust.db".to_string(), "nested/../../krust.db".to_string()] {
+        let resp = client.post(server.url("/krust/backup")).query(&[("path", &path)]).send().await.unwrap();
+        assert_eq!(resp.status(), 400, "{}", path);
+        let status: Value = resp.json().await.unwrap();
+        assert_eq!(status["reason"], "BadRequest");
+        assert_eq!(status["message"], format!("{} is not a path within the backups directory", path));
+    }
+    assert!(!outside.exists());
+    assert!(!dir.path().join("krust.db").exists());
+
+    // Nor is there anywhere to back up to without a data directory
+    let server = common::TestServer::start().await;
+    let resp = client.post(server.url("/krust/backup?path=krust.db")).send().await.unwrap();
+    assert_eq!(resp.status(), 400);
+}
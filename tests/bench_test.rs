@@ -0,0 +1,67 @@
+use reqwest;
+use krust::bench::{self, BenchOptions, Summary};
+use std::time::Duration;
+
+mod common;
+
+#[tokio::test]
+async fn test_bench_against_a_server() {
+    let server = common::TestServer::start().await;
+    let options = BenchOptions {
+        server: server.base_url(),
+        pods: 6,
+        deployments: 2,
+        rate: 0.0,
+        concurrency: 3,
+        ..BenchOptions::default()
+    };
+
+    let report = bench::run(&options).await.unwrap();
+    assert!(report.errors.is_empty(), "{:?}", report.errors);
+    for operation in ["create", "update", "delete"] {
+        assert_eq!(report.requests[&format!("{} pods", operation)].count, 6);
+        assert_eq!(report.requests[&format!("{} deployments", operation)].count, 2);
+    }
+    // Every pod write showed up on the watch
+    assert_eq!(report.watch_lag.count, 18);
+    assert_eq!(report.missed_events, 0);
+    assert!(report.to_string().contains("watch lag (pods)"));
+
+    // Nothing is left behind
+    let pods: serde_json::Value = reqwest::get(server.url("/api/v1/namespaces/default/pods?labelSelector=krust.io/bench"))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(pods["items"].as_array().unwrap().len(), 0);
+}
+
+#[test]
+fn test_bench_options() {
+    let args = ["--pods", "50", "--rate=10", "--server", "http://krust:6443/", "--concurrency", "0"];
+    let options = BenchOptions::parse(args.map(String::from)).unwrap();
+    assert_eq!(options.pods, 50);
+    assert_eq!(options.rate, 10.0);
+    assert_eq!(options.server, "http://krust:6443");
+    assert_eq!(options.concurrency, 1);
+    assert_eq!(options.deployments, BenchOptions::default().deployments);
+
+    assert!(BenchOptions::parse(["--pods".to_string()]).is_err());
+    assert!(BenchOptions::parse(["--pods=many".to_string()]).is_err());
+    assert!(BenchOptions::parse(["--verbose".to_string()]).is_err());
+}
+
+#[test]
+fn test_percentiles() {
+    let samples = (1..=100).map(Duration::from_millis).collect();
+    let summary = Summary::of(samples);
+    assert_eq!(summary.count, 100);
+    assert_eq!(summary.p50, Duration::from_millis(50));
+    assert_eq!(summary.p90, Duration::from_millis(90));
+    assert_eq!(summary.p99, Duration::from_millis(99));
+    assert_eq!(summary.max, Duration::from_millis(100));
+
+    assert_eq!(Summary::of(vec![Duration::from_millis(7)]).p99, Duration::from_millis(7));
+    assert_eq!(Summary::of(Vec::new()), Summary::default());
+}
@@ -0,0 +1,99 @@
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+
+mod common;
+
+/// Polls `condition` until it holds, for at most five seconds.
+async fn eventually(condition: impl Fn() -> bool) -> bool {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while Instant::now() < deadline {
+        if condition() {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    condition()
+}
+
+#[tokio::test]
+async fn test_abandoned_watches_stop() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let mut watches = Vec::new();
+    for _ in 0..20 {
+        let resp = client
+            .get(server.url("/api/v1/namespaces/default/pods?watch=true"))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 200);
+        watches.push(resp);
+    }
+    assert_eq!(server.storage.watch().active_watches(), 20);
+
+    // Hanging up ends the watches on the server too
+    drop(watches);
+    assert!(eventually(|| server.storage.watch().active_watches() == 0).await);
+    assert!(eventually(|| server.storage.open_requests() == 0).await);
+}
+
+/// Holds every database connection until the returned ones are dropped.
+async fn block_database(server: &common::TestServer) -> Vec<sqlx::pool::PoolConnection<sqlx::Sqlite>> {
+    let mut held = Vec::new();
+    for _ in 0..server.storage.pool.options().get_max_connections() {
+        held.push(server.storage.pool.acquire().await.unwrap());
+    }
+    held
+}
+
+#[tokio::test]
+async fn test_client_timeouts_do_not_exhaust_the_pool() {
+    let server = common::TestServer::start().await;
+    let impatient = reqwest::Client::builder().timeout(Duration::from_millis(100)).build().unwrap();
+
+    // Far more requests than there are connections, each given up on while
+    // it waits for one
+    let held = block_database(&server).await;
+    let requests = (0..50).map(|i| {
+        let path = match i % 2 {
+            0 => "/api/v1/namespaces/default/pods",
+            _ => "/api/v1/namespaces/default/configmaps?watch=true",
+        };
+        impatient.get(server.url(path)).send()
+    });
+    for result in futures::future::join_all(requests).await {
+        assert!(result.unwrap_err().is_timeout());
+    }
+    drop(held);
+
+    // None of the abandoned requests are left to take connections
+    let started = Instant::now();
+    let resp = reqwest::get(server.url("/api/v1/namespaces/default/pods")).await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(started.elapsed() < Duration::from_secs(1));
+    assert!(eventually(|| server.storage.watch().active_watches() == 0).await);
+}
+
+#[tokio::test]
+async fn test_abandoned_requests_are_not_carried_out() {
+    let server = common::TestServer::start().await;
+    let impatient = reqwest::Client::builder().timeout(Duration::from_millis(100)).build().unwrap();
+
+    let held = block_database(&server).await;
+    let requests = (0..10).map(|i| {
+        let configmap = json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": format!("abandoned-{}", i) } });
+        impatient.post(server.url("/api/v1/namespaces/default/configmaps")).json(&configmap).send()
+    });
+    for result in futures::future::join_all(requests).await {
+        assert!(result.unwrap_err().is_timeout());
+    }
+    // Once the server has seen the clients go, they're cancelled while
+    // still queued for a connection and never get to write
+    assert!(eventually(|| server.storage.open_requests() == 0).await);
+    drop(held);
+
+    let list: Value = reqwest::get(server.url("/api/v1/namespaces/default/configmaps")).await.unwrap().json().await.unwrap();
+    let names: Vec<&str> = list["items"].as_array().unwrap().iter().filter_map(|item| item["metadata"]["name"].as_str()).collect();
+    assert!(names.iter().all(|name| !name.starts_with("abandoned-")), "{:?}", names);
+}
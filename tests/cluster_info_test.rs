@@ -0,0 +1,64 @@
+use reqwest;
+use serde_json::Value;
+use std::time::Duration;
+
+mod common;
+
+async fn wait_for_configmap(client: &reqwest::Client, server: &common::TestServer, namespace: &str, name: &str) -> Value {
+    let url = server.url(&format!("/api/v1/namespaces/{}/configmaps/{}", namespace, name));
+    for _ in 0..20 {
+        let resp = client.get(&url).send().await.unwrap();
+        if resp.status() == 200 {
+            return resp.json().await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    panic!("{} never appeared in namespace {}", name, namespace);
+}
+
+#[tokio::test]
+async fn test_cluster_info_is_published() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let cluster_info = wait_for_configmap(&client, &server, "kube-public", "cluster-info").await;
+    let kubeconfig: Value = serde_yaml::from_str(cluster_info["data"]["kubeconfig"].as_str().unwrap()).unwrap();
+    let cluster = &kubeconfig["clusters"][0]["cluster"];
+    assert_eq!(cluster["server"], server.base_url());
+    assert!(cluster["certificate-authority-data"].as_str().is_some_and(|ca| !ca.is_empty()));
+    assert!(kubeconfig["users"].is_null(), "cluster-info must not carry credentials");
+
+    let kubeadm = wait_for_configmap(&client, &server, "kube-system", "kubeadm-config").await;
+    let configuration: Value = serde_yaml::from_str(kubeadm["data"]["ClusterConfiguration"].as_str().unwrap()).unwrap();
+    assert_eq!(configuration["kind"], "ClusterConfiguration");
+    assert_eq!(configuration["kubernetesVersion"], "v1.29.0");
+    assert_eq!(configuration["controlPlaneEndpoint"], server.base_url().trim_start_matches("http://"));
+    assert_eq!(configuration["networking"]["dnsDomain"], "cluster.local");
+
+    let version = wait_for_configmap(&client, &server, "kube-system", "krust-version").await;
+    assert_eq!(version["data"]["version"], env!("CARGO_PKG_VERSION"));
+
+    // Changes are undone, and deleted ones come back
+    let url = server.url("/api/v1/namespaces/kube-public/configmaps/cluster-info");
+    let mut edited = cluster_info.clone();
+    edited["data"]["kubeconfig"] = Value::from("tampered");
+    client.put(&url).json(&edited).send().await.unwrap();
+    client.delete(server.url("/api/v1/namespaces/kube-system/configmaps/krust-version")).send().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    assert_eq!(wait_for_configmap(&client, &server, "kube-public", "cluster-info").await["data"], cluster_info["data"]);
+    assert_eq!(wait_for_configmap(&client, &server, "kube-system", "krust-version").await["data"], version["data"]);
+}
+
+#[tokio::test]
+async fn test_cluster_info_is_readable_anonymously() {
+    // Without authenticators every caller is system:anonymous
+    let config = krust::Config::parse("authorization:\n  mode: RBAC\n").unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    wait_for_configmap(&client, &server, "kube-public", "cluster-info").await;
+    let resp = client.get(server.url("/api/v1/namespaces/kube-public/configmaps/kube-root-ca.crt")).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+    let resp = client.get(server.url("/api/v1/namespaces/kube-system/configmaps/kubeadm-config")).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+}
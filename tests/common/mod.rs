@@ -1,34 +1,162 @@
 // Common test utilities and helpers
+//
+// Every test boots its own krust instance in-process: the API server on an
+// ephemeral port, backed by a private in-memory database, with the scheduler,
+// controllers and a fake kubelet running in the background. Nothing needs to
+// be started beforehand and tests can't see each other's objects.
+#![allow(dead_code)]
 
-use std::sync::Once;
+use std::net::SocketAddr;
 
-static INIT: Once = Once::new();
-static mut SERVER_AVAILABLE: bool = false;
+use krust::{controllers, runtime::{self, FakeKubelet}, scheduler::Scheduler, Config, Storage};
+use serde_json::{json, Value};
+use tokio::task::JoinHandle;
 
-pub async fn check_server() -> bool {
-    INIT.call_once(|| {
-        let runtime = tokio::runtime::Runtime::new().unwrap();
-        let result = runtime.block_on(async {
-            reqwest::get("http://localhost:6443/livez").await.is_ok()
-        });
-        unsafe {
-            SERVER_AVAILABLE = result;
+pub struct TestServer {
+    pub storage: Storage,
+    addr: SocketAddr,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl TestServer {
+    pub async fn start() -> Self {
+        Self::start_with_config(Config::default()).await
+    }
+
+    pub async fn start_with_config(config: Config) -> Self {
+        let storage = Storage::in_memory()
+            .await
+            .expect("failed to open in-memory database");
+        storage.migrate().await.expect("failed to run migrations");
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .expect("failed to bind test listener");
+        let addr = listener.local_addr().unwrap();
+
+        let mut tasks = Vec::new();
+
+        let scheduler = Scheduler::new(storage.clone(), &config);
+        let kubelet = FakeKubelet::new(storage.clone(), &config);
+        tasks.extend(controllers::spawn_all(&storage, &config));
+        tasks.extend(runtime::fake_kubelet::spawn_simulated_nodes(&storage, &config));
+
+        let server_storage = storage.clone();
+        tasks.push(tokio::spawn(async move {
+            if let Err(e) = krust::api::server::serve(listener, server_storage, config).await {
+                eprintln!("Test server failed: {}", e);
+            }
+        }));
+
+        tasks.push(tokio::spawn(async move {
+            let _ = scheduler.run().await;
+        }));
+
+        tasks.push(tokio::spawn(async move {
+            let _ = kubelet.run().await;
+        }));
+
+        Self {
+            storage,
+            addr,
+            tasks,
         }
-        if !result {
-            eprintln!("⚠️  Warning: Server not running at localhost:6443");
-            eprintln!("   Integration tests will be skipped");
-            eprintln!("   Start the server with: cargo run");
+    }
+
+    /// Root URL of the server, e.g. `http://127.0.0.1:41234`.
+    pub fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Absolute URL for an API path such as `/api/v1/namespaces`.
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{}", self.base_url(), path)
+    }
+
+    /// Waits for the scheduler and fake kubelet to bring a pod to Running.
+    pub async fn wait_for_pod_running(&self, namespace: &str, name: &str) {
+        for _ in 0..50 {
+            if let Ok(pod) = self.storage.pods().get(namespace, name).await {
+                if pod["status"]["phase"] == "Running" {
+                    return;
+                }
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(200)).await;
         }
-    });
-    unsafe { SERVER_AVAILABLE }
+        panic!("pod {}/{} never reached Running", namespace, name);
+    }
 }
 
-#[macro_export]
-macro_rules! require_server {
-    () => {
-        if !crate::common::check_server().await {
-            eprintln!("Skipping test - server not running");
-            return;
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
         }
-    };
-}
\ No newline at end of file
+    }
+}
+
+/// A Pod named `name` running one container, `app` with the nginx image,
+/// with the fields of `spec` set over that spec.
+pub fn pod(name: &str, spec: Value) -> Value {
+    let mut pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": name },
+        "spec": { "containers": [{ "name": "app", "image": "nginx" }] }
+    });
+    for (key, value) in spec.as_object().into_iter().flatten() {
+        pod["spec"][key] = value.clone();
+    }
+    pod
+}
+
+/// `object` with the fields of `metadata` set over its metadata.
+pub fn with_metadata(mut object: Value, metadata: Value) -> Value {
+    for (key, value) in metadata.as_object().into_iter().flatten() {
+        object["metadata"][key] = value.clone();
+    }
+    object
+}
+
+/// Wipes the data directory a `krust` started by the test would use, as
+/// `krust reset` does, for tests that run the binary rather than a
+/// TestServer.
+pub fn reset_data_dir() {
+    let config = Config::from_arg_list(Vec::new()).expect("failed to read the krust config");
+    let data_dir = config.data_dir().expect("the data directory is always set");
+    if let Err(e) = data_dir.reset() {
+        panic!("failed to reset {}: {:#}", data_dir.root().display(), e);
+    }
+}
+
+/// POSTs `body` to `url`, whatever the answer.
+pub async fn post(client: &reqwest::Client, url: &str, body: &Value) -> reqwest::Response {
+    client.post(url).json(body).send().await.expect("request failed")
+}
+
+/// Creates an object by POSTing it to the list at `url`, which must answer
+/// 201 Created, and returns it as created.
+pub async fn create(client: &reqwest::Client, url: &str, body: Value) -> Value {
+    let response = post(client, url, &body).await;
+    assert_eq!(response.status(), 201, "POST {}", url);
+    response.json().await.unwrap()
+}
+
+/// Creates a Pod in the default namespace; see `create`.
+pub async fn create_pod(client: &reqwest::Client, server: &TestServer, pod: Value) -> Value {
+    create(client, &server.url("/api/v1/namespaces/default/pods"), pod).await
+}
+
+/// GETs `url`, which must answer with success, and returns the body.
+pub async fn get(client: &reqwest::Client, url: &str) -> Value {
+    let response = client.get(url).send().await.expect("request failed");
+    assert!(response.status().is_success(), "GET {}: {}", url, response.status());
+    response.json().await.unwrap()
+}
+
+/// GETs `url`, returning the status and the body, if it is JSON.
+pub async fn lookup(client: &reqwest::Client, url: &str) -> (u16, Value) {
+    let response = client.get(url).send().await.expect("request failed");
+    let status = response.status().as_u16();
+    (status, response.json().await.unwrap_or_default())
+}
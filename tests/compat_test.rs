@@ -0,0 +1,122 @@
+use reqwest;
+use serde_json::{json, Value};
+use serial_test::serial;
+
+mod common;
+
+#[tokio::test]
+#[serial]
+async fn test_unsupported_fields_are_reported() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pod_name = format!("compat-{}", &uuid::Uuid::new_v4().to_string()[..8]);
+
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": {
+            "name": pod_name
+        },
+        "spec": {
+            "securityContext": {
+                "runAsNonRoot": true
+            },
+            "containers": [
+                {
+                    "name": "app",
+                    "image": "nginx:latest",
+                    "imagePullPolicy": "Always",
+                    "livenessProbe": {
+                        "httpGet": { "path": "/", "port": 80 }
+                    }
+                }
+            ]
+        }
+    });
+
+    let resp = client
+        .post(&format!("{}/api/v1/namespaces/default/pods", server.base_url()))
+        .json(&pod)
+        .send()
+        .await
+        .expect("Failed to create pod");
+    assert_eq!(resp.status(), 201);
+
+    let created: Value = resp.json().await.unwrap();
+    let annotation = created["metadata"]["annotations"]["krust.io/unsupported-fields"]
+        .as_str()
+        .expect("unsupported fields annotation should be set");
+    let fields: Vec<String> = serde_json::from_str(annotation).unwrap();
+    assert!(fields.contains(&"spec.securityContext".to_string()));
+    assert!(fields.contains(&"spec.containers[0].livenessProbe".to_string()));
+    // The kubelet honors imagePullPolicy
+    assert!(!fields.iter().any(|f| f.ends_with("imagePullPolicy")));
+
+    // The report lists the pod with a reason per field
+    let report: Value = client
+        .get(&format!("{}/krust/compat?namespace=default", server.base_url()))
+        .send()
+        .await
+        .expect("Failed to get compat report")
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["kind"], "CompatibilityReport");
+
+    let entry = report["pods"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|p| p["name"] == pod_name.as_str())
+        .expect("pod should appear in compat report");
+    let reported = entry["unsupportedFields"].as_array().unwrap();
+    assert_eq!(reported.len(), fields.len());
+    assert!(reported.iter().all(|f| f["reason"].as_str().map(|r| !r.is_empty()).unwrap_or(false)));
+
+    // Cleanup
+    let _ = client
+        .delete(&format!("{}/api/v1/namespaces/default/pods/{}", server.base_url(), pod_name))
+        .send()
+        .await;
+}
+
+#[tokio::test]
+#[serial]
+async fn test_supported_pod_has_no_annotation() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pod_name = format!("compat-ok-{}", &uuid::Uuid::new_v4().to_string()[..8]);
+
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": {
+            "name": pod_name
+        },
+        "spec": {
+            "containers": [
+                {
+                    "name": "app",
+                    "image": "nginx:latest",
+                    "env": [{ "name": "MODE", "value": "test" }]
+                }
+            ]
+        }
+    });
+
+    let resp = client
+        .post(&format!("{}/api/v1/namespaces/default/pods", server.base_url()))
+        .json(&pod)
+        .send()
+        .await
+        .expect("Failed to create pod");
+    assert_eq!(resp.status(), 201);
+
+    let created: Value = resp.json().await.unwrap();
+    assert!(created["metadata"]["annotations"]["krust.io/unsupported-fields"].is_null());
+
+    let _ = client
+        .delete(&format!("{}/api/v1/namespaces/default/pods/{}", server.base_url(), pod_name))
+        .send()
+        .await;
+}
@@ -0,0 +1,145 @@
+use krust::storage::compression;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+mod common;
+
+const LAST_APPLIED: &str = "kubectl.kubernetes.io/last-applied-configuration";
+
+// A deployment whose containers and last-applied annotation run to tens of
+// kilobytes of JSON, as a real one with lots of env does
+fn big_deployment(name: &str) -> Value {
+    let env: Vec<Value> = (0..300)
+        .map(|i| json!({ "name": format!("SETTING_{}", i), "value": format!("value-of-setting-number-{}", i) }))
+        .collect();
+    let mut deployment = json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": name, "labels": { "app": name } },
+        "spec": {
+            "replicas": 0,
+            "selector": { "matchLabels": { "app": name } },
+            "template": {
+                "metadata": { "labels": { "app": name } },
+                "spec": { "containers": [{ "name": "app", "image": "nginx", "env": env }] }
+            }
+        }
+    });
+    let last_applied = deployment.to_string();
+    deployment["metadata"]["annotations"] = json!({ LAST_APPLIED: last_applied });
+    deployment
+}
+
+#[tokio::test]
+async fn test_large_columns_are_compressed_transparently() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let base_url = server.url("/apis/apps/v1/namespaces/default/deployments");
+
+    let mut deployment = big_deployment("big");
+    deployment["metadata"]["annotations"]["krust.io/protected"] = json!("true");
+    let response = client.post(&base_url).json(&deployment).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let small = json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": "small", "annotations": { "team": "web" } },
+        "spec": {
+            "replicas": 0,
+            "selector": { "matchLabels": { "app": "small" } },
+            "template": { "metadata": { "labels": { "app": "small" } }, "spec": { "containers": [{ "name": "app", "image": "nginx" }] } }
+        }
+    });
+    let response = client.post(&base_url).json(&small).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+
+    // The big one's spec and annotations are compressed, the small one's aren't
+    let rows: Vec<(String, String, String)> = sqlx::query_as(
+        "SELECT name, typeof(spec), typeof(annotations) FROM deployments WHERE namespace = 'default' ORDER BY name",
+    )
+    .fetch_all(&*server.storage.pool)
+    .await
+    .unwrap();
+    assert_eq!(rows, vec![
+        ("big".to_string(), "blob".to_string(), "blob".to_string()),
+        ("small".to_string(), "text".to_string(), "text".to_string()),
+    ]);
+
+    // Reads, lists and updates see the objects as written
+    let fetched: Value = client.get(format!("{}/big", base_url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(fetched["spec"]["template"], deployment["spec"]["template"]);
+    assert_eq!(fetched["metadata"]["annotations"], deployment["metadata"]["annotations"]);
+    let list: Value = client.get(&base_url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(list["items"].as_array().unwrap().len(), 2);
+
+    let mut updated = fetched.clone();
+    updated["spec"]["template"]["spec"]["containers"][0]["image"] = json!("nginx:1.27");
+    let response = client.put(format!("{}/big", base_url)).json(&updated).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let fetched: Value = client.get(format!("{}/big", base_url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(fetched["spec"]["template"]["spec"]["containers"][0]["image"], "nginx:1.27");
+
+    // Its ReplicaSet is created from the compressed template, and its
+    // compressed protection annotation still protects it
+    let mut replicasets = Value::Null;
+    for _ in 0..50 {
+        replicasets = client
+            .get(server.url("/apis/apps/v1/namespaces/default/replicasets?labelSelector=app%3Dbig"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if !replicasets["items"].as_array().unwrap().is_empty() {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    let template = &replicasets["items"][0]["spec"]["template"]["spec"];
+    assert_eq!(template["containers"][0]["env"].as_array().unwrap().len(), 300);
+    let response = client.delete(format!("{}/big", base_url)).send().await.unwrap();
+    assert!(response.status().is_client_error());
+    let response = client.delete(format!("{}/small", base_url)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+}
+
+#[tokio::test]
+async fn test_compressed_replicasets_are_selected_on_their_status() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let base_url = server.url("/apis/apps/v1/namespaces/default/replicasets");
+
+    let mut replicaset = big_deployment("big");
+    replicaset["kind"] = json!("ReplicaSet");
+    let response = client.post(&base_url).json(&replicaset).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let mut stored: Value = response.json().await.unwrap();
+
+    // A status as big as the spec, which the controller keeps the
+    // conditions of
+    let conditions: Vec<Value> = (0..300)
+        .map(|i| json!({ "type": format!("example.com/condition-{}", i), "status": "True", "message": "set by the test" }))
+        .collect();
+    stored["status"]["conditions"] = json!(conditions);
+    let response = client.put(format!("{}/big/status", base_url)).json(&stored).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let (spec, status, size): (String, String, i64) =
+        sqlx::query_as("SELECT typeof(spec), typeof(status), length(status) FROM replicasets WHERE name = 'big'")
+            .fetch_one(&*server.storage.pool)
+            .await
+            .unwrap();
+    assert_eq!((spec.as_str(), status.as_str()), ("blob", "text"));
+    assert!(size > compression::DEFAULT_THRESHOLD as i64);
+
+    let selected = |selector: &'static str| {
+        let client = client.clone();
+        let url = format!("{}?fieldSelector={}", base_url, selector);
+        async move {
+            let list: Value = client.get(url).send().await.unwrap().json().await.unwrap();
+            list["items"].as_array().unwrap().iter().map(|rs| rs["metadata"]["name"].as_str().unwrap().to_string()).collect::<Vec<_>>()
+        }
+    };
+    assert_eq!(selected("status.replicas%3D0").await, vec!["big"]);
+    assert!(selected("status.replicas%3D3").await.is_empty());
+}
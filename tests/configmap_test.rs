@@ -1,16 +1,13 @@
 use reqwest;
 use serde_json::json;
 
+mod common;
+
 #[tokio::test]
 async fn test_configmap_crud_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Test 1: Create a ConfigMap
     let configmap = json!({
@@ -140,14 +137,9 @@ async fn test_configmap_crud_operations() {
 
 #[tokio::test]
 async fn test_configmap_binary_data() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Create ConfigMap with binary data
     let configmap = json!({
@@ -193,14 +185,9 @@ async fn test_configmap_binary_data() {
 
 #[tokio::test]
 async fn test_configmap_immutable() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Create an immutable ConfigMap
     let configmap = json!({
@@ -0,0 +1,145 @@
+use reqwest;
+use serde_json::{json, Value};
+
+mod common;
+
+#[tokio::test]
+async fn test_stale_writes_get_conflict() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/api/v1/namespaces/default/configmaps/shared");
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "shared" }, "data": {} }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // Two writers read the same version; the first to write wins
+    let read: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    let mut first = read.clone();
+    first["data"]["writer"] = json!("first");
+    let resp = client.put(&url).json(&first).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let written: Value = resp.json().await.unwrap();
+
+    let mut second = read.clone();
+    second["data"]["writer"] = json!("second");
+    let resp = client.put(&url).json(&second).send().await.unwrap();
+    assert_eq!(resp.status(), 409);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["kind"], "Status");
+    assert_eq!(status["reason"], "Conflict");
+    assert_eq!(status["code"], 409);
+    assert_eq!(status["details"]["name"], "shared");
+    assert!(status["message"].as_str().unwrap().contains("the object has been modified"));
+
+    // Patches are held to a version they carry too
+    let patch = |version: &Value| {
+        client
+            .patch(&url)
+            .header("Content-Type", "application/merge-patch+json")
+            .body(json!({ "metadata": { "resourceVersion": version }, "data": { "writer": "patch" } }).to_string())
+            .send()
+    };
+    assert_eq!(patch(&read["metadata"]["resourceVersion"]).await.unwrap().status(), 409);
+    assert_eq!(patch(&written["metadata"]["resourceVersion"]).await.unwrap().status(), 200);
+
+    let stored: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(stored["data"]["writer"], "patch");
+
+    // Without a version, a write goes through whatever came before it
+    second["metadata"].as_object_mut().unwrap().remove("resourceVersion");
+    let resp = client.put(&url).json(&second).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_concurrent_read_modify_writes_are_all_kept() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/api/v1/namespaces/default/configmaps/counter");
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "counter" }, "data": {} }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // Each writer retries on conflict, like client-go's RetryOnConflict
+    let writers = (0..8).map(|i| {
+        let client = client.clone();
+        let url = url.clone();
+        tokio::spawn(async move {
+            loop {
+                let mut configmap: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+                configmap["data"][format!("writer-{}", i)] = json!("done");
+                let resp = client.put(&url).json(&configmap).send().await.unwrap();
+                match resp.status().as_u16() {
+                    200 => break,
+                    409 => continue,
+                    other => panic!("unexpected status {}", other),
+                }
+            }
+        })
+    });
+    for writer in writers.collect::<Vec<_>>() {
+        writer.await.unwrap();
+    }
+
+    let configmap: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    for i in 0..8 {
+        assert_eq!(configmap["data"][format!("writer-{}", i)], "done", "{}", configmap);
+    }
+}
+
+#[tokio::test]
+async fn test_writers_of_one_version_are_swapped_in_once() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/api/v1/namespaces/default/configmaps/swapped");
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "swapped" }, "data": {} }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let read: Value = resp.json().await.unwrap();
+
+    // All carry the version they read; only one gets to write over it
+    let writers = (0..8).map(|i| {
+        let client = client.clone();
+        let url = url.clone();
+        let mut configmap = read.clone();
+        configmap["data"]["writer"] = json!(i.to_string());
+        tokio::spawn(async move { client.put(&url).json(&configmap).send().await.unwrap().status().as_u16() })
+    });
+    let mut statuses = Vec::new();
+    for writer in writers.collect::<Vec<_>>() {
+        statuses.push(writer.await.unwrap());
+    }
+    statuses.sort();
+    assert_eq!(statuses, vec![200, 409, 409, 409, 409, 409, 409, 409]);
+
+    // The claim is on the row as it is now
+    let stored: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    let version = stored["metadata"]["resourceVersion"].as_str().unwrap();
+    let uid = stored["metadata"]["uid"].as_str().unwrap();
+    let stale = read["metadata"]["resourceVersion"].as_str().unwrap();
+    let claim = |uid: Option<&'static str>, version: &str| {
+        let storage = server.storage.clone();
+        let version = version.to_string();
+        async move { storage.claim_object("configmaps", Some("default"), "swapped", uid, Some(&version)).await.unwrap() }
+    };
+    assert!(claim(None, version).await);
+    assert!(!claim(None, stale).await);
+    assert!(!claim(Some("another-uid"), version).await);
+    assert!(server.storage.claim_object("configmaps", Some("default"), "swapped", Some(uid), None).await.unwrap());
+    assert!(!server.storage.claim_object("configmaps", Some("default"), "missing", None, Some(version)).await.unwrap());
+}
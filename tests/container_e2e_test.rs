@@ -2,6 +2,8 @@ use std::process::Command;
 use std::thread;
 use std::time::Duration;
 
+mod common;
+
 fn wait_for_condition<F>(condition: F, timeout_secs: u64, message: &str) -> bool 
 where
     F: Fn() -> bool,
@@ -30,10 +32,7 @@ fn test_pod_with_real_container() {
     
     thread::sleep(Duration::from_secs(1));
     
-    Command::new("rm")
-        .args(&["-f", "krust.db"])
-        .output()
-        .ok();
+    common::reset_data_dir();
     
     // Start server
     println!("Starting Krust server...");
@@ -247,10 +246,7 @@ fn test_pod_with_probes() {
     
     thread::sleep(Duration::from_secs(1));
     
-    Command::new("rm")
-        .args(&["-f", "krust.db"])
-        .output()
-        .ok();
+    common::reset_data_dir();
     
     // Start server
     let mut server = Command::new("cargo")
@@ -0,0 +1,62 @@
+use krust::controllers::Resync;
+use krust::Config;
+use reqwest;
+use serde_json::Value;
+use std::time::Duration;
+
+mod common;
+
+fn controller(report: &Value, name: &str) -> Value {
+    report["controllers"].as_array().unwrap().iter().find(|c| c["name"] == name).unwrap().clone()
+}
+
+#[tokio::test]
+async fn test_controllers_report_defaults() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let report: Value = client.get(server.url("/krust/controllers")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(report["kind"], "ControllerList");
+    assert_eq!(report["jitter"], 0.1);
+    assert_eq!(controller(&report, "deployment")["resyncPeriodSeconds"], 2.0);
+    assert_eq!(controller(&report, "scheduler")["resyncPeriodSeconds"], 1.0);
+    assert_eq!(controller(&report, "hpa")["resyncPeriodSeconds"], 15.0);
+    assert_eq!(controller(&report, "endpoints")["maxWaitSeconds"], 2.2);
+}
+
+#[tokio::test]
+async fn test_controllers_report_configured() {
+    let config = Config::parse(
+        "autoscaling:\n  syncPeriodSeconds: 30\ncontrollers:\n  jitter: 0\n  resyncPeriodSeconds:\n    deployment: 10\n    scheduler: 0.5\n",
+    )
+    .unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let report: Value = client.get(server.url("/krust/controllers")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(report["jitter"], 0.0);
+    assert_eq!(controller(&report, "deployment")["resyncPeriodSeconds"], 10.0);
+    assert_eq!(controller(&report, "deployment")["maxWaitSeconds"], 10.0);
+    assert_eq!(controller(&report, "scheduler")["resyncPeriodSeconds"], 0.5);
+    assert_eq!(controller(&report, "hpa")["resyncPeriodSeconds"], 30.0);
+    assert_eq!(controller(&report, "replicaSet")["resyncPeriodSeconds"], 2.0);
+}
+
+#[test]
+fn test_resync_jitter_stays_within_bounds() {
+    let config = Config::parse("controllers:\n  jitter: 0.5\n  resyncPeriodSeconds:\n    job: 4\n").unwrap();
+    let resync = Resync::new(&config, "job");
+    assert_eq!(resync.period, Duration::from_secs(4));
+    for _ in 0..100 {
+        let wait = resync.next_wait();
+        assert!(wait >= Duration::from_secs(4) && wait <= Duration::from_secs(6), "{:?}", wait);
+    }
+}
+
+#[test]
+fn test_controllers_config_validation() {
+    let unknown = Config::parse("controllers:\n  resyncPeriodSeconds:\n    teleporter: 1\n").unwrap_err();
+    assert!(format!("{:#}", unknown).contains("unknown controller \"teleporter\""));
+    assert!(Config::parse("controllers:\n  resyncPeriodSeconds:\n    job: 0\n").is_err());
+    assert!(Config::parse("controllers:\n  jitter: 2\n").is_err());
+}
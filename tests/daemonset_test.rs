@@ -1,16 +1,13 @@
 use reqwest;
 use serde_json::json;
 
+mod common;
+
 #[tokio::test]
 async fn test_daemonset_crud_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/apps/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/apps/v1");
     
     // Test 1: Create a DaemonSet
     let daemonset = json!({
@@ -197,14 +194,9 @@ async fn test_daemonset_crud_operations() {
 
 #[tokio::test]
 async fn test_daemonset_node_selector() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/apps/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/apps/v1");
     
     // Create DaemonSet with node selector
     let daemonset = json!({
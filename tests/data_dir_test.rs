@@ -0,0 +1,58 @@
+use krust::data_dir::DataDir;
+use krust::Config;
+use std::process::Command;
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|arg| arg.to_string()).collect()
+}
+
+#[test]
+fn test_data_dir_flag() {
+    let config = Config::from_arg_list(args(&["--data-dir", "/srv/krust"])).unwrap();
+    assert_eq!(config.data_dir(), Some(DataDir::new("/srv/krust")));
+    let config = Config::from_arg_list(args(&["--data-dir=/srv/other"])).unwrap();
+    assert_eq!(config.data_dir(), Some(DataDir::new("/srv/other")));
+    assert!(Config::from_arg_list(args(&["--data-dir"])).is_err());
+
+    // The binary always has one; embedded servers only when configured
+    assert!(Config::default().data_dir().is_none());
+
+    let data_dir = DataDir::new("/srv/krust");
+    assert_eq!(data_dir.database_url(), "sqlite:/srv/krust/krust.db?mode=rwc");
+    assert_eq!(data_dir.pki().to_str(), Some("/srv/krust/pki"));
+    assert_eq!(data_dir.pod("1234").to_str(), Some("/srv/krust/pods/1234"));
+}
+
+#[test]
+fn test_reset_removes_only_krust_state() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path().join("state");
+    std::fs::create_dir_all(root.join("pki")).unwrap();
+    std::fs::create_dir_all(root.join("pods/1234")).unwrap();
+    std::fs::write(root.join("krust.db"), "").unwrap();
+    std::fs::write(root.join("krust.db-wal"), "").unwrap();
+    std::fs::write(root.join("pki/ca.crt"), "").unwrap();
+    std::fs::write(root.join("notes.txt"), "mine").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_krust"))
+        .args(["reset", "--data-dir"])
+        .arg(&root)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("krust.db"), "{}", stdout);
+
+    let mut left: Vec<_> = std::fs::read_dir(&root)
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().into_string().unwrap())
+        .collect();
+    left.sort();
+    assert_eq!(left, ["notes.txt"]);
+
+    // Without anything else in it the directory goes too
+    std::fs::remove_file(root.join("notes.txt")).unwrap();
+    std::fs::write(root.join("krust.db"), "").unwrap();
+    DataDir::new(&root).reset().unwrap();
+    assert!(!root.exists());
+}
@@ -0,0 +1,226 @@
+use reqwest;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+// Polls for up to `seconds` until `url` is gone, returning whether it went
+async fn gone_within(client: &reqwest::Client, url: &str, seconds: u64) -> bool {
+    for _ in 0..seconds * 10 {
+        if common::lookup(client, url).await.0 == 404 {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+// The names of the ReplicaSets and pods of the deployment <app>
+async fn owned(client: &reqwest::Client, server: &common::TestServer, app: &str) -> (Vec<String>, Vec<String>) {
+    let names = |list: Value| -> Vec<String> {
+        list["items"].as_array().unwrap().iter().map(|item| item["metadata"]["name"].as_str().unwrap().to_string()).collect()
+    };
+    let (_, replicasets) = common::lookup(client, &server.url(&format!("/apis/apps/v1/namespaces/default/replicasets?labelSelector=app%3D{}", app))).await;
+    let (_, pods) = common::lookup(client, &server.url(&format!("/api/v1/namespaces/default/pods?labelSelector=app%3D{}", app))).await;
+    (names(replicasets), names(pods))
+}
+
+async fn create_deployment(client: &reqwest::Client, server: &common::TestServer, name: &str) {
+    let deployment = json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": name },
+        "spec": {
+            "replicas": 2,
+            "selector": { "matchLabels": { "app": name } },
+            "template": {
+                "metadata": { "labels": { "app": name } },
+                "spec": { "containers": [{ "name": "app", "image": "nginx" }] }
+            }
+        }
+    });
+    let resp = client.post(server.url("/apis/apps/v1/namespaces/default/deployments")).json(&deployment).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    for _ in 0..100 {
+        let (replicasets, pods) = owned(client, server, name).await;
+        if replicasets.len() == 1 && pods.len() == 2 {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("deployment {} never got its ReplicaSet and pods", name);
+}
+
+#[tokio::test]
+async fn test_delete_preconditions() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/api/v1/namespaces/default/configmaps/settings");
+
+    let configmap = json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "settings" }, "data": { "a": "1" } });
+    let resp = client.post(server.url("/api/v1/namespaces/default/configmaps")).json(&configmap).send().await.unwrap();
+    let created: Value = resp.json().await.unwrap();
+    let uid = created["metadata"]["uid"].as_str().unwrap();
+    let version = created["metadata"]["resourceVersion"].as_str().unwrap();
+
+    let options = json!({ "kind": "DeleteOptions", "apiVersion": "v1", "preconditions": { "uid": "not-the-uid" } });
+    let resp = client.delete(&url).json(&options).send().await.unwrap();
+    assert_eq!(resp.status(), 409);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "Conflict");
+    assert_eq!(
+        status["message"],
+        format!("Operation cannot be fulfilled on configmaps \"settings\": Precondition failed: UID in precondition: not-the-uid, UID in object meta: {}", uid)
+    );
+
+    let options = json!({ "preconditions": { "uid": uid, "resourceVersion": "1" } });
+    let resp = client.delete(&url).json(&options).send().await.unwrap();
+    assert_eq!(resp.status(), 409);
+    let status: Value = resp.json().await.unwrap();
+    assert!(status["message"].as_str().unwrap().contains("ResourceVersion in precondition: 1"));
+    assert_eq!(common::lookup(&client, &url).await.0, 200);
+
+    let resp = client.delete(&url).json(&json!({ "propagationPolicy": "Sideways" })).send().await.unwrap();
+    assert_eq!(resp.status(), 400);
+
+    let options = json!({ "preconditions": { "uid": uid, "resourceVersion": version } });
+    let resp = client.delete(&url).json(&options).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(common::lookup(&client, &url).await.0, 404);
+}
+
+#[tokio::test]
+async fn test_pods_get_their_grace_period() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pods = server.url("/api/v1/namespaces/default/pods");
+
+    // Its containers ignore SIGTERM, so it takes the whole grace period
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "stubborn", "annotations": { "krust.io/fake-termination-seconds": "60" } },
+        "spec": { "containers": [{ "name": "app", "image": "nginx" }] }
+    });
+    assert_eq!(client.post(&pods).json(&pod).send().await.unwrap().status(), 201);
+    server.wait_for_pod_running("default", "stubborn").await;
+
+    let url = server.url("/api/v1/namespaces/default/pods/stubborn");
+    let resp = client.delete(&url).json(&json!({ "gracePeriodSeconds": 2 })).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let deleting: Value = resp.json().await.unwrap();
+    assert_eq!(deleting["metadata"]["deletionGracePeriodSeconds"], 2);
+    assert!(deleting["metadata"]["deletionTimestamp"].is_string());
+
+    // It's still there while its kubelet stops it, and gone once killed
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    let (status, fetched) = common::lookup(&client, &url).await;
+    assert_eq!(status, 200);
+    assert_eq!(fetched["metadata"]["deletionGracePeriodSeconds"], 2);
+    assert!(gone_within(&client, &url, 5).await);
+
+    // A grace period of zero deletes it at once
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "forced", "annotations": { "krust.io/fake-termination-seconds": "60" } },
+        "spec": { "containers": [{ "name": "app", "image": "nginx" }] }
+    });
+    assert_eq!(client.post(&pods).json(&pod).send().await.unwrap().status(), 201);
+    server.wait_for_pod_running("default", "forced").await;
+    let url = server.url("/api/v1/namespaces/default/pods/forced?gracePeriodSeconds=0");
+    assert_eq!(client.delete(&url).send().await.unwrap().status(), 200);
+    assert_eq!(common::lookup(&client, &server.url("/api/v1/namespaces/default/pods/forced")).await.0, 404);
+}
+
+#[tokio::test]
+async fn test_propagation_policies() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let deployments = server.url("/apis/apps/v1/namespaces/default/deployments");
+
+    // Background, the default: the ReplicaSet and its pods follow
+    create_deployment(&client, &server, "background").await;
+    let resp = client.delete(format!("{}/background", deployments)).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let mut cleaned_up = false;
+    for _ in 0..100 {
+        if owned(&client, &server, "background").await == (vec![], vec![]) {
+            cleaned_up = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(cleaned_up, "dependents left behind: {:?}", owned(&client, &server, "background").await);
+
+    // Orphan: they stay, no longer owned
+    create_deployment(&client, &server, "orphan").await;
+    let resp = client.delete(format!("{}/orphan", deployments)).json(&json!({ "propagationPolicy": "Orphan" })).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    let (replicasets, pods) = owned(&client, &server, "orphan").await;
+    assert_eq!((replicasets.len(), pods.len()), (1, 2));
+    let (_, replicaset) = common::lookup(&client, &server.url(&format!("/apis/apps/v1/namespaces/default/replicasets/{}", replicasets[0]))).await;
+    assert_eq!(replicaset["metadata"]["ownerReferences"], json!([]));
+
+    // Foreground: the deployment waits for them
+    create_deployment(&client, &server, "foreground").await;
+    let url = format!("{}/foreground", deployments);
+    let resp = client.delete(&url).json(&json!({ "propagationPolicy": "Foreground" })).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let deleting: Value = resp.json().await.unwrap();
+    assert_eq!(deleting["metadata"]["finalizers"], json!(["foregroundDeletion"]));
+    assert!(deleting["metadata"]["deletionTimestamp"].is_string());
+    assert_eq!(common::lookup(&client, &url).await.0, 200);
+    assert!(gone_within(&client, &url, 10).await);
+    assert_eq!(owned(&client, &server, "foreground").await, (vec![], vec![]));
+}
+
+#[tokio::test]
+async fn test_foreground_deletion_scales_down_without_replacing_pods() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    create_deployment(&client, &server, "teardown").await;
+    let (replicasets, _) = owned(&client, &server, "teardown").await;
+
+    // A finalizer holds the ReplicaSet once its pods are gone
+    let replicaset = server.url(&format!("/apis/apps/v1/namespaces/default/replicasets/{}", replicasets[0]));
+    let resp = client
+        .patch(&replicaset)
+        .header("Content-Type", "application/merge-patch+json")
+        .body(json!({ "metadata": { "finalizers": ["example.com/hold"] } }).to_string())
+        .send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let url = server.url("/apis/apps/v1/namespaces/default/deployments/teardown");
+    let resp = client.delete(&url).json(&json!({ "propagationPolicy": "Foreground" })).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let mut scaled_down = false;
+    for _ in 0..150 {
+        let (_, rs) = common::lookup(&client, &replicaset).await;
+        if owned(&client, &server, "teardown").await.1.is_empty() && rs["status"]["replicas"] == 0 {
+            scaled_down = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(scaled_down, "the ReplicaSet never scaled down: {:?}", owned(&client, &server, "teardown").await);
+
+    // Its pods aren't replaced while it waits, and the deployment waits for it
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    let (status, rs) = common::lookup(&client, &replicaset).await;
+    assert_eq!(status, 200);
+    assert!(rs["metadata"]["deletionTimestamp"].is_string());
+    assert_eq!(owned(&client, &server, "teardown").await.1, Vec::<String>::new());
+    assert_eq!(common::lookup(&client, &url).await.0, 200);
+
+    let resp = client
+        .patch(&replicaset)
+        .header("Content-Type", "application/merge-patch+json")
+        .body(json!({ "metadata": { "finalizers": null } }).to_string())
+        .send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(gone_within(&client, &url, 10).await);
+    assert_eq!(owned(&client, &server, "teardown").await, (vec![], vec![]));
+}
@@ -1,16 +1,13 @@
 use reqwest;
 use serde_json::json;
 
+mod common;
+
 #[tokio::test]
 async fn test_deployment_crud_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/apps/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/apps/v1");
     
     // Create a Deployment
     let deployment = json!({
@@ -223,14 +220,9 @@ async fn test_deployment_crud_operations() {
 
 #[tokio::test]
 async fn test_deployment_rollout() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/apps/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/apps/v1");
     
     // Create initial Deployment
     let deployment = json!({
@@ -316,4 +308,48 @@ async fn test_deployment_rollout() {
         .send()
         .await
         .unwrap();
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_deployment_status_is_watched() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let mut watch = client
+        .get(server.url("/apis/apps/v1/namespaces/default/deployments?watch=true"))
+        .send()
+        .await
+        .unwrap();
+
+    let deployment = json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": "watched" },
+        "spec": {
+            "replicas": 2,
+            "selector": { "matchLabels": { "app": "watched" } },
+            "template": {
+                "metadata": { "labels": { "app": "watched" } },
+                "spec": { "containers": [{ "name": "app", "image": "nginx" }] }
+            }
+        }
+    });
+    let response = client.post(server.url("/apis/apps/v1/namespaces/default/deployments")).json(&deployment).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+
+    // The rollout shows up as MODIFIED events, each with a newer resourceVersion
+    let mut versions = Vec::new();
+    let mut buffer = String::new();
+    loop {
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(10), watch.chunk()).await.unwrap().unwrap().unwrap();
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+        let Some(end) = buffer.rfind('\n') else { continue };
+        let lines: Vec<String> = buffer[..end].lines().map(String::from).collect();
+        buffer.drain(..=end);
+        let events: Vec<serde_json::Value> = lines.iter().map(|line| serde_json::from_str(line).unwrap()).collect();
+        versions.extend(events.iter().map(|e| e["object"]["metadata"]["resourceVersion"].as_str().unwrap().parse::<i64>().unwrap()));
+        if events.iter().any(|e| e["type"] == "MODIFIED" && e["object"]["status"]["readyReplicas"] == 2) {
+            break;
+        }
+    }
+    assert!(versions.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", versions);
+}
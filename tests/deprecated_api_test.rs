@@ -0,0 +1,108 @@
+use reqwest;
+use serde_json::{json, Value};
+
+mod common;
+
+#[tokio::test]
+async fn test_removed_api_returns_helpful_status() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let cronjob = json!({
+        "apiVersion": "batch/v1beta1",
+        "kind": "CronJob",
+        "metadata": { "name": "legacy" },
+        "spec": {
+            "schedule": "*/5 * * * *",
+            "jobTemplate": { "spec": { "template": { "spec": { "containers": [] } } } }
+        }
+    });
+
+    let response = client
+        .post(server.url("/apis/batch/v1beta1/namespaces/default/cronjobs"))
+        .json(&cronjob)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 404);
+    let warning = response.headers()["warning"].to_str().unwrap().to_string();
+    assert!(warning.starts_with("299 - "));
+    assert!(warning.contains("unavailable in v1.25+"));
+
+    let status: Value = response.json().await.unwrap();
+    assert_eq!(status["kind"], "Status");
+    assert_eq!(status["reason"], "NotFound");
+    let message = status["message"].as_str().unwrap();
+    assert!(message.contains("batch/v1 CronJob"), "unexpected message: {}", message);
+
+    // The request shows up in the deprecation report
+    let report: Value = client
+        .get(server.url("/krust/deprecations"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let entry = report["removedAPIs"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|api| api["groupVersion"] == "batch/v1beta1" && api["resource"] == "cronjobs")
+        .expect("batch/v1beta1 cronjobs should be listed");
+    assert!(entry["requests"].as_u64().unwrap() >= 1);
+    assert_eq!(entry["replacement"], "batch/v1");
+}
+
+#[tokio::test]
+async fn test_removed_group_version_discovery() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(server.url("/apis/extensions/v1beta1"))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 404);
+    let status: Value = response.json().await.unwrap();
+    let message = status["message"].as_str().unwrap();
+    assert!(message.contains("apps/v1"));
+    assert!(message.contains("networking.k8s.io/v1"));
+}
+
+#[tokio::test]
+async fn test_unknown_path_returns_status() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(server.url("/apis/example.com/v1/widgets"))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 404);
+    let status: Value = response.json().await.unwrap();
+    assert_eq!(status["kind"], "Status");
+    assert_eq!(status["message"], "the server could not find the requested resource");
+}
+
+#[tokio::test]
+async fn test_unimplemented_group_explains_why() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let response = client
+        .get(server.url("/apis/apiextensions.k8s.io/v1/customresourcedefinitions"))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(response.status(), 404);
+    let status: Value = response.json().await.unwrap();
+    let message = status["message"].as_str().unwrap();
+    assert!(message.contains("CustomResourceDefinitions are not supported"), "unexpected message: {}", message);
+}
@@ -0,0 +1,240 @@
+use krust::config::DnsServerConfig;
+use krust::runtime::dns_server::{truncate, udp_limit, Answer, DnsServer, Zone};
+use krust::Config;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+const A: u16 = 1;
+const CNAME: u16 = 5;
+const SRV: u16 = 33;
+
+fn query(name: &str, record_type: u16) -> Vec<u8> {
+    let mut query = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+    for label in name.split('.') {
+        query.push(label.len() as u8);
+        query.extend_from_slice(label.as_bytes());
+    }
+    query.push(0);
+    query.extend_from_slice(&record_type.to_be_bytes());
+    query.extend_from_slice(&1u16.to_be_bytes());
+    query
+}
+
+// The reply's rcode and its answer and additional records, as their type
+// and data, with names and addresses written out
+struct Reply {
+    rcode: u8,
+    answers: Vec<(u16, String)>,
+    additional: Vec<(u16, String)>,
+}
+
+fn read_name(message: &[u8], mut offset: usize) -> (String, usize) {
+    let mut labels = Vec::new();
+    let mut end = None;
+    loop {
+        let length = message[offset] as usize;
+        if length & 0xc0 == 0xc0 {
+            end.get_or_insert(offset + 2);
+            offset = ((length & 0x3f) << 8) | message[offset + 1] as usize;
+            continue;
+        }
+        offset += 1;
+        if length == 0 {
+            break;
+        }
+        labels.push(String::from_utf8_lossy(&message[offset..offset + length]).into_owned());
+        offset += length;
+    }
+    (labels.join("."), end.unwrap_or(offset))
+}
+
+fn parse(message: &[u8]) -> Reply {
+    assert_eq!(message[..2], [0x12, 0x34]);
+    let count = |at: usize| u16::from_be_bytes([message[at], message[at + 1]]) as usize;
+    let (_, mut offset) = read_name(message, 12);
+    offset += 4;
+    let mut records = Vec::new();
+    for _ in 0..count(6) + count(10) {
+        let (name, end) = read_name(message, offset);
+        let record_type = count(end) as u16;
+        let length = count(end + 8);
+        let data = end + 10;
+        let value = match record_type {
+            A => message[data..data + 4].iter().map(u8::to_string).collect::<Vec<_>>().join("."),
+            CNAME => read_name(message, data).0,
+            SRV => format!("{} {} {} {}", count(data), count(data + 2), count(data + 4), read_name(message, data + 6).0),
+            _ => String::new(),
+        };
+        records.push((record_type, format!("{} {}", name, value)));
+        offset = data + length;
+    }
+    let additional = records.split_off(count(6));
+    Reply { rcode: message[3] & 0x0f, answers: records, additional }
+}
+
+fn ask(zone: &Zone, name: &str, record_type: u16) -> Reply {
+    match zone.answer(&query(name, record_type)) {
+        Some(Answer::Reply(reply)) => parse(&reply),
+        answer => panic!("{} wasn't answered: {:?}", name, answer),
+    }
+}
+
+fn service(name: &str, spec: Value) -> Value {
+    json!({ "metadata": { "namespace": "default", "name": name }, "spec": spec })
+}
+
+fn zone() -> Zone {
+    let services = [
+        service("web", json!({ "clusterIP": "10.96.0.20", "ports": [{ "name": "http", "port": 80 }, { "port": 81 }] })),
+        service("db", json!({ "clusterIP": "None", "ports": [{ "name": "pg", "port": 5432 }] })),
+        service("search", json!({ "type": "ExternalName", "externalName": "search.example.com" })),
+    ];
+    let endpoints = [json!({
+        "metadata": { "namespace": "default", "name": "db" },
+        "subsets": [{
+            "addresses": [{ "ip": "10.244.0.5", "hostname": "db-0" }, { "ip": "10.244.0.6" }],
+            "ports": [{ "name": "pg", "port": 5432, "protocol": "TCP" }]
+        }]
+    })];
+    Zone::new("cluster.local", &services, &endpoints)
+}
+
+#[test]
+fn test_services_resolve_to_cluster_ips_and_named_ports() {
+    let zone = zone();
+    let reply = ask(&zone, "web.default.svc.cluster.local", A);
+    assert_eq!(reply.rcode, 0);
+    assert_eq!(reply.answers, [(A, "web.default.svc.cluster.local 10.96.0.20".to_string())]);
+    // Names are matched whatever their case
+    assert_eq!(ask(&zone, "WEB.Default.svc.cluster.local", A).answers.len(), 1);
+
+    let reply = ask(&zone, "_http._tcp.web.default.svc.cluster.local", SRV);
+    assert_eq!(reply.answers, [(SRV, "_http._tcp.web.default.svc.cluster.local 0 100 80 web.default.svc.cluster.local".to_string())]);
+    assert_eq!(reply.additional, [(A, "web.default.svc.cluster.local 10.96.0.20".to_string())]);
+
+    // The service exists without SRV records, and the namespace with none
+    let reply = ask(&zone, "web.default.svc.cluster.local", SRV);
+    assert_eq!((reply.rcode, reply.answers.len()), (0, 0));
+    assert_eq!(ask(&zone, "default.svc.cluster.local", A).rcode, 0);
+    assert_eq!(ask(&zone, "missing.default.svc.cluster.local", A).rcode, 3);
+    assert_eq!(ask(&zone, "web.other.svc.cluster.local", A).rcode, 3);
+}
+
+#[test]
+fn test_headless_services_resolve_to_their_endpoints() {
+    let zone = zone();
+    let reply = ask(&zone, "db.default.svc.cluster.local", A);
+    assert_eq!(
+        reply.answers,
+        [(A, "db.default.svc.cluster.local 10.244.0.5".to_string()), (A, "db.default.svc.cluster.local 10.244.0.6".to_string())]
+    );
+    // Endpoints are named by their hostname, or else their dashed IP
+    let reply = ask(&zone, "db-0.db.default.svc.cluster.local", A);
+    assert_eq!(reply.answers, [(A, "db-0.db.default.svc.cluster.local 10.244.0.5".to_string())]);
+    let reply = ask(&zone, "10-244-0-6.db.default.svc.cluster.local", A);
+    assert_eq!(reply.answers, [(A, "10-244-0-6.db.default.svc.cluster.local 10.244.0.6".to_string())]);
+
+    let reply = ask(&zone, "_pg._tcp.db.default.svc.cluster.local", SRV);
+    assert_eq!(
+        reply.answers,
+        [
+            (SRV, "_pg._tcp.db.default.svc.cluster.local 0 50 5432 db-0.db.default.svc.cluster.local".to_string()),
+            (SRV, "_pg._tcp.db.default.svc.cluster.local 0 50 5432 10-244-0-6.db.default.svc.cluster.local".to_string()),
+        ]
+    );
+    assert_eq!(reply.additional.len(), 2);
+}
+
+#[test]
+fn test_external_names_pods_and_other_domains() {
+    let zone = zone();
+    let reply = ask(&zone, "search.default.svc.cluster.local", A);
+    assert_eq!(reply.answers, [(CNAME, "search.default.svc.cluster.local search.example.com".to_string())]);
+
+    let reply = ask(&zone, "10-244-0-9.default.pod.cluster.local", A);
+    assert_eq!(reply.answers, [(A, "10-244-0-9.default.pod.cluster.local 10.244.0.9".to_string())]);
+    assert_eq!(ask(&zone, "web.default.pod.cluster.local", A).rcode, 3);
+
+    assert_eq!(zone.answer(&query("example.com", A)), Some(Answer::Forward));
+    assert_eq!(zone.answer(&[0; 4]), None);
+}
+
+#[test]
+fn test_long_udp_replies_are_truncated() {
+    let services: Vec<Value> = (0..40)
+        .map(|i| service("many", json!({ "clusterIPs": [format!("10.96.1.{}", i)] })))
+        .collect();
+    let zone = Zone::new("cluster.local", &services, &[]);
+    let query = query("many.default.svc.cluster.local", A);
+    let Some(Answer::Reply(reply)) = zone.answer(&query) else {
+        panic!("no reply");
+    };
+    assert_eq!(parse(&reply).answers.len(), 40);
+    assert_eq!(udp_limit(&query), 512);
+    let truncated = truncate(reply, udp_limit(&query));
+    assert_eq!(truncated[2] & 0x02, 0x02);
+    assert!(parse(&truncated).answers.is_empty());
+}
+
+#[tokio::test]
+async fn test_server_answers_for_services_created() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/services"))
+        .json(&json!({
+            "apiVersion": "v1",
+            "kind": "Service",
+            "metadata": { "name": "web" },
+            "spec": { "selector": { "app": "web" }, "ports": [{ "port": 80 }] }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let service: Value = resp.json().await.unwrap();
+    let cluster_ip = service["spec"]["clusterIP"].as_str().unwrap().to_string();
+
+    let mut config = Config::default();
+    config.dns.server = DnsServerConfig { enabled: true, address: Some("127.0.0.1:0".parse().unwrap()) };
+    let dns = DnsServer::bind(server.storage.clone(), &config.dns).await.unwrap();
+    let address = dns.local_addr();
+    // resolv.conf can't name a port other than 53
+    assert_eq!(dns.nameserver(), None);
+    tokio::spawn(async move { dns.run().await });
+
+    let socket = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    socket.connect(address).await.unwrap();
+    let mut answers = Vec::new();
+    for _ in 0..50 {
+        socket.send(&query("web.default.svc.cluster.local", A)).await.unwrap();
+        let mut buf = vec![0; 512];
+        let length = tokio::time::timeout(Duration::from_secs(1), socket.recv(&mut buf)).await.unwrap().unwrap();
+        answers = parse(&buf[..length]).answers;
+        if !answers.is_empty() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(answers, [(A, format!("web.default.svc.cluster.local {}", cluster_ip))]);
+
+    // And over TCP
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let mut stream = tokio::net::TcpStream::connect(address).await.unwrap();
+    let query = query("web.default.svc.cluster.local", A);
+    stream.write_u16(query.len() as u16).await.unwrap();
+    stream.write_all(&query).await.unwrap();
+    let mut reply = vec![0; stream.read_u16().await.unwrap() as usize];
+    stream.read_exact(&mut reply).await.unwrap();
+    assert_eq!(parse(&reply).answers, answers);
+}
+
+#[test]
+fn test_server_can_be_turned_off() {
+    assert!(Config::default().dns.server.enabled);
+    let config = Config::parse("dns:\n  server:\n    enabled: false\n    address: 127.0.0.1:5353\n").unwrap();
+    assert!(!config.dns.server.enabled);
+    assert_eq!(config.dns.server.address, Some("127.0.0.1:5353".parse().unwrap()));
+}
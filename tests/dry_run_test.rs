@@ -0,0 +1,92 @@
+use serde_json::{json, Value};
+
+mod common;
+
+#[tokio::test]
+async fn test_dry_run_writes_are_answered_but_not_kept() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pods = server.url("/api/v1/namespaces/default/pods");
+
+    let resp = client.post(format!("{}?dryRun=All", pods)).json(&common::pod("dry", json!({}))).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let created: Value = resp.json().await.unwrap();
+    assert_eq!(created["metadata"]["name"], "dry");
+    assert!(created["metadata"]["uid"].is_string());
+    let resp = client.get(format!("{}/dry", pods)).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+
+    let resp = client.post(&pods).json(&common::with_metadata(common::pod("web", json!({})), json!({ "labels": { "app": "web" } }))).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let mut stored: Value = resp.json().await.unwrap();
+
+    stored["metadata"]["labels"]["app"] = json!("api");
+    let resp = client.put(format!("{}/web?dryRun=All", pods)).json(&stored).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let updated: Value = resp.json().await.unwrap();
+    assert_eq!(updated["metadata"]["labels"]["app"], "api");
+
+    let resp = client
+        .patch(format!("{}/web?dryRun=All", pods))
+        .header("Content-Type", "application/merge-patch+json")
+        .json(&json!({ "metadata": { "labels": { "tier": "front" } } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let patched: Value = resp.json().await.unwrap();
+    assert_eq!(patched["metadata"]["labels"]["tier"], "front");
+
+    let resp = client.delete(format!("{}/web?dryRun=All", pods)).send().await.unwrap();
+    assert!(resp.status().is_success());
+
+    let resp = client.get(format!("{}/web", pods)).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let unchanged: Value = resp.json().await.unwrap();
+    assert_eq!(unchanged["metadata"]["labels"], json!({ "app": "web" }));
+    assert_eq!(unchanged["metadata"]["resourceVersion"], stored["metadata"]["resourceVersion"]);
+}
+
+#[tokio::test]
+async fn test_unsupported_dry_run_is_refused() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/pods?dryRun=Server"))
+        .json(&common::pod("web", json!({})))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["message"], "Unsupported value: \"Server\": supported values: \"All\"");
+    let resp = client.get(server.url("/api/v1/namespaces/default/pods/web")).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_dry_run_delete_options_in_the_body() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pod = server.url("/api/v1/namespaces/default/pods/web");
+
+    let resp = client.post(server.url("/api/v1/namespaces/default/pods")).json(&common::pod("web", json!({}))).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let stored: Value = resp.json().await.unwrap();
+
+    let resp = client.delete(&pod).json(&json!({ "kind": "DeleteOptions", "apiVersion": "v1", "dryRun": ["All"] })).send().await.unwrap();
+    assert!(resp.status().is_success());
+    let resp = client.get(&pod).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let unchanged: Value = resp.json().await.unwrap();
+    assert!(unchanged["metadata"]["deletionTimestamp"].is_null());
+    assert_eq!(unchanged["metadata"]["resourceVersion"], stored["metadata"]["resourceVersion"]);
+
+    let resp = client.delete(&pod).json(&json!({ "dryRun": ["Server"] })).send().await.unwrap();
+    assert_eq!(resp.status(), 400);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["message"], "Unsupported value: \"Server\": supported values: \"All\"");
+    let resp = client.get(&pod).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+}
@@ -2,17 +2,14 @@ use reqwest;
 use serde_json::json;
 use uuid::Uuid;
 
+mod common;
+
 #[tokio::test]
 async fn test_namespace_edge_cases() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
+    let base_url = server.url("/api/v1");
     
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
-
     // Test 1: Create namespace with empty name (should fail)
     let ns = json!({
         "apiVersion": "v1",
@@ -170,15 +167,10 @@ async fn test_namespace_edge_cases() {
 
 #[tokio::test]
 async fn test_pod_edge_cases() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
+    let base_url = server.url("/api/v1");
     
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
-
     // Ensure test namespace exists
     let ns_name = format!("test-pod-edge-{}", Uuid::new_v4());
     let ns = json!({
@@ -371,15 +363,10 @@ async fn test_pod_edge_cases() {
 
 #[tokio::test]
 async fn test_service_edge_cases() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
+    let base_url = server.url("/api/v1");
     
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
-
     // Ensure test namespace exists
     let ns_name = format!("test-svc-edge-{}", Uuid::new_v4());
     let ns = json!({
@@ -596,15 +583,10 @@ async fn test_service_edge_cases() {
 
 #[tokio::test]
 async fn test_concurrent_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
+    let base_url = server.url("/api/v1");
     
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
-
     // Test concurrent namespace creation
     let ns_base = format!("test-concurrent-{}", Uuid::new_v4());
     let mut handles = vec![];
@@ -735,15 +717,10 @@ async fn test_concurrent_operations() {
 
 #[tokio::test]
 async fn test_resource_validation() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
+    let base_url = server.url("/api/v1");
     
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
-
     // Test 1: Wrong API version
     let ns = json!({
         "apiVersion": "v2",  // Wrong version
@@ -874,15 +851,10 @@ async fn test_resource_validation() {
 
 #[tokio::test]
 async fn test_configmap_edge_cases() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
+    let base_url = server.url("/api/v1");
     
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
-
     // Ensure test namespace exists
     let ns_name = format!("test-cm-edge-{}", Uuid::new_v4());
     let ns = json!({
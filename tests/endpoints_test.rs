@@ -2,6 +2,8 @@ use std::process::Command;
 use std::thread;
 use std::time::Duration;
 
+mod common;
+
 #[test]
 #[ignore] // Run with: cargo test --test endpoints_test -- --ignored --nocapture
 fn test_endpoints_automatic_creation() {
@@ -15,10 +17,7 @@ fn test_endpoints_automatic_creation() {
     
     thread::sleep(Duration::from_secs(1));
     
-    Command::new("rm")
-        .args(&["-f", "krust.db"])
-        .output()
-        .ok();
+    common::reset_data_dir();
     
     // Start server
     println!("Starting Krust server...");
@@ -118,10 +117,7 @@ fn test_endpoints_pod_selection() {
     
     thread::sleep(Duration::from_secs(1));
     
-    Command::new("rm")
-        .args(&["-f", "krust.db"])
-        .output()
-        .ok();
+    common::reset_data_dir();
     
     // Start server
     let mut server = Command::new("./target/release/krust")
@@ -313,10 +309,7 @@ fn test_endpoints_dynamic_update() {
     
     thread::sleep(Duration::from_secs(1));
     
-    Command::new("rm")
-        .args(&["-f", "krust.db"])
-        .output()
-        .ok();
+    common::reset_data_dir();
     
     // Start server
     let mut server = Command::new("./target/release/krust")
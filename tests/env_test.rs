@@ -0,0 +1,127 @@
+use krust::models::quantity::Resources;
+use krust::runtime::env;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+mod common;
+
+const ALLOCATABLE: Resources = Resources { cpu_millis: 4000, memory_bytes: 8 * 1024 * 1024 * 1024 };
+
+fn pod(containers: Value) -> Value {
+    json!({
+        "metadata": {
+            "name": "web",
+            "namespace": "default",
+            "uid": "web-uid",
+            "labels": { "app": "web", "tier": "front" }
+        },
+        "spec": { "nodeName": "krust-node", "serviceAccountName": "web", "containers": containers },
+        "status": { "podIP": "10.244.0.7", "podIPs": [{ "ip": "10.244.0.7" }], "hostIP": "192.168.1.2" }
+    })
+}
+
+#[tokio::test]
+async fn test_env_is_resolved_from_every_source() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let base_url = server.url("/api/v1/namespaces/default");
+    common::create(&client, &format!("{}/configmaps", base_url), json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": { "name": "web-config" },
+        "data": { "MODE": "production", "LOG_LEVEL": "info", "not a name": "skipped" }
+    })).await;
+    common::create(&client, &format!("{}/secrets", base_url), json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": { "name": "web-db" },
+        "data": { "password": "czNjcjN0", "user": "d2Vi" }
+    })).await;
+
+    let container = json!({
+        "name": "web",
+        "image": "nginx",
+        "resources": { "limits": { "memory": "256Mi" }, "requests": { "cpu": "250m" } },
+        "envFrom": [
+            { "configMapRef": { "name": "web-config" } },
+            { "secretRef": { "name": "web-db" }, "prefix": "DB_" },
+            { "configMapRef": { "name": "absent", "optional": true } }
+        ],
+        "env": [
+            { "name": "LOG_LEVEL", "value": "debug" },
+            { "name": "PASSWORD", "valueFrom": { "secretKeyRef": { "name": "web-db", "key": "password" } } },
+            { "name": "MODE_COPY", "valueFrom": { "configMapKeyRef": { "name": "web-config", "key": "MODE" } } },
+            { "name": "EXTRA", "valueFrom": { "configMapKeyRef": { "name": "web-config", "key": "absent", "optional": true } } },
+            { "name": "POD_NAME", "valueFrom": { "fieldRef": { "fieldPath": "metadata.name" } } },
+            { "name": "POD_IP", "valueFrom": { "fieldRef": { "fieldPath": "status.podIP" } } },
+            { "name": "NODE", "valueFrom": { "fieldRef": { "fieldPath": "spec.nodeName" } } },
+            { "name": "APP", "valueFrom": { "fieldRef": { "fieldPath": "metadata.labels['app']" } } },
+            { "name": "MEMORY_MI", "valueFrom": { "resourceFieldRef": { "resource": "limits.memory", "divisor": "1Mi" } } },
+            { "name": "CPU_LIMIT", "valueFrom": { "resourceFieldRef": { "resource": "limits.cpu" } } },
+            { "name": "CPU_REQUEST_M", "valueFrom": { "resourceFieldRef": { "resource": "requests.cpu", "divisor": "1m" } } },
+            { "name": "URL", "value": "http://$(POD_IP):8080/$(MODE)?user=$(DB_user)&$(UNDEFINED)&$$(POD_IP)" }
+        ]
+    });
+    let resolved = env::resolve(&server.storage, &pod(json!([container.clone()])), &container, &ALLOCATABLE).await.unwrap();
+    let vars: Vec<(&str, &str)> = resolved.iter().map(|(n, v)| (n.as_str(), v.as_str())).collect();
+    assert_eq!(vars, vec![
+        ("LOG_LEVEL", "debug"),
+        ("MODE", "production"),
+        ("DB_password", "s3cr3t"),
+        ("DB_user", "web"),
+        ("PASSWORD", "s3cr3t"),
+        ("MODE_COPY", "production"),
+        ("POD_NAME", "web"),
+        ("POD_IP", "10.244.0.7"),
+        ("NODE", "krust-node"),
+        ("APP", "web"),
+        ("MEMORY_MI", "256"),
+        ("CPU_LIMIT", "4"),
+        ("CPU_REQUEST_M", "250"),
+        ("URL", "http://10.244.0.7:8080/production?user=web&$(UNDEFINED)&$(POD_IP)"),
+    ]);
+}
+
+#[tokio::test]
+async fn test_missing_env_sources_fail() {
+    let server = common::TestServer::start().await;
+    let container = |env: Value| json!({ "name": "web", "image": "nginx", "env": [env] });
+
+    // A ConfigMap, Secret or key that isn't there fails the container unless
+    // it's optional, as does a field the downward API doesn't have
+    for source in [
+        json!({ "configMapKeyRef": { "name": "absent", "key": "MODE" } }),
+        json!({ "secretKeyRef": { "name": "absent", "key": "password" } }),
+        json!({ "fieldRef": { "fieldPath": "metadata.generation" } }),
+        json!({ "resourceFieldRef": { "resource": "limits.gpu" } }),
+    ] {
+        let container = container(json!({ "name": "VALUE", "valueFrom": source }));
+        let result = env::resolve(&server.storage, &pod(json!([container.clone()])), &container, &ALLOCATABLE).await;
+        assert!(result.is_err(), "{} should fail", source);
+    }
+    let container = json!({ "name": "web", "envFrom": [{ "secretRef": { "name": "absent" } }] });
+    assert!(env::resolve(&server.storage, &pod(json!([container.clone()])), &container, &ALLOCATABLE).await.is_err());
+}
+
+#[tokio::test]
+async fn test_env_sources_are_supported() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "configured" },
+        "spec": {
+            "containers": [{
+                "name": "app",
+                "image": "nginx",
+                "envFrom": [{ "configMapRef": { "name": "app-config" } }],
+                "env": [{ "name": "POD_IP", "valueFrom": { "fieldRef": { "fieldPath": "status.podIP" } } }]
+            }]
+        }
+    });
+    let response = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Value = response.json().await.unwrap();
+    assert!(created["metadata"]["annotations"]["krust.io/unsupported-fields"].is_null());
+}
@@ -0,0 +1,111 @@
+use reqwest;
+use serde_json::{json, Value};
+use sqlx::Row;
+use std::time::Duration;
+
+mod common;
+
+const CLUSTER: &str = "nodes:\n  edge-1: {}\n";
+
+// A pod on the simulated node, using the storage the annotation describes
+fn on_edge(name: &str, usage: &str, mut spec: Value) -> Value {
+    spec["nodeSelector"] = json!({ "kubernetes.io/hostname": "edge-1" });
+    common::with_metadata(common::pod(name, spec), json!({ "annotations": { "krust.io/fake-ephemeral-storage": usage } }))
+}
+
+async fn wait_for_phase(client: &reqwest::Client, server: &common::TestServer, name: &str, phase: &str) -> Value {
+    for _ in 0..100 {
+        let pod: Value = client
+            .get(server.url(&format!("/api/v1/namespaces/default/pods/{}", name)))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if pod["status"]["phase"] == phase {
+            return pod;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("pod {} never reached {}", name, phase);
+}
+
+#[tokio::test]
+async fn test_pods_over_their_ephemeral_storage_limits_are_evicted() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    // An emptyDir past its sizeLimit
+    let scratch = json!({
+        "containers": [{ "name": "app", "image": "busybox:1.35" }],
+        "volumes": [{ "name": "cache", "emptyDir": { "sizeLimit": "10Mi" } }]
+    });
+    common::create_pod(&client, &server, on_edge("scratch", "cache=20Mi", scratch)).await;
+    let evicted = wait_for_phase(&client, &server, "scratch", "Failed").await;
+    assert_eq!(evicted["status"]["reason"], "Evicted");
+    assert_eq!(evicted["status"]["message"], "Usage of EmptyDir volume \"cache\" exceeds the limit \"10Mi\". ");
+    let conditions = evicted["status"]["conditions"].as_array().unwrap();
+    let disruption = conditions.iter().find(|c| c["type"] == "DisruptionTarget").unwrap();
+    assert_eq!(disruption["reason"], "TerminationByKubelet");
+    let ready = conditions.iter().find(|c| c["type"] == "Ready").unwrap();
+    assert_eq!(ready["status"], "False");
+
+    let events = sqlx::query("SELECT type FROM events WHERE reason = 'Evicted' AND involved_object_name = 'scratch'")
+        .fetch_all(&*server.storage.pool)
+        .await
+        .unwrap();
+    assert_eq!(events.len(), 1);
+    assert_eq!(events[0].get::<String, _>("type"), "Warning");
+
+    // A container past its own limit
+    let limited = json!({
+        "containers": [
+            { "name": "app", "image": "busybox:1.35", "resources": { "limits": { "ephemeral-storage": "1Gi" } } },
+            { "name": "sidecar", "image": "busybox:1.35" }
+        ]
+    });
+    common::create_pod(&client, &server, on_edge("limited", "app=2Gi,sidecar=5Gi", limited)).await;
+    let evicted = wait_for_phase(&client, &server, "limited", "Failed").await;
+    assert_eq!(evicted["status"]["reason"], "Evicted");
+    assert_eq!(evicted["status"]["message"], "Container app exceeded its local ephemeral storage limit \"1Gi\". ");
+
+    // Memory-backed emptyDirs don't count toward the pod's disk usage
+    let within = json!({
+        "containers": [{ "name": "app", "image": "busybox:1.35", "resources": { "limits": { "ephemeral-storage": "100Mi" } } }],
+        "volumes": [{ "name": "shm", "emptyDir": { "medium": "Memory" } }, { "name": "data", "emptyDir": {} }]
+    });
+    common::create_pod(&client, &server, on_edge("within", "app=30Mi,shm=500Mi,data=20Mi", within)).await;
+    server.wait_for_pod_running("default", "within").await;
+
+    // The node's stats summary shows what it uses once the kubelet has
+    // accounted for it
+    let mut summary = Value::Null;
+    for _ in 0..100 {
+        summary = client
+            .get(server.url("/api/v1/nodes/edge-1/proxy/stats/summary"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        if summary["pods"][0]["ephemeral-storage"]["usedBytes"] != 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(summary["node"]["nodeName"], "edge-1");
+    let pods = summary["pods"].as_array().unwrap();
+    assert_eq!(pods.len(), 1);
+    assert_eq!(pods[0]["podRef"]["name"], "within");
+    assert_eq!(pods[0]["ephemeral-storage"]["usedBytes"], 50 << 20);
+    assert_eq!(pods[0]["containers"][0]["rootfs"]["usedBytes"], 30 << 20);
+    let data = pods[0]["volume"].as_array().unwrap().iter().find(|v| v["name"] == "data").unwrap();
+    assert_eq!(data["usedBytes"], 20 << 20);
+    assert_eq!(wait_for_phase(&client, &server, "within", "Running").await["status"]["phase"], "Running");
+
+    let resp = client.get(server.url("/api/v1/nodes/nowhere/proxy/stats/summary")).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
@@ -0,0 +1,178 @@
+use reqwest;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+// Events about the object with the given uid, as `kubectl describe` asks for them
+async fn events_for(client: &reqwest::Client, server: &common::TestServer, namespace: &str, uid: &str) -> Vec<Value> {
+    let url = server.url(&format!("/api/v1/namespaces/{}/events?fieldSelector=involvedObject.uid%3D{}", namespace, uid));
+    let list: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(list["kind"], "EventList");
+    list["items"].as_array().unwrap().clone()
+}
+
+// Polls for up to five seconds until every reason has been reported for `uid`
+async fn wait_for_reasons(client: &reqwest::Client, server: &common::TestServer, uid: &str, reasons: &[&str]) -> Vec<Value> {
+    let mut events = Vec::new();
+    for _ in 0..50 {
+        events = events_for(client, server, "default", uid).await;
+        if reasons.iter().all(|reason| events.iter().any(|e| e["reason"] == *reason)) {
+            return events;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("expected events {:?} for {}, got {:#?}", reasons, uid, events);
+}
+
+#[tokio::test]
+async fn test_event_crud() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let event = json!({
+        "apiVersion": "v1",
+        "kind": "Event",
+        "metadata": { "name": "web.17a2b3c4" },
+        "involvedObject": { "kind": "Pod", "namespace": "default", "name": "web", "uid": "1234" },
+        "reason": "BackOff",
+        "message": "Back-off restarting failed container",
+        "type": "Warning",
+        "source": { "component": "kubelet", "host": "node-1" },
+        "count": 1
+    });
+    let resp = client.post(server.url("/api/v1/namespaces/default/events")).json(&event).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let created: Value = resp.json().await.unwrap();
+    assert_eq!(created["metadata"]["namespace"], "default");
+    assert_eq!(created["involvedObject"]["name"], "web");
+    assert_eq!(created["source"]["component"], "kubelet");
+    assert!(created["metadata"]["resourceVersion"].is_string());
+
+    let resp = client.post(server.url("/api/v1/namespaces/default/events")).json(&event).send().await.unwrap();
+    assert_eq!(resp.status(), 409);
+
+    let resp = client.get(server.url("/api/v1/namespaces/default/events/web.17a2b3c4")).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let fetched: Value = resp.json().await.unwrap();
+    assert_eq!(fetched["reason"], "BackOff");
+    assert_eq!(fetched["type"], "Warning");
+
+    let url = server.url("/api/v1/events?fieldSelector=involvedObject.name%3Dweb,involvedObject.kind%3DPod");
+    let list: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(list["items"].as_array().unwrap().len(), 1);
+
+    let url = server.url("/api/v1/namespaces/default/events?fieldSelector=involvedObject.name%3Dother");
+    let list: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert!(list["items"].as_array().unwrap().is_empty());
+
+    let resp = client.delete(server.url("/api/v1/namespaces/default/events/web.17a2b3c4")).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client.get(server.url("/api/v1/namespaces/default/events/web.17a2b3c4")).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_event_requires_name() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let event = json!({ "apiVersion": "v1", "kind": "Event", "metadata": {}, "reason": "Test" });
+    let resp = client.post(server.url("/api/v1/namespaces/default/events")).json(&event).send().await.unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_pod_lifecycle_events() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "web" },
+        "spec": { "containers": [{ "name": "nginx", "image": "nginx:1.25" }] }
+    });
+    let resp = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let created: Value = resp.json().await.unwrap();
+    let uid = created["metadata"]["uid"].as_str().unwrap();
+    server.wait_for_pod_running("default", "web").await;
+
+    let events = wait_for_reasons(&client, &server, uid, &["Scheduled", "Pulled", "Created", "Started"]).await;
+    let scheduled = events.iter().find(|e| e["reason"] == "Scheduled").unwrap();
+    assert_eq!(scheduled["type"], "Normal");
+    assert_eq!(scheduled["source"]["component"], "default-scheduler");
+    assert!(scheduled["message"].as_str().unwrap().starts_with("Successfully assigned default/web to "));
+
+    let started = events.iter().find(|e| e["reason"] == "Started").unwrap();
+    assert_eq!(started["source"]["component"], "kubelet");
+    assert_eq!(started["involvedObject"]["fieldPath"], "spec.containers{nginx}");
+    assert_eq!(started["message"], "Started container nginx");
+}
+
+#[tokio::test]
+async fn test_failed_scheduling_event() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "stuck" },
+        "spec": {
+            "nodeSelector": { "disktype": "nonexistent" },
+            "containers": [{ "name": "app", "image": "nginx" }]
+        }
+    });
+    let resp = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod).send().await.unwrap();
+    let created: Value = resp.json().await.unwrap();
+    let uid = created["metadata"]["uid"].as_str().unwrap();
+
+    let events = wait_for_reasons(&client, &server, uid, &["FailedScheduling"]).await;
+    let failed: Vec<&Value> = events.iter().filter(|e| e["reason"] == "FailedScheduling").collect();
+    assert_eq!(failed.len(), 1, "repeated failures are folded into one event");
+    assert_eq!(failed[0]["type"], "Warning");
+    assert!(failed[0]["message"].as_str().unwrap().starts_with("0/"));
+}
+
+#[tokio::test]
+async fn test_controller_events() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let deployment = json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": "web" },
+        "spec": {
+            "replicas": 2,
+            "selector": { "matchLabels": { "app": "web" } },
+            "template": {
+                "metadata": { "labels": { "app": "web" } },
+                "spec": { "containers": [{ "name": "nginx", "image": "nginx" }] }
+            }
+        }
+    });
+    let resp = client.post(server.url("/apis/apps/v1/namespaces/default/deployments")).json(&deployment).send().await.unwrap();
+    let created: Value = resp.json().await.unwrap();
+    let uid = created["metadata"]["uid"].as_str().unwrap();
+
+    let events = wait_for_reasons(&client, &server, uid, &["ScalingReplicaSet"]).await;
+    let scaling = events.iter().find(|e| e["reason"] == "ScalingReplicaSet").unwrap();
+    assert_eq!(scaling["source"]["component"], "deployment-controller");
+    assert!(scaling["message"].as_str().unwrap().ends_with(" to 2"));
+
+    let url = server.url("/api/v1/namespaces/default/events?fieldSelector=involvedObject.kind%3DReplicaSet,reason%3DSuccessfulCreate");
+    for _ in 0..50 {
+        let list: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+        let items = list["items"].as_array().unwrap();
+        if items.len() == 2 {
+            assert_eq!(items[0]["source"]["component"], "replicaset-controller");
+            assert!(items[0]["message"].as_str().unwrap().starts_with("Created pod: web-"));
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the ReplicaSet never reported creating its pods");
+}
@@ -0,0 +1,197 @@
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+fn eviction(name: &str, delete_options: Value) -> Value {
+    json!({
+        "apiVersion": "policy/v1",
+        "kind": "Eviction",
+        "metadata": { "name": name, "namespace": "default" },
+        "deleteOptions": delete_options
+    })
+}
+
+async fn evict(server: &common::TestServer, client: &reqwest::Client, name: &str, delete_options: Value) -> reqwest::Response {
+    client
+        .post(server.url(&format!("/api/v1/namespaces/default/pods/{}/eviction", name)))
+        .json(&eviction(name, delete_options))
+        .send()
+        .await
+        .unwrap()
+}
+
+fn web_pod(name: &str) -> Value {
+    common::with_metadata(common::pod(name, json!({})), json!({ "labels": { "app": "web" } }))
+}
+
+async fn create_pdb(server: &common::TestServer, client: &reqwest::Client, spec: Value) {
+    let mut spec = spec;
+    spec["selector"] = json!({ "matchLabels": { "app": "web" } });
+    let resp = client
+        .post(server.url("/apis/policy/v1/namespaces/default/poddisruptionbudgets"))
+        .json(&json!({
+            "apiVersion": "policy/v1",
+            "kind": "PodDisruptionBudget",
+            "metadata": { "name": "web" },
+            "spec": spec
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+}
+
+// Waits for the disruption controller to give the budget `expected` status
+// fields, returning its status
+async fn wait_for_budget(server: &common::TestServer, client: &reqwest::Client, expected: Value) -> Value {
+    let mut status = Value::Null;
+    for _ in 0..50 {
+        let pdb: Value = client
+            .get(server.url("/apis/policy/v1/namespaces/default/poddisruptionbudgets/web"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        status = pdb["status"].clone();
+        if expected.as_object().unwrap().iter().all(|(field, value)| status[field] == *value) {
+            return status;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    panic!("budget never reached {}, status is {}", expected, status);
+}
+
+#[tokio::test]
+async fn test_eviction_refused_once_budget_is_spent() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    for name in ["web-1", "web-2"] {
+        common::create_pod(&client, &server, web_pod(name)).await;
+        server.wait_for_pod_running("default", name).await;
+    }
+    create_pdb(&server, &client, json!({ "minAvailable": 1 })).await;
+    let status = wait_for_budget(
+        &server,
+        &client,
+        json!({ "currentHealthy": 2, "desiredHealthy": 1, "disruptionsAllowed": 1, "expectedPods": 2 }),
+    )
+    .await;
+    assert_eq!(status["conditions"][0]["type"], "DisruptionAllowed");
+    assert_eq!(status["conditions"][0]["status"], "True");
+
+    // A dry run leaves both the pod and the budget be
+    let resp = evict(&server, &client, "web-1", json!({ "dryRun": ["All"] })).await;
+    assert_eq!(resp.status(), 201);
+    let resp = client.get(server.url("/api/v1/namespaces/default/pods/web-1")).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let pdb: Value = client
+        .get(server.url("/apis/policy/v1/namespaces/default/poddisruptionbudgets/web"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(pdb["status"]["disruptionsAllowed"], 1);
+    assert!(pdb["status"]["disruptedPods"].is_null());
+
+    let resp = evict(&server, &client, "web-1", json!({ "gracePeriodSeconds": 0 })).await;
+    assert_eq!(resp.status(), 201);
+    let resp = client.get(server.url("/api/v1/namespaces/default/pods/web-1")).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+
+    // The eviction is spent at once, before the controller's next pass
+    let resp = evict(&server, &client, "web-2", json!({ "dryRun": ["All"] })).await;
+    assert_eq!(resp.status(), 429);
+    let resp = evict(&server, &client, "web-2", json!({ "gracePeriodSeconds": 0 })).await;
+    assert_eq!(resp.status(), 429);
+    let refusal: Value = resp.json().await.unwrap();
+    assert_eq!(refusal["reason"], "TooManyRequests");
+    assert_eq!(refusal["message"], "Cannot evict pod as it would violate the pod's disruption budget.");
+
+    let status = wait_for_budget(&server, &client, json!({ "currentHealthy": 1, "disruptionsAllowed": 0 })).await;
+    assert!(status["disruptedPods"].is_null(), "{}", status);
+    assert_eq!(status["conditions"][0]["status"], "False");
+    assert_eq!(status["conditions"][0]["reason"], "InsufficientPods");
+    let resp = evict(&server, &client, "web-2", json!(null)).await;
+    assert_eq!(resp.status(), 429);
+    let refusal: Value = resp.json().await.unwrap();
+    assert_eq!(
+        refusal["details"]["causes"],
+        json!([{ "reason": "DisruptionBudget", "message": "The disruption budget web needs 1 healthy pods and has 1 currently" }])
+    );
+    let resp = client.get(server.url("/api/v1/namespaces/default/pods/web-2")).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_budget_percentages_are_of_the_controllers_replicas() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    create_pdb(&server, &client, json!({ "maxUnavailable": "25%" })).await;
+    let resp = client
+        .post(server.url("/apis/apps/v1/namespaces/default/replicasets"))
+        .json(&json!({
+            "apiVersion": "apps/v1",
+            "kind": "ReplicaSet",
+            "metadata": { "name": "web" },
+            "spec": {
+                "replicas": 4,
+                "selector": { "matchLabels": { "app": "web" } },
+                "template": {
+                    "metadata": { "labels": { "app": "web" } },
+                    "spec": { "containers": [{ "name": "app", "image": "nginx" }] }
+                }
+            }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    wait_for_budget(
+        &server,
+        &client,
+        json!({ "currentHealthy": 4, "desiredHealthy": 3, "disruptionsAllowed": 1, "expectedPods": 4 }),
+    )
+    .await;
+}
+
+#[tokio::test]
+async fn test_eviction_without_budget_deletes_pod() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    common::create_pod(&client, &server, web_pod("web")).await;
+    server.wait_for_pod_running("default", "web").await;
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/pods/web/eviction"))
+        .json(&eviction("other", json!(null)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let resp = evict(&server, &client, "missing", json!(null)).await;
+    assert_eq!(resp.status(), 404);
+
+    // The pod gets its grace period, as when deleted
+    let resp = evict(&server, &client, "web", json!(null)).await;
+    assert_eq!(resp.status(), 201);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["status"], "Success");
+    let mut gone = false;
+    for _ in 0..50 {
+        let resp = client.get(server.url("/api/v1/namespaces/default/pods/web")).send().await.unwrap();
+        if resp.status() == 404 {
+            gone = true;
+            break;
+        }
+        let pod: Value = resp.json().await.unwrap();
+        assert!(pod["metadata"]["deletionTimestamp"].is_string(), "{}", pod);
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    assert!(gone, "the evicted pod was never deleted");
+}
@@ -0,0 +1,92 @@
+use reqwest;
+use serde_json::{json, Value};
+
+mod common;
+
+#[tokio::test]
+async fn test_export_strips_server_fields() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/services"))
+        .json(&json!({
+            "apiVersion": "v1",
+            "kind": "Service",
+            "metadata": { "name": "web", "labels": { "app": "web" } },
+            "spec": { "ports": [{ "port": 80 }], "selector": { "app": "web" } }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    let live: Value = client
+        .get(server.url("/api/v1/namespaces/default/services/web"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(live["metadata"]["uid"].is_string());
+    assert!(live["spec"]["clusterIP"].is_string());
+
+    let exported: Value = client
+        .get(server.url("/api/v1/namespaces/default/services/web?export=true"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let metadata = &exported["metadata"];
+    for field in ["uid", "resourceVersion", "creationTimestamp", "managedFields"] {
+        assert!(metadata[field].is_null(), "{} kept: {}", field, exported);
+    }
+    assert!(exported["status"].is_null());
+    assert!(exported["spec"]["clusterIP"].is_null());
+    assert_eq!(metadata["name"], "web");
+    assert_eq!(metadata["namespace"], "default");
+    assert_eq!(metadata["labels"]["app"], "web");
+    assert_eq!(exported["spec"]["ports"][0]["port"], 80);
+
+    // Every item of a list is a manifest of its own
+    let list: Value = client
+        .get(server.url("/api/v1/namespaces/default/services?export=true"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(list["metadata"]["resourceVersion"].is_null());
+    let item = list["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|item| item["metadata"]["name"] == "web")
+        .unwrap();
+    assert_eq!(item["kind"], "Service");
+    assert_eq!(item["apiVersion"], "v1");
+    assert!(item["metadata"]["uid"].is_null());
+
+    // The exported manifest can be created again elsewhere
+    let mut copy = exported.clone();
+    copy["metadata"]["name"] = json!("web-copy");
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/services"))
+        .json(&copy)
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    // Other verbs are unaffected
+    let resp = client
+        .delete(server.url("/api/v1/namespaces/default/services/web-copy?export=true"))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+}
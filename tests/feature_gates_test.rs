@@ -0,0 +1,88 @@
+use reqwest;
+use krust::Config;
+use serde_json::{json, Value};
+
+mod common;
+
+fn args(list: &[&str]) -> Vec<String> {
+    list.iter().map(|arg| arg.to_string()).collect()
+}
+
+fn gate(report: &Value, name: &str) -> Value {
+    report["gates"].as_array().unwrap().iter().find(|g| g["name"] == name).unwrap().clone()
+}
+
+#[tokio::test]
+async fn test_feature_gates_default() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let report: Value = client.get(server.url("/krust/feature-gates")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(report["kind"], "FeatureGateList");
+    assert_eq!(gate(&report, "EphemeralContainers")["enabled"], true);
+    assert_eq!(gate(&report, "HPAScaleToZero"), json!({ "name": "HPAScaleToZero", "enabled": false, "default": false, "stage": "Alpha" }));
+}
+
+#[tokio::test]
+async fn test_feature_gates_switch_features() {
+    let config = Config::parse("featureGates:\n  EphemeralContainers: false\n  HPAScaleToZero: true\n").unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let report: Value = client.get(server.url("/krust/feature-gates")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(gate(&report, "EphemeralContainers")["enabled"], false);
+    assert_eq!(gate(&report, "HPAScaleToZero")["enabled"], true);
+
+    // `kubectl debug` finds no ephemeral containers subresource
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "web" },
+        "spec": { "containers": [{ "name": "app", "image": "nginx:latest" }] }
+    });
+    let resp = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let debug = json!({ "spec": { "ephemeralContainers": [{ "name": "debugger", "image": "busybox" }] } });
+    let resp = client
+        .patch(server.url("/api/v1/namespaces/default/pods/web/ephemeralcontainers"))
+        .header("Content-Type", "application/strategic-merge-patch+json")
+        .body(debug.to_string())
+        .send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+
+    // HPAScaleToZero lets an HPA with an External metric go to zero
+    let hpa = json!({
+        "apiVersion": "autoscaling/v2",
+        "kind": "HorizontalPodAutoscaler",
+        "metadata": { "name": "worker" },
+        "spec": {
+            "scaleTargetRef": { "apiVersion": "apps/v1", "kind": "Deployment", "name": "worker" },
+            "minReplicas": 0,
+            "maxReplicas": 5,
+            "metrics": [{
+                "type": "External",
+                "external": { "metric": { "name": "queue_messages" }, "target": { "type": "AverageValue", "averageValue": "10" } }
+            }]
+        }
+    });
+    let resp = client
+        .post(server.url("/apis/autoscaling/v2/namespaces/default/horizontalpodautoscalers"))
+        .json(&hpa).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+}
+
+#[test]
+fn test_feature_gates_flag() {
+    let config = Config::from_arg_list(args(&["--feature-gates=EphemeralContainers=false,WatchBookmark=false"])).unwrap();
+    assert!(!config.feature_gates.enabled("EphemeralContainers"));
+    assert!(!config.feature_gates.enabled("WatchBookmark"));
+    assert!(!config.feature_gates.enabled("HPAScaleToZero"));
+
+    let config = Config::from_arg_list(args(&["--feature-gates", "HPAScaleToZero=true"])).unwrap();
+    assert!(config.scale_to_zero());
+
+    let unknown = Config::from_arg_list(args(&["--feature-gates=Teleportation=true"])).unwrap_err();
+    assert_eq!(unknown.to_string(), "unrecognized feature gate: Teleportation");
+    assert!(Config::from_arg_list(args(&["--feature-gates=EphemeralContainers=maybe"])).is_err());
+    assert!(Config::parse("featureGates:\n  Teleportation: true\n").is_err());
+}
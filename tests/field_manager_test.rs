@@ -0,0 +1,246 @@
+use reqwest;
+use serde_json::{json, Value};
+
+mod common;
+
+#[tokio::test]
+async fn test_last_modified_by_tracks_each_writer() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let base_url = server.url("/api/v1");
+
+    let configmap = json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": {
+            "name": "contested",
+            "namespace": "default"
+        },
+        "data": {
+            "owner": "first"
+        }
+    });
+
+    // Created by kubectl, identified from its User-Agent
+    let response = client
+        .post(&format!("{}/namespaces/default/configmaps", base_url))
+        .header("User-Agent", "kubectl/v1.29.0 (linux/amd64) kubernetes/abcdef")
+        .json(&configmap)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+
+    // Updated by a controller that names itself with fieldManager
+    let mut updated = configmap.clone();
+    updated["data"]["owner"] = json!("second");
+    let response = client
+        .put(&format!("{}/namespaces/default/configmaps/contested?fieldManager=my-controller", base_url))
+        .header("User-Agent", "my-controller/0.1")
+        .json(&updated)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let report: Value = client
+        .get(server.url("/krust/writers?namespace=default&kind=ConfigMap&name=contested"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(report["kind"], "WriterReport");
+
+    let items = report["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    let object = &items[0];
+    assert_eq!(object["name"], "contested");
+    assert_eq!(object["writes"], 2);
+    assert_eq!(object["lastModifiedBy"]["manager"], "my-controller");
+    assert_eq!(object["lastModifiedBy"]["operation"], "Update");
+    assert_eq!(object["managers"], json!(["kubectl", "my-controller"]));
+}
+
+#[tokio::test]
+async fn test_failed_writes_are_not_recorded() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    // Updating an object that doesn't exist fails and leaves no trace
+    let response = client
+        .put(server.url("/api/v1/namespaces/default/configmaps/missing"))
+        .json(&json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": { "name": "missing", "namespace": "default" }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert!(!response.status().is_success());
+
+    let report: Value = client
+        .get(server.url("/krust/writers?name=missing"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(report["items"].as_array().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_repeated_writes_keep_one_row_per_manager() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/api/v1/namespaces/default/configmaps/busy");
+
+    let configmap = json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": { "name": "busy", "namespace": "default" },
+        "data": { "count": "0" }
+    });
+    let response = client
+        .post(server.url("/api/v1/namespaces/default/configmaps?fieldManager=creator"))
+        .json(&configmap)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+    for count in 1..=5 {
+        let response = client
+            .patch(format!("{}?fieldManager=counter", url))
+            .header("Content-Type", "application/merge-patch+json")
+            .json(&json!({ "data": { "count": count.to_string() } }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response.status(), 200);
+    }
+    let response = client
+        .post(server.url("/api/v1/namespaces?fieldManager=creator"))
+        .json(&json!({ "apiVersion": "v1", "kind": "Namespace", "metadata": { "name": "busy" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+
+    let rows: i64 = sqlx::query_scalar("SELECT count(*) FROM object_writers WHERE name = 'busy'")
+        .fetch_one(server.storage.pool())
+        .await
+        .unwrap();
+    assert_eq!(rows, 3);
+
+    let report: Value = client.get(server.url("/krust/writers?name=busy")).send().await.unwrap().json().await.unwrap();
+    let items = report["items"].as_array().unwrap();
+    assert_eq!(items.len(), 2);
+    assert_eq!(items[0]["kind"], "ConfigMap");
+    assert_eq!(items[0]["writes"], 6);
+    assert_eq!(items[0]["managers"], json!(["creator", "counter"]));
+    assert_eq!(items[0]["lastModifiedBy"]["manager"], "counter");
+    assert_eq!(items[0]["lastModifiedBy"]["operation"], "Patch");
+    assert_eq!(items[1]["kind"], "Namespace");
+    assert!(items[1]["namespace"].is_null());
+}
+
+#[tokio::test]
+async fn test_objects_show_their_writers_in_managed_fields() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/api/v1/namespaces/default/configmaps/shown");
+
+    let response = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .header("User-Agent", "kubectl/v1.29.0 (linux/amd64) kubernetes/abcdef")
+        .json(&json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": { "name": "shown", "namespace": "default" },
+            "data": { "owner": "first" }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+    let created: Value = response.json().await.unwrap();
+    assert_eq!(created["metadata"]["managedFields"][0]["manager"], "kubectl");
+
+    let response = client
+        .patch(format!("{}?fieldManager=my-controller", url))
+        .header("Content-Type", "application/merge-patch+json")
+        .json(&json!({ "data": { "owner": "second" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+
+    let object: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    let managed_fields = object["metadata"]["managedFields"].as_array().unwrap();
+    let managers: Vec<_> = managed_fields.iter().map(|entry| entry["manager"].as_str().unwrap()).collect();
+    assert_eq!(managers, ["kubectl", "my-controller"]);
+    for entry in managed_fields {
+        assert_eq!(entry["operation"], "Update");
+        assert_eq!(entry["apiVersion"], "v1");
+        assert!(entry["time"].is_string());
+    }
+
+    let list: Value = client
+        .get(server.url("/api/v1/namespaces/default/configmaps"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let listed = list["items"].as_array().unwrap().iter().find(|item| item["metadata"]["name"] == "shown").unwrap();
+    assert_eq!(listed["metadata"]["managedFields"], object["metadata"]["managedFields"]);
+}
+
+#[tokio::test]
+async fn test_deleted_objects_take_their_writers_with_them() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/api/v1/namespaces/default/configmaps/reborn");
+    let create = |manager: &'static str| {
+        client
+            .post(server.url(&format!("/api/v1/namespaces/default/configmaps?fieldManager={}", manager)))
+            .json(&json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "reborn" }, "data": {} }))
+            .send()
+    };
+
+    assert_eq!(create("first-life").await.unwrap().status(), 201);
+    let response = client
+        .patch(format!("{}?fieldManager=patcher", url))
+        .header("Content-Type", "application/merge-patch+json")
+        .json(&json!({ "data": { "patched": "true" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    assert_eq!(client.delete(format!("{}?fieldManager=deleter", url)).send().await.unwrap().status(), 200);
+
+    let rows: i64 = sqlx::query_scalar("SELECT count(*) FROM object_writers WHERE name = 'reborn'")
+        .fetch_one(server.storage.pool())
+        .await
+        .unwrap();
+    assert_eq!(rows, 0);
+
+    // Created again under the name, it has only its own writers
+    let response = create("second-life").await.unwrap();
+    assert_eq!(response.status(), 201);
+    let created: Value = response.json().await.unwrap();
+    let managers: Vec<_> =
+        created["metadata"]["managedFields"].as_array().unwrap().iter().map(|entry| entry["manager"].clone()).collect();
+    assert_eq!(managers, vec![json!("second-life")]);
+
+    let report: Value = client.get(server.url("/krust/writers?name=reborn")).send().await.unwrap().json().await.unwrap();
+    let items = report["items"].as_array().unwrap();
+    assert_eq!(items.len(), 1);
+    assert_eq!(items[0]["managers"], json!(["second-life"]));
+    assert_eq!(items[0]["writes"], 1);
+    assert_eq!(items[0]["uid"], created["metadata"]["uid"]);
+}
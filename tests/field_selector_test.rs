@@ -0,0 +1,136 @@
+use reqwest;
+use krust::storage::{FieldSelector, ListSelector};
+use krust::Storage;
+use serde_json::{json, Value};
+
+mod common;
+
+fn names(list: &Value) -> Vec<String> {
+    let mut names: Vec<String> = list["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["metadata"]["name"].as_str().unwrap().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn test_pods_by_phase_and_node() {
+    let storage = Storage::in_memory().await.unwrap();
+    storage.migrate().await.unwrap();
+    let pods = storage.pods();
+
+    for name in ["running", "pending", "unscheduled"] {
+        pods.create("default", common::pod(name, json!({}))).await.unwrap();
+    }
+    pods.create("kube-system", common::pod("elsewhere", json!({}))).await.unwrap();
+    pods.update_status("default", "running", "Running", Some("node-1")).await.unwrap();
+    pods.update_status("default", "pending", "Pending", Some("node-2")).await.unwrap();
+    pods.update_status("kube-system", "elsewhere", "Running", Some("node-1")).await.unwrap();
+
+    let select = |selector: &str| ListSelector::parse(Some(selector), None).unwrap();
+
+    let running = pods.list_matching(None, &select("status.phase=Running")).await.unwrap();
+    assert_eq!(names(&running), ["elsewhere", "running"]);
+
+    let running = pods.list_matching(Some("default"), &select("status.phase==Running")).await.unwrap();
+    assert_eq!(names(&running), ["running"]);
+
+    let on_node = pods.list_matching(None, &select("spec.nodeName=node-1,metadata.namespace=kube-system")).await.unwrap();
+    assert_eq!(names(&on_node), ["elsewhere"]);
+
+    // An empty value matches pods the scheduler hasn't placed yet
+    let unscheduled = pods.list_matching(None, &select("spec.nodeName=")).await.unwrap();
+    assert_eq!(names(&unscheduled), ["unscheduled"]);
+    let scheduled = pods.list_matching(Some("default"), &select("spec.nodeName!=")).await.unwrap();
+    assert_eq!(names(&scheduled), ["pending", "running"]);
+
+    let not_running = pods.list_matching(Some("default"), &select("status.phase!=Running")).await.unwrap();
+    assert_eq!(names(&not_running), ["pending", "unscheduled"]);
+}
+
+#[tokio::test]
+async fn test_field_selectors_over_the_api() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    for name in ["web", "db"] {
+        let service = json!({
+            "apiVersion": "v1",
+            "kind": "Service",
+            "metadata": { "name": name },
+            "spec": { "ports": [{ "port": 80 }], "selector": { "app": name } }
+        });
+        let resp = client.post(server.url("/api/v1/namespaces/default/services")).json(&service).send().await.unwrap();
+        assert!(resp.status().is_success());
+    }
+    for (name, kind) in [("token", "kubernetes.io/service-account-token"), ("plain", "Opaque")] {
+        let mut secret = json!({
+            "apiVersion": "v1",
+            "kind": "Secret",
+            "metadata": { "name": name },
+            "type": kind,
+            "data": {}
+        });
+        if name == "token" {
+            secret["metadata"]["annotations"] = json!({ "kubernetes.io/service-account.name": "default" });
+        }
+        let resp = client.post(server.url("/api/v1/namespaces/default/secrets")).json(&secret).send().await.unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    let list = |path: &str| client.get(server.url(path)).send();
+
+    let resp = list("/api/v1/namespaces/default/services?fieldSelector=metadata.name%3Dweb").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(names(&resp.json().await.unwrap()), ["web"]);
+
+    let resp = list("/api/v1/services?fieldSelector=metadata.name!%3Dweb,metadata.namespace%3Ddefault").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(!names(&resp.json().await.unwrap()).contains(&"web".to_string()));
+
+    let resp = list("/api/v1/namespaces/default/secrets?fieldSelector=type%3DOpaque").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(names(&resp.json().await.unwrap()), ["plain"]);
+
+    let resp = list("/api/v1/namespaces?fieldSelector=metadata.name%3Ddefault").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(names(&resp.json().await.unwrap()), ["default"]);
+
+    let resp = list("/api/v1/nodes?fieldSelector=metadata.name%3Dnowhere").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(names(&resp.json().await.unwrap()).is_empty());
+
+    // Fields a kind can't be selected on, and selectors that don't parse
+    for path in [
+        "/api/v1/namespaces/default/services?fieldSelector=spec.ports%3D80",
+        "/apis/apps/v1/deployments?fieldSelector=status.phase%3DRunning",
+        "/api/v1/nodes?fieldSelector=status.phase%3DReady",
+        "/api/v1/pods?fieldSelector=status.phase",
+        "/api/v1/pods?fieldSelector=%3DRunning",
+    ] {
+        assert_eq!(list(path).await.unwrap().status(), 400, "{}", path);
+    }
+}
+
+#[test]
+fn test_parse_field_selectors() {
+    assert_eq!(FieldSelector::parse("").unwrap(), FieldSelector::default());
+    assert!(FieldSelector::parse("a=b, c!=d,e==f").is_ok());
+    assert!(FieldSelector::parse("status.phase").is_err());
+
+    let selector = FieldSelector::parse("metadata.name=web,spec.unschedulable!=true").unwrap();
+    let field = |name: &'static str, unschedulable: &'static str| {
+        move |label: &str| match label {
+            "metadata.name" => Some(name.to_string()),
+            "spec.unschedulable" => Some(unschedulable.to_string()),
+            _ => None,
+        }
+    };
+    assert!(selector.matches(field("web", "false")).unwrap());
+    assert!(!selector.matches(field("web", "true")).unwrap());
+    assert!(!selector.matches(field("db", "false")).unwrap());
+    assert!(FieldSelector::parse("status.phase=Ready").unwrap().matches(field("web", "false")).is_err());
+}
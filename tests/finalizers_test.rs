@@ -0,0 +1,265 @@
+use reqwest;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+// Polls until `url` is gone, returning whether it went
+async fn gone(client: &reqwest::Client, url: &str) -> bool {
+    for _ in 0..50 {
+        if common::lookup(client, url).await.0 == 404 {
+            return true;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    false
+}
+
+#[tokio::test]
+async fn test_delete_waits_for_finalizers() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let configmaps = server.url("/api/v1/namespaces/default/configmaps");
+    let url = server.url("/api/v1/namespaces/default/configmaps/guarded");
+
+    let configmap = json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": { "name": "guarded", "finalizers": ["example.com/cleanup", "example.com/audit"] },
+        "data": { "key": "value" }
+    });
+    let resp = client.post(&configmaps).json(&configmap).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let created: Value = resp.json().await.unwrap();
+    assert_eq!(created["metadata"]["finalizers"], json!(["example.com/cleanup", "example.com/audit"]));
+
+    // Deleting only marks it
+    let resp = client.delete(&url).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let marked: Value = resp.json().await.unwrap();
+    assert!(marked["metadata"]["deletionTimestamp"].is_string());
+    let (status, fetched) = common::lookup(&client, &url).await;
+    assert_eq!(status, 200);
+    assert_eq!(fetched["metadata"]["deletionTimestamp"], marked["metadata"]["deletionTimestamp"]);
+    assert_eq!(fetched["data"]["key"], "value");
+    let (_, list) = common::lookup(&client, &configmaps).await;
+    let listed = list["items"].as_array().unwrap().iter().find(|item| item["metadata"]["name"] == "guarded").unwrap();
+    assert_eq!(listed["metadata"]["finalizers"], json!(["example.com/cleanup", "example.com/audit"]));
+
+    // No finalizers can be added to it now
+    let mut update = fetched.clone();
+    update["metadata"]["finalizers"] = json!(["example.com/cleanup", "example.com/audit", "example.com/late"]);
+    let resp = client.put(&url).json(&update).send().await.unwrap();
+    assert_eq!(resp.status(), 422);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "Invalid");
+    assert!(status["message"].as_str().unwrap().contains("no new finalizers can be added if the object is being deleted"));
+
+    // Removing them one at a time, by update and by patch, deletes it with
+    // the last one
+    let mut update = fetched.clone();
+    update["metadata"]["finalizers"] = json!(["example.com/audit"]);
+    let resp = client.put(&url).json(&update).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let (status, fetched) = common::lookup(&client, &url).await;
+    assert_eq!(status, 200);
+    assert_eq!(fetched["metadata"]["finalizers"], json!(["example.com/audit"]));
+
+    let resp = client
+        .patch(&url)
+        .header("Content-Type", "application/json-patch+json")
+        .json(&json!([{ "op": "remove", "path": "/metadata/finalizers/0" }]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(common::lookup(&client, &url).await.0, 404);
+
+    // An object of the same name starts with none
+    let resp = client
+        .post(&configmaps)
+        .json(&json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "guarded" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let (_, fetched) = common::lookup(&client, &url).await;
+    assert!(fetched["metadata"]["finalizers"].is_null());
+    assert_eq!(client.delete(&url).send().await.unwrap().status(), 200);
+    assert_eq!(common::lookup(&client, &url).await.0, 404);
+}
+
+#[tokio::test]
+async fn test_claims_in_use_are_protected() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let claim_url = server.url("/api/v1/namespaces/default/persistentvolumeclaims/data");
+
+    let claim = json!({
+        "apiVersion": "v1",
+        "kind": "PersistentVolumeClaim",
+        "metadata": { "name": "data" },
+        "spec": { "accessModes": ["ReadWriteOnce"], "resources": { "requests": { "storage": "1Gi" } } }
+    });
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/persistentvolumeclaims"))
+        .json(&claim)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let created: Value = resp.json().await.unwrap();
+    assert_eq!(created["metadata"]["finalizers"], json!(["kubernetes.io/pvc-protection"]));
+
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "writer" },
+        "spec": {
+            "containers": [{ "name": "app", "image": "busybox", "volumeMounts": [{ "name": "data", "mountPath": "/data" }] }],
+            "volumes": [{ "name": "data", "persistentVolumeClaim": { "claimName": "data" } }]
+        }
+    });
+    let resp = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // The claim outlives its deletion while the pod uses it
+    assert_eq!(client.delete(&claim_url).send().await.unwrap().status(), 200);
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let (status, fetched) = common::lookup(&client, &claim_url).await;
+    assert_eq!(status, 200);
+    assert!(fetched["metadata"]["deletionTimestamp"].is_string());
+    assert_eq!(fetched["metadata"]["finalizers"], json!(["kubernetes.io/pvc-protection"]));
+
+    let resp = client.delete(server.url("/api/v1/namespaces/default/pods/writer")).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(gone(&client, &claim_url).await);
+}
+
+#[tokio::test]
+async fn test_namespace_terminates_after_its_contents() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let namespace_url = server.url("/api/v1/namespaces/winding-down");
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces"))
+        .json(&json!({ "apiVersion": "v1", "kind": "Namespace", "metadata": { "name": "winding-down" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let configmaps = server.url("/api/v1/namespaces/winding-down/configmaps");
+    for (name, finalizers) in [("plain", json!([])), ("held", json!(["example.com/cleanup"]))] {
+        let configmap = json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": name, "finalizers": finalizers } });
+        assert_eq!(client.post(&configmaps).json(&configmap).send().await.unwrap().status(), 201);
+    }
+
+    // What can go goes at once; the namespace waits for the rest
+    let resp = client.delete(&namespace_url).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let (status, namespace) = common::lookup(&client, &namespace_url).await;
+    assert_eq!(status, 200);
+    assert_eq!(namespace["status"]["phase"], "Terminating");
+    assert!(namespace["metadata"]["deletionTimestamp"].is_string());
+    assert_eq!(common::lookup(&client, &format!("{}/plain", configmaps)).await.0, 404);
+    let (status, held) = common::lookup(&client, &format!("{}/held", configmaps)).await;
+    assert_eq!(status, 200);
+    assert!(held["metadata"]["deletionTimestamp"].is_string());
+
+    // Nothing new can be made in it
+    let resp = client
+        .post(&configmaps)
+        .json(&json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "late" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+
+    let resp = client
+        .patch(format!("{}/held", configmaps))
+        .header("Content-Type", "application/merge-patch+json")
+        .json(&json!({ "metadata": { "finalizers": null } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(common::lookup(&client, &format!("{}/held", configmaps)).await.0, 404);
+    assert!(gone(&client, &namespace_url).await);
+}
+
+// The condition of `type` on a namespace, once the namespace controller has
+// set it
+async fn namespace_condition(client: &reqwest::Client, url: &str, kind: &str) -> Value {
+    for _ in 0..50 {
+        let (_, namespace) = common::lookup(client, url).await;
+        let conditions = namespace["status"]["conditions"].as_array().cloned().unwrap_or_default();
+        if let Some(condition) = conditions.into_iter().find(|c| c["type"] == kind) {
+            return condition;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("namespace never got a {} condition", kind);
+}
+
+#[tokio::test]
+async fn test_terminating_namespace_reports_what_remains() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let namespace_url = server.url("/api/v1/namespaces/stuck");
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces"))
+        .json(&json!({ "apiVersion": "v1", "kind": "Namespace", "metadata": { "name": "stuck" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let configmaps = server.url("/api/v1/namespaces/stuck/configmaps");
+    for name in ["first", "second"] {
+        let configmap = json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": name, "finalizers": ["example.com/cleanup"] } });
+        assert_eq!(client.post(&configmaps).json(&configmap).send().await.unwrap().status(), 201);
+    }
+    let secret = json!({ "apiVersion": "v1", "kind": "Secret", "metadata": { "name": "kept", "finalizers": ["example.com/audit"] } });
+    let resp = client.post(server.url("/api/v1/namespaces/stuck/secrets")).json(&secret).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+
+    assert_eq!(client.delete(&namespace_url).send().await.unwrap().status(), 200);
+    let content = namespace_condition(&client, &namespace_url, "NamespaceContentRemaining").await;
+    assert_eq!(content["status"], "True");
+    assert_eq!(content["reason"], "SomeResourcesRemain");
+    assert_eq!(
+        content["message"],
+        "Some resources are remaining: configmaps. has 2 resource instances, secrets. has 1 resource instances"
+    );
+    let finalizers = namespace_condition(&client, &namespace_url, "NamespaceFinalizersRemaining").await;
+    assert_eq!(finalizers["status"], "True");
+    assert_eq!(finalizers["reason"], "SomeFinalizersRemain");
+    assert_eq!(
+        finalizers["message"],
+        "Some content in the namespace has finalizers remaining: example.com/audit in 1 resource instances, example.com/cleanup in 2 resource instances"
+    );
+    let discovery = namespace_condition(&client, &namespace_url, "NamespaceDeletionDiscoveryFailure").await;
+    assert_eq!(discovery["status"], "False");
+
+    // The conditions follow what's left
+    let resp = client
+        .patch(server.url("/api/v1/namespaces/stuck/secrets/kept"))
+        .header("Content-Type", "application/merge-patch+json")
+        .json(&json!({ "metadata": { "finalizers": null } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let mut message = Value::Null;
+    for _ in 0..50 {
+        message = namespace_condition(&client, &namespace_url, "NamespaceFinalizersRemaining").await["message"].clone();
+        if message == "Some content in the namespace has finalizers remaining: example.com/cleanup in 2 resource instances" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(message, "Some content in the namespace has finalizers remaining: example.com/cleanup in 2 resource instances");
+    let (_, namespace) = common::lookup(&client, &namespace_url).await;
+    assert_eq!(namespace["status"]["phase"], "Terminating");
+}
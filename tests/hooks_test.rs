@@ -0,0 +1,91 @@
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod common;
+
+#[tokio::test]
+async fn test_admission_hooks_change_and_refuse_writes() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pods = server.url("/api/v1/namespaces/default/pods");
+
+    server.storage.hooks().on_create("pods", |pod| {
+        if pod["metadata"]["name"] == "refused" {
+            return Err("no pods called refused".to_string());
+        }
+        pod["metadata"]["labels"]["injected"] = json!("true");
+        Ok(())
+    });
+    server.storage.hooks().on_update("pods", |pod| {
+        pod["metadata"]["labels"]["updated"] = json!("true");
+        Ok(())
+    });
+
+    let response = client.post(&pods).json(&common::pod("web", json!({}))).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Value = response.json().await.unwrap();
+    assert_eq!(created["metadata"]["labels"]["injected"], "true");
+
+    let response = client.post(&pods).json(&common::pod("refused", json!({}))).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let status: Value = response.json().await.unwrap();
+    assert_eq!(status["message"], "admission hook denied the request: no pods called refused");
+    let response = client.get(format!("{}/refused", pods)).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    let response = client.put(format!("{}/web", pods)).json(&created).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    let updated: Value = response.json().await.unwrap();
+    assert_eq!(updated["metadata"]["labels"]["updated"], "true");
+
+    // Other resources aren't hooked
+    let config_map = json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "refused" } });
+    let response = client.post(server.url("/api/v1/namespaces/default/configmaps")).json(&config_map).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+}
+
+#[tokio::test]
+async fn test_lifecycle_hooks_hear_of_every_write() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let heard: Arc<Mutex<Vec<String>>> = Arc::default();
+
+    for (kind, hooks) in [("created", 0), ("updated", 1), ("deleted", 2)] {
+        let heard = heard.clone();
+        // krust publishes ConfigMaps of its own, which are left out
+        let hook = move |config_map: &Value| {
+            let name = config_map["metadata"]["name"].as_str().unwrap();
+            if ["settings", "direct"].contains(&name) {
+                heard.lock().unwrap().push(format!("{} {}", kind, name));
+            }
+        };
+        let registered = match hooks {
+            0 => server.storage.hooks().on_object_created("configmaps", hook).await,
+            1 => server.storage.hooks().on_object_updated("configmaps", hook).await,
+            _ => server.storage.hooks().on_object_deleted("configmaps", hook).await,
+        };
+        registered.unwrap();
+    }
+
+    let url = server.url("/api/v1/namespaces/default/configmaps");
+    let config_map = json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "settings" }, "data": { "a": "1" } });
+    assert_eq!(client.post(&url).json(&config_map).send().await.unwrap().status(), StatusCode::CREATED);
+    let mut changed: Value = client.get(format!("{}/settings", url)).send().await.unwrap().json().await.unwrap();
+    changed["data"]["a"] = json!("2");
+    assert_eq!(client.put(format!("{}/settings", url)).json(&changed).send().await.unwrap().status(), StatusCode::OK);
+    assert_eq!(client.delete(format!("{}/settings", url)).send().await.unwrap().status(), StatusCode::OK);
+    // Writes made by the stores directly are heard of as well as the API's
+    let direct = json!({ "metadata": { "name": "direct" }, "data": {} });
+    server.storage.configmaps().create("default", direct).await.unwrap();
+
+    let expected = vec!["created settings", "updated settings", "deleted settings", "created direct"];
+    for _ in 0..50 {
+        if heard.lock().unwrap().len() >= expected.len() {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(*heard.lock().unwrap(), expected);
+}
@@ -0,0 +1,185 @@
+use reqwest;
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+mod common;
+
+const CLUSTER: &str = "nodes:\n  edge-1:\n    internalIP: 10.1.0.5\n";
+
+// A pod the agent Services select
+fn agent_pod(name: &str, spec: Value) -> Value {
+    common::with_metadata(common::pod(name, spec), json!({ "labels": { "app": "agent" } }))
+}
+
+#[tokio::test]
+async fn test_host_network_pods_use_the_node_address() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let node = common::get(&client, &server.url("/api/v1/nodes/edge-1")).await;
+    assert_eq!(node["status"]["addresses"][0]["address"], "10.1.0.5");
+
+    let on_edge = json!({ "kubernetes.io/hostname": "edge-1" });
+    common::create(&client, &server.url("/api/v1/namespaces/default/pods"), agent_pod("host", json!({ "hostNetwork": true, "nodeSelector": on_edge }))).await;
+    common::create(&client, &server.url("/api/v1/namespaces/default/pods"), agent_pod("overlay", json!({ "nodeSelector": on_edge }))).await;
+    server.wait_for_pod_running("default", "host").await;
+    server.wait_for_pod_running("default", "overlay").await;
+
+    let host = common::get(&client, &server.url("/api/v1/namespaces/default/pods/host")).await;
+    assert_eq!(host["status"]["podIP"], "10.1.0.5");
+    assert_eq!(host["status"]["hostIP"], "10.1.0.5");
+
+    let overlay = common::get(&client, &server.url("/api/v1/namespaces/default/pods/overlay")).await;
+    assert_eq!(overlay["status"]["hostIP"], "10.1.0.5");
+    assert!(overlay["status"]["podIP"].as_str().unwrap().starts_with("10.244."));
+
+    assert!(krust::Config::parse("nodes:\n  edge-1:\n    internalIP: edge\n").is_err());
+}
+
+#[tokio::test]
+async fn test_endpoints_of_host_network_pods() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    common::create(
+        &client,
+        &server.url("/api/v1/namespaces/default/pods"),
+        agent_pod("host", json!({ "hostNetwork": true, "nodeSelector": { "kubernetes.io/hostname": "edge-1" } })),
+    )
+    .await;
+    common::create(&client, &server.url("/api/v1/namespaces/default/services"), json!({
+        "apiVersion": "v1",
+        "kind": "Service",
+        "metadata": { "name": "agent" },
+        "spec": { "selector": { "app": "agent" }, "ports": [{ "port": 9100 }] }
+    }))
+    .await;
+    server.wait_for_pod_running("default", "host").await;
+
+    let mut address = Value::Null;
+    for _ in 0..20 {
+        let endpoints = common::get(&client, &server.url("/api/v1/namespaces/default/endpoints/agent")).await;
+        address = endpoints["subsets"][0]["addresses"][0].clone();
+        if !address.is_null() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    }
+    assert_eq!(address["ip"], "10.1.0.5");
+    assert_eq!(address["nodeName"], "edge-1");
+    assert_eq!(address["targetRef"]["name"], "host");
+}
+
+#[tokio::test]
+async fn test_external_ips_forward_to_endpoints() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    // The "agent" listens on the node, where its hostNetwork pod runs
+    let agent = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let agent_port = agent.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut conn, _)) = agent.accept().await {
+            tokio::spawn(async move {
+                let mut buf = [0u8; 64];
+                let n = conn.read(&mut buf).await.unwrap_or(0);
+                let _ = conn.write_all(&[b"agent:", &buf[..n]].concat()).await;
+            });
+        }
+    });
+
+    // A free port on the external address
+    let probe = tokio::net::TcpListener::bind("127.0.0.2:0").await.unwrap();
+    let service_port = probe.local_addr().unwrap().port();
+    drop(probe);
+
+    common::create(&client, &server.url("/api/v1/namespaces/default/pods"), agent_pod("agent", json!({ "hostNetwork": true }))).await;
+    common::create(&client, &server.url("/api/v1/namespaces/default/services"), json!({
+        "apiVersion": "v1",
+        "kind": "Service",
+        "metadata": { "name": "agent" },
+        "spec": {
+            "selector": { "app": "agent" },
+            "externalIPs": ["127.0.0.2"],
+            "ports": [{ "port": service_port, "targetPort": agent_port }]
+        }
+    }))
+    .await;
+
+    let mut reply = Vec::new();
+    for _ in 0..30 {
+        if let Ok(mut conn) = tokio::net::TcpStream::connect(("127.0.0.2", service_port)).await {
+            conn.write_all(b"ping").await.unwrap();
+            reply.clear();
+            let _ = conn.read_to_end(&mut reply).await;
+            if !reply.is_empty() {
+                break;
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    }
+    assert_eq!(reply, b"agent:ping");
+}
+
+#[test]
+fn test_dns_policies() {
+    use krust::runtime::dns::{resolver, Resolver};
+
+    let cluster = krust::Config::parse("dns:\n  clusterDNS: [10.96.0.10]\n").unwrap().dns;
+    let node = Resolver::parse("nameserver 192.168.1.1\nsearch lan\noptions edns0\n");
+    let resolve = |spec: Value| resolver(&spec, "web", &cluster, &node);
+
+    let cluster_first = resolve(json!({})).unwrap();
+    assert_eq!(cluster_first.nameservers, ["10.96.0.10"]);
+    assert_eq!(cluster_first.searches, ["web.svc.cluster.local", "svc.cluster.local", "cluster.local", "lan"]);
+    assert_eq!(cluster_first.options, ["ndots:5"]);
+
+    // hostNetwork pods keep the node's resolver unless they ask for the cluster's
+    assert_eq!(resolve(json!({ "hostNetwork": true })), None);
+    assert_eq!(resolve(json!({ "hostNetwork": true, "dnsPolicy": "ClusterFirstWithHostNet" })), Some(cluster_first.clone()));
+    assert_eq!(resolve(json!({ "dnsPolicy": "Default" })), None);
+
+    // dnsConfig adds to the policy's resolver, replacing repeated options
+    let custom = resolve(json!({
+        "dnsPolicy": "Default",
+        "dnsConfig": { "nameservers": ["1.1.1.1"], "searches": ["corp"], "options": [{ "name": "edns0" }, { "name": "ndots", "value": "2" }] }
+    }))
+    .unwrap();
+    assert_eq!(custom.to_resolv_conf(), "nameserver 192.168.1.1\nnameserver 1.1.1.1\nsearch lan corp\noptions edns0 ndots:2\n");
+
+    let none = resolve(json!({ "dnsPolicy": "None", "dnsConfig": { "nameservers": ["9.9.9.9"] } })).unwrap();
+    assert_eq!(none.to_resolv_conf(), "nameserver 9.9.9.9\n");
+
+    // Without a cluster resolver, ClusterFirst means the node's
+    let standalone = krust::Config::default().dns;
+    assert_eq!(resolver(&json!({}), "web", &standalone, &node), None);
+    assert!(krust::Config::parse("dns:\n  clusterDNS: [kube-dns]\n").is_err());
+}
+
+#[tokio::test]
+async fn test_dns_settings_are_validated() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    for spec in [
+        json!({ "dnsPolicy": "ClusterLast" }),
+        json!({ "dnsPolicy": "None" }),
+        json!({ "dnsConfig": { "nameservers": ["dns.example.com"] } }),
+        json!({ "dnsConfig": { "nameservers": ["1.1.1.1", "1.0.0.1", "8.8.8.8", "8.8.4.4"] } }),
+    ] {
+        let resp = client
+            .post(server.url("/api/v1/namespaces/default/pods"))
+            .json(&agent_pod("dns", spec.clone()))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 422, "{}", spec);
+    }
+
+    let spec = json!({ "hostNetwork": true, "dnsPolicy": "ClusterFirstWithHostNet", "dnsConfig": { "options": [{ "name": "ndots", "value": "1" }] } });
+    common::create(&client, &server.url("/api/v1/namespaces/default/pods"), agent_pod("agent", spec)).await;
+    let agent = common::get(&client, &server.url("/api/v1/namespaces/default/pods/agent")).await;
+    assert!(agent["metadata"]["annotations"]["krust.io/unsupported-fields"].is_null());
+}
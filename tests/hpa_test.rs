@@ -1,16 +1,13 @@
 use reqwest;
 use serde_json::json;
 
+mod common;
+
 #[tokio::test]
 async fn test_hpa_crud_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/autoscaling/v2";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/autoscaling/v2");
     
     // Create HorizontalPodAutoscaler
     let hpa = json!({
@@ -204,16 +201,10 @@ async fn test_hpa_crud_operations() {
 
 #[tokio::test]
 async fn test_hpa_controller_scaling() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let api_base = "http://localhost:6443/api/v1";
-    let apps_base = "http://localhost:6443/apis/apps/v1";
-    let hpa_base = "http://localhost:6443/apis/autoscaling/v2";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let apps_base = server.url("/apis/apps/v1");
+    let hpa_base = server.url("/apis/autoscaling/v2");
     
     // Create a Deployment to scale
     let deployment = json!({
@@ -0,0 +1,115 @@
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use krust::runtime::images;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+async fn event_reasons(client: &reqwest::Client, server: &common::TestServer, uid: &str) -> Vec<(String, String)> {
+    let url = server.url(&format!("/api/v1/namespaces/default/events?fieldSelector=involvedObject.uid%3D{}", uid));
+    let events: Value = client.get(url).send().await.unwrap().json().await.unwrap();
+    events["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| (e["reason"].as_str().unwrap().to_string(), e["message"].as_str().unwrap().to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_image_pull_policy_and_backoff() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pods = server.url("/api/v1/namespaces/default/pods");
+
+    // A tagged image already there isn't pulled, but an Always one is
+    let pod = |name: &str, image: &str, annotations: Value| json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": name, "annotations": annotations },
+        "spec": { "containers": [{ "name": "app", "image": image }] }
+    });
+    let tagged = common::create(&client, &pods, pod("tagged", "nginx:1.25", json!({}))).await;
+    let latest = common::create(&client, &pods, pod("latest", "nginx", json!({}))).await;
+    server.wait_for_pod_running("default", "tagged").await;
+    server.wait_for_pod_running("default", "latest").await;
+    let events = event_reasons(&client, &server, tagged["metadata"]["uid"].as_str().unwrap()).await;
+    assert!(events.contains(&("Pulled".to_string(), "Container image \"nginx:1.25\" already present on machine".to_string())));
+    assert!(!events.iter().any(|(reason, _)| reason == "Pulling"));
+    let events = event_reasons(&client, &server, latest["metadata"]["uid"].as_str().unwrap()).await;
+    assert!(events.contains(&("Pulling".to_string(), "Pulling image \"nginx\"".to_string())));
+    assert!(events.iter().any(|(reason, message)| reason == "Pulled" && message.starts_with("Successfully pulled image \"nginx\" in ")));
+
+    // A pull that fails leaves the pod Pending, waiting to try again
+    let annotations = json!({ "krust.io/fake-image-pull-error": "manifest unknown" });
+    let failing = common::create(&client, &pods, pod("failing", "registry.example.com/app:latest", annotations)).await;
+    let uid = failing["metadata"]["uid"].as_str().unwrap();
+    let mut pod = Value::Null;
+    for _ in 0..50 {
+        pod = client.get(format!("{}/failing", pods)).send().await.unwrap().json().await.unwrap();
+        if pod["status"]["containerStatuses"][0]["state"]["waiting"]["reason"] == "ImagePullBackOff" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let waiting = &pod["status"]["containerStatuses"][0]["state"]["waiting"];
+    assert_eq!(waiting["reason"], "ImagePullBackOff", "{:#}", pod["status"]);
+    assert_eq!(waiting["message"], "Back-off pulling image \"registry.example.com/app:latest\"");
+    assert_ne!(pod["status"]["phase"], "Running");
+    let events = event_reasons(&client, &server, uid).await;
+    let failed = "Failed to pull image \"registry.example.com/app:latest\": manifest unknown";
+    assert!(events.contains(&("Failed".to_string(), failed.to_string())), "{:?}", events);
+    assert!(events.contains(&("Failed".to_string(), "Error: ErrImagePull".to_string())));
+    assert!(events.iter().any(|(reason, _)| reason == "BackOff"));
+}
+
+#[tokio::test]
+async fn test_image_pull_secrets() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let base_url = server.url("/api/v1/namespaces/default");
+
+    assert_eq!(images::split("localhost:5000/team/app"), ("localhost:5000/team/app", "latest"));
+    assert_eq!(images::split("nginx:1.25"), ("nginx", "1.25"));
+    assert_eq!(images::registry("localhost:5000/team/app"), "localhost:5000");
+    assert_eq!(images::registry("library/nginx"), "docker.io");
+
+    let docker_config = |auths: Value| json!({ ".dockerconfigjson": STANDARD.encode(json!({ "auths": auths }).to_string()) });
+    common::create(&client, &format!("{}/secrets", base_url), json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": { "name": "private" },
+        "type": "kubernetes.io/dockerconfigjson",
+        "data": docker_config(json!({ "registry.example.com": { "username": "ci", "password": "s3cret" } }))
+    })).await;
+    common::create(&client, &format!("{}/secrets", base_url), json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": { "name": "hub" },
+        "type": "kubernetes.io/dockerconfigjson",
+        "data": docker_config(json!({ "https://index.docker.io/v1/": { "auth": STANDARD.encode("hubuser:hubpass") } }))
+    })).await;
+
+    // The pod's imagePullSecrets are tried in order, missing ones skipped
+    let pod = json!({
+        "metadata": { "name": "app", "namespace": "default" },
+        "spec": { "imagePullSecrets": [{ "name": "absent" }, { "name": "private" }, { "name": "hub" }] }
+    });
+    let private = images::credentials(&server.storage, &pod, "registry.example.com/team/app:1.0").await.unwrap().unwrap();
+    assert_eq!((private.username.as_deref(), private.password.as_deref()), (Some("ci"), Some("s3cret")));
+    let hub = images::credentials(&server.storage, &pod, "nginx").await.unwrap().unwrap();
+    assert_eq!((hub.username.as_deref(), hub.password.as_deref()), (Some("hubuser"), Some("hubpass")));
+    assert!(images::credentials(&server.storage, &pod, "quay.io/team/app").await.unwrap().is_none());
+
+    // Pods naming none use their ServiceAccount's
+    common::create(&client, &format!("{}/serviceaccounts", base_url), json!({
+        "apiVersion": "v1",
+        "kind": "ServiceAccount",
+        "metadata": { "name": "builder" },
+        "imagePullSecrets": [{ "name": "private" }]
+    })).await;
+    let pod = json!({ "metadata": { "name": "app", "namespace": "default" }, "spec": { "serviceAccountName": "builder" } });
+    let private = images::credentials(&server.storage, &pod, "registry.example.com/team/app:1.0").await.unwrap().unwrap();
+    assert_eq!(private.username.as_deref(), Some("ci"));
+}
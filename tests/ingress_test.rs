@@ -1,16 +1,13 @@
 use reqwest;
 use serde_json::json;
 
+mod common;
+
 #[tokio::test]
 async fn test_ingress_crud_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/networking.k8s.io/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/networking.k8s.io/v1");
     
     // Test 1: Create an Ingress
     let ingress = json!({
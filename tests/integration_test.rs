@@ -4,21 +4,12 @@ use serde_json::{json, Value};
 use std::time::Duration;
 use tokio::time::sleep;
 
-const API_BASE: &str = "http://localhost:6443/api/v1";
-
-async fn wait_for_krust() -> Result<(), Box<dyn std::error::Error>> {
-    for _ in 0..10 {
-        if reqwest::get(format!("{}/namespaces", API_BASE)).await.is_ok() {
-            return Ok(());
-        }
-        sleep(Duration::from_secs(1)).await;
-    }
-    Err("Krust failed to start".into())
-}
+mod common;
 
 #[tokio::test]
 async fn test_namespace_crud_operations() {
-    wait_for_krust().await.expect("Krust should be running");
+    let server = common::TestServer::start().await;
+    let api_base = server.url("/api/v1");
     
     let client = reqwest::Client::new();
     let ns_name = format!("test-ns-{}", uuid::Uuid::new_v4());
@@ -36,7 +27,7 @@ async fn test_namespace_crud_operations() {
     });
     
     let create_resp = client
-        .post(format!("{}/namespaces", API_BASE))
+        .post(format!("{}/namespaces", api_base))
         .json(&create_body)
         .send()
         .await
@@ -50,7 +41,7 @@ async fn test_namespace_crud_operations() {
     
     // List namespaces to see what's in the database
     let list_check = client
-        .get(format!("{}/namespaces", API_BASE))
+        .get(format!("{}/namespaces", api_base))
         .send()
         .await
         .expect("Failed to list namespaces");
@@ -59,7 +50,7 @@ async fn test_namespace_crud_operations() {
     
     // Get namespace
     let get_resp = client
-        .get(format!("{}/namespaces/{}", API_BASE, ns_name))
+        .get(format!("{}/namespaces/{}", api_base, ns_name))
         .send()
         .await
         .expect("Failed to get namespace");
@@ -79,7 +70,7 @@ async fn test_namespace_crud_operations() {
     
     // List namespaces - should include our namespace
     let list_resp = client
-        .get(format!("{}/namespaces", API_BASE))
+        .get(format!("{}/namespaces", api_base))
         .send()
         .await
         .expect("Failed to list namespaces");
@@ -104,7 +95,7 @@ async fn test_namespace_crud_operations() {
     });
     
     let update_resp = client
-        .put(format!("{}/namespaces/{}", API_BASE, ns_name))
+        .put(format!("{}/namespaces/{}", api_base, ns_name))
         .json(&update_body)
         .send()
         .await
@@ -116,7 +107,7 @@ async fn test_namespace_crud_operations() {
     
     // Delete namespace
     let delete_resp = client
-        .delete(format!("{}/namespaces/{}", API_BASE, ns_name))
+        .delete(format!("{}/namespaces/{}", api_base, ns_name))
         .send()
         .await
         .expect("Failed to delete namespace");
@@ -125,7 +116,7 @@ async fn test_namespace_crud_operations() {
     
     // Verify deletion
     let verify_resp = client
-        .get(format!("{}/namespaces/{}", API_BASE, ns_name))
+        .get(format!("{}/namespaces/{}", api_base, ns_name))
         .send()
         .await
         .expect("Failed to verify deletion");
@@ -135,7 +126,8 @@ async fn test_namespace_crud_operations() {
 
 #[tokio::test]
 async fn test_pod_creation_and_persistence() {
-    wait_for_krust().await.expect("Krust should be running");
+    let server = common::TestServer::start().await;
+    let api_base = server.url("/api/v1");
     
     let client = reqwest::Client::new();
     let ns_name = format!("test-pods-{}", uuid::Uuid::new_v4());
@@ -151,7 +143,7 @@ async fn test_pod_creation_and_persistence() {
     });
     
     client
-        .post(format!("{}/namespaces", API_BASE))
+        .post(format!("{}/namespaces", api_base))
         .json(&ns_body)
         .send()
         .await
@@ -181,7 +173,7 @@ async fn test_pod_creation_and_persistence() {
     });
     
     let create_resp = client
-        .post(format!("{}/namespaces/{}/pods", API_BASE, ns_name))
+        .post(format!("{}/namespaces/{}/pods", api_base, ns_name))
         .json(&pod_body)
         .send()
         .await
@@ -193,7 +185,7 @@ async fn test_pod_creation_and_persistence() {
     
     // Get pod
     let get_resp = client
-        .get(format!("{}/namespaces/{}/pods/{}", API_BASE, ns_name, pod_name))
+        .get(format!("{}/namespaces/{}/pods/{}", api_base, ns_name, pod_name))
         .send()
         .await
         .expect("Failed to get pod");
@@ -205,7 +197,7 @@ async fn test_pod_creation_and_persistence() {
     
     // List pods in namespace
     let list_resp = client
-        .get(format!("{}/namespaces/{}/pods", API_BASE, ns_name))
+        .get(format!("{}/namespaces/{}/pods", api_base, ns_name))
         .send()
         .await
         .expect("Failed to list pods");
@@ -228,7 +220,7 @@ async fn test_pod_creation_and_persistence() {
     });
     
     let status_resp = client
-        .patch(format!("{}/namespaces/{}/pods/{}/status", API_BASE, ns_name, pod_name))
+        .patch(format!("{}/namespaces/{}/pods/{}/status", api_base, ns_name, pod_name))
         .json(&status_body)
         .send()
         .await
@@ -240,7 +232,7 @@ async fn test_pod_creation_and_persistence() {
     
     // Delete pod
     let delete_resp = client
-        .delete(format!("{}/namespaces/{}/pods/{}", API_BASE, ns_name, pod_name))
+        .delete(format!("{}/namespaces/{}/pods/{}", api_base, ns_name, pod_name))
         .send()
         .await
         .expect("Failed to delete pod");
@@ -249,7 +241,7 @@ async fn test_pod_creation_and_persistence() {
     
     // Verify deletion
     let verify_resp = client
-        .get(format!("{}/namespaces/{}/pods/{}", API_BASE, ns_name, pod_name))
+        .get(format!("{}/namespaces/{}/pods/{}", api_base, ns_name, pod_name))
         .send()
         .await
         .expect("Failed to verify deletion");
@@ -258,7 +250,7 @@ async fn test_pod_creation_and_persistence() {
     
     // Cleanup namespace
     client
-        .delete(format!("{}/namespaces/{}", API_BASE, ns_name))
+        .delete(format!("{}/namespaces/{}", api_base, ns_name))
         .send()
         .await
         .ok();
@@ -266,7 +258,8 @@ async fn test_pod_creation_and_persistence() {
 
 #[tokio::test]
 async fn test_service_account_operations() {
-    wait_for_krust().await.expect("Krust should be running");
+    let server = common::TestServer::start().await;
+    let api_base = server.url("/api/v1");
     
     let client = reqwest::Client::new();
     let ns_name = format!("test-sa-{}", uuid::Uuid::new_v4());
@@ -282,7 +275,7 @@ async fn test_service_account_operations() {
     });
     
     client
-        .post(format!("{}/namespaces", API_BASE))
+        .post(format!("{}/namespaces", api_base))
         .json(&ns_body)
         .send()
         .await
@@ -302,7 +295,7 @@ async fn test_service_account_operations() {
     });
     
     let create_resp = client
-        .post(format!("{}/namespaces/{}/serviceaccounts", API_BASE, ns_name))
+        .post(format!("{}/namespaces/{}/serviceaccounts", api_base, ns_name))
         .json(&sa_body)
         .send()
         .await
@@ -314,7 +307,7 @@ async fn test_service_account_operations() {
     
     // Get service account
     let get_resp = client
-        .get(format!("{}/namespaces/{}/serviceaccounts/{}", API_BASE, ns_name, sa_name))
+        .get(format!("{}/namespaces/{}/serviceaccounts/{}", api_base, ns_name, sa_name))
         .send()
         .await
         .expect("Failed to get service account");
@@ -325,7 +318,7 @@ async fn test_service_account_operations() {
     
     // List service accounts
     let list_resp = client
-        .get(format!("{}/namespaces/{}/serviceaccounts", API_BASE, ns_name))
+        .get(format!("{}/namespaces/{}/serviceaccounts", api_base, ns_name))
         .send()
         .await
         .expect("Failed to list service accounts");
@@ -338,7 +331,7 @@ async fn test_service_account_operations() {
     
     // Delete service account
     let delete_resp = client
-        .delete(format!("{}/namespaces/{}/serviceaccounts/{}", API_BASE, ns_name, sa_name))
+        .delete(format!("{}/namespaces/{}/serviceaccounts/{}", api_base, ns_name, sa_name))
         .send()
         .await
         .expect("Failed to delete service account");
@@ -347,7 +340,7 @@ async fn test_service_account_operations() {
     
     // Cleanup namespace
     client
-        .delete(format!("{}/namespaces/{}", API_BASE, ns_name))
+        .delete(format!("{}/namespaces/{}", api_base, ns_name))
         .send()
         .await
         .ok();
@@ -355,7 +348,8 @@ async fn test_service_account_operations() {
 
 #[tokio::test]
 async fn test_service_operations() {
-    wait_for_krust().await.expect("Krust should be running");
+    let server = common::TestServer::start().await;
+    let api_base = server.url("/api/v1");
     
     let client = reqwest::Client::new();
     let ns_name = format!("test-svc-{}", uuid::Uuid::new_v4());
@@ -371,7 +365,7 @@ async fn test_service_operations() {
     });
     
     client
-        .post(format!("{}/namespaces", API_BASE))
+        .post(format!("{}/namespaces", api_base))
         .json(&ns_body)
         .send()
         .await
@@ -398,7 +392,7 @@ async fn test_service_operations() {
     });
     
     let create_resp = client
-        .post(format!("{}/namespaces/{}/services", API_BASE, ns_name))
+        .post(format!("{}/namespaces/{}/services", api_base, ns_name))
         .json(&svc_body)
         .send()
         .await
@@ -410,7 +404,7 @@ async fn test_service_operations() {
     
     // Get service
     let get_resp = client
-        .get(format!("{}/namespaces/{}/services/{}", API_BASE, ns_name, svc_name))
+        .get(format!("{}/namespaces/{}/services/{}", api_base, ns_name, svc_name))
         .send()
         .await
         .expect("Failed to get service");
@@ -422,7 +416,7 @@ async fn test_service_operations() {
     
     // Delete service
     let delete_resp = client
-        .delete(format!("{}/namespaces/{}/services/{}", API_BASE, ns_name, svc_name))
+        .delete(format!("{}/namespaces/{}/services/{}", api_base, ns_name, svc_name))
         .send()
         .await
         .expect("Failed to delete service");
@@ -431,7 +425,7 @@ async fn test_service_operations() {
     
     // Cleanup namespace
     client
-        .delete(format!("{}/namespaces/{}", API_BASE, ns_name))
+        .delete(format!("{}/namespaces/{}", api_base, ns_name))
         .send()
         .await
         .ok();
@@ -439,7 +433,8 @@ async fn test_service_operations() {
 
 #[tokio::test]
 async fn test_configmap_operations() {
-    wait_for_krust().await.expect("Krust should be running");
+    let server = common::TestServer::start().await;
+    let api_base = server.url("/api/v1");
     
     let client = reqwest::Client::new();
     let ns_name = format!("test-cm-{}", uuid::Uuid::new_v4());
@@ -455,7 +450,7 @@ async fn test_configmap_operations() {
     });
     
     client
-        .post(format!("{}/namespaces", API_BASE))
+        .post(format!("{}/namespaces", api_base))
         .json(&ns_body)
         .send()
         .await
@@ -476,7 +471,7 @@ async fn test_configmap_operations() {
     });
     
     let create_resp = client
-        .post(format!("{}/namespaces/{}/configmaps", API_BASE, ns_name))
+        .post(format!("{}/namespaces/{}/configmaps", api_base, ns_name))
         .json(&cm_body)
         .send()
         .await
@@ -489,7 +484,7 @@ async fn test_configmap_operations() {
     
     // Get ConfigMap
     let get_resp = client
-        .get(format!("{}/namespaces/{}/configmaps/{}", API_BASE, ns_name, cm_name))
+        .get(format!("{}/namespaces/{}/configmaps/{}", api_base, ns_name, cm_name))
         .send()
         .await
         .expect("Failed to get configmap");
@@ -513,7 +508,7 @@ async fn test_configmap_operations() {
     });
     
     let update_resp = client
-        .put(format!("{}/namespaces/{}/configmaps/{}", API_BASE, ns_name, cm_name))
+        .put(format!("{}/namespaces/{}/configmaps/{}", api_base, ns_name, cm_name))
         .json(&update_body)
         .send()
         .await
@@ -526,7 +521,7 @@ async fn test_configmap_operations() {
     
     // Delete ConfigMap
     let delete_resp = client
-        .delete(format!("{}/namespaces/{}/configmaps/{}", API_BASE, ns_name, cm_name))
+        .delete(format!("{}/namespaces/{}/configmaps/{}", api_base, ns_name, cm_name))
         .send()
         .await
         .expect("Failed to delete configmap");
@@ -535,7 +530,7 @@ async fn test_configmap_operations() {
     
     // Cleanup namespace
     client
-        .delete(format!("{}/namespaces/{}", API_BASE, ns_name))
+        .delete(format!("{}/namespaces/{}", api_base, ns_name))
         .send()
         .await
         .ok();
@@ -543,7 +538,8 @@ async fn test_configmap_operations() {
 
 #[tokio::test]
 async fn test_secret_operations() {
-    wait_for_krust().await.expect("Krust should be running");
+    let server = common::TestServer::start().await;
+    let api_base = server.url("/api/v1");
     
     let client = reqwest::Client::new();
     let ns_name = format!("test-secret-{}", uuid::Uuid::new_v4());
@@ -559,7 +555,7 @@ async fn test_secret_operations() {
     });
     
     client
-        .post(format!("{}/namespaces", API_BASE))
+        .post(format!("{}/namespaces", api_base))
         .json(&ns_body)
         .send()
         .await
@@ -581,7 +577,7 @@ async fn test_secret_operations() {
     });
     
     let create_resp = client
-        .post(format!("{}/namespaces/{}/secrets", API_BASE, ns_name))
+        .post(format!("{}/namespaces/{}/secrets", api_base, ns_name))
         .json(&secret_body)
         .send()
         .await
@@ -593,7 +589,7 @@ async fn test_secret_operations() {
     
     // Get Secret
     let get_resp = client
-        .get(format!("{}/namespaces/{}/secrets/{}", API_BASE, ns_name, secret_name))
+        .get(format!("{}/namespaces/{}/secrets/{}", api_base, ns_name, secret_name))
         .send()
         .await
         .expect("Failed to get secret");
@@ -604,7 +600,7 @@ async fn test_secret_operations() {
     
     // Delete Secret
     let delete_resp = client
-        .delete(format!("{}/namespaces/{}/secrets/{}", API_BASE, ns_name, secret_name))
+        .delete(format!("{}/namespaces/{}/secrets/{}", api_base, ns_name, secret_name))
         .send()
         .await
         .expect("Failed to delete secret");
@@ -613,7 +609,7 @@ async fn test_secret_operations() {
     
     // Cleanup namespace
     client
-        .delete(format!("{}/namespaces/{}", API_BASE, ns_name))
+        .delete(format!("{}/namespaces/{}", api_base, ns_name))
         .send()
         .await
         .ok();
@@ -621,7 +617,8 @@ async fn test_secret_operations() {
 
 #[tokio::test]
 async fn test_deployment_operations() {
-    wait_for_krust().await.expect("Krust should be running");
+    let server = common::TestServer::start().await;
+    let api_base = server.url("/api/v1");
     
     let client = reqwest::Client::new();
     let ns_name = format!("test-deploy-{}", uuid::Uuid::new_v4());
@@ -637,7 +634,7 @@ async fn test_deployment_operations() {
     });
     
     client
-        .post(format!("{}/namespaces", API_BASE))
+        .post(format!("{}/namespaces", api_base))
         .json(&ns_body)
         .send()
         .await
@@ -675,7 +672,7 @@ async fn test_deployment_operations() {
     });
     
     let create_resp = client
-        .post(format!("http://localhost:6443/apis/apps/v1/namespaces/{}/deployments", ns_name))
+        .post(format!("{}/apis/apps/v1/namespaces/{}/deployments", server.base_url(), ns_name))
         .json(&deploy_body)
         .send()
         .await
@@ -688,7 +685,7 @@ async fn test_deployment_operations() {
     
     // Get Deployment
     let get_resp = client
-        .get(format!("http://localhost:6443/apis/apps/v1/namespaces/{}/deployments/{}", ns_name, deploy_name))
+        .get(format!("{}/apis/apps/v1/namespaces/{}/deployments/{}", server.base_url(), ns_name, deploy_name))
         .send()
         .await
         .expect("Failed to get deployment");
@@ -703,7 +700,7 @@ async fn test_deployment_operations() {
     });
     
     let scale_resp = client
-        .patch(format!("http://localhost:6443/apis/apps/v1/namespaces/{}/deployments/{}", ns_name, deploy_name))
+        .patch(format!("{}/apis/apps/v1/namespaces/{}/deployments/{}", server.base_url(), ns_name, deploy_name))
         .json(&scale_body)
         .send()
         .await
@@ -715,7 +712,7 @@ async fn test_deployment_operations() {
     
     // Delete Deployment
     let delete_resp = client
-        .delete(format!("http://localhost:6443/apis/apps/v1/namespaces/{}/deployments/{}", ns_name, deploy_name))
+        .delete(format!("{}/apis/apps/v1/namespaces/{}/deployments/{}", server.base_url(), ns_name, deploy_name))
         .send()
         .await
         .expect("Failed to delete deployment");
@@ -724,15 +721,17 @@ async fn test_deployment_operations() {
     
     // Cleanup namespace
     client
-        .delete(format!("{}/namespaces/{}", API_BASE, ns_name))
+        .delete(format!("{}/namespaces/{}", api_base, ns_name))
         .send()
         .await
         .ok();
 }
 
 #[tokio::test]
+#[ignore = "requires Docker to run the pod's container"]
 async fn test_pod_logs() {
-    wait_for_krust().await.expect("Krust should be running");
+    let server = common::TestServer::start().await;
+    let api_base = server.url("/api/v1");
     
     let client = reqwest::Client::new();
     let ns_name = format!("test-logs-{}", uuid::Uuid::new_v4());
@@ -748,7 +747,7 @@ async fn test_pod_logs() {
     });
     
     client
-        .post(format!("{}/namespaces", API_BASE))
+        .post(format!("{}/namespaces", api_base))
         .json(&ns_body)
         .send()
         .await
@@ -772,7 +771,7 @@ async fn test_pod_logs() {
     });
     
     let create_resp = client
-        .post(format!("{}/namespaces/{}/pods", API_BASE, ns_name))
+        .post(format!("{}/namespaces/{}/pods", api_base, ns_name))
         .json(&pod_body)
         .send()
         .await
@@ -785,7 +784,7 @@ async fn test_pod_logs() {
     
     // Get pod logs
     let logs_resp = client
-        .get(format!("{}/namespaces/{}/pods/{}/log", API_BASE, ns_name, pod_name))
+        .get(format!("{}/namespaces/{}/pods/{}/log", api_base, ns_name, pod_name))
         .send()
         .await
         .expect("Failed to get pod logs");
@@ -796,22 +795,20 @@ async fn test_pod_logs() {
     
     // Cleanup
     client
-        .delete(format!("{}/namespaces/{}/pods/{}", API_BASE, ns_name, pod_name))
+        .delete(format!("{}/namespaces/{}/pods/{}", api_base, ns_name, pod_name))
         .send()
         .await
         .ok();
     
     client
-        .delete(format!("{}/namespaces/{}", API_BASE, ns_name))
+        .delete(format!("{}/namespaces/{}", api_base, ns_name))
         .send()
         .await
         .ok();
 }
 
 // Helper function for base64 encoding
-mod base64 {
-    pub fn encode(input: &str) -> String {
-        use base64::Engine;
-        base64::engine::general_purpose::STANDARD.encode(input)
-    }
+fn encode_base64(input: &str) -> String {
+    use base64::Engine;
+    base64::engine::general_purpose::STANDARD.encode(input)
 }
\ No newline at end of file
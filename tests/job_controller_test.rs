@@ -0,0 +1,192 @@
+use reqwest;
+use serde_json::{json, Value};
+
+mod common;
+
+// Replace failed pods without delay unless a test asks otherwise
+const FAST_BACKOFF: &str = "jobs:\n  backoffSeconds: 0\n";
+
+fn job(name: &str, restart_policy: &str, exit_code: i64, backoff_limit: i64, completions: i64) -> Value {
+    json!({
+        "apiVersion": "batch/v1",
+        "kind": "Job",
+        "metadata": { "name": name },
+        "spec": {
+            "completions": completions,
+            "backoffLimit": backoff_limit,
+            "template": {
+                "metadata": {
+                    "annotations": { "krust.io/fake-exit-code": exit_code.to_string() }
+                },
+                "spec": {
+                    "containers": [{ "name": "worker", "image": "busybox:1.35" }],
+                    "restartPolicy": restart_policy
+                }
+            }
+        }
+    })
+}
+
+async fn create_job(client: &reqwest::Client, server: &common::TestServer, job: &Value) -> reqwest::Response {
+    client
+        .post(server.url("/apis/batch/v1/namespaces/default/jobs"))
+        .json(job)
+        .send()
+        .await
+        .expect("Failed to create job")
+}
+
+/// Polls the Job until it has a Complete or Failed condition.
+async fn wait_for_job_finished(client: &reqwest::Client, server: &common::TestServer, name: &str) -> Value {
+    for _ in 0..60 {
+        let job: Value = client
+            .get(server.url(&format!("/apis/batch/v1/namespaces/default/jobs/{}", name)))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let finished = job["status"]["conditions"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .any(|c| c["type"] == "Complete" || c["type"] == "Failed");
+        if finished {
+            return job;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+    }
+    panic!("job {} never finished", name);
+}
+
+async fn job_pods(server: &common::TestServer, job_name: &str) -> Vec<Value> {
+    let pods = server.storage.pods().list(Some("default")).await.unwrap();
+    pods["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|p| p["metadata"]["labels"]["job-name"] == job_name)
+        .cloned()
+        .collect()
+}
+
+fn condition<'a>(job: &'a Value, type_: &str) -> &'a Value {
+    job["status"]["conditions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["type"] == type_)
+        .unwrap_or_else(|| panic!("job has no {} condition", type_))
+}
+
+#[tokio::test]
+async fn test_restart_policy_never_creates_a_new_pod_per_retry() {
+    let config = krust::Config::parse(FAST_BACKOFF).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let resp = create_job(&client, &server, &job("never", "Never", 1, 2, 1)).await;
+    assert_eq!(resp.status(), 201);
+
+    let finished = wait_for_job_finished(&client, &server, "never").await;
+    let failed = condition(&finished, "Failed");
+    assert_eq!(failed["reason"], "BackoffLimitExceeded");
+    // The first run plus backoffLimit retries, each a failed pod of its own
+    assert_eq!(finished["status"]["failed"], 3);
+    assert_eq!(finished["status"]["succeeded"], 0);
+    assert_eq!(finished["status"]["active"], 0);
+
+    let pods = job_pods(&server, "never").await;
+    assert_eq!(pods.len(), 3);
+    for pod in &pods {
+        assert_eq!(pod["status"]["phase"], "Failed");
+        let container = &pod["status"]["containerStatuses"][0];
+        assert_eq!(container["restartCount"], 0);
+        assert_eq!(container["state"]["terminated"]["exitCode"], 1);
+    }
+}
+
+#[tokio::test]
+async fn test_restart_policy_on_failure_restarts_in_place() {
+    let config = krust::Config::parse(FAST_BACKOFF).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let resp = create_job(&client, &server, &job("onfailure", "OnFailure", 1, 3, 1)).await;
+    assert_eq!(resp.status(), 201);
+
+    // Watch the single pod restart before the Job gives up on it
+    let mut restarts = 0;
+    for _ in 0..40 {
+        let pods = job_pods(&server, "onfailure").await;
+        assert!(pods.len() <= 1, "OnFailure must not create replacement pods");
+        if let Some(pod) = pods.first() {
+            restarts = pod["status"]["containerStatuses"][0]["restartCount"].as_i64().unwrap_or(0);
+            if restarts > 0 {
+                assert_eq!(pod["status"]["phase"], "Running");
+                assert_eq!(pod["status"]["containerStatuses"][0]["lastState"]["terminated"]["exitCode"], 1);
+                break;
+            }
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(250)).await;
+    }
+    assert!(restarts > 0, "container was never restarted");
+
+    let finished = wait_for_job_finished(&client, &server, "onfailure").await;
+    assert_eq!(condition(&finished, "Failed")["reason"], "BackoffLimitExceeded");
+    // Restarts aren't failures; only the pod removed when the Job failed is
+    assert_eq!(finished["status"]["failed"], 1);
+    assert_eq!(finished["status"]["active"], 0);
+}
+
+#[tokio::test]
+async fn test_successful_pods_complete_the_job() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let resp = create_job(&client, &server, &job("done", "Never", 0, 6, 2)).await;
+    assert_eq!(resp.status(), 201);
+
+    let finished = wait_for_job_finished(&client, &server, "done").await;
+    assert_eq!(condition(&finished, "Complete")["status"], "True");
+    assert_eq!(finished["status"]["succeeded"], 2);
+    assert_eq!(finished["status"]["failed"], 0);
+    assert!(finished["status"]["completionTime"].is_string());
+
+    let pods = job_pods(&server, "done").await;
+    assert_eq!(pods.len(), 2);
+    assert!(pods.iter().all(|p| p["status"]["phase"] == "Succeeded"));
+}
+
+#[tokio::test]
+async fn test_failed_pods_are_replaced_after_backoff() {
+    let config = krust::Config::parse("jobs:\n  backoffSeconds: 2\n").unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let resp = create_job(&client, &server, &job("backoff", "Never", 1, 1, 1)).await;
+    assert_eq!(resp.status(), 201);
+
+    wait_for_job_finished(&client, &server, "backoff").await;
+
+    let mut pods = job_pods(&server, "backoff").await;
+    assert_eq!(pods.len(), 2);
+    pods.sort_by_key(|p| p["metadata"]["creationTimestamp"].as_str().unwrap().to_string());
+
+    let parse = |ts: &Value| chrono::DateTime::parse_from_rfc3339(ts.as_str().unwrap()).unwrap();
+    let first_failed = parse(&pods[0]["status"]["containerStatuses"][0]["state"]["terminated"]["finishedAt"]);
+    let replaced = parse(&pods[1]["metadata"]["creationTimestamp"]);
+    assert!(replaced - first_failed >= chrono::Duration::seconds(2));
+}
+
+#[tokio::test]
+async fn test_job_rejects_restart_policy_always() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let resp = create_job(&client, &server, &job("always", "Always", 0, 6, 1)).await;
+    assert_eq!(resp.status(), 422);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "Invalid");
+}
@@ -2,6 +2,8 @@ use std::process::Command;
 use std::thread;
 use std::time::Duration;
 
+mod common;
+
 #[test]
 #[ignore] // Run with: cargo test --test kubectl_integration_test -- --ignored --nocapture
 fn test_kubectl_pod_lifecycle() {
@@ -16,10 +18,7 @@ fn test_kubectl_pod_lifecycle() {
     thread::sleep(Duration::from_secs(1));
     
     // Clean database
-    Command::new("rm")
-        .args(&["-f", "krust.db"])
-        .output()
-        .ok();
+    common::reset_data_dir();
     
     // Start Krust server in background
     println!("Starting Krust server...");
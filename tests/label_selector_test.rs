@@ -0,0 +1,110 @@
+use reqwest;
+use krust::storage::{LabelSelector, ListSelector};
+use krust::Storage;
+use serde_json::{json, Value};
+
+mod common;
+
+fn configmap(name: &str, labels: Value) -> Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": { "name": name, "labels": labels },
+        "data": {}
+    })
+}
+
+fn names(list: &Value) -> Vec<String> {
+    let mut names: Vec<String> = list["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|item| item["metadata"]["name"].as_str().unwrap().to_string())
+        .collect();
+    names.sort();
+    names
+}
+
+#[tokio::test]
+async fn test_stores_filter_on_labels() {
+    let storage = Storage::in_memory().await.unwrap();
+    storage.migrate().await.unwrap();
+    let configmaps = storage.configmaps();
+
+    configmaps.create("default", configmap("web", json!({ "app": "web", "tier": "frontend" }))).await.unwrap();
+    configmaps.create("default", configmap("cache", json!({ "app": "cache", "tier": "backend", "canary": "true" }))).await.unwrap();
+    configmaps.create("default", configmap("db", json!({ "app.kubernetes.io/name": "db", "tier": "backend" }))).await.unwrap();
+    configmaps.create("default", configmap("bare", json!({}))).await.unwrap();
+
+    let list = |labels: &'static str| {
+        let configmaps = storage.configmaps();
+        async move {
+            let selector = ListSelector::parse(None, Some(labels)).unwrap();
+            names(&configmaps.list_matching(Some("default"), &selector).await.unwrap())
+        }
+    };
+
+    assert_eq!(list("app=web").await, ["web"]);
+    assert_eq!(list("tier==backend").await, ["cache", "db"]);
+    // != and notin also match objects without the label at all
+    assert_eq!(list("tier!=backend").await, ["bare", "web"]);
+    assert_eq!(list("tier in (frontend, backend),!canary").await, ["db", "web"]);
+    assert_eq!(list("app notin (web,cache)").await, ["bare", "db"]);
+    assert_eq!(list("canary").await, ["cache"]);
+    assert_eq!(list("app.kubernetes.io/name=db").await, ["db"]);
+    assert!(list("app=web,tier=backend").await.is_empty());
+}
+
+#[tokio::test]
+async fn test_label_selectors_over_the_api() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    for (name, app) in [("web", "web"), ("api", "api")] {
+        let pod = json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": { "name": name, "labels": { "app": app } },
+            "spec": { "containers": [{ "name": "app", "image": "nginx:1.25" }] }
+        });
+        let resp = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod).send().await.unwrap();
+        assert!(resp.status().is_success());
+    }
+
+    let list = |path: &str| client.get(server.url(path)).send();
+
+    let resp = list("/api/v1/namespaces/default/pods?labelSelector=app%3Dweb").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(names(&resp.json().await.unwrap()), ["web"]);
+
+    let resp = list("/api/v1/pods?labelSelector=app%20in%20(web,api)&fieldSelector=metadata.name!%3Dweb").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(names(&resp.json().await.unwrap()), ["api"]);
+
+    let resp = list("/api/v1/namespaces?labelSelector=missing").await.unwrap();
+    assert_eq!(resp.status(), 200);
+    assert!(names(&resp.json().await.unwrap()).is_empty());
+
+    for path in [
+        "/api/v1/pods?labelSelector=app%20in%20(web",
+        "/api/v1/pods?labelSelector=app%20among%20(web)",
+        "/api/v1/pods?labelSelector=a%20b",
+    ] {
+        assert_eq!(list(path).await.unwrap().status(), 400, "{}", path);
+    }
+}
+
+#[test]
+fn test_label_selector_matches() {
+    let labels = json!({ "app": "web", "tier": "frontend" });
+    let matches = |selector: &str| LabelSelector::parse(selector).unwrap().matches(&labels);
+
+    assert!(matches(""));
+    assert!(matches("app=web,tier"));
+    assert!(matches("tier in (frontend),!canary"));
+    assert!(matches("app notin (db)"));
+    assert!(!matches("app!=web"));
+    assert!(!matches("canary"));
+    assert!(LabelSelector::parse("app in ()").is_err());
+    assert!(LabelSelector::parse("app)").is_err());
+}
@@ -0,0 +1,170 @@
+use reqwest;
+use serde_json::{json, Value};
+
+mod common;
+
+const LAST_APPLIED: &str = "kubectl.kubernetes.io/last-applied-configuration";
+
+fn deployment(labels: Value, replicas: i64) -> Value {
+    json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": "web", "namespace": "default", "labels": labels },
+        "spec": {
+            "replicas": replicas,
+            "selector": { "matchLabels": { "app": "web" } },
+            "template": {
+                "metadata": { "labels": { "app": "web" } },
+                "spec": { "containers": [{ "name": "web", "image": "nginx:1.25" }] }
+            }
+        }
+    })
+}
+
+/// The object as `kubectl apply` sends it: the configuration plus itself,
+/// serialized, in the last-applied annotation.
+fn with_last_applied(mut config: Value) -> Value {
+    let applied = config.to_string();
+    config["metadata"]["annotations"] = json!({ LAST_APPLIED: applied });
+    config
+}
+
+async fn patch(client: &reqwest::Client, url: &str, body: &Value) -> Value {
+    let resp = client
+        .patch(url)
+        .header("Content-Type", "application/strategic-merge-patch+json")
+        .json(body)
+        .send()
+        .await
+        .expect("Failed to patch");
+    assert_eq!(resp.status(), 200);
+    resp.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_apply_removes_fields_dropped_from_the_configuration() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/apis/apps/v1/namespaces/default/deployments/web");
+
+    let first = with_last_applied(deployment(json!({ "app": "web", "tier": "frontend" }), 2));
+    let resp = client
+        .post(server.url("/apis/apps/v1/namespaces/default/deployments"))
+        .json(&first)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // Someone else labels the deployment meanwhile
+    patch(&client, &url, &json!({ "metadata": { "labels": { "owner": "ops" } } })).await;
+
+    // The next apply drops the tier label and only sends the new annotation
+    // and what changed
+    let second = deployment(json!({ "app": "web" }), 3);
+    let applied = second.to_string();
+    let patched = patch(
+        &client,
+        &url,
+        &json!({
+            "metadata": { "annotations": { LAST_APPLIED: applied } },
+            "spec": { "replicas": 3 }
+        }),
+    )
+    .await;
+
+    let labels = &patched["metadata"]["labels"];
+    assert_eq!(labels["app"], "web");
+    assert!(labels.get("tier").is_none(), "dropped label kept: {}", labels);
+    assert_eq!(labels["owner"], "ops");
+    assert_eq!(patched["spec"]["replicas"], 3);
+    assert_eq!(patched["metadata"]["annotations"][LAST_APPLIED], applied);
+}
+
+#[tokio::test]
+async fn test_last_applied_annotation_is_stored_verbatim() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/api/v1/namespaces/default/configmaps/settings");
+
+    // Large, with characters that naive handling tends to mangle
+    let data: serde_json::Map<String, Value> = (0..200)
+        .map(|i| (format!("key-{}", i), json!(format!("\"quoted\" value {} with \\ and ünïcode\n", i))))
+        .collect();
+    let config = json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": { "name": "settings", "namespace": "default", "annotations": { "team": "platform" } },
+        "data": data
+    });
+    let applied = config.to_string();
+    let mut body = config.clone();
+    body["metadata"]["annotations"][LAST_APPLIED] = json!(applied);
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&body)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // Patching another annotation leaves it and the rest alone
+    let patched = patch(&client, &url, &json!({ "metadata": { "annotations": { "reviewed": "yes" } } })).await;
+    let annotations = &patched["metadata"]["annotations"];
+    assert_eq!(annotations[LAST_APPLIED], applied);
+    assert_eq!(annotations["team"], "platform");
+    assert_eq!(annotations["reviewed"], "yes");
+
+    let stored: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(stored["metadata"]["annotations"][LAST_APPLIED], applied);
+}
+
+#[tokio::test]
+async fn test_apply_removes_configmap_keys_and_patch_takes_precedence() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/api/v1/namespaces/default/configmaps/app");
+
+    let config = |data: Value| {
+        with_last_applied(json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": { "name": "app", "namespace": "default" },
+            "data": data
+        }))
+    };
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&config(json!({ "a": "1", "b": "2", "c": "3" })))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // b and c leave the configuration, but the patch keeps c explicitly
+    let mut next = config(json!({ "a": "1" }));
+    next["data"] = json!({ "c": "kept" });
+    let patched = patch(&client, &url, &next).await;
+
+    assert_eq!(patched["data"], json!({ "a": "1", "c": "kept" }));
+}
+
+#[tokio::test]
+async fn test_plain_patches_do_not_remove_fields() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/apis/apps/v1/namespaces/default/deployments/web");
+
+    let resp = client
+        .post(server.url("/apis/apps/v1/namespaces/default/deployments"))
+        .json(&with_last_applied(deployment(json!({ "app": "web", "tier": "frontend" }), 1)))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // Without a new last-applied configuration nothing is inferred
+    let patched = patch(&client, &url, &json!({ "metadata": { "labels": { "app": "site" } } })).await;
+    assert_eq!(patched["metadata"]["labels"], json!({ "app": "site", "tier": "frontend" }));
+}
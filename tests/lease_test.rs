@@ -0,0 +1,162 @@
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+// Heartbeats every 200ms rather than every 10s
+const CLUSTER: &str = r#"
+nodes:
+  krust-node: {}
+  small-1:
+    memory: 512Mi
+    ephemeralStorage: 1Gi
+controllers:
+  resyncPeriodSeconds:
+    nodeStatus: 0.2
+"#;
+
+// The node's condition of the given type once it has the given status
+async fn wait_for_condition(server: &common::TestServer, client: &reqwest::Client, node: &str, kind: &str, status: &str) -> Value {
+    let url = server.url(&format!("/api/v1/nodes/{}", node));
+    let mut conditions = Value::Null;
+    for _ in 0..50 {
+        let node = common::get(client, &url).await;
+        conditions = node["status"]["conditions"].clone();
+        if let Some(condition) = conditions.as_array().into_iter().flatten().find(|c| c["type"] == kind && c["status"] == status) {
+            return condition.clone();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("{} never had {}={}: {}", node, kind, status, conditions);
+}
+
+// A pod on small-1 whose fake usage the annotations set
+fn pod_on_small(name: &str, annotations: Value) -> Value {
+    let pod = common::pod(name, json!({ "nodeSelector": { "kubernetes.io/hostname": "small-1" } }));
+    common::with_metadata(pod, json!({ "annotations": annotations }))
+}
+
+#[tokio::test]
+async fn test_leases_can_be_created_watched_and_deleted() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/apis/coordination.k8s.io/v1/namespaces/default/leases");
+    let mut watch = client.get(format!("{}?watch=true", url)).send().await.unwrap();
+
+    let resp = client
+        .post(&url)
+        .json(&json!({
+            "apiVersion": "coordination.k8s.io/v1",
+            "kind": "Lease",
+            "metadata": { "name": "my-controller" },
+            "spec": { "holderIdentity": "replica-a", "leaseDurationSeconds": 15 }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let mut lease: Value = resp.json().await.unwrap();
+    assert_eq!(lease["spec"]["holderIdentity"], "replica-a");
+
+    // Another replica takes it over, as leader election does
+    lease["spec"]["holderIdentity"] = json!("replica-b");
+    lease["spec"]["leaseTransitions"] = json!(1);
+    let resp = client.put(format!("{}/my-controller", url)).json(&lease).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let updated: Value = resp.json().await.unwrap();
+    assert_eq!(updated["spec"]["holderIdentity"], "replica-b");
+
+    // Replacing it with what was read before that is a conflict
+    let resp = client.put(format!("{}/my-controller", url)).json(&lease).send().await.unwrap();
+    assert_eq!(resp.status(), 409);
+
+    let mut events = Vec::new();
+    let mut buffer = String::new();
+    while events.len() < 2 {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), watch.chunk()).await.unwrap().unwrap().unwrap();
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+        while let Some(end) = buffer.find('\n') {
+            events.push(serde_json::from_str::<Value>(&buffer[..end]).unwrap());
+            buffer.drain(..=end);
+        }
+    }
+    assert_eq!(events[0]["type"], "ADDED");
+    assert_eq!(events[1]["type"], "MODIFIED");
+    assert_eq!(events[1]["object"]["spec"]["holderIdentity"], "replica-b");
+
+    let resp = client.delete(format!("{}/my-controller", url)).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client.get(format!("{}/my-controller", url)).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_kubelets_renew_their_nodes_leases() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+    let url = server.url("/apis/coordination.k8s.io/v1/namespaces/kube-node-lease/leases");
+
+    let mut leases = Value::Null;
+    for _ in 0..50 {
+        leases = common::get(&client, &url).await;
+        if leases["items"].as_array().is_some_and(|items| items.len() == 2) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let names: Vec<&str> = leases["items"].as_array().unwrap().iter().map(|l| l["metadata"]["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["krust-node", "small-1"]);
+    let lease = &leases["items"][1];
+    assert_eq!(lease["spec"]["holderIdentity"], "small-1");
+    assert_eq!(lease["spec"]["leaseDurationSeconds"], 40);
+    assert_eq!(lease["metadata"]["ownerReferences"][0]["kind"], "Node");
+    assert_eq!(lease["metadata"]["ownerReferences"][0]["name"], "small-1");
+
+    // Each heartbeat renews it
+    let renewed = lease["spec"]["renewTime"].clone();
+    let mut lease = Value::Null;
+    for _ in 0..30 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        lease = common::get(&client, &format!("{}/small-1", url)).await;
+        if lease["spec"]["renewTime"] != renewed {
+            break;
+        }
+    }
+    assert_ne!(lease["spec"]["renewTime"], renewed);
+    assert_eq!(lease["spec"]["acquireTime"], leases["items"][1]["spec"]["acquireTime"]);
+    assert_eq!(lease["spec"]["leaseTransitions"], 0);
+
+    // The node reports its conditions alongside
+    wait_for_condition(&server, &client, "small-1", "MemoryPressure", "False").await;
+    let node = common::get(&client, &server.url("/api/v1/nodes/small-1")).await;
+    let kinds: Vec<&str> = node["status"]["conditions"].as_array().unwrap().iter().map(|c| c["type"].as_str().unwrap()).collect();
+    assert_eq!(kinds, ["MemoryPressure", "DiskPressure", "PIDPressure", "Ready"]);
+    assert_eq!(node["status"]["conditions"][3]["reason"], "KubeletReady");
+    assert_eq!(node["status"]["capacity"]["ephemeral-storage"], "1Gi");
+}
+
+#[tokio::test]
+async fn test_nodes_report_memory_and_disk_pressure() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+    let before = wait_for_condition(&server, &client, "small-1", "MemoryPressure", "False").await;
+
+    // Less than 100Mi left of the node's 512Mi
+    common::create_pod(&client, &server, pod_on_small("hungry", json!({ "krust.io/fake-memory-usage": "450Mi" }))).await;
+    server.wait_for_pod_running("default", "hungry").await;
+    let condition = wait_for_condition(&server, &client, "small-1", "MemoryPressure", "True").await;
+    assert_eq!(condition["reason"], "KubeletHasInsufficientMemory");
+    assert_ne!(condition["lastTransitionTime"], before["lastTransitionTime"]);
+
+    // More than 90% of its 1Gi of ephemeral storage used
+    common::create_pod(&client, &server, pod_on_small("hoarder", json!({ "krust.io/fake-ephemeral-storage": "app=950Mi" }))).await;
+    server.wait_for_pod_running("default", "hoarder").await;
+    let condition = wait_for_condition(&server, &client, "small-1", "DiskPressure", "True").await;
+    assert_eq!(condition["reason"], "KubeletHasDiskPressure");
+
+    // krust-node has no ephemeral storage configured, so never has pressure
+    let condition = wait_for_condition(&server, &client, "krust-node", "DiskPressure", "False").await;
+    assert_eq!(condition["reason"], "KubeletHasNoDiskPressure");
+}
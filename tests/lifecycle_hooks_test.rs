@@ -0,0 +1,162 @@
+use axum::{extract::State, http::HeaderMap, http::StatusCode, routing::get, Router};
+use serde_json::{json, Value};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+mod common;
+
+// The paths the hook server was sent, with their X-Pod headers
+type Received = Arc<Mutex<Vec<(String, String)>>>;
+
+// Serves the hooks' GETs, failing those for /broken, and returns its port
+async fn hook_server(received: Received) -> u16 {
+    let record = |path: &'static str, status: StatusCode| {
+        move |State(received): State<Received>, headers: HeaderMap| async move {
+            let pod = headers.get("x-pod").and_then(|v| v.to_str().ok()).unwrap_or_default().to_string();
+            received.lock().unwrap().push((path.to_string(), pod));
+            status
+        }
+    };
+    let app = Router::new()
+        .route("/started", get(record("/started", StatusCode::OK)))
+        .route("/stopping", get(record("/stopping", StatusCode::NO_CONTENT)))
+        .route("/broken", get(record("/broken", StatusCode::INTERNAL_SERVER_ERROR)))
+        .with_state(received);
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+    port
+}
+
+fn hook(port: u16, path: &str, pod: &str) -> Value {
+    json!({ "httpGet": { "host": "127.0.0.1", "port": port, "path": path, "httpHeaders": [{ "name": "X-Pod", "value": pod }] } })
+}
+
+async fn events(client: &reqwest::Client, server: &common::TestServer, uid: &str) -> Vec<(String, String)> {
+    let url = server.url(&format!("/api/v1/namespaces/default/events?fieldSelector=involvedObject.uid%3D{}", uid));
+    let events: Value = client.get(url).send().await.unwrap().json().await.unwrap();
+    events["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|e| (e["reason"].as_str().unwrap().to_string(), e["message"].as_str().unwrap().to_string()))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_hooks_run_around_the_grace_period() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let received = Received::default();
+    let port = hook_server(received.clone()).await;
+
+    // Its containers take a minute to exit on SIGTERM, longer than its grace
+    // period
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "hooked", "annotations": { "krust.io/fake-termination-seconds": "60" } },
+        "spec": {
+            "terminationGracePeriodSeconds": 2,
+            "containers": [{
+                "name": "app",
+                "image": "nginx:1.25",
+                "lifecycle": { "postStart": hook(port, "/started", "hooked"), "preStop": hook(port, "/stopping", "hooked") }
+            }]
+        }
+    });
+    let resp = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let uid = resp.json::<Value>().await.unwrap()["metadata"]["uid"].as_str().unwrap().to_string();
+    server.wait_for_pod_running("default", "hooked").await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(*received.lock().unwrap(), vec![("/started".to_string(), "hooked".to_string())]);
+
+    // Deleting it runs the preStop hook and reports it no longer ready
+    // while it's stopped, then kills it when the grace period ends
+    let url = server.url("/api/v1/namespaces/default/pods/hooked");
+    assert_eq!(client.delete(&url).send().await.unwrap().status(), 200);
+    tokio::time::sleep(Duration::from_millis(1000)).await;
+    assert!(received.lock().unwrap().contains(&("/stopping".to_string(), "hooked".to_string())));
+    let stopping: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert_eq!(stopping["status"]["containerStatuses"][0]["ready"], false, "{:#}", stopping["status"]);
+    let ready = stopping["status"]["conditions"].as_array().unwrap().iter().find(|c| c["type"] == "Ready").unwrap().clone();
+    assert_eq!(ready["status"], "False");
+    assert_eq!(ready["message"], "containers with unready status: [app]");
+
+    let mut gone = false;
+    for _ in 0..50 {
+        if client.get(&url).send().await.unwrap().status() == 404 {
+            gone = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(gone);
+    assert!(events(&client, &server, &uid).await.contains(&("Killing".to_string(), "Stopping container app".to_string())));
+}
+
+#[tokio::test]
+async fn test_failing_hooks() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let received = Received::default();
+    let port = hook_server(received.clone()).await;
+    let pods = server.url("/api/v1/namespaces/default/pods");
+
+    // A container whose postStart hook fails is killed
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "broken" },
+        "spec": {
+            "restartPolicy": "Never",
+            "containers": [{ "name": "app", "image": "nginx:1.25", "lifecycle": { "postStart": hook(port, "/broken", "broken") } }]
+        }
+    });
+    let resp = client.post(&pods).json(&pod).send().await.unwrap();
+    let uid = resp.json::<Value>().await.unwrap()["metadata"]["uid"].as_str().unwrap().to_string();
+    let mut pod = Value::Null;
+    for _ in 0..50 {
+        pod = server.storage.pods().get("default", "broken").await.unwrap();
+        if pod["status"]["phase"] == "Failed" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(pod["status"]["phase"], "Failed", "{:#}", pod["status"]);
+    assert_eq!(pod["status"]["containerStatuses"][0]["state"]["terminated"]["exitCode"], 137);
+    let message = format!(
+        "HTTP lifecycle hook (http://127.0.0.1:{}/broken) for Container \"app\" in Pod \"broken_default({})\" failed - error: received status 500 Internal Server Error",
+        port, uid
+    );
+    let reported = events(&client, &server, &uid).await;
+    assert!(reported.contains(&("FailedPostStartHook".to_string(), message)), "{:?}", reported);
+    assert!(reported.contains(&("Killing".to_string(), "FailedPostStartHook".to_string())));
+
+    // One whose preStop hook fails is stopped all the same
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "reluctant" },
+        "spec": {
+            "containers": [{ "name": "app", "image": "nginx:1.25", "lifecycle": { "preStop": hook(port, "/broken", "reluctant") } }]
+        }
+    });
+    let resp = client.post(&pods).json(&pod).send().await.unwrap();
+    let uid = resp.json::<Value>().await.unwrap()["metadata"]["uid"].as_str().unwrap().to_string();
+    server.wait_for_pod_running("default", "reluctant").await;
+    let url = format!("{}/reluctant", pods);
+    assert_eq!(client.delete(&url).send().await.unwrap().status(), 200);
+    let mut gone = false;
+    for _ in 0..50 {
+        if client.get(&url).send().await.unwrap().status() == 404 {
+            gone = true;
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert!(gone);
+    assert!(received.lock().unwrap().contains(&("/broken".to_string(), "reluctant".to_string())));
+    assert!(events(&client, &server, &uid).await.iter().any(|(reason, _)| reason == "FailedPreStopHook"));
+}
@@ -0,0 +1,192 @@
+use serde_json::{json, Value};
+
+mod common;
+
+// A namespace whose containers default to 250m/256Mi requests and
+// 500m/512Mi limits, and may use between 100m and 1 CPU
+async fn start_with_limit_range(namespace: &str) -> (common::TestServer, reqwest::Client) {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let resp = common::post(
+        &client,
+        &server.url("/api/v1/namespaces"),
+        &json!({ "apiVersion": "v1", "kind": "Namespace", "metadata": { "name": namespace } }),
+    )
+    .await;
+    assert_eq!(resp.status(), 201);
+    let resp = common::post(
+        &client,
+        &server.url(&format!("/api/v1/namespaces/{}/limitranges", namespace)),
+        &json!({
+            "apiVersion": "v1",
+            "kind": "LimitRange",
+            "metadata": { "name": "limits" },
+            "spec": {
+                "limits": [
+                    {
+                        "type": "Container",
+                        "default": { "cpu": "500m", "memory": "512Mi" },
+                        "defaultRequest": { "cpu": "250m", "memory": "256Mi" },
+                        "min": { "cpu": "100m" },
+                        "max": { "cpu": "1" },
+                        "maxLimitRequestRatio": { "memory": "4" }
+                    },
+                    { "type": "Pod", "max": { "memory": "1Gi" } }
+                ]
+            }
+        }),
+    )
+    .await;
+    assert_eq!(resp.status(), 201);
+    (server, client)
+}
+
+fn new_pod(name: &str, containers: Value) -> Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": name },
+        "spec": { "containers": containers }
+    })
+}
+
+#[tokio::test]
+async fn test_limitrange_defaults_missing_requests_and_limits() {
+    let (server, client) = start_with_limit_range("limited").await;
+
+    let resp = common::post(
+        &client,
+        &server.url("/api/v1/namespaces/limited/pods"),
+        &new_pod(
+            "defaulted",
+            json!([
+                { "name": "bare", "image": "nginx" },
+                { "name": "capped", "image": "nginx", "resources": { "limits": { "cpu": "200m" } } }
+            ]),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), 201);
+    let pod: Value = resp.json().await.unwrap();
+
+    let bare = &pod["spec"]["containers"][0]["resources"];
+    assert_eq!(bare["requests"], json!({ "cpu": "250m", "memory": "256Mi" }));
+    assert_eq!(bare["limits"], json!({ "cpu": "500m", "memory": "512Mi" }));
+    // A container's own limit is its request, not the namespace's default
+    let capped = &pod["spec"]["containers"][1]["resources"];
+    assert_eq!(capped["requests"], json!({ "cpu": "200m", "memory": "256Mi" }));
+    assert_eq!(capped["limits"], json!({ "cpu": "200m", "memory": "512Mi" }));
+    assert_eq!(
+        pod["metadata"]["annotations"]["kubernetes.io/limit-ranger"],
+        "LimitRanger plugin set: cpu, memory request for container bare; cpu, memory limit for container bare; \
+         memory request for container capped; memory limit for container capped"
+    );
+
+    // Namespaces without LimitRanges leave pods as they are
+    let resp = common::post(
+        &client,
+        &server.url("/api/v1/namespaces/default/pods"),
+        &new_pod("untouched", json!([{ "name": "bare", "image": "nginx" }])),
+    )
+    .await;
+    assert_eq!(resp.status(), 201);
+    let pod: Value = resp.json().await.unwrap();
+    assert!(pod["spec"]["containers"][0]["resources"]["limits"].is_null());
+    assert!(pod["metadata"]["annotations"]["kubernetes.io/limit-ranger"].is_null());
+}
+
+#[tokio::test]
+async fn test_limitrange_refuses_pods_outside_its_constraints() {
+    let (server, client) = start_with_limit_range("limited").await;
+    let url = server.url("/api/v1/namespaces/limited/pods");
+
+    let resp = common::post(
+        &client,
+        &url,
+        &new_pod(
+            "greedy",
+            json!([{
+                "name": "app",
+                "image": "nginx",
+                "resources": {
+                    "requests": { "cpu": "50m", "memory": "100Mi" },
+                    "limits": { "cpu": "2", "memory": "1Gi" }
+                }
+            }]),
+        ),
+    )
+    .await;
+    assert_eq!(resp.status(), 403);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "Forbidden");
+    assert_eq!(
+        status["message"],
+        "pods \"greedy\" is forbidden: [minimum cpu usage per Container is 100m, but request is 50m, \
+         maximum cpu usage per Container is 1, but limit is 2, \
+         memory max limit to request ratio per Container is 4, but provided ratio is 10.240000]"
+    );
+
+    // Each container is within the ranges, but not the pod as a whole
+    let container = json!({ "image": "nginx", "resources": { "limits": { "memory": "768Mi" } } });
+    let mut containers = vec![container.clone(), container];
+    containers[0]["name"] = json!("first");
+    containers[1]["name"] = json!("second");
+    let resp = common::post(&client, &url, &new_pod("pair", json!(containers))).await;
+    assert_eq!(resp.status(), 403);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(
+        status["message"],
+        "pods \"pair\" is forbidden: [maximum memory usage per Pod is 1Gi, but limit is 1536Mi, \
+         maximum memory usage per Pod is 1Gi, but request is 1536Mi]"
+    );
+
+    let resp = client.get(format!("{}/greedy", url)).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_limitrange_applies_to_controller_pods() {
+    let (server, client) = start_with_limit_range("limited").await;
+
+    let resp = common::post(
+        &client,
+        &server.url("/apis/apps/v1/namespaces/limited/replicasets"),
+        &json!({
+            "apiVersion": "apps/v1",
+            "kind": "ReplicaSet",
+            "metadata": { "name": "web" },
+            "spec": {
+                "replicas": 1,
+                "selector": { "matchLabels": { "app": "web" } },
+                "template": {
+                    "metadata": { "labels": { "app": "web" } },
+                    "spec": { "containers": [{ "name": "app", "image": "nginx" }] }
+                }
+            }
+        }),
+    )
+    .await;
+    assert_eq!(resp.status(), 201);
+
+    let mut pods = Vec::new();
+    for _ in 0..50 {
+        let list: Value = client
+            .get(server.url("/api/v1/namespaces/limited/pods"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        pods = list["items"].as_array().cloned().unwrap_or_default();
+        if !pods.is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    assert_eq!(pods.len(), 1, "the ReplicaSet made no pod");
+    assert_eq!(
+        pods[0]["spec"]["containers"][0]["resources"]["limits"],
+        json!({ "cpu": "500m", "memory": "512Mi" })
+    );
+}
@@ -5,6 +5,8 @@ use std::time::Duration;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 
+mod common;
+
 // Mock kubectl client that implements SPDY protocol
 struct MockKubectl {
     api_server: String,
@@ -128,7 +130,7 @@ fn setup_test() -> std::process::Child {
         .output()
         .ok();
     thread::sleep(Duration::from_secs(1));
-    Command::new("rm").args(&["-f", "krust.db"]).output().ok();
+    common::reset_data_dir();
 
     // Start server
     let server = Command::new("cargo")
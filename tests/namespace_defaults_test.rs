@@ -0,0 +1,99 @@
+use reqwest;
+use serde_json::{json, Value};
+
+mod common;
+
+const CONFIG: &str = r#"
+namespaceDefaults:
+  limitRanges:
+    - metadata:
+        name: default-limits
+      spec:
+        limits:
+          - type: Container
+            default:
+              cpu: 500m
+              memory: 256Mi
+  resourceQuotas:
+    - metadata:
+        name: default-quota
+      spec:
+        hard:
+          pods: "10"
+"#;
+
+#[tokio::test]
+async fn test_new_namespace_gets_default_limitrange_and_quota() {
+    let config = krust::Config::parse(CONFIG).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+    let base_url = server.url("/api/v1");
+
+    let namespace = json!({
+        "apiVersion": "v1",
+        "kind": "Namespace",
+        "metadata": { "name": "team-a" }
+    });
+    let response = client
+        .post(&format!("{}/namespaces", base_url))
+        .json(&namespace)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+
+    let response = client
+        .get(&format!("{}/namespaces/team-a/limitranges/default-limits", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let limitrange: Value = response.json().await.unwrap();
+    assert_eq!(limitrange["metadata"]["namespace"], "team-a");
+    assert_eq!(limitrange["spec"]["limits"][0]["default"]["cpu"], "500m");
+
+    let response = client
+        .get(&format!("{}/namespaces/team-a/resourcequotas/default-quota", base_url))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let quota: Value = response.json().await.unwrap();
+    assert_eq!(quota["spec"]["hard"]["pods"], "10");
+}
+
+#[tokio::test]
+async fn test_no_defaults_without_config() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let base_url = server.url("/api/v1");
+
+    let namespace = json!({
+        "apiVersion": "v1",
+        "kind": "Namespace",
+        "metadata": { "name": "team-b" }
+    });
+    client
+        .post(&format!("{}/namespaces", base_url))
+        .json(&namespace)
+        .send()
+        .await
+        .unwrap();
+
+    let list: Value = client
+        .get(&format!("{}/namespaces/team-b/limitranges", base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(list["items"].as_array().unwrap().is_empty());
+}
+
+#[test]
+fn test_config_requires_template_names() {
+    let config = "namespaceDefaults:\n  resourceQuotas:\n    - spec:\n        hard:\n          pods: \"1\"\n";
+    assert!(krust::Config::parse(config).is_err());
+    assert!(krust::Config::parse("").is_ok());
+}
@@ -0,0 +1,171 @@
+use krust::runtime::network_policy::{ruleset, PolicyPod};
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+
+fn pod(namespace: &str, name: &str, labels: Value, ip: &str) -> PolicyPod {
+    PolicyPod {
+        pod: json!({
+            "metadata": { "namespace": namespace, "name": name, "labels": labels },
+            "spec": { "containers": [{ "name": "app", "ports": [{ "name": "http", "containerPort": 8080 }] }] }
+        }),
+        ips: vec![ip.to_string()],
+    }
+}
+
+fn policy(namespace: &str, spec: Value) -> Value {
+    json!({ "metadata": { "namespace": namespace, "name": "policy" }, "spec": spec })
+}
+
+fn namespaces() -> BTreeMap<String, Value> {
+    BTreeMap::from([
+        ("default".to_string(), json!({ "kubernetes.io/metadata.name": "default" })),
+        ("monitoring".to_string(), json!({ "kubernetes.io/metadata.name": "monitoring", "team": "ops" })),
+    ])
+}
+
+fn pods() -> Vec<PolicyPod> {
+    vec![
+        pod("default", "db", json!({ "app": "db" }), "172.17.0.2"),
+        pod("default", "web", json!({ "app": "web" }), "172.17.0.3"),
+        pod("monitoring", "prometheus", json!({ "app": "prometheus" }), "172.17.0.4"),
+    ]
+}
+
+fn rules(script: &str) -> Vec<&str> {
+    script.lines().filter(|line| line.starts_with("-A") || line.starts_with("-X")).collect()
+}
+
+#[test]
+fn test_pods_without_policies_are_not_isolated() {
+    let script = ruleset(&[], &pods(), &namespaces(), &[]);
+    assert_eq!(
+        script,
+        "*filter\n:KRUST-NETPOL - [0:0]\n-A KRUST-NETPOL -m conntrack --ctstate RELATED,ESTABLISHED -j RETURN\nCOMMIT\n"
+    );
+}
+
+#[test]
+fn test_ingress_rules_allow_selected_peers_and_ports() {
+    let policies = [policy(
+        "default",
+        json!({
+            "podSelector": { "matchLabels": { "app": "db" } },
+            "ingress": [
+                { "from": [{ "podSelector": { "matchLabels": { "app": "web" } } }], "ports": [{ "port": 5432 }] },
+                { "from": [{ "namespaceSelector": { "matchLabels": { "team": "ops" } } }], "ports": [{ "port": "http" }] }
+            ]
+        }),
+    )];
+    let script = ruleset(&policies, &pods(), &namespaces(), &[]);
+    assert_eq!(
+        rules(&script),
+        [
+            "-A KRUST-NETPOL -m conntrack --ctstate RELATED,ESTABLISHED -j RETURN",
+            "-A KRUST-NETPOL -d 172.17.0.2/32 -j KRUST-NETPOL-I0",
+            "-A KRUST-NETPOL-I0 -s 172.17.0.3/32 -p tcp --dport 5432 -j MARK --set-xmark 0x20000/0x20000",
+            // The named port is the db pod's own
+            "-A KRUST-NETPOL-I0 -s 172.17.0.4/32 -p tcp --dport 8080 -j MARK --set-xmark 0x20000/0x20000",
+            "-A KRUST-NETPOL-I0 -m mark --mark 0x20000/0x20000 -j RETURN",
+            "-A KRUST-NETPOL-I0 -j DROP",
+        ]
+    );
+}
+
+#[test]
+fn test_policy_without_rules_denies_everything_it_isolates() {
+    // Every pod in the namespace, both ways; the other namespace is left be
+    let policies = [policy("default", json!({ "podSelector": {}, "policyTypes": ["Ingress", "Egress"] }))];
+    let script = ruleset(&policies, &pods(), &namespaces(), &[]);
+    let rules = rules(&script);
+    assert!(rules.contains(&"-A KRUST-NETPOL -s 172.17.0.2/32 -j KRUST-NETPOL-E0"));
+    assert!(rules.contains(&"-A KRUST-NETPOL -s 172.17.0.3/32 -j KRUST-NETPOL-E1"));
+    assert!(rules.contains(&"-A KRUST-NETPOL -d 172.17.0.3/32 -j KRUST-NETPOL-I1"));
+    assert!(rules.contains(&"-A KRUST-NETPOL-E1 -j DROP"));
+    assert!(!script.contains("172.17.0.4"), "{}", script);
+    // Egress is checked before ingress
+    let egress = rules.iter().position(|rule| rule.ends_with("-j KRUST-NETPOL-E0")).unwrap();
+    let ingress = rules.iter().position(|rule| rule.ends_with("-j KRUST-NETPOL-I0")).unwrap();
+    assert!(egress < ingress);
+}
+
+#[test]
+fn test_egress_to_ip_blocks_skips_their_exceptions() {
+    let policies = [policy(
+        "default",
+        json!({
+            "podSelector": { "matchLabels": { "app": "web" } },
+            "policyTypes": ["Egress"],
+            "egress": [
+                { "to": [{ "ipBlock": { "cidr": "10.0.0.0/8", "except": ["10.1.0.0/16"] } }], "ports": [{ "protocol": "UDP", "port": 53 }] },
+                { "ports": [{ "port": 443, "endPort": 444 }] }
+            ]
+        }),
+    )];
+    let script = ruleset(&policies, &pods(), &namespaces(), &[]);
+    assert_eq!(
+        rules(&script),
+        [
+            "-A KRUST-NETPOL -m conntrack --ctstate RELATED,ESTABLISHED -j RETURN",
+            "-A KRUST-NETPOL -s 172.17.0.3/32 -j KRUST-NETPOL-E1",
+            "-A KRUST-NETPOL-B1 -d 10.1.0.0/16 -j RETURN",
+            "-A KRUST-NETPOL-B1 -d 10.0.0.0/8 -p udp --dport 53 -j MARK --set-xmark 0x10000/0x10000",
+            "-A KRUST-NETPOL-E1 -j KRUST-NETPOL-B1",
+            "-A KRUST-NETPOL-E1 -p tcp --dport 443:444 -j MARK --set-xmark 0x10000/0x10000",
+            "-A KRUST-NETPOL-E1 -m mark --mark 0x10000/0x10000 -j RETURN",
+            "-A KRUST-NETPOL-E1 -j DROP",
+        ]
+    );
+}
+
+#[test]
+fn test_ip_blocks_and_ports_that_do_not_parse_are_left_out() {
+    // As a policy stored before they were checked could have them
+    let policies = [policy(
+        "default",
+        json!({
+            "podSelector": { "matchLabels": { "app": "web" } },
+            "policyTypes": ["Egress"],
+            "egress": [{
+                "to": [
+                    { "ipBlock": { "cidr": "10.0.0.0/8\n-A DOCKER-USER -j ACCEPT" } },
+                    { "ipBlock": { "cidr": "10.0.0.0/8", "except": ["10.1.0.0/16 -j ACCEPT"] } },
+                    { "ipBlock": { "cidr": "fd00::/8" } },
+                    { "ipBlock": { "cidr": "192.168.0.0/16" } }
+                ],
+                "ports": [{ "protocol": "tcp\n-A DOCKER-USER -j ACCEPT", "port": 80 }, { "port": 70000 }, { "port": 443 }]
+            }]
+        }),
+    )];
+    let script = ruleset(&policies, &pods(), &namespaces(), &[]);
+    assert!(!script.contains("DOCKER-USER"), "{}", script);
+    assert!(!script.contains("ACCEPT"), "{}", script);
+    assert_eq!(
+        rules(&script),
+        [
+            "-A KRUST-NETPOL -m conntrack --ctstate RELATED,ESTABLISHED -j RETURN",
+            "-A KRUST-NETPOL -s 172.17.0.3/32 -j KRUST-NETPOL-E1",
+            "-A KRUST-NETPOL-B1 -d 192.168.0.0/16 -p tcp --dport 443 -j MARK --set-xmark 0x10000/0x10000",
+            "-A KRUST-NETPOL-E1 -j KRUST-NETPOL-B1",
+            "-A KRUST-NETPOL-E1 -m mark --mark 0x10000/0x10000 -j RETURN",
+            "-A KRUST-NETPOL-E1 -j DROP",
+        ]
+    );
+}
+
+#[test]
+fn test_chains_no_longer_used_are_deleted() {
+    let policies = [policy("default", json!({ "podSelector": { "matchLabels": { "app": "db" } } }))];
+    let stale = ["KRUST-NETPOL-I0".to_string(), "KRUST-NETPOL-E7".to_string()];
+    let script = ruleset(&policies, &pods(), &namespaces(), &stale);
+    assert!(script.contains(":KRUST-NETPOL-I0 - [0:0]\n"));
+    assert!(script.contains(":KRUST-NETPOL-E7 - [0:0]\n"));
+    assert!(script.ends_with("-X KRUST-NETPOL-E7\nCOMMIT\n"), "{}", script);
+    assert!(!script.contains("-X KRUST-NETPOL-I0"));
+}
+
+#[test]
+fn test_enforcement_can_be_turned_off() {
+    assert!(krust::Config::default().network_policy.enforce);
+    let config = krust::Config::parse("networkPolicy:\n  enforce: false\n").unwrap();
+    assert!(!config.network_policy.enforce);
+}
@@ -1,16 +1,13 @@
 use reqwest;
 use serde_json::json;
 
+mod common;
+
 #[tokio::test]
 async fn test_networkpolicy_crud_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/networking.k8s.io/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/networking.k8s.io/v1");
     
     // Test 1: Create a NetworkPolicy
     let network_policy = json!({
@@ -123,4 +120,61 @@ async fn test_networkpolicy_crud_operations() {
         .unwrap();
     
     assert_eq!(response.status(), 404);
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_networkpolicy_ip_blocks_and_ports_are_validated() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let policies = server.url("/apis/networking.k8s.io/v1/namespaces/default/networkpolicies");
+    let policy = |egress: serde_json::Value| {
+        json!({
+            "apiVersion": "networking.k8s.io/v1",
+            "kind": "NetworkPolicy",
+            "metadata": { "name": "egress", "namespace": "default" },
+            "spec": { "podSelector": {}, "policyTypes": ["Egress"], "egress": egress }
+        })
+    };
+
+    // A CIDR carrying rules of its own never gets near iptables
+    let hostile = policy(json!([{ "to": [{ "ipBlock": { "cidr": "10.0.0.0/8\n-A DOCKER-USER -j ACCEPT" } }] }]));
+    let response = client.post(&policies).json(&hostile).send().await.unwrap();
+    assert_eq!(response.status(), 422);
+    let status: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(status["reason"], "Invalid");
+    assert_eq!(status["details"]["causes"][0]["field"], "spec.egress[0].to[0].ipBlock.cidr");
+    let response = client.get(format!("{}/egress", policies)).send().await.unwrap();
+    assert_eq!(response.status(), 404);
+
+    let response = client
+        .post(&policies)
+        .json(&policy(json!([{ "ports": [{ "protocol": "ICMP", "port": 53 }] }])))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 422);
+    let status: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(status["details"]["causes"][0]["field"], "spec.egress[0].ports[0].protocol");
+    assert_eq!(status["details"]["causes"][0]["reason"], "FieldValueNotSupported");
+
+    let valid = policy(json!([{ "to": [{ "ipBlock": { "cidr": "10.0.0.0/8", "except": ["10.1.0.0/16"] } }], "ports": [{ "protocol": "UDP", "port": 53 }] }]));
+    let response = client.post(&policies).json(&valid).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+
+    // Updates and patches are held to the same
+    let mut update = valid.clone();
+    update["spec"]["egress"][0]["to"][0]["ipBlock"]["cidr"] = json!("10.0.0.0/33");
+    let response = client.put(format!("{}/egress", policies)).json(&update).send().await.unwrap();
+    assert_eq!(response.status(), 422);
+    let response = client
+        .patch(format!("{}/egress", policies))
+        .header("Content-Type", "application/merge-patch+json")
+        .json(&json!({ "spec": { "egress": [{ "to": [{ "ipBlock": { "cidr": "10.0.0.0/8", "except": ["not-a-cidr"] } }] }] } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 422);
+
+    let stored: serde_json::Value = client.get(format!("{}/egress", policies)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(stored["spec"]["egress"], valid["spec"]["egress"]);
+}
@@ -0,0 +1,109 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+mod common;
+
+const CLUSTER: &str = r#"
+nodes:
+  krust-node:
+    cpu: "4"
+    memory: 8Gi
+  east-1:
+    cpu: "4"
+    memory: 8Gi
+    zone: us-east-1a
+  east-2:
+    cpu: "4"
+    memory: 8Gi
+    zone: us-east-1b
+"#;
+
+// A pod placed by `affinity`, requesting half a CPU
+fn pod_with_affinity(name: &str, affinity: Value) -> Value {
+    common::pod(name, json!({
+        "affinity": { "nodeAffinity": affinity },
+        "containers": [{ "name": "app", "image": "nginx:latest", "resources": { "requests": { "cpu": "500m", "memory": "512Mi" } } }]
+    }))
+}
+
+fn zones(zones: &[&str]) -> Value {
+    json!({ "matchExpressions": [{ "key": "topology.kubernetes.io/zone", "operator": "In", "values": zones }] })
+}
+
+// Waits for the pod to be bound and returns its node
+async fn node_of(server: &common::TestServer, name: &str) -> String {
+    for _ in 0..50 {
+        if let Some(node) = server.storage.pods().get("default", name).await.unwrap()["spec"]["nodeName"].as_str() {
+            return node.to_string();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("pod {} was never scheduled", name);
+}
+
+#[tokio::test]
+async fn test_required_node_affinity_and_spreading() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+    let pods_url = server.url("/api/v1/namespaces/default/pods");
+
+    // Replicas limited to the zoned nodes spread over them, by what each has left
+    let required = json!({ "requiredDuringSchedulingIgnoredDuringExecution": { "nodeSelectorTerms": [zones(&["us-east-1a", "us-east-1b"])] } });
+    let mut placed: BTreeMap<String, usize> = BTreeMap::new();
+    for i in 0..4 {
+        let name = format!("web-{}", i);
+        let resp = client.post(&pods_url).json(&pod_with_affinity(&name, required.clone())).send().await.unwrap();
+        assert_eq!(resp.status(), 201);
+        *placed.entry(node_of(&server, &name).await).or_default() += 1;
+    }
+    assert_eq!(placed, BTreeMap::from([("east-1".to_string(), 2), ("east-2".to_string(), 2)]));
+
+    // matchFields picks nodes by name
+    let by_name = json!({ "requiredDuringSchedulingIgnoredDuringExecution": { "nodeSelectorTerms": [
+        { "matchFields": [{ "key": "metadata.name", "operator": "NotIn", "values": ["east-1", "east-2"] }] }
+    ] } });
+    client.post(&pods_url).json(&pod_with_affinity("local", by_name)).send().await.unwrap();
+    assert_eq!(node_of(&server, "local").await, "krust-node");
+
+    // No node matching is reported as for a nodeSelector
+    let nowhere = json!({ "requiredDuringSchedulingIgnoredDuringExecution": { "nodeSelectorTerms": [zones(&["eu-west-1a"])] } });
+    client.post(&pods_url).json(&pod_with_affinity("lost", nowhere)).send().await.unwrap();
+    let message = "0/3 nodes are available: 3 node(s) didn't match Pod's node affinity/selector.";
+    for _ in 0..50 {
+        let pod = server.storage.pods().get("default", "lost").await.unwrap();
+        if pod["status"]["conditions"][0]["message"] == message {
+            assert!(pod["spec"]["nodeName"].is_null());
+            assert!(pod["metadata"]["annotations"]["krust.io/unsupported-fields"].is_null());
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the pod was never reported unschedulable");
+}
+
+#[tokio::test]
+async fn test_preferred_node_affinity_and_taints() {
+    let cluster = format!("{}    taints:\n      - key: maintenance\n        effect: PreferNoSchedule\n", CLUSTER);
+    let config = krust::Config::parse(&cluster).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+    let pods_url = server.url("/api/v1/namespaces/default/pods");
+
+    // The preferred terms' weights decide between otherwise equal nodes
+    let preferred = |zone: &str| json!({ "preferredDuringSchedulingIgnoredDuringExecution": [
+        { "weight": 80, "preference": zones(&[zone]) },
+        { "weight": 20, "preference": { "matchExpressions": [{ "key": "topology.kubernetes.io/zone", "operator": "DoesNotExist" }] } }
+    ] });
+    client.post(&pods_url).json(&pod_with_affinity("east", preferred("us-east-1a"))).send().await.unwrap();
+    assert_eq!(node_of(&server, "east").await, "east-1");
+
+    // but a PreferNoSchedule taint the pod doesn't tolerate outweighs them
+    client.post(&pods_url).json(&pod_with_affinity("avoiding", preferred("us-east-1b"))).send().await.unwrap();
+    assert_ne!(node_of(&server, "avoiding").await, "east-2");
+    let mut tolerating = pod_with_affinity("tolerating", preferred("us-east-1b"));
+    tolerating["spec"]["tolerations"] = json!([{ "key": "maintenance", "operator": "Exists" }]);
+    client.post(&pods_url).json(&tolerating).send().await.unwrap();
+    assert_eq!(node_of(&server, "tolerating").await, "east-2");
+}
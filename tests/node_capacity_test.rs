@@ -0,0 +1,145 @@
+use reqwest;
+use serde_json::{json, Value};
+
+mod common;
+
+async fn get_pod(client: &reqwest::Client, server: &common::TestServer, name: &str) -> Value {
+    client
+        .get(server.url(&format!("/api/v1/namespaces/default/pods/{}", name)))
+        .send()
+        .await
+        .expect("Failed to get pod")
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_scheduler_respects_node_pod_capacity() {
+    let config = krust::Config::parse("nodes:\n  krust-node:\n    pods: 2\n").unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let node: Value = client
+        .get(server.url("/api/v1/nodes/krust-node"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(node["status"]["capacity"]["pods"], "2");
+    assert_eq!(node["status"]["allocatable"]["pods"], "2");
+
+    for name in ["pod-1", "pod-2"] {
+        common::create_pod(&client, &server, common::pod(name, json!({}))).await;
+        server.wait_for_pod_running("default", name).await;
+    }
+
+    common::create_pod(&client, &server, common::pod("pod-3", json!({}))).await;
+    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+
+    let pod = get_pod(&client, &server, "pod-3").await;
+    assert_eq!(pod["status"]["phase"], "Pending");
+    assert!(pod["spec"]["nodeName"].is_null());
+
+    // Freeing a slot lets the pending pod in
+    client
+        .delete(server.url("/api/v1/namespaces/default/pods/pod-1"))
+        .send()
+        .await
+        .unwrap();
+    server.wait_for_pod_running("default", "pod-3").await;
+}
+
+#[tokio::test]
+async fn test_kubelet_rejects_pods_beyond_max_pods() {
+    // The scheduler believes there is room for 5 pods, the kubelet only runs 1
+    let config = krust::Config::parse("nodes:\n  krust-node:\n    pods: 5\n    maxPods: 1\n").unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    common::create_pod(&client, &server, common::pod("first", json!({}))).await;
+    server.wait_for_pod_running("default", "first").await;
+
+    common::create_pod(&client, &server, common::pod("second", json!({}))).await;
+    let mut pod = Value::Null;
+    for _ in 0..30 {
+        pod = get_pod(&client, &server, "second").await;
+        if pod["status"]["phase"] == "Failed" {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+
+    assert_eq!(pod["status"]["phase"], "Failed");
+    assert_eq!(pod["status"]["reason"], "OutOfpods");
+    assert!(pod["status"]["message"]
+        .as_str()
+        .unwrap()
+        .contains("used: 1, capacity: 1"));
+    assert_eq!(pod["spec"]["nodeName"], "krust-node");
+}
+
+#[test]
+fn test_config_node_settings() {
+    // Nodes other than krust-node are simulated
+    let config = krust::Config::parse("nodes:\n  other-node:\n    pods: 5\n").unwrap();
+    assert_eq!(config.node_names(), ["krust-node", "other-node"]);
+    assert_eq!(config.node("other-node").pods, 5);
+
+    let config = krust::Config::parse("nodes:\n  krust-node:\n    pods: 5\n").unwrap();
+    assert_eq!(config.node("krust-node").kubelet_max_pods(), 5);
+    assert_eq!(krust::Config::default().node("krust-node").pods, 110);
+}
+
+#[test]
+fn test_config_node_resources_default_to_the_host() {
+    let node = krust::Config::default().node("krust-node");
+    assert_eq!(node.cpu, krust::config::host_cpu());
+    assert_eq!(node.memory, krust::config::host_memory());
+    let capacity = node.capacity();
+    assert!(capacity.cpu_millis >= 1000);
+    assert!(capacity.memory_bytes > 0);
+
+    // Given ones override the host's
+    let config = krust::Config::parse("nodes:\n  krust-node:\n    cpu: 500m\n").unwrap();
+    assert_eq!(config.node("krust-node").cpu, "500m");
+    assert_eq!(config.node("krust-node").memory, krust::config::host_memory());
+}
+
+#[tokio::test]
+async fn test_scheduler_uses_host_cpu() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let node: Value = client.get(server.url("/api/v1/nodes/krust-node")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(node["status"]["capacity"]["cpu"], krust::config::host_cpu());
+    assert_eq!(node["status"]["allocatable"]["memory"], krust::config::host_memory());
+
+    // A pod asking for more CPUs than the host has never fits
+    let cpus: u64 = krust::config::host_cpu().parse().unwrap();
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/pods"))
+        .json(&json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": { "name": "too-big" },
+            "spec": {
+                "containers": [{
+                    "name": "app",
+                    "image": "nginx:latest",
+                    "resources": { "requests": { "cpu": (cpus + 1).to_string() } }
+                }]
+            }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+
+    let pod = get_pod(&client, &server, "too-big").await;
+    assert_eq!(pod["status"]["phase"], "Pending");
+    assert!(pod["spec"]["nodeName"].is_null());
+}
@@ -0,0 +1,187 @@
+use reqwest::header::CONTENT_TYPE;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+const CLUSTER: &str = r#"
+nodes:
+  krust-node: {}
+  worker-1: {}
+"#;
+
+fn pod_on(name: &str, node: &str, tolerations: Value) -> Value {
+    common::pod(name, json!({ "nodeSelector": { "kubernetes.io/hostname": node }, "tolerations": tolerations }))
+}
+
+// Patches the node as kubectl cordon, uncordon and taint do
+async fn patch_node(server: &common::TestServer, client: &reqwest::Client, name: &str, patch: Value) -> reqwest::Response {
+    client
+        .patch(server.url(&format!("/api/v1/nodes/{}", name)))
+        .header(CONTENT_TYPE, "application/strategic-merge-patch+json")
+        .json(&patch)
+        .send()
+        .await
+        .unwrap()
+}
+
+async fn wait_for_node_name(server: &common::TestServer, client: &reqwest::Client, name: &str) -> Value {
+    let url = server.url(&format!("/api/v1/namespaces/default/pods/{}", name));
+    for _ in 0..50 {
+        let pod = common::get(client, &url).await;
+        if pod["spec"]["nodeName"].is_string() {
+            return pod["spec"]["nodeName"].clone();
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("pod {} was never scheduled", name);
+}
+
+#[tokio::test]
+async fn test_cordoned_nodes_take_no_new_pods() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let resp = patch_node(&server, &client, "worker-1", json!({ "spec": { "unschedulable": true } })).await;
+    assert_eq!(resp.status(), 200);
+    let node: Value = resp.json().await.unwrap();
+    assert_eq!(node["spec"]["unschedulable"], true);
+    assert_eq!(node["spec"]["taints"][0]["key"], "node.kubernetes.io/unschedulable");
+    assert_eq!(node["spec"]["taints"][0]["effect"], "NoSchedule");
+    let cordoned = common::get(&client, &server.url("/api/v1/nodes?fieldSelector=spec.unschedulable%3Dtrue")).await;
+    assert_eq!(cordoned["items"].as_array().unwrap().len(), 1);
+    assert_eq!(cordoned["items"][0]["metadata"]["name"], "worker-1");
+
+    for (name, tolerations) in [
+        ("web", json!([])),
+        ("agent", json!([{ "key": "node.kubernetes.io/unschedulable", "operator": "Exists", "effect": "NoSchedule" }])),
+    ] {
+        let resp = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod_on(name, "worker-1", tolerations)).send().await.unwrap();
+        assert_eq!(resp.status(), 201);
+    }
+
+    // Pods tolerating the cordon still go there, as DaemonSet pods do
+    assert_eq!(wait_for_node_name(&server, &client, "agent").await, "worker-1");
+    let url = server.url("/krust/scheduling?namespace=default&name=web");
+    let mut report = Value::Null;
+    for _ in 0..30 {
+        report = common::get(&client, &url).await;
+        if report["pods"].as_array().is_some_and(|pods| !pods.is_empty()) {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    assert_eq!(
+        report["pods"][0]["message"],
+        "0/2 nodes are available: 1 node(s) didn't match Pod's node affinity/selector, 1 node(s) were unschedulable."
+    );
+
+    // Uncordoning lets the waiting pod on, and takes the taint away
+    let resp = patch_node(&server, &client, "worker-1", json!({ "spec": { "unschedulable": null } })).await;
+    assert_eq!(resp.status(), 200);
+    let node: Value = resp.json().await.unwrap();
+    assert!(node["spec"]["taints"].is_null(), "{}", node);
+    assert_eq!(wait_for_node_name(&server, &client, "web").await, "worker-1");
+}
+
+#[tokio::test]
+async fn test_drain_evicts_the_pods_of_a_cordoned_node() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+    for name in ["web-1", "web-2"] {
+        let resp = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod_on(name, "worker-1", json!([]))).send().await.unwrap();
+        assert_eq!(resp.status(), 201);
+        server.wait_for_pod_running("default", name).await;
+    }
+
+    // What kubectl drain does: cordon, then evict the node's pods and wait
+    // for them to go
+    let resp = patch_node(&server, &client, "worker-1", json!({ "spec": { "unschedulable": true } })).await;
+    assert_eq!(resp.status(), 200);
+    let pods = common::get(&client, &server.url("/api/v1/pods?fieldSelector=spec.nodeName%3Dworker-1")).await;
+    let names: Vec<&str> = pods["items"].as_array().unwrap().iter().map(|pod| pod["metadata"]["name"].as_str().unwrap()).collect();
+    assert_eq!(names, ["web-1", "web-2"]);
+    for name in &names {
+        let resp = client
+            .post(server.url(&format!("/api/v1/namespaces/default/pods/{}/eviction", name)))
+            .json(&json!({ "apiVersion": "policy/v1", "kind": "Eviction", "metadata": { "name": name, "namespace": "default" } }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 201);
+    }
+    let mut remaining = names.len();
+    for _ in 0..100 {
+        let pods = common::get(&client, &server.url("/api/v1/pods?fieldSelector=spec.nodeName%3Dworker-1")).await;
+        remaining = pods["items"].as_array().unwrap().len();
+        if remaining == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(remaining, 0);
+
+    // Recreated, a pod goes to the node left
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/pods"))
+        .json(&json!({ "apiVersion": "v1", "kind": "Pod", "metadata": { "name": "web-1" }, "spec": { "containers": [{ "name": "app", "image": "nginx" }] } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    assert_eq!(wait_for_node_name(&server, &client, "web-1").await, "krust-node");
+}
+
+#[tokio::test]
+async fn test_node_taints_can_be_changed() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+    let mut watch = client.get(server.url("/api/v1/nodes?watch=true")).send().await.unwrap();
+
+    let taints = json!([{ "key": "dedicated", "value": "db", "effect": "NoExecute" }]);
+    let resp = patch_node(&server, &client, "worker-1", json!({ "spec": { "taints": taints } })).await;
+    assert_eq!(resp.status(), 200);
+    let node = common::get(&client, &server.url("/api/v1/nodes/worker-1")).await;
+    assert_eq!(node["spec"]["taints"], taints);
+
+    // Watches see the change after the nodes they started with, among the
+    // status their kubelets report
+    let mut events = Vec::new();
+    let mut buffer = String::new();
+    while !events.iter().any(|e: &Value| e["object"]["spec"]["taints"] == taints) {
+        let chunk = tokio::time::timeout(Duration::from_secs(5), watch.chunk()).await.unwrap().unwrap().unwrap();
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+        while let Some(end) = buffer.find('\n') {
+            events.push(serde_json::from_str::<Value>(&buffer[..end]).unwrap());
+            buffer.drain(..=end);
+        }
+    }
+    assert_eq!(events[0]["type"], "ADDED");
+    assert_eq!(events[1]["type"], "ADDED");
+    let changed = events.iter().position(|e| e["object"]["spec"]["taints"] == taints).unwrap();
+    assert!(changed >= 2);
+    assert_eq!(events[changed]["type"], "MODIFIED");
+
+    // Replacing a node that changed since it was read is a conflict
+    let mut stale = node.clone();
+    stale["metadata"]["resourceVersion"] = json!("1");
+    stale["spec"] = json!({});
+    let resp = client.put(server.url("/api/v1/nodes/worker-1")).json(&stale).send().await.unwrap();
+    assert_eq!(resp.status(), 409);
+
+    // Only the spec is the API's to change
+    let resp = patch_node(&server, &client, "worker-1", json!({ "spec": { "taints": [{ "key": "dedicated", "effect": "Sometimes" }] } })).await;
+    assert_eq!(resp.status(), 422);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(
+        status["message"],
+        "Node \"worker-1\" is invalid: spec.taints[0].effect: Unsupported value: \"Sometimes\": supported values: \"NoSchedule\", \"PreferNoSchedule\", \"NoExecute\""
+    );
+    let resp = patch_node(&server, &client, "worker-1", json!({ "metadata": { "labels": { "disk": "ssd" } } })).await;
+    assert_eq!(resp.status(), 422);
+    let resp = patch_node(&server, &client, "missing", json!({ "spec": { "unschedulable": true } })).await;
+    assert_eq!(resp.status(), 404);
+}
@@ -0,0 +1,122 @@
+use reqwest;
+use serde_json::json;
+
+mod common;
+
+const CLUSTER: &str = r#"
+nodeProfiles:
+  gpu:
+    cpu: "32"
+    memory: 128Gi
+    instanceType: g5.8xlarge
+    labels:
+      accelerator: nvidia
+    taints:
+      - key: nvidia.com/gpu
+        effect: NoSchedule
+nodes:
+  gpu-1:
+    profile: gpu
+    zone: us-east-1a
+    region: us-east-1
+    labels:
+      pool: training
+  arm-1:
+    architecture: arm64
+    zone: us-east-1b
+    region: us-east-1
+"#;
+
+#[tokio::test]
+async fn test_nodes_carry_profile_labels_and_taints() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let nodes = common::get(&client, &server.url("/api/v1/nodes")).await;
+    let names: Vec<&str> = nodes["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|n| n["metadata"]["name"].as_str().unwrap())
+        .collect();
+    assert_eq!(names, ["krust-node", "arm-1", "gpu-1"]);
+
+    let gpu = common::get(&client, &server.url("/api/v1/nodes/gpu-1")).await;
+    let labels = &gpu["metadata"]["labels"];
+    assert_eq!(labels["kubernetes.io/hostname"], "gpu-1");
+    assert_eq!(labels["node.kubernetes.io/instance-type"], "g5.8xlarge");
+    assert_eq!(labels["topology.kubernetes.io/zone"], "us-east-1a");
+    assert_eq!(labels["topology.kubernetes.io/region"], "us-east-1");
+    // Labels of the node are merged with the profile's
+    assert_eq!(labels["accelerator"], "nvidia");
+    assert_eq!(labels["pool"], "training");
+    assert_eq!(gpu["spec"]["taints"][0]["key"], "nvidia.com/gpu");
+    assert_eq!(gpu["status"]["capacity"]["cpu"], "32");
+    assert_eq!(gpu["status"]["allocatable"]["memory"], "128Gi");
+
+    let arm = common::get(&client, &server.url("/api/v1/nodes/arm-1")).await;
+    assert_eq!(arm["metadata"]["labels"]["kubernetes.io/arch"], "arm64");
+    assert_eq!(arm["status"]["nodeInfo"]["architecture"], "arm64");
+    assert!(arm["spec"]["taints"].is_null());
+
+    let resp = client.get(server.url("/api/v1/nodes/missing")).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_node_selector_places_pods_on_simulated_nodes() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    common::create_pod(&client, &server, common::pod("on-arm", json!({ "nodeSelector": { "kubernetes.io/arch": "arm64" } }))).await;
+    server.wait_for_pod_running("default", "on-arm").await;
+    let pod = common::get(&client, &server.url("/api/v1/namespaces/default/pods/on-arm")).await;
+    assert_eq!(pod["spec"]["nodeName"], "arm-1");
+
+    // No node matches, so the pod stays pending
+    common::create_pod(&client, &server, common::pod("nowhere", json!({ "nodeSelector": { "topology.kubernetes.io/zone": "eu-west-1a" } }))).await;
+    tokio::time::sleep(std::time::Duration::from_secs(2)).await;
+    let pod = common::get(&client, &server.url("/api/v1/namespaces/default/pods/nowhere")).await;
+    assert_eq!(pod["status"]["phase"], "Pending");
+    assert!(pod["spec"]["nodeName"].is_null());
+}
+
+#[tokio::test]
+async fn test_tainted_nodes_only_take_tolerating_pods() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+    let on_gpu = json!({ "accelerator": "nvidia" });
+
+    common::create_pod(&client, &server, common::pod("intolerant", json!({ "nodeSelector": on_gpu }))).await;
+    let tolerant = common::pod(
+        "tolerant",
+        json!({
+            "nodeSelector": on_gpu,
+            "tolerations": [{ "key": "nvidia.com/gpu", "operator": "Exists", "effect": "NoSchedule" }]
+        }),
+    );
+    common::create_pod(&client, &server, tolerant).await;
+
+    server.wait_for_pod_running("default", "tolerant").await;
+    let pod = common::get(&client, &server.url("/api/v1/namespaces/default/pods/tolerant")).await;
+    assert_eq!(pod["spec"]["nodeName"], "gpu-1");
+
+    let pod = common::get(&client, &server.url("/api/v1/namespaces/default/pods/intolerant")).await;
+    assert_eq!(pod["status"]["phase"], "Pending");
+    assert!(pod["spec"]["nodeName"].is_null());
+}
+
+#[test]
+fn test_invalid_node_profiles_are_rejected() {
+    let unknown = krust::Config::parse("nodes:\n  worker-1:\n    profile: missing\n").unwrap_err();
+    assert!(unknown.to_string().contains("unknown profile"), "{}", unknown);
+
+    let effect = "nodes:\n  worker-1:\n    taints:\n      - key: dedicated\n        effect: Sometimes\n";
+    assert!(krust::Config::parse(effect).is_err());
+
+    let memory = "nodes:\n  worker-1:\n    memory: lots\n";
+    assert!(krust::Config::parse(memory).is_err());
+}
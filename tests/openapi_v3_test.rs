@@ -0,0 +1,90 @@
+use serde_json::Value;
+
+mod common;
+
+// The kinds a document has schemas for, as group/version/kind
+fn kinds(document: &Value) -> Vec<String> {
+    document["components"]["schemas"]
+        .as_object()
+        .unwrap()
+        .values()
+        .flat_map(|schema| schema["x-kubernetes-group-version-kind"].as_array().cloned().unwrap_or_default())
+        .map(|gvk| format!("{}/{}/{}", gvk["group"].as_str().unwrap(), gvk["version"].as_str().unwrap(), gvk["kind"].as_str().unwrap()))
+        .collect()
+}
+
+#[tokio::test]
+async fn test_openapi_v3_core_document() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let paths = common::get(&client, &server.url("/openapi/v3")).await;
+    let url = paths["paths"]["api/v1"]["serverRelativeURL"].as_str().unwrap();
+    assert!(url.starts_with("/openapi/v3/api/v1?hash="), "{}", url);
+    assert!(paths["paths"]["apis/apps/v1"]["serverRelativeURL"].is_string());
+    assert!(paths["paths"]["apis/autoscaling/v2"]["serverRelativeURL"].is_string());
+
+    let document = common::get(&client, &server.url(url)).await;
+    assert_eq!(document["openapi"], "3.0.0");
+    let schemas = &document["components"]["schemas"];
+    let pod = &schemas["io.k8s.api.core.v1.Pod"];
+    assert_eq!(pod["x-kubernetes-group-version-kind"][0]["kind"], "Pod");
+    assert_eq!(pod["properties"]["spec"]["allOf"][0]["$ref"], "#/components/schemas/io.k8s.api.core.v1.PodSpec");
+    assert!(pod["properties"]["spec"]["description"].is_string());
+    // The schemas are complete, down to what the objects refer to
+    let containers = &schemas["io.k8s.api.core.v1.PodSpec"]["properties"]["containers"];
+    assert_eq!(containers["items"]["$ref"], "#/components/schemas/io.k8s.api.core.v1.Container");
+    assert!(schemas["io.k8s.api.core.v1.Container"]["properties"]["image"]["description"].is_string());
+    assert!(schemas["io.k8s.apimachinery.pkg.apis.meta.v1.ObjectMeta"]["properties"]["labels"].is_object());
+    assert_eq!(schemas["io.k8s.api.core.v1.PodList"]["properties"]["items"]["items"]["$ref"], "#/components/schemas/io.k8s.api.core.v1.Pod");
+
+    let pods = &document["paths"]["/api/v1/namespaces/{namespace}/pods"];
+    assert_eq!(pods["get"]["operationId"], "listCoreV1NamespacedPod");
+    assert_eq!(pods["post"]["operationId"], "createCoreV1NamespacedPod");
+    let pod_path = &document["paths"]["/api/v1/namespaces/{namespace}/pods/{name}"];
+    assert_eq!(pod_path["get"]["operationId"], "readCoreV1NamespacedPod");
+    assert_eq!(pod_path["patch"]["x-kubernetes-group-version-kind"]["kind"], "Pod");
+    assert_eq!(
+        document["paths"]["/api/v1/namespaces/{namespace}/pods/{name}/status"]["get"]["operationId"],
+        "readCoreV1NamespacedPodStatus"
+    );
+    assert_eq!(document["paths"]["/api/v1/nodes/{name}"]["get"]["operationId"], "readCoreV1Node");
+
+    let deployments = common::get(&client, &server.url("/openapi/v3/apis/apps/v1")).await;
+    let scale = &deployments["paths"]["/apis/apps/v1/namespaces/{namespace}/deployments/{name}/scale"];
+    assert_eq!(scale["put"]["operationId"], "replaceAppsV1NamespacedDeploymentScale");
+    assert_eq!(
+        scale["get"]["responses"]["200"]["content"]["application/json"]["schema"]["$ref"],
+        "#/components/schemas/io.k8s.api.autoscaling.v1.Scale"
+    );
+
+    let (status, missing) = common::lookup(&client, &server.url("/openapi/v3/apis/example.com/v1")).await;
+    assert_eq!(status, 404);
+    assert_eq!(missing["kind"], "Status");
+    assert_eq!(missing["reason"], "NotFound");
+    assert_eq!(missing["code"], 404);
+}
+
+#[tokio::test]
+async fn test_openapi_v3_covers_discovery() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let paths = common::get(&client, &server.url("/openapi/v3")).await;
+
+    // Every resource discovery offers has a schema in its group version's
+    // document
+    for (path, entry) in paths["paths"].as_object().unwrap() {
+        let list = common::get(&client, &server.url(&format!("/{}", path))).await;
+        let document = common::get(&client, &server.url(entry["serverRelativeURL"].as_str().unwrap())).await;
+        let kinds = kinds(&document);
+        let group_version = list["groupVersion"].as_str().unwrap();
+        let (group, version) = group_version.split_once('/').unwrap_or(("", group_version));
+        for resource in list["resources"].as_array().unwrap() {
+            if resource["name"].as_str().unwrap().contains('/') {
+                continue;
+            }
+            let kind = format!("{}/{}/{}", group, version, resource["kind"].as_str().unwrap());
+            assert!(kinds.contains(&kind), "no schema for {} in {}", kind, path);
+        }
+    }
+}
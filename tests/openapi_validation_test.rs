@@ -2,6 +2,8 @@ use std::process::Command;
 use std::thread;
 use std::time::Duration;
 
+mod common;
+
 #[test]
 #[ignore] // Run with: cargo test --test openapi_validation_test -- --ignored --nocapture
 fn test_kubectl_validation_without_flag() {
@@ -15,10 +17,7 @@ fn test_kubectl_validation_without_flag() {
     
     thread::sleep(Duration::from_secs(1));
     
-    Command::new("rm")
-        .args(&["-f", "krust.db"])
-        .output()
-        .ok();
+    common::reset_data_dir();
     
     // Start server
     println!("Starting Krust server...");
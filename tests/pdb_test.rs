@@ -1,16 +1,13 @@
 use reqwest;
 use serde_json::json;
 
+mod common;
+
 #[tokio::test]
 async fn test_pdb_crud() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/policy/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/policy/v1");
     
     // Create namespace first
     let namespace = json!({
@@ -22,7 +19,7 @@ async fn test_pdb_crud() {
     });
     
     let _ = client
-        .post("http://localhost:6443/api/v1/namespaces")
+        .post(server.url("/api/v1/namespaces"))
         .json(&namespace)
         .send()
         .await;
@@ -131,14 +128,9 @@ async fn test_pdb_crud() {
 
 #[tokio::test]
 async fn test_pdb_with_max_unavailable() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/policy/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/policy/v1");
     
     // Create namespace first
     let namespace = json!({
@@ -150,7 +142,7 @@ async fn test_pdb_with_max_unavailable() {
     });
     
     let _ = client
-        .post("http://localhost:6443/api/v1/namespaces")
+        .post(server.url("/api/v1/namespaces"))
         .json(&namespace)
         .send()
         .await;
@@ -0,0 +1,132 @@
+use serde_json::{json, Value};
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+mod common;
+
+const CLUSTER: &str = r#"
+nodes:
+  krust-node:
+    cpu: "2"
+  zone-a:
+    cpu: "64"
+    zone: us-east-1a
+  zone-b-1:
+    cpu: "2"
+    zone: us-east-1b
+  zone-b-2:
+    cpu: "2"
+    zone: us-east-1b
+"#;
+
+// A pod labelled app=`app`, requesting a quarter of a small node
+fn app_pod(name: &str, app: &str, mut spec: Value) -> Value {
+    spec["containers"] = json!([{ "name": "app", "image": "nginx:latest", "resources": { "requests": { "cpu": "500m" } } }]);
+    common::with_metadata(common::pod(name, spec), json!({ "labels": { "app": app } }))
+}
+
+fn term(app: &str, topology_key: &str) -> Value {
+    json!({ "labelSelector": { "matchLabels": { "app": app } }, "topologyKey": topology_key })
+}
+
+// Waits for the pod to be bound or found unschedulable, returning its node
+// or why it can't have one
+async fn placement(server: &common::TestServer, name: &str) -> Result<String, String> {
+    for _ in 0..50 {
+        let pod = server.storage.pods().get("default", name).await.unwrap();
+        if let Some(node) = pod["spec"]["nodeName"].as_str() {
+            return Ok(node.to_string());
+        }
+        let condition = &pod["status"]["conditions"][0];
+        if condition["reason"] == "Unschedulable" {
+            return Err(condition["message"].as_str().unwrap().to_string());
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("pod {} was never scheduled", name);
+}
+
+#[tokio::test]
+async fn test_pod_affinity_and_anti_affinity() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    // One replica per node, and none once every node has one
+    let apart = json!({ "affinity": { "podAntiAffinity": { "requiredDuringSchedulingIgnoredDuringExecution": [term("web", "kubernetes.io/hostname")] } } });
+    let mut nodes = Vec::new();
+    for i in 0..4 {
+        let name = format!("web-{}", i);
+        common::create_pod(&client, &server, app_pod(&name, "web", apart.clone())).await;
+        nodes.push(placement(&server, &name).await.unwrap());
+    }
+    nodes.sort();
+    assert_eq!(nodes, ["krust-node", "zone-a", "zone-b-1", "zone-b-2"]);
+    common::create_pod(&client, &server, app_pod("web-4", "web", apart)).await;
+    assert_eq!(placement(&server, "web-4").await.unwrap_err(), "0/4 nodes are available: 4 node(s) didn't match pod anti-affinity rules.");
+
+    // A pod joins those it has affinity for in their zone, and keeps out
+    // of the way of those with anti-affinity for it
+    let near_db = json!({ "affinity": { "podAffinity": { "requiredDuringSchedulingIgnoredDuringExecution": [term("db", "topology.kubernetes.io/zone")] } } });
+    let db = json!({
+        "nodeSelector": { "kubernetes.io/hostname": "zone-b-1" },
+        "affinity": { "podAntiAffinity": { "requiredDuringSchedulingIgnoredDuringExecution": [term("batch", "kubernetes.io/hostname")] } }
+    });
+    common::create_pod(&client, &server, app_pod("db", "db", db)).await;
+    assert_eq!(placement(&server, "db").await.unwrap(), "zone-b-1");
+    common::create_pod(&client, &server, app_pod("batch", "batch", near_db)).await;
+    assert_eq!(placement(&server, "batch").await.unwrap(), "zone-b-2");
+
+    // The first of a group attracted to each other goes anywhere
+    let together = json!({ "affinity": { "podAffinity": { "requiredDuringSchedulingIgnoredDuringExecution": [term("group", "kubernetes.io/hostname")] } } });
+    common::create_pod(&client, &server, app_pod("group-0", "group", together.clone())).await;
+    let first = placement(&server, "group-0").await.unwrap();
+    common::create_pod(&client, &server, app_pod("group-1", "group", together)).await;
+    assert_eq!(placement(&server, "group-1").await.unwrap(), first);
+}
+
+#[tokio::test]
+async fn test_topology_spread_constraints() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    // The big node would take them all, but they're spread across zones,
+    // leaving out the node without one
+    let spread = json!({ "topologySpreadConstraints": [{
+        "maxSkew": 1,
+        "topologyKey": "topology.kubernetes.io/zone",
+        "whenUnsatisfiable": "DoNotSchedule",
+        "labelSelector": { "matchLabels": { "app": "web" } }
+    }] });
+    let mut zones: BTreeMap<String, usize> = BTreeMap::new();
+    for i in 0..4 {
+        let name = format!("web-{}", i);
+        common::create_pod(&client, &server, app_pod(&name, "web", spread.clone())).await;
+        let node = placement(&server, &name).await.unwrap();
+        assert_ne!(node, "krust-node");
+        *zones.entry(node.trim_end_matches(char::is_numeric).trim_end_matches('-').to_string()).or_default() += 1;
+    }
+    assert_eq!(zones, BTreeMap::from([("zone-a".to_string(), 2), ("zone-b".to_string(), 2)]));
+
+    // A node without the topology label is ruled out
+    let on_local = json!({ "nodeSelector": { "kubernetes.io/hostname": "krust-node" } });
+    let mut local = app_pod("local", "web", on_local);
+    local["spec"]["topologySpreadConstraints"] = spread["topologySpreadConstraints"].clone();
+    common::create_pod(&client, &server, local).await;
+    let message = placement(&server, "local").await.unwrap_err();
+    assert!(message.contains("1 node(s) didn't match pod topology spread constraints (missing required label)"), "{}", message);
+
+    // ScheduleAnyway only prefers the emptier zone
+    let mut soft = spread.clone();
+    soft["topologySpreadConstraints"][0]["whenUnsatisfiable"] = json!("ScheduleAnyway");
+    soft["topologySpreadConstraints"][0]["labelSelector"]["matchLabels"]["app"] = json!("worker");
+    let mut workers = Vec::new();
+    for i in 0..2 {
+        let name = format!("worker-{}", i);
+        common::create_pod(&client, &server, app_pod(&name, "worker", soft.clone())).await;
+        workers.push(placement(&server, &name).await.unwrap());
+    }
+    assert_eq!(workers[0], "zone-a");
+    assert!(workers[1].starts_with("zone-b"), "{:?}", workers);
+}
@@ -1,16 +1,13 @@
 use reqwest;
 use serde_json::json;
 
+mod common;
+
 #[tokio::test]
 async fn test_pod_exec_websocket() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Create a Pod
     let pod = json!({
@@ -60,14 +57,9 @@ async fn test_pod_exec_websocket() {
 
 #[tokio::test]
 async fn test_pod_attach_websocket() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Create a Pod
     let pod = json!({
@@ -117,14 +109,9 @@ async fn test_pod_attach_websocket() {
 
 #[tokio::test]
 async fn test_pod_portforward() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Create a Pod with a port
     let pod = json!({
@@ -153,6 +140,9 @@ async fn test_pod_portforward() {
         .await
         .unwrap();
     
+    // Port-forwarding is only allowed once the pod is running
+    server.wait_for_pod_running("default", "test-pod-portforward").await;
+    
     // Test portforward endpoint exists (implemented with WebSocket support)
     let response = client
         .get(&format!("{}/namespaces/default/pods/test-pod-portforward/portforward", base_url))
@@ -0,0 +1,76 @@
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+// A pod the db Service selects
+fn db_pod(name: &str, spec: Value) -> Value {
+    common::with_metadata(common::pod(name, spec), json!({ "labels": { "app": "db" } }))
+}
+
+#[tokio::test]
+async fn test_subdomain_pods_are_published_by_hostname() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let service = json!({
+        "apiVersion": "v1",
+        "kind": "Service",
+        "metadata": { "name": "db" },
+        "spec": { "clusterIP": "None", "selector": { "app": "db" }, "ports": [{ "port": 5432 }] }
+    });
+    let resp = client.post(server.url("/api/v1/namespaces/default/services")).json(&service).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    for pod in [db_pod("db-0", json!({ "hostname": "db-0", "subdomain": "db" })), db_pod("db-other", json!({ "hostname": "other" }))] {
+        let resp = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod).send().await.unwrap();
+        assert_eq!(resp.status(), 201);
+    }
+    server.wait_for_pod_running("default", "db-0").await;
+    server.wait_for_pod_running("default", "db-other").await;
+
+    // Only the pod in the service's subdomain gets a hostname in its endpoints
+    for _ in 0..50 {
+        let endpoints = server.storage.endpoints().get("default", "db").await.unwrap();
+        let addresses = endpoints["subsets"][0]["addresses"].as_array().cloned().unwrap_or_default();
+        if addresses.len() == 2 {
+            let hostname = |pod: &str| addresses.iter().find(|a| a["targetRef"]["name"] == pod).unwrap()["hostname"].clone();
+            assert_eq!(hostname("db-0"), "db-0");
+            assert!(hostname("db-other").is_null());
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the endpoints never listed both pods");
+}
+
+#[tokio::test]
+async fn test_hostnames_are_checked() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pods_url = server.url("/api/v1/namespaces/default/pods");
+
+    let resp = client.post(&pods_url).json(&db_pod("upper", json!({ "hostname": "DB_0" }))).send().await.unwrap();
+    assert_eq!(resp.status(), 422);
+
+    // A fully qualified hostname longer than the kernel allows fails the pod
+    let long = db_pod("long", json!({ "hostname": "a".repeat(40), "subdomain": "db", "setHostnameAsFQDN": true }));
+    let created: Value = client.post(&pods_url).json(&long).send().await.unwrap().json().await.unwrap();
+    let uid = created["metadata"]["uid"].as_str().unwrap();
+    for _ in 0..50 {
+        let pod = server.storage.pods().get("default", "long").await.unwrap();
+        if pod["status"]["phase"] == "Failed" {
+            let url = server.url(&format!("/api/v1/namespaces/default/events?fieldSelector=involvedObject.uid%3D{}", uid));
+            let events: Value = client.get(url).send().await.unwrap().json().await.unwrap();
+            let failed = events["items"].as_array().unwrap().iter().find(|e| e["reason"] == "FailedCreatePodSandBox").unwrap();
+            let fqdn = format!("{}.db.default.svc.cluster.local", "a".repeat(40));
+            let message = format!(
+                "Failed to create pod sandbox: failed to construct FQDN from pod hostname and cluster domain, FQDN {} is too long (64 characters is the max, 69 characters requested)",
+                fqdn
+            );
+            assert_eq!(failed["message"], message);
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("the pod never failed");
+}
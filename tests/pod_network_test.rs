@@ -0,0 +1,61 @@
+use krust::runtime::pod_network::{self, next_free};
+use serde_json::{json, Value};
+use std::collections::{BTreeSet, HashSet};
+use std::net::Ipv4Addr;
+
+mod common;
+
+#[test]
+fn test_pods_get_the_lowest_free_address() {
+    assert_eq!(next_free(&BTreeSet::new()), Some(Ipv4Addr::new(10, 244, 0, 2)));
+    let used = BTreeSet::from([Ipv4Addr::new(10, 244, 0, 2), Ipv4Addr::new(10, 244, 0, 4)]);
+    assert_eq!(next_free(&used), Some(Ipv4Addr::new(10, 244, 0, 3)));
+
+    // The gateway and the range Docker hands out are never given to pods
+    assert!(!pod_network::is_pod_address(pod_network::GATEWAY));
+    assert!(pod_network::is_pod_address(Ipv4Addr::new(10, 244, 127, 254)));
+    assert!(!pod_network::is_pod_address(Ipv4Addr::new(10, 244, 128, 1)));
+}
+
+#[tokio::test]
+async fn test_pods_get_addresses_of_their_own() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    for name in ["web-1", "web-2", "web-3"] {
+        let resp = client
+            .post(server.url("/api/v1/namespaces/default/pods"))
+            .json(&json!({
+                "apiVersion": "v1",
+                "kind": "Pod",
+                "metadata": { "name": name },
+                "spec": { "containers": [{ "name": "app", "image": "nginx" }] }
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 201);
+    }
+
+    let mut addresses = HashSet::new();
+    for name in ["web-1", "web-2", "web-3"] {
+        server.wait_for_pod_running("default", name).await;
+        let pod: Value = client
+            .get(server.url(&format!("/api/v1/namespaces/default/pods/{}", name)))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        let ip = pod["status"]["podIP"].as_str().unwrap().to_string();
+        assert!(pod_network::is_pod_address(ip.parse().unwrap()), "{}", ip);
+        assert_eq!(pod["status"]["podIPs"], json!([{ "ip": ip }]));
+
+        // A pod keeps the address it was given
+        let uid = pod["metadata"]["uid"].as_str().unwrap();
+        let again = pod_network::assign(&server.storage, uid, &pod["spec"], "127.0.0.1").await.unwrap();
+        assert_eq!(again, ip);
+        addresses.insert(ip);
+    }
+    assert_eq!(addresses.len(), 3);
+}
@@ -0,0 +1,135 @@
+use reqwest;
+use serde_json::{json, Value};
+
+mod common;
+
+fn patched_pod(name: &str) -> Value {
+    let pod = common::pod(name, json!({ "containers": [{ "name": "app", "image": "nginx:latest" }] }));
+    common::with_metadata(pod, json!({ "labels": { "app": "patched" } }))
+}
+
+async fn patch(client: &reqwest::Client, url: String, body: Value) -> Value {
+    let resp = client
+        .patch(url)
+        .header("Content-Type", "application/merge-patch+json")
+        .json(&body)
+        .send()
+        .await
+        .expect("Failed to patch pod");
+    assert_eq!(resp.status(), 200);
+    resp.json().await.unwrap()
+}
+
+async fn get_pod(client: &reqwest::Client, server: &common::TestServer, name: &str) -> Value {
+    client
+        .get(server.url(&format!("/api/v1/namespaces/default/pods/{}", name)))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap()
+}
+
+#[tokio::test]
+async fn test_concurrent_label_patches_are_all_kept() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    common::create_pod(&client, &server, patched_pod("labelled")).await;
+
+    let url = server.url("/api/v1/namespaces/default/pods/labelled");
+    let patches = (0..20).map(|i| {
+        let body = json!({ "metadata": { "labels": { format!("key-{}", i): i.to_string() } } });
+        patch(&client, url.clone(), body)
+    });
+    futures::future::join_all(patches).await;
+
+    let pod = get_pod(&client, &server, "labelled").await;
+    let labels = pod["metadata"]["labels"].as_object().unwrap();
+    assert_eq!(labels["app"], "patched");
+    for i in 0..20 {
+        assert_eq!(labels[&format!("key-{}", i)], i.to_string());
+    }
+    // Every patch got a resourceVersion of its own
+    assert!(pod["metadata"]["resourceVersion"].as_str().unwrap().parse::<i64>().unwrap() > 20);
+}
+
+#[tokio::test]
+async fn test_merge_patch_null_removes_only_that_label() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    common::create_pod(&client, &server, patched_pod("unlabelled")).await;
+
+    let url = server.url("/api/v1/namespaces/default/pods/unlabelled");
+    patch(&client, url.clone(), json!({ "metadata": { "labels": { "tier": "web" } } })).await;
+    let patched = patch(&client, url, json!({ "metadata": { "labels": { "app": null } } })).await;
+
+    assert_eq!(patched["metadata"]["labels"], json!({ "tier": "web" }));
+    assert_eq!(patched["spec"]["containers"][0]["image"], "nginx:latest");
+}
+
+#[tokio::test]
+async fn test_status_patch_keeps_other_status_fields_and_spec() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    common::create_pod(&client, &server, patched_pod("status-patched")).await;
+    server.wait_for_pod_running("default", "status-patched").await;
+
+    let url = server.url("/api/v1/namespaces/default/pods/status-patched/status");
+    let label_url = server.url("/api/v1/namespaces/default/pods/status-patched");
+    tokio::join!(
+        patch(&client, url.clone(), json!({ "status": { "message": "checked" } })),
+        patch(&client, label_url, json!({ "metadata": { "labels": { "checked": "true" } } })),
+    );
+    patch(&client, url, json!({ "status": { "reason": "Checked" } })).await;
+
+    let pod = get_pod(&client, &server, "status-patched").await;
+    assert_eq!(pod["status"]["message"], "checked");
+    assert_eq!(pod["status"]["reason"], "Checked");
+    assert_eq!(pod["status"]["phase"], "Running");
+    assert!(!pod["status"]["containerStatuses"].as_array().unwrap().is_empty());
+    assert_eq!(pod["metadata"]["labels"]["checked"], "true");
+    assert_eq!(pod["spec"]["nodeName"], "krust-node");
+}
+
+#[tokio::test]
+async fn test_json_patch_operations_apply_to_the_pod() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    common::create_pod(&client, &server, patched_pod("json-patched")).await;
+
+    let url = server.url("/api/v1/namespaces/default/pods/json-patched");
+    let operations = json!([
+        { "op": "test", "path": "/metadata/labels/app", "value": "patched" },
+        { "op": "add", "path": "/metadata/labels/tier", "value": "web" },
+        { "op": "remove", "path": "/metadata/labels/app" },
+        { "op": "replace", "path": "/spec/containers/0/image", "value": "nginx:1.25" }
+    ]);
+    let resp = client
+        .patch(&url)
+        .header("Content-Type", "application/json-patch+json")
+        .json(&operations)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let patched: Value = resp.json().await.unwrap();
+    assert_eq!(patched["metadata"]["labels"], json!({ "tier": "web" }));
+    assert_eq!(patched["spec"]["containers"][0]["image"], "nginx:1.25");
+
+    let pod = get_pod(&client, &server, "json-patched").await;
+    assert_eq!(pod["metadata"]["labels"], json!({ "tier": "web" }));
+    assert_eq!(pod["spec"]["containers"][0]["image"], "nginx:1.25");
+
+    // A failed test operation leaves the pod as it was
+    let resp = client
+        .patch(&url)
+        .header("Content-Type", "application/json-patch+json")
+        .json(&json!([{ "op": "test", "path": "/metadata/labels/tier", "value": "db" }]))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 422);
+    let pod = get_pod(&client, &server, "json-patched").await;
+    assert_eq!(pod["metadata"]["labels"], json!({ "tier": "web" }));
+}
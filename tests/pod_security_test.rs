@@ -0,0 +1,188 @@
+use reqwest;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+fn namespace(name: &str, labels: Value) -> Value {
+    json!({ "apiVersion": "v1", "kind": "Namespace", "metadata": { "name": name, "labels": labels } })
+}
+
+fn warnings(resp: &reqwest::Response) -> Vec<String> {
+    resp.headers()
+        .get_all("warning")
+        .iter()
+        .map(|value| value.to_str().unwrap().to_string())
+        .collect()
+}
+
+#[tokio::test]
+async fn test_namespace_labels_enforce_and_warn() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let labels = json!({
+        "pod-security.kubernetes.io/enforce": "baseline",
+        "pod-security.kubernetes.io/warn": "restricted",
+        "pod-security.kubernetes.io/warn-version": "v1.30"
+    });
+    let resp = client.post(server.url("/api/v1/namespaces")).json(&namespace("secure", labels)).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let pods = server.url("/api/v1/namespaces/secure/pods");
+
+    // Privileged containers and host namespaces break the baseline
+    let privileged = json!({
+        "hostNetwork": true,
+        "containers": [
+            { "name": "app", "image": "nginx", "securityContext": { "privileged": true } },
+            { "name": "debug", "image": "busybox", "securityContext": { "privileged": true, "capabilities": { "add": ["SYS_ADMIN"] } } }
+        ]
+    });
+    let resp = client.post(&pods).json(&common::pod("privileged", privileged)).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "Forbidden");
+    assert_eq!(
+        status["message"],
+        "pods \"privileged\" is forbidden: violates PodSecurity \"baseline:latest\": \
+         non-default capabilities (container \"debug\" must not include \"SYS_ADMIN\" in securityContext.capabilities.add), \
+         host namespaces (hostNetwork=true), \
+         privileged (containers \"app\", \"debug\" must not set securityContext.privileged=true)"
+    );
+
+    // A plain pod meets the baseline but not the restricted level it's
+    // warned about
+    let resp = client.post(&pods).json(&common::pod("plain", json!({}))).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    assert_eq!(
+        warnings(&resp),
+        vec![
+            "299 - \"would violate PodSecurity \\\"restricted:v1.30\\\": \
+             allowPrivilegeEscalation != false (container \\\"app\\\" must set securityContext.allowPrivilegeEscalation=false), \
+             unrestricted capabilities (container \\\"app\\\" must set securityContext.capabilities.drop=[\\\"ALL\\\"]), \
+             runAsNonRoot != true (pod or container \\\"app\\\" must set securityContext.runAsNonRoot=true), \
+             seccompProfile (pod or container \\\"app\\\" must set securityContext.seccompProfile.type to \\\"RuntimeDefault\\\" or \\\"Localhost\\\")\""
+        ]
+    );
+
+    let hardened = json!({
+        "securityContext": { "runAsNonRoot": true, "seccompProfile": { "type": "RuntimeDefault" } },
+        "containers": [{
+            "name": "app",
+            "image": "nginx",
+            "securityContext": { "allowPrivilegeEscalation": false, "capabilities": { "drop": ["ALL"], "add": ["NET_BIND_SERVICE"] } }
+        }],
+        "volumes": [{ "name": "cache", "emptyDir": {} }]
+    });
+    let resp = client.post(&pods).json(&common::pod("hardened", hardened)).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    assert!(warnings(&resp).is_empty());
+
+    // Tightening enforce warns about the pods already there that it would
+    // refuse
+    let resp = client
+        .patch(server.url("/api/v1/namespaces/secure"))
+        .header("Content-Type", "application/merge-patch+json")
+        .json(&json!({ "metadata": { "labels": { "pod-security.kubernetes.io/enforce": "restricted" } } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    assert_eq!(
+        warnings(&resp),
+        vec![
+            "299 - \"existing pods in namespace \\\"secure\\\" violate the new PodSecurity enforce level \\\"restricted:latest\\\"\"",
+            "299 - \"plain: allowPrivilegeEscalation != false, unrestricted capabilities, runAsNonRoot != true, seccompProfile\""
+        ]
+    );
+    let resp = client
+        .post(&pods)
+        .json(&common::pod("plain-again", json!({})))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+}
+
+#[tokio::test]
+async fn test_workloads_are_warned_about_and_their_pods_refused() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    // A level that isn't one is enforced as restricted
+    let labels = json!({ "pod-security.kubernetes.io/enforce": "strict" });
+    let resp = client.post(server.url("/api/v1/namespaces")).json(&namespace("typo", labels)).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let resp = client
+        .post(server.url("/api/v1/namespaces/typo/pods"))
+        .json(&common::pod("plain", json!({})))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 403);
+    let status: Value = resp.json().await.unwrap();
+    assert!(status["message"].as_str().unwrap().contains("violates PodSecurity \"restricted:latest\""));
+
+    let labels = json!({ "pod-security.kubernetes.io/enforce": "baseline", "pod-security.kubernetes.io/warn": "baseline" });
+    let resp = client.post(server.url("/api/v1/namespaces")).json(&namespace("team", labels)).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let replicaset = json!({
+        "apiVersion": "apps/v1",
+        "kind": "ReplicaSet",
+        "metadata": { "name": "logs" },
+        "spec": {
+            "replicas": 2,
+            "selector": { "matchLabels": { "app": "logs" } },
+            "template": {
+                "metadata": { "labels": { "app": "logs" } },
+                "spec": {
+                    "containers": [{ "name": "agent", "image": "busybox" }],
+                    "volumes": [{ "name": "varlog", "hostPath": { "path": "/var/log" } }]
+                }
+            }
+        }
+    });
+    let resp = client
+        .post(server.url("/apis/apps/v1/namespaces/team/replicasets"))
+        .json(&replicaset)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    assert_eq!(
+        warnings(&resp),
+        vec!["299 - \"would violate PodSecurity \\\"baseline:latest\\\": hostPath volumes (volume \\\"varlog\\\")\""]
+    );
+
+    // The controller's pods are refused, which the ReplicaSet reports
+    let mut condition = Value::Null;
+    for _ in 0..50 {
+        let rs: Value = client
+            .get(server.url("/apis/apps/v1/namespaces/team/replicasets/logs"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        condition = rs["status"]["conditions"][0].clone();
+        if condition["reason"] == "FailedCreate" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(condition["type"], "ReplicaFailure");
+    assert_eq!(condition["reason"], "FailedCreate");
+    let message = condition["message"].as_str().unwrap();
+    assert!(message.contains("violates PodSecurity \"baseline:latest\": hostPath volumes (volume \"varlog\")"), "{}", message);
+    let pods: Value = client
+        .get(server.url("/api/v1/namespaces/team/pods"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(pods["items"].as_array().unwrap().is_empty());
+}
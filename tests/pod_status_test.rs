@@ -2,6 +2,8 @@ use std::process::Command;
 use std::thread;
 use std::time::Duration;
 
+mod common;
+
 #[test]
 #[ignore] // Run with: cargo test --test pod_status_test -- --ignored --nocapture
 fn test_comprehensive_pod_status() {
@@ -15,10 +17,7 @@ fn test_comprehensive_pod_status() {
     
     thread::sleep(Duration::from_secs(1));
     
-    Command::new("rm")
-        .args(&["-f", "krust.db"])
-        .output()
-        .ok();
+    common::reset_data_dir();
     
     // Start server
     println!("Starting Krust server...");
@@ -206,10 +205,7 @@ fn test_pod_ready_status() {
     
     thread::sleep(Duration::from_secs(1));
     
-    Command::new("rm")
-        .args(&["-f", "krust.db"])
-        .output()
-        .ok();
+    common::reset_data_dir();
     
     // Start server
     let mut server = Command::new("cargo")
@@ -1,16 +1,13 @@
 use reqwest;
-use serde_json::json;
+use serde_json::{json, Value};
+
+mod common;
 
 #[tokio::test]
 async fn test_pod_status_subresource() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Create a Pod
     let pod = json!({
@@ -142,14 +139,9 @@ async fn test_pod_status_subresource() {
 
 #[tokio::test]
 async fn test_pod_ephemeralcontainers() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Create a Pod
     let pod = json!({
@@ -221,16 +213,11 @@ async fn test_pod_ephemeralcontainers() {
 
 #[tokio::test]
 async fn test_pod_binding() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
-    // Create a Pod
+    // Create a Pod for a scheduler of its own, which the default one leaves be
     let pod = json!({
         "apiVersion": "v1",
         "kind": "Pod",
@@ -239,6 +226,7 @@ async fn test_pod_binding() {
             "namespace": "default"
         },
         "spec": {
+            "schedulerName": "my-scheduler",
             "containers": [{
                 "name": "nginx",
                 "image": "nginx:alpine"
@@ -252,6 +240,18 @@ async fn test_pod_binding() {
         .send()
         .await
         .unwrap();
+
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    let pod: serde_json::Value = client
+        .get(&format!("{}/namespaces/default/pods/test-pod-binding", base_url))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(pod["spec"]["nodeName"].is_null(), "{}", pod);
+    assert_eq!(pod["status"]["phase"], "Pending");
     
     // Create a binding (schedule the pod to a node)
     let binding = json!({
@@ -274,21 +274,9 @@ async fn test_pod_binding() {
         .send()
         .await
         .unwrap();
-    
-    if response.status() == 404 {
-        eprintln!("Pod binding endpoint not implemented yet");
-        // Clean up
-        client
-            .delete(&format!("{}/namespaces/default/pods/test-pod-binding", base_url))
-            .send()
-            .await
-            .unwrap();
-        return;
-    }
-    
     assert_eq!(response.status(), 201);
     
-    // Verify pod is bound
+    // Verify pod is bound, and that its kubelet takes it from there
     let response = client
         .get(&format!("{}/namespaces/default/pods/test-pod-binding", base_url))
         .send()
@@ -297,6 +285,35 @@ async fn test_pod_binding() {
     
     let pod: serde_json::Value = response.json().await.unwrap();
     assert_eq!(pod["spec"]["nodeName"], "krust-node");
+    let scheduled = pod["status"]["conditions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|c| c["type"] == "PodScheduled")
+        .unwrap();
+    assert_eq!(scheduled["status"], "True");
+    server.wait_for_pod_running("default", "test-pod-binding").await;
+
+    // A pod is bound once
+    let response = client
+        .post(&format!("{}/namespaces/default/pods/test-pod-binding/binding", base_url))
+        .json(&binding)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 409);
+    let status: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(
+        status["message"],
+        "Operation cannot be fulfilled on pods/binding \"test-pod-binding\": pod test-pod-binding is already assigned to node \"krust-node\""
+    );
+    let response = client
+        .post(&format!("{}/namespaces/default/pods/missing/binding", base_url))
+        .json(&json!({ "metadata": { "name": "missing" }, "target": { "name": "krust-node" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 404);
     
     // Clean up
     client
@@ -304,4 +321,150 @@ async fn test_pod_binding() {
         .send()
         .await
         .unwrap();
-}
\ No newline at end of file
+}
+
+#[tokio::test]
+async fn test_scheduled_pods_stay_pending_until_started() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let mut watch = client
+        .get(server.url("/api/v1/namespaces/default/pods?watch=true"))
+        .send()
+        .await
+        .unwrap();
+
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "scheduled" },
+        "spec": { "containers": [{ "name": "nginx", "image": "nginx:alpine" }] }
+    });
+    let response = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+    server.wait_for_pod_running("default", "scheduled").await;
+
+    // Pending, then bound while still Pending, then Running
+    let mut phases: Vec<(String, bool)> = Vec::new();
+    let mut buffer = String::new();
+    while !phases.iter().any(|(phase, _)| phase == "Running") {
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(5), watch.chunk()).await.unwrap().unwrap().unwrap();
+        buffer.push_str(&String::from_utf8_lossy(&chunk));
+        while let Some(end) = buffer.find('\n') {
+            let event: serde_json::Value = serde_json::from_str(&buffer[..end]).unwrap();
+            buffer.drain(..=end);
+            let pod = &event["object"];
+            phases.push((pod["status"]["phase"].as_str().unwrap().to_string(), pod["spec"]["nodeName"].is_string()));
+        }
+    }
+    phases.dedup();
+    assert_eq!(
+        phases,
+        [("Pending".to_string(), false), ("Pending".to_string(), true), ("Running".to_string(), true)]
+    );
+}
+
+#[tokio::test]
+async fn test_pods_left_scheduled_are_migrated_to_pending() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    // Bound by an earlier krust, which gave such pods a phase of their own
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "left" },
+        "spec": { "schedulerName": "my-scheduler", "containers": [{ "name": "nginx", "image": "nginx:alpine" }] }
+    });
+    let response = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+    sqlx::query(
+        "UPDATE pods SET phase = 'Scheduled', node_name = 'gone', status = json_set(status, '$.phase', 'Scheduled')
+         WHERE name = 'left'",
+    )
+    .execute(server.storage.pool())
+    .await
+    .unwrap();
+    sqlx::query(include_str!("../migrations/031_drop_scheduled_phase.sql"))
+        .execute(server.storage.pool())
+        .await
+        .unwrap();
+
+    let pod: Value = client.get(server.url("/api/v1/namespaces/default/pods/left")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(pod["status"]["phase"], "Pending");
+
+    let pending: Value = client
+        .get(server.url("/api/v1/namespaces/default/pods?fieldSelector=status.phase%3DPending"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let names: Vec<&str> = pending["items"].as_array().unwrap().iter().map(|p| p["metadata"]["name"].as_str().unwrap()).collect();
+    assert!(names.contains(&"left"), "{}", pending);
+
+    // and are started by their node's kubelet all the same
+    sqlx::query("UPDATE pods SET node_name = ? WHERE name = 'left'")
+        .bind(krust::config::NODE_NAME)
+        .execute(server.storage.pool())
+        .await
+        .unwrap();
+    server.wait_for_pod_running("default", "left").await;
+}
+
+#[tokio::test]
+async fn test_readiness_gates() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let status_url = server.url("/api/v1/namespaces/default/pods/gated/status");
+
+    // Bound to a node nothing runs, so only this test writes its status
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "gated" },
+        "spec": {
+            "nodeName": "elsewhere",
+            "readinessGates": [{ "conditionType": "target-health.example.com/web" }],
+            "containers": [{ "name": "web", "image": "nginx:alpine" }]
+        }
+    });
+    let response = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+
+    let patch_status = |status: Value| client.patch(&status_url).json(&json!({ "status": status })).send();
+    let condition = |pod: &Value, kind: &str| -> Value {
+        pod["status"]["conditions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|c| c["type"] == kind)
+            .cloned()
+            .unwrap_or(Value::Null)
+    };
+
+    // The containers are ready, but the gate's condition hasn't been reported
+    let response = patch_status(json!({ "conditions": [{ "type": "ContainersReady", "status": "True" }] })).await.unwrap();
+    assert_eq!(response.status(), 200);
+    let pod: Value = response.json().await.unwrap();
+    assert_eq!(condition(&pod, "ContainersReady")["status"], "True");
+    assert_eq!(condition(&pod, "Ready")["status"], "False");
+    assert_eq!(condition(&pod, "Ready")["reason"], "ReadinessGatesNotReady");
+    assert_eq!(condition(&pod, "Initialized")["status"], "True");
+
+    let gate = |status: &str| json!({ "conditions": [{ "type": "target-health.example.com/web", "status": status }] });
+    let pod: Value = patch_status(gate("False")).await.unwrap().json().await.unwrap();
+    assert_eq!(condition(&pod, "Ready")["status"], "False");
+
+    let pod: Value = patch_status(gate("True")).await.unwrap().json().await.unwrap();
+    assert_eq!(condition(&pod, "target-health.example.com/web")["status"], "True");
+    assert_eq!(condition(&pod, "ContainersReady")["status"], "True");
+    assert_eq!(condition(&pod, "Ready")["status"], "True");
+
+    // Ready follows the gate back down
+    let pod: Value = patch_status(gate("False")).await.unwrap().json().await.unwrap();
+    assert_eq!(condition(&pod, "Ready")["status"], "False");
+    assert!(pod["metadata"]["annotations"]["krust.io/unsupported-fields"]
+        .as_str()
+        .map_or(true, |fields| !fields.contains("readinessGates")));
+}
@@ -2,24 +2,12 @@ use reqwest;
 use serde_json::{json, Value};
 use serial_test::serial;
 
-const BASE_URL: &str = "http://localhost:6443";
-
-async fn ensure_server_running() -> bool {
-    let client = reqwest::Client::new();
-    match client.get(&format!("{}/healthz", BASE_URL)).send().await {
-        Ok(resp) => resp.status().is_success(),
-        Err(_) => false
-    }
-}
+mod common;
 
 #[tokio::test]
 #[serial]
 async fn test_pod_lifecycle() {
-    if !ensure_server_running().await {
-        eprintln!("Server not running, skipping integration test");
-        return;
-    }
-    
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
     
     // Create a pod
@@ -48,7 +36,7 @@ async fn test_pod_lifecycle() {
     });
     
     let resp = client
-        .post(&format!("{}/api/v1/namespaces/default/pods", BASE_URL))
+        .post(&format!("{}/api/v1/namespaces/default/pods", server.base_url()))
         .json(&pod)
         .send()
         .await
@@ -64,7 +52,7 @@ async fn test_pod_lifecycle() {
     
     // Get the pod
     let resp = client
-        .get(&format!("{}/api/v1/namespaces/default/pods/test-pod", BASE_URL))
+        .get(&format!("{}/api/v1/namespaces/default/pods/test-pod", server.base_url()))
         .send()
         .await
         .unwrap();
@@ -76,7 +64,7 @@ async fn test_pod_lifecycle() {
     
     // List pods
     let resp = client
-        .get(&format!("{}/api/v1/namespaces/default/pods", BASE_URL))
+        .get(&format!("{}/api/v1/namespaces/default/pods", server.base_url()))
         .send()
         .await
         .unwrap();
@@ -91,7 +79,7 @@ async fn test_pod_lifecycle() {
     updated_pod["metadata"]["labels"]["environment"] = json!("test");
     
     let resp = client
-        .put(&format!("{}/api/v1/namespaces/default/pods/test-pod", BASE_URL))
+        .put(&format!("{}/api/v1/namespaces/default/pods/test-pod", server.base_url()))
         .json(&updated_pod)
         .send()
         .await
@@ -111,7 +99,7 @@ async fn test_pod_lifecycle() {
     });
     
     let resp = client
-        .patch(&format!("{}/api/v1/namespaces/default/pods/test-pod", BASE_URL))
+        .patch(&format!("{}/api/v1/namespaces/default/pods/test-pod", server.base_url()))
         .json(&patch)
         .send()
         .await
@@ -123,7 +111,7 @@ async fn test_pod_lifecycle() {
     
     // Delete the pod
     let resp = client
-        .delete(&format!("{}/api/v1/namespaces/default/pods/test-pod", BASE_URL))
+        .delete(&format!("{}/api/v1/namespaces/default/pods/test-pod", server.base_url()))
         .send()
         .await
         .unwrap();
@@ -132,7 +120,7 @@ async fn test_pod_lifecycle() {
     
     // Verify pod is deleted
     let resp = client
-        .get(&format!("{}/api/v1/namespaces/default/pods/test-pod", BASE_URL))
+        .get(&format!("{}/api/v1/namespaces/default/pods/test-pod", server.base_url()))
         .send()
         .await
         .unwrap();
@@ -143,15 +131,11 @@ async fn test_pod_lifecycle() {
 #[tokio::test]
 #[serial]
 async fn test_pod_not_found() {
-    if !ensure_server_running().await {
-        eprintln!("Server not running, skipping integration test");
-        return;
-    }
-    
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
     
     let resp = client
-        .get(&format!("{}/api/v1/namespaces/default/pods/nonexistent", BASE_URL))
+        .get(&format!("{}/api/v1/namespaces/default/pods/nonexistent", server.base_url()))
         .send()
         .await
         .unwrap();
@@ -162,11 +146,7 @@ async fn test_pod_not_found() {
 #[tokio::test]
 #[serial]
 async fn test_list_all_pods() {
-    if !ensure_server_running().await {
-        eprintln!("Server not running, skipping integration test");
-        return;
-    }
-    
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
     
     // Create pods in different namespaces
@@ -187,7 +167,7 @@ async fn test_list_all_pods() {
     });
     
     client
-        .post(&format!("{}/api/v1/namespaces/default/pods", BASE_URL))
+        .post(&format!("{}/api/v1/namespaces/default/pods", server.base_url()))
         .json(&pod1)
         .send()
         .await
@@ -195,7 +175,7 @@ async fn test_list_all_pods() {
     
     // List all pods
     let resp = client
-        .get(&format!("{}/api/v1/pods", BASE_URL))
+        .get(&format!("{}/api/v1/pods", server.base_url()))
         .send()
         .await
         .unwrap();
@@ -207,7 +187,7 @@ async fn test_list_all_pods() {
     
     // Clean up
     client
-        .delete(&format!("{}/api/v1/namespaces/default/pods/pod-default", BASE_URL))
+        .delete(&format!("{}/api/v1/namespaces/default/pods/pod-default", server.base_url()))
         .send()
         .await
         .unwrap();
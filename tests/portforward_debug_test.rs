@@ -5,6 +5,8 @@ use std::time::Duration;
 use std::io::{Write, Read};
 use tokio::time::timeout;
 
+mod common;
+
 fn setup_krust_server() -> std::process::Child {
     // Clean up any existing server
     Command::new("pkill")
@@ -12,7 +14,7 @@ fn setup_krust_server() -> std::process::Child {
         .output()
         .ok();
     thread::sleep(Duration::from_secs(1));
-    Command::new("rm").args(&["-f", "krust.db"]).output().ok();
+    common::reset_data_dir();
 
     // Start server with debug logging
     let server = Command::new("cargo")
@@ -5,6 +5,8 @@ use std::time::Duration;
 use std::io::Write;
 use tokio::time::timeout;
 
+mod common;
+
 fn setup_environment() -> std::process::Child {
     // Clean up
     Command::new("pkill")
@@ -12,7 +14,7 @@ fn setup_environment() -> std::process::Child {
         .output()
         .ok();
     thread::sleep(Duration::from_secs(1));
-    Command::new("rm").args(&["-f", "krust.db"]).output().ok();
+    common::reset_data_dir();
 
     // Start server
     let server = Command::new("cargo")
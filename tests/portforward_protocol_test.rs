@@ -1,89 +1,289 @@
-// Comprehensive tests for kubectl port-forward protocol implementation
-use krust::api::kubectl_portforward::*;
+use futures::{SinkExt, StreamExt};
+use krust::api::spdy::{self, Frame};
+use serde_json::json;
+use std::collections::HashMap;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
 
-#[test]
-fn test_kubectl_stream_ids() {
-    // kubectl uses specific stream IDs for port forwarding
-    // Based on Kubernetes source, streams are numbered:
-    // - Stream 0: data for port 1
-    // - Stream 1: error for port 1  
-    // - Stream 2: data for port 2
-    // - Stream 3: error for port 2
-    // etc.
-    
-    assert_eq!(get_data_stream_id(0), 0);
-    assert_eq!(get_error_stream_id(0), 1);
-    assert_eq!(get_data_stream_id(1), 2);
-    assert_eq!(get_error_stream_id(1), 3);
+mod common;
+
+type Socket = tokio_tungstenite::WebSocketStream<tokio_tungstenite::MaybeTlsStream<tokio::net::TcpStream>>;
+
+// A hostNetwork pod, whose ports are the node's own
+async fn start_with_host_network_pod() -> common::TestServer {
+    let server = common::TestServer::start().await;
+    let resp = reqwest::Client::new()
+        .post(server.url("/api/v1/namespaces/default/pods"))
+        .json(&json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": { "name": "web" },
+            "spec": {
+                "hostNetwork": true,
+                "containers": [{ "name": "app", "image": "nginx:alpine" }]
+            }
+        }))
+        .send()
+        .await
+        .expect("Failed to create pod");
+    assert_eq!(resp.status(), 201);
+    server.wait_for_pod_running("default", "web").await;
+    server
 }
 
-#[test]
-fn test_kubectl_initial_frames() {
-    // kubectl sends specific initialization frames
-    // These are not standard SPDY but kubectl's own format
-    
-    // First frame is always [0x80, 0x01] for protocol version
-    let init_frame = create_kubectl_init_frame();
-    assert_eq!(init_frame[0], 0x80);
-    assert_eq!(init_frame[1], 0x01);
+// Accepts connections on a free port and echoes back what they send
+async fn echo_listener() -> u16 {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut stream, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let (mut reader, mut writer) = stream.split();
+                let _ = tokio::io::copy(&mut reader, &mut writer).await;
+            });
+        }
+    });
+    port
 }
 
-#[test]
-fn test_kubectl_port_header() {
-    // kubectl sends port information in a specific format
-    // The port is sent as 2 bytes in network byte order
-    
-    let port_frame = create_port_frame(8080, 80);
-    // 8080 = 0x1F90, 80 = 0x0050
-    assert_eq!(port_frame.len(), 4);
-    assert_eq!(port_frame[0], 0x1F);
-    assert_eq!(port_frame[1], 0x90);
-    assert_eq!(port_frame[2], 0x00);
-    assert_eq!(port_frame[3], 0x50);
+// A port nothing listens on
+async fn closed_port() -> u16 {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    listener.local_addr().unwrap().port()
 }
 
-#[test]
-fn test_kubectl_data_frame() {
-    // kubectl data frames have a specific format:
-    // [stream_id:1][flags:1][length:2][data:n]
-    
-    let data = b"Hello, World!";
-    let frame = create_kubectl_data_frame(0, data);
-    
-    assert_eq!(frame[0], 0); // stream_id
-    assert_eq!(frame[1], 0); // flags
-    assert_eq!(u16::from_be_bytes([frame[2], frame[3]]), data.len() as u16);
-    assert_eq!(&frame[4..], data);
+// The client side of a port-forwarding connection, as kubectl drives it
+struct Client {
+    socket: Socket,
+    encoder: spdy::Encoder,
+    decoder: spdy::Decoder,
+    next_stream: u32,
 }
 
-#[test]
-fn test_parse_kubectl_frame() {
-    // Test parsing kubectl frames
-    let data = b"Test data";
-    let frame = create_kubectl_data_frame(2, data);
-    
-    let parsed = parse_kubectl_frame(&frame).unwrap();
-    assert_eq!(parsed.stream_id, 2);
-    assert_eq!(parsed.flags, 0);
-    assert_eq!(parsed.data, data);
+impl Client {
+    async fn connect(server: &common::TestServer, pod: &str) -> Self {
+        let url = server.url(&format!("/api/v1/namespaces/default/pods/{}/portforward", pod));
+        let mut request = url.replace("http://", "ws://").into_client_request().unwrap();
+        request
+            .headers_mut()
+            .insert("Sec-WebSocket-Protocol", "SPDY/3.1+portforward.k8s.io".parse().unwrap());
+        let (socket, response) = tokio_tungstenite::connect_async(request).await.expect("upgrade failed");
+        assert_eq!(response.headers()["sec-websocket-protocol"], "SPDY/3.1+portforward.k8s.io");
+        Self {
+            socket,
+            encoder: spdy::Encoder::new(),
+            decoder: spdy::Decoder::new(),
+            next_stream: 1,
+        }
+    }
+
+    async fn send(&mut self, frame: Frame) {
+        self.socket.send(Message::Binary(self.encoder.encode(&frame))).await.unwrap();
+    }
+
+    async fn recv(&mut self) -> Option<Frame> {
+        loop {
+            if let Some(frame) = self.decoder.next_frame().unwrap() {
+                return Some(frame);
+            }
+            match self.socket.next().await? {
+                Ok(Message::Binary(bytes)) => self.decoder.feed(&bytes),
+                Ok(Message::Close(_)) | Err(_) => return None,
+                Ok(_) => {}
+            }
+        }
+    }
+
+    // Opens the error and data streams for a connection to `port`,
+    // returning their ids
+    async fn open(&mut self, port: u16, request_id: u32) -> (u32, u32) {
+        let mut ids = Vec::new();
+        for kind in ["error", "data"] {
+            let stream_id = self.next_stream;
+            self.next_stream += 2;
+            let headers = vec![
+                ("streamtype".to_string(), kind.to_string()),
+                ("port".to_string(), port.to_string()),
+                ("requestid".to_string(), request_id.to_string()),
+            ];
+            self.send(Frame::SynStream { stream_id, fin: false, headers }).await;
+            ids.push(stream_id);
+        }
+        (ids[0], ids[1])
+    }
+
+    // Reads frames until every stream in `streams` is finished, returning
+    // what each received
+    async fn read_until_finished(&mut self, streams: &[u32]) -> HashMap<u32, Vec<u8>> {
+        let mut received: HashMap<u32, Vec<u8>> = HashMap::new();
+        let mut open: Vec<u32> = streams.to_vec();
+        while !open.is_empty() {
+            let frame = tokio::time::timeout(std::time::Duration::from_secs(10), self.recv())
+                .await
+                .expect("timed out waiting for streams to finish")
+                .expect("connection closed");
+            if let Frame::Data { stream_id, fin, data } = frame {
+                received.entry(stream_id).or_default().extend(data);
+                if fin {
+                    open.retain(|id| *id != stream_id);
+                }
+            }
+        }
+        received
+    }
 }
 
 #[test]
-fn test_kubectl_error_frame() {
-    // Error frames use odd stream IDs
-    let error_msg = b"Connection refused";
-    let frame = create_kubectl_error_frame(0, error_msg);
-    
-    assert_eq!(frame[0], 1); // error stream for port 0
-    assert_eq!(&frame[4..], error_msg);
+fn test_frames_round_trip_through_the_codec() {
+    let frames = vec![
+        Frame::SynStream {
+            stream_id: 1,
+            fin: false,
+            headers: vec![("streamtype".into(), "data".into()), ("port".into(), "8080".into())],
+        },
+        Frame::SynReply { stream_id: 1, fin: false, headers: vec![] },
+        Frame::Data { stream_id: 1, fin: false, data: b"hello".to_vec() },
+        Frame::Data { stream_id: 1, fin: true, data: vec![] },
+        Frame::Ping { id: 7 },
+        Frame::RstStream { stream_id: 3, status: spdy::REFUSED_STREAM },
+        Frame::Headers { stream_id: 5, headers: vec![("requestid".into(), "0".into())] },
+        Frame::GoAway { last_stream_id: 5, status: 0 },
+    ];
+
+    // One compression context per direction spans the connection, and
+    // frames arrive split however the transport likes
+    let mut encoder = spdy::Encoder::new();
+    let bytes: Vec<u8> = frames.iter().flat_map(|frame| encoder.encode(frame)).collect();
+    let mut decoder = spdy::Decoder::new();
+    let mut decoded = Vec::new();
+    for chunk in bytes.chunks(3) {
+        decoder.feed(chunk);
+        while let Some(frame) = decoder.next_frame().unwrap() {
+            decoded.push(frame);
+        }
+    }
+    assert_eq!(decoded, frames);
 }
 
 #[test]
-fn test_kubectl_close_frame() {
-    // kubectl sends specific frames to close streams
-    let close_frame = create_kubectl_close_frame(0);
-    
-    assert_eq!(close_frame[0], 0); // stream_id
-    assert_eq!(close_frame[1], 0x01); // FIN flag
-    assert_eq!(u16::from_be_bytes([close_frame[2], close_frame[3]]), 0); // length = 0
-}
\ No newline at end of file
+fn test_header_names_are_read_in_lower_case() {
+    let mut encoder = spdy::Encoder::new();
+    let mut decoder = spdy::Decoder::new();
+    decoder.feed(&encoder.encode(&Frame::SynStream {
+        stream_id: 1,
+        fin: false,
+        headers: vec![("StreamType".into(), "error".into())],
+    }));
+    let Some(Frame::SynStream { headers, .. }) = decoder.next_frame().unwrap() else {
+        panic!("expected a SYN_STREAM");
+    };
+    assert_eq!(spdy::header(&headers, "streamtype"), Some("error"));
+}
+
+#[tokio::test]
+async fn test_portforward_bridges_data_stream_to_pod_port() {
+    let server = start_with_host_network_pod().await;
+    let port = echo_listener().await;
+    let mut client = Client::connect(&server, "web").await;
+
+    let (error, data) = client.open(port, 0).await;
+    client.send(Frame::Data { stream_id: error, fin: true, data: vec![] }).await;
+    client.send(Frame::Data { stream_id: data, fin: false, data: b"hello, ".to_vec() }).await;
+    client.send(Frame::Data { stream_id: data, fin: false, data: b"pod".to_vec() }).await;
+    client.send(Frame::Data { stream_id: data, fin: true, data: vec![] }).await;
+
+    let received = client.read_until_finished(&[error, data]).await;
+    assert_eq!(received.get(&data).map(Vec::as_slice), Some(&b"hello, pod"[..]));
+    assert!(received.get(&error).is_none_or(Vec::is_empty), "unexpected error: {:?}", received.get(&error));
+}
+
+#[tokio::test]
+async fn test_portforward_runs_concurrent_connections_to_several_ports() {
+    let server = start_with_host_network_pod().await;
+    let ports = [echo_listener().await, echo_listener().await];
+    let mut client = Client::connect(&server, "web").await;
+
+    // Open both pairs before sending anything, like kubectl does when two
+    // local connections arrive at once
+    let first = client.open(ports[0], 0).await;
+    let second = client.open(ports[1], 1).await;
+    client.send(Frame::Data { stream_id: second.1, fin: true, data: b"second".to_vec() }).await;
+    client.send(Frame::Data { stream_id: first.1, fin: true, data: b"first".to_vec() }).await;
+
+    let received = client.read_until_finished(&[first.0, first.1, second.0, second.1]).await;
+    assert_eq!(received[&first.1], b"first");
+    assert_eq!(received[&second.1], b"second");
+}
+
+#[tokio::test]
+async fn test_portforward_reports_unreachable_port_on_error_stream() {
+    let server = start_with_host_network_pod().await;
+    let port = closed_port().await;
+    let mut client = Client::connect(&server, "web").await;
+
+    let (error, data) = client.open(port, 0).await;
+    let received = client.read_until_finished(&[error, data]).await;
+    let message = String::from_utf8(received[&error].clone()).unwrap();
+    assert!(
+        message.starts_with(&format!("error forwarding port {} to pod web, uid ", port)),
+        "unexpected error: {}",
+        message
+    );
+}
+
+#[tokio::test]
+async fn test_portforward_upgrades_to_spdy() {
+    let server = start_with_host_network_pod().await;
+    let port = echo_listener().await;
+
+    // Clients without WebSocket support upgrade the connection itself
+    let address = server.base_url().trim_start_matches("http://").to_string();
+    let mut stream = tokio::net::TcpStream::connect(&address).await.unwrap();
+    let request = format!(
+        "POST /api/v1/namespaces/default/pods/web/portforward HTTP/1.1\r\nHost: {}\r\nConnection: Upgrade\r\n\
+         Upgrade: SPDY/3.1\r\nX-Stream-Protocol-Version: portforward.k8s.io\r\nContent-Length: 0\r\n\r\n",
+        address
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut head = Vec::new();
+    while !head.ends_with(b"\r\n\r\n") {
+        head.push(stream.read_u8().await.unwrap());
+    }
+    let head = String::from_utf8(head).unwrap().to_ascii_lowercase();
+    assert!(head.starts_with("http/1.1 101"), "{}", head);
+    assert!(head.contains("x-stream-protocol-version: portforward.k8s.io"), "{}", head);
+
+    let mut encoder = spdy::Encoder::new();
+    for (stream_id, kind) in [(1, "error"), (3, "data")] {
+        let headers = vec![
+            ("streamtype".to_string(), kind.to_string()),
+            ("port".to_string(), port.to_string()),
+            ("requestid".to_string(), "0".to_string()),
+        ];
+        stream.write_all(&encoder.encode(&Frame::SynStream { stream_id, fin: false, headers })).await.unwrap();
+    }
+    let frame = Frame::Data { stream_id: 3, fin: true, data: b"over spdy".to_vec() };
+    stream.write_all(&encoder.encode(&frame)).await.unwrap();
+
+    let mut decoder = spdy::Decoder::new();
+    let mut echoed = Vec::new();
+    let mut buffer = [0; 4096];
+    'read: loop {
+        let n = tokio::time::timeout(std::time::Duration::from_secs(10), stream.read(&mut buffer))
+            .await
+            .expect("timed out waiting for data")
+            .unwrap();
+        assert!(n > 0, "connection closed");
+        decoder.feed(&buffer[..n]);
+        while let Some(frame) = decoder.next_frame().unwrap() {
+            if let Frame::Data { stream_id: 3, fin, data } = frame {
+                echoed.extend(data);
+                if fin {
+                    break 'read;
+                }
+            }
+        }
+    }
+    assert_eq!(echoed, b"over spdy");
+}
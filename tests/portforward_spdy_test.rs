@@ -7,6 +7,8 @@ use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::TcpStream;
 use tokio_tungstenite::{connect_async, tungstenite::Message};
 
+mod common;
+
 fn wait_for_condition<F>(condition: F, timeout_secs: u64, message: &str) -> bool
 where
     F: Fn() -> bool,
@@ -33,7 +35,7 @@ async fn test_kubectl_portforward_protocol() {
         .output()
         .ok();
     tokio::time::sleep(Duration::from_secs(1)).await;
-    Command::new("rm").args(&["-f", "krust.db"]).output().ok();
+    common::reset_data_dir();
 
     // Start server
     println!("Starting Krust server...");
@@ -176,7 +178,7 @@ async fn test_portforward_stream_multiplexing() {
         .output()
         .ok();
     tokio::time::sleep(Duration::from_secs(1)).await;
-    Command::new("rm").args(&["-f", "krust.db"]).output().ok();
+    common::reset_data_dir();
 
     // Start server
     let mut server = Command::new("cargo")
@@ -324,7 +326,7 @@ async fn test_portforward_error_handling() {
         .output()
         .ok();
     tokio::time::sleep(Duration::from_secs(1)).await;
-    Command::new("rm").args(&["-f", "krust.db"]).output().ok();
+    common::reset_data_dir();
 
     let mut server = Command::new("cargo")
         .args(&["run"])
@@ -405,7 +407,7 @@ async fn test_portforward_bidirectional_streaming() {
         .output()
         .ok();
     tokio::time::sleep(Duration::from_secs(1)).await;
-    Command::new("rm").args(&["-f", "krust.db"]).output().ok();
+    common::reset_data_dir();
 
     let mut server = Command::new("cargo")
         .args(&["run"])
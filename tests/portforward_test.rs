@@ -3,6 +3,8 @@ use std::thread;
 use std::time::Duration;
 use std::io::Write;
 
+mod common;
+
 fn wait_for_condition<F>(condition: F, timeout_secs: u64, message: &str) -> bool 
 where
     F: Fn() -> bool,
@@ -31,10 +33,7 @@ fn test_port_forward_basic() {
     
     thread::sleep(Duration::from_secs(1));
     
-    Command::new("rm")
-        .args(&["-f", "krust.db"])
-        .output()
-        .ok();
+    common::reset_data_dir();
     
     // Start server
     println!("Starting Krust server...");
@@ -158,10 +157,7 @@ fn test_port_forward_tcp_streaming() {
     
     thread::sleep(Duration::from_secs(1));
     
-    Command::new("rm")
-        .args(&["-f", "krust.db"])
-        .output()
-        .ok();
+    common::reset_data_dir();
     
     // Start server
     println!("Starting Krust server...");
@@ -0,0 +1,169 @@
+use reqwest;
+use serde_json::{json, Value};
+use sqlx::Row;
+
+mod common;
+
+fn pod_requesting(name: &str, cpu: &str, priority_class: Option<&str>) -> Value {
+    let mut pod = common::pod(name, json!({
+        "containers": [{ "name": "app", "image": "nginx:latest", "resources": { "requests": { "cpu": cpu } } }]
+    }));
+    if let Some(class) = priority_class {
+        pod["spec"]["priorityClassName"] = json!(class);
+    }
+    pod
+}
+
+// The pod sizes below assume an 8 CPU node, whatever the host has
+async fn start_server() -> common::TestServer {
+    let config = krust::Config::parse("nodes:\n  krust-node:\n    cpu: \"8\"\n").unwrap();
+    common::TestServer::start_with_config(config).await
+}
+
+async fn create_priority_class(client: &reqwest::Client, server: &common::TestServer, name: &str, value: i64, policy: &str) {
+    let resp = client
+        .post(server.url("/apis/scheduling.k8s.io/v1/priorityclasses"))
+        .json(&json!({
+            "apiVersion": "scheduling.k8s.io/v1",
+            "kind": "PriorityClass",
+            "metadata": { "name": name },
+            "value": value,
+            "preemptionPolicy": policy
+        }))
+        .send()
+        .await
+        .expect("Failed to create priority class");
+    assert!(resp.status().is_success());
+}
+
+#[tokio::test]
+async fn test_pod_priority_is_resolved_from_class() {
+    let server = start_server().await;
+    let client = reqwest::Client::new();
+    let pods = server.url("/api/v1/namespaces/default/pods");
+
+    create_priority_class(&client, &server, "high", 1000, "PreemptLowerPriority").await;
+
+    let resp = common::post(&client, &pods, &pod_requesting("prio", "100m", Some("high"))).await;
+    assert_eq!(resp.status(), 201);
+    let created: Value = resp.json().await.unwrap();
+    assert_eq!(created["spec"]["priority"], 1000);
+    assert_eq!(created["spec"]["preemptionPolicy"], "PreemptLowerPriority");
+
+    let resp = common::post(&client, &pods, &pod_requesting("plain", "100m", None)).await;
+    let created: Value = resp.json().await.unwrap();
+    assert_eq!(created["spec"]["priority"], 0);
+
+    // Unknown classes are rejected at admission
+    let resp = common::post(&client, &pods, &pod_requesting("missing", "100m", Some("no-such-class"))).await;
+    assert_eq!(resp.status(), 403);
+}
+
+#[tokio::test]
+async fn test_high_priority_pod_preempts_lower_priority_pod() {
+    let server = start_server().await;
+    let client = reqwest::Client::new();
+    let pods = server.url("/api/v1/namespaces/default/pods");
+
+    // Fill the node's 8 CPUs with low priority pods
+    for name in ["low-1", "low-2"] {
+        let resp = common::post(&client, &pods, &pod_requesting(name, "4", None)).await;
+        assert_eq!(resp.status(), 201);
+        server.wait_for_pod_running("default", name).await;
+    }
+
+    create_priority_class(&client, &server, "critical", 100000, "PreemptLowerPriority").await;
+    let resp = common::post(&client, &pods, &pod_requesting("urgent", "4", Some("critical"))).await;
+    assert_eq!(resp.status(), 201);
+    let urgent: Value = resp.json().await.unwrap();
+
+    // The preemptor only binds once a victim has terminated
+    server.wait_for_pod_running("default", "urgent").await;
+
+    let remaining: Vec<String> = client
+        .get(server.url("/api/v1/namespaces/default/pods"))
+        .send()
+        .await
+        .unwrap()
+        .json::<Value>()
+        .await
+        .unwrap()["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["metadata"]["name"].as_str().unwrap().to_string())
+        .collect();
+    assert_eq!(remaining.len(), 2);
+    assert!(remaining.contains(&"urgent".to_string()));
+
+    // Exactly one victim got a Preempted event naming the preemptor
+    let events = sqlx::query(
+        "SELECT involved_object_name, message FROM events WHERE reason = 'Preempted'"
+    )
+    .fetch_all(&*server.storage.pool)
+    .await
+    .unwrap();
+    assert_eq!(events.len(), 1);
+    let victim: String = events[0].get("involved_object_name");
+    assert!(victim.starts_with("low-"));
+    assert!(!remaining.contains(&victim));
+    let message: String = events[0].get("message");
+    assert!(message.contains(urgent["metadata"]["uid"].as_str().unwrap()));
+    assert!(message.contains("krust-node"));
+
+    // The preemptor was nominated to the node while it waited
+    let nominated = sqlx::query(
+        "SELECT object FROM events WHERE resource_name = 'urgent' AND object LIKE '%nominatedNodeName%'"
+    )
+    .fetch_all(&*server.storage.pool)
+    .await
+    .unwrap();
+    assert!(!nominated.is_empty());
+
+    // ...and the nomination is cleared once it is bound
+    let urgent: Value = client
+        .get(server.url("/api/v1/namespaces/default/pods/urgent"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(urgent["spec"]["nodeName"], "krust-node");
+    assert!(urgent["status"]["nominatedNodeName"].is_null());
+}
+
+#[tokio::test]
+async fn test_preemption_policy_never_waits_for_room() {
+    let server = start_server().await;
+    let client = reqwest::Client::new();
+    let pods = server.url("/api/v1/namespaces/default/pods");
+
+    let resp = common::post(&client, &pods, &pod_requesting("low", "8", None)).await;
+    assert_eq!(resp.status(), 201);
+    server.wait_for_pod_running("default", "low").await;
+
+    create_priority_class(&client, &server, "polite", 1000, "Never").await;
+    let resp = common::post(&client, &pods, &pod_requesting("waiting", "1", Some("polite"))).await;
+    assert_eq!(resp.status(), 201);
+
+    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+
+    let waiting: Value = client
+        .get(server.url("/api/v1/namespaces/default/pods/waiting"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(waiting["status"]["phase"], "Pending");
+    assert!(waiting["status"]["nominatedNodeName"].is_null());
+
+    let low = client
+        .get(server.url("/api/v1/namespaces/default/pods/low"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(low.status(), 200);
+}
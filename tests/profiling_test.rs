@@ -0,0 +1,53 @@
+use reqwest;
+use krust::Config;
+use serde_json::Value;
+use std::time::Duration;
+
+mod common;
+
+#[tokio::test]
+async fn test_profiling_endpoints() {
+    let mut config = Config::default();
+    config.api_server.profiling = true;
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let resp = client.get(server.url("/debug/pprof/")).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let index: Value = resp.json().await.unwrap();
+    assert!(index["profiles"]["profile"].is_string());
+
+    // Every background loop shows up once it has run
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let tasks: Value = client.get(server.url("/debug/pprof/tasks")).send().await.unwrap().json().await.unwrap();
+    assert!(tasks["runtime"]["workers"].as_u64().unwrap() >= 1);
+    assert!(tasks["runtime"]["aliveTasks"].as_u64().unwrap() >= 1);
+    let loops = tasks["loops"].as_array().unwrap();
+    for name in ["scheduler", "replicaset controller", "endpoints controller"] {
+        let stats = loops.iter().find(|l| l["name"] == name).unwrap_or_else(|| panic!("no {} in {}", name, tasks));
+        assert!(stats["iterations"].as_u64().unwrap() >= 1);
+        assert!(stats["busySeconds"].as_f64().unwrap() >= 0.0);
+    }
+
+    let heap: Value = client.get(server.url("/debug/pprof/heap")).send().await.unwrap().json().await.unwrap();
+    assert!(heap["residentBytes"].as_u64().unwrap() > 0);
+
+    let resp = client.get(server.url("/debug/pprof/profile?seconds=1")).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let profile: Value = resp.json().await.unwrap();
+    assert_eq!(profile["seconds"], 1);
+    assert!(profile["cpuSeconds"].as_f64().unwrap() >= 0.0);
+    assert!(profile["loops"].as_array().unwrap().iter().any(|l| l["name"] == "scheduler"));
+
+    let resp = client.get(server.url("/debug/pprof/profile?seconds=0")).send().await.unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_profiling_is_off_by_default() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    for path in ["/debug/pprof/", "/debug/pprof/tasks", "/debug/pprof/heap", "/debug/pprof/profile?seconds=1"] {
+        assert_eq!(client.get(server.url(path)).send().await.unwrap().status(), 404, "{}", path);
+    }
+}
@@ -0,0 +1,111 @@
+use reqwest;
+use serde_json::{json, Value};
+
+mod common;
+
+fn annotations(protected: Value) -> String {
+    json!({ "metadata": { "annotations": { "krust.io/protected": protected } } }).to_string()
+}
+
+#[tokio::test]
+async fn test_protected_objects_cannot_be_deleted() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let configmap = server.url("/api/v1/namespaces/default/configmaps/settings");
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/configmaps"))
+        .json(&json!({
+            "apiVersion": "v1",
+            "kind": "ConfigMap",
+            "metadata": { "name": "settings", "annotations": { "krust.io/protected": "true" } },
+            "data": { "key": "value" }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let resp = client.delete(&configmap).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["kind"], "Status");
+    assert_eq!(status["reason"], "Forbidden");
+    let message = status["message"].as_str().unwrap();
+    assert!(message.contains("kubectl annotate configmaps settings -n default krust.io/protected-"), "{}", message);
+    assert_eq!(client.get(&configmap).send().await.unwrap().status(), 200);
+
+    // Once the annotation is gone the delete goes through
+    let resp = client
+        .patch(&configmap)
+        .header("Content-Type", "application/merge-patch+json")
+        .body(annotations(Value::Null))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client.delete(&configmap).send().await.unwrap();
+    assert!(resp.status().is_success());
+}
+
+#[tokio::test]
+async fn test_protected_namespaces_and_their_contents_hold_namespace_deletion() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let namespace = server.url("/api/v1/namespaces/keep");
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces"))
+        .json(&json!({
+            "apiVersion": "v1",
+            "kind": "Namespace",
+            "metadata": { "name": "keep", "annotations": { "krust.io/protected": "true" } }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let resp = client.delete(&namespace).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+    let status: Value = resp.json().await.unwrap();
+    assert!(status["message"].as_str().unwrap().contains("kubectl annotate namespaces keep krust.io/protected-"));
+
+    // A protected object inside still holds the namespace once it is unprotected
+    let resp = client
+        .post(server.url("/api/v1/namespaces/keep/secrets"))
+        .json(&json!({
+            "apiVersion": "v1",
+            "kind": "Secret",
+            "metadata": { "name": "credentials", "annotations": { "krust.io/protected": "true" } },
+            "data": {}
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    let resp = client
+        .patch(&namespace)
+        .header("Content-Type", "application/merge-patch+json")
+        .body(annotations(json!("false")))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let resp = client.delete(&namespace).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+    let status: Value = resp.json().await.unwrap();
+    assert!(status["message"].as_str().unwrap().contains("secrets/credentials"), "{}", status);
+
+    let resp = client
+        .patch(server.url("/api/v1/namespaces/keep/secrets/credentials"))
+        .header("Content-Type", "application/merge-patch+json")
+        .body(annotations(Value::Null))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = client.delete(&namespace).send().await.unwrap();
+    assert!(resp.status().is_success());
+}
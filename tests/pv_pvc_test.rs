@@ -1,16 +1,13 @@
 use reqwest;
 use serde_json::json;
 
+mod common;
+
 #[tokio::test]
 async fn test_persistentvolume_crud_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Test 1: Create a PersistentVolume
     let pv = json!({
@@ -130,14 +127,9 @@ async fn test_persistentvolume_crud_operations() {
 
 #[tokio::test]
 async fn test_persistentvolumeclaim_crud_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // First create a PersistentVolume to bind to
     let pv = json!({
@@ -300,14 +292,9 @@ async fn test_persistentvolumeclaim_crud_operations() {
 
 #[tokio::test]
 async fn test_pv_pvc_binding() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Create a PersistentVolume
     let pv = json!({
@@ -1,16 +1,13 @@
 use reqwest;
 use serde_json::json;
 
+mod common;
+
 #[tokio::test]
 async fn test_role_crud_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/rbac.authorization.k8s.io/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/rbac.authorization.k8s.io/v1");
     
     // Create a Role
     let role = json!({
@@ -97,14 +94,9 @@ async fn test_role_crud_operations() {
 
 #[tokio::test]
 async fn test_rolebinding_crud_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/rbac.authorization.k8s.io/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/rbac.authorization.k8s.io/v1");
     
     // Create a RoleBinding
     let rolebinding = json!({
@@ -168,14 +160,9 @@ async fn test_rolebinding_crud_operations() {
 
 #[tokio::test]
 async fn test_clusterrole_crud_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/rbac.authorization.k8s.io/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/rbac.authorization.k8s.io/v1");
     
     // Create a ClusterRole
     let clusterrole = json!({
@@ -254,14 +241,9 @@ async fn test_clusterrole_crud_operations() {
 
 #[tokio::test]
 async fn test_clusterrolebinding_crud_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/rbac.authorization.k8s.io/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/rbac.authorization.k8s.io/v1");
     
     // Create a ClusterRoleBinding
     let clusterrolebinding = json!({
@@ -1,16 +1,13 @@
 use reqwest;
 use serde_json::json;
 
+mod common;
+
 #[tokio::test]
 async fn test_replicaset_crud_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/apps/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/apps/v1");
     
     // Test 1: Create a ReplicaSet
     let replicaset = json!({
@@ -202,14 +199,9 @@ async fn test_replicaset_crud_operations() {
 
 #[tokio::test]
 async fn test_replicaset_owner_references() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/apps/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/apps/v1");
     
     // Create a ReplicaSet with owner references
     let replicaset = json!({
@@ -267,4 +259,251 @@ async fn test_replicaset_owner_references() {
         .send()
         .await
         .unwrap();
-}
\ No newline at end of file
+}
+
+async fn pod_count(client: &reqwest::Client, server: &common::TestServer, app: &str) -> usize {
+    let pods: serde_json::Value = client
+        .get(server.url(&format!("/api/v1/namespaces/default/pods?labelSelector=app%3D{}", app)))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    pods["items"].as_array().unwrap().len()
+}
+
+#[tokio::test]
+async fn test_replicaset_creates_pods_in_batches() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    // Every pod is rejected until its PriorityClass exists
+    let replicaset = json!({
+        "apiVersion": "apps/v1",
+        "kind": "ReplicaSet",
+        "metadata": { "name": "batched" },
+        "spec": {
+            "replicas": 20,
+            "selector": { "matchLabels": { "app": "batched" } },
+            "template": {
+                "metadata": { "labels": { "app": "batched" } },
+                "spec": {
+                    "priorityClassName": "batch-high",
+                    "containers": [{ "name": "app", "image": "nginx:alpine" }]
+                }
+            }
+        }
+    });
+    let response = client
+        .post(server.url("/apis/apps/v1/namespaces/default/replicasets"))
+        .json(&replicaset)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+
+    let mut status = serde_json::Value::Null;
+    for _ in 0..20 {
+        tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
+        let rs: serde_json::Value = client
+            .get(server.url("/apis/apps/v1/namespaces/default/replicasets/batched"))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        status = rs["status"].clone();
+        if status["conditions"][0]["status"] == "True" {
+            break;
+        }
+    }
+    let condition = &status["conditions"][0];
+    assert_eq!(condition["type"], "ReplicaFailure");
+    assert_eq!(condition["reason"], "FailedCreate");
+    assert!(condition["message"].as_str().unwrap().contains("batch-high"));
+    assert_eq!(pod_count(&client, &server, "batched").await, 0);
+
+    // Once pods are accepted, the batches grow until all twenty are there
+    let class = json!({
+        "apiVersion": "scheduling.k8s.io/v1",
+        "kind": "PriorityClass",
+        "metadata": { "name": "batch-high" },
+        "value": 1000
+    });
+    let response = client
+        .post(server.url("/apis/scheduling.k8s.io/v1/priorityclasses"))
+        .json(&class)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+
+    let mut count = 0;
+    for _ in 0..20 {
+        tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
+        count = pod_count(&client, &server, "batched").await;
+        if count == 20 {
+            break;
+        }
+    }
+    assert_eq!(count, 20);
+}
+
+#[tokio::test]
+async fn test_replicasets_are_got_many_at_once() {
+    let server = common::TestServer::start().await;
+    let mut uids = Vec::new();
+    // Owned by a kind krust doesn't keep, so the garbage collector leaves them be
+    for (name, owner) in [("first", "owner-a"), ("second", "owner-b"), ("third", "owner-a")] {
+        let replicaset = json!({
+            "metadata": {
+                "name": name,
+                "ownerReferences": [{ "apiVersion": "example.com/v1", "kind": "Rollout", "name": owner, "uid": owner, "controller": true }]
+            },
+            "spec": {
+                "replicas": 0,
+                "selector": { "matchLabels": { "app": name } },
+                "template": { "metadata": { "labels": { "app": name } }, "spec": { "containers": [{ "name": "app", "image": "nginx" }] } }
+            }
+        });
+        let created = server.storage.replicasets().create("default", replicaset).await.unwrap();
+        uids.push(created["metadata"]["uid"].as_str().unwrap().to_string());
+    }
+
+    // Unknown and deleted uids are left out; the rest come oldest first
+    server.storage.replicasets().delete("default", "second").await.unwrap();
+    let wanted = vec![uids[2].clone(), "unknown".to_string(), uids[1].clone(), uids[0].clone()];
+    let found = server.storage.replicasets().get_many_by_uids(&wanted).await.unwrap();
+    let names: Vec<&str> = found.iter().map(|rs| rs["metadata"]["name"].as_str().unwrap()).collect();
+    assert_eq!(names, vec!["first", "third"]);
+    assert_eq!(found[0]["metadata"]["ownerReferences"][0]["uid"], "owner-a");
+
+    let mut owned = server.storage.owners().dependent_uids("replicasets", &["owner-a".to_string()]).await.unwrap();
+    let mut owned_by_a = owned.remove("owner-a").unwrap();
+    owned_by_a.sort();
+    let mut expected = vec![uids[0].clone(), uids[2].clone()];
+    expected.sort();
+    assert_eq!(owned_by_a, expected);
+    assert!(owned.is_empty());
+}
+
+#[tokio::test]
+async fn test_replicaset_status_counts_ready_pods() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/apis/apps/v1/namespaces/default/replicasets/gated");
+
+    // Its pods run, but aren't ready until their gate's condition says so
+    let replicaset = json!({
+        "apiVersion": "apps/v1",
+        "kind": "ReplicaSet",
+        "metadata": { "name": "gated" },
+        "spec": {
+            "replicas": 2,
+            "selector": { "matchLabels": { "app": "gated" } },
+            "template": {
+                "metadata": { "labels": { "app": "gated" } },
+                "spec": {
+                    "readinessGates": [{ "conditionType": "example.com/gate" }],
+                    "containers": [{ "name": "nginx", "image": "nginx:alpine" }]
+                }
+            }
+        }
+    });
+    let response = client.post(server.url("/apis/apps/v1/namespaces/default/replicasets")).json(&replicaset).send().await.unwrap();
+    assert_eq!(response.status(), 201);
+
+    let status_until = |done: fn(&serde_json::Value) -> bool| {
+        let client = client.clone();
+        let url = url.clone();
+        async move {
+            for _ in 0..50 {
+                let replicaset: serde_json::Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+                if done(&replicaset) {
+                    return replicaset;
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+            }
+            panic!("the ReplicaSet's status never got there");
+        }
+    };
+    let replicaset = status_until(|rs| rs["status"]["replicas"] == 2).await;
+    assert_eq!(replicaset["status"]["readyReplicas"], 0);
+    assert_eq!(replicaset["status"]["observedGeneration"], 1);
+
+    let pods: serde_json::Value = client
+        .get(server.url("/api/v1/namespaces/default/pods?labelSelector=app%3Dgated"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let pod = pods["items"][0]["metadata"]["name"].as_str().unwrap();
+    server.wait_for_pod_running("default", pod).await;
+    let gate = json!({ "status": { "conditions": [{ "type": "example.com/gate", "status": "True" }] } });
+    let response = client
+        .patch(server.url(&format!("/api/v1/namespaces/default/pods/{}/status", pod)))
+        .json(&gate)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 200);
+    let replicaset = status_until(|rs| rs["status"]["readyReplicas"] == 1).await;
+    assert_eq!(replicaset["status"]["availableReplicas"], 1);
+
+    // A new spec is a new generation, which the status catches up with
+    let mut replicaset = replicaset;
+    replicaset["spec"]["replicas"] = json!(3);
+    let response = client.put(&url).json(&replicaset).send().await.unwrap();
+    assert_eq!(response.status(), 200);
+    let replicaset: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(replicaset["metadata"]["generation"], 2);
+    status_until(|rs| rs["status"]["observedGeneration"] == 2 && rs["status"]["replicas"] == 3).await;
+}
+
+#[tokio::test]
+async fn test_replicaset_keeps_its_pods_after_compaction() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let replicaset = json!({
+        "apiVersion": "apps/v1",
+        "kind": "ReplicaSet",
+        "metadata": { "name": "compacted" },
+        "spec": {
+            "replicas": 2,
+            "selector": { "matchLabels": { "app": "compacted" } },
+            "template": {
+                "metadata": { "labels": { "app": "compacted" } },
+                "spec": { "containers": [{ "name": "app", "image": "nginx:alpine" }] }
+            }
+        }
+    });
+    let response = client
+        .post(server.url("/apis/apps/v1/namespaces/default/replicasets"))
+        .json(&replicaset)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), 201);
+
+    let mut count = 0;
+    for _ in 0..20 {
+        tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
+        count = pod_count(&client, &server, "compacted").await;
+        if count == 2 {
+            break;
+        }
+    }
+    assert_eq!(count, 2);
+
+    // The watch events its pods were written with go, but they still count
+    server.storage.watch().compact(0).await.unwrap();
+    for _ in 0..12 {
+        tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
+        assert_eq!(pod_count(&client, &server, "compacted").await, 2);
+    }
+}
@@ -0,0 +1,90 @@
+use reqwest;
+use serde_json::Value;
+use std::time::{Duration, Instant};
+
+mod common;
+
+/// Holds every database connection, so requests needing one hang until the
+/// returned connections are dropped.
+async fn block_database(server: &common::TestServer) -> Vec<sqlx::pool::PoolConnection<sqlx::Sqlite>> {
+    let mut held = Vec::new();
+    for _ in 0..server.storage.pool.options().get_max_connections() {
+        held.push(server.storage.pool.acquire().await.unwrap());
+    }
+    held
+}
+
+#[tokio::test]
+async fn test_regular_requests_time_out() {
+    let config = krust::Config::parse("apiServer:\n  requestTimeoutSeconds: 1\n").unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let held = block_database(&server).await;
+
+    let started = Instant::now();
+    let resp = reqwest::get(server.url("/api/v1/namespaces/default/pods")).await.unwrap();
+    assert_eq!(resp.status(), 504);
+    assert!(started.elapsed() < Duration::from_secs(5));
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["kind"], "Status");
+    assert_eq!(status["reason"], "Timeout");
+    assert_eq!(status["code"], 504);
+
+    // Requests that don't wait on anything are unaffected
+    drop(held);
+    let resp = reqwest::get(server.url("/api/v1/namespaces/default/pods")).await.unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_timeout_seconds_overrides_the_default() {
+    let server = common::TestServer::start().await;
+    let _held = block_database(&server).await;
+
+    let started = Instant::now();
+    let resp = reqwest::get(server.url("/api/v1/namespaces/default/configmaps?timeoutSeconds=1"))
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 504);
+    assert!(started.elapsed() < Duration::from_secs(5));
+}
+
+#[tokio::test]
+async fn test_watches_are_not_cut_off() {
+    let config = krust::Config::parse("apiServer:\n  requestTimeoutSeconds: 1\n").unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let held = block_database(&server).await;
+
+    let watch = tokio::spawn(reqwest::get(server.url("/api/v1/namespaces/default/pods?watch=true")));
+
+    // Well past the timeout, the watch is still waiting rather than failed
+    tokio::time::sleep(Duration::from_millis(2500)).await;
+    assert!(!watch.is_finished());
+
+    drop(held);
+    let resp = tokio::time::timeout(Duration::from_secs(10), watch)
+        .await
+        .expect("watch never started")
+        .unwrap()
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_objects_named_like_long_running_requests_time_out() {
+    let config = krust::Config::parse("apiServer:\n  requestTimeoutSeconds: 1\n").unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let _held = block_database(&server).await;
+
+    for path in [
+        "/api/v1/namespaces/watch/pods",
+        "/api/v1/namespaces/default/pods/exec",
+        "/api/v1/namespaces/proxy/configmaps/attach",
+        "/apis/apps/v1/namespaces/default/deployments/portforward",
+        "/api/v1/nodes/watch",
+    ] {
+        let started = Instant::now();
+        let resp = reqwest::get(server.url(path)).await.unwrap();
+        assert_eq!(resp.status(), 504, "{}", path);
+        assert!(started.elapsed() < Duration::from_secs(5), "{}", path);
+    }
+}
@@ -0,0 +1,61 @@
+use krust::runtime::cgroups::ContainerLimits;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+#[test]
+fn test_limits_become_docker_cgroup_settings() {
+    let limited = json!({
+        "name": "app",
+        "resources": { "requests": { "cpu": "250m", "memory": "64Mi" }, "limits": { "cpu": "1.5", "memory": "128Mi" } }
+    });
+    let limits = ContainerLimits::from_container(&limited);
+    assert_eq!(limits, ContainerLimits { nano_cpus: Some(1_500_000_000), memory: Some(128 * 1024 * 1024), cpu_shares: 256 });
+    let host_config = limits.host_config();
+    assert_eq!(host_config.memory_swap, Some(128 * 1024 * 1024));
+
+    // Requests default to limits, and containers with neither get the
+    // fewest shares
+    let limit_only = ContainerLimits::from_container(&json!({ "resources": { "limits": { "cpu": "2" } } }));
+    assert_eq!(limit_only.cpu_shares, 2048);
+    let best_effort = ContainerLimits::from_container(&json!({ "name": "app" }));
+    assert_eq!(best_effort, ContainerLimits { nano_cpus: None, memory: None, cpu_shares: 2 });
+}
+
+#[tokio::test]
+async fn test_containers_over_their_memory_limit_are_oom_killed() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "hungry", "annotations": { "krust.io/fake-memory-usage": "200Mi" } },
+        "spec": {
+            "restartPolicy": "Never",
+            "containers": [{
+                "name": "app",
+                "image": "nginx:1.25",
+                "resources": { "limits": { "cpu": "500m", "memory": "128Mi" } }
+            }]
+        }
+    });
+    let resp = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let mut pod = Value::Null;
+    for _ in 0..50 {
+        pod = server.storage.pods().get("default", "hungry").await.unwrap();
+        if pod["status"]["phase"] == "Failed" {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(pod["status"]["phase"], "Failed", "{:#}", pod["status"]);
+    let status = &pod["status"]["containerStatuses"][0];
+    assert_eq!(status["state"]["terminated"]["reason"], "OOMKilled");
+    assert_eq!(status["state"]["terminated"]["exitCode"], 137);
+    assert_eq!(status["allocatedResources"], json!({ "cpu": "500m", "memory": "128Mi" }));
+    assert_eq!(status["resources"]["limits"], json!({ "cpu": "500m", "memory": "128Mi" }));
+}
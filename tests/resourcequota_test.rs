@@ -1,16 +1,13 @@
 use reqwest;
 use serde_json::json;
 
+mod common;
+
 #[tokio::test]
 async fn test_resourcequota_crud() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Create namespace first
     let namespace = json!({
@@ -146,14 +143,9 @@ async fn test_resourcequota_crud() {
 
 #[tokio::test]
 async fn test_limitrange_crud() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Create namespace first
     let namespace = json!({
@@ -279,14 +271,9 @@ async fn test_limitrange_crud() {
 
 #[tokio::test]
 async fn test_quota_enforcement() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Create namespace first
     let namespace = json!({
@@ -321,7 +308,7 @@ async fn test_quota_enforcement() {
     });
     
     client
-        .post(&format!("{}/api/v1/namespaces/quota-test/resourcequotas", base_url))
+        .post(&format!("{}/namespaces/quota-test/resourcequotas", base_url))
         .json(&quota)
         .send()
         .await
@@ -350,7 +337,7 @@ async fn test_quota_enforcement() {
     });
     
     let response = client
-        .post(&format!("{}/api/v1/namespaces/quota-test/pods", base_url))
+        .post(&format!("{}/namespaces/quota-test/pods", base_url))
         .json(&pod1)
         .send()
         .await
@@ -381,7 +368,7 @@ async fn test_quota_enforcement() {
     });
     
     let response = client
-        .post(&format!("{}/api/v1/namespaces/quota-test/pods", base_url))
+        .post(&format!("{}/namespaces/quota-test/pods", base_url))
         .json(&pod2)
         .send()
         .await
@@ -0,0 +1,314 @@
+use reqwest;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+fn deployment(name: &str, replicas: i64, image: &str, annotations: Value) -> Value {
+    json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": name, "annotations": annotations },
+        "spec": {
+            "replicas": replicas,
+            "selector": { "matchLabels": { "app": name } },
+            "template": {
+                "metadata": { "labels": { "app": name } },
+                "spec": { "containers": [{ "name": "app", "image": image }] }
+            },
+            "strategy": { "type": "RollingUpdate", "rollingUpdate": { "maxSurge": 1, "maxUnavailable": 0 } }
+        }
+    })
+}
+
+// Replicas of the deployment's ReplicaSets, by the image they run
+async fn replicas_by_image(server: &common::TestServer, name: &str) -> Vec<(String, i64)> {
+    let list = server.storage.replicasets().list(Some("default")).await.unwrap();
+    let mut replicas: Vec<(String, i64)> = list["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|rs| rs["metadata"]["ownerReferences"][0]["name"] == name)
+        .map(|rs| {
+            let image = rs["spec"]["template"]["spec"]["containers"][0]["image"].as_str().unwrap().to_string();
+            (image, rs["spec"]["replicas"].as_i64().unwrap())
+        })
+        .collect();
+    replicas.sort();
+    replicas
+}
+
+async fn wait_for(server: &common::TestServer, name: &str, what: &str, done: impl Fn(&Value) -> bool) -> Value {
+    for _ in 0..100 {
+        let deployment = server.storage.deployments().get("default", name).await.unwrap();
+        if done(&deployment) {
+            return deployment;
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    panic!("deployment {} never {}: {:?}", name, what, replicas_by_image(server, name).await);
+}
+
+async fn set_image(client: &reqwest::Client, server: &common::TestServer, name: &str, image: &str) {
+    let url = server.url(&format!("/apis/apps/v1/namespaces/default/deployments/{}", name));
+    let mut deployment: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    deployment["spec"]["template"]["spec"]["containers"][0]["image"] = json!(image);
+    let resp = client.put(&url).json(&deployment).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+}
+
+#[tokio::test]
+async fn test_rolling_update_replaces_old_replicaset() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(server.url("/apis/apps/v1/namespaces/default/deployments"))
+        .json(&deployment("web", 3, "app:v1", json!({})))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    wait_for(&server, "web", "became available", |d| d["status"]["availableReplicas"] == 3).await;
+
+    set_image(&client, &server, "web", "app:v2").await;
+    let deployment = wait_for(&server, "web", "rolled out", |d| {
+        d["status"]["updatedReplicas"] == 3 && d["status"]["replicas"] == 3 && d["status"]["observedGeneration"] == d["metadata"]["generation"]
+    }).await;
+    let progressing = deployment["status"]["conditions"].as_array().unwrap().iter().find(|c| c["type"] == "Progressing").unwrap().clone();
+    assert_eq!(progressing["reason"], "NewReplicaSetAvailable");
+    assert_eq!(replicas_by_image(&server, "web").await, [("app:v1".to_string(), 0), ("app:v2".to_string(), 3)]);
+}
+
+#[tokio::test]
+async fn test_rollout_pauses_at_annotated_percentages() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let annotations = json!({ "krust.io/rollout-pause-at": "50" });
+    let resp = client
+        .post(server.url("/apis/apps/v1/namespaces/default/deployments"))
+        .json(&deployment("canary", 4, "app:v1", annotations))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    wait_for(&server, "canary", "became available", |d| d["status"]["availableReplicas"] == 4).await;
+
+    set_image(&client, &server, "canary", "app:v2").await;
+    let deployment = wait_for(&server, "canary", "paused", |d| d["spec"]["paused"] == true).await;
+    assert_eq!(replicas_by_image(&server, "canary").await, [("app:v1".to_string(), 2), ("app:v2".to_string(), 2)]);
+
+    // It stays there until resumed
+    tokio::time::sleep(Duration::from_secs(3)).await;
+    assert_eq!(replicas_by_image(&server, "canary").await, [("app:v1".to_string(), 2), ("app:v2".to_string(), 2)]);
+    let deployment_status = server.storage.deployments().get("default", "canary").await.unwrap()["status"].clone();
+    let progressing = deployment_status["conditions"].as_array().unwrap().iter().find(|c| c["type"] == "Progressing").unwrap().clone();
+    assert_eq!(progressing["reason"], "DeploymentPaused");
+
+    let uid = deployment["metadata"]["uid"].as_str().unwrap();
+    let url = server.url(&format!("/api/v1/namespaces/default/events?fieldSelector=involvedObject.uid%3D{}", uid));
+    let events: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    let paused = events["items"].as_array().unwrap().iter().find(|e| e["reason"] == "RolloutPaused").unwrap();
+    assert!(paused["message"].as_str().unwrap().starts_with("Paused at 50%: 2 of 4 replicas"));
+
+    // What `kubectl rollout resume` sends
+    let resp = client
+        .patch(server.url("/apis/apps/v1/namespaces/default/deployments/canary"))
+        .header("Content-Type", "application/merge-patch+json")
+        .body(json!({ "spec": { "paused": null } }).to_string())
+        .send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    wait_for(&server, "canary", "finished rolling out", |d| d["status"]["updatedReplicas"] == 4 && d["status"]["replicas"] == 4).await;
+    assert_eq!(replicas_by_image(&server, "canary").await, [("app:v1".to_string(), 0), ("app:v2".to_string(), 4)]);
+}
+
+// The deployment's ReplicaSets as `kubectl rollout history` finds them: by
+// the deployment's selector, with their revisions
+async fn history(client: &reqwest::Client, server: &common::TestServer, name: &str) -> Vec<(String, String)> {
+    let url = server.url(&format!("/apis/apps/v1/namespaces/default/replicasets?labelSelector=app%3D{}", name));
+    let list: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    let mut revisions: Vec<(String, String)> = list["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|rs| {
+            let revision = rs["metadata"]["annotations"]["deployment.kubernetes.io/revision"].as_str().unwrap().to_string();
+            let image = rs["spec"]["template"]["spec"]["containers"][0]["image"].as_str().unwrap().to_string();
+            (revision, image)
+        })
+        .collect();
+    revisions.sort();
+    revisions
+}
+
+#[tokio::test]
+async fn test_rollout_undo_revives_previous_revision() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let annotations = json!({ "kubernetes.io/change-cause": "first release" });
+    let resp = client
+        .post(server.url("/apis/apps/v1/namespaces/default/deployments"))
+        .json(&deployment("web", 2, "app:v1", annotations))
+        .send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    wait_for(&server, "web", "became available", |d| d["status"]["availableReplicas"] == 2).await;
+    set_image(&client, &server, "web", "app:v2").await;
+    let deployment = wait_for(&server, "web", "rolled out", |d| {
+        d["status"]["updatedReplicas"] == 2 && d["status"]["replicas"] == 2 && d["status"]["observedGeneration"] == d["metadata"]["generation"]
+    }).await;
+    assert_eq!(deployment["metadata"]["annotations"]["deployment.kubernetes.io/revision"], "2");
+    assert_eq!(history(&client, &server, "web").await, [("1".to_string(), "app:v1".to_string()), ("2".to_string(), "app:v2".to_string())]);
+
+    // Each revision's pods carry its template hash
+    let list = server.storage.replicasets().list(Some("default")).await.unwrap();
+    let first = list["items"].as_array().unwrap().iter().find(|rs| rs["metadata"]["annotations"]["deployment.kubernetes.io/revision"] == "1").unwrap().clone();
+    let hash = first["metadata"]["labels"]["pod-template-hash"].as_str().unwrap();
+    assert_eq!(first["metadata"]["name"], format!("web-{}", hash));
+    assert_eq!(first["spec"]["selector"]["matchLabels"]["pod-template-hash"], hash);
+    assert_eq!(first["metadata"]["annotations"]["kubernetes.io/change-cause"], "first release");
+
+    // What `kubectl rollout undo` sends: revision 1's template, as kubectl
+    // decodes it, and its annotations
+    let mut template = first["spec"]["template"].clone();
+    template["metadata"]["labels"].as_object_mut().unwrap().remove("pod-template-hash");
+    template["metadata"]["creationTimestamp"] = json!(null);
+    let undo = json!([
+        { "op": "replace", "path": "/spec/template", "value": template },
+        { "op": "replace", "path": "/metadata/annotations", "value": {
+            "deployment.kubernetes.io/revision": "2",
+            "kubernetes.io/change-cause": "first release"
+        } }
+    ]);
+    let resp = client
+        .patch(server.url("/apis/apps/v1/namespaces/default/deployments/web"))
+        .header("Content-Type", "application/json-patch+json")
+        .body(undo.to_string())
+        .send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+
+    let deployment = wait_for(&server, "web", "rolled back", |d| {
+        d["metadata"]["annotations"]["deployment.kubernetes.io/revision"] == "3" && d["status"]["updatedReplicas"] == 2 && d["status"]["replicas"] == 2
+    }).await;
+    assert_eq!(deployment["spec"]["template"]["spec"]["containers"][0]["image"], "app:v1");
+    assert_eq!(replicas_by_image(&server, "web").await, [("app:v1".to_string(), 2), ("app:v2".to_string(), 0)]);
+    assert_eq!(history(&client, &server, "web").await, [("2".to_string(), "app:v2".to_string()), ("3".to_string(), "app:v1".to_string())]);
+}
+
+#[tokio::test]
+async fn test_revision_history_limit_trims_old_replicasets() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let mut manifest = deployment("trimmed", 1, "app:v1", json!({}));
+    manifest["spec"]["revisionHistoryLimit"] = json!(1);
+    let resp = client.post(server.url("/apis/apps/v1/namespaces/default/deployments")).json(&manifest).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+
+    for (revision, image) in [(1, "app:v1"), (2, "app:v2"), (3, "app:v3")] {
+        if revision > 1 {
+            set_image(&client, &server, "trimmed", image).await;
+        }
+        wait_for(&server, "trimmed", "rolled out", |d| {
+            d["metadata"]["annotations"]["deployment.kubernetes.io/revision"] == revision.to_string()
+                && d["status"]["updatedReplicas"] == 1
+                && d["status"]["replicas"] == 1
+                && d["status"]["availableReplicas"] == 1
+        }).await;
+    }
+
+    for _ in 0..40 {
+        if history(&client, &server, "trimmed").await.len() == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    assert_eq!(history(&client, &server, "trimmed").await, [("2".to_string(), "app:v2".to_string()), ("3".to_string(), "app:v3".to_string())]);
+}
+
+// ControllerRevisions of a StatefulSet: (revision, image), oldest first
+async fn controller_revisions(client: &reqwest::Client, server: &common::TestServer, name: &str) -> Vec<(i64, String)> {
+    let url = server.url(&format!("/apis/apps/v1/namespaces/default/controllerrevisions?labelSelector=app%3D{}", name));
+    let list: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    let mut revisions: Vec<(i64, String)> = list["items"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| {
+            let image = r["data"]["spec"]["template"]["spec"]["containers"][0]["image"].as_str().unwrap().to_string();
+            (r["revision"].as_i64().unwrap(), image)
+        })
+        .collect();
+    revisions.sort();
+    revisions
+}
+
+async fn wait_for_revisions(client: &reqwest::Client, server: &common::TestServer, name: &str, expected: &[(i64, &str)]) -> Vec<(i64, String)> {
+    let expected: Vec<(i64, String)> = expected.iter().map(|(revision, image)| (*revision, image.to_string())).collect();
+    for _ in 0..40 {
+        let revisions = controller_revisions(client, server, name).await;
+        if revisions == expected {
+            return revisions;
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    panic!("{} never had revisions {:?}: {:?}", name, expected, controller_revisions(client, server, name).await);
+}
+
+#[tokio::test]
+async fn test_statefulset_history_and_undo() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let statefulset = json!({
+        "apiVersion": "apps/v1",
+        "kind": "StatefulSet",
+        "metadata": { "name": "db" },
+        "spec": {
+            "serviceName": "db",
+            "replicas": 1,
+            "selector": { "matchLabels": { "app": "db" } },
+            "template": {
+                "metadata": { "labels": { "app": "db" } },
+                "spec": { "containers": [{ "name": "db", "image": "db:v1" }] }
+            }
+        }
+    });
+    let url = server.url("/apis/apps/v1/namespaces/default/statefulsets/db");
+    let resp = client.post(server.url("/apis/apps/v1/namespaces/default/statefulsets")).json(&statefulset).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    wait_for_revisions(&client, &server, "db", &[(1, "db:v1")]).await;
+
+    // What `kubectl set image statefulset/db db=db:v2` amounts to
+    let patch = json!({ "spec": { "template": { "spec": { "containers": [{ "name": "db", "image": "db:v2" }] } } } });
+    let resp = client
+        .patch(&url)
+        .header("Content-Type", "application/merge-patch+json")
+        .body(patch.to_string())
+        .send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    wait_for_revisions(&client, &server, "db", &[(1, "db:v1"), (2, "db:v2")]).await;
+
+    // What `kubectl rollout undo statefulset/db` sends: revision 1's data
+    let list: Value = client
+        .get(server.url("/apis/apps/v1/namespaces/default/controllerrevisions?labelSelector=app%3Ddb"))
+        .send().await.unwrap().json().await.unwrap();
+    let first = list["items"].as_array().unwrap().iter().find(|r| r["revision"] == 1).unwrap().clone();
+    assert_eq!(first["metadata"]["ownerReferences"][0]["kind"], "StatefulSet");
+    let resp = client
+        .patch(&url)
+        .header("Content-Type", "application/strategic-merge-patch+json")
+        .body(first["data"].to_string())
+        .send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let rolled_back: Value = resp.json().await.unwrap();
+    assert_eq!(rolled_back["spec"]["template"]["spec"]["containers"][0]["image"], "db:v1");
+    assert!(rolled_back["spec"]["template"].get("$patch").is_none());
+
+    wait_for_revisions(&client, &server, "db", &[(2, "db:v2"), (3, "db:v1")]).await;
+    for _ in 0..20 {
+        let statefulset: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+        if statefulset["status"]["updateRevision"] == first["metadata"]["name"] {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    panic!("StatefulSet db never reported revision {}", first["metadata"]["name"]);
+}
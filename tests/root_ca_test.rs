@@ -0,0 +1,98 @@
+use reqwest;
+use serde_json::{json, Value};
+use std::io::Write;
+use std::time::Duration;
+
+mod common;
+
+async fn wait_for_root_ca(client: &reqwest::Client, server: &common::TestServer, namespace: &str) -> Value {
+    let url = server.url(&format!("/api/v1/namespaces/{}/configmaps/kube-root-ca.crt", namespace));
+    for _ in 0..20 {
+        let resp = client.get(&url).send().await.unwrap();
+        if resp.status() == 200 {
+            return resp.json().await.unwrap();
+        }
+        tokio::time::sleep(Duration::from_millis(250)).await;
+    }
+    panic!("kube-root-ca.crt never appeared in namespace {}", namespace);
+}
+
+#[tokio::test]
+async fn test_root_ca_is_published_in_every_namespace() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let default = wait_for_root_ca(&client, &server, "default").await;
+    let bundle = default["data"]["ca.crt"].as_str().unwrap().to_string();
+    assert!(bundle.starts_with("-----BEGIN CERTIFICATE-----"), "not a PEM certificate: {}", bundle);
+    assert_eq!(wait_for_root_ca(&client, &server, "kube-system").await["data"]["ca.crt"], bundle);
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces"))
+        .json(&json!({ "apiVersion": "v1", "kind": "Namespace", "metadata": { "name": "team-a" } }))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    assert_eq!(wait_for_root_ca(&client, &server, "team-a").await["data"]["ca.crt"], bundle);
+}
+
+#[tokio::test]
+async fn test_root_ca_is_restored() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/api/v1/namespaces/default/configmaps/kube-root-ca.crt");
+
+    let mut published = wait_for_root_ca(&client, &server, "default").await;
+    let bundle = published["data"]["ca.crt"].clone();
+
+    // Edits are reverted
+    published["data"] = json!({ "ca.crt": "tampered" });
+    let resp = client.put(&url).json(&published).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let mut restored = Value::Null;
+    for _ in 0..20 {
+        tokio::time::sleep(Duration::from_millis(250)).await;
+        restored = client.get(&url).send().await.unwrap().json().await.unwrap();
+        if restored["data"]["ca.crt"] == bundle {
+            break;
+        }
+    }
+    assert_eq!(restored["data"]["ca.crt"], bundle);
+
+    // And so is deleting it
+    let resp = client.delete(&url).send().await.unwrap();
+    assert!(resp.status().is_success());
+    assert_eq!(wait_for_root_ca(&client, &server, "default").await["data"]["ca.crt"], bundle);
+}
+
+#[tokio::test]
+async fn test_root_ca_file() {
+    let bundle = "-----BEGIN CERTIFICATE-----\nMIIBkrust\n-----END CERTIFICATE-----\n";
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(bundle.as_bytes()).unwrap();
+
+    let yaml = format!("apiServer:\n  rootCAFile: {}\n", file.path().display());
+    let config = krust::Config::parse(&yaml).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let published = wait_for_root_ca(&client, &server, "default").await;
+    assert_eq!(published["data"]["ca.crt"], bundle);
+}
+
+#[tokio::test]
+async fn test_generated_ca_is_kept_in_the_data_dir() {
+    let dir = tempfile::tempdir().unwrap();
+    let yaml = format!("dataDir: {}\n", dir.path().display());
+    let client = reqwest::Client::new();
+
+    let first = common::TestServer::start_with_config(krust::Config::parse(&yaml).unwrap()).await;
+    let bundle = wait_for_root_ca(&client, &first, "default").await["data"]["ca.crt"].clone();
+    assert_eq!(std::fs::read_to_string(dir.path().join("pki/ca.crt")).unwrap(), bundle.as_str().unwrap());
+    assert!(std::fs::read_to_string(dir.path().join("pki/ca.key")).unwrap().contains("PRIVATE KEY"));
+
+    // A restart publishes the same CA
+    let second = common::TestServer::start_with_config(krust::Config::parse(&yaml).unwrap()).await;
+    assert_eq!(wait_for_root_ca(&client, &second, "default").await["data"]["ca.crt"], bundle);
+}
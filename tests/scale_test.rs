@@ -0,0 +1,144 @@
+use reqwest;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+const APPS: &str = "/apis/apps/v1/namespaces/default";
+
+fn deployment(name: &str) -> Value {
+    json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": name, "labels": { "team": "web" } },
+        "spec": {
+            "selector": { "matchLabels": { "app": name } },
+            "strategy": { "type": "Recreate" },
+            "template": {
+                "metadata": { "labels": { "app": name } },
+                "spec": { "containers": [{ "name": "web", "image": "nginx:1.25" }] }
+            }
+        }
+    })
+}
+
+async fn send(request: reqwest::RequestBuilder, expected: u16) -> Value {
+    let resp = request.send().await.unwrap();
+    assert_eq!(resp.status(), expected);
+    resp.json().await.unwrap_or(Value::Null)
+}
+
+#[tokio::test]
+async fn test_absent_replicas_default_to_one() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url(&format!("{}/deployments/web", APPS));
+
+    let created = send(client.post(server.url(&format!("{}/deployments", APPS))).json(&deployment("web")), 201).await;
+    assert_eq!(created["spec"]["replicas"], 1);
+    let scale = send(client.get(format!("{}/scale", url)), 200).await;
+    assert_eq!(scale["spec"]["replicas"], 1);
+
+    // Scaled down to zero, the count is kept rather than defaulted again
+    let mut zero = created.clone();
+    zero["spec"]["replicas"] = json!(0);
+    assert_eq!(send(client.put(&url).json(&zero), 200).await["spec"]["replicas"], 0);
+
+    // An update leaving replicas out means the default
+    let updated = send(client.put(&url).json(&deployment("web")), 200).await;
+    assert_eq!(updated["spec"]["replicas"], 1);
+
+    let statefulset = json!({
+        "apiVersion": "apps/v1",
+        "kind": "StatefulSet",
+        "metadata": { "name": "db" },
+        "spec": {
+            "serviceName": "db",
+            "selector": { "matchLabels": { "app": "db" } },
+            "template": {
+                "metadata": { "labels": { "app": "db" } },
+                "spec": { "containers": [{ "name": "db", "image": "postgres:16" }] }
+            }
+        }
+    });
+    send(client.post(server.url(&format!("{}/statefulsets", APPS))).json(&statefulset), 201).await;
+    let scale = send(client.get(server.url(&format!("{}/statefulsets/db/scale", APPS))), 200).await;
+    assert_eq!(scale["spec"]["replicas"], 1);
+}
+
+#[tokio::test]
+async fn test_scaling_keeps_the_spec_and_resizes_the_replicaset() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url(&format!("{}/deployments/web", APPS));
+
+    send(client.post(server.url(&format!("{}/deployments", APPS))).json(&deployment("web")), 201).await;
+
+    let scale = json!({ "apiVersion": "autoscaling/v1", "kind": "Scale", "spec": { "replicas": 3 } });
+    let scaled = send(client.put(format!("{}/scale", url)).json(&scale), 200).await;
+    assert_eq!(scaled["kind"], "Scale");
+    assert_eq!(scaled["spec"]["replicas"], 3);
+
+    let patched = send(
+        client
+            .patch(format!("{}/scale", url))
+            .header("Content-Type", "application/merge-patch+json")
+            .json(&json!({ "spec": { "replicas": 2 } })),
+        200,
+    )
+    .await;
+    assert_eq!(patched["spec"]["replicas"], 2);
+
+    let stored = send(client.get(&url), 200).await;
+    assert_eq!(stored["spec"]["replicas"], 2);
+    assert_eq!(stored["spec"]["strategy"]["type"], "Recreate");
+    assert_eq!(stored["spec"]["template"]["spec"]["containers"][0]["image"], "nginx:1.25");
+    assert_eq!(stored["metadata"]["labels"]["team"], "web");
+
+    // The deployment controller carries the new count over to its ReplicaSet
+    let mut replicas = Value::Null;
+    for _ in 0..20 {
+        let list = send(client.get(server.url(&format!("{}/replicasets", APPS))), 200).await;
+        replicas = list["items"][0]["spec"]["replicas"].clone();
+        if replicas == 2 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+    assert_eq!(replicas, 2);
+
+    // A Scale has to say how many replicas it wants
+    send(client.put(format!("{}/scale", url)).json(&json!({ "kind": "Scale", "spec": {} })), 400).await;
+    send(client.put(format!("{}/scale", url)).json(&json!({ "kind": "Scale", "spec": { "replicas": -1 } })), 400).await;
+}
+
+#[tokio::test]
+async fn test_replicaset_scale() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url(&format!("{}/replicasets/web", APPS));
+
+    let replicaset = json!({
+        "apiVersion": "apps/v1",
+        "kind": "ReplicaSet",
+        "metadata": { "name": "web" },
+        "spec": {
+            "selector": { "matchLabels": { "app": "web" } },
+            "template": {
+                "metadata": { "labels": { "app": "web" } },
+                "spec": { "containers": [{ "name": "web", "image": "nginx:1.25" }] }
+            }
+        }
+    });
+    let created = send(client.post(server.url(&format!("{}/replicasets", APPS))).json(&replicaset), 201).await;
+    assert_eq!(created["spec"]["replicas"], 1);
+
+    let scale = json!({ "kind": "Scale", "spec": { "replicas": 0 } });
+    assert_eq!(send(client.put(format!("{}/scale", url)).json(&scale), 200).await["spec"]["replicas"], 0);
+    let stored = send(client.get(&url), 200).await;
+    assert_eq!(stored["spec"]["replicas"], 0);
+    assert_eq!(stored["spec"]["template"]["spec"]["containers"][0]["name"], "web");
+
+    send(client.put(format!("{}/scale", url)).json(&json!({ "kind": "Scale", "spec": {} })), 400).await;
+    send(client.put(server.url(&format!("{}/replicasets/missing/scale", APPS))).json(&scale), 404).await;
+}
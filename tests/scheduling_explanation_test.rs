@@ -0,0 +1,88 @@
+use reqwest;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+const CLUSTER: &str = r#"
+nodes:
+  krust-node:
+    cpu: "1"
+  gpu-1:
+    cpu: "4"
+    taints:
+      - key: nvidia.com/gpu
+        effect: NoSchedule
+"#;
+
+fn pod_requesting(name: &str, cpu: &str, node_selector: Value) -> Value {
+    common::pod(name, json!({
+        "nodeSelector": node_selector,
+        "containers": [{ "name": "app", "image": "nginx:latest", "resources": { "requests": { "cpu": cpu } } }]
+    }))
+}
+
+// Polls the scheduling report until it explains the pod
+async fn explanation(client: &reqwest::Client, server: &common::TestServer, name: &str) -> Value {
+    let url = server.url(&format!("/krust/scheduling?namespace=default&name={}", name));
+    for _ in 0..30 {
+        let report: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+        if let Some(pod) = report["pods"].as_array().and_then(|pods| pods.first()) {
+            return pod.clone();
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    panic!("pod {} was never explained", name);
+}
+
+#[tokio::test]
+async fn test_unschedulable_pods_are_explained() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let resp = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod_requesting("big", "2", json!(null))).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let explained = explanation(&client, &server, "big").await;
+    let message = "0/2 nodes are available: 1 Insufficient cpu, 1 node(s) had untolerated taint {nvidia.com/gpu: }.";
+    assert_eq!(explained["message"], message);
+    let nodes = explained["nodes"].as_array().unwrap();
+    let reasons = |node: &str| nodes.iter().find(|n| n["name"] == node).unwrap()["reasons"].clone();
+    assert_eq!(reasons("krust-node"), json!(["Insufficient cpu"]));
+    assert_eq!(reasons("gpu-1"), json!(["node(s) had untolerated taint {nvidia.com/gpu: }"]));
+
+    // kubectl describe shows the same on the pod and in its events
+    let pod = server.storage.pods().get("default", "big").await.unwrap();
+    let scheduled = pod["status"]["conditions"].as_array().unwrap().iter().find(|c| c["type"] == "PodScheduled").unwrap().clone();
+    assert_eq!(scheduled["status"], "False");
+    assert_eq!(scheduled["reason"], "Unschedulable");
+    assert_eq!(scheduled["message"], message);
+    let uid = pod["metadata"]["uid"].as_str().unwrap();
+    let url = server.url(&format!("/api/v1/namespaces/default/events?fieldSelector=involvedObject.uid%3D{}", uid));
+    let events: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    assert!(events["items"].as_array().unwrap().iter().any(|e| e["reason"] == "FailedScheduling" && e["message"] == message));
+
+    // Deleted pods are no longer explained
+    client.delete(server.url("/api/v1/namespaces/default/pods/big?gracePeriodSeconds=0")).send().await.unwrap();
+    tokio::time::sleep(Duration::from_millis(1500)).await;
+    let report: Value = client.get(server.url("/krust/scheduling")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(report["pods"], json!([]));
+}
+
+#[tokio::test]
+async fn test_node_selector_mismatch_is_explained() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let selector = json!({ "disktype": "ssd" });
+    let resp = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod_requesting("picky", "100m", selector)).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+
+    let explained = explanation(&client, &server, "picky").await;
+    assert_eq!(
+        explained["message"],
+        "0/2 nodes are available: 1 node(s) didn't match Pod's node affinity/selector, 1 node(s) had untolerated taint {nvidia.com/gpu: }."
+    );
+}
@@ -0,0 +1,74 @@
+use krust::Storage;
+use serde_json::Value;
+
+mod common;
+
+#[tokio::test]
+async fn test_schema_report() {
+    let server = common::TestServer::start().await;
+
+    let report: Value = reqwest::get(server.url("/krust/schema")).await.unwrap().json().await.unwrap();
+    assert_eq!(report["kind"], "SchemaReport");
+    assert_eq!(report["upToDate"], true);
+    assert_eq!(report["version"], report["latest"]);
+    assert!(report["pending"].as_array().unwrap().is_empty());
+    let applied = report["applied"].as_array().unwrap();
+    assert_eq!(applied[0]["version"], 1);
+    assert_eq!(applied[0]["description"], "initial schema");
+    assert!(applied[0]["installedOn"].is_string());
+}
+
+#[tokio::test]
+async fn test_schema_from_other_versions() {
+    let storage = Storage::in_memory().await.unwrap();
+
+    // A fresh database lacks every migration
+    let status = storage.schema().await.unwrap();
+    assert_eq!(status.version, 0);
+    assert_eq!(status.pending.len() as i64, status.latest);
+    assert!(!status.up_to_date());
+    storage.migrate().await.unwrap();
+    let latest = storage.schema().await.unwrap().latest;
+
+    // A newer krust's migrations are passed over
+    sqlx::query(
+        "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+         VALUES (999, 'from the future', true, x'00', 0)",
+    )
+    .execute(storage.pool())
+    .await
+    .unwrap();
+    storage.migrate().await.unwrap();
+    let status = storage.schema().await.unwrap();
+    assert_eq!((status.version, status.latest), (999, latest));
+    assert_eq!(status.unknown[0].description, "from the future");
+    assert!(status.up_to_date());
+
+    // One changed since it was applied, or half applied, is refused
+    sqlx::query("UPDATE _sqlx_migrations SET checksum = x'00' WHERE version = 1").execute(storage.pool()).await.unwrap();
+    let error = storage.migrate().await.unwrap_err().to_string();
+    assert!(error.contains("migration 001 (initial schema) was changed after it was applied"), "{}", error);
+    sqlx::query("UPDATE _sqlx_migrations SET success = false WHERE version = 999").execute(storage.pool()).await.unwrap();
+    let status = storage.schema().await.unwrap();
+    assert_eq!(status.failed[0].version, 999);
+    assert!(!status.compatible());
+}
+
+// Migrations from here on only add to the schema, so older krusts can keep
+// using databases newer ones have migrated
+#[test]
+fn test_migrations_are_additive() {
+    const LAST_DESTRUCTIVE: i64 = 31;
+    for entry in std::fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/migrations")).unwrap() {
+        let path = entry.unwrap().path();
+        let name = path.file_name().unwrap().to_string_lossy().to_string();
+        let version: i64 = name.split('_').next().unwrap().parse().unwrap();
+        if version <= LAST_DESTRUCTIVE {
+            continue;
+        }
+        let sql = std::fs::read_to_string(&path).unwrap().to_uppercase();
+        for statement in ["DROP ", "RENAME ", "DELETE FROM ", "UPDATE "] {
+            assert!(!sql.contains(statement), "{} has {}", name, statement.trim());
+        }
+    }
+}
@@ -0,0 +1,117 @@
+use reqwest;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+// A node whose container runtime, like Docker Desktop's, has no AppArmor
+const CLUSTER: &str = r#"
+nodes:
+  mac-1:
+    securityProfiles: [seccomp]
+"#;
+
+fn annotated_pod(name: &str, annotations: Value, spec: Value) -> Value {
+    common::with_metadata(common::pod(name, spec), json!({ "annotations": annotations }))
+}
+
+// Polls until the kubelet has reported on the pod's security profiles
+async fn profiles_condition(server: &common::TestServer, name: &str) -> (Value, Value) {
+    for _ in 0..50 {
+        let pod = server.storage.pods().get("default", name).await.unwrap();
+        let conditions = pod["status"]["conditions"].as_array().cloned().unwrap_or_default();
+        if let Some(condition) = conditions.into_iter().find(|c| c["type"] == "krust.io/SecurityProfilesApplied") {
+            return (pod, condition);
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("pod {} never got a security profiles condition", name);
+}
+
+#[tokio::test]
+async fn test_profiles_are_applied() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pods = server.url("/api/v1/namespaces/default/pods");
+
+    let spec = json!({ "securityContext": { "seccompProfile": { "type": "RuntimeDefault" } } });
+    let annotations = json!({ "container.apparmor.security.beta.kubernetes.io/app": "runtime/default" });
+    assert_eq!(common::post(&client, &pods, &annotated_pod("web", annotations, spec)).await.status(), 201);
+
+    let (pod, condition) = profiles_condition(&server, "web").await;
+    assert_eq!(condition["status"], "True");
+    assert!(pod["metadata"]["annotations"]["krust.io/unsupported-fields"].is_null());
+}
+
+#[tokio::test]
+async fn test_unsupported_profiles_are_reported() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+    let pods = server.url("/api/v1/namespaces/default/pods");
+
+    let spec = json!({
+        "nodeSelector": { "kubernetes.io/hostname": "mac-1" },
+        "securityContext": { "seccompProfile": { "type": "RuntimeDefault" } }
+    });
+    let annotations = json!({ "container.apparmor.security.beta.kubernetes.io/app": "localhost/k8s-nginx" });
+    assert_eq!(common::post(&client, &pods, &annotated_pod("web", annotations, spec)).await.status(), 201);
+
+    let (pod, condition) = profiles_condition(&server, "web").await;
+    assert_eq!(condition["status"], "False");
+    assert_eq!(condition["reason"], "ProfilesUnsupported");
+    assert_eq!(
+        condition["message"],
+        "AppArmor profile Localhost k8s-nginx is not applied: the container runtime doesn't support AppArmor"
+    );
+
+    let recorded = pod["metadata"]["annotations"]["krust.io/unsupported-fields"].as_str().unwrap();
+    let fields: Vec<String> = serde_json::from_str(recorded).unwrap();
+    assert_eq!(fields, ["metadata.annotations[container.apparmor.security.beta.kubernetes.io/app]"]);
+
+    let uid = pod["metadata"]["uid"].as_str().unwrap();
+    let url = server.url(&format!("/api/v1/namespaces/default/events?fieldSelector=involvedObject.uid%3D{}", uid));
+    let events: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+    let warning = events["items"].as_array().unwrap().iter().find(|e| e["reason"] == "SecurityProfileUnsupported").unwrap();
+    assert_eq!(warning["type"], "Warning");
+
+    let compat: Value = client.get(server.url("/krust/compat")).send().await.unwrap().json().await.unwrap();
+    assert!(compat.to_string().contains("can't apply AppArmor profiles"));
+}
+
+#[tokio::test]
+async fn test_invalid_profiles_are_rejected() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pods = server.url("/api/v1/namespaces/default/pods");
+
+    let spec = json!({ "securityContext": { "seccompProfile": { "type": "Localhost" } } });
+    assert_eq!(common::post(&client, &pods, &annotated_pod("no-path", json!({}), spec)).await.status(), 422);
+
+    let spec = json!({ "containers": [{
+        "name": "app",
+        "image": "nginx:latest",
+        "securityContext": { "appArmorProfile": { "type": "Strict" } }
+    }] });
+    assert_eq!(common::post(&client, &pods, &annotated_pod("bad-type", json!({}), spec)).await.status(), 422);
+
+    let annotations = json!({ "container.apparmor.security.beta.kubernetes.io/app": "docker-default" });
+    assert_eq!(common::post(&client, &pods, &annotated_pod("bad-annotation", annotations, json!({}))).await.status(), 422);
+}
+
+#[tokio::test]
+async fn test_profiles_alone_are_not_unsupported_fields() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pods = server.url("/api/v1/namespaces/default/pods");
+
+    let spec = json!({ "containers": [{
+        "name": "app",
+        "image": "nginx:latest",
+        "securityContext": { "seccompProfile": { "type": "Unconfined" } }
+    }] });
+    assert_eq!(common::post(&client, &pods, &annotated_pod("web", json!({}), spec)).await.status(), 201);
+
+    let pod = server.storage.pods().get("default", "web").await.unwrap();
+    assert!(pod["metadata"]["annotations"]["krust.io/unsupported-fields"].is_null());
+}
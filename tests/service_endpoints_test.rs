@@ -1,16 +1,13 @@
 use reqwest;
 use serde_json::json;
 
+mod common;
+
 #[tokio::test]
 async fn test_service_endpoints_sync() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let api_base = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let api_base = server.url("/api/v1");
     
     // Create a Service with a selector
     let service = json!({
@@ -200,14 +197,9 @@ async fn test_service_endpoints_sync() {
 
 #[tokio::test]
 async fn test_endpoints_crud() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let api_base = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let api_base = server.url("/api/v1");
     
     // Create Endpoints manually
     let endpoints = json!({
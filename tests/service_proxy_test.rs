@@ -0,0 +1,266 @@
+use reqwest;
+use serde_json::{json, Value};
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream, UdpSocket};
+
+mod common;
+
+fn service(name: &str, spec: Value) -> Value {
+    json!({ "apiVersion": "v1", "kind": "Service", "metadata": { "name": name }, "spec": spec })
+}
+
+// A hostNetwork pod, so its endpoint is this machine, serving `dns` on `port`
+fn dns_pod(port: u16) -> Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "dns", "labels": { "app": "dns" } },
+        "spec": {
+            "hostNetwork": true,
+            "containers": [{
+                "name": "dns",
+                "image": "coredns/coredns:1.11.1",
+                "ports": [
+                    { "name": "dns", "containerPort": port, "protocol": "UDP" },
+                    { "name": "dns-tcp", "containerPort": port, "protocol": "TCP" }
+                ]
+            }]
+        }
+    })
+}
+
+// A UDP server on the node that answers every datagram
+async fn udp_echo() -> u16 {
+    let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let port = socket.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        let mut buf = [0u8; 512];
+        while let Ok((n, peer)) = socket.recv_from(&mut buf).await {
+            let _ = socket.send_to(&[b"echo:", &buf[..n]].concat(), peer).await;
+        }
+    });
+    port
+}
+
+// Sends datagrams to `addr` until one is answered
+async fn exchange(addr: (&str, u16), message: &[u8]) -> Vec<u8> {
+    let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let mut buf = [0u8; 512];
+    for _ in 0..30 {
+        client.send_to(message, addr).await.unwrap();
+        if let Ok(Ok((n, _))) = tokio::time::timeout(Duration::from_millis(500), client.recv_from(&mut buf)).await {
+            return buf[..n].to_vec();
+        }
+    }
+    panic!("no reply from {:?}", addr);
+}
+
+#[tokio::test]
+async fn test_sctp_ports_are_refused() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let spec = json!({ "selector": { "app": "sig" }, "ports": [{ "port": 80 }, { "port": 9899, "protocol": "SCTP" }] });
+    let response = common::post(&client, &server.url("/api/v1/namespaces/default/services"), &service("sig", spec)).await;
+    assert_eq!(response.status(), 422);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(body["reason"], "Invalid");
+    assert_eq!(
+        body["message"],
+        "Service \"sig\" is invalid: spec.ports[1].protocol: Unsupported value: \"SCTP\": supported values: \"TCP\", \"UDP\""
+    );
+}
+
+#[tokio::test]
+async fn test_node_ports_are_allocated() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let spec = json!({ "type": "NodePort", "selector": { "app": "web" }, "ports": [{ "port": 80 }, { "port": 53, "protocol": "UDP", "name": "dns" }] });
+    let created = common::create(&client, &server.url("/api/v1/namespaces/default/services"), service("web", spec)).await;
+    assert!(created["spec"]["clusterIP"].is_string());
+    let first = created["spec"]["ports"][0]["nodePort"].as_i64().unwrap();
+    let second = created["spec"]["ports"][1]["nodePort"].as_i64().unwrap();
+    assert!((30000..=32767).contains(&first));
+    assert!((30000..=32767).contains(&second));
+    assert_ne!(first, second);
+
+    let spec = json!({ "type": "NodePort", "ports": [{ "port": 80, "nodePort": first }] });
+    let response = common::post(&client, &server.url("/api/v1/namespaces/default/services"), &service("taken", spec)).await;
+    assert_eq!(response.status(), 422);
+    let body: Value = response.json().await.unwrap();
+    assert_eq!(
+        body["message"],
+        format!("Service \"taken\" is invalid: spec.ports[0].nodePort: Invalid value: {}: provided port is already allocated", first)
+    );
+
+    let spec = json!({ "type": "NodePort", "ports": [{ "port": 80, "nodePort": 8080 }] });
+    let response = common::post(&client, &server.url("/api/v1/namespaces/default/services"), &service("low", spec)).await;
+    assert_eq!(response.status(), 422);
+    let body: Value = response.json().await.unwrap();
+    assert!(body["message"].as_str().unwrap().contains("The range of valid ports is 30000-32767"));
+}
+
+#[tokio::test]
+async fn test_udp_external_ip_with_named_target_port() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let echo_port = udp_echo().await;
+
+    // A free UDP port on the external address
+    let probe = UdpSocket::bind("127.0.0.3:0").await.unwrap();
+    let service_port = probe.local_addr().unwrap().port();
+    drop(probe);
+
+    common::create_pod(&client, &server, dns_pod(echo_port)).await;
+    let spec = json!({
+        "selector": { "app": "dns" },
+        "externalIPs": ["127.0.0.3"],
+        "ports": [{ "name": "dns", "port": service_port, "targetPort": "dns", "protocol": "UDP" }]
+    });
+    common::create(&client, &server.url("/api/v1/namespaces/default/services"), service("dns", spec)).await;
+
+    assert_eq!(exchange(("127.0.0.3", service_port), b"query").await, b"echo:query");
+
+    let endpoints: Value = client.get(server.url("/api/v1/namespaces/default/endpoints/dns")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(endpoints["subsets"][0]["ports"], json!([{ "name": "dns", "port": echo_port, "protocol": "UDP" }]));
+}
+
+#[tokio::test]
+async fn test_udp_node_port() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let echo_port = udp_echo().await;
+
+    // A node port nothing else on this machine is using
+    let node_port = (31000..32767)
+        .find(|port| std::net::UdpSocket::bind(("0.0.0.0", *port)).is_ok())
+        .unwrap();
+
+    common::create_pod(&client, &server, dns_pod(echo_port)).await;
+    let spec = json!({
+        "type": "NodePort",
+        "selector": { "app": "dns" },
+        "ports": [{ "port": 53, "targetPort": echo_port, "nodePort": node_port, "protocol": "UDP" }]
+    });
+    common::create(&client, &server.url("/api/v1/namespaces/default/services"), service("dns", spec)).await;
+
+    assert_eq!(exchange(("127.0.0.1", node_port), b"query").await, b"echo:query");
+}
+
+// A TCP server on the node that echoes what it's sent
+async fn tcp_echo() -> u16 {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let port = listener.local_addr().unwrap().port();
+    tokio::spawn(async move {
+        while let Ok((mut conn, _)) = listener.accept().await {
+            tokio::spawn(async move {
+                let (mut read, mut write) = conn.split();
+                let _ = tokio::io::copy(&mut read, &mut write).await;
+            });
+        }
+    });
+    port
+}
+
+// Connects to `addr` once the proxy is listening on it
+async fn connect(addr: (&str, u16)) -> TcpStream {
+    for _ in 0..50 {
+        if let Ok(conn) = TcpStream::connect(addr).await {
+            return conn;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("nothing listening on {:?}", addr);
+}
+
+// A TCP port free on `ip`
+fn free_port(ip: &str) -> u16 {
+    std::net::TcpListener::bind((ip, 0)).unwrap().local_addr().unwrap().port()
+}
+
+#[tokio::test]
+async fn test_proxy_tracks_connections() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let echo_port = tcp_echo().await;
+    let service_port = free_port("127.0.0.4");
+
+    let mut pod = dns_pod(echo_port);
+    pod["metadata"] = json!({ "name": "echo", "labels": { "app": "echo" } });
+    common::create_pod(&client, &server, pod).await;
+    let spec = json!({
+        "selector": { "app": "echo" },
+        "externalIPs": ["127.0.0.4"],
+        "ports": [{ "name": "dns-tcp", "port": service_port, "targetPort": echo_port }]
+    });
+    common::create(&client, &server.url("/api/v1/namespaces/default/services"), service("tcp-echo", spec)).await;
+
+    // The echo answers once the endpoints are in
+    let mut conn = loop {
+        let mut conn = connect(("127.0.0.4", service_port)).await;
+        conn.write_all(b"hello").await.unwrap();
+        let mut buf = [0u8; 5];
+        if let Ok(Ok(_)) = tokio::time::timeout(Duration::from_secs(1), conn.read_exact(&mut buf)).await {
+            assert_eq!(&buf, b"hello");
+            break conn;
+        }
+    };
+
+    let table: Value = client.get(server.url("/krust/conntrack?service=tcp-echo")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(table["kind"], "ConnectionTrackingTable");
+    let connections = table["connections"].as_array().unwrap();
+    assert_eq!(connections.len(), 1);
+    let entry = &connections[0];
+    assert_eq!(entry["protocol"], "TCP");
+    assert_eq!(entry["namespace"], "default");
+    assert_eq!(entry["portName"], "dns-tcp");
+    assert_eq!(entry["frontend"], format!("127.0.0.4:{}", service_port));
+    assert_eq!(entry["backend"], format!("127.0.0.1:{}", echo_port));
+    assert_eq!(entry["bytesSent"], 5);
+    assert_eq!(entry["bytesReceived"], 5);
+
+    // Once closed it's gone from the table and counted for its backend
+    conn.shutdown().await.unwrap();
+    let mut rest = Vec::new();
+    conn.read_to_end(&mut rest).await.unwrap();
+    drop(conn);
+    let mut metrics = Value::Null;
+    for _ in 0..50 {
+        metrics = client.get(server.url("/krust/proxy?service=tcp-echo")).send().await.unwrap().json().await.unwrap();
+        if metrics["services"][0]["backends"][0]["activeConnections"] == 0 {
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    assert_eq!(metrics["kind"], "ServiceProxyMetrics");
+    let backend = &metrics["services"][0]["backends"][0];
+    assert_eq!(backend["address"], format!("127.0.0.1:{}", echo_port));
+    assert_eq!(backend["activeConnections"], 0);
+    assert!(backend["totalConnections"].as_u64().unwrap() >= 1);
+    assert_eq!(backend["errors"], 0);
+    let table: Value = client.get(server.url("/krust/conntrack?service=tcp-echo")).send().await.unwrap().json().await.unwrap();
+    assert_eq!(table["connections"], json!([]));
+}
+
+#[tokio::test]
+async fn test_proxy_counts_connections_without_endpoints() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let service_port = free_port("127.0.0.5");
+
+    let spec = json!({ "selector": { "app": "nowhere" }, "externalIPs": ["127.0.0.5"], "ports": [{ "port": service_port }] });
+    common::create(&client, &server.url("/api/v1/namespaces/default/services"), service("nowhere", spec)).await;
+
+    let mut conn = connect(("127.0.0.5", service_port)).await;
+    let mut rest = Vec::new();
+    let _ = conn.read_to_end(&mut rest).await;
+
+    let metrics: Value = client.get(server.url("/krust/proxy?service=nowhere")).send().await.unwrap().json().await.unwrap();
+    let services = metrics["services"].as_array().unwrap();
+    assert_eq!(services.len(), 1);
+    assert_eq!(services[0]["protocol"], "TCP");
+    assert_eq!(services[0]["droppedNoEndpoints"], 1);
+    assert_eq!(services[0]["backends"], json!([]));
+}
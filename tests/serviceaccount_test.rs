@@ -1,16 +1,15 @@
 use reqwest;
-use serde_json::json;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Value};
+
+mod common;
 
 #[tokio::test]
 async fn test_serviceaccount_crud() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Create namespace first
     let namespace = json!({
@@ -132,14 +131,9 @@ async fn test_serviceaccount_crud() {
 
 #[tokio::test]
 async fn test_default_serviceaccount() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/api/v1");
     
     // Create namespace
     let namespace = json!({
@@ -172,4 +166,231 @@ async fn test_default_serviceaccount() {
     // The default ServiceAccount might or might not exist depending on controller implementation
     assert!(response.status() == reqwest::StatusCode::OK || 
             response.status() == reqwest::StatusCode::NOT_FOUND);
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_legacy_token_secret_is_populated() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let base_url = server.url("/api/v1/namespaces/default");
+
+    // The Secret may come before its ServiceAccount, as charts often order them
+    let secret = json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": {
+            "name": "builder-token",
+            "annotations": { "kubernetes.io/service-account.name": "builder" }
+        },
+        "type": "kubernetes.io/service-account-token"
+    });
+    let response = client.post(format!("{}/secrets", base_url)).json(&secret).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+
+    tokio::time::sleep(tokio::time::Duration::from_millis(1500)).await;
+    let pending: Value = client.get(format!("{}/secrets/builder-token", base_url)).send().await.unwrap().json().await.unwrap();
+    assert!(pending["data"]["token"].is_null());
+
+    let account = json!({ "apiVersion": "v1", "kind": "ServiceAccount", "metadata": { "name": "builder" } });
+    let response = client.post(format!("{}/serviceaccounts", base_url)).json(&account).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let account: Value = response.json().await.unwrap();
+
+    let mut populated = Value::Null;
+    for _ in 0..20 {
+        tokio::time::sleep(tokio::time::Duration::from_millis(250)).await;
+        populated = client.get(format!("{}/secrets/builder-token", base_url)).send().await.unwrap().json().await.unwrap();
+        if !populated["data"]["token"].is_null() {
+            break;
+        }
+    }
+    let decode = |key: &str| String::from_utf8(STANDARD.decode(populated["data"][key].as_str().unwrap()).unwrap()).unwrap();
+    assert!(!decode("token").is_empty());
+    assert_eq!(decode("namespace"), "default");
+    assert!(decode("ca.crt").starts_with("-----BEGIN CERTIFICATE-----"));
+    assert_eq!(
+        populated["metadata"]["annotations"]["kubernetes.io/service-account.uid"],
+        account["metadata"]["uid"]
+    );
+
+    // A token already in place is left alone
+    let token = populated["data"]["token"].clone();
+    tokio::time::sleep(tokio::time::Duration::from_millis(1500)).await;
+    let later: Value = client.get(format!("{}/secrets/builder-token", base_url)).send().await.unwrap().json().await.unwrap();
+    assert_eq!(later["data"]["token"], token);
+}
+
+async fn review(client: &reqwest::Client, server: &common::TestServer, token: &str, audiences: &[&str]) -> Value {
+    let review = json!({
+        "apiVersion": "authentication.k8s.io/v1",
+        "kind": "TokenReview",
+        "spec": { "token": token, "audiences": audiences }
+    });
+    let response = client
+        .post(server.url("/apis/authentication.k8s.io/v1/tokenreviews"))
+        .json(&review)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    response.json::<Value>().await.unwrap()["status"].clone()
+}
+
+#[tokio::test]
+async fn test_token_request_and_review() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let base_url = server.url("/api/v1/namespaces/default");
+
+    let account = json!({ "apiVersion": "v1", "kind": "ServiceAccount", "metadata": { "name": "robot" } });
+    let response = client.post(format!("{}/serviceaccounts", base_url)).json(&account).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let account: Value = response.json().await.unwrap();
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "web" },
+        "spec": { "serviceAccountName": "robot", "containers": [{ "name": "web", "image": "nginx" }] }
+    });
+    let response = client.post(format!("{}/pods", base_url)).json(&pod).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let pod: Value = response.json().await.unwrap();
+
+    let request = json!({
+        "apiVersion": "authentication.k8s.io/v1",
+        "kind": "TokenRequest",
+        "spec": {
+            "audiences": ["vault"],
+            "expirationSeconds": 600,
+            "boundObjectRef": { "apiVersion": "v1", "kind": "Pod", "name": "web" }
+        }
+    });
+    let response = client.post(format!("{}/serviceaccounts/robot/token", base_url)).json(&request).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let issued: Value = response.json().await.unwrap();
+    let token = issued["status"]["token"].as_str().unwrap().to_string();
+    assert_eq!(issued["spec"]["boundObjectRef"]["uid"], pod["metadata"]["uid"]);
+
+    // A JWT whose claims say what it's for
+    let payload = token.split('.').nth(1).unwrap();
+    let claims: Value = serde_json::from_slice(&base64::engine::general_purpose::URL_SAFE_NO_PAD.decode(payload).unwrap()).unwrap();
+    assert_eq!(claims["sub"], "system:serviceaccount:default:robot");
+    assert_eq!(claims["aud"], json!(["vault"]));
+    assert_eq!(claims["exp"].as_i64().unwrap() - claims["iat"].as_i64().unwrap(), 600);
+    assert_eq!(claims["kubernetes.io"]["pod"]["name"], "web");
+
+    let status = review(&client, &server, &token, &["vault"]).await;
+    assert_eq!(status["authenticated"], true);
+    assert_eq!(status["audiences"], json!(["vault"]));
+    assert_eq!(status["user"]["username"], "system:serviceaccount:default:robot");
+    assert_eq!(status["user"]["uid"], account["metadata"]["uid"]);
+    assert_eq!(status["user"]["extra"]["authentication.kubernetes.io/pod-name"], json!(["web"]));
+
+    // Not meant for the API server
+    let status = review(&client, &server, &token, &[]).await;
+    assert_eq!(status["authenticated"], false);
+    assert!(status["error"].as_str().unwrap().contains("audiences"));
+
+    let forged = format!("{}x", token);
+    assert_eq!(review(&client, &server, &forged, &["vault"]).await["authenticated"], false);
+
+    // Too short a lifetime, or a bound object that doesn't exist
+    let short = json!({ "spec": { "expirationSeconds": 60 } });
+    let response = client.post(format!("{}/serviceaccounts/robot/token", base_url)).json(&short).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNPROCESSABLE_ENTITY);
+    let unbound = json!({ "spec": { "boundObjectRef": { "kind": "Pod", "name": "missing" } } });
+    let response = client.post(format!("{}/serviceaccounts/robot/token", base_url)).json(&unbound).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+
+    // The token goes with the pod it's bound to
+    let response = client.delete(format!("{}/pods/web", base_url)).send().await.unwrap();
+    assert!(response.status().is_success());
+    let status = review(&client, &server, &token, &["vault"]).await;
+    assert_eq!(status["authenticated"], false);
+
+    // A token for the API server works as a bearer token until the account is deleted
+    let response = client.post(format!("{}/serviceaccounts/robot/token", base_url)).json(&json!({})).send().await.unwrap();
+    let issued: Value = response.json().await.unwrap();
+    let token = issued["status"]["token"].as_str().unwrap().to_string();
+    assert_eq!(issued["spec"]["audiences"], json!(["https://kubernetes.default.svc.cluster.local"]));
+    assert_eq!(review(&client, &server, &token, &[]).await["authenticated"], true);
+    client.delete(format!("{}/serviceaccounts/robot", base_url)).send().await.unwrap();
+    assert_eq!(review(&client, &server, &token, &[]).await["authenticated"], false);
+}
+
+#[tokio::test]
+async fn test_projected_token_volume() {
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use krust::runtime::projected_volume;
+
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let base_url = server.url("/api/v1/namespaces/default");
+
+    let account = json!({ "apiVersion": "v1", "kind": "ServiceAccount", "metadata": { "name": "reader" } });
+    let response = client.post(format!("{}/serviceaccounts", base_url)).json(&account).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let volume = json!({
+        "name": "vault-token",
+        "projected": {
+            "sources": [
+                { "serviceAccountToken": { "audience": "vault", "expirationSeconds": 600, "path": "token" } },
+                { "configMap": { "name": "kube-root-ca.crt", "items": [{ "key": "ca.crt", "path": "ca.crt" }] } },
+                { "downwardAPI": { "items": [{ "path": "namespace", "fieldRef": { "fieldPath": "metadata.namespace" } }] } }
+            ]
+        }
+    });
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "vault-agent" },
+        "spec": {
+            "serviceAccountName": "reader",
+            "containers": [{ "name": "agent", "image": "vault", "volumeMounts": [{ "name": "vault-token", "mountPath": "/var/run/secrets/vault" }] }],
+            "volumes": [volume.clone()]
+        }
+    });
+    let response = client.post(format!("{}/pods", base_url)).json(&pod).send().await.unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CREATED);
+    let pod: Value = response.json().await.unwrap();
+    for _ in 0..50 {
+        if client.get(format!("{}/configmaps/kube-root-ca.crt", base_url)).send().await.unwrap().status().is_success() {
+            break;
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+
+    // The token is for the audience and lifetime asked for, and bound to the pod
+    let dir = tempfile::tempdir().unwrap();
+    projected_volume::project(&server.storage, &pod, &volume, dir.path()).await.unwrap();
+    let token = std::fs::read_to_string(dir.path().join("token")).unwrap();
+    let claims: Value = serde_json::from_slice(&URL_SAFE_NO_PAD.decode(token.split('.').nth(1).unwrap()).unwrap()).unwrap();
+    assert_eq!(claims["aud"], json!(["vault"]));
+    assert_eq!(claims["exp"].as_i64().unwrap() - claims["iat"].as_i64().unwrap(), 600);
+    assert_eq!(claims["kubernetes.io"]["pod"]["name"], "vault-agent");
+    assert!(std::fs::read_to_string(dir.path().join("ca.crt")).unwrap().contains("BEGIN CERTIFICATE"));
+    assert_eq!(std::fs::read_to_string(dir.path().join("namespace")).unwrap(), "default");
+
+    let status = review(&client, &server, &token, &["vault"]).await;
+    assert_eq!(status["authenticated"], true);
+    assert_eq!(status["user"]["username"], "system:serviceaccount:default:reader");
+    assert_eq!(review(&client, &server, &token, &["other"]).await["authenticated"], false);
+
+    // It's reissued once 80% of its lifetime has passed, not before
+    let refresh_at = projected_volume::refresh_at(&token).unwrap();
+    assert_eq!(refresh_at.timestamp(), claims["iat"].as_i64().unwrap() + 480);
+    projected_volume::project(&server.storage, &pod, &volume, dir.path()).await.unwrap();
+    assert_eq!(std::fs::read_to_string(dir.path().join("token")).unwrap(), token);
+
+    // One that can't be read is replaced whole
+    std::fs::write(dir.path().join("token"), "stale").unwrap();
+    projected_volume::project(&server.storage, &pod, &volume, dir.path()).await.unwrap();
+    let replaced = std::fs::read_to_string(dir.path().join("token")).unwrap();
+    assert_ne!(replaced, "stale");
+    assert_eq!(review(&client, &server, &replaced, &["vault"]).await["authenticated"], true);
+    let mut files: Vec<String> = std::fs::read_dir(dir.path())
+        .unwrap()
+        .map(|entry| entry.unwrap().file_name().to_string_lossy().to_string())
+        .collect();
+    files.sort();
+    assert_eq!(files, vec!["ca.crt", "namespace", "token"]);
+}
@@ -0,0 +1,63 @@
+use krust::smoke::{self, SmokeOptions};
+use serde_json::Value;
+use std::time::Duration;
+
+mod common;
+
+#[tokio::test]
+async fn test_smoke_against_a_server() {
+    let server = common::TestServer::start().await;
+    assert!(smoke::is_running(&server.base_url()).await);
+    let options = SmokeOptions {
+        server: server.base_url(),
+        timeout: Duration::from_secs(20),
+        ..SmokeOptions::default()
+    };
+
+    let report = smoke::run(&options).await.unwrap();
+    let names: Vec<&str> = report.checks.iter().map(|check| check.name).collect();
+    assert_eq!(&names[..4], ["api server", "apply workload", "deployment ready", "service endpoints"]);
+    for check in &report.checks[..4] {
+        assert!(check.result.is_ok(), "{}", report);
+    }
+    assert!(report.checks[1].result.as_ref().unwrap().contains("deployment/nginx, service/nginx, ingress/nginx"));
+    assert!(report.to_string().contains("PASS  deployment ready"));
+    assert!(report.cleanup_error.is_none(), "{}", report);
+
+    // The namespace is on its way out afterwards
+    let namespace: Value = reqwest::get(server.url(&format!("/api/v1/namespaces/{}", report.namespace)))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(namespace["metadata"]["deletionTimestamp"].is_string() || namespace["code"] == 404);
+}
+
+#[tokio::test]
+async fn test_smoke_reports_an_unreachable_server() {
+    let options = SmokeOptions {
+        server: "http://127.0.0.1:9".to_string(),
+        ..SmokeOptions::default()
+    };
+    assert!(!smoke::is_running(&options.server).await);
+    let report = smoke::run(&options).await.unwrap();
+    assert_eq!(report.checks.len(), 1);
+    assert!(!report.passed());
+    assert!(report.to_string().starts_with("FAIL  api server"));
+    assert!(report.to_string().ends_with("smoke test failed"));
+}
+
+#[test]
+fn test_smoke_options() {
+    let args = ["--server", "http://krust:6443/", "--timeout=30", "--keep", "--data-dir", "/tmp/krust", "--profiling"];
+    let options = SmokeOptions::parse(args.map(String::from)).unwrap();
+    assert_eq!(options.server, "http://krust:6443");
+    assert_eq!(options.timeout, Duration::from_secs(30));
+    assert!(options.keep);
+    assert_eq!(options.server_args, ["--data-dir", "/tmp/krust", "--profiling"]);
+
+    assert!(SmokeOptions::parse(["--timeout".to_string()]).is_err());
+    assert!(SmokeOptions::parse(["--timeout=soon".to_string()]).is_err());
+    assert!(SmokeOptions::parse(["--replicas=3".to_string()]).is_err());
+}
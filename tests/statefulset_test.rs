@@ -1,16 +1,13 @@
 use reqwest;
 use serde_json::json;
 
+mod common;
+
 #[tokio::test]
 async fn test_statefulset_crud_operations() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/apps/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/apps/v1");
     
     // Test 1: Create a StatefulSet
     let statefulset = json!({
@@ -222,14 +219,9 @@ async fn test_statefulset_crud_operations() {
 
 #[tokio::test]
 async fn test_statefulset_pod_management() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/apps/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/apps/v1");
     
     // Create a StatefulSet
     let statefulset = json!({
@@ -292,7 +284,7 @@ async fn test_statefulset_pod_management() {
     
     // Check that pods are created with predictable names
     let pod_response = client
-        .get("http://localhost:6443/api/v1/namespaces/default/pods")
+        .get(server.url("/api/v1/namespaces/default/pods"))
         .send()
         .await
         .unwrap();
@@ -312,14 +304,9 @@ async fn test_statefulset_pod_management() {
 
 #[tokio::test]
 async fn test_statefulset_parallel_pod_management() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/apis/apps/v1";
-    
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
+    let base_url = server.url("/apis/apps/v1");
     
     // Create a StatefulSet with Parallel pod management
     let statefulset = json!({
@@ -0,0 +1,177 @@
+use reqwest;
+use serde_json::{json, Value};
+
+mod common;
+
+async fn send(request: reqwest::RequestBuilder) -> Value {
+    let resp = request.send().await.expect("Request failed");
+    assert!(resp.status().is_success(), "unexpected status {}", resp.status());
+    resp.json().await.unwrap()
+}
+
+#[tokio::test]
+async fn test_pod_writes_are_isolated_from_status() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pod_url = server.url("/api/v1/namespaces/default/pods/isolated");
+
+    send(client.post(server.url("/api/v1/namespaces/default/pods")).json(&json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "isolated" },
+        "spec": { "containers": [{ "name": "app", "image": "nginx:latest" }] }
+    })))
+    .await;
+    server.wait_for_pod_running("default", "isolated").await;
+
+    // A PUT of the pod carrying a different status changes only the rest
+    let mut pod = send(client.get(&pod_url)).await;
+    pod["metadata"]["labels"] = json!({ "tier": "web" });
+    pod["status"]["phase"] = json!("Failed");
+    let updated = send(client.put(&pod_url).json(&pod)).await;
+    assert_eq!(updated["metadata"]["labels"]["tier"], "web");
+    assert_eq!(updated["status"]["phase"], "Running");
+
+    // Same for a merge patch
+    let patched = send(client.patch(&pod_url).json(&json!({
+        "metadata": { "labels": { "patched": "true" } },
+        "status": { "phase": "Failed" }
+    })))
+    .await;
+    assert_eq!(patched["metadata"]["labels"]["patched"], "true");
+    assert_eq!(patched["status"]["phase"], "Running");
+
+    // And /status only ever takes the status
+    let status_url = format!("{}/status", pod_url);
+    let mut pod = send(client.get(&pod_url)).await;
+    pod["spec"]["activeDeadlineSeconds"] = json!(30);
+    pod["status"]["message"] = json!("checked");
+    send(client.put(&status_url).json(&pod)).await;
+    send(client.patch(&status_url).json(&json!({
+        "spec": { "activeDeadlineSeconds": 60 },
+        "status": { "reason": "Checked" }
+    })))
+    .await;
+
+    let pod = send(client.get(&pod_url)).await;
+    assert!(pod["spec"]["activeDeadlineSeconds"].is_null());
+    assert_eq!(pod["status"]["message"], "checked");
+    assert_eq!(pod["status"]["reason"], "Checked");
+    assert_eq!(pod["status"]["phase"], "Running");
+}
+
+#[tokio::test]
+async fn test_deployment_spec_and_status_are_written_separately() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/apis/apps/v1/namespaces/default/deployments/split");
+
+    send(client.post(server.url("/apis/apps/v1/namespaces/default/deployments")).json(&json!({
+        "apiVersion": "apps/v1",
+        "kind": "Deployment",
+        "metadata": { "name": "split" },
+        "spec": {
+            "replicas": 1,
+            "selector": { "matchLabels": { "app": "split" } },
+            "template": {
+                "metadata": { "labels": { "app": "split" } },
+                "spec": { "containers": [{ "name": "app", "image": "nginx:latest" }] }
+            }
+        }
+    })))
+    .await;
+
+    let mut deployment = send(client.get(&url)).await;
+    deployment["spec"]["replicas"] = json!(2);
+    deployment["status"] = json!({ "replicas": 99 });
+    let updated = send(client.put(&url).json(&deployment)).await;
+    assert_eq!(updated["spec"]["replicas"], 2);
+    assert_ne!(updated["status"]["replicas"], 99);
+
+    // The next write builds on this one, so it carries the new version
+    deployment["metadata"]["resourceVersion"] = updated["metadata"]["resourceVersion"].clone();
+    deployment["spec"]["replicas"] = json!(5);
+    deployment["status"] = json!({ "observedGeneration": 42 });
+    let updated = send(client.put(format!("{}/status", url)).json(&deployment)).await;
+    assert_eq!(updated["spec"]["replicas"], 2);
+    assert_eq!(updated["status"]["observedGeneration"], 42);
+}
+
+#[tokio::test]
+async fn test_hpa_spec_and_status_are_written_separately() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/apis/autoscaling/v2/namespaces/default/horizontalpodautoscalers/split");
+
+    send(client.post(server.url("/apis/autoscaling/v2/namespaces/default/horizontalpodautoscalers")).json(&json!({
+        "apiVersion": "autoscaling/v2",
+        "kind": "HorizontalPodAutoscaler",
+        "metadata": { "name": "split" },
+        "spec": {
+            "scaleTargetRef": { "apiVersion": "apps/v1", "kind": "Deployment", "name": "web" },
+            "minReplicas": 1,
+            "maxReplicas": 3
+        }
+    })))
+    .await;
+
+    let mut hpa = send(client.get(&url)).await;
+    hpa["spec"]["maxReplicas"] = json!(5);
+    hpa["status"] = json!({ "currentReplicas": 4, "desiredReplicas": 4 });
+    let updated = send(client.put(&url).json(&hpa)).await;
+    assert_eq!(updated["spec"]["maxReplicas"], 5);
+    assert_ne!(updated["status"]["currentReplicas"], 4);
+
+    hpa["metadata"]["resourceVersion"] = updated["metadata"]["resourceVersion"].clone();
+    hpa["spec"]["maxReplicas"] = json!(10);
+    let updated = send(client.put(format!("{}/status", url)).json(&hpa)).await;
+    assert_eq!(updated["spec"]["maxReplicas"], 5);
+    assert_eq!(updated["status"]["currentReplicas"], 4);
+
+    let stored = send(client.get(&url)).await;
+    assert_eq!(stored["spec"]["maxReplicas"], 5);
+    assert_eq!(stored["status"]["desiredReplicas"], 4);
+}
+
+#[tokio::test]
+async fn test_ingress_status_is_only_written_through_status() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let url = server.url("/apis/networking.k8s.io/v1/namespaces/default/ingresses/split");
+
+    send(client.post(server.url("/apis/networking.k8s.io/v1/namespaces/default/ingresses")).json(&json!({
+        "apiVersion": "networking.k8s.io/v1",
+        "kind": "Ingress",
+        "metadata": { "name": "split" },
+        "spec": {
+            "rules": [{
+                "host": "example.com",
+                "http": {
+                    "paths": [{
+                        "path": "/",
+                        "pathType": "Prefix",
+                        "backend": { "service": { "name": "web", "port": { "number": 80 } } }
+                    }]
+                }
+            }]
+        }
+    })))
+    .await;
+
+    let load_balancer = json!({ "ingress": [{ "ip": "192.0.2.10" }] });
+    let mut ingress = send(client.get(&url)).await;
+    ingress["spec"]["rules"][0]["host"] = json!("status.example.com");
+    ingress["status"] = json!({ "loadBalancer": load_balancer });
+    let updated = send(client.put(format!("{}/status", url)).json(&ingress)).await;
+    assert_eq!(updated["status"]["loadBalancer"], load_balancer);
+    assert_eq!(updated["spec"]["rules"][0]["host"], "example.com");
+
+    ingress["metadata"]["resourceVersion"] = updated["metadata"]["resourceVersion"].clone();
+    ingress["spec"]["rules"][0]["host"] = json!("www.example.com");
+    ingress["status"] = json!({ "loadBalancer": { "ingress": [] } });
+    send(client.put(&url).json(&ingress)).await;
+
+    let stored = send(client.get(&url)).await;
+    assert_eq!(stored["spec"]["rules"][0]["host"], "www.example.com");
+    assert_eq!(stored["status"]["loadBalancer"], load_balancer);
+}
@@ -0,0 +1,102 @@
+use futures::StreamExt;
+use krust::Storage;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+async fn storage() -> Storage {
+    let storage = Storage::in_memory().await.expect("failed to open in-memory database");
+    storage.migrate().await.expect("failed to run migrations");
+    storage
+}
+
+fn configmap(name: &str) -> Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": { "name": name },
+        "data": { "key": "value" }
+    })
+}
+
+fn replicaset(name: &str) -> Value {
+    json!({
+        "metadata": { "name": name },
+        "spec": {
+            "replicas": 1,
+            "selector": { "matchLabels": { "app": name } },
+            "template": {
+                "metadata": { "labels": { "app": name } },
+                "spec": { "containers": [{ "name": "app", "image": "nginx:1.25" }] }
+            }
+        }
+    })
+}
+
+#[tokio::test]
+async fn test_committed_writes_are_visible() {
+    let storage = storage().await;
+
+    let tx = storage.transaction().await.unwrap();
+    tx.configmaps().create("default", configmap("settings")).await.unwrap();
+    tx.replicasets().create("default", replicaset("web")).await.unwrap();
+
+    // Reads inside the transaction see its own writes
+    assert_eq!(tx.configmaps().get("default", "settings").await.unwrap()["data"]["key"], "value");
+    tx.commit().await.unwrap();
+
+    assert!(storage.configmaps().get("default", "settings").await.is_ok());
+    assert!(storage.replicasets().get("default", "web").await.is_ok());
+}
+
+#[tokio::test]
+async fn test_unfinished_transactions_leave_nothing_behind() {
+    let storage = storage().await;
+
+    let tx = storage.transaction().await.unwrap();
+    tx.configmaps().create("default", configmap("rolled-back")).await.unwrap();
+    tx.replicasets().create("default", replicaset("rolled-back")).await.unwrap();
+    tx.rollback().await.unwrap();
+
+    // Dropping a transaction, e.g. on an early return, rolls it back too
+    {
+        let tx = storage.transaction().await.unwrap();
+        tx.configmaps().create("default", configmap("dropped")).await.unwrap();
+        sqlx::query("UPDATE namespaces SET labels = '{\"touched\":\"yes\"}' WHERE name = 'default'")
+            .execute(tx.db())
+            .await
+            .unwrap();
+    }
+
+    for name in ["rolled-back", "dropped"] {
+        assert!(storage.configmaps().get("default", name).await.is_err());
+    }
+    assert!(storage.replicasets().get("default", "rolled-back").await.is_err());
+
+    let labels: Option<String> = sqlx::query_scalar("SELECT labels FROM namespaces WHERE name = 'default'")
+        .fetch_one(storage.pool())
+        .await
+        .unwrap();
+    assert!(!labels.unwrap_or_default().contains("touched"));
+}
+
+#[tokio::test]
+async fn test_watches_see_transactions_once_committed() {
+    let storage = storage().await;
+    let watch = storage.watch();
+    let from = watch.latest_version().await.unwrap();
+    let mut events = watch.watch_stream("configmaps".to_string(), None, Some(from.to_string())).await.unwrap();
+
+    let tx = storage.transaction().await.unwrap();
+    tx.configmaps().create("default", configmap("discarded")).await.unwrap();
+    tx.rollback().await.unwrap();
+
+    let tx = storage.transaction().await.unwrap();
+    tx.configmaps().create("default", configmap("kept")).await.unwrap();
+    let early = tokio::time::timeout(Duration::from_millis(200), events.next()).await;
+    assert!(early.is_err(), "event sent before the commit: {:?}", early);
+    tx.commit().await.unwrap();
+
+    let event = tokio::time::timeout(Duration::from_secs(1), events.next()).await.unwrap().unwrap().unwrap();
+    assert_eq!(event["type"], "ADDED");
+    assert_eq!(event["object"]["metadata"]["name"], "kept");
+}
@@ -0,0 +1,150 @@
+use futures::StreamExt;
+use reqwest;
+use serde_json::{json, Value};
+use tokio_tungstenite::tungstenite::{client::IntoClientRequest, Message};
+
+mod common;
+
+const STREAMING: &str = "streaming:\n  enabled: true\n  port: 0\n";
+
+async fn start_with_pod(config: &str) -> common::TestServer {
+    let config = krust::Config::parse(config).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let resp = reqwest::Client::new()
+        .post(server.url("/api/v1/namespaces/default/pods"))
+        .json(&json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": { "name": "shell" },
+            "spec": { "containers": [{ "name": "app", "image": "busybox:1.35" }] }
+        }))
+        .send()
+        .await
+        .expect("Failed to create pod");
+    assert_eq!(resp.status(), 201);
+    server.wait_for_pod_running("default", "shell").await;
+    server
+}
+
+fn no_redirects() -> reqwest::Client {
+    reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .unwrap()
+}
+
+/// Location of the streaming URL the API redirects `path` to.
+async fn stream_url(server: &common::TestServer, path: &str) -> String {
+    let resp = no_redirects().get(server.url(path)).send().await.unwrap();
+    assert_eq!(resp.status(), 302);
+    resp.headers()["location"].to_str().unwrap().to_string()
+}
+
+/// Opens a channel stream at `url` and returns the Status it ends with.
+async fn error_channel_status(url: &str) -> Value {
+    let mut request = url.replace("http://", "ws://").into_client_request().unwrap();
+    request
+        .headers_mut()
+        .insert("Sec-WebSocket-Protocol", "v4.channel.k8s.io".parse().unwrap());
+    let (mut socket, response) = tokio_tungstenite::connect_async(request).await.expect("upgrade failed");
+    assert_eq!(response.headers()["sec-websocket-protocol"], "v4.channel.k8s.io");
+
+    let mut status = None;
+    while let Some(Ok(message)) = socket.next().await {
+        if let Message::Binary(data) = message {
+            if data.first() == Some(&3) {
+                status = Some(serde_json::from_slice::<Value>(&data[1..]).unwrap());
+            }
+        }
+    }
+    status.expect("no status on the error channel")
+}
+
+#[tokio::test]
+async fn test_exec_redirects_to_single_use_streaming_url() {
+    let server = start_with_pod(STREAMING).await;
+
+    let url = stream_url(&server, "/api/v1/namespaces/default/pods/shell/exec?command=ls&command=-l&stdout=true").await;
+    assert!(!url.starts_with(&server.base_url()), "streams are served from their own port: {}", url);
+    assert!(url.contains("/exec/"));
+
+    // Using the URL spends it, whether or not the connection is upgraded
+    let resp = reqwest::get(&url).await.unwrap();
+    assert_eq!(resp.status(), 400);
+    let resp = reqwest::get(&url).await.unwrap();
+    assert_eq!(resp.status(), 404);
+
+    // A token for one kind of stream doesn't open another
+    let url = stream_url(&server, "/api/v1/namespaces/default/pods/shell/attach?stdout=true").await;
+    let resp = reqwest::get(url.replace("/attach/", "/exec/")).await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_exec_stream_ends_with_status_on_error_channel() {
+    let server = start_with_pod(STREAMING).await;
+
+    let url = stream_url(&server, "/api/v1/namespaces/default/pods/shell/exec?command=true&stdout=true").await;
+
+    // Pods of the fake kubelet have no container to run in, so the exec fails,
+    // and says so the way a kubelet would
+    let status = error_channel_status(&url).await;
+    assert_eq!(status["status"], "Failure");
+}
+
+#[tokio::test]
+async fn test_stream_requests_are_validated_before_redirecting() {
+    let server = start_with_pod(STREAMING).await;
+    let client = no_redirects();
+
+    let no_command = client
+        .get(server.url("/api/v1/namespaces/default/pods/shell/exec?stdout=true"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(no_command.status(), 400);
+
+    let unknown_container = client
+        .get(server.url("/api/v1/namespaces/default/pods/shell/exec?command=ls&container=sidecar"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(unknown_container.status(), 400);
+
+    let missing_pod = client
+        .get(server.url("/api/v1/namespaces/default/pods/missing/attach"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(missing_pod.status(), 404);
+}
+
+#[tokio::test]
+async fn test_streaming_urls_expire() {
+    let server = start_with_pod("streaming:\n  enabled: true\n  port: 0\n  tokenTtlSeconds: 1\n").await;
+
+    let url = stream_url(&server, "/api/v1/namespaces/default/pods/shell/exec?command=ls").await;
+    tokio::time::sleep(std::time::Duration::from_millis(1500)).await;
+    let resp = reqwest::get(&url).await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[tokio::test]
+async fn test_streams_are_served_by_the_api_without_streaming() {
+    let server = start_with_pod("").await;
+
+    // There's nowhere to redirect to, so the request itself has to be upgraded
+    for path in ["exec?command=ls", "attach?stdout=true"] {
+        let resp = no_redirects()
+            .get(server.url(&format!("/api/v1/namespaces/default/pods/shell/{}", path)))
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(resp.status(), 400, "{}", path);
+    }
+
+    for path in ["exec?command=true&stdout=true", "attach?stdin=true&stdout=true&stderr=true&tty=true"] {
+        let status = error_channel_status(&server.url(&format!("/api/v1/namespaces/default/pods/shell/{}", path))).await;
+        assert_eq!(status["status"], "Failure", "{}", path);
+    }
+}
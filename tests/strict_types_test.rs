@@ -0,0 +1,81 @@
+use serde_json::{json, Value};
+
+mod common;
+
+#[cfg(feature = "strict-types")]
+#[tokio::test]
+async fn test_fields_of_the_wrong_type_are_refused() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let container = json!({ "name": "web", "image": "nginx", "ports": [{ "containerPort": "80" }] });
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/pods"))
+        .json(&common::pod("typo", json!({ "containers": [container] })))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "BadRequest");
+    assert_eq!(
+        status["message"],
+        "Pod in version \"v1\" cannot be handled as a Pod: spec.containers[0].ports[0].containerPort: invalid type: string \"80\", expected i32"
+    );
+    let resp = client.get(server.url("/api/v1/namespaces/default/pods/typo")).send().await.unwrap();
+    assert_eq!(resp.status(), 404);
+}
+
+#[cfg(feature = "strict-types")]
+#[tokio::test]
+async fn test_unknown_fields_follow_field_validation() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pods = server.url("/api/v1/namespaces/default/pods");
+    let container = json!({ "name": "web", "image": "nginx", "imagePullPolcy": "Always" });
+
+    let resp = client
+        .post(format!("{}?fieldValidation=Strict", pods))
+        .json(&common::pod("strict", json!({ "containers": [container.clone()] })))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 400);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(
+        status["message"],
+        "Pod in version \"v1\" cannot be handled as a Pod: strict decoding error: unknown field \"spec.containers[0].imagePullPolcy\""
+    );
+
+    // Warn is the default
+    let resp = client.post(&pods).json(&common::pod("warned", json!({ "containers": [container.clone()] }))).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let warning = resp.headers().get("warning").unwrap().to_str().unwrap().to_string();
+    assert_eq!(warning, "299 - \"unknown field \\\"spec.containers[0].imagePullPolcy\\\"\"");
+
+    let resp = client
+        .post(format!("{}?fieldValidation=Ignore", pods))
+        .json(&common::pod("ignored", json!({ "containers": [container] })))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    assert!(resp.headers().get("warning").is_none());
+}
+
+#[cfg(not(feature = "strict-types"))]
+#[tokio::test]
+async fn test_lenient_mode_takes_unknown_fields() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let container = json!({ "name": "web", "image": "nginx", "imagePullPolcy": "Always" });
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/pods?fieldValidation=Strict"))
+        .json(&common::pod("lenient", json!({ "containers": [container] })))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 201);
+    assert!(resp.headers().get("warning").is_none());
+}
@@ -0,0 +1,244 @@
+use reqwest;
+use serde_json::{json, Value};
+
+mod common;
+
+const AS_TABLE: &str = "application/json;as=Table;g=meta.k8s.io;v=v1,application/json";
+
+fn column_names(table: &Value) -> Vec<&str> {
+    table["columnDefinitions"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|column| column["name"].as_str().unwrap())
+        .collect()
+}
+
+// The cells of the row named `name`, by column name
+fn row<'a>(table: &'a Value, name: &str) -> Vec<(&'a str, &'a Value)> {
+    let row = table["rows"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .find(|row| row["cells"][0] == name)
+        .unwrap_or_else(|| panic!("no row for {}: {}", name, table));
+    column_names(table).into_iter().zip(row["cells"].as_array().unwrap()).collect()
+}
+
+fn cell<'a>(table: &'a Value, name: &str, column: &str) -> &'a Value {
+    row(table, name).into_iter().find(|(c, _)| *c == column).unwrap().1
+}
+
+#[tokio::test]
+async fn test_pods_as_table() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/pods"))
+        .json(&json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": { "name": "web" },
+            "spec": { "containers": [{ "name": "nginx", "image": "nginx" }] }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    server.wait_for_pod_running("default", "web").await;
+
+    let resp = client
+        .get(server.url("/api/v1/namespaces/default/pods"))
+        .header("Accept", AS_TABLE)
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    assert!(resp.headers()["content-type"].to_str().unwrap().contains("as=Table"));
+    let table: Value = resp.json().await.unwrap();
+    assert_eq!(table["kind"], "Table");
+    assert_eq!(table["apiVersion"], "meta.k8s.io/v1");
+    assert!(table["metadata"]["resourceVersion"].is_string());
+    assert_eq!(
+        column_names(&table),
+        ["Name", "Ready", "Status", "Restarts", "Age", "IP", "Node", "Nominated Node", "Readiness Gates"]
+    );
+    let wide: Vec<i64> = table["columnDefinitions"].as_array().unwrap().iter().map(|c| c["priority"].as_i64().unwrap()).collect();
+    assert_eq!(wide, [0, 0, 0, 0, 0, 1, 1, 1, 1]);
+
+    assert_eq!(cell(&table, "web", "Ready"), "1/1");
+    assert_eq!(cell(&table, "web", "Status"), "Running");
+    assert_eq!(cell(&table, "web", "Restarts"), "0");
+    assert!(cell(&table, "web", "Age").as_str().unwrap().ends_with('s'));
+    assert_ne!(cell(&table, "web", "Node"), "<none>");
+    assert_eq!(cell(&table, "web", "Nominated Node"), "<none>");
+
+    // By default each row carries the object's metadata only
+    let object = &table["rows"][0]["object"];
+    assert_eq!(object["kind"], "PartialObjectMetadata");
+    assert_eq!(object["metadata"]["name"], "web");
+    assert!(object["spec"].is_null());
+
+    // A single object is a table of one row, and can carry all of itself
+    let table: Value = client
+        .get(server.url("/api/v1/namespaces/default/pods/web?includeObject=Object"))
+        .header("Accept", AS_TABLE)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(table["rows"].as_array().unwrap().len(), 1);
+    assert_eq!(table["rows"][0]["object"]["kind"], "Pod");
+    assert_eq!(table["rows"][0]["object"]["spec"]["containers"][0]["image"], "nginx");
+
+    // Without the Accept header nothing changes
+    let pod: Value = client
+        .get(server.url("/api/v1/namespaces/default/pods/web"))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(pod["kind"], "Pod");
+}
+
+#[tokio::test]
+async fn test_workloads_as_table() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let resp = client
+        .post(server.url("/apis/apps/v1/namespaces/default/deployments"))
+        .json(&json!({
+            "apiVersion": "apps/v1",
+            "kind": "Deployment",
+            "metadata": { "name": "api" },
+            "spec": {
+                "replicas": 2,
+                "selector": { "matchLabels": { "app": "api" } },
+                "template": {
+                    "metadata": { "labels": { "app": "api" } },
+                    "spec": { "containers": [{ "name": "server", "image": "api:1" }] }
+                }
+            }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    let table: Value = client
+        .get(server.url("/apis/apps/v1/namespaces/default/deployments"))
+        .header("Accept", AS_TABLE)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        column_names(&table),
+        ["Name", "Ready", "Up-to-date", "Available", "Age", "Containers", "Images", "Selector"]
+    );
+    assert!(cell(&table, "api", "Ready").as_str().unwrap().ends_with("/2"));
+    assert_eq!(cell(&table, "api", "Containers"), "server");
+    assert_eq!(cell(&table, "api", "Images"), "api:1");
+    assert_eq!(cell(&table, "api", "Selector"), "app=api");
+
+    let resp = client
+        .post(server.url("/api/v1/namespaces/default/services"))
+        .json(&json!({
+            "apiVersion": "v1",
+            "kind": "Service",
+            "metadata": { "name": "api" },
+            "spec": { "ports": [{ "port": 80 }, { "port": 53, "protocol": "UDP" }], "selector": { "app": "api" } }
+        }))
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+    let table: Value = client
+        .get(server.url("/api/v1/namespaces/default/services"))
+        .header("Accept", AS_TABLE)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(cell(&table, "api", "Type"), "ClusterIP");
+    assert_eq!(cell(&table, "api", "External-IP"), "<none>");
+    assert_eq!(cell(&table, "api", "Port(s)"), "80/TCP,53/UDP");
+    assert_eq!(cell(&table, "api", "Selector"), "app=api");
+
+    // Resources without a printer of their own get a name and a timestamp
+    let table: Value = client
+        .get(server.url("/apis/rbac.authorization.k8s.io/v1/clusterroles"))
+        .header("Accept", AS_TABLE)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(column_names(&table), ["Name", "Created At"]);
+}
+
+#[tokio::test]
+async fn test_watch_as_table() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let mut resp = client
+        .get(server.url("/api/v1/namespaces/default/configmaps?watch=true&labelSelector=app%3Dtable"))
+        .header("Accept", AS_TABLE)
+        .send()
+        .await
+        .unwrap();
+    assert!(resp.status().is_success());
+
+    for name in ["first", "second"] {
+        let created = client
+            .post(server.url("/api/v1/namespaces/default/configmaps"))
+            .json(&json!({
+                "apiVersion": "v1",
+                "kind": "ConfigMap",
+                "metadata": { "name": name, "labels": { "app": "table" } },
+                "data": { "a": "1", "b": "2" }
+            }))
+            .send()
+            .await
+            .unwrap();
+        assert!(created.status().is_success());
+    }
+
+    let mut buffered = String::new();
+    let mut events = Vec::new();
+    while events.len() < 2 {
+        let chunk = tokio::time::timeout(std::time::Duration::from_secs(10), resp.chunk())
+            .await
+            .expect("no watch event within 10s")
+            .unwrap()
+            .expect("watch ended");
+        buffered.push_str(std::str::from_utf8(&chunk).unwrap());
+        while let Some(end) = buffered.find('\n') {
+            let line: String = buffered.drain(..=end).collect();
+            events.push(serde_json::from_str::<Value>(&line).unwrap());
+        }
+    }
+
+    assert_eq!(events[0]["type"], "ADDED");
+    let table = &events[0]["object"];
+    assert_eq!(table["kind"], "Table");
+    assert_eq!(column_names(table), ["Name", "Data", "Age"]);
+    assert_eq!(table["rows"][0]["cells"][0], "first");
+    assert_eq!(table["rows"][0]["cells"][1], 2);
+
+    // Only the first event says what the columns are
+    assert!(events[1]["object"]["columnDefinitions"].is_null());
+    assert_eq!(events[1]["object"]["rows"][0]["cells"][0], "second");
+}
@@ -3,6 +3,8 @@ use reqwest;
 use serde_json::json;
 use uuid::Uuid;
 
+mod common;
+
 // Test helper to ensure namespace exists
 async fn ensure_namespace(client: &reqwest::Client, base_url: &str, ns_name: &str) {
     let ns = json!({
@@ -23,17 +25,12 @@ async fn ensure_namespace(client: &reqwest::Client, base_url: &str, ns_name: &st
 
 #[tokio::test]
 async fn test_pod_immutability_validation() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
+    let base_url = server.url("/api/v1");
     
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
-
     let ns_name = format!("test-pod-immut-{}", Uuid::new_v4());
-    ensure_namespace(&client, base_url, &ns_name).await;
+    ensure_namespace(&client, &base_url, &ns_name).await;
 
     // Test 1: Create a pod
     let pod = json!({
@@ -95,8 +92,16 @@ async fn test_pod_immutability_validation() {
         );
     }
     
-    // Test 3: Metadata updates should be allowed
-    let mut metadata_update = created.clone();
+    // Test 3: Metadata updates should be allowed, based on the pod as it is
+    // now since the scheduler and kubelet have written it since its creation
+    let mut metadata_update: serde_json::Value = client
+        .get(format!("{}/namespaces/{}/pods/immutable-pod", base_url, ns_name))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
     metadata_update["metadata"]["labels"] = json!({"env": "test", "version": "v1"});
     metadata_update["metadata"]["annotations"] = json!({"updated": "true"});
     
@@ -143,15 +148,10 @@ async fn test_pod_immutability_validation() {
 
 #[tokio::test]
 async fn test_api_version_validation() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
+    let base_url = server.url("/api/v1");
     
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
-
     // Test 1: Wrong API version for namespace (should auto-correct or reject)
     let ns = json!({
         "apiVersion": "v2",  // Wrong version
@@ -229,7 +229,7 @@ async fn test_api_version_validation() {
     
     // Test 4: Test with pods endpoint
     let ns_name = format!("test-pod-apiv-{}", Uuid::new_v4());
-    ensure_namespace(&client, base_url, &ns_name).await;
+    ensure_namespace(&client, &base_url, &ns_name).await;
     
     let pod_wrong_version = json!({
         "apiVersion": "apps/v1",  // Wrong for core pods
@@ -263,17 +263,12 @@ async fn test_api_version_validation() {
 
 #[tokio::test]
 async fn test_service_port_validation() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
+    let base_url = server.url("/api/v1");
     
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
-
     let ns_name = format!("test-svc-port-{}", Uuid::new_v4());
-    ensure_namespace(&client, base_url, &ns_name).await;
+    ensure_namespace(&client, &base_url, &ns_name).await;
 
     // Test 1: Port number out of range (>65535)
     let service_high_port = json!({
@@ -464,17 +459,12 @@ async fn test_service_port_validation() {
 
 #[tokio::test]
 async fn test_secret_validation() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
+    let base_url = server.url("/api/v1");
     
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
-
     let ns_name = format!("test-secret-val-{}", Uuid::new_v4());
-    ensure_namespace(&client, base_url, &ns_name).await;
+    ensure_namespace(&client, &base_url, &ns_name).await;
 
     // Test 1: Invalid base64 in data field
     let secret_invalid_base64 = json!({
@@ -670,15 +660,10 @@ async fn test_secret_validation() {
 
 #[tokio::test]
 async fn test_resource_name_validation() {
+    let server = common::TestServer::start().await;
     let client = reqwest::Client::new();
-    let base_url = "http://localhost:6443/api/v1";
+    let base_url = server.url("/api/v1");
     
-    // Check if server is running
-    if client.get("http://localhost:6443/livez").send().await.is_err() {
-        eprintln!("Server not running, skipping test");
-        return;
-    }
-
     // Test various invalid names across different resources
     let long_name = "a".repeat(254);
     let test_cases = vec![
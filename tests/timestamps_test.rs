@@ -0,0 +1,103 @@
+use reqwest;
+use krust::models::time;
+use krust::Storage;
+use serde_json::{json, Value};
+
+mod common;
+
+fn is_api_timestamp(value: &Value) -> bool {
+    let value = value.as_str().unwrap_or_default();
+    chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%SZ").is_ok()
+}
+
+#[tokio::test]
+async fn test_creation_timestamps_are_rfc3339_utc() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    // Kinds whose tables used to fill the column in with SQLite's default
+    let objects = [
+        ("/api/v1/namespaces", "/api/v1/namespaces/stamped", json!({ "apiVersion": "v1", "kind": "Namespace", "metadata": { "name": "stamped" } })),
+        (
+            "/api/v1/namespaces/default/limitranges",
+            "/api/v1/namespaces/default/limitranges/limits",
+            json!({ "apiVersion": "v1", "kind": "LimitRange", "metadata": { "name": "limits" }, "spec": { "limits": [] } }),
+        ),
+        (
+            "/api/v1/namespaces/default/serviceaccounts",
+            "/api/v1/namespaces/default/serviceaccounts/builder",
+            json!({ "apiVersion": "v1", "kind": "ServiceAccount", "metadata": { "name": "builder" } }),
+        ),
+        (
+            "/api/v1/namespaces/default/configmaps",
+            "/api/v1/namespaces/default/configmaps/settings",
+            json!({ "apiVersion": "v1", "kind": "ConfigMap", "metadata": { "name": "settings" }, "data": { "key": "value" } }),
+        ),
+    ];
+
+    for (collection, path, object) in objects {
+        let resp = client.post(server.url(collection)).json(&object).send().await.unwrap();
+        assert!(resp.status().is_success(), "creating {} failed: {}", path, resp.status());
+        let created: Value = resp.json().await.unwrap();
+        let stamp = &created["metadata"]["creationTimestamp"];
+        assert!(is_api_timestamp(stamp), "{} was created at {}", path, stamp);
+
+        // What is read back is what the create returned
+        let stored: Value = client.get(server.url(path)).send().await.unwrap().json().await.unwrap();
+        assert_eq!(&stored["metadata"]["creationTimestamp"], stamp, "{}", path);
+    }
+}
+
+#[tokio::test]
+async fn test_built_in_namespaces_are_stamped() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    // The ones the migrations create rather than the API
+    for name in ["default", "kube-system", "kube-public", "kube-node-lease"] {
+        let namespace: Value = client.get(server.url(&format!("/api/v1/namespaces/{}", name))).send().await.unwrap().json().await.unwrap();
+        let stamp = &namespace["metadata"]["creationTimestamp"];
+        assert!(is_api_timestamp(stamp), "{} was created at {}", name, stamp);
+    }
+}
+
+#[tokio::test]
+async fn test_existing_rows_are_normalized() {
+    let storage = Storage::in_memory().await.unwrap();
+    storage.migrate().await.unwrap();
+
+    for (name, stamp) in [
+        ("sqlite", "2024-05-01 12:30:00"),
+        ("offset", "2024-05-01T14:30:00.123456789+02:00"),
+    ] {
+        sqlx::query("INSERT INTO serviceaccounts (uid, name, namespace, creation_timestamp) VALUES (?, ?, 'default', ?)")
+            .bind(name)
+            .bind(name)
+            .bind(stamp)
+            .execute(storage.pool())
+            .await
+            .unwrap();
+    }
+
+    // 018 ran while the write log it normalizes was still there
+    sqlx::query("CREATE TABLE object_writes (timestamp TEXT)").execute(storage.pool()).await.unwrap();
+    sqlx::query(include_str!("../migrations/018_normalize_timestamps.sql"))
+        .execute(storage.pool())
+        .await
+        .unwrap();
+
+    let stamps: Vec<String> = sqlx::query_scalar("SELECT creation_timestamp FROM serviceaccounts ORDER BY name")
+        .fetch_all(storage.pool())
+        .await
+        .unwrap();
+    assert_eq!(stamps, ["2024-05-01T12:30:00Z", "2024-05-01T12:30:00Z"]);
+}
+
+#[test]
+fn test_time_helpers() {
+    let parsed = time::parse("2024-05-01 12:30:00").unwrap();
+    assert_eq!(time::format(parsed), "2024-05-01T12:30:00Z");
+    assert_eq!(time::parse("2024-05-01T14:30:00+02:00"), Some(parsed));
+    assert!(time::parse("yesterday").is_none());
+    assert!(is_api_timestamp(&json!(time::now())));
+}
@@ -0,0 +1,86 @@
+use reqwest;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use krust::pki::{ClusterCa, Usage};
+use serde_json::Value;
+use std::path::Path;
+
+mod common;
+
+/// Waits for the server to write its kubeconfig and returns it.
+async fn kubeconfig(dir: &Path) -> Value {
+    let path = dir.join("kubeconfig");
+    for _ in 0..50 {
+        if let Ok(contents) = std::fs::read_to_string(&path) {
+            return serde_yaml::from_str(&contents).unwrap();
+        }
+        tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
+    }
+    panic!("no kubeconfig written to {}", path.display());
+}
+
+fn data(kubeconfig: &Value, section: &str, field: &str) -> Vec<u8> {
+    let encoded = kubeconfig[section][0][&section[..section.len() - 1]][field].as_str().unwrap();
+    STANDARD.decode(encoded).unwrap()
+}
+
+#[tokio::test]
+async fn test_https_with_client_certificates() {
+    let dir = tempfile::tempdir().unwrap();
+    let config = format!(
+        "dataDir: {}\ntls:\n  enabled: true\nauthorization:\n  mode: RBAC\n",
+        dir.path().display()
+    );
+    let server = common::TestServer::start_with_config(krust::Config::parse(&config).unwrap()).await;
+    let kubeconfig = kubeconfig(dir.path()).await;
+
+    let server_url = kubeconfig["clusters"][0]["cluster"]["server"].as_str().unwrap().to_string();
+    assert_eq!(server_url, server.base_url().replace("http://", "https://"));
+    let ca = data(&kubeconfig, "clusters", "certificate-authority-data");
+    assert_eq!(ca, std::fs::read(dir.path().join("pki/ca.crt")).unwrap());
+
+    // The serving certificate checks out against the cluster CA
+    let anonymous = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(&ca).unwrap())
+        .build()
+        .unwrap();
+    let resp = anonymous.get(format!("{}/version", server_url)).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = anonymous.get(format!("{}/api/v1/namespaces/default/pods", server_url)).send().await.unwrap();
+    assert_eq!(resp.status(), 403);
+
+    // The kubeconfig's client certificate makes the caller a cluster admin
+    let identity = reqwest::Identity::from_pkcs8_pem(
+        &data(&kubeconfig, "users", "client-certificate-data"),
+        &data(&kubeconfig, "users", "client-key-data"),
+    )
+    .unwrap();
+    let admin = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(&ca).unwrap())
+        .identity(identity)
+        .build()
+        .unwrap();
+    let resp = admin.get(format!("{}/api/v1/namespaces/default/pods", server_url)).send().await.unwrap();
+    assert_eq!(resp.status(), 200);
+    let resp = admin
+        .post(format!("{}/apis/authentication.k8s.io/v1/selfsubjectreviews", server_url))
+        .json(&serde_json::json!({}))
+        .send()
+        .await
+        .unwrap();
+    let review: Value = resp.json().await.unwrap();
+    assert_eq!(review["status"]["userInfo"]["username"], "krust-admin");
+    assert!(review["status"]["userInfo"]["groups"].as_array().unwrap().iter().any(|g| g == "system:masters"));
+
+    // Certificates from any other CA are refused
+    let (cert, key) = ClusterCa::generate().unwrap().issue("intruder", &["system:masters"], Usage::Client).unwrap();
+    let intruder = reqwest::Client::builder()
+        .add_root_certificate(reqwest::Certificate::from_pem(&ca).unwrap())
+        .identity(reqwest::Identity::from_pkcs8_pem(cert.as_bytes(), key.as_bytes()).unwrap())
+        .build()
+        .unwrap();
+    assert!(intruder.get(format!("{}/version", server_url)).send().await.is_err());
+
+    // And there's no plain HTTP any more
+    assert!(reqwest::get(server.url("/version")).await.is_err());
+}
@@ -0,0 +1,112 @@
+use reqwest;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+const CLUSTER: &str = r#"
+nodes:
+  krust-node: {}
+  worker-1: {}
+"#;
+
+// A pod pinned to `node` (if any) with the given containers' extras and volumes
+fn pod_on(name: &str, node: Option<&str>, container: Value, volumes: Value) -> Value {
+    let mut app = json!({ "name": "app", "image": "nginx:1.25" });
+    app.as_object_mut().unwrap().extend(container.as_object().cloned().unwrap_or_default());
+    common::pod(name, json!({
+        "nodeSelector": node.map(|node| json!({ "kubernetes.io/hostname": node })),
+        "containers": [app],
+        "volumes": volumes
+    }))
+}
+
+async fn node_of(server: &common::TestServer, name: &str) -> String {
+    for _ in 0..30 {
+        let pod = server.storage.pods().get("default", name).await.unwrap();
+        if let Some(node) = pod["spec"]["nodeName"].as_str() {
+            return node.to_string();
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    panic!("pod {} was never scheduled", name);
+}
+
+async fn explanation(client: &reqwest::Client, server: &common::TestServer, name: &str) -> String {
+    let url = server.url(&format!("/krust/scheduling?namespace=default&name={}", name));
+    for _ in 0..30 {
+        let report: Value = client.get(&url).send().await.unwrap().json().await.unwrap();
+        if let Some(message) = report["pods"][0]["message"].as_str() {
+            return message.to_string();
+        }
+        tokio::time::sleep(Duration::from_millis(200)).await;
+    }
+    panic!("pod {} was never explained", name);
+}
+
+#[tokio::test]
+async fn test_read_write_once_claims_keep_pods_together() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    for (name, mode) in [("shared", "ReadWriteOnce"), ("exclusive", "ReadWriteOncePod")] {
+        let claim = json!({
+            "apiVersion": "v1",
+            "kind": "PersistentVolumeClaim",
+            "metadata": { "name": name },
+            "spec": { "accessModes": [mode], "resources": { "requests": { "storage": "1Gi" } } }
+        });
+        let resp = client.post(server.url("/api/v1/namespaces/default/persistentvolumeclaims")).json(&claim).send().await.unwrap();
+        assert_eq!(resp.status(), 201);
+    }
+    let volume = |claim: &str| json!([{ "name": "data", "persistentVolumeClaim": { "claimName": claim } }]);
+
+    // The claim is mounted on worker-1, so a second pod using it goes there
+    // too rather than to krust-node
+    common::create_pod(&client, &server, pod_on("writer", Some("worker-1"), json!({}), volume("shared"))).await;
+    assert_eq!(node_of(&server, "writer").await, "worker-1");
+    common::create_pod(&client, &server, pod_on("reader", None, json!({}), volume("shared"))).await;
+    assert_eq!(node_of(&server, "reader").await, "worker-1");
+
+    // A ReadWriteOncePod claim is for one pod wherever it runs
+    common::create_pod(&client, &server, pod_on("owner", None, json!({}), volume("exclusive"))).await;
+    node_of(&server, "owner").await;
+    common::create_pod(&client, &server, pod_on("intruder", None, json!({}), volume("exclusive"))).await;
+    assert_eq!(
+        explanation(&client, &server, "intruder").await,
+        "0/2 nodes are available: 2 node has pod using PersistentVolumeClaim with the same name and ReadWriteOncePod access mode."
+    );
+}
+
+#[tokio::test]
+async fn test_host_port_and_host_path_conflicts() {
+    let config = krust::Config::parse(CLUSTER).unwrap();
+    let server = common::TestServer::start_with_config(config).await;
+    let client = reqwest::Client::new();
+
+    let port = |port: i64, protocol: &str| json!({ "ports": [{ "containerPort": 80, "hostPort": port, "protocol": protocol }] });
+    common::create_pod(&client, &server, pod_on("web", Some("worker-1"), port(8080, "TCP"), json!(null))).await;
+    node_of(&server, "web").await;
+    // The same port over UDP is free
+    common::create_pod(&client, &server, pod_on("dns", Some("worker-1"), port(8080, "UDP"), json!(null))).await;
+    node_of(&server, "dns").await;
+    common::create_pod(&client, &server, pod_on("web-2", Some("worker-1"), port(8080, "TCP"), json!(null))).await;
+    assert_eq!(
+        explanation(&client, &server, "web-2").await,
+        "0/2 nodes are available: 1 node(s) didn't have free ports for the requested pod ports, 1 node(s) didn't match Pod's node affinity/selector."
+    );
+
+    // Pods may read a host directory together, but not while one writes it
+    let host_path = json!([{ "name": "logs", "hostPath": { "path": "/var/log/app" } }]);
+    let mount = |read_only: bool| json!({ "volumeMounts": [{ "name": "logs", "mountPath": "/logs", "readOnly": read_only }] });
+    common::create_pod(&client, &server, pod_on("tail-1", Some("worker-1"), mount(true), host_path.clone())).await;
+    common::create_pod(&client, &server, pod_on("tail-2", Some("worker-1"), mount(true), host_path.clone())).await;
+    node_of(&server, "tail-1").await;
+    node_of(&server, "tail-2").await;
+    common::create_pod(&client, &server, pod_on("logger", Some("worker-1"), mount(false), host_path)).await;
+    assert_eq!(
+        explanation(&client, &server, "logger").await,
+        "0/2 nodes are available: 1 node(s) didn't match Pod's node affinity/selector, 1 node(s) had no available disk."
+    );
+}
@@ -0,0 +1,167 @@
+use krust::runtime::volumes::{self, Volume};
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+mod common;
+
+#[tokio::test]
+async fn test_pod_volumes_are_prepared_and_mounted() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let base_url = server.url("/api/v1/namespaces/default");
+    let node = tempfile::tempdir().unwrap();
+    let pod_dir = node.path().join("pod");
+    let disk = node.path().join("disk");
+
+    common::create(&client, &format!("{}/configmaps", base_url), json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": { "name": "web-config" },
+        "data": { "nginx.conf": "worker_processes 1;", "mime.types": "types {}" }
+    })).await;
+    common::create(&client, &format!("{}/secrets", base_url), json!({
+        "apiVersion": "v1",
+        "kind": "Secret",
+        "metadata": { "name": "web-tls" },
+        "data": { "tls.key": "a2V5", "tls.crt": "Y2VydA==" }
+    })).await;
+    common::create(&client, &server.url("/api/v1/persistentvolumes"), json!({
+        "apiVersion": "v1",
+        "kind": "PersistentVolume",
+        "metadata": { "name": "web-data" },
+        "spec": {
+            "capacity": { "storage": "1Gi" },
+            "accessModes": ["ReadWriteOnce"],
+            "hostPath": { "path": disk.to_str().unwrap(), "type": "DirectoryOrCreate" }
+        }
+    })).await;
+    common::create(&client, &format!("{}/persistentvolumeclaims", base_url), json!({
+        "apiVersion": "v1",
+        "kind": "PersistentVolumeClaim",
+        "metadata": { "name": "web-data" },
+        "spec": {
+            "accessModes": ["ReadWriteOnce"],
+            "resources": { "requests": { "storage": "1Gi" } },
+            "volumeName": "web-data"
+        }
+    })).await;
+
+    let logs = node.path().join("logs");
+    let container = json!({
+        "name": "web",
+        "image": "nginx",
+        "volumeMounts": [
+            { "name": "config", "mountPath": "/etc/nginx/nginx.conf", "subPath": "nginx.conf" },
+            { "name": "tls", "mountPath": "/etc/tls" },
+            { "name": "data", "mountPath": "/var/www", "subPath": "html" },
+            { "name": "logs", "mountPath": "/var/log/nginx", "readOnly": true },
+            { "name": "cache", "mountPath": "/var/cache" },
+            { "name": "scratch", "mountPath": "/tmp" }
+        ]
+    });
+    let pod = json!({
+        "metadata": { "name": "web", "namespace": "default", "uid": "web-uid" },
+        "spec": {
+            "containers": [container.clone()],
+            "volumes": [
+                { "name": "config", "configMap": { "name": "web-config" } },
+                { "name": "tls", "secret": { "secretName": "web-tls", "items": [{ "key": "tls.crt", "path": "certs/tls.crt" }] } },
+                { "name": "data", "persistentVolumeClaim": { "claimName": "web-data" } },
+                { "name": "logs", "hostPath": { "path": logs.to_str().unwrap(), "type": "DirectoryOrCreate" } },
+                { "name": "cache", "emptyDir": {} },
+                { "name": "scratch", "emptyDir": { "medium": "Memory", "sizeLimit": "64Mi" } },
+                { "name": "podinfo", "downwardAPI": { "items": [{ "path": "name", "fieldRef": { "fieldPath": "metadata.name" } }] } }
+            ]
+        }
+    });
+
+    let prepared = volumes::prepare(&server.storage, &pod, &pod_dir).await.unwrap();
+    let config = volumes::dir(&pod_dir, "kubernetes.io~configmap", "config");
+    assert_eq!(std::fs::read_to_string(config.join("nginx.conf")).unwrap(), "worker_processes 1;");
+    let tls = volumes::dir(&pod_dir, "kubernetes.io~secret", "tls");
+    assert_eq!(std::fs::read_to_string(tls.join("certs/tls.crt")).unwrap(), "cert");
+    assert!(!tls.join("tls.key").exists());
+    let podinfo = volumes::dir(&pod_dir, "kubernetes.io~downward-api", "podinfo");
+    assert_eq!(std::fs::read_to_string(podinfo.join("name")).unwrap(), "web");
+    assert!(logs.is_dir());
+    assert_eq!(prepared["data"], Volume::Bind { path: disk.clone(), read_only: false });
+    assert_eq!(prepared["scratch"], Volume::Tmpfs("size=67108864".to_string()));
+
+    let (mut binds, tmpfs) = volumes::mounts(&container, &prepared).unwrap();
+    binds.sort();
+    let cache = volumes::dir(&pod_dir, "kubernetes.io~empty-dir", "cache");
+    let mut expected = vec![
+        format!("{}:/etc/nginx/nginx.conf:ro", config.join("nginx.conf").display()),
+        format!("{}:/etc/tls:ro", tls.display()),
+        format!("{}:/var/www", disk.join("html").display()),
+        format!("{}:/var/log/nginx:ro", logs.display()),
+        format!("{}:/var/cache", cache.display()),
+    ];
+    expected.sort();
+    assert_eq!(binds, expected);
+    assert!(disk.join("html").is_dir());
+    assert_eq!(tmpfs.get("/tmp").map(String::as_str), Some("size=67108864"));
+
+    // Rewriting the volumes picks up changes to the ConfigMap
+    let mut config_map: Value = client.get(format!("{}/configmaps/web-config", base_url)).send().await.unwrap().json().await.unwrap();
+    config_map["data"]["nginx.conf"] = json!("worker_processes 4;");
+    let response = client.put(format!("{}/configmaps/web-config", base_url)).json(&config_map).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::OK);
+    volumes::project(&server.storage, &pod, &pod_dir).await.unwrap();
+    assert_eq!(std::fs::read_to_string(config.join("nginx.conf")).unwrap(), "worker_processes 4;");
+}
+
+#[tokio::test]
+async fn test_unmountable_volumes_fail() {
+    let server = common::TestServer::start().await;
+    let node = tempfile::tempdir().unwrap();
+    let pod_dir = node.path().join("pod");
+    let pod = |volume: Value| json!({
+        "metadata": { "name": "web", "namespace": "default", "uid": "web-uid" },
+        "spec": { "containers": [{ "name": "web", "image": "nginx" }], "volumes": [volume] }
+    });
+
+    // Missing ConfigMaps fail unless optional, as do unbound claims and
+    // hostPaths that aren't what their type says
+    let missing = json!({ "name": "config", "configMap": { "name": "absent" } });
+    assert!(volumes::prepare(&server.storage, &pod(missing), &pod_dir).await.is_err());
+    let optional = json!({ "name": "config", "configMap": { "name": "absent", "optional": true } });
+    assert!(volumes::prepare(&server.storage, &pod(optional), &pod_dir).await.is_ok());
+    let unclaimed = json!({ "name": "data", "persistentVolumeClaim": { "claimName": "absent" } });
+    assert!(volumes::prepare(&server.storage, &pod(unclaimed), &pod_dir).await.is_err());
+    let file = json!({ "name": "host", "hostPath": { "path": node.path().to_str().unwrap(), "type": "File" } });
+    assert!(volumes::prepare(&server.storage, &pod(file), &pod_dir).await.is_err());
+
+    // A subPath can't climb out of its volume
+    let cache = json!({ "name": "cache", "emptyDir": {} });
+    let prepared = volumes::prepare(&server.storage, &pod(cache), &pod_dir).await.unwrap();
+    let container = json!({ "name": "web", "volumeMounts": [{ "name": "cache", "mountPath": "/c", "subPath": "../escape" }] });
+    assert!(volumes::mounts(&container, &prepared).is_err());
+}
+
+#[tokio::test]
+async fn test_only_unmounted_volume_kinds_are_unsupported() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let pod = json!({
+        "apiVersion": "v1",
+        "kind": "Pod",
+        "metadata": { "name": "shares" },
+        "spec": {
+            "containers": [{
+                "name": "app",
+                "image": "nginx",
+                "volumeMounts": [{ "name": "cache", "mountPath": "/cache" }, { "name": "share", "mountPath": "/share" }]
+            }],
+            "volumes": [
+                { "name": "cache", "emptyDir": {} },
+                { "name": "share", "nfs": { "server": "nfs.example.com", "path": "/exports" } }
+            ]
+        }
+    });
+    let response = client.post(server.url("/api/v1/namespaces/default/pods")).json(&pod).send().await.unwrap();
+    assert_eq!(response.status(), StatusCode::CREATED);
+    let created: Value = response.json().await.unwrap();
+    let fields: Vec<String> = serde_json::from_str(created["metadata"]["annotations"]["krust.io/unsupported-fields"].as_str().unwrap()).unwrap();
+    assert_eq!(fields, vec!["spec.volumes[1]"]);
+}
@@ -0,0 +1,387 @@
+use reqwest;
+use serde_json::{json, Value};
+use std::time::Duration;
+
+mod common;
+
+// Reads a watch response one event at a time
+struct Watch {
+    resp: reqwest::Response,
+    buffer: Vec<u8>,
+}
+
+impl Watch {
+    async fn open(url: String) -> Self {
+        let resp = reqwest::get(url).await.unwrap();
+        assert_eq!(resp.status(), 200);
+        assert_eq!(resp.headers()["content-type"], "application/json");
+        Self { resp, buffer: Vec::new() }
+    }
+
+    // The next event, or None once the server ends the watch
+    async fn next(&mut self) -> Option<Value> {
+        loop {
+            if let Some(end) = self.buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = self.buffer.drain(..=end).collect();
+                return Some(serde_json::from_slice(&line).unwrap());
+            }
+            let chunk = tokio::time::timeout(Duration::from_secs(10), self.resp.chunk())
+                .await
+                .expect("no watch event within 10s")
+                .unwrap()?;
+            self.buffer.extend_from_slice(&chunk);
+        }
+    }
+
+    // The next event about the object `name`, skipping those about objects
+    // the controllers create
+    async fn expect(&mut self, event_type: &str, name: &str) -> Value {
+        loop {
+            let event = self.next().await.expect("watch ended");
+            if event["object"]["metadata"]["name"] == name {
+                assert_eq!(event["type"], event_type, "{}", event);
+                return event;
+            }
+        }
+    }
+}
+
+fn configmap(name: &str) -> Value {
+    json!({
+        "apiVersion": "v1",
+        "kind": "ConfigMap",
+        "metadata": { "name": name },
+        "data": { "key": "value" }
+    })
+}
+
+#[tokio::test]
+async fn test_watch_configmaps() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let collection = server.url("/api/v1/namespaces/default/configmaps");
+
+    let resp = client.post(&collection).json(&configmap("existing")).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+
+    // Without a resourceVersion, the watch starts with what's there
+    let mut watch = Watch::open(format!("{}?watch=true", collection)).await;
+    watch.expect("ADDED", "existing").await;
+
+    let resp = client.post(&collection).json(&configmap("settings")).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let added = watch.expect("ADDED", "settings").await;
+
+    let resp = client
+        .patch(format!("{}/settings", collection))
+        .header("Content-Type", "application/merge-patch+json")
+        .body(json!({ "data": { "key": "changed" } }).to_string())
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.status(), 200);
+    let modified = watch.expect("MODIFIED", "settings").await;
+    assert_eq!(modified["object"]["data"]["key"], "changed");
+
+    // Resource versions grow from one event to the next
+    let version = |event: &Value| event["object"]["metadata"]["resourceVersion"].as_str().unwrap().parse::<i64>().unwrap();
+    assert!(version(&modified) > version(&added));
+
+    let resp = client.delete(format!("{}/settings", collection)).send().await.unwrap();
+    assert!(resp.status().is_success());
+    watch.expect("DELETED", "settings").await;
+}
+
+#[tokio::test]
+async fn test_watch_from_a_list() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+
+    let service = |name: &str| {
+        json!({
+            "apiVersion": "v1",
+            "kind": "Service",
+            "metadata": { "name": name, "labels": { "app": name } },
+            "spec": { "ports": [{ "port": 80 }], "selector": { "app": name } }
+        })
+    };
+    let resp = client.post(server.url("/api/v1/namespaces/default/services")).json(&service("old")).send().await.unwrap();
+    assert!(resp.status().is_success());
+
+    // A list says which version it's current as of, and a watch from there
+    // only sees what comes after it
+    let list: Value = client.get(server.url("/api/v1/services")).send().await.unwrap().json().await.unwrap();
+    let version = list["metadata"]["resourceVersion"].as_str().unwrap();
+    assert!(version.parse::<i64>().unwrap() > 0);
+
+    let mut watch = Watch::open(server.url(&format!(
+        "/api/v1/services?watch=true&resourceVersion={}&labelSelector=app%20in%20(web,old)",
+        version
+    )))
+    .await;
+
+    for name in ["db", "web"] {
+        let resp = client.post(server.url("/api/v1/namespaces/default/services")).json(&service(name)).send().await.unwrap();
+        assert!(resp.status().is_success());
+    }
+    watch.expect("ADDED", "web").await;
+
+    // Watches cover the other kinds too, jobs and secrets included
+    for (collection, object) in [
+        (
+            "/apis/batch/v1/namespaces/default/jobs",
+            json!({
+                "apiVersion": "batch/v1",
+                "kind": "Job",
+                "metadata": { "name": "once" },
+                "spec": { "template": { "spec": {
+                    "restartPolicy": "Never",
+                    "containers": [{ "name": "main", "image": "busybox" }]
+                } } }
+            }),
+        ),
+        (
+            "/api/v1/namespaces/default/secrets",
+            json!({ "apiVersion": "v1", "kind": "Secret", "metadata": { "name": "once" }, "data": {} }),
+        ),
+    ] {
+        let mut watch = Watch::open(format!("{}?watch=true&resourceVersion={}", server.url(collection), version)).await;
+        let resp = client.post(server.url(collection)).json(&object).send().await.unwrap();
+        assert!(resp.status().is_success(), "{}", collection);
+        watch.expect("ADDED", "once").await;
+    }
+}
+
+#[tokio::test]
+async fn test_many_watches_get_events_right_away() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let collection = server.url("/api/v1/namespaces/default/configmaps");
+
+    let mut watches = Vec::new();
+    for _ in 0..20 {
+        watches.push(Watch::open(format!("{}?watch=true", collection)).await);
+    }
+
+    let started = std::time::Instant::now();
+    let resp = client.post(&collection).json(&configmap("fanout")).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    for watch in &mut watches {
+        watch.expect("ADDED", "fanout").await;
+    }
+    // Events aren't polled for, so none waits for a polling interval
+    assert!(started.elapsed() < Duration::from_millis(400), "took {:?}", started.elapsed());
+}
+
+#[tokio::test]
+async fn test_watch_bookmarks_and_timeout() {
+    let server = common::TestServer::start().await;
+
+    let mut watch = Watch::open(server.url(
+        "/apis/apps/v1/namespaces/default/deployments?watch=true&allowWatchBookmarks=true&timeoutSeconds=1",
+    ))
+    .await;
+
+    // Nothing to list, so the first event is the bookmark sent as the watch
+    // times out, and then it ends
+    let bookmark = watch.next().await.expect("watch ended without a bookmark");
+    assert_eq!(bookmark["type"], "BOOKMARK");
+    assert_eq!(bookmark["object"]["kind"], "Deployment");
+    assert!(bookmark["object"]["metadata"]["resourceVersion"].as_str().unwrap().parse::<i64>().is_ok());
+    assert!(watch.next().await.is_none());
+
+    let resp = reqwest::get(server.url("/api/v1/pods?watch=true&resourceVersion=latest")).await.unwrap();
+    assert_eq!(resp.status(), 400);
+}
+
+#[tokio::test]
+async fn test_watch_from_compacted_version_is_gone() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let collection = server.url("/api/v1/namespaces/default/configmaps");
+
+    let list: Value = client.get(&collection).send().await.unwrap().json().await.unwrap();
+    let old = list["metadata"]["resourceVersion"].as_str().unwrap().to_string();
+
+    // A watch from a version replays what came after it
+    let resp = client.post(&collection).json(&configmap("first")).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    let mut watch = Watch::open(format!("{}?watch=true&resourceVersion={}", collection, old)).await;
+    watch.expect("ADDED", "first").await;
+
+    // Once those events are compacted away, the watch can't be resumed
+    server.storage.watch().compact(0).await.unwrap();
+    let resp = client.get(format!("{}?watch=true&resourceVersion={}", collection, old)).send().await.unwrap();
+    assert_eq!(resp.status(), 410);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["kind"], "Status");
+    assert_eq!(status["reason"], "Expired");
+    assert_eq!(status["code"], 410);
+    assert!(status["message"].as_str().unwrap().starts_with("too old resource version"));
+
+    // ...but a relist gives a version to watch from again
+    let list: Value = client.get(&collection).send().await.unwrap().json().await.unwrap();
+    let current = list["metadata"]["resourceVersion"].as_str().unwrap();
+    let mut watch = Watch::open(format!("{}?watch=true&resourceVersion={}", collection, current)).await;
+    let resp = client.post(&collection).json(&configmap("second")).send().await.unwrap();
+    assert_eq!(resp.status(), 201);
+    watch.expect("ADDED", "second").await;
+}
+
+#[tokio::test]
+async fn test_resource_versions_are_cluster_wide() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let configmaps = server.url("/api/v1/namespaces/default/configmaps");
+    let version = |object: &Value| object["metadata"]["resourceVersion"].as_str().unwrap().parse::<i64>().unwrap();
+
+    let created: Value = client.post(&configmaps).json(&configmap("shared")).send().await.unwrap().json().await.unwrap();
+    let secret: Value = client
+        .post(server.url("/api/v1/namespaces/default/secrets"))
+        .json(&json!({ "apiVersion": "v1", "kind": "Secret", "metadata": { "name": "shared" }, "data": {} }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let patched: Value = client
+        .patch(format!("{}/shared", configmaps))
+        .header("Content-Type", "application/merge-patch+json")
+        .body(json!({ "data": { "key": "changed" } }).to_string())
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+
+    // Writes to different kinds draw from the same sequence
+    assert!(version(&secret) > version(&created));
+    assert!(version(&patched) > version(&secret));
+
+    // ...so a version from one kind is a place to watch another from
+    let mut watch = Watch::open(format!("{}?watch=true&resourceVersion={}", configmaps, version(&secret))).await;
+    let modified = watch.expect("MODIFIED", "shared").await;
+    assert_eq!(version(&modified["object"]), version(&patched));
+
+    // A delete is a write of its own
+    let resp = client.delete(format!("{}/shared", configmaps)).send().await.unwrap();
+    assert!(resp.status().is_success());
+    let deleted = watch.expect("DELETED", "shared").await;
+    assert!(version(&deleted["object"]) > version(&patched));
+}
+
+#[tokio::test]
+async fn test_resource_versions_commit_in_order() {
+    let server = common::TestServer::start().await;
+
+    // Writes made at once commit in the order of their versions, so a watch
+    // that has seen one can't have missed an earlier one
+    let writes = (0..20).map(|i| {
+        let storage = server.storage.clone();
+        tokio::spawn(async move { storage.configmaps().create("default", configmap(&format!("ordered-{}", i))).await })
+    });
+    for write in futures::future::join_all(writes).await {
+        write.unwrap().unwrap();
+    }
+    let versions: Vec<i64> = sqlx::query_scalar(
+        "SELECT resource_version FROM events WHERE resource_type = 'configmaps' AND resource_name LIKE 'ordered-%' ORDER BY id",
+    )
+    .fetch_all(&*server.storage.pool)
+    .await
+    .unwrap();
+    assert_eq!(versions.len(), 20);
+    assert!(versions.windows(2).all(|pair| pair[0] < pair[1]), "{:?}", versions);
+
+    // Writes made outside the stores take theirs in a transaction too
+    assert!(server.storage.next_resource_version().await.is_err());
+    let tx = server.storage.transaction().await.unwrap();
+    assert!(tx.next_resource_version().await.is_ok());
+    tx.rollback().await.unwrap();
+}
+
+#[tokio::test]
+async fn test_list_resource_version_match() {
+    let server = common::TestServer::start().await;
+    let client = reqwest::Client::new();
+    let collection = server.url("/api/v1/namespaces/default/configmaps");
+    // The configmaps of the test, leaving out the ones the controllers publish
+    let names = |list: &Value| -> Vec<(String, String)> {
+        list["items"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|item| ["a", "b", "c"].contains(&item["metadata"]["name"].as_str().unwrap()))
+            .map(|item| (item["metadata"]["name"].as_str().unwrap().to_string(), item["data"]["key"].as_str().unwrap().to_string()))
+            .collect()
+    };
+    let list_at = |query: String| {
+        let client = client.clone();
+        let collection = collection.clone();
+        async move { client.get(format!("{}?{}", collection, query)).send().await.unwrap() }
+    };
+
+    client.post(&collection).json(&configmap("a")).send().await.unwrap();
+    let b: Value = client.post(&collection).json(&configmap("b")).send().await.unwrap().json().await.unwrap();
+    let then = b["metadata"]["resourceVersion"].as_str().unwrap().to_string();
+    client
+        .patch(format!("{}/a", collection))
+        .header("Content-Type", "application/merge-patch+json")
+        .body(json!({ "data": { "key": "changed" } }).to_string())
+        .send()
+        .await
+        .unwrap();
+    client.delete(format!("{}/b", collection)).send().await.unwrap();
+    client.post(&collection).json(&configmap("c")).send().await.unwrap();
+
+    // Exact serves the list as it was, at the version asked for
+    let resp = list_at(format!("resourceVersion={}&resourceVersionMatch=Exact", then)).await;
+    assert_eq!(resp.status(), 200);
+    let list: Value = resp.json().await.unwrap();
+    assert_eq!(list["metadata"]["resourceVersion"], then.as_str());
+    let mut items = names(&list);
+    items.sort();
+    assert_eq!(items, [("a".to_string(), "value".to_string()), ("b".to_string(), "value".to_string())]);
+
+    // NotOlderThan serves the latest list
+    let list: Value = list_at(format!("resourceVersion={}&resourceVersionMatch=NotOlderThan", then)).await.json().await.unwrap();
+    let latest: i64 = list["metadata"]["resourceVersion"].as_str().unwrap().parse().unwrap();
+    assert!(latest > then.parse().unwrap());
+    let mut items = names(&list);
+    items.sort();
+    assert_eq!(items, [("a".to_string(), "changed".to_string()), ("c".to_string(), "value".to_string())]);
+
+    // A version not written yet can't be served
+    let resp = list_at(format!("resourceVersion={}&resourceVersionMatch=NotOlderThan", latest + 1000)).await;
+    assert_eq!(resp.status(), 504);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "Timeout");
+    assert_eq!(status["details"]["causes"][0]["reason"], "ResourceVersionTooLarge");
+
+    // ...nor one whose writes have been compacted away
+    server.storage.watch().compact(0).await.unwrap();
+    let resp = list_at(format!("resourceVersion={}&resourceVersionMatch=Exact", then)).await;
+    assert_eq!(resp.status(), 410);
+    let status: Value = resp.json().await.unwrap();
+    assert_eq!(status["reason"], "Expired");
+}
+
+#[tokio::test]
+async fn test_invalid_resource_version_match() {
+    let server = common::TestServer::start().await;
+    let collection = server.url("/api/v1/namespaces/default/configmaps");
+
+    for (query, cause) in [
+        ("resourceVersionMatch=Exact", "resourceVersionMatch is forbidden unless resourceVersion is provided"),
+        ("resourceVersion=0&resourceVersionMatch=Exact", "resourceVersionMatch \"exact\" is forbidden for resourceVersion \"0\""),
+        ("resourceVersion=1&resourceVersionMatch=Newest", "Unsupported value: \"Newest\""),
+        ("watch=true&resourceVersion=1&resourceVersionMatch=NotOlderThan", "resourceVersionMatch is forbidden for watch"),
+    ] {
+        let resp = reqwest::get(format!("{}?{}", collection, query)).await.unwrap();
+        assert_eq!(resp.status(), 422, "{}", query);
+        let status: Value = resp.json().await.unwrap();
+        assert_eq!(status["reason"], "Invalid");
+        assert!(status["message"].as_str().unwrap().contains(cause), "{}", status);
+    }
+}
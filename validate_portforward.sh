@@ -16,7 +16,7 @@ trap cleanup EXIT
 
 # Start fresh
 cleanup
-rm -f krust.db
+cargo run -q -- reset
 
 echo "1. Starting Krust server..."
 cargo run > /tmp/krust.log 2>&1 &